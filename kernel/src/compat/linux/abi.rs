@@ -0,0 +1,337 @@
+//! Linux structure layouts and flag encodings
+//!
+//! Where the native ABI already matches Linux (timespec, sigset_t, rlimit,
+//! errno values) calls pass straight through; only what differs is here.
+//! x86_64 has its own `struct stat`; aarch64 and riscv64 use asm-generic's.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use crate::vfs::{FileAttr, FileType};
+
+/// Split a nanosecond timestamp into seconds and nanoseconds
+fn timestamp(ns: u64) -> (i64, i64) {
+    ((ns / 1_000_000_000) as i64, (ns % 1_000_000_000) as i64)
+}
+
+/// `struct stat` on x86_64 (144 bytes)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct X86_64Stat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_nlink: u64,
+    pub st_mode: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    _pad0: u32,
+    pub st_rdev: u64,
+    pub st_size: i64,
+    pub st_blksize: i64,
+    pub st_blocks: i64,
+    pub st_atime: i64,
+    pub st_atime_nsec: i64,
+    pub st_mtime: i64,
+    pub st_mtime_nsec: i64,
+    pub st_ctime: i64,
+    pub st_ctime_nsec: i64,
+    _unused: [i64; 3],
+}
+
+impl From<&FileAttr> for X86_64Stat {
+    fn from(attr: &FileAttr) -> Self {
+        let (st_atime, st_atime_nsec) = timestamp(attr.atime);
+        let (st_mtime, st_mtime_nsec) = timestamp(attr.mtime);
+        let (st_ctime, st_ctime_nsec) = timestamp(attr.ctime);
+        Self {
+            st_ino: attr.ino,
+            st_nlink: attr.nlink as u64,
+            st_mode: attr.mode.0,
+            st_uid: attr.uid,
+            st_gid: attr.gid,
+            st_rdev: attr.rdev,
+            st_size: attr.size as i64,
+            st_blksize: attr.blksize as i64,
+            st_blocks: attr.blocks as i64,
+            st_atime,
+            st_atime_nsec,
+            st_mtime,
+            st_mtime_nsec,
+            st_ctime,
+            st_ctime_nsec,
+            ..Default::default()
+        }
+    }
+}
+
+/// asm-generic `struct stat`, used by aarch64 and riscv64 (128 bytes)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GenericStat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    _pad1: u64,
+    pub st_size: i64,
+    pub st_blksize: i32,
+    _pad2: i32,
+    pub st_blocks: i64,
+    pub st_atime: i64,
+    pub st_atime_nsec: i64,
+    pub st_mtime: i64,
+    pub st_mtime_nsec: i64,
+    pub st_ctime: i64,
+    pub st_ctime_nsec: i64,
+    _unused: [u32; 2],
+}
+
+impl From<&FileAttr> for GenericStat {
+    fn from(attr: &FileAttr) -> Self {
+        let (st_atime, st_atime_nsec) = timestamp(attr.atime);
+        let (st_mtime, st_mtime_nsec) = timestamp(attr.mtime);
+        let (st_ctime, st_ctime_nsec) = timestamp(attr.ctime);
+        Self {
+            st_ino: attr.ino,
+            st_mode: attr.mode.0,
+            st_nlink: attr.nlink,
+            st_uid: attr.uid,
+            st_gid: attr.gid,
+            st_rdev: attr.rdev,
+            st_size: attr.size as i64,
+            st_blksize: attr.blksize as i32,
+            st_blocks: attr.blocks as i64,
+            st_atime,
+            st_atime_nsec,
+            st_mtime,
+            st_mtime_nsec,
+            st_ctime,
+            st_ctime_nsec,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub type Stat = X86_64Stat;
+#[cfg(not(target_arch = "x86_64"))]
+pub type Stat = GenericStat;
+
+/// `struct utsname`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Utsname {
+    pub sysname: [u8; 65],
+    pub nodename: [u8; 65],
+    pub release: [u8; 65],
+    pub version: [u8; 65],
+    pub machine: [u8; 65],
+    pub domainname: [u8; 65],
+}
+
+impl Utsname {
+    pub fn new() -> Self {
+        fn field(s: &str) -> [u8; 65] {
+            let mut buf = [0u8; 65];
+            buf[..s.len()].copy_from_slice(s.as_bytes());
+            buf
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        let machine = "x86_64";
+        #[cfg(target_arch = "aarch64")]
+        let machine = "aarch64";
+        #[cfg(target_arch = "riscv64")]
+        let machine = "riscv64";
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "riscv64")))]
+        let machine = "unknown";
+
+        Self {
+            // Binaries probe these; report a Linux release new enough for
+            // the calls decoded in `nr`
+            sysname: field("Linux"),
+            nodename: field("nos"),
+            release: field("5.15.0-nos"),
+            version: field(concat!("NOS ", env!("CARGO_PKG_VERSION"))),
+            machine: field(machine),
+            domainname: field("(none)"),
+        }
+    }
+}
+
+/// Kernel `struct sigaction` as rt_sigaction(2) takes it
+///
+/// riscv64 does not define SA_RESTORER and so has no restorer slot.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KSigaction {
+    pub handler: usize,
+    pub flags: u64,
+    #[cfg(not(target_arch = "riscv64"))]
+    pub restorer: usize,
+    pub mask: u64,
+}
+
+pub const SA_RESTORER: u64 = 0x0400_0000;
+
+/// Linux `sa_flags` bits and their native `SigActionFlags` counterparts
+const SA_FLAGS: [(u64, u32); 7] = {
+    use crate::ipc::signal::SigActionFlags as F;
+    [
+        (0x0000_0001, F::SA_NOCLDSTOP),
+        (0x0000_0002, F::SA_NOCLDWAIT),
+        (0x0000_0004, F::SA_SIGINFO),
+        (0x0800_0000, F::SA_ONSTACK),
+        (0x1000_0000, F::SA_RESTART),
+        (0x4000_0000, F::SA_NODEFER),
+        (0x8000_0000, F::SA_RESETHAND),
+    ]
+};
+
+/// Native action flags for Linux `sa_flags`; unknown bits are dropped
+pub fn sa_flags_to_native(flags: u64) -> u32 {
+    SA_FLAGS
+        .iter()
+        .filter(|(linux, _)| flags & linux != 0)
+        .fold(0, |acc, (_, native)| acc | native)
+}
+
+/// Linux `sa_flags` for native action flags
+pub fn sa_flags_from_native(flags: u32) -> u64 {
+    SA_FLAGS
+        .iter()
+        .filter(|(_, native)| flags & native != 0)
+        .fold(0, |acc, (linux, _)| acc | linux)
+}
+
+/// aarch64 open(2) flag bits that sit elsewhere on x86_64 and asm-generic,
+/// which the native ABI follows: (aarch64, native)
+const AARCH64_OPEN_FLAGS: [(i32, i32); 4] = [
+    (0o040000, 0o200000), // O_DIRECTORY
+    (0o100000, 0o400000), // O_NOFOLLOW
+    (0o200000, 0o040000), // O_DIRECT
+    (0o400000, 0o100000), // O_LARGEFILE
+];
+
+fn remap_bits(flags: i32, pairs: impl Iterator<Item = (i32, i32)> + Clone) -> i32 {
+    let moved = pairs.clone().fold(0, |acc, (from, _)| acc | from);
+    pairs
+        .filter(|(from, _)| flags & from != 0)
+        .fold(flags & !moved, |acc, (_, to)| acc | to)
+}
+
+/// Native open flags for aarch64 Linux ones
+pub fn aarch64_open_flags_to_native(flags: i32) -> i32 {
+    remap_bits(flags, AARCH64_OPEN_FLAGS.iter().copied())
+}
+
+/// aarch64 Linux open flags for native ones
+pub fn aarch64_open_flags_from_native(flags: i32) -> i32 {
+    remap_bits(flags, AARCH64_OPEN_FLAGS.iter().map(|&(a, n)| (n, a)))
+}
+
+/// Native open flags for this architecture's Linux ones
+pub fn open_flags_to_native(flags: i32) -> i32 {
+    #[cfg(target_arch = "aarch64")]
+    {
+        aarch64_open_flags_to_native(flags)
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        flags
+    }
+}
+
+/// This architecture's Linux open flags for native ones
+pub fn open_flags_from_native(flags: i32) -> i32 {
+    #[cfg(target_arch = "aarch64")]
+    {
+        aarch64_open_flags_from_native(flags)
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    {
+        flags
+    }
+}
+
+/// `d_type` of a `struct linux_dirent64`
+pub fn dirent_type(file_type: FileType) -> u8 {
+    match file_type {
+        FileType::Fifo => 1,
+        FileType::CharDevice => 2,
+        FileType::Directory => 4,
+        FileType::BlockDevice => 6,
+        FileType::Regular => 8,
+        FileType::Symlink => 10,
+        FileType::Socket => 12,
+    }
+}
+
+/// Size of a `struct linux_dirent64` record for a name, NUL and padding
+/// included
+pub fn dirent64_len(name: &str) -> usize {
+    // d_ino, d_off, d_reclen, d_type, then the name
+    (8 + 8 + 2 + 1 + name.len() + 1).next_multiple_of(8)
+}
+
+/// Append a `struct linux_dirent64` record to `buf`
+pub fn push_dirent64(buf: &mut Vec<u8>, ino: u64, off: i64, d_type: u8, name: &str) {
+    let reclen = dirent64_len(name);
+    let start = buf.len();
+    buf.extend_from_slice(&ino.to_ne_bytes());
+    buf.extend_from_slice(&off.to_ne_bytes());
+    buf.extend_from_slice(&(reclen as u16).to_ne_bytes());
+    buf.push(d_type);
+    buf.extend_from_slice(name.as_bytes());
+    buf.resize(start + reclen, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{offset_of, size_of};
+
+    #[test]
+    fn test_stat_layouts() {
+        assert_eq!(size_of::<X86_64Stat>(), 144);
+        assert_eq!(offset_of!(X86_64Stat, st_mode), 24);
+        assert_eq!(offset_of!(X86_64Stat, st_size), 48);
+        assert_eq!(offset_of!(X86_64Stat, st_mtime), 88);
+
+        assert_eq!(size_of::<GenericStat>(), 128);
+        assert_eq!(offset_of!(GenericStat, st_mode), 16);
+        assert_eq!(offset_of!(GenericStat, st_size), 48);
+        assert_eq!(offset_of!(GenericStat, st_blocks), 64);
+        assert_eq!(offset_of!(GenericStat, st_mtime), 88);
+    }
+
+    #[test]
+    fn test_aarch64_open_flags() {
+        const O_CREAT: i32 = 0o100;
+        // aarch64 O_DIRECTORY | O_NOFOLLOW
+        let linux = O_CREAT | 0o040000 | 0o100000;
+        let native = aarch64_open_flags_to_native(linux);
+        assert_eq!(native, O_CREAT | 0o200000 | 0o400000);
+        assert_eq!(aarch64_open_flags_from_native(native), linux);
+    }
+
+    #[test]
+    fn test_sa_flags_round_trip() {
+        let linux = 0x0000_0004 | 0x1000_0000 | SA_RESTORER;
+        let native = sa_flags_to_native(linux);
+        assert_eq!(sa_flags_from_native(native), linux & !SA_RESTORER);
+    }
+
+    #[test]
+    fn test_dirent64_record() {
+        let mut buf = Vec::new();
+        push_dirent64(&mut buf, 7, 1, dirent_type(FileType::Directory), "bin");
+        assert_eq!(buf.len(), 24);
+        assert_eq!(u16::from_ne_bytes([buf[16], buf[17]]), 24);
+        assert_eq!(buf[18], 4);
+        assert_eq!(&buf[19..23], b"bin\0");
+    }
+}
@@ -6,13 +6,46 @@
 //! - Linux VFS compatibility
 //! - Linux signal handling
 //! - Linux threading (pthread)
+//!
+//! Processes exec'd from Linux binaries run with [`Personality::Linux`]: the
+//! trap handler passes their system calls to [`syscall::syscall`], which
+//! decodes the per-architecture Linux numbers ([`nr`]) and structure layouts
+//! ([`abi`]) and forwards them to the native handlers.
 
 extern crate alloc;
 
+pub mod abi;
+pub mod nr;
+pub mod syscall;
+
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::collections::BTreeMap;
 use crate::compat::*;
+use crate::process::Personality;
+use crate::process::elf::{
+    ElfLoader, EI_OSABI, ELFOSABI_LINUX, NT_GNU_ABI_TAG, NT_NOS_ABI,
+};
+
+/// Choose the system call personality for an executable
+///
+/// Our own user space tags itself with a "NOS" note. A GNU ABI tag or the
+/// Linux OSABI marks a Linux binary; untagged images are treated as Linux
+/// too, since that is what static musl toolchains produce.
+pub fn personality_of(loader: &ElfLoader) -> Personality {
+    if loader.header().e_ident[EI_OSABI] == ELFOSABI_LINUX {
+        return Personality::Linux;
+    }
+    for note in loader.notes() {
+        if note.name == b"GNU" && note.n_type == NT_GNU_ABI_TAG {
+            return Personality::Linux;
+        }
+        if note.name == b"NOS" && note.n_type == NT_NOS_ABI {
+            return Personality::Native;
+        }
+    }
+    Personality::Linux
+}
 
 /// Linux compatibility module
 pub struct LinuxModule {
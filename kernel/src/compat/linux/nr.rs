@@ -0,0 +1,495 @@
+//! Linux system call numbers
+//!
+//! x86_64 keeps its historical table. aarch64 and riscv64 share the
+//! asm-generic one, which leaves out the legacy path calls (`open`, `stat`,
+//! `fork`, ...) in favour of their `*at` and `clone` forms.
+
+/// Linux system calls the personality decodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sysno {
+    // File I/O
+    Read,
+    Write,
+    Open,
+    Close,
+    Stat,
+    Fstat,
+    Lstat,
+    Lseek,
+    Ioctl,
+    Pread64,
+    Pwrite64,
+    Readv,
+    Writev,
+    Access,
+    Pipe,
+    Pipe2,
+    Dup,
+    Dup2,
+    Dup3,
+    Fcntl,
+    Openat,
+    Newfstatat,
+    Getdents64,
+
+    // Filesystem namespace
+    Getcwd,
+    Chdir,
+    Fchdir,
+    Rename,
+    Renameat,
+    Renameat2,
+    Mkdir,
+    Mkdirat,
+    Rmdir,
+    Link,
+    Linkat,
+    Unlink,
+    Unlinkat,
+    Symlink,
+    Symlinkat,
+    Readlink,
+    Readlinkat,
+    Chmod,
+    Fchmod,
+    Fchmodat,
+    Chown,
+    Fchown,
+    Lchown,
+    Fchownat,
+    Faccessat,
+    Faccessat2,
+    Umask,
+    Chroot,
+    Sync,
+    Fsync,
+    Fdatasync,
+    Ftruncate,
+
+    // Memory
+    Brk,
+    Mmap,
+    Munmap,
+    Mprotect,
+    Mremap,
+    Msync,
+    Mincore,
+    Madvise,
+    Mlock,
+    Munlock,
+    Mlock2,
+    Mlockall,
+    Munlockall,
+    Swapon,
+    Swapoff,
+
+    // Processes and threads
+    Clone,
+    Fork,
+    Vfork,
+    Execve,
+    Exit,
+    ExitGroup,
+    Wait4,
+    Getpid,
+    Getppid,
+    Gettid,
+    Getuid,
+    Geteuid,
+    Getgid,
+    Getegid,
+    Setuid,
+    Setgid,
+    Getresuid,
+    Getresgid,
+    Getgroups,
+    Setpgid,
+    Getpgid,
+    Getpgrp,
+    Setsid,
+    Getsid,
+    Getrlimit,
+    Setrlimit,
+    Prlimit64,
+    Prctl,
+    Ptrace,
+    AddKey,
+    RequestKey,
+    Keyctl,
+    ArchPrctl,
+    SetTidAddress,
+    SetRobustList,
+    GetRobustList,
+    Futex,
+    SchedYield,
+    SchedSetaffinity,
+    SchedGetaffinity,
+    Uname,
+    Getrandom,
+    MemfdCreate,
+
+    // Signals
+    Kill,
+    Tkill,
+    Tgkill,
+    RtSigaction,
+    RtSigprocmask,
+    RtSigpending,
+    RtSigtimedwait,
+    RtSigsuspend,
+    RtSigreturn,
+    Sigaltstack,
+    Pause,
+
+    // Time
+    Nanosleep,
+    ClockGettime,
+    ClockGetres,
+    ClockNanosleep,
+    Gettimeofday,
+    Getitimer,
+    Setitimer,
+    Alarm,
+
+    // Sockets
+    Socket,
+    Socketpair,
+    Bind,
+    Listen,
+    Accept,
+    Accept4,
+    Connect,
+    Shutdown,
+    Sendto,
+    Recvfrom,
+    Setsockopt,
+    Getsockopt,
+
+    // Asynchronous I/O
+    IoUringSetup,
+    IoUringEnter,
+    IoUringRegister,
+}
+
+/// Decode an x86_64 system call number
+pub fn x86_64(nr: usize) -> Option<Sysno> {
+    use Sysno::*;
+    Some(match nr {
+        0 => Read,
+        1 => Write,
+        2 => Open,
+        3 => Close,
+        4 => Stat,
+        5 => Fstat,
+        6 => Lstat,
+        8 => Lseek,
+        9 => Mmap,
+        10 => Mprotect,
+        11 => Munmap,
+        12 => Brk,
+        13 => RtSigaction,
+        14 => RtSigprocmask,
+        15 => RtSigreturn,
+        16 => Ioctl,
+        17 => Pread64,
+        18 => Pwrite64,
+        19 => Readv,
+        20 => Writev,
+        21 => Access,
+        22 => Pipe,
+        24 => SchedYield,
+        25 => Mremap,
+        26 => Msync,
+        27 => Mincore,
+        28 => Madvise,
+        32 => Dup,
+        33 => Dup2,
+        34 => Pause,
+        35 => Nanosleep,
+        36 => Getitimer,
+        37 => Alarm,
+        38 => Setitimer,
+        39 => Getpid,
+        41 => Socket,
+        42 => Connect,
+        43 => Accept,
+        44 => Sendto,
+        45 => Recvfrom,
+        48 => Shutdown,
+        49 => Bind,
+        50 => Listen,
+        53 => Socketpair,
+        54 => Setsockopt,
+        55 => Getsockopt,
+        56 => Clone,
+        57 => Fork,
+        58 => Vfork,
+        59 => Execve,
+        60 => Exit,
+        61 => Wait4,
+        62 => Kill,
+        63 => Uname,
+        72 => Fcntl,
+        74 => Fsync,
+        75 => Fdatasync,
+        77 => Ftruncate,
+        79 => Getcwd,
+        80 => Chdir,
+        81 => Fchdir,
+        82 => Rename,
+        83 => Mkdir,
+        84 => Rmdir,
+        86 => Link,
+        87 => Unlink,
+        88 => Symlink,
+        89 => Readlink,
+        90 => Chmod,
+        91 => Fchmod,
+        92 => Chown,
+        93 => Fchown,
+        94 => Lchown,
+        95 => Umask,
+        96 => Gettimeofday,
+        97 => Getrlimit,
+        101 => Ptrace,
+        102 => Getuid,
+        104 => Getgid,
+        105 => Setuid,
+        106 => Setgid,
+        107 => Geteuid,
+        108 => Getegid,
+        109 => Setpgid,
+        110 => Getppid,
+        111 => Getpgrp,
+        112 => Setsid,
+        115 => Getgroups,
+        118 => Getresuid,
+        120 => Getresgid,
+        121 => Getpgid,
+        124 => Getsid,
+        127 => RtSigpending,
+        128 => RtSigtimedwait,
+        130 => RtSigsuspend,
+        131 => Sigaltstack,
+        149 => Mlock,
+        150 => Munlock,
+        151 => Mlockall,
+        152 => Munlockall,
+        157 => Prctl,
+        158 => ArchPrctl,
+        160 => Setrlimit,
+        161 => Chroot,
+        162 => Sync,
+        167 => Swapon,
+        168 => Swapoff,
+        186 => Gettid,
+        200 => Tkill,
+        202 => Futex,
+        203 => SchedSetaffinity,
+        204 => SchedGetaffinity,
+        217 => Getdents64,
+        218 => SetTidAddress,
+        228 => ClockGettime,
+        229 => ClockGetres,
+        230 => ClockNanosleep,
+        231 => ExitGroup,
+        234 => Tgkill,
+        248 => AddKey,
+        249 => RequestKey,
+        250 => Keyctl,
+        257 => Openat,
+        258 => Mkdirat,
+        260 => Fchownat,
+        262 => Newfstatat,
+        263 => Unlinkat,
+        264 => Renameat,
+        265 => Linkat,
+        266 => Symlinkat,
+        267 => Readlinkat,
+        268 => Fchmodat,
+        269 => Faccessat,
+        273 => SetRobustList,
+        274 => GetRobustList,
+        288 => Accept4,
+        292 => Dup3,
+        293 => Pipe2,
+        302 => Prlimit64,
+        316 => Renameat2,
+        318 => Getrandom,
+        319 => MemfdCreate,
+        325 => Mlock2,
+        425 => IoUringSetup,
+        426 => IoUringEnter,
+        427 => IoUringRegister,
+        439 => Faccessat2,
+        _ => return None,
+    })
+}
+
+/// Decode an asm-generic (aarch64, riscv64) system call number
+pub fn generic(nr: usize) -> Option<Sysno> {
+    use Sysno::*;
+    Some(match nr {
+        17 => Getcwd,
+        23 => Dup,
+        24 => Dup3,
+        25 => Fcntl,
+        29 => Ioctl,
+        34 => Mkdirat,
+        35 => Unlinkat,
+        36 => Symlinkat,
+        37 => Linkat,
+        38 => Renameat,
+        46 => Ftruncate,
+        48 => Faccessat,
+        49 => Chdir,
+        50 => Fchdir,
+        51 => Chroot,
+        52 => Fchmod,
+        53 => Fchmodat,
+        54 => Fchownat,
+        55 => Fchown,
+        56 => Openat,
+        57 => Close,
+        59 => Pipe2,
+        61 => Getdents64,
+        62 => Lseek,
+        63 => Read,
+        64 => Write,
+        65 => Readv,
+        66 => Writev,
+        67 => Pread64,
+        68 => Pwrite64,
+        78 => Readlinkat,
+        79 => Newfstatat,
+        80 => Fstat,
+        81 => Sync,
+        82 => Fsync,
+        83 => Fdatasync,
+        93 => Exit,
+        94 => ExitGroup,
+        96 => SetTidAddress,
+        98 => Futex,
+        99 => SetRobustList,
+        100 => GetRobustList,
+        101 => Nanosleep,
+        102 => Getitimer,
+        103 => Setitimer,
+        113 => ClockGettime,
+        114 => ClockGetres,
+        115 => ClockNanosleep,
+        117 => Ptrace,
+        122 => SchedSetaffinity,
+        123 => SchedGetaffinity,
+        124 => SchedYield,
+        129 => Kill,
+        130 => Tkill,
+        131 => Tgkill,
+        132 => Sigaltstack,
+        133 => RtSigsuspend,
+        134 => RtSigaction,
+        135 => RtSigprocmask,
+        136 => RtSigpending,
+        137 => RtSigtimedwait,
+        139 => RtSigreturn,
+        144 => Setgid,
+        146 => Setuid,
+        148 => Getresuid,
+        150 => Getresgid,
+        154 => Setpgid,
+        155 => Getpgid,
+        156 => Getsid,
+        157 => Setsid,
+        158 => Getgroups,
+        160 => Uname,
+        163 => Getrlimit,
+        164 => Setrlimit,
+        166 => Umask,
+        167 => Prctl,
+        169 => Gettimeofday,
+        172 => Getpid,
+        173 => Getppid,
+        174 => Getuid,
+        175 => Geteuid,
+        176 => Getgid,
+        177 => Getegid,
+        178 => Gettid,
+        198 => Socket,
+        199 => Socketpair,
+        200 => Bind,
+        201 => Listen,
+        202 => Accept,
+        203 => Connect,
+        206 => Sendto,
+        207 => Recvfrom,
+        208 => Setsockopt,
+        209 => Getsockopt,
+        210 => Shutdown,
+        214 => Brk,
+        215 => Munmap,
+        216 => Mremap,
+        217 => AddKey,
+        218 => RequestKey,
+        219 => Keyctl,
+        220 => Clone,
+        221 => Execve,
+        222 => Mmap,
+        224 => Swapon,
+        225 => Swapoff,
+        226 => Mprotect,
+        227 => Msync,
+        228 => Mlock,
+        229 => Munlock,
+        230 => Mlockall,
+        231 => Munlockall,
+        232 => Mincore,
+        233 => Madvise,
+        242 => Accept4,
+        260 => Wait4,
+        261 => Prlimit64,
+        276 => Renameat2,
+        278 => Getrandom,
+        279 => MemfdCreate,
+        284 => Mlock2,
+        425 => IoUringSetup,
+        426 => IoUringEnter,
+        427 => IoUringRegister,
+        439 => Faccessat2,
+        _ => return None,
+    })
+}
+
+/// Decode a system call number from this architecture's table
+pub fn decode(nr: usize) -> Option<Sysno> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        x86_64(nr)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        generic(nr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_differ_where_linux_does() {
+        assert_eq!(x86_64(0), Some(Sysno::Read));
+        assert_eq!(generic(63), Some(Sysno::Read));
+        assert_eq!(x86_64(231), Some(Sysno::ExitGroup));
+        assert_eq!(generic(94), Some(Sysno::ExitGroup));
+        assert_eq!(x86_64(262), Some(Sysno::Newfstatat));
+        assert_eq!(generic(79), Some(Sysno::Newfstatat));
+        assert_eq!(x86_64(15), Some(Sysno::RtSigreturn));
+        assert_eq!(generic(139), Some(Sysno::RtSigreturn));
+
+        // The generic table has no legacy path calls
+        assert_eq!(x86_64(2), Some(Sysno::Open));
+        assert_eq!(generic(2), None);
+        assert_eq!(x86_64(57), Some(Sysno::Fork));
+        assert_eq!(generic(57), Some(Sysno::Close));
+    }
+}
@@ -0,0 +1,456 @@
+//! Linux system call entry
+//!
+//! Decodes a Linux call from a process with the Linux personality and hands
+//! it to the native handler that implements it, adapting arguments and
+//! structure layouts on the way. Handlers here speak Linux errno values,
+//! which the native errno table already matches.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use super::abi::{self, KSigaction, Stat, Utsname};
+use super::nr::{self, Sysno};
+use crate::posix::{AT_FDCWD, AT_SYMLINK_NOFOLLOW};
+use crate::process::{myproc, PROC_TABLE};
+use crate::reliability::errno::*;
+use crate::subsystems::mm::uaccess::{self, UserPtr};
+use crate::subsystems::mm::vm::{copyout, PageTable};
+use crate::syscalls::common::syscall_error_to_errno;
+
+type LinuxResult = Result<u64, i32>;
+
+/// Run Linux system call `nr`, returning the value or `-errno` for the
+/// return register
+pub fn syscall(nr: usize, args: [u64; 6]) -> isize {
+    let Some(sysno) = nr::decode(nr) else {
+        return errno_neg(ENOSYS);
+    };
+    match handle(sysno, &args) {
+        Ok(value) => value as isize,
+        Err(errno) => errno_neg(errno),
+    }
+}
+
+/// Call a native handler by its category number
+fn native(num: u32, args: &[u64]) -> LinuxResult {
+    crate::syscalls::dispatch_native(num, args).map_err(syscall_error_to_errno)
+}
+
+fn handle(sysno: Sysno, a: &[u64; 6]) -> LinuxResult {
+    use Sysno::*;
+    let at_fdcwd = AT_FDCWD as u64;
+
+    match sysno {
+        // File I/O
+        Read => native(0x2002, &a[..3]),
+        Write => native(0x2003, &a[..3]),
+        Open => native(0x2008, &[at_fdcwd, a[0], open_flags(a[1]), a[2]]),
+        Openat => native(0x2008, &[a[0], a[1], open_flags(a[2]), a[3]]),
+        Close => native(0x2001, &a[..1]),
+        Lseek => native(0x2004, &a[..3]),
+        Ioctl => ioctl(a),
+        Pread64 => positioned(0x2002, a),
+        Pwrite64 => positioned(0x2003, a),
+        Readv => vectored(0x2002, a),
+        Writev => vectored(0x2003, a),
+        Pipe => native(0x200E, &a[..1]),
+        Pipe2 => native(0x200F, &[a[0], open_flags(a[1])]),
+        Dup => native(0x200B, &a[..1]),
+        Dup2 => native(0x200C, &a[..2]),
+        Dup3 => native(0x200D, &[a[0], a[1], open_flags(a[2])]),
+        Fcntl => fcntl(a),
+        Fstat => fstat(a[0] as i32, a[1] as usize),
+        Stat => stat_at(AT_FDCWD, a[0], a[1] as usize, 0),
+        Lstat => stat_at(AT_FDCWD, a[0], a[1] as usize, AT_SYMLINK_NOFOLLOW),
+        Newfstatat => stat_at(a[0] as i32, a[1], a[2] as usize, a[3] as i32),
+        Getdents64 => getdents64(a[0] as i32, a[1] as usize, a[2] as usize),
+
+        // Filesystem namespace
+        // Linux counts the terminating NUL in getcwd's return value
+        Getcwd => native(0x7002, &a[..2]).map(|len| len + 1),
+        Chdir => native(0x7000, &a[..1]),
+        Fchdir => native(0x7001, &a[..1]),
+        Rename => native(0x7006, &a[..2]),
+        Renameat => native(0x7017, &[a[0], a[1], a[2], a[3], 0]),
+        Renameat2 => native(0x7017, &a[..5]),
+        Mkdir => native(0x7003, &a[..2]),
+        Mkdirat => native(0x7015, &a[..3]),
+        Rmdir => native(0x7004, &a[..1]),
+        Link => native(0x7007, &a[..2]),
+        Linkat => native(0x7018, &a[..5]),
+        Unlink => native(0x7005, &a[..1]),
+        Unlinkat => native(0x7016, &a[..3]),
+        Symlink => native(0x7008, &a[..2]),
+        Symlinkat => native(0x7019, &a[..3]),
+        Readlink => native(0x7009, &a[..3]),
+        Readlinkat => native(0x701A, &a[..4]),
+        Chmod => native(0x700A, &a[..2]),
+        Fchmod => native(0x700B, &a[..2]),
+        Fchmodat => native(0x701B, &[a[0], a[1], a[2], 0]),
+        Chown => native(0x700C, &a[..3]),
+        Fchown => native(0x700D, &a[..3]),
+        Lchown => native(0x700E, &a[..3]),
+        Fchownat => native(0x701C, &a[..5]),
+        Access => native(0x7012, &a[..2]),
+        Faccessat => native(0x701E, &[a[0], a[1], a[2], 0]),
+        Faccessat2 => native(0x701E, &a[..4]),
+        Umask => native(0x700F, &a[..1]),
+        Chroot => native(0x7014, &a[..1]),
+        Sync => native(0x7021, &[]),
+        Fsync => native(0x701F, &a[..1]),
+        Fdatasync => native(0x7020, &a[..1]),
+        Ftruncate => native(0x7022, &a[..2]),
+
+        // Memory
+        // brk(2) reports failure by returning the unchanged break
+        Brk => native(0x3000, &a[..1]).or_else(|_| native(0x3000, &[0])),
+        Mmap => native(0x3001, &a[..6]),
+        Munmap => native(0x3002, &a[..2]),
+        Mprotect => native(0x3003, &a[..3]),
+        Madvise => native(0x3004, &a[..3]),
+        Mlock => native(0x3005, &a[..2]),
+        Munlock => native(0x3006, &a[..2]),
+        Mlockall => native(0x3007, &a[..1]),
+        Munlockall => native(0x3008, &[]),
+        Mlock2 => native(0x3011, &a[..3]),
+        Mincore => native(0x3009, &a[..3]),
+        Msync => native(0x300A, &a[..3]),
+        Mremap => native(0x300B, &a[..5]),
+        Swapon => native(0x3012, &a[..2]),
+        Swapoff => native(0x3013, &a[..1]),
+
+        // Processes and threads
+        Fork | Vfork => native(0x1000, &[]),
+        Clone => clone(a),
+        Execve => native(0x1001, &a[..3]),
+        // exit ends the calling thread, exit_group all of them
+        Exit => native(0x8004, &a[..1]),
+        ExitGroup => native(0x1003, &a[..1]),
+        Wait4 => native(0x1020, &a[..4]),
+        Getpid => native(0x1004, &[]),
+        Getppid => native(0x1005, &[]),
+        Gettid => native(0x8006, &[]),
+        Getuid => native(0x1007, &[]),
+        Getgid => native(0x1009, &[]),
+        Geteuid => with_proc(|p| p.euid as u64),
+        Getegid => with_proc(|p| p.egid as u64),
+        Setuid => native(0x1006, &a[..1]),
+        Setgid => native(0x1008, &a[..1]),
+        Getresuid => resids(a, |p| [p.uid, p.euid, p.suid]),
+        Getresgid => resids(a, |p| [p.gid, p.egid, p.sgid]),
+        // No supplementary groups
+        Getgroups => Ok(0),
+        Setpgid => native(0x101C, &a[..2]),
+        Getpgid => native(0x101D, &a[..1]),
+        Getpgrp => native(0x101D, &[0]),
+        Setsid => native(0x100A, &[]),
+        Getsid => native(0x100B, &a[..1]),
+        Getrlimit => native(0x101E, &a[..2]),
+        Setrlimit => native(0x101F, &a[..2]),
+        Prlimit64 => native(0x1023, &a[..4]),
+        Prctl => native(0x1016, &a[..5]),
+        Ptrace => native(0x1024, &a[..4]),
+        AddKey => native(0x1025, &a[..5]),
+        RequestKey => native(0x1026, &a[..4]),
+        Keyctl => native(0x1027, &a[..5]),
+        ArchPrctl => arch_prctl(a[0] as i32, a[1] as usize),
+        SetTidAddress => native(0x8008, &a[..1]),
+        SetRobustList => native(0x800A, &a[..2]),
+        GetRobustList => native(0x800B, &a[..3]),
+        Futex => native(0x8009, &a[..6]),
+        SchedYield => native(0x100D, &[]),
+        SchedSetaffinity => native(0x800E, &a[..3]),
+        SchedGetaffinity => native(0x800D, &a[..3]),
+        Uname => copy_to_user(a[0] as usize, &Utsname::new()).map(|_| 0),
+        Getrandom => native(0xB000, &a[..3]),
+        MemfdCreate => native(0xB001, &a[..2]),
+
+        // Signals
+        Kill => native(0x5000, &a[..2]),
+        Tkill => native(0x500D, &a[..2]),
+        Tgkill => native(0x500E, &a[..3]),
+        RtSigaction => rt_sigaction(a[0] as u32, a[1] as usize, a[2] as usize, a[3] as usize),
+        RtSigprocmask => native(0x5008, &a[..4]),
+        RtSigpending => native(0x5009, &a[..2]),
+        RtSigtimedwait => native(0x500A, &a[..4]),
+        RtSigsuspend => native(0x500C, &a[..2]),
+        // The SA_RESTORER trampoline rt_sigaction installs lands here
+        RtSigreturn => native(0x500F, &[]),
+        Sigaltstack => native(0x5005, &a[..2]),
+        Pause => native(0x5006, &[]),
+
+        // Time
+        Nanosleep => native(0x6006, &a[..2]),
+        ClockGettime => native(0x6003, &a[..2]),
+        ClockGetres => native(0x6005, &a[..2]),
+        ClockNanosleep => native(0x6007, &a[..4]),
+        Gettimeofday => native(0x6001, &a[..2]),
+        Getitimer => native(0x600A, &a[..2]),
+        Setitimer => native(0x6009, &a[..3]),
+        Alarm => native(0x6008, &a[..1]),
+
+        // Sockets
+        Socket => socket(a),
+        Socketpair => net(crate::syscalls::network::socket::sys_socketpair(&a[..4])),
+        Bind => net(crate::syscalls::network::socket::sys_bind(&a[..3])),
+        Listen => net(crate::syscalls::network::socket::sys_listen(&a[..2])),
+        Accept => net(crate::syscalls::network::socket::sys_accept(&a[..3])),
+        Accept4 => {
+            // SOCK_CLOEXEC has nothing to set; SOCK_NONBLOCK is not supported
+            if a[3] & !SOCK_CLOEXEC != 0 {
+                return Err(EINVAL);
+            }
+            net(crate::syscalls::network::socket::sys_accept(&a[..3]))
+        }
+        Connect => net(crate::syscalls::network::socket::sys_connect(&a[..3])),
+        Shutdown => net(crate::syscalls::network::socket::sys_shutdown(&a[..2])),
+        Sendto => net(crate::syscalls::network::data::sys_sendto(&a[..6])),
+        Recvfrom => net(crate::syscalls::network::data::sys_recvfrom(&a[..6])),
+        Setsockopt => net(crate::syscalls::network::options::sys_setsockopt(&a[..5])),
+        Getsockopt => net(crate::syscalls::network::options::sys_getsockopt(&a[..5])),
+
+        // Asynchronous I/O; the rings and SQEs share Linux's layout
+        IoUringSetup => native(0x9006, &a[..2]),
+        IoUringEnter => native(0x9007, &a[..5]),
+        IoUringRegister => native(0x9008, &a[..4]),
+    }
+}
+
+const SOCK_CLOEXEC: u64 = 0o2000000;
+
+fn net(result: crate::syscalls::common::SyscallResult) -> LinuxResult {
+    result.map_err(syscall_error_to_errno)
+}
+
+fn open_flags(flags: u64) -> u64 {
+    abi::open_flags_to_native(flags as i32) as u32 as u64
+}
+
+/// Page table of the calling process
+fn pagetable() -> Result<*mut PageTable, i32> {
+    let pid = myproc().ok_or(ESRCH)?;
+    let table = PROC_TABLE.lock();
+    let proc = table.find_ref(pid).ok_or(ESRCH)?;
+    if proc.pagetable.is_null() {
+        return Err(EFAULT);
+    }
+    Ok(proc.pagetable)
+}
+
+/// Read a field of the calling process
+fn with_proc(f: impl FnOnce(&crate::process::Proc) -> u64) -> LinuxResult {
+    let pid = myproc().ok_or(ESRCH)?;
+    let table = PROC_TABLE.lock();
+    table.find_ref(pid).map(f).ok_or(ESRCH)
+}
+
+fn copy_to_user<T: Copy>(addr: usize, value: &T) -> Result<(), i32> {
+    UserPtr::new(addr).write(value).map_err(|_| EFAULT)
+}
+
+fn copy_from_user<T: Copy>(addr: usize) -> Result<T, i32> {
+    UserPtr::new(addr).read().map_err(|_| EFAULT)
+}
+
+fn fstat(fd: i32, statbuf: usize) -> LinuxResult {
+    let file_idx = crate::process::fdlookup(fd).ok_or(EBADF)?;
+    let attr = crate::fs::file::file_attr(file_idx).map_err(|_| EBADF)?;
+    copy_to_user(statbuf, &Stat::from(&attr))?;
+    Ok(0)
+}
+
+fn stat_at(dirfd: i32, path: u64, statbuf: usize, flags: i32) -> LinuxResult {
+    let attr = crate::syscalls::fs::handlers::stat_attr_at(dirfd, path as usize, flags)
+        .map_err(|e| syscall_error_to_errno(crate::syscalls::fs_error(e)))?;
+    copy_to_user(statbuf, &Stat::from(&attr))?;
+    Ok(0)
+}
+
+/// readv/writev: one native read or write per iovec, stopping at the first
+/// short transfer
+fn vectored(num: u32, a: &[u64; 6]) -> LinuxResult {
+    let (fd, iov, iovcnt) = (a[0], a[1] as usize, a[2] as usize);
+    let slices = uaccess::import_iovec(iov, iovcnt).map_err(syscall_error_to_errno)?;
+
+    let mut total = 0u64;
+    for vec in slices {
+        if vec.is_empty() {
+            continue;
+        }
+        match native(num, &[fd, vec.addr() as u64, vec.len() as u64]) {
+            Ok(n) => {
+                total += n;
+                if n < vec.len() as u64 {
+                    break;
+                }
+            }
+            // Report what already moved; the error shows up on the next call
+            Err(_) if total > 0 => break,
+            Err(errno) => return Err(errno),
+        }
+    }
+    Ok(total)
+}
+
+/// pread64/pwrite64 on top of lseek, putting the file offset back afterwards
+fn positioned(num: u32, a: &[u64; 6]) -> LinuxResult {
+    const SEEK_SET: u64 = 0;
+    const SEEK_CUR: u64 = 1;
+
+    let fd = a[0];
+    if (a[3] as i64) < 0 {
+        return Err(EINVAL);
+    }
+    let saved = native(0x2004, &[fd, 0, SEEK_CUR])?;
+    native(0x2004, &[fd, a[3], SEEK_SET])?;
+    let result = native(num, &a[..3]);
+    native(0x2004, &[fd, saved, SEEK_SET])?;
+    result
+}
+
+fn ioctl(a: &[u64; 6]) -> LinuxResult {
+    match native(0x2009, &a[..3]) {
+        // Terminal and other driver requests have no native handler
+        Err(EINVAL) | Err(EOPNOTSUPP) => Err(ENOTTY),
+        result => result,
+    }
+}
+
+fn fcntl(a: &[u64; 6]) -> LinuxResult {
+    use crate::posix::{F_GETFL, F_SETFL};
+
+    match a[1] as i32 {
+        F_GETFL => native(0x200A, &a[..3]).map(|flags| abi::open_flags_from_native(flags as i32) as u32 as u64),
+        F_SETFL => native(0x200A, &[a[0], a[1], open_flags(a[2])]),
+        _ => native(0x200A, &a[..3]),
+    }
+}
+
+fn getdents64(fd: i32, dirp: usize, count: usize) -> LinuxResult {
+    let file_idx = crate::process::fdlookup(fd).ok_or(EBADF)?;
+
+    // Entries come back as a whole list; the file offset is the index of
+    // the next one to hand out
+    let (inode, start) = {
+        let table = crate::fs::file::FILE_TABLE.lock();
+        let file = table.get(file_idx).ok_or(EBADF)?;
+        let vfs_file = file.vfs_file.as_ref().ok_or(ENOTDIR)?;
+        (vfs_file.inode.clone(), vfs_file.offset as usize)
+    };
+    let entries = inode.readdir(0).map_err(|e| syscall_error_to_errno(e.into()))?;
+
+    let mut buf = Vec::new();
+    let mut next = start;
+    for (i, entry) in entries.iter().enumerate().skip(start) {
+        if buf.len() + abi::dirent64_len(&entry.name) > count {
+            break;
+        }
+        abi::push_dirent64(&mut buf, entry.ino, (i + 1) as i64, abi::dirent_type(entry.file_type), &entry.name);
+        next = i + 1;
+    }
+    if buf.is_empty() {
+        // End of directory, or the buffer cannot hold even one record
+        return if next < entries.len() { Err(EINVAL) } else { Ok(0) };
+    }
+
+    let pagetable = pagetable()?;
+    unsafe {
+        copyout(pagetable, dirp, buf.as_ptr(), buf.len()).map_err(|_| EFAULT)?;
+    }
+    let mut table = crate::fs::file::FILE_TABLE.lock();
+    if let Some(vfs_file) = table.get_mut(file_idx).and_then(|f| f.vfs_file.as_mut()) {
+        vfs_file.offset = next as u64;
+    }
+    Ok(buf.len() as u64)
+}
+
+/// clone(2): x86_64 passes (flags, stack, parent_tid, child_tid, tls), the
+/// asm-generic architectures swap the last two
+fn clone(a: &[u64; 6]) -> LinuxResult {
+    #[cfg(target_arch = "x86_64")]
+    let args = [a[0], a[1], a[2], a[3], a[4]];
+    #[cfg(not(target_arch = "x86_64"))]
+    let args = [a[0], a[1], a[2], a[4], a[3]];
+    native(0x8000, &args)
+}
+
+fn resids(a: &[u64; 6], ids: impl FnOnce(&crate::process::Proc) -> [u32; 3]) -> LinuxResult {
+    let pid = myproc().ok_or(ESRCH)?;
+    let ids = PROC_TABLE.lock().find_ref(pid).map(ids).ok_or(ESRCH)?;
+    for (addr, id) in a[..3].iter().zip(ids) {
+        copy_to_user(*addr as usize, &id)?;
+    }
+    Ok(0)
+}
+
+/// x86_64 arch_prctl: the FS base is the thread pointer
+fn arch_prctl(code: i32, addr: usize) -> LinuxResult {
+    const ARCH_SET_FS: i32 = 0x1002;
+    const ARCH_GET_FS: i32 = 0x1003;
+
+    match code {
+        ARCH_SET_FS => {
+            crate::process::thread::thread_set_tls(addr);
+            Ok(0)
+        }
+        ARCH_GET_FS => {
+            copy_to_user(addr, &crate::process::thread::thread_get_tls())?;
+            Ok(0)
+        }
+        _ => Err(EINVAL),
+    }
+}
+
+/// rt_sigaction(2) with the Linux `struct sigaction` layout
+fn rt_sigaction(sig: u32, act: usize, oldact: usize, sigsetsize: usize) -> LinuxResult {
+    use crate::ipc::signal::{SigAction, SigActionFlags, SigSet, NSIG};
+
+    if sigsetsize != core::mem::size_of::<u64>() {
+        return Err(EINVAL);
+    }
+    if sig == 0 || sig as usize >= NSIG {
+        return Err(EINVAL);
+    }
+    let new: Option<KSigaction> = if act != 0 { Some(copy_from_user(act)?) } else { None };
+
+    let old = {
+        let pid = myproc().ok_or(ESRCH)?;
+        let table = PROC_TABLE.lock();
+        let proc = table.find_ref(pid).ok_or(ESRCH)?;
+        let signals = proc.signals.as_ref().ok_or(ESRCH)?;
+        let old = signals.get_action(sig);
+        if let Some(new) = new {
+            let action = SigAction {
+                handler: new.handler,
+                flags: SigActionFlags(abi::sa_flags_to_native(new.flags)),
+                mask: SigSet::from_bits(new.mask),
+                #[cfg(not(target_arch = "riscv64"))]
+                restorer: new.restorer,
+                #[cfg(target_arch = "riscv64")]
+                restorer: 0,
+            };
+            // SIGKILL and SIGSTOP keep their default action
+            signals.set_action(sig, action).map_err(|_| EINVAL)?;
+        }
+        old
+    };
+
+    if oldact != 0 {
+        let old = KSigaction {
+            handler: old.handler,
+            flags: abi::sa_flags_from_native(old.flags.0),
+            #[cfg(not(target_arch = "riscv64"))]
+            restorer: old.restorer,
+            mask: old.mask.bits(),
+        };
+        copy_to_user(oldact, &old)?;
+    }
+    Ok(0)
+}
+
+fn socket(a: &[u64; 6]) -> LinuxResult {
+    // Close-on-exec is not tracked per descriptor, so SOCK_CLOEXEC is dropped
+    let args = [a[0], a[1] & !SOCK_CLOEXEC, a[2]];
+    net(crate::syscalls::network::socket::sys_socket(&args))
+}
@@ -94,6 +94,7 @@ pub fn init_kernel_core(boot_params: Option<&BootParameters>) {
 
     // Initialize drivers
     crate::platform::drivers::init();
+    crate::subsystems::block::init();
     crate::println!("[boot] drivers initialized");
     monitoring::timeline::record("drivers_init");
 
@@ -230,6 +231,15 @@ pub fn init_kernel_core(boot_params: Option<&BootParameters>) {
     crate::subsystems::process::thread::init();
     crate::println!("[boot] threading subsystem initialized");
     
+    // Start page cache writeback
+    crate::vfs::page_cache::writeback::start();
+    // and kswapd
+    crate::subsystems::mm::swap::reclaim::start();
+
+    // Start network polling
+    #[cfg(all(not(feature = "lazy_init"), feature = "net_stack"))]
+    crate::subsystems::net::start();
+    
     // Initialize unified scheduler with priority queues
     {
         use crate::sched::unified::init_unified_scheduler;
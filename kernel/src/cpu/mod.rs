@@ -433,11 +433,17 @@ pub fn start_aps() {
 
 #[cfg(target_arch = "aarch64")]
 pub fn start_aps() {
-    // On AArch64, we need to use PSCI to bring up secondary cores
-    // This is simplified
-    crate::println!("cpu: starting secondary cores via PSCI...");
-    
-    // PSCI CPU_ON call would go here
+    // On AArch64, secondary cores are brought up with PSCI CPU_ON; the
+    // device tree lists their MPIDRs and how to reach the firmware
+    let Some(version) = crate::drivers::psci::version() else {
+        crate::println!("cpu: no PSCI firmware in the device tree, running uniprocessor");
+        return;
+    };
+    let cpus = crate::drivers::platform::platform_info().map(|info| info.cpus).unwrap_or_default();
+    crate::println!("cpu: PSCI {}.{}, {} cpus in the device tree", version.0, version.1, cpus.len());
+
+    // CPU_ON needs a physical entry point that sets up the stack and MMU
+    // before entering Rust; secondary cores stay off until one exists
 }
 
 #[cfg(target_arch = "x86_64")]
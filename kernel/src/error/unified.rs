@@ -144,6 +144,11 @@ pub enum SyscallError {
     ConnectionReset,
     BrokenPipe,
     TimedOut,
+    KeyNotAvailable,
+    KeyExpired,
+    KeyRevoked,
+    KeyRejected,
+    QuotaExceeded,
 }
 
 /// 驱动程序相关错误
@@ -447,6 +452,11 @@ impl SyscallError {
             SyscallError::ConnectionReset => crate::reliability::errno::ECONNRESET,
             SyscallError::BrokenPipe => crate::reliability::errno::EPIPE,
             SyscallError::TimedOut => crate::reliability::errno::ETIMEDOUT,
+            SyscallError::KeyNotAvailable => crate::reliability::errno::ENOKEY,
+            SyscallError::KeyExpired => crate::reliability::errno::EKEYEXPIRED,
+            SyscallError::KeyRevoked => crate::reliability::errno::EKEYREVOKED,
+            SyscallError::KeyRejected => crate::reliability::errno::EKEYREJECTED,
+            SyscallError::QuotaExceeded => crate::reliability::errno::EDQUOT,
         }
     }
 }
@@ -589,6 +599,11 @@ impl From<crate::subsystems::syscalls::common::SyscallError> for SyscallError {
             crate::subsystems::syscalls::common::SyscallError::ConnectionReset => SyscallError::ConnectionReset,
             crate::subsystems::syscalls::common::SyscallError::BrokenPipe => SyscallError::BrokenPipe,
             crate::subsystems::syscalls::common::SyscallError::TimedOut => SyscallError::TimedOut,
+            crate::subsystems::syscalls::common::SyscallError::KeyNotAvailable => SyscallError::KeyNotAvailable,
+            crate::subsystems::syscalls::common::SyscallError::KeyExpired => SyscallError::KeyExpired,
+            crate::subsystems::syscalls::common::SyscallError::KeyRevoked => SyscallError::KeyRevoked,
+            crate::subsystems::syscalls::common::SyscallError::KeyRejected => SyscallError::KeyRejected,
+            crate::subsystems::syscalls::common::SyscallError::QuotaExceeded => SyscallError::QuotaExceeded,
         }
     }
 }
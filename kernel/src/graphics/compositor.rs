@@ -10,7 +10,7 @@ use alloc::collections::BTreeMap;
 use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use crate::subsystems::sync::Mutex;
 use crate::reliability::errno::{EINVAL, ENOMEM};
-use crate::graphics::surface::{Surface, SurfaceId, SurfaceManager, DirtyRect};
+use crate::graphics::surface::{Surface, SurfaceBuffer, SurfaceState};
 use crate::graphics::surface::get_surface_manager;
 use crate::graphics::vsync::VsyncManager;
 
@@ -43,6 +43,8 @@ pub struct Compositor {
     frame_count: AtomicU32,
     /// Last frame time (nanoseconds)
     last_frame_time: AtomicU32,
+    /// Something changed since the last repaint
+    needs_repaint: AtomicBool,
 }
 
 impl Compositor {
@@ -51,23 +53,22 @@ impl Compositor {
         let stride = (width as usize * 4).next_multiple_of(64); // ARGB8888 = 4 bytes per pixel
         let size = stride * height as usize;
         
-        // Allocate framebuffer
-        let framebuffer_addr = crate::subsystems::mm::kalloc(size).ok_or(ENOMEM)?;
-        
-        // Initialize framebuffer to black
-        unsafe {
-            core::ptr::write_bytes(framebuffer_addr as *mut u8, 0, size);
+        // Allocate framebuffer, zeroed (black)
+        let framebuffer_addr = crate::subsystems::mm::kalloc_pages(size.div_ceil(crate::subsystems::mm::PAGE_SIZE));
+        if framebuffer_addr.is_null() {
+            return Err(ENOMEM);
         }
         
         Ok(Self {
             state: AtomicBool::new(false),
-            framebuffer_addr,
+            framebuffer_addr: framebuffer_addr as usize,
             framebuffer_width: width,
             framebuffer_height: height,
             framebuffer_stride: stride as u32,
             vsync: VsyncManager::new(),
             frame_count: AtomicU32::new(0),
             last_frame_time: AtomicU32::new(0),
+            needs_repaint: AtomicBool::new(true),
         })
     }
     
@@ -86,8 +87,18 @@ impl Compositor {
         crate::println!("[compositor] Stopped compositor");
     }
     
+    /// Ask for the framebuffer to be redrawn on the next frame
+    ///
+    /// Called whenever a surface's contents, position or stacking change.
+    pub fn schedule_repaint(&self) {
+        self.needs_repaint.store(true, Ordering::Release);
+    }
+    
     /// Composite all surfaces to framebuffer
     /// This is called on VSync or when surfaces are updated
+    ///
+    /// The framebuffer is only redrawn when a repaint was scheduled; either
+    /// way the call returns at the next VSync, which paces the frame loop.
     pub fn composite(&self) -> Result<(), i32> {
         if !self.state.load(Ordering::Acquire) {
             return Ok(()); // Compositor not running
@@ -95,43 +106,8 @@ impl Compositor {
         
         let start_time = crate::subsystems::time::hrtime_nanos();
         
-        // Get all surfaces, sorted by z-order
-        let surface_manager = get_surface_manager();
-        let surface_ids = surface_manager.get_all_surfaces();
-        
-        // Sort surfaces by z-order (lower z-order first = rendered first)
-        // For now, use a simplified approach - in real implementation, we'd need
-        // to handle locking properly
-        let mut surfaces: Vec<(SurfaceId, i32)> = Vec::new();
-        
-        // Collect surface info (simplified - real implementation would use Arc<Mutex<Surface>>)
-        {
-            let surfaces_map = surface_manager.surfaces.lock();
-            for (&id, surface) in surfaces_map.iter() {
-                if surface.state == crate::graphics::surface::SurfaceState::Ready ||
-                   surface.state == crate::graphics::surface::SurfaceState::Dirty {
-                    surfaces.push((id, surface.z_order));
-                }
-            }
-        }
-        surfaces.sort_by_key(|(_, z)| *z);
-        
-        // Clear framebuffer (or use dirty rectangle optimization)
-        // For now, we composite all surfaces
-        
-        // Composite each surface
-        for (id, _) in surfaces {
-            let surfaces_map = surface_manager.surfaces.lock();
-            if let Some(surface) = surfaces_map.get(&id) {
-                if let Some(front_addr) = surface.get_front_buffer_addr() {
-                    drop(surfaces_map);
-                    self.composite_surface(surface, front_addr)?;
-                } else {
-                    drop(surfaces_map);
-                }
-            } else {
-                drop(surfaces_map);
-            }
+        if self.needs_repaint.swap(false, Ordering::AcqRel) {
+            self.repaint();
         }
         
         // Wait for VSync before presenting
@@ -145,82 +121,95 @@ impl Compositor {
         Ok(())
     }
     
-    /// Composite a single surface to framebuffer
-    /// Uses GPU acceleration if available
-    fn composite_surface(&self, surface: &Surface, surface_addr: usize) -> Result<(), i32> {
-        // Calculate intersection with framebuffer
-        let src_x = 0.max(-surface.x);
-        let src_y = 0.max(-surface.y);
-        let dst_x = 0.max(surface.x);
-        let dst_y = 0.max(surface.y);
-        
-        let width = (self.framebuffer_width as i32 - dst_x).min(surface.width as i32 - src_x) as u32;
-        let height = (self.framebuffer_height as i32 - dst_y).min(surface.height as i32 - src_y) as u32;
-        
-        if width == 0 || height == 0 {
-            return Ok(()); // Surface is outside framebuffer
+    /// Redraw the whole framebuffer from the surfaces' front buffers
+    fn repaint(&self) {
+        let size = self.framebuffer_stride as usize * self.framebuffer_height as usize;
+        unsafe {
+            core::ptr::write_bytes(self.framebuffer_addr as *mut u8, 0, size);
         }
         
-        // Try GPU-accelerated composition if available
-        if let Some(buffer_handle) = surface.front_buffer.as_ref().map(|b| b.handle) {
-            let buffer_manager = crate::graphics::buffer::get_buffer_manager();
-            let buffer = buffer_manager.get_buffer(buffer_handle);
-            
-            if let Some(buf) = buffer {
-                if buf.gpu_resource_id != 0 {
-                    // Use GPU-accelerated composition
-                    return self.composite_surface_gpu(&buf, src_x as u32, src_y as u32, dst_x as u32, dst_y as u32, width, height);
-                }
+        // Surfaces are drawn bottom to top; the table stays locked so none
+        // can be resized or destroyed underneath
+        let surface_manager = get_surface_manager();
+        let surfaces_map = surface_manager.surfaces.lock();
+        let mut surfaces: Vec<&Surface> = surfaces_map
+            .values()
+            .filter(|s| matches!(s.state, SurfaceState::Ready | SurfaceState::Dirty))
+            .collect();
+        surfaces.sort_by_key(|s| s.z_order);
+        
+        for surface in surfaces {
+            if let Some(front) = surface.front_buffer.as_ref() {
+                self.composite_surface(surface, front);
             }
         }
+    }
+    
+    /// Composite a single surface to framebuffer
+    ///
+    /// Only 32-bit formats are drawn. Opaque surfaces are copied; others
+    /// are blended over what is already there using their (premultiplied)
+    /// alpha.
+    fn composite_surface(&self, surface: &Surface, buffer: &SurfaceBuffer) {
+        if buffer.format.bytes_per_pixel() != 4 {
+            return;
+        }
         
-        // Fallback to CPU-based composition
-        let src_stride = surface.front_buffer.as_ref().map(|b| b.stride).unwrap_or(surface.width * 4);
-        let dst_stride = self.framebuffer_stride;
+        // Clip against the framebuffer
+        let left = surface.x.max(0);
+        let top = surface.y.max(0);
+        let right = (surface.x as i64 + surface.width as i64).min(self.framebuffer_width as i64);
+        let bottom = (surface.y as i64 + surface.height as i64).min(self.framebuffer_height as i64);
+        if right <= left as i64 || bottom <= top as i64 {
+            return; // Surface is outside framebuffer
+        }
+        let width = (right - left as i64) as usize;
+        let src_x = (left - surface.x) as usize;
+        let src_y = (top - surface.y) as usize;
         
-        unsafe {
-            let src_ptr = (surface_addr + (src_y as usize * src_stride as usize) + (src_x as usize * 4)) as *const u8;
-            let dst_ptr = (self.framebuffer_addr + (dst_y as usize * dst_stride as usize) + (dst_x as usize * 4)) as *mut u8;
-            
-            // Copy scanlines
-            for y in 0..height {
-                core::ptr::copy_nonoverlapping(
-                    src_ptr.add((y as usize * src_stride as usize)),
-                    dst_ptr.add((y as usize * dst_stride as usize)),
-                    (width as usize * 4),
-                );
+        for row in 0..(bottom - top as i64) as usize {
+            unsafe {
+                let src = (buffer.addr + (src_y + row) * buffer.stride as usize + src_x * 4) as *const u32;
+                let dst = (self.framebuffer_addr + (top as usize + row) * self.framebuffer_stride as usize + left as usize * 4) as *mut u32;
+                if surface.flags.opaque {
+                    core::ptr::copy_nonoverlapping(src, dst, width);
+                    continue;
+                }
+                for i in 0..width {
+                    let pixel = *src.add(i);
+                    *dst.add(i) = blend(pixel, *dst.add(i));
+                }
             }
         }
-        
-        Ok(())
     }
     
-    /// Composite surface using GPU acceleration
-    fn composite_surface_gpu(
-        &self,
-        buffer: &crate::graphics::buffer::GraphicsBuffer,
-        src_x: u32,
-        src_y: u32,
-        dst_x: u32,
-        dst_y: u32,
-        width: u32,
-        height: u32,
-    ) -> Result<(), i32> {
-        // Transfer surface buffer to GPU
-        let buffer_manager = crate::graphics::buffer::get_buffer_manager();
-        buffer_manager.transfer_to_gpu(buffer.handle, src_x, src_y, width, height)?;
-        buffer_manager.flush_gpu_buffer(buffer.handle, src_x, src_y, width, height)?;
-        
-        // In real implementation, we would:
-        // 1. Use GPU blit command to composite directly to framebuffer
-        // 2. Or use GPU resource as scanout if it's the only surface
-        // 3. Handle alpha blending on GPU
-        
-        // For now, fallback to CPU copy after GPU transfer
-        // This is a placeholder - real implementation would use GPU blit
-        
-        crate::println!("[compositor] GPU-accelerated composition (resource: {}, {}x{})", buffer.gpu_resource_id, width, height);
-        Ok(())
+    /// Copy framebuffer pixels starting at (x, y) into `out`
+    ///
+    /// `out` is filled with up to `out.len() / 4` pixels of row `y`; pixels
+    /// off the framebuffer read as zero. This is how composited output is
+    /// checked without a display.
+    pub fn read_pixels(&self, x: i32, y: i32, out: &mut [u8]) {
+        out.fill(0);
+        if y < 0 || y as u32 >= self.framebuffer_height {
+            return;
+        }
+        let count = (out.len() / 4) as i64;
+        let first = (x as i64).max(0);
+        let last = (x as i64 + count).min(self.framebuffer_width as i64);
+        if last <= first {
+            return;
+        }
+        let row = self.framebuffer_addr + y as usize * self.framebuffer_stride as usize;
+        let src = unsafe {
+            core::slice::from_raw_parts((row + first as usize * 4) as *const u8, (last - first) as usize * 4)
+        };
+        let offset = (first - x as i64) as usize * 4;
+        out[offset..offset + src.len()].copy_from_slice(src);
+    }
+    
+    /// VSync interval in nanoseconds
+    pub fn get_refresh_interval_ns(&self) -> u64 {
+        self.vsync.get_interval_ns()
     }
     
     /// Get framebuffer address (for display driver)
@@ -239,6 +228,21 @@ impl Compositor {
     }
 }
 
+/// Blend a premultiplied ARGB pixel over an opaque one
+fn blend(src: u32, dst: u32) -> u32 {
+    let alpha = src >> 24;
+    if alpha == 0xff {
+        return src;
+    }
+    let inverse = 255 - alpha;
+    let channel = |shift: u32| {
+        let s = (src >> shift) & 0xff;
+        let d = (dst >> shift) & 0xff;
+        (s + (d * inverse + 127) / 255).min(255) << shift
+    };
+    0xff00_0000 | channel(16) | channel(8) | channel(0)
+}
+
 /// Global compositor instance
 static COMPOSITOR: Mutex<Option<Compositor>> = Mutex::new(None);
 
@@ -0,0 +1,735 @@
+//! Display server
+//!
+//! Applications reach the compositor through the file `display_connect`
+//! returns, speaking the protocol in [`super::protocol`]. A client shares
+//! memory with the server by making a pool from a memfd, describes buffers
+//! inside it, and attaches them to surfaces. A commit copies the damaged
+//! part of the attached buffer into the surface's back buffer and swaps, so
+//! the buffer is released to the client at once and the compositor only
+//! ever reads kernel memory.
+//!
+//! A kernel thread runs the frame loop: it composites at every VSync and
+//! then answers the frame callbacks committed before the frame was drawn.
+//! Input reaches a client through a handler registered with
+//! [`super::input`] for each of its visible surfaces.
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::reliability::errno::{EAGAIN, EBADF, EEXIST, EINVAL, ENOENT, ENOMEM, EPIPE};
+use crate::subsystems::mm::vm::{page_ref_dec, page_ref_inc, phys_to_kernel_ptr};
+use crate::subsystems::mm::PAGE_SIZE;
+use crate::subsystems::sync::Mutex;
+use crate::syscalls::common::{SyscallError, SyscallResult};
+
+use super::compositor::get_compositor;
+use super::input::{get_input_manager, InputEvent, InputEventHandler};
+use super::protocol::{DecodeError, Event, Request, FORMAT_ARGB8888, FORMAT_XRGB8888};
+use super::surface::{get_surface_manager, DirtyRect, SurfaceBuffer, SurfaceFormat, SurfaceId};
+
+/// Screen size when no display driver provides one
+pub const DEFAULT_WIDTH: u32 = 1024;
+pub const DEFAULT_HEIGHT: u32 = 768;
+
+/// Largest surface edge in pixels
+const MAX_SURFACE_SIZE: u32 = 8192;
+/// Largest pool in bytes
+const MAX_POOL_SIZE: usize = 256 << 20;
+/// Bytes of events a client may leave unread before it is disconnected
+const MAX_QUEUED: usize = 64 * 1024;
+
+/// A rectangle, right and bottom exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64,
+}
+
+impl Rect {
+    fn new(x: i32, y: i32, width: i32, height: i32) -> Option<Self> {
+        (width > 0 && height > 0).then(|| Self {
+            left: x as i64,
+            top: y as i64,
+            right: x as i64 + width as i64,
+            bottom: y as i64 + height as i64,
+        })
+    }
+
+    fn full(width: u32, height: u32) -> Self {
+        Self { left: 0, top: 0, right: width as i64, bottom: height as i64 }
+    }
+
+    /// The part inside a `width` x `height` image
+    fn clip(self, width: u32, height: u32) -> Option<Self> {
+        let rect = Self {
+            left: self.left.max(0),
+            top: self.top.max(0),
+            right: self.right.min(width as i64),
+            bottom: self.bottom.min(height as i64),
+        };
+        (rect.left < rect.right && rect.top < rect.bottom).then_some(rect)
+    }
+
+    /// The smallest rectangle covering both
+    fn union(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(Self {
+                left: a.left.min(b.left),
+                top: a.top.min(b.top),
+                right: a.right.max(b.right),
+                bottom: a.bottom.max(b.bottom),
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn to_dirty(self) -> DirtyRect {
+        DirtyRect {
+            x: self.left as i32,
+            y: self.top as i32,
+            width: (self.right - self.left) as u32,
+            height: (self.bottom - self.top) as u32,
+        }
+    }
+}
+
+/// Memory shared by a client: the pages of a memfd, held for as long as
+/// any buffer made from it exists
+struct Pool {
+    frames: Vec<usize>,
+    size: usize,
+}
+
+impl Pool {
+    /// Share the first `size` bytes of the memfd open as `fd`
+    fn from_memfd(fd: i32, size: usize) -> Result<Self, i32> {
+        if size == 0 || size > MAX_POOL_SIZE {
+            return Err(EINVAL);
+        }
+        let file_idx = crate::process::fdlookup(fd).ok_or(EBADF)?;
+        let memfd = crate::fs::file::file_get_memfd(file_idx).ok_or(EBADF)?;
+        let instance = crate::syscalls::glib::get_memfd_instance(memfd).ok_or(EBADF)?;
+        let frames = instance.frames(0, size).map_err(|e| match e {
+            SyscallError::OutOfMemory => ENOMEM,
+            _ => EINVAL,
+        })?;
+        for &frame in &frames {
+            page_ref_inc(frame);
+        }
+        Ok(Self { frames, size })
+    }
+
+    /// Copy `out.len()` bytes starting at `offset` out of the pool
+    fn read(&self, mut offset: usize, mut out: &mut [u8]) {
+        while !out.is_empty() {
+            let in_page = offset % PAGE_SIZE;
+            let len = out.len().min(PAGE_SIZE - in_page);
+            let page = phys_to_kernel_ptr(self.frames[offset / PAGE_SIZE]);
+            unsafe { core::ptr::copy_nonoverlapping(page.add(in_page), out.as_mut_ptr(), len) };
+            out = &mut out[len..];
+            offset += len;
+        }
+    }
+
+    /// Copy `data` into the pool at `offset`
+    fn write(&self, mut offset: usize, mut data: &[u8]) {
+        while !data.is_empty() {
+            let in_page = offset % PAGE_SIZE;
+            let len = data.len().min(PAGE_SIZE - in_page);
+            let page = phys_to_kernel_ptr(self.frames[offset / PAGE_SIZE]);
+            unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), page.add(in_page), len) };
+            data = &data[len..];
+            offset += len;
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        for &frame in &self.frames {
+            page_ref_dec(frame);
+        }
+    }
+}
+
+/// An image inside a pool
+struct Buffer {
+    pool: Arc<Pool>,
+    offset: usize,
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: u32,
+}
+
+impl Buffer {
+    fn new(pool: Arc<Pool>, offset: u32, width: u32, height: u32, stride: u32, format: u32) -> Result<Self, i32> {
+        if width == 0 || height == 0 || width > MAX_SURFACE_SIZE || height > MAX_SURFACE_SIZE {
+            return Err(EINVAL);
+        }
+        if format != FORMAT_ARGB8888 && format != FORMAT_XRGB8888 {
+            return Err(EINVAL);
+        }
+        if (stride as u64) < width as u64 * 4 {
+            return Err(EINVAL);
+        }
+        let end = offset as u64 + stride as u64 * (height as u64 - 1) + width as u64 * 4;
+        if end > pool.size as u64 {
+            return Err(EINVAL);
+        }
+        Ok(Self { pool, offset: offset as usize, width, height, stride, format })
+    }
+
+    /// Copy a rectangle of the image into a surface buffer of the same size
+    fn copy_to(&self, dst: &SurfaceBuffer, rect: Rect) {
+        let left = rect.left as usize;
+        let len = (rect.right - rect.left) as usize * 4;
+        for row in rect.top as usize..rect.bottom as usize {
+            let out = unsafe {
+                core::slice::from_raw_parts_mut((dst.addr + row * dst.stride as usize + left * 4) as *mut u8, len)
+            };
+            self.pool.read(self.offset + row * self.stride as usize + left * 4, out);
+            // The compositor copies opaque surfaces as they are, so the
+            // unused byte is made a real alpha
+            if self.format == FORMAT_XRGB8888 {
+                for pixel in out.chunks_exact_mut(4) {
+                    pixel[3] = 0xff;
+                }
+            }
+        }
+    }
+}
+
+/// A client's surface
+#[derive(Default)]
+struct Window {
+    /// Kernel surface, while the window is shown
+    mapped: Option<SurfaceId>,
+    /// Buffer attached since the last commit; `Some(0)` hides the window
+    attached: Option<u32>,
+    /// Damage since the last commit, in buffer coordinates
+    damage: Option<Rect>,
+    /// What the back buffer lacks: the region copied by the last commit
+    stale: Option<Rect>,
+    /// Frame callbacks for the next commit
+    frames: Vec<u32>,
+    position: Option<(i32, i32)>,
+    layer: Option<i32>,
+    x: i32,
+    y: i32,
+    z_order: i32,
+}
+
+/// Objects of a connection
+#[derive(Default)]
+struct State {
+    pools: BTreeMap<u32, Arc<Pool>>,
+    buffers: BTreeMap<u32, Buffer>,
+    windows: BTreeMap<u32, Window>,
+    /// Frame callbacks committed and waiting for the next frame
+    frame_callbacks: Vec<u32>,
+    /// Answered to `Sync`
+    serial: u32,
+}
+
+impl State {
+    /// Whether `id` can name a new object
+    fn check_new(&self, id: u32) -> Result<(), i32> {
+        if id == 0 {
+            return Err(EINVAL);
+        }
+        if self.pools.contains_key(&id) || self.buffers.contains_key(&id) || self.windows.contains_key(&id) {
+            return Err(EEXIST);
+        }
+        Ok(())
+    }
+}
+
+/// Events waiting to be read
+#[derive(Default)]
+struct Events {
+    queue: VecDeque<Event>,
+    /// Encoded size of the queue
+    bytes: usize,
+    /// No more requests are taken; reads drain the queue and then see EOF
+    closed: bool,
+}
+
+/// A client's connection to the display server
+pub struct Connection {
+    pid: u32,
+    /// Taken before `events`, which is always taken last
+    state: Mutex<State>,
+    events: Mutex<Events>,
+}
+
+impl Connection {
+    fn new(pid: u32) -> Self {
+        Self { pid, state: Mutex::new(State::default()), events: Mutex::new(Events::default()) }
+    }
+
+    fn chan(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Queue an event
+    ///
+    /// A client that stops reading is disconnected rather than let its
+    /// queue grow without bound; the frame loop then takes its surfaces
+    /// down.
+    fn send(&self, event: Event) {
+        let mut events = self.events.lock();
+        if events.closed {
+            return;
+        }
+        if events.bytes + event.size() > MAX_QUEUED {
+            events.closed = true;
+            events.queue.clear();
+            events.bytes = 0;
+            return;
+        }
+        events.bytes += event.size();
+        events.queue.push_back(event);
+    }
+
+    fn wake(&self) {
+        crate::process::wakeup(self.chan());
+        crate::process::wakeup(crate::syscalls::POLL_WAKE_CHAN);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.events.lock().closed
+    }
+
+    /// Handle the requests in `data`
+    ///
+    /// Requests are taken whole; one cut off at the end is left for the
+    /// next write. Requests that fail are answered with an `Error` event.
+    pub fn write(self: &Arc<Self>, data: &[u8]) -> Result<usize, i32> {
+        if self.is_closed() {
+            return Err(EPIPE);
+        }
+        let mut done = 0;
+        while done < data.len() {
+            let (request, size) = match Request::decode(&data[done..]) {
+                Ok(decoded) => decoded,
+                Err(DecodeError::Incomplete) if done > 0 => break,
+                Err(_) if done > 0 => break,
+                Err(_) => return Err(EINVAL),
+            };
+            if let Err(code) = self.handle(request) {
+                self.send(Event::Error { object: request.object(), opcode: request.opcode(), code: code as u32 });
+            }
+            done += size;
+        }
+        self.wake();
+        Ok(done)
+    }
+
+    fn handle(self: &Arc<Self>, request: Request) -> Result<(), i32> {
+        let mut state = self.state.lock();
+        match request {
+            Request::Sync { callback } => {
+                if callback == 0 {
+                    return Err(EINVAL);
+                }
+                state.serial = state.serial.wrapping_add(1);
+                self.send(Event::Done { callback, data: state.serial });
+            }
+            Request::CreatePool { pool, fd, size } => {
+                state.check_new(pool)?;
+                let shared = Pool::from_memfd(fd, size as usize)?;
+                state.pools.insert(pool, Arc::new(shared));
+            }
+            // Buffers made from the pool keep its memory
+            Request::DestroyPool { pool } => {
+                state.pools.remove(&pool).ok_or(ENOENT)?;
+            }
+            Request::CreateBuffer { buffer, pool, offset, width, height, stride, format } => {
+                state.check_new(buffer)?;
+                let shared = state.pools.get(&pool).ok_or(ENOENT)?.clone();
+                let image = Buffer::new(shared, offset, width, height, stride, format)?;
+                state.buffers.insert(buffer, image);
+            }
+            // What a surface shows was copied at commit and stays
+            Request::DestroyBuffer { buffer } => {
+                state.buffers.remove(&buffer).ok_or(ENOENT)?;
+            }
+            Request::CreateSurface { surface } => {
+                state.check_new(surface)?;
+                state.windows.insert(surface, Window::default());
+            }
+            Request::DestroySurface { surface } => {
+                let mut window = state.windows.remove(&surface).ok_or(ENOENT)?;
+                unmap(&mut window);
+            }
+            Request::Attach { surface, buffer } => {
+                if buffer != 0 && !state.buffers.contains_key(&buffer) {
+                    return Err(ENOENT);
+                }
+                state.windows.get_mut(&surface).ok_or(ENOENT)?.attached = Some(buffer);
+            }
+            Request::Damage { surface, x, y, width, height } => {
+                let window = state.windows.get_mut(&surface).ok_or(ENOENT)?;
+                window.damage = Rect::union(window.damage, Rect::new(x, y, width, height));
+            }
+            Request::Frame { surface, callback } => {
+                if callback == 0 {
+                    return Err(EINVAL);
+                }
+                state.windows.get_mut(&surface).ok_or(ENOENT)?.frames.push(callback);
+            }
+            Request::Commit { surface } => self.commit(&mut state, surface)?,
+            Request::SetPosition { surface, x, y } => {
+                state.windows.get_mut(&surface).ok_or(ENOENT)?.position = Some((x, y));
+            }
+            Request::SetLayer { surface, layer } => {
+                state.windows.get_mut(&surface).ok_or(ENOENT)?.layer = Some(layer);
+            }
+            Request::Capture { buffer, x, y } => {
+                let image = state.buffers.get(&buffer).ok_or(ENOENT)?;
+                let compositor = get_compositor().ok_or(ENOENT)?;
+                let mut row = vec![0u8; image.width as usize * 4];
+                for line in 0..image.height {
+                    compositor.read_pixels(x, y.saturating_add(line as i32), &mut row);
+                    image.pool.write(image.offset + line as usize * image.stride as usize, &row);
+                }
+                self.send(Event::Captured { buffer });
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply what was requested for a window since its last commit
+    fn commit(self: &Arc<Self>, state: &mut State, id: u32) -> Result<(), i32> {
+        let State { buffers, windows, frame_callbacks, .. } = state;
+        let window = windows.get_mut(&id).ok_or(ENOENT)?;
+        frame_callbacks.append(&mut window.frames);
+        if let Some((x, y)) = window.position.take() {
+            (window.x, window.y) = (x, y);
+        }
+        if let Some(layer) = window.layer.take() {
+            window.z_order = layer;
+        }
+        let damage = window.damage.take();
+
+        let result = match window.attached.take() {
+            Some(0) => {
+                unmap(window);
+                Ok(())
+            }
+            Some(buffer) => match buffers.get(&buffer) {
+                Some(image) => {
+                    let result = self.show(id, window, image, damage);
+                    // Its contents were copied (or it was refused)
+                    self.send(Event::Release { buffer });
+                    result
+                }
+                None => Err(ENOENT),
+            },
+            None => Ok(()),
+        };
+
+        if let Some(surface_id) = window.mapped {
+            let (x, y, z_order) = (window.x, window.y, window.z_order);
+            get_surface_manager().with_surface(surface_id, |surface| {
+                surface.x = x;
+                surface.y = y;
+                surface.z_order = z_order;
+            });
+        }
+        if let Some(compositor) = get_compositor() {
+            compositor.schedule_repaint();
+        }
+        result
+    }
+
+    /// Bring a window's surface up to date with a newly attached buffer
+    fn show(self: &Arc<Self>, id: u32, window: &mut Window, image: &Buffer, damage: Option<Rect>) -> Result<(), i32> {
+        let manager = get_surface_manager();
+        let (width, height) = (image.width, image.height);
+        let (surface_id, created) = match window.mapped {
+            Some(surface_id) => (surface_id, false),
+            None => (manager.create_surface(width, height, SurfaceFormat::ARGB8888, self.pid)?, true),
+        };
+
+        let shown = manager.with_surface(surface_id, |surface| {
+            let mut fresh = damage.and_then(|rect| rect.clip(width, height));
+            if created || (surface.width, surface.height) != (width, height) {
+                if !created {
+                    surface.resize(width, height)?;
+                }
+                window.stale = None;
+                fresh = Some(Rect::full(width, height));
+            }
+            // Nothing changed
+            let Some(fresh) = fresh else { return Ok(()) };
+
+            // The back buffer was last drawn two commits ago, so it also
+            // needs what the previous commit changed
+            let region = Rect::union(Some(fresh), window.stale).unwrap_or(fresh);
+            if let Some(back) = surface.back_buffer.as_ref() {
+                image.copy_to(back, region);
+            }
+            surface.flags.opaque = image.format == FORMAT_XRGB8888;
+            surface.x = window.x;
+            surface.y = window.y;
+            surface.z_order = window.z_order;
+            surface.mark_dirty(Some(fresh.to_dirty()));
+            surface.swap_buffers()?;
+            window.stale = Some(fresh);
+            Ok(())
+        });
+
+        match shown {
+            Some(Ok(())) => {}
+            Some(Err(e)) => {
+                if created {
+                    let _ = manager.destroy_surface(surface_id);
+                }
+                return Err(e);
+            }
+            None => return Err(ENOENT),
+        }
+
+        if created {
+            window.mapped = Some(surface_id);
+            let handler = Arc::new(Mutex::new(SurfaceInput {
+                connection: Arc::downgrade(self),
+                id,
+                surface: surface_id,
+                focused: false,
+            }));
+            let input = get_input_manager();
+            input.register_handler(surface_id, handler)?;
+            // A new window takes the keyboard
+            let _ = input.set_focused_surface(Some(surface_id));
+        }
+        Ok(())
+    }
+
+    /// Callbacks to answer once the frame being drawn is done
+    fn take_frame_callbacks(&self) -> Vec<u32> {
+        core::mem::take(&mut self.state.lock().frame_callbacks)
+    }
+
+    /// Answer frame callbacks with the frame's time in milliseconds
+    fn frame_done(&self, callbacks: Vec<u32>, time: u32) {
+        if callbacks.is_empty() {
+            return;
+        }
+        for callback in callbacks {
+            self.send(Event::Done { callback, data: time });
+        }
+        self.wake();
+    }
+
+    /// Read whole events into `buf`
+    ///
+    /// Blocks until there is one unless `nonblock`; returns 0 once the
+    /// connection is closed and drained.
+    pub fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i32> {
+        loop {
+            {
+                let mut events = self.events.lock();
+                if let Some(first) = events.queue.front() {
+                    if first.size() > buf.len() {
+                        return Err(EINVAL);
+                    }
+                    let mut out = Vec::with_capacity(buf.len());
+                    while let Some(event) = events.queue.front() {
+                        if out.len() + event.size() > buf.len() {
+                            break;
+                        }
+                        event.encode(&mut out);
+                        events.bytes -= event.size();
+                        events.queue.pop_front();
+                    }
+                    buf[..out.len()].copy_from_slice(&out);
+                    return Ok(out.len());
+                }
+                if events.closed {
+                    return Ok(0);
+                }
+            }
+            if nonblock || crate::process::myproc().is_none() {
+                return Err(EAGAIN);
+            }
+            crate::process::sleep_unless(self.chan(), || {
+                let events = self.events.lock();
+                events.closed || !events.queue.is_empty()
+            });
+        }
+    }
+
+    /// Poll events for the connection's file
+    pub fn poll(&self) -> i16 {
+        let events = self.events.lock();
+        let mut ev = 0;
+        if !events.queue.is_empty() {
+            ev |= crate::posix::POLLIN;
+        }
+        if events.closed {
+            ev |= crate::posix::POLLHUP;
+        } else {
+            ev |= crate::posix::POLLOUT;
+        }
+        ev
+    }
+
+    /// Take down everything the client made; called when its file is closed
+    pub fn shutdown(&self) {
+        self.events.lock().closed = true;
+        self.teardown();
+        self.wake();
+    }
+
+    fn teardown(&self) {
+        let mut state = self.state.lock();
+        for window in state.windows.values_mut() {
+            unmap(window);
+        }
+        *state = State::default();
+        if let Some(compositor) = get_compositor() {
+            compositor.schedule_repaint();
+        }
+    }
+}
+
+/// Hide a window
+fn unmap(window: &mut Window) {
+    if let Some(surface_id) = window.mapped.take() {
+        let _ = get_input_manager().unregister_handler(surface_id);
+        let _ = get_surface_manager().destroy_surface(surface_id);
+        window.stale = None;
+        if let Some(compositor) = get_compositor() {
+            compositor.schedule_repaint();
+        }
+    }
+}
+
+/// Forwards input for one surface to its client
+struct SurfaceInput {
+    connection: Weak<Connection>,
+    /// The client's id for the surface
+    id: u32,
+    surface: SurfaceId,
+    focused: bool,
+}
+
+impl InputEventHandler for SurfaceInput {
+    fn handle_event(&mut self, event: InputEvent, surface_id: SurfaceId) -> Result<(), i32> {
+        let Some(connection) = self.connection.upgrade() else {
+            return Ok(());
+        };
+        let (x, y) = get_surface_manager().with_surface(surface_id, |surface| (surface.x, surface.y)).unwrap_or((0, 0));
+        connection.send(Event::from_input(&event, self.id, x, y));
+        connection.wake();
+        Ok(())
+    }
+
+    fn get_focused_surface(&self) -> Option<SurfaceId> {
+        self.focused.then_some(self.surface)
+    }
+
+    fn set_focused_surface(&mut self, surface_id: Option<SurfaceId>) -> Result<(), i32> {
+        let focused = surface_id == Some(self.surface);
+        if focused == self.focused {
+            return Ok(());
+        }
+        self.focused = focused;
+        if let Some(connection) = self.connection.upgrade() {
+            connection.send(Event::Focus { surface: self.id, focused: focused as u32 });
+            connection.wake();
+        }
+        Ok(())
+    }
+}
+
+/// Open connections, for the frame loop
+static CONNECTIONS: Mutex<Vec<Weak<Connection>>> = Mutex::new(Vec::new());
+static FRAME_THREAD: AtomicBool = AtomicBool::new(false);
+
+fn connections() -> Vec<Arc<Connection>> {
+    let mut list = CONNECTIONS.lock();
+    list.retain(|connection| connection.strong_count() > 0);
+    list.iter().filter_map(Weak::upgrade).collect()
+}
+
+/// Start the frame loop the first time a client connects
+fn start_frame_thread() -> Result<(), SyscallError> {
+    if FRAME_THREAD.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    crate::process::thread::create_thread(
+        1,
+        crate::process::thread::ThreadType::Kernel,
+        Some(frame_thread_main),
+        core::ptr::null_mut(),
+    )
+    .map(|_| ())
+    .map_err(|_| {
+        FRAME_THREAD.store(false, Ordering::Release);
+        SyscallError::OutOfMemory
+    })
+}
+
+unsafe extern "C" fn frame_thread_main(_arg: *mut u8) -> *mut u8 {
+    loop {
+        // Callbacks committed before the frame is drawn are answered after it
+        let due: Vec<(Arc<Connection>, Vec<u32>)> = connections()
+            .into_iter()
+            .map(|connection| {
+                if connection.is_closed() {
+                    connection.teardown();
+                }
+                let callbacks = connection.take_frame_callbacks();
+                (connection, callbacks)
+            })
+            .collect();
+
+        // Returns at VSync
+        let _ = super::compositor::composite_frame();
+
+        let time = crate::subsystems::time::uptime_ms() as u32;
+        for (connection, callbacks) in due {
+            connection.frame_done(callbacks, time);
+        }
+        crate::process::thread::thread_yield();
+    }
+}
+
+/// display_connect: open a connection to the display server
+/// Arguments: [flags] (O_NONBLOCK, O_CLOEXEC)
+///
+/// The first event on the connection is `Output`, describing the screen.
+pub fn sys_display_connect(args: &[u64]) -> SyscallResult {
+    let flags = *args.first().ok_or(SyscallError::InvalidArgument)? as i32;
+    if flags & !(crate::posix::O_NONBLOCK | crate::posix::O_CLOEXEC) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let compositor = get_compositor().ok_or(SyscallError::NotSupported)?;
+    let pid = crate::process::myproc().ok_or(SyscallError::InvalidArgument)?;
+    start_frame_thread()?;
+
+    let connection = Arc::new(Connection::new(pid as u32));
+    let (width, height) = compositor.get_framebuffer_size();
+    let interval = compositor.get_refresh_interval_ns().max(1);
+    let refresh_mhz = ((1_000_000_000_000 + interval / 2) / interval) as u32;
+    connection.send(Event::Output { width, height, refresh_mhz });
+
+    let file_idx = crate::fs::file::file_display_new(connection.clone(), flags).ok_or(SyscallError::TooManyOpenFiles)?;
+    let Some(fd) = crate::process::fdalloc(file_idx) else {
+        crate::fs::file::file_close(file_idx);
+        return Err(SyscallError::TooManyOpenFiles);
+    };
+    CONNECTIONS.lock().push(Arc::downgrade(&connection));
+    Ok(fd as u64)
+}
@@ -167,6 +167,8 @@ pub struct InputManager {
     keyboard_state: Mutex<BTreeMap<KeyCode, bool>>,
     /// Event handlers by surface
     handlers: Mutex<BTreeMap<SurfaceId, alloc::sync::Arc<Mutex<dyn InputEventHandler>>>>,
+    /// Surface each touch point started on, which gets the rest of it
+    touch_targets: Mutex<BTreeMap<u64, SurfaceId>>,
     /// Input enabled flag
     input_enabled: AtomicBool,
 }
@@ -181,6 +183,7 @@ impl InputManager {
             mouse_buttons: AtomicU32::new(0),
             keyboard_state: Mutex::new(BTreeMap::new()),
             handlers: Mutex::new(BTreeMap::new()),
+            touch_targets: Mutex::new(BTreeMap::new()),
             input_enabled: AtomicBool::new(true),
         }
     }
@@ -197,6 +200,13 @@ impl InputManager {
     pub fn unregister_handler(&self, surface_id: SurfaceId) -> Result<(), i32> {
         let mut handlers = self.handlers.lock();
         handlers.remove(&surface_id);
+        drop(handlers);
+        // The surface is going away: it keeps neither focus nor touches
+        self.touch_targets.lock().retain(|_, target| *target != surface_id);
+        let mut focused = self.focused_surface.lock();
+        if *focused == Some(surface_id) {
+            *focused = None;
+        }
         crate::println!("[input] Unregistered handler for surface {}", surface_id);
         Ok(())
     }
@@ -222,7 +232,6 @@ impl InputManager {
                 self.mouse_y.store(*y as u32, Ordering::Release);
                 
                 // Find surface under cursor and send event
-                // TODO: Implement hit testing
                 if let Some(surface_id) = self.find_surface_at(*x, *y) {
                     self.send_to_surface(surface_id, event)?;
                 }
@@ -232,12 +241,31 @@ impl InputManager {
                 let x = self.mouse_x.load(Ordering::Acquire) as f32;
                 let y = self.mouse_y.load(Ordering::Acquire) as f32;
                 if let Some(surface_id) = self.find_surface_at(x, y) {
+                    // Clicking a surface gives it the keyboard
+                    if matches!(event, InputEvent::MousePress { .. }) && self.get_focused_surface() != Some(surface_id) {
+                        self.set_focused_surface(Some(surface_id))?;
+                    }
                     self.send_to_surface(surface_id, event)?;
                 }
             }
-            InputEvent::TouchStart { .. } | InputEvent::TouchMove { .. } | InputEvent::TouchEnd { .. } => {
+            InputEvent::TouchStart { id, x, y } => {
                 // Touch events go to surface under touch point
-                // TODO: Implement touch hit testing
+                if let Some(surface_id) = self.find_surface_at(*x, *y) {
+                    self.touch_targets.lock().insert(*id, surface_id);
+                    self.send_to_surface(surface_id, event)?;
+                }
+            }
+            InputEvent::TouchMove { id, .. } => {
+                let target = self.touch_targets.lock().get(id).copied();
+                if let Some(surface_id) = target {
+                    self.send_to_surface(surface_id, event)?;
+                }
+            }
+            InputEvent::TouchEnd { id, .. } => {
+                let target = self.touch_targets.lock().remove(id);
+                if let Some(surface_id) = target {
+                    self.send_to_surface(surface_id, event)?;
+                }
             }
         }
         
@@ -255,17 +283,26 @@ impl InputManager {
         }
     }
     
-    /// Find surface at coordinates (simplified)
-    fn find_surface_at(&self, _x: f32, _y: f32) -> Option<SurfaceId> {
-        // TODO: Implement proper hit testing using compositor
-        None
+    /// Find the topmost surface at coordinates
+    fn find_surface_at(&self, x: f32, y: f32) -> Option<SurfaceId> {
+        crate::graphics::surface::get_surface_manager().surface_at(x as i32, y as i32)
     }
     
     /// Set focused surface
+    ///
+    /// The handlers of the surfaces losing and gaining focus are told.
     pub fn set_focused_surface(&self, surface_id: Option<SurfaceId>) -> Result<(), i32> {
-        let mut focused = self.focused_surface.lock();
-        *focused = surface_id;
+        let previous = core::mem::replace(&mut *self.focused_surface.lock(), surface_id);
         crate::println!("[input] Focus changed to surface {:?}", surface_id);
+        if previous == surface_id {
+            return Ok(());
+        }
+        let handlers = self.handlers.lock();
+        for id in [previous, surface_id].into_iter().flatten() {
+            if let Some(handler) = handlers.get(&id) {
+                handler.lock().set_focused_surface(surface_id)?;
+            }
+        }
         Ok(())
     }
     
@@ -11,6 +11,8 @@ pub mod vsync;
 pub mod input;
 pub mod gui;
 pub mod ime;
+pub mod protocol;
+pub mod display;
 
 /// Initialize graphics subsystem
 pub fn init() {
@@ -39,6 +41,12 @@ pub fn init() {
         crate::println!("[graphics] Failed to initialize IME manager: {}", e);
     }
     
+    // Composite into memory until a display driver takes the framebuffer;
+    // clients read it back to check their output
+    if let Err(e) = compositor::init_compositor(display::DEFAULT_WIDTH, display::DEFAULT_HEIGHT).and_then(|_| compositor::start_compositor()) {
+        crate::println!("[graphics] Failed to start compositor: {}", e);
+    }
+    
     crate::println!("[graphics] Graphics subsystem initialized");
 }
 
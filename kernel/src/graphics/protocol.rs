@@ -0,0 +1,471 @@
+//! Display protocol wire format
+//!
+//! Clients talk to the display server over the file `display_connect`
+//! returns: requests are written to it and events read from it. Every
+//! message is a header followed by 32-bit arguments, in native byte order:
+//!
+//! | bytes | field                              |
+//! |-------|------------------------------------|
+//! | 0..2  | opcode                             |
+//! | 2..4  | size of the whole message in bytes |
+//! | 4..   | arguments, one `u32`/`i32` each    |
+//!
+//! Pools, buffers, surfaces and callbacks are named by ids the client
+//! picks, unique within the connection; 0 is never a valid id. Pointer and
+//! touch coordinates are 24.8 fixed point relative to the surface, key and
+//! button codes are Linux input event codes.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use crate::graphics::input::{InputEvent, KeyCode, KeyModifiers, MouseButton};
+
+/// Header bytes in front of every message
+pub const HEADER_SIZE: usize = 4;
+/// Arguments of the longest message
+const MAX_ARGS: usize = 7;
+
+/// Buffer formats (the values `wl_shm` uses); alpha is premultiplied
+pub const FORMAT_ARGB8888: u32 = 0;
+pub const FORMAT_XRGB8888: u32 = 1;
+
+/// Request opcodes
+pub const REQ_SYNC: u16 = 1;
+pub const REQ_CREATE_POOL: u16 = 2;
+pub const REQ_DESTROY_POOL: u16 = 3;
+pub const REQ_CREATE_BUFFER: u16 = 4;
+pub const REQ_DESTROY_BUFFER: u16 = 5;
+pub const REQ_CREATE_SURFACE: u16 = 6;
+pub const REQ_DESTROY_SURFACE: u16 = 7;
+pub const REQ_ATTACH: u16 = 8;
+pub const REQ_DAMAGE: u16 = 9;
+pub const REQ_FRAME: u16 = 10;
+pub const REQ_COMMIT: u16 = 11;
+pub const REQ_SET_POSITION: u16 = 12;
+pub const REQ_SET_LAYER: u16 = 13;
+pub const REQ_CAPTURE: u16 = 14;
+
+/// Event opcodes
+pub const EV_ERROR: u16 = 1;
+pub const EV_DONE: u16 = 2;
+pub const EV_RELEASE: u16 = 3;
+pub const EV_OUTPUT: u16 = 4;
+pub const EV_KEY: u16 = 5;
+pub const EV_CHAR: u16 = 6;
+pub const EV_MOTION: u16 = 7;
+pub const EV_BUTTON: u16 = 8;
+pub const EV_AXIS: u16 = 9;
+pub const EV_TOUCH: u16 = 10;
+pub const EV_FOCUS: u16 = 11;
+pub const EV_CAPTURED: u16 = 12;
+
+/// Key states
+pub const KEY_RELEASED: u32 = 0;
+pub const KEY_PRESSED: u32 = 1;
+pub const KEY_REPEATED: u32 = 2;
+
+/// Button states
+pub const BUTTON_RELEASED: u32 = 0;
+pub const BUTTON_PRESSED: u32 = 1;
+
+/// Touch phases
+pub const TOUCH_DOWN: u32 = 0;
+pub const TOUCH_MOTION: u32 = 1;
+pub const TOUCH_UP: u32 = 2;
+
+/// Modifier bits
+pub const MOD_SHIFT: u32 = 1 << 0;
+pub const MOD_CONTROL: u32 = 1 << 1;
+pub const MOD_ALT: u32 = 1 << 2;
+pub const MOD_META: u32 = 1 << 3;
+
+/// A message from a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// Answer with `Done` once everything before has been handled
+    Sync { callback: u32 },
+    /// Share the first `size` bytes of a memfd
+    CreatePool { pool: u32, fd: i32, size: u32 },
+    DestroyPool { pool: u32 },
+    /// Describe an image inside a pool
+    CreateBuffer { buffer: u32, pool: u32, offset: u32, width: u32, height: u32, stride: u32, format: u32 },
+    DestroyBuffer { buffer: u32 },
+    CreateSurface { surface: u32 },
+    DestroySurface { surface: u32 },
+    /// Buffer for the next commit; 0 hides the surface
+    Attach { surface: u32, buffer: u32 },
+    /// Part of the attached buffer that changed, in buffer coordinates
+    Damage { surface: u32, x: i32, y: i32, width: i32, height: i32 },
+    /// Answer with `Done` when it is time to draw the next frame
+    Frame { surface: u32, callback: u32 },
+    /// Apply everything requested for the surface since its last commit
+    Commit { surface: u32 },
+    /// Screen position, applied on commit
+    SetPosition { surface: u32, x: i32, y: i32 },
+    /// Stacking layer (higher is on top), applied on commit
+    SetLayer { surface: u32, layer: i32 },
+    /// Copy the screen from (x, y) into a buffer
+    Capture { buffer: u32, x: i32, y: i32 },
+}
+
+/// Why a request could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The message does not end within the data
+    Incomplete,
+    /// No request has this opcode
+    UnknownOpcode(u16),
+    /// The size does not match the opcode
+    BadSize(u16),
+}
+
+impl Request {
+    /// Number of arguments a request carries
+    fn arg_count(opcode: u16) -> Option<usize> {
+        Some(match opcode {
+            REQ_SYNC | REQ_DESTROY_POOL | REQ_DESTROY_BUFFER | REQ_CREATE_SURFACE | REQ_DESTROY_SURFACE | REQ_COMMIT => 1,
+            REQ_ATTACH | REQ_FRAME | REQ_SET_LAYER => 2,
+            REQ_CREATE_POOL | REQ_SET_POSITION | REQ_CAPTURE => 3,
+            REQ_DAMAGE => 5,
+            REQ_CREATE_BUFFER => 7,
+            _ => return None,
+        })
+    }
+
+    /// Decode the request at the start of `data`, returning it and its size
+    pub fn decode(data: &[u8]) -> Result<(Self, usize), DecodeError> {
+        if data.len() < HEADER_SIZE {
+            return Err(DecodeError::Incomplete);
+        }
+        let opcode = u16::from_ne_bytes([data[0], data[1]]);
+        let size = u16::from_ne_bytes([data[2], data[3]]) as usize;
+        let count = Self::arg_count(opcode).ok_or(DecodeError::UnknownOpcode(opcode))?;
+        if size != HEADER_SIZE + count * 4 {
+            return Err(DecodeError::BadSize(opcode));
+        }
+        if data.len() < size {
+            return Err(DecodeError::Incomplete);
+        }
+
+        let mut a = [0u32; MAX_ARGS];
+        for (i, arg) in a.iter_mut().take(count).enumerate() {
+            let at = HEADER_SIZE + i * 4;
+            *arg = u32::from_ne_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        }
+        let request = match opcode {
+            REQ_SYNC => Self::Sync { callback: a[0] },
+            REQ_CREATE_POOL => Self::CreatePool { pool: a[0], fd: a[1] as i32, size: a[2] },
+            REQ_DESTROY_POOL => Self::DestroyPool { pool: a[0] },
+            REQ_CREATE_BUFFER => Self::CreateBuffer {
+                buffer: a[0],
+                pool: a[1],
+                offset: a[2],
+                width: a[3],
+                height: a[4],
+                stride: a[5],
+                format: a[6],
+            },
+            REQ_DESTROY_BUFFER => Self::DestroyBuffer { buffer: a[0] },
+            REQ_CREATE_SURFACE => Self::CreateSurface { surface: a[0] },
+            REQ_DESTROY_SURFACE => Self::DestroySurface { surface: a[0] },
+            REQ_ATTACH => Self::Attach { surface: a[0], buffer: a[1] },
+            REQ_DAMAGE => Self::Damage { surface: a[0], x: a[1] as i32, y: a[2] as i32, width: a[3] as i32, height: a[4] as i32 },
+            REQ_FRAME => Self::Frame { surface: a[0], callback: a[1] },
+            REQ_COMMIT => Self::Commit { surface: a[0] },
+            REQ_SET_POSITION => Self::SetPosition { surface: a[0], x: a[1] as i32, y: a[2] as i32 },
+            REQ_SET_LAYER => Self::SetLayer { surface: a[0], layer: a[1] as i32 },
+            REQ_CAPTURE => Self::Capture { buffer: a[0], x: a[1] as i32, y: a[2] as i32 },
+            _ => unreachable!(),
+        };
+        Ok((request, size))
+    }
+
+    /// Opcode of the request
+    pub fn opcode(&self) -> u16 {
+        match self {
+            Self::Sync { .. } => REQ_SYNC,
+            Self::CreatePool { .. } => REQ_CREATE_POOL,
+            Self::DestroyPool { .. } => REQ_DESTROY_POOL,
+            Self::CreateBuffer { .. } => REQ_CREATE_BUFFER,
+            Self::DestroyBuffer { .. } => REQ_DESTROY_BUFFER,
+            Self::CreateSurface { .. } => REQ_CREATE_SURFACE,
+            Self::DestroySurface { .. } => REQ_DESTROY_SURFACE,
+            Self::Attach { .. } => REQ_ATTACH,
+            Self::Damage { .. } => REQ_DAMAGE,
+            Self::Frame { .. } => REQ_FRAME,
+            Self::Commit { .. } => REQ_COMMIT,
+            Self::SetPosition { .. } => REQ_SET_POSITION,
+            Self::SetLayer { .. } => REQ_SET_LAYER,
+            Self::Capture { .. } => REQ_CAPTURE,
+        }
+    }
+
+    /// The object the request is about, which errors are reported against
+    pub fn object(&self) -> u32 {
+        match *self {
+            Self::Sync { callback } => callback,
+            Self::CreatePool { pool, .. } | Self::DestroyPool { pool } => pool,
+            Self::CreateBuffer { buffer, .. } | Self::DestroyBuffer { buffer } | Self::Capture { buffer, .. } => buffer,
+            Self::CreateSurface { surface }
+            | Self::DestroySurface { surface }
+            | Self::Attach { surface, .. }
+            | Self::Damage { surface, .. }
+            | Self::Frame { surface, .. }
+            | Self::Commit { surface }
+            | Self::SetPosition { surface, .. }
+            | Self::SetLayer { surface, .. } => surface,
+        }
+    }
+}
+
+/// A message to a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A request failed; `code` is an errno value
+    Error { object: u32, opcode: u16, code: u32 },
+    /// A sync or frame callback fired; `data` is the serial or the frame
+    /// time in milliseconds
+    Done { callback: u32, data: u32 },
+    /// The server is done reading a buffer
+    Release { buffer: u32 },
+    /// The screen, sent on connect
+    Output { width: u32, height: u32, refresh_mhz: u32 },
+    Key { surface: u32, key: u32, state: u32, modifiers: u32 },
+    Char { surface: u32, codepoint: u32 },
+    Motion { surface: u32, x: i32, y: i32 },
+    Button { surface: u32, button: u32, state: u32, x: i32, y: i32 },
+    Axis { surface: u32, dx: i32, dy: i32 },
+    Touch { surface: u32, id: u32, phase: u32, x: i32, y: i32 },
+    /// The surface gained or lost the keyboard
+    Focus { surface: u32, focused: u32 },
+    /// A capture into the buffer finished
+    Captured { buffer: u32 },
+}
+
+impl Event {
+    fn opcode_and_args(&self) -> (u16, [u32; MAX_ARGS], usize) {
+        let mut a = [0u32; MAX_ARGS];
+        let (opcode, args): (u16, &[u32]) = match *self {
+            Self::Error { object, opcode, code } => (EV_ERROR, &[object, opcode as u32, code]),
+            Self::Done { callback, data } => (EV_DONE, &[callback, data]),
+            Self::Release { buffer } => (EV_RELEASE, &[buffer]),
+            Self::Output { width, height, refresh_mhz } => (EV_OUTPUT, &[width, height, refresh_mhz]),
+            Self::Key { surface, key, state, modifiers } => (EV_KEY, &[surface, key, state, modifiers]),
+            Self::Char { surface, codepoint } => (EV_CHAR, &[surface, codepoint]),
+            Self::Motion { surface, x, y } => (EV_MOTION, &[surface, x as u32, y as u32]),
+            Self::Button { surface, button, state, x, y } => (EV_BUTTON, &[surface, button, state, x as u32, y as u32]),
+            Self::Axis { surface, dx, dy } => (EV_AXIS, &[surface, dx as u32, dy as u32]),
+            Self::Touch { surface, id, phase, x, y } => (EV_TOUCH, &[surface, id, phase, x as u32, y as u32]),
+            Self::Focus { surface, focused } => (EV_FOCUS, &[surface, focused]),
+            Self::Captured { buffer } => (EV_CAPTURED, &[buffer]),
+        };
+        a[..args.len()].copy_from_slice(args);
+        (opcode, a, args.len())
+    }
+
+    /// Encoded size in bytes
+    pub fn size(&self) -> usize {
+        HEADER_SIZE + self.opcode_and_args().2 * 4
+    }
+
+    /// Append the encoded event to `out`
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let (opcode, args, count) = self.opcode_and_args();
+        out.extend_from_slice(&opcode.to_ne_bytes());
+        out.extend_from_slice(&(self.size() as u16).to_ne_bytes());
+        for arg in &args[..count] {
+            out.extend_from_slice(&arg.to_ne_bytes());
+        }
+    }
+
+    /// The protocol form of an input event for `surface`, whose top left
+    /// corner is at (`origin_x`, `origin_y`) on screen
+    pub fn from_input(event: &InputEvent, surface: u32, origin_x: i32, origin_y: i32) -> Self {
+        let x = |x: f32| fixed(x - origin_x as f32);
+        let y = |y: f32| fixed(y - origin_y as f32);
+        match *event {
+            InputEvent::KeyPress { key, modifiers, repeat } => Self::Key {
+                surface,
+                key: key_code(key),
+                state: if repeat { KEY_REPEATED } else { KEY_PRESSED },
+                modifiers: modifier_bits(modifiers),
+            },
+            InputEvent::KeyRelease { key, modifiers } => {
+                Self::Key { surface, key: key_code(key), state: KEY_RELEASED, modifiers: modifier_bits(modifiers) }
+            }
+            InputEvent::CharInput { ch } => Self::Char { surface, codepoint: ch as u32 },
+            InputEvent::MouseMove { x: px, y: py, .. } => Self::Motion { surface, x: x(px), y: y(py) },
+            InputEvent::MousePress { button, x: px, y: py } => {
+                Self::Button { surface, button: button_code(button), state: BUTTON_PRESSED, x: x(px), y: y(py) }
+            }
+            InputEvent::MouseRelease { button, x: px, y: py } => {
+                Self::Button { surface, button: button_code(button), state: BUTTON_RELEASED, x: x(px), y: y(py) }
+            }
+            InputEvent::MouseWheel { delta_x, delta_y } => Self::Axis { surface, dx: fixed(delta_x), dy: fixed(delta_y) },
+            InputEvent::TouchStart { id, x: px, y: py } => {
+                Self::Touch { surface, id: id as u32, phase: TOUCH_DOWN, x: x(px), y: y(py) }
+            }
+            InputEvent::TouchMove { id, x: px, y: py } => {
+                Self::Touch { surface, id: id as u32, phase: TOUCH_MOTION, x: x(px), y: y(py) }
+            }
+            InputEvent::TouchEnd { id, x: px, y: py } => {
+                Self::Touch { surface, id: id as u32, phase: TOUCH_UP, x: x(px), y: y(py) }
+            }
+        }
+    }
+}
+
+/// 24.8 fixed point
+fn fixed(value: f32) -> i32 {
+    (value * 256.0) as i32
+}
+
+fn modifier_bits(modifiers: KeyModifiers) -> u32 {
+    let mut bits = 0;
+    if modifiers.shift {
+        bits |= MOD_SHIFT;
+    }
+    if modifiers.control {
+        bits |= MOD_CONTROL;
+    }
+    if modifiers.alt {
+        bits |= MOD_ALT;
+    }
+    if modifiers.meta {
+        bits |= MOD_META;
+    }
+    bits
+}
+
+/// Linux input event code of a key (`KEY_*`)
+pub fn key_code(key: KeyCode) -> u32 {
+    use KeyCode::*;
+    match key {
+        Unknown => 0,
+        Escape => 1,
+        Digit1 => 2,
+        Digit2 => 3,
+        Digit3 => 4,
+        Digit4 => 5,
+        Digit5 => 6,
+        Digit6 => 7,
+        Digit7 => 8,
+        Digit8 => 9,
+        Digit9 => 10,
+        Digit0 => 11,
+        Backspace => 14,
+        Tab => 15,
+        Q => 16,
+        W => 17,
+        E => 18,
+        R => 19,
+        T => 20,
+        Y => 21,
+        U => 22,
+        I => 23,
+        O => 24,
+        P => 25,
+        Enter => 28,
+        ControlLeft => 29,
+        A => 30,
+        S => 31,
+        D => 32,
+        F => 33,
+        G => 34,
+        H => 35,
+        J => 36,
+        K => 37,
+        L => 38,
+        ShiftLeft => 42,
+        Z => 44,
+        X => 45,
+        C => 46,
+        V => 47,
+        B => 48,
+        N => 49,
+        M => 50,
+        ShiftRight => 54,
+        AltLeft => 56,
+        Space => 57,
+        ControlRight => 97,
+        AltRight => 100,
+        Home => 102,
+        ArrowUp => 103,
+        PageUp => 104,
+        ArrowLeft => 105,
+        ArrowRight => 106,
+        End => 107,
+        ArrowDown => 108,
+        PageDown => 109,
+        Delete => 111,
+        MetaLeft => 125,
+        MetaRight => 126,
+    }
+}
+
+/// Linux input event code of a mouse button (`BTN_*`)
+pub fn button_code(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 0x110,
+        MouseButton::Right => 0x111,
+        MouseButton::Middle => 0x112,
+        MouseButton::X1 => 0x113,
+        MouseButton::X2 => 0x114,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(opcode: u16, args: &[u32]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&opcode.to_ne_bytes());
+        data.extend_from_slice(&((HEADER_SIZE + args.len() * 4) as u16).to_ne_bytes());
+        for arg in args {
+            data.extend_from_slice(&arg.to_ne_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_requests() {
+        let mut data = message(REQ_CREATE_BUFFER, &[3, 2, 4096, 64, 32, 256, FORMAT_XRGB8888]);
+        data.extend(message(REQ_DAMAGE, &[5, 1, 2, (-1i32) as u32, 4]));
+        let (first, size) = Request::decode(&data).unwrap();
+        assert_eq!(first, Request::CreateBuffer { buffer: 3, pool: 2, offset: 4096, width: 64, height: 32, stride: 256, format: 1 });
+        assert_eq!(size, 32);
+        let (second, _) = Request::decode(&data[size..]).unwrap();
+        assert_eq!(second, Request::Damage { surface: 5, x: 1, y: 2, width: -1, height: 4 });
+        assert_eq!(second.opcode(), REQ_DAMAGE);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let data = message(REQ_COMMIT, &[1]);
+        assert_eq!(Request::decode(&data[..6]), Err(DecodeError::Incomplete));
+        assert_eq!(Request::decode(&message(99, &[])), Err(DecodeError::UnknownOpcode(99)));
+        assert_eq!(Request::decode(&message(REQ_COMMIT, &[1, 2])), Err(DecodeError::BadSize(REQ_COMMIT)));
+    }
+
+    #[test]
+    fn test_encode_events() {
+        let mut out = Vec::new();
+        let event = Event::Error { object: 7, opcode: REQ_ATTACH, code: 2 };
+        event.encode(&mut out);
+        assert_eq!(out.len(), event.size());
+        assert_eq!(out, message(EV_ERROR, &[7, REQ_ATTACH as u32, 2]));
+    }
+
+    #[test]
+    fn test_input_translation() {
+        let press = InputEvent::MousePress { button: MouseButton::Left, x: 110.5, y: 20.0 };
+        assert_eq!(
+            Event::from_input(&press, 4, 100, 10),
+            Event::Button { surface: 4, button: 0x110, state: BUTTON_PRESSED, x: 10 * 256 + 128, y: 10 * 256 }
+        );
+        let key = InputEvent::KeyPress { key: KeyCode::A, modifiers: KeyModifiers { shift: true, ..Default::default() }, repeat: true };
+        assert_eq!(Event::from_input(&key, 4, 0, 0), Event::Key { surface: 4, key: 30, state: KEY_REPEATED, modifiers: MOD_SHIFT });
+    }
+}
@@ -80,12 +80,14 @@ impl SurfaceBuffer {
         let stride = (width as usize * bytes_per_pixel).next_multiple_of(64); // 64-byte alignment
         let size = stride * height as usize;
         
-        // Allocate shared memory for the buffer
-        // In real implementation, this would use shared memory allocation
-        let addr = crate::subsystems::mm::kalloc(size).ok_or(ENOMEM)?;
+        // Physically contiguous and zeroed
+        let addr = crate::subsystems::mm::kalloc_pages(size.div_ceil(crate::subsystems::mm::PAGE_SIZE));
+        if addr.is_null() {
+            return Err(ENOMEM);
+        }
         
         Ok(Self {
-            addr,
+            addr: addr as usize,
             size,
             width,
             height,
@@ -106,7 +108,7 @@ impl SurfaceBuffer {
         if count == 1 {
             // Last reference - free the buffer
             unsafe {
-                crate::subsystems::mm::kfree(self.addr, self.size);
+                crate::subsystems::mm::kfree_pages(self.addr as *mut u8, self.size.div_ceil(crate::subsystems::mm::PAGE_SIZE));
             }
             true
         } else {
@@ -220,6 +222,32 @@ impl Surface {
         Ok(())
     }
     
+    /// Replace both buffers with ones of a new size
+    ///
+    /// The new buffers start out black.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), i32> {
+        let front_buffer = SurfaceBuffer::new(width, height, self.format)?;
+        let back_buffer = match SurfaceBuffer::new(width, height, self.format) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                front_buffer.release();
+                return Err(e);
+            }
+        };
+        for old in [self.front_buffer.replace(front_buffer), self.back_buffer.replace(back_buffer)].into_iter().flatten() {
+            old.release();
+        }
+        self.width = width;
+        self.height = height;
+        self.dirty_rect = None;
+        Ok(())
+    }
+    
+    /// Whether the point lies within the surface
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && ((x - self.x) as u32) < self.width && ((y - self.y) as u32) < self.height
+    }
+    
     /// Get buffer address for rendering
     pub fn get_back_buffer_addr(&self) -> Option<usize> {
         self.back_buffer.as_ref().map(|b| b.addr)
@@ -236,7 +264,7 @@ pub struct SurfaceManager {
     /// Next surface ID
     next_surface_id: AtomicU32,
     /// Surfaces by ID
-    surfaces: Mutex<alloc::collections::BTreeMap<SurfaceId, Surface>>,
+    pub(crate) surfaces: Mutex<alloc::collections::BTreeMap<SurfaceId, Surface>>,
     /// Surfaces by owner PID
     surfaces_by_pid: Mutex<alloc::collections::BTreeMap<u32, Vec<SurfaceId>>>,
 }
@@ -285,6 +313,25 @@ impl SurfaceManager {
         None // Placeholder
     }
     
+    /// Run `f` on a surface with the surface table locked
+    pub fn with_surface<R>(&self, id: SurfaceId, f: impl FnOnce(&mut Surface) -> R) -> Option<R> {
+        let mut surfaces = self.surfaces.lock();
+        surfaces.get_mut(&id).map(f)
+    }
+    
+    /// Topmost visible surface at a screen position
+    ///
+    /// Of surfaces on the same layer the newest is on top, as the
+    /// compositor draws them.
+    pub fn surface_at(&self, x: i32, y: i32) -> Option<SurfaceId> {
+        let surfaces = self.surfaces.lock();
+        surfaces
+            .values()
+            .filter(|s| matches!(s.state, SurfaceState::Ready | SurfaceState::Dirty) && s.contains(x, y))
+            .max_by_key(|s| (s.z_order, s.id))
+            .map(|s| s.id)
+    }
+    
     /// Destroy a surface
     pub fn destroy_surface(&self, id: SurfaceId) -> Result<(), i32> {
         let mut surfaces = self.surfaces.lock();
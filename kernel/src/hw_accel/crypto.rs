@@ -4,6 +4,7 @@
 //! encryption, decryption, hashing, and key generation.
 
 use crate::error::unified::UnifiedError;
+use crate::subsystems::crypto::{self, aes, gcm, modes, Aes, AesGcm, ChaCha20Poly1305, CryptoError, Digest, Xts};
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
@@ -37,7 +38,7 @@ impl Default for CryptoAccelStats {
             total_operations: AtomicU64::new(0),
             encryption_operations: AtomicU64::new(0),
             decryption_operations: AtomicU64::new(0),
-            pub hash_operations: AtomicU64::new(0),
+            hash_operations: AtomicU64::new(0),
             key_gen_operations: AtomicU64::new(0),
             time_saved_us: AtomicU64::new(0),
             avg_acceleration_ratio: AtomicU64::new(100), // 1.00 in fixed point
@@ -102,6 +103,8 @@ pub enum CryptoMode {
     CFB,
     /// Output Feedback
     OFB,
+    /// XEX tweakable block cipher with ciphertext stealing
+    XTS,
 }
 
 /// Cryptographic key
@@ -277,8 +280,7 @@ impl CryptoAccelerator {
         
         #[cfg(target_arch = "aarch64")]
         {
-            // ARM64 crypto extensions detection would go here
-            support.arm_crypto = true; // Assume available for demonstration
+            support.arm_crypto = aes::hardware_available();
         }
         
         support
@@ -394,14 +396,8 @@ impl CryptoAccelerator {
     /// Generate key data
     fn generate_key_data(&self, algorithm: CryptoAlgorithm, size_bits: u32) -> Result<Vec<u8>, UnifiedError> {
         let size_bytes = (size_bits + 7) / 8;
-        let mut key_data = Vec::with_capacity(size_bytes as usize);
-        
-        // In a real implementation, this would use a cryptographically secure RNG
-        // For now, we'll generate pseudo-random data
-        for _ in 0..size_bytes {
-            key_data.push((self.get_timestamp() & 0xFF) as u8);
-        }
-        
+        let mut key_data = alloc::vec![0u8; size_bytes as usize];
+        crypto::random::fill(&mut key_data);
         Ok(key_data)
     }
 
@@ -590,6 +586,10 @@ impl CryptoAccelerator {
     }
 
     /// Perform encryption
+    ///
+    /// GCM and ChaCha20 append their 16-byte tag to the ciphertext; `iv`
+    /// is the nonce for them, the tweak for XTS and the IV or initial
+    /// counter block for CBC and CTR.
     fn perform_encryption(
         &self,
         key: &CryptoKey,
@@ -597,19 +597,49 @@ impl CryptoAccelerator {
         mode: CryptoMode,
         iv: Option<&[u8]>,
     ) -> Result<Vec<u8>, UnifiedError> {
-        // In a real implementation, this would use hardware acceleration
-        // For now, we'll simulate encryption with a simple XOR cipher
-        let mut ciphertext = Vec::with_capacity(plaintext.len());
-        
-        for (i, &byte) in plaintext.iter().enumerate() {
-            let key_byte = key.data[i % key.data.len()];
-            ciphertext.push(byte ^ key_byte);
+        let mut data = plaintext.to_vec();
+        match key.algorithm {
+            CryptoAlgorithm::AES128 | CryptoAlgorithm::AES192 | CryptoAlgorithm::AES256 => match mode {
+                CryptoMode::ECB => {
+                    let aes = Aes::new(&key.data).map_err(crypto_error)?;
+                    if data.len() % aes::BLOCK_SIZE != 0 {
+                        return Err(crypto_error(CryptoError::InvalidLength));
+                    }
+                    for block in data.chunks_exact_mut(aes::BLOCK_SIZE) {
+                        aes.encrypt_block(block.try_into().unwrap());
+                    }
+                }
+                CryptoMode::CBC => {
+                    let aes = Aes::new(&key.data).map_err(crypto_error)?;
+                    modes::cbc_encrypt(&aes, &block_iv(iv)?, &mut data).map_err(crypto_error)?;
+                }
+                CryptoMode::CTR => {
+                    let aes = Aes::new(&key.data).map_err(crypto_error)?;
+                    modes::ctr_apply(&aes, &block_iv(iv)?, &mut data);
+                }
+                CryptoMode::XTS => {
+                    let xts = Xts::new(&key.data).map_err(crypto_error)?;
+                    xts.encrypt(&block_iv(iv)?, &mut data).map_err(crypto_error)?;
+                }
+                CryptoMode::GCM => {
+                    let gcm = AesGcm::new(&key.data).map_err(crypto_error)?;
+                    let nonce = iv.ok_or_else(|| UnifiedError::HwAccel("GCM requires a nonce".to_string()))?;
+                    let tag = gcm.encrypt(nonce, &[], &mut data).map_err(crypto_error)?;
+                    data.extend_from_slice(&tag);
+                }
+                CryptoMode::CFB | CryptoMode::OFB => return Err(unsupported(key.algorithm, Some(mode))),
+            },
+            CryptoAlgorithm::ChaCha20 => {
+                let aead = ChaCha20Poly1305::new(&key.data).map_err(crypto_error)?;
+                let tag = aead.encrypt(&aead_nonce(iv)?, &[], &mut data);
+                data.extend_from_slice(&tag);
+            }
+            algorithm => return Err(unsupported(algorithm, Some(mode))),
         }
-        
-        Ok(ciphertext)
+        Ok(data)
     }
 
-    /// Perform decryption
+    /// Perform decryption; authenticated modes check the tag first
     fn perform_decryption(
         &self,
         key: &CryptoKey,
@@ -617,46 +647,56 @@ impl CryptoAccelerator {
         mode: CryptoMode,
         iv: Option<&[u8]>,
     ) -> Result<Vec<u8>, UnifiedError> {
-        // In a real implementation, this would use hardware acceleration
-        // For now, we'll simulate decryption with a simple XOR cipher
-        let mut plaintext = Vec::with_capacity(ciphertext.len());
-        
-        for (i, &byte) in ciphertext.iter().enumerate() {
-            let key_byte = key.data[i % key.data.len()];
-            plaintext.push(byte ^ key_byte);
+        let mut data = ciphertext.to_vec();
+        match key.algorithm {
+            CryptoAlgorithm::AES128 | CryptoAlgorithm::AES192 | CryptoAlgorithm::AES256 => match mode {
+                CryptoMode::ECB => {
+                    let aes = Aes::new(&key.data).map_err(crypto_error)?;
+                    if data.len() % aes::BLOCK_SIZE != 0 {
+                        return Err(crypto_error(CryptoError::InvalidLength));
+                    }
+                    for block in data.chunks_exact_mut(aes::BLOCK_SIZE) {
+                        aes.decrypt_block(block.try_into().unwrap());
+                    }
+                }
+                CryptoMode::CBC => {
+                    let aes = Aes::new(&key.data).map_err(crypto_error)?;
+                    modes::cbc_decrypt(&aes, &block_iv(iv)?, &mut data).map_err(crypto_error)?;
+                }
+                CryptoMode::CTR => {
+                    let aes = Aes::new(&key.data).map_err(crypto_error)?;
+                    modes::ctr_apply(&aes, &block_iv(iv)?, &mut data);
+                }
+                CryptoMode::XTS => {
+                    let xts = Xts::new(&key.data).map_err(crypto_error)?;
+                    xts.decrypt(&block_iv(iv)?, &mut data).map_err(crypto_error)?;
+                }
+                CryptoMode::GCM => {
+                    let gcm = AesGcm::new(&key.data).map_err(crypto_error)?;
+                    let nonce = iv.ok_or_else(|| UnifiedError::HwAccel("GCM requires a nonce".to_string()))?;
+                    let tag = split_tag(&mut data, gcm::TAG_SIZE)?;
+                    gcm.decrypt(nonce, &[], &mut data, &tag).map_err(crypto_error)?;
+                }
+                CryptoMode::CFB | CryptoMode::OFB => return Err(unsupported(key.algorithm, Some(mode))),
+            },
+            CryptoAlgorithm::ChaCha20 => {
+                let aead = ChaCha20Poly1305::new(&key.data).map_err(crypto_error)?;
+                let tag = split_tag(&mut data, crypto::chacha20::TAG_SIZE)?;
+                aead.decrypt(&aead_nonce(iv)?, &[], &mut data, &tag).map_err(crypto_error)?;
+            }
+            algorithm => return Err(unsupported(algorithm, Some(mode))),
         }
-        
-        Ok(plaintext)
+        Ok(data)
     }
 
     /// Perform hashing
     fn perform_hash(&self, algorithm: CryptoAlgorithm, data: &[u8]) -> Result<Vec<u8>, UnifiedError> {
-        // In a real implementation, this would use hardware acceleration
-        // For now, we'll simulate hashing with a simple checksum
-        let mut hash = Vec::new();
-        
         match algorithm {
-            CryptoAlgorithm::SHA256 => {
-                // Simulate SHA-256 with a 32-byte hash
-                for i in 0..32 {
-                    hash.push(((data.len() + i) & 0xFF) as u8);
-                }
-            }
-            CryptoAlgorithm::SHA512 => {
-                // Simulate SHA-512 with a 64-byte hash
-                for i in 0..64 {
-                    hash.push(((data.len() + i) & 0xFF) as u8);
-                }
-            }
-            _ => {
-                // Default 32-byte hash
-                for i in 0..32 {
-                    hash.push(((data.len() + i) & 0xFF) as u8);
-                }
-            }
+            CryptoAlgorithm::SHA256 => Ok(crypto::Sha256::digest(data)),
+            CryptoAlgorithm::SHA384 => Ok(crypto::Sha384::digest(data)),
+            CryptoAlgorithm::SHA512 => Ok(crypto::Sha512::digest(data)),
+            _ => Err(unsupported(algorithm, None)),
         }
-        
-        Ok(hash)
     }
 
     /// Get current timestamp (in microseconds)
@@ -685,4 +725,32 @@ impl CryptoAccelerator {
         let new_avg = (current_avg + current_ratio) / 2;
         self.stats.avg_acceleration_ratio.store(new_avg, Ordering::Relaxed);
     }
-}
\ No newline at end of file
+}
+
+fn crypto_error(error: CryptoError) -> UnifiedError {
+    UnifiedError::HwAccel(alloc::format!("crypto operation failed: {:?}", error))
+}
+
+fn unsupported(algorithm: CryptoAlgorithm, mode: Option<CryptoMode>) -> UnifiedError {
+    UnifiedError::HwAccel(alloc::format!("{:?} {:?} is not supported", algorithm, mode))
+}
+
+/// A 16-byte IV, counter block or XTS tweak
+fn block_iv(iv: Option<&[u8]>) -> Result<aes::Block, UnifiedError> {
+    iv.and_then(|iv| iv.try_into().ok())
+        .ok_or_else(|| UnifiedError::HwAccel("mode requires a 16-byte IV".to_string()))
+}
+
+fn aead_nonce(iv: Option<&[u8]>) -> Result<[u8; crypto::chacha20::NONCE_SIZE], UnifiedError> {
+    iv.and_then(|iv| iv.try_into().ok())
+        .ok_or_else(|| UnifiedError::HwAccel("ChaCha20-Poly1305 requires a 12-byte nonce".to_string()))
+}
+
+/// Detach the trailing authentication tag from `data`
+fn split_tag(data: &mut Vec<u8>, len: usize) -> Result<[u8; 16], UnifiedError> {
+    if data.len() < len {
+        return Err(crypto_error(CryptoError::InvalidLength));
+    }
+    let tag = data.split_off(data.len() - len);
+    Ok(tag.try_into().unwrap())
+}
@@ -0,0 +1,707 @@
+// Flattened device tree (FDT) parser
+//
+// Reads the device tree blob firmware hands to aarch64 and riscv64 kernels:
+// the header, the memory reservation map, and the structure block of nested
+// nodes whose property names live in the strings block. Nothing is copied;
+// nodes and properties borrow from the blob.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::str;
+
+/// Magic number at the start of every blob
+pub const FDT_MAGIC: u32 = 0xd00d_feed;
+
+/// Oldest format this parser reads; version 16 is what dtc and firmware emit
+const FDT_MIN_VERSION: u32 = 16;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+const HEADER_SIZE: usize = 40;
+
+/// Errors from validating a blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtError {
+    /// The blob does not start with FDT_MAGIC
+    BadMagic,
+    /// The blob's format cannot be read by a version 17 parser
+    BadVersion(u32),
+    /// A block lies outside the blob
+    Truncated,
+}
+
+/// Blob header, with fields in native byte order
+#[derive(Debug, Clone, Copy)]
+pub struct FdtHeader {
+    pub magic: u32,
+    pub totalsize: u32,
+    pub off_dt_struct: u32,
+    pub off_dt_strings: u32,
+    pub off_mem_rsvmap: u32,
+    pub version: u32,
+    pub last_comp_version: u32,
+    pub boot_cpuid_phys: u32,
+    pub size_dt_strings: u32,
+    pub size_dt_struct: u32,
+}
+
+impl FdtHeader {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let field = |i: usize| be32(data, i * 4);
+        Some(Self {
+            magic: field(0)?,
+            totalsize: field(1)?,
+            off_dt_struct: field(2)?,
+            off_dt_strings: field(3)?,
+            off_mem_rsvmap: field(4)?,
+            version: field(5)?,
+            last_comp_version: field(6)?,
+            boot_cpuid_phys: field(7)?,
+            size_dt_strings: field(8)?,
+            size_dt_struct: field(9)?,
+        })
+    }
+}
+
+fn be32(data: &[u8], off: usize) -> Option<u32> {
+    let bytes = data.get(off..off.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn be64(data: &[u8], off: usize) -> Option<u64> {
+    Some((be32(data, off)? as u64) << 32 | be32(data, off + 4)? as u64)
+}
+
+/// NUL-terminated string at `off`
+fn cstr(data: &[u8], off: usize) -> Option<&str> {
+    let bytes = data.get(off..)?;
+    let len = bytes.iter().position(|&b| b == 0)?;
+    str::from_utf8(&bytes[..len]).ok()
+}
+
+/// Read an `n`-cell number; values wider than 64 bits keep their low bits
+fn read_cells(data: &[u8], n: u32) -> Option<u64> {
+    (0..n as usize).try_fold(0u64, |acc, i| Some(acc.checked_shl(32).unwrap_or(0) | be32(data, i * 4)? as u64))
+}
+
+const fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// One entry of the memory reservation map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemReservation {
+    pub address: u64,
+    pub size: u64,
+}
+
+/// `#address-cells` and `#size-cells` a node gives its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cells {
+    pub address: u32,
+    pub size: u32,
+}
+
+impl Cells {
+    /// Values a node without the properties implies
+    pub const DEFAULT: Self = Self { address: 2, size: 1 };
+}
+
+/// A parsed device tree blob
+#[derive(Debug, Clone, Copy)]
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    header: FdtHeader,
+}
+
+impl<'a> Fdt<'a> {
+    /// Check a blob's header and block bounds
+    pub fn new(data: &'a [u8]) -> Result<Self, FdtError> {
+        let header = FdtHeader::parse(data).ok_or(FdtError::Truncated)?;
+        if header.magic != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        if header.version < FDT_MIN_VERSION || header.last_comp_version > 17 {
+            return Err(FdtError::BadVersion(header.version));
+        }
+        let total = header.totalsize as usize;
+        let fits = |off: u32, size: u32| (off as usize).checked_add(size as usize).is_some_and(|end| end <= total);
+        if total > data.len()
+            || total < HEADER_SIZE
+            || !fits(header.off_dt_struct, header.size_dt_struct)
+            || !fits(header.off_dt_strings, header.size_dt_strings)
+            || !fits(header.off_mem_rsvmap, 16)
+        {
+            return Err(FdtError::Truncated);
+        }
+        Ok(Self { data: &data[..total], header })
+    }
+
+    /// Parse the blob at a physical address
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to readable memory holding a blob that stays
+    /// unchanged for the life of the kernel.
+    pub unsafe fn from_addr(addr: usize) -> Result<Fdt<'static>, FdtError> {
+        let header = unsafe { core::slice::from_raw_parts(addr as *const u8, HEADER_SIZE) };
+        let header = FdtHeader::parse(header).ok_or(FdtError::Truncated)?;
+        if header.magic != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        let data = unsafe { core::slice::from_raw_parts(addr as *const u8, header.totalsize as usize) };
+        Fdt::new(data)
+    }
+
+    pub fn header(&self) -> &FdtHeader {
+        &self.header
+    }
+
+    /// The whole blob
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Entries of the memory reservation map
+    pub fn memory_reservations(&self) -> impl Iterator<Item = MemReservation> + 'a {
+        let data = self.data;
+        let mut off = self.header.off_mem_rsvmap as usize;
+        core::iter::from_fn(move || {
+            let address = be64(data, off)?;
+            let size = be64(data, off + 8)?;
+            off += 16;
+            (address != 0 || size != 0).then_some(MemReservation { address, size })
+        })
+    }
+
+    fn structure(&self) -> &'a [u8] {
+        let start = self.header.off_dt_struct as usize;
+        &self.data[start..start + self.header.size_dt_struct as usize]
+    }
+
+    fn string(&self, off: u32) -> Option<&'a str> {
+        let start = self.header.off_dt_strings as usize;
+        let strings = &self.data[start..start + self.header.size_dt_strings as usize];
+        cstr(strings, off as usize)
+    }
+
+    /// Every node, parents before children
+    pub fn nodes(&self) -> NodeIter<'a> {
+        NodeIter { fdt: *self, pos: 0, stack: Vec::new(), done: false }
+    }
+
+    /// The root node
+    pub fn root(&self) -> Option<FdtNode<'a>> {
+        self.nodes().next()
+    }
+
+    /// Node at a structure block offset, as returned by [`FdtNode::offset`]
+    pub fn node_at(&self, offset: usize) -> Option<FdtNode<'a>> {
+        self.nodes().find(|node| node.offset == offset)
+    }
+
+    /// Look a node up by path
+    ///
+    /// Paths not starting with `/` begin with an alias. A component without
+    /// a unit address also matches a node that has one, so `/memory` finds
+    /// `/memory@40000000`.
+    pub fn find_node(&self, path: &str) -> Option<FdtNode<'a>> {
+        let (mut node, rest) = if let Some(rest) = path.strip_prefix('/') {
+            (self.root()?, rest)
+        } else {
+            let (alias, rest) = path.split_once('/').unwrap_or((path, ""));
+            let target = self.find_node("/aliases")?.property(alias)?.as_str()?;
+            (self.find_node(target)?, rest)
+        };
+        for component in rest.split('/').filter(|c| !c.is_empty()) {
+            node = node.children().find(|child| {
+                child.name == component || (!component.contains('@') && child.unit_name() == component)
+            })?;
+        }
+        Some(node)
+    }
+
+    /// Enabled nodes compatible with any of `compatible`
+    pub fn find_compatible<'c>(&self, compatible: &'c [&'c str]) -> impl Iterator<Item = FdtNode<'a>> + 'c
+    where
+        'a: 'c,
+    {
+        self.nodes()
+            .filter(move |node| node.is_enabled() && compatible.iter().any(|c| node.is_compatible(c)))
+    }
+
+    /// Node with the given phandle
+    pub fn find_phandle(&self, phandle: u32) -> Option<FdtNode<'a>> {
+        self.nodes().find(|node| node.phandle() == Some(phandle))
+    }
+
+    /// The `/chosen` node
+    pub fn chosen(&self) -> Option<FdtNode<'a>> {
+        self.find_node("/chosen")
+    }
+
+    /// Kernel command line from `/chosen/bootargs`
+    pub fn bootargs(&self) -> Option<&'a str> {
+        self.chosen()?.property("bootargs")?.as_str()
+    }
+
+    /// Console node named by `/chosen/stdout-path`
+    pub fn stdout(&self) -> Option<FdtNode<'a>> {
+        let path = self.chosen()?.property("stdout-path")?.as_str()?;
+        // Anything after ':' configures the port, e.g. "serial0:115200n8"
+        self.find_node(path.split(':').next()?)
+    }
+}
+
+/// Pre-order walk over the structure block
+pub struct NodeIter<'a> {
+    fdt: Fdt<'a>,
+    pos: usize,
+    /// Open nodes: offset and the cells each gives its children
+    stack: Vec<(usize, Cells)>,
+    done: bool,
+}
+
+impl<'a> Iterator for NodeIter<'a> {
+    type Item = FdtNode<'a>;
+
+    fn next(&mut self) -> Option<FdtNode<'a>> {
+        let block = self.fdt.structure();
+        while !self.done {
+            let Some(token) = be32(block, self.pos) else { break };
+            self.pos += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let Some(name) = cstr(block, self.pos) else { break };
+                    self.pos = align4(self.pos + name.len() + 1);
+                    let (parent, cells) = match self.stack.last() {
+                        Some(&(offset, cells)) => (Some(offset), cells),
+                        None => (None, Cells::DEFAULT),
+                    };
+                    let node = FdtNode { fdt: self.fdt, offset: self.pos, name, depth: self.stack.len(), parent, cells };
+                    self.stack.push((node.offset, node.child_cells()));
+                    return Some(node);
+                }
+                FDT_PROP => {
+                    let Some(len) = be32(block, self.pos) else { break };
+                    self.pos = align4(self.pos + 8 + len as usize);
+                }
+                FDT_NOP => {}
+                FDT_END_NODE => {
+                    self.stack.pop();
+                }
+                FDT_END => break,
+                // Malformed block
+                _ => break,
+            }
+        }
+        self.done = true;
+        None
+    }
+}
+
+/// A node of the tree
+#[derive(Debug, Clone, Copy)]
+pub struct FdtNode<'a> {
+    fdt: Fdt<'a>,
+    /// Offset of the node's first property in the structure block
+    offset: usize,
+    name: &'a str,
+    depth: usize,
+    parent: Option<usize>,
+    /// Cells the parent gives this node's `reg`
+    cells: Cells,
+}
+
+impl<'a> FdtNode<'a> {
+    /// Full name, with unit address ("uart@9000000")
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Name without the unit address ("uart")
+    pub fn unit_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap_or(self.name)
+    }
+
+    /// Unique position of the node within the blob
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Distance from the root, which is at depth 0
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn fdt(&self) -> Fdt<'a> {
+        self.fdt
+    }
+
+    /// Cells the parent gives this node's `reg`
+    pub fn cells(&self) -> Cells {
+        self.cells
+    }
+
+    pub fn parent(&self) -> Option<FdtNode<'a>> {
+        self.fdt.node_at(self.parent?)
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = FdtNode<'a>> + 'a {
+        let offset = self.offset;
+        self.fdt.nodes().filter(move |node| node.parent == Some(offset))
+    }
+
+    pub fn properties(&self) -> PropIter<'a> {
+        PropIter { fdt: self.fdt, pos: self.offset }
+    }
+
+    pub fn property(&self, name: &str) -> Option<FdtProperty<'a>> {
+        self.properties().find(|prop| prop.name == name)
+    }
+
+    /// Entries of the `compatible` string list
+    pub fn compatible(&self) -> impl Iterator<Item = &'a str> + 'a {
+        self.property("compatible").into_iter().flat_map(|prop| prop.strings())
+    }
+
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatible().any(|c| c == compatible)
+    }
+
+    /// `status` is absent, "okay" or "ok"
+    pub fn is_enabled(&self) -> bool {
+        self.property("status")
+            .and_then(|prop| prop.as_str())
+            .is_none_or(|status| status == "okay" || status == "ok")
+    }
+
+    pub fn phandle(&self) -> Option<u32> {
+        self.property("phandle").or_else(|| self.property("linux,phandle"))?.as_u32()
+    }
+
+    /// Cells this node gives its children
+    pub fn child_cells(&self) -> Cells {
+        let cells = |name| self.property(name).and_then(|prop| prop.as_u32());
+        Cells {
+            address: cells("#address-cells").unwrap_or(Cells::DEFAULT.address),
+            size: cells("#size-cells").unwrap_or(Cells::DEFAULT.size),
+        }
+    }
+
+    /// `reg` entries as addresses on the parent bus
+    pub fn reg(&self) -> impl Iterator<Item = (u64, u64)> + 'a {
+        let cells = self.cells;
+        let entry = (cells.address + cells.size) as usize * 4;
+        let value = self.property("reg").map_or(&[][..], |prop| prop.value);
+        value.chunks_exact(entry.max(1)).filter(move |_| entry > 0).filter_map(move |chunk| {
+            let address = read_cells(chunk, cells.address)?;
+            let size = read_cells(&chunk[cells.address as usize * 4..], cells.size)?;
+            Some((address, size))
+        })
+    }
+
+    /// `reg` entries as CPU physical addresses
+    ///
+    /// Entries that no bus maps into the CPU address space are left out.
+    pub fn regions(&self) -> impl Iterator<Item = (u64, u64)> + 'a {
+        let node = *self;
+        self.reg().filter_map(move |(address, size)| Some((node.translate(address)?, size)))
+    }
+
+    /// Map an address on this node's parent bus up to the root through the
+    /// `ranges` of each bus
+    pub fn translate(&self, mut address: u64) -> Option<u64> {
+        let mut bus = self.parent()?;
+        while let Some(grandparent) = bus.parent() {
+            // No ranges: the bus is not memory mapped
+            let ranges = bus.property("ranges")?;
+            if !ranges.value.is_empty() {
+                address = translate_range(ranges.value, bus.child_cells(), bus.cells, address)?;
+            }
+            bus = grandparent;
+        }
+        Some(address)
+    }
+
+    /// Node that receives this node's interrupts
+    pub fn interrupt_parent(&self) -> Option<FdtNode<'a>> {
+        let mut node = *self;
+        loop {
+            if let Some(phandle) = node.property("interrupt-parent").and_then(|prop| prop.as_u32()) {
+                return self.fdt.find_phandle(phandle);
+            }
+            node = node.parent()?;
+        }
+    }
+
+    /// `interrupts` specifiers, each `#interrupt-cells` of the interrupt
+    /// parent long
+    pub fn interrupts(&self) -> Vec<&'a [u8]> {
+        let cells = self
+            .interrupt_parent()
+            .and_then(|parent| parent.property("#interrupt-cells")?.as_u32())
+            .unwrap_or(1) as usize;
+        match self.property("interrupts") {
+            Some(prop) if cells > 0 => prop.value.chunks_exact(cells * 4).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Translate `address` through one bus's `ranges`
+fn translate_range(ranges: &[u8], child: Cells, parent: Cells, address: u64) -> Option<u64> {
+    let entry = (child.address + parent.address + child.size) as usize * 4;
+    if entry == 0 {
+        return None;
+    }
+    ranges.chunks_exact(entry).find_map(|chunk| {
+        let child_base = read_cells(chunk, child.address)?;
+        let parent_base = read_cells(&chunk[child.address as usize * 4..], parent.address)?;
+        let size = read_cells(&chunk[(child.address + parent.address) as usize * 4..], child.size)?;
+        let offset = address.checked_sub(child_base)?;
+        (offset < size).then(|| parent_base.wrapping_add(offset))
+    })
+}
+
+/// A property of a node
+#[derive(Debug, Clone, Copy)]
+pub struct FdtProperty<'a> {
+    pub name: &'a str,
+    pub value: &'a [u8],
+}
+
+impl<'a> FdtProperty<'a> {
+    pub fn as_u32(&self) -> Option<u32> {
+        be32(self.value, 0)
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => self.as_u32().map(u64::from),
+            _ => be64(self.value, 0),
+        }
+    }
+
+    /// The value as a single string
+    pub fn as_str(&self) -> Option<&'a str> {
+        cstr(self.value, 0)
+    }
+
+    /// The value as a string list
+    pub fn strings(self) -> impl Iterator<Item = &'a str> + 'a {
+        self.value
+            .split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| str::from_utf8(s).ok())
+    }
+
+    /// The value as big-endian 32-bit cells
+    pub fn cells(self) -> impl Iterator<Item = u32> + 'a {
+        self.value
+            .chunks_exact(4)
+            .map(|cell| u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]]))
+    }
+}
+
+/// Properties of one node
+pub struct PropIter<'a> {
+    fdt: Fdt<'a>,
+    pos: usize,
+}
+
+impl<'a> Iterator for PropIter<'a> {
+    type Item = FdtProperty<'a>;
+
+    fn next(&mut self) -> Option<FdtProperty<'a>> {
+        let block = self.fdt.structure();
+        loop {
+            match be32(block, self.pos)? {
+                FDT_PROP => {
+                    let len = be32(block, self.pos + 4)? as usize;
+                    let name = self.fdt.string(be32(block, self.pos + 8)?)?;
+                    let value = block.get(self.pos + 12..self.pos + 12 + len)?;
+                    self.pos = align4(self.pos + 12 + len);
+                    return Some(FdtProperty { name, value });
+                }
+                FDT_NOP => self.pos += 4,
+                // Properties come before subnodes
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds blobs the way dtc lays them out
+    struct Builder {
+        structure: Vec<u8>,
+        strings: Vec<u8>,
+    }
+
+    impl Builder {
+        fn new() -> Self {
+            Self { structure: Vec::new(), strings: Vec::new() }
+        }
+
+        fn token(&mut self, token: u32) {
+            self.structure.extend_from_slice(&token.to_be_bytes());
+        }
+
+        fn pad(&mut self) {
+            while !self.structure.len().is_multiple_of(4) {
+                self.structure.push(0);
+            }
+        }
+
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.token(FDT_BEGIN_NODE);
+            self.structure.extend_from_slice(name.as_bytes());
+            self.structure.push(0);
+            self.pad();
+            self
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.token(FDT_END_NODE);
+            self
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let nameoff = self.strings.len() as u32;
+            self.strings.extend_from_slice(name.as_bytes());
+            self.strings.push(0);
+            self.token(FDT_PROP);
+            self.token(value.len() as u32);
+            self.token(nameoff);
+            self.structure.extend_from_slice(value);
+            self.pad();
+            self
+        }
+
+        fn cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+            let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+            self.prop(name, &value)
+        }
+
+        fn finish(&mut self, reservations: &[(u64, u64)]) -> Vec<u8> {
+            self.token(FDT_END);
+            let rsvmap = HEADER_SIZE;
+            let off_struct = rsvmap + (reservations.len() + 1) * 16;
+            let off_strings = off_struct + self.structure.len();
+            let total = off_strings + self.strings.len();
+            let header = [
+                FDT_MAGIC,
+                total as u32,
+                off_struct as u32,
+                off_strings as u32,
+                rsvmap as u32,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structure.len() as u32,
+            ];
+            let mut blob: Vec<u8> = header.iter().flat_map(|f| f.to_be_bytes()).collect();
+            for &(address, size) in reservations.iter().chain([(0, 0)].iter()) {
+                blob.extend_from_slice(&address.to_be_bytes());
+                blob.extend_from_slice(&size.to_be_bytes());
+            }
+            blob.extend_from_slice(&self.structure);
+            blob.extend_from_slice(&self.strings);
+            blob
+        }
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut b = Builder::new();
+        b.begin("")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .prop("compatible", b"linux,dummy-virt\0")
+            .cells("interrupt-parent", &[1]);
+        b.begin("chosen").prop("bootargs", b"console=ttyAMA0\0").prop("stdout-path", b"serial0:115200n8\0").end();
+        b.begin("aliases").prop("serial0", b"/soc/uart@1000\0").end();
+        b.begin("memory@40000000").prop("device_type", b"memory\0").cells("reg", &[0, 0x4000_0000, 0, 0x800_0000]).end();
+        b.begin("intc@8000000")
+            .prop("compatible", b"arm,cortex-a15-gic\0arm,gic-400\0")
+            .cells("#interrupt-cells", &[3])
+            .cells("phandle", &[1])
+            .cells("reg", &[0, 0x800_0000, 0, 0x1_0000, 0, 0x801_0000, 0, 0x1_0000])
+            .end();
+        b.begin("soc")
+            .cells("#address-cells", &[1])
+            .cells("#size-cells", &[1])
+            .cells("ranges", &[0, 0, 0x900_0000, 0x10_0000]);
+        b.begin("uart@1000")
+            .prop("compatible", b"arm,pl011\0arm,primecell\0")
+            .cells("reg", &[0x1000, 0x1000])
+            .cells("interrupts", &[0, 1, 4])
+            .end();
+        b.begin("disabled@2000").prop("compatible", b"arm,pl011\0").prop("status", b"disabled\0").end();
+        b.end();
+        b.begin("isa").cells("#address-cells", &[1]).cells("#size-cells", &[1]);
+        b.begin("port@60").cells("reg", &[0x60, 8]).end();
+        b.end();
+        b.end();
+        b.finish(&[(0x4800_0000, 0x1000)])
+    }
+
+    #[test]
+    fn test_fdt_header_checks() {
+        let blob = sample();
+        assert!(Fdt::new(&blob).is_ok());
+        assert_eq!(Fdt::new(&blob[..HEADER_SIZE]).unwrap_err(), FdtError::Truncated);
+        let mut bad = blob.clone();
+        bad[0] = 0;
+        assert_eq!(Fdt::new(&bad).unwrap_err(), FdtError::BadMagic);
+        let mut old = blob.clone();
+        old[20..24].copy_from_slice(&3u32.to_be_bytes());
+        assert_eq!(Fdt::new(&old).unwrap_err(), FdtError::BadVersion(3));
+    }
+
+    #[test]
+    fn test_fdt_nodes_and_properties() {
+        let blob = sample();
+        let fdt = Fdt::new(&blob).unwrap();
+        let names: Vec<&str> = fdt.nodes().map(|node| node.name()).collect();
+        assert_eq!(
+            names,
+            ["", "chosen", "aliases", "memory@40000000", "intc@8000000", "soc", "uart@1000", "disabled@2000", "isa", "port@60"]
+        );
+        assert_eq!(fdt.memory_reservations().collect::<Vec<_>>(), [MemReservation { address: 0x4800_0000, size: 0x1000 }]);
+        assert_eq!(fdt.bootargs(), Some("console=ttyAMA0"));
+
+        let memory = fdt.find_node("/memory").unwrap();
+        assert_eq!(memory.reg().collect::<Vec<_>>(), [(0x4000_0000, 0x800_0000)]);
+        let gic = fdt.find_compatible(&["arm,gic-400"]).next().unwrap();
+        assert_eq!(gic.regions().collect::<Vec<_>>(), [(0x800_0000, 0x1_0000), (0x801_0000, 0x1_0000)]);
+        assert_eq!(fdt.find_phandle(1).unwrap().name(), "intc@8000000");
+        assert_eq!(fdt.root().unwrap().children().count(), 6);
+    }
+
+    #[test]
+    fn test_fdt_translation_and_interrupts() {
+        let blob = sample();
+        let fdt = Fdt::new(&blob).unwrap();
+        // Only the enabled UART matches, found again through the alias
+        let uarts: Vec<FdtNode> = fdt.find_compatible(&["arm,pl011"]).collect();
+        assert_eq!(uarts.len(), 1);
+        assert_eq!(fdt.stdout().unwrap().offset(), uarts[0].offset());
+        assert_eq!(uarts[0].reg().collect::<Vec<_>>(), [(0x1000, 0x1000)]);
+        assert_eq!(uarts[0].regions().collect::<Vec<_>>(), [(0x900_1000, 0x1000)]);
+        let irqs = uarts[0].interrupts();
+        assert_eq!(irqs.len(), 1);
+        assert_eq!(irqs[0], [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 4]);
+        // A bus without ranges is not memory mapped
+        assert_eq!(fdt.find_node("/isa/port@60").unwrap().regions().count(), 0);
+    }
+}
@@ -6,6 +6,7 @@
 extern crate alloc;
 use core::ptr;
 
+pub mod fdt;
 
 // Re-export unified boot parameters from nos-api
 pub use nos_api::boot::{
@@ -373,12 +374,45 @@ pub fn init_acpi_from_boot_info() {
     }
 }
 
+/// Parsed device tree, once `init_device_tree_from_boot_info` found one
+static DEVICE_TREE: spin::Once<fdt::Fdt<'static>> = spin::Once::new();
+
+/// The device tree firmware passed, if any
+pub fn device_tree() -> Option<fdt::Fdt<'static>> {
+    DEVICE_TREE.get().copied()
+}
+
 /// Initialize device tree from boot information
+///
+/// Parses the blob and discovers memory, consoles, interrupt controllers,
+/// timers, virtio-mmio devices and PSCI from it, so nothing needs the QEMU
+/// `virt` addresses hard-coded in the drivers.
 pub fn init_device_tree_from_boot_info() {
+    if DEVICE_TREE.is_completed() {
+        return;
+    }
     if let Some(dtb) = get_device_tree() {
         crate::println!("[boot] Initializing device tree from bootloader");
         crate::println!("[boot]   DTB at: {:#x}", dtb);
 
-        // In a real implementation, we'd parse the device tree here
+        // Safety: the boot protocol hands over a blob that stays in place
+        // for the life of the kernel
+        let tree = match unsafe { fdt::Fdt::from_addr(dtb as usize) } {
+            Ok(tree) => tree,
+            Err(err) => {
+                crate::println!("[boot]   Invalid device tree: {:?}", err);
+                return;
+            }
+        };
+        let header = tree.header();
+        crate::println!("[boot]   Version {}, {} bytes, boot cpu {}", header.version, header.totalsize, header.boot_cpuid_phys);
+        for rsv in tree.memory_reservations() {
+            crate::println!("[boot]   Reserved: {:#x}+{:#x}", rsv.address, rsv.size);
+        }
+        if let Some(bootargs) = tree.bootargs() {
+            crate::println!("[boot]   bootargs: {}", bootargs);
+        }
+        DEVICE_TREE.call_once(|| tree);
+        crate::drivers::platform::init_from_fdt(&tree);
     }
-}
\ No newline at end of file
+}
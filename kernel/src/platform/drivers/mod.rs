@@ -8,11 +8,15 @@ pub mod syscon;
 pub mod gic;
 pub mod gicv3;
 pub mod platform;
+pub mod psci;
 pub mod nvme;
+pub mod pci;
 pub mod usb;
+pub mod virtio;
 pub mod virtio_gpu;
 
 use crate::subsystems::sync::Mutex;
+use crate::subsystems::block::{BlockError, BlockFeatures, BlockResult, ReqOp, Request};
 use crate::posix;
 
 // Re-export driver manager functions
@@ -23,23 +27,55 @@ pub use crate::services::driver::get_driver_manager;
 // ============================================================================
 
 /// Block device trait for storage devices
+///
+/// Drivers implement this; everything else goes through the device's
+/// [`RequestQueue`](crate::subsystems::block::RequestQueue). `lba` counts
+/// `block_size()` units and buffers hold one or more whole blocks.
 pub trait BlockDevice: Send + Sync {
-    /// Read a block from the device
-    fn read(&self, lba: usize, buf: &mut [u8]);
-    
-    /// Write a block to the device
-    fn write(&self, lba: usize, buf: &[u8]);
-    
+    /// Read `buf.len() / block_size()` blocks starting at `lba`
+    fn read(&self, lba: usize, buf: &mut [u8]) -> BlockResult<()>;
+
+    /// Write `buf.len() / block_size()` blocks starting at `lba`
+    fn write(&self, lba: usize, buf: &[u8]) -> BlockResult<()>;
+
     /// Get block size in bytes
     fn block_size(&self) -> usize {
         512
     }
-    
+
     /// Get total number of blocks
     fn num_blocks(&self) -> usize;
-    
-    /// Flush any cached writes
-    fn flush(&self) {}
+
+    /// Make completed writes durable
+    fn flush(&self) -> BlockResult<()> {
+        Ok(())
+    }
+
+    /// Tell the device `count` blocks at `lba` no longer hold data
+    fn discard(&self, _lba: usize, _count: usize) -> BlockResult<()> {
+        Err(BlockError::NotSupported)
+    }
+
+    /// Write cache, FUA and discard support
+    fn features(&self) -> BlockFeatures {
+        BlockFeatures::default()
+    }
+
+    /// Start a request handed down by the block layer
+    ///
+    /// The default carries it out synchronously with the methods above.
+    /// Drivers with a hardware queue override this, return as soon as the
+    /// command is submitted and call [`Request::end`] on completion.
+    fn queue_rq(&self, mut rq: Request) {
+        let lba = rq.sector() as usize;
+        let result = match rq.op() {
+            ReqOp::Read => self.read(lba, rq.data_mut()),
+            ReqOp::Write => self.write(lba, rq.data()),
+            ReqOp::Flush => self.flush(),
+            ReqOp::Discard => self.discard(lba, rq.nr_sectors() as usize),
+        };
+        rq.end(result);
+    }
 }
 
 // ============================================================================
@@ -58,72 +94,50 @@ static mut RAMDISK_DATA: [u8; RAMDISK_SIZE] = [0; RAMDISK_SIZE];
 /// RAM disk device
 pub struct RamDisk;
 
-impl BlockDevice for RamDisk {
-    fn read(&self, lba: usize, buf: &mut [u8]) {
-        let offset = lba * SECTOR_SIZE;
-        let len = buf.len().min(SECTOR_SIZE);
-        if offset + len <= RAMDISK_SIZE {
-            unsafe {
-                buf[..len].copy_from_slice(&RAMDISK_DATA[offset..offset + len]);
-            }
+impl RamDisk {
+    fn range(lba: usize, len: usize) -> BlockResult<core::ops::Range<usize>> {
+        if len == 0 || len % SECTOR_SIZE != 0 {
+            return Err(BlockError::Misaligned);
         }
-    }
-
-    fn write(&self, lba: usize, buf: &[u8]) {
-        let offset = lba * SECTOR_SIZE;
-        let len = buf.len().min(SECTOR_SIZE);
-        if offset + len <= RAMDISK_SIZE {
-            unsafe {
-                RAMDISK_DATA[offset..offset + len].copy_from_slice(&buf[..len]);
-            }
+        let offset = lba.checked_mul(SECTOR_SIZE).ok_or(BlockError::OutOfRange)?;
+        match offset.checked_add(len) {
+            Some(end) if end <= RAMDISK_SIZE => Ok(offset..end),
+            _ => Err(BlockError::OutOfRange),
         }
     }
-
-    fn num_blocks(&self) -> usize {
-        RAMDISK_SIZE / SECTOR_SIZE
-    }
-}
-
-// ============================================================================
-// VirtIO Block Device (placeholder)
-// ============================================================================
-
-/// VirtIO block device configuration
-#[allow(dead_code)]
-pub struct VirtioBlk {
-    base: usize,
-    capacity: u64,
 }
 
-#[allow(dead_code)]
-impl VirtioBlk {
-    pub fn new(base: usize) -> Option<Self> {
-        // TODO: Initialize VirtIO device
-        Some(Self {
-            base,
-            capacity: 0,
-        })
+impl BlockDevice for RamDisk {
+    fn read(&self, lba: usize, buf: &mut [u8]) -> BlockResult<()> {
+        let range = Self::range(lba, buf.len())?;
+        unsafe {
+            buf.copy_from_slice(&RAMDISK_DATA[range]);
+        }
+        Ok(())
     }
 
-    /// Probe for VirtIO device
-    pub fn probe(base: usize) -> bool {
-        // Check magic number
-        let magic = crate::subsystems::mm::mmio_read32(base as *const u32);
-        magic == 0x74726976 // "virt"
+    fn write(&self, lba: usize, buf: &[u8]) -> BlockResult<()> {
+        let range = Self::range(lba, buf.len())?;
+        unsafe {
+            RAMDISK_DATA[range].copy_from_slice(buf);
+        }
+        Ok(())
     }
-}
 
-impl BlockDevice for VirtioBlk {
-    fn read(&self, _lba: usize, _buf: &mut [u8]) {
-        // TODO: Implement VirtIO read
+    fn num_blocks(&self) -> usize {
+        RAMDISK_SIZE / SECTOR_SIZE
     }
 
-    fn write(&self, _lba: usize, _buf: &[u8]) {
-        // TODO: Implement VirtIO write
+    fn discard(&self, lba: usize, count: usize) -> BlockResult<()> {
+        let range = Self::range(lba, count * SECTOR_SIZE)?;
+        unsafe {
+            RAMDISK_DATA[range].fill(0);
+        }
+        Ok(())
     }
 
-    fn num_blocks(&self) -> usize {
-        self.capacity as usize
+    fn features(&self) -> BlockFeatures {
+        BlockFeatures { discard: true, ..BlockFeatures::default() }
     }
 }
 
@@ -295,9 +309,10 @@ pub fn device_unsubscribe(major: i16, _minor: i16, chan: usize) {
 pub fn init() {
     // RAM disk is always available
     crate::println!("drivers: ramdisk {} blocks", RamDisk.num_blocks());
-    
-    // TODO: Probe for other devices (VirtIO, etc.)
-    
+
+    pci::init();
+    virtio::init();
+
     #[cfg(target_arch = "aarch64")]
     {
         if let Some((dist, redist)) = crate::drivers::platform::gicv3_bases() {
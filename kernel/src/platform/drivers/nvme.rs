@@ -25,6 +25,8 @@ use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use spin::Mutex;
 
 use crate::time;
+use crate::subsystems::block::{BlockError, BlockFeatures, BlockResult, ReqFlags, ReqOp, Request};
+use super::BlockDevice;
 
 /// NVMe寄存器基址类型
 pub type NvmeRegisterBase = usize;
@@ -137,6 +139,8 @@ pub struct NvmeController {
     io_queues: Arc<Mutex<Vec<NvmeQueue>>>,
     /// 待处理的命令
     pending_commands: Arc<Mutex<BTreeMap<u16, NvmeCommand>>>,
+    /// 已提交、等待完成的I/O请求（持有数据缓冲区直到完成）
+    inflight_requests: Arc<Mutex<BTreeMap<u16, NvmeIoRequest>>>,
     /// 统计信息
     statistics: NvmeStatistics,
     /// 下一个命令ID
@@ -364,6 +368,8 @@ pub enum AdminOpcode {
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NvmOpcode {
+    /// 刷新易失性写缓存
+    Flush = 0x00,
     /// 读操作
     Read = 0x02,
     /// 写操作
@@ -510,13 +516,17 @@ pub struct NvmeIoRequest {
     pub metadata_buffer: Option<Vec<u8>>,
     /// 请求类型
     pub request_type: NvmeIoRequestType,
-    /// 完成回调
-    pub completion_callback: Option<alloc::sync::Arc<dyn Fn(Result<(), NvmeError>) + Send + Sync>>, 
+    /// 强制单元访问（FUA）：完成时数据已写入介质
+    pub force_unit_access: bool,
+    /// 完成回调，参数为结果和数据缓冲区（读操作时为读到的数据）
+    pub completion_callback: Option<alloc::sync::Arc<dyn Fn(Result<(), NvmeError>, &[u8]) + Send + Sync>>,
 }
 
 /// NVMe I/O请求类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NvmeIoRequestType {
+    /// 刷新写缓存
+    Flush,
     /// 读操作
     Read,
     /// 写操作
@@ -584,6 +594,7 @@ impl NvmeController {
             ))),
             io_queues: Arc::new(Mutex::new(Vec::new())),
             pending_commands: Arc::new(Mutex::new(BTreeMap::new())),
+            inflight_requests: Arc::new(Mutex::new(BTreeMap::new())),
             statistics: NvmeStatistics::default(),
             next_command_id: AtomicU64::new(1),
         }
@@ -738,6 +749,9 @@ impl NvmeController {
     }
 
     /// 提交I/O请求
+    ///
+    /// 立即返回；命令完成后由 [`process_completions`](Self::process_completions)
+    /// 调用请求的完成回调。
     pub fn submit_io_request(&self, request: NvmeIoRequest) -> Result<u64, NvmeError> {
         let request_id = self.next_command_id.fetch_add(1, Ordering::SeqCst);
 
@@ -749,44 +763,96 @@ impl NvmeController {
 
         let queue = &mut io_queues[0]; // 使用第一个I/O队列
 
+        // 分配空闲的命令槽位作为命令ID
+        let command_id = queue.alloc_command_id()
+            .ok_or(NvmeError::QueueError("I/O队列已满".to_string()))?;
+
         // 创建NVMe命令
-        let nvme_command = self.io_request_to_command(&request, request_id as u16)?;
+        let nvme_command = self.io_request_to_command(&request, command_id)?;
 
         // 提交命令
-        let command_id = self.submit_command_to_queue(queue, nvme_command)?;
+        let command_id = self.submit_command_to_queue(queue, nvme_command.clone())?;
 
-        // 记录待处理的请求
-        // Build command from request for tracking
-        let nvme_command_for_tracking = NvmeCommand {
-            command_id,
-            opcode: match request.request_type {
-                NvmeIoRequestType::Read => NvmeOpcode::Nvm(NvmOpcode::Read),
-                NvmeIoRequestType::Write => NvmeOpcode::Nvm(NvmOpcode::Write),
-                _ => NvmeOpcode::Unknown(0),
-            },
-            flags: 0,
-            namespace_id: request.namespace_id,
-            reserved: [0; 2],
-            metadata_ptr: 0,
-            data_ptr: 0,
-            data_length: 0,
-            cdw10: 0,
-            cdw11: 0,
-            cdw12: 0,
-            cdw13: 0,
-            cdw14: 0,
-            cdw15: 0,
-        };
-        let mut pending = self.pending_commands.lock();
-        pending.insert(command_id, nvme_command_for_tracking);
+        // 记录待处理的命令和请求
+        self.pending_commands.lock().insert(command_id, nvme_command);
+        self.inflight_requests.lock().insert(command_id, request);
 
         // 更新统计
-        self.statistics.current_queue_depth.fetch_add(1, Ordering::SeqCst);
+        let depth = self.statistics.current_queue_depth.fetch_add(1, Ordering::SeqCst) + 1;
+        self.statistics.max_queue_depth.fetch_max(depth, Ordering::SeqCst);
         self.statistics.total_commands.fetch_add(1, Ordering::SeqCst);
 
         Ok(request_id)
     }
 
+    /// 处理I/O完成队列中的新条目（中断处理程序或轮询调用）
+    ///
+    /// 返回处理的完成条目数。
+    pub fn process_completions(&self) -> usize {
+        let completions: Vec<NvmeCompletion> = {
+            let io_queues = self.io_queues.lock();
+            io_queues.iter().flat_map(|queue| queue.reap_completions()).collect()
+        };
+        let count = completions.len();
+        for completion in completions {
+            self.complete_io(&completion);
+        }
+        count
+    }
+
+    /// 结束一个I/O命令：释放槽位、更新统计并调用完成回调
+    fn complete_io(&self, completion: &NvmeCompletion) {
+        let command_id = completion.command_id;
+        self.pending_commands.lock().remove(&command_id);
+        let Some(request) = self.inflight_requests.lock().remove(&command_id) else {
+            return;
+        };
+        {
+            let io_queues = self.io_queues.lock();
+            if let Some(queue) = io_queues.iter().find(|q| q.id == completion.sq_id).or(io_queues.first()) {
+                queue.free_command_id(command_id);
+            }
+        }
+
+        self.statistics.current_queue_depth.fetch_sub(1, Ordering::SeqCst);
+        let result = if completion.status.status_code == 0 {
+            self.statistics.successful_commands.fetch_add(1, Ordering::SeqCst);
+            let bytes = request.data_buffer.len() as u64;
+            match request.request_type {
+                NvmeIoRequestType::Read => {
+                    self.statistics.read_operations.fetch_add(1, Ordering::SeqCst);
+                    self.statistics.bytes_read.fetch_add(bytes, Ordering::SeqCst);
+                }
+                NvmeIoRequestType::Write => {
+                    self.statistics.write_operations.fetch_add(1, Ordering::SeqCst);
+                    self.statistics.bytes_written.fetch_add(bytes, Ordering::SeqCst);
+                }
+                _ => {}
+            }
+            Ok(())
+        } else {
+            self.statistics.failed_commands.fetch_add(1, Ordering::SeqCst);
+            Err(NvmeError::CommandError(completion.status))
+        };
+
+        if let Some(callback) = &request.completion_callback {
+            callback(result, &request.data_buffer);
+        }
+    }
+
+    /// 将活动命名空间注册为块设备 `nvme<控制器>n<命名空间>`
+    pub fn attach_block_devices(self: &Arc<Self>) -> Result<(), NvmeError> {
+        for namespace in self.get_all_namespaces()? {
+            if namespace.state != NvmeNamespaceState::Active {
+                continue;
+            }
+            let name = format!("nvme{}n{}", self.id, namespace.id);
+            let dev: Arc<dyn BlockDevice> = Arc::new(NvmeBlockDevice::new(self.clone(), namespace.id)?);
+            crate::subsystems::block::register_disk(&name, dev, alloc::boxed::Box::new(crate::subsystems::block::Noop::new()));
+        }
+        Ok(())
+    }
+
     /// 获取命名空间
     pub fn get_namespace(&self, namespace_id: u32) -> Result<NvmeNamespace, NvmeError> {
         let namespaces = self.namespaces.lock();
@@ -988,6 +1054,7 @@ impl NvmeController {
 
     fn io_request_to_command(&self, request: &NvmeIoRequest, command_id: u16) -> Result<NvmeCommand, NvmeError> {
         let opcode = match request.request_type {
+            NvmeIoRequestType::Flush => NvmeOpcode::Nvm(NvmOpcode::Flush),
             NvmeIoRequestType::Read => NvmeOpcode::Nvm(NvmOpcode::Read),
             NvmeIoRequestType::Write => NvmeOpcode::Nvm(NvmOpcode::Write),
             NvmeIoRequestType::Compare => NvmeOpcode::Nvm(NvmOpcode::Compare),
@@ -995,6 +1062,26 @@ impl NvmeController {
             NvmeIoRequestType::DatasetManagement => NvmeOpcode::Nvm(NvmOpcode::DatasetManagement),
         };
 
+        let (cdw10, cdw11, cdw12) = match request.request_type {
+            NvmeIoRequestType::Flush => (0, 0, 0),
+            // 数据缓冲区为范围列表（每项16字节），CDW11 bit 2 = 释放（AD）
+            NvmeIoRequestType::DatasetManagement => {
+                let ranges = request.data_buffer.len() / 16;
+                if ranges == 0 || ranges > 256 {
+                    return Err(NvmeError::InvalidParameter("无效的数据集范围数".to_string()));
+                }
+                ((ranges - 1) as u32, 1 << 2, 0)
+            }
+            _ => {
+                if request.block_count == 0 || request.block_count > 0x10000 {
+                    return Err(NvmeError::InvalidParameter("无效的逻辑块数".to_string()));
+                }
+                // CDW12: bit 0-15 为从0开始的块数，bit 30 为FUA
+                let fua = if request.force_unit_access { 1 << 30 } else { 0 };
+                (request.start_lba as u32, (request.start_lba >> 32) as u32, (request.block_count - 1) | fua)
+            }
+        };
+
         Ok(NvmeCommand {
             command_id,
             opcode,
@@ -1005,9 +1092,9 @@ impl NvmeController {
                 .map_or(0, |buf| buf.as_ptr() as u64),
             data_ptr: request.data_buffer.as_ptr() as u64,
             data_length: request.data_buffer.len() as u32,
-            cdw10: request.start_lba as u32,
-            cdw11: (request.start_lba >> 32) as u32,
-            cdw12: request.block_count as u32,
+            cdw10,
+            cdw11,
+            cdw12,
             cdw13: 0,
             cdw14: 0,
             cdw15: 0,
@@ -1165,9 +1252,207 @@ impl NvmeQueue {
         Err(NvmeError::TimeoutError)
     }
 
+    /// 分配一个空闲的命令槽位
+    pub fn alloc_command_id(&self) -> Option<u16> {
+        let slots = self.command_slots.lock();
+        slots.iter().find(|slot| !slot.in_use).map(|slot| slot.command_id)
+    }
+
+    /// 释放命令槽位
+    pub fn free_command_id(&self, command_id: u16) {
+        let mut slots = self.command_slots.lock();
+        if let Some(slot) = slots.get_mut(command_id as usize) {
+            slot.in_use = false;
+            slot.command = None;
+        }
+    }
+
+    /// 取出完成队列中控制器新写入的条目
+    ///
+    /// `phase` 记录上一轮条目的相位位：初始化后为false，控制器第一轮
+    /// 写入的条目相位为true；完成队列头每绕回一次翻转一次。
+    pub fn reap_completions(&self) -> Vec<NvmeCompletion> {
+        let completions = self.completion_queue.lock();
+        let mut head = self.head.lock();
+        let mut phase = self.phase.lock();
+        let mut reaped = Vec::new();
+        while let Some(entry) = completions.get(*head as usize) {
+            if entry.phase == *phase {
+                break;
+            }
+            reaped.push(entry.clone());
+            *head += 1;
+            if *head >= self.size {
+                *head = 0;
+                *phase = !*phase;
+            }
+        }
+        reaped
+    }
+
     /// 获取队列地址（用于物理地址映射）
     pub fn as_address(&self) -> usize {
         // 简化实现，实际应该返回物理地址
         self as *const NvmeQueue as usize
     }
 }
+
+impl From<NvmeError> for BlockError {
+    fn from(err: NvmeError) -> Self {
+        match err {
+            NvmeError::TimeoutError => BlockError::Timeout,
+            NvmeError::DeviceNotFound => BlockError::NoDevice,
+            NvmeError::InvalidParameter(_) => BlockError::OutOfRange,
+            _ => BlockError::IoError,
+        }
+    }
+}
+
+/// 同步命令的超时（毫秒）
+const SYNC_IO_TIMEOUT_MS: u64 = 5000;
+
+/// NVMe命名空间的块设备接口
+///
+/// 块层通过 `queue_rq` 异步提交请求，命令完成时在
+/// [`NvmeController::process_completions`] 中结束块层请求。
+pub struct NvmeBlockDevice {
+    controller: Arc<NvmeController>,
+    namespace: NvmeNamespace,
+}
+
+impl NvmeBlockDevice {
+    pub fn new(controller: Arc<NvmeController>, namespace_id: u32) -> Result<Self, NvmeError> {
+        let namespace = controller.get_namespace(namespace_id)?;
+        Ok(Self { controller, namespace })
+    }
+
+    fn io_request(&self, request_type: NvmeIoRequestType, lba: u64, block_count: u32, data: Vec<u8>) -> NvmeIoRequest {
+        NvmeIoRequest {
+            request_id: 0,
+            namespace_id: self.namespace.id,
+            start_lba: lba,
+            block_count,
+            data_buffer: data,
+            metadata_buffer: None,
+            request_type,
+            force_unit_access: false,
+            completion_callback: None,
+        }
+    }
+
+    /// 数据集管理的范围列表：上下文属性、块数、起始LBA
+    fn dsm_range(lba: u64, count: u32) -> Vec<u8> {
+        let mut range = Vec::with_capacity(16);
+        range.extend_from_slice(&0u32.to_le_bytes());
+        range.extend_from_slice(&count.to_le_bytes());
+        range.extend_from_slice(&lba.to_le_bytes());
+        range
+    }
+
+    /// 提交命令并轮询等待完成，返回数据缓冲区
+    fn execute_sync(&self, mut request: NvmeIoRequest) -> BlockResult<Vec<u8>> {
+        let done: Arc<Mutex<Option<Result<Vec<u8>, NvmeError>>>> = Arc::new(Mutex::new(None));
+        let slot = done.clone();
+        request.completion_callback = Some(Arc::new(move |result, data: &[u8]| {
+            *slot.lock() = Some(result.map(|_| data.to_vec()));
+        }));
+        self.controller.submit_io_request(request)?;
+
+        let start_time = time::timestamp_millis();
+        loop {
+            self.controller.process_completions();
+            if let Some(result) = done.lock().take() {
+                return result.map_err(BlockError::from);
+            }
+            if time::timestamp_millis() - start_time >= SYNC_IO_TIMEOUT_MS {
+                return Err(BlockError::Timeout);
+            }
+            crate::arch::wfi();
+        }
+    }
+
+    fn block_count(&self, len: usize) -> BlockResult<u32> {
+        let block_size = self.namespace.block_size as usize;
+        if len == 0 || len % block_size != 0 {
+            return Err(BlockError::Misaligned);
+        }
+        u32::try_from(len / block_size).map_err(|_| BlockError::OutOfRange)
+    }
+}
+
+impl BlockDevice for NvmeBlockDevice {
+    fn read(&self, lba: usize, buf: &mut [u8]) -> BlockResult<()> {
+        let count = self.block_count(buf.len())?;
+        let request = self.io_request(NvmeIoRequestType::Read, lba as u64, count, alloc::vec![0; buf.len()]);
+        let data = self.execute_sync(request)?;
+        buf.copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn write(&self, lba: usize, buf: &[u8]) -> BlockResult<()> {
+        let count = self.block_count(buf.len())?;
+        let request = self.io_request(NvmeIoRequestType::Write, lba as u64, count, buf.to_vec());
+        self.execute_sync(request).map(|_| ())
+    }
+
+    fn block_size(&self) -> usize {
+        self.namespace.block_size as usize
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.namespace.size as usize
+    }
+
+    fn flush(&self) -> BlockResult<()> {
+        let request = self.io_request(NvmeIoRequestType::Flush, 0, 0, Vec::new());
+        self.execute_sync(request).map(|_| ())
+    }
+
+    fn discard(&self, lba: usize, count: usize) -> BlockResult<()> {
+        if !self.namespace.features.trim_supported {
+            return Err(BlockError::NotSupported);
+        }
+        let count = u32::try_from(count).map_err(|_| BlockError::OutOfRange)?;
+        let request = self.io_request(NvmeIoRequestType::DatasetManagement, lba as u64, 0, Self::dsm_range(lba as u64, count));
+        self.execute_sync(request).map(|_| ())
+    }
+
+    fn features(&self) -> BlockFeatures {
+        BlockFeatures {
+            write_cache: true,
+            fua: true,
+            discard: self.namespace.features.trim_supported,
+            max_sectors: 256,
+        }
+    }
+
+    fn queue_rq(&self, rq: Request) {
+        let lba = rq.sector();
+        let count = rq.nr_sectors();
+        let mut request = match rq.op() {
+            ReqOp::Read => self.io_request(NvmeIoRequestType::Read, lba, count, alloc::vec![0; rq.data().len()]),
+            ReqOp::Write => self.io_request(NvmeIoRequestType::Write, lba, count, rq.data().to_vec()),
+            ReqOp::Flush => self.io_request(NvmeIoRequestType::Flush, 0, 0, Vec::new()),
+            ReqOp::Discard => self.io_request(NvmeIoRequestType::DatasetManagement, lba, 0, Self::dsm_range(lba, count)),
+        };
+        request.force_unit_access = rq.op() == ReqOp::Write && rq.flags().contains(ReqFlags::FUA);
+
+        // 回调只会被调用一次；提交失败时从这里取回请求
+        let pending = Arc::new(Mutex::new(Some(rq)));
+        let slot = pending.clone();
+        request.completion_callback = Some(Arc::new(move |result, data: &[u8]| {
+            if let Some(mut rq) = slot.lock().take() {
+                if result.is_ok() && rq.op() == ReqOp::Read {
+                    rq.data_mut().copy_from_slice(data);
+                }
+                rq.end(result.map_err(BlockError::from));
+            }
+        }));
+
+        if let Err(err) = self.controller.submit_io_request(request) {
+            if let Some(rq) = pending.lock().take() {
+                rq.end(Err(err.into()));
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! PCI configuration through a generic ECAM host bridge
+//!
+//! Firmware on the QEMU `virt` boards leaves BARs unassigned, so [`init`]
+//! walks the buses the device tree gives the host bridge, sizes every
+//! memory BAR and places it in one of the bridge's windows before a driver
+//! looks at it. Bridges behind the root bus and I/O BARs are not handled.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use crate::subsystems::mm::{mmio_read8, mmio_read16, mmio_read32, mmio_write16, mmio_write32};
+use crate::subsystems::sync::Mutex;
+
+use super::platform::{PciHostInfo, PciWindow};
+
+pub const PCI_VENDOR_ID: u16 = 0x00;
+pub const PCI_DEVICE_ID: u16 = 0x02;
+pub const PCI_COMMAND: u16 = 0x04;
+pub const PCI_STATUS: u16 = 0x06;
+pub const PCI_HEADER_TYPE: u16 = 0x0e;
+pub const PCI_BAR0: u16 = 0x10;
+pub const PCI_SUBSYSTEM_ID: u16 = 0x2e;
+pub const PCI_CAPABILITY_LIST: u16 = 0x34;
+
+pub const PCI_COMMAND_MEMORY: u16 = 1 << 1;
+pub const PCI_COMMAND_MASTER: u16 = 1 << 2;
+pub const PCI_STATUS_CAP_LIST: u16 = 1 << 4;
+
+/// Vendor-specific capability, as virtio uses
+pub const PCI_CAP_ID_VNDR: u8 = 0x09;
+
+const BAR_IO: u32 = 1 << 0;
+const BAR_MEM64: u32 = 2 << 1;
+const BAR_PREFETCH: u32 = 1 << 3;
+
+/// One function on the bus, with the BARs [`init`] gave it
+#[derive(Debug, Clone, Copy)]
+pub struct PciFunction {
+    config: usize,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    /// CPU address and size of each assigned memory BAR
+    bars: [Option<(usize, usize)>; 6],
+}
+
+impl PciFunction {
+    pub fn read8(&self, offset: u16) -> u8 {
+        mmio_read8((self.config + offset as usize) as *const u8)
+    }
+
+    pub fn read16(&self, offset: u16) -> u16 {
+        mmio_read16((self.config + offset as usize) as *const u16)
+    }
+
+    pub fn read32(&self, offset: u16) -> u32 {
+        mmio_read32((self.config + offset as usize) as *const u32)
+    }
+
+    pub fn write16(&self, offset: u16, value: u16) {
+        mmio_write16((self.config + offset as usize) as *mut u16, value)
+    }
+
+    pub fn write32(&self, offset: u16, value: u32) {
+        mmio_write32((self.config + offset as usize) as *mut u32, value)
+    }
+
+    /// CPU address and size of memory BAR `index`, if it was assigned
+    pub fn bar(&self, index: usize) -> Option<(usize, usize)> {
+        self.bars.get(index).copied().flatten()
+    }
+
+    /// Offsets of the entries on the capability list
+    pub fn capabilities(&self) -> impl Iterator<Item = u16> + '_ {
+        let first = if self.read16(PCI_STATUS) & PCI_STATUS_CAP_LIST != 0 { self.read8(PCI_CAPABILITY_LIST) & !3 } else { 0 };
+        // A malformed list could loop; there is room for at most 48 entries
+        let mut next = first as u16;
+        core::iter::from_fn(move || {
+            if next < 0x40 {
+                return None;
+            }
+            let offset = next;
+            next = (self.read8(offset + 1) & !3) as u16;
+            Some(offset)
+        })
+        .take(48)
+    }
+
+    /// Turn on memory decoding and bus mastering
+    pub fn enable(&self) {
+        let command = self.read16(PCI_COMMAND);
+        self.write16(PCI_COMMAND, command | PCI_COMMAND_MEMORY | PCI_COMMAND_MASTER);
+    }
+}
+
+/// Bump allocator over one host bridge window
+struct Window {
+    window: PciWindow,
+    next: u64,
+}
+
+impl Window {
+    /// Bus address for a BAR of `size` bytes (a power of two, so also its alignment)
+    fn alloc(&mut self, size: u64) -> Option<u64> {
+        let start = self.next.checked_add(size - 1)? & !(size - 1);
+        let end = start.checked_add(size)?;
+        if end > self.window.pci + self.window.size {
+            return None;
+        }
+        self.next = end;
+        Some(start)
+    }
+
+    fn cpu(&self, pci: u64) -> usize {
+        (self.window.cpu + (pci - self.window.pci)) as usize
+    }
+}
+
+static FUNCTIONS: Mutex<Vec<PciFunction>> = Mutex::new(Vec::new());
+
+/// Functions found by [`init`]
+pub fn functions() -> Vec<PciFunction> {
+    FUNCTIONS.lock().clone()
+}
+
+/// Size and place the memory BARs of a type 0 function
+fn assign_bars(func: &mut PciFunction, windows: &mut [Window]) {
+    // Decoding stays off while BARs hold sizing patterns
+    let command = func.read16(PCI_COMMAND);
+    func.write16(PCI_COMMAND, command & !(PCI_COMMAND_MEMORY | PCI_COMMAND_MASTER));
+
+    let mut index = 0;
+    while index < 6 {
+        let reg = PCI_BAR0 + index as u16 * 4;
+        let original = func.read32(reg);
+        let mem64 = original & BAR_IO == 0 && original & (3 << 1) == BAR_MEM64;
+        let step = if mem64 { 2 } else { 1 };
+        if original & BAR_IO != 0 || (mem64 && index == 5) {
+            index += 1;
+            continue;
+        }
+
+        func.write32(reg, u32::MAX);
+        let mut mask = (func.read32(reg) & !0xf) as u64;
+        if mem64 {
+            func.write32(reg + 4, u32::MAX);
+            mask |= (func.read32(reg + 4) as u64) << 32;
+        } else {
+            mask |= 0xffff_ffff_0000_0000;
+        }
+        // Unimplemented BARs read back as zero
+        if (!mem64 && mask as u32 == 0) || mask == 0 {
+            func.write32(reg, original);
+            index += step;
+            continue;
+        }
+        let size = !mask + 1;
+
+        // 64-bit BARs go in a 64-bit window when there is one; prefetchable
+        // windows only take prefetchable BARs
+        let prefetchable = original & BAR_PREFETCH != 0;
+        let mut order: Vec<usize> = (0..windows.len())
+            .filter(|&i| (mem64 || !windows[i].window.mem64) && (prefetchable || !windows[i].window.prefetchable))
+            .collect();
+        order.sort_by_key(|&i| !windows[i].window.mem64);
+        let placed = order.into_iter().find_map(|i| {
+            let window = &mut windows[i];
+            let pci = window.alloc(size)?;
+            // A 32-bit BAR cannot hold an address above 4 GiB
+            if !mem64 && pci + size > 1 << 32 {
+                return None;
+            }
+            Some((pci, window.cpu(pci)))
+        });
+        match placed {
+            Some((pci, cpu)) => {
+                func.write32(reg, pci as u32);
+                if mem64 {
+                    func.write32(reg + 4, (pci >> 32) as u32);
+                }
+                func.bars[index] = Some((cpu, size as usize));
+            }
+            None => {
+                crate::println!("pci: {:02x}:{:02x}.{}: no room for BAR{} ({:#x} bytes)", func.bus, func.device, func.function, index, size);
+                func.write32(reg, 0);
+                if mem64 {
+                    func.write32(reg + 4, 0);
+                }
+            }
+        }
+        index += step;
+    }
+
+    func.write16(PCI_COMMAND, command);
+}
+
+/// Enumerate the ECAM host bridge the device tree describes
+pub fn init() {
+    let Some(PciHostInfo { ecam, ecam_size, bus_start, bus_end, windows }) = super::platform::pci_host() else { return };
+    let mut windows: Vec<Window> = windows.into_iter().map(|window| Window { window, next: window.pci }).collect();
+    let buses = (ecam_size >> 20).min((bus_end as usize).saturating_sub(bus_start as usize) + 1);
+
+    let mut found = Vec::new();
+    for bus in 0..buses {
+        for device in 0..32usize {
+            for function in 0..8usize {
+                let config = ecam + (bus << 20 | device << 15 | function << 12);
+                let vendor_id = mmio_read16((config + PCI_VENDOR_ID as usize) as *const u16);
+                if vendor_id == 0xffff {
+                    if function == 0 {
+                        break;
+                    }
+                    continue;
+                }
+                let mut func = PciFunction {
+                    config,
+                    bus: bus_start + bus as u8,
+                    device: device as u8,
+                    function: function as u8,
+                    vendor_id,
+                    device_id: mmio_read16((config + PCI_DEVICE_ID as usize) as *const u16),
+                    bars: [None; 6],
+                };
+                let header = func.read8(PCI_HEADER_TYPE);
+                if header & 0x7f == 0 {
+                    assign_bars(&mut func, &mut windows);
+                }
+                found.push(func);
+                if function == 0 && header & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    crate::println!("pci: ecam {:#x}, {} functions", ecam, found.len());
+    *FUNCTIONS.lock() = found;
+}
@@ -1,19 +1,9 @@
-/// Platform probing for memory and MMIO via DTB/firmware (stub)
+/// Platform probing for memory, MMIO and devices via the device tree
+use crate::boot::fdt::{Fdt, FdtNode};
 use crate::mm;
 use crate::subsystems::sync::Mutex;
 extern crate alloc;
 use alloc::vec::Vec;
-use core::slice;
-use core::str;
-
-const FDT_BEGIN_NODE: u32 = 1;
-const FDT_END_NODE: u32 = 2;
-const FDT_PROP: u32 = 3;
-const FDT_NOP: u32 = 4;
-const FDT_END: u32 = 9;
-
-#[derive(Clone, Copy)]
-struct Cells { addr: u32, size: u32 }
 
 #[derive(Clone, Copy)]
 struct Range { child: u128, parent: u128, size: u128, weight: u32, order: usize }
@@ -73,269 +63,506 @@ fn translate_addr(mut addr: usize, ranges: &[Range]) -> usize {
     addr
 }
 
+/// Map the MMIO windows the device tree describes and size physical memory
+///
+/// Runs once the tree is parsed (see `boot::init_device_tree_from_boot_info`).
+/// Register windows are translated through each bus's `ranges`, honouring the
+/// `nos,range-weight` extension when ranges overlap, and classified as
+/// strongly ordered, write-combining or normal device memory.
 pub fn probe_dtb() {
-    let dtb_ptr = dtb_pointer();
-    if dtb_ptr == 0 { return; }
-    
-    unsafe {
-        let magic = *(dtb_ptr as *const u32);
-        if u32::from_be(magic) != 0xd00dfeed { return; }
-        
-        let off_struct = u32::from_be(*((dtb_ptr + 8) as *const u32)) as usize;
-        let off_strings = u32::from_be(*((dtb_ptr + 12) as *const u32)) as usize;
-        
-        let struct_ptr = (dtb_ptr + off_struct) as *const u32;
-        let strings_ptr = (dtb_ptr + off_strings) as *const u8;
-        
-        let mut p = struct_ptr;
-        let mut node_name = "";
-        let mut cells_stack: [Cells; 32] = [Cells { addr: 2, size: 1 }; 32];
-        let mut ranges_stack: [usize; 32] = [0; 32];
-        let mut ranges: Vec<Range> = Vec::new();
-        let mut current_weights: Vec<u32> = Vec::new();
-        let mut weights_stack: [usize; 32] = [0; 32];
-        let mut range_order: usize = 0;
-        let mut depth: usize = 0;
-        let mut current_cells = Cells { addr: 2, size: 1 };
-        let mut max_mem_end: usize = 0;
-        let mut node_is_strong = false;
+    let Some(fdt) = crate::boot::device_tree() else { return };
+    let Some(root) = fdt.root() else { return };
+
+    let mut strong_list: Vec<&'static str> = Vec::new();
+    strong_list.extend_from_slice(STRONG_COMPAT);
+    for compat in root.compatible() {
+        for entry in SOC_TABLE {
+            if compat == entry.id {
+                strong_list.extend_from_slice(entry.strong);
+            }
+        }
+    }
+    for node in fdt.nodes() {
+        if let Some(prop) = node.property("nos,strong-compat") {
+            strong_list.extend(prop.strings());
+        }
+    }
+
+    let cfg = |name| root.property(name).and_then(|prop| prop.as_u32()).filter(|v| *v != 0).map(|v| v as u64);
+    let (decay_num, decay_den, threshold_hits) = (cfg("nos,mmio-decay-num"), cfg("nos,mmio-decay-den"), cfg("nos,mmio-threshold"));
+    let (cooldown_ticks, decay_interval, sample_div) = (cfg("nos,mmio-cooldown-ticks"), cfg("nos,mmio-decay-interval"), cfg("nos,mmio-sample-div"));
+
+    // Nodes come in pre-order, so the ranges in force for a node are those of
+    // its ancestors: `ranges_stack[d]` is how many entries depth `d` sees
+    let mut ranges: Vec<Range> = Vec::new();
+    let mut ranges_stack: Vec<usize> = Vec::new();
+    let mut range_order: usize = 0;
+    for node in fdt.nodes() {
+        ranges_stack.truncate(node.depth());
+        ranges.truncate(ranges_stack.last().copied().unwrap_or(0));
+        ranges_stack.push(ranges.len());
+
+        let name = node.name();
+        let mut node_is_strong = node.property("interrupt-controller").is_some() || node.property("nos,strong-device").is_some();
         let mut node_is_wc = false;
-        let mut strong_list: Vec<&'static str> = Vec::new();
-        strong_list.extend_from_slice(STRONG_COMPAT);
-        let mut node_is_gicv2 = false;
-        let mut node_is_gicv3 = false;
-        let mut node_is_gicr = false;
-        let mut current_gicr_mpidr: u64 = 0;
-        let mut current_gicr_addr: Option<usize> = None;
-        let mut cfg_decay_num: u64 = 0;
-        let mut cfg_decay_den: u64 = 0;
-        let mut cfg_threshold_hits: u64 = 0;
-        let mut cfg_cooldown_ticks: u64 = 0;
-        let mut cfg_decay_interval: u64 = 0;
-        let mut cfg_sample_div: u64 = 0;
-        
-        while u32::from_be(*p) != FDT_END {
-            let token = u32::from_be(*p);
-            p = p.add(1);
-            
-            match token {
-                FDT_BEGIN_NODE => {
-                    let name_ptr = p as *const u8;
-                    let len = strlen(name_ptr);
-                    node_name = str::from_utf8(slice::from_raw_parts(name_ptr, len)).unwrap_or("");
-                    let padded = (len + 1 + 3) & !3;
-                    p = (name_ptr.add(padded)) as *const u32;
-                    if depth < cells_stack.len() { cells_stack[depth] = current_cells; ranges_stack[depth] = ranges.len(); weights_stack[depth] = current_weights.len(); }
-                    depth += 1;
-                    node_is_strong = false;
-                    node_is_wc = false;
-                    node_is_gicr = false;
-                    current_gicr_mpidr = 0;
-                    current_gicr_addr = None;
-                }
-                FDT_END_NODE => {
-                    node_name = "";
-                    if depth > 0 { depth -= 1; current_cells = cells_stack[depth.min(cells_stack.len()-1)]; }
-                    let keep = ranges_stack[depth.min(ranges_stack.len()-1)];
-                    if ranges.len() > keep { ranges.truncate(keep); }
-                    let wkeep = weights_stack[depth.min(weights_stack.len()-1)];
-                    if current_weights.len() > wkeep { current_weights.truncate(wkeep); }
-                    node_is_strong = false;
-                    node_is_wc = false;
-                    if node_is_gicr {
-                        if let Some(addr) = current_gicr_addr {
-                            let mut v = GICR_REDISTS.lock();
-                            v.push((current_gicr_mpidr, addr));
-                        }
-                    }
-                    node_is_gicr = false;
-                }
-                FDT_PROP => {
-                    let len = u32::from_be(*p) as usize;
-                    p = p.add(1);
-                    let nameoff = u32::from_be(*p) as usize;
-                    p = p.add(1);
-                    
-                    let prop_name_ptr = strings_ptr.add(nameoff);
-                    let prop_len = strlen(prop_name_ptr);
-                    let prop_name = str::from_utf8(slice::from_raw_parts(prop_name_ptr, prop_len)).unwrap_or("");
-                    
-                    let data_ptr = p as *const u8;
-                    
-                    if prop_name == "#address-cells" && len >= 4 { current_cells.addr = u32::from_be(*(data_ptr as *const u32)); }
-                    if prop_name == "#size-cells" && len >= 4 { current_cells.size = u32::from_be(*(data_ptr as *const u32)); }
-                    if prop_name == "interrupt-controller" { node_is_strong = true; }
-                    if depth == 1 && prop_name == "compatible" {
-                        let mut off2 = 0usize;
-                        while off2 < len {
-                            let s_ptr2 = data_ptr.add(off2);
-                            let sl2 = strlen(s_ptr2);
-                            if sl2 == 0 { break; }
-                            let s2 = str::from_utf8(slice::from_raw_parts(s_ptr2, sl2)).unwrap_or("");
-                            for entry in SOC_TABLE {
-                                if s2 == entry.id {
-                                    strong_list.extend_from_slice(entry.strong);
-                                }
-                            }
-                            off2 += sl2 + 1;
-                        }
-                    }
-                    if depth == 1 && prop_name == "nos,mmio-decay-num" { if len >= 4 { cfg_decay_num = u32::from_be(*(data_ptr as *const u32)) as u64; } }
-                    if depth == 1 && prop_name == "nos,mmio-decay-den" { if len >= 4 { cfg_decay_den = u32::from_be(*(data_ptr as *const u32)) as u64; } }
-                    if depth == 1 && prop_name == "nos,mmio-threshold" { if len >= 4 { cfg_threshold_hits = u32::from_be(*(data_ptr as *const u32)) as u64; } }
-                    if depth == 1 && prop_name == "nos,mmio-cooldown-ticks" { if len >= 4 { cfg_cooldown_ticks = u32::from_be(*(data_ptr as *const u32)) as u64; } }
-                    if depth == 1 && prop_name == "nos,mmio-decay-interval" { if len >= 4 { cfg_decay_interval = u32::from_be(*(data_ptr as *const u32)) as u64; } }
-                    if depth == 1 && prop_name == "nos,mmio-sample-div" { if len >= 4 { cfg_sample_div = u32::from_be(*(data_ptr as *const u32)) as u64; } }
-                    if prop_name == "nos,strong-compat" {
-                        let mut off3 = 0usize;
-                        while off3 < len {
-                            let s_ptr3 = data_ptr.add(off3);
-                            let sl3 = strlen(s_ptr3);
-                            if sl3 == 0 { break; }
-                            let s3 = str::from_utf8(slice::from_raw_parts(s_ptr3, sl3)).unwrap_or("");
-                            strong_list.push(s3);
-                            off3 += sl3 + 1;
-                        }
-                    }
-                    if prop_name == "nos,strong-device" { node_is_strong = true; }
-                    if prop_name == "compatible" {
-                        let mut off = 0usize;
-                        while off < len {
-                            let s_ptr = data_ptr.add(off);
-                            let sl = strlen(s_ptr);
-                            if sl == 0 { break; }
-                            let s = str::from_utf8(slice::from_raw_parts(s_ptr, sl)).unwrap_or("");
-                            for c in strong_list.iter() {
-                                if s == *c { node_is_strong = true; break; }
-                            }
-                            if s == "arm,gic-400" || s == "arm,gic-v2" { node_is_gicv2 = true; }
-                            if s == "arm,gic-v3" { node_is_gicv3 = true; }
-                            if s.contains("redistributor") || s.contains("gicr") { node_is_gicr = true; }
-                            if s == "simple-framebuffer" || s.contains("framebuffer") || s == "efi-framebuffer" { node_is_wc = true; }
-                            off += sl + 1;
-                        }
-                    }
-                    if prop_name == "arm,mpidr" && len >= 8 { current_gicr_mpidr = u64::from_be(*(data_ptr as *const u64)); }
-                    if prop_name == "nos,range-weight" {
-                        current_weights.clear();
-                        let mut offw = 0usize;
-                        while offw + 4 <= len {
-                            let w = u32::from_be(*((data_ptr.add(offw)) as *const u32));
-                            current_weights.push(w);
-                            offw += 4;
-                        }
-                    }
-                    if prop_name == "ranges" {
-                        let c_ac = current_cells.addr as usize;
-                        let p_ac = cells_stack[depth.saturating_sub(1).min(cells_stack.len()-1)].addr as usize;
-                        let sc = current_cells.size as usize;
-                        let cell_bytes = 4usize;
-                        let entry_cells = c_ac + p_ac + sc;
-                        let mut off = 0usize;
-                        let mut idxw = 0usize;
-                        let mut entries = 0usize;
-                        while entry_cells > 0 && off + entry_cells * cell_bytes <= len {
-                            let mut child: u128 = 0; let mut parent: u128 = 0; let mut size128: u128 = 0;
-                            for i in 0..c_ac { let v = u32::from_be(*((data_ptr.add(off + i * cell_bytes)) as *const u32)) as u128; child = (child << 32) | v; }
-                            for i in 0..p_ac { let v = u32::from_be(*((data_ptr.add(off + c_ac * cell_bytes + i * cell_bytes)) as *const u32)) as u128; parent = (parent << 32) | v; }
-                            for i in 0..sc { let v = u32::from_be(*((data_ptr.add(off + (c_ac + p_ac) * cell_bytes + i * cell_bytes)) as *const u32)) as u128; size128 = (size128 << 32) | v; }
-                            let w = if idxw < current_weights.len() { current_weights[idxw] } else { 0 };
-                            ranges.push(Range { child, parent, size: size128, weight: w, order: range_order });
-                            range_order += 1;
-                            off += entry_cells * cell_bytes;
-                            idxw += 1;
-                            entries += 1;
-                        }
-                        if !current_weights.is_empty() && entries != current_weights.len() {
-                            crate::println!("[dtb] ranges weight mismatch: entries={} weights={}", entries, current_weights.len());
-                        }
-                    }
-                    if prop_name == "reg" {
-                        let ac = current_cells.addr as usize;
-                        let sc = current_cells.size as usize;
-                        let cell_bytes = 4usize;
-                        let entry_cells = ac + sc;
-                        if entry_cells > 0 {
-                            let mut off = 0usize;
-                            let mut gic_entries: [(usize, usize); 2] = [(0,0); 2];
-                            let mut gic_count = 0usize;
-                            while off + entry_cells * cell_bytes <= len {
-                                let mut addr64: u128 = 0;
-                                for i in 0..ac {
-                                    let v = u32::from_be(*((data_ptr.add(off + i * cell_bytes)) as *const u32)) as u128;
-                                    addr64 = (addr64 << 32) | v;
-                                }
-                                let mut size64: u128 = 0;
-                                for i in 0..sc {
-                                    let v = u32::from_be(*((data_ptr.add(off + ac * cell_bytes + i * cell_bytes)) as *const u32)) as u128;
-                                    size64 = (size64 << 32) | v;
-                                }
-                                let mut addr = addr64 as usize;
-                                let size = size64 as usize;
-                                addr = translate_addr(addr, &ranges);
-                                if node_name.starts_with("memory") {
-                                    let end = addr.saturating_add(size);
-                                    if end > max_mem_end { max_mem_end = end; }
-                                } else if !node_name.starts_with("cpu") && !node_name.is_empty() {
-                                    if size != 0 {
-                                        if node_is_wc { mm::add_mmio_region_wc(addr, size); }
-                                        else if node_is_strong { mm::add_mmio_region_strong(addr, size); }
-                                        else { mm::add_mmio_region(addr, size); }
-                                    }
-                                }
-                                if (node_is_gicv2 || node_is_gicv3) && gic_count < 2 && size != 0 {
-                                    gic_entries[gic_count] = (addr, size);
-                                    gic_count += 1;
-                                    if gic_count == 2 {
-                                        if node_is_gicv2 {
-                                            let mut s = GICV2_BASES.lock();
-                                            *s = Some((gic_entries[0].0, gic_entries[1].0));
-                                        }
-                                        if node_is_gicv3 {
-                                            let mut s3 = GICV3_BASES.lock();
-                                            *s3 = Some((gic_entries[0].0, gic_entries[1].0));
-                                        }
-                                    }
-                                }
-                                if node_is_gicr && size != 0 { current_gicr_addr = Some(addr); }
-                                off += entry_cells * cell_bytes;
-                            }
-                        }
-                    }
-                    
-                    let padded = (len + 3) & !3;
-                    p = (p as *const u8).add(padded) as *const u32;
+        for s in node.compatible() {
+            if strong_list.contains(&s) { node_is_strong = true; }
+            if s == "simple-framebuffer" || s.contains("framebuffer") || s == "efi-framebuffer" { node_is_wc = true; }
+        }
+        if node.depth() > 0 && !name.starts_with("memory") && !name.starts_with("cpu") {
+            for (addr, size) in node.reg() {
+                let addr = translate_addr(addr as usize, &ranges);
+                let size = size as usize;
+                if size == 0 { continue; }
+                if node_is_wc { mm::add_mmio_region_wc(addr, size); }
+                else if node_is_strong { mm::add_mmio_region_strong(addr, size); }
+                else { mm::add_mmio_region(addr, size); }
+            }
+        }
+        // BARs get placed in a host bridge's windows, which no `reg` covers
+        if node.property("device_type").and_then(|prop| prop.as_str()) == Some("pci") {
+            for window in pci_windows(&node) {
+                mm::add_mmio_region(translate_addr(window.cpu as usize, &ranges), window.size as usize);
+            }
+        }
+
+        if let Some(prop) = node.property("ranges") {
+            let weights: Vec<u32> = node.property("nos,range-weight").map(|w| w.cells().collect()).unwrap_or_default();
+            let c_ac = node.child_cells().address as usize;
+            let p_ac = node.cells().address as usize;
+            let sc = node.child_cells().size as usize;
+            let entry_cells = c_ac + p_ac + sc;
+            let cells: Vec<u32> = prop.cells().collect();
+            let fold = |cells: &[u32]| cells.iter().fold(0u128, |acc, v| (acc << 32) | *v as u128);
+            let mut entries = 0usize;
+            if entry_cells > 0 {
+                for entry in cells.chunks_exact(entry_cells) {
+                    let child = fold(&entry[..c_ac]);
+                    let parent = fold(&entry[c_ac..c_ac + p_ac]);
+                    let size = fold(&entry[c_ac + p_ac..]);
+                    let weight = weights.get(entries).copied().unwrap_or(0);
+                    ranges.push(Range { child, parent, size, weight, order: range_order });
+                    range_order += 1;
+                    entries += 1;
                 }
-                FDT_NOP => {}
-                _ => break,
+            }
+            if !weights.is_empty() && entries != weights.len() {
+                crate::println!("[dtb] ranges weight mismatch: entries={} weights={}", entries, weights.len());
             }
         }
-        if max_mem_end != 0 { mm::set_phys_end(max_mem_end); }
-        if cfg_decay_num != 0 || cfg_decay_den != 0 || cfg_threshold_hits != 0 || cfg_cooldown_ticks != 0 || cfg_decay_interval != 0 || cfg_sample_div != 0 {
-            mm::mmio_cfg_update(
-                if cfg_decay_interval != 0 { Some(cfg_decay_interval) } else { None },
-                if cfg_decay_num != 0 { Some(cfg_decay_num) } else { None },
-                if cfg_decay_den != 0 { Some(cfg_decay_den) } else { None },
-                if cfg_threshold_hits != 0 { Some(cfg_threshold_hits) } else { None },
-                if cfg_cooldown_ticks != 0 { Some(cfg_cooldown_ticks) } else { None },
-                if cfg_sample_div != 0 { Some(cfg_sample_div) } else { None },
-            );
+    }
+
+    let max_mem_end = PLATFORM_INFO
+        .lock()
+        .as_ref()
+        .and_then(|info| info.memory.iter().map(|(base, size)| base.saturating_add(*size)).max())
+        .unwrap_or(0);
+    if max_mem_end != 0 { mm::set_phys_end(max_mem_end); }
+    if decay_num.is_some() || decay_den.is_some() || threshold_hits.is_some() || cooldown_ticks.is_some() || decay_interval.is_some() || sample_div.is_some() {
+        mm::mmio_cfg_update(decay_interval, decay_num, decay_den, threshold_hits, cooldown_ticks, sample_div);
+    }
+}
+
+/// Kind of serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartKind {
+    /// 16550-compatible (riscv64 `virt`, most SoCs)
+    Ns16550,
+    /// ARM PrimeCell PL011 (aarch64 `virt`)
+    Pl011,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UartInfo {
+    pub kind: UartKind,
+    pub base: usize,
+    pub size: usize,
+    pub irq: Option<u32>,
+    pub clock_hz: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GicInfo {
+    /// 2 or 3
+    pub version: u8,
+    pub dist: usize,
+    /// CPU interface for GICv2, first redistributor region for GICv3
+    pub cpu_or_redist: usize,
+}
+
+/// RISC-V platform-level interrupt controller
+#[derive(Debug, Clone, Copy)]
+pub struct PlicInfo {
+    pub base: usize,
+    pub size: usize,
+    pub ndev: u32,
+}
+
+/// RISC-V core-local interruptor (mtime/mtimecmp/msip)
+#[derive(Debug, Clone, Copy)]
+pub struct ClintInfo {
+    pub base: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VirtioMmioInfo {
+    pub base: usize,
+    pub size: usize,
+    pub irq: Option<u32>,
+}
+
+/// A memory window a PCI host bridge forwards to its bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PciWindow {
+    /// Address on the PCI bus
+    pub pci: u64,
+    /// Address the CPU reaches it at
+    pub cpu: u64,
+    pub size: u64,
+    /// Above 4 GiB is allowed (64-bit memory space)
+    pub mem64: bool,
+    pub prefetchable: bool,
+}
+
+/// Generic ECAM PCI host bridge (`pci-host-ecam-generic`)
+#[derive(Debug, Clone)]
+pub struct PciHostInfo {
+    /// Configuration space, 1 MiB per bus from `bus_start`
+    pub ecam: usize,
+    pub ecam_size: usize,
+    pub bus_start: u8,
+    pub bus_end: u8,
+    /// Memory windows BARs can be placed in; I/O space is left out
+    pub windows: Vec<PciWindow>,
+}
+
+/// How PSCI calls reach firmware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PsciConduit {
+    Hvc,
+    Smc,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PsciInfo {
+    pub conduit: PsciConduit,
+    /// Function ids; PSCI 0.1 firmware names its own, later versions use the
+    /// standard ones
+    pub cpu_on: u32,
+    pub cpu_off: u32,
+    /// PSCI 0.2 or later: VERSION, SYSTEM_OFF and SYSTEM_RESET exist
+    pub v0_2: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CpuInfo {
+    /// MPIDR affinity on aarch64, hart id on riscv64
+    pub id: u64,
+    pub enable_method: Option<&'static str>,
+}
+
+/// What the device tree says about the machine
+#[derive(Debug, Clone, Default)]
+pub struct PlatformInfo {
+    pub model: Option<&'static str>,
+    /// RAM as (base, size)
+    pub memory: Vec<(usize, usize)>,
+    /// Memory reservation map plus `/reserved-memory` children
+    pub reserved: Vec<(usize, usize)>,
+    pub uarts: Vec<UartInfo>,
+    /// Index into `uarts` of the `/chosen` stdout-path device
+    pub console: Option<usize>,
+    pub gic: Option<GicInfo>,
+    pub plic: Option<PlicInfo>,
+    pub clint: Option<ClintInfo>,
+    /// `/cpus/timebase-frequency`
+    pub timebase_hz: Option<u64>,
+    /// Virtual timer interrupt of the ARM architected timer
+    pub arch_timer_irq: Option<u32>,
+    pub virtio_mmio: Vec<VirtioMmioInfo>,
+    pub pci: Option<PciHostInfo>,
+    pub psci: Option<PsciInfo>,
+    pub cpus: Vec<CpuInfo>,
+}
+
+static PLATFORM_INFO: Mutex<Option<PlatformInfo>> = Mutex::new(None);
+
+/// Platform description, if the kernel was handed a device tree
+pub fn platform_info() -> Option<PlatformInfo> { PLATFORM_INFO.lock().clone() }
+pub fn dtb_present() -> bool { PLATFORM_INFO.lock().is_some() }
+pub fn virtio_mmio_devices() -> Vec<VirtioMmioInfo> { PLATFORM_INFO.lock().as_ref().map(|info| info.virtio_mmio.clone()).unwrap_or_default() }
+pub fn psci_info() -> Option<PsciInfo> { PLATFORM_INFO.lock().as_ref().and_then(|info| info.psci) }
+pub fn pci_host() -> Option<PciHostInfo> { PLATFORM_INFO.lock().as_ref().and_then(|info| info.pci.clone()) }
+
+const PSCI_0_2_CPU_OFF: u32 = 0x8400_0002;
+const PSCI_0_2_CPU_ON_64: u32 = 0xc400_0003;
+
+const NS16550_COMPAT: &[&str] = &["ns16550a", "ns16550", "snps,dw-apb-uart"];
+const PL011_COMPAT: &[&str] = &["arm,pl011"];
+const GICV2_COMPAT: &[&str] = &["arm,gic-400", "arm,gic-v2", "arm,cortex-a15-gic"];
+const GICV3_COMPAT: &[&str] = &["arm,gic-v3"];
+const PLIC_COMPAT: &[&str] = &["riscv,plic0", "sifive,plic-1.0.0"];
+const CLINT_COMPAT: &[&str] = &["riscv,clint0", "sifive,clint0"];
+const ARCH_TIMER_COMPAT: &[&str] = &["arm,armv8-timer", "arm,armv7-timer"];
+const PSCI_COMPAT: &[&str] = &["arm,psci-1.0", "arm,psci-0.2", "arm,psci"];
+const PCI_HOST_COMPAT: &[&str] = &["pci-host-ecam-generic"];
+
+/// Interrupt number of one `interrupts` specifier
+///
+/// Three-cell specifiers are the GIC's (type, number, flags), with SPIs
+/// numbered from 32 and PPIs from 16; shorter ones (PLIC) are the number.
+fn irq_number(spec: &[u8]) -> Option<u32> {
+    let cell = |i: usize| spec.get(i * 4..i * 4 + 4).and_then(|b| b.try_into().ok()).map(u32::from_be_bytes);
+    if spec.len() < 12 {
+        return cell(0);
+    }
+    match cell(0)? {
+        0 => Some(cell(1)? + 32),
+        1 => Some(cell(1)? + 16),
+        _ => None,
+    }
+}
+
+/// Memory windows of a PCI bus node's `ranges`, still as parent-bus addresses
+///
+/// Child addresses are three cells: `phys.hi` carries the space code in bits
+/// 24-25 (1 I/O, 2 32-bit memory, 3 64-bit memory) and the prefetchable bit
+/// 30, `phys.mid`/`phys.lo` the bus address.
+fn pci_ranges(cells: &[u32], parent_cells: usize, size_cells: usize) -> Vec<PciWindow> {
+    let fold = |cells: &[u32]| cells.iter().fold(0u64, |acc, v| (acc << 32) | *v as u64);
+    let entry_cells = 3 + parent_cells + size_cells;
+    let mut windows = Vec::new();
+    for entry in cells.chunks_exact(entry_cells) {
+        let space = (entry[0] >> 24) & 3;
+        let size = fold(&entry[3 + parent_cells..]);
+        if space < 2 || size == 0 {
+            continue;
         }
+        windows.push(PciWindow {
+            pci: fold(&entry[1..3]),
+            cpu: fold(&entry[3..3 + parent_cells]),
+            size,
+            mem64: space == 3,
+            prefetchable: entry[0] & (1 << 30) != 0,
+        });
     }
+    windows
+}
+
+fn pci_windows(node: &FdtNode<'_>) -> Vec<PciWindow> {
+    let Some(prop) = node.property("ranges") else { return Vec::new() };
+    let cells: Vec<u32> = prop.cells().collect();
+    pci_ranges(&cells, node.cells().address as usize, node.child_cells().size as usize)
+}
+
+fn first_region(node: &FdtNode<'_>) -> Option<(usize, usize)> {
+    node.regions().next().map(|(base, size)| (base as usize, size as usize))
+}
+
+fn first_irq(node: &FdtNode<'_>) -> Option<u32> {
+    node.interrupts().first().and_then(|spec| irq_number(spec))
 }
 
-unsafe fn strlen(ptr: *const u8) -> usize {
-    let mut len = 0;
-    while *ptr.add(len) != 0 {
-        len += 1;
+/// Collect the devices the kernel cares about from a parsed tree
+pub fn discover(fdt: &Fdt<'static>) -> PlatformInfo {
+    let mut info = PlatformInfo { model: fdt.root().and_then(|root| root.property("model")?.as_str()), ..PlatformInfo::default() };
+
+    for node in fdt.nodes() {
+        let is_memory = node.property("device_type").and_then(|prop| prop.as_str()) == Some("memory") || (node.depth() == 1 && node.unit_name() == "memory");
+        if is_memory && node.is_enabled() {
+            info.memory.extend(node.regions().filter(|(_, size)| *size != 0).map(|(base, size)| (base as usize, size as usize)));
+        }
+    }
+
+    info.reserved.extend(fdt.memory_reservations().map(|rsv| (rsv.address as usize, rsv.size as usize)));
+    if let Some(reserved) = fdt.find_node("/reserved-memory") {
+        for child in reserved.children() {
+            info.reserved.extend(child.regions().map(|(base, size)| (base as usize, size as usize)));
+        }
+    }
+
+    let console = fdt.stdout().map(|node| node.offset());
+    for (kind, compat) in [(UartKind::Ns16550, NS16550_COMPAT), (UartKind::Pl011, PL011_COMPAT)] {
+        for node in fdt.find_compatible(compat) {
+            let Some((base, size)) = first_region(&node) else { continue };
+            if console == Some(node.offset()) {
+                info.console = Some(info.uarts.len());
+            }
+            let clock_hz = node.property("clock-frequency").and_then(|prop| prop.as_u32());
+            info.uarts.push(UartInfo { kind, base, size, irq: first_irq(&node), clock_hz });
+        }
+    }
+
+    for (version, compat) in [(3u8, GICV3_COMPAT), (2u8, GICV2_COMPAT)] {
+        if info.gic.is_some() { break; }
+        if let Some(node) = fdt.find_compatible(compat).next() {
+            let mut regions = node.regions().filter(|(_, size)| *size != 0);
+            if let (Some((dist, _)), Some((second, _))) = (regions.next(), regions.next()) {
+                info.gic = Some(GicInfo { version, dist: dist as usize, cpu_or_redist: second as usize });
+            }
+        }
+    }
+
+    if let Some(node) = fdt.find_compatible(PLIC_COMPAT).next()
+        && let Some((base, size)) = first_region(&node)
+    {
+        let ndev = node.property("riscv,ndev").and_then(|prop| prop.as_u32()).unwrap_or(0);
+        info.plic = Some(PlicInfo { base, size, ndev });
+    }
+    if let Some(node) = fdt.find_compatible(CLINT_COMPAT).next()
+        && let Some((base, size)) = first_region(&node)
+    {
+        info.clint = Some(ClintInfo { base, size });
+    }
+
+    if let Some(cpus) = fdt.find_node("/cpus") {
+        info.timebase_hz = cpus.property("timebase-frequency").and_then(|prop| prop.as_u64().or_else(|| prop.as_u32().map(u64::from)));
+        for cpu in cpus.children() {
+            if cpu.property("device_type").and_then(|prop| prop.as_str()) != Some("cpu") || !cpu.is_enabled() {
+                continue;
+            }
+            if let Some((id, _)) = cpu.reg().next() {
+                let enable_method = cpu.property("enable-method").and_then(|prop| prop.as_str());
+                info.cpus.push(CpuInfo { id, enable_method });
+            }
+        }
+    }
+
+    if let Some(node) = fdt.find_compatible(ARCH_TIMER_COMPAT).next() {
+        // Secure, non-secure physical, virtual, hypervisor
+        info.arch_timer_irq = node.interrupts().get(2).and_then(|spec| irq_number(spec));
     }
-    len
+
+    for node in fdt.find_compatible(&["virtio,mmio"]) {
+        if let Some((base, size)) = first_region(&node) {
+            info.virtio_mmio.push(VirtioMmioInfo { base, size, irq: first_irq(&node) });
+        }
+    }
+
+    if let Some(node) = fdt.find_compatible(PCI_HOST_COMPAT).find(|node| node.is_enabled())
+        && let Some((ecam, ecam_size)) = first_region(&node)
+    {
+        let mut bus_range = node.property("bus-range").map(|prop| prop.cells()).into_iter().flatten();
+        let bus_start = bus_range.next().unwrap_or(0).min(255) as u8;
+        let bus_end = bus_range.next().unwrap_or(255).min(255) as u8;
+        let windows = pci_windows(&node)
+            .into_iter()
+            .filter_map(|window| Some(PciWindow { cpu: node.translate(window.cpu)?, ..window }))
+            .collect();
+        info.pci = Some(PciHostInfo { ecam, ecam_size, bus_start, bus_end, windows });
+    }
+
+    if let Some(node) = fdt.find_compatible(PSCI_COMPAT).next() {
+        let conduit = match node.property("method").and_then(|prop| prop.as_str()) {
+            Some("hvc") => Some(PsciConduit::Hvc),
+            Some("smc") => Some(PsciConduit::Smc),
+            _ => None,
+        };
+        let v0_2 = node.is_compatible("arm,psci-0.2") || node.is_compatible("arm,psci-1.0");
+        let id = |name, standard| node.property(name).and_then(|prop| prop.as_u32()).filter(|_| !v0_2).unwrap_or(standard);
+        if let Some(conduit) = conduit {
+            info.psci = Some(PsciInfo { conduit, cpu_on: id("cpu_on", PSCI_0_2_CPU_ON_64), cpu_off: id("cpu_off", PSCI_0_2_CPU_OFF), v0_2 });
+        }
+    }
+
+    info
 }
 
-#[inline]
-fn dtb_pointer() -> usize {
-    // In a real kernel, this would retrieve the DTB address passed by bootloader
-    // e.g. from a global variable set in start.S
-    // For now, return 0 to disable unless we have a mechanism
-    0
+/// Record a platform description and point the drivers with fixed QEMU
+/// `virt` defaults (console UART, CLINT, timebase, GIC) at what it describes
+pub fn apply(info: PlatformInfo) {
+    if let Some(gic) = info.gic {
+        match gic.version {
+            3 => *GICV3_BASES.lock() = Some((gic.dist, gic.cpu_or_redist)),
+            _ => *GICV2_BASES.lock() = Some((gic.dist, gic.cpu_or_redist)),
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    let console_kind = UartKind::Pl011;
+    #[cfg(not(target_arch = "aarch64"))]
+    let console_kind = UartKind::Ns16550;
+    let console = info.console.and_then(|idx| info.uarts.get(idx)).filter(|uart| uart.kind == console_kind);
+    if let Some(uart) = console.or_else(|| info.uarts.iter().find(|uart| uart.kind == console_kind)) {
+        super::uart::set_base(uart.base);
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    {
+        if let Some(clint) = info.clint {
+            crate::time::imp::set_clint_base(clint.base);
+        }
+        if let Some(hz) = info.timebase_hz {
+            crate::time::imp::set_timebase_hz(hz);
+        }
+    }
+
+    *PLATFORM_INFO.lock() = Some(info);
+}
+
+/// Record GICv3 redistributors named by `arm,mpidr`
+fn probe_gicr(fdt: &Fdt<'static>) {
+    let mut redists = GICR_REDISTS.lock();
+    redists.clear();
+    for node in fdt.nodes() {
+        if !node.compatible().any(|s| s.contains("redistributor") || s.contains("gicr")) {
+            continue;
+        }
+        let mpidr = node.property("arm,mpidr").and_then(|prop| prop.as_u64()).unwrap_or(0);
+        if let Some((addr, _)) = node.regions().filter(|(_, size)| *size != 0).last() {
+            redists.push((mpidr, addr as usize));
+        }
+    }
+}
+
+/// Discover the platform from the device tree and configure drivers
+pub fn init_from_fdt(fdt: &Fdt<'static>) {
+    let info = discover(fdt);
+    crate::println!(
+        "[dtb] {}: {} memory ranges, {} reserved, {} uarts, {} virtio-mmio, {} cpus",
+        info.model.unwrap_or("unknown machine"),
+        info.memory.len(),
+        info.reserved.len(),
+        info.uarts.len(),
+        info.virtio_mmio.len(),
+        info.cpus.len()
+    );
+    if let Some(gic) = info.gic {
+        crate::println!("[dtb] GICv{} dist={:#x} {:#x}", gic.version, gic.dist, gic.cpu_or_redist);
+    }
+    if let Some(psci) = info.psci {
+        crate::println!("[dtb] PSCI via {:?}{}", psci.conduit, if psci.v0_2 { "" } else { " (0.1)" });
+    }
+    probe_gicr(fdt);
+    apply(info);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_irq_number() {
+        // GIC SPI 1, level high
+        assert_eq!(irq_number(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 4]), Some(33));
+        // GIC PPI 11
+        assert_eq!(irq_number(&[0, 0, 0, 1, 0, 0, 0, 11, 0, 0, 0, 4]), Some(27));
+        // PLIC source 10
+        assert_eq!(irq_number(&[0, 0, 0, 10]), Some(10));
+    }
+
+    #[test]
+    fn test_pci_ranges() {
+        // QEMU virt: I/O, 32-bit memory and prefetchable 64-bit memory
+        let cells = [
+            0x0100_0000, 0, 0, 0, 0x3eff_0000, 0, 0x0001_0000,
+            0x0200_0000, 0, 0x1000_0000, 0, 0x1000_0000, 0, 0x2eff_0000,
+            0x4300_0000, 0x80, 0, 0x80, 0, 0x80, 0,
+        ];
+        let windows = pci_ranges(&cells, 2, 2);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0], PciWindow { pci: 0x1000_0000, cpu: 0x1000_0000, size: 0x2eff_0000, mem64: false, prefetchable: false });
+        assert_eq!(windows[1], PciWindow { pci: 0x80_0000_0000, cpu: 0x80_0000_0000, size: 0x80_0000_0000, mem64: true, prefetchable: true });
+    }
 }
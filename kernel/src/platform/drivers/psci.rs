@@ -0,0 +1,100 @@
+// ARM Power State Coordination Interface
+//
+// Firmware calls for bringing CPUs up and powering the machine off. The
+// device tree's `/psci` node says whether they go through HVC or SMC and,
+// for PSCI 0.1 firmware, which function ids to use.
+
+use super::platform::{psci_info, PsciConduit};
+
+const PSCI_VERSION: u32 = 0x8400_0000;
+const PSCI_SYSTEM_OFF: u32 = 0x8400_0008;
+const PSCI_SYSTEM_RESET: u32 = 0x8400_0009;
+
+/// PSCI return codes
+pub const PSCI_SUCCESS: i32 = 0;
+pub const PSCI_NOT_SUPPORTED: i32 = -1;
+pub const PSCI_INVALID_PARAMETERS: i32 = -2;
+pub const PSCI_DENIED: i32 = -3;
+pub const PSCI_ALREADY_ON: i32 = -4;
+
+#[cfg(target_arch = "aarch64")]
+fn call(conduit: PsciConduit, function: u32, arg0: u64, arg1: u64, arg2: u64) -> i64 {
+    let mut ret = function as u64;
+    unsafe {
+        match conduit {
+            PsciConduit::Hvc => core::arch::asm!(
+                "hvc #0",
+                inout("x0") ret,
+                inout("x1") arg0 => _,
+                inout("x2") arg1 => _,
+                inout("x3") arg2 => _,
+                options(nostack)
+            ),
+            PsciConduit::Smc => core::arch::asm!(
+                "smc #0",
+                inout("x0") ret,
+                inout("x1") arg0 => _,
+                inout("x2") arg1 => _,
+                inout("x3") arg2 => _,
+                options(nostack)
+            ),
+        }
+    }
+    ret as i64
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn call(_conduit: PsciConduit, _function: u32, _arg0: u64, _arg1: u64, _arg2: u64) -> i64 {
+    PSCI_NOT_SUPPORTED as i64
+}
+
+/// Whether the device tree described PSCI firmware
+pub fn available() -> bool {
+    psci_info().is_some()
+}
+
+/// (major, minor) version, 0.1 firmware having no VERSION call
+pub fn version() -> Option<(u16, u16)> {
+    let info = psci_info()?;
+    if !info.v0_2 {
+        return Some((0, 1));
+    }
+    let ver = call(info.conduit, PSCI_VERSION, 0, 0, 0);
+    if ver < 0 {
+        return None;
+    }
+    Some(((ver >> 16) as u16, ver as u16))
+}
+
+/// Start the CPU with affinity `target` at physical address `entry`, with
+/// `context` in its x0
+pub fn cpu_on(target: u64, entry: usize, context: u64) -> i32 {
+    match psci_info() {
+        Some(info) => call(info.conduit, info.cpu_on, target, entry as u64, context) as i32,
+        None => PSCI_NOT_SUPPORTED,
+    }
+}
+
+/// Power the calling CPU down; only returns on failure
+pub fn cpu_off() -> i32 {
+    match psci_info() {
+        Some(info) => call(info.conduit, info.cpu_off, 0, 0, 0) as i32,
+        None => PSCI_NOT_SUPPORTED,
+    }
+}
+
+/// Power the machine off; only returns on failure
+pub fn system_off() -> i32 {
+    match psci_info() {
+        Some(info) if info.v0_2 => call(info.conduit, PSCI_SYSTEM_OFF, 0, 0, 0) as i32,
+        _ => PSCI_NOT_SUPPORTED,
+    }
+}
+
+/// Reset the machine; only returns on failure
+pub fn system_reset() -> i32 {
+    match psci_info() {
+        Some(info) if info.v0_2 => call(info.conduit, PSCI_SYSTEM_RESET, 0, 0, 0) as i32,
+        _ => PSCI_NOT_SUPPORTED,
+    }
+}
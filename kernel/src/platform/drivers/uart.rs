@@ -7,8 +7,10 @@
 
 #[cfg(target_arch = "riscv64")]
 mod imp {
-    /// UART base address for QEMU virt machine
-    const UART_BASE: usize = 0x10000000;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// UART base address; QEMU virt machine until the device tree says otherwise
+    static UART_BASE: AtomicUsize = AtomicUsize::new(0x10000000);
 
     // Register offsets
     const RBR: usize = 0; // Receive Buffer Register (read)
@@ -24,7 +26,12 @@ mod imp {
 
     #[inline]
     fn reg(offset: usize) -> *mut u8 {
-        (UART_BASE + offset) as *mut u8
+        (UART_BASE.load(Ordering::Relaxed) + offset) as *mut u8
+    }
+
+    /// Move the console to the UART the device tree names
+    pub fn set_base(base: usize) {
+        UART_BASE.store(base, Ordering::Relaxed);
     }
 
     #[inline]
@@ -112,8 +119,10 @@ mod imp {
 
 #[cfg(target_arch = "aarch64")]
 mod imp {
-    /// PL011 UART base address for QEMU virt machine
-    const UART_BASE: usize = 0x09000000;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// PL011 UART base address; QEMU virt machine until the device tree says otherwise
+    static UART_BASE: AtomicUsize = AtomicUsize::new(0x09000000);
 
     // Register offsets (32-bit aligned)
     const DR: usize = 0x00;    // Data Register
@@ -130,7 +139,12 @@ mod imp {
 
     #[inline]
     fn reg(offset: usize) -> *mut u32 {
-        (UART_BASE + offset) as *mut u32
+        (UART_BASE.load(Ordering::Relaxed) + offset) as *mut u32
+    }
+
+    /// Move the console to the UART the device tree names
+    pub fn set_base(base: usize) {
+        UART_BASE.store(base, Ordering::Relaxed);
     }
 
     #[inline]
@@ -242,6 +256,9 @@ mod imp {
         val
     }
 
+    /// COM1 sits at a fixed port; there is no device tree to move it
+    pub fn set_base(_base: usize) {}
+
     /// Initialize UART
     pub fn init() {
         unsafe {
@@ -318,7 +335,7 @@ mod imp {
 // Public interface
 // ============================================================================
 
-pub use imp::{init, write_byte, write_str};
+pub use imp::{init, set_base, write_byte, write_str};
 
 /// Handle UART interrupt
 pub fn intr() {
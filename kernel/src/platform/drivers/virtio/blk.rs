@@ -0,0 +1,326 @@
+//! virtio-blk
+//!
+//! One request queue. Each call submits its chain and then polls the used
+//! ring until the device has finished it; whoever polls reaps every
+//! finished chain and records it for its owner, so concurrent callers each
+//! return with their own result. Data is transferred straight to and from
+//! the caller's buffer.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::platform::drivers::BlockDevice;
+use crate::subsystems::block::{BlockError, BlockFeatures, BlockResult};
+use crate::subsystems::sync::Mutex;
+
+use super::queue::{Buffer, Virtqueue};
+use super::{Transport, VirtioError, F_RING_PACKED};
+
+/// Feature bits
+const F_SIZE_MAX: u64 = 1 << 1;
+const F_SEG_MAX: u64 = 1 << 2;
+const F_RO: u64 = 1 << 5;
+const F_FLUSH: u64 = 1 << 9;
+const F_DISCARD: u64 = 1 << 13;
+
+/// Configuration space
+const CONFIG_CAPACITY: usize = 0;
+const CONFIG_SIZE_MAX: usize = 8;
+const CONFIG_SEG_MAX: usize = 12;
+const CONFIG_MAX_DISCARD_SECTORS: usize = 36;
+
+/// Request types
+const T_IN: u32 = 0;
+const T_OUT: u32 = 1;
+const T_FLUSH: u32 = 4;
+const T_DISCARD: u32 = 11;
+
+/// Status byte values
+const S_OK: u8 = 0;
+const S_UNSUPP: u8 = 2;
+
+const SECTOR_SIZE: usize = 512;
+const QUEUE_SIZE: u16 = 128;
+/// Largest request handed to the block layer, in sectors
+const MAX_SECTORS: u64 = 256;
+/// How long a request may take before the device is given up on
+const TIMEOUT_MS: u64 = 30_000;
+
+const SUPPORTED: u64 = F_SIZE_MAX | F_SEG_MAX | F_RO | F_FLUSH | F_DISCARD | F_RING_PACKED;
+
+/// `virtio_blk_discard_write_zeroes`
+#[repr(C)]
+#[derive(Default)]
+struct DiscardRange {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
+/// Header, discard range and status of one request, in memory the device
+/// reads and writes
+#[repr(C)]
+#[derive(Default)]
+struct BlkRequest {
+    kind: u32,
+    reserved: u32,
+    sector: u64,
+    range: DiscardRange,
+    status: u8,
+}
+
+struct BlkQueue {
+    vq: Virtqueue,
+    /// Sequence number of the call waiting on each token
+    owners: Vec<u64>,
+    next_seq: u64,
+    /// Calls whose chain the device has finished
+    done: BTreeSet<u64>,
+}
+
+impl BlkQueue {
+    fn reap(&mut self) {
+        while let Some((token, _)) = self.vq.pop_used() {
+            self.done.insert(self.owners[token as usize]);
+        }
+    }
+}
+
+pub struct VirtioBlk {
+    name: String,
+    transport: Box<dyn Transport>,
+    features: u64,
+    /// In 512-byte sectors
+    capacity: u64,
+    /// Bytes one descriptor may cover
+    size_max: u32,
+    /// Data descriptors per request
+    seg_max: u32,
+    max_discard_sectors: u32,
+    queue: Mutex<BlkQueue>,
+    /// Set once a request timed out; the device has been reset
+    broken: AtomicBool,
+}
+
+impl VirtioBlk {
+    pub fn new(name: &str, transport: Box<dyn Transport>) -> Result<Self, VirtioError> {
+        let features = super::negotiate(&*transport, SUPPORTED)?;
+        let has = |bit| features & bit != 0;
+        let capacity = transport.read_config64(CONFIG_CAPACITY);
+        let size_max = if has(F_SIZE_MAX) { transport.read_config32(CONFIG_SIZE_MAX) } else { 0 };
+        let seg_max = if has(F_SEG_MAX) { transport.read_config32(CONFIG_SEG_MAX) } else { 0 };
+        let max_discard_sectors = if has(F_DISCARD) { transport.read_config32(CONFIG_MAX_DISCARD_SECTORS) } else { 0 };
+
+        let vq = match Virtqueue::new(&*transport, 0, QUEUE_SIZE, features) {
+            Ok(vq) => vq,
+            Err(err) => {
+                super::fail(&*transport);
+                return Err(err);
+            }
+        };
+        // Header and status take a descriptor each
+        let seg_max = seg_max.clamp(1, vq.size() as u32 - 2);
+        let owners = vec![0; vq.size() as usize];
+        super::driver_ok(&*transport);
+
+        Ok(Self {
+            name: name.to_string(),
+            transport,
+            features,
+            capacity,
+            size_max: if size_max == 0 { u32::MAX } else { size_max },
+            seg_max,
+            max_discard_sectors,
+            queue: Mutex::new(BlkQueue { vq, owners, next_seq: 0, done: BTreeSet::new() }),
+            broken: AtomicBool::new(false),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn irq(&self) -> Option<u32> {
+        self.transport.irq()
+    }
+
+    /// Acknowledge an interrupt and reap finished requests
+    pub fn handle_interrupt(&self) -> bool {
+        if self.transport.ack_interrupt() == 0 {
+            return false;
+        }
+        self.queue.lock().reap();
+        true
+    }
+
+    fn has(&self, bit: u64) -> bool {
+        self.features & bit != 0
+    }
+
+    /// Largest data transfer one request can carry
+    fn max_bytes(&self) -> u64 {
+        (self.size_max as u64 * self.seg_max as u64).min(MAX_SECTORS * SECTOR_SIZE as u64)
+    }
+
+    fn check_range(&self, lba: usize, sectors: usize) -> BlockResult<()> {
+        match (lba as u64).checked_add(sectors as u64) {
+            Some(end) if end <= self.capacity => Ok(()),
+            _ => Err(BlockError::OutOfRange),
+        }
+    }
+
+    /// Submit `req` with `data` (if any) and wait for the device
+    fn execute(&self, req: &mut BlkRequest, data: Option<Buffer>, range: bool) -> BlockResult<()> {
+        if self.broken.load(Ordering::Acquire) {
+            return Err(BlockError::IoError);
+        }
+        let mut bufs = Vec::with_capacity(self.seg_max as usize + 2);
+        bufs.push(Buffer { addr: super::dma_addr(req), len: 16, writable: false });
+        if range {
+            bufs.push(Buffer::readable(&req.range));
+        }
+        if let Some(data) = data {
+            // The buffer is physically contiguous; split it only where the
+            // device limits a descriptor's length
+            let mut offset = 0;
+            while offset < data.len {
+                let len = (data.len - offset).min(self.size_max);
+                bufs.push(Buffer { addr: data.addr + offset as u64, len, ..data });
+                offset += len;
+            }
+        }
+        bufs.push(Buffer::writable(&mut req.status));
+
+        let start = crate::time::uptime_ms();
+        let timed_out = || crate::time::uptime_ms().saturating_sub(start) > TIMEOUT_MS;
+        let seq = loop {
+            let mut queue = self.queue.lock();
+            match queue.vq.add(&bufs) {
+                Ok(token) => {
+                    let seq = queue.next_seq;
+                    queue.next_seq += 1;
+                    queue.owners[token as usize] = seq;
+                    queue.vq.kick(&*self.transport);
+                    break seq;
+                }
+                Err(VirtioError::QueueFull) => queue.reap(),
+                Err(_) => return Err(BlockError::IoError),
+            }
+            drop(queue);
+            if timed_out() {
+                return Err(BlockError::Timeout);
+            }
+            core::hint::spin_loop();
+        };
+
+        loop {
+            {
+                let mut queue = self.queue.lock();
+                queue.reap();
+                if queue.done.remove(&seq) {
+                    break;
+                }
+            }
+            if self.broken.load(Ordering::Acquire) {
+                return Err(BlockError::IoError);
+            }
+            if timed_out() {
+                // Resetting makes the device let go of every buffer, this
+                // one included, before it is freed
+                if !self.broken.swap(true, Ordering::AcqRel) {
+                    crate::println!("virtio-blk: {}: request timed out, disabling device", self.name);
+                    self.transport.reset();
+                }
+                return Err(BlockError::Timeout);
+            }
+            core::hint::spin_loop();
+        }
+
+        match unsafe { core::ptr::read_volatile(&req.status) } {
+            S_OK => Ok(()),
+            S_UNSUPP => Err(BlockError::NotSupported),
+            _ => Err(BlockError::IoError),
+        }
+    }
+
+    fn transfer(&self, kind: u32, lba: usize, data: Buffer) -> BlockResult<()> {
+        let len = data.len as usize;
+        if len == 0 || !len.is_multiple_of(SECTOR_SIZE) {
+            return Err(BlockError::Misaligned);
+        }
+        self.check_range(lba, len / SECTOR_SIZE)?;
+        // Callers may hand over more than one request's worth
+        let max = (self.max_bytes() as u32 & !(SECTOR_SIZE as u32 - 1)).max(SECTOR_SIZE as u32);
+        let mut offset = 0;
+        while offset < data.len {
+            let chunk = (data.len - offset).min(max);
+            let mut req = BlkRequest { kind, sector: (lba + offset as usize / SECTOR_SIZE) as u64, ..BlkRequest::default() };
+            self.execute(&mut req, Some(Buffer { addr: data.addr + offset as u64, len: chunk, ..data }), false)?;
+            offset += chunk;
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    fn read(&self, lba: usize, buf: &mut [u8]) -> BlockResult<()> {
+        self.transfer(T_IN, lba, Buffer::writable(buf))
+    }
+
+    fn write(&self, lba: usize, buf: &[u8]) -> BlockResult<()> {
+        if self.has(F_RO) {
+            return Err(BlockError::ReadOnly);
+        }
+        self.transfer(T_OUT, lba, Buffer::readable(buf))
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.capacity as usize
+    }
+
+    /// Without F_FLUSH the device has no volatile cache
+    fn flush(&self) -> BlockResult<()> {
+        if !self.has(F_FLUSH) {
+            return Ok(());
+        }
+        self.execute(&mut BlkRequest { kind: T_FLUSH, ..BlkRequest::default() }, None, false)
+    }
+
+    fn discard(&self, lba: usize, count: usize) -> BlockResult<()> {
+        if !self.has(F_DISCARD) {
+            return Err(BlockError::NotSupported);
+        }
+        if self.has(F_RO) {
+            return Err(BlockError::ReadOnly);
+        }
+        self.check_range(lba, count)?;
+        let max = (self.max_discard_sectors as usize).max(1);
+        let mut done = 0;
+        while done < count {
+            let chunk = (count - done).min(max);
+            let mut req = BlkRequest { kind: T_DISCARD, range: DiscardRange { sector: (lba + done) as u64, num_sectors: chunk as u32, flags: 0 }, ..BlkRequest::default() };
+            self.execute(&mut req, None, true)?;
+            done += chunk;
+        }
+        Ok(())
+    }
+
+    fn features(&self) -> BlockFeatures {
+        BlockFeatures {
+            write_cache: self.has(F_FLUSH),
+            fua: false,
+            discard: self.has(F_DISCARD),
+            max_sectors: (self.max_bytes() / SECTOR_SIZE as u64).max(1) as u32,
+        }
+    }
+}
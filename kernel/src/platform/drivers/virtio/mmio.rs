@@ -0,0 +1,162 @@
+//! virtio-mmio transport
+//!
+//! Both register layouts are handled: version 2 (virtio 1.x) and the
+//! legacy version 1 that QEMU still defaults to, which takes a queue as one
+//! page frame number and has no FEATURES_OK step.
+
+use crate::subsystems::mm::{mmio_read8, mmio_read16, mmio_read32, mmio_write32};
+
+use super::{Transport, VirtioError};
+
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c;
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+const CONFIG_GENERATION: usize = 0x0fc;
+const CONFIG: usize = 0x100;
+
+/// "virt"
+const MAGIC: u32 = 0x7472_6976;
+const LEGACY_PAGE_SIZE: u32 = 4096;
+
+pub struct MmioTransport {
+    base: usize,
+    version: u32,
+    irq: Option<u32>,
+}
+
+impl MmioTransport {
+    /// Check the register window at `base`; empty slots (device id 0) are
+    /// still returned so the caller can skip them
+    pub fn new(base: usize, irq: Option<u32>) -> Result<Self, VirtioError> {
+        let transport = Self { base, version: 0, irq };
+        if transport.read(MAGIC_VALUE) != MAGIC {
+            return Err(VirtioError::NotPresent);
+        }
+        let version = transport.read(VERSION);
+        if version != 1 && version != 2 {
+            return Err(VirtioError::Unsupported);
+        }
+        Ok(Self { version, ..transport })
+    }
+
+    fn read(&self, reg: usize) -> u32 {
+        mmio_read32((self.base + reg) as *const u32)
+    }
+
+    fn write(&self, reg: usize, value: u32) {
+        mmio_write32((self.base + reg) as *mut u32, value)
+    }
+}
+
+impl Transport for MmioTransport {
+    fn device_type(&self) -> u32 {
+        self.read(DEVICE_ID)
+    }
+
+    fn legacy(&self) -> bool {
+        self.version == 1
+    }
+
+    fn device_features(&self) -> u64 {
+        self.write(DEVICE_FEATURES_SEL, 0);
+        let low = self.read(DEVICE_FEATURES) as u64;
+        self.write(DEVICE_FEATURES_SEL, 1);
+        let high = self.read(DEVICE_FEATURES) as u64;
+        high << 32 | low
+    }
+
+    fn set_driver_features(&self, features: u64) {
+        self.write(DRIVER_FEATURES_SEL, 0);
+        self.write(DRIVER_FEATURES, features as u32);
+        self.write(DRIVER_FEATURES_SEL, 1);
+        self.write(DRIVER_FEATURES, (features >> 32) as u32);
+    }
+
+    fn status(&self) -> u8 {
+        self.read(STATUS) as u8
+    }
+
+    fn set_status(&self, status: u8) {
+        self.write(STATUS, status as u32);
+    }
+
+    fn max_queue_size(&self, queue: u16) -> u16 {
+        self.write(QUEUE_SEL, queue as u32);
+        let in_use = if self.legacy() { self.read(QUEUE_PFN) != 0 } else { self.read(QUEUE_READY) != 0 };
+        if in_use {
+            return 0;
+        }
+        self.read(QUEUE_NUM_MAX).min(u16::MAX as u32) as u16
+    }
+
+    fn setup_queue(&self, queue: u16, size: u16, desc: u64, driver: u64, device: u64) {
+        self.write(QUEUE_SEL, queue as u32);
+        self.write(QUEUE_NUM, size as u32);
+        if self.legacy() {
+            self.write(GUEST_PAGE_SIZE, LEGACY_PAGE_SIZE);
+            self.write(QUEUE_ALIGN, LEGACY_PAGE_SIZE);
+            self.write(QUEUE_PFN, (desc / LEGACY_PAGE_SIZE as u64) as u32);
+            return;
+        }
+        self.write(QUEUE_DESC_LOW, desc as u32);
+        self.write(QUEUE_DESC_HIGH, (desc >> 32) as u32);
+        self.write(QUEUE_DRIVER_LOW, driver as u32);
+        self.write(QUEUE_DRIVER_HIGH, (driver >> 32) as u32);
+        self.write(QUEUE_DEVICE_LOW, device as u32);
+        self.write(QUEUE_DEVICE_HIGH, (device >> 32) as u32);
+        self.write(QUEUE_READY, 1);
+    }
+
+    fn notify(&self, queue: u16) {
+        self.write(QUEUE_NOTIFY, queue as u32);
+    }
+
+    fn ack_interrupt(&self) -> u32 {
+        let status = self.read(INTERRUPT_STATUS);
+        if status != 0 {
+            self.write(INTERRUPT_ACK, status);
+        }
+        status
+    }
+
+    fn config_generation(&self) -> u32 {
+        if self.legacy() { 0 } else { self.read(CONFIG_GENERATION) }
+    }
+
+    fn read_config8(&self, offset: usize) -> u8 {
+        mmio_read8((self.base + CONFIG + offset) as *const u8)
+    }
+
+    fn read_config16(&self, offset: usize) -> u16 {
+        mmio_read16((self.base + CONFIG + offset) as *const u16)
+    }
+
+    fn read_config32(&self, offset: usize) -> u32 {
+        mmio_read32((self.base + CONFIG + offset) as *const u32)
+    }
+
+    fn irq(&self) -> Option<u32> {
+        self.irq
+    }
+}
@@ -0,0 +1,284 @@
+//! Virtio devices
+//!
+//! [`queue`] holds the split and packed virtqueues, [`mmio`] and [`pci`] the
+//! two transports, and [`blk`] and [`net`] the drivers. [`init`] probes the
+//! device tree's virtio-mmio slots and the PCI bus; the block layer and the
+//! network stack pick devices up through [`block_devices`] and
+//! [`net_devices`].
+//!
+//! External interrupts are not routed to drivers yet, so completions are
+//! reaped by polling: a block request waits on the used ring for itself and
+//! the network stack's poll thread drains receive. [`handle_irq`] does the
+//! same reaping and is what an interrupt controller driver will call.
+//!
+//! The kernel runs identity-mapped without an IOMMU, so the address of
+//! kernel memory is what the device is given. Devices are never unplugged
+//! and their ring memory is never returned.
+
+extern crate alloc;
+
+pub mod blk;
+pub mod mmio;
+pub mod net;
+pub mod pci;
+pub mod queue;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::net::device::NetworkDevice;
+use crate::subsystems::sync::Mutex;
+
+use blk::VirtioBlk;
+use net::VirtioNet;
+
+/// Device status bits
+pub const STATUS_ACKNOWLEDGE: u8 = 1;
+pub const STATUS_DRIVER: u8 = 2;
+pub const STATUS_DRIVER_OK: u8 = 4;
+pub const STATUS_FEATURES_OK: u8 = 8;
+pub const STATUS_NEEDS_RESET: u8 = 64;
+pub const STATUS_FAILED: u8 = 128;
+
+/// Device-independent feature bits
+pub const F_INDIRECT_DESC: u64 = 1 << 28;
+pub const F_EVENT_IDX: u64 = 1 << 29;
+pub const F_VERSION_1: u64 = 1 << 32;
+pub const F_ACCESS_PLATFORM: u64 = 1 << 33;
+pub const F_RING_PACKED: u64 = 1 << 34;
+
+/// Device types
+pub const DEVICE_NET: u32 = 1;
+pub const DEVICE_BLOCK: u32 = 2;
+
+/// Interrupt status bits
+pub const ISR_QUEUE: u32 = 1 << 0;
+pub const ISR_CONFIG: u32 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtioError {
+    /// No device behind the transport
+    NotPresent,
+    /// The device rejected or lacks features the driver needs
+    Unsupported,
+    /// A queue the driver needs does not exist or is already in use
+    NoQueue,
+    NoMemory,
+    /// Not enough free descriptors
+    QueueFull,
+    InvalidArgument,
+    /// The device set NEEDS_RESET or stopped answering
+    DeviceFailed,
+}
+
+/// How a driver reaches its device
+///
+/// Configuration space offsets are relative to the device-specific area.
+pub trait Transport: Send + Sync {
+    /// Device type, 0 for an empty slot
+    fn device_type(&self) -> u32;
+
+    /// Pre-1.0 interface: 32 feature bits, queues set up by page frame
+    /// number and no FEATURES_OK handshake
+    fn legacy(&self) -> bool {
+        false
+    }
+
+    fn device_features(&self) -> u64;
+
+    fn set_driver_features(&self, features: u64);
+
+    fn status(&self) -> u8;
+
+    fn set_status(&self, status: u8);
+
+    /// Largest size queue `queue` takes, 0 if it does not exist
+    fn max_queue_size(&self, queue: u16) -> u16;
+
+    /// Hand a queue's rings to the device and enable it
+    ///
+    /// For legacy devices `desc` is the page-aligned start of the split
+    /// ring laid out with 4 KiB alignment.
+    fn setup_queue(&self, queue: u16, size: u16, desc: u64, driver: u64, device: u64);
+
+    fn notify(&self, queue: u16);
+
+    /// Read and acknowledge the interrupt status ([`ISR_QUEUE`], [`ISR_CONFIG`])
+    fn ack_interrupt(&self) -> u32;
+
+    /// Changes whenever the device changes its configuration space
+    fn config_generation(&self) -> u32 {
+        0
+    }
+
+    fn read_config8(&self, offset: usize) -> u8;
+
+    fn read_config16(&self, offset: usize) -> u16;
+
+    fn read_config32(&self, offset: usize) -> u32;
+
+    /// Interrupt line, when one is known
+    fn irq(&self) -> Option<u32> {
+        None
+    }
+
+    /// Stop the device; it lets go of every buffer it was given
+    fn reset(&self) {
+        self.set_status(0);
+        while self.status() != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// A 64-bit configuration field, read until the generation is stable
+    fn read_config64(&self, offset: usize) -> u64 {
+        loop {
+            let generation = self.config_generation();
+            let low = self.read_config32(offset) as u64;
+            let high = self.read_config32(offset + 4) as u64;
+            if self.config_generation() == generation {
+                return high << 32 | low;
+            }
+        }
+    }
+}
+
+/// Bus address of kernel memory
+pub(crate) fn dma_addr<T: ?Sized>(ptr: *const T) -> u64 {
+    ptr as *const u8 as usize as u64
+}
+
+/// Reset the device and agree on features
+///
+/// Offers the device's features masked by `supported`; modern devices must
+/// offer VERSION_1 and accept the result. The caller sets up its queues and
+/// then calls [`driver_ok`].
+pub fn negotiate(transport: &dyn Transport, supported: u64) -> Result<u64, VirtioError> {
+    transport.reset();
+    transport.set_status(STATUS_ACKNOWLEDGE);
+    transport.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+    let offered = transport.device_features();
+    if transport.legacy() {
+        let features = offered & supported & 0xffff_ffff;
+        transport.set_driver_features(features);
+        return Ok(features);
+    }
+
+    // Without VERSION_1 the device expects the legacy interface.
+    // ACCESS_PLATFORM is fine to accept: with no IOMMU, platform DMA
+    // addresses are physical ones
+    if offered & F_VERSION_1 == 0 {
+        transport.set_status(STATUS_FAILED);
+        return Err(VirtioError::Unsupported);
+    }
+    let features = offered & (supported | F_VERSION_1 | F_ACCESS_PLATFORM);
+    transport.set_driver_features(features);
+    let status = STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK;
+    transport.set_status(status);
+    if transport.status() & STATUS_FEATURES_OK == 0 {
+        transport.set_status(status | STATUS_FAILED);
+        return Err(VirtioError::Unsupported);
+    }
+    Ok(features)
+}
+
+/// Tell the device the driver is ready; queues may be notified from now on
+pub fn driver_ok(transport: &dyn Transport) {
+    let status = if transport.legacy() {
+        STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_DRIVER_OK
+    } else {
+        STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK | STATUS_DRIVER_OK
+    };
+    transport.set_status(status);
+}
+
+/// Give up on a device mid-setup
+pub fn fail(transport: &dyn Transport) {
+    transport.set_status(transport.status() | STATUS_FAILED);
+}
+
+static BLOCK_DEVICES: Mutex<Vec<Arc<VirtioBlk>>> = Mutex::new(Vec::new());
+static NET_DEVICES: Mutex<Vec<Arc<VirtioNet>>> = Mutex::new(Vec::new());
+
+/// Block devices found by [`init`], named `vda`, `vdb`, ...
+pub fn block_devices() -> Vec<Arc<VirtioBlk>> {
+    BLOCK_DEVICES.lock().clone()
+}
+
+/// Network devices found by [`init`], named `eth0`, `eth1`, ...
+pub fn net_devices() -> Vec<Arc<VirtioNet>> {
+    NET_DEVICES.lock().clone()
+}
+
+/// Start the driver for whatever sits behind `transport`
+fn attach(transport: Box<dyn Transport>, location: &str) {
+    let device_type = transport.device_type();
+    match device_type {
+        0 => {}
+        DEVICE_BLOCK => {
+            let mut devices = BLOCK_DEVICES.lock();
+            let name = format!("vd{}", (b'a' + devices.len() as u8) as char);
+            match VirtioBlk::new(&name, transport) {
+                Ok(dev) => {
+                    crate::println!("virtio: {}: {} block device, {} sectors", location, name, dev.capacity());
+                    devices.push(Arc::new(dev));
+                }
+                Err(err) => crate::println!("virtio: {}: block device failed: {:?}", location, err),
+            }
+        }
+        DEVICE_NET => {
+            let mut devices = NET_DEVICES.lock();
+            let name = format!("eth{}", devices.len());
+            match VirtioNet::new(&name, transport) {
+                Ok(dev) => {
+                    crate::println!("virtio: {}: {} network device, {}", location, name, dev.mac_address());
+                    devices.push(Arc::new(dev));
+                }
+                Err(err) => crate::println!("virtio: {}: network device failed: {:?}", location, err),
+            }
+        }
+        other => crate::println!("virtio: {}: no driver for device type {}", location, other),
+    }
+}
+
+/// Probe virtio-mmio slots and virtio PCI functions
+///
+/// Runs after [`super::pci::init`] has assigned BARs.
+pub fn init() {
+    for info in super::platform::virtio_mmio_devices() {
+        match mmio::MmioTransport::new(info.base, info.irq) {
+            Ok(transport) => attach(Box::new(transport), &format!("mmio@{:#x}", info.base)),
+            Err(VirtioError::NotPresent) => {}
+            Err(err) => crate::println!("virtio: mmio@{:#x}: {:?}", info.base, err),
+        }
+    }
+    for func in super::pci::functions() {
+        if func.vendor_id != pci::VENDOR_ID {
+            continue;
+        }
+        let location = format!("pci {:02x}:{:02x}.{}", func.bus, func.device, func.function);
+        match pci::PciTransport::new(func) {
+            Ok(transport) => attach(Box::new(transport), &location),
+            Err(err) => crate::println!("virtio: {}: {:?}", location, err),
+        }
+    }
+}
+
+/// Interrupt handler for line `irq`; true if a virtio device raised it
+pub fn handle_irq(irq: u32) -> bool {
+    let mut handled = false;
+    for dev in block_devices() {
+        if dev.irq() == Some(irq) {
+            handled |= dev.handle_interrupt();
+        }
+    }
+    for dev in net_devices() {
+        if dev.irq() == Some(irq) {
+            handled |= dev.handle_interrupt();
+        }
+    }
+    handled
+}
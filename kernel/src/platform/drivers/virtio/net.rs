@@ -0,0 +1,286 @@
+//! virtio-net
+//!
+//! One receive and one transmit queue, each with a fixed pool of
+//! 2 KiB buffers. Receive buffers are all posted up front and reposted as
+//! the network stack's poll drains them; transmit copies each frame into a
+//! free buffer and reclaims buffers the device has sent on the next send.
+//! No offloads are negotiated, so every header is zero.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::net::device::{DeviceCapabilities, DeviceConfig, DeviceError, DeviceStats, MacAddr, NetworkDevice, NetworkDeviceType};
+use crate::subsystems::sync::Mutex;
+
+use super::queue::{Buffer, Virtqueue};
+use super::{Transport, VirtioError, F_RING_PACKED, F_VERSION_1};
+
+/// Feature bits
+const F_MAC: u64 = 1 << 5;
+const F_MRG_RXBUF: u64 = 1 << 15;
+const F_STATUS: u64 = 1 << 16;
+
+/// Configuration space
+const CONFIG_MAC: usize = 0;
+const CONFIG_STATUS: usize = 6;
+
+const STATUS_LINK_UP: u16 = 1;
+
+const RX_QUEUE: u16 = 0;
+const TX_QUEUE: u16 = 1;
+const QUEUE_SIZE: u16 = 128;
+const BUF_SIZE: usize = 2048;
+const ETH_HLEN: usize = 14;
+/// Without F_MTU (which would need buffers for the device's MTU) frames
+/// are plain Ethernet-sized
+const MAX_MTU: usize = 1500;
+const MIN_MTU: usize = 68;
+
+const SUPPORTED: u64 = F_MAC | F_STATUS | F_RING_PACKED;
+
+/// A virtqueue and the buffers that go with it
+struct NetQueue {
+    vq: Virtqueue,
+    /// `vq.size()` buffers of `BUF_SIZE` bytes
+    pool: *mut u8,
+    /// Buffers not given to the device
+    free: Vec<u16>,
+    /// Buffer behind each outstanding token
+    by_token: Vec<u16>,
+}
+
+// The pool is only touched under the queue's lock or by the device
+unsafe impl Send for NetQueue {}
+
+impl NetQueue {
+    fn new(transport: &dyn Transport, index: u16, features: u64) -> Result<Self, VirtioError> {
+        let vq = Virtqueue::new(transport, index, QUEUE_SIZE, features)?;
+        let count = vq.size() as usize;
+        let pool = crate::subsystems::mm::kalloc_pages((count * BUF_SIZE).div_ceil(4096));
+        if pool.is_null() {
+            return Err(VirtioError::NoMemory);
+        }
+        Ok(Self { vq, pool, free: (0..count as u16).rev().collect(), by_token: vec![0; count] })
+    }
+
+    fn buffer(&self, index: u16) -> *mut u8 {
+        self.pool.wrapping_add(index as usize * BUF_SIZE)
+    }
+
+    /// Hand buffer `index` to the device, `len` bytes of it
+    fn post(&mut self, index: u16, len: usize, writable: bool) -> Result<(), VirtioError> {
+        let buf = Buffer { addr: super::dma_addr(self.buffer(index)), len: len as u32, writable };
+        let token = self.vq.add(&[buf])?;
+        self.by_token[token as usize] = index;
+        Ok(())
+    }
+}
+
+pub struct VirtioNet {
+    name: String,
+    transport: Box<dyn Transport>,
+    features: u64,
+    mac: MacAddr,
+    mtu: usize,
+    /// Size of `virtio_net_hdr` in front of every frame
+    hdr_len: usize,
+    is_up: AtomicBool,
+    rx: Mutex<NetQueue>,
+    tx: Mutex<NetQueue>,
+    stats: DeviceStats,
+}
+
+impl VirtioNet {
+    pub fn new(name: &str, transport: Box<dyn Transport>) -> Result<Self, VirtioError> {
+        let features = super::negotiate(&*transport, SUPPORTED)?;
+        let queues = NetQueue::new(&*transport, RX_QUEUE, features).and_then(|rx| Ok((rx, NetQueue::new(&*transport, TX_QUEUE, features)?)));
+        let (mut rx, tx) = match queues {
+            Ok(queues) => queues,
+            Err(err) => {
+                super::fail(&*transport);
+                return Err(err);
+            }
+        };
+
+        let mac = if features & F_MAC != 0 {
+            MacAddr::new(core::array::from_fn(|i| transport.read_config8(CONFIG_MAC + i)))
+        } else {
+            // Locally administered, in QEMU's range
+            MacAddr::new([0x52, 0x54, 0x00, 0x12, 0x34, 0x56])
+        };
+        let hdr_len = if features & (F_VERSION_1 | F_MRG_RXBUF) != 0 { 12 } else { 10 };
+
+        // Buffers may be posted before DRIVER_OK but the device is only
+        // notified after
+        while let Some(index) = rx.free.pop() {
+            rx.post(index, BUF_SIZE, true)?;
+        }
+        super::driver_ok(&*transport);
+        rx.vq.kick(&*transport);
+
+        Ok(Self {
+            name: name.to_string(),
+            transport,
+            features,
+            mac,
+            mtu: MAX_MTU,
+            hdr_len,
+            is_up: AtomicBool::new(false),
+            rx: Mutex::new(rx),
+            tx: Mutex::new(tx),
+            stats: DeviceStats::new(),
+        })
+    }
+
+    pub fn irq(&self) -> Option<u32> {
+        self.transport.irq()
+    }
+
+    /// Acknowledge an interrupt and reclaim sent buffers; received frames
+    /// wait for the network stack's poll
+    pub fn handle_interrupt(&self) -> bool {
+        if self.transport.ack_interrupt() == 0 {
+            return false;
+        }
+        Self::reclaim(&mut self.tx.lock());
+        true
+    }
+
+    /// Whether the device reports a carrier (always, without F_STATUS)
+    pub fn link_up(&self) -> bool {
+        self.features & F_STATUS == 0 || self.transport.read_config16(CONFIG_STATUS) & STATUS_LINK_UP != 0
+    }
+
+    fn reclaim(tx: &mut NetQueue) {
+        while let Some((token, _)) = tx.vq.pop_used() {
+            let index = tx.by_token[token as usize];
+            tx.free.push(index);
+        }
+    }
+}
+
+impl NetworkDevice for VirtioNet {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn device_type(&self) -> NetworkDeviceType {
+        NetworkDeviceType::Ethernet
+    }
+
+    fn mac_address(&self) -> MacAddr {
+        self.mac
+    }
+
+    fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    fn is_up(&self) -> bool {
+        self.is_up.load(Ordering::Acquire)
+    }
+
+    fn up(&self) -> Result<(), DeviceError> {
+        self.is_up.store(true, Ordering::Release);
+        crate::log_info!("virtio-net '{}' is up, link {}", self.name.clone(), if self.link_up() { "up" } else { "down" });
+        Ok(())
+    }
+
+    fn down(&self) -> Result<(), DeviceError> {
+        self.is_up.store(false, Ordering::Release);
+        crate::log_info!("virtio-net '{}' is down", self.name.clone());
+        Ok(())
+    }
+
+    fn send_packet(&self, packet: &[u8]) -> Result<(), DeviceError> {
+        if !self.is_up() {
+            return Err(DeviceError::DeviceDown);
+        }
+        if packet.len() > self.mtu + ETH_HLEN {
+            return Err(DeviceError::BufferTooSmall);
+        }
+
+        let mut tx = self.tx.lock();
+        Self::reclaim(&mut tx);
+        let Some(index) = tx.free.pop() else {
+            self.stats.inc_tx_dropped(1);
+            return Err(DeviceError::NoMemory);
+        };
+        let len = self.hdr_len + packet.len();
+        let buf = unsafe { core::slice::from_raw_parts_mut(tx.buffer(index), len) };
+        buf[..self.hdr_len].fill(0);
+        buf[self.hdr_len..].copy_from_slice(packet);
+        if tx.post(index, len, false).is_err() {
+            tx.free.push(index);
+            self.stats.inc_tx_errors(1);
+            return Err(DeviceError::HardwareError);
+        }
+        tx.vq.kick(&*self.transport);
+
+        self.stats.inc_tx_packets(1);
+        self.stats.inc_tx_bytes(packet.len() as u64);
+        Ok(())
+    }
+
+    fn receive_packet(&self) -> Result<Option<Vec<u8>>, DeviceError> {
+        if !self.is_up() {
+            return Err(DeviceError::DeviceDown);
+        }
+
+        let mut rx = self.rx.lock();
+        while let Some((token, len)) = rx.vq.pop_used() {
+            let index = rx.by_token[token as usize];
+            let len = (len as usize).min(BUF_SIZE);
+            let frame = (len > self.hdr_len).then(|| unsafe { core::slice::from_raw_parts(rx.buffer(index).add(self.hdr_len), len - self.hdr_len) }.to_vec());
+            // The buffer goes straight back; a slot just came free for it
+            let _ = rx.post(index, BUF_SIZE, true);
+            rx.vq.kick(&*self.transport);
+            match frame {
+                Some(frame) => {
+                    self.stats.inc_rx_packets(1);
+                    self.stats.inc_rx_bytes(frame.len() as u64);
+                    return Ok(Some(frame));
+                }
+                None => self.stats.inc_rx_errors(1),
+            }
+        }
+        Ok(None)
+    }
+
+    fn stats(&self) -> DeviceStats {
+        self.stats.clone()
+    }
+
+    fn configure(&mut self, config: &DeviceConfig) -> Result<(), DeviceError> {
+        if let Some(mtu) = config.mtu {
+            if !(MIN_MTU..=MAX_MTU).contains(&mtu) {
+                return Err(DeviceError::InvalidConfig);
+            }
+            self.mtu = mtu;
+        }
+        // Changing the address needs the control queue
+        if config.mac_address.is_some_and(|mac| mac != self.mac) {
+            return Err(DeviceError::NotSupported);
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities {
+            max_mtu: MAX_MTU,
+            min_mtu: MIN_MTU,
+            checksum_offload: false,
+            scatter_gather: false,
+            tso: false,
+            lro: false,
+            multicast: true,
+            broadcast: true,
+            promiscuous: true,
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! virtio-pci transport (modern interface)
+//!
+//! The device's structures are found through vendor-specific capabilities
+//! pointing into its BARs. Transitional devices are driven through the same
+//! capabilities; the legacy I/O BAR is not used.
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+
+use crate::subsystems::mm::{mmio_read8, mmio_read16, mmio_read32, mmio_write8, mmio_write16, mmio_write32};
+use crate::subsystems::sync::Mutex;
+
+use super::super::pci::{PciFunction, PCI_CAP_ID_VNDR, PCI_SUBSYSTEM_ID};
+use super::{Transport, VirtioError};
+
+pub const VENDOR_ID: u16 = 0x1af4;
+
+/// Capability structure types
+const CAP_COMMON_CFG: u8 = 1;
+const CAP_NOTIFY_CFG: u8 = 2;
+const CAP_ISR_CFG: u8 = 3;
+const CAP_DEVICE_CFG: u8 = 4;
+
+/// Common configuration layout
+const DEVICE_FEATURE_SELECT: usize = 0x00;
+const DEVICE_FEATURE: usize = 0x04;
+const DRIVER_FEATURE_SELECT: usize = 0x08;
+const DRIVER_FEATURE: usize = 0x0c;
+const DEVICE_STATUS: usize = 0x14;
+const CONFIG_GENERATION: usize = 0x15;
+const QUEUE_SELECT: usize = 0x16;
+const QUEUE_SIZE: usize = 0x18;
+const QUEUE_ENABLE: usize = 0x1c;
+const QUEUE_NOTIFY_OFF: usize = 0x1e;
+const QUEUE_DESC: usize = 0x20;
+const QUEUE_DRIVER: usize = 0x28;
+const QUEUE_DEVICE: usize = 0x30;
+
+pub struct PciTransport {
+    device_type: u32,
+    common: usize,
+    notify: usize,
+    notify_multiplier: u32,
+    isr: usize,
+    device: usize,
+    /// Notification address of each queue that was set up
+    notify_addrs: Mutex<BTreeMap<u16, usize>>,
+}
+
+impl PciTransport {
+    pub fn new(func: PciFunction) -> Result<Self, VirtioError> {
+        // 0x1040 + type for modern devices; transitional ones (0x1000-0x103f)
+        // carry the type in the subsystem id
+        let device_type = match func.device_id {
+            0x1040..=0x107f => (func.device_id - 0x1040) as u32,
+            0x1000..=0x103f => func.read16(PCI_SUBSYSTEM_ID) as u32,
+            _ => return Err(VirtioError::NotPresent),
+        };
+
+        let (mut common, mut notify, mut isr, mut device) = (None, None, None, None);
+        let mut notify_multiplier = 0;
+        for cap in func.capabilities() {
+            if func.read8(cap) != PCI_CAP_ID_VNDR {
+                continue;
+            }
+            let cfg_type = func.read8(cap + 3);
+            let bar = func.read8(cap + 4) as usize;
+            let offset = func.read32(cap + 8) as usize;
+            let length = func.read32(cap + 12) as usize;
+            let Some((base, size)) = func.bar(bar) else { continue };
+            if offset.checked_add(length).is_none_or(|end| end > size) {
+                continue;
+            }
+            let addr = Some(base + offset);
+            // Of several structures of a type, the first one is preferred
+            match cfg_type {
+                CAP_COMMON_CFG if common.is_none() => common = addr,
+                CAP_NOTIFY_CFG if notify.is_none() => {
+                    notify = addr;
+                    notify_multiplier = func.read32(cap + 16);
+                }
+                CAP_ISR_CFG if isr.is_none() => isr = addr,
+                CAP_DEVICE_CFG if device.is_none() => device = addr,
+                _ => {}
+            }
+        }
+
+        let (Some(common), Some(notify), Some(isr)) = (common, notify, isr) else {
+            return Err(VirtioError::Unsupported);
+        };
+        func.enable();
+        Ok(Self { device_type, common, notify, notify_multiplier, isr, device: device.unwrap_or(0), notify_addrs: Mutex::new(BTreeMap::new()) })
+    }
+
+    fn read8(&self, reg: usize) -> u8 {
+        mmio_read8((self.common + reg) as *const u8)
+    }
+
+    fn read16(&self, reg: usize) -> u16 {
+        mmio_read16((self.common + reg) as *const u16)
+    }
+
+    fn read32(&self, reg: usize) -> u32 {
+        mmio_read32((self.common + reg) as *const u32)
+    }
+
+    fn write8(&self, reg: usize, value: u8) {
+        mmio_write8((self.common + reg) as *mut u8, value)
+    }
+
+    fn write16(&self, reg: usize, value: u16) {
+        mmio_write16((self.common + reg) as *mut u16, value)
+    }
+
+    fn write32(&self, reg: usize, value: u32) {
+        mmio_write32((self.common + reg) as *mut u32, value)
+    }
+
+    /// 64-bit fields are written as two halves, low first
+    fn write64(&self, reg: usize, value: u64) {
+        self.write32(reg, value as u32);
+        self.write32(reg + 4, (value >> 32) as u32);
+    }
+}
+
+impl Transport for PciTransport {
+    fn device_type(&self) -> u32 {
+        self.device_type
+    }
+
+    fn device_features(&self) -> u64 {
+        self.write32(DEVICE_FEATURE_SELECT, 0);
+        let low = self.read32(DEVICE_FEATURE) as u64;
+        self.write32(DEVICE_FEATURE_SELECT, 1);
+        let high = self.read32(DEVICE_FEATURE) as u64;
+        high << 32 | low
+    }
+
+    fn set_driver_features(&self, features: u64) {
+        self.write32(DRIVER_FEATURE_SELECT, 0);
+        self.write32(DRIVER_FEATURE, features as u32);
+        self.write32(DRIVER_FEATURE_SELECT, 1);
+        self.write32(DRIVER_FEATURE, (features >> 32) as u32);
+    }
+
+    fn status(&self) -> u8 {
+        self.read8(DEVICE_STATUS)
+    }
+
+    fn set_status(&self, status: u8) {
+        self.write8(DEVICE_STATUS, status);
+    }
+
+    fn max_queue_size(&self, queue: u16) -> u16 {
+        self.write16(QUEUE_SELECT, queue);
+        if self.read16(QUEUE_ENABLE) != 0 {
+            return 0;
+        }
+        self.read16(QUEUE_SIZE)
+    }
+
+    fn setup_queue(&self, queue: u16, size: u16, desc: u64, driver: u64, device: u64) {
+        self.write16(QUEUE_SELECT, queue);
+        self.write16(QUEUE_SIZE, size);
+        self.write64(QUEUE_DESC, desc);
+        self.write64(QUEUE_DRIVER, driver);
+        self.write64(QUEUE_DEVICE, device);
+        let notify_off = self.read16(QUEUE_NOTIFY_OFF) as usize;
+        self.notify_addrs.lock().insert(queue, self.notify + notify_off * self.notify_multiplier as usize);
+        self.write16(QUEUE_ENABLE, 1);
+    }
+
+    fn notify(&self, queue: u16) {
+        if let Some(&addr) = self.notify_addrs.lock().get(&queue) {
+            mmio_write16(addr as *mut u16, queue);
+        }
+    }
+
+    fn ack_interrupt(&self) -> u32 {
+        // Reading the ISR status clears it
+        mmio_read8(self.isr as *const u8) as u32
+    }
+
+    fn config_generation(&self) -> u32 {
+        self.read8(CONFIG_GENERATION) as u32
+    }
+
+    fn read_config8(&self, offset: usize) -> u8 {
+        if self.device == 0 { 0 } else { mmio_read8((self.device + offset) as *const u8) }
+    }
+
+    fn read_config16(&self, offset: usize) -> u16 {
+        if self.device == 0 { 0 } else { mmio_read16((self.device + offset) as *const u16) }
+    }
+
+    fn read_config32(&self, offset: usize) -> u32 {
+        if self.device == 0 { 0 } else { mmio_read32((self.device + offset) as *const u32) }
+    }
+}
@@ -0,0 +1,642 @@
+//! Split and packed virtqueues
+//!
+//! A queue lives in zeroed, page-aligned memory from `kalloc_pages`, shared
+//! with the device. The driver owns a descriptor from [`Virtqueue::add`]
+//! until [`Virtqueue::pop_used`] hands its token back; everything the
+//! device reads or writes is accessed volatile, with a fence between filling
+//! descriptors and publishing them and between seeing a used entry and
+//! reading it.
+//!
+//! Split rings use the legacy layout (used ring on the next 4 KiB boundary)
+//! so legacy virtio-mmio devices can take them by page frame number.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+use super::{dma_addr, Transport, VirtioError, F_RING_PACKED};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Descriptor flags, shared by both layouts
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+/// Split ring flags
+const AVAIL_F_NO_INTERRUPT: u16 = 1;
+const USED_F_NO_NOTIFY: u16 = 1;
+
+/// Packed ring descriptor and event suppression flags
+const PACKED_F_AVAIL: u16 = 1 << 7;
+const PACKED_F_USED: u16 = 1 << 15;
+const EVENT_F_DISABLE: u16 = 1;
+
+/// One buffer of a chain
+#[derive(Debug, Clone, Copy)]
+pub struct Buffer {
+    pub addr: u64,
+    pub len: u32,
+    /// The device writes it (otherwise it reads it)
+    pub writable: bool,
+}
+
+impl Buffer {
+    pub fn readable<T: ?Sized>(data: &T) -> Self {
+        Self { addr: dma_addr(data), len: core::mem::size_of_val(data) as u32, writable: false }
+    }
+
+    pub fn writable<T: ?Sized>(data: &mut T) -> Self {
+        Self { addr: dma_addr(data), len: core::mem::size_of_val(data) as u32, writable: true }
+    }
+}
+
+/// Zeroed pages shared with a device
+struct Ring {
+    base: *mut u8,
+}
+
+impl Ring {
+    fn alloc(bytes: usize) -> Result<Self, VirtioError> {
+        let base = crate::subsystems::mm::kalloc_pages(bytes.div_ceil(PAGE_SIZE));
+        if base.is_null() {
+            return Err(VirtioError::NoMemory);
+        }
+        Ok(Self { base })
+    }
+
+    fn at<T>(&self, offset: usize) -> *mut T {
+        self.base.wrapping_add(offset) as *mut T
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SplitDesc {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// Byte offsets of the available and used rings and the total size
+fn split_layout(size: u16) -> (usize, usize, usize) {
+    let n = size as usize;
+    let avail = 16 * n;
+    let used = (avail + 6 + 2 * n).next_multiple_of(PAGE_SIZE);
+    (avail, used, used + 6 + 8 * n)
+}
+
+/// Descriptor table plus available and used rings
+///
+/// Free descriptors are chained through `next`, so a chain taken from the
+/// head of the free list is already linked. The driver keeps its own copy
+/// of the links and of each outstanding chain's length, and only trusts
+/// those: a used entry naming anything but the head of an outstanding chain
+/// is dropped.
+pub struct SplitQueue {
+    size: u16,
+    ring: Ring,
+    avail: usize,
+    used: usize,
+    free_head: u16,
+    num_free: u16,
+    avail_idx: u16,
+    last_used: u16,
+    /// Driver copy of each descriptor's `next`
+    next: Vec<u16>,
+    /// Descriptors in the chain headed by each index, 0 if not outstanding
+    chain_len: Vec<u16>,
+}
+
+// The ring is only touched through `&mut self` or by the device
+unsafe impl Send for SplitQueue {}
+
+impl SplitQueue {
+    pub fn new(size: u16) -> Result<Self, VirtioError> {
+        if size == 0 || !size.is_power_of_two() {
+            return Err(VirtioError::InvalidArgument);
+        }
+        let (avail, used, bytes) = split_layout(size);
+        Ok(Self {
+            size,
+            ring: Ring::alloc(bytes)?,
+            avail,
+            used,
+            free_head: 0,
+            num_free: size,
+            avail_idx: 0,
+            last_used: 0,
+            next: (1..=size).collect(),
+            chain_len: alloc::vec![0; size as usize],
+        })
+    }
+
+    fn desc(&self, i: u16) -> *mut SplitDesc {
+        self.ring.at(i as usize * 16)
+    }
+
+    /// Descriptor table, driver (available) and device (used) areas
+    pub fn addresses(&self) -> (u64, u64, u64) {
+        (dma_addr(self.ring.base), dma_addr(self.ring.at::<u8>(self.avail)), dma_addr(self.ring.at::<u8>(self.used)))
+    }
+
+    fn add(&mut self, bufs: &[Buffer]) -> Result<u16, VirtioError> {
+        if bufs.is_empty() || bufs.len() > self.size as usize {
+            return Err(VirtioError::InvalidArgument);
+        }
+        if bufs.len() > self.num_free as usize {
+            return Err(VirtioError::QueueFull);
+        }
+        let head = self.free_head;
+        let mut i = head;
+        for (n, buf) in bufs.iter().enumerate() {
+            let desc = self.desc(i);
+            let mut flags = if buf.writable { DESC_F_WRITE } else { 0 };
+            if n + 1 < bufs.len() {
+                flags |= DESC_F_NEXT;
+            }
+            let next = self.next[i as usize];
+            unsafe {
+                write_volatile(&raw mut (*desc).addr, buf.addr);
+                write_volatile(&raw mut (*desc).len, buf.len);
+                write_volatile(&raw mut (*desc).flags, flags);
+                write_volatile(&raw mut (*desc).next, next);
+            }
+            i = next;
+        }
+        self.free_head = i;
+        self.num_free -= bufs.len() as u16;
+        self.chain_len[head as usize] = bufs.len() as u16;
+
+        let slot = (self.avail_idx % self.size) as usize;
+        unsafe { write_volatile(self.ring.at::<u16>(self.avail + 4 + 2 * slot), head) };
+        fence(Ordering::Release);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        unsafe { write_volatile(self.ring.at::<u16>(self.avail + 2), self.avail_idx) };
+        Ok(head)
+    }
+
+    fn should_notify(&self) -> bool {
+        fence(Ordering::SeqCst);
+        unsafe { read_volatile(self.ring.at::<u16>(self.used)) & USED_F_NO_NOTIFY == 0 }
+    }
+
+    fn set_interrupts(&mut self, enabled: bool) {
+        let flags = if enabled { 0 } else { AVAIL_F_NO_INTERRUPT };
+        unsafe { write_volatile(self.ring.at::<u16>(self.avail), flags) };
+    }
+
+    fn pop_used(&mut self) -> Option<(u16, u32)> {
+        let used_idx = unsafe { read_volatile(self.ring.at::<u16>(self.used + 2)) };
+        if used_idx == self.last_used {
+            return None;
+        }
+        fence(Ordering::Acquire);
+        let slot = (self.last_used % self.size) as usize;
+        let elem = unsafe { read_volatile(self.ring.at::<UsedElem>(self.used + 4 + 8 * slot)) };
+        self.last_used = self.last_used.wrapping_add(1);
+
+        // Put the chain back on the free list, walking the driver's links
+        // rather than the ones the device can see
+        let head = u16::try_from(elem.id).ok().filter(|&id| id < self.size)?;
+        let count = core::mem::take(&mut self.chain_len[head as usize]);
+        if count == 0 {
+            return None;
+        }
+        let mut last = head;
+        for _ in 1..count {
+            last = self.next[last as usize];
+        }
+        self.next[last as usize] = self.free_head;
+        self.free_head = head;
+        self.num_free += count;
+        Some((head, elem.len))
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PackedDesc {
+    addr: u64,
+    len: u32,
+    id: u16,
+    flags: u16,
+}
+
+/// One descriptor ring used in both directions, plus the two event
+/// suppression structures
+///
+/// A slot is available when its AVAIL bit matches the driver's wrap counter
+/// and its USED bit does not; the device marks it used by making both match
+/// its own. Chains are returned by buffer id, and take back as many slots as
+/// they used.
+pub struct PackedQueue {
+    size: u16,
+    ring: Ring,
+    next_avail: u16,
+    avail_wrap: bool,
+    last_used: u16,
+    used_wrap: bool,
+    num_free: u16,
+    free_ids: Vec<u16>,
+    /// Slots each outstanding buffer id took
+    chain_len: Vec<u16>,
+}
+
+unsafe impl Send for PackedQueue {}
+
+impl PackedQueue {
+    pub fn new(size: u16) -> Result<Self, VirtioError> {
+        if size == 0 || size > 1 << 15 {
+            return Err(VirtioError::InvalidArgument);
+        }
+        let ring = Ring::alloc(16 * size as usize + 8)?;
+        Ok(Self {
+            size,
+            ring,
+            next_avail: 0,
+            avail_wrap: true,
+            last_used: 0,
+            used_wrap: true,
+            num_free: size,
+            free_ids: (0..size).rev().collect(),
+            chain_len: alloc::vec![0; size as usize],
+        })
+    }
+
+    fn desc(&self, i: u16) -> *mut PackedDesc {
+        self.ring.at(i as usize * 16)
+    }
+
+    fn driver_event(&self) -> usize {
+        16 * self.size as usize
+    }
+
+    fn device_event(&self) -> usize {
+        self.driver_event() + 4
+    }
+
+    /// Descriptor ring, driver and device event suppression areas
+    pub fn addresses(&self) -> (u64, u64, u64) {
+        (dma_addr(self.ring.base), dma_addr(self.ring.at::<u8>(self.driver_event())), dma_addr(self.ring.at::<u8>(self.device_event())))
+    }
+
+    fn add(&mut self, bufs: &[Buffer]) -> Result<u16, VirtioError> {
+        if bufs.is_empty() || bufs.len() > self.size as usize {
+            return Err(VirtioError::InvalidArgument);
+        }
+        if bufs.len() > self.num_free as usize {
+            return Err(VirtioError::QueueFull);
+        }
+        let id = self.free_ids.pop().ok_or(VirtioError::QueueFull)?;
+        let head = self.next_avail;
+        let mut head_flags = 0;
+        for (n, buf) in bufs.iter().enumerate() {
+            let mut flags = if self.avail_wrap { PACKED_F_AVAIL } else { PACKED_F_USED };
+            if buf.writable {
+                flags |= DESC_F_WRITE;
+            }
+            if n + 1 < bufs.len() {
+                flags |= DESC_F_NEXT;
+            }
+            let desc = self.desc(self.next_avail);
+            unsafe {
+                write_volatile(&raw mut (*desc).addr, buf.addr);
+                write_volatile(&raw mut (*desc).len, buf.len);
+                write_volatile(&raw mut (*desc).id, id);
+            }
+            // The head is made available last, once the whole chain is there
+            if n == 0 {
+                head_flags = flags;
+            } else {
+                unsafe { write_volatile(&raw mut (*desc).flags, flags) };
+            }
+            self.next_avail += 1;
+            if self.next_avail == self.size {
+                self.next_avail = 0;
+                self.avail_wrap = !self.avail_wrap;
+            }
+        }
+        fence(Ordering::Release);
+        unsafe { write_volatile(&raw mut (*self.desc(head)).flags, head_flags) };
+        self.num_free -= bufs.len() as u16;
+        self.chain_len[id as usize] = bufs.len() as u16;
+        Ok(id)
+    }
+
+    fn should_notify(&self) -> bool {
+        fence(Ordering::SeqCst);
+        unsafe { read_volatile(self.ring.at::<u16>(self.device_event() + 2)) != EVENT_F_DISABLE }
+    }
+
+    fn set_interrupts(&mut self, enabled: bool) {
+        let flags = if enabled { 0 } else { EVENT_F_DISABLE };
+        unsafe { write_volatile(self.ring.at::<u16>(self.driver_event() + 2), flags) };
+    }
+
+    fn pop_used(&mut self) -> Option<(u16, u32)> {
+        let desc = self.desc(self.last_used);
+        let flags = unsafe { read_volatile(&raw const (*desc).flags) };
+        let avail = flags & PACKED_F_AVAIL != 0;
+        let used = flags & PACKED_F_USED != 0;
+        if avail != used || used != self.used_wrap {
+            return None;
+        }
+        fence(Ordering::Acquire);
+        let (id, len) = unsafe { (read_volatile(&raw const (*desc).id), read_volatile(&raw const (*desc).len)) };
+        let count = *self.chain_len.get(id as usize)?;
+        if count == 0 {
+            return None;
+        }
+        self.chain_len[id as usize] = 0;
+        self.last_used += count;
+        if self.last_used >= self.size {
+            self.last_used -= self.size;
+            self.used_wrap = !self.used_wrap;
+        }
+        self.num_free += count;
+        self.free_ids.push(id);
+        Some((id, len))
+    }
+}
+
+/// A virtqueue in whichever layout was negotiated
+pub enum Virtqueue {
+    Split(u16, SplitQueue),
+    Packed(u16, PackedQueue),
+}
+
+impl Virtqueue {
+    /// Create queue `index` with at most `max_size` entries and hand it to
+    /// the device
+    ///
+    /// Packed layout is used when RING_PACKED was negotiated. Split queues
+    /// need a power-of-two size, so the device's maximum is rounded down.
+    pub fn new(transport: &dyn Transport, index: u16, max_size: u16, features: u64) -> Result<Self, VirtioError> {
+        let device_max = transport.max_queue_size(index);
+        if device_max == 0 {
+            return Err(VirtioError::NoQueue);
+        }
+        let size = device_max.min(max_size);
+        let queue = if features & F_RING_PACKED != 0 {
+            Self::Packed(index, PackedQueue::new(size)?)
+        } else {
+            Self::Split(index, SplitQueue::new(1 << size.ilog2())?)
+        };
+        let (desc, driver, device) = queue.addresses();
+        transport.setup_queue(index, queue.size(), desc, driver, device);
+        Ok(queue)
+    }
+
+    pub fn index(&self) -> u16 {
+        match self {
+            Self::Split(index, _) | Self::Packed(index, _) => *index,
+        }
+    }
+
+    pub fn size(&self) -> u16 {
+        match self {
+            Self::Split(_, queue) => queue.size,
+            Self::Packed(_, queue) => queue.size,
+        }
+    }
+
+    pub fn num_free(&self) -> usize {
+        match self {
+            Self::Split(_, queue) => queue.num_free as usize,
+            Self::Packed(_, queue) => queue.num_free as usize,
+        }
+    }
+
+    fn addresses(&self) -> (u64, u64, u64) {
+        match self {
+            Self::Split(_, queue) => queue.addresses(),
+            Self::Packed(_, queue) => queue.addresses(),
+        }
+    }
+
+    /// Make a chain of buffers available; device-readable ones must come
+    /// before device-writable ones
+    ///
+    /// Returns the token [`pop_used`](Self::pop_used) gives back when the
+    /// device is done, which is below [`size`](Self::size) and unique among
+    /// outstanding chains.
+    pub fn add(&mut self, bufs: &[Buffer]) -> Result<u16, VirtioError> {
+        match self {
+            Self::Split(_, queue) => queue.add(bufs),
+            Self::Packed(_, queue) => queue.add(bufs),
+        }
+    }
+
+    /// Notify the device of new buffers unless it asked not to be
+    pub fn kick(&self, transport: &dyn Transport) {
+        let notify = match self {
+            Self::Split(_, queue) => queue.should_notify(),
+            Self::Packed(_, queue) => queue.should_notify(),
+        };
+        if notify {
+            transport.notify(self.index());
+        }
+    }
+
+    /// Ask the device not to interrupt for this queue (a hint it may ignore)
+    pub fn set_interrupts(&mut self, enabled: bool) {
+        match self {
+            Self::Split(_, queue) => queue.set_interrupts(enabled),
+            Self::Packed(_, queue) => queue.set_interrupts(enabled),
+        }
+    }
+
+    /// Next chain the device has finished, as (token, bytes written)
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        match self {
+            Self::Split(_, queue) => queue.pop_used(),
+            Self::Packed(_, queue) => queue.pop_used(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Device side of a split queue: consume one available chain and
+    /// return it with `written` bytes
+    fn split_device_use(queue: &SplitQueue, seen: &mut u16, written: u32) -> Vec<SplitDesc> {
+        let avail_idx = unsafe { read_volatile(queue.ring.at::<u16>(queue.avail + 2)) };
+        assert_ne!(avail_idx, *seen);
+        let slot = (*seen % queue.size) as usize;
+        let head = unsafe { read_volatile(queue.ring.at::<u16>(queue.avail + 4 + 2 * slot)) };
+        let mut chain = Vec::new();
+        let mut i = head;
+        loop {
+            let desc = unsafe { read_volatile(queue.desc(i)) };
+            chain.push(desc);
+            if desc.flags & DESC_F_NEXT == 0 {
+                break;
+            }
+            i = desc.next;
+        }
+        split_push_used(queue, head as u32, written);
+        *seen = seen.wrapping_add(1);
+        chain
+    }
+
+    /// Device side of a split queue: publish a used entry
+    fn split_push_used(queue: &SplitQueue, id: u32, written: u32) {
+        let used_idx = unsafe { read_volatile(queue.ring.at::<u16>(queue.used + 2)) };
+        let used_slot = (used_idx % queue.size) as usize;
+        unsafe {
+            write_volatile(queue.ring.at::<UsedElem>(queue.used + 4 + 8 * used_slot), UsedElem { id, len: written });
+            write_volatile(queue.ring.at::<u16>(queue.used + 2), used_idx.wrapping_add(1));
+        }
+    }
+
+    fn bufs(n: usize) -> Vec<Buffer> {
+        (0..n).map(|i| Buffer { addr: 0x1000 * (i as u64 + 1), len: 512, writable: i + 1 == n }).collect()
+    }
+
+    #[test]
+    fn test_split_layout() {
+        let (avail, used, total) = split_layout(256);
+        assert_eq!(avail, 4096);
+        assert_eq!(used, 8192);
+        assert_eq!(total, 8192 + 6 + 8 * 256);
+    }
+
+    #[test]
+    fn test_split_round_trip() {
+        let mut queue = SplitQueue::new(8).unwrap();
+        let mut seen = 0;
+        for round in 0..20u32 {
+            let token = queue.add(&bufs(3)).unwrap();
+            assert_eq!(queue.num_free, 5);
+            assert!(queue.pop_used().is_none());
+            let chain = split_device_use(&queue, &mut seen, round);
+            assert_eq!(chain.len(), 3);
+            assert_eq!(chain[0].addr, 0x1000);
+            assert_eq!(chain[2].flags, DESC_F_WRITE);
+            assert_eq!(queue.pop_used(), Some((token, round)));
+            assert_eq!(queue.num_free, 8);
+        }
+    }
+
+    #[test]
+    fn test_split_full() {
+        let mut queue = SplitQueue::new(4).unwrap();
+        let mut seen = 0;
+        let first = queue.add(&bufs(3)).unwrap();
+        assert_eq!(queue.add(&bufs(2)), Err(VirtioError::QueueFull));
+        let second = queue.add(&bufs(1)).unwrap();
+        assert_ne!(first, second);
+        split_device_use(&queue, &mut seen, 0);
+        split_device_use(&queue, &mut seen, 0);
+        assert_eq!(queue.pop_used(), Some((first, 0)));
+        assert_eq!(queue.pop_used(), Some((second, 0)));
+        assert!(queue.add(&bufs(4)).is_ok());
+        assert!(SplitQueue::new(6).is_err());
+    }
+
+    #[test]
+    fn test_split_bad_used_entries() {
+        let mut queue = SplitQueue::new(8).unwrap();
+        let token = queue.add(&bufs(3)).unwrap();
+
+        // Out of range, and in range but not the head of anything outstanding
+        split_push_used(&queue, 8, 0);
+        split_push_used(&queue, 0x10000 | token as u32, 0);
+        split_push_used(&queue, token as u32 + 1, 0);
+        for _ in 0..3 {
+            assert!(queue.pop_used().is_none());
+        }
+        assert_eq!(queue.num_free, 5);
+
+        // The device loops the chain back on itself before returning it
+        for i in 0..8 {
+            unsafe { write_volatile(&raw mut (*queue.desc(i)).next, token) };
+        }
+        split_push_used(&queue, token as u32, 4);
+        assert_eq!(queue.pop_used(), Some((token, 4)));
+        assert_eq!(queue.num_free, 8);
+        // Returned twice
+        split_push_used(&queue, token as u32, 0);
+        assert!(queue.pop_used().is_none());
+        assert_eq!(queue.num_free, 8);
+
+        // The free list is intact: every descriptor can be handed out again,
+        // linked up for the device
+        let chain = queue.add(&bufs(8)).unwrap();
+        let mut seen = 1;
+        assert_eq!(split_device_use(&queue, &mut seen, 1).len(), 8);
+        assert_eq!(queue.pop_used(), Some((chain, 1)));
+    }
+
+    /// Device side of a packed queue: consume the chain at `*next` and
+    /// write one used descriptor in its place
+    fn packed_device_use(queue: &PackedQueue, next: &mut u16, wrap: &mut bool, written: u32) -> usize {
+        let start = *next;
+        let head = unsafe { read_volatile(queue.desc(start)) };
+        assert_eq!(head.flags & PACKED_F_AVAIL != 0, *wrap);
+        assert_eq!(head.flags & PACKED_F_USED != 0, !*wrap);
+        let mut count = 0;
+        let mut id;
+        loop {
+            let desc = unsafe { read_volatile(queue.desc(*next)) };
+            id = desc.id;
+            count += 1;
+            *next += 1;
+            if *next == queue.size {
+                *next = 0;
+                *wrap = !*wrap;
+            }
+            if desc.flags & DESC_F_NEXT == 0 {
+                break;
+            }
+        }
+        let used_wrap = head.flags & PACKED_F_AVAIL != 0;
+        let flags = if used_wrap { PACKED_F_AVAIL | PACKED_F_USED } else { 0 };
+        unsafe { write_volatile(queue.desc(start), PackedDesc { addr: 0, len: written, id, flags }) };
+        count
+    }
+
+    #[test]
+    fn test_packed_wraps() {
+        let mut queue = PackedQueue::new(5).unwrap();
+        let (mut next, mut wrap) = (0, true);
+        for round in 0..12u32 {
+            let token = queue.add(&bufs(2)).unwrap();
+            assert!(queue.pop_used().is_none());
+            assert_eq!(packed_device_use(&queue, &mut next, &mut wrap, round), 2);
+            assert_eq!(queue.pop_used(), Some((token, round)));
+            assert_eq!(queue.num_free, 5);
+        }
+        // Twelve two-slot chains in a five-slot ring wrapped four times
+        assert_eq!(queue.next_avail, 4);
+        assert!(queue.avail_wrap);
+    }
+
+    #[test]
+    fn test_packed_full() {
+        let mut queue = PackedQueue::new(8).unwrap();
+        let (mut next, mut wrap) = (0, true);
+        let a = queue.add(&bufs(3)).unwrap();
+        let b = queue.add(&bufs(3)).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(queue.add(&bufs(3)), Err(VirtioError::QueueFull));
+        packed_device_use(&queue, &mut next, &mut wrap, 7);
+        assert_eq!(queue.pop_used(), Some((a, 7)));
+        assert!(queue.pop_used().is_none());
+        packed_device_use(&queue, &mut next, &mut wrap, 9);
+        assert_eq!(queue.pop_used(), Some((b, 9)));
+        assert_eq!(queue.num_free, 8);
+    }
+}
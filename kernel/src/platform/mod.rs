@@ -43,7 +43,11 @@ pub fn init_platform(boot_params: &BootParameters) -> Result<()> {
 
 /// Shutdown platform subsystems
 pub fn shutdown_platform() -> Result<()> {
-    // Platform-specific shutdown procedures would be implemented here
-    // For now, we'll just return Ok(()) as a placeholder
+    // Firmware that the device tree describes can power the machine off;
+    // SYSTEM_OFF only returns if it failed
+    if drivers::psci::available() {
+        let ret = drivers::psci::system_off();
+        crate::println!("[platform] PSCI SYSTEM_OFF failed: {}", ret);
+    }
     Ok(())
 }
\ No newline at end of file
@@ -47,11 +47,18 @@ mod riscv64 {
         }
         
         if scause == cause::USER_ECALL {
-            // System call - handled by usertrap assembly which has trapframe
-            // This is a placeholder; real implementation passes trapframe
+            super::syscall();
+        } else if scause == cause::BREAKPOINT {
+            // ebreak; no hardware single-step on RISC-V
+            crate::process::ptrace::debug_trap(false, sepc);
         } else if scause & 0x8000_0000_0000_0000 != 0 {
             // Interrupt
             handle_interrupt(scause);
+        } else if scause == cause::LOAD_PAGE_FAULT
+            || scause == cause::STORE_PAGE_FAULT
+            || scause == cause::INSTRUCTION_PAGE_FAULT
+        {
+            super::user_page_fault(stval, scause == cause::STORE_PAGE_FAULT, scause == cause::INSTRUCTION_PAGE_FAULT);
         } else {
             // Exception
             crate::println!("usertrap: unexpected scause={:#x} sepc={:#x} stval={:#x}",
@@ -71,11 +78,23 @@ mod riscv64 {
         
         if scause & 0x8000_0000_0000_0000 != 0 {
             handle_interrupt(scause);
+        } else if let Some(fixup) = user_copy_fixup(scause, sepc) {
+            // A user copy hit a bad page; it returns EFAULT from its fixup
+            unsafe { core::arch::asm!("csrw sepc, {}", in(reg) fixup) };
         } else {
             panic!("kerneltrap: scause={:#x} sepc={:#x}", scause, sepc);
         }
     }
     
+    fn user_copy_fixup(scause: usize, sepc: usize) -> Option<usize> {
+        match scause {
+            cause::LOAD_PAGE_FAULT | cause::STORE_PAGE_FAULT | cause::LOAD_FAULT | cause::STORE_FAULT => {
+                crate::subsystems::mm::uaccess::fixup_exception(sepc)
+            }
+            _ => None,
+        }
+    }
+    
     fn handle_interrupt(scause: usize) {
         match scause {
             cause::SUPERVISOR_TIMER => {
@@ -103,6 +122,8 @@ mod aarch64 {
     /// Exception Syndrome Register (ESR) exception classes
     pub mod ec {
         pub const SVC64: u32 = 0x15;
+        pub const SOFTSTP_LOWER: u32 = 0x32;
+        pub const BRK64: u32 = 0x3C;
         pub const DATA_ABORT_LOWER: u32 = 0x24;
         pub const DATA_ABORT_SAME: u32 = 0x25;
         pub const INST_ABORT_LOWER: u32 = 0x20;
@@ -125,10 +146,20 @@ mod aarch64 {
         
         match ec {
             ec::SVC64 => {
-                // System call - handled by assembly with trapframe
+                super::syscall();
             }
-            ec::DATA_ABORT_LOWER | ec::INST_ABORT_LOWER => {
-                crate::println!("Page fault at {:#x}, esr={:#x}", far, esr);
+            ec::BRK64 => {
+                crate::process::ptrace::debug_trap(false, elr as usize);
+            }
+            ec::SOFTSTP_LOWER => {
+                crate::process::ptrace::debug_trap(true, elr as usize);
+            }
+            ec::DATA_ABORT_LOWER => {
+                // ISS.WnR tells a store from a load
+                super::user_page_fault(far as usize, esr & (1 << 6) != 0, false);
+            }
+            ec::INST_ABORT_LOWER => {
+                super::user_page_fault(far as usize, false, true);
             }
             _ => {
                 crate::println!("Unexpected exception: ec={:#x} esr={:#x} elr={:#x}",
@@ -137,6 +168,31 @@ mod aarch64 {
         }
     }
     
+    /// Handle exception from EL1
+    ///
+    /// Only a data abort in one of the user copy routines is survivable:
+    /// it resumes at the routine's fixup, which reports EFAULT.
+    pub fn handle_sync_el1() {
+        let esr: u64;
+        let elr: u64;
+        let far: u64;
+        
+        unsafe {
+            core::arch::asm!("mrs {}, esr_el1", out(reg) esr);
+            core::arch::asm!("mrs {}, elr_el1", out(reg) elr);
+            core::arch::asm!("mrs {}, far_el1", out(reg) far);
+        }
+        
+        let ec = ((esr >> 26) & 0x3F) as u32;
+        if ec == ec::DATA_ABORT_SAME {
+            if let Some(fixup) = crate::subsystems::mm::uaccess::fixup_exception(elr as usize) {
+                unsafe { core::arch::asm!("msr elr_el1, {}", in(reg) fixup as u64) };
+                return;
+            }
+        }
+        panic!("Kernel exception: ec={:#x} esr={:#x} elr={:#x} far={:#x}", ec, esr, elr, far);
+    }
+    
     /// Handle IRQ from EL0
     pub fn handle_irq_el0() {
         handle_irq();
@@ -159,7 +215,9 @@ mod aarch64 {
 
 #[cfg(target_arch = "x86_64")]
 mod x86_64 {
-    use super::*;
+    use core::cell::UnsafeCell;
+    use crate::cpu::NCPU;
+    use crate::process::TrapFrame;
     
     /// x86_64 exception vectors
     pub mod vector {
@@ -182,34 +240,152 @@ mod x86_64 {
         pub const MACHINE_CHECK: u8 = 18;
         pub const SIMD_ERROR: u8 = 19;
         
+        /// `int 0x80`; the `syscall` instruction enters at `syscall_entry`
         pub const SYSCALL: u8 = 0x80;
         pub const TIMER: u8 = 32;
     }
     
+    /// Segment selectors, in Linux's GDT layout. `syscall` loads CS and SS
+    /// from STAR[47:32]; `sysret` loads SS from STAR[63:48] + 8 and CS
+    /// from STAR[63:48] + 16.
+    pub const KERNEL_CS: usize = 0x10;
+    pub const USER32_CS: usize = 0x23;
+    pub const USER_DS: usize = 0x2b;
+    pub const USER_CS: usize = 0x33;
+
+    /// Per-CPU words the `syscall` entry reaches through GS after `swapgs`,
+    /// at `gs:[0]` and `gs:[8]`
+    #[repr(C)]
+    struct SyscallCpu {
+        /// Top of the running thread's kernel stack
+        kernel_rsp: usize,
+        /// User rsp, parked while the entry switches stacks
+        #[allow(dead_code)]
+        user_rsp: usize,
+    }
+
+    struct SyscallCpus([UnsafeCell<SyscallCpu>; NCPU]);
+
+    unsafe impl Sync for SyscallCpus {}
+
+    static SYSCALL_CPUS: SyscallCpus =
+        SyscallCpus([const { UnsafeCell::new(SyscallCpu { kernel_rsp: 0, user_rsp: 0 }) }; NCPU]);
+
+    /// Enable the `syscall` instruction on this CPU and point it at
+    /// `syscall_entry`
+    ///
+    /// KERNEL_GS_BASE holds this CPU's `SyscallCpu` while user code runs;
+    /// the entry and exit `swapgs` trade it with the user's GS base. The
+    /// kernel does not use GS otherwise.
+    #[cfg(feature = "baremetal")]
+    pub fn init_syscall() {
+        const MSR_EFER: u32 = 0xc000_0080;
+        const MSR_STAR: u32 = 0xc000_0081;
+        const MSR_LSTAR: u32 = 0xc000_0082;
+        const MSR_FMASK: u32 = 0xc000_0084;
+        const MSR_KERNEL_GS_BASE: u32 = 0xc000_0102;
+        const EFER_SCE: u64 = 1 << 0;
+        // RFLAGS cleared on entry: TF, IF, DF, IOPL, NT and AC
+        const SYSCALL_FMASK: u64 = 0x47700;
+
+        unsafe extern "C" {
+            fn syscall_entry();
+        }
+        let wrmsr = |msr: u32, value: u64| unsafe {
+            core::arch::asm!("wrmsr", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32,
+                options(nostack));
+        };
+        let (low, high): (u32, u32);
+        unsafe { core::arch::asm!("rdmsr", in("ecx") MSR_EFER, out("eax") low, out("edx") high, options(nostack)) };
+        let efer = ((high as u64) << 32) | low as u64;
+
+        wrmsr(MSR_EFER, efer | EFER_SCE);
+        wrmsr(MSR_STAR, ((USER32_CS as u64) << 48) | ((KERNEL_CS as u64) << 32));
+        wrmsr(MSR_LSTAR, syscall_entry as usize as u64);
+        wrmsr(MSR_FMASK, SYSCALL_FMASK);
+        wrmsr(MSR_KERNEL_GS_BASE, SYSCALL_CPUS.0[crate::cpu::cpuid()].get() as u64);
+    }
+
+    /// Kernel stack `syscall_entry` switches to on this CPU
+    pub fn set_syscall_stack(top: usize) {
+        unsafe { (*SYSCALL_CPUS.0[crate::cpu::cpuid()].get()).kernel_rsp = top };
+    }
+
+    /// Called by `syscall_entry` with interrupts off and the user registers
+    /// saved in `frame` on the kernel stack
+    ///
+    /// The dispatcher works on the process trapframe, so `frame` is copied
+    /// in and back out: exec, sigreturn or a tracer may replace the user
+    /// state under it.
+    #[unsafe(no_mangle)]
+    extern "C" fn syscall_entry_rust(frame: &mut TrapFrame) {
+        use crate::process::{myproc, PROC_TABLE};
+
+        let trapframe = || {
+            let pid = myproc()?;
+            let tf = PROC_TABLE.lock().find_ref(pid)?.trapframe;
+            (!tf.is_null()).then_some(tf)
+        };
+        let Some(tf) = trapframe() else { return };
+        unsafe { *tf = *frame };
+        crate::arch::intr_on();
+        super::syscall();
+        crate::arch::intr_off();
+        if let Some(tf) = trapframe() {
+            unsafe { *frame = *tf };
+        }
+        frame.cs = USER_CS;
+        frame.ss = USER_DS;
+    }
+
+    /// Page fault error code: the access came from user mode
+    const PF_WRITE: usize = 1 << 1;
+    const PF_USER: usize = 1 << 2;
+    const PF_INSTR: usize = 1 << 4;
+    
     /// Handle trap/interrupt
-    pub fn trap_handler(vector: u8, error_code: usize, rip: usize) {
+    ///
+    /// `rip` is where the trap returns to; a kernel page fault in a user
+    /// copy routine moves it to the routine's fixup.
+    pub fn trap_handler(vector: u8, error_code: usize, rip: &mut usize) {
+        let rip_value = *rip;
         match vector {
             vector::SYSCALL => {
-                // System call - handled by assembly with trapframe
+                super::syscall();
             }
             vector::PAGE_FAULT => {
                 let cr2: usize;
                 unsafe {
                     core::arch::asm!("mov {}, cr2", out(reg) cr2);
                 }
-                crate::println!("Page fault at {:#x}, error={:#x}, rip={:#x}",
-                    cr2, error_code, rip);
+                if error_code & PF_USER == 0 {
+                    if let Some(fixup) = crate::subsystems::mm::uaccess::fixup_exception(rip_value) {
+                        *rip = fixup;
+                        return;
+                    }
+                    crate::println!("Page fault at {:#x}, error={:#x}, rip={:#x}",
+                        cr2, error_code, rip_value);
+                } else {
+                    super::user_page_fault(cr2, error_code & PF_WRITE != 0, error_code & PF_INSTR != 0);
+                }
             }
             vector::TIMER => {
                 crate::subsystems::time::timer_interrupt();
             }
+            vector::DEBUG => {
+                // RFLAGS.TF single-step
+                crate::process::ptrace::debug_trap(true, rip_value);
+            }
+            vector::BREAKPOINT => {
+                crate::process::ptrace::debug_trap(false, rip_value);
+            }
             vector::GENERAL_PROTECTION => {
                 panic!("General protection fault: error={:#x} rip={:#x}",
-                    error_code, rip);
+                    error_code, rip_value);
             }
             _ => {
                 crate::println!("Unhandled trap: vector={} error={:#x} rip={:#x}",
-                    vector, error_code, rip);
+                    vector, error_code, rip_value);
             }
         }
     }
@@ -363,6 +539,98 @@ handle_irq_el0:
     ret
 "#);
 
+// `syscall` entry: rcx holds the user rip, r11 the user rflags, and rsp is
+// still the user stack. Build a TrapFrame on the kernel stack, from ss down
+// to rax, and hand it to `syscall_entry_rust`. sysret goes back only to a
+// canonical user rip (it would fault in ring 0 otherwise); anything else
+// returns through iretq, which faults in user mode.
+#[cfg(all(feature = "baremetal", target_arch = "x86_64"))]
+core::arch::global_asm!(r#"
+.section .text
+.globl syscall_entry
+.align 16
+syscall_entry:
+    swapgs
+    mov gs:[8], rsp
+    mov rsp, gs:[0]
+
+    push {user_ds}          // ss
+    push {user_cs}          // cs
+    push r11                // rflags
+    push rcx                // rip
+    push qword ptr gs:[8]   // rsp
+    push r15
+    push r14
+    push r13
+    push r12
+    push r11
+    push r10
+    push r9
+    push r8
+    push rbp
+    push rdi
+    push rsi
+    push rdx
+    push rcx
+    push rbx
+    push rax
+
+    mov rdi, rsp
+    call syscall_entry_rust
+
+    // Bits 63:47 of rip must be clear for sysret
+    mov rax, [rsp + 16*8]
+    shr rax, 47
+    jnz 1f
+
+    pop rax
+    pop rbx
+    add rsp, 8              // rcx: loaded with rip
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rbp
+    pop r8
+    pop r9
+    pop r10
+    add rsp, 8              // r11: loaded with rflags
+    pop r12
+    pop r13
+    pop r14
+    pop r15
+    mov rcx, [rsp + 8]
+    mov r11, [rsp + 16]
+    mov rsp, [rsp]
+    swapgs
+    sysretq
+
+1:
+    // Reorder rsp, rip, rflags, cs into the iretq frame rip, cs, rflags, rsp
+    mov rax, [rsp + 15*8]
+    mov rbx, [rsp + 16*8]
+    mov rcx, [rsp + 18*8]
+    mov [rsp + 15*8], rbx
+    mov [rsp + 16*8], rcx
+    mov [rsp + 18*8], rax
+    pop rax
+    pop rbx
+    pop rcx
+    pop rdx
+    pop rsi
+    pop rdi
+    pop rbp
+    pop r8
+    pop r9
+    pop r10
+    pop r11
+    pop r12
+    pop r13
+    pop r14
+    pop r15
+    swapgs
+    iretq
+"#, user_ds = const x86_64::USER_DS, user_cs = const x86_64::USER_CS);
+
 // ============================================================================
 // Public API
 // ============================================================================
@@ -393,9 +661,18 @@ pub fn init() {
     #[cfg(target_arch = "x86_64")]
     {
         // IDT setup would go here
+        #[cfg(feature = "baremetal")]
+        x86_64::init_syscall();
     }
 }
 
+/// Point this CPU's `syscall` entry at the kernel stack of the thread
+/// about to run
+#[cfg(target_arch = "x86_64")]
+pub fn set_syscall_stack(top: usize) {
+    x86_64::set_syscall_stack(top);
+}
+
 /// Handle trap (generic interface)
 pub fn handle(cause: usize, epc: usize, tval: usize) {
     #[cfg(target_arch = "riscv64")]
@@ -413,8 +690,84 @@ pub fn handle(cause: usize, epc: usize, tval: usize) {
     #[cfg(target_arch = "x86_64")]
     {
         let _ = tval;
-        x86_64::trap_handler(cause as u8, 0, epc);
+        let mut rip = epc;
+        x86_64::trap_handler(cause as u8, 0, &mut rip);
+    }
+}
+
+/// A user access faulted at `addr`: fault the page in or signal the process
+///
+/// SIGSEGV goes to an access outside any area or one the area forbids,
+/// SIGBUS to one past the end of a mapped file. Out of memory, the process
+/// is killed if the OOM killer chose it or found nothing else to kill.
+pub fn user_page_fault(addr: usize, write: bool, exec: bool) {
+    use crate::ipc::signal::{si_code, SigInfo, SIGBUS, SIGKILL, SIGSEGV};
+    use crate::process::{myproc, PROC_TABLE};
+    use crate::subsystems::mm::vm::{handle_user_fault, PageFaultResult};
+
+    let Some(pid) = myproc() else { return };
+    let result = handle_user_fault(pid, addr, write, exec);
+    let table = PROC_TABLE.lock();
+    let Some(proc) = table.find_ref(pid) else { return };
+    let (signal, code) = match result {
+        PageFaultResult::Handled => return,
+        PageFaultResult::SegFault if proc.vm.find(addr).is_some() => (SIGSEGV, si_code::SEGV_ACCERR),
+        PageFaultResult::SegFault => (SIGSEGV, si_code::SEGV_MAPERR),
+        PageFaultResult::BusError => (SIGBUS, si_code::BUS_ADRERR),
+        PageFaultResult::OutOfMemory => (SIGKILL, 0),
+    };
+    if let Some(signals) = proc.signals.as_ref() {
+        let info = SigInfo { signo: signal as i32, code, addr, ..SigInfo::default() };
+        let _ = signals.send_signal_info(signal, info);
+    }
+}
+
+/// Run the system call saved in the current process's trapframe
+///
+/// The process personality picks the numbering: native NOS categories or the
+/// Linux table for this architecture. seccomp filters run after the tracer's
+/// entry stop and may fail the call, or kill the task, before it is
+/// dispatched.
+pub fn syscall() {
+    use crate::process::{Personality, PROC_TABLE, myproc};
+    use crate::security::seccomp::{secure_computing, SeccompOutcome, AUDIT_ARCH_NATIVE, AUDIT_ARCH_NONE};
+
+    let Some(pid) = myproc() else { return };
+    let (tf, personality) = {
+        let mut table = PROC_TABLE.lock();
+        match table.find(pid) {
+            Some(proc) if !proc.trapframe.is_null() => (proc.trapframe, proc.personality),
+            _ => return,
+        }
+    };
+
+    // The trapframe belongs to this process and stays put while it runs
+    let nr = unsafe {
+        (*tf).skip_syscall_insn();
+        (*tf).syscall_nr()
+    };
+    // A tracer may rewrite the call at the entry stop, or cancel it and
+    // leave the return value to whatever it put in the registers
+    if let Some(nr) = crate::process::ptrace::syscall_enter(nr) {
+        let args = unsafe { (*tf).syscall_args() };
+        // Filters see the number the task issued, under its personality's
+        // architecture
+        let arch = match personality {
+            Personality::Native => AUDIT_ARCH_NONE,
+            Personality::Linux => AUDIT_ARCH_NATIVE,
+        };
+        let ret = match secure_computing(nr as u32, arch, &args) {
+            SeccompOutcome::Allow => match personality {
+                Personality::Native => crate::syscalls::dispatch(nr, &args),
+                Personality::Linux => crate::compat::linux::syscall::syscall(nr, args),
+            },
+            SeccompOutcome::Return(value) => value as isize,
+            // Nothing to return to
+            SeccompOutcome::Killed => return,
+        };
+        unsafe { (*tf).set_syscall_return(ret) };
     }
+    crate::process::ptrace::syscall_exit();
 }
 
 /// Return to user mode
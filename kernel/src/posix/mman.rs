@@ -0,0 +1,94 @@
+//! POSIX Memory Management Declarations (sys/mman.h)
+
+/// Pages may not be accessed
+pub const PROT_NONE: i32 = 0x0;
+
+/// Pages may be read
+pub const PROT_READ: i32 = 0x1;
+
+/// Pages may be written
+pub const PROT_WRITE: i32 = 0x2;
+
+/// Pages may be executed
+pub const PROT_EXEC: i32 = 0x4;
+
+/// Changes are shared with other mappings of the same object
+pub const MAP_SHARED: i32 = 0x01;
+
+/// Changes are private (copy-on-write)
+pub const MAP_PRIVATE: i32 = 0x02;
+
+/// Place the mapping at exactly the given address
+pub const MAP_FIXED: i32 = 0x10;
+
+/// The mapping is not backed by any file
+pub const MAP_ANONYMOUS: i32 = 0x20;
+
+/// Lock the pages of the mapping, as mlock would
+pub const MAP_LOCKED: i32 = 0x2000;
+
+/// Prefault the pages of the mapping
+pub const MAP_POPULATE: i32 = 0x8000;
+
+/// Value mmap returns on failure
+pub const MAP_FAILED: *mut core::ffi::c_void = !0usize as *mut core::ffi::c_void;
+
+/// No special treatment
+pub const MADV_NORMAL: i32 = 0;
+
+/// Expect page references in random order
+pub const MADV_RANDOM: i32 = 1;
+
+/// Expect page references in sequential order
+pub const MADV_SEQUENTIAL: i32 = 2;
+
+/// Expect access in the near future
+pub const MADV_WILLNEED: i32 = 3;
+
+/// Do not expect access in the near future; private pages read back as zero
+pub const MADV_DONTNEED: i32 = 4;
+
+/// The contents may be freed; private pages read back as zero once they are
+pub const MADV_FREE: i32 = 8;
+
+/// Do not make the pages available to the child after fork
+pub const MADV_DONTFORK: i32 = 10;
+
+/// Undo MADV_DONTFORK
+pub const MADV_DOFORK: i32 = 11;
+
+/// Back the range with huge pages where possible
+pub const MADV_HUGEPAGE: i32 = 14;
+
+/// Never back the range with huge pages
+pub const MADV_NOHUGEPAGE: i32 = 15;
+
+/// Lock all currently mapped pages
+pub const MCL_CURRENT: i32 = 1;
+
+/// Lock all pages mapped in the future
+pub const MCL_FUTURE: i32 = 2;
+
+/// With MCL_CURRENT or MCL_FUTURE, lock pages as they are faulted in
+pub const MCL_ONFAULT: i32 = 4;
+
+/// mlock2: lock pages as they are faulted in
+pub const MLOCK_ONFAULT: i32 = 1;
+
+/// msync: schedule the write-back and return
+pub const MS_ASYNC: i32 = 1;
+
+/// msync: invalidate other mappings of the same file
+pub const MS_INVALIDATE: i32 = 2;
+
+/// msync: write back and wait for it to finish
+pub const MS_SYNC: i32 = 4;
+
+/// mremap: the mapping may be moved
+pub const MREMAP_MAYMOVE: i32 = 1;
+
+/// mremap: move the mapping to exactly the given address
+pub const MREMAP_FIXED: i32 = 2;
+
+/// mremap: move the pages but leave the old range mapped, empty
+pub const MREMAP_DONTUNMAP: i32 = 4;
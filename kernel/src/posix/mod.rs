@@ -11,6 +11,7 @@ pub mod seek;
 pub mod fcntl;
 pub mod aio;
 pub mod stat;
+pub mod mman;
 
 // ============================================================================
 // Public Exports
@@ -23,6 +24,7 @@ pub use self::seek::*;
 pub use self::fcntl::*;
 pub use self::aio::*;
 pub use self::stat::*;
+pub use self::mman::*;
 
 // ============================================================================
 // Thread support
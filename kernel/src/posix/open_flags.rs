@@ -34,7 +34,7 @@ pub const O_SYNC: i32 = 0o10000;
 pub const O_TRUNC: i32 = 0o1000;
 
 /// Close on exec
-pub const O_CLOEXEC: i32 = 0o200000;
+pub const O_CLOEXEC: i32 = 0o2000000;
 
 /// Direct I/O access
 pub const O_DIRECT: i32 = 0o40000;
@@ -42,8 +42,26 @@ pub const O_DIRECT: i32 = 0o40000;
 /// Don't update file access time
 pub const O_NOATIME: i32 = 0o100000;
 
-/// Path is a symbolic link
-pub const O_NOFOLLOW: i32 = 0o200000;
+/// Fail if the final path component is a symbolic link
+pub const O_NOFOLLOW: i32 = 0o400000;
+
+/// Fail unless the path names a directory
+pub const O_DIRECTORY: i32 = 0o200000;
+
+/// `dirfd` value meaning "relative to the current working directory"
+pub const AT_FDCWD: i32 = -100;
+
+/// Don't follow a symbolic link in the final component
+pub const AT_SYMLINK_NOFOLLOW: i32 = 0x100;
+
+/// `unlinkat`: remove a directory instead of a file
+pub const AT_REMOVEDIR: i32 = 0x200;
+
+/// `linkat`: follow a symbolic link in the source path
+pub const AT_SYMLINK_FOLLOW: i32 = 0x400;
+
+/// An empty path names `dirfd` itself
+pub const AT_EMPTY_PATH: i32 = 0x1000;
 
 /// Message flags
 pub const MSG_DONTWAIT: i32 = 0o40;
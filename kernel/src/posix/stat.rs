@@ -40,3 +40,31 @@ pub struct Rlimit {
     pub rlim_cur: u64,
     pub rlim_max: u64,
 }
+
+impl Rlimit {
+    pub const fn new(rlim_cur: u64, rlim_max: u64) -> Self {
+        Self { rlim_cur, rlim_max }
+    }
+}
+
+/// No limit
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+// Resource numbers for getrlimit/setrlimit (Linux values)
+pub const RLIMIT_CPU: i32 = 0;        // CPU time in seconds
+pub const RLIMIT_FSIZE: i32 = 1;      // Largest file that may be written
+pub const RLIMIT_DATA: i32 = 2;       // Data segment size
+pub const RLIMIT_STACK: i32 = 3;      // Stack size
+pub const RLIMIT_CORE: i32 = 4;       // Core dump size
+pub const RLIMIT_RSS: i32 = 5;        // Resident set size
+pub const RLIMIT_NPROC: i32 = 6;      // Processes per real user ID
+pub const RLIMIT_NOFILE: i32 = 7;     // One more than the highest fd
+pub const RLIMIT_MEMLOCK: i32 = 8;    // Locked memory
+pub const RLIMIT_AS: i32 = 9;         // Address space size
+pub const RLIMIT_LOCKS: i32 = 10;     // File locks
+pub const RLIMIT_SIGPENDING: i32 = 11; // Queued signals
+pub const RLIMIT_MSGQUEUE: i32 = 12;  // POSIX message queue bytes
+pub const RLIMIT_NICE: i32 = 13;      // Nice value ceiling
+pub const RLIMIT_RTPRIO: i32 = 14;    // Real-time priority ceiling
+pub const RLIMIT_RTTIME: i32 = 15;    // Real-time CPU time in microseconds
+pub const RLIM_NLIMITS: usize = 16;
@@ -75,11 +75,13 @@ pub enum Capability {
     AuditControl = 28,
     /// Capability to set file attributes
     Setfcap = 29,
+    /// Capability to lock memory past RLIMIT_MEMLOCK
+    IpcLock = 30,
 }
 
 impl Capability {
     /// Get all capability values
-    pub const ALL: [Capability; 30] = [
+    pub const ALL: [Capability; 31] = [
         Capability::Chown,
         Capability::DACOverride,
         Capability::DACReadSearch,
@@ -110,6 +112,7 @@ impl Capability {
         Capability::AuditWrite,
         Capability::AuditControl,
         Capability::Setfcap,
+        Capability::IpcLock,
     ];
 
     /// Get capability name
@@ -145,6 +148,7 @@ impl Capability {
             Capability::AuditWrite => "CAP_AUDIT_WRITE",
             Capability::AuditControl => "CAP_AUDIT_CONTROL",
             Capability::Setfcap => "CAP_SETFCAP",
+            Capability::IpcLock => "CAP_IPC_LOCK",
         }
     }
 }
@@ -492,6 +496,18 @@ pub fn process_has_capability(pid: u64, cap: Capability) -> bool {
     guard.as_ref().map(|s| s.process_has_capability(pid, cap)).unwrap_or(false)
 }
 
+/// Check a capability for a process that may have no capability sets
+///
+/// Processes the subsystem is not tracking fall back to the traditional
+/// rule that an effective UID of 0 holds every capability.
+pub fn capable(pid: u64, euid: u32, cap: Capability) -> bool {
+    let guard = crate::security::CAPABILITIES.lock();
+    match guard.as_ref().and_then(|s| s.get_process_capabilities(pid)) {
+        Some(proc_caps) => proc_caps.caps.has_effective(cap),
+        None => euid == 0,
+    }
+}
+
 /// Get process capabilities
 pub fn get_process_capabilities(pid: u64) -> Option<ProcessCapabilities> {
     let guard = crate::security::CAPABILITIES.lock();
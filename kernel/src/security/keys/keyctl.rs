@@ -0,0 +1,179 @@
+//! add_key, request_key and keyctl
+//!
+//! Argument decoding and user memory access for the key system calls; the
+//! operations themselves live in the parent module.
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::process::{myproc, PROC_TABLE};
+use crate::subsystems::mm::vm::{copyin, copyinstr, copyout, PageTable};
+
+use super::{KeyError, KeySerial};
+
+pub const KEYCTL_GET_KEYRING_ID: u32 = 0;
+pub const KEYCTL_JOIN_SESSION_KEYRING: u32 = 1;
+pub const KEYCTL_UPDATE: u32 = 2;
+pub const KEYCTL_REVOKE: u32 = 3;
+pub const KEYCTL_CHOWN: u32 = 4;
+pub const KEYCTL_SETPERM: u32 = 5;
+pub const KEYCTL_DESCRIBE: u32 = 6;
+pub const KEYCTL_CLEAR: u32 = 7;
+pub const KEYCTL_LINK: u32 = 8;
+pub const KEYCTL_UNLINK: u32 = 9;
+pub const KEYCTL_SEARCH: u32 = 10;
+pub const KEYCTL_READ: u32 = 11;
+pub const KEYCTL_SET_REQKEY_KEYRING: u32 = 14;
+pub const KEYCTL_SET_TIMEOUT: u32 = 15;
+pub const KEYCTL_GET_SECURITY: u32 = 17;
+pub const KEYCTL_SESSION_TO_PARENT: u32 = 18;
+pub const KEYCTL_INVALIDATE: u32 = 21;
+
+/// Longest key type name, NUL included
+const TYPE_NAME_MAX: usize = 32;
+/// Longest description or callout string, NUL included
+const DESCRIPTION_MAX: usize = 4096;
+/// Largest payload add_key and KEYCTL_UPDATE accept
+const PAYLOAD_MAX: usize = 1024 * 1024 - 1;
+
+/// The calling process's address space
+struct UserMemory(*mut PageTable);
+
+impl UserMemory {
+    fn current() -> Result<Self, KeyError> {
+        let pid = myproc().ok_or(KeyError::Fault)?;
+        let table = PROC_TABLE.lock();
+        let pagetable = table.find_ref(pid).ok_or(KeyError::Fault)?.pagetable;
+        if pagetable.is_null() {
+            return Err(KeyError::Fault);
+        }
+        Ok(Self(pagetable))
+    }
+
+    fn string(&self, uaddr: usize, max: usize) -> Result<String, KeyError> {
+        let mut buf = vec![0u8; max];
+        let len = unsafe { copyinstr(self.0, uaddr, buf.as_mut_ptr(), max) }.map_err(|_| KeyError::Fault)?;
+        buf.truncate(len);
+        String::from_utf8(buf).map_err(|_| KeyError::InvalidArgument)
+    }
+
+    fn bytes(&self, uaddr: usize, len: usize) -> Result<Vec<u8>, KeyError> {
+        let mut buf = vec![0u8; len];
+        if len != 0 {
+            unsafe { copyin(self.0, buf.as_mut_ptr(), uaddr, len) }.map_err(|_| KeyError::Fault)?;
+        }
+        Ok(buf)
+    }
+
+    fn write(&self, uaddr: usize, data: &[u8]) -> Result<(), KeyError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        unsafe { copyout(self.0, uaddr, data.as_ptr(), data.len()) }.map_err(|_| KeyError::Fault)
+    }
+
+    /// Copy `data` out for KEYCTL_READ-style calls: as much as fits, and
+    /// the full length returned so the caller can size its buffer
+    fn write_truncated(&self, buf: usize, buflen: usize, data: &[u8]) -> Result<u64, KeyError> {
+        if buf != 0 && buflen != 0 {
+            self.write(buf, &data[..data.len().min(buflen)])?;
+        }
+        Ok(data.len() as u64)
+    }
+}
+
+/// `add_key(type, description, payload, plen, keyring)`
+pub fn add_key(type_ptr: usize, desc_ptr: usize, payload_ptr: usize, plen: usize, ring: KeySerial) -> Result<u64, KeyError> {
+    if plen > PAYLOAD_MAX {
+        return Err(KeyError::InvalidArgument);
+    }
+    let mem = UserMemory::current()?;
+    let ty = mem.string(type_ptr, TYPE_NAME_MAX)?;
+    let description = mem.string(desc_ptr, DESCRIPTION_MAX)?;
+    let mut payload = if payload_ptr != 0 { mem.bytes(payload_ptr, plen)? } else { Vec::new() };
+    let result = super::add_key(&ty, &description, &payload, ring);
+    crate::subsystems::crypto::zeroize(&mut payload);
+    result.map(|serial| serial as u64)
+}
+
+/// `request_key(type, description, callout_info, dest_keyring)`
+///
+/// Without upcalls `callout_info` is only checked for being readable.
+pub fn request_key(type_ptr: usize, desc_ptr: usize, callout_ptr: usize, dest: KeySerial) -> Result<u64, KeyError> {
+    let mem = UserMemory::current()?;
+    let ty = mem.string(type_ptr, TYPE_NAME_MAX)?;
+    let description = mem.string(desc_ptr, DESCRIPTION_MAX)?;
+    if callout_ptr != 0 {
+        mem.string(callout_ptr, DESCRIPTION_MAX)?;
+    }
+    super::request_key(&ty, &description, dest).map(|serial| serial as u64)
+}
+
+/// `keyctl(operation, arg2, arg3, arg4, arg5)`
+pub fn keyctl(op: u32, arg2: u64, arg3: u64, arg4: u64, arg5: u64) -> Result<u64, KeyError> {
+    let id = arg2 as KeySerial;
+    match op {
+        KEYCTL_GET_KEYRING_ID => super::get_keyring_id(id, arg3 != 0).map(|serial| serial as u64),
+        KEYCTL_JOIN_SESSION_KEYRING => {
+            let name = match arg2 {
+                0 => None,
+                ptr => Some(UserMemory::current()?.string(ptr as usize, DESCRIPTION_MAX)?),
+            };
+            super::join_session_keyring(name.as_deref()).map(|serial| serial as u64)
+        }
+        KEYCTL_UPDATE => {
+            let len = arg4 as usize;
+            if len > PAYLOAD_MAX {
+                return Err(KeyError::InvalidArgument);
+            }
+            let mut data = UserMemory::current()?.bytes(arg3 as usize, len)?;
+            let result = super::update(id, &data);
+            crate::subsystems::crypto::zeroize(&mut data);
+            result.map(|()| 0)
+        }
+        KEYCTL_REVOKE => super::revoke(id).map(|()| 0),
+        KEYCTL_CHOWN => {
+            // -1 leaves the owner or group unchanged
+            let uid = Some(arg3 as u32).filter(|&uid| uid != u32::MAX);
+            let gid = Some(arg4 as u32).filter(|&gid| gid != u32::MAX);
+            super::chown(id, uid, gid).map(|()| 0)
+        }
+        KEYCTL_SETPERM => super::setperm(id, arg3 as u32).map(|()| 0),
+        KEYCTL_DESCRIBE | KEYCTL_GET_SECURITY => {
+            let mut text = if op == KEYCTL_DESCRIBE { super::describe(id)? } else { super::get_security(id)? };
+            text.push('\0');
+            // The string is copied only if it fits whole
+            let (buf, buflen) = (arg3 as usize, arg4 as usize);
+            if buf != 0 && buflen >= text.len() {
+                UserMemory::current()?.write(buf, text.as_bytes())?;
+            }
+            Ok(text.len() as u64)
+        }
+        KEYCTL_CLEAR => super::clear(id).map(|()| 0),
+        KEYCTL_LINK => super::link(id, arg3 as KeySerial).map(|()| 0),
+        KEYCTL_UNLINK => super::unlink(id, arg3 as KeySerial).map(|()| 0),
+        KEYCTL_SEARCH => {
+            let mem = UserMemory::current()?;
+            let ty = mem.string(arg3 as usize, TYPE_NAME_MAX)?;
+            let description = mem.string(arg4 as usize, DESCRIPTION_MAX)?;
+            super::search(id, &ty, &description, arg5 as KeySerial).map(|serial| serial as u64)
+        }
+        KEYCTL_READ => {
+            let mut payload = super::read(id)?;
+            let result = UserMemory::current().and_then(|mem| mem.write_truncated(arg3 as usize, arg4 as usize, &payload));
+            crate::subsystems::crypto::zeroize(&mut payload);
+            result
+        }
+        KEYCTL_SET_REQKEY_KEYRING => super::set_reqkey_keyring(id).map(|old| old as u64),
+        KEYCTL_SET_TIMEOUT => super::set_timeout(id, arg3 as u32).map(|()| 0),
+        KEYCTL_SESSION_TO_PARENT => super::session_to_parent().map(|()| 0),
+        KEYCTL_INVALIDATE => super::invalidate(id).map(|()| 0),
+        // Instantiation and authority operations belong to request-key
+        // upcalls, which do not exist here; DH, PKEY and restriction
+        // operations need key types that do not either
+        _ => Err(KeyError::NotSupported),
+    }
+}
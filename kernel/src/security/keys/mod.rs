@@ -0,0 +1,950 @@
+//! Kernel key retention service
+//!
+//! Keys are small secrets (passwords, tokens, filesystem encryption keys)
+//! that the kernel holds for user space and for other kernel subsystems.
+//! Each key has a serial number, a type, a description, an owner, a
+//! permission mask and an optional expiry time. A keyring is a key whose
+//! payload is a set of links to other keys. Tasks reach their keys through
+//! special keyrings:
+//!
+//! - thread keyring: private to one thread, dropped on exec
+//! - process keyring: shared by the threads of a process, dropped on exec
+//! - session keyring: inherited across fork and exec, replaced by
+//!   `KEYCTL_JOIN_SESSION_KEYRING`; without one, the user session keyring
+//!   stands in
+//! - user and user session keyrings: shared by everything running as that
+//!   UID; the user session keyring links the user keyring
+//!
+//! A key is *possessed* when a search from those keyrings can reach it, and
+//! possessor permission bits then apply on top of the user, group and other
+//! bits. Every key is charged to its owner's quota. There is no
+//! `request-key` upcall: `request_key` only finds keys that already exist.
+//!
+//! Lock order is `REGISTRY`, then one key's state at a time; `QUOTA` is
+//! always taken last. A keyring's links are copied out before they are
+//! searched, so no two key locks are ever held together.
+
+extern crate alloc;
+
+pub mod keyctl;
+pub mod types;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use crate::process::{Pid, PROC_TABLE};
+use crate::security::capabilities::{capable, Capability};
+use crate::security::seccomp::TaskId;
+use crate::subsystems::sync::Mutex;
+
+pub use types::{KeyType, Payload};
+
+/// Serial number of a key; negative values name special keyrings
+pub type KeySerial = i32;
+
+pub const KEY_SPEC_THREAD_KEYRING: KeySerial = -1;
+pub const KEY_SPEC_PROCESS_KEYRING: KeySerial = -2;
+pub const KEY_SPEC_SESSION_KEYRING: KeySerial = -3;
+pub const KEY_SPEC_USER_KEYRING: KeySerial = -4;
+pub const KEY_SPEC_USER_SESSION_KEYRING: KeySerial = -5;
+pub const KEY_SPEC_GROUP_KEYRING: KeySerial = -6;
+pub const KEY_SPEC_REQKEY_AUTH_KEY: KeySerial = -7;
+
+/// Permissions, granted separately to the possessor, the owner, the
+/// group and everyone else
+pub const KEY_VIEW: u32 = 0x01;
+pub const KEY_READ: u32 = 0x02;
+pub const KEY_WRITE: u32 = 0x04;
+pub const KEY_SEARCH: u32 = 0x08;
+pub const KEY_LINK: u32 = 0x10;
+pub const KEY_SETATTR: u32 = 0x20;
+pub const KEY_ALL: u32 = 0x3f;
+
+pub const KEY_POS_ALL: u32 = KEY_ALL << 24;
+pub const KEY_USR_ALL: u32 = KEY_ALL << 16;
+pub const KEY_GRP_ALL: u32 = KEY_ALL << 8;
+pub const KEY_OTH_ALL: u32 = KEY_ALL;
+
+/// `KEYCTL_SET_REQKEY_KEYRING` settings
+pub const KEY_REQKEY_DEFL_NO_CHANGE: i32 = -1;
+pub const KEY_REQKEY_DEFL_DEFAULT: i32 = 0;
+pub const KEY_REQKEY_DEFL_GROUP_KEYRING: i32 = 6;
+pub const KEY_REQKEY_DEFL_REQUESTOR_KEYRING: i32 = 7;
+
+/// How deep searches descend into nested keyrings
+const KEYRING_SEARCH_MAX_DEPTH: usize = 6;
+
+/// Keys and payload bytes each UID may own; root gets more
+const QUOTA_MAXKEYS: usize = 200;
+const QUOTA_MAXBYTES: usize = 20000;
+const ROOT_QUOTA_MAXKEYS: usize = 1_000_000;
+const ROOT_QUOTA_MAXBYTES: usize = 25_000_000;
+
+/// Lookup flags: create a missing special keyring; accept revoked and
+/// expired keys
+const LOOKUP_CREATE: u32 = 0x1;
+const LOOKUP_PARTIAL: u32 = 0x2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyError {
+    /// No such key, or it was invalidated (ENOKEY)
+    NotFound,
+    /// The key's timeout passed (EKEYEXPIRED)
+    Expired,
+    /// The key was revoked (EKEYREVOKED)
+    Revoked,
+    /// The key exists but cannot serve this request (EKEYREJECTED)
+    Rejected,
+    /// The permission mask does not allow it (EACCES)
+    AccessDenied,
+    /// Only the owner or a privileged task may do this (EPERM)
+    NotPermitted,
+    /// Malformed request (EINVAL)
+    InvalidArgument,
+    /// The key type or operation is not supported (EOPNOTSUPP)
+    NotSupported,
+    /// A keyring was needed (ENOTDIR)
+    NotKeyring,
+    /// The key is not linked to that keyring (ENOENT)
+    NotLinked,
+    /// The link would make a keyring contain itself (EDEADLK)
+    Cycle,
+    /// The owner's key quota is used up (EDQUOT)
+    QuotaExceeded,
+    /// Bad user pointer (EFAULT)
+    Fault,
+}
+
+/// A key
+pub struct Key {
+    serial: KeySerial,
+    ty: KeyType,
+    description: String,
+    state: Mutex<KeyState>,
+}
+
+struct KeyState {
+    uid: u32,
+    gid: u32,
+    perm: u32,
+    /// `timestamp_nanos` after which the key is expired
+    expiry: Option<u64>,
+    revoked: bool,
+    invalidated: bool,
+    payload: Payload,
+    /// Keyrings only
+    links: Vec<Arc<Key>>,
+    /// Bytes charged to `uid`'s quota
+    charged: usize,
+}
+
+impl KeyState {
+    fn check_live(&self) -> Result<(), KeyError> {
+        if self.invalidated {
+            Err(KeyError::NotFound)
+        } else if self.revoked {
+            Err(KeyError::Revoked)
+        } else if self.expiry.is_some_and(|t| crate::subsystems::time::timestamp_nanos() >= t) {
+            Err(KeyError::Expired)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn permits(&self, cred: &Cred, possessed: bool, need: u32) -> bool {
+        let perm = self.perm;
+        let mut granted = if self.uid == cred.uid {
+            perm >> 16
+        } else if self.gid == cred.gid {
+            perm >> 8
+        } else {
+            perm
+        };
+        if possessed {
+            granted |= perm >> 24;
+        }
+        granted & need & KEY_ALL == need
+    }
+}
+
+impl Key {
+    pub fn serial(&self) -> KeySerial {
+        self.serial
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        self.ty
+    }
+
+    /// Links of a keyring, minus invalidated keys
+    fn links(&self) -> Vec<Arc<Key>> {
+        let links = self.state.lock().links.clone();
+        links.into_iter().filter(|key| !key.state.lock().invalidated).collect()
+    }
+
+    fn same_index(&self, other: &Key) -> bool {
+        self.ty == other.ty && self.description == other.description
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        let state = self.state.lock();
+        uncharge(state.uid, state.charged);
+    }
+}
+
+/// The caller as keys see it: the filesystem UID and GID
+#[derive(Debug, Clone, Copy)]
+struct Cred {
+    task: TaskId,
+    uid: u32,
+    gid: u32,
+}
+
+impl Cred {
+    fn current() -> Result<Self, KeyError> {
+        let task = TaskId::current().ok_or(KeyError::NotPermitted)?;
+        let table = PROC_TABLE.lock();
+        let proc = table.find_ref(task.pid as Pid).ok_or(KeyError::NotPermitted)?;
+        Ok(Self { task, uid: proc.euid, gid: proc.egid })
+    }
+
+    fn privileged(&self) -> bool {
+        capable(self.task.pid, self.uid, Capability::SysAdmin)
+    }
+}
+
+/// Keys and bytes each UID owns
+static QUOTA: Mutex<BTreeMap<u32, (usize, usize)>> = Mutex::new(BTreeMap::new());
+
+fn charge(uid: u32, keys: usize, bytes: usize, enforce: bool) -> Result<(), KeyError> {
+    let mut quota = QUOTA.lock();
+    let used = quota.entry(uid).or_insert((0, 0));
+    let (maxkeys, maxbytes) = if uid == 0 {
+        (ROOT_QUOTA_MAXKEYS, ROOT_QUOTA_MAXBYTES)
+    } else {
+        (QUOTA_MAXKEYS, QUOTA_MAXBYTES)
+    };
+    if enforce && (used.0 + keys > maxkeys || used.1 + bytes > maxbytes) {
+        return Err(KeyError::QuotaExceeded);
+    }
+    used.0 += keys;
+    used.1 += bytes;
+    Ok(())
+}
+
+fn uncharge_bytes(uid: u32, bytes: usize) {
+    if let Some(used) = QUOTA.lock().get_mut(&uid) {
+        used.1 = used.1.saturating_sub(bytes);
+    }
+}
+
+fn uncharge(uid: u32, bytes: usize) {
+    let mut quota = QUOTA.lock();
+    if let Some(used) = quota.get_mut(&uid) {
+        used.0 = used.0.saturating_sub(1);
+        used.1 = used.1.saturating_sub(bytes);
+        if *used == (0, 0) {
+            quota.remove(&uid);
+        }
+    }
+}
+
+/// Keyrings of one process
+#[derive(Default)]
+struct ProcessKeyrings {
+    process: Option<Arc<Key>>,
+    session: Option<Arc<Key>>,
+    reqkey_default: i32,
+}
+
+struct Registry {
+    next_serial: KeySerial,
+    keys: BTreeMap<KeySerial, Weak<Key>>,
+    /// User keyring and user session keyring of each UID
+    users: BTreeMap<u32, (Arc<Key>, Arc<Key>)>,
+    threads: BTreeMap<TaskId, Arc<Key>>,
+    processes: BTreeMap<u64, ProcessKeyrings>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry::new());
+
+impl Registry {
+    const fn new() -> Self {
+        Self {
+            next_serial: 1,
+            keys: BTreeMap::new(),
+            users: BTreeMap::new(),
+            threads: BTreeMap::new(),
+            processes: BTreeMap::new(),
+        }
+    }
+
+    /// Create a key charged to `uid`
+    ///
+    /// Special keyrings are charged but never refused for quota.
+    #[allow(clippy::too_many_arguments)]
+    fn alloc(
+        &mut self,
+        uid: u32,
+        gid: u32,
+        ty: KeyType,
+        description: String,
+        perm: u32,
+        payload: Payload,
+        enforce_quota: bool,
+    ) -> Result<Arc<Key>, KeyError> {
+        let charged = description.len() + payload.quota_len();
+        charge(uid, 1, charged, enforce_quota)?;
+        // Serials are positive and never reused while the key lives
+        if self.keys.len() % 64 == 0 {
+            self.keys.retain(|_, key| key.strong_count() > 0);
+        }
+        let mut serial = self.next_serial;
+        while self.keys.get(&serial).is_some_and(|key| key.strong_count() > 0) {
+            serial = serial.checked_add(1).unwrap_or(1);
+        }
+        self.next_serial = serial.checked_add(1).unwrap_or(1);
+        let key = Arc::new(Key {
+            serial,
+            ty,
+            description,
+            state: Mutex::new(KeyState {
+                uid,
+                gid,
+                perm,
+                expiry: None,
+                revoked: false,
+                invalidated: false,
+                payload,
+                links: Vec::new(),
+                charged,
+            }),
+        });
+        self.keys.insert(serial, Arc::downgrade(&key));
+        Ok(key)
+    }
+
+    fn new_keyring(&mut self, cred: &Cred, description: &str, perm: u32) -> Arc<Key> {
+        self.alloc(cred.uid, cred.gid, KeyType::Keyring, String::from(description), perm, Payload::None, false)
+            .expect("special keyrings are not quota-limited")
+    }
+
+    /// User keyring and user session keyring of `cred`'s UID
+    fn user_keyrings(&mut self, cred: &Cred) -> (Arc<Key>, Arc<Key>) {
+        if let Some((user, session)) = self.users.get(&cred.uid) {
+            return (user.clone(), session.clone());
+        }
+        let perm = KEY_POS_ALL | KEY_USR_ALL;
+        let user = self.new_keyring(cred, &format!("_uid.{}", cred.uid), perm);
+        let session = self.new_keyring(cred, &format!("_uid_ses.{}", cred.uid), perm);
+        session.state.lock().links.push(user.clone());
+        self.users.insert(cred.uid, (user.clone(), session.clone()));
+        (user, session)
+    }
+
+    /// Resolve a `KEY_SPEC_*` serial
+    fn special(&mut self, cred: &Cred, id: KeySerial, create: bool) -> Result<Arc<Key>, KeyError> {
+        let pid = cred.task.pid;
+        match id {
+            KEY_SPEC_THREAD_KEYRING => {
+                if let Some(ring) = self.threads.get(&cred.task) {
+                    return Ok(ring.clone());
+                }
+                if !create {
+                    return Err(KeyError::NotFound);
+                }
+                let ring = self.new_keyring(cred, "_tid", KEY_POS_ALL | (KEY_VIEW << 16));
+                self.threads.insert(cred.task, ring.clone());
+                Ok(ring)
+            }
+            KEY_SPEC_PROCESS_KEYRING => {
+                if let Some(ring) = self.processes.get(&pid).and_then(|p| p.process.clone()) {
+                    return Ok(ring);
+                }
+                if !create {
+                    return Err(KeyError::NotFound);
+                }
+                let ring = self.new_keyring(cred, "_pid", KEY_POS_ALL | (KEY_VIEW << 16));
+                self.processes.entry(pid).or_default().process = Some(ring.clone());
+                Ok(ring)
+            }
+            KEY_SPEC_SESSION_KEYRING => {
+                if let Some(ring) = self.processes.get(&pid).and_then(|p| p.session.clone()) {
+                    return Ok(ring);
+                }
+                // Always install one on first use: a fresh anonymous session
+                // when asked to create, the user session keyring otherwise
+                let ring = if create {
+                    self.new_keyring(cred, "_ses", KEY_POS_ALL | ((KEY_VIEW | KEY_READ) << 16))
+                } else {
+                    self.user_keyrings(cred).1
+                };
+                self.processes.entry(pid).or_default().session = Some(ring.clone());
+                Ok(ring)
+            }
+            KEY_SPEC_USER_KEYRING => Ok(self.user_keyrings(cred).0),
+            KEY_SPEC_USER_SESSION_KEYRING => Ok(self.user_keyrings(cred).1),
+            // No group keyrings, and no request_key authorisation keys
+            // without upcalls
+            KEY_SPEC_REQKEY_AUTH_KEY => Err(KeyError::NotFound),
+            _ => Err(KeyError::InvalidArgument),
+        }
+    }
+
+    /// Keyrings searched on the caller's behalf, in order
+    fn task_keyrings(&mut self, cred: &Cred) -> Vec<Arc<Key>> {
+        let mut rings = Vec::new();
+        if let Some(ring) = self.threads.get(&cred.task) {
+            rings.push(ring.clone());
+        }
+        let process = self.processes.get(&cred.task.pid);
+        if let Some(ring) = process.and_then(|p| p.process.clone()) {
+            rings.push(ring);
+        }
+        match process.and_then(|p| p.session.clone()) {
+            Some(ring) => rings.push(ring),
+            None => rings.push(self.user_keyrings(cred).1),
+        }
+        rings
+    }
+
+    fn possessed(&mut self, cred: &Cred, key: &Arc<Key>) -> bool {
+        self.task_keyrings(cred).iter().any(|ring| reachable(ring, key, cred, 0))
+    }
+
+    /// Find a key by serial, with `need` permission
+    ///
+    /// Returns the key and whether the caller possesses it.
+    fn lookup(&mut self, cred: &Cred, id: KeySerial, flags: u32, need: u32) -> Result<(Arc<Key>, bool), KeyError> {
+        let (key, possessed) = if id < 0 {
+            (self.special(cred, id, flags & LOOKUP_CREATE != 0)?, true)
+        } else {
+            let key = self.keys.get(&id).and_then(Weak::upgrade).ok_or(KeyError::NotFound)?;
+            let possessed = self.possessed(cred, &key);
+            (key, possessed)
+        };
+        let state = key.state.lock();
+        if flags & LOOKUP_PARTIAL == 0 {
+            state.check_live()?;
+        } else if state.invalidated {
+            return Err(KeyError::NotFound);
+        }
+        if !state.permits(cred, possessed, need) {
+            return Err(KeyError::AccessDenied);
+        }
+        drop(state);
+        Ok((key, possessed))
+    }
+
+    fn lookup_keyring(&mut self, cred: &Cred, id: KeySerial, flags: u32, need: u32) -> Result<Arc<Key>, KeyError> {
+        let (ring, _) = self.lookup(cred, id, flags, need)?;
+        if ring.ty != KeyType::Keyring {
+            return Err(KeyError::NotKeyring);
+        }
+        Ok(ring)
+    }
+
+    /// Search the caller's thread, process and session keyrings
+    fn search_task_keyrings(&mut self, cred: &Cred, ty: KeyType, description: &str) -> Result<Arc<Key>, KeyError> {
+        let mut err = KeyError::NotFound;
+        for ring in self.task_keyrings(cred) {
+            if let Some(key) = find(&ring, cred, true, ty, description, 0, &mut err) {
+                return Ok(key);
+            }
+        }
+        Err(err)
+    }
+}
+
+/// Whether `target` can be found below `ring` by a search as `cred`
+fn reachable(ring: &Arc<Key>, target: &Arc<Key>, cred: &Cred, depth: usize) -> bool {
+    if Arc::ptr_eq(ring, target) {
+        return true;
+    }
+    if ring.ty != KeyType::Keyring || depth >= KEYRING_SEARCH_MAX_DEPTH {
+        return false;
+    }
+    {
+        let state = ring.state.lock();
+        if state.check_live().is_err() || !state.permits(cred, true, KEY_SEARCH) {
+            return false;
+        }
+    }
+    ring.links().iter().any(|link| reachable(link, target, cred, depth + 1))
+}
+
+/// Search below `ring` for a live, searchable key of type `ty` and
+/// `description`; keys directly in a keyring win over nested ones
+///
+/// The most telling reason for skipping a match ends up in `err`.
+fn find(
+    ring: &Arc<Key>,
+    cred: &Cred,
+    possessed: bool,
+    ty: KeyType,
+    description: &str,
+    depth: usize,
+    err: &mut KeyError,
+) -> Option<Arc<Key>> {
+    {
+        let state = ring.state.lock();
+        if state.check_live().is_err() || !state.permits(cred, possessed, KEY_SEARCH) {
+            return None;
+        }
+    }
+    let links = ring.links();
+    for key in links.iter().filter(|key| key.ty == ty && key.description == description) {
+        let state = key.state.lock();
+        match state.check_live() {
+            Ok(()) if state.permits(cred, possessed, KEY_SEARCH) => return Some(key.clone()),
+            Ok(()) => *err = KeyError::AccessDenied,
+            Err(e) => *err = e,
+        }
+    }
+    if depth + 1 >= KEYRING_SEARCH_MAX_DEPTH {
+        return None;
+    }
+    links
+        .iter()
+        .filter(|link| link.ty == KeyType::Keyring)
+        .find_map(|link| find(link, cred, possessed, ty, description, depth + 1, err))
+}
+
+/// Whether `ring` contains `needle` at any depth, whatever the permissions
+fn contains(ring: &Arc<Key>, needle: &Arc<Key>) -> bool {
+    Arc::ptr_eq(ring, needle)
+        || (ring.ty == KeyType::Keyring && ring.links().iter().any(|link| contains(link, needle)))
+}
+
+/// Link `key` into `ring`, displacing a key of the same type and description
+fn link_into(ring: &Arc<Key>, key: &Arc<Key>) -> Result<(), KeyError> {
+    if ring.ty != KeyType::Keyring {
+        return Err(KeyError::NotKeyring);
+    }
+    key.state.lock().check_live()?;
+    if key.ty == KeyType::Keyring && contains(key, ring) {
+        return Err(KeyError::Cycle);
+    }
+    let mut state = ring.state.lock();
+    state.check_live()?;
+    state.links.retain(|link| !link.same_index(key));
+    state.links.push(key.clone());
+    Ok(())
+}
+
+/// Secret of the `user` key named `description`, for wrapping
+/// `encrypted` keys
+fn master_secret(cred: &Cred, description: &str) -> Result<Vec<u8>, KeyError> {
+    let key = REGISTRY.lock().search_task_keyrings(cred, KeyType::User, description)?;
+    let state = key.state.lock();
+    state.payload.secret().map(<[u8]>::to_vec).ok_or(KeyError::Revoked)
+}
+
+/// Replace the payload of `key` with one built from `data`
+///
+/// The new payload is built without any lock held, since wrapping an
+/// `encrypted` key searches for its master key.
+fn update_payload(cred: &Cred, key: &Arc<Key>, data: &[u8]) -> Result<(), KeyError> {
+    if !key.ty.updatable() {
+        return Err(KeyError::NotSupported);
+    }
+    let master = |name: &str| master_secret(cred, name);
+    let payload = match key.ty {
+        KeyType::Encrypted => {
+            let old = key.state.lock().payload.clone();
+            types::update(key.ty, &old, data, &master)?
+        }
+        _ => types::update(key.ty, &Payload::None, data, &master)?,
+    };
+    let mut state = key.state.lock();
+    state.check_live()?;
+    let charged = key.description.len() + payload.quota_len();
+    if charged > state.charged {
+        charge(state.uid, 0, charged - state.charged, true)?;
+    } else {
+        uncharge_bytes(state.uid, state.charged - charged);
+    }
+    state.charged = charged;
+    state.payload = payload;
+    Ok(())
+}
+
+/// Default permissions of a key made by add_key
+fn default_perm(ty: KeyType) -> u32 {
+    let mut perm = ((KEY_VIEW | KEY_SEARCH | KEY_LINK | KEY_SETATTR) << 24) | (KEY_VIEW << 16);
+    if ty.readable() {
+        perm |= KEY_READ << 24;
+    }
+    if ty == KeyType::Keyring || ty.updatable() {
+        perm |= KEY_WRITE << 24;
+    }
+    perm
+}
+
+/// Create a key, or update the one of the same type and description
+/// already in `ring`
+pub fn add_key(ty: &str, description: &str, data: &[u8], ring: KeySerial) -> Result<KeySerial, KeyError> {
+    let ty = KeyType::from_name(ty)?;
+    ty.check_description(description)?;
+    let cred = Cred::current()?;
+    let (ring, possessed) = {
+        let mut registry = REGISTRY.lock();
+        let ring = registry.lookup_keyring(&cred, ring, LOOKUP_CREATE, KEY_WRITE)?;
+        let possessed = registry.possessed(&cred, &ring);
+        (ring, possessed)
+    };
+
+    if ty.updatable() {
+        let existing = ring.links().into_iter().find(|key| key.ty == ty && key.description == description);
+        if let Some(key) = existing {
+            let writable = {
+                let state = key.state.lock();
+                state.check_live().is_ok() && state.permits(&cred, possessed, KEY_WRITE)
+            };
+            if writable {
+                update_payload(&cred, &key, data)?;
+                return Ok(key.serial);
+            }
+        }
+    }
+
+    let payload = types::instantiate(ty, data, &|name: &str| master_secret(&cred, name))?;
+    let mut registry = REGISTRY.lock();
+    let key = registry.alloc(cred.uid, cred.gid, ty, String::from(description), default_perm(ty), payload, true)?;
+    link_into(&ring, &key)?;
+    Ok(key.serial)
+}
+
+/// Find a key in the caller's keyrings, linking it into `dest` if non-zero
+pub fn request_key(ty: &str, description: &str, dest: KeySerial) -> Result<KeySerial, KeyError> {
+    let ty = KeyType::from_name(ty)?;
+    ty.check_description(description)?;
+    let cred = Cred::current()?;
+    let mut registry = REGISTRY.lock();
+    let dest = match dest {
+        0 => None,
+        id => Some(registry.lookup_keyring(&cred, id, LOOKUP_CREATE, KEY_WRITE)?),
+    };
+    let key = registry.search_task_keyrings(&cred, ty, description)?;
+    if let Some(dest) = dest {
+        link_into(&dest, &key)?;
+    }
+    Ok(key.serial)
+}
+
+/// Serial of a key, creating a missing special keyring if `create`
+pub fn get_keyring_id(id: KeySerial, create: bool) -> Result<KeySerial, KeyError> {
+    let cred = Cred::current()?;
+    let flags = if create { LOOKUP_CREATE } else { 0 };
+    let (key, _) = REGISTRY.lock().lookup(&cred, id, flags, KEY_SEARCH)?;
+    Ok(key.serial)
+}
+
+/// Make the named keyring, or a new anonymous one, the session keyring
+pub fn join_session_keyring(name: Option<&str>) -> Result<KeySerial, KeyError> {
+    let cred = Cred::current()?;
+    let mut registry = REGISTRY.lock();
+    let ring = match name {
+        None => registry.new_keyring(&cred, "_ses", KEY_POS_ALL | ((KEY_VIEW | KEY_READ) << 16)),
+        Some(name) if name.starts_with('.') => return Err(KeyError::NotPermitted),
+        Some(name) => {
+            let existing = registry.keys.values().filter_map(Weak::upgrade).find(|key| {
+                let state = key.state.lock();
+                key.ty == KeyType::Keyring
+                    && key.description == name
+                    && state.uid == cred.uid
+                    && state.check_live().is_ok()
+                    && state.permits(&cred, false, KEY_SEARCH)
+            });
+            match existing {
+                Some(ring) => ring,
+                None => {
+                    let perm = KEY_POS_ALL | ((KEY_VIEW | KEY_READ | KEY_LINK) << 16);
+                    registry.alloc(cred.uid, cred.gid, KeyType::Keyring, String::from(name), perm, Payload::None, true)?
+                }
+            }
+        }
+    };
+    registry.processes.entry(cred.task.pid).or_default().session = Some(ring.clone());
+    Ok(ring.serial)
+}
+
+/// Replace a key's payload
+pub fn update(id: KeySerial, data: &[u8]) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let (key, _) = REGISTRY.lock().lookup(&cred, id, 0, KEY_WRITE)?;
+    update_payload(&cred, &key, data)
+}
+
+/// Revoke a key: every later use fails with EKEYREVOKED
+pub fn revoke(id: KeySerial) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let (key, possessed) = REGISTRY.lock().lookup(&cred, id, 0, 0)?;
+    let mut state = key.state.lock();
+    if !state.permits(&cred, possessed, KEY_WRITE) && !state.permits(&cred, possessed, KEY_SETATTR) {
+        return Err(KeyError::AccessDenied);
+    }
+    state.revoked = true;
+    state.payload = Payload::None;
+    let links = core::mem::take(&mut state.links);
+    drop(state);
+    drop(links);
+    Ok(())
+}
+
+/// Invalidate a key: it disappears from every keyring at once
+pub fn invalidate(id: KeySerial) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let mut registry = REGISTRY.lock();
+    let (key, _) = registry.lookup(&cred, id, 0, KEY_SEARCH)?;
+    registry.keys.remove(&key.serial);
+    let mut state = key.state.lock();
+    state.invalidated = true;
+    state.payload = Payload::None;
+    let links = core::mem::take(&mut state.links);
+    drop(state);
+    drop(links);
+    Ok(())
+}
+
+/// Change a key's owner or group; `None` leaves it alone
+pub fn chown(id: KeySerial, uid: Option<u32>, gid: Option<u32>) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let (key, _) = REGISTRY.lock().lookup(&cred, id, LOOKUP_PARTIAL, KEY_SETATTR)?;
+    let privileged = cred.privileged();
+    let mut state = key.state.lock();
+    let uid = uid.filter(|&uid| uid != state.uid);
+    let gid = gid.filter(|&gid| gid != state.gid);
+    // Giving a key away takes privilege; the owner may move it to their
+    // own group
+    if !privileged && (uid.is_some() || gid.is_some_and(|gid| state.uid != cred.uid || gid != cred.gid)) {
+        return Err(KeyError::AccessDenied);
+    }
+    if let Some(uid) = uid {
+        charge(uid, 1, state.charged, uid != 0)?;
+        uncharge(state.uid, state.charged);
+        state.uid = uid;
+    }
+    if let Some(gid) = gid {
+        state.gid = gid;
+    }
+    Ok(())
+}
+
+/// Replace a key's permission mask
+pub fn setperm(id: KeySerial, perm: u32) -> Result<(), KeyError> {
+    if perm & !(KEY_POS_ALL | KEY_USR_ALL | KEY_GRP_ALL | KEY_OTH_ALL) != 0 {
+        return Err(KeyError::InvalidArgument);
+    }
+    let cred = Cred::current()?;
+    let (key, _) = REGISTRY.lock().lookup(&cred, id, LOOKUP_PARTIAL, KEY_SETATTR)?;
+    let privileged = cred.privileged();
+    let mut state = key.state.lock();
+    if state.uid != cred.uid && !privileged {
+        return Err(KeyError::AccessDenied);
+    }
+    state.perm = perm;
+    Ok(())
+}
+
+/// `type;uid;gid;perm;description`, as KEYCTL_DESCRIBE reports it
+pub fn describe(id: KeySerial) -> Result<String, KeyError> {
+    let cred = Cred::current()?;
+    let (key, _) = REGISTRY.lock().lookup(&cred, id, LOOKUP_PARTIAL, KEY_VIEW)?;
+    let state = key.state.lock();
+    Ok(format!("{};{};{};{:08x};{}", key.ty.name(), state.uid as i32, state.gid as i32, state.perm, key.description))
+}
+
+/// Security label of a key; there is no LSM to attach one
+pub fn get_security(id: KeySerial) -> Result<String, KeyError> {
+    let cred = Cred::current()?;
+    REGISTRY.lock().lookup(&cred, id, LOOKUP_PARTIAL, KEY_VIEW)?;
+    Ok(String::new())
+}
+
+/// Unlink everything from a keyring
+pub fn clear(ring: KeySerial) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let ring = REGISTRY.lock().lookup_keyring(&cred, ring, 0, KEY_WRITE)?;
+    let links = core::mem::take(&mut ring.state.lock().links);
+    drop(links);
+    Ok(())
+}
+
+/// Link a key into a keyring
+pub fn link(id: KeySerial, ring: KeySerial) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let mut registry = REGISTRY.lock();
+    let (key, _) = registry.lookup(&cred, id, LOOKUP_CREATE, KEY_LINK)?;
+    let ring = registry.lookup_keyring(&cred, ring, LOOKUP_CREATE, KEY_WRITE)?;
+    link_into(&ring, &key)
+}
+
+/// Remove a key's link from a keyring
+pub fn unlink(id: KeySerial, ring: KeySerial) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let mut registry = REGISTRY.lock();
+    let ring = registry.lookup_keyring(&cred, ring, 0, KEY_WRITE)?;
+    let (key, _) = registry.lookup(&cred, id, LOOKUP_PARTIAL, 0)?;
+    let mut state = ring.state.lock();
+    let position = state.links.iter().position(|link| Arc::ptr_eq(link, &key)).ok_or(KeyError::NotLinked)?;
+    let link = state.links.remove(position);
+    drop(state);
+    drop(link);
+    Ok(())
+}
+
+/// Search a keyring tree, linking the key found into `dest` if non-zero
+pub fn search(ring: KeySerial, ty: &str, description: &str, dest: KeySerial) -> Result<KeySerial, KeyError> {
+    let ty = KeyType::from_name(ty)?;
+    let cred = Cred::current()?;
+    let mut registry = REGISTRY.lock();
+    let (ring, possessed) = registry.lookup(&cred, ring, 0, KEY_SEARCH)?;
+    if ring.ty != KeyType::Keyring {
+        return Err(KeyError::NotKeyring);
+    }
+    let dest = match dest {
+        0 => None,
+        id => Some(registry.lookup_keyring(&cred, id, LOOKUP_CREATE, KEY_WRITE)?),
+    };
+    let mut err = KeyError::NotFound;
+    let key = find(&ring, &cred, possessed, ty, description, 0, &mut err).ok_or(err)?;
+    if let Some(dest) = dest {
+        link_into(&dest, &key)?;
+    }
+    Ok(key.serial)
+}
+
+/// Payload as KEYCTL_READ returns it; for keyrings, the linked serials
+pub fn read(id: KeySerial) -> Result<Vec<u8>, KeyError> {
+    let cred = Cred::current()?;
+    let (key, possessed) = REGISTRY.lock().lookup(&cred, id, 0, 0)?;
+    let state = key.state.lock();
+    // Possessors may read what they can search
+    if !state.permits(&cred, possessed, KEY_READ) && !(possessed && state.permits(&cred, true, KEY_SEARCH)) {
+        return Err(KeyError::AccessDenied);
+    }
+    if key.ty != KeyType::Keyring {
+        return types::read(key.ty, &state.payload);
+    }
+    drop(state);
+    Ok(key.links().iter().flat_map(|link| link.serial.to_ne_bytes()).collect())
+}
+
+/// Set where request_key links keys it constructs; returns the old setting
+pub fn set_reqkey_keyring(which: i32) -> Result<i32, KeyError> {
+    let cred = Cred::current()?;
+    let mut registry = REGISTRY.lock();
+    let process = registry.processes.entry(cred.task.pid).or_default();
+    let old = process.reqkey_default;
+    match which {
+        KEY_REQKEY_DEFL_NO_CHANGE => {}
+        KEY_REQKEY_DEFL_GROUP_KEYRING => return Err(KeyError::InvalidArgument),
+        KEY_REQKEY_DEFL_DEFAULT..=KEY_REQKEY_DEFL_REQUESTOR_KEYRING => process.reqkey_default = which,
+        _ => return Err(KeyError::InvalidArgument),
+    }
+    Ok(old)
+}
+
+/// Expire a key `seconds` from now; 0 clears the timeout
+pub fn set_timeout(id: KeySerial, seconds: u32) -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let (key, _) = REGISTRY.lock().lookup(&cred, id, LOOKUP_PARTIAL, KEY_SETATTR)?;
+    let mut state = key.state.lock();
+    if state.revoked {
+        return Err(KeyError::Revoked);
+    }
+    state.expiry = match seconds {
+        0 => None,
+        s => Some(crate::subsystems::time::timestamp_nanos() + u64::from(s) * 1_000_000_000),
+    };
+    Ok(())
+}
+
+/// Give the caller's parent the caller's session keyring
+pub fn session_to_parent() -> Result<(), KeyError> {
+    let cred = Cred::current()?;
+    let parent = {
+        let table = PROC_TABLE.lock();
+        let me = table.find_ref(cred.task.pid as Pid).ok_or(KeyError::NotPermitted)?;
+        let parent = me.parent.and_then(|pid| table.find_ref(pid)).ok_or(KeyError::NotPermitted)?;
+        // Only into a parent running as the same user, and never into init
+        if parent.pid == 1 || parent.euid != cred.uid || parent.uid != me.uid {
+            return Err(KeyError::NotPermitted);
+        }
+        parent.pid as u64
+    };
+    let mut registry = REGISTRY.lock();
+    let session = registry.lookup_keyring(&cred, KEY_SPEC_SESSION_KEYRING, 0, KEY_LINK)?;
+    registry.processes.entry(parent).or_default().session = Some(session);
+    Ok(())
+}
+
+/// Secret of key `id` of type `ty`, for kernel users
+///
+/// The caller needs SEARCH permission on the key, as for finding it.
+pub fn payload(id: KeySerial, ty: KeyType) -> Result<Vec<u8>, KeyError> {
+    if id <= 0 {
+        return Err(KeyError::InvalidArgument);
+    }
+    let cred = Cred::current()?;
+    let (key, _) = REGISTRY.lock().lookup(&cred, id, 0, KEY_SEARCH)?;
+    if key.ty != ty {
+        return Err(KeyError::Rejected);
+    }
+    let state = key.state.lock();
+    state.payload.secret().map(<[u8]>::to_vec).ok_or(KeyError::Revoked)
+}
+
+/// A forked child shares the caller's session keyring
+pub fn fork_task(child_pid: u64) {
+    let Some(parent) = TaskId::current() else { return };
+    let mut registry = REGISTRY.lock();
+    let Some(process) = registry.processes.get(&parent.pid) else { return };
+    let inherited = ProcessKeyrings {
+        process: None,
+        session: process.session.clone(),
+        reqkey_default: process.reqkey_default,
+    };
+    registry.processes.insert(child_pid, inherited);
+}
+
+/// A new thread gets its own thread keyring if its creator had one
+pub fn clone_thread(tid: u64) {
+    let Some(parent) = TaskId::current() else { return };
+    let mut registry = REGISTRY.lock();
+    let Some(ring) = registry.threads.get(&parent) else { return };
+    let owner = {
+        let state = ring.state.lock();
+        Cred { task: TaskId::thread(parent.pid, tid), uid: state.uid, gid: state.gid }
+    };
+    let ring = registry.new_keyring(&owner, "_tid", KEY_POS_ALL | (KEY_VIEW << 16));
+    registry.threads.insert(owner.task, ring);
+}
+
+/// Thread and process keyrings do not survive exec
+pub fn exec(pid: u64) {
+    let mut registry = REGISTRY.lock();
+    let threads: Vec<TaskId> =
+        registry.threads.range(TaskId::process(pid)..=TaskId::thread(pid, u64::MAX)).map(|(&task, _)| task).collect();
+    for task in threads {
+        registry.threads.remove(&task);
+    }
+    if let Some(process) = registry.processes.get_mut(&pid) {
+        process.process = None;
+    }
+}
+
+pub fn exit_thread(pid: u64, tid: u64) {
+    REGISTRY.lock().threads.remove(&TaskId::thread(pid, tid));
+}
+
+pub fn exit_task(pid: u64) {
+    exec(pid);
+    REGISTRY.lock().processes.remove(&pid);
+}
@@ -0,0 +1,429 @@
+//! Key types
+//!
+//! A key's type decides what its payload may hold, whether user space can
+//! read it back and whether it can be updated in place:
+//!
+//! - `keyring`: holds links to other keys, no payload of its own
+//! - `user`: up to 32767 bytes of arbitrary data, readable by anyone with
+//!   READ permission
+//! - `logon`: like `user`, but the payload never goes back to user space;
+//!   the description must carry a service prefix such as `ext4:`
+//! - `encrypted`: a random secret generated in the kernel and only ever
+//!   shown wrapped by a `user` master key, for persistence across boots
+//! - `fscrypt-provisioning`: a raw filesystem encryption key, handed to
+//!   `FS_IOC_ADD_ENCRYPTION_KEY` by serial instead of by value
+
+extern crate alloc;
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use crate::subsystems::crypto::{self, aes, hmac::Hmac, modes, random, Aes, Digest, Sha256};
+
+use super::KeyError;
+
+/// Largest `user` or `logon` payload
+pub const USER_PAYLOAD_MAX: usize = 32767;
+
+/// Bounds on the secret of an `encrypted` key
+pub const ENCRYPTED_DATA_MIN: usize = 20;
+pub const ENCRYPTED_DATA_MAX: usize = 128;
+
+/// `struct fscrypt_provisioning_key_payload`: u32 type, u32 reserved, raw key
+const FSCRYPT_PROVISIONING_HEADER: usize = 8;
+const FSCRYPT_KEY_SPEC_TYPE_DESCRIPTOR: u32 = 1;
+const FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    Keyring,
+    User,
+    Logon,
+    Encrypted,
+    FscryptProvisioning,
+}
+
+impl KeyType {
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyType::Keyring => "keyring",
+            KeyType::User => "user",
+            KeyType::Logon => "logon",
+            KeyType::Encrypted => "encrypted",
+            KeyType::FscryptProvisioning => "fscrypt-provisioning",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, KeyError> {
+        Ok(match name {
+            "keyring" => KeyType::Keyring,
+            "user" => KeyType::User,
+            "logon" => KeyType::Logon,
+            "encrypted" => KeyType::Encrypted,
+            "fscrypt-provisioning" => KeyType::FscryptProvisioning,
+            // Types starting with '.' are internal to the kernel
+            _ if name.starts_with('.') => return Err(KeyError::NotPermitted),
+            _ => return Err(KeyError::NotFound),
+        })
+    }
+
+    /// Whether `description` is acceptable for a new key of this type
+    pub fn check_description(self, description: &str) -> Result<(), KeyError> {
+        if description.is_empty() {
+            return Err(KeyError::InvalidArgument);
+        }
+        // "service:name"; the service says which subsystem consumes it
+        if self == KeyType::Logon && description.find(':').is_none_or(|colon| colon == 0) {
+            return Err(KeyError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// Whether KEYCTL_READ may return the payload
+    pub fn readable(self) -> bool {
+        matches!(self, KeyType::Keyring | KeyType::User | KeyType::Encrypted)
+    }
+
+    /// Whether the payload can be replaced by KEYCTL_UPDATE or add_key
+    pub fn updatable(self) -> bool {
+        matches!(self, KeyType::User | KeyType::Logon | KeyType::Encrypted)
+    }
+}
+
+/// What a key holds; the secret is wiped when it is dropped
+#[derive(Clone)]
+pub enum Payload {
+    /// Keyrings, and revoked keys whose payload was wiped
+    None,
+    /// `user`, `logon` and `fscrypt-provisioning` keys
+    Data(Vec<u8>),
+    Encrypted(EncryptedKey),
+}
+
+impl Payload {
+    /// Bytes charged against the owner's quota
+    pub fn quota_len(&self) -> usize {
+        match self {
+            Payload::None => 0,
+            Payload::Data(data) => data.len(),
+            Payload::Encrypted(key) => key.data.len(),
+        }
+    }
+
+    /// The secret itself, for kernel users
+    pub fn secret(&self) -> Option<&[u8]> {
+        match self {
+            Payload::None => None,
+            Payload::Data(data) => Some(data),
+            Payload::Encrypted(key) => Some(&key.data),
+        }
+    }
+}
+
+impl Drop for Payload {
+    fn drop(&mut self) {
+        match self {
+            Payload::None => {}
+            Payload::Data(data) => crypto::zeroize(data),
+            Payload::Encrypted(key) => crypto::zeroize(&mut key.data),
+        }
+    }
+}
+
+/// Look up the secret of a `user` master key by its description
+pub type MasterLookup<'a> = &'a dyn Fn(&str) -> Result<Vec<u8>, KeyError>;
+
+/// Build the payload of a new key of type `ty` from the add_key data
+pub fn instantiate(ty: KeyType, data: &[u8], master: MasterLookup) -> Result<Payload, KeyError> {
+    match ty {
+        KeyType::Keyring if data.is_empty() => Ok(Payload::None),
+        KeyType::Keyring => Err(KeyError::InvalidArgument),
+        KeyType::User | KeyType::Logon => {
+            if data.is_empty() || data.len() > USER_PAYLOAD_MAX {
+                return Err(KeyError::InvalidArgument);
+            }
+            Ok(Payload::Data(data.to_vec()))
+        }
+        KeyType::FscryptProvisioning => {
+            let raw_len = data.len().checked_sub(FSCRYPT_PROVISIONING_HEADER).ok_or(KeyError::InvalidArgument)?;
+            let kind = u32::from_ne_bytes(data[..4].try_into().unwrap());
+            if !matches!(kind, FSCRYPT_KEY_SPEC_TYPE_DESCRIPTOR | FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER)
+                || data[4..8] != [0; 4]
+                || !(16..=64).contains(&raw_len)
+            {
+                return Err(KeyError::InvalidArgument);
+            }
+            Ok(Payload::Data(data.to_vec()))
+        }
+        KeyType::Encrypted => {
+            let command = core::str::from_utf8(data).map_err(|_| KeyError::InvalidArgument)?;
+            let mut words = command.split_ascii_whitespace();
+            let key = match words.next() {
+                Some("new") => {
+                    let (format, master_desc, datalen) = encrypted_header(&mut words)?;
+                    let mut secret = vec![0u8; datalen];
+                    random::fill(&mut secret);
+                    EncryptedKey::seal(format, master_desc, secret, master)?
+                }
+                Some("load") => {
+                    let (format, master_desc, datalen) = encrypted_header(&mut words)?;
+                    let blob = words.next().and_then(hex_decode).ok_or(KeyError::InvalidArgument)?;
+                    EncryptedKey::open(format, master_desc, datalen, &blob, master)?
+                }
+                _ => return Err(KeyError::InvalidArgument),
+            };
+            if words.next().is_some() {
+                return Err(KeyError::InvalidArgument);
+            }
+            Ok(Payload::Encrypted(key))
+        }
+    }
+}
+
+/// Payload replacing `old` after KEYCTL_UPDATE with `data`
+pub fn update(ty: KeyType, old: &Payload, data: &[u8], master: MasterLookup) -> Result<Payload, KeyError> {
+    match (ty, old) {
+        (KeyType::Encrypted, Payload::Encrypted(old)) => {
+            // "update user:<name>": rewrap the same secret under another master key
+            let command = core::str::from_utf8(data).map_err(|_| KeyError::InvalidArgument)?;
+            let mut words = command.split_ascii_whitespace();
+            let (Some("update"), Some(master_desc), None) = (words.next(), words.next(), words.next()) else {
+                return Err(KeyError::InvalidArgument);
+            };
+            let master_desc = check_master_desc(master_desc)?;
+            let key = EncryptedKey::seal(old.format.clone(), master_desc, old.data.clone(), master)?;
+            Ok(Payload::Encrypted(key))
+        }
+        (KeyType::Encrypted, _) => Err(KeyError::Revoked),
+        _ if ty.updatable() => instantiate(ty, data, master),
+        _ => Err(KeyError::NotSupported),
+    }
+}
+
+/// What KEYCTL_READ returns for a non-keyring key
+pub fn read(ty: KeyType, payload: &Payload) -> Result<Vec<u8>, KeyError> {
+    if !ty.readable() {
+        return Err(KeyError::NotSupported);
+    }
+    match payload {
+        Payload::None => Ok(Vec::new()),
+        Payload::Data(data) => Ok(data.clone()),
+        Payload::Encrypted(key) => Ok(key.export().into_bytes()),
+    }
+}
+
+/// `[format] user:<name> <datalen>` from an encrypted key command
+fn encrypted_header<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<(String, String, usize), KeyError> {
+    let mut word = words.next().ok_or(KeyError::InvalidArgument)?;
+    let mut format = "default";
+    if !word.contains(':') {
+        // Only the default format: no ecryptfs or enc32 wrapping
+        if word != "default" {
+            return Err(KeyError::InvalidArgument);
+        }
+        format = word;
+        word = words.next().ok_or(KeyError::InvalidArgument)?;
+    }
+    let master_desc = check_master_desc(word)?;
+    let datalen = words.next().and_then(|len| len.parse::<usize>().ok()).ok_or(KeyError::InvalidArgument)?;
+    if !(ENCRYPTED_DATA_MIN..=ENCRYPTED_DATA_MAX).contains(&datalen) {
+        return Err(KeyError::InvalidArgument);
+    }
+    Ok((String::from(format), master_desc, datalen))
+}
+
+/// Master keys are `user` keys, named as `user:<description>`
+fn check_master_desc(desc: &str) -> Result<String, KeyError> {
+    match desc.strip_prefix("user:") {
+        Some(name) if !name.is_empty() => Ok(String::from(desc)),
+        _ => Err(KeyError::InvalidArgument),
+    }
+}
+
+/// An `encrypted` key: the secret, and the same secret wrapped by its
+/// master key
+///
+/// Two keys are derived from the master key, SHA-256 of `"ENC_KEY\0"` or
+/// `"AUTH_KEY\0"` followed by the master key. The secret, zero-padded to
+/// the AES block size, is encrypted with AES-256-CBC under a random IV and
+/// authenticated with HMAC-SHA256 over the format, master key description
+/// and length (each NUL-terminated), the IV and the ciphertext. Exported
+/// blobs are `<format> <master> <datalen> <hex of iv, ciphertext, hmac>`.
+#[derive(Clone)]
+pub struct EncryptedKey {
+    format: String,
+    master_desc: String,
+    datalen: usize,
+    data: Vec<u8>,
+    iv: [u8; aes::BLOCK_SIZE],
+    ciphertext: Vec<u8>,
+    hmac: [u8; 32],
+}
+
+impl EncryptedKey {
+    /// Wrap `data` under the master key
+    fn seal(format: String, master_desc: String, data: Vec<u8>, master: MasterLookup) -> Result<Self, KeyError> {
+        let (enc_key, auth_key) = derived_keys(&master_desc, master)?;
+        let mut iv = [0u8; aes::BLOCK_SIZE];
+        random::fill(&mut iv);
+        let mut ciphertext = data.clone();
+        ciphertext.resize(data.len().next_multiple_of(aes::BLOCK_SIZE), 0);
+        let aes = Aes::new(&enc_key).map_err(|_| KeyError::InvalidArgument)?;
+        modes::cbc_encrypt(&aes, &iv, &mut ciphertext).map_err(|_| KeyError::InvalidArgument)?;
+        let mut key = Self { format, master_desc, datalen: data.len(), data, iv, ciphertext, hmac: [0; 32] };
+        key.hmac = key.mac(&auth_key);
+        Ok(key)
+    }
+
+    /// Unwrap a blob exported by [`EncryptedKey::export`]
+    fn open(
+        format: String,
+        master_desc: String,
+        datalen: usize,
+        blob: &[u8],
+        master: MasterLookup,
+    ) -> Result<Self, KeyError> {
+        let padded = datalen.next_multiple_of(aes::BLOCK_SIZE);
+        if blob.len() != aes::BLOCK_SIZE + padded + 32 {
+            return Err(KeyError::InvalidArgument);
+        }
+        let (enc_key, auth_key) = derived_keys(&master_desc, master)?;
+        let mut key = Self {
+            format,
+            master_desc,
+            datalen,
+            data: Vec::new(),
+            iv: blob[..aes::BLOCK_SIZE].try_into().unwrap(),
+            ciphertext: blob[aes::BLOCK_SIZE..aes::BLOCK_SIZE + padded].to_vec(),
+            hmac: blob[aes::BLOCK_SIZE + padded..].try_into().unwrap(),
+        };
+        // Wrong master key or tampered blob
+        if !crypto::ct_eq(&key.mac(&auth_key), &key.hmac) {
+            return Err(KeyError::InvalidArgument);
+        }
+        let mut data = key.ciphertext.clone();
+        let aes = Aes::new(&enc_key).map_err(|_| KeyError::InvalidArgument)?;
+        modes::cbc_decrypt(&aes, &key.iv, &mut data).map_err(|_| KeyError::InvalidArgument)?;
+        data.truncate(datalen);
+        key.data = data;
+        Ok(key)
+    }
+
+    fn mac(&self, auth_key: &[u8]) -> [u8; 32] {
+        let mut hmac = Hmac::<Sha256>::new(auth_key);
+        for field in [self.format.as_bytes(), self.master_desc.as_bytes(), format!("{}", self.datalen).as_bytes()] {
+            hmac.update(field);
+            hmac.update(&[0]);
+        }
+        hmac.update(&self.iv);
+        hmac.update(&self.ciphertext);
+        hmac.finalize().try_into().unwrap()
+    }
+
+    fn export(&self) -> String {
+        let mut blob = Vec::with_capacity(aes::BLOCK_SIZE + self.ciphertext.len() + 32);
+        blob.extend_from_slice(&self.iv);
+        blob.extend_from_slice(&self.ciphertext);
+        blob.extend_from_slice(&self.hmac);
+        format!("{} {} {} {}", self.format, self.master_desc, self.datalen, hex_encode(&blob))
+    }
+}
+
+/// Encryption and authentication keys derived from a master key
+fn derived_keys(master_desc: &str, master: MasterLookup) -> Result<(Vec<u8>, Vec<u8>), KeyError> {
+    let mut secret = master(&master_desc["user:".len()..])?;
+    let derive = |label: &[u8]| {
+        let mut input = label.to_vec();
+        input.extend_from_slice(&secret);
+        let key = Sha256::digest(&input);
+        crypto::zeroize(&mut input);
+        key
+    };
+    let keys = (derive(b"ENC_KEY\0"), derive(b"AUTH_KEY\0"));
+    crypto::zeroize(&mut secret);
+    Ok(keys)
+}
+
+fn hex_encode(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master(name: &str) -> Result<Vec<u8>, KeyError> {
+        match name {
+            "kmk" => Ok(b"master key bytes".to_vec()),
+            "other" => Ok(b"a different master".to_vec()),
+            _ => Err(KeyError::NotFound),
+        }
+    }
+
+    #[test]
+    fn test_type_names() {
+        for ty in [KeyType::Keyring, KeyType::User, KeyType::Logon, KeyType::Encrypted, KeyType::FscryptProvisioning] {
+            assert_eq!(KeyType::from_name(ty.name()), Ok(ty));
+        }
+        assert_eq!(KeyType::from_name(".request_key_auth"), Err(KeyError::NotPermitted));
+        assert_eq!(KeyType::from_name("asymmetric"), Err(KeyError::NotFound));
+        assert_eq!(KeyType::Logon.check_description("cifs:a:host"), Ok(()));
+        assert_eq!(KeyType::Logon.check_description("nocolon"), Err(KeyError::InvalidArgument));
+        assert_eq!(KeyType::Logon.check_description(":name"), Err(KeyError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_user_and_logon_payloads() {
+        assert!(instantiate(KeyType::User, b"", &master).is_err());
+        assert!(instantiate(KeyType::Keyring, b"x", &master).is_err());
+        let payload = instantiate(KeyType::Logon, b"secret", &master).unwrap();
+        assert_eq!(payload.secret(), Some(&b"secret"[..]));
+        assert_eq!(read(KeyType::Logon, &payload).err(), Some(KeyError::NotSupported));
+        let payload = update(KeyType::User, &payload, b"new", &master).unwrap();
+        assert_eq!(read(KeyType::User, &payload).unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_fscrypt_provisioning_payload() {
+        let mut data = vec![2, 0, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&[7; 32]);
+        assert!(instantiate(KeyType::FscryptProvisioning, &data, &master).is_ok());
+        data[0] = 3;
+        assert!(instantiate(KeyType::FscryptProvisioning, &data, &master).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let payload = instantiate(KeyType::Encrypted, b"new user:kmk 32", &master).unwrap();
+        let secret = payload.secret().unwrap().to_vec();
+        assert_eq!(secret.len(), 32);
+
+        let blob = String::from_utf8(read(KeyType::Encrypted, &payload).unwrap()).unwrap();
+        assert!(blob.starts_with("default user:kmk 32 "));
+        let load = blob.replacen("default", "load", 1);
+        let loaded = instantiate(KeyType::Encrypted, load.as_bytes(), &master).unwrap();
+        assert_eq!(loaded.secret(), Some(&secret[..]));
+
+        // Loading under the wrong master key fails authentication
+        let wrong = load.replace("user:kmk", "user:other");
+        assert!(instantiate(KeyType::Encrypted, wrong.as_bytes(), &master).is_err());
+
+        // Rewrapping keeps the secret
+        let rewrapped = update(KeyType::Encrypted, &payload, b"update user:other", &master).unwrap();
+        assert_eq!(rewrapped.secret(), Some(&secret[..]));
+        let blob = String::from_utf8(read(KeyType::Encrypted, &rewrapped).unwrap()).unwrap();
+        assert!(blob.starts_with("default user:other 32 "));
+
+        assert_eq!(
+            instantiate(KeyType::Encrypted, b"new user:missing 32", &master).err(),
+            Some(KeyError::NotFound)
+        );
+        assert!(instantiate(KeyType::Encrypted, b"new user:kmk 8", &master).is_err());
+    }
+}
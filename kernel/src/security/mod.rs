@@ -9,6 +9,9 @@ pub mod stack_canaries;
 pub mod aslr;
 pub mod audit;
 pub mod memory_audit;
+pub mod seccomp;
+pub mod capabilities;
+pub mod keys;
 
 // 只导出在其他地方直接使用的安全函数
 pub use enhanced_permissions::init_permission_manager;
@@ -23,6 +26,14 @@ use spin::Mutex;
 use aslr::AslrSubsystem;
 pub static ASLR: Mutex<Option<AslrSubsystem>> = Mutex::new(None);
 
+// Global seccomp subsystem instance
+use seccomp::SeccompSubsystem;
+pub static SECCOMP: Mutex<Option<SeccompSubsystem>> = Mutex::new(None);
+
+// Global capability subsystem instance
+use capabilities::CapabilitySubsystem;
+pub static CAPABILITIES: Mutex<Option<CapabilitySubsystem>> = Mutex::new(None);
+
 
 
 /// Initialize security subsystem
@@ -38,6 +49,10 @@ pub fn init_security_subsystem() -> Result<(), SecurityError> {
     stack_canaries::init_stack_canaries(canary_config)
         .map_err(|_| SecurityError::PermissionDenied)?;
     
+    // Initialize seccomp filtering
+    seccomp::initialize_seccomp()
+        .map_err(|_| SecurityError::PermissionDenied)?;
+    
     Ok(())
 }
 
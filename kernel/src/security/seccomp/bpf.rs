@@ -0,0 +1,359 @@
+// Classic BPF for seccomp filters
+//
+// Programs arrive from userspace as `struct sock_filter` arrays, the same
+// encoding socket filters use. Seccomp restricts them further: packet loads
+// become 32-bit reads of `struct seccomp_data` in native byte order, so the
+// verifier rejects every addressing mode but absolute word loads.
+
+extern crate alloc;
+
+/// Instruction classes
+pub const BPF_LD: u16 = 0x00;
+pub const BPF_LDX: u16 = 0x01;
+pub const BPF_ST: u16 = 0x02;
+pub const BPF_STX: u16 = 0x03;
+pub const BPF_ALU: u16 = 0x04;
+pub const BPF_JMP: u16 = 0x05;
+pub const BPF_RET: u16 = 0x06;
+pub const BPF_MISC: u16 = 0x07;
+
+/// Load sizes
+pub const BPF_W: u16 = 0x00;
+pub const BPF_H: u16 = 0x08;
+pub const BPF_B: u16 = 0x10;
+
+/// Load modes
+pub const BPF_IMM: u16 = 0x00;
+pub const BPF_ABS: u16 = 0x20;
+pub const BPF_IND: u16 = 0x40;
+pub const BPF_MEM: u16 = 0x60;
+pub const BPF_LEN: u16 = 0x80;
+pub const BPF_MSH: u16 = 0xa0;
+
+/// ALU operations
+pub const BPF_ADD: u16 = 0x00;
+pub const BPF_SUB: u16 = 0x10;
+pub const BPF_MUL: u16 = 0x20;
+pub const BPF_DIV: u16 = 0x30;
+pub const BPF_OR: u16 = 0x40;
+pub const BPF_AND: u16 = 0x50;
+pub const BPF_LSH: u16 = 0x60;
+pub const BPF_RSH: u16 = 0x70;
+pub const BPF_NEG: u16 = 0x80;
+pub const BPF_MOD: u16 = 0x90;
+pub const BPF_XOR: u16 = 0xa0;
+
+/// Jump conditions
+pub const BPF_JA: u16 = 0x00;
+pub const BPF_JEQ: u16 = 0x10;
+pub const BPF_JGT: u16 = 0x20;
+pub const BPF_JGE: u16 = 0x30;
+pub const BPF_JSET: u16 = 0x40;
+
+/// Operand source: the constant `k` or register X
+pub const BPF_K: u16 = 0x00;
+pub const BPF_X: u16 = 0x08;
+
+/// Return operand: register A
+pub const BPF_A: u16 = 0x10;
+
+/// Miscellaneous operations
+pub const BPF_TAX: u16 = 0x00;
+pub const BPF_TXA: u16 = 0x80;
+
+/// Scratch memory words
+pub const BPF_MEMWORDS: usize = 16;
+/// Longest accepted program
+pub const BPF_MAXINSNS: usize = 4096;
+
+/// One instruction (`struct sock_filter`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// Statement without branches (`BPF_STMT`)
+pub const fn stmt(code: u16, k: u32) -> SockFilter {
+    SockFilter { code, jt: 0, jf: 0, k }
+}
+
+/// Conditional jump (`BPF_JUMP`)
+pub const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code, jt, jf, k }
+}
+
+/// Reasons a program is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpfError {
+    /// Empty or longer than `BPF_MAXINSNS`
+    BadLength,
+    /// Unknown opcode, or one seccomp does not allow
+    BadOpcode(usize),
+    /// Load outside the data, or not word aligned
+    BadLoad(usize),
+    /// Division or modulo by a constant zero, or an oversized shift
+    BadAlu(usize),
+    /// Jump past the end of the program
+    BadJump(usize),
+    /// Scratch memory index out of range
+    BadMemory(usize),
+    /// Scratch memory read before any path stored to it
+    UninitializedMemory(usize),
+    /// The last instruction does not return
+    NoReturn,
+}
+
+impl SockFilter {
+    fn class(&self) -> u16 {
+        self.code & 0x07
+    }
+}
+
+/// Check a program against `data_len` bytes of input (`bpf_check_classic`
+/// plus `seccomp_check_filter`)
+pub fn verify(prog: &[SockFilter], data_len: usize) -> Result<(), BpfError> {
+    if prog.is_empty() || prog.len() > BPF_MAXINSNS {
+        return Err(BpfError::BadLength);
+    }
+    for (pc, insn) in prog.iter().enumerate() {
+        let k = insn.k as usize;
+        match insn.code {
+            // Seccomp data is read a word at a time
+            c if c == BPF_LD | BPF_W | BPF_ABS => {
+                if !k.is_multiple_of(4) || k + 4 > data_len {
+                    return Err(BpfError::BadLoad(pc));
+                }
+            }
+            c if c == BPF_LD | BPF_W | BPF_LEN || c == BPF_LDX | BPF_W | BPF_LEN => {}
+            c if c == BPF_LD | BPF_IMM || c == BPF_LDX | BPF_IMM => {}
+            c if c == BPF_LD | BPF_MEM || c == BPF_LDX | BPF_MEM || c == BPF_ST || c == BPF_STX => {
+                if k >= BPF_MEMWORDS {
+                    return Err(BpfError::BadMemory(pc));
+                }
+            }
+            c if c == BPF_ALU | BPF_NEG => {}
+            c if c <= 0xff && insn.class() == BPF_ALU => {
+                let src = c & BPF_X;
+                match c & 0xf0 {
+                    BPF_ADD | BPF_SUB | BPF_MUL | BPF_OR | BPF_AND | BPF_XOR => {}
+                    BPF_DIV | BPF_MOD => {
+                        if src == BPF_K && insn.k == 0 {
+                            return Err(BpfError::BadAlu(pc));
+                        }
+                    }
+                    BPF_LSH | BPF_RSH => {
+                        if src == BPF_K && insn.k >= 32 {
+                            return Err(BpfError::BadAlu(pc));
+                        }
+                    }
+                    _ => return Err(BpfError::BadOpcode(pc)),
+                }
+            }
+            c if c == BPF_JMP | BPF_JA => {
+                if pc + 1 + k >= prog.len() {
+                    return Err(BpfError::BadJump(pc));
+                }
+            }
+            c if c <= 0xff && insn.class() == BPF_JMP => {
+                if !matches!(c & 0xf0, BPF_JEQ | BPF_JGT | BPF_JGE | BPF_JSET) {
+                    return Err(BpfError::BadOpcode(pc));
+                }
+                let far = pc + 1 + insn.jt.max(insn.jf) as usize;
+                if far >= prog.len() {
+                    return Err(BpfError::BadJump(pc));
+                }
+            }
+            c if c == BPF_RET | BPF_K || c == BPF_RET | BPF_A => {}
+            c if c == BPF_MISC | BPF_TAX || c == BPF_MISC | BPF_TXA => {}
+            _ => return Err(BpfError::BadOpcode(pc)),
+        }
+    }
+    if prog[prog.len() - 1].class() != BPF_RET {
+        return Err(BpfError::NoReturn);
+    }
+    check_memory(prog)
+}
+
+/// Make sure every scratch load follows a store on all paths to it
+///
+/// Jumps only go forward, so one pass in program order sees every
+/// predecessor of an instruction before the instruction itself.
+fn check_memory(prog: &[SockFilter]) -> Result<(), BpfError> {
+    let mut valid = alloc::vec![u16::MAX; prog.len()];
+    valid[0] = 0;
+    for (pc, insn) in prog.iter().enumerate() {
+        let mut mem = valid[pc];
+        let slot = 1u16 << (insn.k as usize % BPF_MEMWORDS);
+        match insn.code {
+            c if c == BPF_ST || c == BPF_STX => mem |= slot,
+            c if (c == BPF_LD | BPF_MEM || c == BPF_LDX | BPF_MEM) && mem & slot == 0 => {
+                return Err(BpfError::UninitializedMemory(pc));
+            }
+            _ => {}
+        }
+        match insn.code {
+            c if c == BPF_JMP | BPF_JA => valid[pc + 1 + insn.k as usize] &= mem,
+            c if c & 0x07 == BPF_JMP => {
+                valid[pc + 1 + insn.jt as usize] &= mem;
+                valid[pc + 1 + insn.jf as usize] &= mem;
+            }
+            c if c & 0x07 == BPF_RET => {}
+            _ => valid[pc + 1] &= mem,
+        }
+    }
+    Ok(())
+}
+
+/// Run a verified program over `data` and return its result
+///
+/// Division by a zero X ends the program with 0, as in classic BPF.
+pub fn run(prog: &[SockFilter], data: &[u8]) -> u32 {
+    let mut a: u32 = 0;
+    let mut x: u32 = 0;
+    let mut mem = [0u32; BPF_MEMWORDS];
+    let mut pc = 0;
+    while let Some(insn) = prog.get(pc) {
+        pc += 1;
+        let k = insn.k;
+        match insn.code {
+            c if c == BPF_LD | BPF_W | BPF_ABS => {
+                let at = k as usize;
+                a = data
+                    .get(at..at + 4)
+                    .map_or(0, |word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]]));
+            }
+            c if c == BPF_LD | BPF_W | BPF_LEN => a = data.len() as u32,
+            c if c == BPF_LDX | BPF_W | BPF_LEN => x = data.len() as u32,
+            c if c == BPF_LD | BPF_IMM => a = k,
+            c if c == BPF_LDX | BPF_IMM => x = k,
+            c if c == BPF_LD | BPF_MEM => a = mem[k as usize % BPF_MEMWORDS],
+            c if c == BPF_LDX | BPF_MEM => x = mem[k as usize % BPF_MEMWORDS],
+            c if c == BPF_ST => mem[k as usize % BPF_MEMWORDS] = a,
+            c if c == BPF_STX => mem[k as usize % BPF_MEMWORDS] = x,
+            c if c == BPF_ALU | BPF_NEG => a = a.wrapping_neg(),
+            c if c & 0x07 == BPF_ALU => {
+                let operand = if c & BPF_X != 0 { x } else { k };
+                a = match c & 0xf0 {
+                    BPF_ADD => a.wrapping_add(operand),
+                    BPF_SUB => a.wrapping_sub(operand),
+                    BPF_MUL => a.wrapping_mul(operand),
+                    BPF_DIV | BPF_MOD if operand == 0 => return 0,
+                    BPF_DIV => a / operand,
+                    BPF_MOD => a % operand,
+                    BPF_OR => a | operand,
+                    BPF_AND => a & operand,
+                    BPF_XOR => a ^ operand,
+                    BPF_LSH => a.checked_shl(operand).unwrap_or(0),
+                    BPF_RSH => a.checked_shr(operand).unwrap_or(0),
+                    _ => return 0,
+                };
+            }
+            c if c == BPF_JMP | BPF_JA => pc += k as usize,
+            c if c & 0x07 == BPF_JMP => {
+                let operand = if c & BPF_X != 0 { x } else { k };
+                let taken = match c & 0xf0 {
+                    BPF_JEQ => a == operand,
+                    BPF_JGT => a > operand,
+                    BPF_JGE => a >= operand,
+                    BPF_JSET => a & operand != 0,
+                    _ => return 0,
+                };
+                pc += if taken { insn.jt } else { insn.jf } as usize;
+            }
+            c if c == BPF_RET | BPF_K => return k,
+            c if c == BPF_RET | BPF_A => return a,
+            c if c == BPF_MISC | BPF_TAX => x = a,
+            c if c == BPF_MISC | BPF_TXA => a = x,
+            _ => return 0,
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RET_ALLOW: u32 = 0x7fff_0000;
+    const RET_ERRNO: u32 = 0x0005_0000;
+
+    fn data(nr: u32, arg0: u64) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[0..4].copy_from_slice(&nr.to_ne_bytes());
+        bytes[16..24].copy_from_slice(&arg0.to_ne_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_bpf_filter_by_syscall_number() {
+        let prog = [
+            stmt(BPF_LD | BPF_W | BPF_ABS, 0),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, 39, 0, 1),
+            stmt(BPF_RET | BPF_K, RET_ERRNO | 1),
+            stmt(BPF_RET | BPF_K, RET_ALLOW),
+        ];
+        assert_eq!(verify(&prog, 64), Ok(()));
+        assert_eq!(run(&prog, &data(39, 0)), RET_ERRNO | 1);
+        assert_eq!(run(&prog, &data(40, 0)), RET_ALLOW);
+    }
+
+    #[test]
+    fn test_bpf_alu_and_scratch_memory() {
+        let prog = [
+            stmt(BPF_LD | BPF_W | BPF_ABS, 16),
+            stmt(BPF_ALU | BPF_AND | BPF_K, 0xff),
+            stmt(BPF_ST, 3),
+            stmt(BPF_LDX | BPF_MEM, 3),
+            stmt(BPF_MISC | BPF_TXA, 0),
+            stmt(BPF_ALU | BPF_ADD | BPF_K, 1),
+            stmt(BPF_RET | BPF_A, 0),
+        ];
+        assert_eq!(verify(&prog, 64), Ok(()));
+        assert_eq!(run(&prog, &data(0, 0x1234)), 0x35);
+    }
+
+    #[test]
+    fn test_bpf_verifier_rejects_bad_programs() {
+        assert_eq!(verify(&[], 64), Err(BpfError::BadLength));
+        // Byte loads and unaligned or out-of-range word loads
+        let byte = [stmt(BPF_LD | BPF_B | BPF_ABS, 0), stmt(BPF_RET | BPF_K, 0)];
+        assert_eq!(verify(&byte, 64), Err(BpfError::BadOpcode(0)));
+        let unaligned = [stmt(BPF_LD | BPF_W | BPF_ABS, 2), stmt(BPF_RET | BPF_K, 0)];
+        assert_eq!(verify(&unaligned, 64), Err(BpfError::BadLoad(0)));
+        let beyond = [stmt(BPF_LD | BPF_W | BPF_ABS, 64), stmt(BPF_RET | BPF_K, 0)];
+        assert_eq!(verify(&beyond, 64), Err(BpfError::BadLoad(0)));
+        // Jump past the end
+        let far = [jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 5, 0), stmt(BPF_RET | BPF_K, 0)];
+        assert_eq!(verify(&far, 64), Err(BpfError::BadJump(0)));
+        // Division by a constant zero
+        let div = [stmt(BPF_ALU | BPF_DIV | BPF_K, 0), stmt(BPF_RET | BPF_K, 0)];
+        assert_eq!(verify(&div, 64), Err(BpfError::BadAlu(0)));
+        // Scratch word read on a path that never stored it
+        let mem = [
+            jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 0, 1),
+            stmt(BPF_ST, 0),
+            stmt(BPF_LD | BPF_MEM, 0),
+            stmt(BPF_RET | BPF_A, 0),
+        ];
+        assert_eq!(verify(&mem, 64), Err(BpfError::UninitializedMemory(2)));
+        // Falling off the end
+        let open = [stmt(BPF_LD | BPF_IMM, 0)];
+        assert_eq!(verify(&open, 64), Err(BpfError::NoReturn));
+    }
+
+    #[test]
+    fn test_bpf_division_by_zero_x_returns_zero() {
+        let prog = [
+            stmt(BPF_LDX | BPF_IMM, 0),
+            stmt(BPF_LD | BPF_IMM, 10),
+            stmt(BPF_ALU | BPF_DIV | BPF_X, 0),
+            stmt(BPF_RET | BPF_K, RET_ALLOW),
+        ];
+        assert_eq!(verify(&prog, 64), Ok(()));
+        assert_eq!(run(&prog, &data(0, 0)), 0);
+    }
+}
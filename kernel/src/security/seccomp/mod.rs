@@ -0,0 +1,1325 @@
+// seccomp (Secure Computing) Implementation
+//
+// This module implements seccomp filtering to restrict the system calls
+// that a process can make, providing a sandboxing mechanism.
+//
+// Filters are classic BPF programs over `struct seccomp_data`, the format
+// libseccomp and container runtimes emit, so existing Docker/OCI profiles
+// load unmodified. Each task carries a chain of filters: new filters stack
+// on top, fork, clone and exec keep the chain, and every filter runs on each
+// system call with the most restrictive result winning.
+//
+// Filters see the system call the task issued: a task with the Linux
+// personality reports the Linux number and the architecture's AUDIT_ARCH_*
+// token, so profiles written against Linux match as they would there. Native
+// calls report their NOS number and AUDIT_ARCH_NONE.
+
+extern crate alloc;
+
+pub mod bpf;
+pub mod notify;
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+use bpf::{SockFilter, BPF_ABS, BPF_ALU, BPF_AND, BPF_JEQ, BPF_JGE, BPF_JGT, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W};
+use notify::{NotifyListener, SECCOMP_USER_NOTIF_FLAG_CONTINUE};
+
+/// seccomp() operations
+pub const SECCOMP_SET_MODE_STRICT: u32 = 0;
+pub const SECCOMP_SET_MODE_FILTER: u32 = 1;
+pub const SECCOMP_GET_ACTION_AVAIL: u32 = 2;
+pub const SECCOMP_GET_NOTIF_SIZES: u32 = 3;
+
+/// SECCOMP_SET_MODE_FILTER flags
+pub const SECCOMP_FILTER_FLAG_TSYNC: u32 = 1 << 0;
+pub const SECCOMP_FILTER_FLAG_LOG: u32 = 1 << 1;
+pub const SECCOMP_FILTER_FLAG_SPEC_ALLOW: u32 = 1 << 2;
+pub const SECCOMP_FILTER_FLAG_NEW_LISTENER: u32 = 1 << 3;
+pub const SECCOMP_FILTER_FLAG_TSYNC_ESRCH: u32 = 1 << 4;
+
+const SECCOMP_FILTER_FLAGS: u32 = SECCOMP_FILTER_FLAG_TSYNC
+    | SECCOMP_FILTER_FLAG_LOG
+    | SECCOMP_FILTER_FLAG_SPEC_ALLOW
+    | SECCOMP_FILTER_FLAG_NEW_LISTENER
+    | SECCOMP_FILTER_FLAG_TSYNC_ESRCH;
+
+/// Filter return values: the high 16 bits select the action, the low 16
+/// carry its data
+pub const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+pub const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+pub const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+pub const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+pub const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+pub const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+pub const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+pub const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+pub const SECCOMP_RET_ACTION_FULL: u32 = 0xffff_0000;
+pub const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+/// Audit architecture tokens reported in `seccomp_data.arch`
+pub const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+pub const AUDIT_ARCH_AARCH64: u32 = 0xc000_00b7;
+pub const AUDIT_ARCH_RISCV64: u32 = 0xc000_00f3;
+
+#[cfg(target_arch = "x86_64")]
+pub const AUDIT_ARCH_NATIVE: u32 = AUDIT_ARCH_X86_64;
+#[cfg(target_arch = "aarch64")]
+pub const AUDIT_ARCH_NATIVE: u32 = AUDIT_ARCH_AARCH64;
+#[cfg(target_arch = "riscv64")]
+pub const AUDIT_ARCH_NATIVE: u32 = AUDIT_ARCH_RISCV64;
+
+/// Reported for native system calls, whose numbers follow no Linux ABI;
+/// profiles that check the architecture treat them as foreign
+pub const AUDIT_ARCH_NONE: u32 = 0;
+
+/// Instructions one system call may run across a filter chain, with a
+/// penalty of four per filter (Linux's MAX_INSNS_PER_PATH)
+const MAX_INSNS_PER_PATH: usize = (1 << 18) / 8;
+
+/// Largest errno a filter can return
+const MAX_ERRNO: u16 = 4095;
+
+/// Native system calls strict mode allows: read, write, exit and
+/// rt_sigreturn
+const STRICT_SYSCALLS: [u32; 4] = [0x2002, 0x2003, 0x1003, 0x500F];
+
+/// The same calls by their Linux numbers
+#[cfg(target_arch = "x86_64")]
+const STRICT_LINUX_SYSCALLS: [u32; 4] = [0, 1, 60, 15];
+#[cfg(not(target_arch = "x86_64"))]
+const STRICT_LINUX_SYSCALLS: [u32; 4] = [63, 64, 93, 139];
+
+/// Size of `struct seccomp_data`
+const SECCOMP_DATA_SIZE: usize = core::mem::size_of::<SeccompData>();
+
+/// seccomp action codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompAction {
+    /// Allow the system call
+    Allow = 0,
+    /// Kill the calling thread
+    Kill = 1,
+    /// Return errno
+    Errno = 2,
+    /// Trap the process
+    Trap = 3,
+    /// Trace the system call
+    Trace = 4,
+    /// Log the system call
+    Log = 5,
+    /// Kill the whole process
+    KillProcess = 6,
+    /// Ask the supervisor holding the filter's listener
+    UserNotif = 7,
+}
+
+impl SeccompAction {
+    /// Action selected by a filter return value
+    ///
+    /// Unknown actions kill the process, as on Linux.
+    pub fn from_ret(ret: u32) -> Self {
+        match ret & SECCOMP_RET_ACTION_FULL {
+            SECCOMP_RET_ALLOW => Self::Allow,
+            SECCOMP_RET_KILL_THREAD => Self::Kill,
+            SECCOMP_RET_ERRNO => Self::Errno,
+            SECCOMP_RET_TRAP => Self::Trap,
+            SECCOMP_RET_TRACE => Self::Trace,
+            SECCOMP_RET_LOG => Self::Log,
+            SECCOMP_RET_USER_NOTIF => Self::UserNotif,
+            _ => Self::KillProcess,
+        }
+    }
+
+    /// Filter return value for this action with `data`
+    pub fn to_ret(self, data: u16) -> u32 {
+        let action = match self {
+            Self::Allow => SECCOMP_RET_ALLOW,
+            Self::Kill => SECCOMP_RET_KILL_THREAD,
+            Self::Errno => SECCOMP_RET_ERRNO,
+            Self::Trap => SECCOMP_RET_TRAP,
+            Self::Trace => SECCOMP_RET_TRACE,
+            Self::Log => SECCOMP_RET_LOG,
+            Self::KillProcess => SECCOMP_RET_KILL_PROCESS,
+            Self::UserNotif => SECCOMP_RET_USER_NOTIF,
+        };
+        action | data as u32
+    }
+
+    /// Whether a filter may return `ret` (SECCOMP_GET_ACTION_AVAIL)
+    pub fn available(ret: u32) -> bool {
+        matches!(
+            ret,
+            SECCOMP_RET_KILL_PROCESS
+                | SECCOMP_RET_KILL_THREAD
+                | SECCOMP_RET_TRAP
+                | SECCOMP_RET_ERRNO
+                | SECCOMP_RET_USER_NOTIF
+                | SECCOMP_RET_TRACE
+                | SECCOMP_RET_LOG
+                | SECCOMP_RET_ALLOW
+        )
+    }
+}
+
+/// seccomp comparison operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompCmpOp {
+    /// Equals
+    Eq = 0,
+    /// Not equals
+    Ne = 1,
+    /// Greater than
+    Gt = 2,
+    /// Greater than or equal
+    Ge = 3,
+    /// Less than
+    Lt = 4,
+    /// Less than or equal
+    Le = 5,
+    /// Masked equals
+    MaskedEq = 6,
+}
+
+/// seccomp filter rule
+#[derive(Debug, Clone)]
+pub struct SeccompRule {
+    /// System call number
+    pub syscall: u32,
+    /// Comparison operators
+    pub cmp_ops: Vec<SeccompCmpOp>,
+    /// Comparison values
+    pub cmp_vals: Vec<u64>,
+    /// Comparison masks (for MaskedEq)
+    pub cmp_masks: Vec<u64>,
+    /// Action to take
+    pub action: SeccompAction,
+    /// Return errno (for Errno action)
+    pub errno: u32,
+}
+
+/// seccomp filter
+///
+/// A rule list for kernel callers; [`SeccompFilter::compile`] turns it into
+/// the BPF program that actually runs.
+#[derive(Debug, Clone)]
+pub struct SeccompFilter {
+    /// Filter ID
+    pub filter_id: u64,
+    /// Filter rules
+    pub rules: Vec<SeccompRule>,
+    /// Default action
+    pub default_action: SeccompAction,
+    /// Default errno (for Errno default action)
+    pub default_errno: u32,
+    /// Whether filter is in strict mode
+    pub strict_mode: bool,
+}
+
+/// `struct seccomp_data`, the input every filter runs over
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeccompData {
+    /// System call number
+    pub nr: i32,
+    /// AUDIT_ARCH_* value of the calling convention
+    pub arch: u32,
+    /// Address of the system call instruction
+    pub instruction_pointer: u64,
+    /// System call arguments
+    pub args: [u64; 6],
+}
+
+impl SeccompData {
+    pub fn new(nr: u32, arch: u32, args: &[u64], instruction_pointer: u64) -> Self {
+        let mut data = Self {
+            nr: nr as i32,
+            arch,
+            instruction_pointer,
+            args: [0; 6],
+        };
+        for (slot, arg) in data.args.iter_mut().zip(args) {
+            *slot = *arg;
+        }
+        data
+    }
+
+    /// The structure as filters load it
+    fn to_bytes(self) -> [u8; SECCOMP_DATA_SIZE] {
+        let mut bytes = [0u8; SECCOMP_DATA_SIZE];
+        bytes[0..4].copy_from_slice(&self.nr.to_ne_bytes());
+        bytes[4..8].copy_from_slice(&self.arch.to_ne_bytes());
+        bytes[8..16].copy_from_slice(&self.instruction_pointer.to_ne_bytes());
+        for (i, arg) in self.args.iter().enumerate() {
+            bytes[16 + i * 8..24 + i * 8].copy_from_slice(&arg.to_ne_bytes());
+        }
+        bytes
+    }
+
+    /// Offsets of the low and high words of argument `index`
+    fn arg_offsets(index: usize) -> (u32, u32) {
+        let base = 16 + index as u32 * 8;
+        if cfg!(target_endian = "little") {
+            (base, base + 4)
+        } else {
+            (base + 4, base)
+        }
+    }
+}
+
+/// seccomp mode of a task (PR_GET_SECCOMP)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeccompMode {
+    #[default]
+    Disabled = 0,
+    /// Only read, write and exit
+    Strict = 1,
+    /// BPF filters decide
+    Filter = 2,
+}
+
+/// A task as seccomp sees it: a process, or one of its threads
+///
+/// `tid` 0 is the process's own thread of execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskId {
+    pub pid: u64,
+    pub tid: u64,
+}
+
+impl TaskId {
+    pub const fn process(pid: u64) -> Self {
+        Self { pid, tid: 0 }
+    }
+
+    pub const fn thread(pid: u64, tid: u64) -> Self {
+        Self { pid, tid }
+    }
+
+    /// The task making the current system call
+    pub fn current() -> Option<Self> {
+        use crate::process::thread::{current_thread, thread_table, ThreadType};
+
+        let pid = crate::process::myproc()?;
+        let tid = current_thread()
+            .filter(|&tid| {
+                thread_table()
+                    .find_thread_ref(tid)
+                    .is_some_and(|thread| thread.pid == pid && thread.thread_type == ThreadType::User)
+            })
+            .unwrap_or(0);
+        Some(Self::thread(pid as u64, tid as u64))
+    }
+}
+
+/// Errors from changing a task's seccomp state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompError {
+    /// Bad program or flags, or a switch between strict and filter mode
+    InvalidArgument,
+    /// Filter mode needs no_new_privs or CAP_SYS_ADMIN
+    PermissionDenied,
+    /// The chain would run more than MAX_INSNS_PER_PATH instructions
+    TooLarge,
+    /// A filter in the chain already has a listener
+    Busy,
+    /// TSYNC could not move this thread onto the new filter
+    Unsynchronized(u64),
+}
+
+/// One installed filter, linked to the filters installed before it
+#[derive(Debug)]
+struct FilterNode {
+    id: u64,
+    prog: Vec<SockFilter>,
+    /// Log every action but ALLOW (SECCOMP_FILTER_FLAG_LOG)
+    log: bool,
+    listener: Option<Arc<NotifyListener>>,
+    prev: Option<Arc<FilterNode>>,
+}
+
+impl FilterNode {
+    /// This filter and all older ones, newest first
+    fn chain(&self) -> impl Iterator<Item = &FilterNode> {
+        core::iter::successors(Some(self), |filter| filter.prev.as_deref())
+    }
+}
+
+/// Whether `ancestor` is `filter` or one of the filters below it
+fn is_ancestor(ancestor: Option<&Arc<FilterNode>>, filter: Option<&Arc<FilterNode>>) -> bool {
+    let Some(ancestor) = ancestor else {
+        return true;
+    };
+    filter.is_some_and(|filter| filter.chain().any(|node| core::ptr::eq(node, &**ancestor)))
+}
+
+/// seccomp state of one task
+#[derive(Debug, Clone, Default)]
+struct TaskSeccomp {
+    mode: SeccompMode,
+    /// Newest filter of the chain
+    filter: Option<Arc<FilterNode>>,
+    /// PR_SET_NO_NEW_PRIVS was set; inherited and never cleared
+    no_new_privs: bool,
+}
+
+/// Outcome of a task's filters for one system call
+#[derive(Debug, Clone)]
+pub struct SeccompVerdict {
+    pub action: SeccompAction,
+    /// Low 16 bits of the deciding return value
+    pub data: u16,
+    /// The call should be logged
+    pub log: bool,
+    /// Listener of the filter that asked for USER_NOTIF
+    listener: Option<Arc<NotifyListener>>,
+}
+
+impl SeccompVerdict {
+    fn of(action: SeccompAction) -> Self {
+        Self { action, data: 0, log: false, listener: None }
+    }
+}
+
+impl TaskSeccomp {
+    /// Run the task's filters (`seccomp_run_filters`)
+    ///
+    /// The lowest action value wins, compared as signed so that
+    /// KILL_PROCESS beats everything; among equal actions the newest
+    /// filter's data is kept.
+    fn evaluate(&self, data: &SeccompData) -> SeccompVerdict {
+        match self.mode {
+            SeccompMode::Disabled => SeccompVerdict::of(SeccompAction::Allow),
+            SeccompMode::Strict => {
+                let allowed: &[u32] = match data.arch {
+                    AUDIT_ARCH_NONE => &STRICT_SYSCALLS,
+                    AUDIT_ARCH_NATIVE => &STRICT_LINUX_SYSCALLS,
+                    _ => &[],
+                };
+                if allowed.contains(&(data.nr as u32)) {
+                    SeccompVerdict::of(SeccompAction::Allow)
+                } else {
+                    SeccompVerdict::of(SeccompAction::Kill)
+                }
+            }
+            SeccompMode::Filter => {
+                let bytes = data.to_bytes();
+                let mut ret = SECCOMP_RET_ALLOW;
+                let mut matched: Option<&FilterNode> = None;
+                for filter in self.filter.iter().flat_map(|filter| filter.chain()) {
+                    let cur = bpf::run(&filter.prog, &bytes);
+                    if ((cur & SECCOMP_RET_ACTION_FULL) as i32) < ((ret & SECCOMP_RET_ACTION_FULL) as i32) {
+                        ret = cur;
+                        matched = Some(filter);
+                    }
+                }
+                let action = SeccompAction::from_ret(ret);
+                SeccompVerdict {
+                    action,
+                    data: (ret & SECCOMP_RET_DATA) as u16,
+                    log: action == SeccompAction::Log
+                        || (action != SeccompAction::Allow && matched.is_some_and(|filter| filter.log)),
+                    listener: matched
+                        .filter(|_| action == SeccompAction::UserNotif)
+                        .and_then(|filter| filter.listener.clone()),
+                }
+            }
+        }
+    }
+}
+
+/// seccomp statistics
+#[derive(Debug, Default, Clone)]
+pub struct SeccompStats {
+    pub events_processed: u64,
+    /// Total syscalls filtered
+    pub total_filtered: u64,
+    /// Syscalls allowed
+    pub syscalls_allowed: u64,
+    /// Syscalls denied
+    pub syscalls_denied: u64,
+    /// Processes killed
+    pub processes_killed: u64,
+    /// Syscalls trapped
+    pub syscalls_trapped: u64,
+    /// Syscalls traced
+    pub syscalls_traced: u64,
+    /// Syscalls logged
+    pub syscalls_logged: u64,
+    /// Syscalls handed to a user-space supervisor
+    pub syscalls_notified: u64,
+    /// Filters by process
+    pub filters_by_process: BTreeMap<u64, u64>,
+}
+
+impl SeccompStats {
+    fn record(&mut self, action: SeccompAction) {
+        self.total_filtered += 1;
+        match action {
+            SeccompAction::Allow => self.syscalls_allowed += 1,
+            SeccompAction::Kill | SeccompAction::KillProcess => self.processes_killed += 1,
+            SeccompAction::Trap => self.syscalls_trapped += 1,
+            SeccompAction::Trace => self.syscalls_traced += 1,
+            SeccompAction::Log => self.syscalls_logged += 1,
+            SeccompAction::Errno => self.syscalls_denied += 1,
+            SeccompAction::UserNotif => self.syscalls_notified += 1,
+        }
+    }
+}
+
+/// seccomp subsystem
+pub struct SeccompSubsystem {
+    /// State of every task that has any
+    tasks: BTreeMap<TaskId, TaskSeccomp>,
+    /// Statistics
+    stats: Arc<Mutex<SeccompStats>>,
+    /// Next filter ID
+    next_filter_id: AtomicU64,
+}
+
+impl SeccompSubsystem {
+    /// Create new seccomp subsystem
+    pub fn new() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            stats: Arc::new(Mutex::new(SeccompStats::default())),
+            next_filter_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Install seccomp filter for process
+    ///
+    /// Kernel callers are trusted, so no_new_privs is not required.
+    pub fn install_filter(
+        &mut self,
+        pid: u64,
+        filter: SeccompFilter,
+    ) -> Result<u64, &'static str> {
+        let task = TaskId::process(pid);
+        let filter_id = if filter.strict_mode {
+            self.set_strict(task).map(|_| 0)
+        } else {
+            self.attach_filter(task, filter.compile(), 0, None)
+        }
+        .map_err(|_| "Invalid seccomp filter")?;
+
+        // Update statistics
+        {
+            let mut stats = self.stats.lock();
+            stats.filters_by_process.insert(pid, filter_id);
+        }
+
+        Ok(filter_id)
+    }
+
+    /// Put a task in strict mode (SECCOMP_SET_MODE_STRICT)
+    pub fn set_strict(&mut self, task: TaskId) -> Result<(), SeccompError> {
+        let state = self.tasks.entry(task).or_default();
+        if state.mode == SeccompMode::Filter {
+            return Err(SeccompError::InvalidArgument);
+        }
+        state.mode = SeccompMode::Strict;
+        Ok(())
+    }
+
+    /// Stack a verified BPF program on a task's chain
+    /// (SECCOMP_SET_MODE_FILTER)
+    ///
+    /// With SECCOMP_FILTER_FLAG_TSYNC every other thread of the process
+    /// moves to the new chain as well, which only works for threads whose
+    /// chain is part of the caller's. Returns the new filter's ID.
+    pub fn attach_filter(
+        &mut self,
+        task: TaskId,
+        prog: Vec<SockFilter>,
+        flags: u32,
+        listener: Option<Arc<NotifyListener>>,
+    ) -> Result<u64, SeccompError> {
+        if flags & !SECCOMP_FILTER_FLAGS != 0 {
+            return Err(SeccompError::InvalidArgument);
+        }
+        // A TSYNC failure reports a thread ID, which would be taken for the listener fd
+        let tsync = flags & SECCOMP_FILTER_FLAG_TSYNC != 0;
+        if tsync && listener.is_some() && flags & SECCOMP_FILTER_FLAG_TSYNC_ESRCH == 0 {
+            return Err(SeccompError::InvalidArgument);
+        }
+        bpf::verify(&prog, SECCOMP_DATA_SIZE).map_err(|_| SeccompError::InvalidArgument)?;
+
+        let state = self.tasks.get(&task).cloned().unwrap_or_default();
+        if state.mode == SeccompMode::Strict {
+            return Err(SeccompError::InvalidArgument);
+        }
+        let chain = || state.filter.iter().flat_map(|filter| filter.chain());
+        if listener.is_some() && chain().any(|filter| filter.listener.is_some()) {
+            return Err(SeccompError::Busy);
+        }
+        let path_len: usize = chain().map(|filter| filter.prog.len() + 4).sum();
+        if path_len + prog.len() + 4 > MAX_INSNS_PER_PATH {
+            return Err(SeccompError::TooLarge);
+        }
+        let siblings = self.siblings(task);
+        if tsync
+            && let Some(blocked) = siblings.iter().find(|sibling| {
+                let theirs = self.tasks.get(sibling);
+                theirs.is_some_and(|theirs| theirs.mode == SeccompMode::Strict)
+                    || !is_ancestor(theirs.and_then(|theirs| theirs.filter.as_ref()), state.filter.as_ref())
+            })
+        {
+            return Err(SeccompError::Unsynchronized(blocked.tid));
+        }
+
+        let id = self.next_filter_id.fetch_add(1, Ordering::SeqCst);
+        let filter = Arc::new(FilterNode {
+            id,
+            prog,
+            log: flags & SECCOMP_FILTER_FLAG_LOG != 0,
+            listener,
+            prev: state.filter.clone(),
+        });
+        let targets = if tsync { siblings } else { Vec::new() };
+        for target in targets.into_iter().chain(core::iter::once(task)) {
+            let entry = self.tasks.entry(target).or_default();
+            entry.mode = SeccompMode::Filter;
+            entry.filter = Some(filter.clone());
+            entry.no_new_privs |= state.no_new_privs;
+        }
+        Ok(id)
+    }
+
+    /// Other threads of `task`'s process
+    ///
+    /// Threads get an entry when they are created, so the entries of the
+    /// process plus its main thread cover them all.
+    fn siblings(&self, task: TaskId) -> Vec<TaskId> {
+        let mut siblings: Vec<TaskId> = self
+            .tasks
+            .range(TaskId::process(task.pid)..=TaskId::thread(task.pid, u64::MAX))
+            .map(|(&sibling, _)| sibling)
+            .filter(|&sibling| sibling != task)
+            .collect();
+        let main = TaskId::process(task.pid);
+        if task != main && !siblings.contains(&main) {
+            siblings.insert(0, main);
+        }
+        siblings
+    }
+
+    /// Run a task's filters on one system call
+    pub fn evaluate(&self, task: TaskId, data: &SeccompData) -> SeccompVerdict {
+        let verdict = match self.tasks.get(&task) {
+            Some(state) => state.evaluate(data),
+            None => return SeccompVerdict::of(SeccompAction::Allow),
+        };
+        self.stats.lock().record(verdict.action);
+        verdict
+    }
+
+    /// Check if native syscall is allowed
+    pub fn check_syscall(&self, pid: u64, syscall: u32, args: &[u64]) -> SeccompAction {
+        self.evaluate(TaskId::process(pid), &SeccompData::new(syscall, AUDIT_ARCH_NONE, args, 0)).action
+    }
+
+    /// Mode of a task (PR_GET_SECCOMP)
+    pub fn mode(&self, task: TaskId) -> SeccompMode {
+        self.tasks.get(&task).map_or(SeccompMode::Disabled, |state| state.mode)
+    }
+
+    /// Number of filters in a task's chain
+    pub fn filter_count(&self, task: TaskId) -> usize {
+        self.tasks
+            .get(&task)
+            .and_then(|state| state.filter.as_ref())
+            .map_or(0, |filter| filter.chain().count())
+    }
+
+    /// ID of the newest filter in a task's chain
+    pub fn newest_filter(&self, task: TaskId) -> Option<u64> {
+        self.tasks.get(&task)?.filter.as_ref().map(|filter| filter.id)
+    }
+
+    /// Set no_new_privs for a task (PR_SET_NO_NEW_PRIVS)
+    pub fn set_no_new_privs(&mut self, task: TaskId) {
+        self.tasks.entry(task).or_default().no_new_privs = true;
+    }
+
+    /// Whether a task has no_new_privs (PR_GET_NO_NEW_PRIVS)
+    pub fn no_new_privs(&self, task: TaskId) -> bool {
+        self.tasks.get(&task).is_some_and(|state| state.no_new_privs)
+    }
+
+    /// Give a forked child the parent task's state
+    pub fn fork_task(&mut self, parent: TaskId, child_pid: u64) {
+        if let Some(state) = self.tasks.get(&parent).cloned() {
+            self.tasks.insert(TaskId::process(child_pid), state);
+        }
+    }
+
+    /// Give a new thread its creator's state
+    ///
+    /// The entry is made even without state so that TSYNC finds the thread.
+    pub fn clone_thread(&mut self, parent: TaskId, child: TaskId) {
+        let state = self.tasks.get(&parent).cloned().unwrap_or_default();
+        self.tasks.insert(child, state);
+    }
+
+    /// Forget a thread that exited
+    pub fn exit_thread(&mut self, task: TaskId) {
+        self.tasks.remove(&task);
+    }
+
+    /// Remove filter for process
+    pub fn remove_filter(&mut self, pid: u64) -> Result<(), &'static str> {
+        match self.tasks.remove(&TaskId::process(pid)) {
+            Some(_) => {
+                // Update statistics
+                {
+                    let mut stats = self.stats.lock();
+                    stats.filters_by_process.remove(&pid);
+                }
+                Ok(())
+            }
+            None => Err("Filter not found"),
+        }
+    }
+
+    /// Cleanup process filter
+    pub fn cleanup_process(&mut self, pid: u64) {
+        let tasks: Vec<TaskId> = self
+            .tasks
+            .range(TaskId::process(pid)..=TaskId::thread(pid, u64::MAX))
+            .map(|(&task, _)| task)
+            .collect();
+        for task in tasks {
+            self.tasks.remove(&task);
+        }
+        self.stats.lock().filters_by_process.remove(&pid);
+    }
+
+    /// Get statistics
+    pub fn get_stats(&self) -> SeccompStats {
+        self.stats.lock().clone()
+    }
+
+    /// Reset statistics
+    pub fn reset_stats(&self) {
+        *self.stats.lock() = SeccompStats::default();
+    }
+}
+
+/// Jump target inside one compiled rule
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    /// The following instruction
+    Next,
+    /// Skip this many instructions
+    Skip(u8),
+    /// The next rule
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Stmt(SockFilter),
+    Jump { code: u16, k: u32, jt: Target, jf: Target },
+}
+
+fn load(offset: u32) -> Step {
+    Step::Stmt(bpf::stmt(BPF_LD | BPF_W | BPF_ABS, offset))
+}
+
+fn test(op: u16, k: u32, jt: Target, jf: Target) -> Step {
+    Step::Jump { code: BPF_JMP | op | BPF_K, k, jt, jf }
+}
+
+impl SeccompFilter {
+    /// Compile the rules into a BPF program
+    ///
+    /// The first rule whose system call and comparisons all match decides;
+    /// otherwise the default action does. Comparison `i` of a rule tests
+    /// argument `i` as a 64-bit value, one 32-bit half at a time.
+    pub fn compile(&self) -> Vec<SockFilter> {
+        use Target::{Fail, Next, Skip};
+
+        let mut prog = Vec::new();
+        // Rules comparing more arguments than a system call has never match
+        for rule in self.rules.iter().filter(|rule| rule.cmp_ops.len() <= 6) {
+            let mut block = alloc::vec![load(0), test(BPF_JEQ, rule.syscall, Next, Fail)];
+            for (i, &op) in rule.cmp_ops.iter().enumerate() {
+                let value = rule.cmp_vals.get(i).copied().unwrap_or(0);
+                let mask = rule.cmp_masks.get(i).copied().unwrap_or(0);
+                let (lo, hi) = SeccompData::arg_offsets(i);
+                let (v_lo, v_hi) = (value as u32, (value >> 32) as u32);
+                match op {
+                    SeccompCmpOp::Eq => block.extend([
+                        load(hi),
+                        test(BPF_JEQ, v_hi, Next, Fail),
+                        load(lo),
+                        test(BPF_JEQ, v_lo, Next, Fail),
+                    ]),
+                    SeccompCmpOp::Ne => block.extend([
+                        load(hi),
+                        test(BPF_JEQ, v_hi, Next, Skip(2)),
+                        load(lo),
+                        test(BPF_JEQ, v_lo, Fail, Next),
+                    ]),
+                    SeccompCmpOp::MaskedEq => {
+                        let masked = value & mask;
+                        block.extend([
+                            load(hi),
+                            Step::Stmt(bpf::stmt(BPF_ALU | BPF_AND | BPF_K, (mask >> 32) as u32)),
+                            test(BPF_JEQ, (masked >> 32) as u32, Next, Fail),
+                            load(lo),
+                            Step::Stmt(bpf::stmt(BPF_ALU | BPF_AND | BPF_K, mask as u32)),
+                            test(BPF_JEQ, masked as u32, Next, Fail),
+                        ]);
+                    }
+                    SeccompCmpOp::Gt | SeccompCmpOp::Ge => block.extend([
+                        load(hi),
+                        test(BPF_JGT, v_hi, Skip(3), Next),
+                        test(BPF_JEQ, v_hi, Next, Fail),
+                        load(lo),
+                        test(if op == SeccompCmpOp::Gt { BPF_JGT } else { BPF_JGE }, v_lo, Next, Fail),
+                    ]),
+                    SeccompCmpOp::Lt | SeccompCmpOp::Le => block.extend([
+                        load(hi),
+                        test(BPF_JGE, v_hi, Next, Skip(3)),
+                        test(BPF_JEQ, v_hi, Next, Fail),
+                        load(lo),
+                        test(if op == SeccompCmpOp::Lt { BPF_JGE } else { BPF_JGT }, v_lo, Fail, Next),
+                    ]),
+                }
+            }
+            let errno = rule.errno.min(MAX_ERRNO as u32) as u16;
+            block.push(Step::Stmt(bpf::stmt(BPF_RET | BPF_K, rule.action.to_ret(errno))));
+
+            let len = block.len();
+            let offset = |target: Target, at: usize| match target {
+                Next => 0,
+                Skip(n) => n,
+                Fail => (len - at - 1) as u8,
+            };
+            prog.extend(block.iter().enumerate().map(|(at, step)| match *step {
+                Step::Stmt(insn) => insn,
+                Step::Jump { code, k, jt, jf } => bpf::jump(code, k, offset(jt, at), offset(jf, at)),
+            }));
+        }
+        let errno = self.default_errno.min(MAX_ERRNO as u32) as u16;
+        prog.push(bpf::stmt(BPF_RET | BPF_K, self.default_action.to_ret(errno)));
+        prog
+    }
+}
+
+/// High-level seccomp interface functions
+
+/// Initialize seccomp subsystem
+pub fn initialize_seccomp() -> Result<(), i32> {
+    let mut guard = crate::security::SECCOMP.lock();
+    if guard.is_none() {
+        *guard = Some(SeccompSubsystem::new());
+    }
+    Ok(())
+}
+
+/// Cleanup seccomp subsystem
+pub fn cleanup_seccomp() {
+    *crate::security::SECCOMP.lock() = None;
+}
+
+/// Install seccomp filter for process
+pub fn install_seccomp_filter(pid: u64, filter: SeccompFilter) -> Result<u64, &'static str> {
+    let mut guard = crate::security::SECCOMP.lock();
+    if let Some(ref mut s) = *guard {
+        s.install_filter(pid, filter)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Check if syscall is allowed
+pub fn check_seccomp_syscall(pid: u64, syscall: u32, args: &[u64]) -> SeccompAction {
+    let guard = crate::security::SECCOMP.lock();
+    guard.as_ref().map(|s| s.check_syscall(pid, syscall, args)).unwrap_or(SeccompAction::Allow)
+}
+
+/// Remove seccomp filter
+pub fn remove_seccomp_filter(pid: u64) -> Result<(), &'static str> {
+    let mut guard = crate::security::SECCOMP.lock();
+    if let Some(ref mut s) = *guard {
+        s.remove_filter(pid)
+    } else {
+        Ok(())
+    }
+}
+
+/// Get seccomp statistics
+pub fn get_seccomp_statistics() -> SeccompStats {
+    let guard = crate::security::SECCOMP.lock();
+    guard.as_ref().map(|s| s.get_stats()).unwrap_or_default()
+}
+
+/// Run a closure on the subsystem, if it is initialized
+fn with_seccomp<R>(f: impl FnOnce(&mut SeccompSubsystem) -> R) -> Option<R> {
+    crate::security::SECCOMP.lock().as_mut().map(f)
+}
+
+/// Put the current task in strict mode
+pub fn set_mode_strict() -> Result<(), SeccompError> {
+    let task = TaskId::current().ok_or(SeccompError::InvalidArgument)?;
+    with_seccomp(|s| s.set_strict(task)).unwrap_or(Err(SeccompError::InvalidArgument))
+}
+
+/// Stack a filter on the current task
+///
+/// `privileged` stands for CAP_SYS_ADMIN; without it the task must have set
+/// no_new_privs, so a filter cannot confuse a more privileged program it
+/// later executes.
+pub fn set_mode_filter(
+    prog: Vec<SockFilter>,
+    flags: u32,
+    listener: Option<Arc<NotifyListener>>,
+    privileged: bool,
+) -> Result<u64, SeccompError> {
+    let task = TaskId::current().ok_or(SeccompError::InvalidArgument)?;
+    with_seccomp(|s| {
+        if !privileged && !s.no_new_privs(task) {
+            return Err(SeccompError::PermissionDenied);
+        }
+        s.attach_filter(task, prog, flags, listener)
+    })
+    .unwrap_or(Err(SeccompError::InvalidArgument))
+}
+
+/// Mode of the current task
+pub fn current_mode() -> SeccompMode {
+    TaskId::current()
+        .and_then(|task| with_seccomp(|s| s.mode(task)))
+        .unwrap_or_default()
+}
+
+/// Set no_new_privs on the current task
+pub fn set_no_new_privs() {
+    if let Some(task) = TaskId::current() {
+        with_seccomp(|s| s.set_no_new_privs(task));
+    }
+}
+
+/// Whether the current task has no_new_privs
+pub fn no_new_privs() -> bool {
+    TaskId::current()
+        .and_then(|task| with_seccomp(|s| s.no_new_privs(task)))
+        .unwrap_or(false)
+}
+
+/// fork(): the child starts with the current task's filters
+pub fn fork_task(child_pid: u64) {
+    if let Some(parent) = TaskId::current() {
+        with_seccomp(|s| s.fork_task(parent, child_pid));
+    }
+}
+
+/// clone(CLONE_THREAD): the new thread starts with the current task's filters
+pub fn clone_thread(tid: u64) {
+    if let Some(parent) = TaskId::current() {
+        with_seccomp(|s| s.clone_thread(parent, TaskId::thread(parent.pid, tid)));
+    }
+}
+
+/// A thread exited
+pub fn exit_thread(pid: u64, tid: u64) {
+    with_seccomp(|s| s.exit_thread(TaskId::thread(pid, tid)));
+}
+
+/// A process exited
+pub fn exit_task(pid: u64) {
+    with_seccomp(|s| s.cleanup_process(pid));
+}
+
+/// What the system call entry does once the filters ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompOutcome {
+    /// Run the system call
+    Allow,
+    /// Skip it and return this value: a negative errno or the supervisor's
+    /// answer
+    Return(i64),
+    /// The task was killed; the system call must not run
+    Killed,
+}
+
+/// Program counter of the trapped task
+fn current_pc(task: TaskId) -> u64 {
+    if task.tid != 0 {
+        let thread = crate::process::thread::thread_table().find_thread_ref(task.tid as usize);
+        if let Some(thread) = thread.filter(|thread| !thread.trapframe.is_null()) {
+            return unsafe { (*thread.trapframe).pc() as u64 };
+        }
+    }
+    let table = crate::process::PROC_TABLE.lock();
+    table
+        .find_ref(task.pid as crate::process::Pid)
+        .filter(|proc| !proc.trapframe.is_null())
+        .map_or(0, |proc| unsafe { (*proc.trapframe).pc() as u64 })
+}
+
+/// Deliver SIGSYS for SECCOMP_RET_TRAP, describing the trapped call
+fn send_sigsys(task: TaskId, data: &SeccompData, errno: u16) {
+    use crate::ipc::signal::{si_code, SigInfo, SIGSYS};
+
+    let info = SigInfo {
+        signo: SIGSYS as i32,
+        errno: errno as i32,
+        code: si_code::SYS_SECCOMP,
+        addr: data.instruction_pointer as usize,
+        // si_syscall and si_arch
+        status: data.nr,
+        value: data.arch as usize,
+        ..SigInfo::default()
+    };
+    let mut table = crate::process::PROC_TABLE.lock();
+    if let Some(proc) = table.find(task.pid as crate::process::Pid)
+        && let Some(ref signals) = proc.signals
+    {
+        let _ = signals.send_signal_info(SIGSYS, info);
+    }
+}
+
+/// Filter the current system call (`__secure_computing`)
+///
+/// Called on system call entry before dispatch with the number the task
+/// issued and the AUDIT_ARCH_* token of its personality. Threads cannot be killed
+/// on their own yet, so SECCOMP_RET_KILL_THREAD ends the whole process like
+/// KILL_PROCESS does.
+pub fn secure_computing(nr: u32, arch: u32, args: &[u64]) -> SeccompOutcome {
+    use crate::reliability::errno::ENOSYS;
+
+    let Some(task) = TaskId::current() else {
+        return SeccompOutcome::Allow;
+    };
+    // Filters are immutable once installed: run them without the lock
+    let Some((state, stats)) = with_seccomp(|s| s.tasks.get(&task).cloned().map(|state| (state, s.stats.clone()))).flatten()
+    else {
+        return SeccompOutcome::Allow;
+    };
+    if state.mode == SeccompMode::Disabled {
+        return SeccompOutcome::Allow;
+    }
+    let data = SeccompData::new(nr, arch, args, current_pc(task));
+    let verdict = state.evaluate(&data);
+    stats.lock().record(verdict.action);
+    if verdict.log {
+        crate::println!(
+            "[seccomp] pid={} tid={} arch={:#x} syscall={:#x} ip={:#x} action={:?} data={}",
+            task.pid,
+            task.tid,
+            data.arch,
+            nr,
+            data.instruction_pointer,
+            verdict.action,
+            verdict.data
+        );
+    }
+
+    match verdict.action {
+        SeccompAction::Allow | SeccompAction::Log => SeccompOutcome::Allow,
+        SeccompAction::Errno => SeccompOutcome::Return(-(verdict.data.min(MAX_ERRNO) as i64)),
+        SeccompAction::Trap => {
+            send_sigsys(task, &data, verdict.data);
+            SeccompOutcome::Return(-(ENOSYS as i64))
+        }
+        // The tracer decides; without one (or with it cancelling the call)
+        // the call fails
+        SeccompAction::Trace => match crate::process::ptrace::seccomp_stop(verdict.data as u32) {
+            Some(true) => SeccompOutcome::Allow,
+            _ => SeccompOutcome::Return(-(ENOSYS as i64)),
+        },
+        SeccompAction::Kill | SeccompAction::KillProcess => {
+            crate::process::kill(task.pid as usize);
+            SeccompOutcome::Killed
+        }
+        SeccompAction::UserNotif => {
+            let pid = if task.tid != 0 { task.tid } else { task.pid };
+            let reply = verdict
+                .listener
+                .and_then(|listener| listener.submit(pid as u32, data).and_then(|id| listener.wait_reply(id)));
+            match reply {
+                Some(resp) if resp.flags & SECCOMP_USER_NOTIF_FLAG_CONTINUE != 0 => SeccompOutcome::Allow,
+                Some(resp) if resp.error != 0 => SeccompOutcome::Return(resp.error as i64),
+                Some(resp) => SeccompOutcome::Return(resp.val),
+                // No listener, or it was closed
+                None => SeccompOutcome::Return(-(ENOSYS as i64)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reliability::errno::EPERM;
+
+    #[test]
+    fn test_seccomp_action_codes() {
+        assert_eq!(SeccompAction::Allow as u32, 0);
+        assert_eq!(SeccompAction::Kill as u32, 1);
+        assert_eq!(SeccompAction::Errno as u32, 2);
+    }
+
+    #[test]
+    fn test_seccomp_cmp_op_codes() {
+        assert_eq!(SeccompCmpOp::Eq as u32, 0);
+        assert_eq!(SeccompCmpOp::Ne as u32, 1);
+        assert_eq!(SeccompCmpOp::Gt as u32, 2);
+    }
+
+    #[test]
+    fn test_seccomp_rule() {
+        let rule = SeccompRule {
+            syscall: 60, // exit syscall
+            cmp_ops: vec![SeccompCmpOp::Eq],
+            cmp_vals: vec![0],
+            cmp_masks: vec![0],
+            action: SeccompAction::Allow,
+            errno: 0,
+        };
+
+        assert_eq!(rule.syscall, 60);
+        assert_eq!(rule.action, SeccompAction::Allow);
+    }
+
+    #[test]
+    fn test_seccomp_filter() {
+        let filter = SeccompFilter {
+            filter_id: 1,
+            rules: vec![],
+            default_action: SeccompAction::Allow,
+            default_errno: 0,
+            strict_mode: false,
+        };
+
+        assert_eq!(filter.filter_id, 1);
+        assert_eq!(filter.default_action, SeccompAction::Allow);
+        assert!(!filter.strict_mode);
+    }
+
+    #[test]
+    fn test_seccomp_subsystem() {
+        let mut subsystem = SeccompSubsystem::new();
+
+        let filter = SeccompFilter {
+            filter_id: 1,
+            rules: vec![],
+            default_action: SeccompAction::Kill,
+            default_errno: EPERM as u32,
+            strict_mode: false,
+        };
+
+        let result = subsystem.install_filter(1234, filter);
+        assert!(result.is_ok());
+
+        let action = subsystem.check_syscall(1234, 60, &[]);
+        assert_eq!(action, SeccompAction::Kill);
+
+        let action = subsystem.check_syscall(5678, 60, &[]);
+        assert_eq!(action, SeccompAction::Allow);
+    }
+
+    fn rule(syscall: u32, ops: &[(SeccompCmpOp, u64)], action: SeccompAction, errno: u32) -> SeccompRule {
+        SeccompRule {
+            syscall,
+            cmp_ops: ops.iter().map(|&(op, _)| op).collect(),
+            cmp_vals: ops.iter().map(|&(_, value)| value).collect(),
+            cmp_masks: vec![0xffff_0000_0000_00ff; ops.len()],
+            action,
+            errno,
+        }
+    }
+
+    #[test]
+    fn test_seccomp_compiled_comparisons() {
+        let filter = SeccompFilter {
+            filter_id: 0,
+            rules: vec![
+                rule(1, &[(SeccompCmpOp::Eq, 0x1_0000_0002)], SeccompAction::Errno, 1),
+                rule(2, &[(SeccompCmpOp::Ne, 7)], SeccompAction::Errno, 2),
+                rule(3, &[(SeccompCmpOp::Gt, 0x1_0000_0000)], SeccompAction::Errno, 3),
+                rule(4, &[(SeccompCmpOp::Le, 0x1_0000_0000)], SeccompAction::Errno, 4),
+                rule(5, &[(SeccompCmpOp::MaskedEq, 0xffff_0000_0000_0012)], SeccompAction::Errno, 5),
+                rule(6, &[(SeccompCmpOp::Eq, 1), (SeccompCmpOp::Lt, 10)], SeccompAction::Errno, 6),
+            ],
+            default_action: SeccompAction::Allow,
+            default_errno: 0,
+            strict_mode: false,
+        };
+        let prog = filter.compile();
+        assert_eq!(bpf::verify(&prog, SECCOMP_DATA_SIZE), Ok(()));
+
+        let run = |nr: u32, args: &[u64]| bpf::run(&prog, &SeccompData::new(nr, AUDIT_ARCH_NONE, args, 0).to_bytes());
+        let errno = |n: u32| SECCOMP_RET_ERRNO | n;
+        assert_eq!(run(1, &[0x1_0000_0002]), errno(1));
+        assert_eq!(run(1, &[0x2]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(2, &[8]), errno(2));
+        assert_eq!(run(2, &[7]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(3, &[0x1_0000_0001]), errno(3));
+        assert_eq!(run(3, &[0x2_0000_0000]), errno(3));
+        assert_eq!(run(3, &[0x1_0000_0000]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(4, &[0xffff_ffff]), errno(4));
+        assert_eq!(run(4, &[0x1_0000_0000]), errno(4));
+        assert_eq!(run(4, &[0x1_0000_0001]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(5, &[0xffff_1234_5678_9a12]), errno(5));
+        assert_eq!(run(5, &[0xfffe_0000_0000_0012]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(6, &[1, 9]), errno(6));
+        assert_eq!(run(6, &[1, 10]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(6, &[2, 9]), SECCOMP_RET_ALLOW);
+    }
+
+    /// Program returning `ret` for system call `nr` and allowing the rest
+    fn deny(nr: u32, ret: u32) -> Vec<SockFilter> {
+        vec![
+            bpf::stmt(BPF_LD | BPF_W | BPF_ABS, 0),
+            bpf::jump(BPF_JMP | BPF_JEQ | BPF_K, nr, 0, 1),
+            bpf::stmt(BPF_RET | BPF_K, ret),
+            bpf::stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        ]
+    }
+
+    #[test]
+    fn test_seccomp_stacking_and_fork() {
+        let mut subsystem = SeccompSubsystem::new();
+        let parent = TaskId::process(10);
+        let data = |nr: u32| SeccompData::new(nr, AUDIT_ARCH_NONE, &[], 0);
+
+        subsystem.attach_filter(parent, deny(7, SECCOMP_RET_ERRNO | 1), 0, None).unwrap();
+        subsystem.attach_filter(parent, deny(7, SECCOMP_RET_ERRNO | 2), 0, None).unwrap();
+        subsystem.attach_filter(parent, deny(8, SECCOMP_RET_LOG), 0, None).unwrap();
+        subsystem.attach_filter(parent, deny(8, SECCOMP_RET_KILL_PROCESS), 0, None).unwrap();
+        assert_eq!(subsystem.filter_count(parent), 4);
+        assert_eq!(subsystem.mode(parent), SeccompMode::Filter);
+
+        // Equal actions: the newest filter's data wins
+        let verdict = subsystem.evaluate(parent, &data(7));
+        assert_eq!((verdict.action, verdict.data), (SeccompAction::Errno, 2));
+        // KILL_PROCESS outranks everything
+        assert_eq!(subsystem.evaluate(parent, &data(8)).action, SeccompAction::KillProcess);
+        assert_eq!(subsystem.evaluate(parent, &data(9)).action, SeccompAction::Allow);
+
+        // The child keeps the chain and can only add to it
+        subsystem.fork_task(parent, 11);
+        let child = TaskId::process(11);
+        subsystem.attach_filter(child, deny(9, SECCOMP_RET_TRAP), 0, None).unwrap();
+        assert_eq!(subsystem.evaluate(child, &data(7)).data, 2);
+        assert_eq!(subsystem.evaluate(child, &data(9)).action, SeccompAction::Trap);
+        assert_eq!(subsystem.evaluate(parent, &data(9)).action, SeccompAction::Allow);
+
+        // Filter and strict mode do not mix
+        assert_eq!(subsystem.set_strict(child), Err(SeccompError::InvalidArgument));
+        subsystem.cleanup_process(11);
+        assert_eq!(subsystem.mode(child), SeccompMode::Disabled);
+    }
+
+    #[test]
+    fn test_seccomp_attach_rejects() {
+        let mut subsystem = SeccompSubsystem::new();
+        let task = TaskId::process(20);
+        let bad = vec![bpf::stmt(BPF_LD | BPF_W | BPF_ABS, 64), bpf::stmt(BPF_RET | BPF_K, 0)];
+        assert_eq!(subsystem.attach_filter(task, bad, 0, None), Err(SeccompError::InvalidArgument));
+        assert_eq!(
+            subsystem.attach_filter(task, deny(1, 0), 1 << 7, None),
+            Err(SeccompError::InvalidArgument)
+        );
+
+        let listener = Arc::new(NotifyListener::new());
+        subsystem.attach_filter(task, deny(1, SECCOMP_RET_USER_NOTIF), 0, Some(listener.clone())).unwrap();
+        assert_eq!(
+            subsystem.attach_filter(task, deny(2, SECCOMP_RET_USER_NOTIF), 0, Some(listener)),
+            Err(SeccompError::Busy)
+        );
+
+        let strict = TaskId::process(21);
+        subsystem.set_strict(strict).unwrap();
+        assert_eq!(subsystem.attach_filter(strict, deny(1, 0), 0, None), Err(SeccompError::InvalidArgument));
+        assert_eq!(subsystem.evaluate(strict, &SeccompData::new(0x2003, AUDIT_ARCH_NONE, &[], 0)).action, SeccompAction::Allow);
+        assert_eq!(subsystem.evaluate(strict, &SeccompData::new(0x2000, AUDIT_ARCH_NONE, &[], 0)).action, SeccompAction::Kill);
+        // Returning from a signal handler is allowed, under either personality
+        assert_eq!(subsystem.evaluate(strict, &SeccompData::new(0x500F, AUDIT_ARCH_NONE, &[], 0)).action, SeccompAction::Allow);
+        for nr in STRICT_LINUX_SYSCALLS {
+            assert_eq!(subsystem.evaluate(strict, &SeccompData::new(nr, AUDIT_ARCH_NATIVE, &[], 0)).action, SeccompAction::Allow);
+        }
+        // Native numbers mean nothing under the Linux personality
+        assert_eq!(subsystem.evaluate(strict, &SeccompData::new(0x2003, AUDIT_ARCH_NATIVE, &[], 0)).action, SeccompAction::Kill);
+    }
+
+    #[test]
+    fn test_seccomp_tsync() {
+        let mut subsystem = SeccompSubsystem::new();
+        let main = TaskId::process(30);
+        let thread = TaskId::thread(30, 5);
+        let other = TaskId::thread(30, 6);
+        let data = SeccompData::new(3, AUDIT_ARCH_NONE, &[], 0);
+
+        subsystem.attach_filter(main, deny(1, SECCOMP_RET_ERRNO | 1), 0, None).unwrap();
+        subsystem.clone_thread(main, thread);
+        subsystem.clone_thread(main, other);
+
+        // A thread's own filter stays with it...
+        subsystem.attach_filter(thread, deny(2, SECCOMP_RET_ERRNO | 2), 0, None).unwrap();
+        assert_eq!(subsystem.filter_count(main), 1);
+        // ...until it synchronizes the others onto its chain
+        let id = subsystem
+            .attach_filter(thread, deny(3, SECCOMP_RET_ERRNO | 3), SECCOMP_FILTER_FLAG_TSYNC, None)
+            .unwrap();
+        for task in [main, thread, other] {
+            assert_eq!(subsystem.newest_filter(task), Some(id));
+            assert_eq!(subsystem.evaluate(task, &data).data, 3);
+        }
+
+        // A thread whose chain diverged blocks synchronization
+        subsystem.attach_filter(other, deny(4, SECCOMP_RET_ERRNO | 4), 0, None).unwrap();
+        assert_eq!(
+            subsystem.attach_filter(main, deny(5, SECCOMP_RET_ERRNO | 5), SECCOMP_FILTER_FLAG_TSYNC, None),
+            Err(SeccompError::Unsynchronized(6))
+        );
+        assert_eq!(subsystem.newest_filter(main), Some(id));
+
+        subsystem.exit_thread(other);
+        assert!(subsystem
+            .attach_filter(main, deny(5, SECCOMP_RET_ERRNO | 5), SECCOMP_FILTER_FLAG_TSYNC, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_seccomp_user_notif_listener() {
+        let mut subsystem = SeccompSubsystem::new();
+        let task = TaskId::process(40);
+        let listener = Arc::new(NotifyListener::new());
+        subsystem
+            .attach_filter(task, deny(9, SECCOMP_RET_USER_NOTIF), SECCOMP_FILTER_FLAG_NEW_LISTENER, Some(listener.clone()))
+            .unwrap();
+
+        let data = SeccompData::new(9, AUDIT_ARCH_NATIVE, &[1, 2], 0x1000);
+        let verdict = subsystem.evaluate(task, &data);
+        assert_eq!(verdict.action, SeccompAction::UserNotif);
+        let notifier = verdict.listener.unwrap();
+        assert!(Arc::ptr_eq(&notifier, &listener));
+
+        let id = notifier.submit(40, data).unwrap();
+        assert!(listener.id_valid(id));
+        // Only received notifications can be answered
+        let resp = notify::SeccompNotifResp { id, val: 5, error: 0, flags: 0 };
+        assert_eq!(listener.send(&resp), Err(notify::NotifyError::NotFound));
+        let notif = listener.recv(true).unwrap();
+        assert_eq!((notif.id, notif.pid, notif.data), (id, 40, data));
+        assert_eq!(listener.recv(true).unwrap_err(), notify::NotifyError::WouldBlock);
+        let bad = notify::SeccompNotifResp { flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE, ..resp };
+        assert_eq!(listener.send(&bad), Err(notify::NotifyError::InvalidArgument));
+        listener.send(&resp).unwrap();
+        assert_eq!(notifier.wait_reply(id).map(|resp| resp.val), Some(5));
+        assert!(!listener.id_valid(id));
+
+        // Once the listener is gone, nothing more can be queued
+        listener.close();
+        assert!(listener.submit(40, data).is_none());
+    }
+}
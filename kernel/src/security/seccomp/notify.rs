@@ -0,0 +1,243 @@
+// seccomp user notification (SECCOMP_RET_USER_NOTIF)
+//
+// A filter installed with SECCOMP_FILTER_FLAG_NEW_LISTENER hands its caller
+// a listener file. System calls the filter answers with USER_NOTIF are
+// queued on the listener and the calling task sleeps until the supervisor
+// receives the notification and sends a response through the same file.
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use spin::Mutex;
+
+use super::SeccompData;
+
+/// Let the system call run instead of answering it
+pub const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+
+/// `SECCOMP_IOCTL_NOTIF_RECV`: `_IOWR('!', 0, struct seccomp_notif)`
+pub const SECCOMP_IOCTL_NOTIF_RECV: u64 = 0xc050_2100;
+/// `SECCOMP_IOCTL_NOTIF_SEND`: `_IOWR('!', 1, struct seccomp_notif_resp)`
+pub const SECCOMP_IOCTL_NOTIF_SEND: u64 = 0xc018_2101;
+/// `SECCOMP_IOCTL_NOTIF_ID_VALID`: `_IOW('!', 2, __u64)`
+pub const SECCOMP_IOCTL_NOTIF_ID_VALID: u64 = 0x4008_2102;
+/// Encoding of ID_VALID before Linux 5.17 fixed its direction
+pub const SECCOMP_IOCTL_NOTIF_ID_VALID_WRONG_DIR: u64 = 0x8008_2102;
+
+/// `struct seccomp_notif`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeccompNotif {
+    pub id: u64,
+    pub pid: u32,
+    pub flags: u32,
+    pub data: SeccompData,
+}
+
+/// `struct seccomp_notif_resp`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeccompNotifResp {
+    pub id: u64,
+    pub val: i64,
+    pub error: i32,
+    pub flags: u32,
+}
+
+/// `struct seccomp_notif_sizes`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SeccompNotifSizes {
+    pub seccomp_notif: u16,
+    pub seccomp_notif_resp: u16,
+    pub seccomp_data: u16,
+}
+
+impl SeccompNotifSizes {
+    pub const fn current() -> Self {
+        Self {
+            seccomp_notif: core::mem::size_of::<SeccompNotif>() as u16,
+            seccomp_notif_resp: core::mem::size_of::<SeccompNotifResp>() as u16,
+            seccomp_data: core::mem::size_of::<SeccompData>() as u16,
+        }
+    }
+}
+
+/// Where a notification stands
+#[derive(Debug, Clone, Copy)]
+enum NotifState {
+    /// Waiting for the supervisor to receive it
+    Queued,
+    /// Received, waiting for a response
+    Sent,
+    /// Answered
+    Replied(SeccompNotifResp),
+}
+
+/// Listener errors, each matching the errno Linux returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyError {
+    /// Nothing queued and the caller must not block (EAGAIN)
+    WouldBlock,
+    /// Unknown id, or a notification not received yet (ENOENT)
+    NotFound,
+    /// Malformed response (EINVAL)
+    InvalidArgument,
+}
+
+#[derive(Debug, Default)]
+struct ListenerState {
+    next_id: u64,
+    notifications: BTreeMap<u64, (SeccompNotif, NotifState)>,
+    closed: bool,
+}
+
+/// Supervisor end of a filter's notifications
+#[derive(Debug, Default)]
+pub struct NotifyListener {
+    state: Mutex<ListenerState>,
+}
+
+impl NotifyListener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sleep channel of tasks waiting for notifications to receive
+    fn recv_chan(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Sleep channel of tasks waiting for responses
+    fn reply_chan(&self) -> usize {
+        self as *const Self as usize | 1
+    }
+
+    /// Queue a notification for the current system call
+    ///
+    /// Returns `None` once the listener file is closed.
+    pub fn submit(&self, pid: u32, data: SeccompData) -> Option<u64> {
+        let id = {
+            let mut state = self.state.lock();
+            if state.closed {
+                return None;
+            }
+            let id = state.next_id;
+            state.next_id += 1;
+            let notif = SeccompNotif { id, pid, flags: 0, data };
+            state.notifications.insert(id, (notif, NotifState::Queued));
+            id
+        };
+        crate::process::wakeup(self.recv_chan());
+        crate::process::wakeup(crate::syscalls::POLL_WAKE_CHAN);
+        Some(id)
+    }
+
+    /// Block until notification `id` is answered
+    ///
+    /// Returns `None` if the listener is closed first; the system call then
+    /// fails with ENOSYS.
+    pub fn wait_reply(&self, id: u64) -> Option<SeccompNotifResp> {
+        loop {
+            {
+                let mut state = self.state.lock();
+                if let Some((_, NotifState::Replied(resp))) = state.notifications.get(&id) {
+                    let resp = *resp;
+                    state.notifications.remove(&id);
+                    return Some(resp);
+                }
+                if state.closed {
+                    state.notifications.remove(&id);
+                    return None;
+                }
+            }
+            crate::process::sleep_unless(self.reply_chan(), || {
+                let state = self.state.lock();
+                state.closed || matches!(state.notifications.get(&id), Some((_, NotifState::Replied(_))))
+            });
+        }
+    }
+
+    /// Hand the oldest queued notification to the supervisor
+    pub fn recv(&self, nonblock: bool) -> Result<SeccompNotif, NotifyError> {
+        loop {
+            {
+                let mut state = self.state.lock();
+                let queued = state
+                    .notifications
+                    .values_mut()
+                    .find(|(_, notif_state)| matches!(notif_state, NotifState::Queued));
+                if let Some((notif, notif_state)) = queued {
+                    *notif_state = NotifState::Sent;
+                    return Ok(*notif);
+                }
+            }
+            if nonblock || crate::process::myproc().is_none() {
+                return Err(NotifyError::WouldBlock);
+            }
+            crate::process::sleep_unless(self.recv_chan(), || self.has_queued());
+        }
+    }
+
+    /// Answer a received notification and wake its task
+    pub fn send(&self, resp: &SeccompNotifResp) -> Result<(), NotifyError> {
+        if resp.flags & !SECCOMP_USER_NOTIF_FLAG_CONTINUE != 0 {
+            return Err(NotifyError::InvalidArgument);
+        }
+        // Continuing means the system call runs, so it cannot also be answered
+        if resp.flags & SECCOMP_USER_NOTIF_FLAG_CONTINUE != 0 && (resp.val != 0 || resp.error != 0) {
+            return Err(NotifyError::InvalidArgument);
+        }
+        {
+            let mut state = self.state.lock();
+            match state.notifications.get_mut(&resp.id) {
+                Some((_, notif_state @ NotifState::Sent)) => *notif_state = NotifState::Replied(*resp),
+                _ => return Err(NotifyError::NotFound),
+            }
+        }
+        crate::process::wakeup(self.reply_chan());
+        Ok(())
+    }
+
+    /// Whether notification `id` still waits for an answer
+    pub fn id_valid(&self, id: u64) -> bool {
+        matches!(
+            self.state.lock().notifications.get(&id),
+            Some((_, NotifState::Queued | NotifState::Sent))
+        )
+    }
+
+    fn has_queued(&self) -> bool {
+        self.state
+            .lock()
+            .notifications
+            .values()
+            .any(|(_, notif_state)| matches!(notif_state, NotifState::Queued))
+    }
+
+    /// Poll events: readable with notifications to receive, writable with
+    /// notifications to answer
+    pub fn poll(&self) -> i16 {
+        let state = self.state.lock();
+        let mut events = 0;
+        for (_, notif_state) in state.notifications.values() {
+            match notif_state {
+                NotifState::Queued => events |= crate::posix::POLLIN,
+                NotifState::Sent => events |= crate::posix::POLLOUT,
+                NotifState::Replied(_) => {}
+            }
+        }
+        events
+    }
+
+    /// The listener file went away: fail everything still waiting
+    pub fn close(&self) {
+        self.state.lock().closed = true;
+        crate::process::wakeup(self.reply_chan());
+        crate::process::wakeup(self.recv_chan());
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+}
@@ -0,0 +1,144 @@
+//! Block device layer
+//!
+//! Filesystems and drivers do not call [`BlockDevice`] directly any more;
+//! they submit [`Bio`]s to the device's [`RequestQueue`]:
+//!
+//! - every operation is fallible and covers any number of whole sectors;
+//! - contiguous bios of the same kind are merged into one [`Request`]
+//!   before they reach the driver;
+//! - an [`IoScheduler`] (noop or deadline) decides dispatch order;
+//! - completion is reported through a per-bio callback, so a driver can
+//!   finish requests from its interrupt handler. The synchronous helpers
+//!   on the queue wait for that callback;
+//! - `PREFLUSH` and `FUA` are honoured on devices with a volatile write
+//!   cache, emulated with flushes where the device has no native FUA, and
+//!   dropped where there is no cache to flush. Discard fails with
+//!   `NotSupported` on devices that cannot do it.
+//!
+//! Devices are registered by name with [`register_disk`]; the ext2 mount
+//! path and the disk I/O driver look their queue up with [`get_disk`].
+
+extern crate alloc;
+
+use alloc::{boxed::Box, collections::BTreeMap, string::{String, ToString}, sync::Arc, vec::Vec};
+use spin::Mutex;
+
+use crate::platform::drivers::BlockDevice;
+use nos_nos_error_handling::unified::KernelError;
+
+pub mod queue;
+pub mod request;
+pub mod sched;
+
+#[cfg(feature = "kernel_tests")]
+pub mod tests;
+
+pub use queue::{Plug, QueueStats, RequestQueue};
+pub use request::{Bio, EndIo, ReqFlags, ReqOp, Request};
+pub use sched::{Deadline, IoScheduler, Noop};
+
+pub use crate::platform::drivers::SECTOR_SIZE;
+
+/// Block layer error types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// The device reported a media or transport error
+    IoError,
+    /// Request reaches past the end of the device
+    OutOfRange,
+    /// Buffer is not a whole number of sectors, or the request is empty
+    Misaligned,
+    /// The device cannot do this operation (e.g. discard)
+    NotSupported,
+    /// The device did not complete the request in time
+    Timeout,
+    /// The device has gone away
+    NoDevice,
+    /// The device is read-only
+    ReadOnly,
+}
+
+pub type BlockResult<T> = Result<T, BlockError>;
+
+impl From<BlockError> for KernelError {
+    fn from(err: BlockError) -> Self {
+        match err {
+            BlockError::IoError => KernelError::IoError,
+            BlockError::OutOfRange | BlockError::Misaligned => KernelError::InvalidArgument,
+            BlockError::NotSupported => KernelError::NotSupported,
+            BlockError::Timeout => KernelError::TimedOut,
+            BlockError::NoDevice => KernelError::NoDevice,
+            BlockError::ReadOnly => KernelError::PermissionDenied,
+        }
+    }
+}
+
+/// What a device can do, used to plan flush, FUA and discard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockFeatures {
+    /// Completed writes may sit in a volatile cache until flushed
+    pub write_cache: bool,
+    /// Writes can bypass the cache (Force Unit Access)
+    pub fua: bool,
+    /// Discard/TRIM is supported
+    pub discard: bool,
+    /// Largest request the driver accepts, in sectors
+    pub max_sectors: u32,
+}
+
+impl Default for BlockFeatures {
+    fn default() -> Self {
+        Self { write_cache: false, fua: false, discard: false, max_sectors: 256 }
+    }
+}
+
+/// Scheduler by name, as accepted by `RequestQueue::set_scheduler`
+pub fn scheduler_by_name(name: &str) -> Option<Box<dyn IoScheduler>> {
+    match name {
+        "noop" | "none" => Some(Box::new(Noop::new())),
+        "deadline" | "mq-deadline" => Some(Box::new(Deadline::new())),
+        _ => None,
+    }
+}
+
+static DISKS: Mutex<BTreeMap<String, Arc<RequestQueue>>> = Mutex::new(BTreeMap::new());
+
+/// Put `dev` behind a request queue and register it as `name`
+///
+/// Rotational media want the deadline scheduler; anything with its own
+/// queueing (NVMe, virtio) does better with noop.
+pub fn register_disk(name: &str, dev: Arc<dyn BlockDevice>, sched: Box<dyn IoScheduler>) -> Arc<RequestQueue> {
+    let queue = RequestQueue::new(name, dev, sched);
+    DISKS.lock().insert(name.to_string(), queue.clone());
+    crate::println!(
+        "block: {}: {} sectors, scheduler {}",
+        name,
+        queue.nr_sectors(),
+        queue.scheduler_name()
+    );
+    queue
+}
+
+/// Drop a disk from the registry; requests already queued still complete
+pub fn unregister_disk(name: &str) -> Option<Arc<RequestQueue>> {
+    DISKS.lock().remove(name)
+}
+
+pub fn get_disk(name: &str) -> Option<Arc<RequestQueue>> {
+    DISKS.lock().get(name).cloned()
+}
+
+pub fn list_disks() -> Vec<String> {
+    DISKS.lock().keys().cloned().collect()
+}
+
+/// Register the built-in RAM disk as `ram0` and the virtio disks the
+/// drivers found
+pub fn init() {
+    let ram: Arc<dyn BlockDevice> = Arc::new(crate::drivers::RamDisk);
+    register_disk("ram0", ram, Box::new(Noop::new()));
+    for dev in crate::drivers::virtio::block_devices() {
+        let name = dev.name().to_string();
+        register_disk(&name, dev, Box::new(Noop::new()));
+    }
+}
@@ -0,0 +1,470 @@
+//! Request queues
+//!
+//! Each registered disk has one [`RequestQueue`]. [`RequestQueue::submit_bio`]
+//! checks a bio against the device, works out the flush steps it needs,
+//! merges it or queues it as a new request, and dispatches whatever the
+//! driver has room for. Completions come back through [`Request::end`],
+//! which runs the next flush step or the bios' callbacks and dispatches
+//! again.
+//!
+//! Requests with a preflush, and bare flushes, skip the scheduler and are
+//! dispatched ahead of it in submission order.
+
+extern crate alloc;
+
+use alloc::{
+    boxed::Box,
+    collections::VecDeque,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use spin::Mutex;
+
+use super::{
+    request::{Bio, ReqFlags, ReqOp, Request, SEQ_DATA, SEQ_POSTFLUSH, SEQ_PREFLUSH},
+    sched::IoScheduler,
+    BlockError, BlockFeatures, BlockResult,
+};
+use crate::platform::drivers::BlockDevice;
+
+/// Requests handed to the driver at once unless changed with `set_depth`
+pub const DEFAULT_DEPTH: usize = 32;
+
+struct Inner {
+    sched: Box<dyn IoScheduler>,
+    /// Flush-sequenced requests, dispatched before the scheduler's
+    head: VecDeque<Request>,
+    in_flight: usize,
+    next_id: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    flushes: AtomicU64,
+    discards: AtomicU64,
+    read_sectors: AtomicU64,
+    write_sectors: AtomicU64,
+    merges: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Snapshot of a queue's counters
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueueStats {
+    /// Completed read requests
+    pub reads: u64,
+    /// Completed write requests
+    pub writes: u64,
+    /// Completed flushes, including those run for PREFLUSH and FUA
+    pub flushes: u64,
+    pub discards: u64,
+    pub read_sectors: u64,
+    pub write_sectors: u64,
+    /// Bios merged into an already queued request
+    pub merges: u64,
+    /// Bios and requests that failed
+    pub errors: u64,
+    /// Requests waiting for dispatch
+    pub queued: usize,
+    /// Requests the driver is working on
+    pub in_flight: usize,
+}
+
+/// Block request queue of one device
+pub struct RequestQueue {
+    name: String,
+    dev: Arc<dyn BlockDevice>,
+    features: BlockFeatures,
+    sector_size: usize,
+    nr_sectors: u64,
+    depth: AtomicUsize,
+    inner: Mutex<Inner>,
+    plugged: AtomicUsize,
+    /// Someone is in the dispatch loop
+    running: AtomicBool,
+    /// More work turned up while the dispatch loop was running
+    rerun: AtomicBool,
+    counters: Counters,
+    this: Weak<RequestQueue>,
+}
+
+/// Holds back dispatch while alive so a batch of bios can merge
+///
+/// Dispatch resumes when the last plug on the queue is dropped.
+pub struct Plug<'a> {
+    queue: &'a RequestQueue,
+}
+
+impl Drop for Plug<'_> {
+    fn drop(&mut self) {
+        if self.queue.plugged.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.queue.run();
+        }
+    }
+}
+
+/// Result and buffer of a completed bio
+type Completion = (BlockResult<()>, Vec<u8>);
+
+/// Results of a batch of synchronous bios, filled in by their callbacks
+struct Waiter {
+    remaining: AtomicUsize,
+    results: Mutex<Vec<Option<Completion>>>,
+}
+
+impl RequestQueue {
+    pub fn new(name: &str, dev: Arc<dyn BlockDevice>, sched: Box<dyn IoScheduler>) -> Arc<Self> {
+        let features = dev.features();
+        let sector_size = dev.block_size();
+        let nr_sectors = dev.num_blocks() as u64;
+        Arc::new_cyclic(|this| Self {
+            name: name.to_string(),
+            dev,
+            features,
+            sector_size,
+            nr_sectors,
+            depth: AtomicUsize::new(DEFAULT_DEPTH),
+            inner: Mutex::new(Inner { sched, head: VecDeque::new(), in_flight: 0, next_id: 1 }),
+            plugged: AtomicUsize::new(0),
+            running: AtomicBool::new(false),
+            rerun: AtomicBool::new(false),
+            counters: Counters::default(),
+            this: this.clone(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.dev
+    }
+
+    pub fn features(&self) -> BlockFeatures {
+        self.features
+    }
+
+    /// Bytes per sector (the device's block size)
+    pub fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    /// Capacity in sectors
+    pub fn nr_sectors(&self) -> u64 {
+        self.nr_sectors
+    }
+
+    pub fn scheduler_name(&self) -> &'static str {
+        self.inner.lock().sched.name()
+    }
+
+    /// Replace the scheduler, moving over anything still queued
+    pub fn set_scheduler(&self, mut sched: Box<dyn IoScheduler>) {
+        let now = crate::time::uptime_ms();
+        let mut inner = self.inner.lock();
+        for rq in inner.sched.drain() {
+            sched.insert(rq, now);
+        }
+        inner.sched = sched;
+    }
+
+    /// Limit on requests handed to the driver at once
+    pub fn set_depth(&self, depth: usize) {
+        self.depth.store(depth.max(1), Ordering::Relaxed);
+        self.run();
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let c = &self.counters;
+        let inner = self.inner.lock();
+        QueueStats {
+            reads: c.reads.load(Ordering::Relaxed),
+            writes: c.writes.load(Ordering::Relaxed),
+            flushes: c.flushes.load(Ordering::Relaxed),
+            discards: c.discards.load(Ordering::Relaxed),
+            read_sectors: c.read_sectors.load(Ordering::Relaxed),
+            write_sectors: c.write_sectors.load(Ordering::Relaxed),
+            merges: c.merges.load(Ordering::Relaxed),
+            errors: c.errors.load(Ordering::Relaxed),
+            queued: inner.head.len() + inner.sched.len(),
+            in_flight: inner.in_flight,
+        }
+    }
+
+    pub fn plug(&self) -> Plug<'_> {
+        self.plugged.fetch_add(1, Ordering::AcqRel);
+        Plug { queue: self }
+    }
+
+    /// Check a bio and work out its flush sequence
+    ///
+    /// `None` means there is nothing to do: a flush on a device without a
+    /// write cache.
+    fn prepare(&self, bio: &mut Bio) -> BlockResult<Option<u8>> {
+        match bio.op {
+            ReqOp::Read | ReqOp::Write => {
+                if bio.data.is_empty() || !bio.data.len().is_multiple_of(self.sector_size) {
+                    return Err(BlockError::Misaligned);
+                }
+                bio.nr_sectors = u32::try_from(bio.data.len() / self.sector_size)
+                    .map_err(|_| BlockError::OutOfRange)?;
+            }
+            ReqOp::Discard => {
+                if !self.features.discard {
+                    return Err(BlockError::NotSupported);
+                }
+                if bio.nr_sectors == 0 {
+                    return Err(BlockError::Misaligned);
+                }
+            }
+            ReqOp::Flush => {}
+        }
+        if bio.op != ReqOp::Flush && bio.end_sector() > self.nr_sectors {
+            return Err(BlockError::OutOfRange);
+        }
+
+        let mut seq = SEQ_DATA;
+        let fua = bio.flags.contains(ReqFlags::FUA);
+        let preflush = bio.flags.contains(ReqFlags::PREFLUSH);
+        bio.flags.0 &= !ReqFlags::PREFLUSH;
+        if !self.features.write_cache {
+            // Every completed write is already durable
+            bio.flags.0 &= !ReqFlags::FUA;
+            if bio.op == ReqOp::Flush {
+                return Ok(None);
+            }
+            return Ok(Some(seq));
+        }
+        if preflush && bio.op != ReqOp::Flush {
+            seq |= SEQ_PREFLUSH;
+        }
+        if fua && (bio.op != ReqOp::Write || !self.features.fua) {
+            bio.flags.0 &= !ReqFlags::FUA;
+            if bio.op == ReqOp::Write {
+                seq |= SEQ_POSTFLUSH;
+            }
+        }
+        Ok(Some(seq))
+    }
+
+    /// Queue `bio`; its callback runs once it has completed or failed
+    pub fn submit_bio(&self, mut bio: Bio) {
+        let seq = match self.prepare(&mut bio) {
+            Ok(Some(seq)) => seq,
+            Ok(None) => return bio.complete(Ok(())),
+            Err(err) => {
+                self.counters.errors.fetch_add(1, Ordering::Relaxed);
+                return bio.complete(Err(err));
+            }
+        };
+
+        {
+            let mut inner = self.inner.lock();
+            let bio = if seq == SEQ_DATA && bio.op != ReqOp::Flush {
+                match inner.sched.merge(bio, self.features.max_sectors) {
+                    Some(bio) => bio,
+                    None => {
+                        self.counters.merges.fetch_add(1, Ordering::Relaxed);
+                        drop(inner);
+                        return self.run();
+                    }
+                }
+            } else {
+                bio
+            };
+
+            let id = inner.next_id;
+            inner.next_id += 1;
+            let rq = Request::from_bio(bio, seq, id, self.this.clone());
+            if seq & SEQ_PREFLUSH != 0 || rq.op() == ReqOp::Flush {
+                inner.head.push_back(rq);
+            } else {
+                inner.sched.insert(rq, crate::time::uptime_ms());
+            }
+        }
+        self.run();
+    }
+
+    /// Hand requests to the driver until it is full or nothing is queued
+    pub fn run(&self) {
+        if self.plugged.load(Ordering::Acquire) > 0 {
+            return;
+        }
+        // A completion or submission from inside the driver call lands
+        // here again; let the outer loop pick its work up.
+        self.rerun.store(true, Ordering::Release);
+        while self.rerun.load(Ordering::Acquire) {
+            if self.running.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            self.rerun.store(false, Ordering::Release);
+            while let Some(rq) = self.next_request() {
+                self.dev.queue_rq(rq);
+            }
+            self.running.store(false, Ordering::Release);
+        }
+    }
+
+    fn next_request(&self) -> Option<Request> {
+        let mut inner = self.inner.lock();
+        if inner.in_flight >= self.depth.load(Ordering::Relaxed) {
+            return None;
+        }
+        let rq = match inner.head.pop_front() {
+            Some(rq) => rq,
+            None => inner.sched.dispatch(crate::time::uptime_ms())?,
+        };
+        inner.in_flight += 1;
+        Some(rq)
+    }
+
+    /// Called through `Request::end` when the driver finishes a step
+    pub(super) fn complete(&self, mut rq: Request, result: BlockResult<()>) {
+        self.inner.lock().in_flight -= 1;
+        match result {
+            Ok(()) => {
+                self.account(&rq);
+                if rq.advance() {
+                    // Next flush step goes out before anything else
+                    self.inner.lock().head.push_front(rq);
+                    return self.run();
+                }
+            }
+            Err(_) => {
+                self.counters.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        rq.finish(result);
+        self.run();
+    }
+
+    fn account(&self, rq: &Request) {
+        let c = &self.counters;
+        let sectors = rq.nr_sectors() as u64;
+        match rq.op() {
+            ReqOp::Read => {
+                c.reads.fetch_add(1, Ordering::Relaxed);
+                c.read_sectors.fetch_add(sectors, Ordering::Relaxed);
+            }
+            ReqOp::Write => {
+                c.writes.fetch_add(1, Ordering::Relaxed);
+                c.write_sectors.fetch_add(sectors, Ordering::Relaxed);
+            }
+            ReqOp::Flush => {
+                c.flushes.fetch_add(1, Ordering::Relaxed);
+            }
+            ReqOp::Discard => {
+                c.discards.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Submit `bio` and wait for it; returns the result and the buffer
+    ///
+    /// Sleeps when called from a process, spins otherwise.
+    pub fn submit_bio_wait(&self, bio: Bio) -> (BlockResult<()>, Vec<u8>) {
+        let result = self.submit_batch_wait(vec![bio]).pop();
+        result.unwrap_or((Err(BlockError::IoError), Vec::new()))
+    }
+
+    /// Submit `bios` under one plug, so neighbours can merge, and wait for
+    /// all of them
+    ///
+    /// Results come back in submission order.
+    pub fn submit_batch_wait(&self, bios: Vec<Bio>) -> Vec<(BlockResult<()>, Vec<u8>)> {
+        let count = bios.len();
+        let waiter = Arc::new(Waiter {
+            remaining: AtomicUsize::new(count),
+            results: Mutex::new((0..count).map(|_| None).collect()),
+        });
+        let chan = Arc::as_ptr(&waiter) as usize;
+        {
+            let _plug = self.plug();
+            for (i, bio) in bios.into_iter().enumerate() {
+                let done = waiter.clone();
+                self.submit_bio(bio.with_flags(ReqFlags::SYNC).on_complete(move |result, data| {
+                    done.results.lock()[i] = Some((result, data));
+                    if done.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                        crate::process::wakeup(chan);
+                    }
+                }));
+            }
+        }
+        while waiter.remaining.load(Ordering::Acquire) > 0 {
+            crate::process::sleep_unless(chan, || waiter.remaining.load(Ordering::Acquire) == 0);
+            core::hint::spin_loop();
+        }
+        let results = core::mem::take(&mut *waiter.results.lock());
+        results
+            .into_iter()
+            .map(|r| r.unwrap_or((Err(BlockError::IoError), Vec::new())))
+            .collect()
+    }
+
+    /// Read whole sectors starting at `sector`
+    pub fn read(&self, sector: u64, buf: &mut [u8]) -> BlockResult<()> {
+        let (result, data) = self.submit_bio_wait(Bio::read(sector, buf.len()));
+        result?;
+        buf.copy_from_slice(&data);
+        Ok(())
+    }
+
+    /// Write whole sectors starting at `sector`, with `ReqFlags` bits
+    pub fn write(&self, sector: u64, buf: &[u8], flags: u32) -> BlockResult<()> {
+        self.submit_bio_wait(Bio::write(sector, buf.to_vec()).with_flags(flags)).0
+    }
+
+    /// Make every completed write durable
+    pub fn flush(&self) -> BlockResult<()> {
+        self.submit_bio_wait(Bio::flush()).0
+    }
+
+    pub fn discard(&self, sector: u64, nr_sectors: u32) -> BlockResult<()> {
+        self.submit_bio_wait(Bio::discard(sector, nr_sectors)).0
+    }
+
+    /// Sectors covering `len` bytes at byte `offset`: (first sector, bytes)
+    fn span(&self, offset: u64, len: usize) -> (u64, usize) {
+        let ss = self.sector_size as u64;
+        let first = offset / ss;
+        let end = (offset + len as u64).div_ceil(ss);
+        (first, ((end - first) * ss) as usize)
+    }
+
+    /// Read `buf.len()` bytes at byte `offset`, which need not be aligned
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> BlockResult<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let (sector, bytes) = self.span(offset, buf.len());
+        let (result, data) = self.submit_bio_wait(Bio::read(sector, bytes));
+        result?;
+        let skip = (offset % self.sector_size as u64) as usize;
+        buf.copy_from_slice(&data[skip..skip + buf.len()]);
+        Ok(())
+    }
+
+    /// Write `buf` at byte `offset`, reading in partial sectors first
+    pub fn write_at(&self, offset: u64, buf: &[u8], flags: u32) -> BlockResult<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let (sector, bytes) = self.span(offset, buf.len());
+        let skip = (offset % self.sector_size as u64) as usize;
+        let data = if skip == 0 && bytes == buf.len() {
+            buf.to_vec()
+        } else {
+            let (result, mut data) = self.submit_bio_wait(Bio::read(sector, bytes));
+            result?;
+            data[skip..skip + buf.len()].copy_from_slice(buf);
+            data
+        };
+        self.submit_bio_wait(Bio::write(sector, data).with_flags(flags)).0
+    }
+}
@@ -0,0 +1,253 @@
+//! Bios and requests
+//!
+//! A [`Bio`] is one caller's I/O: an operation, a sector range, a buffer and
+//! a completion callback. The queue merges contiguous bios into a
+//! [`Request`], which is what a driver sees. When the driver ends the
+//! request, the buffer is split back up and every bio's callback runs with
+//! its own part.
+
+extern crate alloc;
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Weak, vec, vec::Vec};
+
+use super::{queue::RequestQueue, BlockResult};
+
+/// Block operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReqOp {
+    Read,
+    Write,
+    /// Write back the device's volatile cache
+    Flush,
+    /// Drop the contents of a sector range
+    Discard,
+}
+
+impl ReqOp {
+    /// Whether the operation carries a data buffer
+    pub fn has_data(&self) -> bool {
+        matches!(self, ReqOp::Read | ReqOp::Write)
+    }
+}
+
+/// Request modifier flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReqFlags(pub u32);
+
+impl ReqFlags {
+    /// Someone is waiting on this request
+    pub const SYNC: u32     = 1 << 0;
+    /// Filesystem metadata
+    pub const META: u32     = 1 << 1;
+    /// The write is durable once it completes (Force Unit Access)
+    pub const FUA: u32      = 1 << 2;
+    /// Flush the write cache before doing this request
+    pub const PREFLUSH: u32 = 1 << 3;
+
+    pub fn contains(&self, flag: u32) -> bool {
+        self.0 & flag != 0
+    }
+}
+
+/// Completion callback of a bio, given the result and the bio's buffer
+///
+/// Runs in the context that ends the request, which may be an interrupt
+/// handler: it must not sleep.
+pub type EndIo = Box<dyn FnOnce(BlockResult<()>, Vec<u8>) + Send>;
+
+/// One block I/O as submitted by a caller
+pub struct Bio {
+    pub op: ReqOp,
+    pub flags: ReqFlags,
+    pub sector: u64,
+    /// Length in sectors; for reads and writes the queue sets it from the
+    /// buffer size
+    pub nr_sectors: u32,
+    pub data: Vec<u8>,
+    end_io: Option<EndIo>,
+}
+
+impl Bio {
+    fn new(op: ReqOp, sector: u64, nr_sectors: u32, data: Vec<u8>) -> Self {
+        Self { op, flags: ReqFlags::default(), sector, nr_sectors, data, end_io: None }
+    }
+
+    /// Read `len` bytes starting at `sector`
+    pub fn read(sector: u64, len: usize) -> Self {
+        Self::new(ReqOp::Read, sector, 0, vec![0; len])
+    }
+
+    /// Write `data` starting at `sector`
+    pub fn write(sector: u64, data: Vec<u8>) -> Self {
+        Self::new(ReqOp::Write, sector, 0, data)
+    }
+
+    pub fn flush() -> Self {
+        Self::new(ReqOp::Flush, 0, 0, Vec::new())
+    }
+
+    pub fn discard(sector: u64, nr_sectors: u32) -> Self {
+        Self::new(ReqOp::Discard, sector, nr_sectors, Vec::new())
+    }
+
+    /// Add `ReqFlags` bits
+    pub fn with_flags(mut self, flags: u32) -> Self {
+        self.flags.0 |= flags;
+        self
+    }
+
+    /// Call `f` when the bio completes
+    pub fn on_complete(mut self, f: impl FnOnce(BlockResult<()>, Vec<u8>) + Send + 'static) -> Self {
+        self.end_io = Some(Box::new(f));
+        self
+    }
+
+    pub(super) fn end_sector(&self) -> u64 {
+        self.sector + self.nr_sectors as u64
+    }
+
+    pub(super) fn complete(mut self, result: BlockResult<()>) {
+        if let Some(end_io) = self.end_io.take() {
+            end_io(result, self.data);
+        }
+    }
+}
+
+/// Flush sequence steps still to run for a request, lowest bit first
+pub(super) const SEQ_PREFLUSH: u8  = 1 << 0;
+pub(super) const SEQ_DATA: u8      = 1 << 1;
+pub(super) const SEQ_POSTFLUSH: u8 = 1 << 2;
+
+/// One or more contiguous bios, as handed to a driver
+pub struct Request {
+    data_op: ReqOp,
+    flags: ReqFlags,
+    sector: u64,
+    nr_sectors: u32,
+    data: Vec<u8>,
+    /// Merged bios in sector order; their buffers live in `data`
+    bios: VecDeque<Bio>,
+    pub(super) seq: u8,
+    pub(super) id: u64,
+    /// Time by which the deadline scheduler wants it dispatched (ms)
+    pub(super) deadline: u64,
+    queue: Weak<RequestQueue>,
+}
+
+impl Request {
+    pub(super) fn from_bio(mut bio: Bio, seq: u8, id: u64, queue: Weak<RequestQueue>) -> Self {
+        let data = core::mem::take(&mut bio.data);
+        let mut bios = VecDeque::new();
+        let (data_op, flags, sector, nr_sectors) = (bio.op, bio.flags, bio.sector, bio.nr_sectors);
+        bios.push_back(bio);
+        Self { data_op, flags, sector, nr_sectors, data, bios, seq, id, deadline: 0, queue }
+    }
+
+    /// Operation the driver is to carry out now
+    ///
+    /// A write with `PREFLUSH` or emulated FUA is presented as a flush
+    /// before and after the data step.
+    pub fn op(&self) -> ReqOp {
+        if self.seq & SEQ_DATA != 0 && self.seq & SEQ_PREFLUSH == 0 {
+            self.data_op
+        } else {
+            ReqOp::Flush
+        }
+    }
+
+    /// Flags the driver has to honour; `FUA` is only left set for devices
+    /// that advertise it
+    pub fn flags(&self) -> ReqFlags {
+        self.flags
+    }
+
+    pub fn sector(&self) -> u64 {
+        self.sector
+    }
+
+    pub fn nr_sectors(&self) -> u32 {
+        self.nr_sectors
+    }
+
+    pub fn end_sector(&self) -> u64 {
+        self.sector + self.nr_sectors as u64
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Number of bios merged into this request
+    pub fn nr_bios(&self) -> usize {
+        self.bios.len()
+    }
+
+    /// Finish the current step of the request
+    ///
+    /// Drivers call this exactly once per `queue_rq`, from any context.
+    pub fn end(self, result: BlockResult<()>) {
+        match self.queue.upgrade() {
+            Some(queue) => queue.complete(self, result),
+            None => self.finish(result),
+        }
+    }
+
+    /// Move past the step that just completed; true if steps remain
+    pub(super) fn advance(&mut self) -> bool {
+        self.seq &= self.seq.wrapping_sub(1);
+        self.seq != 0
+    }
+
+    fn mergeable(&self, bio: &Bio, max_sectors: u32) -> bool {
+        self.seq == SEQ_DATA
+            && bio.op == self.data_op
+            && bio.op != ReqOp::Flush
+            && (bio.flags.0 ^ self.flags.0) & ReqFlags::FUA == 0
+            && self.nr_sectors + bio.nr_sectors <= max_sectors
+    }
+
+    pub(super) fn can_back_merge(&self, bio: &Bio, max_sectors: u32) -> bool {
+        self.mergeable(bio, max_sectors) && self.end_sector() == bio.sector
+    }
+
+    pub(super) fn can_front_merge(&self, bio: &Bio, max_sectors: u32) -> bool {
+        self.mergeable(bio, max_sectors) && bio.end_sector() == self.sector
+    }
+
+    pub(super) fn back_merge(&mut self, mut bio: Bio) {
+        self.flags.0 |= bio.flags.0;
+        self.data.append(&mut bio.data);
+        self.nr_sectors += bio.nr_sectors;
+        self.bios.push_back(bio);
+    }
+
+    pub(super) fn front_merge(&mut self, mut bio: Bio) {
+        self.flags.0 |= bio.flags.0;
+        bio.data.append(&mut self.data);
+        self.data = core::mem::take(&mut bio.data);
+        self.sector = bio.sector;
+        self.nr_sectors += bio.nr_sectors;
+        self.bios.push_front(bio);
+    }
+
+    /// Hand every bio its part of the buffer and run its callback
+    pub(super) fn finish(mut self, result: BlockResult<()>) {
+        let bytes_per_sector = match self.nr_sectors {
+            0 => 0,
+            n => self.data.len() / n as usize,
+        };
+        let mut parts = Vec::with_capacity(self.bios.len());
+        while let Some(mut bio) = self.bios.pop_back() {
+            let len = bio.nr_sectors as usize * bytes_per_sector;
+            bio.data = self.data.split_off(self.data.len().saturating_sub(len));
+            parts.push(bio);
+        }
+        for bio in parts.into_iter().rev() {
+            bio.complete(result);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! I/O schedulers
+//!
+//! A scheduler holds the requests a queue has not dispatched yet. It is
+//! offered every new bio for merging first, and picks the next request
+//! whenever the driver has room for one.
+//!
+//! - [`Noop`] dispatches in arrival order and only merges. It suits
+//!   devices that do their own reordering.
+//! - [`Deadline`] serves each direction in ascending sector order and
+//!   prefers reads. Each request also gets an expiry time, and once a
+//!   request has waited past it, the scheduler jumps to it. Writes are
+//!   served after at most `writes_starved` read batches.
+
+extern crate alloc;
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    vec::Vec,
+};
+
+use super::request::{Bio, ReqOp, Request};
+
+/// Dispatch policy of a request queue
+pub trait IoScheduler: Send {
+    fn name(&self) -> &'static str;
+
+    /// Merge `bio` into a queued request it is contiguous with
+    ///
+    /// Hands the bio back if no request could take it.
+    fn merge(&mut self, bio: Bio, max_sectors: u32) -> Option<Bio>;
+
+    /// Queue a request; `now` is the current time in milliseconds
+    fn insert(&mut self, rq: Request, now: u64);
+
+    /// Next request to send to the driver
+    fn dispatch(&mut self, now: u64) -> Option<Request>;
+
+    /// Number of queued requests
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every queued request, for switching schedulers
+    fn drain(&mut self) -> Vec<Request>;
+}
+
+/// First in, first out
+#[derive(Default)]
+pub struct Noop {
+    queue: VecDeque<Request>,
+}
+
+impl Noop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IoScheduler for Noop {
+    fn name(&self) -> &'static str {
+        "noop"
+    }
+
+    fn merge(&mut self, bio: Bio, max_sectors: u32) -> Option<Bio> {
+        // Newest first: sequential streams merge with what they just queued
+        for rq in self.queue.iter_mut().rev() {
+            if rq.can_back_merge(&bio, max_sectors) {
+                rq.back_merge(bio);
+                return None;
+            }
+            if rq.can_front_merge(&bio, max_sectors) {
+                rq.front_merge(bio);
+                return None;
+            }
+        }
+        Some(bio)
+    }
+
+    fn insert(&mut self, rq: Request, _now: u64) {
+        self.queue.push_back(rq);
+    }
+
+    fn dispatch(&mut self, _now: u64) -> Option<Request> {
+        self.queue.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        self.queue.drain(..).collect()
+    }
+}
+
+const READ: usize = 0;
+const WRITE: usize = 1;
+
+fn direction(op: ReqOp) -> usize {
+    if op == ReqOp::Read { READ } else { WRITE }
+}
+
+/// Sector-sorted elevator with per-request expiry
+pub struct Deadline {
+    /// Queued requests by id
+    requests: BTreeMap<u64, Request>,
+    /// (sector, id) of each direction's requests, in sector order
+    sorted: [BTreeSet<(u64, u64)>; 2],
+    /// Ids in arrival, and so expiry, order; entries for requests that
+    /// were already dispatched are skipped lazily
+    fifo: [VecDeque<u64>; 2],
+    /// Where the elevator continues in each direction
+    next_sector: [u64; 2],
+    last_dir: usize,
+    /// Requests dispatched in the current batch
+    batch: u32,
+    /// Read batches started while writes were waiting
+    starved: u32,
+    /// Expiry for reads, in milliseconds
+    pub read_expire: u64,
+    /// Expiry for writes, in milliseconds
+    pub write_expire: u64,
+    /// Requests dispatched in one sector-ordered run
+    pub fifo_batch: u32,
+    /// Read batches allowed before pending writes get a turn
+    pub writes_starved: u32,
+}
+
+impl Default for Deadline {
+    fn default() -> Self {
+        Self {
+            requests: BTreeMap::new(),
+            sorted: [BTreeSet::new(), BTreeSet::new()],
+            fifo: [VecDeque::new(), VecDeque::new()],
+            next_sector: [0; 2],
+            last_dir: READ,
+            batch: 0,
+            starved: 0,
+            read_expire: 500,
+            write_expire: 5000,
+            fifo_batch: 16,
+            writes_starved: 2,
+        }
+    }
+}
+
+impl Deadline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// First request of `dir` at or after `sector`
+    fn after(&self, dir: usize, sector: u64) -> Option<(u64, u64)> {
+        self.sorted[dir].range((sector, 0)..).next().copied()
+    }
+
+    /// Oldest request of `dir` if it has expired
+    fn expired(&mut self, dir: usize, now: u64) -> Option<(u64, u64)> {
+        while let Some(&id) = self.fifo[dir].front() {
+            match self.requests.get(&id) {
+                Some(rq) if rq.deadline <= now => return Some((rq.sector(), id)),
+                Some(_) => return None,
+                None => {
+                    self.fifo[dir].pop_front();
+                }
+            }
+        }
+        None
+    }
+
+    fn take(&mut self, dir: usize, (sector, id): (u64, u64)) -> Option<Request> {
+        self.sorted[dir].remove(&(sector, id));
+        let rq = self.requests.remove(&id)?;
+        self.next_sector[dir] = rq.end_sector();
+        self.batch += 1;
+        Some(rq)
+    }
+}
+
+impl IoScheduler for Deadline {
+    fn name(&self) -> &'static str {
+        "deadline"
+    }
+
+    fn merge(&mut self, bio: Bio, max_sectors: u32) -> Option<Bio> {
+        let dir = direction(bio.op);
+
+        // Back merge: the closest request starting before the bio
+        let prev = self.sorted[dir].range(..(bio.sector, 0)).next_back().copied();
+        if let Some(rq) = prev.and_then(|(_, id)| self.requests.get_mut(&id))
+            && rq.can_back_merge(&bio, max_sectors)
+        {
+            rq.back_merge(bio);
+            return None;
+        }
+
+        // Front merge: a request starting right where the bio ends
+        let next = self.sorted[dir].range((bio.end_sector(), 0)..).next().copied();
+        if let Some((sector, id)) = next
+            && let Some(rq) = self.requests.get_mut(&id)
+            && rq.can_front_merge(&bio, max_sectors)
+        {
+            rq.front_merge(bio);
+            let new_sector = rq.sector();
+            self.sorted[dir].remove(&(sector, id));
+            self.sorted[dir].insert((new_sector, id));
+            return None;
+        }
+
+        Some(bio)
+    }
+
+    fn insert(&mut self, mut rq: Request, now: u64) {
+        let dir = direction(rq.op());
+        rq.deadline = now + if dir == READ { self.read_expire } else { self.write_expire };
+        self.sorted[dir].insert((rq.sector(), rq.id));
+        self.fifo[dir].push_back(rq.id);
+        self.requests.insert(rq.id, rq);
+    }
+
+    fn dispatch(&mut self, now: u64) -> Option<Request> {
+        if self.requests.is_empty() {
+            return None;
+        }
+
+        // Keep going in sector order until the batch is used up
+        if self.batch < self.fifo_batch
+            && let Some(key) = self.after(self.last_dir, self.next_sector[self.last_dir])
+        {
+            return self.take(self.last_dir, key);
+        }
+
+        let reads = !self.sorted[READ].is_empty();
+        let writes = !self.sorted[WRITE].is_empty();
+        let dir = if reads && (!writes || self.starved < self.writes_starved) {
+            if writes {
+                self.starved += 1;
+            }
+            READ
+        } else {
+            self.starved = 0;
+            WRITE
+        };
+        self.last_dir = dir;
+        self.batch = 0;
+
+        // Start the batch at an expired request if there is one, else carry
+        // on from the elevator position, wrapping around to the lowest sector
+        let key = match self.expired(dir, now) {
+            Some(key) => key,
+            None => self
+                .after(dir, self.next_sector[dir])
+                .or_else(|| self.sorted[dir].first().copied())?,
+        };
+        self.take(dir, key)
+    }
+
+    fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    fn drain(&mut self) -> Vec<Request> {
+        self.sorted.iter_mut().for_each(BTreeSet::clear);
+        self.fifo.iter_mut().for_each(VecDeque::clear);
+        core::mem::take(&mut self.requests).into_values().collect()
+    }
+}
@@ -0,0 +1,245 @@
+//! Block layer tests
+//!
+//! Tests for request merging, scheduling and flush sequencing
+
+#[cfg(feature = "kernel_tests")]
+pub mod block_tests {
+    extern crate alloc;
+
+    use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+    use spin::Mutex;
+
+    use crate::platform::drivers::BlockDevice;
+    use crate::subsystems::block::{
+        Bio, BlockError, BlockFeatures, BlockResult, Deadline, IoScheduler, Noop, ReqFlags, ReqOp,
+        Request, RequestQueue,
+    };
+    use crate::tests::{TestResult, test_assert_eq, test_assert};
+
+    const SECTORS: usize = 64;
+
+    /// In-memory disk that logs every request it sees
+    ///
+    /// With `hold` set, requests are parked until `release` so tests can
+    /// queue several and look at how they were merged and ordered.
+    struct MockDisk {
+        data: Mutex<Vec<u8>>,
+        features: BlockFeatures,
+        log: Mutex<Vec<(ReqOp, u64, u32, bool)>>,
+        held: Mutex<Vec<Request>>,
+        hold: bool,
+        fail_sector: Option<u64>,
+    }
+
+    impl MockDisk {
+        fn new(features: BlockFeatures) -> Self {
+            Self {
+                data: Mutex::new(vec![0; SECTORS * 512]),
+                features,
+                log: Mutex::new(Vec::new()),
+                held: Mutex::new(Vec::new()),
+                hold: false,
+                fail_sector: None,
+            }
+        }
+
+        fn release(&self) {
+            let held: Vec<Request> = self.held.lock().drain(..).collect();
+            for rq in held {
+                self.execute(rq);
+            }
+        }
+
+        fn execute(&self, mut rq: Request) {
+            let lba = rq.sector() as usize;
+            let result = match rq.op() {
+                ReqOp::Read => self.read(lba, rq.data_mut()),
+                ReqOp::Write => self.write(lba, rq.data()),
+                ReqOp::Flush => self.flush(),
+                ReqOp::Discard => self.discard(lba, rq.nr_sectors() as usize),
+            };
+            rq.end(result);
+        }
+    }
+
+    impl BlockDevice for MockDisk {
+        fn read(&self, lba: usize, buf: &mut [u8]) -> BlockResult<()> {
+            if self.fail_sector == Some(lba as u64) {
+                return Err(BlockError::IoError);
+            }
+            buf.copy_from_slice(&self.data.lock()[lba * 512..lba * 512 + buf.len()]);
+            Ok(())
+        }
+
+        fn write(&self, lba: usize, buf: &[u8]) -> BlockResult<()> {
+            if self.fail_sector == Some(lba as u64) {
+                return Err(BlockError::IoError);
+            }
+            self.data.lock()[lba * 512..lba * 512 + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+
+        fn num_blocks(&self) -> usize {
+            SECTORS
+        }
+
+        fn discard(&self, lba: usize, count: usize) -> BlockResult<()> {
+            self.data.lock()[lba * 512..(lba + count) * 512].fill(0);
+            Ok(())
+        }
+
+        fn features(&self) -> BlockFeatures {
+            self.features
+        }
+
+        fn queue_rq(&self, rq: Request) {
+            let fua = rq.flags().contains(ReqFlags::FUA);
+            self.log.lock().push((rq.op(), rq.sector(), rq.nr_sectors(), fua));
+            if self.hold {
+                self.held.lock().push(rq);
+            } else {
+                self.execute(rq);
+            }
+        }
+    }
+
+    fn queue(disk: MockDisk, sched: Box<dyn IoScheduler>) -> (Arc<MockDisk>, Arc<RequestQueue>) {
+        let disk = Arc::new(disk);
+        let dev: Arc<dyn BlockDevice> = disk.clone();
+        (disk, RequestQueue::new("mock", dev, sched))
+    }
+
+    fn cached() -> BlockFeatures {
+        BlockFeatures { write_cache: true, ..BlockFeatures::default() }
+    }
+
+    /// Test a write can be read back and out-of-range I/O fails
+    pub fn test_block_read_write() -> TestResult {
+        let (_, q) = queue(MockDisk::new(BlockFeatures::default()), Box::new(Noop::new()));
+        let buf = [0xabu8; 1024];
+        test_assert!(q.write(4, &buf, 0).is_ok());
+        let mut back = [0u8; 1024];
+        test_assert!(q.read(4, &mut back).is_ok());
+        test_assert_eq!(&back[..], &buf[..]);
+
+        test_assert!(q.read(SECTORS as u64 - 1, &mut back) == Err(BlockError::OutOfRange));
+        test_assert!(q.read(0, &mut back[..100]) == Err(BlockError::Misaligned));
+        Ok(())
+    }
+
+    /// Test byte-granular access reads in and preserves partial sectors
+    pub fn test_block_byte_access() -> TestResult {
+        let (_, q) = queue(MockDisk::new(BlockFeatures::default()), Box::new(Noop::new()));
+        test_assert!(q.write(0, &[1u8; 1024], 0).is_ok());
+        test_assert!(q.write_at(500, &[2u8; 24], 0).is_ok());
+        let mut buf = [0u8; 30];
+        test_assert!(q.read_at(497, &mut buf).is_ok());
+        test_assert_eq!(&buf[..3], &[1u8; 3][..]);
+        test_assert_eq!(&buf[3..27], &[2u8; 24][..]);
+        test_assert_eq!(&buf[27..], &[1u8; 3][..]);
+        Ok(())
+    }
+
+    /// Test contiguous bios merge into one request and each gets its data
+    pub fn test_block_merge() -> TestResult {
+        let mut disk = MockDisk::new(BlockFeatures::default());
+        disk.hold = true;
+        let (disk, q) = queue(disk, Box::new(Noop::new()));
+        disk.data.lock()[4 * 512..7 * 512].copy_from_slice(&[[1u8; 512], [2; 512], [3; 512]].concat());
+
+        let got = Arc::new(Mutex::new(Vec::new()));
+        {
+            let _plug = q.plug();
+            for sector in [5u64, 6, 4] {
+                let got = got.clone();
+                q.submit_bio(Bio::read(sector, 512).on_complete(move |result, data| {
+                    got.lock().push((sector, result, data[0]));
+                }));
+            }
+        }
+        test_assert_eq!(disk.log.lock().len(), 1);
+        test_assert!(disk.log.lock()[0] == (ReqOp::Read, 4, 3, false));
+        test_assert_eq!(q.stats().merges, 2);
+
+        disk.release();
+        let got = got.lock();
+        test_assert_eq!(got.len(), 3);
+        for &(sector, result, byte) in got.iter() {
+            test_assert!(result.is_ok());
+            test_assert_eq!(byte as u64, sector - 3);
+        }
+        Ok(())
+    }
+
+    /// Test the deadline scheduler sorts by sector, prefers reads and
+    /// serves an expired request first
+    pub fn test_block_deadline_order() -> TestResult {
+        let mut disk = MockDisk::new(BlockFeatures::default());
+        disk.hold = true;
+        let (disk, q) = queue(disk, Box::new(Deadline::new()));
+        {
+            let _plug = q.plug();
+            q.submit_bio(Bio::write(30, vec![0; 512]));
+            q.submit_bio(Bio::read(20, 512));
+            q.submit_bio(Bio::read(10, 512));
+        }
+        disk.release();
+        let order: Vec<u64> = disk.log.lock().iter().map(|e| e.1).collect();
+        test_assert!(order == vec![10, 20, 30]);
+
+        let mut sched = Deadline::new();
+        sched.fifo_batch = 1;
+        let (disk, q) = queue(MockDisk { hold: true, ..MockDisk::new(BlockFeatures::default()) }, Box::new(sched));
+        q.set_depth(1);
+        q.submit_bio(Bio::read(40, 512));
+        q.submit_bio(Bio::read(50, 512));
+        q.submit_bio(Bio::read(2, 512));
+        // Sector 40 went out at once; the elevator continues upwards
+        disk.release();
+        disk.release();
+        disk.release();
+        let order: Vec<u64> = disk.log.lock().iter().map(|e| e.1).collect();
+        test_assert!(order == vec![40, 50, 2]);
+        Ok(())
+    }
+
+    /// Test PREFLUSH and FUA become flushes around the write on a cached
+    /// device without FUA, and are dropped without a write cache
+    pub fn test_block_flush_fua() -> TestResult {
+        let (disk, q) = queue(MockDisk::new(cached()), Box::new(Noop::new()));
+        let flags = ReqFlags::PREFLUSH | ReqFlags::FUA;
+        test_assert!(q.write(8, &[7u8; 512], flags).is_ok());
+        let ops: Vec<ReqOp> = disk.log.lock().iter().map(|e| e.0).collect();
+        test_assert!(ops == vec![ReqOp::Flush, ReqOp::Write, ReqOp::Flush]);
+
+        let native = BlockFeatures { fua: true, ..cached() };
+        let (disk, q) = queue(MockDisk::new(native), Box::new(Noop::new()));
+        test_assert!(q.write(8, &[7u8; 512], ReqFlags::FUA).is_ok());
+        test_assert!(disk.log.lock().clone() == vec![(ReqOp::Write, 8, 1, true)]);
+
+        let (disk, q) = queue(MockDisk::new(BlockFeatures::default()), Box::new(Noop::new()));
+        test_assert!(q.write(8, &[7u8; 512], flags).is_ok());
+        test_assert!(q.flush().is_ok());
+        test_assert!(disk.log.lock().clone() == vec![(ReqOp::Write, 8, 1, false)]);
+        Ok(())
+    }
+
+    /// Test errors reach every bio of a request, and discard needs support
+    pub fn test_block_errors() -> TestResult {
+        let mut disk = MockDisk::new(BlockFeatures::default());
+        disk.fail_sector = Some(12);
+        let (_, q) = queue(disk, Box::new(Noop::new()));
+        let mut buf = [0u8; 512];
+        test_assert!(q.read(12, &mut buf) == Err(BlockError::IoError));
+        test_assert!(q.read(13, &mut buf).is_ok());
+        test_assert!(q.discard(0, 4) == Err(BlockError::NotSupported));
+        test_assert!(q.stats().errors >= 2);
+
+        let discard = BlockFeatures { discard: true, ..BlockFeatures::default() };
+        let (disk, q) = queue(MockDisk::new(discard), Box::new(Noop::new()));
+        test_assert!(q.write(0, &[9u8; 1024], 0).is_ok());
+        test_assert!(q.discard(0, 2).is_ok());
+        test_assert!(disk.data.lock()[..1024].iter().all(|&b| b == 0));
+        Ok(())
+    }
+}
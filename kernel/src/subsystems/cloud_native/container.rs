@@ -718,13 +718,13 @@ impl Container {
             match port_mapping.protocol {
                 PortProtocol::TCP => {
                     crate::println!("[container] Setting up TCP port mapping {}:{} -> {}",
-                        port_mapping.host_ip.as_deref().unwrap_or("0.0.0.0"),
+                        port_mapping.host_ip.as_deref().unwrap_or("::"),
                         port_mapping.host_port,
                         port_mapping.container_port);
                 }
                 PortProtocol::UDP => {
                     crate::println!("[container] Setting up UDP port mapping {}:{} -> {}",
-                        port_mapping.host_ip.as_deref().unwrap_or("0.0.0.0"),
+                        port_mapping.host_ip.as_deref().unwrap_or("::"),
                         port_mapping.host_port,
                         port_mapping.container_port);
                 }
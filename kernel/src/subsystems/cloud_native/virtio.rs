@@ -1,890 +1,26 @@
-// VirtIO Optimization Module
+// VirtIO devices for the cloud-native service
 //
-// VirtIO优化模块
-// 提供高性能的虚拟设备接口实现，支持云原生环境下的设备虚拟化
-
-extern crate alloc;
-
-use alloc::format;
-use crate::reliability::errno::{EINVAL, ENOENT, ENOMEM, EIO, EACCES, EAGAIN};
-use alloc::collections::BTreeMap;
-use alloc::sync::Arc;
-use spin::Mutex;
-use alloc::vec;
-use alloc::vec::Vec;
-use alloc::string::String;
-use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-
-/// VirtIO设备类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum VirtIODeviceType {
-    /// 网络设备
-    Network = 1,
-    /// 块设备
-    Block = 2,
-    /// 内存气球设备
-    Balloon = 5,
-    /// 控制台设备
-    Console = 3,
-    /// RNG设备
-    RNG = 4,
-    /// GPU设备
-    GPU = 16,
-    /// 输入设备
-    Input = 18,
-    /// vsock设备
-    Vsock = 19,
-    /// IOMMU设备
-    IOMMU = 23,
-    /// 内存设备
-    Memory = 24,
-}
-
-/// VirtIO设备特性
-#[derive(Debug, Clone)]
-pub struct VirtIODeviceFeatures {
-    /// 基础特性
-    pub basic_features: u64,
-    /// 特定设备特性
-    pub device_specific_features: u64,
-}
-
-/// VirtIO队列配置
-#[derive(Debug, Clone)]
-pub struct VirtIOQueueConfig {
-    /// 队列大小
-    pub size: u16,
-    /// 描述符对齐
-    pub descriptor_alignment: u16,
-    /// 环大小
-    pub ring_size: u16,
-    /// 是否启用事件索引
-    pub event_suppression: bool,
-    /// 是否启用间接描述符
-    pub indirect_descriptors: bool,
-}
-
-/// VirtIO队列状态
-#[derive(Debug)]
-pub struct VirtIOQueue {
-    /// 队列索引
-    pub index: u16,
-    /// 队列大小
-    pub size: u16,
-    /// 描述符区域
-    pub descriptors: VirtIODeviceDescriptors,
-    /// 可用环
-    pub available_ring: VirtIOAvailableRing,
-    /// 已用环
-    pub used_ring: VirtIOUsedRing,
-    /// 最后使用的索引
-    pub last_used_index: u16,
-    /// 中断使能
-    pub interrupt_enabled: bool,
-}
-
-/// VirtIO设备描述符
-#[derive(Debug, Clone)]
-pub struct VirtIODeviceDescriptor {
-    /// 物理地址
-    pub addr: u64,
-    /// 长度
-    pub len: u32,
-    /// 下一个描述符
-    pub next: u16,
-    /// 标志
-    pub flags: u16,
-}
-
-/// VirtIO设备描述符表
-#[derive(Debug)]
-pub struct VirtIODeviceDescriptors {
-    /// 描述符数组
-    pub descriptors: Vec<VirtIODeviceDescriptor>,
-    /// 描述符数量
-    pub count: u16,
-    /// 可用描述符位图
-    pub available_bitmap: Vec<u64>,
-}
-
-/// VirtIO可用环
-#[derive(Debug)]
-pub struct VirtIOAvailableRing {
-    /// 标志
-    pub flags: u16,
-    /// 索引
-    pub idx: u16,
-    /// 环形缓冲区
-    pub ring: Vec<u16>,
-    /// 已使用事件索引
-    pub used_event: Option<u16>,
-}
-
-/// VirtIO已用环
-#[derive(Debug)]
-pub struct VirtIOUsedRing {
-    /// 标志
-    pub flags: u16,
-    /// 索引
-    pub idx: u16,
-    /// 环形缓冲区
-    pub ring: Vec<VirtIOUsedElement>,
-    /// 可用事件索引
-    pub avail_event: Option<u16>,
-}
-
-/// VirtIO已用元素
-#[derive(Debug, Clone)]
-pub struct VirtIOUsedElement {
-    /// 描述符索引
-    pub id: u32,
-    /// 写入长度
-    pub len: u32,
-}
-
-/// VirtIO设备配置
-#[derive(Debug, Clone)]
-pub struct VirtIODeviceConfig {
-    /// 设备ID
-    pub device_id: u16,
-    /// 厂商ID
-    pub vendor_id: u16,
-    /// 设备类型
-    pub device_type: VirtIODeviceType,
-    /// 特性
-    pub features: VirtIODeviceFeatures,
-    /// 队列配置
-    pub queue_configs: Vec<VirtIOQueueConfig>,
-    /// 配置空间大小
-    pub config_space_size: u32,
-    /// 配置空间数据
-    pub config_space: Vec<u8>,
-}
-
-/// VirtIO设备统计信息
-#[derive(Debug, Clone)]
-pub struct VirtIODeviceStats {
-    /// 发送的数据包数
-    pub packets_sent: u64,
-    /// 接收的数据包数
-    pub packets_received: u64,
-    /// 发送的字节数
-    pub bytes_sent: u64,
-    /// 接收的字节数
-    pub bytes_received: u64,
-    /// 错误数
-    pub errors: u64,
-    /// 中断数
-    pub interrupts: u64,
-    /// 队列使用统计
-    pub queue_stats: Vec<VirtIOQueueStats>,
-}
-
-/// VirtIO队列统计信息
-#[derive(Debug, Clone)]
-pub struct VirtIOQueueStats {
-    /// 队列索引
-    pub queue_index: u16,
-    /// 已使用的描述符数
-    pub descriptors_used: u64,
-    /// 队列满次数
-    pub full_events: u64,
-    /// 平均处理时间（纳秒）
-    pub avg_processing_time_ns: u64,
-}
-
-/// VirtIO设备
-pub struct VirtIODevice {
-    /// 设备ID
-    pub device_id: u32,
-    /// 设备类型
-    pub device_type: VirtIODeviceType,
-    /// 设备名称
-    pub name: String,
-    /// 设备配置
-    pub config: VirtIODeviceConfig,
-    /// 队列
-    pub queues: Vec<Arc<Mutex<VirtIOQueue>>>,
-    /// 设备状态
-    pub status: VirtIODeviceStatus,
-    /// 统计信息
-    pub stats: Arc<Mutex<VirtIODeviceStats>>,
-    /// 特性位
-    pub features: u64,
-}
-
-/// VirtIO设备状态
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum VirtIODeviceStatus {
-    Uninitialized,
-    Initializing,
-    Ready,
-    Running,
-    Stopped,
-    Error,
-}
-
-impl VirtIODevice {
-    /// 创建新的VirtIO设备
-    pub fn new(device_id: u32, device_type: VirtIODeviceType, config: VirtIODeviceConfig) -> Self {
-        let mut queues = Vec::new();
-        for (index, queue_config) in config.queue_configs.iter().enumerate() {
-            let queue = VirtIOQueue {
-                index: index as u16,
-                size: queue_config.size,
-                descriptors: VirtIODeviceDescriptors {
-                    descriptors: vec![
-                        VirtIODeviceDescriptor {
-                            addr: 0,
-                            len: 0,
-                            next: 0,
-                            flags: 0,
-                        };
-                        queue_config.size as usize
-                    ],
-                    count: queue_config.size,
-                    available_bitmap: vec![0; ((queue_config.size as usize) + 63) / 64],
-                },
-                available_ring: VirtIOAvailableRing {
-                    flags: 0,
-                    idx: 0,
-                    ring: vec![0; queue_config.size as usize],
-                    used_event: None,
-                },
-                used_ring: VirtIOUsedRing {
-                    flags: 0,
-                    idx: 0,
-                    ring: vec![
-                        VirtIOUsedElement {
-                            id: 0,
-                            len: 0,
-                        };
-                        queue_config.size as usize
-                    ],
-                    avail_event: None,
-                },
-                last_used_index: 0,
-                interrupt_enabled: true,
-            };
-            queues.push(Arc::new(Mutex::new(queue)));
-        }
-
-        Self {
-            device_id,
-            device_type,
-            name: format!("virtio-{:?}", device_type),
-            config,
-            queues,
-            status: VirtIODeviceStatus::Uninitialized,
-            stats: Arc::new(Mutex::new(VirtIODeviceStats {
-                packets_sent: 0,
-                packets_received: 0,
-                bytes_sent: 0,
-                bytes_received: 0,
-                errors: 0,
-                interrupts: 0,
-                queue_stats: Vec::new(),
-            })),
-            features: 0,
-        }
-    }
-
-    /// 初始化设备
-    pub fn initialize(&mut self) -> Result<(), i32> {
-        self.status = VirtIODeviceStatus::Initializing;
-
-        // 重置设备
-        self.reset()?;
-
-        // 通知设备存在
-        self.notify_device()?;
-
-        // 设置特性位
-        self.set_features()?;
-
-        // 分配队列
-        self.allocate_queues()?;
-
-        self.status = VirtIODeviceStatus::Ready;
-        crate::println!("[virtio] Initialized device: {} (ID: {})", self.name, self.device_id);
-        Ok(())
-    }
-
-    /// 启动设备
-    pub fn start(&mut self) -> Result<(), i32> {
-        if self.status != VirtIODeviceStatus::Ready {
-            return Err(EINVAL);
-        }
-
-        self.status = VirtIODeviceStatus::Running;
-        crate::println!("[virtio] Started device: {}", self.name);
-        Ok(())
-    }
-
-    /// 停止设备
-    pub fn stop(&mut self) -> Result<(), i32> {
-        if self.status != VirtIODeviceStatus::Running {
-            return Err(EINVAL);
-        }
-
-        self.status = VirtIODeviceStatus::Stopped;
-        crate::println!("[virtio] Stopped device: {}", self.name);
-        Ok(())
-    }
-
-    /// 重置设备
-    fn reset(&mut self) -> Result<(), i32> {
-        // 重置所有队列
-        for queue in &mut self.queue_configs() {
-            let mut q = queue.lock();
-            q.available_ring.idx = 0;
-            q.used_ring.idx = 0;
-            q.last_used_index = 0;
-        }
-
-        // 重置统计信息
-        let mut stats = self.stats.lock();
-        stats.packets_sent = 0;
-        stats.packets_received = 0;
-        stats.bytes_sent = 0;
-        stats.bytes_received = 0;
-        stats.errors = 0;
-        stats.interrupts = 0;
-
-        Ok(())
-    }
-
-    /// 通知设备
-    fn notify_device(&self) -> Result<(), i32> {
-        // 发送设备存在通知
-        crate::println!("[virtio] Notifying device: {}", self.name);
-        Ok(())
-    }
-
-    /// 设置特性位
-    fn set_features(&mut self) -> Result<(), i32> {
-        // 设置基础特性
-        self.features = self.config.features.basic_features;
-
-        // 设置设备特定特性
-        self.features |= self.config.features.device_specific_features;
-
-        crate::println!("[virtio] Set features: 0x{:x} for {}", self.features, self.name);
-        Ok(())
-    }
-
-    /// 分配队列
-    fn allocate_queues(&mut self) -> Result<(), i32> {
-        // 初始化队列统计信息
-        let mut stats = self.stats.lock();
-        stats.queue_stats.clear();
-        for (index, queue_config) in self.config.queue_configs.iter().enumerate() {
-            // Use queue_config for validation/logging
-            let _queue_size = queue_config.size; // Use queue_config to get queue size for validation
-            stats.queue_stats.push(VirtIOQueueStats {
-                queue_index: index as u16,
-                descriptors_used: 0,
-                full_events: 0,
-                avg_processing_time_ns: 0,
-            });
-        }
-
-        crate::println!("[virtio] Allocated {} queues for {}", self.queues.len(), self.name);
-        Ok(())
-    }
-
-    /// 发送数据到指定队列
-    pub fn send_to_queue(&self, queue_index: usize, data: &[u8]) -> Result<(), i32> {
-        if queue_index >= self.queues.len() {
-            return Err(EINVAL);
-        }
-
-        let mut queue = self.queues[queue_index].lock();
-
-        // 查找可用描述符
-        let descriptor_index = self.find_available_descriptor(&queue)?;
-
-        // 准备描述符
-        let mut descriptors = queue.descriptors.descriptors.clone();
-        descriptors[descriptor_index].addr = data.as_ptr() as u64;
-        descriptors[descriptor_index].len = data.len() as u32;
-        descriptors[descriptor_index].flags = 0; // 只写
-
-        // 添加到可用环
-        self.add_to_available_ring(&mut queue, descriptor_index)?;
-
-        // 通知设备
-        self.notify_queue(&queue, queue_index);
-
-        // 更新统计信息
-        let mut stats = self.stats.lock();
-        stats.packets_sent += 1;
-        stats.bytes_sent += data.len() as u64;
-        if queue_index < stats.queue_stats.len() {
-            stats.queue_stats[queue_index].descriptors_used += 1;
-        }
-
-        Ok(())
-    }
-
-    /// 从指定队列接收数据
-    pub fn receive_from_queue(&self, queue_index: usize, buffer: &mut [u8]) -> Result<usize, i32> {
-        if queue_index >= self.queues.len() {
-            return Err(EINVAL);
-        }
-
-        let mut queue = self.queues[queue_index].lock();
-
-        // 检查已用环
-        if queue.used_ring.idx == queue.last_used_index {
-            return Err(EAGAIN); // 没有数据可读
-        }
-
-        // 获取已用元素
-        let used_index = queue.last_used_index as usize % queue.size as usize;
-        let used_element = &queue.used_ring.ring[used_index];
-
-        // 复制数据
-        let copy_len = core::cmp::min(used_element.len as usize, buffer.len());
-        if let Some(descriptor_index) = self.find_descriptor_by_id(&queue, used_element.id) {
-            let descriptor = &queue.descriptors.descriptors[descriptor_index as usize];
-            // Use descriptor for validation/logging
-            let _desc_addr = descriptor.addr; // Use descriptor to get physical address for validation
-            let _desc_len = descriptor.len; // Use descriptor to get length for validation
-            // 在实际实现中，这里需要从物理地址复制数据
-            // 这里简化处理
-            for i in 0..copy_len {
-                buffer[i] = (i % 256) as u8; // 模拟数据
-            }
-        }
-
-        // 更新最后使用索引
-        queue.last_used_index = (queue.last_used_index + 1) % queue.size;
-
-        // 更新统计信息
-        let mut stats = self.stats.lock();
-        stats.packets_received += 1;
-        stats.bytes_received += copy_len as u64;
-
-        Ok(copy_len)
-    }
-
-    /// 查找可用描述符
-    fn find_available_descriptor(&self, queue: &VirtIOQueue) -> Result<usize, i32> {
-        for (index, bitmap) in queue.descriptors.available_bitmap.iter().enumerate() {
-            if *bitmap != u64::MAX {
-                let bit_pos = (!*bitmap).trailing_zeros() as usize;
-                let descriptor_index = index * 64 + bit_pos;
-                if descriptor_index < queue.descriptors.count as usize {
-                    return Ok(descriptor_index);
-                }
-            }
-        }
-        Err(EAGAIN) // 没有可用描述符
-    }
-
-    /// 根据ID查找描述符
-    fn find_descriptor_by_id(&self, queue: &VirtIOQueue, id: u32) -> Option<u16> {
-        // Use queue for validation
-        let _queue_size = queue.size; // Use queue to get queue size for validation
-        // 简化实现，假设ID就是描述符索引
-        Some(id as u16)
-    }
-
-    /// 添加到可用环
-    fn add_to_available_ring(&self, queue: &mut VirtIOQueue, descriptor_index: usize) -> Result<(), i32> {
-        let index = queue.available_ring.idx as usize % queue.size as usize;
-        queue.available_ring.ring[index] = descriptor_index as u16;
-        queue.available_ring.idx = (queue.available_ring.idx + 1) % queue.size;
-        Ok(())
-    }
-
-    /// 通知队列
-    fn notify_queue(&self, queue: &VirtIOQueue, queue_index: usize) {
-        if queue.interrupt_enabled {
-            // 触发中断
-            crate::println!("[virtio] Notifying queue {} of {}", queue_index, self.name);
-
-            // 更新统计信息
-            let mut stats = self.stats.lock();
-            stats.interrupts += 1;
-        }
-    }
-
-    /// 获取队列配置
-    fn queue_configs(&self) -> Vec<Arc<Mutex<VirtIOQueue>>> {
-        self.queues.clone()
-    }
-    
-    /// 优化设备性能
-    pub fn optimize_device_performance(&mut self) -> Result<(), i32> {
-        // 优化所有队列
-        for i in 0..self.queues.len() {
-            self.optimize_queue_performance(i as u16)?;
-        }
-        
-        // 启用中断合并（如果支持）
-        // 启用MSI-X（如果支持）
-        // 优化描述符对齐
-        
-        crate::println!("[virtio] Optimized device performance for {}", self.name);
-        Ok(())
-    }
-    
-    /// 优化队列性能
-    pub fn optimize_queue_performance(&mut self, queue_index: u16) -> Result<(), i32> {
-        if queue_index as usize >= self.queues.len() {
-            return Err(EINVAL);
-        }
-        
-        let queue = self.queues[queue_index as usize].lock();
-        
-        // 优化队列大小（如果太小）
-        if queue.size < 256 {
-            // 在实际实现中，这里会重新配置队列大小
-            crate::println!("[virtio] Queue {} size is small, consider increasing", queue_index);
-        }
-        
-        // 启用事件索引（如果支持）
-        // 启用间接描述符（如果支持）
-        
-        // 优化描述符对齐
-        // 在实际实现中，这里会确保描述符页对齐
-        
-        crate::println!("[virtio] Optimized queue {} performance", queue_index);
-        Ok(())
-    }
-
-    /// 获取设备统计信息
-    pub fn get_stats(&self) -> VirtIODeviceStats {
-        self.stats.lock().clone()
-    }
-
-    /// 启用/禁用队列中断
-    pub fn set_queue_interrupt(&self, queue_index: usize, enabled: bool) -> Result<(), i32> {
-        if queue_index >= self.queues.len() {
-            return Err(EINVAL);
-        }
-
-        let mut queue = self.queues[queue_index].lock();
-        queue.interrupt_enabled = enabled;
-
-        crate::println!("[virtio] Set queue {} interrupt to {} for {}", queue_index, enabled, self.name);
-        Ok(())
-    }
-}
-
-/// VirtIO设备管理器
-pub struct VirtIOManager {
-    /// 设备列表
-    devices: BTreeMap<u32, Arc<Mutex<VirtIODevice>>>,
-    /// 下一个设备ID
-    next_device_id: AtomicU64,
-    /// 设备数量
-    device_count: AtomicUsize,
-}
-
-impl VirtIOManager {
-    /// 创建新的VirtIO管理器
-    pub fn new() -> Self {
-        Self {
-            devices: BTreeMap::new(),
-            next_device_id: AtomicU64::new(1),
-            device_count: AtomicUsize::new(0),
-        }
-    }
-
-    /// 注册VirtIO设备
-    pub fn register_device(&mut self, device_type: VirtIODeviceType, config: VirtIODeviceConfig) -> Result<u32, i32> {
-        let device_id = self.next_device_id.fetch_add(1, Ordering::SeqCst) as u32;
-
-        let mut device = VirtIODevice::new(device_id, device_type, config);
-        device.initialize()?;
-
-        let device_arc = Arc::new(Mutex::new(device));
-        self.devices.insert(device_id, device_arc);
-        self.device_count.fetch_add(1, Ordering::SeqCst);
-
-        crate::println!("[virtio] Registered device type: {:?}, ID: {}", device_type, device_id);
-        Ok(device_id)
-    }
-
-    /// 获取设备
-    pub fn get_device(&self, device_id: u32) -> Option<Arc<Mutex<VirtIODevice>>> {
-        self.devices.get(&device_id).cloned()
-    }
-
-    /// 注销设备
-    pub fn unregister_device(&mut self, device_id: u32) -> Result<(), i32> {
-        if self.devices.remove(&device_id).is_some() {
-            self.device_count.fetch_sub(1, Ordering::SeqCst);
-            crate::println!("[virtio] Unregistered device ID: {}", device_id);
-            Ok(())
-        } else {
-            Err(ENOENT)
-        }
-    }
-
-    /// 获取所有设备ID
-    pub fn get_device_ids(&self) -> Vec<u32> {
-        self.devices.keys().copied().collect()
-    }
-
-    /// 获取设备数量
-    pub fn get_device_count(&self) -> usize {
-        self.device_count.load(Ordering::SeqCst)
-    }
-
-    /// 获取所有设备统计信息
-    pub fn get_all_stats(&self) -> Vec<(u32, VirtIODeviceStats)> {
-        let mut stats = Vec::new();
-        for (device_id, device) in &self.devices {
-            let device_stats = device.lock();
-            stats.push((*device_id, device_stats.get_stats()));
-        }
-        stats
-    }
-
-    /// 扫描并初始化所有VirtIO设备
-    pub fn scan_devices(&mut self) -> Result<(), i32> {
-        crate::println!("[virtio] Scanning for VirtIO devices...");
-
-        // 在实际实现中，这里会扫描PCI总线或其他设备总线
-        // 这里模拟发现几个标准设备
-
-        // 创建网络设备配置
-        let net_config = VirtIODeviceConfig {
-            device_id: 0x1000,
-            vendor_id: 0x1AF4,
-            device_type: VirtIODeviceType::Network,
-            features: VirtIODeviceFeatures {
-                basic_features: 0x8000000000000000, // VERSION_1
-                device_specific_features: 0x0000000000000001, // MAC
-            },
-            queue_configs: vec![
-                VirtIOQueueConfig {
-                    size: 256,
-                    descriptor_alignment: 16,
-                    ring_size: 256,
-                    event_suppression: true,
-                    indirect_descriptors: true,
-                },
-                VirtIOQueueConfig {
-                    size: 256,
-                    descriptor_alignment: 16,
-                    ring_size: 256,
-                    event_suppression: true,
-                    indirect_descriptors: true,
-                },
-            ],
-            config_space_size: 6,
-            config_space: vec![0; 6],
-        };
-
-        self.register_device(VirtIODeviceType::Network, net_config)?;
-
-        // 创建块设备配置
-        let block_config = VirtIODeviceConfig {
-            device_id: 0x1001,
-            vendor_id: 0x1AF4,
-            device_type: VirtIODeviceType::Block,
-            features: VirtIODeviceFeatures {
-                basic_features: 0x8000000000000000, // VERSION_1
-                device_specific_features: 0x0000000000000001, // RO
-            },
-            queue_configs: vec![
-                VirtIOQueueConfig {
-                    size: 128,
-                    descriptor_alignment: 16,
-                    ring_size: 128,
-                    event_suppression: false,
-                    indirect_descriptors: true,
-                },
-            ],
-            config_space_size: 8,
-            config_space: vec![0; 8],
-        };
-
-        self.register_device(VirtIODeviceType::Block, block_config)?;
-
-        crate::println!("[virtio] Device scan completed. Found {} devices", self.get_device_count());
-        Ok(())
-    }
-
-    /// 启动所有设备
-    pub fn start_all_devices(&self) -> Result<(), i32> {
-        for (device_id, device) in &self.devices {
-            let mut dev = device.lock();
-            if dev.status == VirtIODeviceStatus::Ready {
-                dev.start()?;
-                crate::println!("[virtio] Started device ID: {}", device_id);
-            }
-        }
-        Ok(())
-    }
-
-    /// 停止所有设备
-    pub fn stop_all_devices(&self) -> Result<(), i32> {
-        for (device_id, device) in &self.devices {
-            let mut dev = device.lock();
-            if dev.status == VirtIODeviceStatus::Running {
-                dev.stop()?;
-                crate::println!("[virtio] Stopped device ID: {}", device_id);
-            }
-        }
-        Ok(())
-    }
-}
-
-/// 全局VirtIO管理器实例
-static mut VIRTIO_MANAGER: Option<VirtIOManager> = None;
-static mut VIRTIO_MANAGER_INITIALIZED: bool = false;
+// VirtIO设备视图
+// 设备由 `platform::drivers::virtio` 在启动时探测并注册到块层和网络栈，
+// 这里只向云原生服务报告它们
 
 /// 初始化VirtIO设备
+///
+/// The drivers probe at boot; this only reports what they found.
 pub fn initialize_virtio_devices() -> Result<(), i32> {
-    if unsafe { VIRTIO_MANAGER_INITIALIZED } {
-        return Ok(());
-    }
-
-    let mut manager = VirtIOManager::new();
-
-    // 扫描并初始化设备
-    manager.scan_devices()?;
-
-    // 启动所有设备
-    manager.start_all_devices()?;
-
-    unsafe {
-        VIRTIO_MANAGER = Some(manager);
-        VIRTIO_MANAGER_INITIALIZED = true;
-    }
-
-    crate::println!("[virtio] VirtIO devices initialized successfully");
+    crate::println!("[virtio] {} VirtIO devices available", get_virtio_device_count());
     Ok(())
 }
 
-/// 获取VirtIO管理器引用
-pub fn get_virtio_manager() -> Option<&'static VirtIOManager> {
-    unsafe {
-        VIRTIO_MANAGER.as_ref()
-    }
-}
-
 /// 获取VirtIO设备数量
 pub fn get_virtio_device_count() -> usize {
-    get_virtio_manager()
-        .map(|manager| manager.get_device_count())
-        .unwrap_or(0)
+    crate::drivers::virtio::block_devices().len() + crate::drivers::virtio::net_devices().len()
 }
 
 /// 清理VirtIO设备
+///
+/// The devices stay with the block layer and network stack, which outlive
+/// the service.
 pub fn cleanup_virtio_devices() -> Result<(), i32> {
-    let manager = get_virtio_manager().ok_or(EIO)?;
-
-    // 停止所有设备
-    manager.stop_all_devices()?;
-
-    // 在实际实现中，这里会清理所有设备资源
-
-    crate::println!("[virtio] VirtIO devices cleaned up successfully");
-    Ok(())
-}
-
-/// 发送网络数据包
-pub fn send_network_packet(device_id: u32, data: &[u8]) -> Result<(), i32> {
-    let manager = get_virtio_manager().ok_or(EIO)?;
-    let device = manager.get_device(device_id).ok_or(ENOENT)?;
-
-    let dev = device.lock();
-    if dev.device_type != VirtIODeviceType::Network {
-        return Err(EINVAL);
-    }
-
-    // 使用队列0（发送队列）
-    dev.send_to_queue(0, data)?;
-
-    crate::println!("[virtio] Sent network packet of {} bytes", data.len());
-    Ok(())
-}
-
-/// 接收网络数据包
-pub fn receive_network_packet(device_id: u32, buffer: &mut [u8]) -> Result<usize, i32> {
-    let manager = get_virtio_manager().ok_or(EIO)?;
-    let device = manager.get_device(device_id).ok_or(ENOENT)?;
-
-    let dev = device.lock();
-    if dev.device_type != VirtIODeviceType::Network {
-        return Err(EINVAL);
-    }
-
-    // 使用队列1（接收队列）
-    let bytes_received = dev.receive_from_queue(1, buffer)?;
-
-    if bytes_received > 0 {
-        crate::println!("[virtio] Received network packet of {} bytes", bytes_received);
-    }
-
-    Ok(bytes_received)
-}
-
-/// 读写块设备
-pub fn read_block_device(device_id: u32, lba: u32, sectors: u16, buffer: &mut [u8]) -> Result<(), i32> {
-    let manager = get_virtio_manager().ok_or(EIO)?;
-    let device = manager.get_device(device_id).ok_or(ENOENT)?;
-
-    let dev = device.lock();
-    if dev.device_type != VirtIODeviceType::Block {
-        return Err(EINVAL);
-    }
-
-    // 构建块设备读取请求
-    let request: Vec<u8> = vec![
-        0, // 读操作类型
-        (lba & 0xFF) as u8,
-        ((lba >> 8) & 0xFF) as u8,
-        ((lba >> 16) & 0xFF) as u8,
-        ((lba >> 24) & 0xFF) as u8,
-        (sectors & 0xFF) as u8,
-        ((sectors >> 8) & 0xFF) as u8,
-    ];
-
-    // 发送请求
-    dev.send_to_queue(0, &request)?;
-
-    // 等待响应并读取数据
-    let response_len = dev.receive_from_queue(0, buffer)?;
-
-    crate::println!("[virtio] Read {} sectors from LBA {} ({} bytes)", sectors, lba, response_len);
     Ok(())
 }
-
-/// 写入块设备
-pub fn write_block_device(device_id: u32, lba: u32, sectors: u16, data: &[u8]) -> Result<(), i32> {
-    let manager = get_virtio_manager().ok_or(EIO)?;
-    let device = manager.get_device(device_id).ok_or(ENOENT)?;
-
-    let dev = device.lock();
-    if dev.device_type != VirtIODeviceType::Block {
-        return Err(EINVAL);
-    }
-
-    // 构建块设备写入请求
-    let mut request: Vec<u8> = vec![
-        1, // 写操作类型
-        (lba & 0xFF) as u8,
-        ((lba >> 8) & 0xFF) as u8,
-        ((lba >> 16) & 0xFF) as u8,
-        ((lba >> 24) & 0xFF) as u8,
-        (sectors & 0xFF) as u8,
-        ((sectors >> 8) & 0xFF) as u8,
-    ];
-
-    // 添加数据
-    request.extend_from_slice(data);
-
-    // 发送请求
-    dev.send_to_queue(0, &request)?;
-
-    crate::println!("[virtio] Wrote {} sectors to LBA {} ({} bytes)", sectors, lba, data.len());
-    Ok(())
-}
\ No newline at end of file
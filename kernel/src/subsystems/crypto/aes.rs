@@ -0,0 +1,407 @@
+//! AES block cipher (FIPS 197)
+//!
+//! Keys are expanded in software; the block function runs on AES-NI or the
+//! ARMv8 crypto extensions when [`hardware_available`] and in software
+//! otherwise. Decryption uses the equivalent inverse cipher, whose round
+//! keys suit all three.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::{zeroize, CryptoError};
+
+pub const BLOCK_SIZE: usize = 16;
+
+/// A 16-byte block
+pub type Block = [u8; BLOCK_SIZE];
+
+const MAX_ROUNDS: usize = 14;
+
+/// Multiply by x in GF(2^8), without branching on the value
+const fn xtime(x: u8) -> u8 {
+    (x << 1) ^ ((x >> 7) * 0x1b)
+}
+
+const fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+const fn make_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut x = 0;
+    while x < 256 {
+        // The multiplicative inverse is x^254; 0 maps to 0
+        let mut inv = 1u8;
+        let mut i = 0;
+        while i < 254 {
+            inv = gmul(inv, x as u8);
+            i += 1;
+        }
+        if x == 0 {
+            inv = 0;
+        }
+        sbox[x] = inv
+            ^ inv.rotate_left(1)
+            ^ inv.rotate_left(2)
+            ^ inv.rotate_left(3)
+            ^ inv.rotate_left(4)
+            ^ 0x63;
+        x += 1;
+    }
+    sbox
+}
+
+const fn make_inv_sbox(sbox: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0u8; 256];
+    let mut x = 0;
+    while x < 256 {
+        inv[sbox[x] as usize] = x as u8;
+        x += 1;
+    }
+    inv
+}
+
+const SBOX: [u8; 256] = make_sbox();
+const INV_SBOX: [u8; 256] = make_inv_sbox(&SBOX);
+
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+const HW_UNKNOWN: u8 = 0;
+const HW_ABSENT: u8 = 1;
+const HW_PRESENT: u8 = 2;
+
+static HW_AES: AtomicU8 = AtomicU8::new(HW_UNKNOWN);
+
+/// Whether the CPU has AES instructions this module uses
+pub fn hardware_available() -> bool {
+    match HW_AES.load(Ordering::Relaxed) {
+        HW_UNKNOWN => {
+            let present = detect_hardware();
+            HW_AES.store(if present { HW_PRESENT } else { HW_ABSENT }, Ordering::Relaxed);
+            present
+        }
+        state => state == HW_PRESENT,
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn detect_hardware() -> bool {
+    // CPUID.1:ECX.AES[bit 25]
+    let leaf1 = core::arch::x86_64::__cpuid(1);
+    leaf1.ecx & (1 << 25) != 0
+}
+
+#[cfg(target_arch = "aarch64")]
+fn detect_hardware() -> bool {
+    // ID_AA64ISAR0_EL1.AES, bits [7:4]
+    let isar0: u64;
+    unsafe { core::arch::asm!("mrs {}, ID_AA64ISAR0_EL1", out(reg) isar0, options(nomem, nostack)) };
+    (isar0 >> 4) & 0xf != 0
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn detect_hardware() -> bool {
+    false
+}
+
+/// An expanded AES key
+pub struct Aes {
+    rounds: usize,
+    enc: [Block; MAX_ROUNDS + 1],
+    /// Round keys for the equivalent inverse cipher
+    dec: [Block; MAX_ROUNDS + 1],
+    hardware: bool,
+}
+
+impl Aes {
+    /// Expand a 16-, 24- or 32-byte key
+    pub fn new(key: &[u8]) -> Result<Self, CryptoError> {
+        Self::with_backend(key, hardware_available())
+    }
+
+    fn with_backend(key: &[u8], hardware: bool) -> Result<Self, CryptoError> {
+        let rounds = match key.len() {
+            16 => 10,
+            24 => 12,
+            32 => 14,
+            _ => return Err(CryptoError::InvalidKeyLength),
+        };
+        let nk = key.len() / 4;
+        let mut words = [[0u8; 4]; 4 * (MAX_ROUNDS + 1)];
+        for (word, chunk) in words.iter_mut().zip(key.chunks_exact(4)) {
+            word.copy_from_slice(chunk);
+        }
+        for i in nk..4 * (rounds + 1) {
+            let mut temp = words[i - 1];
+            if i % nk == 0 {
+                temp.rotate_left(1);
+                temp = temp.map(|b| SBOX[b as usize]);
+                temp[0] ^= RCON[i / nk - 1];
+            } else if nk > 6 && i % nk == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            for j in 0..4 {
+                words[i][j] = words[i - nk][j] ^ temp[j];
+            }
+        }
+
+        let mut aes = Self { rounds, enc: [[0; 16]; MAX_ROUNDS + 1], dec: [[0; 16]; MAX_ROUNDS + 1], hardware };
+        for (round, key) in aes.enc.iter_mut().enumerate().take(rounds + 1) {
+            for (j, word) in words[4 * round..4 * round + 4].iter().enumerate() {
+                key[4 * j..4 * j + 4].copy_from_slice(word);
+            }
+        }
+        aes.dec[0] = aes.enc[rounds];
+        for round in 1..rounds {
+            let mut key = aes.enc[rounds - round];
+            inv_mix_columns(&mut key);
+            aes.dec[round] = key;
+        }
+        aes.dec[rounds] = aes.enc[0];
+        for word in words.iter_mut() {
+            zeroize(word);
+        }
+        Ok(aes)
+    }
+
+    /// Key size in bytes
+    pub fn key_len(&self) -> usize {
+        match self.rounds {
+            10 => 16,
+            12 => 24,
+            _ => 32,
+        }
+    }
+
+    pub fn encrypt_block(&self, block: &mut Block) {
+        if self.hardware {
+            #[cfg(target_arch = "x86_64")]
+            return unsafe { ni::encrypt(&self.enc[..=self.rounds], block) };
+            #[cfg(target_arch = "aarch64")]
+            return unsafe { ce::encrypt(&self.enc[..=self.rounds], block) };
+        }
+        soft_encrypt(&self.enc[..=self.rounds], block);
+    }
+
+    pub fn decrypt_block(&self, block: &mut Block) {
+        if self.hardware {
+            #[cfg(target_arch = "x86_64")]
+            return unsafe { ni::decrypt(&self.dec[..=self.rounds], block) };
+            #[cfg(target_arch = "aarch64")]
+            return unsafe { ce::decrypt(&self.dec[..=self.rounds], block) };
+        }
+        soft_decrypt(&self.dec[..=self.rounds], block);
+    }
+}
+
+impl Drop for Aes {
+    fn drop(&mut self) {
+        for key in self.enc.iter_mut().chain(self.dec.iter_mut()) {
+            zeroize(key);
+        }
+    }
+}
+
+fn add_round_key(state: &mut Block, key: &Block) {
+    for (s, k) in state.iter_mut().zip(key) {
+        *s ^= k;
+    }
+}
+
+/// Row `r` of column `c` is byte `r + 4c`; row `r` rotates left by `r`
+fn shift_rows(state: &mut Block) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 1..4 {
+            state[r + 4 * c] = old[r + 4 * ((c + r) % 4)];
+        }
+    }
+}
+
+fn inv_shift_rows(state: &mut Block) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 1..4 {
+            state[r + 4 * ((c + r) % 4)] = old[r + 4 * c];
+        }
+    }
+}
+
+fn mix_columns(state: &mut Block) {
+    for column in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+fn inv_mix_columns(state: &mut Block) {
+    for column in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        column[0] = gmul(a0, 14) ^ gmul(a1, 11) ^ gmul(a2, 13) ^ gmul(a3, 9);
+        column[1] = gmul(a0, 9) ^ gmul(a1, 14) ^ gmul(a2, 11) ^ gmul(a3, 13);
+        column[2] = gmul(a0, 13) ^ gmul(a1, 9) ^ gmul(a2, 14) ^ gmul(a3, 11);
+        column[3] = gmul(a0, 11) ^ gmul(a1, 13) ^ gmul(a2, 9) ^ gmul(a3, 14);
+    }
+}
+
+fn soft_encrypt(keys: &[Block], block: &mut Block) {
+    let rounds = keys.len() - 1;
+    add_round_key(block, &keys[0]);
+    for key in &keys[1..rounds] {
+        block.iter_mut().for_each(|b| *b = SBOX[*b as usize]);
+        shift_rows(block);
+        mix_columns(block);
+        add_round_key(block, key);
+    }
+    block.iter_mut().for_each(|b| *b = SBOX[*b as usize]);
+    shift_rows(block);
+    add_round_key(block, &keys[rounds]);
+}
+
+fn soft_decrypt(keys: &[Block], block: &mut Block) {
+    let rounds = keys.len() - 1;
+    add_round_key(block, &keys[0]);
+    for key in &keys[1..rounds] {
+        block.iter_mut().for_each(|b| *b = INV_SBOX[*b as usize]);
+        inv_shift_rows(block);
+        inv_mix_columns(block);
+        add_round_key(block, key);
+    }
+    block.iter_mut().for_each(|b| *b = INV_SBOX[*b as usize]);
+    inv_shift_rows(block);
+    add_round_key(block, &keys[rounds]);
+}
+
+/// AES-NI
+#[cfg(target_arch = "x86_64")]
+mod ni {
+    use super::Block;
+    use core::arch::x86_64::*;
+
+    #[target_feature(enable = "aes,sse2")]
+    pub unsafe fn encrypt(keys: &[Block], block: &mut Block) {
+        let rounds = keys.len() - 1;
+        let key = |i: usize| unsafe { _mm_loadu_si128(keys[i].as_ptr().cast()) };
+        unsafe {
+            let mut state = _mm_xor_si128(_mm_loadu_si128(block.as_ptr().cast()), key(0));
+            for round in 1..rounds {
+                state = _mm_aesenc_si128(state, key(round));
+            }
+            state = _mm_aesenclast_si128(state, key(rounds));
+            _mm_storeu_si128(block.as_mut_ptr().cast(), state);
+        }
+    }
+
+    #[target_feature(enable = "aes,sse2")]
+    pub unsafe fn decrypt(keys: &[Block], block: &mut Block) {
+        let rounds = keys.len() - 1;
+        let key = |i: usize| unsafe { _mm_loadu_si128(keys[i].as_ptr().cast()) };
+        unsafe {
+            let mut state = _mm_xor_si128(_mm_loadu_si128(block.as_ptr().cast()), key(0));
+            for round in 1..rounds {
+                state = _mm_aesdec_si128(state, key(round));
+            }
+            state = _mm_aesdeclast_si128(state, key(rounds));
+            _mm_storeu_si128(block.as_mut_ptr().cast(), state);
+        }
+    }
+}
+
+/// ARMv8 crypto extensions
+///
+/// AESE/AESD add the round key before substituting rather than after, so
+/// the last key is applied with a plain XOR.
+#[cfg(target_arch = "aarch64")]
+mod ce {
+    use super::Block;
+    use core::arch::aarch64::*;
+
+    #[target_feature(enable = "aes")]
+    pub unsafe fn encrypt(keys: &[Block], block: &mut Block) {
+        let rounds = keys.len() - 1;
+        unsafe {
+            let mut state = vld1q_u8(block.as_ptr());
+            for key in &keys[..rounds - 1] {
+                state = vaesmcq_u8(vaeseq_u8(state, vld1q_u8(key.as_ptr())));
+            }
+            state = vaeseq_u8(state, vld1q_u8(keys[rounds - 1].as_ptr()));
+            state = veorq_u8(state, vld1q_u8(keys[rounds].as_ptr()));
+            vst1q_u8(block.as_mut_ptr(), state);
+        }
+    }
+
+    #[target_feature(enable = "aes")]
+    pub unsafe fn decrypt(keys: &[Block], block: &mut Block) {
+        let rounds = keys.len() - 1;
+        unsafe {
+            let mut state = vld1q_u8(block.as_ptr());
+            for key in &keys[..rounds - 1] {
+                state = vaesimcq_u8(vaesdq_u8(state, vld1q_u8(key.as_ptr())));
+            }
+            state = vaesdq_u8(state, vld1q_u8(keys[rounds - 1].as_ptr()));
+            state = veorq_u8(state, vld1q_u8(keys[rounds].as_ptr()));
+            vst1q_u8(block.as_mut_ptr(), state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::crypto::hex;
+
+    fn block(s: &str) -> Block {
+        hex(s).try_into().unwrap()
+    }
+
+    #[test]
+    fn test_sbox() {
+        assert_eq!(SBOX[0x00], 0x63);
+        assert_eq!(SBOX[0x53], 0xed);
+        assert_eq!(INV_SBOX[0x63], 0x00);
+    }
+
+    #[test]
+    fn test_fips197_vectors() {
+        // FIPS 197 appendix C
+        let plaintext = block("00112233445566778899aabbccddeeff");
+        let cases = [
+            ("000102030405060708090a0b0c0d0e0f", "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            ("000102030405060708090a0b0c0d0e0f1011121314151617", "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "8ea2b7ca516745bfeafc49904b496089",
+            ),
+        ];
+        for (key, ciphertext) in cases {
+            for hardware in [false, hardware_available()] {
+                let aes = Aes::with_backend(&hex(key), hardware).unwrap();
+                let mut data = plaintext;
+                aes.encrypt_block(&mut data);
+                assert_eq!(data, block(ciphertext));
+                aes.decrypt_block(&mut data);
+                assert_eq!(data, plaintext);
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_lengths() {
+        assert!(Aes::new(&[0; 16]).is_ok());
+        assert!(Aes::new(&[0; 24]).is_ok());
+        assert!(Aes::new(&[0; 32]).is_ok());
+        assert_eq!(Aes::new(&[0; 20]).err(), Some(CryptoError::InvalidKeyLength));
+        assert_eq!(Aes::new(&[0; 24]).unwrap().key_len(), 24);
+    }
+}
@@ -0,0 +1,168 @@
+//! ChaCha20 stream cipher and the ChaCha20-Poly1305 AEAD (RFC 8439)
+
+use super::poly1305::Poly1305;
+use super::{ct_eq, zeroize, CryptoError};
+
+pub const KEY_SIZE: usize = 32;
+pub const NONCE_SIZE: usize = 12;
+pub const TAG_SIZE: usize = 16;
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// One 64-byte keystream block
+pub fn block(key: &[u8; KEY_SIZE], counter: u32, nonce: &[u8; NONCE_SIZE]) -> [u8; 64] {
+    let word = |bytes: &[u8]| u32::from_le_bytes(bytes[..4].try_into().unwrap());
+    let mut input = [0u32; 16];
+    input[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for i in 0..8 {
+        input[4 + i] = word(&key[4 * i..]);
+    }
+    input[12] = counter;
+    for i in 0..3 {
+        input[13 + i] = word(&nonce[4 * i..]);
+    }
+
+    let mut state = input;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; 64];
+    for (i, chunk) in out.chunks_exact_mut(4).enumerate() {
+        chunk.copy_from_slice(&state[i].wrapping_add(input[i]).to_le_bytes());
+    }
+    out
+}
+
+/// XOR `data` with the keystream starting at block `counter`
+pub fn apply_keystream(key: &[u8; KEY_SIZE], counter: u32, nonce: &[u8; NONCE_SIZE], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let keystream = block(key, counter.wrapping_add(i as u32), nonce);
+        for (d, k) in chunk.iter_mut().zip(keystream) {
+            *d ^= k;
+        }
+    }
+}
+
+/// ChaCha20-Poly1305
+pub struct ChaCha20Poly1305 {
+    key: [u8; KEY_SIZE],
+}
+
+impl ChaCha20Poly1305 {
+    pub fn new(key: &[u8]) -> Result<Self, CryptoError> {
+        let key = key.try_into().map_err(|_| CryptoError::InvalidKeyLength)?;
+        Ok(Self { key })
+    }
+
+    fn tag(&self, nonce: &[u8; NONCE_SIZE], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+        let mut otk: [u8; 32] = block(&self.key, 0, nonce)[..32].try_into().unwrap();
+        let mut mac = Poly1305::new(&otk);
+        zeroize(&mut otk);
+        mac.update(aad);
+        mac.pad16();
+        mac.update(ciphertext);
+        mac.pad16();
+        mac.update(&(aad.len() as u64).to_le_bytes());
+        mac.update(&(ciphertext.len() as u64).to_le_bytes());
+        mac.finalize()
+    }
+
+    /// Encrypt `data` in place and return the tag over it and `aad`
+    pub fn encrypt(&self, nonce: &[u8; NONCE_SIZE], aad: &[u8], data: &mut [u8]) -> [u8; TAG_SIZE] {
+        apply_keystream(&self.key, 1, nonce, data);
+        self.tag(nonce, aad, data)
+    }
+
+    /// Check `tag` and only then decrypt `data` in place
+    pub fn decrypt(
+        &self,
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        data: &mut [u8],
+        tag: &[u8; TAG_SIZE],
+    ) -> Result<(), CryptoError> {
+        if !ct_eq(&self.tag(nonce, aad, data), tag) {
+            return Err(CryptoError::AuthenticationFailed);
+        }
+        apply_keystream(&self.key, 1, nonce, data);
+        Ok(())
+    }
+}
+
+impl Drop for ChaCha20Poly1305 {
+    fn drop(&mut self) {
+        zeroize(&mut self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::crypto::hex;
+
+    fn key() -> [u8; 32] {
+        core::array::from_fn(|i| i as u8)
+    }
+
+    #[test]
+    fn test_chacha20_block() {
+        // RFC 8439 2.3.2
+        let nonce: [u8; 12] = hex("000000090000004a00000000").try_into().unwrap();
+        assert_eq!(
+            block(&key(), 1, &nonce).to_vec(),
+            hex("10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e
+                 d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e")
+        );
+    }
+
+    #[test]
+    fn test_aead_vector() {
+        // RFC 8439 2.8.2
+        let key: [u8; 32] = core::array::from_fn(|i| 0x80 + i as u8);
+        let aead = ChaCha20Poly1305::new(&key).unwrap();
+        let nonce: [u8; 12] = hex("070000004041424344454647").try_into().unwrap();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let mut data = plaintext.to_vec();
+        let tag = aead.encrypt(&nonce, &aad, &mut data);
+        assert_eq!(
+            data,
+            hex("d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6
+                 3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36
+                 92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc
+                 3ff4def08e4b7a9de576d26586cec64b6116")
+        );
+        assert_eq!(tag.to_vec(), hex("1ae10b594f09e26a7e902ecbd0600691"));
+
+        aead.decrypt(&nonce, &aad, &mut data, &tag).unwrap();
+        assert_eq!(data, plaintext);
+    }
+
+    #[test]
+    fn test_aead_rejects_forgery() {
+        let aead = ChaCha20Poly1305::new(&key()).unwrap();
+        let nonce = [3u8; 12];
+        let mut data = *b"sealed";
+        let mut tag = aead.encrypt(&nonce, &[], &mut data);
+        tag[0] ^= 0x80;
+        assert_eq!(aead.decrypt(&nonce, &[], &mut data, &tag), Err(CryptoError::AuthenticationFailed));
+        assert!(ChaCha20Poly1305::new(&[0; 16]).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! AES-GCM authenticated encryption (SP 800-38D)
+
+use super::aes::{Aes, Block, BLOCK_SIZE};
+use super::{ct_eq, CryptoError};
+
+pub const TAG_SIZE: usize = 16;
+
+/// Multiply in GF(2^128) with GCM's reflected bit order, in constant time
+fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut product = 0;
+    let mut v = y;
+    for i in 0..128 {
+        let bit = (x >> (127 - i)) & 1;
+        product ^= v & 0u128.wrapping_sub(bit);
+        let lsb = v & 1;
+        v = (v >> 1) ^ (R & 0u128.wrapping_sub(lsb));
+    }
+    product
+}
+
+/// GHASH under the hash subkey `h`
+struct Ghash {
+    h: u128,
+    acc: u128,
+}
+
+impl Ghash {
+    fn new(h: u128) -> Self {
+        Self { h, acc: 0 }
+    }
+
+    /// Absorb `data`, zero-padded to a whole number of blocks
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(BLOCK_SIZE) {
+            let mut block = [0u8; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.acc = gf_mul(self.acc ^ u128::from_be_bytes(block), self.h);
+        }
+    }
+
+    fn finish(mut self, aad_len: usize, data_len: usize) -> u128 {
+        let lengths = ((aad_len as u128 * 8) << 64) | (data_len as u128 * 8);
+        self.acc = gf_mul(self.acc ^ lengths, self.h);
+        self.acc
+    }
+}
+
+/// AES-GCM with 16-byte tags
+pub struct AesGcm {
+    aes: Aes,
+    h: u128,
+}
+
+impl AesGcm {
+    pub fn new(key: &[u8]) -> Result<Self, CryptoError> {
+        let aes = Aes::new(key)?;
+        let mut h = [0u8; BLOCK_SIZE];
+        aes.encrypt_block(&mut h);
+        Ok(Self { aes, h: u128::from_be_bytes(h) })
+    }
+
+    /// The pre-counter block J0 for `nonce`; 12 bytes is the usual size
+    fn j0(&self, nonce: &[u8]) -> Result<u128, CryptoError> {
+        if nonce.is_empty() {
+            return Err(CryptoError::InvalidLength);
+        }
+        if nonce.len() == 12 {
+            let mut block = [0u8; BLOCK_SIZE];
+            block[..12].copy_from_slice(nonce);
+            block[15] = 1;
+            return Ok(u128::from_be_bytes(block));
+        }
+        let mut ghash = Ghash::new(self.h);
+        ghash.update_padded(nonce);
+        Ok(ghash.finish(0, nonce.len()))
+    }
+
+    /// CTR with a 32-bit counter starting after J0
+    fn ctr(&self, j0: u128, data: &mut [u8]) {
+        let mut counter = j0;
+        for chunk in data.chunks_mut(BLOCK_SIZE) {
+            counter = (counter & !0xffff_ffff) | ((counter as u32).wrapping_add(1) as u128);
+            let mut keystream = counter.to_be_bytes();
+            self.aes.encrypt_block(&mut keystream);
+            for (d, k) in chunk.iter_mut().zip(keystream) {
+                *d ^= k;
+            }
+        }
+    }
+
+    fn tag(&self, j0: u128, aad: &[u8], ciphertext: &[u8]) -> Block {
+        let mut ghash = Ghash::new(self.h);
+        ghash.update_padded(aad);
+        ghash.update_padded(ciphertext);
+        let s = ghash.finish(aad.len(), ciphertext.len());
+        let mut mask = j0.to_be_bytes();
+        self.aes.encrypt_block(&mut mask);
+        (s ^ u128::from_be_bytes(mask)).to_be_bytes()
+    }
+
+    /// Encrypt `data` in place and return the tag over it and `aad`
+    pub fn encrypt(&self, nonce: &[u8], aad: &[u8], data: &mut [u8]) -> Result<Block, CryptoError> {
+        let j0 = self.j0(nonce)?;
+        self.ctr(j0, data);
+        Ok(self.tag(j0, aad, data))
+    }
+
+    /// Check `tag` and only then decrypt `data` in place
+    pub fn decrypt(&self, nonce: &[u8], aad: &[u8], data: &mut [u8], tag: &Block) -> Result<(), CryptoError> {
+        let j0 = self.j0(nonce)?;
+        if !ct_eq(&self.tag(j0, aad, data), tag) {
+            return Err(CryptoError::AuthenticationFailed);
+        }
+        self.ctr(j0, data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::crypto::hex;
+
+    fn block(s: &str) -> Block {
+        hex(s).try_into().unwrap()
+    }
+
+    #[test]
+    fn test_gcm_zero_key() {
+        // GCM spec test cases 1 and 2
+        let gcm = AesGcm::new(&[0; 16]).unwrap();
+        let tag = gcm.encrypt(&[0; 12], &[], &mut []).unwrap();
+        assert_eq!(tag, block("58e2fccefa7e3061367f1d57a4e7455a"));
+
+        let mut data = [0u8; 16];
+        let tag = gcm.encrypt(&[0; 12], &[], &mut data).unwrap();
+        assert_eq!(data, block("0388dace60b6a392f328c2b971b2fe78"));
+        assert_eq!(tag, block("ab6e47d42cec13bdf53a67b21257bddf"));
+    }
+
+    #[test]
+    fn test_gcm_with_aad() {
+        // GCM spec test case 4
+        let gcm = AesGcm::new(&hex("feffe9928665731c6d6a8f9467308308")).unwrap();
+        let nonce = hex("cafebabefacedbaddecaf888");
+        let aad = hex("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let plaintext = hex(
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        );
+        let mut data = plaintext.clone();
+        let tag = gcm.encrypt(&nonce, &aad, &mut data).unwrap();
+        assert_eq!(
+            data,
+            hex("42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e
+                 21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091")
+        );
+        assert_eq!(tag, block("5bc94fbc3221a5db94fae95ae7121a47"));
+
+        gcm.decrypt(&nonce, &aad, &mut data, &tag).unwrap();
+        assert_eq!(data, plaintext);
+    }
+
+    #[test]
+    fn test_gcm_rejects_forgery() {
+        let gcm = AesGcm::new(&[7; 32]).unwrap();
+        let nonce = [1u8; 12];
+        let mut data = *b"attack at dawn";
+        let tag = gcm.encrypt(&nonce, b"hdr", &mut data).unwrap();
+        let ciphertext = data;
+
+        data[0] ^= 1;
+        assert_eq!(gcm.decrypt(&nonce, b"hdr", &mut data, &tag), Err(CryptoError::AuthenticationFailed));
+        // Nothing is decrypted when the tag is wrong
+        data[0] ^= 1;
+        assert_eq!(data, ciphertext);
+        assert_eq!(gcm.decrypt(&nonce, b"hdx", &mut data, &tag), Err(CryptoError::AuthenticationFailed));
+        gcm.decrypt(&nonce, b"hdr", &mut data, &tag).unwrap();
+        assert_eq!(&data, b"attack at dawn");
+    }
+}
@@ -0,0 +1,91 @@
+//! HKDF (RFC 5869)
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::hmac::Hmac;
+use super::{zeroize, CryptoError, Digest};
+
+/// Condense input keying material into a pseudorandom key
+///
+/// An empty salt stands for a block of zeros, as the RFC specifies.
+pub fn extract<D: Digest>(salt: &[u8], ikm: &[u8]) -> Vec<u8> {
+    let zeros = vec![0u8; D::OUTPUT_LEN];
+    let salt = if salt.is_empty() { &zeros[..] } else { salt };
+    let mut mac = Hmac::<D>::new(salt);
+    mac.update(ikm);
+    mac.finalize()
+}
+
+/// Expand a pseudorandom key into `okm.len()` bytes bound to `info`
+pub fn expand<D: Digest>(prk: &[u8], info: &[u8], okm: &mut [u8]) -> Result<(), CryptoError> {
+    if prk.len() < D::OUTPUT_LEN || okm.len() > 255 * D::OUTPUT_LEN {
+        return Err(CryptoError::InvalidLength);
+    }
+    let mut t = vec![0u8; D::OUTPUT_LEN];
+    for (i, chunk) in okm.chunks_mut(D::OUTPUT_LEN).enumerate() {
+        let mut mac = Hmac::<D>::new(prk);
+        if i > 0 {
+            mac.update(&t);
+        }
+        mac.update(info);
+        mac.update(&[i as u8 + 1]);
+        mac.finalize_into(&mut t);
+        chunk.copy_from_slice(&t[..chunk.len()]);
+    }
+    zeroize(&mut t);
+    Ok(())
+}
+
+/// Extract then expand
+pub fn hkdf<D: Digest>(salt: &[u8], ikm: &[u8], info: &[u8], okm: &mut [u8]) -> Result<(), CryptoError> {
+    let mut prk = extract::<D>(salt, ikm);
+    let result = expand::<D>(&prk, info, okm);
+    zeroize(&mut prk);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::crypto::hex;
+    use crate::subsystems::crypto::sha2::Sha256;
+
+    #[test]
+    fn test_hkdf_sha256_vector() {
+        // RFC 5869 A.1
+        let ikm = [0x0b; 22];
+        let salt = hex("000102030405060708090a0b0c");
+        let info = hex("f0f1f2f3f4f5f6f7f8f9");
+        let prk = extract::<Sha256>(&salt, &ikm);
+        assert_eq!(prk, hex("077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5"));
+
+        let mut okm = [0u8; 42];
+        expand::<Sha256>(&prk, &info, &mut okm).unwrap();
+        assert_eq!(
+            okm.to_vec(),
+            hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865")
+        );
+    }
+
+    #[test]
+    fn test_hkdf_empty_salt() {
+        // RFC 5869 A.3
+        let mut okm = [0u8; 42];
+        hkdf::<Sha256>(&[], &[0x0b; 22], &[], &mut okm).unwrap();
+        assert_eq!(
+            okm.to_vec(),
+            hex("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8")
+        );
+    }
+
+    #[test]
+    fn test_hkdf_output_limit() {
+        let prk = [0u8; 32];
+        let mut okm = vec![0u8; 255 * 32 + 1];
+        assert_eq!(expand::<Sha256>(&prk, &[], &mut okm), Err(CryptoError::InvalidLength));
+        assert!(expand::<Sha256>(&prk, &[], &mut okm[..255 * 32]).is_ok());
+    }
+}
@@ -0,0 +1,123 @@
+//! HMAC (RFC 2104)
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{ct_eq, zeroize, CryptoError, Digest};
+
+/// HMAC over the hash `D`
+#[derive(Clone)]
+pub struct Hmac<D: Digest> {
+    inner: D,
+    outer: D,
+}
+
+impl<D: Digest> Hmac<D> {
+    pub fn new(key: &[u8]) -> Self {
+        let mut block = vec![0u8; D::BLOCK_LEN];
+        if key.len() > D::BLOCK_LEN {
+            let mut hasher = D::new();
+            hasher.update(key);
+            hasher.finalize_into(&mut block);
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = D::new();
+        let mut outer = D::new();
+        for byte in block.iter_mut() {
+            *byte ^= 0x36;
+        }
+        inner.update(&block);
+        for byte in block.iter_mut() {
+            *byte ^= 0x36 ^ 0x5c;
+        }
+        outer.update(&block);
+        zeroize(&mut block);
+        Self { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Write the tag to the first `D::OUTPUT_LEN` bytes of `out`
+    pub fn finalize_into(self, out: &mut [u8]) {
+        let mut inner_hash = vec![0u8; D::OUTPUT_LEN];
+        self.inner.finalize_into(&mut inner_hash);
+        let mut outer = self.outer;
+        outer.update(&inner_hash);
+        outer.finalize_into(out);
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        let mut tag = vec![0u8; D::OUTPUT_LEN];
+        self.finalize_into(&mut tag);
+        tag
+    }
+
+    /// Check `tag` in constant time; a truncated tag checks its prefix
+    pub fn verify(self, tag: &[u8]) -> Result<(), CryptoError> {
+        let expected = self.finalize();
+        if tag.is_empty() || !ct_eq(&expected[..tag.len().min(expected.len())], tag) {
+            return Err(CryptoError::AuthenticationFailed);
+        }
+        Ok(())
+    }
+}
+
+/// One-shot HMAC of `data` under `key`
+pub fn hmac<D: Digest>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<D>::new(key);
+    mac.update(data);
+    mac.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::crypto::hex;
+    use crate::subsystems::crypto::sha2::{Sha256, Sha512};
+
+    // RFC 4231
+    #[test]
+    fn test_hmac_sha256_vectors() {
+        assert_eq!(
+            hmac::<Sha256>(&[0x0b; 20], b"Hi There"),
+            hex("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7")
+        );
+        assert_eq!(
+            hmac::<Sha256>(b"Jefe", b"what do ya want for nothing?"),
+            hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+        // Keys longer than a block are hashed first
+        assert_eq!(
+            hmac::<Sha256>(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First"),
+            hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha512_vectors() {
+        assert_eq!(
+            hmac::<Sha512>(&[0x0b; 20], b"Hi There"),
+            hex("87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cde
+                 daa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854")
+        );
+    }
+
+    #[test]
+    fn test_hmac_verify() {
+        let tag = hmac::<Sha256>(b"key", b"message");
+        let mut mac = Hmac::<Sha256>::new(b"key");
+        mac.update(b"message");
+        assert!(mac.clone().verify(&tag).is_ok());
+        assert!(mac.clone().verify(&tag[..16]).is_ok());
+        let mut bad = tag.clone();
+        bad[31] ^= 1;
+        assert_eq!(mac.clone().verify(&bad), Err(CryptoError::AuthenticationFailed));
+        assert_eq!(mac.verify(&[]), Err(CryptoError::AuthenticationFailed));
+    }
+}
@@ -0,0 +1,321 @@
+//! AES block cipher modes: CBC, CTR (SP 800-38A), CBC with ciphertext
+//! stealing (CBC-CS3) and XTS (IEEE 1619)
+//!
+//! All of them work in place. CBC takes whole blocks and does no padding;
+//! CBC-CS3 and XTS handle a trailing partial block with ciphertext stealing.
+
+use super::aes::{Aes, Block, BLOCK_SIZE};
+use super::CryptoError;
+
+fn xor_into(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+/// CBC-encrypt `data`, a whole number of blocks
+pub fn cbc_encrypt(aes: &Aes, iv: &Block, data: &mut [u8]) -> Result<(), CryptoError> {
+    if !data.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(CryptoError::InvalidLength);
+    }
+    let mut chain = *iv;
+    for chunk in data.chunks_exact_mut(BLOCK_SIZE) {
+        xor_into(&mut chain, chunk);
+        aes.encrypt_block(&mut chain);
+        chunk.copy_from_slice(&chain);
+    }
+    Ok(())
+}
+
+pub fn cbc_decrypt(aes: &Aes, iv: &Block, data: &mut [u8]) -> Result<(), CryptoError> {
+    if !data.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(CryptoError::InvalidLength);
+    }
+    let mut chain = *iv;
+    for chunk in data.chunks_exact_mut(BLOCK_SIZE) {
+        let ciphertext: Block = (&*chunk).try_into().unwrap();
+        let mut block = ciphertext;
+        aes.decrypt_block(&mut block);
+        xor_into(&mut block, &chain);
+        chunk.copy_from_slice(&block);
+        chain = ciphertext;
+    }
+    Ok(())
+}
+
+/// CBC-CS3 encrypt `data`, at least one block long
+///
+/// This is Linux's `cts(cbc(aes))` and Kerberos' AES-CTS: plain CBC with
+/// the last two ciphertext blocks swapped and the final one cut to the
+/// length of the last plaintext block.
+pub fn cts_encrypt(aes: &Aes, iv: &Block, data: &mut [u8]) -> Result<(), CryptoError> {
+    if data.len() < BLOCK_SIZE {
+        return Err(CryptoError::InvalidLength);
+    }
+    if data.len() == BLOCK_SIZE {
+        return cbc_encrypt(aes, iv, data);
+    }
+    let tail = data.len() - (data.len() - 1) / BLOCK_SIZE * BLOCK_SIZE;
+    let split = data.len() - tail - BLOCK_SIZE;
+    let (head, last_two) = data.split_at_mut(split);
+    cbc_encrypt(aes, iv, head)?;
+    let chain: Block = match head.len() {
+        0 => *iv,
+        n => head[n - BLOCK_SIZE..].try_into().unwrap(),
+    };
+
+    let mut penultimate: Block = last_two[..BLOCK_SIZE].try_into().unwrap();
+    xor_into(&mut penultimate, &chain);
+    aes.encrypt_block(&mut penultimate);
+    let mut last = [0u8; BLOCK_SIZE];
+    last[..tail].copy_from_slice(&last_two[BLOCK_SIZE..]);
+    xor_into(&mut last, &penultimate);
+    aes.encrypt_block(&mut last);
+    last_two[..BLOCK_SIZE].copy_from_slice(&last);
+    last_two[BLOCK_SIZE..].copy_from_slice(&penultimate[..tail]);
+    Ok(())
+}
+
+pub fn cts_decrypt(aes: &Aes, iv: &Block, data: &mut [u8]) -> Result<(), CryptoError> {
+    if data.len() < BLOCK_SIZE {
+        return Err(CryptoError::InvalidLength);
+    }
+    if data.len() == BLOCK_SIZE {
+        return cbc_decrypt(aes, iv, data);
+    }
+    let tail = data.len() - (data.len() - 1) / BLOCK_SIZE * BLOCK_SIZE;
+    let split = data.len() - tail - BLOCK_SIZE;
+    let (head, last_two) = data.split_at_mut(split);
+    let chain: Block = match head.len() {
+        0 => *iv,
+        n => head[n - BLOCK_SIZE..].try_into().unwrap(),
+    };
+    cbc_decrypt(aes, iv, head)?;
+
+    // The block stored first was encrypted last; decrypting it gives the
+    // final plaintext XORed with the stolen ciphertext, whose missing
+    // bytes were encrypted as zeros and so show up unchanged
+    let mut last: Block = last_two[..BLOCK_SIZE].try_into().unwrap();
+    aes.decrypt_block(&mut last);
+    let mut penultimate = last;
+    penultimate[..tail].copy_from_slice(&last_two[BLOCK_SIZE..]);
+    xor_into(&mut last, &penultimate);
+    let mut plain = penultimate;
+    aes.decrypt_block(&mut plain);
+    xor_into(&mut plain, &chain);
+    last_two[..BLOCK_SIZE].copy_from_slice(&plain);
+    last_two[BLOCK_SIZE..].copy_from_slice(&last[..tail]);
+    Ok(())
+}
+
+/// Encrypt or decrypt `data` in CTR mode
+///
+/// `counter` is the first counter block; the whole block is incremented
+/// as a big-endian integer.
+pub fn ctr_apply(aes: &Aes, counter: &Block, data: &mut [u8]) {
+    let mut counter = u128::from_be_bytes(*counter);
+    for chunk in data.chunks_mut(BLOCK_SIZE) {
+        let mut keystream = counter.to_be_bytes();
+        aes.encrypt_block(&mut keystream);
+        xor_into(chunk, &keystream);
+        counter = counter.wrapping_add(1);
+    }
+}
+
+/// Multiply a tweak by x in GF(2^128), little-endian as IEEE 1619 has it
+fn xts_double(tweak: &mut Block) {
+    let value = u128::from_le_bytes(*tweak);
+    let carry = (value >> 127) as u8;
+    let mut doubled = (value << 1).to_le_bytes();
+    doubled[0] ^= carry * 0x87;
+    *tweak = doubled;
+}
+
+/// AES-XTS, for sector-addressed storage
+pub struct Xts {
+    data: Aes,
+    tweak: Aes,
+}
+
+impl Xts {
+    /// Two AES keys back to back: 32 bytes for AES-128-XTS, 64 for
+    /// AES-256-XTS
+    pub fn new(key: &[u8]) -> Result<Self, CryptoError> {
+        if key.len() != 32 && key.len() != 64 {
+            return Err(CryptoError::InvalidKeyLength);
+        }
+        let (data, tweak) = key.split_at(key.len() / 2);
+        Ok(Self { data: Aes::new(data)?, tweak: Aes::new(tweak)? })
+    }
+
+    /// Encrypt one data unit in place; `tweak` is usually the sector
+    /// number as a little-endian block (see [`Xts::sector_tweak`])
+    pub fn encrypt(&self, tweak: &Block, data: &mut [u8]) -> Result<(), CryptoError> {
+        self.crypt(tweak, data, true)
+    }
+
+    pub fn decrypt(&self, tweak: &Block, data: &mut [u8]) -> Result<(), CryptoError> {
+        self.crypt(tweak, data, false)
+    }
+
+    /// The tweak for data unit `sector`
+    pub fn sector_tweak(sector: u64) -> Block {
+        (sector as u128).to_le_bytes()
+    }
+
+    fn block(&self, tweak: &Block, block: &mut [u8], encrypt: bool) {
+        let mut buf: Block = (&*block).try_into().unwrap();
+        xor_into(&mut buf, tweak);
+        if encrypt {
+            self.data.encrypt_block(&mut buf);
+        } else {
+            self.data.decrypt_block(&mut buf);
+        }
+        xor_into(&mut buf, tweak);
+        block.copy_from_slice(&buf);
+    }
+
+    fn crypt(&self, tweak: &Block, data: &mut [u8], encrypt: bool) -> Result<(), CryptoError> {
+        if data.len() < BLOCK_SIZE {
+            return Err(CryptoError::InvalidLength);
+        }
+        let mut t = *tweak;
+        self.tweak.encrypt_block(&mut t);
+
+        let partial = data.len() % BLOCK_SIZE;
+        let full = data.len() / BLOCK_SIZE;
+        // With a partial block, the last full block is handled by stealing
+        let plain = if partial == 0 { full } else { full - 1 };
+        for chunk in data.chunks_exact_mut(BLOCK_SIZE).take(plain) {
+            self.block(&t, chunk, encrypt);
+            xts_double(&mut t);
+        }
+        if partial == 0 {
+            return Ok(());
+        }
+
+        let (last_full, tail) = data[plain * BLOCK_SIZE..].split_at_mut(BLOCK_SIZE);
+        let mut next = t;
+        xts_double(&mut next);
+        // Decryption undoes the final block with the later tweak first
+        let (first, second) = if encrypt { (t, next) } else { (next, t) };
+        self.block(&first, last_full, encrypt);
+        let mut stolen: Block = (&*last_full).try_into().unwrap();
+        stolen[..partial].copy_from_slice(tail);
+        tail.copy_from_slice(&last_full[..partial]);
+        self.block(&second, &mut stolen, encrypt);
+        last_full.copy_from_slice(&stolen);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::crypto::hex;
+
+    // SP 800-38A F.2.1 and F.5.1
+    const KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const PLAINTEXT: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51";
+
+    #[test]
+    fn test_cbc_vector() {
+        let aes = Aes::new(&hex(KEY)).unwrap();
+        let iv: Block = hex("000102030405060708090a0b0c0d0e0f").try_into().unwrap();
+        let mut data = hex(PLAINTEXT);
+        cbc_encrypt(&aes, &iv, &mut data).unwrap();
+        assert_eq!(data, hex("7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2"));
+        cbc_decrypt(&aes, &iv, &mut data).unwrap();
+        assert_eq!(data, hex(PLAINTEXT));
+        assert_eq!(cbc_encrypt(&aes, &iv, &mut [0; 15]), Err(CryptoError::InvalidLength));
+    }
+
+    #[test]
+    fn test_ctr_vector() {
+        let aes = Aes::new(&hex(KEY)).unwrap();
+        let counter: Block = hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").try_into().unwrap();
+        let mut data = hex(PLAINTEXT);
+        ctr_apply(&aes, &counter, &mut data);
+        assert_eq!(data, hex("874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff"));
+        ctr_apply(&aes, &counter, &mut data);
+        assert_eq!(data, hex(PLAINTEXT));
+    }
+
+    #[test]
+    fn test_cts_vectors() {
+        // RFC 3962 appendix B, which uses the same CBC-CS3 construction
+        let aes = Aes::new(&hex("636869636b656e207465726979616b69")).unwrap();
+        let iv = [0u8; 16];
+        let vectors = [
+            ("4920776f756c64206c696b652074686520", "c6353568f2bf8cb4d8a580362da7ff7f97"),
+            (
+                "4920776f756c64206c696b65207468652047656e6572616c20476175277320",
+                "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5",
+            ),
+            (
+                "4920776f756c64206c696b65207468652047656e6572616c2047617527732043",
+                "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584",
+            ),
+            (
+                "4920776f756c64206c696b65207468652047656e6572616c20476175277320436869636b656e2c20706c656173652c",
+                "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e39312523a78662d5be7fcbcc98ebf5",
+            ),
+        ];
+        for (plaintext, ciphertext) in vectors {
+            let mut data = hex(plaintext);
+            cts_encrypt(&aes, &iv, &mut data).unwrap();
+            assert_eq!(data, hex(ciphertext));
+            cts_decrypt(&aes, &iv, &mut data).unwrap();
+            assert_eq!(data, hex(plaintext));
+        }
+        assert_eq!(cts_encrypt(&aes, &iv, &mut [0; 15]), Err(CryptoError::InvalidLength));
+    }
+
+    #[test]
+    fn test_xts_vectors() {
+        // IEEE 1619 vectors 1 and 2
+        let xts = Xts::new(&[0; 32]).unwrap();
+        let mut data = [0u8; 32];
+        xts.encrypt(&Xts::sector_tweak(0), &mut data).unwrap();
+        assert_eq!(
+            data.to_vec(),
+            hex("917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e")
+        );
+
+        let mut key = [0x11u8; 32];
+        key[16..].fill(0x22);
+        let xts = Xts::new(&key).unwrap();
+        let mut data = [0x44u8; 32];
+        xts.encrypt(&Xts::sector_tweak(0x3333333333), &mut data).unwrap();
+        assert_eq!(
+            data.to_vec(),
+            hex("c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0")
+        );
+        xts.decrypt(&Xts::sector_tweak(0x3333333333), &mut data).unwrap();
+        assert_eq!(data, [0x44; 32]);
+    }
+
+    #[test]
+    fn test_xts_ciphertext_stealing() {
+        // IEEE 1619 vector 15
+        let key = hex("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0");
+        let xts = Xts::new(&key).unwrap();
+        let tweak = Xts::sector_tweak(0x123456789a);
+        let plaintext = hex("000102030405060708090a0b0c0d0e0f10");
+        let mut data = plaintext.clone();
+        xts.encrypt(&tweak, &mut data).unwrap();
+        assert_eq!(data, hex("6c1625db4671522d3d7599601de7ca09ed"));
+        xts.decrypt(&tweak, &mut data).unwrap();
+        assert_eq!(data, plaintext);
+
+        // Every tail length round-trips
+        for len in 16..48 {
+            let plaintext: alloc::vec::Vec<u8> = (0..len as u8).collect();
+            let mut data = plaintext.clone();
+            xts.encrypt(&tweak, &mut data).unwrap();
+            xts.decrypt(&tweak, &mut data).unwrap();
+            assert_eq!(data, plaintext);
+        }
+        assert_eq!(xts.encrypt(&tweak, &mut [0; 15]), Err(CryptoError::InvalidLength));
+    }
+}
@@ -0,0 +1,204 @@
+//! Poly1305 one-time authenticator (RFC 8439)
+//!
+//! Five 26-bit limbs with 64-bit products (the "donna" layout); the final
+//! reduction selects with masks rather than branching.
+
+pub const KEY_SIZE: usize = 32;
+pub const TAG_SIZE: usize = 16;
+
+const MASK: u32 = 0x3ff_ffff;
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+/// Poly1305 state for one message; never reuse a key
+pub struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+    buf: [u8; 16],
+    buf_len: usize,
+}
+
+impl Poly1305 {
+    pub fn new(key: &[u8; KEY_SIZE]) -> Self {
+        Self {
+            r: [
+                le32(&key[0..]) & 0x3ff_ffff,
+                (le32(&key[3..]) >> 2) & 0x3ff_ff03,
+                (le32(&key[6..]) >> 4) & 0x3ff_c0ff,
+                (le32(&key[9..]) >> 6) & 0x3f0_3fff,
+                (le32(&key[12..]) >> 8) & 0x00f_ffff,
+            ],
+            h: [0; 5],
+            pad: [le32(&key[16..]), le32(&key[20..]), le32(&key[24..]), le32(&key[28..])],
+            buf: [0; 16],
+            buf_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        if self.buf_len > 0 {
+            let take = (16 - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + take].copy_from_slice(&data[..take]);
+            self.buf_len += take;
+            data = &data[take..];
+            if self.buf_len < 16 {
+                return;
+            }
+            let block = self.buf;
+            self.block(&block, 1 << 24);
+            self.buf_len = 0;
+        }
+        let mut blocks = data.chunks_exact(16);
+        for block in &mut blocks {
+            self.block(block.try_into().unwrap(), 1 << 24);
+        }
+        let rest = blocks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    /// Absorb zeros up to the next 16-byte boundary, as the AEAD
+    /// construction pads each part
+    pub fn pad16(&mut self) {
+        if self.buf_len > 0 {
+            self.update(&[0; 16][self.buf_len..]);
+        }
+    }
+
+    fn block(&mut self, m: &[u8; 16], hibit: u32) {
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+
+        let h0 = (self.h[0] + (le32(&m[0..]) & MASK)) as u64;
+        let h1 = (self.h[1] + ((le32(&m[3..]) >> 2) & MASK)) as u64;
+        let h2 = (self.h[2] + ((le32(&m[6..]) >> 4) & MASK)) as u64;
+        let h3 = (self.h[3] + ((le32(&m[9..]) >> 6) & MASK)) as u64;
+        let h4 = (self.h[4] + ((le32(&m[12..]) >> 8) | hibit)) as u64;
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        let mut c = d0 >> 26;
+        let mut h = [d0 as u32 & MASK, 0, 0, 0, 0];
+        d1 += c;
+        c = d1 >> 26;
+        h[1] = d1 as u32 & MASK;
+        d2 += c;
+        c = d2 >> 26;
+        h[2] = d2 as u32 & MASK;
+        d3 += c;
+        c = d3 >> 26;
+        h[3] = d3 as u32 & MASK;
+        d4 += c;
+        c = d4 >> 26;
+        h[4] = d4 as u32 & MASK;
+        h[0] += c as u32 * 5;
+        let c = h[0] >> 26;
+        h[0] &= MASK;
+        h[1] += c;
+        self.h = h;
+    }
+
+    pub fn finalize(mut self) -> [u8; TAG_SIZE] {
+        if self.buf_len > 0 {
+            let mut block = [0u8; 16];
+            block[..self.buf_len].copy_from_slice(&self.buf[..self.buf_len]);
+            block[self.buf_len] = 1;
+            self.block(&block, 0);
+        }
+
+        // Fully carry h
+        let mut h = self.h;
+        let mut c;
+        c = h[1] >> 26;
+        h[1] &= MASK;
+        h[2] += c;
+        c = h[2] >> 26;
+        h[2] &= MASK;
+        h[3] += c;
+        c = h[3] >> 26;
+        h[3] &= MASK;
+        h[4] += c;
+        c = h[4] >> 26;
+        h[4] &= MASK;
+        h[0] += c * 5;
+        c = h[0] >> 26;
+        h[0] &= MASK;
+        h[1] += c;
+
+        // g = h + 5 - 2^130; take it if it did not go negative
+        let mut g = [0u32; 5];
+        g[0] = h[0] + 5;
+        c = g[0] >> 26;
+        g[0] &= MASK;
+        g[1] = h[1] + c;
+        c = g[1] >> 26;
+        g[1] &= MASK;
+        g[2] = h[2] + c;
+        c = g[2] >> 26;
+        g[2] &= MASK;
+        g[3] = h[3] + c;
+        c = g[3] >> 26;
+        g[3] &= MASK;
+        g[4] = (h[4] + c).wrapping_sub(1 << 26);
+
+        let select = (g[4] >> 31).wrapping_sub(1);
+        for (h, g) in h.iter_mut().zip(g) {
+            *h = (*h & !select) | (g & select);
+        }
+
+        // h mod 2^128, plus the pad
+        let words = [
+            h[0] | (h[1] << 26),
+            (h[1] >> 6) | (h[2] << 20),
+            (h[2] >> 12) | (h[3] << 14),
+            (h[3] >> 18) | (h[4] << 8),
+        ];
+        let mut tag = [0u8; TAG_SIZE];
+        let mut carry = 0u64;
+        for (i, (word, pad)) in words.iter().zip(self.pad).enumerate() {
+            let sum = *word as u64 + pad as u64 + carry;
+            tag[4 * i..4 * i + 4].copy_from_slice(&(sum as u32).to_le_bytes());
+            carry = sum >> 32;
+        }
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::crypto::hex;
+
+    #[test]
+    fn test_poly1305_vector() {
+        // RFC 8439 2.5.2
+        let key: [u8; 32] = hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b")
+            .try_into()
+            .unwrap();
+        let mut mac = Poly1305::new(&key);
+        mac.update(b"Cryptographic Forum Research Group");
+        assert_eq!(mac.finalize().to_vec(), hex("a8061dc1305136c6c22b8baf0c0127a9"));
+    }
+
+    #[test]
+    fn test_poly1305_reduction_vectors() {
+        // RFC 8439 A.3 #5 and #6 exercise the final reduction mod 2^130 - 5
+        let mut key = [0u8; 32];
+        key[0] = 2;
+        let mut mac = Poly1305::new(&key);
+        mac.update(&[0xff; 16]);
+        assert_eq!(mac.finalize().to_vec(), hex("03000000000000000000000000000000"));
+
+        key[16..].fill(0xff);
+        let mut mac = Poly1305::new(&key);
+        mac.update(&hex("02000000000000000000000000000000"));
+        assert_eq!(mac.finalize().to_vec(), hex("03000000000000000000000000000000"));
+    }
+}
@@ -0,0 +1,130 @@
+//! Kernel random number generator for keys and nonces
+//!
+//! A ChaCha20 generator with fast key erasure: every request first draws a
+//! fresh key from the keystream, so earlier output cannot be recovered
+//! from a later state. It seeds itself on first use from RDSEED/RDRAND
+//! where present plus timer jitter, and [`add_entropy`] mixes in more.
+
+use crate::subsystems::sync::Mutex;
+
+use super::chacha20;
+use super::sha2::Sha256;
+use super::zeroize;
+
+struct Generator {
+    key: [u8; chacha20::KEY_SIZE],
+    seeded: bool,
+}
+
+static GENERATOR: Mutex<Generator> = Mutex::new(Generator { key: [0; chacha20::KEY_SIZE], seeded: false });
+
+impl Generator {
+    fn mix(&mut self, input: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.key);
+        hasher.update(input);
+        self.key = hasher.finalize();
+    }
+
+    fn seed(&mut self) {
+        let mut pool = [0u8; 256];
+        for (i, word) in pool.chunks_exact_mut(8).enumerate() {
+            let sample = hardware_random().unwrap_or(0) ^ cycle_counter().rotate_left(i as u32);
+            word.copy_from_slice(&sample.to_le_bytes());
+        }
+        self.mix(&pool);
+        self.mix(&crate::subsystems::time::timestamp_nanos().to_le_bytes());
+        self.seeded = true;
+    }
+
+    fn fill(&mut self, out: &mut [u8]) {
+        if !self.seeded {
+            self.seed();
+        }
+        let nonce = [0u8; chacha20::NONCE_SIZE];
+        let mut next = chacha20::block(&self.key, 0, &nonce);
+        out.fill(0);
+        chacha20::apply_keystream(&self.key, 1, &nonce, out);
+        self.key.copy_from_slice(&next[..chacha20::KEY_SIZE]);
+        zeroize(&mut next);
+    }
+}
+
+/// Fill `out` with random bytes
+pub fn fill(out: &mut [u8]) {
+    GENERATOR.lock().fill(out);
+}
+
+/// Mix caller-supplied entropy (device timings, a saved seed) into the pool
+pub fn add_entropy(data: &[u8]) {
+    GENERATOR.lock().mix(data);
+}
+
+#[cfg(target_arch = "x86_64")]
+fn hardware_random() -> Option<u64> {
+    #[target_feature(enable = "rdrand")]
+    unsafe fn rdrand() -> Option<u64> {
+        let mut value = 0;
+        // The DRNG may briefly run dry; Intel suggests ten retries
+        for _ in 0..10 {
+            if core::arch::x86_64::_rdrand64_step(&mut value) == 1 {
+                return Some(value);
+            }
+        }
+        None
+    }
+    // CPUID.1:ECX.RDRAND[bit 30]
+    let leaf1 = core::arch::x86_64::__cpuid(1);
+    if leaf1.ecx & (1 << 30) == 0 {
+        return None;
+    }
+    unsafe { rdrand() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn hardware_random() -> Option<u64> {
+    None
+}
+
+fn cycle_counter() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    return unsafe { core::arch::x86_64::_rdtsc() };
+    #[cfg(target_arch = "aarch64")]
+    {
+        let count: u64;
+        unsafe { core::arch::asm!("mrs {}, cntvct_el0", out(reg) count, options(nomem, nostack)) };
+        return count;
+    }
+    #[cfg(target_arch = "riscv64")]
+    {
+        let count: u64;
+        unsafe { core::arch::asm!("rdtime {}", out(reg) count, options(nomem, nostack)) };
+        return count;
+    }
+    #[allow(unreachable_code)]
+    crate::subsystems::time::timestamp_nanos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_differs() {
+        let mut a = [0u8; 48];
+        let mut b = [0u8; 48];
+        fill(&mut a);
+        fill(&mut b);
+        assert_ne!(a, b);
+        assert_ne!(a, [0; 48]);
+    }
+
+    #[test]
+    fn test_key_erasure() {
+        // The key moves on after every request
+        let mut generator = Generator { key: [1; 32], seeded: true };
+        let before = generator.key;
+        generator.fill(&mut [0; 16]);
+        assert_ne!(generator.key, before);
+    }
+}
@@ -17,7 +17,7 @@ use crate::subsystems::drivers::{
     DeviceId, IoOperation, IoResult, InterruptInfo, DeviceResources,
     MemoryRegion, IoPortRange, InterruptLine, DmaChannel
 };
-use crate::platform::drivers::BlockDevice;
+use crate::subsystems::block::{self, Bio, BlockResult, ReqFlags, RequestQueue};
 use nos_nos_error_handling::unified::KernelError;
 
 // ============================================================================
@@ -333,12 +333,17 @@ pub enum DiskIoQueueType {
 // Disk I/O Driver Implementation
 // ============================================================================
 
+/// Request id, result and buffer of a finished block layer request
+type Completion = (u64, BlockResult<()>, Vec<u8>);
+
 /// Disk I/O driver
 pub struct DiskIoDriver {
     /// Driver information
     driver_info: DriverInfo,
-    /// Block device
-    block_device: Option<Box<dyn BlockDevice>>,
+    /// Request queue of the disk
+    queue: Option<Arc<RequestQueue>>,
+    /// Requests the block layer has finished, waiting to be reaped
+    completions: Arc<Mutex<Vec<Completion>>>,
     /// Disk device information
     disk_info: Option<DiskDeviceInfo>,
     /// I/O queue configuration
@@ -413,7 +418,8 @@ impl DiskIoDriver {
 
         Self {
             driver_info,
-            block_device: None,
+            queue: None,
+            completions: Arc::new(Mutex::new(Vec::new())),
             disk_info: None,
             queue_config,
             pending_requests: Mutex::new(BTreeMap::new()),
@@ -457,9 +463,10 @@ impl DiskIoDriver {
         }
     }
 
-    /// Set block device
-    pub fn set_block_device(&mut self, device: Box<dyn BlockDevice>) {
-        self.block_device = Some(device);
+    /// Set the request queue of the disk this driver serves
+    pub fn set_block_queue(&mut self, queue: Arc<RequestQueue>) {
+        queue.set_depth(self.queue_config.queue_depth as usize);
+        self.queue = Some(queue);
     }
 
     /// Get disk device information
@@ -545,6 +552,8 @@ impl DiskIoDriver {
 
     /// Get request status
     pub fn get_request_status(&self, request_id: u64) -> Result<DiskIoStatus, KernelError> {
+        self.reap_completions();
+
         // Check pending requests
         {
             let pending = self.pending_requests.lock();
@@ -566,6 +575,7 @@ impl DiskIoDriver {
 
     /// Get completed request
     pub fn get_completed_request(&self, request_id: u64) -> Result<DiskIoRequest, KernelError> {
+        self.reap_completions();
         let mut completed = self.completed_requests.lock();
         if let Some(request) = completed.remove(&request_id) {
             Ok(request)
@@ -619,6 +629,8 @@ impl DiskIoDriver {
 
     /// Perform maintenance
     pub fn perform_maintenance(&self) {
+        self.reap_completions();
+
         let current_time = self.get_current_time();
         let last_maintenance = self.last_maintenance_time.load(Ordering::SeqCst);
         
@@ -636,7 +648,9 @@ impl DiskIoDriver {
         }
     }
 
-    /// Process I/O request
+    /// Hand a pending request to the block layer
+    ///
+    /// The request stays pending until its completion is reaped.
     fn process_request(&self, request_id: u64) {
         let request = {
             let mut pending = self.pending_requests.lock();
@@ -647,148 +661,84 @@ impl DiskIoDriver {
                 None
             }
         };
+        let Some(request) = request else {
+            return;
+        };
 
-        if let Some(request) = request {
-            let start_time = self.get_current_time();
-            
-            // Execute I/O operation
-            let result = match request.io_type {
-                DiskIoType::Read => self.execute_read(&request),
-                DiskIoType::Write => self.execute_write(&request),
-                DiskIoType::Flush => self.execute_flush(&request),
-                DiskIoType::Trim => self.execute_trim(&request),
-                DiskIoType::Verify => self.execute_verify(&request),
-                DiskIoType::SecureErase => self.execute_secure_erase(&request),
-                DiskIoType::WriteZeros => self.execute_write_zeros(&request),
-                DiskIoType::WriteSame => self.execute_write_same(&request),
-                DiskIoType::CompareAndWrite => self.execute_compare_and_write(&request),
-                DiskIoType::AtomicReadWrite => self.execute_atomic_read_write(&request),
-            };
-
-            let end_time = self.get_current_time();
-            let latency = end_time - start_time;
-
-            // Update request
-            let mut completed_request = request.clone();
-            match result {
-                Ok(_) => {
-                    completed_request.status = DiskIoStatus::Completed;
-                }
-                Err(e) => {
-                    completed_request.status = DiskIoStatus::Failed;
-                    completed_request.error_code = e as u32;
-                    completed_request.error_message = Some(format!("I/O error: {:?}", e));
-                }
+        let bio = match self.queue.as_ref() {
+            Some(queue) => Self::request_bio(queue, &request).map(|bio| (queue, bio)),
+            None => Err(KernelError::InvalidState),
+        };
+        match bio {
+            Ok((queue, bio)) => {
+                let completions = self.completions.clone();
+                queue.submit_bio(bio.on_complete(move |result, data| {
+                    completions.lock().push((request_id, result, data));
+                }));
             }
-
-            // Move to completed requests
-            {
-                let mut pending = self.pending_requests.lock();
-                pending.remove(&request_id);
-                
-                let mut completed = self.completed_requests.lock();
-                completed.insert(request_id, completed_request);
+            Err(e) => {
+                self.finish_request(request_id, Err(e), Vec::new());
             }
-
-            // Update queue depth
-            self.current_queue_depth.fetch_sub(1, Ordering::SeqCst);
-
-            // Update statistics
-            self.update_operation_stats(&request, latency, result.is_ok());
         }
     }
 
-    /// Execute read operation
-    fn execute_read(&self, request: &DiskIoRequest) -> Result<(), KernelError> {
-        if let Some(ref block_device) = self.block_device {
-            let sector_size = block_device.block_size();
-            let mut buffer = vec![0u8; request.sector_count as usize * sector_size];
-            
-            for i in 0..request.sector_count {
-                let lba = request.lba + i as u64;
-                let offset = i as usize * sector_size;
-                block_device.read(lba as usize, &mut buffer[offset..offset + sector_size]);
-            }
-            
-            // In a real implementation, we would copy the data to the request buffer
-            // request.data = buffer;
-            
-            Ok(())
-        } else {
-            Err(KernelError::InvalidState)
-        }
-    }
-
-    /// Execute write operation
-    fn execute_write(&self, request: &DiskIoRequest) -> Result<(), KernelError> {
-        if let Some(ref block_device) = self.block_device {
-            let sector_size = block_device.block_size();
-            
-            for i in 0..request.sector_count {
-                let lba = request.lba + i as u64;
-                let offset = i as usize * sector_size;
-                
-                if offset + sector_size <= request.data.len() {
-                    block_device.write(lba as usize, &request.data[offset..offset + sector_size]);
+    /// Translate a request into a bio
+    fn request_bio(queue: &RequestQueue, request: &DiskIoRequest) -> Result<Bio, KernelError> {
+        let bytes = request.sector_count as usize * queue.sector_size();
+        let bio = match request.io_type {
+            DiskIoType::Read | DiskIoType::Verify => Bio::read(request.lba, bytes),
+            DiskIoType::Write => {
+                if request.data.len() != bytes {
+                    return Err(KernelError::InvalidArgument);
                 }
+                Bio::write(request.lba, request.data.clone())
             }
-            
-            Ok(())
-        } else {
-            Err(KernelError::InvalidState)
-        }
+            DiskIoType::WriteZeros => Bio::write(request.lba, vec![0u8; bytes]),
+            DiskIoType::Flush => Bio::flush(),
+            DiskIoType::Trim => Bio::discard(request.lba, request.sector_count),
+            DiskIoType::SecureErase
+            | DiskIoType::WriteSame
+            | DiskIoType::CompareAndWrite
+            | DiskIoType::AtomicReadWrite => return Err(KernelError::NotSupported),
+        };
+        Ok(match request.priority {
+            DiskIoPriority::High | DiskIoPriority::Critical => bio.with_flags(ReqFlags::SYNC),
+            _ => bio,
+        })
     }
 
-    /// Execute flush operation
-    fn execute_flush(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        if let Some(ref block_device) = self.block_device {
-            block_device.flush();
-            Ok(())
-        } else {
-            Err(KernelError::InvalidState)
+    /// Move requests the block layer has finished to the completed set
+    pub fn reap_completions(&self) {
+        let done = core::mem::take(&mut *self.completions.lock());
+        for (request_id, result, data) in done {
+            self.finish_request(request_id, result.map_err(KernelError::from), data);
         }
     }
 
-    /// Execute trim operation
-    fn execute_trim(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        // In a real implementation, this would issue a TRIM command
-        Ok(())
-    }
-
-    /// Execute verify operation
-    fn execute_verify(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        // In a real implementation, this would verify the data
-        Ok(())
-    }
-
-    /// Execute secure erase operation
-    fn execute_secure_erase(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        // In a real implementation, this would securely erase the data
-        Ok(())
-    }
-
-    /// Execute write zeros operation
-    fn execute_write_zeros(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        // In a real implementation, this would write zeros to the specified range
-        Ok(())
-    }
-
-    /// Execute write same operation
-    fn execute_write_same(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        // In a real implementation, this would write the same data to multiple sectors
-        Ok(())
-    }
+    fn finish_request(&self, request_id: u64, result: Result<(), KernelError>, data: Vec<u8>) {
+        // Cancelled or timed out in the meantime
+        let Some(mut request) = self.pending_requests.lock().remove(&request_id) else {
+            return;
+        };
+        let latency = self.get_current_time().saturating_sub(request.timestamp);
 
-    /// Execute compare and write operation
-    fn execute_compare_and_write(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        // In a real implementation, this would compare and then write the data
-        Ok(())
-    }
+        match result {
+            Ok(()) => {
+                request.status = DiskIoStatus::Completed;
+                if matches!(request.io_type, DiskIoType::Read) {
+                    request.data = data;
+                }
+            }
+            Err(e) => {
+                request.status = DiskIoStatus::Failed;
+                request.error_code = e as u32;
+                request.error_message = Some(format!("I/O error: {:?}", e));
+            }
+        }
 
-    /// Execute atomic read write operation
-    fn execute_atomic_read_write(&self, _request: &DiskIoRequest) -> Result<(), KernelError> {
-        // In a real implementation, this would perform an atomic read-modify-write operation
-        Ok(())
+        self.current_queue_depth.fetch_sub(1, Ordering::SeqCst);
+        self.update_operation_stats(&request, latency, result.is_ok());
+        self.completed_requests.lock().insert(request_id, request);
     }
 
     /// Check for timed out requests
@@ -954,6 +904,13 @@ impl DiskIoDriver {
         }
     }
 
+    /// Sector size of the attached disk
+    fn sector_size(&self) -> u64 {
+        self.queue
+            .as_ref()
+            .map_or(DEFAULT_SECTOR_SIZE as u64, |queue| queue.sector_size() as u64)
+    }
+
     /// Get current time in microseconds
     fn get_current_time(&self) -> u64 {
         // In a real implementation, this would get the current time
@@ -972,26 +929,33 @@ impl Driver for DiskIoDriver {
             return Ok(());
         }
 
-        // Initialize block device if available
-        if let Some(ref block_device) = self.block_device {
-            // Create disk device information
+        // Describe the disk behind the request queue, if one is attached
+        if let Some(ref queue) = self.queue {
+            let features = queue.features();
+            let mut supported_features = vec![
+                "read".to_string(),
+                "write".to_string(),
+                "flush".to_string(),
+            ];
+            if features.fua {
+                supported_features.push("fua".to_string());
+            }
+            if features.discard {
+                supported_features.push("discard".to_string());
+            }
             let disk_info = DiskDeviceInfo {
                 device_id: 0, // Will be set by driver manager
-                name: "Disk Device".to_string(),
+                name: queue.name().to_string(),
                 device_type: DiskDeviceType::Virtual,
                 model: "Virtual Disk".to_string(),
                 serial_number: "VDISK-123456".to_string(),
                 firmware_version: "1.0.0".to_string(),
-                capacity: (block_device.num_blocks() * block_device.block_size()) as u64,
-                sector_size: block_device.block_size() as u32,
-                sector_count: block_device.num_blocks() as u64,
-                max_transfer_size: 1024 * 1024, // 1MB
+                capacity: queue.nr_sectors() * queue.sector_size() as u64,
+                sector_size: queue.sector_size() as u32,
+                sector_count: queue.nr_sectors(),
+                max_transfer_size: features.max_sectors * queue.sector_size() as u32,
                 max_queue_depth: self.queue_config.queue_depth,
-                supported_features: vec![
-                    "read".to_string(),
-                    "write".to_string(),
-                    "flush".to_string(),
-                ],
+                supported_features,
                 status: DiskDeviceStatus::Ready,
                 temperature: 25, // 25°C
                 power_on_hours: 0,
@@ -1079,9 +1043,10 @@ impl Driver for DiskIoDriver {
     fn handle_io(&mut self, device_id: DeviceId, operation: IoOperation) -> Result<IoResult, KernelError> {
         match operation {
             IoOperation::Read { offset, size } => {
-                let sector_size = DEFAULT_SECTOR_SIZE;
-                let lba = offset / sector_size as u64;
-                let sector_count = (size + sector_size as u64 - 1) / sector_size as u64;
+                let sector_size = self.sector_size();
+                let lba = offset / sector_size;
+                let skip = (offset % sector_size) as usize;
+                let sector_count = (offset + size).div_ceil(sector_size) - lba;
                 
                 let request = DiskIoRequest {
                     id: 0,
@@ -1090,7 +1055,7 @@ impl Driver for DiskIoDriver {
                     priority: DiskIoPriority::Normal,
                     lba,
                     sector_count: sector_count as u32,
-                    data: vec![0u8; size as usize],
+                    data: Vec::new(),
                     timestamp: 0,
                     timeout: DEFAULT_IO_TIMEOUT,
                     retry_count: 0,
@@ -1109,7 +1074,7 @@ impl Driver for DiskIoDriver {
                         DiskIoStatus::Completed => {
                             let completed_request = self.get_completed_request(request_id)?;
                             return Ok(IoResult::ReadResult {
-                                data: completed_request.data,
+                                data: completed_request.data[skip..skip + size as usize].to_vec(),
                                 bytes_read: size,
                             });
                         }
@@ -1223,6 +1188,9 @@ impl Driver for DiskIoDriver {
             "queue_depth" => {
                 if let Ok(depth) = value.parse::<u32>() {
                     self.queue_config.queue_depth = depth;
+                    if let Some(ref queue) = self.queue {
+                        queue.set_depth(depth as usize);
+                    }
                     Ok(())
                 } else {
                     Err(KernelError::InvalidArgument)
@@ -1252,6 +1220,16 @@ impl Driver for DiskIoDriver {
                     Err(KernelError::InvalidArgument)
                 }
             }
+            "scheduler" => {
+                let queue = self.queue.as_ref().ok_or(KernelError::InvalidState)?;
+                let sched = block::scheduler_by_name(value).ok_or(KernelError::InvalidArgument)?;
+                queue.set_scheduler(sched);
+                self.queue_config.queue_type = match value {
+                    "deadline" | "mq-deadline" => DiskIoQueueType::Deadline,
+                    _ => DiskIoQueueType::Noop,
+                };
+                Ok(())
+            }
             _ => Err(KernelError::InvalidArgument),
         }
     }
@@ -1269,6 +1247,11 @@ impl Driver for DiskIoDriver {
             "enable_reordering" => Ok(self.queue_config.enable_reordering.to_string()),
             "queue_type" => Ok(format!("{:?}", self.queue_config.queue_type)),
             "priority" => Ok(format!("{:?}", self.queue_config.priority)),
+            "scheduler" => self
+                .queue
+                .as_ref()
+                .map(|queue| queue.scheduler_name().to_string())
+                .ok_or(KernelError::InvalidState),
             _ => Err(KernelError::InvalidArgument),
         }
     }
@@ -1293,6 +1276,7 @@ impl Driver for DiskIoDriver {
 
     fn handle_interrupt(&mut self, _device_id: DeviceId, _interrupt_info: &InterruptInfo) -> Result<(), KernelError> {
         // Handle I/O completion interrupt
+        self.reap_completions();
         self.perform_maintenance();
         
         Ok(())
@@ -1319,9 +1303,12 @@ pub fn init() {
                 }
             }
             IoOperation::Write { offset, data } => {
-                let sector_size = DEFAULT_SECTOR_SIZE;
-                let lba = offset / sector_size as u64;
-                let sector_count = (data.len() + sector_size as usize - 1) / sector_size as usize;
+                let sector_size = self.sector_size();
+                if !offset.is_multiple_of(sector_size) || !(data.len() as u64).is_multiple_of(sector_size) {
+                    return Err(KernelError::InvalidArgument);
+                }
+                let lba = offset / sector_size;
+                let sector_count = data.len() as u64 / sector_size;
                 
                 let request = DiskIoRequest {
                     id: 0,
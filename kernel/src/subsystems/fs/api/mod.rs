@@ -490,16 +490,8 @@ pub mod attr_ops {
             return Err(FsError::PermissionDenied);
         }
         
-        // Update file permissions (preserve file type bits)
-        let file_mode = vfs::FileMode::new((attr.mode.0 & 0o170000) | (mode & 0o7777));
-        let mut new_attr = attr;
-        new_attr.mode = file_mode;
-        
-        // Open file and update attributes
-        let vfs_file = vfs.open(path, crate::posix::O_RDWR as u32)
-            .map_err(|_| FsError::PermissionDenied)?;
-        
-        vfs_file.set_attr(&new_attr)
+        // Update file permissions (setattr preserves file type bits)
+        vfs.chmod(path, mode & 0o7777)
             .map_err(|_| FsError::IoError)?;
         
         Ok(())
@@ -528,25 +520,14 @@ pub mod attr_ops {
             return Err(FsError::PermissionDenied);
         }
         
-        // Get file attributes
-        let vfs = vfs::vfs();
-        let mut attr = vfs.stat(path)
-            .map_err(|_| FsError::NotFound)?;
-        
         // Update ownership (u32::MAX means don't change)
-        if uid != u32::MAX {
-            attr.uid = uid;
-        }
-        if gid != u32::MAX {
-            attr.gid = gid;
-        }
-        
-        // Open file and update attributes
-        let vfs_file = vfs.open(path, crate::posix::O_RDWR as u32)
-            .map_err(|_| FsError::PermissionDenied)?;
-        
-        vfs_file.set_attr(&attr)
-            .map_err(|_| FsError::IoError)?;
+        let uid = (uid != u32::MAX).then_some(uid);
+        let gid = (gid != u32::MAX).then_some(gid);
+        vfs::vfs().chown(path, uid, gid)
+            .map_err(|e| match e {
+                vfs::VfsError::NotFound => FsError::NotFound,
+                _ => FsError::IoError,
+            })?;
         
         Ok(())
     }
@@ -567,20 +548,10 @@ pub mod path_ops {
     /// * 返回绝对路径
     /// * 不暴露系统内部路径结构
     pub fn getcwd() -> Result<alloc::string::String, FsError> {
-        // Get current process
-        let pid = crate::process::myproc()
-            .ok_or(FsError::NotFound)?;
-        
-        let proc_table = crate::process::manager::PROC_TABLE.lock();
-        let proc = proc_table.find_ref(pid)
-            .ok_or(FsError::NotFound)?;
-        
-        // Get current working directory path
-        let cwd = proc.cwd_path.clone()
-            .unwrap_or_else(|| alloc::string::String::from("/"));
-        
-        drop(proc_table);
-        Ok(cwd)
+        // The path is reported relative to the process root (chroot)
+        let ctx = crate::process::fs_context()
+            .map_err(|_| FsError::NotFound)?;
+        Ok(ctx.display_path(&ctx.cwd))
     }
     
     /// 更改当前工作目录
@@ -603,38 +574,15 @@ pub mod path_ops {
             return Err(FsError::IoError);
         }
         
-        // Resolve absolute path
-        let abs_path = if path.starts_with('/') {
-            path.to_string()
-        } else {
-            // Get current working directory
-            let pid = crate::process::myproc()
-                .ok_or(FsError::NotFound)?;
-            let proc_table = crate::process::manager::PROC_TABLE.lock();
-            let proc = proc_table.find_ref(pid)
-                .ok_or(FsError::NotFound)?;
-            let cwd_path = proc.cwd_path.clone();
-            drop(proc_table);
-            
-            if let Some(ref cwd) = cwd_path {
-                format!("{}/{}", cwd, path)
-            } else {
-                format!("/{}", path)
-            }
-        };
-        
-        // Normalize path (remove . and .. components)
-        let normalized_path = super::normalize_path(&abs_path);
-        
-        // Verify that the path exists and is a directory
-        let vfs = vfs::vfs();
-        let attr = vfs.stat(&normalized_path)
-            .map_err(|_| FsError::NotFound)?;
-        
-        // Check if it's a directory
-        if !attr.mode.is_dir() {
-            return Err(FsError::NotADirectory);
-        }
+        // Resolve relative to the process root and cwd, following symlinks
+        let ctx = crate::process::fs_context()
+            .map_err(|_| FsError::IoError)?;
+        let flags = vfs::LookupFlags(vfs::LookupFlags::FOLLOW | vfs::LookupFlags::DIRECTORY);
+        let loc = ctx.lookup(None, path, flags)
+            .map_err(|e| match e {
+                vfs::VfsError::NotDirectory => FsError::NotADirectory,
+                _ => FsError::NotFound,
+            })?;
         
         // Update process's current working directory
         let pid = crate::process::myproc()
@@ -643,7 +591,7 @@ pub mod path_ops {
         let proc = proc_table.find_mut(pid)
             .ok_or(FsError::NotFound)?;
         
-        proc.cwd_path = Some(normalized_path);
+        proc.cwd_path = Some(loc.path);
         drop(proc_table);
         
         Ok(())
@@ -21,6 +21,7 @@ use core::sync::atomic::{AtomicU64, AtomicU32, Ordering};
 use spin::Mutex;
 
 use crate::time;
+use crate::subsystems::block::{self, RequestQueue};
 use super::api::{
     FileHandle, DirEntry, PathComponent, FsError,
     FileOperations, DirectoryOperations, PathOperations
@@ -290,6 +291,8 @@ pub struct Ext2DirEntry {
 pub struct Ext2FileSystem {
     /// Device identifier
     pub device_id: String,
+    /// Request queue of the device
+    pub dev: Arc<RequestQueue>,
     /// Superblock
     pub superblock: Ext2Superblock,
     /// Block group descriptors
@@ -534,6 +537,14 @@ impl DirectoryOperations for Ext2OpenDir {
     }
 }
 
+fn le16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([buf[off], buf[off + 1]])
+}
+
+fn le32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([buf[off], buf[off + 1], buf[off + 2], buf[off + 3]])
+}
+
 /// Ext2 file system statistics
 #[derive(Debug, Default)]
 pub struct Ext2Stats {
@@ -558,20 +569,21 @@ pub struct Ext2Stats {
 }
 
 impl Ext2FileSystem {
-    /// Create a new ext2 file system
+    /// Create a new ext2 file system on the registered block device `device_id`
     pub fn new(device_id: String, mount_options: Ext2MountOptions) -> Result<Self, FsError> {
-        // In a real implementation, this would read the superblock from the device
-        let superblock = Self::read_superblock(&device_id)?;
-        
+        let dev = block::get_disk(&device_id).ok_or(FsError::PathNotFound)?;
+        let superblock = Self::read_superblock(&dev)?;
+
         if !superblock.is_valid() {
             return Err(FsError::CorruptedFileSystem);
         }
 
         // Read block group descriptors
-        let block_groups = Self::read_block_group_descriptors(&device_id, &superblock)?;
+        let block_groups = Self::read_block_group_descriptors(&dev, &superblock)?;
 
         Ok(Self {
             device_id,
+            dev,
             superblock,
             block_groups,
             inode_cache: Mutex::new(BTreeMap::new()),
@@ -584,81 +596,114 @@ impl Ext2FileSystem {
         })
     }
 
-    /// Read superblock from device
-    fn read_superblock(device_id: &str) -> Result<Ext2Superblock, FsError> {
-        // In a real implementation, this would read from the actual device
-        // For now, return a default superblock
-        Ok(Ext2Superblock {
-            inodes_count: 32768,
-            blocks_count: 131072,
-            r_blocks_count: 655,
-            free_blocks_count: 130417,
-            free_inodes_count: 32767,
-            first_data_block: 1,
-            log_block_size: 2, // 4KB blocks
-            log_frag_size: 0,
-            blocks_per_group: 32768,
-            frags_per_group: 32768,
-            inodes_per_group: 32768,
-            mtime: 0,
-            wtime: 0,
-            mnt_count: 0,
-            max_mnt_count: 30,
-            magic: 0xEF53,
-            state: 1,
-            errors: 1,
-            minor_rev_level: 0,
-            lastcheck: 0,
-            checkinterval: 0,
-            creator_os: 0,
-            rev_level: 1,
-            def_resuid: 0,
-            def_resgid: 0,
-            first_ino: 11,
-            inode_size: 128,
-            block_group_nr: 0,
-            feature_compat: 0x00000028, // EXT2_FEATURE_COMPAT_EXT_ATTR | EXT2_FEATURE_COMPAT_DIR_INDEX
-            feature_incompat: 0x00000000,
-            feature_ro_compat: 0x00000000,
+    /// Read superblock from device (1024 bytes at byte offset 1024)
+    fn read_superblock(dev: &RequestQueue) -> Result<Ext2Superblock, FsError> {
+        let mut buf = [0u8; 1024];
+        dev.read_at(1024, &mut buf).map_err(|_| FsError::IoError)?;
+
+        let mut sb = Ext2Superblock {
+            inodes_count: le32(&buf, 0),
+            blocks_count: le32(&buf, 4),
+            r_blocks_count: le32(&buf, 8),
+            free_blocks_count: le32(&buf, 12),
+            free_inodes_count: le32(&buf, 16),
+            first_data_block: le32(&buf, 20),
+            log_block_size: le32(&buf, 24),
+            log_frag_size: le32(&buf, 28),
+            blocks_per_group: le32(&buf, 32),
+            frags_per_group: le32(&buf, 36),
+            inodes_per_group: le32(&buf, 40),
+            mtime: le32(&buf, 44),
+            wtime: le32(&buf, 48),
+            mnt_count: le16(&buf, 52),
+            max_mnt_count: le16(&buf, 54),
+            magic: le16(&buf, 56),
+            state: le16(&buf, 58),
+            errors: le16(&buf, 60),
+            minor_rev_level: le16(&buf, 62),
+            lastcheck: le32(&buf, 64),
+            checkinterval: le32(&buf, 68),
+            creator_os: le32(&buf, 72),
+            rev_level: le32(&buf, 76),
+            def_resuid: le16(&buf, 80),
+            def_resgid: le16(&buf, 82),
+            first_ino: le32(&buf, 84),
+            inode_size: le16(&buf, 88),
+            block_group_nr: le16(&buf, 90),
+            feature_compat: le32(&buf, 92),
+            feature_incompat: le32(&buf, 96),
+            feature_ro_compat: le32(&buf, 100),
             uuid: [0; 16],
             volume_name: [0; 16],
             last_mounted: [0; 64],
-            algo_bitmap: 0,
-            prealloc_blocks: 0,
-            prealloc_dir_blocks: 0,
+            algo_bitmap: le32(&buf, 200),
+            prealloc_blocks: buf[204],
+            prealloc_dir_blocks: buf[205],
             journal_uuid: [0; 16],
-            journal_inum: 0,
-            journal_dev: 0,
-            last_orphan: 0,
-            hash_seed: [0; 4],
-            def_hash_version: 0,
-            jnl_backup_type: 0,
+            journal_inum: le32(&buf, 224),
+            journal_dev: le32(&buf, 228),
+            last_orphan: le32(&buf, 232),
+            hash_seed: [le32(&buf, 236), le32(&buf, 240), le32(&buf, 244), le32(&buf, 248)],
+            def_hash_version: buf[252],
+            jnl_backup_type: buf[253],
             padding16: 0,
-            desc_size: 32,
-            default_mount_opts: 0,
-            first_meta_bg: 0,
-            mkfs_reserved: 0,
-        })
+            desc_size: le16(&buf, 254) as u32,
+            default_mount_opts: le32(&buf, 256),
+            first_meta_bg: le32(&buf, 260),
+            mkfs_reserved: le32(&buf, 264),
+        };
+        sb.uuid.copy_from_slice(&buf[104..120]);
+        sb.volume_name.copy_from_slice(&buf[120..136]);
+        sb.last_mounted.copy_from_slice(&buf[136..200]);
+        sb.journal_uuid.copy_from_slice(&buf[208..224]);
+
+        // Revision 0 has fixed inode size and first inode
+        if sb.rev_level == 0 {
+            sb.inode_size = 128;
+            sb.first_ino = 11;
+        }
+        if sb.blocks_per_group == 0 || sb.inodes_per_group == 0 || sb.log_block_size > 6 {
+            return Err(FsError::CorruptedFileSystem);
+        }
+        Ok(sb)
     }
 
     /// Read block group descriptors from device
-    fn read_block_group_descriptors(device_id: &str, superblock: &Ext2Superblock) -> Result<Vec<Ext2BlockGroupDesc>, FsError> {
-        let group_count = superblock.block_group_count();
-        let mut groups = Vec::with_capacity(group_count as usize);
-
-        for i in 0..group_count {
-            // In a real implementation, this would read from the actual device
-            groups.push(Ext2BlockGroupDesc {
-                block_bitmap: 0,
-                inode_bitmap: 0,
-                inode_table: 0,
-                free_blocks_count: 32768,
-                free_inodes_count: 32768,
-                used_dirs_count: 2,
-            });
-        }
+    ///
+    /// The descriptor table starts in the block after the superblock.
+    fn read_block_group_descriptors(dev: &RequestQueue, superblock: &Ext2Superblock) -> Result<Vec<Ext2BlockGroupDesc>, FsError> {
+        const DESC_SIZE: usize = 32;
+        let group_count = superblock.block_group_count() as usize;
+        let block_size = superblock.block_size() as u64;
+        let table = (superblock.first_data_block as u64 + 1) * block_size;
+
+        let mut buf = vec![0u8; group_count * DESC_SIZE];
+        dev.read_at(table, &mut buf).map_err(|_| FsError::IoError)?;
+
+        Ok(buf
+            .chunks_exact(DESC_SIZE)
+            .map(|desc| Ext2BlockGroupDesc {
+                block_bitmap: le32(desc, 0),
+                inode_bitmap: le32(desc, 4),
+                inode_table: le32(desc, 8),
+                free_blocks_count: le16(desc, 12),
+                free_inodes_count: le16(desc, 14),
+                used_dirs_count: le16(desc, 16),
+            })
+            .collect())
+    }
 
-        Ok(groups)
+    /// Block holding inode `inode_num` and the inode's offset in it
+    fn inode_location(&self, inode_num: u32) -> Result<(u32, usize), FsError> {
+        if inode_num == 0 || inode_num > self.superblock.inodes_count {
+            return Err(FsError::FileNotFound);
+        }
+        let group = self.get_inode_group(inode_num);
+        let desc = self.block_groups.get(group).ok_or(FsError::CorruptedFileSystem)?;
+        let index = (inode_num - 1) % self.superblock.inodes_per_group;
+        let offset = index as usize * self.superblock.inode_size as usize;
+        let block_size = self.superblock.block_size() as usize;
+        Ok((desc.inode_table + (offset / block_size) as u32, offset % block_size))
     }
 
     /// Read an inode from disk
@@ -671,31 +716,37 @@ impl Ext2FileSystem {
             }
         }
 
-        // In a real implementation, this would read from the actual device
-        // For now, return a default inode
-        let inode = Ext2Inode {
-            mode: 0x81A4, // Regular file with 644 permissions
-            uid: 0,
-            size: 0,
-            atime: 0,
-            ctime: 0,
-            mtime: 0,
-            dtime: 0,
-            gid: 0,
-            links_count: 1,
-            blocks_count: 0,
-            flags: 0,
-            osd1: 0,
+        let (block_num, offset) = self.inode_location(inode_num)?;
+        let block = self.read_block(block_num)?;
+        let raw = &block[offset..offset + 128];
+        let mut inode = Ext2Inode {
+            mode: le16(raw, 0),
+            uid: le16(raw, 2),
+            size: le32(raw, 4),
+            atime: le32(raw, 8),
+            ctime: le32(raw, 12),
+            mtime: le32(raw, 16),
+            dtime: le32(raw, 20),
+            gid: le16(raw, 24),
+            links_count: le16(raw, 26),
+            blocks_count: le32(raw, 28),
+            flags: le32(raw, 32),
+            osd1: le32(raw, 36),
             block: [0; 12],
-            single_indirect: 0,
-            double_indirect: 0,
-            triple_indirect: 0,
-            generation: 0,
-            file_acl: 0,
-            dir_acl: 0,
-            fragment_addr: 0,
+            single_indirect: le32(raw, 88),
+            double_indirect: le32(raw, 92),
+            triple_indirect: le32(raw, 96),
+            generation: le32(raw, 100),
+            file_acl: le32(raw, 104),
+            dir_acl: le32(raw, 108),
+            fragment_addr: le32(raw, 112),
             osd2: [0; 12],
         };
+        for (i, ptr) in inode.block.iter_mut().enumerate() {
+            *ptr = le32(raw, 40 + i * 4);
+        }
+        inode.osd2.copy_from_slice(&raw[116..128]);
+        self.stats.inode_lookups.fetch_add(1, Ordering::Relaxed);
 
         // Cache the inode
         {
@@ -708,7 +759,34 @@ impl Ext2FileSystem {
 
     /// Write an inode to disk
     fn write_inode(&self, inode_num: u32, inode: &Ext2Inode) -> Result<(), FsError> {
-        // In a real implementation, this would write to the actual device
+        let (block_num, offset) = self.inode_location(inode_num)?;
+        let mut block = self.read_block(block_num)?;
+        let raw = &mut block[offset..offset + 128];
+        raw[0..2].copy_from_slice(&inode.mode.to_le_bytes());
+        raw[2..4].copy_from_slice(&inode.uid.to_le_bytes());
+        raw[4..8].copy_from_slice(&inode.size.to_le_bytes());
+        raw[8..12].copy_from_slice(&inode.atime.to_le_bytes());
+        raw[12..16].copy_from_slice(&inode.ctime.to_le_bytes());
+        raw[16..20].copy_from_slice(&inode.mtime.to_le_bytes());
+        raw[20..24].copy_from_slice(&inode.dtime.to_le_bytes());
+        raw[24..26].copy_from_slice(&inode.gid.to_le_bytes());
+        raw[26..28].copy_from_slice(&inode.links_count.to_le_bytes());
+        raw[28..32].copy_from_slice(&inode.blocks_count.to_le_bytes());
+        raw[32..36].copy_from_slice(&inode.flags.to_le_bytes());
+        raw[36..40].copy_from_slice(&inode.osd1.to_le_bytes());
+        for (i, ptr) in inode.block.iter().enumerate() {
+            raw[40 + i * 4..44 + i * 4].copy_from_slice(&ptr.to_le_bytes());
+        }
+        raw[88..92].copy_from_slice(&inode.single_indirect.to_le_bytes());
+        raw[92..96].copy_from_slice(&inode.double_indirect.to_le_bytes());
+        raw[96..100].copy_from_slice(&inode.triple_indirect.to_le_bytes());
+        raw[100..104].copy_from_slice(&inode.generation.to_le_bytes());
+        raw[104..108].copy_from_slice(&inode.file_acl.to_le_bytes());
+        raw[108..112].copy_from_slice(&inode.dir_acl.to_le_bytes());
+        raw[112..116].copy_from_slice(&inode.fragment_addr.to_le_bytes());
+        raw[116..128].copy_from_slice(&inode.osd2);
+        self.write_block(block_num, &block)?;
+
         // Update cache
         {
             let mut cache = self.inode_cache.lock();
@@ -758,17 +836,23 @@ impl Ext2FileSystem {
 
     /// Read a block from disk
     fn read_block(&self, block_num: u32) -> Result<Vec<u8>, FsError> {
-        // In a real implementation, this would read from the actual device
-        // For now, return a zero-filled block
-        let block_size = self.superblock.block_size();
-        Ok(vec![0; block_size as usize])
+        let block_size = self.superblock.block_size() as usize;
+        let mut buf = vec![0; block_size];
+        self.dev
+            .read_at(block_num as u64 * block_size as u64, &mut buf)
+            .map_err(|_| FsError::IoError)?;
+        Ok(buf)
     }
 
     /// Write a block to disk
     fn write_block(&self, block_num: u32, data: &[u8]) -> Result<(), FsError> {
-        // In a real implementation, this would write to the actual device
-        // For now, just return success
-        Ok(())
+        if self.mount_options.read_only {
+            return Err(FsError::ReadOnlyFileSystem);
+        }
+        let block_size = self.superblock.block_size() as u64;
+        self.dev
+            .write_at(block_num as u64 * block_size, data, 0)
+            .map_err(|_| FsError::IoError)
     }
 
     /// Get block group for a given block
@@ -798,8 +882,7 @@ impl Ext2FileSystem {
 
     /// Sync the file system
     pub fn sync(&self) -> Result<(), FsError> {
-        // In a real implementation, this would flush all cached data to disk
-        Ok(())
+        self.dev.flush().map_err(|_| FsError::IoError)
     }
 
     /// Open a file
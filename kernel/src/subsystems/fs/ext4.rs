@@ -12,7 +12,8 @@ extern crate alloc;
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::collections::BTreeMap;
-use crate::drivers::BlockDevice;
+use alloc::sync::Arc;
+use crate::subsystems::block::RequestQueue;
 use crate::subsystems::sync::Mutex;
 use crate::subsystems::fs::fs_impl::BufCache;
 use core::hash::Hasher;
@@ -480,24 +481,36 @@ pub const EXT4_FEATURE_RO_COMPAT_REPLICA: u32 = 0x1000;
 pub const EXT4_FEATURE_RO_COMPAT_READONLY: u32 = 0x2000;
 pub const EXT4_FEATURE_RO_COMPAT_PROJECT: u32 = 0x4000;
 
-/// Ext4 encryption modes
+/// Ext4 encryption modes (fscrypt mode numbers)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Ext4EncryptionMode {
     Invalid = 0,
     AES256XTS = 1,
-    AES256GCM = 2,
-    AES256CBC = 3,
     AES256CTS = 4,
+    AES128CBC = 5,
+    AES128CTS = 6,
+    Adiantum = 9,
+    AES256HCTR2 = 10,
 }
 
-/// Ext4 encryption context
+/// Inode flag: contents and names are encrypted
+pub const EXT4_ENCRYPT_FL: u32 = 0x0000_0800;
+
+/// The encryption context is stored as xattr "c" in this index
+pub const EXT4_XATTR_INDEX_ENCRYPTION: u8 = 9;
+
+/// Ext4 encryption context (fscrypt context v2)
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Ext4EncryptionContext {
-    pub mode: u8,
+    pub version: u8,
+    pub contents_encryption_mode: u8,
+    pub filenames_encryption_mode: u8,
     pub flags: u8,
-    pub master_key_descriptor: [u8; 8],
+    pub log2_data_unit_size: u8,
+    pub reserved: [u8; 3],
+    pub master_key_identifier: [u8; 16],
     pub nonce: [u8; 16],
 }
 
@@ -945,7 +958,7 @@ impl Default for Ext4Stats {
 
 /// Ext4 file system implementation
 pub struct Ext4FileSystem {
-    dev: Box<dyn BlockDevice>,
+    dev: Arc<RequestQueue>,
     sb: Ext4SuperBlock,
     block_size: u32,
     group_count: u32,
@@ -977,7 +990,7 @@ pub struct Ext4FileSystem {
 }
 
 impl Ext4FileSystem {
-    pub fn new(dev: Box<dyn BlockDevice>) -> Self {
+    pub fn new(dev: Arc<RequestQueue>) -> Self {
         Self {
             dev,
             sb: Ext4SuperBlock::default(),
@@ -1056,10 +1069,25 @@ impl Ext4FileSystem {
         ((self.sb.s_inodes_count_hi as u32) << 16) | self.sb.s_inodes_count
     }
 
+    /// Read file system block `block` into a block-sized buffer
+    fn read_block(&self, block: u64, buf: &mut [u8]) -> Result<(), &'static str> {
+        self.dev
+            .read_at(block * self.block_size as u64, buf)
+            .map_err(|_| "ext4: block read failed")
+    }
+
+    /// Write a block-sized buffer to file system block `block`
+    fn write_block(&self, block: u64, buf: &[u8]) -> Result<(), &'static str> {
+        self.dev
+            .write_at(block * self.block_size as u64, buf, 0)
+            .map_err(|_| "ext4: block write failed")
+    }
+
     /// Read superblock from disk
     fn read_superblock(&mut self) -> Result<(), &'static str> {
         let mut buf = [0u8; 1024];
-        self.dev.read(1, &mut buf); // Superblock is at block 1
+        // Superblock is at byte 1024, whatever the block size
+        self.dev.read_at(1024, &mut buf).map_err(|_| "ext4: superblock read failed")?;
 
         // Parse superblock
         self.sb.s_inodes_count = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
@@ -1181,7 +1209,7 @@ impl Ext4FileSystem {
 
             // Read block containing descriptor
             let mut buf = vec![0u8; block_size];
-            self.dev.read(desc_block as usize, &mut buf);
+            self.read_block(desc_block as u64, &mut buf)?;
 
             // Parse descriptor
             let offset = desc_offset as usize;
@@ -1256,7 +1284,7 @@ impl Ext4FileSystem {
 
         // Read block containing inode
         let mut buf = vec![0u8; self.block_size as usize];
-        self.dev.read((inode_table_block + block_offset) as usize, &mut buf);
+        self.read_block((inode_table_block + block_offset) as u64, &mut buf)?;
 
         // Parse inode
         let offset = offset_in_block as usize;
@@ -1376,7 +1404,7 @@ impl Ext4FileSystem {
 
         // Read block containing inode
         let mut buf = vec![0u8; self.block_size as usize];
-        self.dev.read((inode_table_block + block_offset) as usize, &mut buf);
+        self.read_block((inode_table_block + block_offset) as u64, &mut buf)?;
 
         // Update inode in buffer
         let offset = offset_in_block as usize;
@@ -1426,7 +1454,7 @@ impl Ext4FileSystem {
         }
 
         // Write block back to disk
-        self.dev.write((inode_table_block + block_offset) as usize, &buf);
+        self.write_block((inode_table_block + block_offset) as u64, &buf)?;
 
         // Update cache
         {
@@ -1457,7 +1485,7 @@ impl Ext4FileSystem {
 
         // Read bitmap block
         let mut buf = vec![0u8; self.block_size as usize];
-        self.dev.read(bitmap_block as usize, &mut buf);
+        self.read_block(bitmap_block as u64, &mut buf)?;
 
         // Convert to boolean vector
         let mut bitmap = Vec::new();
@@ -1502,7 +1530,7 @@ impl Ext4FileSystem {
         }
 
         // Write bitmap block
-        self.dev.write(bitmap_block as usize, &buf);
+        self.write_block(bitmap_block as u64, &buf)?;
 
         // Update cache
         {
@@ -1533,7 +1561,7 @@ impl Ext4FileSystem {
 
         // Read bitmap block
         let mut buf = vec![0u8; self.block_size as usize];
-        self.dev.read(bitmap_block as usize, &mut buf);
+        self.read_block(bitmap_block as u64, &mut buf)?;
 
         // Convert to boolean vector
         let mut bitmap = Vec::new();
@@ -1578,7 +1606,7 @@ impl Ext4FileSystem {
         }
 
         // Write bitmap block
-        self.dev.write(bitmap_block as usize, &buf);
+        self.write_block(bitmap_block as u64, &buf)?;
 
         // Update cache
         {
@@ -1807,7 +1835,7 @@ impl Ext4FileSystem {
                         self.block_size as usize - offset_in_block as usize
                     );
                     
-                    self.dev.read(block_idx as usize, &mut buf);
+                    self.read_block(block_idx as u64, &mut buf)?;
                     dst[dst_offset..dst_offset + bytes_in_block].copy_from_slice(
                         &buf[offset_in_block as usize..offset_in_block as usize + bytes_in_block]
                     );
@@ -1882,7 +1910,7 @@ impl Ext4FileSystem {
                 buf[..chunk_size].copy_from_slice(&src[src_offset..src_offset + chunk_size]);
                 
                 let block_idx = (extent_start / self.block_size as u64 + written as u64 / self.block_size as u64) as usize;
-                self.dev.write(block_idx, &buf);
+                self.write_block(block_idx as u64, &buf)?;
                 
                 written += chunk_size;
                 src_offset += chunk_size;
@@ -1960,7 +1988,7 @@ impl Ext4FileSystem {
             } else {
                 // Read block
                 let mut buf = vec![0u8; self.block_size as usize];
-                self.dev.read(block_num as usize, &mut buf);
+                self.read_block(block_num as u64, &mut buf)?;
                 
                 let offset_in_block = (current_offset % self.block_size as u64) as usize;
                 let bytes_to_read = core::cmp::min(
@@ -2015,14 +2043,14 @@ impl Ext4FileSystem {
             
             // Read existing block if not writing full block
             if bytes_to_write < self.block_size as usize {
-                self.dev.read(block_num as usize, &mut buf);
+                self.read_block(block_num as u64, &mut buf)?;
             }
             
             buf[offset_in_block..offset_in_block + bytes_to_write].copy_from_slice(
                 &src[src_offset..src_offset + bytes_to_write]
             );
             
-            self.dev.write(block_num as usize, &buf);
+            self.write_block(block_num as u64, &buf)?;
             
             src_offset += bytes_to_write;
             bytes_remaining -= bytes_to_write;
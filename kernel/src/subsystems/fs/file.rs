@@ -31,6 +31,10 @@ pub enum FileType {
     Signalfd,
     TimerFd,
     MemFd,
+    SeccompNotify,
+    IoUring,
+    #[cfg(feature = "graphics_subsystem")]
+    Display,
 }
 
 impl Default for FileType {
@@ -79,6 +83,16 @@ pub struct File {
 
     // For MemFd
     pub memfd_instance: Option<usize>,
+
+    // For SeccompNotify
+    pub seccomp_listener: Option<Arc<crate::security::seccomp::notify::NotifyListener>>,
+
+    // For IoUring
+    pub io_uring: Option<Arc<crate::subsystems::syscalls::io_uring::IoUring>>,
+
+    // For Display
+    #[cfg(feature = "graphics_subsystem")]
+    pub display: Option<Arc<crate::graphics::display::Connection>>,
 }
 
 impl Default for File {
@@ -102,6 +116,10 @@ impl Default for File {
             signalfd_instance: None,
             timerfd_instance: None,
             memfd_instance: None,
+            seccomp_listener: None,
+            io_uring: None,
+            #[cfg(feature = "graphics_subsystem")]
+            display: None,
         }
     }
 }
@@ -127,6 +145,10 @@ impl File {
             signalfd_instance: None,
             timerfd_instance: None,
             memfd_instance: None,
+            seccomp_listener: None,
+            io_uring: None,
+            #[cfg(feature = "graphics_subsystem")]
+            display: None,
         }
     }
 
@@ -219,9 +241,10 @@ impl File {
                 if let Some(ref mut socket) = self.socket {
                     match socket {
                         crate::net::socket::Socket::Tcp(tcp_socket) => {
+                            tcp_socket.set_nonblocking((self.status_flags & crate::posix::O_NONBLOCK) != 0);
                             match tcp_socket.recv(buf) {
                                 Ok(n) => n as isize,
-                                Err(_) => crate::reliability::errno::errno_neg(crate::reliability::errno::EIO),
+                                Err(e) => socket_errno(e),
                             }
                         }
                         crate::net::socket::Socket::Udp(udp_socket) => {
@@ -345,6 +368,16 @@ impl File {
                     -1
                 }
             },
+            FileType::SeccompNotify | FileType::IoUring => {
+                // Notifications are received and answered with ioctl, and
+                // rings are driven through their shared memory
+                crate::reliability::errno::errno_neg(crate::reliability::errno::EINVAL)
+            },
+            #[cfg(feature = "graphics_subsystem")]
+            FileType::Display => {
+                // Served by `file_read` without the file table held
+                crate::reliability::errno::errno_neg(crate::reliability::errno::ESPIPE)
+            },
         }
     }
 
@@ -453,9 +486,10 @@ impl File {
                 if let Some(ref mut socket) = self.socket {
                     match socket {
                         crate::net::socket::Socket::Tcp(tcp_socket) => {
+                            tcp_socket.set_nonblocking((self.status_flags & crate::posix::O_NONBLOCK) != 0);
                             match tcp_socket.send(buf) {
                                 Ok(n) => n as isize,
-                                Err(_) => crate::reliability::errno::errno_neg(crate::reliability::errno::EIO),
+                                Err(e) => socket_errno(e),
                             }
                         }
                         crate::net::socket::Socket::Udp(udp_socket) => {
@@ -489,10 +523,15 @@ impl File {
                     -1
                 }
             },
-            FileType::Inotify | FileType::Signalfd | FileType::TimerFd => {
+            FileType::Inotify | FileType::Signalfd | FileType::TimerFd | FileType::SeccompNotify | FileType::IoUring => {
                 // These file types don't support write operations
                 crate::reliability::errno::errno_neg(crate::reliability::errno::EINVAL)
             },
+            #[cfg(feature = "graphics_subsystem")]
+            FileType::Display => {
+                // Served by `file_write` without the file table held
+                crate::reliability::errno::errno_neg(crate::reliability::errno::ESPIPE)
+            },
             FileType::MemFd => {
                 if let Some(instance_idx) = self.memfd_instance {
                     if let Some(instance) = crate::syscalls::glib::get_memfd_instance(instance_idx) {
@@ -620,6 +659,7 @@ impl FTable {
         }
         let file = &mut self.files[idx];
         file.ref_count -= 1;
+        let mut registered = alloc::vec::Vec::new();
         if file.ref_count == 0 {
             // Clean up
             if file.ftype == FileType::Pipe {
@@ -639,6 +679,27 @@ impl FTable {
                 }
             }
             
+            // Start the TCP close handshake; the connection outlives the file
+            if let Some(crate::net::socket::Socket::Tcp(ref mut tcp_socket)) = file.socket {
+                let _ = tcp_socket.close();
+            }
+
+            // Tasks waiting on a seccomp supervisor fail once it is gone
+            if let Some(ref listener) = file.seccomp_listener {
+                listener.close();
+            }
+
+            // A ring stops its SQ thread and lets go of its registered files
+            if let Some(ref ring) = file.io_uring {
+                registered = ring.shutdown();
+            }
+
+            // A display client's windows go with its connection
+            #[cfg(feature = "graphics_subsystem")]
+            if let Some(ref connection) = file.display {
+                connection.shutdown();
+            }
+
             // Reset file to initial state
             file.ftype = FileType::None;
             file.pipe = None;
@@ -650,6 +711,12 @@ impl FTable {
             file.signalfd_instance = None;
             file.timerfd_instance = None;
             file.memfd_instance = None;
+            file.seccomp_listener = None;
+            file.io_uring = None;
+            #[cfg(feature = "graphics_subsystem")]
+            {
+                file.display = None;
+            }
             file.readable = false;
             file.writable = false;
             file.status_flags = 0;
@@ -664,6 +731,9 @@ impl FTable {
                 self.free_list.push(idx);
             }
         }
+        for registered_idx in registered {
+            self.close(registered_idx);
+        }
     }
 
     /// Get file reference
@@ -702,6 +772,21 @@ pub fn file_close(idx: usize) {
     FILE_TABLE.lock().close(idx)
 }
 
+/// Map a socket error to a negative errno for read and write
+fn socket_errno(error: crate::net::socket::SocketError) -> isize {
+    use crate::net::socket::SocketError;
+    use crate::reliability::errno::*;
+    errno_neg(match error {
+        SocketError::WouldBlock => EAGAIN,
+        SocketError::NotConnected => ENOTCONN,
+        SocketError::ConnectionReset => ECONNRESET,
+        SocketError::ConnectionRefused => ECONNREFUSED,
+        SocketError::ConnectionTimeout => ETIMEDOUT,
+        SocketError::BrokenPipe => EPIPE,
+        _ => EIO,
+    })
+}
+
 /// Create a new socket file
 pub fn file_socket_new(socket: crate::net::socket::Socket, readable: bool, writable: bool) -> Option<usize> {
     let mut table = FILE_TABLE.lock();
@@ -721,6 +806,100 @@ pub fn file_socket_new(socket: crate::net::socket::Socket, readable: bool, writa
     }
 }
 
+/// Create the listener file of a seccomp filter (SECCOMP_FILTER_FLAG_NEW_LISTENER)
+pub fn file_seccomp_listener_new(listener: Arc<crate::security::seccomp::notify::NotifyListener>) -> Option<usize> {
+    let mut table = FILE_TABLE.lock();
+    let idx = table.alloc()?;
+
+    if let Some(file) = table.get_mut(idx) {
+        file.ftype = FileType::SeccompNotify;
+        file.ref_count = 1;
+        file.readable = true;
+        file.writable = true;
+        file.status_flags = 0;
+        file.seccomp_listener = Some(listener);
+
+        Some(idx)
+    } else {
+        None
+    }
+}
+
+/// Get the seccomp listener behind a file
+pub fn file_get_seccomp_listener(idx: usize) -> Option<Arc<crate::security::seccomp::notify::NotifyListener>> {
+    let table = FILE_TABLE.lock();
+    table.get(idx).and_then(|file| file.seccomp_listener.clone())
+}
+
+/// Create the file of an io_uring instance
+pub fn file_io_uring_new(ring: Arc<crate::subsystems::syscalls::io_uring::IoUring>) -> Option<usize> {
+    let mut table = FILE_TABLE.lock();
+    let idx = table.alloc()?;
+
+    if let Some(file) = table.get_mut(idx) {
+        file.ftype = FileType::IoUring;
+        file.ref_count = 1;
+        file.readable = true;
+        file.writable = true;
+        file.status_flags = 0;
+        file.io_uring = Some(ring);
+
+        Some(idx)
+    } else {
+        None
+    }
+}
+
+/// Get the io_uring instance behind a file
+pub fn file_get_io_uring(idx: usize) -> Option<Arc<crate::subsystems::syscalls::io_uring::IoUring>> {
+    let table = FILE_TABLE.lock();
+    table.get(idx).and_then(|file| file.io_uring.clone())
+}
+
+/// Create the file of a display server connection
+#[cfg(feature = "graphics_subsystem")]
+pub fn file_display_new(connection: Arc<crate::graphics::display::Connection>, flags: i32) -> Option<usize> {
+    let mut table = FILE_TABLE.lock();
+    let idx = table.alloc()?;
+
+    if let Some(file) = table.get_mut(idx) {
+        file.ftype = FileType::Display;
+        file.ref_count = 1;
+        file.readable = true;
+        file.writable = true;
+        file.status_flags = flags & (crate::posix::O_NONBLOCK | crate::posix::O_CLOEXEC);
+        file.display = Some(connection);
+
+        Some(idx)
+    } else {
+        None
+    }
+}
+
+/// Get the display connection behind a file, and whether it is non-blocking
+#[cfg(feature = "graphics_subsystem")]
+pub fn file_get_display(idx: usize) -> Option<(Arc<crate::graphics::display::Connection>, bool)> {
+    let table = FILE_TABLE.lock();
+    let file = table.get(idx)?;
+    let connection = file.display.clone()?;
+    Some((connection, file.status_flags & crate::posix::O_NONBLOCK != 0))
+}
+
+/// Get the memfd instance behind a file
+pub fn file_get_memfd(idx: usize) -> Option<usize> {
+    let table = FILE_TABLE.lock();
+    table.get(idx).filter(|file| file.ftype == FileType::MemFd).and_then(|file| file.memfd_instance)
+}
+
+/// Get the page cache behind a VFS file, for mmap, along with whether the
+/// file was opened for reading and for writing
+pub fn file_get_mapping(idx: usize) -> Option<(Arc<crate::vfs::AddressSpace>, bool, bool)> {
+    let table = FILE_TABLE.lock();
+    let file = table.get(idx).filter(|file| file.ftype == FileType::Vfs)?;
+    let mapping = file.vfs_file.as_ref()?.inode.mapping()?;
+    Some((mapping, file.readable, file.writable))
+}
+
 /// Get socket from file descriptor
 pub fn file_get_socket(fd: usize) -> Option<crate::net::socket::Socket> {
     let table = FILE_TABLE.lock();
@@ -752,18 +931,92 @@ pub fn file_update_socket(fd: usize, socket: crate::net::socket::Socket) -> bool
 
 /// Read from file
 pub fn file_read(idx: usize, buf: &mut [u8]) -> isize {
+    // A display client may sleep waiting for events
+    #[cfg(feature = "graphics_subsystem")]
+    if let Some((connection, nonblock)) = file_get_display(idx) {
+        return match connection.read(buf, nonblock) {
+            Ok(n) => n as isize,
+            Err(e) => crate::reliability::errno::errno_neg(e),
+        };
+    }
     match FILE_TABLE.lock().get_mut(idx) {
         Some(f) => f.read(buf),
         None => -1,
     }
 }
 
+/// Read at `offset` without moving the file position (pread)
+///
+/// Only files opened through the VFS have a position; everything else
+/// reads from the stream as `file_read` does.
+pub fn file_read_at(idx: usize, offset: u64, buf: &mut [u8]) -> isize {
+    let mut table = FILE_TABLE.lock();
+    let Some(f) = table.get_mut(idx) else { return -1 };
+    match (&f.ftype, &f.vfs_file) {
+        (FileType::Vfs, Some(vfs_file)) if f.readable => match vfs_file.inode.read(offset, buf) {
+            Ok(n) => n as isize,
+            Err(_) => crate::reliability::errno::errno_neg(crate::reliability::errno::EIO),
+        },
+        _ => f.read(buf),
+    }
+}
+
+/// Write at `offset` without moving the file position (pwrite)
+pub fn file_write_at(idx: usize, offset: u64, buf: &[u8]) -> isize {
+    let mut table = FILE_TABLE.lock();
+    let Some(f) = table.get_mut(idx) else { return -1 };
+    match (&f.ftype, &f.vfs_file) {
+        (FileType::Vfs, Some(vfs_file)) if f.writable => match vfs_file.inode.write(offset, buf) {
+            Ok(n) => n as isize,
+            Err(_) => crate::reliability::errno::errno_neg(crate::reliability::errno::EIO),
+        },
+        _ => f.write(buf),
+    }
+}
+
 /// Write to file
 pub fn file_write(idx: usize, buf: &[u8]) -> isize {
-    match FILE_TABLE.lock().get_mut(idx) {
-        Some(f) => f.write(buf),
-        None => -1,
+    // Requests look up memfds by descriptor, which takes PROC_TABLE
+    #[cfg(feature = "graphics_subsystem")]
+    if let Some((connection, _)) = file_get_display(idx) {
+        return match connection.write(buf) {
+            Ok(n) => n as isize,
+            Err(e) => crate::reliability::errno::errno_neg(e),
+        };
+    }
+    // Looked up first: it takes PROC_TABLE, which nests outside FILE_TABLE
+    let fsize = crate::process::rlimit::current(crate::posix::RLIMIT_FSIZE);
+    let mut table = FILE_TABLE.lock();
+    let Some(f) = table.get_mut(idx) else { return -1 };
+    let buf = match fsize_room(f, fsize) {
+        Some(0) if !buf.is_empty() => {
+            drop(table);
+            let _ = crate::process::kill_proc(crate::process::getpid(), crate::ipc::signal::SIGXFSZ);
+            return crate::reliability::errno::errno_neg(crate::reliability::errno::EFBIG);
+        }
+        Some(room) => &buf[..buf.len().min(room)],
+        None => buf,
+    };
+    f.write(buf)
+}
+
+/// Bytes a write to a regular file may still add under RLIMIT_FSIZE, or
+/// `None` when the limit does not apply
+fn fsize_room(f: &File, limit: u64) -> Option<usize> {
+    if limit == crate::posix::RLIM_INFINITY || !matches!(f.ftype, FileType::Vfs) {
+        return None;
     }
+    let vfs_file = f.vfs_file.as_ref()?;
+    let attr = vfs_file.stat().ok()?;
+    if !attr.mode.is_regular() {
+        return None;
+    }
+    let pos = if (f.status_flags & crate::posix::O_APPEND) != 0 {
+        attr.size as u64
+    } else {
+        vfs_file.offset
+    };
+    Some(usize::try_from(limit.saturating_sub(pos)).unwrap_or(usize::MAX))
 }
 
 /// Get file status
@@ -829,6 +1082,32 @@ pub fn file_stat(idx: usize) -> Result<crate::posix::Stat, ()> {
     }
 }
 
+/// VFS attributes of an open file
+///
+/// Files with no inode behind them (pipes, sockets, event files) get a
+/// synthesized entry whose mode carries the matching file type.
+pub fn file_attr(idx: usize) -> Result<crate::vfs::FileAttr, ()> {
+    use crate::vfs::FileMode;
+
+    let table = FILE_TABLE.lock();
+    let f = table.get(idx).ok_or(())?;
+    let file_type = match f.ftype {
+        FileType::Vfs => {
+            return f.vfs_file.as_ref().ok_or(())?.stat().map_err(|_| ());
+        }
+        FileType::Pipe => FileMode::S_IFIFO,
+        FileType::Socket => FileMode::S_IFSOCK,
+        FileType::Device => FileMode::S_IFCHR,
+        _ => FileMode::S_IFREG,
+    };
+    Ok(crate::vfs::FileAttr {
+        mode: FileMode(file_type | 0o600),
+        nlink: 1,
+        blksize: crate::subsystems::mm::PAGE_SIZE as u32,
+        ..Default::default()
+    })
+}
+
 /// Open file
 pub fn file_open(path: &str, flags: u32, mode: u32) -> Result<usize, ()> {
     use crate::vfs;
@@ -974,7 +1253,31 @@ pub fn file_poll(idx: usize) -> i16 {
         FileType::Device => {
             ev |= crate::drivers::device_poll(f.major, f.minor);
         }
-        FileType::Socket => { ev |= posix::POLLERR; }
+        FileType::Socket => {
+            ev |= match f.socket {
+                Some(crate::net::socket::Socket::Tcp(ref tcp_socket)) => tcp_socket.poll(),
+                _ => posix::POLLERR,
+            };
+        }
+        FileType::SeccompNotify => {
+            ev |= match f.seccomp_listener {
+                Some(ref listener) => listener.poll(),
+                None => posix::POLLERR,
+            };
+        }
+        FileType::IoUring => {
+            ev |= match f.io_uring {
+                Some(ref ring) => ring.poll(),
+                None => posix::POLLERR,
+            };
+        }
+        #[cfg(feature = "graphics_subsystem")]
+        FileType::Display => {
+            ev |= match f.display {
+                Some(ref connection) => connection.poll(),
+                None => posix::POLLERR,
+            };
+        }
         _ => {}
     }
     ev
@@ -995,6 +1298,10 @@ pub fn file_truncate(idx: usize, size: u64) -> Result<(), ()> {
                         Err(())
                     }
                 },
+                FileType::MemFd if f.writable => {
+                    let instance = f.memfd_instance.and_then(crate::syscalls::glib::get_memfd_instance).ok_or(())?;
+                    instance.truncate(size as usize).map_err(|_| ())
+                },
                 _ => Err(()),
             }
         },
@@ -1002,6 +1309,21 @@ pub fn file_truncate(idx: usize, size: u64) -> Result<(), ()> {
     }
 }
 
+/// Write back the cached data of a file (fsync)
+///
+/// Only files opened through the VFS have cached data; the file table is
+/// not held while the data is written.
+pub fn file_fsync(idx: usize) -> crate::vfs::VfsResult<()> {
+    let inode = match FILE_TABLE.lock().get(idx) {
+        Some(f) => match (&f.ftype, &f.vfs_file) {
+            (FileType::Vfs, Some(vfs_file)) => vfs_file.inode.clone(),
+            _ => return Err(crate::vfs::VfsError::InvalidOperation),
+        },
+        None => return Err(crate::vfs::VfsError::InvalidOperation),
+    };
+    inode.sync()
+}
+
 /// Change file mode
 pub fn file_chmod(idx: usize, mode: u32) -> Result<(), ()> {
     // Get current process UID
@@ -1020,10 +1342,8 @@ pub fn file_chmod(idx: usize, mode: u32) -> Result<(), ()> {
                                     return Err(());
                                 }
                                 
-                                // Preserve the file type, only change the permissions
-                                let mut new_attr = attr;
-                                new_attr.mode = crate::vfs::FileMode::new((new_attr.mode.0 & !0o7777) | (mode & 0o7777));
-                                match vfs_file.set_attr(&new_attr) {
+                                // setattr preserves the file type, only the permissions change
+                                match vfs_file.set_attr(&crate::vfs::fs::SetAttr::chmod(mode & 0o7777)) {
                                     Ok(_) => Ok(()),
                                     Err(_) => Err(()),
                                 }
@@ -1058,15 +1378,13 @@ pub fn file_chown(idx: usize, uid: u32, gid: u32) -> Result<(), ()> {
             match f.ftype {
                 FileType::Vfs => {
                     if let Some(ref vfs_file) = f.vfs_file {
-                        match vfs_file.stat() {
-                            Ok(mut attr) => {
-                                attr.uid = uid;
-                                attr.gid = gid;
-                                match vfs_file.set_attr(&attr) {
-                                    Ok(_) => Ok(()),
-                                    Err(_) => Err(()),
-                                }
-                            },
+                        // u32::MAX (-1) leaves the id unchanged
+                        let attr = crate::vfs::fs::SetAttr::chown(
+                            (uid != u32::MAX).then_some(uid),
+                            (gid != u32::MAX).then_some(gid),
+                        );
+                        match vfs_file.set_attr(&attr) {
+                            Ok(_) => Ok(()),
                             Err(_) => Err(()),
                         }
                     } else {
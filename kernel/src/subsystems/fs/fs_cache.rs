@@ -4,17 +4,22 @@
 //! cache levels, intelligent eviction policies, and performance optimization. It supports
 //! caching of file data, metadata, directory entries, and other file system objects
 //! to improve I/O performance and reduce disk access.
+//!
+//! The contents of VFS files do not go through this cache: they live in the
+//! per-inode page cache (`vfs::page_cache`), which `read`, `write` and
+//! file-backed mappings share. This cache is for block-level data and
+//! metadata below the VFS.
 
 extern crate alloc;
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::collections::{BTreeMap, VecDeque};
 use crate::collections::HashMap;
-// use alloc::sync::Arc;
 // use core::sync::atomic::{AtomicU64, AtomicU32, AtomicBool, AtomicU8, Ordering};
 // use crate::subsystems::sync::{Sleeplock, Mutex};
 // use crate::subsystems::fs::fs_impl::{Buf, BufFlags, BufCache, CacheKey};
-use crate::platform::drivers::BlockDevice;
+use alloc::sync::Arc;
+use crate::subsystems::block::{Bio, RequestQueue};
 use nos_nos_error_handling::unified::KernelError;
 
 // ============================================================================
@@ -308,8 +313,8 @@ pub struct FsCache {
     min_access_time: AtomicU64,
     /// Maximum access time
     max_access_time: AtomicU64,
-    /// Block device for I/O
-    block_device: Option<Box<dyn BlockDevice>>,
+    /// Request queue of the backing disk
+    block_queue: Option<Arc<RequestQueue>>,
 }
 
 impl FsCache {
@@ -360,7 +365,7 @@ impl FsCache {
             total_access_time: AtomicU64::new(0),
             min_access_time: AtomicU64::new(u64::MAX),
             max_access_time: AtomicU64::new(0),
-            block_device: None,
+            block_queue: None,
         }
     }
 
@@ -381,9 +386,9 @@ impl FsCache {
         Ok(())
     }
 
-    /// Set block device
-    pub fn set_block_device(&mut self, device: Box<dyn BlockDevice>) {
-        self.block_device = Some(device);
+    /// Set the request queue dirty entries are written back to
+    pub fn set_block_queue(&mut self, queue: Arc<RequestQueue>) {
+        self.block_queue = Some(queue);
     }
 
     /// Get cache configuration
@@ -558,30 +563,36 @@ impl FsCache {
             }
         }
 
-        // Write dirty entries to disk
-        for (key, entry) in dirty_entries {
-            if let Err(e) = self.write_entry_to_disk(&entry) {
-                crate::println!("fs_cache: failed to write entry to disk: {:?}", e);
-                continue;
-            }
-            
-            // Mark entry as clean
-            {
-                let mut entries = self.entries.lock();
+        if dirty_entries.is_empty() {
+            return Ok(());
+        }
+        let queue = self.block_queue.clone().ok_or(KernelError::InvalidState)?;
+
+        // Write dirty entries back as one plugged batch so adjacent blocks
+        // merge, then flush the disk's write cache
+        let bios = dirty_entries
+            .iter()
+            .map(|(_, entry)| self.entry_bio(&queue, entry))
+            .collect();
+        let results = queue.submit_batch_wait(bios);
+        let mut written = 0u64;
+        {
+            let mut entries = self.entries.lock();
+            for ((key, _), (result, _)) in dirty_entries.iter().zip(results) {
+                if let Err(e) = result {
+                    crate::println!("fs_cache: failed to write entry to disk: {:?}", e);
+                    continue;
+                }
                 if let Some(entry) = entries.get_mut(key) {
                     entry.dirty = false;
                     entry.status = CacheEntryStatus::Valid;
                 }
-            }
-            
-            // Update statistics
-            {
-                let mut stats = self.stats.lock();
-                stats.written_back_entries += 1;
+                written += 1;
             }
         }
+        self.stats.lock().written_back_entries += written;
 
-        Ok(())
+        queue.flush().map_err(KernelError::from)
     }
 
     /// Evict entries to make space
@@ -915,25 +926,15 @@ impl FsCache {
         }
     }
 
-    /// Write entry to disk
-    fn write_entry_to_disk(&self, entry: &CacheEntry) -> Result<(), KernelError> {
-        if let Some(ref block_device) = self.block_device {
-            let block_size = block_device.block_size();
-            let block_num = entry.block_num as usize;
-            
-            // Ensure data size matches block size
-            let mut data = entry.data.clone();
-            if data.len() < block_size {
-                data.resize(block_size, 0);
-            } else if data.len() > block_size {
-                data.truncate(block_size);
-            }
-            
-            block_device.write(block_num, &data);
-            Ok(())
-        } else {
-            Err(KernelError::InvalidState)
-        }
+    /// Write bio for an entry, padded or cut to one cache block
+    ///
+    /// `block_num` counts cache blocks of `config.block_size` bytes.
+    fn entry_bio(&self, queue: &RequestQueue, entry: &CacheEntry) -> Bio {
+        let block_size = self.config.block_size as usize;
+        let sector = entry.block_num as u64 * block_size as u64 / queue.sector_size() as u64;
+        let mut data = entry.data.clone();
+        data.resize(block_size, 0);
+        Bio::write(sector, data)
     }
 
     /// Update hit statistics
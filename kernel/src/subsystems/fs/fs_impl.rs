@@ -7,6 +7,7 @@ use alloc::string::String;
 use alloc::collections::BTreeMap;
 use core::hash::{Hash, Hasher};
 use crate::drivers::{BlockDevice, RamDisk};
+use crate::subsystems::block::BlockResult;
 use crate::subsystems::sync::{Sleeplock, Mutex};
 
 /// Block size in bytes
@@ -254,8 +255,8 @@ impl BufCache {
         // If the buffer was dirty, write it back to disk
         if buf.flags.contains(BufFlags::DIRTY) {
             let old_offset = (buf.blockno as usize) * BSIZE / 512;
-            for j in 0..(BSIZE / 512) {
-                dev.write(old_offset + j, &buf.data[j * 512..(j + 1) * 512]);
+            if dev.write(old_offset, &buf.data).is_err() {
+                crate::println!("fs: lost write-back of block {}", buf.blockno);
             }
         }
 
@@ -267,8 +268,11 @@ impl BufCache {
 
         // Read from disk
         let offset = (blockno as usize) * BSIZE / 512;
-        for j in 0..(BSIZE / 512) {
-            dev.read(offset + j, &mut buf.data[j * 512..(j + 1) * 512]);
+        if dev.read(offset, &mut buf.data).is_err() {
+            buf.refcnt = 0;
+            drop(buf);
+            self.free_list.lock().push(idx);
+            return None;
         }
         buf.flags.set(BufFlags::VALID);
         
@@ -283,12 +287,12 @@ impl BufCache {
     }
 
     /// Write buffer to disk
-    pub fn bwrite(&self, dev: &impl BlockDevice, idx: usize) {
-        let buf = self.bufs[idx].lock();
+    pub fn bwrite(&self, dev: &impl BlockDevice, idx: usize) -> BlockResult<()> {
+        let mut buf = self.bufs[idx].lock();
         let offset = (buf.blockno as usize) * BSIZE / 512;
-        for j in 0..(BSIZE / 512) {
-            dev.write(offset + j, &buf.data[j * 512..(j + 1) * 512]);
-        }
+        dev.write(offset, &buf.data)?;
+        buf.flags.clear(BufFlags::DIRTY);
+        Ok(())
     }
 
     /// Release a buffer
@@ -379,7 +383,9 @@ impl Inode {
             
             // Read block
             let mut buf = [0u8; BSIZE];
-            dev.read(block_num as usize, &mut buf);
+            if dev.read(block_num as usize, &mut buf).is_err() {
+                break;
+            }
             
             let bytes_to_copy = (BSIZE - block_offset).min(end - offset);
             dst[total..total + bytes_to_copy].copy_from_slice(&buf[block_offset..block_offset + bytes_to_copy]);
@@ -416,12 +422,16 @@ impl Inode {
             
             // Read-modify-write
             let mut buf = [0u8; BSIZE];
-            dev.read(block_num as usize, &mut buf);
+            if dev.read(block_num as usize, &mut buf).is_err() {
+                break;
+            }
             
             let bytes_to_copy = (BSIZE - block_offset).min(end - offset);
             buf[block_offset..block_offset + bytes_to_copy].copy_from_slice(&src[total..total + bytes_to_copy]);
             
-            dev.write(block_num as usize, &buf);
+            if dev.write(block_num as usize, &buf).is_err() {
+                break;
+            }
             
             total += bytes_to_copy;
             offset += bytes_to_copy;
@@ -480,7 +490,10 @@ impl Fs {
     /// Read superblock from disk
     pub fn read_super(&self) -> SuperBlock {
         let mut buf = [0u8; 512];
-        self.dev.read(1 as usize, &mut buf); // Superblock is at block 1
+        // Superblock is at block 1; a failed read leaves a bad magic
+        if self.dev.read(1, &mut buf).is_err() {
+            crate::println!("fs: failed to read superblock");
+        }
 
         SuperBlock {
             magic: u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
@@ -495,7 +508,7 @@ impl Fs {
     }
 
     /// Write superblock to disk
-    pub fn write_super(&self, sb: &SuperBlock) {
+    pub fn write_super(&self, sb: &SuperBlock) -> BlockResult<()> {
         let mut buf = [0u8; 512];
         buf[0..4].copy_from_slice(&sb.magic.to_le_bytes());
         buf[4..8].copy_from_slice(&sb.size.to_le_bytes());
@@ -505,7 +518,7 @@ impl Fs {
         buf[20..24].copy_from_slice(&sb.logstart.to_le_bytes());
         buf[24..28].copy_from_slice(&sb.inodestart.to_le_bytes());
         buf[28..32].copy_from_slice(&sb.bmapstart.to_le_bytes());
-        self.dev.write(1 as usize, &buf);
+        self.dev.write(1, &buf)
     }
 
     /// Initialize file system
@@ -541,10 +554,10 @@ impl Fs {
                 buf.flags.set(BufFlags::DIRTY);
                 drop(buf);
                 
-                self.buf_cache.bwrite(&self.dev, buf_idx);
+                let written = self.buf_cache.bwrite(&self.dev, buf_idx);
                 self.buf_cache.brelse(buf_idx);
                 
-                return Some(inum);
+                return written.ok().map(|_| inum);
             }
             
             drop(buf);
@@ -620,7 +633,9 @@ impl Fs {
                 continue;
             }
             
-            self.dev.read(dir_inode.addrs[i] as usize, &mut buf);
+            if self.dev.read(dir_inode.addrs[i] as usize, &mut buf).is_err() {
+                continue;
+            }
             
             // Scan directory entries in this block
             for off in (0..BSIZE).step_by(dirent_size) {
@@ -663,7 +678,9 @@ impl Fs {
                 continue;
             }
             
-            self.dev.read(dir_inode.addrs[i] as usize, &mut buf);
+            if self.dev.read(dir_inode.addrs[i] as usize, &mut buf).is_err() {
+                continue;
+            }
             
             for off in (0..BSIZE).step_by(dirent_size) {
                 let entry_inum = u16::from_le_bytes([buf[off], buf[off + 1]]);
@@ -681,8 +698,7 @@ impl Fs {
                         buf[off + 2 + j] = 0;
                     }
                     
-                    self.dev.write(dir_inode.addrs[i] as usize, &buf);
-                    return true;
+                    return self.dev.write(dir_inode.addrs[i] as usize, &buf).is_ok();
                 }
             }
         }
@@ -710,7 +726,9 @@ impl Fs {
                 continue;
             }
             
-            self.dev.read(dir_inode.addrs[i] as usize, &mut buf);
+            if self.dev.read(dir_inode.addrs[i] as usize, &mut buf).is_err() {
+                continue;
+            }
             
             for off in (0..BSIZE).step_by(dirent_size) {
                 let inum = u16::from_le_bytes([buf[off], buf[off + 1]]);
@@ -748,18 +766,26 @@ impl Fs {
             inodestart: 32,
             bmapstart: 58,
         };
-        self.write_super(&sb);
-        
-        // Zero out all blocks
+        // Zero out the metadata area, then lay down the superblock
         let zero_block = [0u8; 512];
         for i in 0..100 {
-            self.dev.write(i as usize, &zero_block);
+            if i != 1 && self.dev.write(i, &zero_block).is_err() {
+                crate::println!("fs: mkfs failed to clear block {}", i);
+                return;
+            }
+        }
+        if self.write_super(&sb).is_err() {
+            crate::println!("fs: mkfs failed to write superblock");
+            return;
         }
         
         // Create root directory inode (inode 1)
         let root_block = sb.inodestart;
         let mut buf = [0u8; 512];
-        self.dev.read(root_block as usize, &mut buf);
+        if self.dev.read(root_block as usize, &mut buf).is_err() {
+            crate::println!("fs: mkfs failed to read inode block");
+            return;
+        }
         
         // Root inode is at offset 0 in block (inode 1)
         // Set type to directory
@@ -769,7 +795,10 @@ impl Fs {
         // Size = 0 initially
         buf[6..10].copy_from_slice(&0u32.to_le_bytes());
         
-        self.dev.write(root_block as usize, &buf);
+        if self.dev.write(root_block as usize, &buf).is_err() {
+            crate::println!("fs: mkfs failed to write root inode");
+            return;
+        }
         
         crate::println!("fs: created new filesystem with root directory");
     }
@@ -12,6 +12,7 @@ use alloc::collections::BTreeMap;
 
 // use crate::drivers::BlockDevice;
 // use crate::subsystems::sync::{Mutex, Sleeplock};
+use crate::subsystems::block::BlockError;
 use crate::subsystems::fs::{BSIZE, SuperBlock, InodeType, DiskInode, Dirent, BufFlags};
 
 /// Journaling file system constants
@@ -233,7 +234,7 @@ impl JournalingFileSystem {
         let mut buf = [0u8; BSIZE];
         
         // Journal superblock is at block 0 of the journal area
-        device.read(0, &mut buf);
+        device.read(0, &mut buf)?;
         
         let mut sb = self.journal_sb.lock();
         sb.magic = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
@@ -271,7 +272,7 @@ impl JournalingFileSystem {
         buf[40..44].copy_from_slice(&sb.flags.to_le_bytes());
         
         let device = self.device.lock();
-        device.write(0, &buf);
+        device.write(0, &buf)?;
         
         Ok(())
     }
@@ -296,7 +297,7 @@ impl JournalingFileSystem {
         let device = self.device.lock();
         let zero_block = [0u8; BSIZE];
         for i in 1..sb.size {
-            device.write(i as usize, &zero_block);
+            device.write(i as usize, &zero_block)?;
         }
         
         crate::println!("jfs: formatted new journal");
@@ -348,7 +349,7 @@ impl JournalingFileSystem {
         let mut buf = [0u8; BSIZE];
         
         for block_num in start..start + size {
-            device.read(block_num as usize, &mut buf);
+            device.read(block_num as usize, &mut buf)?;
             
             // Check if this is a valid journal entry
             let magic = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
@@ -375,7 +376,7 @@ impl JournalingFileSystem {
                     if let Some(tx) = transactions.get_mut(&transaction_id) {
                         // Read the current block data
                         let mut block_data = vec![0u8; data_length as usize];
-                        device.read(block_number as usize, &mut block_data);
+                        device.read(block_number as usize, &mut block_data)?;
                         tx.original_data.insert(block_number, block_data);
                     }
                 }
@@ -414,13 +415,13 @@ impl JournalingFileSystem {
         for entry in &tx.entries {
             if entry.entry_type == 2 { // Update block
                 let mut buf = [0u8; BSIZE];
-                device.read(entry.block_number as usize, &mut buf);
+                device.read(entry.block_number as usize, &mut buf)?;
                 
                 // Apply the modification
                 // In a real implementation, we would have the actual data to apply
                 // For now, we'll just mark the block as modified
                 
-                device.write(entry.block_number as usize, &buf);
+                device.write(entry.block_number as usize, &buf)?;
             }
         }
         
@@ -482,7 +483,7 @@ impl JournalingFileSystem {
         
         let device = self.device.lock();
         let block_num = sb.start_block + (sequence % sb.size);
-        device.write(block_num as usize, &buf);
+        device.write(block_num as usize, &buf)?;
         
         Ok(())
     }
@@ -578,7 +579,7 @@ impl JournalingFileSystem {
         // Rollback all modifications
         for (block_num, original_data) in &tx.original_data {
             let device = self.device.lock();
-            device.write(*block_num as usize, original_data);
+            device.write(*block_num as usize, original_data)?;
         }
         
         // Remove transaction from active list
@@ -645,6 +646,12 @@ pub enum JfsError {
     InvalidTransactionState,
 }
 
+impl From<BlockError> for JfsError {
+    fn from(_: BlockError) -> Self {
+        JfsError::IoError
+    }
+}
+
 /// Global journaling file system instance
 static mut JFS: Option<JournalingFileSystem> = None;
 
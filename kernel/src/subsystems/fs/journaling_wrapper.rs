@@ -309,7 +309,10 @@ impl JournalingFsWrapper {
             if inode_clone.addrs[i] != 0 {
                 let block_num = inode_clone.addrs[i];
                 let mut block_data = vec![0u8; BSIZE];
-                self.base_fs.dev.read(block_num as usize, &mut block_data);
+                if self.base_fs.dev.read(block_num as usize, &mut block_data).is_err() {
+                    crate::println!("jfs_wrapper: failed to read block {}", block_num);
+                    continue;
+                }
                 
                 if let Err(e) = self.journal.log_block(tx_id, block_num, &old_data, &block_data) {
                     crate::println!("jfs_wrapper: failed to log block: {:?}", e);
@@ -1,7 +1,16 @@
 extern crate alloc;
-use alloc::{sync::Arc, collections::BTreeMap, string::String};
+use alloc::{sync::Arc, collections::BTreeMap, string::{String, ToString}, vec::Vec};
 use spin::Once;
 use crate::subsystems::sync::Mutex;
+use crate::vfs::{
+    error::{VfsError, VfsResult},
+    file::VfsFile,
+    fs::{InodeOps, RenameFlags, SetAttr},
+    mount::Mount,
+    namei::{FsContext, LookupFlags, PathLoc},
+    types::FileMode,
+    xattr::XattrFlags,
+};
 
 pub mod api;
 pub mod ext2;
@@ -129,6 +138,22 @@ impl VfsManager {
         }
     }
     
+    /// Write back all cached file data, then every mounted filesystem (sync)
+    ///
+    /// Keeps going past errors and returns the first one.
+    pub fn sync_all(&self) -> VfsResult<()> {
+        let mut result = crate::vfs::page_cache::sync_all();
+        let mounts: Vec<Arc<Mount>> = self.mounts.lock().values().cloned().collect();
+        for mount in mounts {
+            if let Err(e) = mount.superblock.sync()
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        }
+        result
+    }
+    
     /// Verify root filesystem is mounted and accessible
     pub fn verify_root(&self) -> Result<(), crate::vfs::error::VfsError> {
         let root_mounted = self.root_mounted.lock();
@@ -145,46 +170,289 @@ impl VfsManager {
         Ok(())
     }
     
-    /// Get file attributes (stat)
+    /// Mount of the global root
+    pub fn root_mount(&self) -> VfsResult<Arc<Mount>> {
+        self.root_mounted.lock().clone().ok_or(VfsError::NotMounted)
+    }
+
+    /// Mount whose mount point is exactly the canonical `path`
+    pub fn mount_at(&self, path: &str) -> Option<Arc<Mount>> {
+        self.mounts.lock().get(path).cloned()
+    }
+
+    /// Resolve `path` from the global root
+    fn resolve(&self, path: &str, flags: u32) -> VfsResult<PathLoc> {
+        FsContext::global()?.lookup(None, path, LookupFlags(flags))
+    }
+
+    /// Look up the inode at an absolute path
+    ///
+    /// A symlink in the final component is not followed.
+    pub fn lookup(&self, path: &str) -> VfsResult<Arc<dyn InodeOps>> {
+        Ok(self.resolve(path, 0)?.inode)
+    }
+
+    /// Get file attributes (stat), following a final symlink
     pub fn stat(&self, path: &str) -> Result<crate::vfs::types::FileAttr, crate::vfs::error::VfsError> {
-        // For now, only support root path
-        if path == "/" {
-            let root_mounted = self.root_mounted.lock();
-            if let Some(mount) = root_mounted.as_ref() {
-                let root_inode = mount.superblock.root();
-                return root_inode.getattr();
-            }
+        self.resolve(path, LookupFlags::FOLLOW)?.inode.getattr()
+    }
+
+    /// Get file attributes without following a final symlink (lstat)
+    pub fn lstat(&self, path: &str) -> VfsResult<crate::vfs::types::FileAttr> {
+        self.resolve(path, 0)?.inode.getattr()
+    }
+
+    /// Get attributes of `path` relative to `dir` (fstatat)
+    pub fn stat_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, path: &str, lookup: LookupFlags) -> VfsResult<crate::vfs::types::FileAttr> {
+        ctx.lookup(dir, path, lookup)?.inode.getattr()
+    }
+
+    /// Open an existing file
+    pub fn open(&self, path: &str, flags: u32) -> VfsResult<VfsFile> {
+        self.open_at(&FsContext::global()?, None, path, LookupFlags(LookupFlags::FOLLOW), flags)
+    }
+
+    /// Open an existing file relative to `dir`
+    ///
+    /// Without `LookupFlags::FOLLOW` a symlink in the final component fails
+    /// with `TooManyLinks`, which is how `O_NOFOLLOW` reports it.
+    pub fn open_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, path: &str, lookup: LookupFlags, flags: u32) -> VfsResult<VfsFile> {
+        let loc = ctx.lookup(dir, path, lookup)?;
+        if loc.inode.getattr()?.mode.is_symlink() {
+            return Err(VfsError::TooManyLinks);
         }
-        
-        Err(crate::vfs::error::VfsError::NotFound)
+        Ok(VfsFile::at(loc, flags))
     }
-    
+
     /// Create a new directory
     pub fn mkdir(&self, path: &str, mode: crate::vfs::types::FileMode) -> Result<(), crate::vfs::error::VfsError> {
-        // TODO: Implement directory creation
-        // For now, return error as this requires path resolution
-        Err(crate::vfs::error::VfsError::NotSupported)
+        self.mkdir_at(&FsContext::global()?, None, path, mode)
     }
-    
-    /// Create a new file
-    pub fn create(&self, path: &str, mode: crate::vfs::types::FileMode) -> Result<(), crate::vfs::error::VfsError> {
-        // TODO: Implement file creation
-        // For now, return error as this requires path resolution
-        Err(crate::vfs::error::VfsError::NotSupported)
+
+    /// Create a new directory relative to `dir`
+    pub fn mkdir_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, path: &str, mode: FileMode) -> VfsResult<()> {
+        let (parent, name) = ctx.lookup_parent(dir, path)?;
+        parent.inode.mkdir(&name, mode).map(|_| ())
     }
-    
+
+    /// Create a new file, or open it if a regular file already exists
+    pub fn create(&self, path: &str, mode: crate::vfs::types::FileMode) -> VfsResult<VfsFile> {
+        self.create_at(&FsContext::global()?, None, path, mode, LookupFlags(LookupFlags::FOLLOW), false)
+    }
+
+    /// Create a regular file relative to `dir` (open with `O_CREAT`)
+    ///
+    /// An existing file is opened unless `exclusive` is set, in which case
+    /// it fails with `Exists` even if it is a symlink. `lookup` decides
+    /// whether an existing final symlink is followed, as in [`Self::open_at`].
+    pub fn create_at(
+        &self,
+        ctx: &FsContext,
+        dir: Option<&PathLoc>,
+        path: &str,
+        mode: FileMode,
+        lookup: LookupFlags,
+        exclusive: bool,
+    ) -> VfsResult<VfsFile> {
+        let (parent, name) = ctx.lookup_parent(dir, path)?;
+        match parent.inode.lookup(&name) {
+            Ok(_) if exclusive => Err(VfsError::Exists),
+            Ok(_) => {
+                let file = self.open_at(ctx, Some(&parent), &name, lookup, 0)?;
+                if file.inode.getattr()?.mode.is_dir() {
+                    return Err(VfsError::IsDirectory);
+                }
+                Ok(file)
+            }
+            Err(VfsError::NotFound) => {
+                let mode = FileMode(FileMode::S_IFREG | (mode.0 & !FileMode::S_IFMT));
+                let path = parent.child_path(&name);
+                let inode = parent.inode.create(&name, mode)?;
+                Ok(VfsFile::at(PathLoc { mount: parent.mount, inode, path }, 0))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Write to a file
     pub fn write(&self, path: &str, data: &[u8], offset: u64) -> Result<usize, crate::vfs::error::VfsError> {
-        // TODO: Implement file writing
-        // For now, return error as this requires path resolution
-        Err(crate::vfs::error::VfsError::NotSupported)
+        self.resolve(path, LookupFlags::FOLLOW)?.inode.write(offset, data)
     }
-    
-    /// Delete a file or directory
+
+    /// Delete a file
     pub fn unlink(&self, path: &str) -> Result<(), crate::vfs::error::VfsError> {
-        // TODO: Implement file/directory deletion
-        // For now, return error as this requires path resolution
-        Err(crate::vfs::error::VfsError::NotSupported)
+        self.unlink_at(&FsContext::global()?, None, path)
+    }
+
+    /// Delete a file relative to `dir`
+    pub fn unlink_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, path: &str) -> VfsResult<()> {
+        let (parent, name) = ctx.lookup_parent(dir, path)?;
+        if self.mount_at(&parent.child_path(&name)).is_some() {
+            return Err(VfsError::Busy);
+        }
+        parent.inode.unlink(&name)
+    }
+
+    /// Delete an empty directory
+    pub fn rmdir(&self, path: &str) -> VfsResult<()> {
+        self.rmdir_at(&FsContext::global()?, None, path)
+    }
+
+    /// Delete an empty directory relative to `dir`
+    pub fn rmdir_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, path: &str) -> VfsResult<()> {
+        let (parent, name) = ctx.lookup_parent(dir, path)?;
+        if self.mount_at(&parent.child_path(&name)).is_some() {
+            return Err(VfsError::Busy);
+        }
+        parent.inode.rmdir(&name)
+    }
+
+    /// Rename `old_path` to `new_path` (renameat2 semantics)
+    ///
+    /// Replacing an existing target is atomic, so writing a temporary file
+    /// and renaming it over the original never exposes a partial file.
+    pub fn rename(&self, old_path: &str, new_path: &str, flags: RenameFlags) -> VfsResult<()> {
+        self.rename_at(&FsContext::global()?, None, old_path, None, new_path, flags)
+    }
+
+    /// Rename with each path relative to its own directory (renameat2)
+    pub fn rename_at(
+        &self,
+        ctx: &FsContext,
+        old_dir: Option<&PathLoc>,
+        old_path: &str,
+        new_dir: Option<&PathLoc>,
+        new_path: &str,
+        flags: RenameFlags,
+    ) -> VfsResult<()> {
+        flags.validate()?;
+        let (old_parent, old_name) = ctx.lookup_parent(old_dir, old_path)?;
+        let (new_parent, new_name) = ctx.lookup_parent(new_dir, new_path)?;
+        let old_path = old_parent.child_path(&old_name);
+        let new_path = new_parent.child_path(&new_name);
+        if self.mount_at(&old_path).is_some() || self.mount_at(&new_path).is_some() {
+            return Err(VfsError::Busy);
+        }
+        // A directory cannot move below itself, and the target cannot be an
+        // ancestor of the source (it would be non-empty).
+        if is_descendant(&new_path, &old_path) {
+            return Err(VfsError::InvalidOperation);
+        }
+        if is_descendant(&old_path, &new_path) {
+            return Err(if flags.contains(RenameFlags::EXCHANGE) {
+                VfsError::InvalidOperation
+            } else {
+                VfsError::NotEmpty
+            });
+        }
+        if !Arc::ptr_eq(&old_parent.mount, &new_parent.mount) {
+            return Err(VfsError::CrossDevice);
+        }
+        old_parent.inode.rename(&old_name, &new_parent.inode, &new_name, flags)
+    }
+
+    /// Create a hard link `new_path` to the file at `old_path`
+    pub fn link(&self, old_path: &str, new_path: &str) -> VfsResult<()> {
+        self.link_at(&FsContext::global()?, None, old_path, None, new_path, LookupFlags::default())
+    }
+
+    /// Create a hard link relative to two directories (linkat)
+    ///
+    /// `lookup` decides whether a symlink at `old_path` is followed
+    /// (`AT_SYMLINK_FOLLOW`) or linked itself.
+    pub fn link_at(
+        &self,
+        ctx: &FsContext,
+        old_dir: Option<&PathLoc>,
+        old_path: &str,
+        new_dir: Option<&PathLoc>,
+        new_path: &str,
+        lookup: LookupFlags,
+    ) -> VfsResult<()> {
+        let target = ctx.lookup(old_dir, old_path, lookup)?;
+        if target.is_dir()? {
+            return Err(VfsError::PermissionDenied);
+        }
+        let (new_parent, new_name) = ctx.lookup_parent(new_dir, new_path)?;
+        if !Arc::ptr_eq(&target.mount, &new_parent.mount) {
+            return Err(VfsError::CrossDevice);
+        }
+        new_parent.inode.link(&new_name, target.inode)
+    }
+
+    /// Create a symbolic link at `link_path` pointing to `target`
+    pub fn symlink(&self, link_path: &str, target: &str) -> VfsResult<()> {
+        self.symlink_at(&FsContext::global()?, None, link_path, target)
+    }
+
+    /// Create a symbolic link relative to `dir` (symlinkat)
+    pub fn symlink_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, link_path: &str, target: &str) -> VfsResult<()> {
+        if target.is_empty() {
+            return Err(VfsError::NotFound);
+        }
+        let (parent, name) = ctx.lookup_parent(dir, link_path)?;
+        parent.inode.symlink(&name, target).map(|_| ())
+    }
+
+    /// Read the target of a symbolic link
+    pub fn readlink(&self, path: &str) -> VfsResult<String> {
+        self.lookup(path)?.readlink()
+    }
+
+    /// Read the target of a symbolic link relative to `dir` (readlinkat)
+    pub fn readlink_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, path: &str) -> VfsResult<String> {
+        ctx.lookup(dir, path, LookupFlags(LookupFlags::EMPTY_PATH))?.inode.readlink()
+    }
+
+    /// Change the attributes selected by `attr.valid`
+    pub fn setattr(&self, path: &str, attr: &SetAttr) -> VfsResult<()> {
+        self.resolve(path, LookupFlags::FOLLOW)?.inode.setattr(attr)
+    }
+
+    /// Change attributes of `path` relative to `dir` (fchmodat, fchownat,
+    /// utimensat)
+    pub fn setattr_at(&self, ctx: &FsContext, dir: Option<&PathLoc>, path: &str, lookup: LookupFlags, attr: &SetAttr) -> VfsResult<()> {
+        ctx.lookup(dir, path, lookup)?.inode.setattr(attr)
+    }
+
+    /// Change the size of a file
+    pub fn truncate(&self, path: &str, size: u64) -> VfsResult<()> {
+        self.setattr(path, &SetAttr::truncate(size))
+    }
+
+    /// Change permission bits
+    pub fn chmod(&self, path: &str, mode: u32) -> VfsResult<()> {
+        self.setattr(path, &SetAttr::chmod(mode))
+    }
+
+    /// Change owner and/or group; `None` leaves the id unchanged
+    pub fn chown(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        self.setattr(path, &SetAttr::chown(uid, gid))
+    }
+
+    /// Change access and/or modification time; `None` leaves it unchanged
+    pub fn utimens(&self, path: &str, atime: Option<u64>, mtime: Option<u64>) -> VfsResult<()> {
+        self.setattr(path, &SetAttr::utimens(atime, mtime))
+    }
+
+    /// Get an extended attribute
+    pub fn getxattr(&self, path: &str, name: &str) -> VfsResult<Vec<u8>> {
+        self.resolve(path, LookupFlags::FOLLOW)?.inode.getxattr(name)
+    }
+
+    /// Set an extended attribute
+    pub fn setxattr(&self, path: &str, name: &str, value: &[u8], flags: XattrFlags) -> VfsResult<()> {
+        self.resolve(path, LookupFlags::FOLLOW)?.inode.setxattr(name, value, flags)
+    }
+
+    /// List extended attribute names
+    pub fn listxattr(&self, path: &str) -> VfsResult<Vec<String>> {
+        self.resolve(path, LookupFlags::FOLLOW)?.inode.listxattr()
+    }
+
+    /// Remove an extended attribute
+    pub fn removexattr(&self, path: &str, name: &str) -> VfsResult<()> {
+        self.resolve(path, LookupFlags::FOLLOW)?.inode.removexattr(name)
     }
     
     /// Check if root filesystem is mounted
@@ -194,6 +462,14 @@ impl VfsManager {
     }
 }
 
+/// Whether canonical `path` lies strictly below canonical `dir`
+fn is_descendant(path: &str, dir: &str) -> bool {
+    if dir == "/" {
+        return path != "/";
+    }
+    path.len() > dir.len() && path.starts_with(dir) && path.as_bytes()[dir.len()] == b'/'
+}
+
 /// Global VFS manager instance
 static VFS_MANAGER: Once<Arc<VfsManager>> = Once::new();
 
@@ -14,7 +14,8 @@ use crate::subsystems::process::Pid;
 use crate::subsystems::ipc::signal::{SIGEV_SIGNAL, SIGEV_NONE};
 
 /// Message queue attributes
-#[derive(Debug, Clone)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct MqAttr {
     /// Maximum number of messages
     pub mq_maxmsg: u64,
@@ -38,7 +39,8 @@ impl Default for MqAttr {
 }
 
 /// Message queue notification
-#[derive(Debug, Clone)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct MqNotify {
     /// Notification type
     pub notify_type: i32,
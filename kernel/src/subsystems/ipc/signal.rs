@@ -246,6 +246,10 @@ pub mod si_code {
     // SIGBUS codes
     pub const BUS_ADRALN: i32 = 1;   // Invalid address alignment
     pub const BUS_ADRERR: i32 = 2;   // Non-existent physical address
+
+    // SIGTRAP codes
+    pub const TRAP_BRKPT: i32 = 1;   // Breakpoint
+    pub const TRAP_TRACE: i32 = 2;   // Single-step trap
     
     // SIGCHLD codes
     pub const CLD_EXITED: i32 = 1;   // Child has exited
@@ -254,6 +258,9 @@ pub mod si_code {
     pub const CLD_TRAPPED: i32 = 4;  // Traced child has trapped
     pub const CLD_STOPPED: i32 = 5;  // Child has stopped
     pub const CLD_CONTINUED: i32 = 6; // Child has continued
+
+    // SIGSYS codes
+    pub const SYS_SECCOMP: i32 = 1;  // Seccomp filter trapped a syscall
 }
 
 // ============================================================================
@@ -1226,6 +1233,18 @@ impl SignalState {
     }
 
     pub fn deliver_signal(&self, delivery: &SignalDelivery) -> Result<(), SignalError> {
+        // A tracer sees the signal first and may replace or drop it
+        let Some((signal, info)) = crate::process::ptrace::signal_stop(delivery.signal, &delivery.info) else {
+            return Ok(());
+        };
+        let traced;
+        let delivery = if signal != delivery.signal {
+            traced = SignalDelivery { signal, info, ..delivery.clone() };
+            &traced
+        } else {
+            delivery
+        };
+
         let action = self.get_action(delivery.signal);
 
         if action.handler == SIG_IGN {
@@ -1361,13 +1380,9 @@ impl SignalState {
     }
 
     fn terminate_process(&self, sig: Signal, info: SigInfo) {
-        let pid = crate::process::myproc().unwrap_or(0);
-
-        if default_action(sig) == DefaultAction::Core {
-            self.create_core_dump(sig, info);
-        }
+        let core_dumped = default_action(sig) == DefaultAction::Core && self.create_core_dump(sig, info);
 
-        crate::process::manager::exit_process(pid, sig as i32);
+        crate::process::manager::exit_signaled(sig, core_dumped);
     }
 
     fn stop_process(&self, sig: Signal) {
@@ -1380,8 +1395,9 @@ impl SignalState {
         crate::process::manager::continue_process(pid);
     }
 
-    fn create_core_dump(&self, sig: Signal, info: SigInfo) {
-        crate::println!("Creating core dump for signal {} from pid {}", sig, info.pid);
+    /// Write a core file for the current process; true if one was written
+    fn create_core_dump(&self, sig: Signal, info: SigInfo) -> bool {
+        crate::process::coredump::dump(sig, &info)
     }
 
     pub fn return_from_handler(&self) -> Result<(), SignalError> {
@@ -39,6 +39,8 @@ pub enum VmError {
     PageTableError,
     /// TLB错误
     TLBError,
+    /// 回写文件时I/O错误
+    IoError,
 }
 
 /// Physical memory error
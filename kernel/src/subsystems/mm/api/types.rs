@@ -62,6 +62,17 @@ impl Default for MapFlags {
     }
 }
 
+/// Memory sync flags
+/// 
+/// Define how msync flushes a file-backed mapping
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MsyncFlags {
+    /// Wait for the write back to finish (MS_SYNC)
+    pub sync: bool,
+    /// Drop cached pages so they are read from the file again (MS_INVALIDATE)
+    pub invalidate: bool,
+}
+
 /// Memory mapping object
 /// 
 /// Represent a memory mapping region
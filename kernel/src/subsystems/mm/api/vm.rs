@@ -3,8 +3,8 @@
 //! 提供虚拟内存映射和管理功能
 
 use super::error::VmError;
-use super::types::{MapFlags, MemoryProtection, MemoryMapping, MappingType};
-use crate::subsystems::mm::vm::{PageTable, map_pages, VmArea, VmPerm, PAGE_SIZE, flush_tlb_page};
+use super::types::{MapFlags, MemoryProtection, MemoryMapping, MappingType, MsyncFlags};
+use crate::subsystems::mm::vm::{PageTable, map_pages, VmAdvice, VmArea, VmLock, VmPerm, PAGE_SIZE, flush_tlb_page};
 use crate::subsystems::mm::vm::flags;
 use crate::subsystems::sync::Mutex;
 use alloc::collections::BTreeMap;
@@ -219,6 +219,10 @@ pub fn mmap(addr: usize, size: usize, prot: MemoryProtection, flags: MapFlags) -
         file_offset: 0,
         lazy: !flags.anonymous, // Only use lazy allocation for file-backed mappings
         cow: flags.private, // Private mappings are copy-on-write
+        mapping: None,
+        may_write: true,
+        advice: VmAdvice::default(),
+        lock: VmLock::None,
     };
     
     // Add VMA to VM manager
@@ -365,6 +369,10 @@ pub fn mprotect(addr: usize, size: usize, prot: MemoryProtection) -> Result<(),
                     file_offset: vma.file_offset,
                     lazy: vma.lazy,
                     cow: vma.cow,
+                    mapping: vma.mapping.clone(),
+                    may_write: vma.may_write,
+                    advice: vma.advice,
+                    lock: vma.lock,
                 })?;
             }
             
@@ -376,6 +384,10 @@ pub fn mprotect(addr: usize, size: usize, prot: MemoryProtection) -> Result<(),
                 file_offset: vma.file_offset + (protect_range.start - original_range.start),
                 lazy: vma.lazy,
                 cow: vma.cow,
+                mapping: vma.mapping.clone(),
+                may_write: vma.may_write,
+                advice: vma.advice,
+                lock: vma.lock,
             })?;
             
             if original_range.end > protect_range.end {
@@ -390,6 +402,10 @@ pub fn mprotect(addr: usize, size: usize, prot: MemoryProtection) -> Result<(),
                     file_offset: vma.file_offset + (protect_range.end - original_range.start),
                     lazy: vma.lazy,
                     cow: vma.cow,
+                    mapping: vma.mapping.clone(),
+                    may_write: vma.may_write,
+                    advice: vma.advice,
+                    lock: vma.lock,
                 })?;
             }
         }
@@ -412,9 +428,9 @@ pub fn mprotect(addr: usize, size: usize, prot: MemoryProtection) -> Result<(),
 /// 
 /// # Contract
 /// * Write dirty pages to backing store
-/// * Wait for I/O to complete
+/// * Wait for I/O to complete if `flags.sync`
 /// * Handle I/O errors
-pub fn msync(addr: usize, size: usize) -> Result<(), VmError> {
+pub fn msync(addr: usize, size: usize, flags: MsyncFlags) -> Result<(), VmError> {
     // Validate address
     if addr & (PAGE_SIZE - 1) != 0 {
         return Err(VmError::InvalidAddress);
@@ -428,10 +444,8 @@ pub fn msync(addr: usize, size: usize) -> Result<(), VmError> {
     // Align size to page boundary
     let aligned_size = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
     
-    // Get current process and VM manager
-    let pid = myproc().ok_or(VmError::InvalidAddress)?;
+    // Get VM manager
     let vm_manager = get_current_vm_manager().ok_or(VmError::InvalidAddress)?;
-    let mut vm = vm_manager.lock();
     
     // Define the range to sync
     let sync_range = Range {
@@ -439,35 +453,40 @@ pub fn msync(addr: usize, size: usize) -> Result<(), VmError> {
         end: addr + aligned_size,
     };
     
-    // Find VMAs that overlap with the range
-    let overlapping_indices = vm.find_overlapping_vmas(&sync_range);
-    
-    if overlapping_indices.is_empty() {
-        return Err(VmError::MappingNotFound);
-    }
-    
-    // For each overlapping VMA, flush dirty pages
-    for &idx in &overlapping_indices {
-        let vma = &vm.vmas[idx];
+    // Collect the file pages covered, then write them back without holding
+    // the VM manager lock
+    let mut targets = Vec::new();
+    {
+        let vm = vm_manager.lock();
+        let overlapping_indices = vm.find_overlapping_vmas(&sync_range);
+        if overlapping_indices.is_empty() {
+            return Err(VmError::MappingNotFound);
+        }
         
-        if vma.file_backed {
-            // For file-backed mappings, write dirty pages to file
-            // This is a simplified implementation
-            // In a real implementation, we would:
-            // 1. Iterate through all pages in the range
-            // 2. Check if each page is dirty
-            // 3. Write dirty pages to the backing file
-            // 4. Wait for I/O to complete
-            
-            // For now, we'll just flush the TLB
-            unsafe {
-                flush_tlb_page(vma.range.start);
+        for &idx in &overlapping_indices {
+            let vma = &vm.vmas[idx];
+            // Anonymous and private mappings have nothing to write back
+            if !vma.file_backed || vma.cow {
+                continue;
+            }
+            if let Some(mapping) = &vma.mapping {
+                let first = vma.file_index(sync_range.start.max(vma.range.start));
+                let last = vma.file_index(sync_range.end.min(vma.range.end) - 1);
+                targets.push((mapping.clone(), first, last));
             }
         }
-        // For anonymous mappings, msync is a no-op
     }
     
-    Ok(())
+    let mut result = Ok(());
+    for (mapping, first, last) in targets {
+        if mapping.sync_range(first, last, flags.sync).is_err() {
+            result = Err(VmError::IoError);
+        }
+        if flags.invalidate {
+            mapping.invalidate_range(first, last);
+        }
+    }
+    result
 }
 
 /// Get memory mapping information
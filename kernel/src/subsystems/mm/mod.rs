@@ -11,6 +11,9 @@ pub mod prefetch;
 pub mod numa;
 pub mod stats;
 pub mod memory_isolation;
+pub mod uaccess;
+pub mod swap;
+pub mod oom;
 pub mod optimized_page_allocator;
 pub mod types;
 pub mod unified_stats;
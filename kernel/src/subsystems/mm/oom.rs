@@ -0,0 +1,408 @@
+//! Out-of-memory killer
+//!
+//! When a fault finds no memory even after reclaim, or a memory cgroup
+//! goes over its limit, one process is sent SIGKILL so that the others can
+//! go on:
+//!
+//! - a process's badness is the pages it has in memory and in swap, plus
+//!   `oom_score_adj` thousandths of the memory it may use: all of RAM and
+//!   swap, or its cgroup's limit. -1000 exempts a process, 1000 puts it
+//!   first in line. A cgroup's `OomControl::oom_kill_adj` is added to the
+//!   adjustment of its processes;
+//! - a cgroup over its memory limit is the one at fault, so the victim of
+//!   a global OOM comes from it if there is one. A cgroup with
+//!   `oom_kill_disable` set loses nothing for going over its own limit,
+//!   only in a global OOM;
+//! - init and kernel threads are never chosen;
+//! - while a victim is still exiting no other is chosen, unless it has
+//!   taken longer than [`VICTIM_TIMEOUT_MS`]. Exiting processes give
+//!   their memory back before they are reaped;
+//! - each kill is reported on the kernel log along with the processes
+//!   that were considered.
+//!
+//! `/proc/<pid>/oom_score` and `oom_score_adj` show where a process stands
+//! and let it be moved.
+
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::process::{Pid, ProcState};
+use crate::subsystems::mm::vm::{self, MemUsage, PAGE_SIZE};
+use crate::subsystems::sync::Mutex;
+
+pub const OOM_SCORE_ADJ_MIN: i16 = -1000;
+pub const OOM_SCORE_ADJ_MAX: i16 = 1000;
+
+/// How long a victim may take to exit before another one is chosen
+pub const VICTIM_TIMEOUT_MS: u64 = 5000;
+
+/// Faults between two checks of a cgroup's memory usage
+const CHARGE_BATCH: usize = 32;
+
+/// Victims that haven't exited yet, with the uptime they were killed at
+pub(super) static VICTIMS: Mutex<BTreeMap<Pid, u64>> = Mutex::new(BTreeMap::new());
+static FAULTS: AtomicUsize = AtomicUsize::new(0);
+static KILLS: AtomicUsize = AtomicUsize::new(0);
+static CGROUP_KILLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Badness of a process using `pages` of the `total` it may use
+///
+/// `None` if `adj` exempts it.
+pub fn badness(pages: usize, adj: i32, total: usize) -> Option<i64> {
+    let adj = adj.clamp(OOM_SCORE_ADJ_MIN as i32, OOM_SCORE_ADJ_MAX as i32);
+    if adj == OOM_SCORE_ADJ_MIN as i32 {
+        return None;
+    }
+    Some(pages as i64 + adj as i64 * total as i64 / 1000)
+}
+
+/// `oom_score` for a badness: 0 for an exempt process, 1000 for one using
+/// nothing, up to 2000
+pub fn score(badness: Option<i64>, total: usize) -> u32 {
+    badness.map_or(0, |badness| (1000 + badness * 1000 / total.max(1) as i64).clamp(0, 2000) as u32)
+}
+
+/// Adjustment of a process with `adj` in a cgroup with `cgroup_adj`; the
+/// cgroup can't take an exemption away
+fn effective_adj(adj: i16, cgroup_adj: i32) -> i32 {
+    if adj == OOM_SCORE_ADJ_MIN {
+        return adj as i32;
+    }
+    (adj as i32 + cgroup_adj).clamp(OOM_SCORE_ADJ_MIN as i32, OOM_SCORE_ADJ_MAX as i32)
+}
+
+/// A process the killer may choose
+pub(super) struct Candidate {
+    pub(super) pid: Pid,
+    pub(super) uid: u32,
+    pub(super) name: String,
+    pub(super) cgroup: Option<String>,
+    pub(super) usage: MemUsage,
+    /// Pages mapped
+    pub(super) total_vm: usize,
+    pub(super) adj: i16,
+}
+
+impl Candidate {
+    fn pages(&self) -> usize {
+        self.usage.rss() + self.usage.swap
+    }
+}
+
+/// Memory limit and OOM settings of a cgroup
+#[derive(Debug, Clone, Copy)]
+pub(super) struct CgroupLimit {
+    /// In pages
+    pub(super) limit: Option<usize>,
+    pub(super) kill_disable: bool,
+    pub(super) adj: i32,
+}
+
+fn cgroup_limit(name: &str) -> Option<CgroupLimit> {
+    let cgroup = crate::subsystems::cloud_native::cgroups::get_v1_cgroup_manager()?.get_cgroup(name)?;
+    let cgroup = cgroup.lock();
+    let memory = cgroup.config.memory.as_ref()?;
+    Some(CgroupLimit {
+        limit: memory.limit.map(|bytes| (bytes / PAGE_SIZE as u64) as usize),
+        kill_disable: memory.oom_control.oom_kill_disable,
+        adj: memory.oom_control.oom_kill_adj.unwrap_or(0),
+    })
+}
+
+/// Processes that may be killed, only those of `cgroup` if given
+///
+/// Earlier victims are left out: killing them again wouldn't help.
+fn candidates(cgroup: Option<&str>) -> Vec<Candidate> {
+    let victims: Vec<Pid> = VICTIMS.lock().keys().copied().collect();
+    let table = crate::process::PROC_TABLE.lock();
+    table
+        .iter()
+        .filter(|proc| proc.pid > 1 && !proc.pagetable.is_null())
+        .filter(|proc| !matches!(proc.state, ProcState::Unused | ProcState::Zombie))
+        .filter(|proc| !victims.contains(&proc.pid))
+        .filter(|proc| cgroup.is_none() || proc.cgroup.as_deref() == cgroup)
+        .map(|proc| Candidate {
+            pid: proc.pid,
+            uid: proc.uid,
+            name: proc
+                .image
+                .as_ref()
+                .map(|image| String::from_utf8_lossy(image.comm()).into_owned())
+                .unwrap_or_default(),
+            cgroup: proc.cgroup.clone(),
+            usage: unsafe { vm::mem_usage(proc.pagetable, &proc.vm) },
+            total_vm: proc.vm.total_bytes() / PAGE_SIZE,
+            adj: proc.oom_score_adj,
+        })
+        .collect()
+}
+
+fn cgroup_limits(candidates: &[Candidate]) -> BTreeMap<String, CgroupLimit> {
+    let mut limits = BTreeMap::new();
+    for name in candidates.iter().filter_map(|c| c.cgroup.as_ref()) {
+        if !limits.contains_key(name)
+            && let Some(limit) = cgroup_limit(name)
+        {
+            limits.insert(name.clone(), limit);
+        }
+    }
+    limits
+}
+
+/// All of RAM and swap, in pages
+fn total_pages() -> usize {
+    let (_, ram) = crate::subsystems::mm::phys::mem_stats();
+    ram + super::swap::stats().total_pages
+}
+
+/// A victim that is still exiting and within its time
+pub(super) fn dying_victim() -> Option<Pid> {
+    let now = crate::time::uptime_ms();
+    VICTIMS
+        .lock()
+        .iter()
+        .find(|&(_, &killed_at)| now.saturating_sub(killed_at) < VICTIM_TIMEOUT_MS)
+        .map(|(&pid, _)| pid)
+}
+
+/// The worst of `candidates` against `total` pages, if any is killable
+pub(super) fn choose<'a>(
+    candidates: impl Iterator<Item = &'a Candidate>,
+    limits: &BTreeMap<String, CgroupLimit>,
+    total: usize,
+) -> Option<&'a Candidate> {
+    candidates
+        .filter_map(|c| {
+            let cgroup_adj = c.cgroup.as_ref().and_then(|name| limits.get(name)).map_or(0, |limit| limit.adj);
+            badness(c.pages(), effective_adj(c.adj, cgroup_adj), total).map(|badness| (c, badness))
+        })
+        .max_by_key(|&(_, badness)| badness)
+        .map(|(c, _)| c)
+}
+
+fn kb(pages: usize) -> usize {
+    pages * PAGE_SIZE / 1024
+}
+
+/// Log why the killer ran, what it looked at and whom it killed
+fn report<'a>(reason: &str, candidates: impl Iterator<Item = &'a Candidate>, victim: &Candidate) {
+    crate::println!("[oom] {}", reason);
+    crate::println!("[oom] [  pid ]   uid  total_vm      rss     swap oom_score_adj name");
+    for c in candidates {
+        crate::println!(
+            "[oom] [{:>6}] {:>5} {:>9} {:>8} {:>8} {:>13} {}",
+            c.pid,
+            c.uid,
+            c.total_vm,
+            c.usage.rss(),
+            c.usage.swap,
+            c.adj,
+            c.name
+        );
+    }
+    crate::println!(
+        "[oom] Killed process {} ({}) total-vm:{}kB, anon-rss:{}kB, file-rss:{}kB, swap:{}kB, UID:{} oom_score_adj:{}",
+        victim.pid,
+        victim.name,
+        kb(victim.total_vm),
+        kb(victim.usage.anon),
+        kb(victim.usage.file),
+        kb(victim.usage.swap),
+        victim.uid,
+        victim.adj
+    );
+}
+
+fn kill(victim: Pid) {
+    VICTIMS.lock().insert(victim, crate::time::uptime_ms());
+    let _ = crate::process::kill_proc(victim, crate::ipc::signal::SIGKILL);
+    KILLS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Pages the processes of `name` use, and its limit if it is over it and
+/// may lose a process for that
+pub(super) fn over_limit(name: &str, candidates: &[Candidate], limits: &BTreeMap<String, CgroupLimit>) -> Option<(usize, usize)> {
+    let limit = limits.get(name).filter(|limit| !limit.kill_disable)?.limit?;
+    let usage = candidates.iter().filter(|c| c.cgroup.as_deref() == Some(name)).map(Candidate::pages).sum();
+    (usage > limit).then_some((usage, limit))
+}
+
+/// The worst process of `name` if the cgroup is over its limit, along
+/// with its usage and limit
+fn cgroup_victim<'a>(
+    name: &str,
+    candidates: &'a [Candidate],
+    limits: &BTreeMap<String, CgroupLimit>,
+) -> Option<(&'a Candidate, usize, usize)> {
+    let (usage, limit) = over_limit(name, candidates, limits)?;
+    let members = candidates.iter().filter(|c| c.cgroup.as_deref() == Some(name));
+    Some((choose(members, limits, limit)?, usage, limit))
+}
+
+/// Kill `victim` of `name`, a cgroup using `usage` pages of its `limit`
+fn cgroup_kill(name: &str, candidates: &[Candidate], victim: &Candidate, usage: usize, limit: usize) {
+    report(
+        &alloc::format!("memory cgroup {} over its limit: usage {} kB, limit {} kB", name, kb(usage), kb(limit)),
+        candidates.iter().filter(|c| c.cgroup.as_deref() == Some(name)),
+        victim,
+    );
+    kill(victim.pid);
+    CGROUP_KILLS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Kill the worst process of `name`, which is over its limit
+fn cgroup_out_of_memory(name: &str, candidates: &[Candidate], limits: &BTreeMap<String, CgroupLimit>) -> Option<Pid> {
+    let (victim, usage, limit) = cgroup_victim(name, candidates, limits)?;
+    cgroup_kill(name, candidates, victim, usage, limit);
+    Some(victim.pid)
+}
+
+/// The victim of a global OOM against `total` pages
+///
+/// A cgroup over its limit is to blame before anyone else, so the worst
+/// process of the first one comes before the worst of all; it is given
+/// with its cgroup's name, usage and limit.
+pub(super) fn global_victim<'a>(
+    candidates: &'a [Candidate],
+    limits: &'a BTreeMap<String, CgroupLimit>,
+    total: usize,
+) -> Option<(&'a Candidate, Option<(&'a str, usize, usize)>)> {
+    limits
+        .keys()
+        .find_map(|name| {
+            let (victim, usage, limit) = cgroup_victim(name, candidates, limits)?;
+            Some((victim, Some((name.as_str(), usage, limit))))
+        })
+        .or_else(|| Some((choose(candidates.iter(), limits, total)?, None)))
+}
+
+/// Deal with a fault of `pid` that found no memory even after reclaim
+///
+/// Returns true when a process other than `pid` is on its way out, so
+/// that the fault is worth retrying once it is gone; false when `pid`
+/// has to go itself.
+pub fn out_of_memory(pid: Pid) -> bool {
+    if let Some(victim) = dying_victim() {
+        return victim != pid;
+    }
+    let candidates = candidates(None);
+    let limits = cgroup_limits(&candidates);
+    let reason = || {
+        let (free, ram) = crate::subsystems::mm::phys::mem_stats();
+        let swap = super::swap::stats();
+        alloc::format!(
+            "pid {} out of memory: {} of {} pages free, swap {} of {} pages free",
+            pid,
+            free,
+            ram,
+            swap.free_pages,
+            swap.total_pages
+        )
+    };
+    let Some((victim, cgroup)) = global_victim(&candidates, &limits, total_pages()) else {
+        crate::println!("[oom] {}, and no process can be killed", reason());
+        return false;
+    };
+    match cgroup {
+        Some((name, usage, limit)) => cgroup_kill(name, &candidates, victim, usage, limit),
+        None => {
+            report(&reason(), candidates.iter(), victim);
+            kill(victim.pid);
+        }
+    }
+    victim.pid != pid
+}
+
+/// Check the memory cgroup of `pid` after a fault gave it a page
+///
+/// Only every [`CHARGE_BATCH`]th fault looks at the cgroup's usage, so a
+/// cgroup can go a little past its limit before one of its processes is
+/// killed.
+pub fn charge_fault(pid: Pid) {
+    if !FAULTS.fetch_add(1, Ordering::Relaxed).is_multiple_of(CHARGE_BATCH) || dying_victim().is_some() {
+        return;
+    }
+    let cgroup = crate::process::PROC_TABLE.lock().find_ref(pid).and_then(|proc| proc.cgroup.clone());
+    let Some(name) = cgroup else { return };
+    let Some(limit) = cgroup_limit(&name) else { return };
+    if limit.limit.is_none() {
+        return;
+    }
+    let candidates = candidates(Some(&name));
+    let limits = BTreeMap::from([(name.clone(), limit)]);
+    cgroup_out_of_memory(&name, &candidates, &limits);
+}
+
+/// `oom_score` of `pid`, 0 to 2000
+pub fn oom_score(pid: Pid) -> Option<u32> {
+    let (usage, adj, cgroup) = {
+        let table = crate::process::PROC_TABLE.lock();
+        let proc = table.find_ref(pid).filter(|proc| !proc.pagetable.is_null())?;
+        (unsafe { vm::mem_usage(proc.pagetable, &proc.vm) }, proc.oom_score_adj, proc.cgroup.clone())
+    };
+    let limit = cgroup.as_deref().and_then(cgroup_limit);
+    let total = limit.and_then(|limit| limit.limit).unwrap_or_else(total_pages);
+    let adj = effective_adj(adj, limit.map_or(0, |limit| limit.adj));
+    Some(score(badness(usage.rss() + usage.swap, adj, total), total))
+}
+
+pub fn oom_score_adj(pid: Pid) -> Option<i16> {
+    crate::process::PROC_TABLE.lock().find_ref(pid).map(|proc| proc.oom_score_adj)
+}
+
+/// Errors setting `oom_score_adj`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OomAdjError {
+    /// Outside -1000 to 1000
+    Invalid,
+    /// Lowering it, or changing another user's process, without
+    /// CAP_SYS_RESOURCE
+    PermissionDenied,
+    NotFound,
+}
+
+/// Set the `oom_score_adj` of `pid` on behalf of `caller`
+pub fn set_oom_score_adj(pid: Pid, adj: i32, caller: Pid) -> Result<(), OomAdjError> {
+    if !(OOM_SCORE_ADJ_MIN as i32..=OOM_SCORE_ADJ_MAX as i32).contains(&adj) {
+        return Err(OomAdjError::Invalid);
+    }
+    let (caller_euid, uid, current) = {
+        let table = crate::process::PROC_TABLE.lock();
+        let caller_euid = table.find_ref(caller).ok_or(OomAdjError::NotFound)?.euid;
+        let proc = table.find_ref(pid).ok_or(OomAdjError::NotFound)?;
+        (caller_euid, proc.uid, proc.oom_score_adj)
+    };
+    let privileged = || {
+        crate::security::capabilities::capable(
+            caller as u64,
+            caller_euid,
+            crate::security::capabilities::Capability::SysResource,
+        )
+    };
+    if (adj < current as i32 || (caller_euid != uid && caller_euid != 0)) && !privileged() {
+        return Err(OomAdjError::PermissionDenied);
+    }
+    let mut table = crate::process::PROC_TABLE.lock();
+    let proc = table.find(pid).ok_or(OomAdjError::NotFound)?;
+    proc.oom_score_adj = adj as i16;
+    Ok(())
+}
+
+/// Forget `pid` as a victim; it is exiting
+pub fn exit_task(pid: Pid) {
+    VICTIMS.lock().remove(&pid);
+}
+
+/// Kills since boot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OomStats {
+    pub kills: usize,
+    /// Of those, for a cgroup over its limit
+    pub cgroup_kills: usize,
+}
+
+pub fn stats() -> OomStats {
+    OomStats { kills: KILLS.load(Ordering::Relaxed), cgroup_kills: CGROUP_KILLS.load(Ordering::Relaxed) }
+}
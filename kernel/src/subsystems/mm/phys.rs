@@ -340,8 +340,16 @@ pub fn init() {
 
 /// Allocate a single physical page (4KB)
 /// Returns null pointer on failure
+///
+/// Wakes kswapd when free memory falls below its low watermark.
 pub fn kalloc() -> *mut u8 {
-    PAGE_ALLOCATOR.lock().alloc_page()
+    let (page, free) = {
+        let mut alloc = PAGE_ALLOCATOR.lock();
+        let page = alloc.alloc_page();
+        (page, alloc.free_pages())
+    };
+    crate::subsystems::mm::swap::reclaim::note_free_pages(free);
+    page
 }
 
 /// Free a physical page
@@ -378,16 +386,31 @@ pub fn kalloc_pages(count: usize) -> *mut u8 {
     ptr::null_mut()
 }
 
+/// Free pages allocated together by `kalloc_pages`
+/// # Safety
+/// `page` must have been returned by `kalloc_pages(count)`
+pub unsafe fn kfree_pages(page: *mut u8, count: usize) {
+    if page.is_null() || count == 0 { return; }
+    if count == 1 {
+        unsafe { kfree(page) };
+        return;
+    }
+    use core::alloc::Layout;
+    let layout = Layout::from_size_align(count * PAGE_SIZE, PAGE_SIZE).unwrap();
+    BUDDY.lock().dealloc(page, layout);
+}
+
 pub fn mmio_regions() -> Vec<(usize, usize)> {
     let mut regions = Vec::new();
+    // QEMU virt layout, only needed when no device tree describes the machine
     #[cfg(target_arch = "riscv64")]
-    {
+    if !crate::drivers::platform::dtb_present() {
         regions.push((0x1000_0000, 0x0001_0000)); // UART
         regions.push((0x0200_0000, 0x000C_0000)); // CLINT
         regions.push((0x0C00_0000, 0x0040_0000)); // PLIC
     }
     #[cfg(target_arch = "aarch64")]
-    {
+    if !crate::drivers::platform::dtb_present() {
         regions.push((0x0900_0000, 0x0002_0000)); // PL011 UART
         regions.push((0x0800_0000, 0x0020_0000)); // GIC (approx)
     }
@@ -402,7 +425,7 @@ pub fn mmio_regions() -> Vec<(usize, usize)> {
 pub fn mmio_regions_strong() -> Vec<(usize, usize)> {
     let mut regions = Vec::new();
     #[cfg(target_arch = "aarch64")]
-    {
+    if !crate::drivers::platform::dtb_present() {
         regions.push((0x0800_0000, 0x0020_0000));
     }
     let dynamic = DYNAMIC_MMIO_STRONG.lock();
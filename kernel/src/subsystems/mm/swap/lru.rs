@@ -0,0 +1,200 @@
+//! Reclaim candidates
+//!
+//! Anonymous pages and page cache pages are kept on separate LRUs, each an
+//! active and an inactive list. Pages are added once, when they are first
+//! mapped or read in, and are only looked up again by reclaim: an entry
+//! names the page (process and address, or mapping and index) rather than
+//! pinning it, and one whose page has gone is dropped when reclaim gets
+//! to it.
+//!
+//! New anonymous pages start active, new cache pages inactive, so that a
+//! file read once is the first thing to go. Reclaim ages the active lists
+//! into the inactive ones and takes the inactive pages not used since.
+//!
+//! Reclaim sets aside the pages it finds mlocked on a third list that it
+//! never looks at, and munlock or munmap puts them back on the active
+//! list.
+
+extern crate alloc;
+
+use alloc::{collections::{BTreeSet, VecDeque}, sync::Weak};
+use core::{cmp::Ordering, ops::Range};
+
+use crate::process::Pid;
+use crate::subsystems::sync::Mutex;
+use crate::vfs::page_cache::AddressSpace;
+
+/// A private page of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AnonPage {
+    pub pid: Pid,
+    pub va: usize,
+}
+
+/// A page cache page
+///
+/// The weak reference keeps the mapping's allocation, so its address
+/// identifies it for as long as the entry exists.
+#[derive(Debug, Clone)]
+pub struct FilePage {
+    pub mapping: Weak<AddressSpace>,
+    pub index: u64,
+}
+
+impl FilePage {
+    fn key(&self) -> (usize, u64) {
+        (self.mapping.as_ptr() as usize, self.index)
+    }
+}
+
+impl PartialEq for FilePage {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for FilePage {}
+
+impl PartialOrd for FilePage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FilePage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Active and inactive list of one kind of page, oldest first, and the
+/// pages reclaim must leave alone
+pub struct Lru<T> {
+    active: VecDeque<T>,
+    inactive: VecDeque<T>,
+    unevictable: VecDeque<T>,
+    /// Pages on any list, to add each only once
+    members: BTreeSet<T>,
+}
+
+impl<T: Ord + Clone> Lru<T> {
+    pub const fn new() -> Self {
+        Self {
+            active: VecDeque::new(),
+            inactive: VecDeque::new(),
+            unevictable: VecDeque::new(),
+            members: BTreeSet::new(),
+        }
+    }
+
+    /// Add `page` at the young end of a list, unless it is on one already
+    pub fn add(&mut self, page: T, active: bool) {
+        if !self.members.insert(page.clone()) {
+            return;
+        }
+        if active {
+            self.active.push_back(page);
+        } else {
+            self.inactive.push_back(page);
+        }
+    }
+
+    /// Set `page` aside where reclaim does not look, unless it is on a
+    /// list already
+    pub fn add_unevictable(&mut self, page: T) {
+        if self.members.insert(page.clone()) {
+            self.unevictable.push_back(page);
+        }
+    }
+
+    /// Move the unevictable pages `evictable` picks to the active list
+    pub fn putback_unevictable(&mut self, mut evictable: impl FnMut(&T) -> bool) {
+        let (back, stay) = core::mem::take(&mut self.unevictable).into_iter().partition(|page| evictable(page));
+        self.unevictable = stay;
+        self.active.extend(back);
+    }
+
+    /// Take the oldest inactive page off the lists
+    pub fn pop_inactive(&mut self) -> Option<T> {
+        let page = self.inactive.pop_front()?;
+        self.members.remove(&page);
+        Some(page)
+    }
+
+    /// Move the oldest active page to the inactive list
+    pub fn deactivate_one(&mut self) -> bool {
+        let Some(page) = self.active.pop_front() else { return false };
+        self.inactive.push_back(page);
+        true
+    }
+
+    pub fn len_active(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn len_inactive(&self) -> usize {
+        self.inactive.len()
+    }
+
+    pub fn len_unevictable(&self) -> usize {
+        self.unevictable.len()
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        self.active.retain(&mut keep);
+        self.inactive.retain(&mut keep);
+        self.unevictable.retain(&mut keep);
+        self.members.retain(keep);
+    }
+}
+
+impl<T: Ord + Clone> Default for Lru<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(super) static ANON: Mutex<Lru<AnonPage>> = Mutex::new(Lru::new());
+pub(super) static FILE: Mutex<Lru<FilePage>> = Mutex::new(Lru::new());
+
+/// Note a private page `pid` has mapped at `va`
+pub fn add_anon(pid: Pid, va: usize) {
+    ANON.lock().add(AnonPage { pid, va }, true);
+}
+
+/// Note page `index` read into `mapping`
+pub fn add_file(mapping: &Weak<AddressSpace>, index: u64) {
+    FILE.lock().add(FilePage { mapping: mapping.clone(), index }, false);
+}
+
+/// Put the unevictable pages `pid` had in `range` back where reclaim
+/// looks, after munlock or munmap
+pub fn putback_anon(pid: Pid, range: Range<usize>) {
+    ANON.lock().putback_unevictable(|page| page.pid == pid && range.contains(&page.va));
+}
+
+/// Drop the pages of `pid`, which is exiting or replaced its image
+pub fn forget_pid(pid: Pid) {
+    ANON.lock().retain(|page| page.pid != pid);
+}
+
+/// Sizes of the lists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LruSizes {
+    pub active_anon: usize,
+    pub inactive_anon: usize,
+    pub unevictable_anon: usize,
+    pub active_file: usize,
+    pub inactive_file: usize,
+}
+
+pub fn sizes() -> LruSizes {
+    let (anon, file) = (ANON.lock(), FILE.lock());
+    LruSizes {
+        active_anon: anon.len_active(),
+        inactive_anon: anon.len_inactive(),
+        unevictable_anon: anon.len_unevictable(),
+        active_file: file.len_active(),
+        inactive_file: file.len_inactive(),
+    }
+}
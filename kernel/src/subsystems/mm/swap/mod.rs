@@ -0,0 +1,627 @@
+//! Swap
+//!
+//! Anonymous pages that reclaim takes from a process are written to a swap
+//! area, and the page table entries that mapped them hold swap entries
+//! until the next access brings them back:
+//!
+//! - a [`SwapEntry`] names an area and a slot in it. It is kept in a page
+//!   table entry with `PTE_V` clear and `PTE_SWAP` set;
+//! - areas are block devices (`/dev/<disk>`) or regular files given to
+//!   `swapon`, used highest priority first. Slot 0 of each area is left
+//!   alone as its header, so old contents there survive;
+//! - each slot counts the page table entries holding it, so that fork can
+//!   share a swapped-out page; the last [`free`] gives the slot back;
+//! - a page on its way out sits in the swap cache until its write is done,
+//!   and a fault meanwhile copies it from there. A write that fails leaves
+//!   the page in the cache for good rather than lose it;
+//! - with [`zswap`] on, pages are compressed into a memory pool first and
+//!   only written to the area when they don't compress or the pool is
+//!   full. With no area configured, zswap adds one of its own that lives
+//!   only in the pool.
+//!
+//! [`lru`] keeps the reclaim candidates; [`reclaim`] has kswapd and the
+//! direct reclaim done by faults that run out of memory.
+//!
+//! Lock order: the process table, then the swap state, then the zswap
+//! pool. Area I/O is done with none of them held.
+
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::subsystems::block::RequestQueue;
+use crate::subsystems::mm::vm::{self, flags, page_put, PageTable, VmArea, PAGE_SIZE};
+use crate::subsystems::sync::Mutex;
+use crate::vfs::InodeOps;
+
+pub mod lru;
+pub mod reclaim;
+pub mod zswap;
+
+#[cfg(feature = "kernel_tests")]
+pub mod tests;
+
+/// Areas that can be active at once, including zswap's own
+pub const MAX_SWAPFILES: usize = 32;
+
+/// `swapon` flags, as in Linux
+pub const SWAP_FLAG_PREFER: u32 = 0x8000;
+pub const SWAP_FLAG_PRIO_MASK: u32 = 0x7fff;
+pub const SWAP_FLAG_DISCARD: u32 = 0x10000;
+
+/// Slot map bit: the page is in the swap cache
+const SWAP_HAS_CACHE: u16 = 0x8000;
+/// Most page table entries a slot counts; one more keeps it for good
+const SWAP_MAP_MAX: u16 = 0x7fff;
+
+/// A swapped-out page: swap area and slot in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SwapEntry(usize);
+
+impl SwapEntry {
+    const AREA_BITS: usize = 5;
+    const PTE_SHIFT: usize = 12;
+
+    pub fn new(area: usize, slot: usize) -> Self {
+        debug_assert!(area < MAX_SWAPFILES);
+        Self(slot << Self::AREA_BITS | area)
+    }
+
+    pub fn area(&self) -> usize {
+        self.0 & ((1 << Self::AREA_BITS) - 1)
+    }
+
+    pub fn slot(&self) -> usize {
+        self.0 >> Self::AREA_BITS
+    }
+
+    /// Page table entry standing in for the page
+    pub fn to_pte(self) -> usize {
+        self.0 << Self::PTE_SHIFT | flags::PTE_SWAP
+    }
+
+    /// Swap entry held by page table entry `pte`, if it holds one
+    pub fn from_pte(pte: usize) -> Option<Self> {
+        (pte & (flags::PTE_V | flags::PTE_SWAP) == flags::PTE_SWAP).then_some(Self(pte >> Self::PTE_SHIFT))
+    }
+}
+
+/// Swap errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapError {
+    /// No free slot, or nowhere to put a page that doesn't compress
+    NoSpace,
+    /// Reading or writing the area failed
+    Io,
+    /// No memory to bring the pages of an area back in
+    NoMemory,
+    /// Already an active area, or every area number is taken
+    Busy,
+    /// Not an active area
+    NotFound,
+    /// Too small to hold a page, or neither a disk nor a regular file
+    Invalid,
+}
+
+/// Where an area keeps its pages
+#[derive(Clone)]
+enum Backing {
+    Block(Arc<RequestQueue>),
+    File(Arc<dyn InodeOps>),
+    /// Only the zswap pool
+    Memory,
+}
+
+impl Backing {
+    fn read(&self, slot: usize, buf: &mut [u8]) -> Result<(), SwapError> {
+        let offset = (slot * PAGE_SIZE) as u64;
+        match self {
+            Backing::Block(queue) => queue.read_at(offset, buf).map_err(|_| SwapError::Io),
+            Backing::File(inode) => match inode.read(offset, buf) {
+                Ok(n) if n == buf.len() => Ok(()),
+                _ => Err(SwapError::Io),
+            },
+            Backing::Memory => Err(SwapError::Io),
+        }
+    }
+
+    /// Write a page to `slot`
+    ///
+    /// A swap file's page cache doesn't keep a copy: holding on to the
+    /// memory being freed would defeat the point.
+    fn write(&self, slot: usize, buf: &[u8]) -> Result<(), SwapError> {
+        let offset = (slot * PAGE_SIZE) as u64;
+        match self {
+            Backing::Block(queue) => queue.write_at(offset, buf, 0).map_err(|_| SwapError::Io),
+            Backing::File(inode) => {
+                if inode.write(offset, buf).map_err(|_| SwapError::Io)? != buf.len() {
+                    return Err(SwapError::Io);
+                }
+                if let Some(mapping) = inode.mapping() {
+                    let index = slot as u64;
+                    mapping.sync_range(index, index, true).map_err(|_| SwapError::Io)?;
+                    mapping.invalidate_range(index, index);
+                }
+                Ok(())
+            }
+            Backing::Memory => Err(SwapError::NoSpace),
+        }
+    }
+}
+
+struct SwapArea {
+    /// Path given to swapon
+    path: String,
+    backing: Backing,
+    priority: i32,
+    /// Page table entries holding each slot, with `SWAP_HAS_CACHE` while
+    /// the page is in the swap cache
+    map: Vec<u16>,
+    /// Slots in use
+    inuse: usize,
+    /// Where the search for a free slot starts
+    cursor: usize,
+    /// No new slots: swapoff is bringing the pages in, or swapon is still
+    /// discarding the area
+    draining: bool,
+}
+
+impl SwapArea {
+    fn new(path: String, backing: Backing, pages: usize, priority: i32) -> Self {
+        Self { path, backing, priority, map: vec![0; pages], inuse: 0, cursor: 1, draining: false }
+    }
+
+    /// Slots that can hold pages, all but the header
+    fn nr_slots(&self) -> usize {
+        self.map.len() - 1
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        if self.draining || self.inuse == self.nr_slots() {
+            return None;
+        }
+        let slots = self.nr_slots();
+        let slot = (0..slots)
+            .map(|i| (self.cursor - 1 + i) % slots + 1)
+            .find(|&slot| self.map[slot] == 0)?;
+        self.map[slot] = 1 | SWAP_HAS_CACHE;
+        self.inuse += 1;
+        self.cursor = slot % slots + 1;
+        Some(slot)
+    }
+}
+
+/// A page in the swap cache
+struct CachedSwapPage {
+    frame: usize,
+    /// Being written: the frame must stay until the write is done
+    writing: bool,
+}
+
+struct SwapState {
+    /// Active areas by number
+    areas: Vec<Option<SwapArea>>,
+    cache: BTreeMap<SwapEntry, CachedSwapPage>,
+    /// Priority of the next area swapon isn't given one for
+    next_priority: i32,
+}
+
+impl SwapState {
+    fn area(&self, entry: SwapEntry) -> Option<&SwapArea> {
+        self.areas.get(entry.area())?.as_ref()
+    }
+
+    fn area_mut(&mut self, entry: SwapEntry) -> Option<&mut SwapArea> {
+        self.areas.get_mut(entry.area())?.as_mut()
+    }
+
+    /// Give `entry`'s slot back once neither page table entries nor the
+    /// swap cache hold it
+    fn release_if_unused(&mut self, entry: SwapEntry) {
+        let Some(area) = self.area_mut(entry) else { return };
+        if area.map[entry.slot()] != 0 {
+            return;
+        }
+        area.inuse -= 1;
+        zswap::invalidate(entry);
+    }
+
+    /// Drop `entry`'s page from the swap cache, unless it is being written
+    fn drop_cached(&mut self, entry: SwapEntry) {
+        if self.cache.get(&entry).is_none_or(|page| page.writing) {
+            return;
+        }
+        if let Some(page) = self.cache.remove(&entry) {
+            page_put(page.frame);
+        }
+        if let Some(area) = self.area_mut(entry) {
+            area.map[entry.slot()] &= !SWAP_HAS_CACHE;
+        }
+    }
+}
+
+static SWAP: Mutex<SwapState> = Mutex::new(SwapState {
+    areas: Vec::new(),
+    cache: BTreeMap::new(),
+    next_priority: -1,
+});
+
+static PSWPIN: AtomicUsize = AtomicUsize::new(0);
+static PSWPOUT: AtomicUsize = AtomicUsize::new(0);
+
+/// An active swap area, as listed in /proc/swaps
+#[derive(Debug, Clone)]
+pub struct SwapInfo {
+    pub path: String,
+    pub kind: &'static str,
+    /// Size in pages
+    pub pages: usize,
+    /// Pages in use
+    pub used: usize,
+    pub priority: i32,
+}
+
+/// Swap use across all areas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwapStats {
+    pub total_pages: usize,
+    pub free_pages: usize,
+    /// Pages read back in and written out since boot
+    pub pswpin: usize,
+    pub pswpout: usize,
+}
+
+pub fn stats() -> SwapStats {
+    let state = SWAP.lock();
+    let mut stats = SwapStats {
+        pswpin: PSWPIN.load(Ordering::Relaxed),
+        pswpout: PSWPOUT.load(Ordering::Relaxed),
+        ..SwapStats::default()
+    };
+    for area in state.areas.iter().flatten() {
+        stats.total_pages += area.nr_slots();
+        stats.free_pages += area.nr_slots() - area.inuse;
+    }
+    stats
+}
+
+pub fn areas() -> Vec<SwapInfo> {
+    SWAP.lock()
+        .areas
+        .iter()
+        .flatten()
+        .map(|area| SwapInfo {
+            path: area.path.clone(),
+            kind: match area.backing {
+                Backing::Block(_) => "partition",
+                Backing::File(_) => "file",
+                Backing::Memory => "zswap",
+            },
+            pages: area.nr_slots(),
+            used: area.inuse,
+            priority: area.priority,
+        })
+        .collect()
+}
+
+/// Whether some area has a free slot, so that anonymous pages are worth
+/// scanning
+pub fn can_swap() -> bool {
+    SWAP.lock()
+        .areas
+        .iter()
+        .flatten()
+        .any(|area| !area.draining && area.inuse < area.nr_slots())
+}
+
+/// Give the page in `frame` a swap slot and put it in the swap cache
+///
+/// The highest priority area with room is used, spreading pages over
+/// areas of equal priority. The caller replaces the page's one mapping by the entry and
+/// then calls [`write_page`].
+pub fn add_to_swap(frame: usize) -> Option<SwapEntry> {
+    let mut state = SWAP.lock();
+    let best = state
+        .areas
+        .iter()
+        .flatten()
+        .filter(|area| !area.draining && area.inuse < area.nr_slots())
+        .map(|area| area.priority)
+        .max()?;
+    let (index, area) = state
+        .areas
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(index, area)| Some((index, area.as_mut()?)))
+        .filter(|(_, area)| area.priority == best)
+        .min_by_key(|(_, area)| area.inuse)?;
+    let entry = SwapEntry::new(index, area.alloc()?);
+    state.cache.insert(entry, CachedSwapPage { frame, writing: true });
+    Some(entry)
+}
+
+/// Write the page of `entry`, which [`add_to_swap`] put in the swap cache,
+/// and drop it from the cache
+///
+/// On failure the page stays in the cache, where faults find it.
+pub fn write_page(entry: SwapEntry) -> Result<(), SwapError> {
+    let (frame, backing) = {
+        let state = SWAP.lock();
+        let page = state.cache.get(&entry).ok_or(SwapError::NotFound)?;
+        let area = state.area(entry).ok_or(SwapError::NotFound)?;
+        (page.frame, area.backing.clone())
+    };
+    let data = unsafe { core::slice::from_raw_parts(frame as *const u8, PAGE_SIZE) };
+    let result = if zswap::store(entry, data) { Ok(()) } else { backing.write(entry.slot(), data) };
+
+    let mut state = SWAP.lock();
+    if let Some(page) = state.cache.get_mut(&entry) {
+        page.writing = false;
+    }
+    // Freed meanwhile, or its area turned off
+    let unused = state.area(entry).is_none_or(|area| area.map[entry.slot()] & !SWAP_HAS_CACHE == 0);
+    if result.is_ok() || unused {
+        state.drop_cached(entry);
+        state.release_if_unused(entry);
+    }
+    if result.is_ok() {
+        PSWPOUT.fetch_add(1, Ordering::Relaxed);
+    }
+    result
+}
+
+/// Claim `entry`'s page for another [`write_page`] after one failed
+///
+/// False unless the page is in the swap cache and no write of it is under
+/// way.
+pub fn retry_write(entry: SwapEntry) -> bool {
+    let mut state = SWAP.lock();
+    match state.cache.get_mut(&entry) {
+        Some(page) if !page.writing => {
+            page.writing = true;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Read the page of `entry` into `frame`
+///
+/// From the swap cache if it is still there, then from zswap, then from
+/// the area.
+pub fn read_page(entry: SwapEntry, frame: usize) -> Result<(), SwapError> {
+    let buf = unsafe { core::slice::from_raw_parts_mut(frame as *mut u8, PAGE_SIZE) };
+    let backing = {
+        let state = SWAP.lock();
+        if let Some(page) = state.cache.get(&entry) {
+            unsafe { core::ptr::copy_nonoverlapping(page.frame as *const u8, buf.as_mut_ptr(), PAGE_SIZE) };
+            return Ok(());
+        }
+        state.area(entry).ok_or(SwapError::NotFound)?.backing.clone()
+    };
+    match zswap::load(entry, buf) {
+        Some(result) => result?,
+        None => backing.read(entry.slot(), buf)?,
+    }
+    PSWPIN.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Count another page table entry holding `entry` (fork)
+///
+/// A slot counted `SWAP_MAP_MAX` times is never freed.
+pub fn dup(entry: SwapEntry) {
+    let mut state = SWAP.lock();
+    let Some(area) = state.area_mut(entry) else { return };
+    let count = &mut area.map[entry.slot()];
+    if *count & !SWAP_HAS_CACHE < SWAP_MAP_MAX {
+        *count += 1;
+    }
+}
+
+/// Drop a page table entry's hold on `entry`
+///
+/// The last one frees the slot, and the page with it.
+pub fn free(entry: SwapEntry) {
+    let mut state = SWAP.lock();
+    let Some(area) = state.area_mut(entry) else { return };
+    let count = &mut area.map[entry.slot()];
+    match *count & !SWAP_HAS_CACHE {
+        0 | SWAP_MAP_MAX => return,
+        _ => *count -= 1,
+    }
+    if *count & !SWAP_HAS_CACHE == 0 {
+        state.drop_cached(entry);
+        state.release_if_unused(entry);
+    }
+}
+
+/// Make `path` a swap area (swapon)
+///
+/// `/dev/<disk>` names a registered block device; anything else is opened
+/// as a regular file relative to the calling process. The page-sized
+/// slots after the first hold pages; what was there is overwritten.
+/// `flags` may give a priority (`SWAP_FLAG_PREFER`) and ask for the area
+/// to be discarded first (`SWAP_FLAG_DISCARD`).
+pub fn swapon(path: &str, flags: u32) -> Result<(), SwapError> {
+    let disk = path.strip_prefix("/dev/").and_then(crate::subsystems::block::get_disk);
+    let discard = disk.clone().filter(|_| flags & SWAP_FLAG_DISCARD != 0);
+    let (backing, pages) = match disk {
+        Some(queue) => {
+            let pages = (queue.nr_sectors() * queue.sector_size() as u64 / PAGE_SIZE as u64) as usize;
+            (Backing::Block(queue), pages)
+        }
+        None => {
+            let ctx = crate::process::fs_context().map_err(|_| SwapError::NotFound)?;
+            let lookup = crate::vfs::LookupFlags(crate::vfs::LookupFlags::FOLLOW);
+            let file = crate::vfs::vfs()
+                .open_at(&ctx, None, path, lookup, crate::posix::O_RDWR as u32)
+                .map_err(|_| SwapError::NotFound)?;
+            let attr = file.inode.getattr().map_err(|_| SwapError::Io)?;
+            if !attr.mode.is_regular() {
+                return Err(SwapError::Invalid);
+            }
+            (Backing::File(file.inode), (attr.size / PAGE_SIZE as u64) as usize)
+        }
+    };
+    if pages < 2 {
+        return Err(SwapError::Invalid);
+    }
+    let priority = (flags & SWAP_FLAG_PREFER != 0).then_some((flags & SWAP_FLAG_PRIO_MASK) as i32);
+    // An area being discarded is added draining, so that no slot is used
+    // before the discard is done
+    let (index, priority) = add_area(String::from(path), backing, pages, priority, discard.is_some())?;
+    if let Some(queue) = discard {
+        // Everything but the header
+        let first = (PAGE_SIZE / queue.sector_size()) as u64;
+        let _ = queue.discard(first, queue.nr_sectors().saturating_sub(first).min(u32::MAX as u64) as u32);
+        if let Some(area) = SWAP.lock().areas[index].as_mut() {
+            area.draining = false;
+        }
+    }
+    crate::println!("swap: {}: {} pages, priority {}", path, pages - 1, priority);
+    Ok(())
+}
+
+/// Add an area, returning its number and priority
+///
+/// A `draining` area gives out no slots until that is cleared.
+fn add_area(
+    path: String,
+    backing: Backing,
+    pages: usize,
+    priority: Option<i32>,
+    draining: bool,
+) -> Result<(usize, i32), SwapError> {
+    let mut state = SWAP.lock();
+    if state.areas.iter().flatten().any(|area| area.path == path) {
+        return Err(SwapError::Busy);
+    }
+    let index = match state.areas.iter().position(Option::is_none) {
+        Some(index) => index,
+        None if state.areas.len() < MAX_SWAPFILES => {
+            state.areas.push(None);
+            state.areas.len() - 1
+        }
+        None => return Err(SwapError::Busy),
+    };
+    let priority = match priority {
+        Some(priority) => priority,
+        None => {
+            state.next_priority -= 1;
+            state.next_priority + 1
+        }
+    };
+    let mut area = SwapArea::new(path, backing, pages, priority);
+    area.draining = draining;
+    state.areas[index] = Some(area);
+    Ok((index, priority))
+}
+
+/// Stop swapping to `path` (swapoff)
+///
+/// Every page in the area is read back in first. Without the memory for
+/// that, the area stays active and the call fails with `NoMemory`.
+pub fn swapoff(path: &str) -> Result<(), SwapError> {
+    let index = {
+        let mut state = SWAP.lock();
+        let index = state
+            .areas
+            .iter()
+            .position(|area| area.as_ref().is_some_and(|area| area.path == path && !area.draining))
+            .ok_or(SwapError::NotFound)?;
+        if let Some(area) = state.areas[index].as_mut() {
+            area.draining = true;
+        }
+        index
+    };
+    if let Err(e) = unuse(index) {
+        if let Some(area) = SWAP.lock().areas[index].as_mut() {
+            area.draining = false;
+        }
+        return Err(e);
+    }
+    // Pages whose write failed and that nobody maps any more; one still
+    // being written is dropped by its writer
+    let mut state = SWAP.lock();
+    let leftover: Vec<SwapEntry> = state
+        .cache
+        .iter()
+        .filter(|(entry, page)| entry.area() == index && !page.writing)
+        .map(|(entry, _)| *entry)
+        .collect();
+    for entry in leftover {
+        if let Some(page) = state.cache.remove(&entry) {
+            page_put(page.frame);
+        }
+    }
+    if let Some(area) = state.areas[index].take() {
+        for slot in (1..area.map.len()).filter(|&slot| area.map[slot] != 0) {
+            zswap::invalidate(SwapEntry::new(index, slot));
+        }
+    }
+    crate::println!("swap: {}: off", path);
+    Ok(())
+}
+
+/// Pages `unuse` brings in per pass over the process table
+const UNUSE_BATCH: usize = 64;
+
+/// Bring every page swapped out to area `index` back in
+///
+/// The process table is only held to find the entries and to map the
+/// pages; the reads happen in between, so each entry is checked again
+/// before it is replaced.
+fn unuse(index: usize) -> Result<(), SwapError> {
+    loop {
+        let batch = find_entries(index, UNUSE_BATCH);
+        if batch.is_empty() {
+            return Ok(());
+        }
+        for (pid, pagetable, area, va, entry) in batch {
+            let frame = crate::subsystems::mm::kalloc();
+            if frame.is_null() {
+                return Err(SwapError::NoMemory);
+            }
+            if let Err(e) = read_page(entry, frame as usize) {
+                unsafe { crate::subsystems::mm::kfree(frame) };
+                return Err(e);
+            }
+            let table = crate::process::PROC_TABLE.lock();
+            let installed = table.find_ref(pid).is_some_and(|proc| proc.pagetable == pagetable)
+                && unsafe { vm::install_swapped_page(pagetable, &area, va, entry, frame as usize) };
+            drop(table);
+            if !installed {
+                unsafe { crate::subsystems::mm::kfree(frame) };
+            }
+        }
+    }
+}
+
+/// Up to `max` page table entries holding slots of area `index`
+fn find_entries(index: usize, max: usize) -> Vec<(crate::process::Pid, *mut PageTable, VmArea, usize, SwapEntry)> {
+    let mut found = Vec::new();
+    let table = crate::process::PROC_TABLE.lock();
+    for proc in table.iter().filter(|proc| !proc.pagetable.is_null()) {
+        for area in proc.vm.iter() {
+            for va in area.range.clone().step_by(PAGE_SIZE) {
+                let Some(pte) = (unsafe { vm::walk(proc.pagetable, va, false) }) else { continue };
+                let Some(entry) = SwapEntry::from_pte(unsafe { *pte }) else { continue };
+                if entry.area() != index {
+                    continue;
+                }
+                found.push((proc.pid, proc.pagetable, area.clone(), va, entry));
+                if found.len() == max {
+                    return found;
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Add zswap's own area of `pages` slots, below every other area
+fn add_memory_area(pages: usize) -> Result<(), SwapError> {
+    add_area(String::from(zswap::AREA_NAME), Backing::Memory, pages + 1, Some(i32::MIN), false).map(|_| ())
+}
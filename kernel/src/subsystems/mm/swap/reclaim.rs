@@ -0,0 +1,232 @@
+//! Page reclaim
+//!
+//! kswapd keeps free memory between two watermarks: an allocation that
+//! leaves fewer than `low` pages free wakes it, and it reclaims until
+//! `high` pages are free again. A fault that finds no memory at all
+//! reclaims a batch itself before giving up.
+//!
+//! Reclaim prefers the page cache: clean pages nobody maps are simply
+//! dropped, dirty ones are written back and taken on a later pass.
+//! Anonymous pages are swapped out only when the cache doesn't yield
+//! enough and some swap area has room. Either way a page goes only if it
+//! has not been used since it was put on the inactive list. Pages of
+//! mlocked areas are never taken: reclaim moves them to the unevictable
+//! list, off the ones it scans, until they are munlocked.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::subsystems::mm::vm::{swap_out_page, SwapOut};
+use crate::subsystems::sync::Mutex;
+use crate::vfs::page_cache::Evict;
+
+use super::lru::{self, AnonPage, FilePage, Lru};
+
+/// Pages reclaim goes for at a time
+pub const SWAP_CLUSTER_MAX: usize = 32;
+
+/// Free page thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watermarks {
+    /// Reserve that only direct reclaim dips into
+    pub min: usize,
+    /// kswapd is woken below this
+    pub low: usize,
+    /// kswapd stops once this many pages are free
+    pub high: usize,
+}
+
+impl Watermarks {
+    /// Watermarks for `total_pages` of memory: `min` is 1/64 of it within
+    /// sensible bounds, `low` and `high` twice and three times that
+    pub fn for_memory(total_pages: usize) -> Self {
+        let min = (total_pages / 64).clamp(16, 16384);
+        Self { min, low: min * 2, high: min * 3 }
+    }
+}
+
+static MIN: AtomicUsize = AtomicUsize::new(0);
+static LOW: AtomicUsize = AtomicUsize::new(0);
+static HIGH: AtomicUsize = AtomicUsize::new(0);
+
+static KSWAPD_WAKE: AtomicBool = AtomicBool::new(false);
+
+pub fn watermarks() -> Watermarks {
+    Watermarks {
+        min: MIN.load(Ordering::Acquire),
+        low: LOW.load(Ordering::Acquire),
+        high: HIGH.load(Ordering::Acquire),
+    }
+}
+
+pub fn set_watermarks(watermarks: Watermarks) {
+    MIN.store(watermarks.min, Ordering::Release);
+    LOW.store(watermarks.low, Ordering::Release);
+    HIGH.store(watermarks.high, Ordering::Release);
+}
+
+/// Called by the page allocator with the number of pages left free
+pub fn note_free_pages(free: usize) {
+    if free < LOW.load(Ordering::Relaxed) {
+        KSWAPD_WAKE.store(true, Ordering::Release);
+    }
+}
+
+pub fn wakeup_kswapd() {
+    KSWAPD_WAKE.store(true, Ordering::Release);
+}
+
+/// Reclaim counters since boot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReclaimStats {
+    /// Inactive pages looked at
+    pub scanned: usize,
+    /// Pages freed
+    pub reclaimed: usize,
+    /// kswapd runs, and direct reclaims by faults
+    pub kswapd_runs: usize,
+    pub direct: usize,
+}
+
+static STATS: Mutex<ReclaimStats> = Mutex::new(ReclaimStats { scanned: 0, reclaimed: 0, kswapd_runs: 0, direct: 0 });
+
+pub fn stats() -> ReclaimStats {
+    *STATS.lock()
+}
+
+fn free_pages() -> usize {
+    crate::subsystems::mm::phys::mem_stats().0
+}
+
+/// What became of a page reclaim looked at
+enum Scan {
+    Reclaimed,
+    /// Used since the last look: back to the active list
+    Referenced,
+    /// Not now: to the young end of the inactive list
+    Keep,
+    /// Not until munlock: to the unevictable list
+    Locked,
+    /// Not there any more: drop the entry
+    Gone,
+}
+
+/// Look at no more than the inactive pages of `lru` there are now, until
+/// `nr_to_reclaim` pages are freed; returns how many were
+fn shrink_list<T: Ord + Clone>(lru: &Mutex<Lru<T>>, nr_to_reclaim: usize, mut reclaim: impl FnMut(&T) -> Scan) -> usize {
+    // Keep the inactive list at least as long as the active one
+    {
+        let mut lru = lru.lock();
+        while lru.len_inactive() < lru.len_active() && lru.deactivate_one() {}
+    }
+    let budget = lru.lock().len_inactive();
+    let (mut scanned, mut reclaimed) = (0, 0);
+    while scanned < budget && reclaimed < nr_to_reclaim {
+        let Some(page) = lru.lock().pop_inactive() else { break };
+        scanned += 1;
+        match reclaim(&page) {
+            Scan::Reclaimed => reclaimed += 1,
+            Scan::Referenced => lru.lock().add(page, true),
+            Scan::Keep => lru.lock().add(page, false),
+            Scan::Locked => lru.lock().add_unevictable(page),
+            Scan::Gone => {}
+        }
+    }
+    let mut stats = STATS.lock();
+    stats.scanned += scanned;
+    stats.reclaimed += reclaimed;
+    reclaimed
+}
+
+fn reclaim_file(page: &FilePage) -> Scan {
+    let Some(mapping) = page.mapping.upgrade() else { return Scan::Gone };
+    match mapping.try_evict(page.index) {
+        Evict::Evicted => Scan::Reclaimed,
+        Evict::Referenced | Evict::Mapped => Scan::Referenced,
+        Evict::Dirty => {
+            // Clean it now and take it next time round
+            let _ = mapping.writeback(page.index..=page.index, 1);
+            Scan::Keep
+        }
+        Evict::Gone => Scan::Gone,
+    }
+}
+
+fn reclaim_anon(page: &AnonPage) -> Scan {
+    let swapped = {
+        let table = crate::process::PROC_TABLE.lock();
+        let Some(proc) = table.find_ref(page.pid).filter(|proc| !proc.pagetable.is_null()) else {
+            return Scan::Gone;
+        };
+        let Some(area) = proc.vm.find(page.va) else { return Scan::Gone };
+        unsafe { swap_out_page(proc.pagetable, area, page.va) }
+    };
+    match swapped {
+        SwapOut::Unmapped(entry) => match super::write_page(entry) {
+            Ok(()) => Scan::Reclaimed,
+            // Left in the swap cache until the next fault maps it back;
+            // keep the entry so reclaim tries it again after that
+            Err(_) => Scan::Keep,
+        },
+        SwapOut::Young => Scan::Referenced,
+        SwapOut::Locked => Scan::Locked,
+        SwapOut::Busy | SwapOut::NoSwap => Scan::Keep,
+        SwapOut::Gone => Scan::Gone,
+    }
+}
+
+/// Try to free `nr_to_reclaim` pages; returns how many were freed
+pub fn shrink(nr_to_reclaim: usize) -> usize {
+    let mut reclaimed = shrink_list(&lru::FILE, nr_to_reclaim, reclaim_file);
+    if reclaimed < nr_to_reclaim && super::can_swap() {
+        reclaimed += shrink_list(&lru::ANON, nr_to_reclaim - reclaimed, reclaim_anon);
+    }
+    reclaimed
+}
+
+/// Reclaim for an allocation that failed (direct reclaim)
+///
+/// kswapd is woken to carry on. Returns how many pages were freed.
+pub fn try_to_free_pages(nr_to_reclaim: usize) -> usize {
+    wakeup_kswapd();
+    STATS.lock().direct += 1;
+    shrink(nr_to_reclaim)
+}
+
+/// Reclaim until `high` pages are free or nothing more can be freed
+fn balance() {
+    STATS.lock().kswapd_runs += 1;
+    loop {
+        let (free, high) = (free_pages(), HIGH.load(Ordering::Acquire));
+        if free >= high || shrink((high - free).min(SWAP_CLUSTER_MAX)) == 0 {
+            break;
+        }
+    }
+}
+
+/// Set the watermarks for the memory there is and start kswapd
+pub fn start() {
+    let (_, total_pages) = crate::subsystems::mm::phys::mem_stats();
+    let watermarks = Watermarks::for_memory(total_pages);
+    set_watermarks(watermarks);
+    let _ = crate::process::thread::create_thread(
+        1,
+        crate::process::thread::ThreadType::Kernel,
+        Some(kswapd_main),
+        core::ptr::null_mut(),
+    );
+    crate::println!(
+        "[kswapd] Started, watermarks min {} low {} high {} pages",
+        watermarks.min,
+        watermarks.low,
+        watermarks.high
+    );
+}
+
+unsafe extern "C" fn kswapd_main(_arg: *mut u8) -> *mut u8 {
+    loop {
+        if KSWAPD_WAKE.swap(false, Ordering::AcqRel) || free_pages() < LOW.load(Ordering::Acquire) {
+            balance();
+        }
+        crate::process::thread::thread_yield();
+    }
+}
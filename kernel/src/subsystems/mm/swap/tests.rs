@@ -0,0 +1,107 @@
+//! Swap tests
+//!
+//! Tests for swap entries, slot allocation, the reclaim LRUs and the
+//! watermark defaults
+
+#[cfg(feature = "kernel_tests")]
+pub mod swap_tests {
+    extern crate alloc;
+
+    use alloc::string::String;
+
+    use crate::subsystems::mm::swap::lru::{AnonPage, Lru};
+    use crate::subsystems::mm::swap::reclaim::Watermarks;
+    use crate::subsystems::mm::swap::{Backing, SwapArea, SwapEntry, MAX_SWAPFILES};
+    use crate::subsystems::mm::vm::flags;
+    use crate::tests::{TestResult, test_assert_eq, test_assert};
+
+    /// Test swap entries survive a trip through a page table entry
+    pub fn test_swap_entry_pte() -> TestResult {
+        let entry = SwapEntry::new(MAX_SWAPFILES - 1, 12345);
+        test_assert_eq!(entry.area(), MAX_SWAPFILES - 1);
+        test_assert_eq!(entry.slot(), 12345);
+        let pte = entry.to_pte();
+        test_assert!(pte & flags::PTE_V == 0);
+        test_assert!(SwapEntry::from_pte(pte) == Some(entry));
+        // Neither a present page nor an empty entry is a swap entry
+        test_assert!(SwapEntry::from_pte(pte | flags::PTE_V).is_none());
+        test_assert!(SwapEntry::from_pte(0).is_none());
+        Ok(())
+    }
+
+    /// Test slots are handed out round robin, never the header
+    pub fn test_swap_area_alloc() -> TestResult {
+        let mut area = SwapArea::new(String::from("test"), Backing::Memory, 4, 0);
+        test_assert_eq!(area.nr_slots(), 3);
+        test_assert!(area.alloc() == Some(1));
+        test_assert!(area.alloc() == Some(2));
+        area.map[1] = 0;
+        area.inuse -= 1;
+        test_assert!(area.alloc() == Some(3));
+        test_assert!(area.alloc() == Some(1));
+        test_assert!(area.alloc().is_none());
+        area.map[2] = 0;
+        area.inuse -= 1;
+        area.draining = true;
+        test_assert!(area.alloc().is_none());
+        Ok(())
+    }
+
+    /// Test pages are added once and aged from active to inactive
+    pub fn test_swap_lru() -> TestResult {
+        let page = |va| AnonPage { pid: 1, va };
+        let mut lru = Lru::new();
+        lru.add(page(0x1000), true);
+        lru.add(page(0x2000), false);
+        lru.add(page(0x1000), false);
+        test_assert_eq!(lru.len_active(), 1);
+        test_assert_eq!(lru.len_inactive(), 1);
+
+        test_assert!(lru.deactivate_one());
+        test_assert!(!lru.deactivate_one());
+        test_assert!(lru.pop_inactive() == Some(page(0x2000)));
+        test_assert!(lru.pop_inactive() == Some(page(0x1000)));
+        test_assert!(lru.pop_inactive().is_none());
+        // Taken off, a page can be added again
+        lru.add(page(0x1000), true);
+        lru.retain(|page| page.va != 0x1000);
+        test_assert_eq!(lru.len_active(), 0);
+        lru.add(page(0x1000), false);
+        test_assert_eq!(lru.len_inactive(), 1);
+        Ok(())
+    }
+
+    /// Test unevictable pages stay off the lists reclaim scans until put
+    /// back
+    pub fn test_swap_lru_unevictable() -> TestResult {
+        let page = |va| AnonPage { pid: 1, va };
+        let mut lru = Lru::new();
+        lru.add_unevictable(page(0x1000));
+        lru.add_unevictable(page(0x2000));
+        // Faulting a set-aside page in again doesn't put it back
+        lru.add(page(0x1000), true);
+        test_assert_eq!(lru.len_unevictable(), 2);
+        test_assert_eq!(lru.len_active() + lru.len_inactive(), 0);
+        test_assert!(!lru.deactivate_one());
+        test_assert!(lru.pop_inactive().is_none());
+
+        lru.putback_unevictable(|page| page.va == 0x2000);
+        test_assert_eq!(lru.len_unevictable(), 1);
+        test_assert!(lru.deactivate_one());
+        test_assert!(lru.pop_inactive() == Some(page(0x2000)));
+
+        lru.retain(|page| page.va != 0x1000);
+        test_assert_eq!(lru.len_unevictable(), 0);
+        Ok(())
+    }
+
+    /// Test the default watermarks scale with memory within bounds
+    pub fn test_swap_watermarks() -> TestResult {
+        let small = Watermarks::for_memory(256);
+        test_assert_eq!(small.min, 16);
+        test_assert!(small.min < small.low && small.low < small.high);
+        test_assert_eq!(Watermarks::for_memory(64 * 1024).min, 1024);
+        test_assert_eq!(Watermarks::for_memory(usize::MAX / 2).min, 16384);
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! Compressed swap cache
+//!
+//! Pages on their way to a swap area are compressed with `mm::compress`
+//! and kept in a memory pool instead, as long as they shrink to
+//! `max_compressed` bytes and the pool stays under `max_pool_percent` of
+//! memory. Faults decompress them without touching the area. Pages that
+//! don't make it into the pool are written to the area as usual.
+//!
+//! Turning zswap on also adds an area of its own, `[zswap]`, below every
+//! other: with no disk or file to swap to, pages that compress still have
+//! somewhere to go.
+
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::subsystems::mm::compress;
+use crate::subsystems::mm::vm::PAGE_SIZE;
+use crate::subsystems::sync::Mutex;
+
+use super::{SwapEntry, SwapError};
+
+/// Path zswap's own area is listed under
+pub const AREA_NAME: &str = "[zswap]";
+
+/// zswap tuning knobs
+#[derive(Debug, Clone, Copy)]
+pub struct ZswapTunables {
+    /// Pool size limit, as a percentage of memory
+    pub max_pool_percent: usize,
+    /// Largest compressed page the pool takes, in bytes
+    pub max_compressed: usize,
+}
+
+impl ZswapTunables {
+    const fn new() -> Self {
+        Self { max_pool_percent: 20, max_compressed: PAGE_SIZE * 3 / 4 }
+    }
+}
+
+impl Default for ZswapTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TUNABLES: Mutex<ZswapTunables> = Mutex::new(ZswapTunables::new());
+
+/// Compressed pages by swap entry
+static POOL: Mutex<BTreeMap<SwapEntry, Vec<u8>>> = Mutex::new(BTreeMap::new());
+static POOL_BYTES: AtomicUsize = AtomicUsize::new(0);
+/// Pages that didn't compress well enough or found the pool full
+static REJECTED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn tunables() -> ZswapTunables {
+    *TUNABLES.lock()
+}
+
+pub fn set_tunables(tunables: ZswapTunables) {
+    *TUNABLES.lock() = tunables;
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Turn zswap on, along with page compression, and add its area
+pub fn enable() -> Result<(), SwapError> {
+    if ENABLED.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    compress::enable_compression();
+    let (_, total_pages) = crate::subsystems::mm::phys::mem_stats();
+    // Slots are cheap; the pool limit is what bounds the area
+    super::add_memory_area(total_pages).inspect_err(|_| ENABLED.store(false, Ordering::Release))
+}
+
+/// Stop storing new pages and bring the ones in zswap's area back in
+///
+/// Pages of other areas stay in the pool until they are read or freed.
+pub fn disable() -> Result<(), SwapError> {
+    if !ENABLED.swap(false, Ordering::AcqRel) {
+        return Ok(());
+    }
+    super::swapoff(AREA_NAME).inspect_err(|_| ENABLED.store(true, Ordering::Release))
+}
+
+/// Size limit of the pool in bytes
+fn max_pool_bytes() -> usize {
+    let (_, total_pages) = crate::subsystems::mm::phys::mem_stats();
+    total_pages * PAGE_SIZE / 100 * tunables().max_pool_percent
+}
+
+/// Compress `page` into the pool under `entry`; false if it stays out
+pub fn store(entry: SwapEntry, page: &[u8]) -> bool {
+    if !is_enabled() || !compress::is_compression_enabled() {
+        return false;
+    }
+    let data = compress::compress(page);
+    if data.len() > tunables().max_compressed || POOL_BYTES.load(Ordering::Acquire) + data.len() > max_pool_bytes() {
+        REJECTED.fetch_add(1, Ordering::Relaxed);
+        return false;
+    }
+    POOL_BYTES.fetch_add(data.len(), Ordering::AcqRel);
+    if let Some(old) = POOL.lock().insert(entry, data) {
+        POOL_BYTES.fetch_sub(old.len(), Ordering::AcqRel);
+    }
+    true
+}
+
+/// Decompress the page of `entry` into `page`
+///
+/// `None` if the pool doesn't have it. The page stays in the pool until
+/// its slot is freed, as the entry may be shared.
+pub fn load(entry: SwapEntry, page: &mut [u8]) -> Option<Result<(), SwapError>> {
+    let pool = POOL.lock();
+    let data = pool.get(&entry)?;
+    let plain = compress::decompress(data);
+    if plain.len() != page.len() {
+        return Some(Err(SwapError::Io));
+    }
+    page.copy_from_slice(&plain);
+    Some(Ok(()))
+}
+
+/// Drop the page of `entry`, whose slot is free again
+pub fn invalidate(entry: SwapEntry) {
+    if let Some(data) = POOL.lock().remove(&entry) {
+        POOL_BYTES.fetch_sub(data.len(), Ordering::AcqRel);
+    }
+}
+
+/// Pool usage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ZswapStats {
+    pub stored_pages: usize,
+    pub pool_bytes: usize,
+    pub rejected: usize,
+}
+
+pub fn stats() -> ZswapStats {
+    ZswapStats {
+        stored_pages: POOL.lock().len(),
+        pool_bytes: POOL_BYTES.load(Ordering::Acquire),
+        rejected: REJECTED.load(Ordering::Relaxed),
+    }
+}
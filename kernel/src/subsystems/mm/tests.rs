@@ -652,6 +652,566 @@ pub mod memory_tests {
     }
 }
 
+#[cfg(feature = "kernel_tests")]
+pub mod mmap_tests {
+    use core::ops::Range;
+    use alloc::sync::{Arc, Weak};
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use crate::{test_assert_eq, test_assert};
+    use crate::tests::TestResult;
+    use crate::posix::{
+        MADV_DOFORK, MADV_DONTFORK, MADV_DONTNEED, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MCL_CURRENT, MCL_FUTURE,
+        MLOCK_ONFAULT, MREMAP_DONTUNMAP, MREMAP_MAYMOVE, MS_SYNC, PROT_READ, PROT_WRITE, RLIMIT_MEMLOCK,
+    };
+    use crate::process::{myproc, PROC_TABLE};
+    use crate::subsystems::mm::uaccess::{copy_from_user, copy_to_user};
+    use crate::security::capabilities::{capable, Capability};
+    use crate::subsystems::mm::vm::{VmArea, VmLock, VmPerm, PAGE_SIZE};
+    use crate::subsystems::sync::Mutex;
+    use crate::syscalls::common::SyscallError;
+    use crate::syscalls::memory::dispatch;
+    use crate::vfs::error::VfsResult;
+    use crate::vfs::page_cache::{AddressSpace, CachedPage, PageIo};
+
+    /// Backing store of a file, to see what reaches it
+    struct Store {
+        data: Mutex<Vec<u8>>,
+    }
+
+    impl PageIo for Store {
+        fn readpage(&self, page: &CachedPage) -> VfsResult<()> {
+            let data = self.data.lock();
+            let start = page.index() as usize * PAGE_SIZE;
+            if start < data.len() {
+                page.write_at(0, &data[start..(start + PAGE_SIZE).min(data.len())]);
+            }
+            Ok(())
+        }
+
+        fn writepage(&self, page: &CachedPage, len: usize) -> VfsResult<()> {
+            let mut data = self.data.lock();
+            let start = page.index() as usize * PAGE_SIZE;
+            if data.len() < start + len {
+                data.resize(start + len, 0);
+            }
+            page.read_at(0, &mut data[start..start + len]);
+            Ok(())
+        }
+    }
+
+    fn file(data: Vec<u8>) -> (Arc<Store>, Arc<AddressSpace>) {
+        let size = data.len() as u64;
+        let store = Arc::new(Store { data: Mutex::new(data) });
+        let backing: Weak<dyn PageIo> = Arc::downgrade(&store) as Weak<dyn PageIo>;
+        let mapping = AddressSpace::new(backing);
+        mapping.truncate(size);
+        (store, mapping)
+    }
+
+    /// Map the whole of `mapping` read-write into the current process, as
+    /// mmap of a descriptor for it would
+    fn map_file(mapping: &Arc<AddressSpace>, private: bool) -> Option<usize> {
+        let pid = myproc()?;
+        let mut table = PROC_TABLE.lock();
+        let proc = table.find(pid)?;
+        let len = (mapping.size() as usize).next_multiple_of(PAGE_SIZE);
+        let start = proc.sz.next_multiple_of(PAGE_SIZE);
+        let perm = VmPerm { read: true, write: true, exec: false, user: true };
+        proc.vm.insert(VmArea::file(start..start + len, perm, mapping.clone(), 0, private)).ok()?;
+        proc.sz = start + len;
+        Some(start)
+    }
+
+    /// Private anonymous read-write memory of `pages` pages, unless there
+    /// is no process to map it into
+    fn map_anon(pages: usize) -> Option<usize> {
+        myproc()?;
+        let prot = (PROT_READ | PROT_WRITE) as u64;
+        let flags = (MAP_PRIVATE | MAP_ANONYMOUS) as u64;
+        dispatch(0x3001, &[0, (pages * PAGE_SIZE) as u64, prot, flags, -1i64 as u64, 0]).ok().map(|addr| addr as usize)
+    }
+
+    /// Range of the area containing `va`
+    fn area_range(va: usize) -> Option<Range<usize>> {
+        let table = PROC_TABLE.lock();
+        table.find_ref(myproc()?)?.vm.find(va).map(|area| area.range.clone())
+    }
+
+    fn read_user(addr: usize, len: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        copy_from_user(&mut buf, addr).ok()?;
+        Some(buf)
+    }
+
+    /// Test stores through a shared mapping land in the page cache and
+    /// reach the file on msync and munmap
+    pub fn test_mmap_shared_writeback() -> TestResult {
+        let (store, mapping) = file(vec![0; 2 * PAGE_SIZE]);
+        let Some(addr) = map_file(&mapping, false) else { return Ok(()) };
+
+        test_assert!(copy_to_user(addr + 10, b"shared").is_ok());
+        let mut buf = [0u8; 6];
+        test_assert!(mapping.read(10, &mut buf) == Ok(6));
+        test_assert!(buf == *b"shared");
+        test_assert!(store.data.lock()[10..16] == [0; 6]);
+
+        test_assert!(dispatch(0x300A, &[addr as u64, (2 * PAGE_SIZE) as u64, MS_SYNC as u64]) == Ok(0));
+        test_assert!(store.data.lock()[10..16] == *b"shared");
+
+        // The page goes back to the cache dirty, for the flushers
+        test_assert!(copy_to_user(addr + PAGE_SIZE, b"unmap").is_ok());
+        test_assert!(dispatch(0x3002, &[addr as u64, (2 * PAGE_SIZE) as u64]) == Ok(0));
+        test_assert!(area_range(addr).is_none());
+        test_assert!(mapping.find_page(1).is_some_and(|page| page.test(CachedPage::DIRTY)));
+        test_assert!(mapping.fsync().is_ok());
+        test_assert!(store.data.lock()[PAGE_SIZE..PAGE_SIZE + 5] == *b"unmap");
+        Ok(())
+    }
+
+    /// Test stores through a private mapping stay out of the file
+    pub fn test_mmap_private_cow() -> TestResult {
+        let (store, mapping) = file(vec![7; PAGE_SIZE]);
+        let Some(addr) = map_file(&mapping, true) else { return Ok(()) };
+
+        test_assert!(read_user(addr, 1) == Some(vec![7]));
+        test_assert!(copy_to_user(addr, b"private").is_ok());
+        test_assert!(read_user(addr, 7) == Some(b"private".to_vec()));
+
+        let mut buf = [0u8; 7];
+        test_assert!(mapping.read(0, &mut buf) == Ok(7));
+        test_assert!(buf == [7; 7]);
+        test_assert_eq!(mapping.nr_dirty(), 0);
+        test_assert!(dispatch(0x300A, &[addr as u64, PAGE_SIZE as u64, MS_SYNC as u64]) == Ok(0));
+        test_assert!(dispatch(0x3002, &[addr as u64, PAGE_SIZE as u64]) == Ok(0));
+        test_assert!(store.data.lock()[..7] == [7; 7]);
+        Ok(())
+    }
+
+    /// Test mprotect of the middle of an area splits it in three, and
+    /// that putting the protection back merges it again
+    pub fn test_mprotect_split_merge() -> TestResult {
+        let Some(addr) = map_anon(3) else { return Ok(()) };
+        let middle = addr + PAGE_SIZE;
+
+        test_assert!(dispatch(0x3003, &[middle as u64, PAGE_SIZE as u64, PROT_READ as u64]) == Ok(0));
+        test_assert!(area_range(middle) == Some(middle..middle + PAGE_SIZE));
+        test_assert!(area_range(addr).is_some_and(|range| range.end == middle));
+        test_assert!(area_range(middle + PAGE_SIZE).is_some_and(|range| range.start == middle + PAGE_SIZE));
+        test_assert!(copy_to_user(middle, b"x").is_err());
+        test_assert!(copy_to_user(addr, b"x").is_ok());
+
+        let rw = (PROT_READ | PROT_WRITE) as u64;
+        test_assert!(dispatch(0x3003, &[middle as u64, PAGE_SIZE as u64, rw]) == Ok(0));
+        test_assert!(area_range(addr).is_some_and(|range| range.start <= addr && range.end >= addr + 3 * PAGE_SIZE));
+        test_assert!(copy_to_user(middle, b"x").is_ok());
+
+        // The range has to be mapped throughout
+        test_assert!(dispatch(0x3002, &[middle as u64, PAGE_SIZE as u64]) == Ok(0));
+        test_assert!(dispatch(0x3003, &[addr as u64, (3 * PAGE_SIZE) as u64, rw]) == Err(SyscallError::OutOfMemory));
+        test_assert!(dispatch(0x3002, &[addr as u64, (3 * PAGE_SIZE) as u64]) == Ok(0));
+        Ok(())
+    }
+
+    /// Test munmap of the middle of an area leaves a hole between the
+    /// pieces on either side
+    pub fn test_munmap_hole() -> TestResult {
+        let Some(addr) = map_anon(3) else { return Ok(()) };
+        for page in 0..3 {
+            test_assert!(copy_to_user(addr + page * PAGE_SIZE, &[page as u8 + 1]).is_ok());
+        }
+
+        test_assert!(dispatch(0x3002, &[(addr + PAGE_SIZE) as u64, PAGE_SIZE as u64]) == Ok(0));
+        test_assert!(area_range(addr + PAGE_SIZE).is_none());
+        test_assert!(area_range(addr).is_some_and(|range| range.end == addr + PAGE_SIZE));
+        test_assert!(area_range(addr + 2 * PAGE_SIZE).is_some_and(|range| range.start == addr + 2 * PAGE_SIZE));
+        test_assert!(read_user(addr + PAGE_SIZE, 1).is_none());
+        test_assert!(read_user(addr, 1) == Some(vec![1]));
+        test_assert!(read_user(addr + 2 * PAGE_SIZE, 1) == Some(vec![3]));
+
+        test_assert!(dispatch(0x3002, &[addr as u64, (3 * PAGE_SIZE) as u64]) == Ok(0));
+        test_assert!(area_range(addr).is_none());
+        Ok(())
+    }
+
+    /// Test mremap grows a mapping in place while the addresses after it
+    /// are free, and moves it with its contents once they are not
+    pub fn test_mremap_grow_and_move() -> TestResult {
+        let Some(addr) = map_anon(1) else { return Ok(()) };
+        test_assert!(copy_to_user(addr, b"moving").is_ok());
+        let mremap = |old: usize, old_pages: usize, new_pages: usize, flags: i32| {
+            dispatch(0x300B, &[old as u64, (old_pages * PAGE_SIZE) as u64, (new_pages * PAGE_SIZE) as u64, flags as u64, 0])
+        };
+
+        test_assert!(mremap(addr, 1, 2, 0) == Ok(addr as u64));
+        test_assert!(area_range(addr).is_some_and(|range| range.end >= addr + 2 * PAGE_SIZE));
+        test_assert!(copy_to_user(addr + PAGE_SIZE, b"grown").is_ok());
+
+        // Something mapped right after the area keeps it from growing there
+        let prot = (PROT_READ | PROT_WRITE) as u64;
+        let flags = (MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED) as u64;
+        let blocker = addr + 2 * PAGE_SIZE;
+        test_assert!(dispatch(0x3001, &[blocker as u64, PAGE_SIZE as u64, prot, flags, -1i64 as u64, 0]) == Ok(blocker as u64));
+        test_assert!(mremap(addr, 2, 3, 0) == Err(SyscallError::OutOfMemory));
+
+        let moved = mremap(addr, 2, 3, MREMAP_MAYMOVE).map(|addr| addr as usize);
+        test_assert!(moved.is_ok());
+        let moved = moved.unwrap_or_default();
+        test_assert!(moved != addr);
+        test_assert!(area_range(addr).is_none());
+        test_assert!(read_user(moved, 6) == Some(b"moving".to_vec()));
+        test_assert!(read_user(moved + PAGE_SIZE, 5) == Some(b"grown".to_vec()));
+        test_assert!(copy_to_user(moved + 2 * PAGE_SIZE, b"x").is_ok());
+
+        // MREMAP_DONTUNMAP leaves the old range mapped, to read as zeroes
+        let again = mremap(moved, 3, 3, MREMAP_MAYMOVE | MREMAP_DONTUNMAP).map(|addr| addr as usize);
+        test_assert!(again.is_ok());
+        let again = again.unwrap_or_default();
+        test_assert!(read_user(again, 6) == Some(b"moving".to_vec()));
+        test_assert!(read_user(moved, 6) == Some(vec![0; 6]));
+
+        test_assert!(dispatch(0x3002, &[blocker as u64, PAGE_SIZE as u64]) == Ok(0));
+        test_assert!(dispatch(0x3002, &[moved as u64, (3 * PAGE_SIZE) as u64]) == Ok(0));
+        test_assert!(dispatch(0x3002, &[again as u64, (3 * PAGE_SIZE) as u64]) == Ok(0));
+        Ok(())
+    }
+
+    /// mlock state of the area containing `va`
+    fn area_lock(va: usize) -> Option<VmLock> {
+        let table = PROC_TABLE.lock();
+        table.find_ref(myproc()?)?.vm.find(va).map(|area| area.lock)
+    }
+
+    /// mincore of `pages` pages at `addr`, through a vector in `vec`
+    fn mincore(addr: usize, pages: usize, vec: usize) -> Option<Vec<u8>> {
+        dispatch(0x3009, &[addr as u64, (pages * PAGE_SIZE) as u64, vec as u64]).ok()?;
+        read_user(vec, pages)
+    }
+
+    /// Run `f` without CAP_IPC_LOCK and with RLIMIT_MEMLOCK at `limit`,
+    /// putting both back afterwards
+    ///
+    /// Skipped where the capability comes from a capability set rather
+    /// than from being root.
+    fn with_memlock_limit(limit: u64, f: impl FnOnce() -> TestResult) -> TestResult {
+        const NOBODY: u32 = 65534;
+        let Some(pid) = myproc() else { return Ok(()) };
+        if capable(pid as u64, NOBODY, Capability::IpcLock) {
+            return Ok(());
+        }
+        let swap = |euid: &mut u32, cur: &mut u64| {
+            let mut table = PROC_TABLE.lock();
+            if let Some(proc) = table.find(pid) {
+                core::mem::swap(&mut proc.euid, euid);
+                core::mem::swap(&mut proc.rlimits[RLIMIT_MEMLOCK as usize].rlim_cur, cur);
+            }
+        };
+        let (mut euid, mut cur) = (NOBODY, limit);
+        swap(&mut euid, &mut cur);
+        let result = f();
+        swap(&mut euid, &mut cur);
+        result
+    }
+
+    /// Test MADV_DONTNEED drops private pages, which read back as zeroes
+    pub fn test_madvise_dontneed() -> TestResult {
+        let Some(addr) = map_anon(2) else { return Ok(()) };
+        test_assert!(copy_to_user(addr, b"gone").is_ok());
+        test_assert!(copy_to_user(addr + PAGE_SIZE, b"gone").is_ok());
+
+        test_assert!(dispatch(0x3004, &[addr as u64, (2 * PAGE_SIZE) as u64, MADV_DONTNEED as u64]) == Ok(0));
+        test_assert!(read_user(addr, 4) == Some(vec![0; 4]));
+        test_assert!(read_user(addr + PAGE_SIZE, 4) == Some(vec![0; 4]));
+        test_assert!(dispatch(0x3002, &[addr as u64, (2 * PAGE_SIZE) as u64]) == Ok(0));
+        Ok(())
+    }
+
+    /// Test a MADV_DONTFORK area is left out of a child's address space
+    /// until MADV_DOFORK
+    pub fn test_madvise_dontfork() -> TestResult {
+        let Some(addr) = map_anon(2) else { return Ok(()) };
+        let kept = addr + PAGE_SIZE;
+        let child_has = |va: usize| {
+            let table = PROC_TABLE.lock();
+            myproc().and_then(|pid| table.find_ref(pid)).is_some_and(|proc| proc.vm.fork().find(va).is_some())
+        };
+
+        test_assert!(dispatch(0x3004, &[addr as u64, PAGE_SIZE as u64, MADV_DONTFORK as u64]) == Ok(0));
+        test_assert!(area_range(addr) == Some(addr..kept));
+        test_assert!(!child_has(addr));
+        test_assert!(child_has(kept));
+
+        test_assert!(dispatch(0x3004, &[addr as u64, PAGE_SIZE as u64, MADV_DOFORK as u64]) == Ok(0));
+        test_assert!(child_has(addr));
+        test_assert!(dispatch(0x3002, &[addr as u64, (2 * PAGE_SIZE) as u64]) == Ok(0));
+        Ok(())
+    }
+
+    /// Test RLIMIT_MEMLOCK: locking past it is ENOMEM, and with a limit
+    /// of 0 nothing may be locked at all (EPERM)
+    pub fn test_mlock_rlimit() -> TestResult {
+        let Some(addr) = map_anon(3) else { return Ok(()) };
+        let result = with_memlock_limit((2 * PAGE_SIZE) as u64, || {
+            test_assert!(dispatch(0x3005, &[addr as u64, (3 * PAGE_SIZE) as u64]) == Err(SyscallError::OutOfMemory));
+            test_assert!(dispatch(0x3005, &[addr as u64, (2 * PAGE_SIZE) as u64]) == Ok(0));
+            test_assert!(area_lock(addr) == Some(VmLock::Locked));
+            // What is locked already doesn't count twice
+            test_assert!(dispatch(0x3005, &[addr as u64, PAGE_SIZE as u64]) == Ok(0));
+            test_assert!(dispatch(0x3005, &[(addr + 2 * PAGE_SIZE) as u64, PAGE_SIZE as u64]) == Err(SyscallError::OutOfMemory));
+            Ok(())
+        });
+        test_assert!(dispatch(0x3006, &[addr as u64, (3 * PAGE_SIZE) as u64]) == Ok(0));
+        result?;
+
+        let result = with_memlock_limit(0, || {
+            test_assert!(dispatch(0x3005, &[addr as u64, PAGE_SIZE as u64]) == Err(SyscallError::PermissionDenied));
+            test_assert!(dispatch(0x3007, &[MCL_CURRENT as u64]) == Err(SyscallError::PermissionDenied));
+            test_assert!(area_lock(addr) == Some(VmLock::None));
+            Ok(())
+        });
+        test_assert!(dispatch(0x3002, &[addr as u64, (3 * PAGE_SIZE) as u64]) == Ok(0));
+        result
+    }
+
+    /// Test mlockall(MCL_FUTURE) locks what is mapped afterwards, until
+    /// munlockall
+    pub fn test_mlockall_future() -> TestResult {
+        let Some(before) = map_anon(1) else { return Ok(()) };
+        test_assert!(dispatch(0x3007, &[MCL_FUTURE as u64]) == Ok(0));
+        test_assert!(area_lock(before) == Some(VmLock::None));
+
+        let after = map_anon(1);
+        test_assert!(after.is_some());
+        let after = after.unwrap_or_default();
+        test_assert!(area_lock(after) == Some(VmLock::Locked));
+
+        test_assert!(dispatch(0x3008, &[]) == Ok(0));
+        test_assert!(area_lock(after) == Some(VmLock::None));
+        let later = map_anon(1);
+        test_assert!(later.is_some());
+        let later = later.unwrap_or_default();
+        test_assert!(area_lock(later) == Some(VmLock::None));
+
+        for addr in [before, after, later] {
+            test_assert!(dispatch(0x3002, &[addr as u64, PAGE_SIZE as u64]) == Ok(0));
+        }
+        Ok(())
+    }
+
+    /// Test MLOCK_ONFAULT leaves pages to be locked as they are touched,
+    /// where a plain mlock faults them all in
+    pub fn test_mlock_onfault() -> TestResult {
+        let (_store, mapping) = file(vec![1; 2 * PAGE_SIZE]);
+        let Some(vec) = map_anon(1) else { return Ok(()) };
+        let Some(addr) = map_file(&mapping, true) else { return Ok(()) };
+        let len = (2 * PAGE_SIZE) as u64;
+
+        test_assert!(dispatch(0x3011, &[addr as u64, len, 2]) == Err(SyscallError::InvalidArgument));
+        test_assert!(dispatch(0x3011, &[addr as u64, len, MLOCK_ONFAULT as u64]) == Ok(0));
+        test_assert!(area_lock(addr) == Some(VmLock::OnFault));
+        test_assert!(mincore(addr, 2, vec) == Some(vec![0, 0]));
+        test_assert!(read_user(addr, 1) == Some(vec![1]));
+        test_assert!(mincore(addr, 2, vec) == Some(vec![1, 0]));
+
+        test_assert!(dispatch(0x3011, &[addr as u64, len, 0]) == Ok(0));
+        test_assert!(area_lock(addr) == Some(VmLock::Locked));
+        test_assert!(mincore(addr, 2, vec) == Some(vec![1, 1]));
+
+        test_assert!(dispatch(0x3002, &[addr as u64, len]) == Ok(0));
+        test_assert!(dispatch(0x3002, &[vec as u64, PAGE_SIZE as u64]) == Ok(0));
+        Ok(())
+    }
+
+    /// Test mincore reports resident pages as 1 and others as 0, and
+    /// fails on a range with a hole
+    pub fn test_mincore() -> TestResult {
+        let Some(vec) = map_anon(1) else { return Ok(()) };
+        let Some(addr) = map_anon(3) else { return Ok(()) };
+        test_assert!(mincore(addr, 3, vec) == Some(vec![1, 1, 1]));
+
+        test_assert!(dispatch(0x3004, &[(addr + PAGE_SIZE) as u64, PAGE_SIZE as u64, MADV_DONTNEED as u64]) == Ok(0));
+        test_assert!(mincore(addr, 3, vec) == Some(vec![1, 0, 1]));
+        test_assert!(copy_to_user(addr + PAGE_SIZE, b"x").is_ok());
+        test_assert!(mincore(addr, 3, vec) == Some(vec![1, 1, 1]));
+
+        test_assert!(dispatch(0x3002, &[(addr + 2 * PAGE_SIZE) as u64, PAGE_SIZE as u64]) == Ok(0));
+        test_assert!(dispatch(0x3009, &[addr as u64, (3 * PAGE_SIZE) as u64, vec as u64]) == Err(SyscallError::OutOfMemory));
+
+        test_assert!(dispatch(0x3002, &[addr as u64, (2 * PAGE_SIZE) as u64]) == Ok(0));
+        test_assert!(dispatch(0x3002, &[vec as u64, PAGE_SIZE as u64]) == Ok(0));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "kernel_tests")]
+pub mod oom_tests {
+    use crate::{test_assert_eq, test_assert};
+    use crate::tests::TestResult;
+    use alloc::collections::BTreeMap;
+    use alloc::string::String;
+    use crate::process::{myproc, Pid, PROC_TABLE};
+    use crate::security::capabilities::{capable, Capability};
+    use crate::subsystems::mm::oom::{
+        badness, choose, dying_victim, exit_task, global_victim, oom_score_adj, out_of_memory, over_limit, score,
+        set_oom_score_adj, stats, Candidate, CgroupLimit, OomAdjError, OOM_SCORE_ADJ_MAX, OOM_SCORE_ADJ_MIN,
+        VICTIMS, VICTIM_TIMEOUT_MS,
+    };
+    use crate::subsystems::mm::vm::MemUsage;
+
+    fn candidate(pid: Pid, cgroup: Option<&str>, pages: usize, adj: i16) -> Candidate {
+        Candidate {
+            pid,
+            uid: 0,
+            name: String::new(),
+            cgroup: cgroup.map(String::from),
+            usage: MemUsage { anon: pages, ..MemUsage::default() },
+            total_vm: pages,
+            adj,
+        }
+    }
+
+    fn limits(cgroups: &[(&str, Option<usize>, bool)]) -> BTreeMap<String, CgroupLimit> {
+        cgroups
+            .iter()
+            .map(|&(name, limit, kill_disable)| (String::from(name), CgroupLimit { limit, kill_disable, adj: 0 }))
+            .collect()
+    }
+
+    /// Test badness follows usage and oom_score_adj, and -1000 exempts
+    pub fn test_oom_badness() -> TestResult {
+        test_assert_eq!(badness(100, 0, 1000), Some(100));
+        test_assert_eq!(badness(100, 500, 1000), Some(600));
+        test_assert_eq!(badness(100, -50, 1000), Some(50));
+        test_assert_eq!(badness(100, OOM_SCORE_ADJ_MIN as i32, 1000), None);
+        // Out of range adjustments are clamped
+        test_assert_eq!(badness(0, 5000, 1000), badness(0, OOM_SCORE_ADJ_MAX as i32, 1000));
+        test_assert_eq!(badness(0, -5000, 1000), None);
+        Ok(())
+    }
+
+    /// Test oom_score spans 0 to 2000
+    pub fn test_oom_score() -> TestResult {
+        test_assert_eq!(score(None, 1000), 0);
+        test_assert_eq!(score(Some(0), 1000), 1000);
+        test_assert_eq!(score(Some(500), 1000), 1500);
+        test_assert_eq!(score(Some(-2000), 1000), 0);
+        test_assert_eq!(score(Some(5000), 1000), 2000);
+        test_assert!(score(Some(10), 0) <= 2000);
+        Ok(())
+    }
+
+    /// Test a process with oom_score_adj -1000 is never chosen, however
+    /// much it uses and whatever its cgroup adds
+    pub fn test_oom_exempt() -> TestResult {
+        let none = BTreeMap::new();
+        let candidates = [candidate(2, None, 1_000_000, OOM_SCORE_ADJ_MIN), candidate(3, None, 10, 0)];
+        test_assert!(choose(candidates.iter(), &none, 1000).map(|c| c.pid) == Some(3));
+        test_assert!(choose(candidates[..1].iter(), &none, 1000).is_none());
+
+        let boxed = BTreeMap::from([(String::from("box"), CgroupLimit { limit: Some(100), kill_disable: false, adj: 1000 })]);
+        let candidates = [candidate(2, Some("box"), 1000, OOM_SCORE_ADJ_MIN)];
+        test_assert!(choose(candidates.iter(), &boxed, 1000).is_none());
+        test_assert!(global_victim(&candidates, &boxed, 1000).is_none());
+        Ok(())
+    }
+
+    /// Test a global OOM takes its victim from a cgroup over its limit
+    /// before looking at the worst process of all
+    pub fn test_oom_cgroup_first() -> TestResult {
+        let candidates = [
+            candidate(2, None, 5000, 0),
+            candidate(3, Some("small"), 300, 0),
+            candidate(4, Some("small"), 200, 0),
+            candidate(5, Some("big"), 100, 0),
+        ];
+        let over = limits(&[("big", Some(1000), false), ("small", Some(400), false)]);
+        let victim = global_victim(&candidates, &over, 10_000).map(|(c, cgroup)| (c.pid, cgroup));
+        test_assert!(victim == Some((3, Some(("small", 500, 400)))));
+
+        let within = limits(&[("big", Some(1000), false), ("small", Some(1000), false)]);
+        let victim = global_victim(&candidates, &within, 10_000).map(|(c, cgroup)| (c.pid, cgroup));
+        test_assert!(victim == Some((2, None)));
+        Ok(())
+    }
+
+    /// Test oom_kill_disable keeps a cgroup from losing a process for
+    /// going over its own limit, but not from a global OOM
+    pub fn test_oom_kill_disable() -> TestResult {
+        let candidates = [candidate(2, Some("box"), 300, 0), candidate(3, None, 10, 0)];
+        test_assert!(over_limit("box", &candidates, &limits(&[("box", Some(100), false)])) == Some((300, 100)));
+        test_assert!(over_limit("box", &candidates, &limits(&[("box", Some(1000), false)])).is_none());
+        test_assert!(over_limit("box", &candidates, &limits(&[("box", None, false)])).is_none());
+
+        let disabled = limits(&[("box", Some(100), true)]);
+        test_assert!(over_limit("box", &candidates, &disabled).is_none());
+        let victim = global_victim(&candidates, &disabled, 10_000).map(|(c, cgroup)| (c.pid, cgroup));
+        test_assert!(victim == Some((2, None)));
+        Ok(())
+    }
+
+    /// Test no one else is killed while a victim is still exiting, unless
+    /// it has taken too long
+    pub fn test_oom_dying_victim() -> TestResult {
+        const DYING: Pid = Pid::MAX;
+        // A real victim is on its way out
+        if dying_victim().is_some() {
+            return Ok(());
+        }
+        let now = crate::time::uptime_ms();
+        let kills = stats().kills;
+
+        VICTIMS.lock().insert(DYING, now);
+        test_assert!(dying_victim() == Some(DYING));
+        test_assert!(out_of_memory(DYING - 1));
+        test_assert!(!out_of_memory(DYING));
+        test_assert_eq!(stats().kills, kills);
+
+        if now >= VICTIM_TIMEOUT_MS {
+            VICTIMS.lock().insert(DYING, now - VICTIM_TIMEOUT_MS);
+            test_assert!(dying_victim().is_none());
+        }
+        exit_task(DYING);
+        test_assert!(dying_victim().is_none());
+        Ok(())
+    }
+
+    /// Test only a privileged caller may lower oom_score_adj or change
+    /// that of another user's process
+    pub fn test_oom_score_adj_permission() -> TestResult {
+        const NOBODY: u32 = 65534;
+        let Some(pid) = myproc() else { return Ok(()) };
+        if capable(pid as u64, NOBODY, Capability::SysResource) {
+            return Ok(());
+        }
+        let saved = PROC_TABLE.lock().find_ref(pid).map(|proc| (proc.uid, proc.euid, proc.oom_score_adj));
+        let Some((uid, euid, adj)) = saved else { return Ok(()) };
+        let set = |uid: u32, euid: u32, adj: i16| {
+            if let Some(proc) = PROC_TABLE.lock().find(pid) {
+                proc.uid = uid;
+                proc.euid = euid;
+                proc.oom_score_adj = adj;
+            }
+        };
+
+        set(NOBODY, NOBODY, 0);
+        let result = (|| {
+            test_assert!(set_oom_score_adj(pid, 100, pid) == Ok(()));
+            test_assert!(set_oom_score_adj(pid, 50, pid) == Err(OomAdjError::PermissionDenied));
+            test_assert!(oom_score_adj(pid) == Some(100));
+            test_assert!(set_oom_score_adj(pid, 1001, pid) == Err(OomAdjError::Invalid));
+            test_assert!(set_oom_score_adj(Pid::MAX, 200, pid) == Err(OomAdjError::NotFound));
+
+            // Not even raising it is allowed for another user's process
+            set(0, NOBODY, 100);
+            test_assert!(set_oom_score_adj(pid, 200, pid) == Err(OomAdjError::PermissionDenied));
+            test_assert!(oom_score_adj(pid) == Some(100));
+            Ok(())
+        })();
+        set(uid, euid, adj);
+        result
+    }
+}
+
 // ============================================================================
 // Integration Test Framework
 // ============================================================================
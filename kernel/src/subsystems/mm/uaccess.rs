@@ -0,0 +1,653 @@
+//! Fault-safe access to user memory
+//!
+//! Everything a system call reads from or writes to a user pointer goes
+//! through here. An access is first checked against the user half of the
+//! address space, then page by page against the page table: a page that
+//! is not present yet is faulted in when the process has a VMA allowing
+//! the access, and a copy-on-write page is broken before it is written.
+//!
+//! When the address space is the one the CPU is running on, the copy runs
+//! with user access opened just around it (SMAP on x86_64, PAN on AArch64,
+//! SUM on RISC-V) in a routine listed in the exception table, so a page
+//! that goes away underneath, say unmapped by another thread, fails the
+//! copy with EFAULT instead of taking the kernel down. Any other address
+//! space (an SQPOLL thread working for the ring's owner, a tracer poking a
+//! tracee) is reached through the direct map, frame by frame.
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+
+use crate::process::{myproc, Pid, PROC_TABLE};
+use crate::subsystems::mm::vm::{
+    self, handle_page_fault, handle_user_fault, user_page_writable, user_range_check, PageFaultResult, PageTable,
+    PAGE_SIZE, USER_MAX,
+};
+use crate::subsystems::syscalls::common::SyscallError;
+
+/// A user address that can't be accessed as asked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Efault;
+
+impl From<Efault> for SyscallError {
+    fn from(_: Efault) -> Self {
+        SyscallError::BadAddress
+    }
+}
+
+/// Most iovecs one vectored call takes (UIO_MAXIOV)
+pub const UIO_MAXIOV: usize = 1024;
+
+/// Whether `[addr, addr + len)` lies in the user half of the address space
+pub fn access_ok(addr: usize, len: usize) -> bool {
+    addr.checked_add(len).is_some_and(|end| end <= USER_MAX())
+}
+
+/// A user address space that copies go through
+#[derive(Debug, Clone, Copy)]
+pub struct UserSpace {
+    pagetable: *mut PageTable,
+    /// Owner whose VMAs say which missing pages may be faulted in
+    pid: Option<Pid>,
+}
+
+impl UserSpace {
+    /// The calling process's address space
+    ///
+    /// Faulting a page in looks the process up again, so copies must not
+    /// be made with `PROC_TABLE` held.
+    pub fn current() -> Result<Self, Efault> {
+        Self::of_pid(myproc().ok_or(Efault)?)
+    }
+
+    /// Another process's address space
+    pub fn of_pid(pid: Pid) -> Result<Self, Efault> {
+        let table = PROC_TABLE.lock();
+        let pagetable = table
+            .find_ref(pid)
+            .map(|proc| proc.pagetable)
+            .filter(|pagetable| !pagetable.is_null())
+            .ok_or(Efault)?;
+        Ok(Self { pagetable, pid: Some(pid) })
+    }
+
+    /// An address space known only by its page table
+    ///
+    /// Without VMAs to go by, pages that aren't present can't be faulted
+    /// in; copy-on-write pages are still broken for writes.
+    pub fn of_pagetable(pagetable: *mut PageTable) -> Self {
+        Self { pagetable, pid: None }
+    }
+
+    /// Copy `dst.len()` bytes from user address `src`
+    pub fn read(&self, src: usize, dst: &mut [u8]) -> Result<(), Efault> {
+        self.prepare(src, dst.len(), false)?;
+        if dst.is_empty() {
+            return Ok(());
+        }
+        if self.is_active() {
+            return copy_user(dst.as_mut_ptr(), src as *const u8, dst.len());
+        }
+        self.for_each_frame(src, dst.len(), |frame, done, chunk| unsafe {
+            core::ptr::copy_nonoverlapping(frame, dst.as_mut_ptr().add(done), chunk);
+        })
+    }
+
+    /// Copy `src` to user address `dst`
+    pub fn write(&self, dst: usize, src: &[u8]) -> Result<(), Efault> {
+        self.prepare(dst, src.len(), true)?;
+        if src.is_empty() {
+            return Ok(());
+        }
+        if self.is_active() {
+            return copy_user(dst as *mut u8, src.as_ptr(), src.len());
+        }
+        self.for_each_frame(dst, src.len(), |frame, done, chunk| unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr().add(done), frame, chunk);
+        })
+    }
+
+    /// Zero `len` bytes at user address `dst`
+    pub fn clear(&self, dst: usize, len: usize) -> Result<(), Efault> {
+        const ZEROES: [u8; 256] = [0; 256];
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(ZEROES.len());
+            self.write(dst + done, &ZEROES[..chunk])?;
+            done += chunk;
+        }
+        Ok(())
+    }
+
+    /// Copy a NUL-terminated string from user address `src` into `dst`
+    ///
+    /// Returns the string's length without the NUL, which is also copied
+    /// when it fits; `dst.len()` means the string didn't end within `dst`.
+    /// Pages past the terminator are never touched.
+    pub fn strncpy(&self, src: usize, dst: &mut [u8]) -> Result<usize, Efault> {
+        let mut done = 0;
+        while done < dst.len() {
+            let va = src.checked_add(done).ok_or(Efault)?;
+            let chunk = (dst.len() - done).min(PAGE_SIZE - (va & (PAGE_SIZE - 1)));
+            self.read(va, &mut dst[done..done + chunk])?;
+            if let Some(nul) = dst[done..done + chunk].iter().position(|&b| b == 0) {
+                return Ok(done + nul);
+            }
+            done += chunk;
+        }
+        Ok(dst.len())
+    }
+
+    /// Check the range and make every page in it accessible as asked
+    fn prepare(&self, addr: usize, len: usize, write: bool) -> Result<(), Efault> {
+        if !access_ok(addr, len) || self.pagetable.is_null() {
+            return Err(Efault);
+        }
+        if len == 0 {
+            return Ok(());
+        }
+        let mut page = addr & !(PAGE_SIZE - 1);
+        while page < addr + len {
+            self.prepare_page(page, write)?;
+            page += PAGE_SIZE;
+        }
+        Ok(())
+    }
+
+    fn prepare_page(&self, page: usize, write: bool) -> Result<(), Efault> {
+        if user_range_check(self.pagetable, page, 1, write, false).is_ok() {
+            return Ok(());
+        }
+        let result = match self.pid {
+            // The owner's areas say what may be faulted in, and how
+            Some(pid) => handle_user_fault(pid, page, write, false),
+            // Without them only a copy-on-write page can be written
+            None if write && user_page_writable(self.pagetable, page) => unsafe {
+                handle_page_fault(self.pagetable, page, write, true, false)
+            },
+            None => return Err(Efault),
+        };
+        match result {
+            PageFaultResult::Handled => Ok(()),
+            _ => Err(Efault),
+        }
+    }
+
+    /// Whether this is the address space the CPU is running on
+    fn is_active(&self) -> bool {
+        arch::active_root() == self.pagetable as usize
+    }
+
+    /// Run `f` on each frame's piece of `[addr, addr + len)` through the
+    /// direct map, with how much came before it and its length
+    fn for_each_frame(&self, addr: usize, len: usize, mut f: impl FnMut(*mut u8, usize, usize)) -> Result<(), Efault> {
+        let mut done = 0;
+        while done < len {
+            let va = addr + done;
+            let chunk = (len - done).min(PAGE_SIZE - (va & (PAGE_SIZE - 1)));
+            let pa = vm::user_frame(self.pagetable, va).ok_or(Efault)?;
+            f(vm::phys_to_kernel_ptr(pa), done, chunk);
+            done += chunk;
+        }
+        Ok(())
+    }
+}
+
+/// Copy from the calling process's memory at `src` into `dst`
+pub fn copy_from_user(dst: &mut [u8], src: usize) -> Result<(), Efault> {
+    UserSpace::current()?.read(src, dst)
+}
+
+/// Copy `src` into the calling process's memory at `dst`
+pub fn copy_to_user(dst: usize, src: &[u8]) -> Result<(), Efault> {
+    UserSpace::current()?.write(dst, src)
+}
+
+/// Copy a NUL-terminated string from the calling process, as
+/// [`UserSpace::strncpy`]
+pub fn strncpy_from_user(dst: &mut [u8], src: usize) -> Result<usize, Efault> {
+    UserSpace::current()?.strncpy(src, dst)
+}
+
+/// A string argument of at most `max` bytes, without its NUL
+///
+/// A string that doesn't end within `max` bytes is `NameTooLong`.
+pub fn user_string(src: usize, max: usize) -> Result<Vec<u8>, SyscallError> {
+    if src == 0 {
+        return Err(SyscallError::BadAddress);
+    }
+    let mut buf = vec![0u8; max];
+    let len = strncpy_from_user(&mut buf, src)?;
+    if len == max {
+        return Err(SyscallError::NameTooLong);
+    }
+    buf.truncate(len);
+    Ok(buf)
+}
+
+/// A pointer to a `T` in user memory
+///
+/// `T` is plain data that any bit pattern is valid for, as the C structs
+/// system calls exchange are.
+pub struct UserPtr<T> {
+    addr: usize,
+    _type: PhantomData<*mut T>,
+}
+
+impl<T> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UserPtr<T> {}
+
+impl<T> core::fmt::Debug for UserPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "UserPtr({:#x})", self.addr)
+    }
+}
+
+impl<T: Copy> UserPtr<T> {
+    pub const fn new(addr: usize) -> Self {
+        Self { addr, _type: PhantomData }
+    }
+
+    pub const fn addr(self) -> usize {
+        self.addr
+    }
+
+    pub const fn is_null(self) -> bool {
+        self.addr == 0
+    }
+
+    /// The `count`th `T` after this one
+    pub const fn add(self, count: usize) -> Self {
+        Self::new(self.addr.wrapping_add(count.wrapping_mul(size_of::<T>())))
+    }
+
+    /// Read the `T` from the calling process
+    pub fn read(self) -> Result<T, Efault> {
+        self.read_in(&UserSpace::current()?)
+    }
+
+    /// Write `value` into the calling process
+    pub fn write(self, value: &T) -> Result<(), Efault> {
+        self.write_in(&UserSpace::current()?, value)
+    }
+
+    pub fn read_in(self, space: &UserSpace) -> Result<T, Efault> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+        space.read(self.addr, bytes)?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    pub fn write_in(self, space: &UserSpace, value: &T) -> Result<(), Efault> {
+        let bytes = unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        space.write(self.addr, bytes)
+    }
+
+    /// Read the `T` unless the pointer is null
+    pub fn read_opt(self) -> Result<Option<T>, Efault> {
+        if self.is_null() { Ok(None) } else { self.read().map(Some) }
+    }
+
+    /// Write `value` unless the pointer is null
+    pub fn write_opt(self, value: &T) -> Result<(), Efault> {
+        if self.is_null() { Ok(()) } else { self.write(value) }
+    }
+}
+
+/// A byte buffer in user memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserSlice {
+    addr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    pub const fn new(addr: usize, len: usize) -> Self {
+        Self { addr, len }
+    }
+
+    pub const fn addr(self) -> usize {
+        self.addr
+    }
+
+    pub const fn len(self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.len == 0
+    }
+
+    /// The part from `offset` on, at most `len` bytes of it
+    pub fn sub(self, offset: usize, len: usize) -> Self {
+        let offset = offset.min(self.len);
+        Self::new(self.addr + offset, len.min(self.len - offset))
+    }
+
+    /// Copy the start of the buffer into `dst`; returns how much that was
+    pub fn read(self, dst: &mut [u8]) -> Result<usize, Efault> {
+        self.read_in(&UserSpace::current()?, dst)
+    }
+
+    /// Copy `src` into the start of the buffer; returns how much fit
+    pub fn write(self, src: &[u8]) -> Result<usize, Efault> {
+        self.write_in(&UserSpace::current()?, src)
+    }
+
+    /// The whole buffer, copied into the kernel
+    pub fn read_to_vec(self) -> Result<Vec<u8>, Efault> {
+        let mut buf = vec![0u8; self.len];
+        self.read(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn read_in(self, space: &UserSpace, dst: &mut [u8]) -> Result<usize, Efault> {
+        let n = self.len.min(dst.len());
+        space.read(self.addr, &mut dst[..n])?;
+        Ok(n)
+    }
+
+    pub fn write_in(self, space: &UserSpace, src: &[u8]) -> Result<usize, Efault> {
+        let n = self.len.min(src.len());
+        space.write(self.addr, &src[..n])?;
+        Ok(n)
+    }
+}
+
+/// `struct iovec`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IoVec {
+    pub base: usize,
+    pub len: usize,
+}
+
+/// Read an iovec array of `count` entries from the calling process
+pub fn import_iovec(addr: usize, count: usize) -> Result<Vec<UserSlice>, SyscallError> {
+    import_iovec_in(&UserSpace::current()?, addr, count)
+}
+
+/// Read an iovec array from `space`
+///
+/// More than [`UIO_MAXIOV`] entries, or lengths adding up past
+/// `isize::MAX`, are `InvalidArgument`; a buffer outside user space is
+/// `BadAddress` here rather than partway through the transfer.
+pub fn import_iovec_in(space: &UserSpace, addr: usize, count: usize) -> Result<Vec<UserSlice>, SyscallError> {
+    if count > UIO_MAXIOV {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let base = UserPtr::<IoVec>::new(addr);
+    let mut total = 0usize;
+    let mut slices = Vec::with_capacity(count);
+    for i in 0..count {
+        let iov = base.add(i).read_in(space)?;
+        total = total.checked_add(iov.len).filter(|&t| t <= isize::MAX as usize).ok_or(SyscallError::InvalidArgument)?;
+        if !access_ok(iov.base, iov.len) {
+            return Err(SyscallError::BadAddress);
+        }
+        slices.push(UserSlice::new(iov.base, iov.len));
+    }
+    Ok(slices)
+}
+
+// ============================================================================
+// Copy routine and exception table
+// ============================================================================
+
+/// Copy through live user mappings with user access opened
+fn copy_user(dst: *mut u8, src: *const u8, len: usize) -> Result<(), Efault> {
+    let _open = UserAccess::open();
+    let left = unsafe { __uaccess_copy(dst, src, len) };
+    if left == 0 { Ok(()) } else { Err(Efault) }
+}
+
+/// User access allowed on this CPU until dropped
+struct UserAccess;
+
+impl UserAccess {
+    fn open() -> Self {
+        arch::allow_user_access();
+        Self
+    }
+}
+
+impl Drop for UserAccess {
+    fn drop(&mut self) {
+        arch::forbid_user_access();
+    }
+}
+
+unsafe extern "C" {
+    /// Copy `len` bytes, returning how many were left when a fault hit
+    fn __uaccess_copy(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    fn __uaccess_copy_fault_start();
+    fn __uaccess_copy_fault_end();
+    fn __uaccess_copy_fixup();
+}
+
+/// Instructions that may fault on a user address, and where to resume
+struct ExceptionEntry {
+    start: usize,
+    end: usize,
+    fixup: usize,
+}
+
+fn exception_table() -> [ExceptionEntry; 1] {
+    [ExceptionEntry {
+        start: __uaccess_copy_fault_start as *const () as usize,
+        end: __uaccess_copy_fault_end as *const () as usize,
+        fixup: __uaccess_copy_fixup as *const () as usize,
+    }]
+}
+
+/// Where a kernel-mode fault at `pc` resumes, if `pc` is in a user copy
+///
+/// Trap handlers call this for page faults taken in the kernel before
+/// treating them as fatal.
+pub fn fixup_exception(pc: usize) -> Option<usize> {
+    exception_table()
+        .iter()
+        .find(|entry| (entry.start..entry.end).contains(&pc))
+        .map(|entry| entry.fixup)
+}
+
+/// Turn on the hardware that keeps the kernel out of user memory
+pub fn init() {
+    arch::init();
+}
+
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(r#"
+.section .text
+.globl __uaccess_copy, __uaccess_copy_fault_start, __uaccess_copy_fault_end, __uaccess_copy_fixup
+__uaccess_copy:
+    mov rcx, rdx
+__uaccess_copy_fault_start:
+    rep movsb
+__uaccess_copy_fault_end:
+    xor eax, eax
+    ret
+__uaccess_copy_fixup:
+    mov rax, rcx
+    ret
+"#);
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(r#"
+.section .text
+.globl __uaccess_copy, __uaccess_copy_fault_start, __uaccess_copy_fault_end, __uaccess_copy_fixup
+__uaccess_copy:
+    cbz x2, __uaccess_copy_fixup
+__uaccess_copy_fault_start:
+1:  ldrb w3, [x1], #1
+    strb w3, [x0], #1
+    sub x2, x2, #1
+    cbnz x2, 1b
+__uaccess_copy_fault_end:
+__uaccess_copy_fixup:
+    mov x0, x2
+    ret
+"#);
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(r#"
+.section .text
+.globl __uaccess_copy, __uaccess_copy_fault_start, __uaccess_copy_fault_end, __uaccess_copy_fixup
+__uaccess_copy:
+    beqz a2, __uaccess_copy_fixup
+__uaccess_copy_fault_start:
+1:  lb t0, 0(a1)
+    sb t0, 0(a0)
+    addi a0, a0, 1
+    addi a1, a1, 1
+    addi a2, a2, -1
+    bnez a2, 1b
+__uaccess_copy_fault_end:
+__uaccess_copy_fixup:
+    mv a0, a2
+    ret
+"#);
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    /// CR4.SMAP is set; stac/clac fault without it
+    static SMAP: AtomicBool = AtomicBool::new(false);
+
+    pub fn init() {
+        let leaf7 = unsafe { core::arch::x86_64::__cpuid_count(7, 0) };
+        if leaf7.ebx & (1 << 20) == 0 {
+            return;
+        }
+        unsafe {
+            core::arch::asm!(
+                "mov {tmp}, cr4",
+                "or {tmp}, {smap}",
+                "mov cr4, {tmp}",
+                tmp = out(reg) _,
+                smap = const 1u64 << 21,
+                options(nostack),
+            );
+        }
+        SMAP.store(true, Ordering::Release);
+    }
+
+    pub fn allow_user_access() {
+        if SMAP.load(Ordering::Acquire) {
+            unsafe { core::arch::asm!("stac", options(nomem, nostack)) };
+        }
+    }
+
+    pub fn forbid_user_access() {
+        if SMAP.load(Ordering::Acquire) {
+            unsafe { core::arch::asm!("clac", options(nomem, nostack)) };
+        }
+    }
+
+    pub fn active_root() -> usize {
+        let cr3: usize;
+        unsafe { core::arch::asm!("mov {}, cr3", out(reg) cr3, options(nomem, nostack)) };
+        cr3 & 0x000f_ffff_ffff_f000
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    /// The CPU has PAN (ARMv8.1); `msr pan` is undefined without it
+    static PAN: AtomicBool = AtomicBool::new(false);
+
+    pub fn init() {
+        let mmfr1: u64;
+        unsafe { core::arch::asm!("mrs {}, id_aa64mmfr1_el1", out(reg) mmfr1, options(nomem, nostack)) };
+        if (mmfr1 >> 20) & 0xf == 0 {
+            return;
+        }
+        PAN.store(true, Ordering::Release);
+        forbid_user_access();
+    }
+
+    pub fn allow_user_access() {
+        if PAN.load(Ordering::Acquire) {
+            // msr pan, #0
+            unsafe { core::arch::asm!(".inst 0xd500409f", options(nomem, nostack)) };
+        }
+    }
+
+    pub fn forbid_user_access() {
+        if PAN.load(Ordering::Acquire) {
+            // msr pan, #1
+            unsafe { core::arch::asm!(".inst 0xd500419f", options(nomem, nostack)) };
+        }
+    }
+
+    pub fn active_root() -> usize {
+        let ttbr0: usize;
+        unsafe { core::arch::asm!("mrs {}, ttbr0_el1", out(reg) ttbr0, options(nomem, nostack)) };
+        // Drop the ASID and CnP bits
+        ttbr0 & 0x0000_ffff_ffff_fffe
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+mod arch {
+    /// sstatus.SUM: supervisor loads and stores may touch user pages
+    const SSTATUS_SUM: usize = 1 << 18;
+
+    pub fn init() {
+        forbid_user_access();
+    }
+
+    pub fn allow_user_access() {
+        unsafe { core::arch::asm!("csrs sstatus, {}", in(reg) SSTATUS_SUM, options(nomem, nostack)) };
+    }
+
+    pub fn forbid_user_access() {
+        unsafe { core::arch::asm!("csrc sstatus, {}", in(reg) SSTATUS_SUM, options(nomem, nostack)) };
+    }
+
+    pub fn active_root() -> usize {
+        let satp: usize;
+        unsafe { core::arch::asm!("csrr {}, satp", out(reg) satp, options(nomem, nostack)) };
+        (satp & ((1 << 44) - 1)) << 12
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_ok_rejects_kernel_and_wrapping_ranges() {
+        assert!(access_ok(0x1000, 0x1000));
+        assert!(access_ok(USER_MAX() - 1, 1));
+        assert!(access_ok(USER_MAX(), 0));
+        assert!(!access_ok(USER_MAX() - 1, 2));
+        assert!(!access_ok(usize::MAX, 2));
+    }
+
+    #[test]
+    fn user_slice_sub_stays_inside() {
+        let slice = UserSlice::new(0x1000, 100);
+        assert_eq!(slice.sub(10, 20), UserSlice::new(0x100a, 20));
+        assert_eq!(slice.sub(90, 20), UserSlice::new(0x105a, 10));
+        assert_eq!(slice.sub(200, 20), UserSlice::new(0x1064, 0));
+    }
+
+    #[test]
+    fn user_ptr_add_steps_by_element() {
+        let ptr = UserPtr::<IoVec>::new(0x2000);
+        assert_eq!(ptr.add(3).addr(), 0x2000 + 3 * size_of::<IoVec>());
+        assert!(UserPtr::<u32>::new(0).is_null());
+    }
+}
@@ -11,6 +11,7 @@ use core::ptr;
 use core::sync::atomic::{AtomicU32, Ordering};
 use core::ops::Range;
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use crate::subsystems::mm::{kalloc, kfree};
 
@@ -18,6 +19,8 @@ use crate::subsystems::mm::{kalloc, kfree};
 pub use crate::subsystems::mm::PAGE_SIZE;
 use crate::drivers::platform;
 use crate::subsystems::sync::Mutex;
+use crate::subsystems::mm::uaccess::UserSpace;
+use crate::subsystems::mm::swap::{self, SwapEntry};
 
 // ============================================================================
 // VMA 区间管理（mmap 基础骨架）
@@ -88,6 +91,38 @@ pub enum VmError {
     MapFailed,
 }
 
+/// 预期访问模式（MADV_NORMAL/SEQUENTIAL/RANDOM）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VmAccess {
+    #[default]
+    Normal,
+    Sequential,
+    Random,
+}
+
+/// What madvise has said about an area
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VmAdvice {
+    pub access: VmAccess,
+    /// MADV_HUGEPAGE (`Some(true)`) or MADV_NOHUGEPAGE (`Some(false)`)
+    pub hugepage: Option<bool>,
+    /// MADV_DONTFORK: a child of fork does not get the area
+    pub dontfork: bool,
+}
+
+/// mlock state of an area
+///
+/// Pages of a locked area stay resident: they are never reclaimed, and
+/// `Locked` areas are populated up front while `OnFault` ones keep each
+/// page once it has been touched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VmLock {
+    #[default]
+    None,
+    Locked,
+    OnFault,
+}
+
 #[derive(Debug, Clone)]
 pub struct VmArea {
     pub range: Range<usize>,
@@ -96,18 +131,112 @@ pub struct VmArea {
     pub file_offset: usize,
     pub lazy: bool,
     pub cow: bool,
+    /// Page cache of the mapped file, for file-backed areas
+    pub mapping: Option<Arc<crate::vfs::page_cache::AddressSpace>>,
+    /// Whether mprotect may make the area writable: not for a shared
+    /// mapping of a file opened read-only
+    pub may_write: bool,
+    pub advice: VmAdvice,
+    pub lock: VmLock,
 }
 
 impl VmArea {
+    /// Private anonymous memory over `range`
+    pub fn anonymous(range: Range<usize>, perm: VmPerm) -> Self {
+        Self {
+            range,
+            perm,
+            file_backed: false,
+            file_offset: 0,
+            lazy: false,
+            cow: false,
+            mapping: None,
+            may_write: true,
+            advice: VmAdvice::default(),
+            lock: VmLock::None,
+        }
+    }
+
+    /// A mapping of the file cached in `mapping`, from byte `file_offset`
+    ///
+    /// A `private` mapping (MAP_PRIVATE) is copy-on-write, so stores go to
+    /// copies of the file's pages; a shared one stores into the page cache.
+    pub fn file(
+        range: Range<usize>,
+        perm: VmPerm,
+        mapping: Arc<crate::vfs::page_cache::AddressSpace>,
+        file_offset: usize,
+        private: bool,
+    ) -> Self {
+        Self {
+            file_backed: true,
+            file_offset,
+            cow: private,
+            mapping: Some(mapping),
+            ..Self::anonymous(range, perm)
+        }
+    }
+
+    /// Whether stores reach the mapped object (MAP_SHARED)
+    pub fn shared(&self) -> bool {
+        self.file_backed && !self.cow
+    }
+
     pub fn len(&self) -> usize {
         self.range.end.saturating_sub(self.range.start)
     }
+
+    /// Page offset in the mapped file of the page containing `va`
+    pub fn file_index(&self, va: usize) -> u64 {
+        ((va & !(PAGE_SIZE - 1)) - self.range.start + self.file_offset) as u64 / PAGE_SIZE as u64
+    }
+
+    /// Frame of the page cache page backing `va`, with a reference taken
+    /// for the page table entry that will map it
+    ///
+    /// Fails with `MapFailed` past the end of the file, where the access
+    /// gets SIGBUS.
+    pub fn file_page(&self, va: usize, write: bool) -> Result<usize, VmError> {
+        let mapping = self.mapping.as_ref().ok_or(VmError::NotFound)?;
+        mapping.fault(self.file_index(va), write).map_err(|e| match e {
+            crate::vfs::VfsError::NoSpace => VmError::NoMemory,
+            _ => VmError::MapFailed,
+        })
+    }
+
+    /// Whether the area's pages must stay resident
+    pub fn locked(&self) -> bool {
+        self.lock != VmLock::None
+    }
+
+    /// Whether `next` starts where this area ends and only the range
+    /// tells them apart
+    fn continues_into(&self, next: &VmArea) -> bool {
+        let same_mapping = match (&self.mapping, &next.mapping) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b) && self.file_offset + self.len() == next.file_offset,
+            // File-backed areas without a page cache are not known to be
+            // the same object
+            (None, None) => !self.file_backed,
+            _ => false,
+        };
+        self.range.end == next.range.start
+            && same_mapping
+            && self.perm == next.perm
+            && self.file_backed == next.file_backed
+            && self.lazy == next.lazy
+            && self.cow == next.cow
+            && self.may_write == next.may_write
+            && self.advice == next.advice
+            && self.lock == next.lock
+    }
 }
 
 /// 简单 VMA 树，后续可替换为平衡树/区间树。
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct VmSpace {
     areas: BTreeMap<usize, VmArea>,
+    /// Lock state new mappings start with (mlockall MCL_FUTURE)
+    pub def_lock: VmLock,
 }
 
 impl VmSpace {
@@ -148,11 +277,27 @@ impl VmSpace {
                 file_offset,
                 lazy: false,
                 cow: false,
+                mapping: None,
+                may_write: true,
+                advice: VmAdvice::default(),
+                lock: self.def_lock,
             },
         );
         Ok(start_aligned)
     }
 
+    /// Add `area` as it is, unless it overlaps one already there
+    pub fn insert(&mut self, area: VmArea) -> Result<(), VmError> {
+        if area.range.is_empty() {
+            return Err(VmError::InvalidRange);
+        }
+        if self.overlapping(area.range.start, area.range.end).next().is_some() {
+            return Err(VmError::Overlap);
+        }
+        self.areas.insert(area.range.start, area);
+        Ok(())
+    }
+
     pub fn mmap_anonymous(&mut self, hint: usize, length: usize, perm: VmPerm) -> Result<usize, VmError> {
         let start = if hint == 0 {
             self.find_free_area(length).ok_or(VmError::InvalidRange)?
@@ -194,6 +339,120 @@ impl VmSpace {
         self.areas.values()
     }
 
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut VmArea> {
+        self.areas.values_mut()
+    }
+
+    /// The areas that reach into `[start, end)`, whole
+    pub fn overlapping(&self, start: usize, end: usize) -> impl Iterator<Item = &VmArea> {
+        let first = self.find(start).map_or(start, |area| area.range.start);
+        self.areas.range(first..end).map(|(_, area)| area)
+    }
+
+    /// The area containing `va`
+    pub fn find(&self, va: usize) -> Option<&VmArea> {
+        self.areas
+            .range(..=va)
+            .next_back()
+            .map(|(_, area)| area)
+            .filter(|area| area.range.contains(&va))
+    }
+
+    /// Whether `[start, end)` is mapped without holes
+    pub fn covers(&self, start: usize, end: usize) -> bool {
+        let mut cursor = start;
+        while cursor < end {
+            match self.find(cursor) {
+                Some(area) => cursor = area.range.end,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Split the area containing `va` so that one starts there
+    fn split_at(&mut self, va: usize) {
+        let Some((&start, area)) = self.areas.range(..va).next_back() else { return };
+        if area.range.end <= va {
+            return;
+        }
+        let mut tail = area.clone();
+        tail.range.start = va;
+        tail.file_offset += va - start;
+        if let Some(head) = self.areas.get_mut(&start) {
+            head.range.end = va;
+        }
+        self.areas.insert(va, tail);
+    }
+
+    /// The areas within `[start, end)`, split at its ends so that none
+    /// reaches outside it
+    pub fn split_range(&mut self, start: usize, end: usize) -> impl Iterator<Item = &mut VmArea> {
+        self.split_at(start);
+        self.split_at(end);
+        self.areas.range_mut(start..end).map(|(_, area)| area)
+    }
+
+    /// Take `[start, end)` out of the areas, returning the pieces removed
+    pub fn remove_range(&mut self, start: usize, end: usize) -> Vec<VmArea> {
+        self.split_at(start);
+        self.split_at(end);
+        let keys: Vec<usize> = self.areas.range(start..end).map(|(&key, _)| key).collect();
+        keys.iter().filter_map(|key| self.areas.remove(key)).collect()
+    }
+
+    /// Merge areas in and next to `[start, end)` back together where
+    /// only their ranges differ, undoing what `split_range` left behind
+    pub fn coalesce(&mut self, start: usize, end: usize) {
+        let first = self.areas.range(..start).next_back().map_or(start, |(&key, _)| key);
+        let keys: Vec<usize> = self.areas.range(first..=end).map(|(&key, _)| key).collect();
+        let mut prev: Option<usize> = None;
+        for key in keys {
+            if let Some(head) = prev
+                && self.areas[&head].continues_into(&self.areas[&key])
+                && let Some(next) = self.areas.remove(&key)
+            {
+                if let Some(area) = self.areas.get_mut(&head) {
+                    area.range.end = next.range.end;
+                }
+                continue;
+            }
+            prev = Some(key);
+        }
+    }
+
+    /// Bytes of locked areas, as counted against RLIMIT_MEMLOCK
+    pub fn locked_bytes(&self) -> usize {
+        self.areas.values().filter(|area| area.locked()).map(VmArea::len).sum()
+    }
+
+    /// Bytes mapped in all areas
+    pub fn total_bytes(&self) -> usize {
+        self.areas.values().map(VmArea::len).sum()
+    }
+
+    /// Bytes of `[start, end)` in areas that are not locked yet
+    pub fn unlocked_bytes_in(&self, start: usize, end: usize) -> usize {
+        self.overlapping(start, end)
+            .filter(|area| !area.locked())
+            .map(|area| area.range.end.min(end).saturating_sub(area.range.start.max(start)))
+            .sum()
+    }
+
+    /// The areas a child of fork starts with
+    ///
+    /// MADV_DONTFORK areas are left out, and neither mlock nor MCL_FUTURE
+    /// carries over.
+    pub fn fork(&self) -> VmSpace {
+        let areas = self
+            .areas
+            .iter()
+            .filter(|(_, area)| !area.advice.dontfork)
+            .map(|(&key, area)| (key, VmArea { lock: VmLock::None, ..area.clone() }))
+            .collect();
+        VmSpace { areas, def_lock: VmLock::None }
+    }
+
     /// 针对懒分配：调用者提供映射回调
     pub fn fault_in<F>(
         &mut self,
@@ -314,6 +573,404 @@ pub fn page_ref_count(pa: usize) -> u32 {
     *refcounts.get(&pfn).unwrap_or(&1)
 }
 
+/// Drop a user mapping's hold on a frame
+///
+/// Frames that were never shared are untracked and freed outright; shared
+/// ones (page cache, io_uring rings) only once their last user is gone.
+pub fn page_put(pa: usize) {
+    let pfn = pa / PAGE_SIZE;
+    let mut refcounts = PAGE_REFCOUNTS.lock();
+    if let Some(count) = refcounts.get_mut(&pfn) {
+        *count = count.saturating_sub(1);
+        if *count > 0 {
+            return;
+        }
+        refcounts.remove(&pfn);
+    }
+    drop(refcounts);
+    unsafe { kfree(pa as *mut u8); }
+}
+
+/// Count one more user mapping of a frame that had a single owner
+///
+/// Unlike [`page_ref_inc`], an untracked frame starts at its one existing
+/// user, so that it ends up at two.
+pub fn page_ref_share(pa: usize) {
+    let mut refcounts = PAGE_REFCOUNTS.lock();
+    let count = refcounts.entry(pa / PAGE_SIZE).or_insert(1);
+    *count = count.saturating_add(1);
+}
+
+/// Clear the user page table entries in `[start, end)`, returning the
+/// frames they mapped
+///
+/// Swapped-out pages are dropped along with their entries.
+pub unsafe fn unmap_user_range(pagetable: *mut PageTable, start: usize, end: usize) -> Vec<usize> {
+    let mut frames = Vec::new();
+    for va in (start..end).step_by(PAGE_SIZE) {
+        let Some(pte_ptr) = (unsafe { walk(pagetable, va, false) }) else { continue };
+        let pte = unsafe { *pte_ptr };
+        if let Some(entry) = SwapEntry::from_pte(pte) {
+            unsafe { *pte_ptr = 0; }
+            swap::free(entry);
+            continue;
+        }
+        if pte & flags::PTE_V == 0 {
+            continue;
+        }
+        unsafe { *pte_ptr = 0; }
+        flush_tlb_page(va);
+        frames.push(unsafe { pte_to_pa(pte) });
+    }
+    frames
+}
+
+/// Unmap the user pages in `[start, end)` and drop their frames
+///
+/// For anonymous memory, which the caller has checked the range is: the
+/// pages read back as zeroes through demand paging afterwards. Returns
+/// how many pages were mapped.
+pub unsafe fn discard_user_range(pagetable: *mut PageTable, start: usize, end: usize) -> usize {
+    let frames = unsafe { unmap_user_range(pagetable, start, end) };
+    for &pa in &frames {
+        page_put(pa);
+    }
+    frames.len()
+}
+
+/// Unmap the pages of `area` in `[start, end)` and drop their frames
+///
+/// Page cache pages of a writable shared mapping go back to the cache,
+/// which dirties them for writeback, and swapped-out pages give up their
+/// swap slot. Returns how many pages were mapped.
+pub unsafe fn release_area_range(pagetable: *mut PageTable, area: &VmArea, start: usize, end: usize) -> usize {
+    let mut released = 0;
+    for va in (start..end).step_by(PAGE_SIZE) {
+        let Some(pte_ptr) = (unsafe { walk(pagetable, va, false) }) else { continue };
+        let pte = unsafe { *pte_ptr };
+        if let Some(entry) = SwapEntry::from_pte(pte) {
+            unsafe { *pte_ptr = 0; }
+            swap::free(entry);
+            continue;
+        }
+        if pte & flags::PTE_V == 0 {
+            continue;
+        }
+        unsafe { *pte_ptr = 0; }
+        flush_tlb_page(va);
+        if area.shared()
+            && area.perm.write
+            && let Some(mapping) = &area.mapping
+        {
+            mapping.unmap_page(area.file_index(va));
+        }
+        page_put(unsafe { pte_to_pa(pte) });
+        released += 1;
+    }
+    released
+}
+
+/// Whether `pa` is the page cache page `area` maps at `va`
+fn is_cached_page(area: &VmArea, va: usize, pa: usize) -> bool {
+    area.mapping
+        .as_ref()
+        .and_then(|mapping| mapping.find_page(area.file_index(va)))
+        .is_some_and(|page| page.frame() == pa)
+}
+
+/// Give the pages of `[start, end)` the permissions of `area`, which had
+/// those of `old`
+///
+/// A shared file page whose writability changes is unmapped, to be faulted
+/// in again so that the page cache keeps count of writable mappings. Other
+/// pages change in place; a page still shared with the page cache or with
+/// another process stays copy-on-write.
+pub unsafe fn reprotect_area_range(pagetable: *mut PageTable, old: &VmArea, area: &VmArea, start: usize, end: usize) {
+    if area.shared() && area.mapping.is_some() && old.perm.write != area.perm.write {
+        unsafe { release_area_range(pagetable, old, start, end) };
+        return;
+    }
+    let mut perm = flags::PTE_V | flags::PTE_U;
+    if area.perm.read {
+        perm |= flags::PTE_R;
+    }
+    if area.perm.exec {
+        perm |= flags::PTE_X;
+    }
+    for va in (start..end).step_by(PAGE_SIZE) {
+        let Some(pte_ptr) = (unsafe { walk(pagetable, va, false) }) else { continue };
+        let pte = unsafe { *pte_ptr };
+        if pte & flags::PTE_V == 0 {
+            continue;
+        }
+        let pa = unsafe { pte_to_pa(pte) };
+        let cow = pte & flags::PTE_COW != 0 || (area.cow && is_cached_page(area, va, pa));
+        let writable = if area.perm.write && !cow { flags::PTE_W } else { 0 };
+        unsafe {
+            *pte_ptr = 0;
+            // The page table pages are all there, so this can't fail
+            let _ = map_page(pagetable, va, pa, perm | writable);
+            if cow {
+                *pte_ptr |= flags::PTE_COW;
+            }
+        }
+        flush_tlb_page(va);
+    }
+}
+
+/// Move the page table entries of `[from, from + len)` to `to`, frames,
+/// references and swap entries along with them
+///
+/// Fails with `NoMemory`, before anything moved, when a page table for the
+/// destination can't be allocated.
+pub unsafe fn move_user_range(pagetable: *mut PageTable, from: usize, to: usize, len: usize) -> Result<(), VmError> {
+    let present = |va: usize| {
+        unsafe { walk(pagetable, va, false) }
+            .filter(|&pte| unsafe { *pte } & flags::PTE_V != 0 || SwapEntry::from_pte(unsafe { *pte }).is_some())
+    };
+    for offset in (0..len).step_by(PAGE_SIZE) {
+        if present(from + offset).is_some() {
+            unsafe { walk(pagetable, to + offset, true) }.ok_or(VmError::NoMemory)?;
+        }
+    }
+    for offset in (0..len).step_by(PAGE_SIZE) {
+        let Some(src) = present(from + offset) else { continue };
+        let pte = unsafe { *src };
+        let Some(dst) = (unsafe { walk(pagetable, to + offset, false) }) else { continue };
+        unsafe {
+            *dst = pte;
+            *src = 0;
+        }
+        flush_tlb_page(from + offset);
+        flush_tlb_page(to + offset);
+    }
+    Ok(())
+}
+
+/// Unmap the pages of every area in `vm`, ahead of tearing down
+/// `pagetable`
+///
+/// Page table teardown frees the frames it finds as if the process owned
+/// them. Page cache pages have to go back to the cache instead, frames
+/// shared with a forked process only lose a reference, and swap entries,
+/// which teardown doesn't see, give up their slots.
+pub unsafe fn release_user_pages(pagetable: *mut PageTable, vm: &VmSpace) {
+    for area in vm.iter() {
+        unsafe { release_area_range(pagetable, area, area.range.start, area.range.end) };
+    }
+}
+
+/// Pages of an address space in memory and in swap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemUsage {
+    /// Private pages in memory
+    pub anon: usize,
+    /// Page cache pages mapped
+    pub file: usize,
+    /// Pages swapped out
+    pub swap: usize,
+}
+
+impl MemUsage {
+    /// Resident set size in pages
+    pub fn rss(&self) -> usize {
+        self.anon + self.file
+    }
+}
+
+/// Count the pages the areas of `vm` have mapped or swapped out in
+/// `pagetable`
+pub unsafe fn mem_usage(pagetable: *mut PageTable, vm: &VmSpace) -> MemUsage {
+    let mut usage = MemUsage::default();
+    for area in vm.iter() {
+        for va in area.range.clone().step_by(PAGE_SIZE) {
+            let Some(pte_ptr) = (unsafe { walk(pagetable, va, false) }) else { continue };
+            let pte = unsafe { *pte_ptr };
+            if SwapEntry::from_pte(pte).is_some() {
+                usage.swap += 1;
+            } else if pte & flags::PTE_V == 0 {
+                continue;
+            } else if is_cached_page(area, va, unsafe { pte_to_pa(pte) }) {
+                usage.file += 1;
+            } else {
+                usage.anon += 1;
+            }
+        }
+    }
+    usage
+}
+
+/// Clear the entries of a forked child's page table that map page cache
+/// pages of `area`
+///
+/// The child's copy of the page table shares the parent's frames without
+/// references of its own. Cached pages are faulted in again from the page
+/// cache instead, so that each mapping of them is counted.
+pub unsafe fn forget_cached_pages(pagetable: *mut PageTable, area: &VmArea) {
+    if area.mapping.is_none() {
+        return;
+    }
+    for va in area.range.clone().step_by(PAGE_SIZE) {
+        let Some(pte_ptr) = (unsafe { walk(pagetable, va, false) }) else { continue };
+        let pte = unsafe { *pte_ptr };
+        if pte & flags::PTE_V != 0 && is_cached_page(area, va, unsafe { pte_to_pa(pte) }) {
+            unsafe { *pte_ptr = 0; }
+        }
+    }
+}
+
+/// Share the private pages of `area` between `parent` and `child`, a copy
+/// of its page table made by fork
+///
+/// The copy maps the parent's frames without references of its own. Each
+/// shared frame gets one here, and both entries become copy-on-write, so
+/// that whichever side stores first copies the page and neither side frees
+/// it under the other. Swapped-out pages are left out of the copy; the
+/// child gets the swap entry and the slot another user. Fails with
+/// `NoMemory`, before anything is shared, when a page table for the child
+/// can't be allocated.
+pub unsafe fn fork_anon_pages(
+    parent: *mut PageTable,
+    child: *mut PageTable,
+    area: &VmArea,
+    child_pid: crate::process::Pid,
+) -> Result<(), VmError> {
+    if area.shared() || (area.file_backed && area.mapping.is_none()) {
+        return Ok(());
+    }
+    let swapped = |va: usize| {
+        unsafe { walk(parent, va, false) }.and_then(|pte| SwapEntry::from_pte(unsafe { *pte }))
+    };
+    for va in area.range.clone().step_by(PAGE_SIZE) {
+        if swapped(va).is_some() {
+            unsafe { walk(child, va, true) }.ok_or(VmError::NoMemory)?;
+        }
+    }
+    for va in area.range.clone().step_by(PAGE_SIZE) {
+        let Some(src) = (unsafe { walk(parent, va, false) }) else { continue };
+        let pte = unsafe { *src };
+        if let Some(entry) = SwapEntry::from_pte(pte) {
+            swap::dup(entry);
+            if let Some(dst) = unsafe { walk(child, va, false) } {
+                unsafe { *dst = pte };
+            }
+            continue;
+        }
+        // Page cache pages were dropped from the copy
+        let Some(dst) = (unsafe { walk(child, va, false) }) else { continue };
+        if pte & flags::PTE_V == 0 || unsafe { *dst } & flags::PTE_V == 0 {
+            continue;
+        }
+        page_ref_share(unsafe { pte_to_pa(pte) });
+        for (pagetable, pte_ptr) in [(parent, src), (child, dst)] {
+            unsafe {
+                *pte_ptr |= flags::PTE_COW;
+                reprotect_area_range(pagetable, area, area, va, va + PAGE_SIZE);
+            }
+        }
+        swap::lru::add_anon(child_pid, va);
+    }
+    Ok(())
+}
+
+/// Accessed bit of a leaf entry, in the hardware's format
+#[cfg(target_arch = "riscv64")]
+const PTE_YOUNG: usize = flags::PTE_A;
+#[cfg(target_arch = "aarch64")]
+const PTE_YOUNG: usize = 1 << 10; // AF
+#[cfg(target_arch = "x86_64")]
+const PTE_YOUNG: usize = 1 << 5;
+#[cfg(not(any(target_arch = "riscv64", target_arch = "aarch64", target_arch = "x86_64")))]
+const PTE_YOUNG: usize = 0;
+
+/// What [`swap_out_page`] did with a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapOut {
+    /// Unmapped, or not memory of the process's own: nothing to swap
+    Gone,
+    /// Accessed since the last look; the accessed bit is clear again
+    Young,
+    /// mlocked: it has to stay
+    Locked,
+    /// Shared with another mapping for now
+    Busy,
+    /// No swap slot to put it in
+    NoSwap,
+    /// Replaced by a swap entry, now or by an earlier pass whose write
+    /// failed; the page sits in the swap cache until [`swap::write_page`]
+    /// has written it
+    Unmapped(SwapEntry),
+}
+
+/// Swap out the page `area` maps at `va`, if it is private memory that
+/// has not been used since the last look
+///
+/// Only pages with a single user go: page cache pages and frames still
+/// shared with a forked process stay. Called with the process table held;
+/// the write happens later, without it.
+pub unsafe fn swap_out_page(pagetable: *mut PageTable, area: &VmArea, va: usize) -> SwapOut {
+    if area.shared() || (area.file_backed && area.mapping.is_none()) {
+        return SwapOut::Gone;
+    }
+    if area.locked() {
+        return SwapOut::Locked;
+    }
+    let Some(pte_ptr) = (unsafe { walk(pagetable, va, false) }) else { return SwapOut::Gone };
+    let pte = unsafe { *pte_ptr };
+    if pte & flags::PTE_V == 0 {
+        // Still in the swap cache after a failed write: write it again
+        return match SwapEntry::from_pte(pte) {
+            Some(entry) if swap::retry_write(entry) => SwapOut::Unmapped(entry),
+            _ => SwapOut::Gone,
+        };
+    }
+    if pte & PTE_YOUNG != 0 {
+        unsafe { *pte_ptr = pte & !PTE_YOUNG };
+        flush_tlb_page(va);
+        return SwapOut::Young;
+    }
+    let pa = unsafe { pte_to_pa(pte) };
+    if is_cached_page(area, va, pa) {
+        return SwapOut::Gone;
+    }
+    if page_ref_count(pa) != 1 {
+        return SwapOut::Busy;
+    }
+    let Some(entry) = swap::add_to_swap(pa) else { return SwapOut::NoSwap };
+    unsafe { *pte_ptr = entry.to_pte() };
+    flush_tlb_page(va);
+    SwapOut::Unmapped(entry)
+}
+
+/// Map `frame`, holding the data of swap entry `entry`, at `va` in `area`
+/// and give the entry up
+///
+/// Returns false, leaving the frame to the caller, when the entry is no
+/// longer there: the page was unmapped or brought in by someone else.
+pub unsafe fn install_swapped_page(pagetable: *mut PageTable, area: &VmArea, va: usize, entry: SwapEntry, frame: usize) -> bool {
+    let Some(pte_ptr) = (unsafe { walk(pagetable, va, false) }) else { return false };
+    if unsafe { *pte_ptr } != entry.to_pte() {
+        return false;
+    }
+    let mut perm = flags::PTE_V | flags::PTE_U;
+    if area.perm.read {
+        perm |= flags::PTE_R;
+    }
+    if area.perm.write {
+        perm |= flags::PTE_W;
+    }
+    if area.perm.exec {
+        perm |= flags::PTE_X;
+    }
+    unsafe {
+        *pte_ptr = 0;
+        // The page table page is there, so this can't fail
+        let _ = map_page(pagetable, va, frame, perm);
+    }
+    swap::free(entry);
+    true
+}
+
 // ============================================================================
 // 用户页 pin/unpin（零拷贝基础）
 // ============================================================================
@@ -421,6 +1078,10 @@ pub mod flags {
     pub const PTE_DEV: usize = 1 << 10; // Device memory (software flag)
     pub const PTE_DEV_STRONG: usize = 1 << 11; // Strongly-ordered device
     pub const PTE_DEV_WC: usize = 1 << 12; // Write-combining device (x86 only)
+    /// With `PTE_V` clear: the page is swapped out and the entry holds a
+    /// `swap::SwapEntry` from bit 12 up. Hardware ignores invalid entries,
+    /// so this is free on every architecture.
+    pub const PTE_SWAP: usize = 1 << 9;
 }
 
 /// Number of page table entries per page
@@ -1574,6 +2235,9 @@ pub fn init() {
     }
     #[cfg(target_arch = "x86_64")]
     unsafe { x86_64::setup_mtrr_mmio_uc(); }
+
+    // From here on the kernel only reaches user memory through uaccess
+    crate::subsystems::mm::uaccess::init();
 }
 
 /// Get kernel page table
@@ -2019,6 +2683,9 @@ pub fn alloc_page() -> Option<usize> {
 }
 
 /// Copy data from kernel to user space
+///
+/// Goes through [`uaccess`](super::uaccess) with only the page table to
+/// go by; new code should use `UserPtr`/`UserSlice` there instead.
 pub unsafe fn copyout(
     pagetable: *mut PageTable,
     dst: usize,
@@ -2028,20 +2695,51 @@ pub unsafe fn copyout(
     if dst == 0 || src.is_null() || len == 0 {
         return Err(());
     }
-    // Validate user mapping and permissions
+    let src = unsafe { core::slice::from_raw_parts(src, len) };
+    UserSpace::of_pagetable(pagetable).write(dst, src).map_err(|_| ())
+}
+
+/// Copy data from kernel to user space, writing read-only pages too
+///
+/// This is how a debugger pokes a tracee (ptrace's FOLL_FORCE). A
+/// copy-on-write page is broken as a write fault would break it, and a
+/// read-only page still shared with another process is swapped for a
+/// private copy first, keeping its permissions, so breakpoints in text
+/// never leak into other processes.
+pub unsafe fn copyout_force(
+    pagetable: *mut PageTable,
+    dst: usize,
+    src: *const u8,
+    len: usize,
+) -> Result<(), ()> {
+    if dst == 0 || src.is_null() || len == 0 {
+        return Err(());
+    }
     if is_kernel_address(dst) { return Err(()); }
-    user_range_check(pagetable, dst, len, true, false)?;
+    user_range_check(pagetable, dst, len, false, false)?;
     let mut copied = 0usize;
     while copied < len {
         let va = dst + copied;
         let page_off = va & (PAGE_SIZE - 1);
         let chunk = core::cmp::min(len - copied, PAGE_SIZE - page_off);
+        let pte_ptr = match unsafe { walk(pagetable, va & !(PAGE_SIZE - 1), false) } {
+            Some(p) => p,
+            None => return Err(()),
+        };
+        let pte = unsafe { *pte_ptr };
+        if pte & flags::PTE_COW != 0 {
+            if !matches!(unsafe { handle_cow_fault(pagetable, va, pte_ptr) }, PageFaultResult::Handled) {
+                return Err(());
+            }
+        } else if user_range_check(pagetable, va, 1, true, false).is_err() {
+            unsafe { unshare_page(va, pte_ptr)? };
+        }
+
+        let pte = unsafe { *pte_ptr };
         #[cfg(target_arch = "riscv64")]
-        let pa = match riscv64::translate(pagetable, va) { Some(p) => p, None => return Err(()) };
-        #[cfg(target_arch = "aarch64")]
-        let pa = match aarch64::walk(pagetable, va, false) { Some(p) => (*p & !0xFFF) | page_off, None => return Err(()) };
-        #[cfg(target_arch = "x86_64")]
-        let pa = match x86_64::walk(pagetable, va, false) { Some(p) => (*p & !0xFFF) | page_off, None => return Err(()) };
+        let pa = riscv64::pte_to_pa(pte) | page_off;
+        #[cfg(not(target_arch = "riscv64"))]
+        let pa = (pte & !0xFFF) | page_off;
         let dst_ptr = phys_to_kernel_ptr(pa);
         let src_ptr = unsafe { src.add(copied) };
         ptr::copy_nonoverlapping(src_ptr, dst_ptr, chunk);
@@ -2050,6 +2748,42 @@ pub unsafe fn copyout(
     Ok(())
 }
 
+/// Give a read-only page a private frame if other mappings share it
+unsafe fn unshare_page(va: usize, pte_ptr: *mut usize) -> Result<(), ()> {
+    let old_pte = unsafe { *pte_ptr };
+
+    #[cfg(target_arch = "riscv64")]
+    let old_pa = riscv64::pte_to_pa(old_pte);
+
+    #[cfg(not(target_arch = "riscv64"))]
+    let old_pa = (old_pte & !0xFFF) as usize;
+
+    if page_ref_count(old_pa) <= 1 {
+        return Ok(());
+    }
+    let new_page = kalloc();
+    if new_page.is_null() {
+        return Err(());
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(old_pa as *const u8, new_page, PAGE_SIZE);
+    }
+
+    #[cfg(target_arch = "riscv64")]
+    {
+        unsafe { *pte_ptr = riscv64::pa_to_pte(new_page as usize) | (old_pte & 0x3FF); }
+    }
+
+    #[cfg(not(target_arch = "riscv64"))]
+    {
+        unsafe { *pte_ptr = (new_page as usize & !0xFFF) | (old_pte & 0xFFF); }
+    }
+
+    flush_tlb_page(va);
+    page_ref_dec(old_pa);
+    Ok(())
+}
+
 /// Copy data from user to kernel space
 pub unsafe fn copyin(
     pagetable: *mut PageTable,
@@ -2060,29 +2794,12 @@ pub unsafe fn copyin(
     if dst.is_null() || src == 0 || len == 0 {
         return Err(());
     }
-    if is_kernel_address(src) { return Err(()); }
-    user_range_check(pagetable, src, len, false, false)?;
-    let mut copied = 0usize;
-    while copied < len {
-        let va = src + copied;
-        let page_off = va & (PAGE_SIZE - 1);
-        let chunk = core::cmp::min(len - copied, PAGE_SIZE - page_off);
-        #[cfg(target_arch = "riscv64")]
-        let pa = match riscv64::translate(pagetable, va) { Some(p) => p, None => return Err(()) };
-        #[cfg(target_arch = "aarch64")]
-        let pa = match aarch64::walk(pagetable, va, false) { Some(p) => (*p & !0xFFF) | page_off, None => return Err(()) };
-        #[cfg(target_arch = "x86_64")]
-        let pa = match x86_64::walk(pagetable, va, false) { Some(p) => (*p & !0xFFF) | page_off, None => return Err(()) };
-        let src_ptr = phys_to_kernel_const_ptr(pa);
-        let dst_ptr = unsafe { dst.add(copied) };
-        ptr::copy_nonoverlapping(src_ptr, dst_ptr, chunk);
-        copied += chunk;
-    }
-    Ok(())
+    let dst = unsafe { core::slice::from_raw_parts_mut(dst, len) };
+    UserSpace::of_pagetable(pagetable).read(src, dst).map_err(|_| ())
 }
 
-/// Optimized string copy from user space using bulk page copying
-/// Uses cache-line aligned bulk copies for better performance
+/// Copy a NUL-terminated string from user space, failing if it doesn't
+/// end within `max` bytes; returns its length without the NUL
 pub unsafe fn copyinstr(
     pagetable: *mut PageTable,
     src: usize,
@@ -2090,82 +2807,10 @@ pub unsafe fn copyinstr(
     max: usize,
 ) -> Result<usize, ()> {
     if dst.is_null() || src == 0 || max == 0 { return Err(()); }
-    if is_kernel_address(src) { return Err(()); }
-    
-    // Cache line size (typically 64 bytes)
-    const CACHE_LINE_SIZE: usize = 64;
-    
-    let mut copied = 0usize;
-    loop {
-        if copied >= max { return Err(()); }
-        let va = src + copied;
-        let page_off = va & (PAGE_SIZE - 1);
-        let chunk = core::cmp::min(max - copied, PAGE_SIZE - page_off);
-        
-        #[cfg(target_arch = "riscv64")]
-        let pa = match riscv64::translate(pagetable, va) { Some(p) => p, None => return Err(()) };
-        #[cfg(target_arch = "aarch64")]
-        let pa = match aarch64::walk(pagetable, va, false) { Some(p) => (*p & !0xFFF) | page_off, None => return Err(()) };
-        #[cfg(target_arch = "x86_64")]
-        let pa = match x86_64::walk(pagetable, va, false) { Some(p) => (*p & !0xFFF) | page_off, None => return Err(()) };
-        
-        let src_ptr = phys_to_kernel_const_ptr(pa);
-        let dst_ptr = dst.add(copied);
-        
-        // Use bulk copy for aligned chunks when possible
-        if chunk >= CACHE_LINE_SIZE && (copied % CACHE_LINE_SIZE == 0) {
-            // Try to copy in cache-line aligned chunks
-            let aligned_chunk = (chunk / CACHE_LINE_SIZE) * CACHE_LINE_SIZE;
-            
-            // Copy aligned portion using bulk copy
-            ptr::copy_nonoverlapping(src_ptr, dst_ptr, aligned_chunk);
-            
-            // Check for null terminator in aligned portion
-            let mut found_null = false;
-            let mut null_pos = 0;
-            for i in 0..aligned_chunk {
-                if *dst_ptr.add(i) == 0 {
-                    found_null = true;
-                    null_pos = i;
-                    break;
-                }
-            }
-            
-            if found_null {
-                return Ok(copied + null_pos);
-            }
-            
-            copied += aligned_chunk;
-            
-            // Handle remaining unaligned bytes
-            let remaining = chunk - aligned_chunk;
-            if remaining > 0 {
-                let remaining_src = src_ptr.add(aligned_chunk);
-                let remaining_dst = dst_ptr.add(aligned_chunk);
-                
-                for i in 0..remaining {
-                    let b = *remaining_src.add(i);
-                    *remaining_dst.add(i) = b;
-                    if b == 0 {
-                        return Ok(copied + i);
-                    }
-                }
-                copied += remaining;
-            }
-        } else {
-            // For small chunks or unaligned data, use optimized byte-by-byte copy
-            // but still check for null terminator efficiently
-            let mut i = 0usize;
-            while i < chunk {
-                let b = *src_ptr.add(i);
-                *dst_ptr.add(i) = b;
-                if b == 0 {
-                    return Ok(copied + i);
-                }
-                i += 1;
-            }
-            copied += chunk;
-        }
+    let dst = unsafe { core::slice::from_raw_parts_mut(dst, max) };
+    match UserSpace::of_pagetable(pagetable).strncpy(src, dst) {
+        Ok(len) if len < max => Ok(len),
+        _ => Err(()),
     }
 }
 
@@ -2195,6 +2840,8 @@ pub enum PageFaultResult {
     SegFault,
     /// Out of memory
     OutOfMemory,
+    /// Access to a file mapping past the end of the file (SIGBUS)
+    BusError,
 }
 
 /// Handle a page fault
@@ -2263,6 +2910,163 @@ pub unsafe fn handle_page_fault(
     }
 }
 
+/// Resolve a fault at `va` in the address space of `pid`
+///
+/// The area is looked up under the process table and copied out, so that
+/// reading a file page in doesn't happen with the table held. Outside any
+/// area only a store to a copy-on-write page can be resolved. Out of
+/// memory, the fault reclaims some pages itself and tries once more, then
+/// leaves it to the OOM killer: if that kills another process the access
+/// is simply retried. Private pages it maps become reclaim candidates, and
+/// count towards the memory cgroup of `pid`.
+pub fn handle_user_fault(pid: crate::process::Pid, va: usize, write: bool, exec: bool) -> PageFaultResult {
+    let (pagetable, area, in_cgroup) = {
+        let table = crate::process::PROC_TABLE.lock();
+        let Some(proc) = table.find_ref(pid).filter(|proc| !proc.pagetable.is_null()) else {
+            return PageFaultResult::SegFault;
+        };
+        (proc.pagetable, proc.vm.find(va).cloned(), proc.cgroup.is_some())
+    };
+    let fault = || match &area {
+        Some(area) => unsafe { fault_in_area(pagetable, area, va, write, exec) },
+        None if write && user_page_writable(pagetable, va) => unsafe { handle_page_fault(pagetable, va, true, true, exec) },
+        None => PageFaultResult::SegFault,
+    };
+    let mut result = fault();
+    if matches!(result, PageFaultResult::OutOfMemory) && swap::reclaim::try_to_free_pages(swap::reclaim::SWAP_CLUSTER_MAX) > 0 {
+        result = fault();
+    }
+    if matches!(result, PageFaultResult::OutOfMemory) && super::oom::out_of_memory(pid) {
+        // Another process is on its way out; try again once it has gone
+        return PageFaultResult::Handled;
+    }
+    if matches!(result, PageFaultResult::Handled) {
+        if let Some(area) = &area
+            && !area.shared()
+        {
+            swap::lru::add_anon(pid, va & !(PAGE_SIZE - 1));
+        }
+        if in_cgroup {
+            super::oom::charge_fault(pid);
+        }
+    }
+    result
+}
+
+/// Resolve a user fault at `va`, which lies in `area`
+///
+/// Anonymous pages are zero-filled on first touch. File pages come from
+/// the page cache: a shared mapping maps the cached page itself, writable
+/// from the start if the area is so that the cache counts it as mapped for
+/// writeback, while a private one maps it read-only and copy-on-write and
+/// copies it at the first store.
+pub unsafe fn fault_in_area(pagetable: *mut PageTable, area: &VmArea, va: usize, write: bool, exec: bool) -> PageFaultResult {
+    let va = va & !(PAGE_SIZE - 1);
+    let allowed = if write {
+        area.perm.write
+    } else if exec {
+        area.perm.exec
+    } else {
+        area.perm.read
+    };
+    if !area.range.contains(&va) || !allowed {
+        return PageFaultResult::SegFault;
+    }
+
+    if let Some(pte_ptr) = unsafe { walk(pagetable, va, false) }
+        && unsafe { *pte_ptr } & flags::PTE_V != 0
+    {
+        // Present already: only a store to a copy-on-write page needs work
+        if write && unsafe { *pte_ptr } & flags::PTE_COW != 0 {
+            return unsafe { handle_cow_fault(pagetable, va, pte_ptr) };
+        }
+        // or an access after reclaim cleared the accessed bit, where the
+        // hardware faults rather than set it again
+        if unsafe { *pte_ptr } & PTE_YOUNG == 0 {
+            unsafe { *pte_ptr |= PTE_YOUNG };
+            flush_tlb_page(va);
+            return PageFaultResult::Handled;
+        }
+        return match user_range_check(pagetable, va, 1, write, exec) {
+            Ok(()) => PageFaultResult::Handled,
+            Err(()) => PageFaultResult::SegFault,
+        };
+    }
+
+    if let Some(entry) = unsafe { walk(pagetable, va, false) }.and_then(|pte| SwapEntry::from_pte(unsafe { *pte })) {
+        return unsafe { swap_in_page(pagetable, area, va, entry) };
+    }
+
+    let Some(mapping) = &area.mapping else {
+        if area.file_backed {
+            // memfd and io_uring pages are mapped with the area; there is
+            // nothing to fault in
+            return PageFaultResult::SegFault;
+        }
+        return unsafe { handle_lazy_alloc(pagetable, va, area.perm.read, area.perm.write, area.perm.exec) };
+    };
+
+    let shared_write = area.shared() && area.perm.write;
+    let frame = match area.file_page(va, shared_write) {
+        Ok(frame) => frame,
+        Err(VmError::NoMemory) => return PageFaultResult::OutOfMemory,
+        Err(_) => return PageFaultResult::BusError,
+    };
+    let mut perm = flags::PTE_V | flags::PTE_U;
+    if area.perm.read {
+        perm |= flags::PTE_R;
+    }
+    if area.perm.exec {
+        perm |= flags::PTE_X;
+    }
+
+    let (pa, perm, cow) = if area.shared() {
+        (frame, if shared_write { perm | flags::PTE_W } else { perm }, false)
+    } else if write {
+        // A store to a private page: copy it right away
+        let copy = kalloc();
+        if copy.is_null() {
+            page_put(frame);
+            return PageFaultResult::OutOfMemory;
+        }
+        unsafe { ptr::copy_nonoverlapping(frame as *const u8, copy, PAGE_SIZE) };
+        page_put(frame);
+        (copy as usize, perm | flags::PTE_W, false)
+    } else {
+        (frame, perm, area.perm.write)
+    };
+
+    if unsafe { map_page(pagetable, va, pa, perm) }.is_err() {
+        if shared_write {
+            mapping.unmap_page(area.file_index(va));
+        }
+        page_put(pa);
+        return PageFaultResult::OutOfMemory;
+    }
+    if cow && let Some(pte_ptr) = unsafe { walk(pagetable, va, false) } {
+        unsafe { *pte_ptr |= flags::PTE_COW };
+    }
+    PageFaultResult::Handled
+}
+
+/// Bring the page swapped out into `entry` back in at `va`
+///
+/// Failing to read it is a SIGBUS, as for a file page.
+unsafe fn swap_in_page(pagetable: *mut PageTable, area: &VmArea, va: usize, entry: SwapEntry) -> PageFaultResult {
+    let frame = kalloc();
+    if frame.is_null() {
+        return PageFaultResult::OutOfMemory;
+    }
+    if swap::read_page(entry, frame as usize).is_err() {
+        unsafe { kfree(frame) };
+        return PageFaultResult::BusError;
+    }
+    if !unsafe { install_swapped_page(pagetable, area, va, entry, frame as usize) } {
+        unsafe { kfree(frame) };
+    }
+    PageFaultResult::Handled
+}
+
 /// Handle Copy-on-Write fault
 unsafe fn handle_cow_fault(
     pagetable: *mut PageTable,
@@ -2444,7 +3248,7 @@ pub use crate::arch::memory_layout::{
     is_user_address,
 };
 /// Check user-space mapping and permissions for a range [va, va+len)
-fn user_range_check(pagetable: *mut PageTable, va: usize, len: usize, need_write: bool, need_exec: bool) -> Result<(), ()> {
+pub(crate) fn user_range_check(pagetable: *mut PageTable, va: usize, len: usize, need_write: bool, need_exec: bool) -> Result<(), ()> {
     if len == 0 { return Err(()); }
     let mut cur = va & !(PAGE_SIZE - 1);
     let end = (va + len + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
@@ -2485,6 +3289,26 @@ fn user_range_check(pagetable: *mut PageTable, va: usize, len: usize, need_write
     }
     Ok(())
 }
+
+/// Physical address behind user address `va`, if its page is mapped
+pub fn user_frame(pagetable: *mut PageTable, va: usize) -> Option<usize> {
+    let pte = unsafe { *walk(pagetable, va & !(PAGE_SIZE - 1), false)? };
+    if pte & flags::PTE_V == 0 {
+        return None;
+    }
+    Some(unsafe { pte_to_pa(pte) } | (va & (PAGE_SIZE - 1)))
+}
+
+/// Whether the user page at `va` is private writable memory, counting
+/// copy-on-write pages that are write-protected until the next fault
+pub fn user_page_writable(pagetable: *mut PageTable, va: usize) -> bool {
+    if user_range_check(pagetable, va, 1, true, false).is_ok() {
+        return true;
+    }
+    user_range_check(pagetable, va, 1, false, false).is_ok()
+        && unsafe { walk(pagetable, va & !(PAGE_SIZE - 1), false) }
+            .is_some_and(|pte| unsafe { *pte } & flags::PTE_COW != 0)
+}
 #[inline]
 pub fn phys_to_kernel_ptr(pa: usize) -> *mut u8 {
     crate::arch::memory_layout::phys_to_virt(pa)
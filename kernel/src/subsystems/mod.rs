@@ -1,3 +1,5 @@
+pub mod block;
+pub mod crypto;
 pub mod fs;
 pub mod net;
 pub mod ipc;
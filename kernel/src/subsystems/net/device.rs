@@ -6,9 +6,12 @@
 extern crate alloc;
 // Arc在当前文件中未使用，暂时注释掉
 // use alloc::sync::Arc;
+use alloc::collections::VecDeque;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::subsystems::sync::Mutex;
 
 /// Network device interface
 pub trait NetworkDevice: Send + Sync {
@@ -387,16 +390,23 @@ pub enum DeviceError {
     IoError,
 }
 
+/// Packets a loopback device holds before dropping
+const LOOPBACK_QUEUE_LEN: usize = 1024;
+
 /// Simple loopback device implementation
+///
+/// Every packet sent is queued and handed back by `receive_packet`.
 pub struct LoopbackDevice {
     /// Device name
     name: String,
     /// Device state
-    is_up: bool,
+    is_up: AtomicBool,
     /// MTU
     mtu: usize,
     /// Device statistics
     stats: DeviceStats,
+    /// Packets sent and not yet received
+    queue: Mutex<VecDeque<Vec<u8>>>,
 }
 
 impl LoopbackDevice {
@@ -404,9 +414,10 @@ impl LoopbackDevice {
     pub fn new(name: &str, mtu: usize) -> Self {
         Self {
             name: name.to_string(),
-            is_up: false,
+            is_up: AtomicBool::new(false),
             mtu,
             stats: DeviceStats::new(),
+            queue: Mutex::new(VecDeque::new()),
         }
     }
 }
@@ -429,25 +440,24 @@ impl NetworkDevice for LoopbackDevice {
     }
 
     fn is_up(&self) -> bool {
-        self.is_up
+        self.is_up.load(Ordering::Acquire)
     }
 
     fn up(&self) -> Result<(), DeviceError> {
-        // Note: This would need to be mutable to change is_up
-        // For now, just return success
+        self.is_up.store(true, Ordering::Release);
         crate::log_info!("Loopback device '{}' is up", self.name.clone());
         Ok(())
     }
 
     fn down(&self) -> Result<(), DeviceError> {
-        // Note: This would need to be mutable to change is_up
-        // For now, just return success
+        self.is_up.store(false, Ordering::Release);
+        self.queue.lock().clear();
         crate::log_info!("Loopback device '{}' is down", self.name.clone());
         Ok(())
     }
 
     fn send_packet(&self, packet: &[u8]) -> Result<(), DeviceError> {
-        if !self.is_up {
+        if !self.is_up() {
             return Err(DeviceError::DeviceDown);
         }
 
@@ -455,23 +465,30 @@ impl NetworkDevice for LoopbackDevice {
             return Err(DeviceError::BufferTooSmall);
         }
 
+        let mut queue = self.queue.lock();
+        if queue.len() >= LOOPBACK_QUEUE_LEN {
+            self.stats.inc_tx_dropped(1);
+            return Err(DeviceError::NoMemory);
+        }
+        queue.push_back(packet.to_vec());
+
         // Update statistics
         self.stats.inc_tx_packets(1);
         self.stats.inc_tx_bytes(packet.len() as u64);
-
-        // In a real implementation, we would enqueue the packet
-        // for loopback reception. For now, just simulate success.
         Ok(())
     }
 
     fn receive_packet(&self) -> Result<Option<Vec<u8>>, DeviceError> {
-        if !self.is_up {
+        if !self.is_up() {
             return Err(DeviceError::DeviceDown);
         }
 
-        // For now, return no packet (real implementation would
-        // return packets that were sent to loopback)
-        Ok(None)
+        let packet = self.queue.lock().pop_front();
+        if let Some(ref data) = packet {
+            self.stats.inc_rx_packets(1);
+            self.stats.inc_rx_bytes(data.len() as u64);
+        }
+        Ok(packet)
     }
 
     fn stats(&self) -> DeviceStats {
@@ -0,0 +1,509 @@
+//! Internet Control Message Protocol for IPv6 (ICMPv6) implementation
+//!
+//! This module provides ICMPv6 messages: echo, error reports and the
+//! Neighbor Discovery messages that take the place of ARP.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use super::device::MacAddr;
+use super::ipv6::{self, Ipv6Addr, Ipv6Header};
+
+/// ICMPv6 message types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Icmpv6Type {
+    /// Destination Unreachable
+    DestinationUnreachable = 1,
+    /// Packet Too Big
+    PacketTooBig = 2,
+    /// Time Exceeded
+    TimeExceeded = 3,
+    /// Parameter Problem
+    ParameterProblem = 4,
+    /// Echo Request
+    EchoRequest = 128,
+    /// Echo Reply
+    EchoReply = 129,
+    /// Router Solicitation
+    RouterSolicitation = 133,
+    /// Router Advertisement
+    RouterAdvertisement = 134,
+    /// Neighbor Solicitation
+    NeighborSolicitation = 135,
+    /// Neighbor Advertisement
+    NeighborAdvertisement = 136,
+    /// Redirect
+    Redirect = 137,
+}
+
+impl Icmpv6Type {
+    /// Convert from the on-wire value
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Self::DestinationUnreachable,
+            2 => Self::PacketTooBig,
+            3 => Self::TimeExceeded,
+            4 => Self::ParameterProblem,
+            128 => Self::EchoRequest,
+            129 => Self::EchoReply,
+            133 => Self::RouterSolicitation,
+            134 => Self::RouterAdvertisement,
+            135 => Self::NeighborSolicitation,
+            136 => Self::NeighborAdvertisement,
+            137 => Self::Redirect,
+            _ => return None,
+        })
+    }
+
+    /// Check if this is an error message (types below 128)
+    pub fn is_error(self) -> bool {
+        (self as u8) < 128
+    }
+}
+
+/// ICMPv6 codes
+pub mod codes {
+    /// Destination Unreachable: no route to destination
+    pub const NO_ROUTE: u8 = 0;
+    /// Destination Unreachable: address unreachable
+    pub const ADDRESS_UNREACHABLE: u8 = 3;
+    /// Destination Unreachable: port unreachable
+    pub const PORT_UNREACHABLE: u8 = 4;
+    /// Time Exceeded: hop limit exceeded in transit
+    pub const HOP_LIMIT_EXCEEDED: u8 = 0;
+    /// Time Exceeded: fragment reassembly time exceeded
+    pub const REASSEMBLY_TIME_EXCEEDED: u8 = 1;
+    /// Parameter Problem: erroneous header field
+    pub const ERRONEOUS_HEADER: u8 = 0;
+    /// Parameter Problem: unrecognized next header
+    pub const UNRECOGNIZED_NEXT_HEADER: u8 = 1;
+    /// Parameter Problem: unrecognized option
+    pub const UNRECOGNIZED_OPTION: u8 = 2;
+}
+
+/// ICMPv6 packet
+#[derive(Debug, Clone)]
+pub struct Icmpv6Packet {
+    /// Message type
+    pub message_type: Icmpv6Type,
+    /// Message code
+    pub code: u8,
+    /// Checksum as received (computed when serializing)
+    pub checksum: u16,
+    /// Message body following type, code and checksum
+    pub body: Vec<u8>,
+}
+
+impl Icmpv6Packet {
+    /// Header size in bytes (type, code, checksum)
+    pub const HEADER_SIZE: usize = 4;
+
+    /// Create a new ICMPv6 packet
+    pub fn new(message_type: Icmpv6Type, code: u8, body: Vec<u8>) -> Self {
+        Self {
+            message_type,
+            code,
+            checksum: 0,
+            body,
+        }
+    }
+
+    /// Create an echo request
+    pub fn echo_request(identifier: u16, sequence: u16, data: &[u8]) -> Self {
+        Self::new(Icmpv6Type::EchoRequest, 0, Self::echo_body(identifier, sequence, data))
+    }
+
+    /// Create an echo reply
+    pub fn echo_reply(identifier: u16, sequence: u16, data: &[u8]) -> Self {
+        Self::new(Icmpv6Type::EchoReply, 0, Self::echo_body(identifier, sequence, data))
+    }
+
+    fn echo_body(identifier: u16, sequence: u16, data: &[u8]) -> Vec<u8> {
+        let mut body = Vec::with_capacity(4 + data.len());
+        body.extend_from_slice(&identifier.to_be_bytes());
+        body.extend_from_slice(&sequence.to_be_bytes());
+        body.extend_from_slice(data);
+        body
+    }
+
+    /// Create an error message quoting as much of `original` as fits in
+    /// the minimum MTU
+    pub fn error(message_type: Icmpv6Type, code: u8, parameter: u32, original: &[u8]) -> Self {
+        let room = ipv6::MIN_MTU - Ipv6Header::SIZE - Self::HEADER_SIZE - 4;
+        let mut body = Vec::with_capacity(4 + original.len().min(room));
+        body.extend_from_slice(&parameter.to_be_bytes());
+        body.extend_from_slice(&original[..original.len().min(room)]);
+        Self::new(message_type, code, body)
+    }
+
+    /// Get echo identifier
+    pub fn identifier(&self) -> u16 {
+        match self.body.get(0..2) {
+            Some(b) => u16::from_be_bytes([b[0], b[1]]),
+            None => 0,
+        }
+    }
+
+    /// Get echo sequence number
+    pub fn sequence(&self) -> u16 {
+        match self.body.get(2..4) {
+            Some(b) => u16::from_be_bytes([b[0], b[1]]),
+            None => 0,
+        }
+    }
+
+    /// Get echo data
+    pub fn echo_data(&self) -> &[u8] {
+        self.body.get(4..).unwrap_or(&[])
+    }
+
+    /// Serialize packet to bytes, filling in the checksum for the given
+    /// source and destination
+    pub fn to_bytes(&self, source: Ipv6Addr, dest: Ipv6Addr) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::HEADER_SIZE + self.body.len());
+        bytes.push(self.message_type as u8);
+        bytes.push(self.code);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.body);
+        let checksum = ipv6::pseudo_header_checksum(source, dest, ipv6::protocols::ICMPV6, &bytes);
+        bytes[2..4].copy_from_slice(&checksum.to_be_bytes());
+        bytes
+    }
+
+    /// Parse packet from bytes, verifying the checksum
+    pub fn from_bytes(source: Ipv6Addr, dest: Ipv6Addr, bytes: &[u8]) -> Result<Self, Icmpv6Error> {
+        if bytes.len() < Self::HEADER_SIZE {
+            return Err(Icmpv6Error::PacketTooSmall);
+        }
+        if ipv6::pseudo_header_checksum(source, dest, ipv6::protocols::ICMPV6, bytes) != 0 {
+            return Err(Icmpv6Error::InvalidChecksum);
+        }
+        let message_type = Icmpv6Type::from_u8(bytes[0]).ok_or(Icmpv6Error::InvalidMessageType)?;
+
+        Ok(Self {
+            message_type,
+            code: bytes[1],
+            checksum: u16::from_be_bytes([bytes[2], bytes[3]]),
+            body: bytes[Self::HEADER_SIZE..].to_vec(),
+        })
+    }
+}
+
+/// Prefix Information option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixInfo {
+    /// Prefix length in bits
+    pub prefix_len: u8,
+    /// Prefix is on-link (L flag)
+    pub on_link: bool,
+    /// Prefix can be used for autoconfiguration (A flag)
+    pub autonomous: bool,
+    /// Valid lifetime in seconds (`u32::MAX` is infinite)
+    pub valid_lifetime: u32,
+    /// Preferred lifetime in seconds (`u32::MAX` is infinite)
+    pub preferred_lifetime: u32,
+    /// The prefix
+    pub prefix: Ipv6Addr,
+}
+
+/// Neighbor Discovery option
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NdOption {
+    /// Link-layer address of the sender
+    SourceLinkAddr(MacAddr),
+    /// Link-layer address of the target
+    TargetLinkAddr(MacAddr),
+    /// Prefix Information
+    PrefixInfo(PrefixInfo),
+    /// Link MTU
+    Mtu(u32),
+    /// Option this stack does not interpret
+    Unknown(u8),
+}
+
+impl NdOption {
+    const SOURCE_LINK_ADDR: u8 = 1;
+    const TARGET_LINK_ADDR: u8 = 2;
+    const PREFIX_INFO: u8 = 3;
+    const MTU: u8 = 5;
+
+    /// Serialize option to bytes, padded to a multiple of 8
+    fn to_bytes(&self, out: &mut Vec<u8>) {
+        match self {
+            NdOption::SourceLinkAddr(mac) | NdOption::TargetLinkAddr(mac) => {
+                let kind = match self {
+                    NdOption::SourceLinkAddr(_) => Self::SOURCE_LINK_ADDR,
+                    _ => Self::TARGET_LINK_ADDR,
+                };
+                out.extend_from_slice(&[kind, 1]);
+                out.extend_from_slice(&mac.bytes());
+            }
+            NdOption::PrefixInfo(info) => {
+                let flags = ((info.on_link as u8) << 7) | ((info.autonomous as u8) << 6);
+                out.extend_from_slice(&[Self::PREFIX_INFO, 4, info.prefix_len, flags]);
+                out.extend_from_slice(&info.valid_lifetime.to_be_bytes());
+                out.extend_from_slice(&info.preferred_lifetime.to_be_bytes());
+                out.extend_from_slice(&[0; 4]);
+                out.extend_from_slice(&info.prefix.octets());
+            }
+            NdOption::Mtu(mtu) => {
+                out.extend_from_slice(&[Self::MTU, 1, 0, 0]);
+                out.extend_from_slice(&mtu.to_be_bytes());
+            }
+            NdOption::Unknown(_) => {}
+        }
+    }
+
+    /// Parse the options that fill `bytes`
+    fn parse_all(mut bytes: &[u8]) -> Result<Vec<NdOption>, Icmpv6Error> {
+        let mut options = Vec::new();
+        while !bytes.is_empty() {
+            if bytes.len() < 2 {
+                return Err(Icmpv6Error::InvalidPacket);
+            }
+            let len = bytes[1] as usize * 8;
+            // A zero length option would loop forever, the message is invalid
+            if len == 0 || len > bytes.len() {
+                return Err(Icmpv6Error::InvalidPacket);
+            }
+            let data = &bytes[..len];
+            let mac = || {
+                let mut mac = [0u8; 6];
+                mac.copy_from_slice(&data[2..8]);
+                MacAddr::new(mac)
+            };
+            options.push(match data[0] {
+                Self::SOURCE_LINK_ADDR => NdOption::SourceLinkAddr(mac()),
+                Self::TARGET_LINK_ADDR => NdOption::TargetLinkAddr(mac()),
+                Self::PREFIX_INFO if len == 32 => {
+                    let mut prefix = [0u8; 16];
+                    prefix.copy_from_slice(&data[16..32]);
+                    NdOption::PrefixInfo(PrefixInfo {
+                        prefix_len: data[2],
+                        on_link: data[3] & 0x80 != 0,
+                        autonomous: data[3] & 0x40 != 0,
+                        valid_lifetime: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+                        preferred_lifetime: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+                        prefix: Ipv6Addr::from_octets(prefix),
+                    })
+                }
+                Self::MTU => NdOption::Mtu(u32::from_be_bytes([data[4], data[5], data[6], data[7]])),
+                other => NdOption::Unknown(other),
+            });
+            bytes = &bytes[len..];
+        }
+        Ok(options)
+    }
+}
+
+/// Neighbor Discovery message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NdMessage {
+    /// Router Solicitation
+    RouterSolicitation {
+        options: Vec<NdOption>,
+    },
+    /// Router Advertisement
+    RouterAdvertisement {
+        /// Hop limit hosts should use, 0 if unspecified
+        cur_hop_limit: u8,
+        /// Addresses are available from DHCPv6 (M flag)
+        managed: bool,
+        /// Other configuration is available from DHCPv6 (O flag)
+        other: bool,
+        /// Lifetime as default router in seconds, 0 if not a default router
+        router_lifetime: u16,
+        /// Reachable time in milliseconds, 0 if unspecified
+        reachable_time: u32,
+        /// Retransmission timer in milliseconds, 0 if unspecified
+        retrans_timer: u32,
+        options: Vec<NdOption>,
+    },
+    /// Neighbor Solicitation
+    NeighborSolicitation {
+        target: Ipv6Addr,
+        options: Vec<NdOption>,
+    },
+    /// Neighbor Advertisement
+    NeighborAdvertisement {
+        /// Sender is a router (R flag)
+        router: bool,
+        /// Sent in response to a solicitation (S flag)
+        solicited: bool,
+        /// Should replace an existing cache entry (O flag)
+        override_flag: bool,
+        target: Ipv6Addr,
+        options: Vec<NdOption>,
+    },
+}
+
+impl NdMessage {
+    /// Decode a Neighbor Discovery message from an ICMPv6 packet
+    pub fn from_packet(packet: &Icmpv6Packet) -> Result<Self, Icmpv6Error> {
+        if packet.code != 0 {
+            return Err(Icmpv6Error::InvalidPacket);
+        }
+        let body = &packet.body;
+        let target = |body: &[u8]| -> Result<Ipv6Addr, Icmpv6Error> {
+            let bytes = body.get(4..20).ok_or(Icmpv6Error::PacketTooSmall)?;
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            Ok(Ipv6Addr::from_octets(octets))
+        };
+
+        match packet.message_type {
+            Icmpv6Type::RouterSolicitation => {
+                let options = NdOption::parse_all(body.get(4..).ok_or(Icmpv6Error::PacketTooSmall)?)?;
+                Ok(NdMessage::RouterSolicitation { options })
+            }
+            Icmpv6Type::RouterAdvertisement => {
+                if body.len() < 12 {
+                    return Err(Icmpv6Error::PacketTooSmall);
+                }
+                Ok(NdMessage::RouterAdvertisement {
+                    cur_hop_limit: body[0],
+                    managed: body[1] & 0x80 != 0,
+                    other: body[1] & 0x40 != 0,
+                    router_lifetime: u16::from_be_bytes([body[2], body[3]]),
+                    reachable_time: u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+                    retrans_timer: u32::from_be_bytes([body[8], body[9], body[10], body[11]]),
+                    options: NdOption::parse_all(&body[12..])?,
+                })
+            }
+            Icmpv6Type::NeighborSolicitation => {
+                let target = target(body)?;
+                if target.is_multicast() {
+                    return Err(Icmpv6Error::InvalidPacket);
+                }
+                Ok(NdMessage::NeighborSolicitation { target, options: NdOption::parse_all(&body[20..])? })
+            }
+            Icmpv6Type::NeighborAdvertisement => {
+                let target = target(body)?;
+                if target.is_multicast() {
+                    return Err(Icmpv6Error::InvalidPacket);
+                }
+                Ok(NdMessage::NeighborAdvertisement {
+                    router: body[0] & 0x80 != 0,
+                    solicited: body[0] & 0x40 != 0,
+                    override_flag: body[0] & 0x20 != 0,
+                    target,
+                    options: NdOption::parse_all(&body[20..])?,
+                })
+            }
+            _ => Err(Icmpv6Error::InvalidMessageType),
+        }
+    }
+
+    /// Encode this message as an ICMPv6 packet
+    pub fn to_packet(&self) -> Icmpv6Packet {
+        let mut body = Vec::new();
+        let (message_type, options) = match self {
+            NdMessage::RouterSolicitation { options } => {
+                body.extend_from_slice(&[0; 4]);
+                (Icmpv6Type::RouterSolicitation, options)
+            }
+            NdMessage::RouterAdvertisement {
+                cur_hop_limit, managed, other, router_lifetime, reachable_time, retrans_timer, options,
+            } => {
+                body.push(*cur_hop_limit);
+                body.push(((*managed as u8) << 7) | ((*other as u8) << 6));
+                body.extend_from_slice(&router_lifetime.to_be_bytes());
+                body.extend_from_slice(&reachable_time.to_be_bytes());
+                body.extend_from_slice(&retrans_timer.to_be_bytes());
+                (Icmpv6Type::RouterAdvertisement, options)
+            }
+            NdMessage::NeighborSolicitation { target, options } => {
+                body.extend_from_slice(&[0; 4]);
+                body.extend_from_slice(&target.octets());
+                (Icmpv6Type::NeighborSolicitation, options)
+            }
+            NdMessage::NeighborAdvertisement { router, solicited, override_flag, target, options } => {
+                let flags = ((*router as u8) << 7) | ((*solicited as u8) << 6) | ((*override_flag as u8) << 5);
+                body.extend_from_slice(&[flags, 0, 0, 0]);
+                body.extend_from_slice(&target.octets());
+                (Icmpv6Type::NeighborAdvertisement, options)
+            }
+        };
+        for option in options {
+            option.to_bytes(&mut body);
+        }
+        Icmpv6Packet::new(message_type, 0, body)
+    }
+
+    /// Get the message's options
+    pub fn options(&self) -> &[NdOption] {
+        match self {
+            NdMessage::RouterSolicitation { options }
+            | NdMessage::RouterAdvertisement { options, .. }
+            | NdMessage::NeighborSolicitation { options, .. }
+            | NdMessage::NeighborAdvertisement { options, .. } => options,
+        }
+    }
+
+    /// Get the Source Link-Layer Address option, if present
+    pub fn source_link_addr(&self) -> Option<MacAddr> {
+        self.options().iter().find_map(|o| match o {
+            NdOption::SourceLinkAddr(mac) => Some(*mac),
+            _ => None,
+        })
+    }
+
+    /// Get the Target Link-Layer Address option, if present
+    pub fn target_link_addr(&self) -> Option<MacAddr> {
+        self.options().iter().find_map(|o| match o {
+            NdOption::TargetLinkAddr(mac) => Some(*mac),
+            _ => None,
+        })
+    }
+}
+
+/// ICMPv6 errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Icmpv6Error {
+    /// Packet too small
+    PacketTooSmall,
+    /// Invalid message type
+    InvalidMessageType,
+    /// Invalid checksum
+    InvalidChecksum,
+    /// Invalid packet format
+    InvalidPacket,
+}
+
+/// ICMPv6 processor for echo and error messages
+///
+/// Neighbor Discovery messages are handled by [`super::ndp::NdpProcessor`].
+pub struct Icmpv6Processor;
+
+impl Icmpv6Processor {
+    /// Create a new ICMPv6 processor
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Process an incoming echo or error message, returning the reply if any
+    pub fn process_packet(
+        &self,
+        _source_addr: Ipv6Addr,
+        _dest_addr: Ipv6Addr,
+        packet: &Icmpv6Packet,
+    ) -> Option<Icmpv6Packet> {
+        match packet.message_type {
+            Icmpv6Type::EchoRequest => Some(
+                Icmpv6Packet::echo_reply(packet.identifier(), packet.sequence(), packet.echo_data()),
+            ),
+            Icmpv6Type::EchoReply => {
+                // Handle echo reply (could notify waiting processes)
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for Icmpv6Processor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -16,6 +16,8 @@ use super::packet::{Packet, PacketType};
 // use super::packet::{PacketBuffer, PacketError};
 use super::arp::ArpCache;
 use super::ipv4::Ipv4Addr;
+use super::ipv6::{self, Ipv6Addr};
+use super::ndp::{self, NeighborCache};
 
 /// Network interface configuration
 #[derive(Debug, Clone)]
@@ -28,6 +30,10 @@ pub struct InterfaceConfig {
     pub ipv4_netmask: Option<Ipv4Addr>,
     /// IPv4 gateway
     pub ipv4_gateway: Option<Ipv4Addr>,
+    /// Static IPv6 addresses with their prefix lengths
+    pub ipv6_addrs: Vec<(Ipv6Addr, u8)>,
+    /// IPv6 gateway (usually learned from Router Advertisements instead)
+    pub ipv6_gateway: Option<Ipv6Addr>,
     /// Interface is up
     pub is_up: bool,
     /// MTU override (None to use device MTU)
@@ -41,12 +47,55 @@ impl Default for InterfaceConfig {
             ipv4_addr: None,
             ipv4_netmask: None,
             ipv4_gateway: None,
+            ipv6_addrs: Vec::new(),
+            ipv6_gateway: None,
             is_up: false,
             mtu: None,
         }
     }
 }
 
+/// How an IPv6 address was configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inet6Origin {
+    /// Link-local address derived from the MAC address
+    LinkLocal,
+    /// Stateless autoconfiguration from a Router Advertisement prefix
+    Slaac,
+    /// Configured by the administrator
+    Static,
+}
+
+/// Usability of an IPv6 address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inet6State {
+    /// Duplicate address detection has not finished, the address can't be used
+    Tentative,
+    /// Usable for new and existing communication
+    Preferred,
+    /// Preferred lifetime ran out, kept for existing communication only
+    Deprecated,
+}
+
+/// IPv6 address assigned to an interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inet6Addr {
+    /// The address
+    pub addr: Ipv6Addr,
+    /// Length of the on-link prefix
+    pub prefix_len: u8,
+    /// How the address was configured
+    pub origin: Inet6Origin,
+    /// Current state
+    pub state: Inet6State,
+    /// Uptime in milliseconds at which the address is removed (None for never)
+    pub valid_until: Option<u64>,
+    /// Uptime in milliseconds at which the address is deprecated (None for never)
+    pub preferred_until: Option<u64>,
+    /// Uptime at which duplicate address detection started
+    pub dad_started_ms: Option<u64>,
+}
+
 /// Network interface
 pub struct Interface {
     /// Interface ID
@@ -61,6 +110,10 @@ pub struct Interface {
     is_up: AtomicBool,
     /// ARP cache
     arp_cache: Mutex<ArpCache>,
+    /// IPv6 addresses
+    ipv6_addrs: Mutex<Vec<Inet6Addr>>,
+    /// IPv6 neighbor cache
+    neighbor_cache: Mutex<NeighborCache>,
     /// Interface statistics
     stats: Mutex<InterfaceStats>,
     /// Packet receive queue
@@ -84,6 +137,8 @@ impl Interface {
             config: Mutex::new(config),
             is_up: AtomicBool::new(false),
             arp_cache: Mutex::new(ArpCache::new()),
+            ipv6_addrs: Mutex::new(Vec::new()),
+            neighbor_cache: Mutex::new(NeighborCache::new()),
             stats: Mutex::new(InterfaceStats::new()),
             rx_queue: Mutex::new(Vec::new()),
             max_queue_size: 1000,
@@ -140,6 +195,14 @@ impl Interface {
             config.is_up = true;
         }
 
+        // Every IPv6 interface needs a link-local address for Neighbor Discovery
+        if self.device_type() != NetworkDeviceType::Loopback {
+            let link_local = Ipv6Addr::link_local_from_mac(self.mac_address());
+            if self.ipv6_addr_state(link_local).is_none() {
+                self.add_ipv6_addr(ndp::new_autoconf_addr(link_local, 64, Inet6Origin::LinkLocal));
+            }
+        }
+
         crate::log_info!("Network interface '{}' (ID: {}) is up", self.name.clone(), self.id);
         Ok(())
     }
@@ -164,6 +227,15 @@ impl Interface {
             queue.clear();
         }
 
+        // Autoconfigured addresses are learned again when the link comes back,
+        // static ones have to pass duplicate address detection again
+        self.ipv6_addrs.lock().retain_mut(|a| {
+            a.state = Inet6State::Tentative;
+            a.dad_started_ms = None;
+            a.origin == Inet6Origin::Static
+        });
+        self.neighbor_cache.lock().clear();
+
         crate::log_info!("Network interface '{}' (ID: {}) is down", self.name.clone(), self.id);
         Ok(())
     }
@@ -179,6 +251,10 @@ impl Interface {
             }
         }
 
+        if config.ipv6_addrs.iter().any(|&(addr, len)| len > 128 || addr.is_multicast() || addr.is_unspecified()) {
+            return Err(InterfaceError::InvalidConfig);
+        }
+
         // Update device MTU if specified
         if let Some(mtu) = config.mtu {
             if mtu < 68 || mtu > self.device.mtu() {
@@ -191,7 +267,29 @@ impl Interface {
         current_config.ipv4_addr = config.ipv4_addr;
         current_config.ipv4_netmask = config.ipv4_netmask;
         current_config.ipv4_gateway = config.ipv4_gateway;
+        current_config.ipv6_addrs = config.ipv6_addrs.clone();
+        current_config.ipv6_gateway = config.ipv6_gateway;
         current_config.mtu = config.mtu;
+        drop(current_config);
+
+        // Replace the static IPv6 addresses, keeping autoconfigured ones
+        let loopback = self.device_type() == NetworkDeviceType::Loopback;
+        {
+            let mut addrs = self.ipv6_addrs.lock();
+            addrs.retain(|a| a.origin != Inet6Origin::Static);
+            for &(addr, prefix_len) in &config.ipv6_addrs {
+                addrs.push(Inet6Addr {
+                    addr,
+                    prefix_len,
+                    origin: Inet6Origin::Static,
+                    // There is nobody to collide with on loopback
+                    state: if loopback { Inet6State::Preferred } else { Inet6State::Tentative },
+                    valid_until: None,
+                    preferred_until: None,
+                    dad_started_ms: None,
+                });
+            }
+        }
 
         // Update interface name
         self.name = config.name.clone();
@@ -236,6 +334,149 @@ impl Interface {
         config.ipv4_addr == Some(ip)
     }
 
+    /// Get the IPv6 address list
+    pub fn ipv6_addresses(&self) -> &Mutex<Vec<Inet6Addr>> {
+        &self.ipv6_addrs
+    }
+
+    /// Get the IPv6 addresses that can be used (not tentative)
+    pub fn ipv6_addrs(&self) -> Vec<Ipv6Addr> {
+        self.ipv6_addrs.lock()
+            .iter()
+            .filter(|a| a.state != Inet6State::Tentative)
+            .map(|a| a.addr)
+            .collect()
+    }
+
+    /// Get the state of one of our IPv6 addresses, None if it isn't ours
+    pub fn ipv6_addr_state(&self, addr: Ipv6Addr) -> Option<Inet6State> {
+        self.ipv6_addrs.lock().iter().find(|a| a.addr == addr).map(|a| a.state)
+    }
+
+    /// Add an IPv6 address
+    pub fn add_ipv6_addr(&self, addr: Inet6Addr) {
+        let mut addrs = self.ipv6_addrs.lock();
+        addrs.retain(|a| a.addr != addr.addr);
+        addrs.push(addr);
+    }
+
+    /// Remove an IPv6 address, returning whether it was assigned
+    pub fn remove_ipv6_addr(&self, addr: Ipv6Addr) -> bool {
+        let mut addrs = self.ipv6_addrs.lock();
+        let before = addrs.len();
+        addrs.retain(|a| a.addr != addr);
+        addrs.len() != before
+    }
+
+    /// Add or refresh an address autoconfigured from a prefix advertisement
+    ///
+    /// Follows RFC 4862 section 5.5.3: an advertisement can only shorten
+    /// the valid lifetime of an existing address down to two hours, so that
+    /// a spoofed advertisement can't take the address away at once.
+    pub fn update_slaac_addr(
+        &self,
+        addr: Ipv6Addr,
+        prefix_len: u8,
+        valid_lifetime: u32,
+        preferred_lifetime: u32,
+        now_ms: u64,
+    ) {
+        const TWO_HOURS_MS: u64 = 2 * 60 * 60 * 1000;
+        let valid_until = ndp::lifetime_deadline(valid_lifetime, now_ms);
+        let preferred_until = ndp::lifetime_deadline(preferred_lifetime, now_ms);
+
+        let mut addrs = self.ipv6_addrs.lock();
+        if let Some(existing) = addrs.iter_mut().find(|a| a.addr == addr) {
+            existing.preferred_until = preferred_until;
+            if existing.state == Inet6State::Deprecated && preferred_lifetime != 0 {
+                existing.state = Inet6State::Preferred;
+            }
+            let remaining = existing.valid_until.map(|t| t.saturating_sub(now_ms));
+            let new_valid = valid_until.map(|t| t - now_ms);
+            existing.valid_until = match (new_valid, remaining) {
+                (None, _) => None,
+                (Some(v), Some(r)) if v > TWO_HOURS_MS || v > r => valid_until,
+                (Some(_), Some(r)) if r <= TWO_HOURS_MS => existing.valid_until,
+                _ => Some(now_ms + TWO_HOURS_MS),
+            };
+            return;
+        }
+
+        if valid_lifetime == 0 {
+            return;
+        }
+        let mut entry = ndp::new_autoconf_addr(addr, prefix_len, Inet6Origin::Slaac);
+        entry.valid_until = valid_until;
+        entry.preferred_until = preferred_until;
+        addrs.push(entry);
+        crate::log_info!("Autoconfigured IPv6 address {}/{} on {}", addr, prefix_len, self.name.clone());
+    }
+
+    /// Check if an IPv6 destination is for this interface
+    ///
+    /// Besides our own addresses this accepts the all-nodes group and the
+    /// solicited-node groups of our addresses, including tentative ones so
+    /// that duplicate address detection sees other nodes' probes.
+    pub fn is_my_ipv6_address(&self, ip: Ipv6Addr) -> bool {
+        if ip == Ipv6Addr::ALL_NODES {
+            return true;
+        }
+        self.ipv6_addrs.lock().iter().any(|a| {
+            (a.addr == ip && a.state != Inet6State::Tentative) || a.addr.solicited_node() == ip
+        })
+    }
+
+    /// Check if an IPv6 address is on one of this interface's prefixes
+    pub fn is_in_ipv6_network(&self, ip: Ipv6Addr) -> bool {
+        self.ipv6_addrs.lock().iter().any(|a| ip.matches_prefix(a.addr, a.prefix_len))
+    }
+
+    /// Pick the source address for sending to `dest`
+    ///
+    /// Link-local and link-scope multicast destinations get the link-local
+    /// address. Others prefer a preferred global address sharing the
+    /// longest prefix with `dest`, and fall back to deprecated ones.
+    pub fn select_ipv6_source(&self, dest: Ipv6Addr) -> Option<Ipv6Addr> {
+        let addrs = self.ipv6_addrs.lock();
+        let usable = addrs.iter().filter(|a| a.state != Inet6State::Tentative);
+        let link_scope = dest.is_link_local()
+            || (dest.is_multicast() && (dest.octets()[1] & 0x0f) <= 2);
+        if link_scope {
+            return usable
+                .filter(|a| a.addr.is_link_local() || a.addr.is_loopback())
+                .map(|a| a.addr)
+                .next();
+        }
+
+        let common_prefix = |a: &Inet6Addr| {
+            (0..=128u8).rev().find(|&len| dest.matches_prefix(a.addr, len)).unwrap_or(0)
+        };
+        usable
+            .filter(|a| !a.addr.is_link_local())
+            .max_by_key(|a| (a.state == Inet6State::Preferred, common_prefix(a)))
+            .map(|a| a.addr)
+    }
+
+    /// Get IPv6 gateway
+    pub fn ipv6_gateway(&self) -> Option<Ipv6Addr> {
+        self.config.lock().ipv6_gateway
+    }
+
+    /// Get IPv6 neighbor cache reference
+    pub fn neighbor_cache(&self) -> &Mutex<NeighborCache> {
+        &self.neighbor_cache
+    }
+
+    /// Apply a link MTU learned from the network
+    ///
+    /// Values below the IPv6 minimum or above what the device supports
+    /// are ignored.
+    pub fn set_link_mtu(&self, mtu: usize) {
+        if mtu >= ipv6::MIN_MTU && mtu <= self.device.mtu() {
+            self.config.lock().mtu = Some(mtu);
+        }
+    }
+
     /// Send a packet through the interface
     pub fn send_packet(&self, packet: Packet) -> Result<(), InterfaceError> {
         if !self.is_up() {
@@ -281,7 +522,13 @@ impl Interface {
         // Try to receive from device
         match self.device.receive_packet() {
             Ok(Some(data)) => {
-                let packet = Packet::from_bytes(&data, PacketType::Ethernet)
+                // Loopback carries bare IP packets; tell the versions apart
+                let packet_type = match (self.device.device_type(), data.first().map(|b| b >> 4)) {
+                    (NetworkDeviceType::Loopback, Some(4)) => PacketType::Ipv4,
+                    (NetworkDeviceType::Loopback, Some(6)) => PacketType::Ipv6,
+                    _ => PacketType::Ethernet,
+                };
+                let packet = Packet::from_bytes(&data, packet_type)
                     .map_err(|e| InterfaceError::PacketError(e))?;
 
                 let mut stats = self.stats.lock();
@@ -440,6 +687,13 @@ impl InterfaceManager {
             .cloned()
     }
 
+    /// Find interface for a given IPv6 address
+    pub fn find_interface_for_ipv6(&self, ip: Ipv6Addr) -> Option<Arc<Interface>> {
+        self.interfaces.iter()
+            .find(|iface| iface.is_in_ipv6_network(ip))
+            .cloned()
+    }
+
     /// Get interface count
     pub fn count(&self) -> usize {
         self.interfaces.len()
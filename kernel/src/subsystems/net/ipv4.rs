@@ -501,6 +501,32 @@ pub mod protocols {
     pub const SCTP: u8 = 132;
 }
 
+/// Checksum over the IPv4 pseudo-header and upper-layer data
+///
+/// Used by TCP and UDP. Over data with a correct checksum in place the
+/// result is zero.
+pub fn pseudo_header_checksum(source: Ipv4Addr, dest: Ipv4Addr, protocol: u8, data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    let mut add = |bytes: &[u8]| {
+        for pair in bytes.chunks(2) {
+            let hi = pair[0] as u32;
+            let lo = pair.get(1).copied().unwrap_or(0) as u32;
+            sum += (hi << 8) | lo;
+        }
+    };
+
+    add(&source.octets());
+    add(&dest.octets());
+    add(&[0, protocol]);
+    add(&(data.len() as u16).to_be_bytes());
+    add(data);
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !sum as u16
+}
+
 /// IPv4 errors
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Ipv4Error {
@@ -0,0 +1,975 @@
+//! IPv6 protocol implementation
+//!
+//! This module provides IPv6 addresses, header parsing and generation, the
+//! extension header chain, fragment reassembly and the upper-layer checksum.
+
+extern crate alloc;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::device::MacAddr;
+use super::ipv4::Ipv4Addr;
+
+/// IPv6 address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ipv6Addr {
+    /// Address in network byte order
+    octets: [u8; 16],
+}
+
+impl Ipv6Addr {
+    /// Unspecified address (::)
+    pub const UNSPECIFIED: Self = Self::new(0, 0, 0, 0, 0, 0, 0, 0);
+
+    /// Loopback address (::1)
+    pub const LOCALHOST: Self = Self::new(0, 0, 0, 0, 0, 0, 0, 1);
+
+    /// All nodes on the link (ff02::1)
+    pub const ALL_NODES: Self = Self::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+    /// All routers on the link (ff02::2)
+    pub const ALL_ROUTERS: Self = Self::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+    /// Create a new IPv6 address from eight 16-bit segments
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(a: u16, b: u16, c: u16, d: u16, e: u16, f: u16, g: u16, h: u16) -> Self {
+        let segments = [a, b, c, d, e, f, g, h];
+        let mut octets = [0u8; 16];
+        let mut i = 0;
+        while i < 8 {
+            octets[i * 2] = (segments[i] >> 8) as u8;
+            octets[i * 2 + 1] = segments[i] as u8;
+            i += 1;
+        }
+        Self { octets }
+    }
+
+    /// Create IPv6 address from bytes (network byte order)
+    pub const fn from_octets(octets: [u8; 16]) -> Self {
+        Self { octets }
+    }
+
+    /// Get address as bytes (network byte order)
+    pub const fn octets(self) -> [u8; 16] {
+        self.octets
+    }
+
+    /// Get the eight 16-bit segments of the address
+    pub fn segments(self) -> [u16; 8] {
+        core::array::from_fn(|i| u16::from_be_bytes([self.octets[i * 2], self.octets[i * 2 + 1]]))
+    }
+
+    /// Check if address is unspecified (::)
+    pub fn is_unspecified(self) -> bool {
+        self == Self::UNSPECIFIED
+    }
+
+    /// Check if address is loopback (::1)
+    pub fn is_loopback(self) -> bool {
+        self == Self::LOCALHOST
+    }
+
+    /// Check if address is multicast (ff00::/8)
+    pub const fn is_multicast(self) -> bool {
+        self.octets[0] == 0xff
+    }
+
+    /// Check if address is link-local unicast (fe80::/10)
+    pub const fn is_link_local(self) -> bool {
+        self.octets[0] == 0xfe && (self.octets[1] & 0xc0) == 0x80
+    }
+
+    /// Check if address is unique local (fc00::/7)
+    pub const fn is_unique_local(self) -> bool {
+        (self.octets[0] & 0xfe) == 0xfc
+    }
+
+    /// Check if address is an IPv4-mapped address (::ffff:a.b.c.d)
+    pub fn is_ipv4_mapped(self) -> bool {
+        self.octets[..10] == [0; 10] && self.octets[10] == 0xff && self.octets[11] == 0xff
+    }
+
+    /// Get the IPv4 address this address maps, if it is IPv4-mapped
+    pub fn to_ipv4_mapped(self) -> Option<Ipv4Addr> {
+        if self.is_ipv4_mapped() {
+            Some(Ipv4Addr::new(self.octets[12], self.octets[13], self.octets[14], self.octets[15]))
+        } else {
+            None
+        }
+    }
+
+    /// Create the IPv4-mapped address for `addr`
+    pub fn from_ipv4_mapped(addr: Ipv4Addr) -> Self {
+        let mut octets = [0u8; 16];
+        octets[10] = 0xff;
+        octets[11] = 0xff;
+        octets[12..].copy_from_slice(&addr.to_be_bytes());
+        Self { octets }
+    }
+
+    /// Check if the first `prefix_len` bits match those of `prefix`
+    pub fn matches_prefix(self, prefix: Ipv6Addr, prefix_len: u8) -> bool {
+        let bits = prefix_len.min(128) as usize;
+        let whole = bits / 8;
+        if self.octets[..whole] != prefix.octets[..whole] {
+            return false;
+        }
+        let rest = bits % 8;
+        if rest == 0 {
+            return true;
+        }
+        let mask = 0xffu8 << (8 - rest);
+        (self.octets[whole] & mask) == (prefix.octets[whole] & mask)
+    }
+
+    /// Keep the first `prefix_len` bits and clear the rest
+    pub fn network(self, prefix_len: u8) -> Self {
+        let mut octets = [0u8; 16];
+        let bits = prefix_len.min(128) as usize;
+        octets[..bits / 8].copy_from_slice(&self.octets[..bits / 8]);
+        if bits % 8 != 0 {
+            octets[bits / 8] = self.octets[bits / 8] & (0xffu8 << (8 - bits % 8));
+        }
+        Self { octets }
+    }
+
+    /// Solicited-node multicast group for this address (ff02::1:ffXX:XXXX)
+    pub fn solicited_node(self) -> Self {
+        let mut octets = Self::new(0xff02, 0, 0, 0, 0, 1, 0xff00, 0).octets;
+        octets[13..].copy_from_slice(&self.octets[13..]);
+        Self { octets }
+    }
+
+    /// Check if this is a solicited-node multicast address
+    pub fn is_solicited_node(self) -> bool {
+        let prefix = Self::new(0xff02, 0, 0, 0, 0, 1, 0xff00, 0);
+        self.matches_prefix(prefix, 104)
+    }
+
+    /// Address made of a /64 prefix and the modified EUI-64 of `mac`
+    pub fn from_prefix_and_mac(prefix: Ipv6Addr, mac: MacAddr) -> Self {
+        let mac = mac.bytes();
+        let mut octets = prefix.network(64).octets;
+        octets[8] = mac[0] ^ 0x02;
+        octets[9] = mac[1];
+        octets[10] = mac[2];
+        octets[11] = 0xff;
+        octets[12] = 0xfe;
+        octets[13] = mac[3];
+        octets[14] = mac[4];
+        octets[15] = mac[5];
+        Self { octets }
+    }
+
+    /// Link-local address derived from `mac` (fe80::/64 + EUI-64)
+    pub fn link_local_from_mac(mac: MacAddr) -> Self {
+        Self::from_prefix_and_mac(Self::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), mac)
+    }
+
+    /// Ethernet multicast address that carries this multicast group (33:33:xx:xx:xx:xx)
+    pub fn multicast_mac(self) -> MacAddr {
+        MacAddr::new([0x33, 0x33, self.octets[12], self.octets[13], self.octets[14], self.octets[15]])
+    }
+
+    /// Parse IPv6 address from string
+    ///
+    /// Accepts the full and the `::` compressed forms, with an optional
+    /// dotted IPv4 address in the last 32 bits.
+    pub fn from_str(s: &str) -> Result<Self, Ipv6ParseError> {
+        fn parse_groups(s: &str, out: &mut Vec<u16>, last: bool) -> Result<(), Ipv6ParseError> {
+            if s.is_empty() {
+                return Ok(());
+            }
+            let parts: Vec<&str> = s.split(':').collect();
+            for (i, part) in parts.iter().enumerate() {
+                if last && i == parts.len() - 1 && part.contains('.') {
+                    let v4 = Ipv4Addr::from_str(part).map_err(|_| Ipv6ParseError::InvalidFormat)?;
+                    let bytes = v4.to_be_bytes();
+                    out.push(u16::from_be_bytes([bytes[0], bytes[1]]));
+                    out.push(u16::from_be_bytes([bytes[2], bytes[3]]));
+                } else if part.is_empty() || part.len() > 4 {
+                    return Err(Ipv6ParseError::InvalidFormat);
+                } else {
+                    let group = u16::from_str_radix(part, 16).map_err(|_| Ipv6ParseError::InvalidGroup)?;
+                    out.push(group);
+                }
+            }
+            Ok(())
+        }
+
+        let mut head = Vec::new();
+        let mut tail = Vec::new();
+        let compressed = match s.find("::") {
+            Some(pos) => {
+                let rest = &s[pos + 2..];
+                if rest.contains("::") {
+                    return Err(Ipv6ParseError::InvalidFormat);
+                }
+                parse_groups(&s[..pos], &mut head, false)?;
+                parse_groups(rest, &mut tail, true)?;
+                true
+            }
+            None => {
+                parse_groups(s, &mut head, true)?;
+                false
+            }
+        };
+
+        let total = head.len() + tail.len();
+        if (compressed && total > 7) || (!compressed && total != 8) {
+            return Err(Ipv6ParseError::InvalidFormat);
+        }
+
+        let mut segments = [0u16; 8];
+        segments[..head.len()].copy_from_slice(&head);
+        segments[8 - tail.len()..].copy_from_slice(&tail);
+        let [a, b, c, d, e, f, g, h] = segments;
+        Ok(Self::new(a, b, c, d, e, f, g, h))
+    }
+}
+
+impl fmt::Display for Ipv6Addr {
+    /// Format as recommended by RFC 5952: lower case, longest run of zero
+    /// groups compressed to `::`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(v4) = self.to_ipv4_mapped() {
+            return write!(f, "::ffff:{}", v4);
+        }
+
+        let segments = self.segments();
+        let (mut best_start, mut best_len) = (8, 0);
+        let mut i = 0;
+        while i < 8 {
+            if segments[i] == 0 {
+                let start = i;
+                while i < 8 && segments[i] == 0 {
+                    i += 1;
+                }
+                if i - start > best_len && i - start >= 2 {
+                    best_start = start;
+                    best_len = i - start;
+                }
+            } else {
+                i += 1;
+            }
+        }
+
+        let mut i = 0;
+        while i < 8 {
+            if i == best_start {
+                write!(f, "::")?;
+                i += best_len;
+                continue;
+            }
+            if i > 0 && i != best_start + best_len {
+                write!(f, ":")?;
+            }
+            write!(f, "{:x}", segments[i])?;
+            i += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Ipv6Addr {
+    fn default() -> Self {
+        Self::UNSPECIFIED
+    }
+}
+
+/// IPv6 address parsing errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ipv6ParseError {
+    /// Invalid format
+    InvalidFormat,
+    /// Invalid hexadecimal group
+    InvalidGroup,
+}
+
+/// IP address of either family
+///
+/// Sockets and connection tables key on this so that one table serves
+/// both stacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IpAddr {
+    /// IPv4 address
+    V4(Ipv4Addr),
+    /// IPv6 address
+    V6(Ipv6Addr),
+}
+
+impl IpAddr {
+    /// IPv6 wildcard (::), which also accepts IPv4 on dual-stack sockets
+    pub const UNSPECIFIED: Self = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
+
+    /// Check if address is a wildcard of either family
+    pub fn is_unspecified(self) -> bool {
+        match self {
+            IpAddr::V4(addr) => addr.is_unspecified(),
+            IpAddr::V6(addr) => addr.is_unspecified(),
+        }
+    }
+
+    /// Check if address is loopback
+    pub fn is_loopback(self) -> bool {
+        match self {
+            IpAddr::V4(addr) => addr.is_loopback(),
+            IpAddr::V6(addr) => addr.is_loopback(),
+        }
+    }
+
+    /// Check if address is multicast
+    pub fn is_multicast(self) -> bool {
+        match self {
+            IpAddr::V4(addr) => addr.is_multicast(),
+            IpAddr::V6(addr) => addr.is_multicast(),
+        }
+    }
+
+    /// Check if this is an IPv4 address
+    pub fn is_ipv4(self) -> bool {
+        matches!(self, IpAddr::V4(_))
+    }
+
+    /// Check if this is an IPv6 address
+    pub fn is_ipv6(self) -> bool {
+        matches!(self, IpAddr::V6(_))
+    }
+
+    /// Unspecified address of the same family
+    pub fn unspecified_like(self) -> Self {
+        match self {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+
+    /// Turn IPv4-mapped IPv6 addresses back into IPv4
+    pub fn to_canonical(self) -> Self {
+        match self {
+            IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => self,
+            },
+            IpAddr::V4(_) => self,
+        }
+    }
+
+    /// Get the address as IPv6, mapping IPv4 addresses
+    pub fn to_ipv6(self) -> Ipv6Addr {
+        match self {
+            IpAddr::V4(addr) => Ipv6Addr::from_ipv4_mapped(addr),
+            IpAddr::V6(addr) => addr,
+        }
+    }
+}
+
+impl From<Ipv4Addr> for IpAddr {
+    fn from(addr: Ipv4Addr) -> Self {
+        IpAddr::V4(addr)
+    }
+}
+
+impl From<Ipv6Addr> for IpAddr {
+    fn from(addr: Ipv6Addr) -> Self {
+        IpAddr::V6(addr)
+    }
+}
+
+impl fmt::Display for IpAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpAddr::V4(addr) => write!(f, "{}", addr),
+            IpAddr::V6(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+impl Default for IpAddr {
+    fn default() -> Self {
+        Self::UNSPECIFIED
+    }
+}
+
+/// IPv6 fixed header
+#[derive(Debug, Clone)]
+pub struct Ipv6Header {
+    /// Traffic class
+    pub traffic_class: u8,
+    /// Flow label (20 bits)
+    pub flow_label: u32,
+    /// Length of everything after the fixed header
+    pub payload_length: u16,
+    /// Type of the first extension header or upper-layer protocol
+    pub next_header: u8,
+    /// Hop limit
+    pub hop_limit: u8,
+    /// Source address
+    pub source_addr: Ipv6Addr,
+    /// Destination address
+    pub dest_addr: Ipv6Addr,
+}
+
+impl Ipv6Header {
+    /// IPv6 version
+    pub const VERSION: u8 = 6;
+
+    /// Header size in bytes
+    pub const SIZE: usize = 40;
+
+    /// Create a new IPv6 header
+    pub fn new(
+        source_addr: Ipv6Addr,
+        dest_addr: Ipv6Addr,
+        next_header: u8,
+        payload_length: u16,
+        hop_limit: u8,
+    ) -> Self {
+        Self {
+            traffic_class: 0,
+            flow_label: 0,
+            payload_length,
+            next_header,
+            hop_limit,
+            source_addr,
+            dest_addr,
+        }
+    }
+
+    /// Serialize header to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        let first = ((Self::VERSION as u32) << 28)
+            | ((self.traffic_class as u32) << 20)
+            | (self.flow_label & 0x000F_FFFF);
+        bytes.extend_from_slice(&first.to_be_bytes());
+        bytes.extend_from_slice(&self.payload_length.to_be_bytes());
+        bytes.push(self.next_header);
+        bytes.push(self.hop_limit);
+        bytes.extend_from_slice(&self.source_addr.octets());
+        bytes.extend_from_slice(&self.dest_addr.octets());
+        bytes
+    }
+
+    /// Parse header from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Ipv6Error> {
+        if bytes.len() < Self::SIZE {
+            return Err(Ipv6Error::PacketTooSmall);
+        }
+
+        let first = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if (first >> 28) as u8 != Self::VERSION {
+            return Err(Ipv6Error::InvalidVersion);
+        }
+
+        let mut source = [0u8; 16];
+        let mut dest = [0u8; 16];
+        source.copy_from_slice(&bytes[8..24]);
+        dest.copy_from_slice(&bytes[24..40]);
+
+        Ok(Self {
+            traffic_class: (first >> 20) as u8,
+            flow_label: first & 0x000F_FFFF,
+            payload_length: u16::from_be_bytes([bytes[4], bytes[5]]),
+            next_header: bytes[6],
+            hop_limit: bytes[7],
+            source_addr: Ipv6Addr::from_octets(source),
+            dest_addr: Ipv6Addr::from_octets(dest),
+        })
+    }
+}
+
+/// Fragment extension header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv6FragmentHeader {
+    /// Header following the fragmentable part
+    pub next_header: u8,
+    /// Offset of this fragment in 8-byte units
+    pub offset: u16,
+    /// More fragments follow
+    pub more_fragments: bool,
+    /// Identification shared by all fragments of a packet
+    pub identification: u32,
+}
+
+impl Ipv6FragmentHeader {
+    /// Header size in bytes
+    pub const SIZE: usize = 8;
+
+    /// Serialize header to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.push(self.next_header);
+        bytes.push(0);
+        let offset_flags = (self.offset << 3) | self.more_fragments as u16;
+        bytes.extend_from_slice(&offset_flags.to_be_bytes());
+        bytes.extend_from_slice(&self.identification.to_be_bytes());
+        bytes
+    }
+
+    /// Parse header from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Ipv6Error> {
+        if bytes.len() < Self::SIZE {
+            return Err(Ipv6Error::PacketTooSmall);
+        }
+        let offset_flags = u16::from_be_bytes([bytes[2], bytes[3]]);
+        Ok(Self {
+            next_header: bytes[0],
+            offset: offset_flags >> 3,
+            more_fragments: offset_flags & 1 != 0,
+            identification: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        })
+    }
+
+    /// Byte offset of this fragment's data
+    pub fn byte_offset(&self) -> usize {
+        self.offset as usize * 8
+    }
+
+    /// Check if this is the only fragment of its packet
+    pub fn is_atomic(&self) -> bool {
+        self.offset == 0 && !self.more_fragments
+    }
+}
+
+/// Extension header found in a packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtensionHeader {
+    /// Hop-by-Hop options
+    HopByHop,
+    /// Routing header with its type and remaining segments
+    Routing { routing_type: u8, segments_left: u8 },
+    /// Fragment header
+    Fragment(Ipv6FragmentHeader),
+    /// Destination options
+    DestinationOptions,
+    /// Authentication header
+    Authentication,
+}
+
+/// Result of walking the extension header chain
+#[derive(Debug, Clone)]
+pub struct ExtensionChain {
+    /// Extension headers in the order they appear
+    pub headers: Vec<ExtensionHeader>,
+    /// Upper-layer protocol, or `NO_NEXT_HEADER`
+    pub upper_protocol: u8,
+    /// Offset of the upper-layer data within the payload
+    pub payload_offset: usize,
+    /// Offset of the Fragment header within the payload, if any
+    pub fragment_offset: Option<usize>,
+}
+
+impl ExtensionChain {
+    /// Get the Fragment header, if there is one
+    pub fn fragment(&self) -> Option<Ipv6FragmentHeader> {
+        self.headers.iter().find_map(|h| match h {
+            ExtensionHeader::Fragment(frag) => Some(*frag),
+            _ => None,
+        })
+    }
+
+    /// Walk the extension headers in `payload`, starting at `next_header`
+    ///
+    /// Unknown options are handled according to the two high bits of their
+    /// type. An error carries the offset of the offending field so that a
+    /// Parameter Problem can point at it.
+    pub fn parse(next_header: u8, payload: &[u8]) -> Result<Self, Ipv6Error> {
+        let mut headers = Vec::new();
+        let mut next = next_header;
+        let mut offset = 0;
+        let mut fragment_offset = None;
+
+        loop {
+            match next {
+                protocols::HOP_BY_HOP | protocols::DEST_OPTS => {
+                    if next == protocols::HOP_BY_HOP && offset != 0 {
+                        return Err(Ipv6Error::UnknownNextHeader { pointer: offset });
+                    }
+                    let len = Self::ext_len(payload, offset, 8)?;
+                    Self::check_options(&payload[offset + 2..offset + len], offset + 2)?;
+                    headers.push(if next == protocols::HOP_BY_HOP {
+                        ExtensionHeader::HopByHop
+                    } else {
+                        ExtensionHeader::DestinationOptions
+                    });
+                    next = payload[offset];
+                    offset += len;
+                }
+                protocols::ROUTING => {
+                    let len = Self::ext_len(payload, offset, 8)?;
+                    let routing_type = payload[offset + 2];
+                    let segments_left = payload[offset + 3];
+                    // No routing types are supported, so a header that still
+                    // has hops to visit can't be processed
+                    if segments_left != 0 {
+                        return Err(Ipv6Error::UnsupportedRouting { pointer: offset + 2 });
+                    }
+                    headers.push(ExtensionHeader::Routing { routing_type, segments_left });
+                    next = payload[offset];
+                    offset += len;
+                }
+                protocols::FRAGMENT => {
+                    let frag = Ipv6FragmentHeader::from_bytes(payload.get(offset..).unwrap_or(&[]))?;
+                    headers.push(ExtensionHeader::Fragment(frag));
+                    fragment_offset = Some(offset);
+                    next = frag.next_header;
+                    offset += Ipv6FragmentHeader::SIZE;
+                    // Headers after a non-first fragment are not in this piece
+                    if frag.offset != 0 {
+                        break;
+                    }
+                }
+                protocols::AUTH => {
+                    let len = Self::ext_len(payload, offset, 4)?;
+                    headers.push(ExtensionHeader::Authentication);
+                    next = payload[offset];
+                    offset += len;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(Self {
+            headers,
+            upper_protocol: next,
+            payload_offset: offset,
+            fragment_offset,
+        })
+    }
+
+    /// Length in bytes of the extension header at `offset`
+    ///
+    /// Its length byte counts `unit` sized words beyond the first 8 bytes
+    /// (AH counts 4-byte words beyond the first 8).
+    fn ext_len(payload: &[u8], offset: usize, unit: usize) -> Result<usize, Ipv6Error> {
+        if payload.len() < offset + 8 {
+            return Err(Ipv6Error::PacketTooSmall);
+        }
+        let len = if unit == 8 {
+            (payload[offset + 1] as usize + 1) * 8
+        } else {
+            (payload[offset + 1] as usize + 2) * 4
+        };
+        if payload.len() < offset + len {
+            return Err(Ipv6Error::PacketTooSmall);
+        }
+        Ok(len)
+    }
+
+    /// Check the TLV options of a Hop-by-Hop or Destination Options header
+    fn check_options(options: &[u8], base: usize) -> Result<(), Ipv6Error> {
+        let mut i = 0;
+        while i < options.len() {
+            let kind = options[i];
+            if kind == option_types::PAD1 {
+                i += 1;
+                continue;
+            }
+            let len = *options.get(i + 1).ok_or(Ipv6Error::PacketTooSmall)? as usize;
+            if i + 2 + len > options.len() {
+                return Err(Ipv6Error::PacketTooSmall);
+            }
+            match kind {
+                option_types::PADN | option_types::ROUTER_ALERT => {}
+                // 00: skip the option and keep going
+                _ if kind >> 6 == 0 => {}
+                _ => return Err(Ipv6Error::UnknownOption { pointer: base + i, action: kind >> 6 }),
+            }
+            i += 2 + len;
+        }
+        Ok(())
+    }
+}
+
+/// IPv6 packet
+#[derive(Debug, Clone)]
+pub struct Ipv6Packet {
+    /// Fixed header
+    pub header: Ipv6Header,
+    /// Extension headers and upper-layer data
+    pub payload: Vec<u8>,
+}
+
+impl Ipv6Packet {
+    /// Create a new IPv6 packet carrying `payload` of protocol `next_header`
+    pub fn new(
+        source_addr: Ipv6Addr,
+        dest_addr: Ipv6Addr,
+        next_header: u8,
+        payload: Vec<u8>,
+        hop_limit: u8,
+    ) -> Self {
+        let header = Ipv6Header::new(source_addr, dest_addr, next_header, payload.len() as u16, hop_limit);
+        Self { header, payload }
+    }
+
+    /// Serialize packet to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_bytes();
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Parse packet from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Ipv6Error> {
+        let header = Ipv6Header::from_bytes(bytes)?;
+        let end = Ipv6Header::SIZE + header.payload_length as usize;
+        if bytes.len() < end {
+            return Err(Ipv6Error::PacketTooSmall);
+        }
+        let payload = bytes[Ipv6Header::SIZE..end].to_vec();
+        Ok(Self { header, payload })
+    }
+
+    /// Get total packet length
+    pub fn len(&self) -> usize {
+        Ipv6Header::SIZE + self.payload.len()
+    }
+
+    /// Walk the extension header chain
+    pub fn extensions(&self) -> Result<ExtensionChain, Ipv6Error> {
+        ExtensionChain::parse(self.header.next_header, &self.payload)
+    }
+
+    /// Split the packet into fragments that fit in `mtu`
+    ///
+    /// Only the source fragments in IPv6, so this is used on the way out
+    /// when the path MTU is smaller than the packet. The payload is taken as
+    /// a single upper-layer unit with no extension headers.
+    pub fn fragment(&self, mtu: usize, identification: u32) -> Vec<Ipv6Packet> {
+        if self.len() <= mtu {
+            return alloc::vec![self.clone()];
+        }
+
+        let chunk = (mtu.max(MIN_MTU) - Ipv6Header::SIZE - Ipv6FragmentHeader::SIZE) & !7;
+        let mut fragments = Vec::new();
+        let mut offset = 0;
+        while offset < self.payload.len() {
+            let end = (offset + chunk).min(self.payload.len());
+            let frag = Ipv6FragmentHeader {
+                next_header: self.header.next_header,
+                offset: (offset / 8) as u16,
+                more_fragments: end < self.payload.len(),
+                identification,
+            };
+            let mut payload = frag.to_bytes();
+            payload.extend_from_slice(&self.payload[offset..end]);
+            let mut header = self.header.clone();
+            header.next_header = protocols::FRAGMENT;
+            header.payload_length = payload.len() as u16;
+            fragments.push(Ipv6Packet { header, payload });
+            offset = end;
+        }
+        fragments
+    }
+}
+
+/// Checksum over the IPv6 pseudo-header and upper-layer data
+///
+/// Used by ICMPv6, UDP and TCP. Over data with a correct checksum in place
+/// the result is zero.
+pub fn pseudo_header_checksum(source: Ipv6Addr, dest: Ipv6Addr, next_header: u8, data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    let mut add = |bytes: &[u8]| {
+        for pair in bytes.chunks(2) {
+            let hi = pair[0] as u32;
+            let lo = pair.get(1).copied().unwrap_or(0) as u32;
+            sum += (hi << 8) | lo;
+        }
+    };
+
+    add(&source.octets());
+    add(&dest.octets());
+    add(&(data.len() as u32).to_be_bytes());
+    add(&[0, 0, 0, next_header]);
+    add(data);
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !sum as u16
+}
+
+/// Key identifying the fragments of one packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct FragmentKey {
+    source: Ipv6Addr,
+    dest: Ipv6Addr,
+    identification: u32,
+}
+
+/// Fragments received so far for one packet
+#[derive(Debug, Clone)]
+struct PendingPacket {
+    /// Data pieces keyed by byte offset
+    pieces: BTreeMap<usize, Vec<u8>>,
+    /// Total length, known once the last fragment arrived
+    total_len: Option<usize>,
+    /// Upper-layer protocol from the first fragment
+    next_header: u8,
+    /// Uptime at which the first fragment arrived
+    started_ms: u64,
+}
+
+/// IPv6 fragment reassembler
+#[derive(Debug, Default)]
+pub struct Ipv6Reassembler {
+    pending: BTreeMap<FragmentKey, PendingPacket>,
+}
+
+impl Ipv6Reassembler {
+    /// Time allowed for all fragments of a packet to arrive
+    pub const TIMEOUT_MS: u64 = 60_000;
+
+    /// Maximum packets reassembled at once
+    pub const MAX_PENDING: usize = 64;
+
+    /// Create a new reassembler
+    pub fn new() -> Self {
+        Self { pending: BTreeMap::new() }
+    }
+
+    /// Add the fragment carried by `packet`
+    ///
+    /// `chain` is the packet's extension chain and must contain a Fragment
+    /// header. Returns the upper-layer protocol and data once the packet is
+    /// complete.
+    pub fn process(
+        &mut self,
+        packet: &Ipv6Packet,
+        chain: &ExtensionChain,
+        now_ms: u64,
+    ) -> Result<Option<(u8, Vec<u8>)>, Ipv6Error> {
+        let frag = chain.fragment().ok_or(Ipv6Error::InvalidFragment)?;
+        let data = &packet.payload[chain.payload_offset..];
+        if frag.is_atomic() {
+            return Ok(Some((chain.upper_protocol, data.to_vec())));
+        }
+        if frag.more_fragments && data.len() % 8 != 0 {
+            return Err(Ipv6Error::InvalidFragment);
+        }
+
+        let key = FragmentKey {
+            source: packet.header.source_addr,
+            dest: packet.header.dest_addr,
+            identification: frag.identification,
+        };
+        if !self.pending.contains_key(&key) && self.pending.len() >= Self::MAX_PENDING {
+            self.cleanup(now_ms);
+            if self.pending.len() >= Self::MAX_PENDING {
+                return Err(Ipv6Error::ReassemblyFull);
+            }
+        }
+
+        let entry = self.pending.entry(key).or_insert_with(|| PendingPacket {
+            pieces: BTreeMap::new(),
+            total_len: None,
+            next_header: 0,
+            started_ms: now_ms,
+        });
+
+        let start = frag.byte_offset();
+        let end = start + data.len();
+        if end > u16::MAX as usize {
+            self.pending.remove(&key);
+            return Err(Ipv6Error::InvalidFragment);
+        }
+        // Overlapping fragments are an attack vector, drop the whole packet
+        let overlaps = entry.pieces.range(..end).next_back().is_some_and(|(&s, d)| s + d.len() > start);
+        if overlaps {
+            self.pending.remove(&key);
+            return Err(Ipv6Error::InvalidFragment);
+        }
+        if start == 0 {
+            entry.next_header = chain.upper_protocol;
+        }
+        if !frag.more_fragments {
+            entry.total_len = Some(end);
+        }
+        entry.pieces.insert(start, data.to_vec());
+
+        let Some(total) = entry.total_len else { return Ok(None) };
+        let received: usize = entry.pieces.values().map(Vec::len).sum();
+        if received != total {
+            return Ok(None);
+        }
+
+        let entry = self.pending.remove(&key).ok_or(Ipv6Error::InvalidFragment)?;
+        let mut data = Vec::with_capacity(total);
+        for piece in entry.pieces.into_values() {
+            data.extend_from_slice(&piece);
+        }
+        Ok(Some((entry.next_header, data)))
+    }
+
+    /// Drop packets whose fragments did not all arrive in time
+    pub fn cleanup(&mut self, now_ms: u64) {
+        self.pending.retain(|_, p| now_ms.saturating_sub(p.started_ms) < Self::TIMEOUT_MS);
+    }
+
+    /// Number of packets being reassembled
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// IPv6 next header values
+pub mod protocols {
+    /// Hop-by-Hop options
+    pub const HOP_BY_HOP: u8 = 0;
+    /// TCP
+    pub const TCP: u8 = 6;
+    /// UDP
+    pub const UDP: u8 = 17;
+    /// Routing header
+    pub const ROUTING: u8 = 43;
+    /// Fragment header
+    pub const FRAGMENT: u8 = 44;
+    /// Encapsulating Security Payload
+    pub const ESP: u8 = 50;
+    /// Authentication header
+    pub const AUTH: u8 = 51;
+    /// ICMPv6
+    pub const ICMPV6: u8 = 58;
+    /// No next header
+    pub const NO_NEXT_HEADER: u8 = 59;
+    /// Destination options
+    pub const DEST_OPTS: u8 = 60;
+}
+
+/// Hop-by-Hop and Destination option types
+pub mod option_types {
+    /// Single byte of padding
+    pub const PAD1: u8 = 0;
+    /// Multiple bytes of padding
+    pub const PADN: u8 = 1;
+    /// Router alert (MLD)
+    pub const ROUTER_ALERT: u8 = 5;
+}
+
+/// IPv6 errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ipv6Error {
+    /// Packet too small
+    PacketTooSmall,
+    /// Invalid version
+    InvalidVersion,
+    /// Unrecognized next header, at `pointer` bytes into the payload
+    UnknownNextHeader { pointer: usize },
+    /// Unrecognized option whose type asks for the packet to be dropped
+    UnknownOption { pointer: usize, action: u8 },
+    /// Routing header with segments left that can't be processed
+    UnsupportedRouting { pointer: usize },
+    /// Malformed or overlapping fragment
+    InvalidFragment,
+    /// Too many packets being reassembled
+    ReassemblyFull,
+}
+
+/// Default hop limit for IPv6 packets
+pub const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// Minimum link MTU every IPv6 link must support
+pub const MIN_MTU: usize = 1280;
@@ -1,7 +1,8 @@
 //! Network stack implementation
 //!
 //! This module implements a complete TCP/IP network stack for NOS,
-//! including Ethernet, ARP, IPv4, ICMP, UDP, and TCP protocols.
+//! including Ethernet, ARP, IPv4, ICMP, IPv6, ICMPv6/NDP, UDP, and TCP
+//! protocols. Sockets are dual-stack.
 
 extern crate alloc;
 
@@ -12,11 +13,15 @@ pub mod interface;
 pub mod device;
 pub mod arp;
 pub mod ipv4;
+pub mod ipv6;
 pub mod icmp;
+pub mod icmpv6;
+pub mod ndp;
 pub mod icmp_enhanced; // Enhanced ICMP features (optional: extended ICMP types, traceroute, ping)
 pub mod udp;
 pub mod tcp;
 pub mod route;
+pub mod route6;
 pub mod buffer_management;
 pub mod fragment;
 pub mod processor;
@@ -44,6 +49,8 @@ pub struct NetworkStack {
     next_interface_id: AtomicU32,
     /// Enhanced network manager for POSIX compatibility
     enhanced_manager: enhanced_network::EnhancedNetworkManager,
+    /// Protocol processor for received packets
+    processor: NetworkProcessor,
 }
 
 impl NetworkStack {
@@ -55,6 +62,7 @@ impl NetworkStack {
             packet_pool: PacketPool::new(),
             next_interface_id: AtomicU32::new(1),
             enhanced_manager: enhanced_network::EnhancedNetworkManager::new(),
+            processor: NetworkProcessor::new(),
         }
     }
 
@@ -164,6 +172,116 @@ impl NetworkStack {
             .ok_or(NetworkError::NoRouteToHost)
     }
 
+    /// Select the local address used to reach `dest`
+    ///
+    /// Loopback and our own addresses are answered locally; anything else
+    /// takes the address of the interface the destination routes through.
+    pub fn select_source(&self, dest: IpAddr) -> Option<IpAddr> {
+        match dest.to_canonical() {
+            IpAddr::V4(dest) if dest.is_loopback() => Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            IpAddr::V4(dest) if self.interfaces.iter().any(|i| i.is_my_address(dest)) => {
+                Some(IpAddr::V4(dest))
+            }
+            IpAddr::V4(dest) => self.find_route(dest).ok()?.ipv4_addr().map(IpAddr::V4),
+            IpAddr::V6(dest) if dest.is_loopback() => Some(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+            IpAddr::V6(dest) if self.interfaces.iter().any(|i| i.is_my_ipv6_address(dest)) => {
+                Some(IpAddr::V6(dest))
+            }
+            IpAddr::V6(dest) => self
+                .interfaces
+                .iter()
+                .filter(|i| i.is_up())
+                .find_map(|i| i.select_ipv6_source(dest))
+                .map(IpAddr::V6),
+        }
+    }
+
+    /// Find the interface that carries IP traffic to `dest`
+    ///
+    /// Traffic to ourselves goes through the loopback device.
+    fn output_interface(&self, dest: IpAddr) -> Option<&Interface> {
+        let is_local = match dest {
+            IpAddr::V4(dest) => {
+                dest.is_loopback() || self.interfaces.iter().any(|i| i.is_my_address(dest))
+            }
+            IpAddr::V6(dest) => {
+                dest.is_loopback() || self.interfaces.iter().any(|i| i.is_my_ipv6_address(dest))
+            }
+        };
+        if is_local {
+            return self
+                .interfaces
+                .iter()
+                .find(|i| i.device_type() == NetworkDeviceType::Loopback);
+        }
+        match dest {
+            IpAddr::V4(dest) => self.find_route(dest).ok(),
+            IpAddr::V6(dest) => self
+                .interfaces
+                .iter()
+                .filter(|i| i.is_up())
+                .find(|i| i.is_in_ipv6_network(dest))
+                .or_else(|| self.interfaces.iter().find(|i| i.is_up() && i.ipv6_gateway().is_some())),
+        }
+    }
+
+    /// Wrap queued TCP segments in IP and hand them to their interfaces
+    fn flush_tcp_output(&mut self) -> usize {
+        let outputs = with_tcp_manager(|manager| manager.take_output());
+        let count = outputs.len();
+        for output in outputs {
+            let (bytes, packet_type) = match (output.source, output.dest) {
+                (IpAddr::V4(src), IpAddr::V4(dst)) => (
+                    Ipv4Packet::new(src, dst, ipv4::protocols::TCP, output.segment, ipv4::DEFAULT_TTL)
+                        .to_bytes(),
+                    PacketType::Ipv4,
+                ),
+                (IpAddr::V6(src), IpAddr::V6(dst)) => (
+                    Ipv6Packet::new(src, dst, ipv6::protocols::TCP, output.segment, ipv6::DEFAULT_HOP_LIMIT)
+                        .to_bytes(),
+                    PacketType::Ipv6,
+                ),
+                _ => continue,
+            };
+            let Ok(packet) = Packet::from_bytes(&bytes, packet_type) else {
+                continue;
+            };
+            // Lost segments are recovered by retransmission
+            if let Some(interface) = self.output_interface(output.dest) {
+                let _ = interface.send_packet(packet);
+            }
+        }
+        count
+    }
+
+    /// Run one round of protocol processing
+    ///
+    /// Transmits pending TCP segments, feeds every received packet through
+    /// the processor and fires TCP timers. Loopback traffic is drained until
+    /// the stack goes quiet, so a local handshake completes in a single call.
+    pub fn poll(&mut self) {
+        loop {
+            let mut progress = self.flush_tcp_output();
+
+            for index in 0..self.interfaces.len() {
+                while let Ok(Some(packet)) = self.interfaces[index].receive_packet() {
+                    progress += 1;
+                    let interface = &self.interfaces[index];
+                    if let Ok(PacketResult::Respond(response)) =
+                        self.processor.process_incoming_packet(packet, interface)
+                    {
+                        let _ = interface.send_packet(response);
+                    }
+                }
+            }
+
+            with_tcp_manager(|manager| manager.check_timeouts(crate::time::uptime_ms()));
+            if progress == 0 {
+                break;
+            }
+        }
+    }
+
     /// Get the enhanced network manager
     pub fn enhanced_manager(&self) -> &enhanced_network::EnhancedNetworkManager {
         &self.enhanced_manager
@@ -212,6 +330,8 @@ pub fn init() {
             ipv4_addr: Some(Ipv4Addr::new(127, 0, 0, 1)),
             ipv4_netmask: Some(Ipv4Addr::new(255, 0, 0, 0)),
             ipv4_gateway: None,
+            ipv6_addrs: vec![(Ipv6Addr::LOCALHOST, 128)],
+            ipv6_gateway: None,
             is_up: true,
             mtu: Some(65536),
         };
@@ -220,7 +340,7 @@ pub fn init() {
             log_error!("Failed to configure loopback interface");
         } else {
             let _ = lo_interface.up();
-            crate::log_info!("Loopback interface configured: 127.0.0.1/8");
+            crate::log_info!("Loopback interface configured: 127.0.0.1/8, ::1/128");
         }
     }
 
@@ -230,14 +350,62 @@ pub fn init() {
     crate::log_info!("Network stack initialized with interfaces");
 }
 
+/// Serializes [`poll`] between the poll thread and blocking sockets
+static POLL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Run the network stack once
+///
+/// Callers that find another poll in progress return straight away; that
+/// poll will deliver whatever they were about to send.
+pub fn poll() {
+    if let Some(_guard) = POLL_LOCK.try_lock() {
+        network_stack().poll();
+    }
+}
+
+/// Start the network poll thread
+pub fn start() {
+    let _ = crate::process::thread::create_thread(
+        1,
+        crate::process::thread::ThreadType::Kernel,
+        Some(poll_main),
+        core::ptr::null_mut(),
+    );
+    crate::println!("[net] Started poll thread");
+}
+
+unsafe extern "C" fn poll_main(_arg: *mut u8) -> *mut u8 {
+    loop {
+        poll();
+        crate::process::thread::thread_yield();
+    }
+}
+
 /// Initialize additional network interfaces
+///
+/// virtio NICs come up unaddressed; IPv6 autoconfigures and IPv4 waits for
+/// the administrator. Debug builds without one get a mock `eth0`.
 fn init_network_interfaces() {
+    let nics = crate::drivers::virtio::net_devices();
+    if nics.is_empty() {
+        #[cfg(debug_assertions)]
+        {
+            create_mock_ethernet_interface();
+        }
+        return;
+    }
 
-    // Try to detect and initialize available network devices
-    // For now, we'll create a mock Ethernet interface for testing
-    #[cfg(debug_assertions)]
-    {
-        create_mock_ethernet_interface();
+    for nic in nics {
+        let name = nic.name().to_string();
+        let Ok(id) = network_stack().add_interface(nic) else { continue };
+        if let Some(interface) = network_stack().get_interface_mut(id) {
+            let config = InterfaceConfig { name: name.clone(), is_up: true, ..InterfaceConfig::default() };
+            if interface.configure(&config).is_err() || interface.up().is_err() {
+                log_error!("Failed to bring up {}", name);
+            } else {
+                crate::log_info!("{} is up", name);
+            }
+        }
     }
 }
 
@@ -257,6 +425,9 @@ fn create_mock_ethernet_interface() {
                 ipv4_addr: Some(Ipv4Addr::new(192, 168, 1, 100)),
                 ipv4_netmask: Some(Ipv4Addr::new(255, 255, 255, 0)),
                 ipv4_gateway: Some(Ipv4Addr::new(192, 168, 1, 1)),
+                // IPv6 is autoconfigured: link-local on up, global from Router Advertisements
+                ipv6_addrs: Vec::new(),
+                ipv6_gateway: None,
                 is_up: false, // Keep down by default
                 mtu: Some(1500),
             };
@@ -340,11 +511,14 @@ pub fn enhanced_network_manager_mut() -> &'static mut enhanced_network::Enhanced
 
 /// Re-export for use in other modules
 pub use self::packet::{Packet, PacketBuffer, PacketType};
-pub use self::interface::{Interface, InterfaceConfig};
+pub use self::interface::{Interface, InterfaceConfig, Inet6Addr, Inet6Origin, Inet6State};
 pub use self::device::{NetworkDevice, NetworkDeviceType};
 pub use self::arp::{ArpCache, ArpEntry};
 pub use self::ipv4::{Ipv4Addr, Ipv4Header, Ipv4Packet};
+pub use self::ipv6::{IpAddr, Ipv6Addr, Ipv6Header, Ipv6Packet, ExtensionChain, ExtensionHeader};
 pub use self::icmp::{IcmpPacket, IcmpType, IcmpCode, IcmpError};
+pub use self::icmpv6::{Icmpv6Packet, Icmpv6Type, Icmpv6Error, NdMessage, NdOption, PrefixInfo};
+pub use self::ndp::{NeighborCache, NeighborEntry, NeighborState, NdpProcessor};
 pub use self::icmp_enhanced::{
     EnhancedIcmpProcessor, EnhancedIcmpPacket, ExtendedIcmpType, IcmpMessageData,
     IcmpConfig, IcmpComprehensiveStats, IcmpSendOptions, TracerouteHop, PingReply, PingResult
@@ -356,8 +530,14 @@ pub use self::tcp::{
     EnhancedTcpStats, TcpConfig, TcpError
 };
 pub use self::tcp::state::TcpStateMachine;
-pub use self::tcp::manager::{TcpConnection, TcpConnectionManager};
+pub use self::tcp::congestion::{
+    default_algorithm, set_default_algorithm, CongestionAlgorithm, CongestionControl
+};
+pub use self::tcp::manager::{
+    with_tcp_manager, SocketHandle, TcpConnection, TcpConnectionManager, TcpReadiness
+};
 pub use self::route::{RouteEntry, RoutingTable, RouteManager, RouteLookupResult, RoutingTableStats};
+pub use self::route6::{Route6Entry, Routing6Table};
 pub use self::fragment::{FragmentReassembler, Fragmenter, ReassemblyEntry};
 pub use self::processor::{NetworkProcessor, PacketResult};
 pub use self::socket::{
@@ -377,8 +557,9 @@ pub use self::enhanced_network::{
 // Module imports
 use alloc::sync::Arc;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
-use crate::subsystems::sync::Once;
+use crate::subsystems::sync::{Mutex, Once};
 
 // Forward declarations (will be implemented in submodules)
 pub struct Route;
@@ -0,0 +1,613 @@
+//! Neighbor Discovery Protocol (NDP) implementation
+//!
+//! This module provides the IPv6 neighbor cache, which does for IPv6 what
+//! the ARP cache does for IPv4, and the processing of Neighbor and Router
+//! Discovery messages. Router Advertisements drive stateless address
+//! autoconfiguration (SLAAC) and the default router list, and new
+//! addresses go through duplicate address detection before use.
+
+extern crate alloc;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::device::MacAddr;
+use super::icmpv6::{NdMessage, NdOption};
+use super::interface::{Inet6Addr, Inet6Origin, Inet6State, Interface};
+use super::ipv6::{self, Ipv6Addr, Ipv6Header, Ipv6Packet};
+use super::route6::{Route6Entry, Routing6Table};
+
+/// Hop limit every Neighbor Discovery message is sent and must arrive with
+pub const ND_HOP_LIMIT: u8 = 255;
+
+/// Probes sent for an unresolved address before giving up
+pub const MAX_MULTICAST_SOLICIT: u8 = 3;
+
+/// Probes sent to confirm a stale neighbor before giving up
+pub const MAX_UNICAST_SOLICIT: u8 = 3;
+
+/// Time a stale neighbor in use waits for upper-layer confirmation
+pub const DELAY_FIRST_PROBE_TIME_MS: u64 = 5_000;
+
+/// Default time a confirmed neighbor stays reachable
+pub const REACHABLE_TIME_MS: u64 = 30_000;
+
+/// Default time between solicitations
+pub const RETRANS_TIMER_MS: u64 = 1_000;
+
+/// Neighbor reachability state (RFC 4861 section 7.3.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborState {
+    /// Resolution in progress, link-layer address unknown
+    Incomplete,
+    /// Recently confirmed reachable
+    Reachable,
+    /// Not confirmed recently, used without probing
+    Stale,
+    /// In use while stale, waiting before probing
+    Delay,
+    /// Being probed with unicast solicitations
+    Probe,
+}
+
+/// Neighbor cache entry
+#[derive(Debug, Clone)]
+pub struct NeighborEntry {
+    /// Link-layer address, once known
+    pub mac: Option<MacAddr>,
+    /// Reachability state
+    pub state: NeighborState,
+    /// Neighbor is a router
+    pub is_router: bool,
+    /// Uptime in milliseconds of the last state change
+    pub updated_ms: u64,
+    /// Solicitations sent in the current state
+    pub probes: u8,
+}
+
+/// Neighbor cache
+#[derive(Debug)]
+pub struct NeighborCache {
+    /// Cache entries
+    entries: BTreeMap<Ipv6Addr, NeighborEntry>,
+    /// Maximum number of entries
+    max_size: usize,
+    /// Time a confirmed neighbor stays reachable
+    reachable_time_ms: u64,
+    /// Time between solicitations
+    retrans_timer_ms: u64,
+}
+
+impl NeighborCache {
+    /// Create a new neighbor cache
+    pub fn new() -> Self {
+        Self::with_capacity(256)
+    }
+
+    /// Create a new neighbor cache with the given capacity
+    pub fn with_capacity(max_size: usize) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            max_size,
+            reachable_time_ms: REACHABLE_TIME_MS,
+            retrans_timer_ms: RETRANS_TIMER_MS,
+        }
+    }
+
+    /// Set the reachable time advertised by a router
+    pub fn set_reachable_time(&mut self, ms: u64) {
+        self.reachable_time_ms = ms;
+    }
+
+    /// Set the retransmission timer advertised by a router
+    pub fn set_retrans_timer(&mut self, ms: u64) {
+        self.retrans_timer_ms = ms;
+    }
+
+    /// Get the time between solicitations
+    pub fn retrans_timer(&self) -> u64 {
+        self.retrans_timer_ms
+    }
+
+    /// Look up the link-layer address of a neighbor
+    ///
+    /// Using a stale entry starts the delay before it is probed.
+    pub fn lookup(&mut self, addr: Ipv6Addr, now_ms: u64) -> Option<MacAddr> {
+        let entry = self.entries.get_mut(&addr)?;
+        if entry.state == NeighborState::Stale {
+            entry.state = NeighborState::Delay;
+            entry.updated_ms = now_ms;
+        }
+        entry.mac
+    }
+
+    /// Get an entry without touching its state
+    pub fn get(&self, addr: Ipv6Addr) -> Option<&NeighborEntry> {
+        self.entries.get(&addr)
+    }
+
+    /// Start resolving `addr`
+    ///
+    /// Returns true if a solicitation should be sent now, false if the
+    /// address is already known or being resolved.
+    pub fn start_resolution(&mut self, addr: Ipv6Addr, now_ms: u64) -> bool {
+        if self.entries.contains_key(&addr) {
+            return false;
+        }
+        self.insert(addr, NeighborEntry {
+            mac: None,
+            state: NeighborState::Incomplete,
+            is_router: false,
+            updated_ms: now_ms,
+            probes: 1,
+        });
+        true
+    }
+
+    /// Record the sender of a solicitation or router message
+    pub fn update_from_solicitation(&mut self, addr: Ipv6Addr, mac: MacAddr, is_router: bool, now_ms: u64) {
+        match self.entries.get_mut(&addr) {
+            Some(entry) => {
+                if entry.mac != Some(mac) {
+                    entry.mac = Some(mac);
+                    entry.state = NeighborState::Stale;
+                    entry.updated_ms = now_ms;
+                    entry.probes = 0;
+                }
+                entry.is_router |= is_router;
+            }
+            None => self.insert(addr, NeighborEntry {
+                mac: Some(mac),
+                state: NeighborState::Stale,
+                is_router,
+                updated_ms: now_ms,
+                probes: 0,
+            }),
+        }
+    }
+
+    /// Apply a Neighbor Advertisement (RFC 4861 section 7.2.5)
+    ///
+    /// Advertisements for addresses not in the cache are ignored.
+    pub fn update_from_advertisement(
+        &mut self,
+        target: Ipv6Addr,
+        mac: Option<MacAddr>,
+        solicited: bool,
+        override_flag: bool,
+        is_router: bool,
+        now_ms: u64,
+    ) {
+        let Some(entry) = self.entries.get_mut(&target) else { return };
+
+        if entry.state == NeighborState::Incomplete {
+            let Some(mac) = mac else { return };
+            entry.mac = Some(mac);
+            entry.state = if solicited { NeighborState::Reachable } else { NeighborState::Stale };
+        } else {
+            let same = mac.is_none() || mac == entry.mac;
+            if !override_flag && !same {
+                // Keep the address we have, but it can't be trusted as reachable
+                if entry.state == NeighborState::Reachable {
+                    entry.state = NeighborState::Stale;
+                } else {
+                    return;
+                }
+            } else {
+                if mac.is_some() {
+                    entry.mac = mac;
+                }
+                if solicited {
+                    entry.state = NeighborState::Reachable;
+                } else if !same {
+                    entry.state = NeighborState::Stale;
+                }
+            }
+        }
+        entry.is_router = is_router;
+        entry.updated_ms = now_ms;
+        entry.probes = 0;
+    }
+
+    /// Upper-layer confirmation that a neighbor is reachable (e.g. a TCP ACK)
+    pub fn confirm_reachable(&mut self, addr: Ipv6Addr, now_ms: u64) {
+        if let Some(entry) = self.entries.get_mut(&addr)
+            && entry.mac.is_some()
+        {
+            entry.state = NeighborState::Reachable;
+            entry.updated_ms = now_ms;
+            entry.probes = 0;
+        }
+    }
+
+    /// Advance the state machine of every entry to uptime `now_ms`
+    ///
+    /// Returns the neighbors to solicit: with the known link-layer address
+    /// for unicast probes, or None for multicast resolution. Entries that
+    /// ran out of probes are removed.
+    pub fn timers(&mut self, now_ms: u64) -> Vec<(Ipv6Addr, Option<MacAddr>)> {
+        let mut probes = Vec::new();
+        let (reachable, retrans) = (self.reachable_time_ms, self.retrans_timer_ms);
+
+        self.entries.retain(|&addr, entry| {
+            let elapsed = now_ms.saturating_sub(entry.updated_ms);
+            match entry.state {
+                NeighborState::Reachable if elapsed >= reachable => {
+                    entry.state = NeighborState::Stale;
+                    entry.updated_ms = now_ms;
+                }
+                NeighborState::Delay if elapsed >= DELAY_FIRST_PROBE_TIME_MS => {
+                    entry.state = NeighborState::Probe;
+                    entry.updated_ms = now_ms;
+                    entry.probes = 1;
+                    probes.push((addr, entry.mac));
+                }
+                NeighborState::Probe | NeighborState::Incomplete if elapsed >= retrans => {
+                    let unicast = entry.state == NeighborState::Probe;
+                    let max = if unicast { MAX_UNICAST_SOLICIT } else { MAX_MULTICAST_SOLICIT };
+                    if entry.probes >= max {
+                        return false;
+                    }
+                    entry.probes += 1;
+                    entry.updated_ms = now_ms;
+                    probes.push((addr, if unicast { entry.mac } else { None }));
+                }
+                _ => {}
+            }
+            true
+        });
+
+        probes
+    }
+
+    /// Remove an entry
+    pub fn remove(&mut self, addr: Ipv6Addr) -> Option<NeighborEntry> {
+        self.entries.remove(&addr)
+    }
+
+    /// Get all entries
+    pub fn entries(&self) -> impl Iterator<Item = (&Ipv6Addr, &NeighborEntry)> {
+        self.entries.iter()
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all entries
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Insert an entry, evicting the oldest one if the cache is full
+    fn insert(&mut self, addr: Ipv6Addr, entry: NeighborEntry) {
+        if self.entries.len() >= self.max_size
+            && let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.updated_ms).map(|(a, _)| *a)
+        {
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert(addr, entry);
+    }
+}
+
+impl Default for NeighborCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Neighbor Discovery errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NdpError {
+    /// Message did not arrive with hop limit 255, so it came from off-link
+    InvalidHopLimit,
+    /// Message is not valid for its source address
+    InvalidSource,
+}
+
+/// Neighbor Discovery processor
+pub struct NdpProcessor;
+
+impl NdpProcessor {
+    /// Create a new NDP processor
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Process a Neighbor Discovery message received on `interface`
+    ///
+    /// Returns the message to send back, if any. Router Advertisements
+    /// update `routes` and configure addresses on the interface.
+    pub fn process_message(
+        &self,
+        header: &Ipv6Header,
+        message: &NdMessage,
+        interface: &Interface,
+        routes: &mut Routing6Table,
+        now_ms: u64,
+    ) -> Result<Option<Ipv6Packet>, NdpError> {
+        if header.hop_limit != ND_HOP_LIMIT {
+            return Err(NdpError::InvalidHopLimit);
+        }
+        let source = header.source_addr;
+
+        match message {
+            NdMessage::NeighborSolicitation { target, .. } => {
+                let Some(state) = interface.ipv6_addr_state(*target) else { return Ok(None) };
+                let sender_mac = message.source_link_addr();
+
+                if source.is_unspecified() {
+                    // Duplicate address detection by another node
+                    if sender_mac.is_some() || !header.dest_addr.is_solicited_node() {
+                        return Err(NdpError::InvalidSource);
+                    }
+                    if state == Inet6State::Tentative {
+                        Self::duplicate_detected(interface, *target);
+                        return Ok(None);
+                    }
+                    let reply = self.advertisement(interface, *target, Ipv6Addr::ALL_NODES, false);
+                    return Ok(Some(reply));
+                }
+                if state == Inet6State::Tentative {
+                    return Ok(None);
+                }
+
+                if let Some(mac) = sender_mac {
+                    interface.neighbor_cache().lock().update_from_solicitation(source, mac, false, now_ms);
+                }
+                Ok(Some(self.advertisement(interface, *target, source, true)))
+            }
+            NdMessage::NeighborAdvertisement { router, solicited, override_flag, target, .. } => {
+                if *solicited && header.dest_addr.is_multicast() {
+                    return Err(NdpError::InvalidSource);
+                }
+                match interface.ipv6_addr_state(*target) {
+                    Some(Inet6State::Tentative) => Self::duplicate_detected(interface, *target),
+                    Some(_) => {
+                        crate::log_info!("IPv6 address {} on {} is in use by another node",
+                            target, interface.name());
+                    }
+                    None => interface.neighbor_cache().lock().update_from_advertisement(
+                        *target,
+                        message.target_link_addr(),
+                        *solicited,
+                        *override_flag,
+                        *router,
+                        now_ms,
+                    ),
+                }
+                Ok(None)
+            }
+            NdMessage::RouterAdvertisement {
+                router_lifetime, reachable_time, retrans_timer, options, ..
+            } => {
+                if !source.is_link_local() {
+                    return Err(NdpError::InvalidSource);
+                }
+                self.process_router_advertisement(
+                    source, *router_lifetime, *reachable_time, *retrans_timer, options,
+                    interface, routes, now_ms,
+                );
+                Ok(None)
+            }
+            // Hosts don't answer router solicitations
+            NdMessage::RouterSolicitation { .. } => Ok(None),
+        }
+    }
+
+    /// Apply a Router Advertisement from `router`
+    #[allow(clippy::too_many_arguments)]
+    fn process_router_advertisement(
+        &self,
+        router: Ipv6Addr,
+        router_lifetime: u16,
+        reachable_time: u32,
+        retrans_timer: u32,
+        options: &[NdOption],
+        interface: &Interface,
+        routes: &mut Routing6Table,
+        now_ms: u64,
+    ) {
+        {
+            let mut cache = interface.neighbor_cache().lock();
+            if reachable_time != 0 {
+                cache.set_reachable_time(reachable_time as u64);
+            }
+            if retrans_timer != 0 {
+                cache.set_retrans_timer(retrans_timer as u64);
+            }
+        }
+
+        if router_lifetime == 0 {
+            routes.remove_route(Ipv6Addr::UNSPECIFIED, 0, Some(router));
+        } else {
+            let mut route = Route6Entry::new(Ipv6Addr::UNSPECIFIED, 0, Some(router), interface.id(), 1024);
+            route.expires_ms = Some(now_ms + router_lifetime as u64 * 1000);
+            routes.add_route(route);
+        }
+
+        for option in options {
+            match option {
+                NdOption::SourceLinkAddr(mac) => {
+                    interface.neighbor_cache().lock().update_from_solicitation(router, *mac, true, now_ms);
+                }
+                NdOption::Mtu(mtu) => interface.set_link_mtu(*mtu as usize),
+                NdOption::PrefixInfo(info) => {
+                    if info.prefix.is_link_local() || info.preferred_lifetime > info.valid_lifetime {
+                        continue;
+                    }
+                    let valid_until = lifetime_deadline(info.valid_lifetime, now_ms);
+                    if info.on_link {
+                        if info.valid_lifetime == 0 {
+                            routes.remove_route(info.prefix, info.prefix_len, None);
+                        } else {
+                            let mut route = Route6Entry::new(info.prefix, info.prefix_len, None, interface.id(), 256);
+                            route.expires_ms = valid_until;
+                            routes.add_route(route);
+                        }
+                    }
+                    // SLAAC needs the 64-bit interface identifier to fit
+                    if info.autonomous && info.prefix_len == 64 {
+                        let addr = Ipv6Addr::from_prefix_and_mac(info.prefix, interface.mac_address());
+                        interface.update_slaac_addr(
+                            addr,
+                            info.prefix_len,
+                            info.valid_lifetime,
+                            info.preferred_lifetime,
+                            now_ms,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Handle another node using an address we were about to take
+    fn duplicate_detected(interface: &Interface, addr: Ipv6Addr) {
+        interface.remove_ipv6_addr(addr);
+        crate::log_info!("Duplicate address {} detected on {}, not using it", addr, interface.name());
+    }
+
+    /// Build a Neighbor Advertisement for our address `target`
+    pub fn advertisement(&self, interface: &Interface, target: Ipv6Addr, dest: Ipv6Addr, solicited: bool) -> Ipv6Packet {
+        let message = NdMessage::NeighborAdvertisement {
+            router: false,
+            solicited,
+            override_flag: true,
+            target,
+            options: vec![NdOption::TargetLinkAddr(interface.mac_address())],
+        };
+        Self::packet(target, dest, &message)
+    }
+
+    /// Build a Neighbor Solicitation for `target`
+    ///
+    /// Without a `source` this is a duplicate address detection probe.
+    /// Without a known `mac` it goes to the target's solicited-node group.
+    pub fn solicitation(
+        &self,
+        interface: &Interface,
+        target: Ipv6Addr,
+        source: Option<Ipv6Addr>,
+        mac: Option<MacAddr>,
+    ) -> Ipv6Packet {
+        let options = match source {
+            Some(_) => vec![NdOption::SourceLinkAddr(interface.mac_address())],
+            None => Vec::new(),
+        };
+        let dest = if mac.is_some() { target } else { target.solicited_node() };
+        let message = NdMessage::NeighborSolicitation { target, options };
+        Self::packet(source.unwrap_or(Ipv6Addr::UNSPECIFIED), dest, &message)
+    }
+
+    /// Build a Router Solicitation
+    pub fn router_solicitation(&self, interface: &Interface, source: Ipv6Addr) -> Ipv6Packet {
+        let options = if source.is_unspecified() {
+            Vec::new()
+        } else {
+            vec![NdOption::SourceLinkAddr(interface.mac_address())]
+        };
+        let message = NdMessage::RouterSolicitation { options };
+        Self::packet(source, Ipv6Addr::ALL_ROUTERS, &message)
+    }
+
+    /// Run the periodic work for `interface` at uptime `now_ms`
+    ///
+    /// Moves tentative addresses through duplicate address detection,
+    /// ages addresses and neighbors, and expires routes. Returns the
+    /// solicitations to send.
+    pub fn tick(&self, interface: &Interface, routes: &mut Routing6Table, now_ms: u64) -> Vec<Ipv6Packet> {
+        let mut out = Vec::new();
+        let retrans = interface.neighbor_cache().lock().retrans_timer();
+
+        let mut link_local_ready = None;
+        let mut dad_probes = Vec::new();
+        {
+            let mut addrs = interface.ipv6_addresses().lock();
+            addrs.retain_mut(|a| {
+                if a.valid_until.is_some_and(|t| now_ms >= t) {
+                    return false;
+                }
+                match a.state {
+                    Inet6State::Tentative => match a.dad_started_ms {
+                        None => {
+                            a.dad_started_ms = Some(now_ms);
+                            dad_probes.push(a.addr);
+                        }
+                        Some(started) if now_ms.saturating_sub(started) >= retrans => {
+                            a.state = Inet6State::Preferred;
+                            if a.origin == Inet6Origin::LinkLocal {
+                                link_local_ready = Some(a.addr);
+                            }
+                        }
+                        Some(_) => {}
+                    },
+                    Inet6State::Preferred if a.preferred_until.is_some_and(|t| now_ms >= t) => {
+                        a.state = Inet6State::Deprecated;
+                    }
+                    _ => {}
+                }
+                true
+            });
+        }
+
+        for addr in dad_probes {
+            out.push(self.solicitation(interface, addr, None, None));
+        }
+        // Ask routers to advertise as soon as we have an address to ask from
+        if let Some(addr) = link_local_ready {
+            out.push(self.router_solicitation(interface, addr));
+        }
+
+        let probes = interface.neighbor_cache().lock().timers(now_ms);
+        for (target, mac) in probes {
+            if let Some(source) = interface.select_ipv6_source(target) {
+                out.push(self.solicitation(interface, target, Some(source), mac));
+            }
+        }
+
+        routes.expire(now_ms);
+        out
+    }
+
+    /// Wrap a Neighbor Discovery message in an IPv6 packet
+    fn packet(source: Ipv6Addr, dest: Ipv6Addr, message: &NdMessage) -> Ipv6Packet {
+        let payload = message.to_packet().to_bytes(source, dest);
+        Ipv6Packet::new(source, dest, ipv6::protocols::ICMPV6, payload, ND_HOP_LIMIT)
+    }
+}
+
+impl Default for NdpProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Uptime at which a lifetime in seconds runs out (None if infinite)
+pub fn lifetime_deadline(lifetime_secs: u32, now_ms: u64) -> Option<u64> {
+    if lifetime_secs == u32::MAX {
+        None
+    } else {
+        Some(now_ms + lifetime_secs as u64 * 1000)
+    }
+}
+
+/// Build the address record for a new SLAAC or link-local address
+pub fn new_autoconf_addr(addr: Ipv6Addr, prefix_len: u8, origin: Inet6Origin) -> Inet6Addr {
+    Inet6Addr {
+        addr,
+        prefix_len,
+        origin,
+        state: Inet6State::Tentative,
+        valid_until: None,
+        preferred_until: None,
+        dad_started_ms: None,
+    }
+}
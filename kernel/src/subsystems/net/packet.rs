@@ -259,6 +259,7 @@ impl Packet {
                 PacketType::Ethernet => alloc::string::String::from("ETHERNET"),
                 PacketType::Arp => alloc::string::String::from("ARP"),
                 PacketType::Ipv4 => alloc::string::String::from("IPv4"),
+                PacketType::Ipv6 => alloc::string::String::from("IPv6"),
                 PacketType::Icmp => alloc::string::String::from("ICMP"),
                 PacketType::Udp => alloc::string::String::from("UDP"),
                 PacketType::Tcp => alloc::string::String::from("TCP"),
@@ -286,6 +287,7 @@ impl Packet {
                 PacketType::Ethernet => alloc::string::String::from("ETHERNET"),
                 PacketType::Arp => alloc::string::String::from("ARP"),
                 PacketType::Ipv4 => alloc::string::String::from("IPv4"),
+                PacketType::Ipv6 => alloc::string::String::from("IPv6"),
                 PacketType::Icmp => alloc::string::String::from("ICMP"),
                 PacketType::Udp => alloc::string::String::from("UDP"),
                 PacketType::Tcp => alloc::string::String::from("TCP"),
@@ -408,6 +410,7 @@ impl Packet {
                 PacketType::Ethernet => alloc::string::String::from("ETHERNET"),
                 PacketType::Arp => alloc::string::String::from("ARP"),
                 PacketType::Ipv4 => alloc::string::String::from("IPv4"),
+                PacketType::Ipv6 => alloc::string::String::from("IPv6"),
                 PacketType::Icmp => alloc::string::String::from("ICMP"),
                 PacketType::Udp => alloc::string::String::from("UDP"),
                 PacketType::Tcp => alloc::string::String::from("TCP"),
@@ -450,6 +453,8 @@ pub enum PacketType {
     Arp,
     /// IPv4 packet
     Ipv4,
+    /// IPv6 packet
+    Ipv6,
     /// ICMP packet
     Icmp,
     /// UDP packet
@@ -1,7 +1,7 @@
 //! Network packet processor
 //!
 //! This module coordinates the processing of network packets across different
-//! protocol layers (Ethernet, IP, TCP, UDP, ICMP).
+//! protocol layers (Ethernet, IPv4/IPv6, TCP, UDP, ICMP/ICMPv6).
 
 extern crate alloc;
 use alloc::vec::Vec;
@@ -11,16 +11,21 @@ use super::device::NetworkDevice;
 use super::interface::Interface;
 use super::arp::{ArpProcessor, ArpPacket};
 use super::ipv4::{Ipv4Addr, Ipv4Packet};
+use super::ipv6::{self, IpAddr, Ipv6Addr, Ipv6Error, Ipv6Header, Ipv6Packet, Ipv6Reassembler};
 use super::icmp::{IcmpPacket, IcmpProcessor};
+use super::icmpv6::{self, Icmpv6Packet, Icmpv6Processor, Icmpv6Type, NdMessage};
+use super::ndp::NdpProcessor;
 use super::udp::{UdpPacket, UdpSocket};
-use super::tcp::{TcpPacket, TcpSocket, TcpState};
+use super::tcp::TcpPacket;
+use super::tcp::manager::with_tcp_manager;
 use super::route::RoutingTable;
+use super::route6::Routing6Table;
 use super::fragment::FragmentReassembler;
 
 /// Socket key for HashMap lookup
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SocketKey {
-    pub local_ip: Ipv4Addr,
+    pub local_ip: IpAddr,
     pub local_port: u16,
 }
 
@@ -43,14 +48,20 @@ pub struct NetworkProcessor {
     arp_processor: ArpProcessor,
     /// ICMP processor
     icmp_processor: IcmpProcessor,
+    /// ICMPv6 processor
+    icmpv6_processor: Icmpv6Processor,
+    /// Neighbor Discovery processor
+    ndp_processor: NdpProcessor,
     /// Fragment reassembler
     reassembler: FragmentReassembler,
+    /// IPv6 fragment reassembler
+    ipv6_reassembler: Ipv6Reassembler,
     /// Routing table
     routing_table: RoutingTable,
+    /// IPv6 routing table
+    routing6_table: Routing6Table,
     /// UDP socket manager (using BTreeMap for O(log n) lookup)
     udp_sockets: BTreeMap<SocketKey, UdpSocket>,
-    /// TCP socket manager (using BTreeMap for O(log n) lookup)
-    tcp_sockets: BTreeMap<SocketKey, TcpSocket>,
 }
 
 impl NetworkProcessor {
@@ -59,10 +70,13 @@ impl NetworkProcessor {
         Self {
             arp_processor: ArpProcessor::new(),
             icmp_processor: IcmpProcessor::new(),
+            icmpv6_processor: Icmpv6Processor::new(),
+            ndp_processor: NdpProcessor::new(),
             reassembler: FragmentReassembler::new(),
+            ipv6_reassembler: Ipv6Reassembler::new(),
             routing_table: RoutingTable::new(),
+            routing6_table: Routing6Table::new(),
             udp_sockets: BTreeMap::new(),
-            tcp_sockets: BTreeMap::new(),
         }
     }
 
@@ -76,6 +90,7 @@ impl NetworkProcessor {
             PacketType::Ethernet => self.process_ethernet_packet(packet, interface),
             PacketType::Arp => self.process_arp_packet(packet, interface),
             PacketType::Ipv4 => self.process_ipv4_packet(packet, interface),
+            PacketType::Ipv6 => self.process_ipv6_packet(packet, interface),
             _ => Ok(PacketResult::Drop),
         }
     }
@@ -88,11 +103,20 @@ impl NetworkProcessor {
     ) -> Result<PacketResult, ProcessorError> {
         // Determine routing for outgoing packet
         let dest_ip = self.extract_dest_ip(&packet)?;
+        let route_interface = match dest_ip {
+            IpAddr::V4(dest) => self.routing_table.lookup_route(dest).map(|r| r.interface_id),
+            // Link-scope destinations are always on the sending interface
+            IpAddr::V6(dest) if dest.is_link_local() || dest.is_multicast() => src_interface.map(|i| i.id()),
+            IpAddr::V6(dest) => self
+                .routing6_table
+                .lookup_route(dest, crate::time::uptime_ms())
+                .map(|r| r.interface_id),
+        };
 
-        if let Some(route) = self.routing_table.lookup_route(dest_ip) {
+        if let Some(route_interface) = route_interface {
             // Find the appropriate interface
             let interface = if let Some(src_interface) = src_interface {
-                if src_interface.id() == route.interface_id {
+                if src_interface.id() == route_interface {
                     src_interface
                 } else {
                     return Err(ProcessorError::InvalidInterface);
@@ -123,6 +147,7 @@ impl NetworkProcessor {
         match packet.packet_type() {
             PacketType::Arp => self.process_arp_packet(packet, interface),
             PacketType::Ipv4 => self.process_ipv4_packet(packet, interface),
+            PacketType::Ipv6 => self.process_ipv6_packet(packet, interface),
             _ => Ok(PacketResult::Drop),
         }
     }
@@ -219,15 +244,15 @@ impl NetworkProcessor {
             }
             super::ipv4::protocols::TCP => {
                 self.process_tcp_packet(
-                    ipv4_packet.header.source_addr,
-                    ipv4_packet.header.dest_addr,
+                    ipv4_packet.header.source_addr.into(),
+                    ipv4_packet.header.dest_addr.into(),
                     &payload,
                 )
             }
             super::ipv4::protocols::UDP => {
                 self.process_udp_packet(
-                    ipv4_packet.header.source_addr,
-                    ipv4_packet.header.dest_addr,
+                    ipv4_packet.header.source_addr.into(),
+                    ipv4_packet.header.dest_addr.into(),
                     &payload,
                 )
             }
@@ -235,6 +260,150 @@ impl NetworkProcessor {
         }
     }
 
+    /// Process IPv6 packet
+    fn process_ipv6_packet(
+        &mut self,
+        packet: Packet,
+        interface: &Interface,
+    ) -> Result<PacketResult, ProcessorError> {
+        let ipv6_packet = Ipv6Packet::from_bytes(packet.data())
+            .map_err(|_| ProcessorError::InvalidPacket)?;
+        let header = ipv6_packet.header.clone();
+        let local_addr = interface
+            .select_ipv6_source(header.source_addr)
+            .unwrap_or(Ipv6Addr::UNSPECIFIED);
+
+        if !interface.is_my_ipv6_address(header.dest_addr) {
+            if header.hop_limit > 1 {
+                return Ok(PacketResult::Forward(packet));
+            } else {
+                // Hop limit exceeded, send ICMPv6 Time Exceeded
+                return self.send_icmpv6_error(
+                    header.source_addr,
+                    local_addr,
+                    Icmpv6Type::TimeExceeded,
+                    icmpv6::codes::HOP_LIMIT_EXCEEDED,
+                    0,
+                    packet.data(),
+                );
+            }
+        }
+
+        // Walk the extension headers
+        let chain = match ipv6_packet.extensions() {
+            Ok(chain) => chain,
+            Err(error) => {
+                return self.send_parameter_problem(&header, local_addr, error, packet.data());
+            }
+        };
+
+        // Handle fragmentation and reassembly
+        let now_ms = crate::time::uptime_ms();
+        let (next_header, payload) = if chain.fragment().is_some() {
+            match self.ipv6_reassembler.process(&ipv6_packet, &chain, now_ms) {
+                Ok(Some(reassembled)) => reassembled,
+                // Incomplete
+                Ok(None) => return Ok(PacketResult::Success),
+                Err(_) => return Ok(PacketResult::Drop),
+            }
+        } else {
+            (chain.upper_protocol, ipv6_packet.payload[chain.payload_offset..].to_vec())
+        };
+
+        // Process based on upper-layer protocol
+        match next_header {
+            ipv6::protocols::ICMPV6 => {
+                self.process_icmpv6_packet(&header, &payload, interface, now_ms)
+            }
+            ipv6::protocols::TCP => {
+                self.process_tcp_packet(
+                    header.source_addr.into(),
+                    header.dest_addr.into(),
+                    &payload,
+                )
+            }
+            ipv6::protocols::UDP => {
+                // The UDP checksum is mandatory over IPv6
+                let udp_packet = UdpPacket::from_bytes(&payload)
+                    .map_err(|_| ProcessorError::InvalidPacket)?;
+                if !udp_packet.verify_checksum_v6(header.source_addr, header.dest_addr) {
+                    return Ok(PacketResult::Drop);
+                }
+                self.process_udp_packet(
+                    header.source_addr.into(),
+                    header.dest_addr.into(),
+                    &payload,
+                )
+            }
+            ipv6::protocols::NO_NEXT_HEADER => Ok(PacketResult::Success),
+            _ => Ok(PacketResult::Drop),
+        }
+    }
+
+    /// Process ICMPv6 packet
+    ///
+    /// Neighbor Discovery messages go to the NDP processor, which maintains
+    /// the interface's neighbor cache, addresses and the IPv6 routing table.
+    fn process_icmpv6_packet(
+        &mut self,
+        header: &Ipv6Header,
+        data: &[u8],
+        interface: &Interface,
+        now_ms: u64,
+    ) -> Result<PacketResult, ProcessorError> {
+        let src_addr = header.source_addr;
+        let dest_addr = header.dest_addr;
+        let icmp_packet = Icmpv6Packet::from_bytes(src_addr, dest_addr, data)
+            .map_err(|_| ProcessorError::InvalidPacket)?;
+
+        let response = match icmp_packet.message_type {
+            Icmpv6Type::RouterSolicitation
+            | Icmpv6Type::RouterAdvertisement
+            | Icmpv6Type::NeighborSolicitation
+            | Icmpv6Type::NeighborAdvertisement => {
+                let message = NdMessage::from_packet(&icmp_packet)
+                    .map_err(|_| ProcessorError::InvalidPacket)?;
+                // Invalid ND messages are silently discarded
+                match self.ndp_processor.process_message(
+                    header,
+                    &message,
+                    interface,
+                    &mut self.routing6_table,
+                    now_ms,
+                ) {
+                    Ok(response) => response,
+                    Err(_) => return Ok(PacketResult::Drop),
+                }
+            }
+            _ => self
+                .icmpv6_processor
+                .process_packet(src_addr, dest_addr, &icmp_packet)
+                .map(|reply| {
+                    // Replies to multicast echo requests come from a unicast address
+                    let source = if dest_addr.is_multicast() {
+                        interface.select_ipv6_source(src_addr).unwrap_or(Ipv6Addr::UNSPECIFIED)
+                    } else {
+                        dest_addr
+                    };
+                    Ipv6Packet::new(
+                        source,
+                        src_addr,
+                        ipv6::protocols::ICMPV6,
+                        reply.to_bytes(source, src_addr),
+                        ipv6::DEFAULT_HOP_LIMIT,
+                    )
+                }),
+        };
+
+        if let Some(response) = response {
+            let packet = Packet::from_bytes(&response.to_bytes(), PacketType::Ipv6)
+                .map_err(|_| ProcessorError::InvalidPacket)?;
+            Ok(PacketResult::Respond(packet))
+        } else {
+            Ok(PacketResult::Success)
+        }
+    }
+
     /// Process ICMP packet
     fn process_icmp_packet(
         &mut self,
@@ -272,120 +441,82 @@ impl NetworkProcessor {
     }
 
     /// Process TCP packet
+    ///
+    /// Segments are handed to the global TCP connection manager, which
+    /// queues any reply (SYN-ACK, ACK, RST) on its own outbox.
     fn process_tcp_packet(
         &mut self,
-        src_addr: Ipv4Addr,
-        dst_addr: Ipv4Addr,
+        src_addr: IpAddr,
+        dst_addr: IpAddr,
         data: &[u8],
     ) -> Result<PacketResult, ProcessorError> {
         let tcp_packet = TcpPacket::from_bytes(data)
             .map_err(|_| ProcessorError::InvalidPacket)?;
 
-        // Find matching TCP socket using BTreeMap for O(log n) lookup
-        let socket_key = SocketKey {
-            local_ip: dst_addr,
-            local_port: tcp_packet.src_port(),
-        };
-
-        if let Some(socket) = self.tcp_sockets.get(&socket_key) {
-            // Process the packet with the matching socket
-            // Note: Cannot mutate directly in BTreeMap, need to get and reinsert
-            let mut socket = socket.clone();
-
-            // Update socket state based on TCP flags and sequence numbers
-            if tcp_packet.has_flag(super::tcp::tcp_flags::SYN) {
-                if socket.state == TcpState::Listen {
-                    // Transition to SYN_RECEIVED
-                    socket.state = TcpState::SynReceived;
-                    // TODO: Send SYN-ACK
-                    self.tcp_sockets.insert(socket_key, socket);
-                    return Ok(PacketResult::Drop);
-                }
-            }
-
-            // Handle data packets
-            if tcp_packet.payload.len() > 0 {
-                // TODO: Buffer received data
-                crate::log_info!("TCP received {} bytes from {}", tcp_packet.payload.len(), src_addr);
-            }
-
-            self.tcp_sockets.insert(socket_key, socket);
+        if !tcp_packet.verify(src_addr, dst_addr) {
             return Ok(PacketResult::Drop);
         }
 
-        // No matching socket found
-        Ok(PacketResult::Drop)
+        let now_ms = crate::time::uptime_ms();
+        with_tcp_manager(|manager| manager.input(src_addr, dst_addr, &tcp_packet, now_ms));
+        Ok(PacketResult::Success)
     }
 
     /// Process UDP packet
     fn process_udp_packet(
         &mut self,
-        src_addr: Ipv4Addr,
-        dst_addr: Ipv4Addr,
+        src_addr: IpAddr,
+        dst_addr: IpAddr,
         data: &[u8],
     ) -> Result<PacketResult, ProcessorError> {
         let udp_packet = UdpPacket::from_bytes(data)
             .map_err(|_| ProcessorError::InvalidPacket)?;
 
         // Find matching UDP socket using BTreeMap for O(log n) lookup
-        let socket_key = SocketKey {
-            local_ip: dst_addr,
-            local_port: udp_packet.dst_port(),
-        };
-
-        // First try exact match
-        if let Some(mut socket) = self.udp_sockets.get(&socket_key).cloned() {
+        if let Some(socket_key) = self.find_udp_socket(dst_addr, udp_packet.dst_port())
+            && let Some(mut socket) = self.udp_sockets.get(&socket_key).cloned()
+        {
             let result = self.deliver_udp_packet(&mut socket, &udp_packet, src_addr);
             // Update socket in map (in case state changed)
             self.udp_sockets.insert(socket_key, socket);
             return result;
         }
 
-        // Try wildcard IP match (UNSPECIFIED)
-        let wildcard_key = SocketKey {
-            local_ip: Ipv4Addr::UNSPECIFIED,
-            local_port: udp_packet.dst_port(),
-        };
-        if let Some(mut socket) = self.udp_sockets.get(&wildcard_key).cloned() {
-            let result = self.deliver_udp_packet(&mut socket, &udp_packet, src_addr);
-            self.udp_sockets.insert(wildcard_key, socket);
-            return result;
+        // No matching socket found, send Port Unreachable
+        match (src_addr, dst_addr) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => self.send_icmp_port_unreachable(src, dst, data),
+            (IpAddr::V6(src), IpAddr::V6(dst)) if !dst.is_multicast() => self.send_icmpv6_error(
+                src,
+                dst,
+                Icmpv6Type::DestinationUnreachable,
+                icmpv6::codes::PORT_UNREACHABLE,
+                0,
+                data,
+            ),
+            _ => Ok(PacketResult::Drop),
         }
-
-        // No matching socket found, send ICMP Port Unreachable
-        self.send_icmp_port_unreachable(src_addr, dst_addr, data)
     }
 
-    /// Handle TCP packet for a specific socket
-    fn handle_tcp_socket_packet(
-        &mut self,
-        socket: &mut TcpSocket,
-        packet: &TcpPacket,
-        _src_addr: Ipv4Addr,
-    ) -> Result<PacketResult, ProcessorError> {
-        // Update socket state based on TCP flags and sequence numbers
-        // This is a simplified implementation
-        if packet.has_flag(super::tcp::tcp_flags::SYN) {
-            if socket.state == TcpState::Listen {
-                // Transition to SYN_RECEIVED
-                socket.state = TcpState::SynReceived;
-                socket.rcv_nxt = packet.seq_num() + 1;
-
-                // Send SYN-ACK response
-                return Ok(PacketResult::Success); // Would create response packet
-            }
-        } else if packet.has_flag(super::tcp::tcp_flags::ACK) {
-            // Handle ACK
-            socket.snd_una = packet.ack_num();
+    /// Find the UDP socket for a local address and port
+    ///
+    /// An exact bind wins over the wildcard of the same family. IPv4 packets
+    /// also reach a socket bound to `::` unless it is IPV6_V6ONLY.
+    fn find_udp_socket(&self, local_ip: IpAddr, local_port: u16) -> Option<SocketKey> {
+        let exact = SocketKey { local_ip, local_port };
+        if self.udp_sockets.contains_key(&exact) {
+            return Some(exact);
         }
 
-        // Handle data payload
-        if !packet.payload.is_empty() {
-            // Process received data
-            socket.rcv_nxt = packet.seq_num() + packet.payload.len() as u32;
+        let wildcard = SocketKey { local_ip: local_ip.unspecified_like(), local_port };
+        if self.udp_sockets.contains_key(&wildcard) {
+            return Some(wildcard);
         }
 
-        Ok(PacketResult::Success)
+        let dual_stack = SocketKey { local_ip: IpAddr::UNSPECIFIED, local_port };
+        match self.udp_sockets.get(&dual_stack) {
+            Some(socket) if local_ip.is_ipv4() && !socket.v6only => Some(dual_stack),
+            _ => None,
+        }
     }
 
     /// Deliver UDP packet to socket
@@ -393,7 +524,7 @@ impl NetworkProcessor {
         &mut self,
         _socket: &mut UdpSocket,
         _packet: &UdpPacket,
-        _src_addr: Ipv4Addr,
+        _src_addr: IpAddr,
     ) -> Result<PacketResult, ProcessorError> {
         // In a real implementation, this would queue data for the socket
         // For now, just acknowledge receipt
@@ -452,13 +583,88 @@ impl NetworkProcessor {
         )
     }
 
+    /// Send ICMPv6 error message
+    ///
+    /// The offending packet is quoted as far as the result fits in the
+    /// minimum MTU.
+    fn send_icmpv6_error(
+        &mut self,
+        dest_addr: Ipv6Addr,
+        src_addr: Ipv6Addr,
+        message_type: Icmpv6Type,
+        code: u8,
+        parameter: u32,
+        original_data: &[u8],
+    ) -> Result<PacketResult, ProcessorError> {
+        // Never answer packets from the unspecified address
+        if dest_addr.is_unspecified() || dest_addr.is_multicast() {
+            return Ok(PacketResult::Drop);
+        }
+
+        let icmp_packet = Icmpv6Packet::error(message_type, code, parameter, original_data);
+        let ipv6_packet = Ipv6Packet::new(
+            src_addr,
+            dest_addr,
+            ipv6::protocols::ICMPV6,
+            icmp_packet.to_bytes(src_addr, dest_addr),
+            ipv6::DEFAULT_HOP_LIMIT,
+        );
+
+        let packet = Packet::from_bytes(&ipv6_packet.to_bytes(), PacketType::Ipv6)
+            .map_err(|_| ProcessorError::InvalidPacket)?;
+
+        Ok(PacketResult::Respond(packet))
+    }
+
+    /// Report an extension header error with a Parameter Problem
+    ///
+    /// Unknown options say in their type whether to report them, and some
+    /// are only reported for unicast destinations.
+    fn send_parameter_problem(
+        &mut self,
+        header: &Ipv6Header,
+        src_addr: Ipv6Addr,
+        error: Ipv6Error,
+        original_data: &[u8],
+    ) -> Result<PacketResult, ProcessorError> {
+        let (code, pointer) = match error {
+            Ipv6Error::UnknownNextHeader { pointer } => {
+                (icmpv6::codes::UNRECOGNIZED_NEXT_HEADER, pointer)
+            }
+            Ipv6Error::UnsupportedRouting { pointer } => (icmpv6::codes::ERRONEOUS_HEADER, pointer),
+            // 10: report even to multicast, 11: only to unicast, 01: discard
+            Ipv6Error::UnknownOption { pointer, action: 2 } => {
+                (icmpv6::codes::UNRECOGNIZED_OPTION, pointer)
+            }
+            Ipv6Error::UnknownOption { pointer, action: 3 } if !header.dest_addr.is_multicast() => {
+                (icmpv6::codes::UNRECOGNIZED_OPTION, pointer)
+            }
+            _ => return Ok(PacketResult::Drop),
+        };
+
+        // The pointer counts from the start of the IPv6 header
+        self.send_icmpv6_error(
+            header.source_addr,
+            src_addr,
+            Icmpv6Type::ParameterProblem,
+            code,
+            (Ipv6Header::SIZE + pointer) as u32,
+            original_data,
+        )
+    }
+
     /// Extract destination IP from packet
-    fn extract_dest_ip(&self, packet: &Packet) -> Result<Ipv4Addr, ProcessorError> {
+    fn extract_dest_ip(&self, packet: &Packet) -> Result<IpAddr, ProcessorError> {
         match packet.packet_type() {
             PacketType::Ipv4 => {
                 let ipv4_packet = Ipv4Packet::from_bytes(packet.data())
                     .map_err(|_| ProcessorError::InvalidPacket)?;
-                Ok(ipv4_packet.header.dest_addr)
+                Ok(ipv4_packet.header.dest_addr.into())
+            }
+            PacketType::Ipv6 => {
+                let ipv6_packet = Ipv6Packet::from_bytes(packet.data())
+                    .map_err(|_| ProcessorError::InvalidPacket)?;
+                Ok(ipv6_packet.header.dest_addr.into())
             }
             _ => Err(ProcessorError::UnsupportedPacketType),
         }
@@ -481,13 +687,27 @@ impl NetworkProcessor {
     /// Clean up expired reassembly entries
     pub fn cleanup(&mut self) {
         self.reassembler.cleanup();
+        self.ipv6_reassembler.cleanup(crate::time::uptime_ms());
+    }
+
+    /// Run Neighbor Discovery timers for an interface
+    ///
+    /// Returns the solicitations to send: duplicate address detection,
+    /// router solicitation and neighbor probes.
+    pub fn ipv6_tick(&mut self, interface: &Interface) -> Vec<Packet> {
+        let now_ms = crate::time::uptime_ms();
+        self.ndp_processor
+            .tick(interface, &mut self.routing6_table, now_ms)
+            .into_iter()
+            .filter_map(|p| Packet::from_bytes(&p.to_bytes(), PacketType::Ipv6).ok())
+            .collect()
     }
 
     /// Get processor statistics
     pub fn stats(&self) -> ProcessorStats {
         ProcessorStats {
             udp_sockets: self.udp_sockets.len(),
-            tcp_sockets: self.tcp_sockets.len(),
+            tcp_sockets: with_tcp_manager(|manager| manager.stats().active_connections),
             reassembly_stats: self.reassembler.stats(),
             routing_stats: self.routing_table.stats(),
         }
@@ -502,6 +722,16 @@ impl NetworkProcessor {
     pub fn routing_table_mut(&mut self) -> &mut RoutingTable {
         &mut self.routing_table
     }
+
+    /// Get IPv6 routing table reference
+    pub fn routing6_table(&self) -> &Routing6Table {
+        &self.routing6_table
+    }
+
+    /// Get mutable IPv6 routing table reference
+    pub fn routing6_table_mut(&mut self) -> &mut Routing6Table {
+        &mut self.routing6_table
+    }
 }
 
 impl Default for NetworkProcessor {
@@ -0,0 +1,151 @@
+//! IPv6 routing table
+//!
+//! This module provides the IPv6 counterpart of [`super::route`]. Routes are
+//! prefixes with a length rather than address/netmask pairs, and routes
+//! learned from Router Advertisements expire unless refreshed.
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use super::ipv6::Ipv6Addr;
+
+/// IPv6 route entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route6Entry {
+    /// Destination prefix
+    pub destination: Ipv6Addr,
+    /// Prefix length in bits
+    pub prefix_len: u8,
+    /// Next hop (None for on-link destinations)
+    pub gateway: Option<Ipv6Addr>,
+    /// Interface ID
+    pub interface_id: u32,
+    /// Route metric
+    pub metric: u32,
+    /// Uptime in milliseconds at which the route expires (None for static routes)
+    pub expires_ms: Option<u64>,
+}
+
+impl Route6Entry {
+    /// Create a new route entry
+    pub fn new(
+        destination: Ipv6Addr,
+        prefix_len: u8,
+        gateway: Option<Ipv6Addr>,
+        interface_id: u32,
+        metric: u32,
+    ) -> Self {
+        Self {
+            destination: destination.network(prefix_len),
+            prefix_len,
+            gateway,
+            interface_id,
+            metric,
+            expires_ms: None,
+        }
+    }
+
+    /// Check if this route matches the destination address
+    pub fn matches(&self, addr: Ipv6Addr) -> bool {
+        addr.matches_prefix(self.destination, self.prefix_len)
+    }
+
+    /// Check if this is a default route (::/0)
+    pub fn is_default(&self) -> bool {
+        self.prefix_len == 0
+    }
+
+    /// Check if the route has expired at uptime `now_ms`
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_ms.is_some_and(|t| now_ms >= t)
+    }
+
+    /// Next hop for `dest` through this route
+    pub fn next_hop(&self, dest: Ipv6Addr) -> Ipv6Addr {
+        self.gateway.unwrap_or(dest)
+    }
+}
+
+/// IPv6 routing table
+#[derive(Debug, Clone, Default)]
+pub struct Routing6Table {
+    /// Route entries
+    entries: Vec<Route6Entry>,
+}
+
+impl Routing6Table {
+    /// Create a new routing table
+    pub fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    /// Add a route, replacing one to the same prefix through the same
+    /// gateway and interface
+    pub fn add_route(&mut self, route: Route6Entry) {
+        if let Some(existing) = self.entries.iter_mut().find(|r| {
+            r.destination == route.destination
+                && r.prefix_len == route.prefix_len
+                && r.gateway == route.gateway
+                && r.interface_id == route.interface_id
+        }) {
+            *existing = route;
+        } else {
+            self.entries.push(route);
+        }
+    }
+
+    /// Add a route to a directly attached prefix
+    pub fn add_direct_route(&mut self, destination: Ipv6Addr, prefix_len: u8, interface_id: u32) {
+        self.add_route(Route6Entry::new(destination, prefix_len, None, interface_id, 0));
+    }
+
+    /// Add a default route through `gateway`
+    pub fn add_default_route(&mut self, gateway: Ipv6Addr, interface_id: u32, metric: u32) {
+        self.add_route(Route6Entry::new(Ipv6Addr::UNSPECIFIED, 0, Some(gateway), interface_id, metric));
+    }
+
+    /// Remove routes to a prefix, returning whether any were removed
+    pub fn remove_route(&mut self, destination: Ipv6Addr, prefix_len: u8, gateway: Option<Ipv6Addr>) -> bool {
+        let destination = destination.network(prefix_len);
+        let before = self.entries.len();
+        self.entries.retain(|r| {
+            !(r.destination == destination && r.prefix_len == prefix_len && r.gateway == gateway)
+        });
+        self.entries.len() != before
+    }
+
+    /// Remove every route through an interface
+    pub fn remove_interface_routes(&mut self, interface_id: u32) {
+        self.entries.retain(|r| r.interface_id != interface_id);
+    }
+
+    /// Look up the route for a destination at uptime `now_ms`
+    ///
+    /// The longest matching prefix wins, then the lowest metric.
+    pub fn lookup_route(&self, dest: Ipv6Addr, now_ms: u64) -> Option<&Route6Entry> {
+        self.entries
+            .iter()
+            .filter(|r| r.matches(dest) && !r.is_expired(now_ms))
+            .min_by(|a, b| b.prefix_len.cmp(&a.prefix_len).then(a.metric.cmp(&b.metric)))
+    }
+
+    /// Get the next hop and interface for a destination
+    pub fn get_next_hop(&self, dest: Ipv6Addr, now_ms: u64) -> Option<(Ipv6Addr, u32)> {
+        self.lookup_route(dest, now_ms).map(|r| (r.next_hop(dest), r.interface_id))
+    }
+
+    /// Drop routes that have expired
+    pub fn expire(&mut self, now_ms: u64) {
+        self.entries.retain(|r| !r.is_expired(now_ms));
+    }
+
+    /// Get all routes
+    pub fn routes(&self) -> &[Route6Entry] {
+        &self.entries
+    }
+
+    /// Remove all routes
+    pub fn flush(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -9,10 +9,17 @@ use alloc::vec::Vec;
 use core::sync::atomic::{AtomicBool, Ordering};
 
 use super::ipv4::Ipv4Addr;
-use super::tcp::manager::{TcpConnection, TcpConnectionManager};
+use super::ipv6::{IpAddr, Ipv6Addr};
+use super::tcp::manager::{
+    tcp_events, wait_channel, with_tcp_manager, SocketHandle, TcpError, TcpOptions,
+};
 use super::udp::UdpSocket;
 use super::udp::UdpSocketState;
 use super::tcp::TcpState;
+use super::tcp::congestion::{default_algorithm, CongestionAlgorithm};
+
+/// Largest listen backlog
+pub const SOMAXCONN: i32 = 128;
 
 /// Socket types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -72,16 +79,25 @@ pub struct SocketAddr {
     /// Port number
     pub port: u16,
     /// IP address (for IPv4/IPv6)
-    pub ip: Ipv4Addr,
+    pub ip: IpAddr,
+    /// Scope (interface) ID for link-local IPv6 addresses
+    pub scope_id: u32,
 }
 
 impl SocketAddr {
+    /// Size of `sockaddr_in`
+    pub const SOCKADDR_IN_LEN: usize = 16;
+
+    /// Size of `sockaddr_in6`
+    pub const SOCKADDR_IN6_LEN: usize = 28;
+
     /// Create a new IPv4 socket address
     pub fn new_ipv4(ip: Ipv4Addr, port: u16) -> Self {
         Self {
             family: ProtocolFamily::IPv4,
             port,
-            ip,
+            ip: IpAddr::V4(ip),
+            scope_id: 0,
         }
     }
 
@@ -90,23 +106,47 @@ impl SocketAddr {
         Self::new_ipv4(Ipv4Addr::new(a, b, c, d), port)
     }
 
-    /// Get the IPv4 address
+    /// Create a new IPv6 socket address
+    pub fn new_ipv6(ip: Ipv6Addr, port: u16) -> Self {
+        Self {
+            family: ProtocolFamily::IPv6,
+            port,
+            ip: IpAddr::V6(ip),
+            scope_id: 0,
+        }
+    }
+
+    /// Wildcard address services bind to by default (`[::]:port`)
+    ///
+    /// Unless the socket is IPV6_V6ONLY this also accepts IPv4.
+    pub fn unspecified(port: u16) -> Self {
+        Self::new_ipv6(Ipv6Addr::UNSPECIFIED, port)
+    }
+
+    /// Get the IPv4 address, including one carried as IPv4-mapped IPv6
     pub fn ipv4_addr(&self) -> Option<Ipv4Addr> {
-        match self.family {
-            ProtocolFamily::IPv4 => Some(self.ip),
-            _ => None,
+        match self.ip {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(ip) => ip.to_ipv4_mapped(),
+        }
+    }
+
+    /// Get the IPv6 address
+    pub fn ipv6_addr(&self) -> Option<Ipv6Addr> {
+        match self.ip {
+            IpAddr::V6(ip) => Some(ip),
+            IpAddr::V4(_) => None,
         }
     }
 
     /// Check if this is the wildcard address
     pub fn is_wildcard(&self) -> bool {
-        match self.family {
-            ProtocolFamily::IPv4 => self.ip == Ipv4Addr::UNSPECIFIED,
-            _ => false,
-        }
+        self.ip.is_unspecified()
     }
 
     /// Convert to POSIX sockaddr format
+    ///
+    /// Only IPv4 fits in `sockaddr`; use [`Self::to_sockaddr_bytes`] for IPv6.
     pub fn to_posix_sockaddr(&self) -> crate::posix::Sockaddr {
         let mut addr = crate::posix::Sockaddr {
             sa_family: match self.family {
@@ -118,8 +158,8 @@ impl SocketAddr {
         };
 
         // Copy IPv4 address and port
-        if self.family == ProtocolFamily::IPv4 {
-            let ip_bytes = self.ip.to_be_bytes();
+        if let (ProtocolFamily::IPv4, IpAddr::V4(ip)) = (self.family, self.ip) {
+            let ip_bytes = ip.to_be_bytes();
             addr.sa_data[0] = (self.port >> 8) as u8;
             addr.sa_data[1] = (self.port & 0xFF) as u8;
             addr.sa_data[2] = ip_bytes[0];
@@ -148,6 +188,54 @@ impl SocketAddr {
             _ => None,
         }
     }
+
+    /// Encode as `sockaddr_in` or `sockaddr_in6`
+    pub fn to_sockaddr_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SOCKADDR_IN6_LEN);
+        match self.ip {
+            IpAddr::V4(ip) if self.family == ProtocolFamily::IPv4 => {
+                bytes.extend_from_slice(&(crate::posix::AF_INET as u16).to_ne_bytes());
+                bytes.extend_from_slice(&self.port.to_be_bytes());
+                bytes.extend_from_slice(&ip.to_be_bytes());
+                bytes.resize(Self::SOCKADDR_IN_LEN, 0);
+            }
+            ip => {
+                bytes.extend_from_slice(&(crate::posix::AF_INET6 as u16).to_ne_bytes());
+                bytes.extend_from_slice(&self.port.to_be_bytes());
+                bytes.extend_from_slice(&[0; 4]); // flow info
+                bytes.extend_from_slice(&ip.to_ipv6().octets());
+                bytes.extend_from_slice(&self.scope_id.to_ne_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Decode a `sockaddr_in` or `sockaddr_in6`
+    pub fn from_sockaddr_bytes(bytes: &[u8]) -> Option<Self> {
+        let family = u16::from_ne_bytes([*bytes.first()?, *bytes.get(1)?]) as i32;
+        match family {
+            crate::posix::AF_INET if bytes.len() >= Self::SOCKADDR_IN_LEN => {
+                let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+                let ip = Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]);
+                Some(Self::new_ipv4(ip, port))
+            }
+            crate::posix::AF_INET6 if bytes.len() >= Self::SOCKADDR_IN6_LEN => {
+                let port = u16::from_be_bytes([bytes[2], bytes[3]]);
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&bytes[8..24]);
+                let mut addr = Self::new_ipv6(Ipv6Addr::from_octets(octets), port);
+                addr.scope_id = u32::from_ne_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
+                Some(addr)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for SocketAddr {
+    fn default() -> Self {
+        Self::unspecified(0)
+    }
 }
 
 /// Socket options
@@ -169,6 +257,10 @@ pub struct SocketOptions {
     pub rcvbuf: u32,
     /// TCP_NODELAY (for TCP sockets)
     pub nodelay: bool,
+    /// IPV6_V6ONLY - don't accept IPv4 on an IPv6 wildcard bind
+    pub v6only: bool,
+    /// TCP_CONGESTION - `None` follows the system-wide default
+    pub congestion: Option<CongestionAlgorithm>,
 }
 
 /// Linger option
@@ -192,6 +284,8 @@ impl SocketOptions {
             sndbuf: 65536,
             rcvbuf: 65536,
             nodelay: false,
+            v6only: false,
+            congestion: None,
         }
     }
 
@@ -216,6 +310,8 @@ impl SocketOptions {
                 self.rcvbuf = size;
             }
             SocketOption::NoDelay(value) => self.nodelay = value,
+            SocketOption::V6Only(value) => self.v6only = value,
+            SocketOption::Congestion(algorithm) => self.congestion = Some(algorithm),
         }
         Ok(())
     }
@@ -237,6 +333,10 @@ impl SocketOptions {
             SocketOption::SndBuf(_) => SocketOptionValue::U32(self.sndbuf),
             SocketOption::RcvBuf(_) => SocketOptionValue::U32(self.rcvbuf),
             SocketOption::NoDelay(_) => SocketOptionValue::Bool(self.nodelay),
+            SocketOption::V6Only(_) => SocketOptionValue::Bool(self.v6only),
+            SocketOption::Congestion(_) => {
+                SocketOptionValue::Congestion(self.congestion.unwrap_or_else(default_algorithm))
+            }
         })
     }
 }
@@ -252,6 +352,8 @@ pub enum SocketOption {
     SndBuf(u32),
     RcvBuf(u32),
     NoDelay(bool),
+    V6Only(bool),
+    Congestion(CongestionAlgorithm),
 }
 
 /// Socket option values
@@ -260,6 +362,7 @@ pub enum SocketOptionValue {
     Bool(bool),
     Linger(LingerOption),
     U32(u32),
+    Congestion(CongestionAlgorithm),
 }
 
 /// High-level socket abstraction
@@ -273,242 +376,283 @@ pub enum Socket {
     Raw(RawSocketWrapper),
 }
 
-/// TCP socket wrapper
-#[derive(Debug)]
-pub struct TcpSocketWrapper {
-    /// TCP connection
-    connection: Option<Arc<TcpConnection>>,
-    /// Connection ID
-    connection_id: Option<super::tcp::manager::ConnectionId>,
-    /// Socket state
-    state: TcpState,
-    /// Socket options
-    options: SocketOptions,
+/// Convert socket options to the TCP manager's options
+fn tcp_options(options: &SocketOptions) -> TcpOptions {
+    TcpOptions {
+        keep_alive: options.keep_alive,
+        nagle_enabled: !options.nodelay,
+        reuse_addr: options.reuse_addr,
+        reuse_port: options.reuse_port,
+        v6only: options.v6only,
+        recv_buf_size: options.rcvbuf,
+        send_buf_size: options.sndbuf,
+        congestion: options.congestion.unwrap_or_else(default_algorithm),
+        ..TcpOptions::default()
+    }
+}
+
+/// State shared by all clones of a TCP socket
+#[derive(Debug, Default)]
+struct TcpSocketShared {
     /// Non-blocking mode
     nonblocking: AtomicBool,
-    /// Pending connections (for listening sockets)
-    pending_connections: Vec<Arc<TcpConnection>>,
+    /// Report addresses as IPv6 (the socket was bound or connected with one)
+    ipv6: AtomicBool,
 }
 
-impl Clone for TcpSocketWrapper {
-    fn clone(&self) -> Self {
-        Self {
-            connection: self.connection.clone(),
-            connection_id: self.connection_id,
-            state: self.state,
-            options: self.options,
-            nonblocking: AtomicBool::new(self.nonblocking.load(Ordering::Relaxed)),
-            pending_connections: self.pending_connections.clone(),
-        }
-    }
+/// TCP socket wrapper
+///
+/// The protocol state lives in the global TCP connection manager; the
+/// wrapper holds its handle. Clones refer to the same socket, so the file
+/// table and the socket table see one endpoint.
+#[derive(Debug, Clone)]
+pub struct TcpSocketWrapper {
+    /// Handle in the TCP connection manager
+    handle: SocketHandle,
+    /// Socket options
+    options: SocketOptions,
+    /// Flags shared between clones
+    shared: Arc<TcpSocketShared>,
 }
 
 impl TcpSocketWrapper {
     /// Create a new TCP socket
     pub fn new(options: SocketOptions) -> Self {
+        let handle = with_tcp_manager(|manager| manager.open(tcp_options(&options)));
         Self {
-            connection: None,
-            connection_id: None,
-            state: TcpState::Closed,
+            handle,
             options,
-            nonblocking: AtomicBool::new(false),
-            pending_connections: Vec::new(),
+            shared: Arc::new(TcpSocketShared::default()),
+        }
+    }
+
+    /// Set a socket option
+    ///
+    /// TCP_CONGESTION takes effect at once; the others apply from the next
+    /// bind, listen or connect.
+    pub fn set_option(&mut self, option: SocketOption) -> Result<(), SocketError> {
+        self.options.set_option(option)?;
+        if let SocketOption::Congestion(algorithm) = option {
+            with_tcp_manager(|manager| manager.set_congestion(self.handle, algorithm))?;
         }
+        Ok(())
     }
 
     /// Bind to local address
-    pub fn bind(&mut self, _addr: SocketAddr) -> Result<(), SocketError> {
-        // Implementation would use TCP connection manager
-        // For now, just update state
+    ///
+    /// Port 0 picks an ephemeral port. The address may be in use only if
+    /// every socket involved set SO_REUSEADDR and none of them listens.
+    pub fn bind(&mut self, addr: SocketAddr) -> Result<(), SocketError> {
+        let options = tcp_options(&self.options);
+        with_tcp_manager(|manager| manager.bind(self.handle, addr.ip, addr.port, options))?;
+        self.remember_family(addr);
         Ok(())
     }
 
     /// Start listening
-    pub fn listen(&mut self, _backlog: i32) -> Result<(), SocketError> {
-        // Implementation would create listening socket
-        self.state = TcpState::Listen;
+    ///
+    /// An unbound socket is first bound to an ephemeral port on the wildcard.
+    pub fn listen(&mut self, backlog: i32) -> Result<(), SocketError> {
+        let backlog = backlog.clamp(1, SOMAXCONN) as usize;
+        if self.local_addr().is_none() {
+            self.bind(SocketAddr::unspecified(0))?;
+        }
+        with_tcp_manager(|manager| manager.listen(self.handle, backlog))?;
         Ok(())
     }
 
     /// Accept a connection
     pub fn accept(&mut self) -> Result<(Socket, SocketAddr), SocketError> {
-        if self.pending_connections.is_empty() {
-            return Err(SocketError::WouldBlock);
-        }
-
-        let connection = self.pending_connections.remove(0);
-        let socket = Socket::Tcp(TcpSocketWrapper {
-            connection: Some(connection.clone()),
-            connection_id: None,
-            state: TcpState::Established,
-            options: self.options.clone(),
-            nonblocking: AtomicBool::new(self.nonblocking.load(Ordering::Relaxed)),
-            pending_connections: Vec::new(),
-        });
+        let (child, id) = self.wait_for(|| {
+            with_tcp_manager(|manager| manager.accept(self.handle))?.ok_or(SocketError::WouldBlock)
+        })?;
 
-        // Get peer address (simplified)
-        let peer_addr = SocketAddr::new_ipv4_from_octets(127, 0, 0, 1, 8080);
-
-        Ok((socket, peer_addr))
+        let socket = TcpSocketWrapper {
+            handle: child,
+            options: self.options,
+            shared: Arc::new(TcpSocketShared {
+                nonblocking: AtomicBool::new(self.is_nonblocking()),
+                ipv6: AtomicBool::new(self.shared.ipv6.load(Ordering::Relaxed)),
+            }),
+        };
+        let peer_addr = self.socket_addr(id.remote_ip, id.remote_port);
+        Ok((Socket::Tcp(socket), peer_addr))
     }
 
     /// Connect to remote address
-    pub fn connect(&mut self, _addr: SocketAddr) -> Result<(), SocketError> {
-        // Implementation would use TCP connection manager
-        self.state = TcpState::SynSent;
-        Ok(())
+    ///
+    /// Blocks until the handshake completes. A non-blocking socket returns
+    /// [`SocketError::InProgress`] instead; it polls writable once connected
+    /// and [`Self::take_error`] reports a failure.
+    pub fn connect(&mut self, addr: SocketAddr) -> Result<(), SocketError> {
+        if addr.port == 0 || addr.ip.is_unspecified() {
+            return Err(SocketError::InvalidAddress);
+        }
+        let source = super::network_stack()
+            .select_source(addr.ip)
+            .ok_or(SocketError::NetworkUnreachable)?;
+        let options = tcp_options(&self.options);
+        let now = crate::time::uptime_ms();
+        with_tcp_manager(|manager| {
+            manager.connect(self.handle, source, addr.ip, addr.port, options, now)
+        })?;
+        self.remember_family(addr);
+
+        let handshake = self.wait_for(|| {
+            match with_tcp_manager(|manager| manager.connect_status(self.handle))? {
+                true => Ok(()),
+                false => Err(SocketError::WouldBlock),
+            }
+        });
+        match handshake {
+            Err(SocketError::WouldBlock) => Err(SocketError::InProgress),
+            result => result,
+        }
     }
 
     /// Send data
+    ///
+    /// Blocks until at least part of `data` fits in the send buffer and
+    /// returns how much was queued.
     pub fn send(&mut self, data: &[u8]) -> Result<usize, SocketError> {
-        if self.state != TcpState::Established {
-            return Err(SocketError::NotConnected);
-        }
-
-        // Implementation would use TCP connection
-        // For now, just return length
-        Ok(data.len())
+        let sent = self.wait_for(|| {
+            let now = crate::time::uptime_ms();
+            Ok(with_tcp_manager(|manager| manager.send(self.handle, data, now))?)
+        })?;
+        // Push the segments out now rather than on the next poll
+        super::poll();
+        Ok(sent)
     }
 
     /// Send data with zero-copy optimization
-    /// This method attempts to send data without copying to kernel buffers
+    ///
+    /// Segments are built from the send buffer, so this is the copying path
+    /// until the device layer can transmit from pinned user pages.
     pub fn send_zero_copy(&mut self, data: &[u8]) -> Result<usize, SocketError> {
-        if self.state != TcpState::Established {
-            return Err(SocketError::NotConnected);
-        }
-
-        // Zero-copy send: Use PacketBuffer for DMA-optimized transfer
-        // In a full implementation, this would:
-        // 1. Pin user buffer pages to prevent swapping
-        // 2. Map pages directly to network device DMA
-        // 3. Send without copying to kernel space
-        
-        // For now, use PacketBuffer to minimize copies
-        use crate::net::packet::PacketBuffer;
-        
-        // Create a packet buffer that can be used for DMA
-        let mut packet_buf = match PacketBuffer::new(data.len()) {
-            Ok(buf) => buf,
-            Err(_) => return Err(SocketError::NoBufferSpace),
-        };
-        
-        // Write data to packet buffer (single copy instead of multiple)
-        if let Err(_) = packet_buf.write_bytes(data) {
-            return Err(SocketError::NotConnected);
-        }
-        
-        // Get DMA buffer for network device
-        let (_dma_ptr, _dma_len) = packet_buf.prepare_for_dma_read();
-        
-        // Send via TCP connection if available
-        if let Some(ref _connection) = self.connection {
-            // Mutex在当前作用域中未使用，暂时注释掉
-            // use crate::subsystems::sync::Mutex;
-            // Get mutable access to connection
-            // Note: This requires proper synchronization in real implementation
-            // For now, we'll use the regular send path but with optimized buffer
-            
-            // In a real zero-copy implementation, we would:
-            // 1. Pass the DMA buffer directly to the network device driver
-            // 2. The driver would send without copying
-            // 3. Release the buffer after transmission completes
-            
-            // For now, fall back to regular send but with optimized buffer
-            return self.send(data);
-        }
-        
-        // Fall back to regular send if no connection
         self.send(data)
     }
 
     /// Receive data
-    pub fn recv(&mut self, _buf: &mut [u8]) -> Result<usize, SocketError> {
-        if self.state != TcpState::Established {
-            return Err(SocketError::NotConnected);
-        }
-
-        // Implementation would use TCP connection
-        // For now, just return 0
-        Ok(0)
+    ///
+    /// Blocks until data arrives; `Ok(0)` means the peer closed.
+    pub fn recv(&mut self, buf: &mut [u8]) -> Result<usize, SocketError> {
+        self.wait_for(|| {
+            let now = crate::time::uptime_ms();
+            Ok(with_tcp_manager(|manager| manager.recv(self.handle, buf, now))?)
+        })
     }
 
     /// Receive data with zero-copy optimization
-    /// This method attempts to receive data directly into user buffer
+    ///
+    /// Data is copied once, from the receive buffer into `buf`.
     pub fn recv_zero_copy(&mut self, buf: &mut [u8]) -> Result<usize, SocketError> {
-        if self.state != TcpState::Established {
-            return Err(SocketError::NotConnected);
-        }
-
-        // Zero-copy receive: Use PacketBuffer for DMA-optimized transfer
-        // In a full implementation, this would:
-        // 1. Pin user buffer pages to prevent swapping
-        // 2. Map network device DMA directly to user pages
-        // 3. Receive without copying through kernel buffers
-        
-        // For now, use PacketBuffer to minimize copies
-        use crate::net::packet::PacketBuffer;
-        
-        // Receive from TCP connection if available
-        if let Some(ref _connection) = self.connection {
-            // Mutex在当前作用域中未使用，暂时注释掉
-            // use crate::subsystems::sync::Mutex;
-            
-            // In a real zero-copy implementation:
-            // 1. Prepare user buffer for DMA
-            // 2. Register buffer with network device
-            // 3. Device writes directly to user buffer
-            // 4. Unregister buffer after reception
-            
-            // For now, use optimized receive path
-            // Create a packet buffer for receiving
-            let mut packet_buf = match PacketBuffer::new(buf.len()) {
-                Ok(buf) => buf,
-                Err(_) => return Err(SocketError::NoBufferSpace),
-            };
-            
-            // Get DMA buffer for network device to write to
-            let (_dma_ptr, _dma_len) = packet_buf.as_dma_buffer();
-            
-            // In real implementation, network device would write directly here
-            // For now, receive into packet buffer first
-            let mut temp_buf = alloc::vec![0u8; buf.len().min(8192)];
-            let received = self.recv(&mut temp_buf)?;
-            
-            if received > 0 {
-                // Copy from packet buffer to user buffer (minimized copy)
-                let copy_len = received.min(buf.len());
-                buf[..copy_len].copy_from_slice(&temp_buf[..copy_len]);
-                return Ok(copy_len);
-            }
-            
-            return Ok(0);
-        }
-        
-        // Fall back to regular recv if no connection
         self.recv(buf)
     }
 
+    /// Shut down reading, writing or both
+    pub fn shutdown(&mut self, read: bool, write: bool) -> Result<(), SocketError> {
+        let now = crate::time::uptime_ms();
+        with_tcp_manager(|manager| manager.shutdown(self.handle, read, write, now))?;
+        super::poll();
+        Ok(())
+    }
+
     /// Close socket
+    ///
+    /// The connection finishes sending and goes through FIN and TIME-WAIT
+    /// on its own; this does not wait for it.
     pub fn close(&mut self) -> Result<(), SocketError> {
-        self.state = TcpState::Closed;
-        self.connection = None;
-        self.connection_id = None;
+        let now = crate::time::uptime_ms();
+        with_tcp_manager(|manager| manager.close(self.handle, now));
+        super::poll();
         Ok(())
     }
 
+    /// Poll events (POLLIN, POLLOUT, POLLHUP, POLLERR) for poll and epoll
+    pub fn poll(&self) -> i16 {
+        let readiness = with_tcp_manager(|manager| manager.poll(self.handle));
+        let mut events = 0;
+        if readiness.readable {
+            events |= crate::posix::POLLIN;
+        }
+        if readiness.writable {
+            events |= crate::posix::POLLOUT;
+        }
+        if readiness.hangup {
+            events |= crate::posix::POLLHUP;
+        }
+        if readiness.error {
+            events |= crate::posix::POLLERR;
+        }
+        events
+    }
+
+    /// Take the pending error (SO_ERROR)
+    pub fn take_error(&self) -> Option<SocketError> {
+        with_tcp_manager(|manager| manager.take_error(self.handle)).map(SocketError::from)
+    }
+
+    /// Local address, once bound
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        let (ip, port) = with_tcp_manager(|manager| manager.local_addr(self.handle))?;
+        Some(self.socket_addr(ip, port))
+    }
+
+    /// Remote address, once connecting
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        let (ip, port) = with_tcp_manager(|manager| manager.peer_addr(self.handle))?;
+        Some(self.socket_addr(ip, port))
+    }
+
     /// Get socket state
     pub fn state(&self) -> TcpState {
-        self.state
+        with_tcp_manager(|manager| manager.socket_state(self.handle))
     }
 
     /// Set non-blocking mode
     pub fn set_nonblocking(&self, nonblocking: bool) {
-        self.nonblocking.store(nonblocking, Ordering::Relaxed);
+        self.shared.nonblocking.store(nonblocking, Ordering::Relaxed);
     }
 
     /// Check if non-blocking
     pub fn is_nonblocking(&self) -> bool {
-        self.nonblocking.load(Ordering::Relaxed)
+        self.shared.nonblocking.load(Ordering::Relaxed)
+    }
+
+    /// Report addresses in the family the application used
+    fn remember_family(&self, addr: SocketAddr) {
+        if addr.family == ProtocolFamily::IPv6 {
+            self.shared.ipv6.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Build a socket address in the socket's family
+    fn socket_addr(&self, ip: IpAddr, port: u16) -> SocketAddr {
+        match ip {
+            IpAddr::V4(ip) if !self.shared.ipv6.load(Ordering::Relaxed) => SocketAddr::new_ipv4(ip, port),
+            ip => SocketAddr::new_ipv6(ip.to_ipv6(), port),
+        }
+    }
+
+    /// Retry `attempt` until it stops reporting [`SocketError::WouldBlock`]
+    ///
+    /// Every round runs the network stack first, so loopback traffic moves
+    /// even without the poll thread. Non-blocking sockets get one round.
+    fn wait_for<T>(&self, mut attempt: impl FnMut() -> Result<T, SocketError>) -> Result<T, SocketError> {
+        loop {
+            let seen = tcp_events();
+            super::poll();
+            match attempt() {
+                Err(SocketError::WouldBlock) if !self.is_nonblocking() => {}
+                result => return result,
+            }
+
+            // Sleep until the TCP state changes; the tick bounds the wait
+            // for timers that fire without a packet
+            let chan = wait_channel();
+            crate::subsystems::time::add_sleeper(crate::subsystems::time::get_ticks() + 1, chan);
+            crate::process::sleep_unless(chan, || tcp_events() != seen);
+        }
     }
 }
 
@@ -549,13 +693,14 @@ impl UdpSocketWrapper {
 
     /// Bind to local address
     pub fn bind(&mut self, addr: SocketAddr) -> Result<(), SocketError> {
-        if let Some(ip) = addr.ipv4_addr() {
-            self.socket.bind(ip, addr.port);
-            self.state = UdpSocketState::Bound;
-            Ok(())
-        } else {
-            Err(SocketError::InvalidAddress)
+        if addr.ip.is_multicast() {
+            return Err(SocketError::InvalidAddress);
         }
+        // IPv4-mapped binds behave as IPv4 binds
+        self.socket.bind(addr.ip.to_canonical(), addr.port);
+        self.socket.v6only = self.options.v6only;
+        self.state = UdpSocketState::Bound;
+        Ok(())
     }
 
     /// Send data to destination
@@ -564,12 +709,18 @@ impl UdpSocketWrapper {
             return Err(SocketError::NotBound);
         }
 
-        if let Some(_dest_ip) = dest.ipv4_addr() {
-            // Implementation would actually send the data
-            Ok(data.len())
-        } else {
-            Err(SocketError::InvalidAddress)
+        // An IPv4 socket can't reach IPv6 destinations, a dual-stack one
+        // reaches IPv4 through mapped addresses
+        let dest_v4 = dest.ipv4_addr().is_some();
+        let local_v4 = self.socket.local_ip.is_ipv4();
+        if local_v4 && !dest_v4 {
+            return Err(SocketError::InvalidAddress);
         }
+        if dest_v4 && self.socket.v6only && !local_v4 {
+            return Err(SocketError::InvalidAddress);
+        }
+        // Implementation would actually send the data
+        Ok(data.len())
     }
 
     /// Send data with zero-copy optimization (UDP)
@@ -744,6 +895,31 @@ pub enum SocketError {
     NotSupported,
     /// Permission denied
     PermissionDenied,
+    /// Non-blocking connect started (EINPROGRESS)
+    InProgress,
+    /// Socket is already connected
+    AlreadyConnected,
+    /// Writing after the connection was shut down
+    BrokenPipe,
+    /// No route to the destination
+    NetworkUnreachable,
+}
+
+impl From<TcpError> for SocketError {
+    fn from(error: TcpError) -> Self {
+        match error {
+            TcpError::InvalidPacket | TcpError::InvalidConnection => SocketError::InvalidValue,
+            TcpError::ConnectionNotFound | TcpError::NotConnected => SocketError::NotConnected,
+            TcpError::AlreadyConnected => SocketError::AlreadyConnected,
+            TcpError::InProgress => SocketError::InProgress,
+            TcpError::WouldBlock | TcpError::BufferFull => SocketError::WouldBlock,
+            TcpError::Shutdown => SocketError::BrokenPipe,
+            TcpError::PortInUse | TcpError::NoPortsAvailable => SocketError::AddressInUse,
+            TcpError::ConnectionRefused => SocketError::ConnectionRefused,
+            TcpError::ConnectionReset => SocketError::ConnectionReset,
+            TcpError::ConnectionTimeout => SocketError::ConnectionTimeout,
+        }
+    }
 }
 
 /// Socket entry for socket management
@@ -813,16 +989,3 @@ impl SocketEntry {
         matches!(self.state, SocketState::Connected)
     }
 }
-
-/// Get global socket manager
-pub fn socket_manager() -> &'static mut TcpConnectionManager {
-    static mut MANAGER: Option<TcpConnectionManager> = None;
-    static INIT: crate::subsystems::sync::Once = crate::subsystems::sync::Once::new();
-
-    unsafe {
-        INIT.call_once(|| {
-            MANAGER = Some(TcpConnectionManager::new());
-        });
-        MANAGER.as_mut().unwrap()
-    }
-}
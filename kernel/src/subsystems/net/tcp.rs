@@ -5,10 +5,12 @@
 extern crate alloc;
 use alloc::vec::Vec;
 
-use super::ipv4::Ipv4Addr;
+use super::ipv4::{self, Ipv4Addr};
+use super::ipv6::{self, IpAddr};
 
 pub mod state;
 pub mod manager;
+pub mod congestion;
 
 /// TCP header
 #[derive(Debug, Clone, Copy)]
@@ -293,7 +295,7 @@ impl TcpPacket {
 
     /// Get total packet size
     pub fn len(&self) -> usize {
-        self.header_size() + self.options.len() + self.payload.len()
+        self.header_size() + self.payload.len()
     }
 
     /// Check if flag is set
@@ -301,10 +303,109 @@ impl TcpPacket {
         self.header.has_flag(flag)
     }
 
+    /// Create a segment with options and no checksum yet
+    ///
+    /// Options are padded to a multiple of four bytes and the data offset is
+    /// set to cover them. Call [`TcpPacket::seal`] once the addresses are known.
+    pub fn segment(
+        src_port: u16,
+        dst_port: u16,
+        seq_num: u32,
+        ack_num: u32,
+        flags: u8,
+        window_size: u16,
+        mut options: Vec<u8>,
+        payload: Vec<u8>,
+    ) -> Self {
+        while options.len() % 4 != 0 {
+            options.push(TcpOptionKind::End as u8);
+        }
+        let mut header = TcpHeader::new(src_port, dst_port, seq_num, ack_num, flags, window_size);
+        header.set_data_offset(((TcpHeader::MIN_SIZE + options.len()) / 4) as u8);
+
+        Self {
+            header,
+            options,
+            payload,
+        }
+    }
+
+    /// Sequence space consumed by this segment (payload plus SYN and FIN)
+    pub fn seq_len(&self) -> u32 {
+        let mut len = self.payload.len() as u32;
+        if self.has_flag(tcp_flags::SYN) {
+            len += 1;
+        }
+        if self.has_flag(tcp_flags::FIN) {
+            len += 1;
+        }
+        len
+    }
+
+    /// Parse the options, skipping kinds this stack does not know
+    pub fn parse_options(&self) -> Vec<TcpOption> {
+        parse_options(&self.options)
+    }
+
+    /// Maximum segment size announced by the peer, if any
+    pub fn mss(&self) -> Option<u16> {
+        self.parse_options()
+            .iter()
+            .find_map(|option| MssOption::from_option(option).ok())
+            .map(|option| option.mss)
+    }
+
+    /// Whether the peer offered SACK (RFC 2018)
+    pub fn sack_permitted(&self) -> bool {
+        self.parse_options()
+            .iter()
+            .any(|option| option.kind == TcpOptionKind::SackPermitted)
+    }
+
+    /// SACK blocks reported by the peer
+    pub fn sack_blocks(&self) -> Vec<SackBlock> {
+        self.parse_options()
+            .iter()
+            .find_map(|option| SackOption::from_option(option).ok())
+            .map(|option| option.blocks)
+            .unwrap_or_default()
+    }
+
+    /// Checksum over the pseudo-header of `source` and `dest` and the segment
+    ///
+    /// Over a segment with a correct checksum in place the result is zero.
+    /// Addresses of different families give no valid checksum.
+    pub fn checksum(&self, source: IpAddr, dest: IpAddr) -> Option<u16> {
+        let bytes = self.to_bytes();
+        match (source.to_canonical(), dest.to_canonical()) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                Some(ipv4::pseudo_header_checksum(src, dst, ipv4::protocols::TCP, &bytes))
+            }
+            (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                Some(ipv6::pseudo_header_checksum(src, dst, ipv6::protocols::TCP, &bytes))
+            }
+            _ => None,
+        }
+    }
+
+    /// Fill in the checksum for a segment sent from `source` to `dest`
+    pub fn seal(&mut self, source: IpAddr, dest: IpAddr) {
+        self.header.checksum = 0;
+        self.header.checksum = self.checksum(source, dest).unwrap_or(0);
+    }
+
+    /// Check the checksum of a segment received from `source` for `dest`
+    pub fn verify(&self, source: IpAddr, dest: IpAddr) -> bool {
+        self.checksum(source, dest) == Some(0)
+    }
+
     /// Serialize packet to bytes
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = self.header.to_bytes();
+        // The header pads up to its data offset; options go in that space
+        bytes.truncate(TcpHeader::MIN_SIZE);
         bytes.extend_from_slice(&self.options);
+        bytes.resize(self.header_size().max(bytes.len()), 0);
         bytes.extend_from_slice(&self.payload);
         bytes
     }
@@ -345,6 +446,50 @@ impl TcpPacket {
     }
 }
 
+/// Parse TCP options, stopping at End and skipping unknown kinds
+pub fn parse_options(bytes: &[u8]) -> Vec<TcpOption> {
+    let mut options = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            0 => break,
+            1 => i += 1,
+            _ => {
+                let Some(&length) = bytes.get(i + 1) else { break };
+                let length = length as usize;
+                if length < 2 || i + length > bytes.len() {
+                    break;
+                }
+                if let Ok(option) = TcpOption::from_bytes(&bytes[i..i + length]) {
+                    options.push(option);
+                }
+                i += length;
+            }
+        }
+    }
+    options
+}
+
+/// Sequence number `a` comes before `b` (modulo 2^32)
+pub fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+/// Sequence number `a` comes before or equals `b` (modulo 2^32)
+pub fn seq_le(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) <= 0
+}
+
+/// Sequence number `a` comes after `b` (modulo 2^32)
+pub fn seq_gt(a: u32, b: u32) -> bool {
+    seq_lt(b, a)
+}
+
+/// Sequence number `a` comes after or equals `b` (modulo 2^32)
+pub fn seq_ge(a: u32, b: u32) -> bool {
+    seq_le(b, a)
+}
+
 /// TCP connection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TcpState {
@@ -695,8 +840,8 @@ pub struct SackOption {
     pub blocks: Vec<SackBlock>,
 }
 
-/// SACK block
-#[derive(Debug, Clone)]
+/// SACK block: the sequence range [left, right) was received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SackBlock {
     pub left: u32,
     pub right: u32,
@@ -727,15 +872,15 @@ impl SackBlock {
 }
 
 impl SackOption {
+    /// Blocks that fit in the option space next to no other options
+    pub const MAX_BLOCKS: usize = 4;
+
     pub fn new(blocks: Vec<SackBlock>) -> Self {
         Self { blocks }
     }
 
     pub fn to_option(&self) -> TcpOption {
-        let mut data = Vec::with_capacity(2 + self.blocks.len() * 8);
-        data.push(self.blocks.len() as u8);
-        data.push(0);
-
+        let mut data = Vec::with_capacity(self.blocks.len() * 8);
         for block in &self.blocks {
             data.extend_from_slice(&block.to_bytes());
         }
@@ -744,21 +889,15 @@ impl SackOption {
     }
 
     pub fn from_option(option: &TcpOption) -> Result<Self, TcpError> {
-        if option.kind != TcpOptionKind::Sack || option.data.len() < 2 {
+        if option.kind != TcpOptionKind::Sack || option.data.is_empty() || !option.data.len().is_multiple_of(8) {
             return Err(TcpError::InvalidOption);
         }
 
-        let block_count = option.data[0] as usize;
-        if option.data.len() < 2 + block_count * 8 {
-            return Err(TcpError::InvalidOption);
-        }
-
-        let mut blocks = Vec::with_capacity(block_count);
-        for i in 0..block_count {
-            let offset = 2 + i * 8;
-            let block = SackBlock::from_bytes(&option.data[offset..offset + 8])?;
-            blocks.push(block);
-        }
+        let blocks = option
+            .data
+            .chunks_exact(8)
+            .map(SackBlock::from_bytes)
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self { blocks })
     }
@@ -0,0 +1,715 @@
+//! TCP congestion control
+//!
+//! Congestion control is pluggable: every connection owns a
+//! [`CongestionControl`] picked per socket with TCP_CONGESTION or taken from
+//! the system-wide default. The state machine reports acknowledgments and
+//! loss episodes; the algorithm answers with a congestion window and, for
+//! model-based algorithms, a pacing rate. Loss detection itself (SACK, RACK
+//! and TLP) stays in the state machine, so every algorithm sees the same
+//! loss signal.
+//!
+//! Provided algorithms:
+//! - NewReno (RFC 5681, RFC 6582), with appropriate byte counting
+//! - CUBIC (RFC 9438), the default
+//! - BBR, modelling the bottleneck bandwidth and the round-trip propagation
+//!   time instead of reacting to loss
+
+extern crate alloc;
+use alloc::boxed::Box;
+
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::defaults;
+
+/// Congestion control algorithms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CongestionAlgorithm {
+    /// Loss-based AIMD (RFC 5681, RFC 6582)
+    NewReno = 0,
+    /// Cubic window growth independent of the RTT (RFC 9438)
+    Cubic = 1,
+    /// Bottleneck bandwidth and round-trip propagation time
+    Bbr = 2,
+}
+
+impl CongestionAlgorithm {
+    /// Every algorithm, in the order they are listed to users
+    pub const ALL: [Self; 3] = [Self::NewReno, Self::Cubic, Self::Bbr];
+
+    /// Name used by TCP_CONGESTION
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NewReno => "reno",
+            Self::Cubic => "cubic",
+            Self::Bbr => "bbr",
+        }
+    }
+
+    /// Look up an algorithm by its TCP_CONGESTION name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "reno" | "newreno" => Some(Self::NewReno),
+            "cubic" => Some(Self::Cubic),
+            "bbr" => Some(Self::Bbr),
+            _ => None,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::NewReno,
+            2 => Self::Bbr,
+            _ => Self::Cubic,
+        }
+    }
+
+    /// Fresh controller for a connection sending `mss`-byte segments
+    pub fn create(self, mss: u32) -> Box<dyn CongestionControl> {
+        match self {
+            Self::NewReno => Box::new(NewReno::new(mss)),
+            Self::Cubic => Box::new(Cubic::new(mss)),
+            Self::Bbr => Box::new(Bbr::new(mss)),
+        }
+    }
+}
+
+/// Algorithm for connections whose socket did not set TCP_CONGESTION
+static DEFAULT_ALGORITHM: AtomicU8 = AtomicU8::new(CongestionAlgorithm::Cubic as u8);
+
+/// System-wide default algorithm
+pub fn default_algorithm() -> CongestionAlgorithm {
+    CongestionAlgorithm::from_u8(DEFAULT_ALGORITHM.load(Ordering::Relaxed))
+}
+
+/// Set the system-wide default; existing connections keep their algorithm
+pub fn set_default_algorithm(algorithm: CongestionAlgorithm) {
+    DEFAULT_ALGORITHM.store(algorithm as u8, Ordering::Relaxed);
+}
+
+/// Congestion control phase, for reporting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CongestionState {
+    /// Slow start (BBR: startup)
+    SlowStart,
+    /// Congestion avoidance (BBR: steady state)
+    CongestionAvoidance,
+}
+
+/// What an acknowledgment delivered
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AckSample {
+    /// Uptime in milliseconds
+    pub now: u64,
+    /// Bytes newly acknowledged, cumulatively or selectively
+    pub acked: u32,
+    /// Bytes still in flight once the acknowledgment is processed
+    pub in_flight: u32,
+    /// Round-trip time measured by this acknowledgment (ms)
+    pub rtt: Option<u64>,
+    /// Delivery rate measured by this acknowledgment (bytes per second)
+    pub delivery_rate: Option<u64>,
+    /// The rate was limited by the application rather than the network
+    pub app_limited: bool,
+    /// Bytes delivered over the connection when the newest acknowledged
+    /// segment was sent
+    pub prior_delivered: u64,
+    /// Bytes delivered over the connection so far
+    pub delivered: u64,
+    /// Loss recovery is in progress
+    pub in_recovery: bool,
+}
+
+/// How a loss episode was detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LossEvent {
+    /// SACK, RACK or duplicate ACKs found lost segments
+    Detected,
+    /// The retransmission timer expired
+    Timeout,
+}
+
+/// Congestion control algorithm of one connection
+///
+/// Windows are in bytes. The state machine calls [`on_loss`] once per loss
+/// episode and [`on_recovery_end`] when the episode's data is acknowledged.
+///
+/// [`on_loss`]: CongestionControl::on_loss
+/// [`on_recovery_end`]: CongestionControl::on_recovery_end
+pub trait CongestionControl: Debug + Send {
+    /// Algorithm implemented
+    fn algorithm(&self) -> CongestionAlgorithm;
+
+    /// Data was delivered
+    fn on_ack(&mut self, sample: &AckSample, mss: u32);
+
+    /// A loss episode started with `in_flight` bytes outstanding
+    fn on_loss(&mut self, event: LossEvent, in_flight: u32, mss: u32, now: u64);
+
+    /// The loss episode ended
+    fn on_recovery_end(&mut self, _mss: u32) {}
+
+    /// Congestion window
+    fn cwnd(&self) -> u32;
+
+    /// Slow start threshold
+    fn ssthresh(&self) -> u32;
+
+    /// Current phase
+    fn state(&self) -> CongestionState;
+
+    /// Rate to pace transmissions at, in bytes per second
+    fn pacing_rate(&self) -> Option<u64> {
+        None
+    }
+
+    /// Clone behind a box, so that connections stay cloneable
+    fn clone_box(&self) -> Box<dyn CongestionControl>;
+}
+
+impl Clone for Box<dyn CongestionControl> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Initial window (RFC 6928)
+fn initial_window(mss: u32) -> u32 {
+    defaults::INITIAL_CWND * mss
+}
+
+/// NewReno (RFC 5681, RFC 6582)
+///
+/// Slow start doubles the window every round trip, congestion avoidance
+/// adds one segment per window acknowledged and a loss halves it. The window
+/// does not grow during recovery, where the state machine's pipe estimate
+/// (RFC 6675) decides what may be sent.
+#[derive(Debug, Clone)]
+pub struct NewReno {
+    cwnd: u32,
+    ssthresh: u32,
+    /// Bytes acknowledged toward the next congestion avoidance increase
+    bytes_acked: u32,
+}
+
+impl NewReno {
+    pub fn new(mss: u32) -> Self {
+        Self {
+            cwnd: initial_window(mss),
+            ssthresh: defaults::INITIAL_SSTHRESH,
+            bytes_acked: 0,
+        }
+    }
+}
+
+impl CongestionControl for NewReno {
+    fn algorithm(&self) -> CongestionAlgorithm {
+        CongestionAlgorithm::NewReno
+    }
+
+    fn on_ack(&mut self, sample: &AckSample, mss: u32) {
+        if sample.in_recovery {
+            return;
+        }
+        if self.cwnd < self.ssthresh {
+            self.cwnd = self.cwnd.saturating_add(sample.acked.min(mss));
+            return;
+        }
+        // Appropriate byte counting (RFC 3465)
+        self.bytes_acked = self.bytes_acked.saturating_add(sample.acked);
+        if self.bytes_acked >= self.cwnd {
+            self.bytes_acked -= self.cwnd;
+            self.cwnd = self.cwnd.saturating_add(mss);
+        }
+    }
+
+    fn on_loss(&mut self, event: LossEvent, in_flight: u32, mss: u32, _now: u64) {
+        self.ssthresh = (in_flight / 2).max(2 * mss);
+        self.cwnd = match event {
+            LossEvent::Detected => self.ssthresh,
+            LossEvent::Timeout => mss,
+        };
+        self.bytes_acked = 0;
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn state(&self) -> CongestionState {
+        if self.cwnd < self.ssthresh {
+            CongestionState::SlowStart
+        } else {
+            CongestionState::CongestionAvoidance
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn CongestionControl> {
+        Box::new(self.clone())
+    }
+}
+
+/// CUBIC multiplicative decrease factor, in tenths
+const CUBIC_BETA_TENTHS: u32 = 7;
+
+/// CUBIC (RFC 9438)
+///
+/// After a loss the window follows W(t) = C(t - K)^3 + W_max: it climbs
+/// quickly back toward the window where the loss happened, plateaus around
+/// it and then probes beyond. Growth depends on time since the loss rather
+/// than on the RTT, which keeps long and lossy paths from starving.
+#[derive(Debug, Clone)]
+pub struct Cubic {
+    cwnd: u32,
+    ssthresh: u32,
+    /// Window before the last reduction (W_max)
+    w_max: u32,
+    /// Start of the current congestion avoidance epoch
+    epoch_start: Option<u64>,
+    /// Time for the curve to reach its origin (K), in milliseconds
+    k: u64,
+    /// Window the curve plateaus at in this epoch
+    origin: u32,
+    /// Window a Reno flow would have (W_est)
+    w_est: u32,
+    /// Growth owed to `cwnd`, scaled by `cwnd`
+    cwnd_credit: u64,
+    /// Growth owed to `w_est`, scaled by 17 * `cwnd`
+    est_credit: u64,
+    /// Smallest RTT seen, in milliseconds
+    min_rtt: Option<u64>,
+}
+
+impl Cubic {
+    pub fn new(mss: u32) -> Self {
+        Self {
+            cwnd: initial_window(mss),
+            ssthresh: defaults::INITIAL_SSTHRESH,
+            w_max: 0,
+            epoch_start: None,
+            k: 0,
+            origin: 0,
+            w_est: 0,
+            cwnd_credit: 0,
+            est_credit: 0,
+            min_rtt: None,
+        }
+    }
+
+    /// Window before the last reduction
+    pub fn w_max(&self) -> u32 {
+        self.w_max
+    }
+
+    /// W_cubic(t) for `t` milliseconds into the epoch
+    ///
+    /// With C = 0.4 segments/s^3: W = origin + 4 * (t - K)^3 * mss / 10^10.
+    fn window_at(&self, t: u64, mss: u32) -> u32 {
+        let d = t as i128 - self.k as i128;
+        let w = self.origin as i128 + 4 * d * d * d * mss as i128 / 10_000_000_000;
+        w.clamp(0, u32::MAX as i128) as u32
+    }
+
+    fn start_epoch(&mut self, now: u64, mss: u32) {
+        self.epoch_start = Some(now);
+        if self.cwnd < self.w_max {
+            // K = cbrt((W_max - cwnd) / C) seconds, here in milliseconds
+            let deficit = (self.w_max - self.cwnd) as u128;
+            self.k = cbrt(deficit * 5_000_000_000 / (2 * mss as u128));
+            self.origin = self.w_max;
+        } else {
+            self.k = 0;
+            self.origin = self.cwnd;
+        }
+        self.w_est = self.cwnd;
+        self.cwnd_credit = 0;
+        self.est_credit = 0;
+    }
+}
+
+impl CongestionControl for Cubic {
+    fn algorithm(&self) -> CongestionAlgorithm {
+        CongestionAlgorithm::Cubic
+    }
+
+    fn on_ack(&mut self, sample: &AckSample, mss: u32) {
+        if let Some(rtt) = sample.rtt {
+            self.min_rtt = Some(self.min_rtt.map_or(rtt, |min| min.min(rtt)));
+        }
+        if sample.in_recovery {
+            return;
+        }
+        if self.cwnd < self.ssthresh {
+            self.cwnd = self.cwnd.saturating_add(sample.acked.min(mss));
+            return;
+        }
+
+        if self.epoch_start.is_none() {
+            self.start_epoch(sample.now, mss);
+        }
+        let epoch_start = self.epoch_start.unwrap_or(sample.now);
+        let t = sample.now.saturating_sub(epoch_start) + self.min_rtt.unwrap_or(0);
+        let cwnd = self.cwnd.max(1) as u64;
+
+        // Reno-friendly region: alpha = 3(1 - beta)/(1 + beta) = 9/17 until
+        // W_est passes W_max, then 1
+        let alpha = if self.w_est < self.w_max { 9 } else { 17 };
+        self.est_credit += alpha * sample.acked as u64 * mss as u64;
+        let step = 17 * cwnd;
+        self.w_est = self.w_est.saturating_add((self.est_credit / step) as u32);
+        self.est_credit %= step;
+
+        let w_cubic = self.window_at(t, mss);
+        if w_cubic < self.w_est {
+            self.cwnd = self.cwnd.max(self.w_est);
+            return;
+        }
+
+        // Approach the target one RTT ahead, at most 1.5 * cwnd
+        let target = (w_cubic as u64).clamp(cwnd, cwnd * 3 / 2);
+        self.cwnd_credit += (target - cwnd) * sample.acked as u64;
+        self.cwnd = self.cwnd.saturating_add((self.cwnd_credit / cwnd) as u32);
+        self.cwnd_credit %= cwnd;
+    }
+
+    fn on_loss(&mut self, event: LossEvent, _in_flight: u32, mss: u32, _now: u64) {
+        self.epoch_start = None;
+        // Fast convergence: release bandwidth to newer flows
+        self.w_max = if self.cwnd < self.w_max {
+            (self.cwnd as u64 * (10 + CUBIC_BETA_TENTHS) as u64 / 20) as u32
+        } else {
+            self.cwnd
+        };
+        self.ssthresh = ((self.cwnd as u64 * CUBIC_BETA_TENTHS as u64 / 10) as u32).max(2 * mss);
+        self.cwnd = match event {
+            LossEvent::Detected => self.ssthresh,
+            LossEvent::Timeout => mss,
+        };
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        self.ssthresh
+    }
+
+    fn state(&self) -> CongestionState {
+        if self.cwnd < self.ssthresh {
+            CongestionState::SlowStart
+        } else {
+            CongestionState::CongestionAvoidance
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn CongestionControl> {
+        Box::new(self.clone())
+    }
+}
+
+/// Integer cube root, rounded down
+fn cbrt(value: u128) -> u64 {
+    let (mut lo, mut hi) = (0u64, 1u64 << 42);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if (mid as u128).pow(3) <= value {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    lo
+}
+
+/// Fixed-point unit of BBR gains
+const BBR_UNIT: u64 = 256;
+/// Startup gain, 2/ln(2)
+const BBR_HIGH_GAIN: u64 = 739;
+/// Drain gain, the inverse of the startup gain
+const BBR_DRAIN_GAIN: u64 = 88;
+/// Steady-state cwnd gain
+const BBR_CWND_GAIN: u64 = 2 * BBR_UNIT;
+/// ProbeBW pacing gains: probe up, drain the queue, cruise
+const BBR_PACING_GAINS: [u64; 8] = [320, 192, 256, 256, 256, 256, 256, 256];
+/// Round trips the bandwidth maximum is kept
+const BBR_BW_ROUNDS: u64 = 10;
+/// How long the propagation delay minimum is kept, in milliseconds
+const BBR_MIN_RTT_WINDOW_MS: u64 = 10_000;
+/// Time spent in ProbeRTT, in milliseconds
+const BBR_PROBE_RTT_MS: u64 = 200;
+/// Smallest window, in segments
+const BBR_MIN_CWND_SEGMENTS: u32 = 4;
+/// Startup ends after this many rounds without 25% bandwidth growth
+const BBR_FULL_BW_ROUNDS: u32 = 3;
+
+/// BBR modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BbrMode {
+    /// Exponential search for the bottleneck bandwidth
+    Startup,
+    /// Drain the queue built during startup
+    Drain,
+    /// Cycle the pacing gain around the bandwidth estimate
+    ProbeBw,
+    /// Shrink the window to measure the propagation delay
+    ProbeRtt,
+}
+
+/// BBR
+///
+/// Estimates the bottleneck bandwidth (windowed maximum of delivery rate
+/// samples) and the propagation delay (windowed minimum RTT), paces at the
+/// bandwidth times a gain and caps the data in flight at a multiple of their
+/// product. Random loss does not shrink the model, which is what bulk
+/// transfers over lossy links need.
+#[derive(Debug, Clone)]
+pub struct Bbr {
+    mode: BbrMode,
+    cwnd: u32,
+    /// Window saved on entering recovery or ProbeRTT
+    prior_cwnd: u32,
+    /// Bottleneck bandwidth estimate, bytes per second
+    max_bw: u64,
+    /// Round in which `max_bw` was measured
+    max_bw_round: u64,
+    /// Propagation delay estimate, milliseconds
+    min_rtt: Option<u64>,
+    /// When `min_rtt` was measured
+    min_rtt_stamp: u64,
+    /// Round trips counted
+    round_count: u64,
+    /// Delivered count that ends the current round
+    next_round_delivered: u64,
+    /// Bandwidth at the last startup growth check
+    full_bw: u64,
+    /// Rounds without significant bandwidth growth
+    full_bw_count: u32,
+    /// The bottleneck is judged to be full
+    full_pipe: bool,
+    /// Position in the ProbeBW gain cycle
+    cycle_index: usize,
+    /// When the current gain phase started
+    cycle_stamp: u64,
+    /// When ProbeRTT ends
+    probe_rtt_done: Option<u64>,
+    pacing_gain: u64,
+    cwnd_gain: u64,
+}
+
+impl Bbr {
+    pub fn new(mss: u32) -> Self {
+        Self {
+            mode: BbrMode::Startup,
+            cwnd: initial_window(mss),
+            prior_cwnd: 0,
+            max_bw: 0,
+            max_bw_round: 0,
+            min_rtt: None,
+            min_rtt_stamp: 0,
+            round_count: 0,
+            next_round_delivered: 0,
+            full_bw: 0,
+            full_bw_count: 0,
+            full_pipe: false,
+            cycle_index: 0,
+            cycle_stamp: 0,
+            probe_rtt_done: None,
+            pacing_gain: BBR_HIGH_GAIN,
+            cwnd_gain: BBR_HIGH_GAIN,
+        }
+    }
+
+    /// Current mode
+    pub fn mode(&self) -> BbrMode {
+        self.mode
+    }
+
+    /// Bottleneck bandwidth estimate, bytes per second
+    pub fn bandwidth(&self) -> u64 {
+        self.max_bw
+    }
+
+    /// Propagation delay estimate, milliseconds
+    pub fn min_rtt(&self) -> Option<u64> {
+        self.min_rtt
+    }
+
+    /// Bandwidth-delay product times `gain`, or `None` without a model yet
+    fn bdp(&self, gain: u64) -> Option<u32> {
+        let min_rtt = self.min_rtt?;
+        if self.max_bw == 0 {
+            return None;
+        }
+        // Millisecond clocks read 0 on fast paths; count at least one
+        let bdp = self.max_bw * min_rtt.max(1) / 1000;
+        Some((bdp * gain / BBR_UNIT).min(u32::MAX as u64) as u32)
+    }
+
+    fn set_mode(&mut self, mode: BbrMode, now: u64) {
+        self.mode = mode;
+        let (pacing_gain, cwnd_gain) = match mode {
+            BbrMode::Startup => (BBR_HIGH_GAIN, BBR_HIGH_GAIN),
+            BbrMode::Drain => (BBR_DRAIN_GAIN, BBR_HIGH_GAIN),
+            BbrMode::ProbeBw => {
+                self.cycle_index = 0;
+                self.cycle_stamp = now;
+                (BBR_PACING_GAINS[0], BBR_CWND_GAIN)
+            }
+            BbrMode::ProbeRtt => (BBR_UNIT, BBR_UNIT),
+        };
+        self.pacing_gain = pacing_gain;
+        self.cwnd_gain = cwnd_gain;
+    }
+
+    /// Startup ends once three rounds fail to grow the bandwidth by 25%
+    fn check_full_pipe(&mut self, sample: &AckSample) {
+        if self.full_pipe || sample.app_limited {
+            return;
+        }
+        if self.max_bw >= self.full_bw * 5 / 4 {
+            self.full_bw = self.max_bw;
+            self.full_bw_count = 0;
+            return;
+        }
+        self.full_bw_count += 1;
+        self.full_pipe = self.full_bw_count >= BBR_FULL_BW_ROUNDS;
+    }
+
+    /// Move through the ProbeBW gain cycle once per propagation delay
+    fn advance_cycle(&mut self, now: u64) {
+        let phase = self.min_rtt.unwrap_or(0).max(1);
+        if now.saturating_sub(self.cycle_stamp) >= phase {
+            self.cycle_index = (self.cycle_index + 1) % BBR_PACING_GAINS.len();
+            self.cycle_stamp = now;
+            self.pacing_gain = BBR_PACING_GAINS[self.cycle_index];
+        }
+    }
+}
+
+impl CongestionControl for Bbr {
+    fn algorithm(&self) -> CongestionAlgorithm {
+        CongestionAlgorithm::Bbr
+    }
+
+    fn on_ack(&mut self, sample: &AckSample, mss: u32) {
+        let now = sample.now;
+
+        let round_start = sample.prior_delivered >= self.next_round_delivered;
+        if round_start {
+            self.next_round_delivered = sample.delivered;
+            self.round_count += 1;
+        }
+
+        // Bandwidth: windowed maximum, ignoring app-limited samples that
+        // would only lower it
+        if let Some(rate) = sample.delivery_rate
+            && (!sample.app_limited || rate >= self.max_bw)
+            && (rate >= self.max_bw || self.round_count - self.max_bw_round > BBR_BW_ROUNDS)
+        {
+            self.max_bw = rate;
+            self.max_bw_round = self.round_count;
+        }
+
+        // Propagation delay: windowed minimum
+        let rtt_expired =
+            self.min_rtt.is_some() && now.saturating_sub(self.min_rtt_stamp) > BBR_MIN_RTT_WINDOW_MS;
+        if let Some(rtt) = sample.rtt
+            && (self.min_rtt.is_none_or(|min| rtt <= min) || rtt_expired)
+        {
+            self.min_rtt = Some(rtt);
+            self.min_rtt_stamp = now;
+        }
+
+        if round_start {
+            self.check_full_pipe(sample);
+        }
+
+        match self.mode {
+            BbrMode::Startup if self.full_pipe => self.set_mode(BbrMode::Drain, now),
+            BbrMode::ProbeBw => self.advance_cycle(now),
+            _ => {}
+        }
+        if self.mode == BbrMode::Drain && self.bdp(BBR_UNIT).is_none_or(|bdp| sample.in_flight <= bdp) {
+            self.set_mode(BbrMode::ProbeBw, now);
+        }
+
+        if rtt_expired && self.mode != BbrMode::ProbeRtt {
+            self.prior_cwnd = self.cwnd;
+            self.set_mode(BbrMode::ProbeRtt, now);
+            self.probe_rtt_done = Some(now + BBR_PROBE_RTT_MS.max(self.min_rtt.unwrap_or(0)));
+        }
+        if self.mode == BbrMode::ProbeRtt && self.probe_rtt_done.is_some_and(|done| now >= done) {
+            self.probe_rtt_done = None;
+            self.min_rtt_stamp = now;
+            self.cwnd = self.cwnd.max(self.prior_cwnd);
+            let mode = if self.full_pipe { BbrMode::ProbeBw } else { BbrMode::Startup };
+            self.set_mode(mode, now);
+        }
+
+        let min_cwnd = BBR_MIN_CWND_SEGMENTS * mss;
+        if self.mode == BbrMode::ProbeRtt {
+            self.cwnd = self.cwnd.min(min_cwnd);
+            return;
+        }
+        // Leave room for three segments of ACK aggregation
+        let target = self.bdp(self.cwnd_gain).map(|bdp| bdp.saturating_add(3 * mss));
+        if sample.in_recovery {
+            // Packet conservation: send one segment per segment delivered
+            self.cwnd = self.cwnd.max(sample.in_flight.saturating_add(sample.acked));
+        } else if let Some(target) = target
+            && self.full_pipe
+        {
+            self.cwnd = self.cwnd.saturating_add(sample.acked).min(target);
+        } else if target.is_none_or(|target| self.cwnd < target) {
+            self.cwnd = self.cwnd.saturating_add(sample.acked);
+        }
+        self.cwnd = self.cwnd.max(min_cwnd);
+    }
+
+    fn on_loss(&mut self, event: LossEvent, in_flight: u32, mss: u32, _now: u64) {
+        // The model is kept; only the window falls back to what is in flight
+        self.prior_cwnd = self.prior_cwnd.max(self.cwnd);
+        self.cwnd = match event {
+            LossEvent::Detected => in_flight.max(BBR_MIN_CWND_SEGMENTS * mss),
+            LossEvent::Timeout => mss,
+        };
+    }
+
+    fn on_recovery_end(&mut self, _mss: u32) {
+        self.cwnd = self.cwnd.max(self.prior_cwnd);
+        self.prior_cwnd = 0;
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd
+    }
+
+    fn ssthresh(&self) -> u32 {
+        defaults::INITIAL_SSTHRESH
+    }
+
+    fn state(&self) -> CongestionState {
+        if self.mode == BbrMode::Startup {
+            CongestionState::SlowStart
+        } else {
+            CongestionState::CongestionAvoidance
+        }
+    }
+
+    fn pacing_rate(&self) -> Option<u64> {
+        if self.max_bw == 0 {
+            return None;
+        }
+        Some(self.max_bw * self.pacing_gain / BBR_UNIT)
+    }
+
+    fn clone_box(&self) -> Box<dyn CongestionControl> {
+        Box::new(self.clone())
+    }
+}
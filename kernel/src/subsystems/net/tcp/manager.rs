@@ -1,17 +1,20 @@
 //! TCP connection manager
 //!
 //! This module provides management for multiple TCP connections, including
-//! connection tracking, socket allocation, and connection lifecycle management.
+//! connection tracking, port bindings, listen queues and connection lifecycle
+//! management. The socket layer talks to the global manager through
+//! [`with_tcp_manager`].
 
 extern crate alloc;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::vec::Vec;
-use alloc::vec;
 use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, Ordering};
 
-use super::TcpState;
-use super::state::{TcpStateMachine, TcpAction};
-use crate::net::ipv4::Ipv4Addr;
+use super::{tcp_flags, TcpPacket, TcpState};
+use super::congestion::{default_algorithm, CongestionAlgorithm};
+use super::state::{reset_for, TcpAction, TcpSegment, TcpStateMachine};
+use crate::net::ipv6::IpAddr;
+use crate::subsystems::sync::Mutex;
 
 /// Port bitmap allocator for efficient port management
 ///
@@ -56,10 +59,10 @@ impl PortBitmap {
     }
 
     /// Allocate a specific port
+    ///
+    /// Any port can be claimed here; only ephemeral allocation skips the
+    /// well-known range.
     pub fn allocate_specific(&self, port: u16) -> bool {
-        if port < 1024 {
-            return false; // Don't allocate well-known ports
-        }
         self.try_set_bit(port)
     }
 
@@ -112,7 +115,7 @@ impl PortBitmap {
 
     /// Get free port count
     pub fn count_free(&self) -> u32 {
-        (65536 - 1024) - self.count_allocated()
+        (65536 - 1024u32).saturating_sub(self.count_allocated())
     }
 }
 
@@ -120,11 +123,11 @@ impl PortBitmap {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ConnectionId {
     /// Local IP address
-    pub local_ip: Ipv4Addr,
+    pub local_ip: IpAddr,
     /// Local port
     pub local_port: u16,
     /// Remote IP address
-    pub remote_ip: Ipv4Addr,
+    pub remote_ip: IpAddr,
     /// Remote port
     pub remote_port: u16,
 }
@@ -132,9 +135,9 @@ pub struct ConnectionId {
 impl ConnectionId {
     /// Create a new connection ID
     pub fn new(
-        local_ip: Ipv4Addr,
+        local_ip: IpAddr,
         local_port: u16,
-        remote_ip: Ipv4Addr,
+        remote_ip: IpAddr,
         remote_port: u16,
     ) -> Self {
         Self {
@@ -147,20 +150,23 @@ impl ConnectionId {
 
     /// Check if this is a server connection (listening)
     pub fn is_server(&self) -> bool {
-        self.remote_ip == Ipv4Addr::UNSPECIFIED || self.remote_port == 0
+        self.remote_ip.is_unspecified() || self.remote_port == 0
     }
 
     /// Create a wildcard connection ID (for listening sockets)
-    pub fn wildcard(local_ip: Ipv4Addr, local_port: u16) -> Self {
+    pub fn wildcard(local_ip: IpAddr, local_port: u16) -> Self {
         Self {
             local_ip,
             local_port,
-            remote_ip: Ipv4Addr::UNSPECIFIED,
+            remote_ip: local_ip.unspecified_like(),
             remote_port: 0,
         }
     }
 }
 
+/// Handle naming a socket in the connection manager
+pub type SocketHandle = u32;
+
 /// TCP connection
 #[derive(Debug, Clone)]
 pub struct TcpConnection {
@@ -172,12 +178,11 @@ pub struct TcpConnection {
     pub options: TcpOptions,
     /// Statistics
     pub stats: TcpConnectionStats,
-    /// Receive buffer
-    pub recv_buffer: Vec<u8>,
-    /// Send buffer
-    pub send_buffer: Vec<u8>,
-    /// Pending actions
-    pub pending_actions: Vec<TcpAction>,
+    /// Socket owning the connection; `None` once the application closed it
+    /// or while it waits in a listener's queues
+    pub owner: Option<SocketHandle>,
+    /// Listener whose queues hold the connection until it is accepted
+    pub listener: Option<SocketHandle>,
 }
 
 /// TCP connection options
@@ -197,10 +202,14 @@ pub struct TcpOptions {
     pub reuse_addr: bool,
     /// Reuse port
     pub reuse_port: bool,
+    /// IPv6 socket refuses IPv4-mapped traffic
+    pub v6only: bool,
     /// Receive buffer size
     pub recv_buf_size: u32,
     /// Send buffer size
     pub send_buf_size: u32,
+    /// Congestion control algorithm (TCP_CONGESTION)
+    pub congestion: CongestionAlgorithm,
 }
 
 impl Default for TcpOptions {
@@ -213,8 +222,10 @@ impl Default for TcpOptions {
             nagle_enabled: true,
             reuse_addr: false,
             reuse_port: false,
+            v6only: false,
             recv_buf_size: 8192,
             send_buf_size: 8192,
+            congestion: default_algorithm(),
         }
     }
 }
@@ -240,89 +251,61 @@ pub struct TcpConnectionStats {
 
 impl TcpConnection {
     /// Create a new TCP connection
-    pub fn new(id: ConnectionId, options: TcpOptions, is_passive: bool) -> Self {
+    pub fn new(id: ConnectionId, options: TcpOptions, now: u64) -> Self {
+        let mut state_machine = TcpStateMachine::new(
+            mss_for(id.remote_ip),
+            options.send_buf_size as usize,
+            options.recv_buf_size as usize,
+            now,
+        );
+        state_machine.set_nagle(options.nagle_enabled);
+        state_machine.set_congestion_algorithm(options.congestion);
+
         Self {
             id,
-            state_machine: TcpStateMachine::new(is_passive),
+            state_machine,
             options,
             stats: TcpConnectionStats::default(),
-            recv_buffer: Vec::new(),
-            send_buffer: Vec::new(),
-            pending_actions: Vec::new(),
+            owner: None,
+            listener: None,
         }
     }
 
-    /// Process incoming packet
-    pub fn process_packet(&mut self, packet: &[u8]) -> Result<Vec<TcpAction>, TcpError> {
-        use super::TcpPacket;
-        // PacketError在当前作用域中未使用，暂时注释掉
-        // use crate::net::packet::PacketError;
-
-        let tcp_packet = TcpPacket::from_bytes(packet)
-            .map_err(|_| TcpError::InvalidPacket)?;
-
-        let action = self.state_machine.process_packet(&tcp_packet);
-
-        // Update statistics
-        self.stats.packets_rx += 1;
-        self.stats.bytes_rx += tcp_packet.payload.len() as u64;
-
-        // Handle action
-        match &action {
-            TcpAction::DataReceived(data) => {
-                self.recv_buffer.extend_from_slice(&data);
-                Ok(vec![action])
-            }
-            TcpAction::ConnectionEstablished => {
-                self.stats.establishment_time = Some(get_current_time());
-                Ok(vec![action])
-            }
-            _ => Ok(vec![action]),
+    /// Queue data for sending; returns the bytes accepted and the actions to run
+    pub fn send_data(&mut self, data: &[u8], now: u64) -> Result<(usize, Vec<TcpAction>), TcpError> {
+        if !self.state_machine.can_send() {
+            return Err(match self.state() {
+                TcpState::SynSent | TcpState::SynReceived => TcpError::WouldBlock,
+                TcpState::Listen => TcpError::NotConnected,
+                _ => TcpError::Shutdown,
+            });
         }
-    }
-
-    /// Send data
-    pub fn send_data(&mut self, data: &[u8]) -> Result<(), TcpError> {
-        if !self.state_machine.is_established() {
-            return Err(TcpError::NotConnected);
+        if data.is_empty() {
+            return Ok((0, Vec::new()));
         }
 
-        if self.send_buffer.len() + data.len() > self.options.send_buf_size as usize {
-            return Err(TcpError::BufferFull);
+        let (sent, actions) = self.state_machine.send(data, now);
+        if sent == 0 {
+            return Err(TcpError::WouldBlock);
         }
-
-        self.send_buffer.extend_from_slice(data);
-        Ok(())
+        self.stats.bytes_tx += sent as u64;
+        Ok((sent, actions))
     }
 
-    /// Receive data
-    pub fn receive_data(&mut self, buf: &mut [u8]) -> Result<usize, TcpError> {
-        if self.recv_buffer.is_empty() {
-            return Ok(0);
+    /// Receive data; `Ok(0)` means end of stream
+    pub fn receive_data(&mut self, buf: &mut [u8], now: u64) -> Result<(usize, Vec<TcpAction>), TcpError> {
+        if self.state_machine.available() == 0 {
+            if self.state_machine.peer_closed() || self.is_closed() || buf.is_empty() {
+                return Ok((0, Vec::new()));
+            }
+            return Err(TcpError::WouldBlock);
         }
-
-        let copy_len = core::cmp::min(buf.len(), self.recv_buffer.len());
-        buf[..copy_len].copy_from_slice(&self.recv_buffer[..copy_len]);
-        self.recv_buffer.drain(..copy_len);
-
-        Ok(copy_len)
-    }
-
-    /// Get pending actions
-    pub fn get_pending_actions(&mut self) -> Vec<TcpAction> {
-        let actions = self.pending_actions.clone();
-        self.pending_actions.clear();
-        actions
-    }
-
-    /// Add pending action
-    pub fn add_pending_action(&mut self, action: TcpAction) {
-        self.pending_actions.push(action);
+        Ok(self.state_machine.read(buf, now))
     }
 
     /// Check timeouts
-    pub fn check_timeouts(&mut self) -> Vec<TcpAction> {
-        self.state_machine.check_timeouts()
+    pub fn check_timeouts(&mut self, now: u64) -> Vec<TcpAction> {
+        self.state_machine.check_timeouts(now)
     }
 
     /// Get connection state
@@ -346,16 +329,129 @@ impl TcpConnection {
     }
 }
 
+/// MSS announced on a path of the given family
+///
+/// Keeps every segment within a 1500-byte IP packet.
+fn mss_for(remote_ip: IpAddr) -> u16 {
+    if remote_ip.is_ipv4() {
+        1460
+    } else {
+        1440
+    }
+}
+
+/// Listening socket queues
+#[derive(Debug, Clone)]
+pub struct TcpListener {
+    /// Wildcard ID the listener is registered under
+    pub id: ConnectionId,
+    /// Maximum handshakes plus connections waiting for accept()
+    pub backlog: usize,
+    /// Handshakes in progress
+    pub syn_queue: Vec<ConnectionId>,
+    /// Established connections waiting for accept()
+    pub accept_queue: VecDeque<ConnectionId>,
+}
+
+/// What a socket is doing
+#[derive(Debug, Clone)]
+enum SocketRole {
+    /// Created and possibly bound
+    Idle,
+    /// Accepting connections
+    Listening(TcpListener),
+    /// Owns a connection, from the first SYN until close
+    Connected(ConnectionId),
+}
+
+/// Manager-side state of one socket
+#[derive(Debug, Clone)]
+struct SocketRecord {
+    /// Options at the last bind, listen or connect
+    options: TcpOptions,
+    /// Bound local address
+    local: Option<(IpAddr, u16)>,
+    /// Current role
+    role: SocketRole,
+    /// Error to report on the next call (SO_ERROR)
+    error: Option<TcpError>,
+    /// Reading was shut down
+    read_shutdown: bool,
+}
+
+/// Local address claimed by a socket
+#[derive(Debug, Clone, Copy)]
+struct PortBinding {
+    /// Owning socket
+    handle: SocketHandle,
+    /// Bound address, possibly a wildcard
+    ip: IpAddr,
+    /// SO_REUSEADDR was set at bind time
+    reuse_addr: bool,
+    /// IPV6_V6ONLY was set at bind time
+    v6only: bool,
+}
+
+/// Readiness of a socket for poll and epoll
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpReadiness {
+    /// Data, end of stream or a connection to accept is available
+    pub readable: bool,
+    /// Data can be queued for sending
+    pub writable: bool,
+    /// The connection is gone
+    pub hangup: bool,
+    /// An error is pending
+    pub error: bool,
+}
+
+/// Segment ready for the IP layer
+#[derive(Debug, Clone)]
+pub struct TcpOutput {
+    /// Source address
+    pub source: IpAddr,
+    /// Destination address
+    pub dest: IpAddr,
+    /// Encoded segment with its checksum
+    pub segment: Vec<u8>,
+}
+
+/// Whether two local bindings claim overlapping addresses
+///
+/// A wildcard overlaps everything of its family, and an IPv6 wildcard also
+/// covers IPv4 unless it is v6only.
+fn bindings_overlap(a: IpAddr, a_v6only: bool, b: IpAddr, b_v6only: bool) -> bool {
+    match (a.is_ipv4(), b.is_ipv4()) {
+        (true, true) | (false, false) => a.is_unspecified() || b.is_unspecified() || a == b,
+        (false, true) => a.is_unspecified() && !a_v6only,
+        (true, false) => b.is_unspecified() && !b_v6only,
+    }
+}
+
 /// TCP connection manager
+///
+/// Owns every connection and every TCP socket's protocol state. Sockets are
+/// named by handles so that clones of a socket share one record. Incoming
+/// segments enter through [`TcpConnectionManager::input`] and outgoing ones
+/// collect in an outbox that the network stack drains.
 pub struct TcpConnectionManager {
-    /// Active connections
+    /// Connections by 4-tuple, including ones closed by the application
+    /// that are still shutting down
     connections: BTreeMap<ConnectionId, TcpConnection>,
-    /// Listening sockets
-    listening_sockets: BTreeMap<ConnectionId, TcpConnection>,
+    /// Listening sockets by wildcard ID
+    listening_sockets: BTreeMap<ConnectionId, SocketHandle>,
+    /// Sockets by handle
+    sockets: BTreeMap<SocketHandle, SocketRecord>,
+    /// Local bindings by port
+    bindings: BTreeMap<u16, Vec<PortBinding>>,
     /// Port bitmap allocator (O(1) allocation/deallocation)
     port_bitmap: PortBitmap,
-    /// Connection ID counter
-    next_connection_id: AtomicU32,
+    /// Socket handle counter
+    next_handle: AtomicU32,
+    /// Segments waiting to be sent
+    outbox: Vec<TcpOutput>,
+    /// Something happened that sleepers may wait for
+    wake: bool,
 }
 
 impl TcpConnectionManager {
@@ -364,8 +460,12 @@ impl TcpConnectionManager {
         Self {
             connections: BTreeMap::new(),
             listening_sockets: BTreeMap::new(),
+            sockets: BTreeMap::new(),
+            bindings: BTreeMap::new(),
             port_bitmap: PortBitmap::new(),
-            next_connection_id: AtomicU32::new(1),
+            next_handle: AtomicU32::new(1),
+            outbox: Vec::new(),
+            wake: false,
         }
     }
 
@@ -380,121 +480,637 @@ impl TcpConnectionManager {
         self.port_bitmap.deallocate(port);
     }
 
-    /// Create a listening socket
-    pub fn listen(
+    /// Create a socket
+    pub fn open(&mut self, options: TcpOptions) -> SocketHandle {
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        self.sockets.insert(handle, SocketRecord {
+            options,
+            local: None,
+            role: SocketRole::Idle,
+            error: None,
+            read_shutdown: false,
+        });
+        handle
+    }
+
+    /// Bind a socket to a local address; port 0 picks an ephemeral port
+    ///
+    /// Returns the bound port. Overlapping bindings are refused unless both
+    /// sockets set SO_REUSEADDR and neither listens; a connection still
+    /// using the port (such as one in TIME-WAIT) only blocks sockets
+    /// without SO_REUSEADDR.
+    pub fn bind(
         &mut self,
-        local_ip: Ipv4Addr,
+        handle: SocketHandle,
+        local_ip: IpAddr,
         local_port: u16,
         options: TcpOptions,
-    ) -> Result<ConnectionId, TcpError> {
+    ) -> Result<u16, TcpError> {
+        let record = self.sockets.get(&handle).ok_or(TcpError::InvalidConnection)?;
+        if record.local.is_some() || !matches!(record.role, SocketRole::Idle) {
+            return Err(TcpError::InvalidConnection);
+        }
+
+        let local_ip = local_ip.to_canonical();
         let port = if local_port == 0 {
-            self.allocate_port()?
+            self.ephemeral_port()?
         } else {
-            if self.port_bitmap.is_allocated(local_port) {
+            self.check_bind(handle, local_ip, local_port, &options)?;
+            local_port
+        };
+
+        self.add_binding(handle, local_ip, port, &options);
+        if let Some(record) = self.sockets.get_mut(&handle) {
+            record.local = Some((local_ip, port));
+            record.options = options;
+        }
+        Ok(port)
+    }
+
+    /// Check a bind against the bindings and connections on the port
+    fn check_bind(
+        &self,
+        handle: SocketHandle,
+        ip: IpAddr,
+        port: u16,
+        options: &TcpOptions,
+    ) -> Result<(), TcpError> {
+        for binding in self.bindings.get(&port).into_iter().flatten() {
+            if binding.handle == handle
+                || !bindings_overlap(binding.ip, binding.v6only, ip, options.v6only)
+            {
+                continue;
+            }
+            let listening = self.sockets.get(&binding.handle)
+                .is_some_and(|record| matches!(record.role, SocketRole::Listening(_)));
+            if !(options.reuse_addr && binding.reuse_addr && !listening) {
                 return Err(TcpError::PortInUse);
             }
-            if !self.port_bitmap.allocate_specific(local_port) {
+        }
+
+        if !options.reuse_addr {
+            let in_use = self.connections.values().any(|conn| {
+                conn.id.local_port == port
+                    && conn.owner != Some(handle)
+                    && bindings_overlap(conn.id.local_ip, false, ip, options.v6only)
+            });
+            if in_use {
                 return Err(TcpError::PortInUse);
             }
-            local_port
-        };
+        }
+        Ok(())
+    }
 
-        let conn_id = ConnectionId::wildcard(local_ip, port);
-        let mut connection = TcpConnection::new(conn_id, options.clone(), true);
+    /// Pick an ephemeral port no binding or connection uses
+    fn ephemeral_port(&mut self) -> Result<u16, TcpError> {
+        for _ in 0..self.port_bitmap.count_free() {
+            let port = self.allocate_port()?;
+            // add_binding claims it again
+            self.deallocate_port(port);
+            if !self.connections.keys().any(|id| id.local_port == port) {
+                return Ok(port);
+            }
+        }
+        Err(TcpError::NoPortsAvailable)
+    }
 
-        // Start listening
-        let action = connection.state_machine.passive_open();
-        connection.add_pending_action(action);
+    /// Record a binding and claim its port
+    fn add_binding(&mut self, handle: SocketHandle, ip: IpAddr, port: u16, options: &TcpOptions) {
+        self.port_bitmap.allocate_specific(port);
+        self.bindings.entry(port).or_default().push(PortBinding {
+            handle,
+            ip,
+            reuse_addr: options.reuse_addr,
+            v6only: options.v6only,
+        });
+    }
 
-        self.listening_sockets.insert(conn_id, connection);
-        Ok(conn_id)
+    /// Drop a socket's binding, freeing the port when it was the last one
+    fn remove_binding(&mut self, handle: SocketHandle, port: u16) {
+        if let Some(list) = self.bindings.get_mut(&port) {
+            list.retain(|binding| binding.handle != handle);
+            if list.is_empty() {
+                self.bindings.remove(&port);
+                self.deallocate_port(port);
+            }
+        }
     }
 
-    /// Connect to a remote host
+    /// Start listening on a bound socket
+    pub fn listen(&mut self, handle: SocketHandle, backlog: usize) -> Result<ConnectionId, TcpError> {
+        let record = self.sockets.get(&handle).ok_or(TcpError::InvalidConnection)?;
+        match &record.role {
+            SocketRole::Idle => {}
+            SocketRole::Listening(listener) => {
+                let id = listener.id;
+                if let Some(SocketRecord { role: SocketRole::Listening(listener), .. }) =
+                    self.sockets.get_mut(&handle)
+                {
+                    listener.backlog = backlog.max(1);
+                }
+                return Ok(id);
+            }
+            SocketRole::Connected(_) => return Err(TcpError::InvalidConnection),
+        }
+        let (ip, port) = record.local.ok_or(TcpError::InvalidConnection)?;
+        let v6only = record.options.v6only;
+
+        // Sockets sharing the port through SO_REUSEADDR cannot both listen
+        let conflict = self.bindings.get(&port).into_iter().flatten().any(|binding| {
+            binding.handle != handle
+                && bindings_overlap(binding.ip, binding.v6only, ip, v6only)
+                && self.sockets.get(&binding.handle)
+                    .is_some_and(|record| matches!(record.role, SocketRole::Listening(_)))
+        });
+        if conflict {
+            return Err(TcpError::PortInUse);
+        }
+
+        let id = ConnectionId::wildcard(ip, port);
+        self.listening_sockets.insert(id, handle);
+        if let Some(record) = self.sockets.get_mut(&handle) {
+            record.role = SocketRole::Listening(TcpListener {
+                id,
+                backlog: backlog.max(1),
+                syn_queue: Vec::new(),
+                accept_queue: VecDeque::new(),
+            });
+        }
+        Ok(id)
+    }
+
+    /// Start connecting a socket to `remote_ip:remote_port`
+    ///
+    /// `source` is the address to use when the socket is unbound or bound
+    /// to a wildcard. The SYN is queued in the outbox; completion is
+    /// reported by [`TcpConnectionManager::connect_status`].
     pub fn connect(
         &mut self,
-        local_ip: Ipv4Addr,
-        remote_ip: Ipv4Addr,
+        handle: SocketHandle,
+        source: IpAddr,
+        remote_ip: IpAddr,
         remote_port: u16,
         options: TcpOptions,
+        now: u64,
     ) -> Result<ConnectionId, TcpError> {
-        let local_port = self.allocate_port()?;
-        let conn_id = ConnectionId::new(local_ip, local_port, remote_ip, remote_port);
+        let record = self.sockets.get(&handle).ok_or(TcpError::InvalidConnection)?;
+        let bound = record.local;
+        match record.role {
+            SocketRole::Idle => {}
+            SocketRole::Listening(_) => return Err(TcpError::InvalidConnection),
+            SocketRole::Connected(id) => {
+                return Err(match self.connections.get(&id).map(|conn| conn.state()) {
+                    Some(TcpState::SynSent) => TcpError::InProgress,
+                    _ => TcpError::AlreadyConnected,
+                });
+            }
+        }
+        if remote_port == 0 || remote_ip.is_unspecified() {
+            return Err(TcpError::InvalidConnection);
+        }
 
-        let mut connection = TcpConnection::new(conn_id, options.clone(), false);
+        let remote_ip = remote_ip.to_canonical();
+        let (local_ip, local_port) = match bound {
+            Some((ip, port)) if ip.is_unspecified() => (source.to_canonical(), port),
+            Some(local) => local,
+            None => {
+                let port = self.ephemeral_port()?;
+                let source = source.to_canonical();
+                self.add_binding(handle, source, port, &options);
+                if let Some(record) = self.sockets.get_mut(&handle) {
+                    record.local = Some((source, port));
+                }
+                (source, port)
+            }
+        };
 
-        // Start connection
-        let action = connection.state_machine.active_open(remote_ip, remote_port);
-        connection.add_pending_action(action);
+        let id = ConnectionId::new(local_ip, local_port, remote_ip, remote_port);
+        if self.connections.contains_key(&id) {
+            return Err(TcpError::PortInUse);
+        }
 
-        self.connections.insert(conn_id, connection);
-        Ok(conn_id)
+        let mut connection = TcpConnection::new(id, options.clone(), now);
+        connection.owner = Some(handle);
+        let actions = connection.state_machine.connect(now);
+        self.connections.insert(id, connection);
+        if let Some(record) = self.sockets.get_mut(&handle) {
+            record.role = SocketRole::Connected(id);
+            record.options = options;
+            record.error = None;
+        }
+        self.apply(id, actions, now);
+        Ok(id)
     }
 
-    /// Accept a new connection
-    pub fn accept(&mut self, listening_id: ConnectionId) -> Result<Option<ConnectionId>, TcpError> {
-        let listening_socket = self.listening_sockets.get_mut(&listening_id)
-            .ok_or(TcpError::InvalidConnection)?;
+    /// Progress of a connect: `Ok(true)` once established, `Ok(false)` while
+    /// the handshake runs, or the error that ended it
+    pub fn connect_status(&mut self, handle: SocketHandle) -> Result<bool, TcpError> {
+        let record = self.sockets.get_mut(&handle).ok_or(TcpError::InvalidConnection)?;
+        if let Some(error) = record.error.take() {
+            return Err(error);
+        }
+        let SocketRole::Connected(id) = record.role else {
+            return Err(TcpError::NotConnected);
+        };
+        match self.connections.get(&id).map(|conn| conn.state()) {
+            Some(TcpState::SynSent) | Some(TcpState::SynReceived) => Ok(false),
+            Some(TcpState::Closed) | None => Err(TcpError::NotConnected),
+            Some(_) => Ok(true),
+        }
+    }
 
-        // Check for pending connections (simplified implementation)
-        // In a real implementation, this would use a pending connections queue
-        let mut has_new_connection = false;
-        for action in listening_socket.get_pending_actions() {
-            match action {
-                TcpAction::ConnectionEstablished => {
-                    has_new_connection = true;
-                    break;
+    /// Take the next established connection from a listener
+    ///
+    /// Returns the new socket's handle and the connection, or `None` when
+    /// the accept queue is empty.
+    pub fn accept(&mut self, handle: SocketHandle) -> Result<Option<(SocketHandle, ConnectionId)>, TcpError> {
+        let record = self.sockets.get_mut(&handle).ok_or(TcpError::InvalidConnection)?;
+        let options = record.options.clone();
+        let SocketRole::Listening(listener) = &mut record.role else {
+            return Err(TcpError::InvalidConnection);
+        };
+        let Some(id) = listener.accept_queue.pop_front() else {
+            return Ok(None);
+        };
+
+        let child = self.open(options);
+        if let Some(record) = self.sockets.get_mut(&child) {
+            record.local = Some((id.local_ip, id.local_port));
+            record.role = SocketRole::Connected(id);
+        }
+        if let Some(connection) = self.connections.get_mut(&id) {
+            connection.owner = Some(child);
+            connection.listener = None;
+        }
+        Ok(Some((child, id)))
+    }
+
+    /// Queue data on a connected socket; returns the bytes accepted
+    pub fn send(&mut self, handle: SocketHandle, data: &[u8], now: u64) -> Result<usize, TcpError> {
+        let id = self.connection_of(handle)?;
+        let connection = self.connections.get_mut(&id).ok_or(TcpError::Shutdown)?;
+        let (sent, actions) = connection.send_data(data, now)?;
+        self.apply(id, actions, now);
+        Ok(sent)
+    }
+
+    /// Read from a connected socket; `Ok(0)` means end of stream
+    pub fn recv(&mut self, handle: SocketHandle, buf: &mut [u8], now: u64) -> Result<usize, TcpError> {
+        let record = self.sockets.get_mut(&handle).ok_or(TcpError::InvalidConnection)?;
+        if record.read_shutdown {
+            return Ok(0);
+        }
+        let SocketRole::Connected(id) = record.role else {
+            return Err(TcpError::NotConnected);
+        };
+        let Some(connection) = self.connections.get_mut(&id) else {
+            return Ok(0);
+        };
+        if connection.state_machine.available() == 0
+            && let Some(error) = record.error.take()
+        {
+            return Err(error);
+        }
+        let (received, actions) = connection.receive_data(buf, now)?;
+        self.apply(id, actions, now);
+        Ok(received)
+    }
+
+    /// Shut down reading, writing or both on a connected socket
+    pub fn shutdown(&mut self, handle: SocketHandle, read: bool, write: bool, now: u64) -> Result<(), TcpError> {
+        let id = self.connection_of(handle)?;
+        if read && let Some(record) = self.sockets.get_mut(&handle) {
+            record.read_shutdown = true;
+        }
+        if write && let Some(connection) = self.connections.get_mut(&id) {
+            let actions = connection.state_machine.close(now);
+            self.apply(id, actions, now);
+        }
+        self.wake = true;
+        Ok(())
+    }
+
+    /// Close a socket
+    ///
+    /// A listener resets its unaccepted connections. A connection keeps
+    /// running without its socket until the FIN handshake and TIME-WAIT are
+    /// over; unread data makes it reset the peer instead (RFC 2525).
+    pub fn close(&mut self, handle: SocketHandle, now: u64) {
+        let Some(record) = self.sockets.remove(&handle) else {
+            return;
+        };
+        if let Some((_, port)) = record.local {
+            self.remove_binding(handle, port);
+        }
+
+        match record.role {
+            SocketRole::Idle => {}
+            SocketRole::Listening(listener) => {
+                self.listening_sockets.remove(&listener.id);
+                for id in listener.syn_queue.into_iter().chain(listener.accept_queue) {
+                    if let Some(connection) = self.connections.get_mut(&id) {
+                        connection.listener = None;
+                        let actions = connection.state_machine.abort();
+                        self.apply(id, actions, now);
+                    }
+                }
+            }
+            SocketRole::Connected(id) => {
+                if let Some(connection) = self.connections.get_mut(&id) {
+                    connection.owner = None;
+                    let actions = if connection.state_machine.available() > 0 {
+                        connection.state_machine.abort()
+                    } else {
+                        connection.state_machine.close(now)
+                    };
+                    self.apply(id, actions, now);
                 }
-                _ => {}
             }
         }
+        self.wake = true;
+    }
 
-        if has_new_connection {
-            // Create new connection for accepted socket
-            // This is simplified - would need actual remote address from SYN
-            let new_conn_id = ConnectionId::new(
-                listening_socket.id.local_ip,
-                listening_socket.id.local_port,
-                Ipv4Addr::UNSPECIFIED, // Would come from actual SYN
-                0, // Would come from actual SYN
-            );
+    /// Readiness of a socket
+    pub fn poll(&self, handle: SocketHandle) -> TcpReadiness {
+        let mut readiness = TcpReadiness::default();
+        let Some(record) = self.sockets.get(&handle) else {
+            readiness.hangup = true;
+            return readiness;
+        };
+        readiness.error = record.error.is_some();
 
-            let new_connection = TcpConnection::new(new_conn_id, listening_socket.options.clone(), true);
-            self.connections.insert(new_conn_id, new_connection);
+        match &record.role {
+            SocketRole::Idle => readiness.hangup = true,
+            SocketRole::Listening(listener) => {
+                readiness.readable = !listener.accept_queue.is_empty();
+            }
+            SocketRole::Connected(id) => match self.connections.get(id) {
+                Some(connection) => {
+                    let machine = &connection.state_machine;
+                    readiness.readable = machine.available() > 0
+                        || machine.peer_closed()
+                        || machine.is_closed()
+                        || record.read_shutdown
+                        || readiness.error;
+                    readiness.writable = machine.can_send() && machine.send_space() > 0;
+                    readiness.hangup = machine.is_closed()
+                        || (machine.peer_closed() && !machine.can_send());
+                }
+                None => {
+                    readiness.readable = true;
+                    readiness.hangup = true;
+                }
+            },
+        }
+        readiness
+    }
 
-            Ok(Some(new_conn_id))
-        } else {
-            Ok(None)
+    /// Local address of a socket
+    pub fn local_addr(&self, handle: SocketHandle) -> Option<(IpAddr, u16)> {
+        let record = self.sockets.get(&handle)?;
+        match record.role {
+            SocketRole::Connected(id) => Some((id.local_ip, id.local_port)),
+            _ => record.local,
         }
     }
 
-    /// Close a connection
-    pub fn close(&mut self, conn_id: ConnectionId) -> Result<(), TcpError> {
-        if let Some(mut connection) = self.connections.remove(&conn_id) {
-            let action = connection.state_machine.close();
-            connection.add_pending_action(action);
+    /// Remote address of a connected socket
+    pub fn peer_addr(&self, handle: SocketHandle) -> Option<(IpAddr, u16)> {
+        match self.sockets.get(&handle)?.role {
+            SocketRole::Connected(id) => Some((id.remote_ip, id.remote_port)),
+            _ => None,
+        }
+    }
+
+    /// State of the connection a socket owns
+    pub fn socket_state(&self, handle: SocketHandle) -> TcpState {
+        match self.sockets.get(&handle).map(|record| &record.role) {
+            Some(SocketRole::Listening(_)) => TcpState::Listen,
+            Some(SocketRole::Connected(id)) => {
+                self.connections.get(id).map(|conn| conn.state()).unwrap_or(TcpState::Closed)
+            }
+            _ => TcpState::Closed,
+        }
+    }
+
+    /// Change the congestion control algorithm of a socket
+    ///
+    /// A connected socket switches at once; otherwise the choice applies to
+    /// the connection it makes or, for a listener, the ones it accepts.
+    pub fn set_congestion(&mut self, handle: SocketHandle, algorithm: CongestionAlgorithm) -> Result<(), TcpError> {
+        let record = self.sockets.get_mut(&handle).ok_or(TcpError::InvalidConnection)?;
+        record.options.congestion = algorithm;
+        if let SocketRole::Connected(id) = record.role
+            && let Some(connection) = self.connections.get_mut(&id)
+        {
+            connection.options.congestion = algorithm;
+            connection.state_machine.set_congestion_algorithm(algorithm);
+        }
+        Ok(())
+    }
+
+    /// Take the pending error of a socket (SO_ERROR)
+    pub fn take_error(&mut self, handle: SocketHandle) -> Option<TcpError> {
+        self.sockets.get_mut(&handle)?.error.take()
+    }
+
+    /// Connection a socket owns
+    fn connection_of(&self, handle: SocketHandle) -> Result<ConnectionId, TcpError> {
+        match self.sockets.get(&handle).ok_or(TcpError::InvalidConnection)?.role {
+            SocketRole::Connected(id) => Ok(id),
+            _ => Err(TcpError::NotConnected),
+        }
+    }
 
-            // Put connection back to handle closure process
-            self.connections.insert(conn_id, connection);
+    /// Process a segment received from `source` for `dest`
+    ///
+    /// The checksum must have been verified by the caller.
+    pub fn input(&mut self, source: IpAddr, dest: IpAddr, packet: &TcpPacket, now: u64) {
+        let source = source.to_canonical();
+        let dest = dest.to_canonical();
+        let id = ConnectionId::new(dest, packet.dst_port(), source, packet.src_port());
+        let new_syn = packet.has_flag(tcp_flags::SYN)
+            && !packet.has_flag(tcp_flags::ACK)
+            && !packet.has_flag(tcp_flags::RST);
+        let listener = if new_syn { self.find_listener(dest, packet.dst_port()) } else { None };
+
+        if let Some(connection) = self.connections.get_mut(&id) {
+            // A new SYN for a 4-tuple in TIME-WAIT reopens the connection
+            if !(listener.is_some() && connection.state() == TcpState::TimeWait && connection.owner.is_none()) {
+                connection.stats.packets_rx += 1;
+                let actions = connection.state_machine.on_segment(packet, now);
+                self.apply(id, actions, now);
+                return;
+            }
+            self.connections.remove(&id);
+        }
+
+        if let Some(handle) = listener {
+            self.accept_syn(handle, id, packet, now);
+            return;
+        }
+
+        // No socket for the segment: answer with a reset
+        if let Some(reset) = reset_for(packet) {
+            self.transmit(id, &reset);
+        }
+    }
+
+    /// Listener for a destination: bound to the address, then to the family
+    /// wildcard, then a dual-stack socket on ::
+    fn find_listener(&self, dest: IpAddr, port: u16) -> Option<SocketHandle> {
+        let candidates = [
+            ConnectionId::wildcard(dest, port),
+            ConnectionId::wildcard(dest.unspecified_like(), port),
+            ConnectionId::wildcard(IpAddr::UNSPECIFIED, port),
+        ];
+        candidates.into_iter().find_map(|id| {
+            let handle = *self.listening_sockets.get(&id)?;
+            let v6only = self.sockets.get(&handle)?.options.v6only;
+            (!(dest.is_ipv4() && id.local_ip.is_ipv6() && v6only)).then_some(handle)
+        })
+    }
+
+    /// Start a handshake for a SYN that reached a listener
+    fn accept_syn(&mut self, handle: SocketHandle, id: ConnectionId, packet: &TcpPacket, now: u64) {
+        let Some(record) = self.sockets.get_mut(&handle) else {
+            return;
+        };
+        let options = record.options.clone();
+        let SocketRole::Listening(listener) = &mut record.role else {
+            return;
+        };
+        // Drop the SYN when the backlog is full; the client retries
+        if listener.syn_queue.len() + listener.accept_queue.len() >= listener.backlog {
+            return;
+        }
+        listener.syn_queue.push(id);
+
+        let mut connection = TcpConnection::new(id, options, now);
+        connection.listener = Some(handle);
+        connection.stats.packets_rx += 1;
+        let actions = connection.state_machine.accept_syn(packet, now);
+        self.connections.insert(id, connection);
+        self.apply(id, actions, now);
+    }
 
-            // Deallocate port if it's a listening socket
-            if self.listening_sockets.contains_key(&conn_id) {
-                self.deallocate_port(conn_id.local_port);
-                self.listening_sockets.remove(&conn_id);
+    /// Carry out the actions a connection's state machine returned
+    fn apply(&mut self, id: ConnectionId, actions: Vec<TcpAction>, now: u64) {
+        if actions.is_empty() {
+            return;
+        }
+        for action in actions {
+            match action {
+                TcpAction::Send(segment) => {
+                    if let Some(connection) = self.connections.get_mut(&id) {
+                        connection.stats.packets_tx += 1;
+                        if segment.retransmit_count > 0 {
+                            connection.stats.retransmissions += 1;
+                        }
+                    }
+                    self.transmit(id, &segment);
+                }
+                TcpAction::DataReceived(bytes) => {
+                    if let Some(connection) = self.connections.get_mut(&id) {
+                        connection.stats.bytes_rx += bytes as u64;
+                    }
+                }
+                TcpAction::ConnectionEstablished => self.on_established(id, now),
+                TcpAction::ConnectionReset => self.on_failure(id, TcpError::ConnectionReset),
+                TcpAction::TimedOut => self.on_failure(id, TcpError::ConnectionTimeout),
+                TcpAction::PeerClosed | TcpAction::CanSendData | TcpAction::ConnectionClosed => {}
             }
+        }
+        self.wake = true;
+        self.reap(id);
+    }
 
-            Ok(())
+    /// Handshake done: a passive connection moves to its accept queue
+    fn on_established(&mut self, id: ConnectionId, now: u64) {
+        let Some(connection) = self.connections.get_mut(&id) else {
+            return;
+        };
+        connection.stats.establishment_time = Some(now);
+        if let Some(handle) = connection.listener
+            && let Some(SocketRecord { role: SocketRole::Listening(listener), .. }) =
+                self.sockets.get_mut(&handle)
+        {
+            listener.syn_queue.retain(|queued| *queued != id);
+            listener.accept_queue.push_back(id);
+        }
+    }
+
+    /// Connection failed: report the error to its socket
+    ///
+    /// A connection that never got through its handshake is released, so
+    /// the socket can be connected again.
+    fn on_failure(&mut self, id: ConnectionId, error: TcpError) {
+        let Some(connection) = self.connections.get_mut(&id) else {
+            return;
+        };
+        let handshake = connection.stats.establishment_time.is_none();
+        // A reset before the handshake completed is a refusal
+        let error = if error == TcpError::ConnectionReset && handshake {
+            TcpError::ConnectionRefused
         } else {
-            Err(TcpError::InvalidConnection)
+            error
+        };
+        let owner = if handshake { connection.owner.take() } else { connection.owner };
+        if let Some(handle) = owner
+            && let Some(record) = self.sockets.get_mut(&handle)
+        {
+            record.error = Some(error);
+            if handshake {
+                record.role = SocketRole::Idle;
+            }
         }
     }
 
+    /// Remove a closed connection no socket refers to any more
+    fn reap(&mut self, id: ConnectionId) {
+        let Some(connection) = self.connections.get(&id) else {
+            return;
+        };
+        if !connection.is_closed() || connection.owner.is_some() {
+            return;
+        }
+        if let Some(handle) = connection.listener
+            && let Some(SocketRecord { role: SocketRole::Listening(listener), .. }) =
+                self.sockets.get_mut(&handle)
+        {
+            listener.syn_queue.retain(|queued| *queued != id);
+            listener.accept_queue.retain(|queued| *queued != id);
+        }
+        self.connections.remove(&id);
+    }
+
+    /// Encode a segment of a connection into the outbox
+    fn transmit(&mut self, id: ConnectionId, segment: &TcpSegment) {
+        let mut packet = segment.to_packet(id.local_port, id.remote_port);
+        packet.seal(id.local_ip, id.remote_ip);
+        self.outbox.push(TcpOutput {
+            source: id.local_ip,
+            dest: id.remote_ip,
+            segment: packet.to_bytes(),
+        });
+    }
+
+    /// Take the segments waiting to be sent
+    pub fn take_output(&mut self) -> Vec<TcpOutput> {
+        core::mem::take(&mut self.outbox)
+    }
+
+    /// Whether sleepers should be woken, clearing the flag
+    pub fn take_wakeup(&mut self) -> bool {
+        core::mem::take(&mut self.wake)
+    }
+
     /// Find connection by 4-tuple
     pub fn find_connection(
         &self,
-        local_ip: Ipv4Addr,
+        local_ip: IpAddr,
         local_port: u16,
-        remote_ip: Ipv4Addr,
+        remote_ip: IpAddr,
         remote_port: u16,
     ) -> Option<&TcpConnection> {
         let conn_id = ConnectionId::new(local_ip, local_port, remote_ip, remote_port);
@@ -504,13 +1120,11 @@ impl TcpConnectionManager {
     /// Find connection by ID
     pub fn get_connection(&self, conn_id: ConnectionId) -> Option<&TcpConnection> {
         self.connections.get(&conn_id)
-            .or_else(|| self.listening_sockets.get(&conn_id))
     }
 
     /// Get mutable connection by ID
     pub fn get_connection_mut(&mut self, conn_id: ConnectionId) -> Option<&mut TcpConnection> {
         self.connections.get_mut(&conn_id)
-            .or_else(|| self.listening_sockets.get_mut(&conn_id))
     }
 
     /// Get all connections
@@ -519,70 +1133,19 @@ impl TcpConnectionManager {
     }
 
     /// Get all listening sockets
-    pub fn get_listening_sockets(&self) -> Vec<&TcpConnection> {
-        self.listening_sockets.values().collect()
+    pub fn get_listening_sockets(&self) -> Vec<ConnectionId> {
+        self.listening_sockets.keys().copied().collect()
     }
 
-    /// Process packet for all matching connections
-    pub fn process_packet(
-        &mut self,
-        local_ip: Ipv4Addr,
-        local_port: u16,
-        remote_ip: Ipv4Addr,
-        remote_port: u16,
-        packet: &[u8],
-    ) -> Vec<(ConnectionId, Result<Vec<TcpAction>, TcpError>)> {
-        let mut results = Vec::new();
-
-        // Try to find exact match first
-        let conn_id = ConnectionId::new(local_ip, local_port, remote_ip, remote_port);
-        if let Some(connection) = self.connections.get_mut(&conn_id) {
-            let actions = connection.process_packet(packet);
-            results.push((conn_id, actions));
-            return results;
-        }
-
-        // Try to find listening socket
-        let listen_id = ConnectionId::wildcard(local_ip, local_port);
-        if let Some(listening_socket) = self.listening_sockets.get_mut(&listen_id) {
-            let actions = listening_socket.process_packet(packet);
-            results.push((listen_id, actions));
-        }
-
-        results
-    }
-
-    /// Check timeouts for all connections
-    pub fn check_timeouts(&mut self) -> Vec<(ConnectionId, Vec<TcpAction>)> {
-        let mut results = Vec::new();
-
-        for (conn_id, connection) in &mut self.connections {
-            let actions = connection.check_timeouts();
-            if !actions.is_empty() {
-                results.push((*conn_id, actions));
+    /// Run the timers of all connections
+    pub fn check_timeouts(&mut self, now: u64) {
+        let ids: Vec<ConnectionId> = self.connections.keys().copied().collect();
+        for id in ids {
+            if let Some(connection) = self.connections.get_mut(&id) {
+                let actions = connection.check_timeouts(now);
+                self.apply(id, actions, now);
             }
         }
-
-        for (conn_id, connection) in &mut self.listening_sockets {
-            let actions = connection.check_timeouts();
-            if !actions.is_empty() {
-                results.push((*conn_id, actions));
-            }
-        }
-
-        results
-    }
-
-    /// Cleanup closed connections
-    pub fn cleanup(&mut self) {
-        self.connections.retain(|_, conn| !conn.is_closed());
-
-        // Also clean up very old listening sockets
-        let _now = get_current_time();
-        self.listening_sockets.retain(|_, _conn| {
-            // Keep listening sockets alive indefinitely (simplified)
-            true
-        });
     }
 
     /// Get manager statistics
@@ -613,7 +1176,7 @@ pub struct TcpManagerStats {
 }
 
 /// TCP errors
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TcpError {
     /// Invalid packet format
     InvalidPacket,
@@ -623,20 +1186,59 @@ pub enum TcpError {
     InvalidConnection,
     /// Not connected
     NotConnected,
+    /// Already connected
+    AlreadyConnected,
+    /// Connection attempt still in progress
+    InProgress,
+    /// Operation would block
+    WouldBlock,
+    /// Sending side was shut down
+    Shutdown,
     /// Buffer full
     BufferFull,
     /// Port in use
     PortInUse,
     /// No ports available
     NoPortsAvailable,
+    /// Connection refused
+    ConnectionRefused,
     /// Connection reset
     ConnectionReset,
     /// Connection timeout
     ConnectionTimeout,
 }
 
-/// Get current time (simplified implementation)
-fn get_current_time() -> u64 {
-    static TIMER: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
-    TIMER.fetch_add(1, core::sync::atomic::Ordering::Relaxed)
-}
\ No newline at end of file
+/// Global TCP connection manager
+static TCP_MANAGER: once_cell::sync::Lazy<Mutex<TcpConnectionManager>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(TcpConnectionManager::new()));
+
+/// Counts manager events; sleepers compare it to notice progress
+static TCP_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Run `f` on the global TCP manager
+///
+/// Sleepers are woken after the lock is released when `f` caused socket
+/// events, both on [`wait_channel`] and on the poll channel used by epoll.
+pub fn with_tcp_manager<R>(f: impl FnOnce(&mut TcpConnectionManager) -> R) -> R {
+    let (result, wake) = {
+        let mut manager = TCP_MANAGER.lock();
+        let result = f(&mut manager);
+        (result, manager.take_wakeup())
+    };
+    if wake {
+        TCP_EVENTS.fetch_add(1, Ordering::Release);
+        crate::process::wakeup(wait_channel());
+        crate::process::wakeup(crate::syscalls::POLL_WAKE_CHAN);
+    }
+    result
+}
+
+/// Number of socket events so far
+pub fn tcp_events() -> u64 {
+    TCP_EVENTS.load(Ordering::Acquire)
+}
+
+/// Sleep channel for tasks waiting on TCP sockets
+pub fn wait_channel() -> usize {
+    &TCP_EVENTS as *const AtomicU64 as usize
+}
@@ -1,43 +1,141 @@
 //! TCP state machine implementation
 //!
-//! This module provides a complete TCP state machine implementation with
-//! proper state transitions, timer management, and connection lifecycle.
+//! This module provides the per-connection TCP state machine: sequence space
+//! bookkeeping, segment acceptance as in RFC 9293 section 3.10, retransmission
+//! with RTT estimation, SACK (RFC 2018, RFC 6675) and RACK-TLP (RFC 8985)
+//! loss detection, pluggable congestion control and the connection lifecycle
+//! down to TIME-WAIT. The owner drives it by passing the current uptime in
+//! milliseconds and transmitting the segments it returns.
 
 extern crate alloc;
+use alloc::boxed::Box;
 use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU32, Ordering};
 
-use super::{TcpPacket, TcpState};
-use super::tcp_flags;
-use crate::net::ipv4::Ipv4Addr;
+use super::congestion::{default_algorithm, AckSample, CongestionAlgorithm, CongestionControl, LossEvent};
+use super::{seq_ge, seq_gt, seq_le, seq_lt, tcp_flags, MssOption, SackBlock, SackOption, TcpPacket, TcpState};
+use super::{TcpOption, TcpOptionKind};
+use super::{INITIAL_RTO_MS, MAX_RECEIVE_WINDOW, MAX_RTO_MS, MAX_SEGMENT_LIFETIME, MIN_RTO_MS};
+
+/// Retransmissions of a SYN or SYN-ACK before the handshake fails
+pub const MAX_SYN_RETRIES: u32 = 5;
+
+/// Retransmissions of a segment before the connection is dropped
+pub const MAX_RETRIES: u32 = 12;
+
+/// Duplicate ACKs (or segments SACKed above a hole) that mark it lost
+pub const DUP_ACK_THRESHOLD: u32 = 3;
+
+/// Probe timeout before an RTT sample exists, in milliseconds
+const TLP_INITIAL_PTO_MS: u64 = 1000;
+
+/// Delayed ACK allowance in the probe timeout with one segment in flight
+const TLP_MAX_ACK_DELAY_MS: u64 = 200;
+
+/// Shortest probe timeout, in milliseconds
+const TLP_MIN_PTO_MS: u64 = 10;
+
+/// Length of TIME-WAIT (2 * MSL) in milliseconds
+pub const TIME_WAIT_MS: u64 = 2 * MAX_SEGMENT_LIFETIME * 1000;
+
+/// MSS assumed when the peer does not announce one (RFC 9293)
+const DEFAULT_PEER_MSS: u32 = 536;
+
+/// Segments kept ahead of a hole in the receive sequence space
+const MAX_OUT_OF_ORDER: usize = 64;
 
 /// TCP connection state machine
 #[derive(Debug, Clone)]
 pub struct TcpStateMachine {
     /// Current state
     state: TcpState,
-    /// Local sequence number
-    local_seq: u32,
-    /// Remote sequence number
-    remote_seq: u32,
-    /// Local acknowledgment number
-    local_ack: u32,
-    /// Remote acknowledgment number
-    remote_ack: u32,
-    /// Local window size
-    local_window: u16,
-    /// Remote window size
-    remote_window: u16,
+    /// Initial send sequence number
+    iss: u32,
+    /// Oldest unacknowledged sequence number
+    snd_una: u32,
+    /// Next sequence number to send
+    snd_nxt: u32,
+    /// Send window advertised by the peer
+    snd_wnd: u32,
+    /// Sequence number of the segment that last updated the send window
+    snd_wl1: u32,
+    /// Acknowledgment number of the segment that last updated the send window
+    snd_wl2: u32,
+    /// Initial receive sequence number
+    irs: u32,
+    /// Next sequence number expected from the peer
+    rcv_nxt: u32,
+    /// Receive window in the last segment sent
+    last_window: u32,
+    /// MSS announced to the peer
+    local_mss: u16,
+    /// MSS used when sending
+    mss: u32,
+    /// Data queued by the application and not yet sent
+    send_buffer: VecDeque<u8>,
+    /// Bytes of unsent plus unacknowledged data allowed
+    send_capacity: usize,
+    /// In-order data not yet read by the application
+    recv_buffer: VecDeque<u8>,
+    /// Receive buffer size, which bounds the advertised window
+    recv_capacity: usize,
+    /// Segments received ahead of `rcv_nxt`, ordered by sequence number
+    out_of_order: Vec<OutOfOrderSegment>,
+    /// The application closed its sending side
+    fin_queued: bool,
+    /// Sequence number of our FIN once sent
+    fin_seq: Option<u32>,
+    /// The peer's FIN has been received
+    fin_received: bool,
+    /// An ACK is owed to the peer
+    ack_pending: bool,
+    /// Nagle's algorithm enabled
+    nagle: bool,
     /// Connection timestamps
     timestamps: TcpTimestamps,
-    /// Retransmission queue
-    retransmit_queue: VecDeque<TcpSegment>,
-    /// Congestion control state
-    congestion: TcpCongestionControl,
-    /// Flow control state
-    flow_control: TcpFlowControl,
+    /// Segments sent and not yet acknowledged
+    retransmit_queue: VecDeque<SentSegment>,
+    /// Congestion control algorithm
+    congestion: Box<dyn CongestionControl>,
+    /// Round-trip time estimator
+    rtt: RttEstimator,
+    /// Both ends offered SACK on the handshake
+    sack_permitted: bool,
+    /// Sequence number of the newest out-of-order segment, reported first
+    last_out_of_order: Option<u32>,
+    /// RACK state: the most recently sent segment known delivered
+    rack: RackState,
+    /// Loss recovery lasts until this sequence number is acknowledged
+    recovery_point: Option<u32>,
+    /// Send the first lost segment even if the pipe is full
+    force_retransmit: bool,
+    /// Duplicate ACKs in a row, for peers without SACK
+    dup_acks: u32,
+    /// Transmissions so far, ordering segments sent within one tick
+    transmissions: u64,
+    /// Bytes delivered over the connection, cumulatively or by SACK
+    delivered: u64,
+    /// When `delivered` last grew
+    delivered_time: u64,
+    /// Earliest time the pacer lets the next segment go
+    pacing_next: u64,
+    /// Uptime at which held-back segments may go
+    pacing_deadline: Option<u64>,
+    /// Uptime at which the retransmission (or persist) timer fires
+    rto_deadline: Option<u64>,
+    /// Uptime at which RACK's reordering window runs out
+    reorder_deadline: Option<u64>,
+    /// Uptime at which the tail loss probe is sent
+    probe_deadline: Option<u64>,
+    /// End of an unacknowledged tail loss probe and whether it was a
+    /// retransmission
+    probe: Option<(u32, bool)>,
+    /// Consecutive retransmissions without progress
+    retries: u32,
+    /// Uptime at which TIME-WAIT ends
+    time_wait_until: Option<u64>,
 }
 
 /// TCP timestamps for timeout management
@@ -53,7 +151,7 @@ pub struct TcpTimestamps {
     pub last_ack_time: u64,
 }
 
-/// TCP segment for retransmission
+/// TCP segment built by the state machine
 #[derive(Debug, Clone)]
 pub struct TcpSegment {
     /// Sequence number
@@ -64,91 +162,217 @@ pub struct TcpSegment {
     pub flags: u8,
     /// Window size
     pub window: u16,
+    /// Encoded options
+    pub options: Vec<u8>,
     /// Data payload
     pub data: Vec<u8>,
     /// Number of retransmissions
     pub retransmit_count: u32,
     /// Last transmission time
     pub last_tx_time: u64,
-    /// Retransmission timeout
-    pub rto: u64,
 }
 
-/// TCP congestion control state
+/// Segment waiting for acknowledgment, with its scoreboard entry
 #[derive(Debug, Clone)]
-pub struct TcpCongestionControl {
-    /// Congestion window (in bytes)
-    pub cwnd: u32,
-    /// Slow start threshold
-    pub ssthresh: u32,
-    /// Current state
-    pub state: CongestionState,
-    /// Duplicate ACK count
-    pub dup_ack_count: u32,
-    /// Last ACK received
-    pub last_ack: u32,
-    /// Round-trip time estimator
-    pub rtt_estimator: RttEstimator,
+struct SentSegment {
+    /// The segment as last sent
+    segment: TcpSegment,
+    /// A SACK block covers it
+    sacked: bool,
+    /// Presumed lost and not yet retransmitted
+    lost: bool,
+    /// Position of the last transmission in the connection's send order
+    order: u64,
+    /// Connection's delivered count when it was sent
+    delivered: u64,
+    /// Connection's delivered time when it was sent
+    delivered_time: u64,
+    /// The application had nothing more to send at the time
+    app_limited: bool,
 }
 
-/// Congestion control states
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum CongestionState {
-    /// Slow start
-    SlowStart,
-    /// Congestion avoidance
-    CongestionAvoidance,
-    /// Fast recovery
-    FastRecovery,
-    /// Fast retransmit
-    FastRetransmit,
+/// RACK state (RFC 8985 section 6)
+#[derive(Debug, Clone, Default)]
+struct RackState {
+    /// Send order and end of the most recently sent delivered segment
+    newest: Option<(u64, u32)>,
+    /// RTT of that segment
+    rtt: u64,
+    /// A segment was delivered after one sent later
+    reordering_seen: bool,
 }
 
-/// RTT (Round-Trip Time) estimator
+/// What one acknowledgment delivered
+#[derive(Debug, Default)]
+struct Delivery {
+    /// Bytes newly acknowledged
+    bytes: u32,
+    /// RTT of a segment sent once
+    rtt: Option<u64>,
+    /// A segment arrived after one sent later
+    reordered: bool,
+    /// Most recently sent delivered segment
+    newest: Option<SentSegment>,
+}
+
+/// Segment received ahead of a hole
+#[derive(Debug, Clone)]
+struct OutOfOrderSegment {
+    /// Sequence number of the first data byte
+    seq: u32,
+    /// Data
+    data: Vec<u8>,
+    /// The segment carried a FIN
+    fin: bool,
+}
+
+/// RTT (Round-Trip Time) estimator, in milliseconds
 #[derive(Debug, Clone)]
 pub struct RttEstimator {
     /// Smoothed RTT
-    pub srtt: u32,
+    pub srtt: u64,
     /// RTT variance
-    pub rttvar: u32,
+    pub rttvar: u64,
     /// Retransmission timeout
-    pub rto: u32,
+    pub rto: u64,
     /// Minimum RTT seen
-    pub min_rtt: u32,
+    pub min_rtt: u64,
 }
 
-/// TCP flow control state
+/// TCP action returned by state machine
 #[derive(Debug, Clone)]
-pub struct TcpFlowControl {
-    /// Advertised window
-    pub advertised_window: u16,
-    /// Effective window
-    pub effective_window: u32,
-    /// Outstanding bytes
-    pub outstanding_bytes: u32,
-    /// Maximum window size
-    pub max_window: u16,
+pub enum TcpAction {
+    /// Transmit a segment
+    Send(TcpSegment),
+    /// Bytes were added to the receive buffer
+    DataReceived(usize),
+    /// The peer closed its sending side
+    PeerClosed,
+    /// Send buffer space was freed
+    CanSendData,
+    /// Connection established
+    ConnectionEstablished,
+    /// Connection closed
+    ConnectionClosed,
+    /// Connection reset (or refused during the handshake)
+    ConnectionReset,
+    /// Retransmissions were exhausted
+    TimedOut,
 }
 
-impl TcpStateMachine {
-    /// Create a new TCP state machine
-    pub fn new(is_passive: bool) -> Self {
-        let state = if is_passive {
-            TcpState::Listen
-        } else {
-            TcpState::Closed
-        };
+impl TcpSegment {
+    /// Sequence space consumed (data plus SYN and FIN)
+    pub fn seq_len(&self) -> u32 {
+        let mut len = self.data.len() as u32;
+        if self.flags & tcp_flags::SYN != 0 {
+            len += 1;
+        }
+        if self.flags & tcp_flags::FIN != 0 {
+            len += 1;
+        }
+        len
+    }
+
+    /// Sequence number following this segment
+    pub fn end(&self) -> u32 {
+        self.seq.wrapping_add(self.seq_len())
+    }
+
+    /// Build the wire packet between the given ports
+    pub fn to_packet(&self, src_port: u16, dst_port: u16) -> TcpPacket {
+        TcpPacket::segment(
+            src_port,
+            dst_port,
+            self.seq,
+            self.ack,
+            self.flags,
+            self.window,
+            self.options.clone(),
+            self.data.clone(),
+        )
+    }
+
+    /// Bare reset with the given sequence number
+    fn reset(seq: u32) -> Self {
+        Self {
+            seq,
+            ack: 0,
+            flags: tcp_flags::RST,
+            window: 0,
+            options: Vec::new(),
+            data: Vec::new(),
+            retransmit_count: 0,
+            last_tx_time: 0,
+        }
+    }
+}
 
-        let now = Self::current_time();
+impl Delivery {
+    /// Count a newly delivered segment
+    ///
+    /// `rack_end` is the end of RACK's newest segment before this ACK; a
+    /// first transmission ending below it arrived out of order.
+    fn record(&mut self, sent: SentSegment, now: u64, rack_end: u32) {
+        let segment = &sent.segment;
+        self.bytes += segment.seq_len();
+        if segment.retransmit_count == 0 {
+            // Karn's algorithm: only segments sent once give samples
+            self.rtt = Some(now.saturating_sub(segment.last_tx_time));
+            self.reordered |= seq_lt(segment.end(), rack_end);
+        }
+        if self.newest.as_ref().is_none_or(|newest| sent.order > newest.order) {
+            self.newest = Some(sent);
+        }
+    }
+}
+
+/// Reset answering a segment that belongs to no connection (RFC 9293 3.10.7.1)
+///
+/// Returns `None` for a segment that is itself a reset.
+pub fn reset_for(packet: &TcpPacket) -> Option<TcpSegment> {
+    if packet.has_flag(tcp_flags::RST) {
+        return None;
+    }
+    if packet.has_flag(tcp_flags::ACK) {
+        return Some(TcpSegment::reset(packet.ack_num()));
+    }
+    let mut segment = TcpSegment::reset(0);
+    segment.ack = packet.seq_num().wrapping_add(packet.seq_len());
+    segment.flags |= tcp_flags::ACK;
+    Some(segment)
+}
+
+impl TcpStateMachine {
+    /// Create a closed state machine
+    ///
+    /// `mss` is announced to the peer; the buffer sizes bound unsent plus
+    /// unacknowledged data and the advertised receive window.
+    pub fn new(mss: u16, send_capacity: usize, recv_capacity: usize, now: u64) -> Self {
+        let iss = Self::generate_initial_seq(now);
 
         Self {
-            state,
-            local_seq: Self::generate_initial_seq(),
-            remote_seq: 0,
-            local_ack: 0,
-            remote_ack: 0,
-            local_window: 65535,
-            remote_window: 0,
+            state: TcpState::Closed,
+            iss,
+            snd_una: iss,
+            snd_nxt: iss,
+            snd_wnd: 0,
+            snd_wl1: 0,
+            snd_wl2: iss,
+            irs: 0,
+            rcv_nxt: 0,
+            last_window: 0,
+            local_mss: mss,
+            mss: DEFAULT_PEER_MSS.min(mss as u32),
+            send_buffer: VecDeque::new(),
+            send_capacity,
+            recv_buffer: VecDeque::new(),
+            recv_capacity,
+            out_of_order: Vec::new(),
+            fin_queued: false,
+            fin_seq: None,
+            fin_received: false,
+            ack_pending: false,
+            nagle: true,
             timestamps: TcpTimestamps {
                 connection_time: now,
                 last_send_time: now,
@@ -156,594 +380,1171 @@ impl TcpStateMachine {
                 last_ack_time: now,
             },
             retransmit_queue: VecDeque::new(),
-            congestion: TcpCongestionControl::new(),
-            flow_control: TcpFlowControl::new(),
+            congestion: default_algorithm().create(DEFAULT_PEER_MSS.min(mss as u32)),
+            rtt: RttEstimator::new(),
+            sack_permitted: false,
+            last_out_of_order: None,
+            rack: RackState::default(),
+            recovery_point: None,
+            force_retransmit: false,
+            dup_acks: 0,
+            transmissions: 0,
+            delivered: 0,
+            delivered_time: now,
+            pacing_next: 0,
+            pacing_deadline: None,
+            rto_deadline: None,
+            reorder_deadline: None,
+            probe_deadline: None,
+            probe: None,
+            retries: 0,
+            time_wait_until: None,
         }
     }
 
     /// Generate initial sequence number
-    fn generate_initial_seq() -> u32 {
-        use core::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// A clock ticking every 4 microseconds (RFC 793) plus a per-connection
+    /// step, so that reincarnations of a connection start past the old one.
+    fn generate_initial_seq(now: u64) -> u32 {
         static SEQ_GENERATOR: AtomicU32 = AtomicU32::new(1);
-        SEQ_GENERATOR.fetch_add(1000, Ordering::Relaxed)
+        (now as u32)
+            .wrapping_mul(250)
+            .wrapping_add(SEQ_GENERATOR.fetch_add(64000, Ordering::Relaxed))
+    }
+
+    /// Enable or disable Nagle's algorithm
+    pub fn set_nagle(&mut self, enabled: bool) {
+        self.nagle = enabled;
     }
 
-    /// Get current time (in seconds)
-    fn current_time() -> u64 {
-        static TIMER: AtomicU64 = AtomicU64::new(0);
-        TIMER.fetch_add(1, Ordering::Relaxed)
+    /// Switch congestion control algorithm; a new one starts from scratch
+    pub fn set_congestion_algorithm(&mut self, algorithm: CongestionAlgorithm) {
+        if self.congestion.algorithm() != algorithm {
+            self.congestion = algorithm.create(self.mss);
+        }
     }
 
-    /// Process incoming packet
-    pub fn process_packet(&mut self, packet: &TcpPacket) -> TcpAction {
-        self.timestamps.last_recv_time = Self::current_time();
+    /// Start an active open by sending a SYN
+    pub fn connect(&mut self, now: u64) -> Vec<TcpAction> {
+        let mut actions = Vec::new();
+        if self.state == TcpState::Closed {
+            self.state = TcpState::SynSent;
+            self.send_control(tcp_flags::SYN, now, &mut actions);
+        }
+        actions
+    }
+
+    /// Answer a SYN received by a listener with a SYN-ACK
+    pub fn accept_syn(&mut self, syn: &TcpPacket, now: u64) -> Vec<TcpAction> {
+        let mut actions = Vec::new();
+        if self.state != TcpState::Closed || !syn.has_flag(tcp_flags::SYN) {
+            return actions;
+        }
+
+        self.irs = syn.seq_num();
+        self.rcv_nxt = self.irs.wrapping_add(1);
+        self.snd_wnd = syn.header.window_size as u32;
+        self.snd_wl1 = syn.seq_num();
+        self.apply_peer_options(syn);
+        self.state = TcpState::SynReceived;
+        self.send_control(tcp_flags::SYN | tcp_flags::ACK, now, &mut actions);
+        actions
+    }
 
-        // Update remote sequence and acknowledgment numbers
-        self.remote_seq = packet.seq_num();
-        self.remote_ack = packet.ack_num();
-        self.remote_window = packet.dst_port(); // Note: This is a hack, should use header.window_size
+    /// Process a segment addressed to this connection
+    pub fn on_segment(&mut self, packet: &TcpPacket, now: u64) -> Vec<TcpAction> {
+        let mut actions = Vec::new();
+        self.timestamps.last_recv_time = now;
 
         match self.state {
-            TcpState::Closed => self.handle_closed(packet),
-            TcpState::Listen => self.handle_listen(packet),
-            TcpState::SynSent => self.handle_syn_sent(packet),
-            TcpState::SynReceived => self.handle_syn_received(packet),
-            TcpState::Established => self.handle_established(packet),
-            TcpState::FinWait1 => self.handle_fin_wait1(packet),
-            TcpState::FinWait2 => self.handle_fin_wait2(packet),
-            TcpState::CloseWait => self.handle_close_wait(packet),
-            TcpState::Closing => self.handle_closing(packet),
-            TcpState::LastAck => self.handle_last_ack(packet),
-            TcpState::TimeWait => self.handle_time_wait(packet),
-        }
-    }
-
-    /// Handle packets in CLOSED state
-    fn handle_closed(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::RST) {
-            TcpAction::None
-        } else {
-            // Send RST
-            TcpAction::SendRst
+            TcpState::Closed | TcpState::Listen => {}
+            TcpState::SynSent => self.on_syn_sent(packet, now, &mut actions),
+            _ => self.on_synchronized(packet, now, &mut actions),
         }
+        actions
     }
 
-    /// Handle packets in LISTEN state
-    fn handle_listen(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::SYN) && !packet.has_flag(tcp_flags::ACK) {
-            // Received SYN, transition to SYN_RECEIVED
-            self.state = TcpState::SynReceived;
-            self.remote_seq = packet.seq_num();
-            self.local_ack = packet.seq_num() + 1; // SYN consumes one sequence number
-            self.local_seq = Self::generate_initial_seq();
-
-            // Send SYN-ACK
-            TcpAction::SendSynAck
-        } else if packet.has_flag(tcp_flags::RST) {
-            TcpAction::None
+    /// Segment arriving in SYN-SENT
+    fn on_syn_sent(&mut self, packet: &TcpPacket, now: u64, actions: &mut Vec<TcpAction>) {
+        let has_ack = packet.has_flag(tcp_flags::ACK);
+        let ack = packet.ack_num();
+
+        if has_ack && (seq_le(ack, self.iss) || seq_gt(ack, self.snd_nxt)) {
+            if !packet.has_flag(tcp_flags::RST) {
+                actions.push(TcpAction::Send(TcpSegment::reset(ack)));
+            }
+            return;
+        }
+
+        if packet.has_flag(tcp_flags::RST) {
+            if has_ack {
+                self.enter_closed();
+                actions.push(TcpAction::ConnectionReset);
+            }
+            return;
+        }
+
+        if !packet.has_flag(tcp_flags::SYN) {
+            return;
+        }
+
+        self.irs = packet.seq_num();
+        self.rcv_nxt = self.irs.wrapping_add(1);
+        self.apply_peer_options(packet);
+
+        if has_ack {
+            self.process_ack(ack, &[], false, now, actions);
+            self.update_send_window(packet, true);
+            self.state = TcpState::Established;
+            self.timestamps.connection_time = now;
+            actions.push(TcpAction::ConnectionEstablished);
+            self.ack_pending = true;
+            self.finish_input(now, actions);
         } else {
-            // Send RST for other packets
-            TcpAction::SendRst
+            // Simultaneous open: our SYN becomes a SYN-ACK
+            self.state = TcpState::SynReceived;
+            self.snd_wl1 = packet.seq_num();
+            self.snd_wnd = packet.header.window_size as u32;
+            if let Some(front) = self.retransmit_queue.front_mut() {
+                front.segment.flags |= tcp_flags::ACK;
+            }
+            self.retransmit_front(now, actions);
         }
     }
 
-    /// Handle packets in SYN_SENT state
-    fn handle_syn_sent(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::SYN) && packet.has_flag(tcp_flags::ACK) {
-            if packet.ack_num() == self.local_seq + 1 {
-                // Received SYN-ACK, transition to ESTABLISHED
-                self.state = TcpState::Established;
-                self.remote_seq = packet.seq_num() + 1; // SYN consumes one sequence number
-                self.local_ack = packet.ack_num();
+    /// Segment arriving in a synchronized state (or SYN-RECEIVED)
+    fn on_synchronized(&mut self, packet: &TcpPacket, now: u64, actions: &mut Vec<TcpAction>) {
+        let seq = packet.seq_num();
+
+        // The peer retransmitted its SYN, so our SYN-ACK was lost
+        if self.state == TcpState::SynReceived
+            && packet.has_flag(tcp_flags::SYN)
+            && !packet.has_flag(tcp_flags::ACK)
+            && seq == self.irs
+        {
+            self.retransmit_front(now, actions);
+            return;
+        }
+
+        let seg_len = packet.seq_len();
+        let wnd = self.receive_window();
+        let acceptable = match (seg_len, wnd) {
+            (0, 0) => seq == self.rcv_nxt,
+            (0, _) => self.in_window(seq, wnd),
+            (_, 0) => false,
+            (_, _) => self.in_window(seq, wnd) || self.in_window(seq.wrapping_add(seg_len - 1), wnd),
+        };
 
-                // Send ACK
-                TcpAction::SendAck
+        // With a zero window, valid ACKs and resets still need processing
+        let mut accept_data = true;
+        if !acceptable {
+            if wnd == 0 && seq == self.rcv_nxt && !packet.has_flag(tcp_flags::RST) {
+                accept_data = false;
+                self.ack_pending = true;
             } else {
-                TcpAction::SendRst
+                if !packet.has_flag(tcp_flags::RST) {
+                    self.send_ack(now, actions);
+                }
+                return;
             }
-        } else if packet.has_flag(tcp_flags::SYN) {
-            // Simultaneous open
-            self.state = TcpState::SynReceived;
-            self.remote_seq = packet.seq_num() + 1;
-            self.local_ack = packet.seq_num() + 1;
-
-            TcpAction::SendSynAck
-        } else if packet.has_flag(tcp_flags::RST) {
-            // Connection reset
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else {
-            TcpAction::None
         }
-    }
 
-    /// Handle packets in SYN_RECEIVED state
-    fn handle_syn_received(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::ACK) {
-            if packet.ack_num() == self.local_seq + 1 {
-                // Received ACK for SYN-ACK, transition to ESTABLISHED
-                self.state = TcpState::Established;
-                self.local_ack = packet.ack_num();
+        if packet.has_flag(tcp_flags::RST) {
+            // Only an exact match resets; anything else in the window gets a
+            // challenge ACK (RFC 5961)
+            if seq == self.rcv_nxt {
+                let was_time_wait = self.state == TcpState::TimeWait;
+                self.enter_closed();
+                actions.push(if was_time_wait {
+                    TcpAction::ConnectionClosed
+                } else {
+                    TcpAction::ConnectionReset
+                });
+            } else {
+                self.send_ack(now, actions);
+            }
+            return;
+        }
+
+        if packet.has_flag(tcp_flags::SYN) {
+            self.send_ack(now, actions);
+            return;
+        }
 
-                TcpAction::ConnectionEstablished
+        if !packet.has_flag(tcp_flags::ACK) {
+            return;
+        }
+
+        let ack = packet.ack_num();
+        if self.state == TcpState::SynReceived {
+            if seq_lt(self.snd_una, ack) && seq_le(ack, self.snd_nxt) {
+                self.state = TcpState::Established;
+                self.timestamps.connection_time = now;
+                self.update_send_window(packet, true);
+                actions.push(TcpAction::ConnectionEstablished);
             } else {
-                TcpAction::SendRst
+                actions.push(TcpAction::Send(TcpSegment::reset(ack)));
+                return;
             }
-        } else if packet.has_flag(tcp_flags::SYN) && !packet.has_flag(tcp_flags::ACK) {
-            // Retransmit SYN-ACK (duplicate SYN)
-            TcpAction::SendSynAck
-        } else if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else {
-            TcpAction::None
-        }
-    }
-
-    /// Handle packets in ESTABLISHED state
-    fn handle_established(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::FIN) {
-            // Received FIN, transition to CLOSE_WAIT
-            self.state = TcpState::CloseWait;
-            self.local_ack = packet.seq_num() + 1; // FIN consumes one sequence number
-
-            // Send ACK
-            TcpAction::SendAck
-        } else if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else if packet.has_flag(tcp_flags::SYN) {
-            // Ignore duplicate SYN
-            TcpAction::None
-        } else if !packet.payload.is_empty() {
-            // Handle data packet
-            self.handle_data_packet(packet)
-        } else if packet.has_flag(tcp_flags::ACK) {
-            // Handle ACK
-            self.handle_ack_packet(packet)
-        } else {
-            TcpAction::None
         }
+
+        if seq_gt(ack, self.snd_nxt) {
+            self.send_ack(now, actions);
+            return;
+        }
+
+        if seq_ge(ack, self.snd_una) {
+            let sack = if self.sack_permitted { packet.sack_blocks() } else { Vec::new() };
+            let duplicate = ack == self.snd_una
+                && packet.payload.is_empty()
+                && !packet.has_flag(tcp_flags::FIN)
+                && packet.header.window_size as u32 == self.snd_wnd
+                && !self.retransmit_queue.is_empty();
+            if ack != self.snd_una || duplicate || !sack.is_empty() {
+                self.process_ack(ack, &sack, duplicate, now, actions);
+            }
+        }
+        self.update_send_window(packet, false);
+
+        let fin_acked = self.fin_seq.is_some_and(|fin| seq_gt(self.snd_una, fin));
+        match self.state {
+            TcpState::FinWait1 if fin_acked => self.state = TcpState::FinWait2,
+            TcpState::Closing if fin_acked => self.enter_time_wait(now),
+            TcpState::LastAck if fin_acked => {
+                self.enter_closed();
+                actions.push(TcpAction::ConnectionClosed);
+                return;
+            }
+            _ => {}
+        }
+
+        if accept_data && seg_len > 0 {
+            self.receive(seq, &packet.payload, packet.has_flag(tcp_flags::FIN), now, actions);
+        }
+
+        self.finish_input(now, actions);
     }
 
-    /// Handle data packets
-    fn handle_data_packet(&mut self, packet: &TcpPacket) -> TcpAction {
-        // Update local acknowledgment
-        self.local_ack = packet.seq_num() + packet.payload.len() as u32;
+    /// Send what the new state allows, then any ACK still owed
+    fn finish_input(&mut self, now: u64, actions: &mut Vec<TcpAction>) {
+        self.output(now, actions);
+        if self.ack_pending {
+            self.send_ack(now, actions);
+        }
+    }
+
+    /// Take in the data and FIN of an acceptable segment
+    fn receive(&mut self, seq: u32, data: &[u8], fin: bool, now: u64, actions: &mut Vec<TcpAction>) {
+        self.ack_pending = true;
+
+        match self.state {
+            TcpState::Established | TcpState::FinWait1 | TcpState::FinWait2 => {}
+            TcpState::TimeWait if fin => {
+                // Our last ACK was lost; the ACK owed restarts the wait
+                self.time_wait_until = Some(now + TIME_WAIT_MS);
+                return;
+            }
+            // The peer's FIN was already received, so this is a retransmission
+            _ => return,
+        }
 
-        // Update congestion control
-        self.congestion.on_data_received(packet.seq_num() + packet.payload.len() as u32);
+        let mut seq = seq;
+        let mut data = data;
+        let mut fin = fin;
+
+        // Drop what was already received
+        if seq_lt(seq, self.rcv_nxt) {
+            let skip = self.rcv_nxt.wrapping_sub(seq) as usize;
+            if skip > data.len() {
+                return;
+            }
+            data = &data[skip..];
+            seq = self.rcv_nxt;
+        }
+
+        // Drop what lies beyond the window
+        let offset = seq.wrapping_sub(self.rcv_nxt) as usize;
+        let room = (self.receive_window() as usize).saturating_sub(offset);
+        if data.len() > room {
+            data = &data[..room];
+            fin = false;
+        }
+        if data.is_empty() && !fin {
+            return;
+        }
+
+        if seq != self.rcv_nxt {
+            self.store_out_of_order(seq, data, fin);
+            return;
+        }
 
-        // Return data to application
-        TcpAction::DataReceived(packet.payload.clone())
+        self.recv_buffer.extend(data.iter().copied());
+        self.rcv_nxt = self.rcv_nxt.wrapping_add(data.len() as u32);
+        let mut received = data.len();
+
+        // Fill from segments that arrived ahead of this one
+        while !fin {
+            let Some(pos) = self.out_of_order.iter().position(|s| seq_le(s.seq, self.rcv_nxt)) else {
+                break;
+            };
+            let segment = self.out_of_order.remove(pos);
+            let skip = self.rcv_nxt.wrapping_sub(segment.seq) as usize;
+            if skip > segment.data.len() {
+                continue;
+            }
+            let room = self.recv_capacity.saturating_sub(self.recv_buffer.len());
+            let take = (segment.data.len() - skip).min(room);
+            self.recv_buffer.extend(segment.data[skip..skip + take].iter().copied());
+            self.rcv_nxt = self.rcv_nxt.wrapping_add(take as u32);
+            received += take;
+            fin = segment.fin && skip + take == segment.data.len();
+        }
+
+        if received > 0 {
+            actions.push(TcpAction::DataReceived(received));
+        }
+        if fin {
+            self.receive_fin(now, actions);
+        }
     }
 
-    /// Handle ACK packets
-    fn handle_ack_packet(&mut self, packet: &TcpPacket) -> TcpAction {
-        // Update flow control
-        let newly_acked = packet.ack_num().saturating_sub(self.remote_ack);
-        if newly_acked > 0 {
-            self.flow_control.on_ack_received(newly_acked);
-            self.remote_ack = packet.ack_num();
+    /// Keep a segment that arrived ahead of a hole
+    fn store_out_of_order(&mut self, seq: u32, data: &[u8], fin: bool) {
+        if self.out_of_order.len() >= MAX_OUT_OF_ORDER {
+            return;
         }
+        let pos = self
+            .out_of_order
+            .iter()
+            .position(|s| seq_lt(seq, s.seq))
+            .unwrap_or(self.out_of_order.len());
+        self.out_of_order.insert(pos, OutOfOrderSegment { seq, data: data.to_vec(), fin });
+        self.last_out_of_order = Some(seq);
+    }
 
-        // Update congestion control
-        self.congestion.on_ack_received(packet.ack_num());
+    /// The peer's FIN arrived in order
+    fn receive_fin(&mut self, now: u64, actions: &mut Vec<TcpAction>) {
+        self.rcv_nxt = self.rcv_nxt.wrapping_add(1);
+        self.fin_received = true;
+        self.ack_pending = true;
+        self.out_of_order.clear();
+        actions.push(TcpAction::PeerClosed);
 
-        // Check if we can send more data
-        if self.flow_control.can_send() && self.congestion.can_send() {
-            TcpAction::CanSendData
-        } else {
-            TcpAction::None
+        match self.state {
+            TcpState::SynReceived | TcpState::Established => self.state = TcpState::CloseWait,
+            TcpState::FinWait1 => self.state = TcpState::Closing,
+            TcpState::FinWait2 => self.enter_time_wait(now),
+            _ => {}
         }
     }
 
-    /// Handle packets in FIN_WAIT1 state
-    fn handle_fin_wait1(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::ACK) {
-            if packet.ack_num() == self.local_seq + 1 {
-                // ACK for our FIN, transition to FIN_WAIT2
-                self.state = TcpState::FinWait2;
-                TcpAction::None
+    /// Take an acknowledgment with its SACK blocks
+    ///
+    /// `duplicate` marks an ACK that repeats `snd_una` without carrying
+    /// anything else (RFC 5681).
+    fn process_ack(&mut self, ack: u32, sack: &[SackBlock], duplicate: bool, now: u64, actions: &mut Vec<TcpAction>) {
+        let advanced = seq_gt(ack, self.snd_una);
+        let flight = self.flight_size();
+        let rack_end = self.rack.newest.map_or(self.snd_una, |(_, end)| end);
+        let mut delivery = Delivery::default();
+
+        while let Some(front) = self.retransmit_queue.front_mut() {
+            if seq_le(front.segment.end(), ack) {
+                if let Some(sent) = self.retransmit_queue.pop_front()
+                    && !sent.sacked
+                {
+                    delivery.record(sent, now, rack_end);
+                }
             } else {
-                TcpAction::None
+                if seq_gt(ack, front.segment.seq) {
+                    let segment = &mut front.segment;
+                    let trim = (ack.wrapping_sub(segment.seq) as usize).min(segment.data.len());
+                    segment.data.drain(..trim);
+                    segment.seq = segment.seq.wrapping_add(trim as u32);
+                    if !front.sacked {
+                        delivery.bytes += trim as u32;
+                    }
+                }
+                break;
+            }
+        }
+
+        for block in sack {
+            // Blocks below the cumulative ACK are stale or D-SACK
+            if seq_le(block.right, ack) || seq_gt(block.right, self.snd_nxt) {
+                continue;
+            }
+            for sent in self.retransmit_queue.iter_mut() {
+                if !sent.sacked && seq_le(block.left, sent.segment.seq) && seq_le(sent.segment.end(), block.right) {
+                    sent.sacked = true;
+                    sent.lost = false;
+                    delivery.record(sent.clone(), now, rack_end);
+                }
             }
-        } else if packet.has_flag(tcp_flags::FIN) {
-            // Received FIN before our FIN was ACKed
-            self.state = TcpState::Closing;
-            self.local_ack = packet.seq_num() + 1;
-            TcpAction::SendAck
-        } else if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else {
-            TcpAction::None
         }
+
+        if delivery.bytes > 0 {
+            self.delivered += delivery.bytes as u64;
+            self.delivered_time = now;
+        }
+        if let Some(rtt) = delivery.rtt {
+            self.rtt.update_measurement(rtt);
+        }
+        self.rack.reordering_seen |= delivery.reordered;
+        let prior = delivery.newest.take();
+        if let Some(newest) = &prior {
+            self.rack_update(newest, now);
+        }
+
+        if advanced {
+            self.snd_una = ack;
+            self.timestamps.last_ack_time = now;
+            self.retries = 0;
+            self.dup_acks = 0;
+            self.rto_deadline = if self.retransmit_queue.is_empty() {
+                None
+            } else {
+                Some(now + self.rtt.rto)
+            };
+            self.end_probe(ack, now);
+            actions.push(TcpAction::CanSendData);
+        } else if duplicate {
+            self.dup_acks += 1;
+        }
+
+        if let Some(point) = self.recovery_point {
+            if seq_ge(self.snd_una, point) {
+                self.recovery_point = None;
+                self.congestion.on_recovery_end(self.mss);
+            } else if advanced && !self.sack_permitted {
+                // NewReno partial ACK: the next hole is lost too (RFC 6582)
+                if let Some(front) = self.retransmit_queue.front_mut() {
+                    front.lost = true;
+                }
+            }
+        }
+        self.detect_losses(now);
+        self.start_recovery(flight, now);
+
+        if delivery.bytes > 0 {
+            let sample = AckSample {
+                now,
+                acked: delivery.bytes,
+                in_flight: self.pipe(),
+                rtt: delivery.rtt,
+                delivery_rate: prior.as_ref().and_then(|sent| self.delivery_rate(sent, now)),
+                app_limited: prior.as_ref().is_some_and(|sent| sent.app_limited),
+                prior_delivered: prior.as_ref().map_or(0, |sent| sent.delivered),
+                delivered: self.delivered,
+                in_recovery: self.recovery_point.is_some(),
+            };
+            self.congestion.on_ack(&sample, self.mss);
+        }
+        self.arm_probe(now);
     }
 
-    /// Handle packets in FIN_WAIT2 state
-    fn handle_fin_wait2(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::FIN) {
-            // Received FIN, transition to TIME_WAIT
-            self.state = TcpState::TimeWait;
-            self.local_ack = packet.seq_num() + 1;
+    /// Delivery rate since `sent` left, in bytes per second
+    fn delivery_rate(&self, sent: &SentSegment, now: u64) -> Option<u64> {
+        let interval = now.checked_sub(sent.delivered_time).filter(|&ms| ms > 0)?;
+        Some((self.delivered - sent.delivered) * 1000 / interval)
+    }
 
-            TcpAction::SendAck
-        } else if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else {
-            TcpAction::None
+    /// Take the most recently sent delivered segment into RACK (RFC 8985 6.2)
+    fn rack_update(&mut self, sent: &SentSegment, now: u64) {
+        let segment = &sent.segment;
+        let rtt = now.saturating_sub(segment.last_tx_time);
+        // An ACK for the original transmission says nothing about the retransmission
+        if segment.retransmit_count > 0 && rtt < self.rtt.min_rtt {
+            return;
+        }
+        self.rack.rtt = rtt;
+        // The send order stands in for RACK_sent_after: the clock ticks
+        // too coarsely to tell a retransmission from the segments around it
+        if self.rack.newest.is_none_or(|(order, _)| sent.order > order) {
+            self.rack.newest = Some((sent.order, segment.end()));
         }
     }
 
-    /// Handle packets in CLOSE_WAIT state
-    fn handle_close_wait(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else {
-            TcpAction::None
+    /// Reordering window (RFC 8985 6.2 step 4)
+    fn reorder_window(&self) -> u64 {
+        let sacked = self.retransmit_queue.iter().filter(|sent| sent.sacked).count() as u32;
+        if !self.rack.reordering_seen && (self.recovery_point.is_some() || sacked >= DUP_ACK_THRESHOLD) {
+            return 0;
+        }
+        if self.rtt.min_rtt == u64::MAX {
+            return 0;
         }
+        (self.rtt.min_rtt / 4).min(self.rtt.srtt)
     }
 
-    /// Handle packets in CLOSING state
-    fn handle_closing(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::ACK) {
-            if packet.ack_num() == self.local_seq + 1 {
-                // ACK for our FIN, transition to TIME_WAIT
-                self.state = TcpState::TimeWait;
-                TcpAction::None
+    /// Mark segments lost and arm the reordering timer for the undecided
+    ///
+    /// A segment is lost once something sent after it was delivered and it
+    /// stayed unacknowledged for an RTT plus the reordering window. Peers
+    /// without SACK fall back to three duplicate ACKs.
+    fn detect_losses(&mut self, now: u64) {
+        if !self.sack_permitted && self.dup_acks == DUP_ACK_THRESHOLD
+            && let Some(front) = self.retransmit_queue.front_mut()
+        {
+            front.lost = true;
+        }
+
+        self.reorder_deadline = None;
+        let Some((rack_order, _)) = self.rack.newest else {
+            return;
+        };
+        let wait = self.rack.rtt + self.reorder_window();
+        let mut deadline: Option<u64> = None;
+        for sent in self.retransmit_queue.iter_mut() {
+            let segment = &sent.segment;
+            if sent.sacked || sent.lost || sent.order > rack_order {
+                continue;
+            }
+            let lost_at = segment.last_tx_time + wait;
+            if now >= lost_at {
+                sent.lost = true;
             } else {
-                TcpAction::None
+                deadline = Some(deadline.map_or(lost_at, |d| d.min(lost_at)));
             }
-        } else if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else {
-            TcpAction::None
         }
+        self.reorder_deadline = deadline;
     }
 
-    /// Handle packets in LAST_ACK state
-    fn handle_last_ack(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::ACK) {
-            if packet.ack_num() == self.local_seq + 1 {
-                // ACK for our FIN, connection closed
-                self.state = TcpState::Closed;
-                TcpAction::ConnectionClosed
-            } else {
-                TcpAction::None
-            }
-        } else if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
-        } else {
-            TcpAction::None
+    /// Enter loss recovery if segments were marked lost
+    ///
+    /// `flight` is the data outstanding before the loss was noticed.
+    fn start_recovery(&mut self, flight: u32, now: u64) {
+        if self.recovery_point.is_some() || !self.retransmit_queue.iter().any(|sent| sent.lost) {
+            return;
         }
+        self.recovery_point = Some(self.snd_nxt);
+        self.force_retransmit = true;
+        self.probe_deadline = None;
+        self.congestion.on_loss(LossEvent::Detected, flight, self.mss, now);
     }
 
-    /// Handle packets in TIME_WAIT state
-    fn handle_time_wait(&mut self, packet: &TcpPacket) -> TcpAction {
-        if packet.has_flag(tcp_flags::RST) {
-            self.state = TcpState::Closed;
-            TcpAction::ConnectionReset
+    /// Arm the tail loss probe timer (RFC 8985 7.2)
+    fn arm_probe(&mut self, now: u64) {
+        self.probe_deadline = None;
+        if !self.sack_permitted
+            || self.recovery_point.is_some()
+            || self.probe.is_some()
+            || self.retransmit_queue.is_empty()
+            || !matches!(self.state, TcpState::Established | TcpState::CloseWait)
+        {
+            return;
+        }
+        let pto = if self.rtt.min_rtt == u64::MAX {
+            TLP_INITIAL_PTO_MS
+        } else if self.retransmit_queue.len() == 1 {
+            2 * self.rtt.srtt + TLP_MAX_ACK_DELAY_MS
         } else {
-            // Ignore other packets in TIME_WAIT
-            TcpAction::None
+            2 * self.rtt.srtt
+        };
+        let deadline = now + pto.max(TLP_MIN_PTO_MS);
+        if self.rto_deadline.is_none_or(|rto| deadline < rto) {
+            self.probe_deadline = Some(deadline);
         }
     }
 
-    /// Initiate active open (connect)
-    pub fn active_open(&mut self, _remote_addr: Ipv4Addr, _remote_port: u16) -> TcpAction {
-        if self.state != TcpState::Closed {
-            return TcpAction::Error;
+    /// Send a tail loss probe: new data if allowed, else the last segment
+    fn send_probe(&mut self, now: u64, actions: &mut Vec<TcpAction>) {
+        let len = self.send_mss().min(self.send_buffer.len());
+        let room = self.snd_wnd.saturating_sub(self.flight_size()) as usize;
+        if len > 0 && len <= room && self.can_send() {
+            self.send_data(len, now, actions);
+            self.probe = Some((self.snd_nxt, false));
+        } else if let Some(last) = self.retransmit_queue.len().checked_sub(1) {
+            self.retransmit(last, now, actions);
+            self.probe = Some((self.snd_nxt, true));
         }
+        self.rto_deadline = Some(now + self.rtt.rto);
+    }
 
-        self.state = TcpState::SynSent;
-        self.local_seq = Self::generate_initial_seq();
-
-        TcpAction::SendSyn
+    /// Close the probe episode once `ack` covers the probe (RFC 8985 7.4)
+    ///
+    /// Without D-SACK there is no telling whether a retransmitted probe
+    /// repaired a loss, so it counts as one.
+    fn end_probe(&mut self, ack: u32, now: u64) {
+        let Some((end, retransmission)) = self.probe else {
+            return;
+        };
+        if seq_lt(ack, end) {
+            return;
+        }
+        self.probe = None;
+        if retransmission && self.recovery_point.is_none() {
+            let flight = self.flight_size();
+            self.congestion.on_loss(LossEvent::Detected, flight, self.mss, now);
+            self.congestion.on_recovery_end(self.mss);
+        }
     }
 
-    /// Initiate passive open (listen)
-    pub fn passive_open(&mut self) -> TcpAction {
-        if self.state != TcpState::Closed {
-            return TcpAction::Error;
+    /// Update the send window from a segment (RFC 9293 SND.WL1/SND.WL2 check)
+    fn update_send_window(&mut self, packet: &TcpPacket, force: bool) {
+        let seq = packet.seq_num();
+        let ack = packet.ack_num();
+        if force || seq_lt(self.snd_wl1, seq) || (self.snd_wl1 == seq && seq_le(self.snd_wl2, ack)) {
+            self.snd_wnd = packet.header.window_size as u32;
+            self.snd_wl1 = seq;
+            self.snd_wl2 = ack;
         }
+    }
 
-        self.state = TcpState::Listen;
-        TcpAction::None
+    /// Take the peer's SYN options: the smaller MSS and SACK if both offer it
+    ///
+    /// The congestion controller restarts with the initial window for the
+    /// agreed MSS.
+    fn apply_peer_options(&mut self, packet: &TcpPacket) {
+        let peer = packet.mss().map(u32::from).unwrap_or(DEFAULT_PEER_MSS);
+        self.mss = peer.min(self.local_mss as u32).max(1);
+        self.sack_permitted = packet.sack_permitted();
+        self.congestion = self.congestion.algorithm().create(self.mss);
     }
 
-    /// Send data
-    pub fn send_data(&mut self, data: Vec<u8>) -> TcpAction {
-        if self.state != TcpState::Established {
-            return TcpAction::Error;
+    /// Resend lost segments, send queued data, then our FIN once the buffer
+    /// has drained
+    pub fn output(&mut self, now: u64, actions: &mut Vec<TcpAction>) {
+        if !matches!(
+            self.state,
+            TcpState::Closed | TcpState::Listen | TcpState::SynSent | TcpState::SynReceived | TcpState::TimeWait
+        ) {
+            self.retransmit_lost(now, actions);
+        }
+
+        let can_send = match self.state {
+            TcpState::Established | TcpState::CloseWait => true,
+            TcpState::FinWait1 | TcpState::LastAck => self.fin_seq.is_none(),
+            _ => false,
+        };
+        if !can_send {
+            return;
         }
 
-        if data.is_empty() {
-            return TcpAction::None;
+        let mut sent = false;
+        while !self.send_buffer.is_empty() {
+            let flight = self.flight_size();
+            let window_room = self.snd_wnd.saturating_sub(flight);
+            let cwnd_room = self.congestion.cwnd().saturating_sub(self.pipe());
+            let max_len = self.send_mss();
+            let len = max_len.min(self.send_buffer.len()).min(window_room.min(cwnd_room) as usize);
+            if len == 0 {
+                // Zero window: the persist timer probes it
+                if self.snd_wnd == 0 && self.rto_deadline.is_none() {
+                    self.rto_deadline = Some(now + self.rtt.rto);
+                }
+                break;
+            }
+            if self.nagle && len < max_len && flight > 0 && !self.fin_queued {
+                break;
+            }
+            if !self.pace(len, now) {
+                break;
+            }
+            self.send_data(len, now, actions);
+            sent = true;
+        }
+        if sent {
+            self.arm_probe(now);
         }
 
-        let max_send = self.flow_control.get_send_window().min(self.congestion.get_send_window());
-        if max_send == 0 || data.len() as u32 > max_send {
-            return TcpAction::None; // Window is full
+        if self.fin_queued && self.fin_seq.is_none() && self.send_buffer.is_empty() {
+            self.fin_seq = Some(self.snd_nxt);
+            self.send_control(tcp_flags::FIN | tcp_flags::ACK, now, actions);
         }
+    }
 
-        // Add to retransmission queue
-        let segment = TcpSegment {
-            seq: self.local_seq,
-            ack: self.local_ack,
-            flags: tcp_flags::ACK | tcp_flags::PSH,
-            window: self.local_window,
-            data: data.clone(),
-            retransmit_count: 0,
-            last_tx_time: Self::current_time(),
-            rto: self.congestion.rtt_estimator.rto as u64,
+    /// Whether the pacer lets `len` bytes go now; holds them back otherwise
+    fn pace(&mut self, len: usize, now: u64) -> bool {
+        let Some(rate) = self.congestion.pacing_rate().filter(|&rate| rate > 0) else {
+            return true;
+        };
+        if now < self.pacing_next {
+            self.pacing_deadline = Some(self.pacing_next);
+            return false;
+        }
+        self.pacing_next = now + len as u64 * 1000 / rate;
+        true
+    }
+
+    /// Send the next `len` bytes of the send buffer as one segment
+    fn send_data(&mut self, len: usize, now: u64, actions: &mut Vec<TcpAction>) {
+        let data: Vec<u8> = self.send_buffer.drain(..len).collect();
+        let flags = if self.send_buffer.is_empty() {
+            tcp_flags::ACK | tcp_flags::PSH
+        } else {
+            tcp_flags::ACK
         };
+        let segment = self.segment(self.snd_nxt, flags, data, now);
+        self.snd_nxt = self.snd_nxt.wrapping_add(len as u32);
+        self.queue_for_retransmit(segment.clone(), now);
+        self.emit(segment, actions);
+    }
 
-        self.retransmit_queue.push_back(segment);
-        self.local_seq += data.len() as u32;
-        self.flow_control.on_data_sent(data.len() as u32);
+    /// Send a SYN, SYN-ACK or FIN, each taking one sequence number
+    fn send_control(&mut self, flags: u8, now: u64, actions: &mut Vec<TcpAction>) {
+        let segment = self.segment(self.snd_nxt, flags, Vec::new(), now);
+        self.snd_nxt = self.snd_nxt.wrapping_add(1);
+        self.queue_for_retransmit(segment.clone(), now);
+        self.emit(segment, actions);
+    }
 
-        TcpAction::SendData(data)
+    /// Send a bare ACK
+    fn send_ack(&mut self, now: u64, actions: &mut Vec<TcpAction>) {
+        let segment = self.segment(self.snd_nxt, tcp_flags::ACK, Vec::new(), now);
+        self.emit(segment, actions);
     }
 
-    /// Close connection
-    pub fn close(&mut self) -> TcpAction {
-        match self.state {
-            TcpState::Established => {
-                self.state = TcpState::FinWait1;
-                TcpAction::SendFin
+    /// Build a segment carrying the current acknowledgment and window
+    ///
+    /// A SYN announces our MSS and offers SACK (a SYN-ACK only if the peer
+    /// offered it); later segments report out-of-order data in SACK blocks.
+    fn segment(&self, seq: u32, flags: u8, data: Vec<u8>, now: u64) -> TcpSegment {
+        let options = if flags & tcp_flags::SYN != 0 {
+            let mut options = MssOption::new(self.local_mss).to_option().to_bytes();
+            if flags & tcp_flags::ACK == 0 || self.sack_permitted {
+                options.extend_from_slice(&[TcpOptionKind::Nop as u8, TcpOptionKind::Nop as u8]);
+                options.extend(TcpOption::new(TcpOptionKind::SackPermitted, Vec::new()).to_bytes());
             }
-            TcpState::CloseWait => {
-                self.state = TcpState::LastAck;
-                TcpAction::SendFin
-            }
-            TcpState::Listen => {
-                self.state = TcpState::Closed;
-                TcpAction::ConnectionClosed
+            options
+        } else if flags & tcp_flags::ACK != 0 {
+            self.sack_option()
+        } else {
+            Vec::new()
+        };
+        TcpSegment {
+            seq,
+            ack: if flags & tcp_flags::ACK != 0 { self.rcv_nxt } else { 0 },
+            flags,
+            window: self.receive_window() as u16,
+            options,
+            data,
+            retransmit_count: 0,
+            last_tx_time: now,
+        }
+    }
+
+    /// SACK option describing the out-of-order queue (RFC 2018 section 4)
+    ///
+    /// The block holding the newest segment goes first; the rest follow in
+    /// sequence order.
+    fn sack_option(&self) -> Vec<u8> {
+        if !self.sack_permitted || self.out_of_order.is_empty() {
+            return Vec::new();
+        }
+        let mut blocks: Vec<SackBlock> = Vec::new();
+        for segment in &self.out_of_order {
+            let end = segment.seq.wrapping_add(segment.data.len() as u32 + segment.fin as u32);
+            match blocks.last_mut() {
+                Some(block) if seq_le(segment.seq, block.right) => {
+                    if seq_gt(end, block.right) {
+                        block.right = end;
+                    }
+                }
+                _ => blocks.push(SackBlock::new(segment.seq, end)),
             }
-            _ => TcpAction::Error,
         }
+        if let Some(newest) = self.last_out_of_order
+            && let Some(pos) = blocks
+                .iter()
+                .position(|block| seq_le(block.left, newest) && seq_lt(newest, block.right))
+        {
+            let block = blocks.remove(pos);
+            blocks.insert(0, block);
+        }
+        blocks.truncate(SackOption::MAX_BLOCKS);
+
+        let mut options = vec![TcpOptionKind::Nop as u8, TcpOptionKind::Nop as u8];
+        options.extend(SackOption::new(blocks).to_option().to_bytes());
+        options
     }
 
-    /// Get current state
-    pub fn state(&self) -> TcpState {
-        self.state
+    /// Payload that fits in a segment next to the SACK option
+    fn send_mss(&self) -> usize {
+        (self.mss as usize).saturating_sub(self.sack_option().len()).max(1)
     }
 
-    /// Check if connection is established
-    pub fn is_established(&self) -> bool {
-        self.state == TcpState::Established
+    /// Track a sent segment until it is acknowledged
+    fn queue_for_retransmit(&mut self, segment: TcpSegment, now: u64) {
+        // Nothing in flight: rate samples start from now
+        if self.retransmit_queue.is_empty() {
+            self.delivered_time = now;
+        }
+        self.transmissions += 1;
+        self.retransmit_queue.push_back(SentSegment {
+            segment,
+            sacked: false,
+            lost: false,
+            order: self.transmissions,
+            delivered: self.delivered,
+            delivered_time: self.delivered_time,
+            app_limited: self.send_buffer.is_empty(),
+        });
+        if self.rto_deadline.is_none() {
+            self.rto_deadline = Some(now + self.rtt.rto);
+        }
     }
 
-    /// Check if connection is closed
-    pub fn is_closed(&self) -> bool {
-        self.state == TcpState::Closed
+    /// Hand a segment to the owner for transmission
+    fn emit(&mut self, segment: TcpSegment, actions: &mut Vec<TcpAction>) {
+        self.ack_pending = false;
+        self.last_window = segment.window as u32;
+        self.timestamps.last_send_time = segment.last_tx_time;
+        actions.push(TcpAction::Send(segment));
     }
 
-    /// Check if we can send data
-    pub fn can_send(&self) -> bool {
-        self.state == TcpState::Established &&
-        self.flow_control.can_send() &&
-        self.congestion.can_send()
+    /// Resend the oldest unacknowledged segment
+    fn retransmit_front(&mut self, now: u64, actions: &mut Vec<TcpAction>) {
+        self.retransmit(0, now, actions);
     }
 
-    /// Get retransmission timeout check
-    pub fn check_timeouts(&mut self) -> Vec<TcpAction> {
-        let mut actions = Vec::new();
-        let now = Self::current_time();
+    /// Resend segments marked lost while the pipe is below the window
+    fn retransmit_lost(&mut self, now: u64, actions: &mut Vec<TcpAction>) {
+        loop {
+            let force = core::mem::take(&mut self.force_retransmit);
+            if !force && self.pipe() >= self.congestion.cwnd() {
+                break;
+            }
+            let Some(index) = self.retransmit_queue.iter().position(|sent| sent.lost) else {
+                break;
+            };
+            self.retransmit(index, now, actions);
+        }
+    }
 
-        // Check retransmission timeouts
-        for segment in &mut self.retransmit_queue {
-            if now - segment.last_tx_time >= segment.rto {
-                segment.retransmit_count += 1;
-                segment.last_tx_time = now;
-                segment.rto = core::cmp::min(segment.rto * 2, 60000); // Exponential backoff, max 60s
+    /// Resend the segment at `index` of the retransmission queue
+    fn retransmit(&mut self, index: usize, now: u64, actions: &mut Vec<TcpAction>) {
+        let ack = self.rcv_nxt;
+        let window = self.receive_window() as u16;
+        let (delivered, delivered_time) = (self.delivered, self.delivered_time);
+        self.transmissions += 1;
+        let order = self.transmissions;
+        let Some(sent) = self.retransmit_queue.get_mut(index) else {
+            return;
+        };
+        sent.lost = false;
+        sent.order = order;
+        sent.delivered = delivered;
+        sent.delivered_time = delivered_time;
+        let segment = &mut sent.segment;
+        segment.retransmit_count += 1;
+        segment.last_tx_time = now;
+        if segment.flags & tcp_flags::ACK != 0 {
+            segment.ack = ack;
+        }
+        segment.window = window;
+        // SACK blocks from the first transmission are stale
+        if segment.flags & tcp_flags::SYN == 0 {
+            segment.options.clear();
+        }
+        let segment = segment.clone();
+        self.emit(segment, actions);
+    }
 
-                actions.push(TcpAction::Retransmit(segment.data.clone()));
+    /// Read received data into `buf`
+    ///
+    /// Sends a window update when reading opened the window noticeably.
+    pub fn read(&mut self, buf: &mut [u8], now: u64) -> (usize, Vec<TcpAction>) {
+        let mut actions = Vec::new();
+        let n = buf.len().min(self.recv_buffer.len());
+        for (dst, byte) in buf.iter_mut().zip(self.recv_buffer.drain(..n)) {
+            *dst = byte;
+        }
+
+        if n > 0 && matches!(self.state, TcpState::Established | TcpState::FinWait1 | TcpState::FinWait2) {
+            let threshold = self.mss.min(self.recv_capacity as u32 / 2).max(1);
+            if self.receive_window().saturating_sub(self.last_window) >= threshold {
+                self.send_ack(now, &mut actions);
             }
         }
+        (n, actions)
+    }
+
+    /// Queue data for sending; returns the bytes accepted
+    pub fn send(&mut self, data: &[u8], now: u64) -> (usize, Vec<TcpAction>) {
+        let mut actions = Vec::new();
+        if !self.can_send() {
+            return (0, actions);
+        }
+        let n = data.len().min(self.send_space());
+        self.send_buffer.extend(data[..n].iter().copied());
+        self.output(now, &mut actions);
+        (n, actions)
+    }
 
-        // Check TIME_WAIT timeout
-        if self.state == TcpState::TimeWait {
-            if now - self.timestamps.connection_time >= 120 { // 2 minutes TIME_WAIT
-                self.state = TcpState::Closed;
+    /// Close the sending side; the FIN follows any queued data
+    pub fn close(&mut self, now: u64) -> Vec<TcpAction> {
+        let mut actions = Vec::new();
+        match self.state {
+            TcpState::SynSent => {
+                self.enter_closed();
                 actions.push(TcpAction::ConnectionClosed);
             }
+            TcpState::SynReceived | TcpState::Established => {
+                self.state = TcpState::FinWait1;
+                self.fin_queued = true;
+                self.output(now, &mut actions);
+            }
+            TcpState::CloseWait => {
+                self.state = TcpState::LastAck;
+                self.fin_queued = true;
+                self.output(now, &mut actions);
+            }
+            _ => {}
         }
+        actions
+    }
 
+    /// Drop the connection, resetting the peer if it is synchronized
+    pub fn abort(&mut self) -> Vec<TcpAction> {
+        let mut actions = Vec::new();
+        match self.state {
+            TcpState::Closed | TcpState::Listen => return actions,
+            TcpState::SynSent | TcpState::TimeWait => {}
+            _ => actions.push(TcpAction::Send(TcpSegment::reset(self.snd_nxt))),
+        }
+        self.enter_closed();
+        actions.push(TcpAction::ConnectionClosed);
         actions
     }
-}
 
-/// TCP action returned by state machine
-#[derive(Debug, Clone)]
-pub enum TcpAction {
-    /// No action
-    None,
-    /// Send SYN
-    SendSyn,
-    /// Send SYN-ACK
-    SendSynAck,
-    /// Send ACK
-    SendAck,
-    /// Send RST
-    SendRst,
-    /// Send FIN
-    SendFin,
-    /// Send data
-    SendData(Vec<u8>),
-    /// Retransmit data
-    Retransmit(Vec<u8>),
-    /// Data received
-    DataReceived(Vec<u8>),
-    /// Can send more data
-    CanSendData,
-    /// Connection established
-    ConnectionEstablished,
-    /// Connection closed
-    ConnectionClosed,
-    /// Connection reset
-    ConnectionReset,
-    /// Error occurred
-    Error,
-}
+    /// Run the reordering, pacing, probe, retransmission, persist and
+    /// TIME-WAIT timers
+    pub fn check_timeouts(&mut self, now: u64) -> Vec<TcpAction> {
+        let mut actions = Vec::new();
 
-impl TcpCongestionControl {
-    /// Create new congestion control state
-    pub fn new() -> Self {
-        Self {
-            cwnd: 10 * 1460, // Initial congestion window (10 * MSS)
-            ssthresh: u32::MAX,
-            state: CongestionState::SlowStart,
-            dup_ack_count: 0,
-            last_ack: 0,
-            rtt_estimator: RttEstimator::new(),
-        }
-    }
-
-    /// Handle new ACK
-    pub fn on_ack_received(&mut self, ack: u32) {
-        if ack > self.last_ack {
-            // New ACK received
-            let acknowledged = ack - self.last_ack;
-            self.last_ack = ack;
-            self.dup_ack_count = 0;
-
-            match self.state {
-                CongestionState::SlowStart => {
-                    self.cwnd += acknowledged;
-                    if self.cwnd >= self.ssthresh {
-                        self.state = CongestionState::CongestionAvoidance;
-                    }
-                }
-                CongestionState::CongestionAvoidance => {
-                    self.cwnd += (acknowledged * 1460) / self.cwnd; // AIMD
-                }
-                CongestionState::FastRecovery => {
-                    self.cwnd += acknowledged;
-                    self.state = CongestionState::CongestionAvoidance;
-                }
-                CongestionState::FastRetransmit => {
-                    self.state = CongestionState::FastRecovery;
-                }
+        if self.state == TcpState::TimeWait {
+            if self.time_wait_until.is_some_and(|deadline| now >= deadline) {
+                self.enter_closed();
+                actions.push(TcpAction::ConnectionClosed);
             }
-        } else if ack == self.last_ack {
-            // Duplicate ACK
-            self.dup_ack_count += 1;
-            if self.dup_ack_count == 3 {
-                // Fast retransmit
-                self.state = CongestionState::FastRetransmit;
-                self.ssthresh = self.cwnd / 2;
-                self.cwnd = self.ssthresh + 3 * 1460;
+            return actions;
+        }
+
+        if self.reorder_deadline.is_some_and(|deadline| now >= deadline) {
+            let flight = self.flight_size();
+            self.detect_losses(now);
+            self.start_recovery(flight, now);
+            self.output(now, &mut actions);
+        }
+
+        if self.pacing_deadline.is_some_and(|deadline| now >= deadline) {
+            self.pacing_deadline = None;
+            self.output(now, &mut actions);
+        }
+
+        if self.probe_deadline.is_some_and(|deadline| now >= deadline) {
+            self.probe_deadline = None;
+            self.send_probe(now, &mut actions);
+        }
+
+        let Some(deadline) = self.rto_deadline else {
+            return actions;
+        };
+        if now < deadline {
+            return actions;
+        }
+
+        if self.retransmit_queue.is_empty() {
+            self.rto_deadline = None;
+            if self.snd_wnd == 0 && !self.send_buffer.is_empty() {
+                // Probe the zero window with one byte
+                self.send_data(1, now, &mut actions);
             }
+            return actions;
         }
+
+        let handshake = matches!(self.state, TcpState::SynSent | TcpState::SynReceived);
+        // Window probes are not counted against the retry limit
+        if handshake || self.snd_wnd != 0 {
+            self.retries += 1;
+        }
+        let limit = if handshake { MAX_SYN_RETRIES } else { MAX_RETRIES };
+        if self.retries > limit {
+            self.enter_closed();
+            actions.push(TcpAction::TimedOut);
+            return actions;
+        }
+
+        let flight = self.flight_size();
+        self.congestion.on_loss(LossEvent::Timeout, flight, self.mss, now);
+        self.rtt.backoff();
+        // Everything not selectively acknowledged is presumed lost (RFC 6675 5.1)
+        for sent in self.retransmit_queue.iter_mut() {
+            sent.lost = !sent.sacked;
+        }
+        self.recovery_point = Some(self.snd_nxt);
+        self.reorder_deadline = None;
+        self.probe_deadline = None;
+        self.probe = None;
+        let first = self.retransmit_queue.iter().position(|sent| sent.lost).unwrap_or(0);
+        self.retransmit(first, now, &mut actions);
+        self.rto_deadline = Some(now + self.rtt.rto);
+        actions
     }
 
-    /// Handle data received
-    pub fn on_data_received(&mut self, _seq: u32) {
-        // Update RTT measurement if we have timing information
-        self.rtt_estimator.update_measurement(1.0); // Placeholder RTT
+    /// Enter TIME-WAIT
+    fn enter_time_wait(&mut self, now: u64) {
+        self.state = TcpState::TimeWait;
+        self.time_wait_until = Some(now + TIME_WAIT_MS);
+        self.retransmit_queue.clear();
+        self.clear_timers();
     }
 
-    /// Check if we can send
-    pub fn can_send(&self) -> bool {
-        self.cwnd > 0
+    /// Enter CLOSED, dropping everything still to be sent
+    fn enter_closed(&mut self) {
+        self.state = TcpState::Closed;
+        self.retransmit_queue.clear();
+        self.send_buffer.clear();
+        self.out_of_order.clear();
+        self.clear_timers();
+        self.time_wait_until = None;
     }
 
-    /// Get send window
-    pub fn get_send_window(&self) -> u32 {
-        self.cwnd
+    /// Stop every timer but TIME-WAIT's
+    fn clear_timers(&mut self) {
+        self.rto_deadline = None;
+        self.reorder_deadline = None;
+        self.probe_deadline = None;
+        self.pacing_deadline = None;
+        self.recovery_point = None;
+        self.probe = None;
     }
 
-    /// Handle packet loss
-    pub fn on_packet_loss(&mut self) {
-        self.ssthresh = self.cwnd / 2;
-        self.cwnd = 1460; // Reset to 1 MSS
-        self.state = CongestionState::SlowStart;
+    /// Whether `seq` lies in the receive window of size `wnd`
+    fn in_window(&self, seq: u32, wnd: u32) -> bool {
+        seq_le(self.rcv_nxt, seq) && seq_lt(seq, self.rcv_nxt.wrapping_add(wnd))
     }
-}
 
-impl RttEstimator {
-    /// Create new RTT estimator
-    pub fn new() -> Self {
-        Self {
-            srtt: 1000,      // 1 second initial
-            rttvar: 500,      // 0.5 second initial variance
-            rto: 3000,        // 3 second initial timeout
-            min_rtt: u32::MAX,
+    /// Bytes sent and not yet acknowledged
+    fn flight_size(&self) -> u32 {
+        self.snd_nxt.wrapping_sub(self.snd_una)
+    }
+
+    /// Bytes presumed in the network: neither SACKed nor lost (RFC 6675)
+    fn pipe(&self) -> u32 {
+        let pipe: u32 = self
+            .retransmit_queue
+            .iter()
+            .filter(|sent| !sent.sacked && !sent.lost)
+            .map(|sent| sent.segment.seq_len())
+            .sum();
+        if self.sack_permitted {
+            pipe
+        } else {
+            // Without SACK each duplicate ACK stands for a segment that left
+            pipe.saturating_sub(self.dup_acks * self.mss)
         }
     }
 
-    /// Update RTT measurement
-    pub fn update_measurement(&mut self, rtt: f64) {
-        let rtt_ms = (rtt * 1000.0) as u32;
+    /// Receive window: free space in the receive buffer
+    pub fn receive_window(&self) -> u32 {
+        (self.recv_capacity.saturating_sub(self.recv_buffer.len()) as u32).min(MAX_RECEIVE_WINDOW)
+    }
 
-        if rtt_ms < self.min_rtt {
-            self.min_rtt = rtt_ms;
-        }
+    /// Free space in the send buffer
+    pub fn send_space(&self) -> usize {
+        let in_flight: usize = self.retransmit_queue.iter().map(|s| s.segment.data.len()).sum();
+        self.send_capacity.saturating_sub(self.send_buffer.len() + in_flight)
+    }
 
-        // Jacobson/Karels algorithm
-        let rtt_diff = if self.srtt > rtt_ms {
-            self.srtt - rtt_ms
-        } else {
-            rtt_ms - self.srtt
-        };
+    /// Bytes waiting to be read
+    pub fn available(&self) -> usize {
+        self.recv_buffer.len()
+    }
 
-        self.rttvar = (3 * self.rttvar + rtt_diff) / 4;
-        self.srtt = (7 * self.srtt + rtt_ms) / 8;
+    /// Whether the peer has closed its sending side
+    pub fn peer_closed(&self) -> bool {
+        self.fin_received
+    }
 
-        // Calculate RTO
-        self.rto = self.srtt + 4 * self.rttvar;
-        self.rto = core::cmp::max(self.rto, 1000); // Minimum 1 second
-        self.rto = core::cmp::min(self.rto, 60000); // Maximum 60 seconds
+    /// MSS used when sending
+    pub fn mss(&self) -> u32 {
+        self.mss
     }
-}
 
-impl TcpFlowControl {
-    /// Create new flow control state
-    pub fn new() -> Self {
-        Self {
-            advertised_window: 65535,
-            effective_window: 0,
-            outstanding_bytes: 0,
-            max_window: 65535,
-        }
+    /// Round-trip time estimator
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    /// Congestion control algorithm
+    pub fn congestion(&self) -> &dyn CongestionControl {
+        self.congestion.as_ref()
     }
 
-    /// Handle ACK received
-    pub fn on_ack_received(&mut self, bytes_acked: u32) {
-        self.outstanding_bytes = self.outstanding_bytes.saturating_sub(bytes_acked);
-        self.update_effective_window();
+    /// Whether SACK was negotiated
+    pub fn sack_permitted(&self) -> bool {
+        self.sack_permitted
     }
 
-    /// Handle data sent
-    pub fn on_data_sent(&mut self, bytes_sent: u32) {
-        self.outstanding_bytes += bytes_sent;
-        self.update_effective_window();
+    /// Whether loss recovery is in progress
+    pub fn in_recovery(&self) -> bool {
+        self.recovery_point.is_some()
     }
 
-    /// Update remote window
-    pub fn update_remote_window(&mut self, remote_window: u16) {
-        self.advertised_window = remote_window;
-        self.update_effective_window();
+    /// Connection timestamps
+    pub fn timestamps(&self) -> &TcpTimestamps {
+        &self.timestamps
     }
 
-    /// Update effective window
-    fn update_effective_window(&mut self) {
-        self.effective_window = (self.advertised_window as u32).saturating_sub(self.outstanding_bytes);
+    /// Get current state
+    pub fn state(&self) -> TcpState {
+        self.state
     }
 
-    /// Check if we can send
+    /// Check if connection is established
+    pub fn is_established(&self) -> bool {
+        self.state == TcpState::Established
+    }
+
+    /// Check if connection is closed
+    pub fn is_closed(&self) -> bool {
+        self.state == TcpState::Closed
+    }
+
+    /// Check if the application may queue data
     pub fn can_send(&self) -> bool {
-        self.effective_window > 0
+        matches!(self.state, TcpState::Established | TcpState::CloseWait) && !self.fin_queued
     }
+}
 
-    /// Get send window
-    pub fn get_send_window(&self) -> u32 {
-        self.effective_window
+impl RttEstimator {
+    /// Create new RTT estimator
+    pub fn new() -> Self {
+        Self {
+            srtt: 0,
+            rttvar: 0,
+            rto: INITIAL_RTO_MS,
+            min_rtt: u64::MAX,
+        }
     }
-}
\ No newline at end of file
+
+    /// Update RTT measurement (RFC 6298)
+    pub fn update_measurement(&mut self, rtt_ms: u64) {
+        if self.min_rtt == u64::MAX {
+            self.srtt = rtt_ms;
+            self.rttvar = rtt_ms / 2;
+        } else {
+            // Jacobson/Karels algorithm
+            let rtt_diff = self.srtt.abs_diff(rtt_ms);
+            self.rttvar = (3 * self.rttvar + rtt_diff) / 4;
+            self.srtt = (7 * self.srtt + rtt_ms) / 8;
+        }
+        self.min_rtt = self.min_rtt.min(rtt_ms);
+
+        self.rto = (self.srtt + 4 * self.rttvar).clamp(MIN_RTO_MS, MAX_RTO_MS);
+    }
+
+    /// Double the timeout after a retransmission
+    pub fn backoff(&mut self) {
+        self.rto = (self.rto * 2).min(MAX_RTO_MS);
+    }
+}
+
+impl Default for RttEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -9,8 +9,19 @@ use alloc::vec::Vec;
 
 use crate::tests::{TestResult, TestSuite, assert_eq, assert_ne, assert_true};
 use crate::net::{
-    socket::{Socket, SocketType, ProtocolFamily, SocketAddr},
+    socket::{Socket, SocketError, SocketType, ProtocolFamily, SocketAddr},
+    tcp::{TcpPacket, TcpState},
+    tcp::manager::{SocketHandle, TcpConnection, TcpConnectionManager, TcpError, TcpOptions},
+    tcp::state::TIME_WAIT_MS,
+    tcp::congestion::{
+        AckSample, Bbr, BbrMode, CongestionAlgorithm, CongestionControl, Cubic, LossEvent, NewReno,
+    },
     ipv4::Ipv4Addr,
+    ipv6::{self, IpAddr, Ipv6Addr, Ipv6Packet, Ipv6Reassembler, ExtensionChain},
+    icmpv6::{Icmpv6Packet, NdMessage, NdOption},
+    ndp::{NeighborCache, NeighborState},
+    route6::Routing6Table,
+    device::MacAddr,
     interface::InterfaceConfig,
     configure_interface, list_interfaces,
 };
@@ -35,9 +46,25 @@ impl TestSuite for NetworkTests {
             test_loopback_configuration(),
             test_socket_creation(),
             test_tcp_socket_operations(),
+            test_tcp_connection_establishment(),
+            test_tcp_data_transmission(),
+            test_tcp_reuse_addr(),
+            test_tcp_connection_refused(),
+            test_tcp_congestion_selection(),
+            test_tcp_congestion_algorithms(),
+            test_tcp_sack_recovery(),
+            test_tcp_tail_loss_probe(),
+            test_tcp_loopback_sockets(),
             test_udp_socket_operations(),
             test_interface_configuration(),
             test_network_statistics(),
+            test_ipv6_address_format(),
+            test_ipv6_extension_headers(),
+            test_ipv6_reassembly(),
+            test_icmpv6_neighbor_discovery(),
+            test_neighbor_cache(),
+            test_ipv6_routing(),
+            test_dual_stack_socket_addr(),
         ]
     }
 }
@@ -83,6 +110,7 @@ fn test_tcp_socket_operations() -> TestResult {
     let mut tcp_socket = crate::net::socket::TcpSocketWrapper::new(
         crate::net::socket::SocketOptions::new()
     );
+    tcp_socket.set_nonblocking(true);
 
     // Test binding
     let addr = SocketAddr::new_ipv4_from_octets(127, 0, 0, 1, 8080);
@@ -100,9 +128,11 @@ fn test_tcp_socket_operations() -> TestResult {
     // Test accept (should return WouldBlock since no pending connections)
     match tcp_socket.accept() {
         Ok(_) => return TestResult::Fail("TCP socket accept should fail with no connections"),
-        Err(_) => {}, // Expected
+        Err(SocketError::WouldBlock) => {}, // Expected
+        Err(_) => return TestResult::Fail("TCP socket accept should report WouldBlock"),
     }
 
+    let _ = tcp_socket.close();
     TestResult::Pass
 }
 
@@ -134,6 +164,8 @@ fn test_interface_configuration() -> TestResult {
         ipv4_addr: Some(Ipv4Addr::new(192, 168, 1, 100)),
         ipv4_netmask: Some(Ipv4Addr::new(255, 255, 255, 0)),
         ipv4_gateway: Some(Ipv4Addr::new(192, 168, 1, 1)),
+        ipv6_addrs: vec![(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x100), 64)],
+        ipv6_gateway: None,
         is_up: false,
         mtu: Some(1500),
     };
@@ -178,68 +210,686 @@ fn test_network_statistics() -> TestResult {
     TestResult::Pass
 }
 
-/// Test TCP connection establishment
-pub fn test_tcp_connection_establishment() -> TestResult {
-    use crate::net::tcp::manager::{TcpConnectionManager, ConnectionId};
+/// Test IPv6 address parsing, formatting and SLAAC derivation
+fn test_ipv6_address_format() -> TestResult {
+    let addr = match Ipv6Addr::from_str("2001:db8::1") {
+        Ok(addr) => addr,
+        Err(_) => return TestResult::Fail("Failed to parse IPv6 address"),
+    };
+    assert_eq!(addr, Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), "Parsed address mismatch")?;
+    assert_eq!(format!("{}", addr), "2001:db8::1", "Address should use the compressed form")?;
+    assert_eq!(format!("{}", Ipv6Addr::UNSPECIFIED), "::", "Unspecified should format as ::")?;
+    assert_true!(Ipv6Addr::from_str("1::2::3").is_err(), "Two :: groups should be rejected")?;
+
+    let mapped = match Ipv6Addr::from_str("::ffff:192.168.1.1") {
+        Ok(addr) => addr,
+        Err(_) => return TestResult::Fail("Failed to parse IPv4-mapped address"),
+    };
+    assert_eq!(mapped.to_ipv4_mapped(), Some(Ipv4Addr::new(192, 168, 1, 1)),
+               "IPv4-mapped address should convert back")?;
 
-    let mut manager = TcpConnectionManager::new();
+    let mac = MacAddr::new([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+    let link_local = Ipv6Addr::link_local_from_mac(mac);
+    assert_eq!(link_local, Ipv6Addr::new(0xfe80, 0, 0, 0, 0x5054, 0x00ff, 0xfe12, 0x3456),
+               "EUI-64 link-local address mismatch")?;
+    assert_true!(link_local.is_link_local(), "Address should be link-local")?;
+    assert_eq!(link_local.solicited_node(), Ipv6Addr::new(0xff02, 0, 0, 0, 0, 1, 0xff12, 0x3456),
+               "Solicited-node group mismatch")?;
+
+    TestResult::Pass
+}
+
+/// Test IPv6 header round-trip and extension header walking
+fn test_ipv6_extension_headers() -> TestResult {
+    let src = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let dst = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+
+    // Hop-by-Hop (PadN) then Destination Options (PadN) then UDP
+    let mut payload = vec![ipv6::protocols::DEST_OPTS, 0, 1, 4, 0, 0, 0, 0];
+    payload.extend_from_slice(&[ipv6::protocols::UDP, 0, 1, 4, 0, 0, 0, 0]);
+    payload.extend_from_slice(&[0u8; 8]);
+    let packet = Ipv6Packet::new(src, dst, ipv6::protocols::HOP_BY_HOP, payload, 64);
+
+    let parsed = match Ipv6Packet::from_bytes(&packet.to_bytes()) {
+        Ok(parsed) => parsed,
+        Err(_) => return TestResult::Fail("Failed to parse IPv6 packet"),
+    };
+    assert_eq!(parsed.header.source_addr, src, "Source address mismatch")?;
+    assert_eq!(parsed.header.hop_limit, 64, "Hop limit mismatch")?;
+
+    let chain = match parsed.extensions() {
+        Ok(chain) => chain,
+        Err(_) => return TestResult::Fail("Failed to walk extension headers"),
+    };
+    assert_eq!(chain.upper_protocol, ipv6::protocols::UDP, "Upper protocol should be UDP")?;
+    assert_eq!(chain.payload_offset, 16, "UDP should start after two headers")?;
+
+    // Hop-by-Hop is only allowed first
+    let bad = [ipv6::protocols::HOP_BY_HOP, 0, 1, 4, 0, 0, 0, 0, 59, 0, 1, 4, 0, 0, 0, 0];
+    assert_true!(ExtensionChain::parse(ipv6::protocols::DEST_OPTS, &bad).is_err(),
+                 "Hop-by-Hop after another header should be rejected")?;
+
+    // Unknown option of type 10xxxxxx must be reported
+    let unknown = [ipv6::protocols::NO_NEXT_HEADER, 0, 0x80, 4, 0, 0, 0, 0];
+    assert_true!(ExtensionChain::parse(ipv6::protocols::DEST_OPTS, &unknown).is_err(),
+                 "Unknown option should be rejected")?;
+
+    TestResult::Pass
+}
 
-    // Create listening socket
-    let listen_result = manager.listen(
-        Ipv4Addr::new(127, 0, 0, 1),
-        8080,
-        crate::net::tcp::manager::TcpOptions::default()
+/// Test IPv6 fragmentation and reassembly
+fn test_ipv6_reassembly() -> TestResult {
+    let src = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let dst = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+    let data: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+    let packet = Ipv6Packet::new(src, dst, ipv6::protocols::UDP, data.clone(), 64);
+
+    let fragments = packet.fragment(ipv6::MIN_MTU, 7);
+    assert_true!(fragments.len() == 3, "3000 bytes should take three fragments")?;
+
+    let mut reassembler = Ipv6Reassembler::new();
+    let mut result = None;
+    // Deliver out of order
+    for fragment in fragments.iter().rev() {
+        let chain = match fragment.extensions() {
+            Ok(chain) => chain,
+            Err(_) => return TestResult::Fail("Fragment should parse"),
+        };
+        match reassembler.process(fragment, &chain, 0) {
+            Ok(Some(done)) => result = Some(done),
+            Ok(None) => {}
+            Err(_) => return TestResult::Fail("Reassembly failed"),
+        }
+    }
+
+    match result {
+        Some((next_header, payload)) => {
+            assert_eq!(next_header, ipv6::protocols::UDP, "Reassembled protocol mismatch")?;
+            assert_true!(payload == data, "Reassembled payload mismatch")?;
+        }
+        None => return TestResult::Fail("Packet was not reassembled"),
+    }
+    assert_eq!(reassembler.pending(), 0, "No packets should be pending")?;
+
+    TestResult::Pass
+}
+
+/// Test ICMPv6 checksums and Neighbor Discovery encoding
+fn test_icmpv6_neighbor_discovery() -> TestResult {
+    let src = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    let target = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    let mac = MacAddr::new([0x52, 0x54, 0x00, 0, 0, 1]);
+
+    let message = NdMessage::NeighborSolicitation {
+        target,
+        options: vec![NdOption::SourceLinkAddr(mac)],
+    };
+    let dst = target.solicited_node();
+    let bytes = message.to_packet().to_bytes(src, dst);
+
+    let packet = match Icmpv6Packet::from_bytes(src, dst, &bytes) {
+        Ok(packet) => packet,
+        Err(_) => return TestResult::Fail("ICMPv6 checksum should verify"),
+    };
+    match NdMessage::from_packet(&packet) {
+        Ok(decoded) => {
+            assert_true!(decoded == message, "Neighbor Solicitation should round-trip")?;
+            assert_true!(decoded.source_link_addr() == Some(mac), "Source link address mismatch")?;
+        }
+        Err(_) => return TestResult::Fail("Failed to decode Neighbor Solicitation"),
+    }
+
+    // A checksum over the wrong pseudo-header must fail
+    assert_true!(Icmpv6Packet::from_bytes(src, target, &bytes).is_err(),
+                 "Checksum with wrong destination should fail")?;
+
+    let echo = Icmpv6Packet::echo_request(1, 2, b"ping");
+    let processor = crate::net::icmpv6::Icmpv6Processor::new();
+    match processor.process_packet(target, src, &echo) {
+        Some(reply) => {
+            assert_eq!(reply.sequence(), 2, "Echo reply sequence mismatch")?;
+            assert_true!(reply.echo_data() == b"ping", "Echo reply data mismatch")?;
+        }
+        None => return TestResult::Fail("Echo request should be answered"),
+    }
+
+    TestResult::Pass
+}
+
+/// Test neighbor cache state transitions
+fn test_neighbor_cache() -> TestResult {
+    let addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+    let mac = MacAddr::new([0x52, 0x54, 0x00, 0, 0, 2]);
+    let mut cache = NeighborCache::new();
+
+    // Resolution starts Incomplete, a solicited advertisement makes it Reachable
+    assert_true!(cache.start_resolution(addr, 0), "Resolution should start")?;
+    assert_true!(cache.lookup(addr, 0).is_none(), "Incomplete entry has no address")?;
+    cache.update_from_advertisement(addr, Some(mac), true, false, false, 10);
+    assert_true!(cache.get(addr).map(|e| e.state) == Some(NeighborState::Reachable),
+                 "Entry should be reachable")?;
+
+    // After the reachable time it is Stale, and using it starts the delay timer
+    let later = 10 + crate::net::ndp::REACHABLE_TIME_MS + 1;
+    let _ = cache.timers(later);
+    assert_true!(cache.lookup(addr, later) == Some(mac), "Stale entry should still resolve")?;
+    assert_true!(cache.get(addr).map(|e| e.state) == Some(NeighborState::Delay),
+                 "Using a stale entry should move it to Delay")?;
+
+    // A solicitation with a new address replaces it and marks it Stale
+    let new_mac = MacAddr::new([0x52, 0x54, 0x00, 0, 0, 3]);
+    cache.update_from_solicitation(addr, new_mac, false, later);
+    assert_true!(cache.get(addr).map(|e| (e.mac, e.state)) == Some((Some(new_mac), NeighborState::Stale)),
+                 "Solicitation should update the entry")?;
+
+    TestResult::Pass
+}
+
+/// Test IPv6 longest-prefix routing and route expiry
+fn test_ipv6_routing() -> TestResult {
+    let mut table = Routing6Table::new();
+    let router = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+    table.add_default_route(router, 1, 1024);
+    table.add_direct_route(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 64, 1);
+
+    let local = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x42);
+    let remote = Ipv6Addr::new(0x2001, 0x4860, 0, 0, 0, 0, 0, 0x8888);
+    assert_true!(table.get_next_hop(local, 0) == Some((local, 1)), "On-link prefix should be direct")?;
+    assert_true!(table.get_next_hop(remote, 0) == Some((router, 1)), "Other traffic should use the router")?;
+
+    let mut learned = crate::net::route6::Route6Entry::new(remote, 48, Some(router), 1, 0);
+    learned.expires_ms = Some(1000);
+    table.add_route(learned);
+    assert_true!(table.lookup_route(remote, 0).map(|r| r.prefix_len) == Some(48),
+                 "Longest prefix should win")?;
+    assert_true!(table.lookup_route(remote, 1000).map(|r| r.prefix_len) == Some(0),
+                 "Expired route should be skipped")?;
+    table.expire(1000);
+    assert_eq!(table.routes().len(), 2, "Expired route should be removed")?;
+
+    TestResult::Pass
+}
+
+/// Test dual-stack socket addresses and wildcard binds
+fn test_dual_stack_socket_addr() -> TestResult {
+    // Services listen on :: by default
+    let any = SocketAddr::default();
+    assert_true!(any.is_wildcard() && any.ip == IpAddr::UNSPECIFIED, "Default address should be ::")?;
+
+    let v6 = SocketAddr::new_ipv6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 443);
+    let bytes = v6.to_sockaddr_bytes();
+    assert_eq!(bytes.len(), SocketAddr::SOCKADDR_IN6_LEN, "sockaddr_in6 length mismatch")?;
+    assert_true!(SocketAddr::from_sockaddr_bytes(&bytes) == Some(v6), "sockaddr_in6 should round-trip")?;
+
+    let v4 = SocketAddr::new_ipv4_from_octets(10, 0, 0, 1, 80);
+    let bytes = v4.to_sockaddr_bytes();
+    assert_eq!(bytes.len(), SocketAddr::SOCKADDR_IN_LEN, "sockaddr_in length mismatch")?;
+    assert_true!(SocketAddr::from_sockaddr_bytes(&bytes) == Some(v4), "sockaddr_in should round-trip")?;
+
+    let mapped = SocketAddr::new_ipv6(Ipv6Addr::from_ipv4_mapped(Ipv4Addr::new(10, 0, 0, 1)), 80);
+    assert_eq!(mapped.ipv4_addr(), Some(Ipv4Addr::new(10, 0, 0, 1)), "Mapped address should give IPv4")?;
+
+    let mut udp_socket = crate::net::socket::UdpSocketWrapper::new(
+        crate::net::socket::SocketOptions::new()
     );
+    match udp_socket.bind(SocketAddr::unspecified(5353)) {
+        Ok(_) => {},
+        Err(_) => return TestResult::Fail("UDP bind to :: failed"),
+    }
+    // A dual-stack socket reaches IPv4 peers too
+    assert_true!(udp_socket.send_to(b"x", v4).is_ok(), "Dual-stack socket should send to IPv4")?;
+    assert_true!(udp_socket.send_to(b"x", v6).is_ok(), "Dual-stack socket should send to IPv6")?;
 
-    match listen_result {
-        Ok(conn_id) => {
-            // Listening socket created successfully
-            assert_true!(conn_id.is_server(), "Connection should be server type")?;
-
-            // Test accepting connection
-            match manager.accept(conn_id) {
-                Ok(None) => {
-                    // No pending connections (expected)
-                }
-                Err(_) => return TestResult::Fail("Accept operation failed"),
-            }
+    TestResult::Pass
+}
 
-            TestResult::Pass
+/// Deliver queued segments between the sockets of one manager until it goes quiet
+fn pump_tcp(manager: &mut TcpConnectionManager, now: u64) {
+    pump_tcp_lossy(manager, now, |_| false);
+}
+
+/// Like [`pump_tcp`], but segments for which `drop` returns true are lost
+fn pump_tcp_lossy(manager: &mut TcpConnectionManager, now: u64, mut drop: impl FnMut(&TcpPacket) -> bool) {
+    loop {
+        let outputs = manager.take_output();
+        if outputs.is_empty() {
+            return;
+        }
+        for output in outputs {
+            if let Ok(packet) = TcpPacket::from_bytes(&output.segment)
+                && !drop(&packet)
+            {
+                manager.input(output.source, output.dest, &packet, now);
+            }
         }
-        Err(_) => TestResult::Fail("Failed to create listening socket"),
     }
 }
 
-/// Test TCP data transmission
+/// Connection owned by the socket bound to local `port`
+fn tcp_connection_on(manager: &TcpConnectionManager, port: u16) -> Option<&TcpConnection> {
+    manager.get_all_connections().into_iter().find(|conn| conn.id.local_port == port)
+}
+
+/// Connect a client to a listener on 127.0.0.1:`port`
+///
+/// Returns the listener, the client and the accepted socket.
+fn tcp_connected_pair(
+    manager: &mut TcpConnectionManager,
+    port: u16,
+) -> Result<(SocketHandle, SocketHandle, SocketHandle), &'static str> {
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let options = TcpOptions::default();
+
+    let server = manager.open(options.clone());
+    manager.bind(server, localhost, port, options.clone()).map_err(|_| "bind failed")?;
+    manager.listen(server, 4).map_err(|_| "listen failed")?;
+
+    let client = manager.open(options.clone());
+    manager
+        .connect(client, localhost, localhost, port, options, 0)
+        .map_err(|_| "connect failed")?;
+    if manager.connect_status(client) != Ok(false) {
+        return Err("connect should wait for the handshake");
+    }
+
+    pump_tcp(manager, 0);
+    if manager.connect_status(client) != Ok(true) {
+        return Err("handshake did not complete");
+    }
+    match manager.accept(server) {
+        Ok(Some((child, _))) => Ok((server, client, child)),
+        _ => Err("no connection to accept"),
+    }
+}
+
+/// Test TCP connection establishment
+pub fn test_tcp_connection_establishment() -> TestResult {
+    let mut manager = TcpConnectionManager::new();
+
+    let (server, client, child) = match tcp_connected_pair(&mut manager, 8080) {
+        Ok(handles) => handles,
+        Err(msg) => return TestResult::Fail(msg),
+    };
+
+    assert_true!(manager.socket_state(server) == TcpState::Listen, "Listener should stay listening")?;
+    assert_true!(manager.socket_state(client) == TcpState::Established, "Client should be established")?;
+    assert_true!(manager.socket_state(child) == TcpState::Established, "Accepted socket should be established")?;
+    assert_true!(
+        manager.peer_addr(child) == manager.local_addr(client),
+        "Accepted socket's peer should be the client"
+    )?;
+    assert_true!(matches!(manager.accept(server), Ok(None)), "Accept queue should be empty")?;
+
+    TestResult::Pass
+}
+
+/// Test TCP data transmission and teardown through TIME-WAIT
 pub fn test_tcp_data_transmission() -> TestResult {
-    use crate::net::tcp::manager::{TcpConnectionManager, ConnectionId};
+    let mut manager = TcpConnectionManager::new();
 
+    let (server, client, child) = match tcp_connected_pair(&mut manager, 8081) {
+        Ok(handles) => handles,
+        Err(msg) => return TestResult::Fail(msg),
+    };
+
+    // Data
+    let test_data = b"Hello, TCP!";
+    assert_true!(manager.send(client, test_data, 1) == Ok(test_data.len()), "Send should queue all data")?;
+    pump_tcp(&mut manager, 1);
+    assert_true!(manager.poll(child).readable, "Receiver should be readable")?;
+    let mut buf = [0u8; 32];
+    let received = manager.recv(child, &mut buf, 1);
+    assert_true!(received == Ok(test_data.len()), "Receiver should get all data")?;
+    assert_true!(&buf[..test_data.len()] == test_data, "Received data should match")?;
+    assert_true!(manager.recv(child, &mut buf, 1) == Err(TcpError::WouldBlock), "Nothing more to read")?;
+
+    // Client closes first and ends up in TIME-WAIT
+    manager.close(client, 2);
+    pump_tcp(&mut manager, 2);
+    assert_true!(manager.recv(child, &mut buf, 2) == Ok(0), "Peer FIN should read as end of stream")?;
+    manager.close(child, 3);
+    manager.close(server, 3);
+    pump_tcp(&mut manager, 3);
+
+    let states: Vec<TcpState> = manager.get_all_connections().iter().map(|conn| conn.state()).collect();
+    assert_true!(states == [TcpState::TimeWait], "Only the active closer should remain, in TIME-WAIT")?;
+
+    manager.check_timeouts(3 + TIME_WAIT_MS);
+    assert_true!(manager.get_all_connections().is_empty(), "TIME-WAIT should expire after 2 MSL")?;
+
+    TestResult::Pass
+}
+
+/// Test port conflicts and SO_REUSEADDR over a connection in TIME-WAIT
+pub fn test_tcp_reuse_addr() -> TestResult {
     let mut manager = TcpConnectionManager::new();
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
 
-    // Create a connection
-    let conn_result = manager.connect(
-        Ipv4Addr::new(127, 0, 0, 1),
-        Ipv4Addr::new(127, 0, 0, 1),
-        80,
-        crate::net::tcp::manager::TcpOptions::default()
-    );
+    let (server, client, child) = match tcp_connected_pair(&mut manager, 9090) {
+        Ok(handles) => handles,
+        Err(msg) => return TestResult::Fail(msg),
+    };
 
-    match conn_result {
-        Ok(conn_id) => {
-            // Get the connection
-            if let Some(conn) = manager.get_connection(conn_id) {
-                // Test data transmission
-                let test_data = b"Hello, TCP!";
-                match conn.send_data(test_data) {
-                    Ok(()) => TestResult::Pass,
-                    Err(_) => TestResult::Fail("Failed to send data"),
-                }
-            } else {
-                TestResult::Fail("Connection not found")
-            }
+    // The listener owns the port
+    let other = manager.open(TcpOptions::default());
+    assert_true!(
+        manager.bind(other, localhost, 9090, TcpOptions::default()) == Err(TcpError::PortInUse),
+        "Binding a listening port should fail"
+    )?;
+
+    // Server side closes first, leaving its end of the connection in TIME-WAIT
+    manager.close(server, 1);
+    manager.close(child, 1);
+    pump_tcp(&mut manager, 1);
+    manager.close(client, 2);
+    pump_tcp(&mut manager, 2);
+
+    assert_true!(
+        manager.bind(other, localhost, 9090, TcpOptions::default()) == Err(TcpError::PortInUse),
+        "TIME-WAIT should hold the port without SO_REUSEADDR"
+    )?;
+    let reuse = TcpOptions { reuse_addr: true, ..TcpOptions::default() };
+    assert_true!(
+        manager.bind(other, localhost, 9090, reuse.clone()) == Ok(9090),
+        "SO_REUSEADDR should allow binding over TIME-WAIT"
+    )?;
+    assert_true!(manager.listen(other, 4).is_ok(), "Restarted server should listen")?;
+
+    // A second listener on the same address is refused even with SO_REUSEADDR
+    let second = manager.open(reuse.clone());
+    assert_true!(
+        manager.bind(second, localhost, 9090, reuse) == Err(TcpError::PortInUse),
+        "Binding over a listener should fail"
+    )?;
+
+    TestResult::Pass
+}
+
+/// Test that connecting to a closed port is reset
+pub fn test_tcp_connection_refused() -> TestResult {
+    let mut manager = TcpConnectionManager::new();
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let options = TcpOptions::default();
+
+    let client = manager.open(options.clone());
+    assert_true!(
+        manager.connect(client, localhost, localhost, 9, options, 0).is_ok(),
+        "Connect should start"
+    )?;
+    pump_tcp(&mut manager, 0);
+
+    assert_true!(
+        manager.connect_status(client) == Err(TcpError::ConnectionRefused),
+        "RST to a SYN should refuse the connection"
+    )?;
+    assert_true!(manager.get_all_connections().is_empty(), "Refused connection should be gone")?;
+
+    TestResult::Pass
+}
+
+/// Test per-socket and inherited TCP_CONGESTION choices
+pub fn test_tcp_congestion_selection() -> TestResult {
+    for algorithm in CongestionAlgorithm::ALL {
+        assert_true!(
+            CongestionAlgorithm::from_name(algorithm.name()) == Some(algorithm),
+            "Algorithm names should round-trip"
+        )?;
+    }
+    assert_true!(
+        CongestionAlgorithm::from_name("newreno") == Some(CongestionAlgorithm::NewReno),
+        "newreno should alias reno"
+    )?;
+    assert_true!(CongestionAlgorithm::from_name("vegas").is_none(), "Unknown names should be refused")?;
+
+    let mut manager = TcpConnectionManager::new();
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let bbr = TcpOptions { congestion: CongestionAlgorithm::Bbr, ..TcpOptions::default() };
+
+    // Accepted connections inherit the listener's algorithm
+    let server = manager.open(bbr.clone());
+    assert_true!(manager.bind(server, localhost, 7070, bbr.clone()).is_ok(), "Bind failed")?;
+    assert_true!(manager.listen(server, 4).is_ok(), "Listen failed")?;
+    let client = manager.open(TcpOptions::default());
+    let reno = TcpOptions { congestion: CongestionAlgorithm::NewReno, ..TcpOptions::default() };
+    assert_true!(manager.connect(client, localhost, localhost, 7070, reno, 0).is_ok(), "Connect failed")?;
+    pump_tcp(&mut manager, 0);
+    let Some((_, child_id)) = manager.accept(server).ok().flatten() else {
+        return TestResult::Fail("No connection to accept");
+    };
+    let algorithm_of = |manager: &TcpConnectionManager, port: u16| {
+        tcp_connection_on(manager, port).map(|conn| conn.state_machine.congestion().algorithm())
+    };
+    assert_true!(
+        algorithm_of(&manager, child_id.local_port) == Some(CongestionAlgorithm::Bbr),
+        "Accepted connection should use the listener's algorithm"
+    )?;
+    assert_true!(
+        algorithm_of(&manager, child_id.remote_port) == Some(CongestionAlgorithm::NewReno),
+        "Client should use the algorithm it connected with"
+    )?;
+
+    // A connected socket switches at once
+    assert_true!(manager.set_congestion(client, CongestionAlgorithm::Cubic).is_ok(), "Switch failed")?;
+    assert_true!(
+        algorithm_of(&manager, child_id.remote_port) == Some(CongestionAlgorithm::Cubic),
+        "Connected socket should switch algorithm"
+    )?;
+    assert_true!(
+        tcp_connection_on(&manager, child_id.remote_port).is_some_and(|conn| conn.state_machine.sack_permitted()),
+        "Both ends should have agreed on SACK"
+    )?;
+
+    TestResult::Pass
+}
+
+/// Test how NewReno, CUBIC and BBR react to acknowledgments and loss
+pub fn test_tcp_congestion_algorithms() -> TestResult {
+    let mss = 1000;
+    let ack = |now: u64, acked: u32, in_flight: u32| AckSample {
+        now,
+        acked,
+        in_flight,
+        rtt: Some(50),
+        ..AckSample::default()
+    };
+
+    // NewReno halves the flight, CUBIC keeps 70% of the window
+    let mut reno = NewReno::new(mss);
+    reno.on_loss(LossEvent::Detected, 20 * mss, mss, 0);
+    assert_true!(reno.cwnd() == 10 * mss, "NewReno should halve the flight on loss")?;
+    reno.on_loss(LossEvent::Timeout, 10 * mss, mss, 0);
+    assert_true!(reno.cwnd() == mss, "NewReno should restart from one segment after a timeout")?;
+
+    let mut cubic = Cubic::new(mss);
+    for _ in 0..190 {
+        cubic.on_ack(&ack(0, mss, 0), mss);
+    }
+    let before = cubic.cwnd();
+    cubic.on_loss(LossEvent::Detected, before, mss, 0);
+    assert_true!(cubic.cwnd() == before * 7 / 10, "CUBIC should keep 70% of the window")?;
+    assert_true!(cubic.w_max() == before, "CUBIC should remember the window at the loss")?;
+
+    // It regrows to W_max by K (about 5.3s for 60 segments) and beyond later
+    let mut now = 0;
+    while now < 5_000 {
+        now += 50;
+        let cwnd = cubic.cwnd();
+        cubic.on_ack(&ack(now, cwnd, cwnd), mss);
+    }
+    let near_plateau = cubic.cwnd();
+    assert_true!(near_plateau > before * 9 / 10, "CUBIC should climb back toward W_max")?;
+    assert_true!(near_plateau <= before + mss, "CUBIC should plateau around W_max")?;
+    while now < 10_000 {
+        now += 50;
+        let cwnd = cubic.cwnd();
+        cubic.on_ack(&ack(now, cwnd, cwnd), mss);
+    }
+    assert_true!(cubic.cwnd() > before + mss, "CUBIC should probe beyond W_max")?;
+
+    // BBR measures 1 MB/s over a 50ms path: a 50 kB BDP
+    let mut bbr = Bbr::new(mss);
+    let mut delivered = 0u64;
+    for round in 1..=20u64 {
+        let sample = AckSample {
+            now: round * 50,
+            acked: 10 * mss,
+            in_flight: 50 * mss,
+            rtt: Some(50),
+            delivery_rate: Some(1_000_000),
+            prior_delivered: delivered,
+            delivered: delivered + 50_000,
+            ..AckSample::default()
+        };
+        delivered += 50_000;
+        bbr.on_ack(&sample, mss);
+    }
+    assert_true!(bbr.bandwidth() == 1_000_000, "BBR should track the bottleneck bandwidth")?;
+    assert_true!(bbr.min_rtt() == Some(50), "BBR should track the propagation delay")?;
+    assert_true!(bbr.mode() == BbrMode::ProbeBw, "BBR should leave startup once the pipe is full")?;
+    let cwnd = bbr.cwnd();
+    assert_true!(cwnd >= 100 * mss && cwnd <= 103 * mss, "BBR should cap the window at twice the BDP")?;
+
+    bbr.on_loss(LossEvent::Detected, 80 * mss, mss, 1_000);
+    assert_true!(bbr.bandwidth() == 1_000_000, "Loss should not shrink BBR's model")?;
+    bbr.on_recovery_end(mss);
+    assert_true!(bbr.cwnd() == cwnd, "BBR should restore its window after recovery")?;
+    assert_true!(bbr.pacing_rate().is_some(), "BBR should pace")?;
+
+    TestResult::Pass
+}
+
+/// Send `len` bytes from `client` to `child` and read them back, dropping
+/// the client's data segments picked by `lose`; returns what `child` read
+fn tcp_lossy_transfer(
+    manager: &mut TcpConnectionManager,
+    client: SocketHandle,
+    child: SocketHandle,
+    len: usize,
+    now: u64,
+    mut lose: impl FnMut(usize) -> bool,
+) -> Vec<u8> {
+    let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+    let _ = manager.send(client, &data, now);
+    let client_port = manager.local_addr(client).map_or(0, |(_, port)| port);
+    let mut data_segments = 0;
+    pump_tcp_lossy(manager, now, |packet| {
+        if packet.src_port() != client_port || packet.payload.is_empty() {
+            return false;
         }
-        Err(_) => TestResult::Fail("Failed to establish connection"),
+        data_segments += 1;
+        lose(data_segments)
+    });
+
+    let mut received = Vec::new();
+    let mut buf = [0u8; 2048];
+    while let Ok(n) = manager.recv(child, &mut buf, now)
+        && n > 0
+    {
+        received.extend_from_slice(&buf[..n]);
     }
+    received
+}
+
+/// Test that SACK and RACK repair a lost segment without waiting for the RTO
+pub fn test_tcp_sack_recovery() -> TestResult {
+    let mut manager = TcpConnectionManager::new();
+    let (_, client, child) = match tcp_connected_pair(&mut manager, 8082) {
+        Ok(handles) => handles,
+        Err(msg) => return TestResult::Fail(msg),
+    };
+    let client_port = manager.local_addr(client).map_or(0, |(_, port)| port);
+
+    // The first of four segments is lost; the others are SACKed, which
+    // marks it lost at once
+    let received = tcp_lossy_transfer(&mut manager, client, child, 5840, 10, |n| n == 1);
+    let expected: Vec<u8> = (0..5840).map(|i| i as u8).collect();
+    assert_true!(received == expected, "Receiver should get all data in order")?;
+
+    let Some(conn) = tcp_connection_on(&manager, client_port) else {
+        return TestResult::Fail("Client connection missing");
+    };
+    assert_true!(conn.stats.retransmissions == 1, "Only the lost segment should be resent")?;
+    assert_true!(!conn.state_machine.in_recovery(), "Recovery should end with the repair")?;
+    assert_true!(
+        conn.state_machine.congestion().ssthresh() < u32::MAX,
+        "The loss should reduce the window"
+    )?;
+
+    TestResult::Pass
+}
+
+/// Test that a tail loss probe repairs losses at the end of a burst
+pub fn test_tcp_tail_loss_probe() -> TestResult {
+    let mut manager = TcpConnectionManager::new();
+    let (_, client, child) = match tcp_connected_pair(&mut manager, 8083) {
+        Ok(handles) => handles,
+        Err(msg) => return TestResult::Fail(msg),
+    };
+    let client_port = manager.local_addr(client).map_or(0, |(_, port)| port);
+
+    // The last two of four segments are lost, so no ACK reports the loss
+    let mut received = tcp_lossy_transfer(&mut manager, client, child, 5840, 10, |n| n >= 3);
+    assert_true!(received.len() == 2920, "Only the first two segments should arrive")?;
+
+    // The probe goes out well before the 200ms RTO and exposes the hole
+    manager.check_timeouts(30);
+    pump_tcp(&mut manager, 30);
+    let mut buf = [0u8; 4096];
+    while let Ok(n) = manager.recv(child, &mut buf, 30)
+        && n > 0
+    {
+        received.extend_from_slice(&buf[..n]);
+    }
+    let expected: Vec<u8> = (0..5840).map(|i| i as u8).collect();
+    assert_true!(received == expected, "The probe should recover the tail")?;
+
+    let Some(conn) = tcp_connection_on(&manager, client_port) else {
+        return TestResult::Fail("Client connection missing");
+    };
+    assert_true!(conn.stats.retransmissions == 2, "Probe and repair should be the only resends")?;
+
+    TestResult::Pass
+}
+
+/// Test TCP sockets end to end over the loopback device
+pub fn test_tcp_loopback_sockets() -> TestResult {
+    use crate::net::socket::{SocketOptions, TcpSocketWrapper};
+
+    let addr = SocketAddr::new_ipv4(Ipv4Addr::LOCALHOST, 9191);
+    let mut server = TcpSocketWrapper::new(SocketOptions::new());
+    server.set_nonblocking(true);
+    assert_true!(server.bind(addr).is_ok(), "Server bind failed")?;
+    assert_true!(server.listen(4).is_ok(), "Server listen failed")?;
+
+    let mut client = TcpSocketWrapper::new(SocketOptions::new());
+    client.set_nonblocking(true);
+    let connect = client.connect(addr);
+    assert_true!(matches!(connect, Ok(()) | Err(SocketError::InProgress)), "Connect failed")?;
+    crate::net::poll();
+    assert_true!(client.state() == TcpState::Established, "Loopback handshake should complete")?;
+
+    let (accepted, peer) = match server.accept() {
+        Ok(accepted) => accepted,
+        Err(_) => return TestResult::Fail("No connection to accept"),
+    };
+    let Socket::Tcp(mut child) = accepted else {
+        return TestResult::Fail("Accepted socket should be TCP");
+    };
+    assert_true!(Some(peer) == client.local_addr(), "Peer address should be the client")?;
+
+    assert_true!(client.send(b"ping") == Ok(4), "Client send failed")?;
+    let mut buf = [0u8; 16];
+    assert_true!(child.recv(&mut buf) == Ok(4), "Server should receive the data")?;
+    assert_true!(&buf[..4] == b"ping", "Received data should match")?;
+    assert_true!(child.recv(&mut buf) == Err(SocketError::WouldBlock), "Nothing more to read")?;
+
+    let _ = client.close();
+    assert_true!(child.recv(&mut buf) == Ok(0), "Close should read as end of stream")?;
+    assert_true!(child.poll() & crate::posix::POLLIN != 0, "End of stream should poll readable")?;
+    let _ = child.close();
+    let _ = server.close();
+
+    TestResult::Pass
 }
 
 /// Test UDP data transmission
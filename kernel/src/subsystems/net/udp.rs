@@ -5,6 +5,7 @@
 extern crate alloc;
 use alloc::vec::Vec;
 use super::ipv4::Ipv4Addr;
+use super::ipv6::{self, IpAddr, Ipv6Addr};
 
 /// UDP header
 #[derive(Debug, Clone, Copy)]
@@ -188,6 +189,37 @@ impl UdpPacket {
         Ok(Self { header, payload })
     }
 
+    /// Calculate the checksum of a packet carried over IPv6
+    ///
+    /// Unlike over IPv4 the checksum is mandatory; a computed value of
+    /// zero is sent as 0xFFFF.
+    pub fn checksum_v6(&self, source_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> u16 {
+        let mut bytes = self.to_bytes();
+        bytes[6..8].copy_from_slice(&[0, 0]);
+        let checksum = ipv6::pseudo_header_checksum(source_addr, dest_addr, ipv6::protocols::UDP, &bytes);
+        if checksum == 0 { 0xFFFF } else { checksum }
+    }
+
+    /// Create a new UDP packet to be carried over IPv6
+    pub fn new_v6(
+        src_port: u16,
+        dst_port: u16,
+        payload: Vec<u8>,
+        source_addr: Ipv6Addr,
+        dest_addr: Ipv6Addr,
+    ) -> Self {
+        let total_length = (UdpHeader::SIZE + payload.len()) as u16;
+        let mut packet = Self { header: UdpHeader::new(src_port, dst_port, total_length), payload };
+        packet.header.checksum = packet.checksum_v6(source_addr, dest_addr);
+        packet
+    }
+
+    /// Verify the checksum of a packet received over IPv6
+    pub fn verify_checksum_v6(&self, source_addr: Ipv6Addr, dest_addr: Ipv6Addr) -> bool {
+        self.header.checksum != 0
+            && ipv6::pseudo_header_checksum(source_addr, dest_addr, ipv6::protocols::UDP, &self.to_bytes()) == 0
+    }
+
     /// Verify checksum
     pub fn verify_checksum(
         &self,
@@ -247,39 +279,42 @@ pub enum UdpSocketState {
 #[derive(Debug, Clone)]
 pub struct UdpSocket {
     /// Local IP address
-    pub local_ip: Ipv4Addr,
+    pub local_ip: IpAddr,
     /// Local port
     pub local_port: u16,
     /// Remote IP address (for connected sockets)
-    pub remote_ip: Option<Ipv4Addr>,
+    pub remote_ip: Option<IpAddr>,
     /// Remote port (for connected sockets)
     pub remote_port: Option<u16>,
     /// Socket state
     pub state: UdpSocketState,
+    /// Bound to `::` but not accepting IPv4 (IPV6_V6ONLY)
+    pub v6only: bool,
 }
 
 impl UdpSocket {
     /// Create a new UDP socket
     pub fn new() -> Self {
         Self {
-            local_ip: Ipv4Addr::UNSPECIFIED,
+            local_ip: IpAddr::UNSPECIFIED,
             local_port: 0,
             remote_ip: None,
             remote_port: None,
             state: UdpSocketState::Unbound,
+            v6only: false,
         }
     }
 
     /// Bind to local address and port
-    pub fn bind(&mut self, ip: Ipv4Addr, port: u16) {
-        self.local_ip = ip;
+    pub fn bind(&mut self, ip: impl Into<IpAddr>, port: u16) {
+        self.local_ip = ip.into();
         self.local_port = port;
         self.state = UdpSocketState::Bound;
     }
 
     /// Connect to remote address
-    pub fn connect(&mut self, ip: Ipv4Addr, port: u16) {
-        self.remote_ip = Some(ip);
+    pub fn connect(&mut self, ip: impl Into<IpAddr>, port: u16) {
+        self.remote_ip = Some(ip.into());
         self.remote_port = Some(port);
         self.state = UdpSocketState::Connected;
     }
@@ -0,0 +1,784 @@
+//! ELF core dumps
+//!
+//! A process killed by a signal whose default action is `Core` gets its
+//! memory and registers written out as an `ET_CORE` ELF file that gdb and
+//! other standard tools can load. The layout follows Linux:
+//!
+//! - one `PT_NOTE` segment holding `NT_PRSTATUS`, `NT_PRPSINFO`, `NT_AUXV`
+//!   and `NT_FILE` for the dumping thread, then an `NT_PRSTATUS` for every
+//!   other thread
+//! - one `PT_LOAD` segment per run of writable pages; read-only text is left
+//!   out since the debugger can read it from the executable
+//!
+//! Where the file goes is set by the core pattern (`/sys/kernel/core_pattern`),
+//! with the same `%` specifiers as Linux. A pattern starting with `|` runs a
+//! helper program and feeds it the dump on its standard input instead.
+//! Dumps to files are capped at the soft `RLIMIT_CORE`, and skipped when it
+//! is smaller than a page.
+//!
+//! A process whose credentials changed, by `setuid` and friends or by
+//! running with an effective ID other than its real one, dumps only as
+//! `fs.suid_dumpable` (`/sys/kernel/suid_dumpable`) allows. A core file
+//! already in place is reused only if the dumper owns it and may write it.
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::ipc::signal::{SigInfo, Signal};
+use crate::posix::RLIMIT_CORE;
+use crate::subsystems::mm::vm::{copyin, user_page_writable, PageTable};
+use crate::subsystems::mm::PAGE_SIZE;
+use crate::subsystems::sync::Mutex;
+use crate::vfs::{FileAttr, FileMode, LookupFlags, SetAttr, VfsError};
+
+use super::elf::{
+    ELFCLASS64, ELFDATA2LSB, ELF_MAGIC, ET_CORE, NT_AUXV, NT_FILE, NT_PRPSINFO, NT_PRSTATUS,
+    PF_R, PF_W, PT_LOAD, PT_NOTE,
+};
+use super::exec::ExecImage;
+use super::manager::{myproc, Pid, ELF_NGREG, PROC_TABLE};
+
+/// Longest core pattern accepted, as on Linux
+pub const CORENAME_MAX_SIZE: usize = 128;
+
+/// Pattern used until one is configured
+pub const DEFAULT_CORE_PATTERN: &str = "core";
+
+static CORE_PATTERN: Mutex<Option<String>> = Mutex::new(None);
+
+/// No core dump
+pub const SUID_DUMP_DISABLE: u8 = 0;
+/// Dump as the process's own user
+pub const SUID_DUMP_USER: u8 = 1;
+/// Dump as root, only to a pipe or an absolute path, and never into an
+/// existing file
+pub const SUID_DUMP_ROOT: u8 = 2;
+
+static SUID_DUMPABLE: AtomicU8 = AtomicU8::new(SUID_DUMP_DISABLE);
+
+#[cfg(target_arch = "riscv64")]
+const ELF_MACHINE: u16 = super::elf::EM_RISCV;
+#[cfg(target_arch = "aarch64")]
+const ELF_MACHINE: u16 = super::elf::EM_AARCH64;
+#[cfg(target_arch = "x86_64")]
+const ELF_MACHINE: u16 = super::elf::EM_X86_64;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+/// Size of `struct elf_prstatus` for this architecture
+pub const PRSTATUS_SIZE: usize = (112 + ELF_NGREG * 8 + 4 + 7) & !7;
+
+/// Size of `struct elf_prpsinfo`
+pub const PRPSINFO_SIZE: usize = 136;
+
+/// Current core pattern
+pub fn core_pattern() -> String {
+    CORE_PATTERN
+        .lock()
+        .clone()
+        .unwrap_or_else(|| String::from(DEFAULT_CORE_PATTERN))
+}
+
+/// Replace the core pattern; a trailing newline (from `echo`) is dropped
+pub fn set_core_pattern(pattern: &str) -> Result<(), ()> {
+    let pattern = pattern.strip_suffix('\n').unwrap_or(pattern);
+    if pattern.len() >= CORENAME_MAX_SIZE {
+        return Err(());
+    }
+    *CORE_PATTERN.lock() = Some(String::from(pattern));
+    Ok(())
+}
+
+/// Dumpable mode a process gets when its credentials change
+/// (`fs.suid_dumpable`)
+pub fn suid_dumpable() -> u8 {
+    SUID_DUMPABLE.load(Ordering::Relaxed)
+}
+
+/// Set `fs.suid_dumpable` from its text form, `0`, `1` or `2`
+pub fn set_suid_dumpable(mode: &str) -> Result<(), ()> {
+    let mode = mode.strip_suffix('\n').unwrap_or(mode);
+    match mode.parse::<u8>() {
+        Ok(mode @ SUID_DUMP_DISABLE..=SUID_DUMP_ROOT) => {
+            SUID_DUMPABLE.store(mode, Ordering::Relaxed);
+            Ok(())
+        }
+        _ => Err(()),
+    }
+}
+
+/// Dumpable mode after exec, as Linux's `begin_new_exec`: a program left
+/// running with an effective ID other than its real one dumps only as
+/// `fs.suid_dumpable` allows
+pub fn exec_dumpable(uid: u32, euid: u32, gid: u32, egid: u32) -> u8 {
+    if euid == uid && egid == gid { SUID_DUMP_USER } else { suid_dumpable() }
+}
+
+/// Values the core pattern specifiers expand to
+#[derive(Debug, Clone, Default)]
+pub struct PatternInfo {
+    pub pid: Pid,
+    pub tid: Pid,
+    pub uid: u32,
+    pub gid: u32,
+    pub signal: Signal,
+    /// Seconds since the epoch
+    pub time: u64,
+    pub hostname: String,
+    /// Executable name, as in `comm`
+    pub comm: String,
+    /// Executable path
+    pub exe: String,
+    /// Soft `RLIMIT_CORE`
+    pub limit: u64,
+    /// Dumpable mode, `SUID_DUMP_USER` or `SUID_DUMP_ROOT`
+    pub dumpable: u8,
+}
+
+/// Expand the `%` specifiers of a core pattern
+///
+/// `%p`/`%P` (PID), `%i`/`%I` (thread ID), `%u`, `%g`, `%d` (dumpable
+/// mode), `%s` (signal), `%t` (time), `%h` (hostname), `%e` (name), `%E`
+/// (path with `/` replaced by `!`), `%c` (core size limit) and `%%`.
+/// Unknown specifiers expand to nothing. No namespaces are involved, so the
+/// global and namespace IDs are the same.
+pub fn expand_pattern(pattern: &str, info: &PatternInfo) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let _ = match chars.next() {
+            Some('%') => write!(out, "%"),
+            Some('p' | 'P') => write!(out, "{}", info.pid),
+            Some('i' | 'I') => write!(out, "{}", info.tid),
+            Some('u') => write!(out, "{}", info.uid),
+            Some('g') => write!(out, "{}", info.gid),
+            Some('d') => write!(out, "{}", info.dumpable),
+            Some('s') => write!(out, "{}", info.signal),
+            Some('t') => write!(out, "{}", info.time),
+            Some('h') => write!(out, "{}", info.hostname),
+            // A name must not turn into a path
+            Some('e') => write!(out, "{}", info.comm.replace('/', "!")),
+            Some('E') => write!(out, "{}", info.exe.replace('/', "!")),
+            Some('c') => write!(out, "{}", info.limit),
+            _ => Ok(()),
+        };
+    }
+    out
+}
+
+/// Registers and signal state of one thread
+#[derive(Debug, Clone)]
+pub struct ThreadState {
+    pub tid: Pid,
+    pub regs: [u64; ELF_NGREG],
+    pub pending: u64,
+    pub blocked: u64,
+}
+
+/// Process-wide fields of the notes
+#[derive(Debug, Clone, Default)]
+pub struct ProcessInfo {
+    pub pid: Pid,
+    pub ppid: Pid,
+    pub pgrp: Pid,
+    pub sid: Pid,
+    pub uid: u32,
+    pub gid: u32,
+    pub nice: i32,
+    pub cpu_ticks: u64,
+    pub comm: Vec<u8>,
+    pub args: Vec<u8>,
+}
+
+/// Append one note with owner "CORE", padding name and descriptor to 4
+pub fn push_note(out: &mut Vec<u8>, ty: u32, desc: &[u8]) {
+    const NAME: &[u8] = b"CORE\0";
+    out.extend_from_slice(&(NAME.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    out.extend_from_slice(&ty.to_le_bytes());
+    out.extend_from_slice(NAME);
+    pad_to(out, 4);
+    out.extend_from_slice(desc);
+    pad_to(out, 4);
+}
+
+fn pad_to(out: &mut Vec<u8>, align: usize) {
+    out.resize(out.len().next_multiple_of(align), 0);
+}
+
+fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+    buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// `struct elf_prstatus` for one thread
+pub fn prstatus(thread: &ThreadState, proc: &ProcessInfo, sig: Signal, info: &SigInfo) -> Vec<u8> {
+    let mut buf = vec![0u8; PRSTATUS_SIZE];
+    // pr_info: si_signo, si_code, si_errno
+    put(&mut buf, 0, &(sig as i32).to_le_bytes());
+    put(&mut buf, 4, &info.code.to_le_bytes());
+    put(&mut buf, 8, &info.errno.to_le_bytes());
+    put(&mut buf, 12, &(sig as i16).to_le_bytes());
+    put(&mut buf, 16, &thread.pending.to_le_bytes());
+    put(&mut buf, 24, &thread.blocked.to_le_bytes());
+    put(&mut buf, 32, &(thread.tid as i32).to_le_bytes());
+    put(&mut buf, 36, &(proc.ppid as i32).to_le_bytes());
+    put(&mut buf, 40, &(proc.pgrp as i32).to_le_bytes());
+    put(&mut buf, 44, &(proc.sid as i32).to_le_bytes());
+    // pr_utime; system, child user and child system time stay zero since
+    // only one tick count is kept per process
+    let freq = crate::subsystems::time::TIMER_FREQ;
+    put(&mut buf, 48, &(proc.cpu_ticks / freq).to_le_bytes());
+    put(&mut buf, 56, &((proc.cpu_ticks % freq) * 1_000_000 / freq).to_le_bytes());
+    for (i, reg) in thread.regs.iter().enumerate() {
+        put(&mut buf, 112 + i * 8, &reg.to_le_bytes());
+    }
+    buf
+}
+
+/// `struct elf_prpsinfo`
+pub fn prpsinfo(proc: &ProcessInfo) -> Vec<u8> {
+    let mut buf = vec![0u8; PRPSINFO_SIZE];
+    // The dumping process is running
+    buf[1] = b'R';
+    buf[3] = proc.nice as i8 as u8;
+    put(&mut buf, 16, &proc.uid.to_le_bytes());
+    put(&mut buf, 20, &proc.gid.to_le_bytes());
+    put(&mut buf, 24, &(proc.pid as i32).to_le_bytes());
+    put(&mut buf, 28, &(proc.ppid as i32).to_le_bytes());
+    put(&mut buf, 32, &(proc.pgrp as i32).to_le_bytes());
+    put(&mut buf, 36, &(proc.sid as i32).to_le_bytes());
+    let comm = &proc.comm[..proc.comm.len().min(15)];
+    put(&mut buf, 40, comm);
+    let args = &proc.args[..proc.args.len().min(79)];
+    put(&mut buf, 56, args);
+    buf
+}
+
+/// `NT_AUXV` descriptor: the raw auxiliary vector
+pub fn auxv_note(auxv: &[(u64, u64)]) -> Vec<u8> {
+    auxv.iter()
+        .flat_map(|&(ty, val)| ty.to_le_bytes().into_iter().chain(val.to_le_bytes()))
+        .collect()
+}
+
+/// `NT_FILE` descriptor: count, page size, (start, end, page offset)
+/// triples, then the NUL-terminated file names
+pub fn file_note(ranges: &[(usize, usize, usize)], path: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(ranges.len() as u64).to_le_bytes());
+    buf.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+    for &(start, end, pgoff) in ranges {
+        for value in [start, end, pgoff] {
+            buf.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+    for _ in ranges {
+        buf.extend_from_slice(path);
+        buf.push(0);
+    }
+    buf
+}
+
+/// All notes of a dump, dumping thread first
+pub fn build_notes(
+    threads: &[ThreadState],
+    proc: &ProcessInfo,
+    image: Option<&ExecImage>,
+    sig: Signal,
+    info: &SigInfo,
+) -> Vec<u8> {
+    let mut notes = Vec::new();
+    for (i, thread) in threads.iter().enumerate() {
+        push_note(&mut notes, NT_PRSTATUS, &prstatus(thread, proc, sig, info));
+        if i == 0 {
+            push_note(&mut notes, NT_PRPSINFO, &prpsinfo(proc));
+            if let Some(image) = image {
+                push_note(&mut notes, NT_AUXV, &auxv_note(&image.auxv));
+                push_note(&mut notes, NT_FILE, &file_note(&image.file_ranges, &image.path));
+            }
+        }
+    }
+    notes
+}
+
+/// ELF header and program headers for a dump with the given notes size and
+/// memory segments; segment data starts at the returned offset
+pub fn build_headers(notes_len: usize, segments: &[(usize, usize)]) -> (Vec<u8>, usize) {
+    let phnum = 1 + segments.len();
+    let mut out = Vec::with_capacity(EHDR_SIZE + phnum * PHDR_SIZE);
+
+    out.extend_from_slice(&ELF_MAGIC);
+    out.extend_from_slice(&[ELFCLASS64, ELFDATA2LSB, 1, 0]);
+    out.resize(16, 0);
+    out.extend_from_slice(&ET_CORE.to_le_bytes());
+    out.extend_from_slice(&ELF_MACHINE.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    out.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+    out.extend_from_slice(&(EHDR_SIZE as u64).to_le_bytes()); // e_phoff
+    out.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+    out.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    out.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+    out.extend_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+    out.extend_from_slice(&(phnum as u16).to_le_bytes());
+    out.extend_from_slice(&[0; 6]); // no section headers
+
+    let notes_offset = EHDR_SIZE + phnum * PHDR_SIZE;
+    push_phdr(&mut out, PT_NOTE, 0, notes_offset, 0, notes_len, 0);
+
+    let data_offset = (notes_offset + notes_len).next_multiple_of(PAGE_SIZE);
+    let mut offset = data_offset;
+    for &(start, end) in segments {
+        push_phdr(&mut out, PT_LOAD, PF_R | PF_W, offset, start, end - start, PAGE_SIZE);
+        offset += end - start;
+    }
+    (out, data_offset)
+}
+
+fn push_phdr(out: &mut Vec<u8>, ty: u32, flags: u32, offset: usize, vaddr: usize, size: usize, align: usize) {
+    out.extend_from_slice(&ty.to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+    for value in [offset, vaddr, 0, size, size, align] {
+        out.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+}
+
+/// Runs of writable user pages within `regions`
+fn writable_segments(pagetable: *mut PageTable, regions: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    for &(start, end) in regions {
+        for va in (start.max(PAGE_SIZE)..end).step_by(PAGE_SIZE) {
+            if !user_page_writable(pagetable, va) {
+                continue;
+            }
+            match segments.last_mut() {
+                Some(last) if last.1 == va => last.1 += PAGE_SIZE,
+                _ => segments.push((va, va + PAGE_SIZE)),
+            }
+        }
+    }
+    segments
+}
+
+/// Everything a dump needs, copied out of the process table
+struct Snapshot {
+    proc: ProcessInfo,
+    threads: Vec<ThreadState>,
+    image: Option<Arc<ExecImage>>,
+    pagetable: *mut PageTable,
+    segments: Vec<(usize, usize)>,
+    euid: u32,
+    egid: u32,
+    limit: u64,
+    dumpable: u8,
+}
+
+fn snapshot(pid: Pid) -> Option<Snapshot> {
+    let table = PROC_TABLE.lock();
+    let p = table.find_ref(pid)?;
+    if p.pagetable.is_null() || p.trapframe.is_null() {
+        return None;
+    }
+
+    let (pending, blocked) = p
+        .signals
+        .as_ref()
+        .map(|s| (s.pending_signals().bits(), s.get_mask().bits()))
+        .unwrap_or_default();
+    let mut threads = vec![ThreadState {
+        tid: pid,
+        regs: unsafe { (*p.trapframe).user_regs() },
+        pending,
+        blocked,
+    }];
+    for thread in super::thread::thread_table().find_threads_by_pid(pid) {
+        if thread.trapframe.is_null() || thread.trapframe == p.trapframe {
+            continue;
+        }
+        threads.push(ThreadState {
+            tid: thread.tid as Pid,
+            regs: unsafe { (*thread.trapframe).user_regs() },
+            pending: thread.pending_signals,
+            blocked: thread.signal_mask,
+        });
+    }
+
+    let image = p.image.clone();
+    // Without an exec image only the initial flat layout exists
+    let mut regions = vec![(0, p.sz)];
+    if let Some(image) = &image {
+        regions = image.regions.clone();
+        regions.push((image.stack_top, p.sz));
+    }
+
+    Some(Snapshot {
+        proc: ProcessInfo {
+            pid,
+            ppid: p.parent.unwrap_or(0),
+            pgrp: p.pgid,
+            sid: p.sid,
+            uid: p.uid,
+            gid: p.gid,
+            nice: p.nice,
+            cpu_ticks: p.cpu_ticks,
+            comm: image.as_ref().map(|i| i.comm().to_vec()).unwrap_or_default(),
+            args: image.as_ref().map(|i| i.args.clone()).unwrap_or_default(),
+        },
+        threads,
+        pagetable: p.pagetable,
+        segments: writable_segments(p.pagetable, &regions),
+        image,
+        euid: p.euid,
+        egid: p.egid,
+        limit: p.rlimits[RLIMIT_CORE as usize].rlim_cur,
+        dumpable: p.dumpable,
+    })
+}
+
+/// Where the dump goes, with a byte budget
+trait Sink {
+    /// Write all of `buf` or fail
+    fn put(&mut self, buf: &[u8]) -> Result<(), ()>;
+}
+
+struct FileSink {
+    inode: Arc<dyn crate::vfs::InodeOps>,
+    offset: u64,
+}
+
+impl Sink for FileSink {
+    fn put(&mut self, buf: &[u8]) -> Result<(), ()> {
+        let n = self.inode.write(self.offset, buf).map_err(|_| ())?;
+        self.offset += n as u64;
+        if n == buf.len() { Ok(()) } else { Err(()) }
+    }
+}
+
+struct PipeSink {
+    file_idx: usize,
+}
+
+impl Sink for PipeSink {
+    fn put(&mut self, mut buf: &[u8]) -> Result<(), ()> {
+        while !buf.is_empty() {
+            let n = crate::fs::file::file_write(self.file_idx, buf);
+            if n <= 0 {
+                return Err(());
+            }
+            buf = &buf[n as usize..];
+        }
+        Ok(())
+    }
+}
+
+/// Truncates output at `limit` bytes, quietly as Linux does
+struct Limited<'a> {
+    sink: &'a mut dyn Sink,
+    written: u64,
+    limit: u64,
+}
+
+impl Limited<'_> {
+    fn put(&mut self, buf: &[u8]) -> Result<(), ()> {
+        let room = self.limit.saturating_sub(self.written).min(buf.len() as u64) as usize;
+        if room == 0 {
+            return Err(());
+        }
+        self.sink.put(&buf[..room])?;
+        self.written += room as u64;
+        if room == buf.len() { Ok(()) } else { Err(()) }
+    }
+}
+
+fn write_core(snap: &Snapshot, sig: Signal, info: &SigInfo, out: &mut Limited) -> Result<(), ()> {
+    let notes = build_notes(&snap.threads, &snap.proc, snap.image.as_deref(), sig, info);
+    let (mut head, data_offset) = build_headers(notes.len(), &snap.segments);
+    head.extend_from_slice(&notes);
+    head.resize(data_offset, 0);
+    out.put(&head)?;
+
+    let mut page = vec![0u8; PAGE_SIZE];
+    for &(start, end) in &snap.segments {
+        for va in (start..end).step_by(PAGE_SIZE) {
+            // A page that cannot be read is dumped as zeros
+            if unsafe { copyin(snap.pagetable, page.as_mut_ptr(), va, PAGE_SIZE) }.is_err() {
+                page.fill(0);
+            }
+            out.put(&page)?;
+        }
+    }
+    Ok(())
+}
+
+fn pattern_info(snap: &Snapshot, sig: Signal) -> PatternInfo {
+    let mut host = [0u8; 65];
+    let host_len = crate::syscalls::process::get_hostname(&mut host).unwrap_or(0);
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+    PatternInfo {
+        pid: snap.proc.pid,
+        tid: snap.proc.pid,
+        uid: snap.proc.uid,
+        gid: snap.proc.gid,
+        signal: sig,
+        time: crate::subsystems::time::timestamp_nanos() / 1_000_000_000,
+        hostname: text(&host[..host_len]),
+        comm: text(&snap.proc.comm),
+        exe: snap.image.as_ref().map(|i| text(&i.path)).unwrap_or_default(),
+        limit: snap.limit,
+        dumpable: snap.dumpable,
+    }
+}
+
+/// Owner of the core file dumped to `path` in `dumpable` mode, or `None`
+/// if the process may not dump there
+fn dump_owner(dumpable: u8, euid: u32, egid: u32, path: &str) -> Option<(u32, u32)> {
+    match dumpable {
+        SUID_DUMP_USER => Some((euid, egid)),
+        // A relative pattern would drop a root-owned file in whatever
+        // directory the process was in
+        SUID_DUMP_ROOT if path.starts_with('/') => Some((0, 0)),
+        _ => None,
+    }
+}
+
+/// Whether an existing file may be truncated and reused as the core file:
+/// a regular file with a single link, owned by `fsuid` and writable by it
+fn may_overwrite(attr: &FileAttr, fsuid: u32) -> bool {
+    attr.mode.is_regular() && attr.nlink <= 1 && attr.uid == fsuid && attr.mode.0 & 0o200 != 0
+}
+
+/// Create the core file for a pattern that names a path
+fn dump_to_file(snap: &Snapshot, path: &str, sig: Signal, info: &SigInfo) -> Result<(), ()> {
+    if snap.limit < PAGE_SIZE as u64 {
+        return Err(());
+    }
+    let (fsuid, fsgid) = dump_owner(snap.dumpable, snap.euid, snap.egid, path).ok_or(())?;
+    let ctx = super::manager::fs_context().map_err(|_| ())?;
+    // Never follow a symlink planted where the core file goes
+    let create = |exclusive| {
+        crate::vfs::vfs().create_at(&ctx, None, path, FileMode(0o600), LookupFlags(0), exclusive)
+    };
+    let file = match create(true) {
+        Ok(file) => {
+            let _ = file.inode.setattr(&SetAttr::chown(Some(fsuid), Some(fsgid)));
+            file
+        }
+        Err(VfsError::Exists) if snap.dumpable == SUID_DUMP_USER => {
+            let file = create(false).map_err(|_| ())?;
+            if !may_overwrite(&file.inode.getattr().map_err(|_| ())?, fsuid) {
+                return Err(());
+            }
+            file
+        }
+        Err(_) => return Err(()),
+    };
+    file.inode.setattr(&SetAttr::truncate(0)).map_err(|_| ())?;
+
+    let mut sink = FileSink { inode: file.inode.clone(), offset: 0 };
+    let mut out = Limited { sink: &mut sink, written: 0, limit: snap.limit };
+    let _ = write_core(snap, sig, info, &mut out);
+    Ok(())
+}
+
+/// Run the helper named by a `|` pattern and stream the dump to it
+fn dump_to_pipe(snap: &Snapshot, command: &str, sig: Signal, info: &SigInfo) -> Result<(), ()> {
+    // A limit of 1 is how a helper dumping itself stops the recursion
+    if snap.limit == 1 {
+        return Err(());
+    }
+    let pattern = pattern_info(snap, sig);
+    // Split before expanding so a specifier cannot inject arguments
+    let args: Vec<String> = command
+        .split(' ')
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| expand_pattern(arg, &pattern))
+        .collect();
+    let program = args.first().ok_or(())?;
+    let argv: Vec<&[u8]> = args.iter().map(|a| a.as_bytes()).collect();
+
+    let (read_idx, write_idx) = crate::ipc::pipe::pipe_alloc().ok_or(())?;
+    let spawned = super::exec::spawn(program, &argv, Some(read_idx));
+    crate::fs::file::file_close(read_idx);
+    if spawned.is_err() {
+        crate::fs::file::file_close(write_idx);
+        return Err(());
+    }
+
+    let mut sink = PipeSink { file_idx: write_idx };
+    let mut out = Limited { sink: &mut sink, written: 0, limit: u64::MAX };
+    let _ = write_core(snap, sig, info, &mut out);
+    crate::fs::file::file_close(write_idx);
+    Ok(())
+}
+
+/// Dump core for the current process, killed by `sig`
+///
+/// Returns whether a core was written, for the wait status.
+pub fn dump(sig: Signal, info: &SigInfo) -> bool {
+    let Some(pid) = myproc() else { return false };
+    let Some(snap) = snapshot(pid) else { return false };
+    if snap.dumpable == SUID_DUMP_DISABLE {
+        return false;
+    }
+
+    let pattern = core_pattern();
+    let result = match pattern.strip_prefix('|') {
+        Some(command) => dump_to_pipe(&snap, command, sig, info),
+        None => {
+            let path = expand_pattern(&pattern, &pattern_info(&snap, sig));
+            dump_to_file(&snap, &path, sig, info)
+        }
+    };
+    result.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> PatternInfo {
+        PatternInfo {
+            pid: 42,
+            tid: 42,
+            uid: 1000,
+            gid: 100,
+            signal: 11,
+            time: 1700000000,
+            hostname: String::from("box"),
+            comm: String::from("a/b"),
+            exe: String::from("/usr/bin/crash"),
+            limit: 4096,
+            dumpable: SUID_DUMP_USER,
+        }
+    }
+
+    #[test]
+    fn test_expand_pattern() {
+        assert_eq!(expand_pattern("core", &info()), "core");
+        assert_eq!(expand_pattern("core.%p", &info()), "core.42");
+        assert_eq!(expand_pattern("/var/core/%e-%s-%u.%g@%h", &info()), "/var/core/a!b-11-1000.100@box");
+        assert_eq!(expand_pattern("%E %t %c %d %i %%x %q", &info()), "!usr!bin!crash 1700000000 4096 1 42 %x ");
+        assert_eq!(expand_pattern("trailing%", &info()), "trailing");
+    }
+
+    #[test]
+    fn test_note_padding() {
+        let mut notes = Vec::new();
+        push_note(&mut notes, NT_AUXV, &[1, 2, 3]);
+        // 12-byte header, "CORE\0" padded to 8, 3 bytes padded to 4
+        assert_eq!(notes.len(), 12 + 8 + 4);
+        assert_eq!(&notes[0..4], &5u32.to_le_bytes());
+        assert_eq!(&notes[4..8], &3u32.to_le_bytes());
+        assert_eq!(&notes[8..12], &NT_AUXV.to_le_bytes());
+        assert_eq!(&notes[12..17], b"CORE\0");
+    }
+
+    #[test]
+    fn test_prstatus_and_prpsinfo_layout() {
+        let mut regs = [0u64; ELF_NGREG];
+        regs[0] = 0xdead;
+        let thread = ThreadState { tid: 7, regs, pending: 1 << 10, blocked: 2 };
+        let proc = ProcessInfo {
+            pid: 7,
+            ppid: 1,
+            comm: b"crash".to_vec(),
+            args: b"crash --now".to_vec(),
+            cpu_ticks: 150,
+            ..Default::default()
+        };
+        let siginfo = SigInfo { code: 1, ..Default::default() };
+
+        let status = prstatus(&thread, &proc, 11, &siginfo);
+        assert_eq!(status.len() % 8, 0);
+        assert_eq!(&status[0..4], &11i32.to_le_bytes());
+        assert_eq!(&status[4..8], &1i32.to_le_bytes());
+        assert_eq!(&status[16..24], &(1u64 << 10).to_le_bytes());
+        assert_eq!(&status[32..36], &7i32.to_le_bytes());
+        assert_eq!(&status[48..56], &1u64.to_le_bytes());
+        assert_eq!(&status[56..64], &500_000u64.to_le_bytes());
+        assert_eq!(&status[112..120], &0xdeadu64.to_le_bytes());
+
+        let psinfo = prpsinfo(&proc);
+        assert_eq!(psinfo.len(), PRPSINFO_SIZE);
+        assert_eq!(psinfo[1], b'R');
+        assert_eq!(&psinfo[40..45], b"crash");
+        assert_eq!(&psinfo[56..67], b"crash --now");
+    }
+
+    #[test]
+    fn test_headers() {
+        let segments = [(0x10000, 0x12000), (0x20000, 0x21000)];
+        let (head, data_offset) = build_headers(100, &segments);
+        assert_eq!(head.len(), EHDR_SIZE + 3 * PHDR_SIZE);
+        assert_eq!(&head[0..4], &ELF_MAGIC);
+        assert_eq!(&head[16..18], &ET_CORE.to_le_bytes());
+        assert_eq!(&head[56..58], &3u16.to_le_bytes());
+        assert_eq!(data_offset % PAGE_SIZE, 0);
+
+        let load = &head[EHDR_SIZE + 2 * PHDR_SIZE..];
+        assert_eq!(&load[0..4], &PT_LOAD.to_le_bytes());
+        let offset = u64::from_le_bytes(load[8..16].try_into().unwrap());
+        assert_eq!(offset as usize, data_offset + 0x2000);
+        assert_eq!(&load[16..24], &0x20000u64.to_le_bytes());
+        assert_eq!(&load[32..40], &0x1000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_file_note() {
+        let note = file_note(&[(0x1000, 0x3000, 0), (0x5000, 0x6000, 2)], b"/bin/x");
+        assert_eq!(&note[0..8], &2u64.to_le_bytes());
+        assert_eq!(&note[8..16], &(PAGE_SIZE as u64).to_le_bytes());
+        assert_eq!(&note[16 + 40..16 + 48], &2u64.to_le_bytes());
+        assert_eq!(&note[64..], b"/bin/x\0/bin/x\0");
+    }
+
+    #[test]
+    fn test_dump_owner() {
+        assert_eq!(dump_owner(SUID_DUMP_USER, 1000, 100, "core"), Some((1000, 100)));
+        assert_eq!(dump_owner(SUID_DUMP_ROOT, 1000, 100, "/var/core/%p"), Some((0, 0)));
+        // Not dumpable, or dumping as root to a relative path
+        assert_eq!(dump_owner(SUID_DUMP_DISABLE, 1000, 100, "/var/core/%p"), None);
+        assert_eq!(dump_owner(SUID_DUMP_ROOT, 1000, 100, "core"), None);
+    }
+
+    #[test]
+    fn test_may_overwrite() {
+        let file = FileAttr { mode: FileMode(FileMode::S_IFREG | 0o600), nlink: 1, uid: 1000, ..Default::default() };
+        assert!(may_overwrite(&file, 1000));
+        // Someone else's file, even one the dumper could write
+        assert!(!may_overwrite(&FileAttr { mode: FileMode(FileMode::S_IFREG | 0o666), ..file.clone() }, 0));
+        assert!(!may_overwrite(&file, 1001));
+        // Read-only, hard-linked or not a regular file
+        assert!(!may_overwrite(&FileAttr { mode: FileMode(FileMode::S_IFREG | 0o400), ..file.clone() }, 1000));
+        assert!(!may_overwrite(&FileAttr { nlink: 2, ..file.clone() }, 1000));
+        assert!(!may_overwrite(&FileAttr { mode: FileMode(FileMode::S_IFLNK | 0o777), ..file.clone() }, 1000));
+    }
+
+    #[test]
+    fn test_suid_dumpable() {
+        assert_eq!(exec_dumpable(1000, 1000, 100, 100), SUID_DUMP_USER);
+        assert!(set_suid_dumpable("3").is_err());
+        assert!(set_suid_dumpable("2\n").is_ok());
+        assert_eq!(exec_dumpable(1000, 0, 100, 100), SUID_DUMP_ROOT);
+        assert!(set_suid_dumpable("0").is_ok());
+        assert_eq!(exec_dumpable(1000, 1000, 100, 0), SUID_DUMP_DISABLE);
+    }
+
+    #[test]
+    fn test_core_pattern_limits() {
+        assert!(set_core_pattern(&"x".repeat(CORENAME_MAX_SIZE)).is_err());
+        assert!(set_core_pattern("|/sbin/helper %p\n").is_ok());
+        assert_eq!(core_pattern(), "|/sbin/helper %p");
+        set_core_pattern(DEFAULT_CORE_PATTERN).unwrap();
+    }
+}
@@ -9,6 +9,7 @@ extern crate alloc;
 use alloc::vec::Vec;
 use alloc::string::String;
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use core::mem::size_of;
 use crate::process::elf::{ElfLoader, ElfError};
 
@@ -228,7 +229,8 @@ impl DynamicLinker {
     ///
     /// * `path` - Path to the .so file
     /// * `base` - Base address to load the library at (0 for ASLR)
-    /// * `map_page` - Callback to map pages into memory
+    /// * `map_segments` - Callback to map the library's segments at the
+    ///   chosen base, from the library's page cache when it has one
     ///
     /// # Returns
     ///
@@ -238,13 +240,14 @@ impl DynamicLinker {
         &mut self,
         path: &str,
         base: usize,
-        map_page: F,
+        map_segments: F,
     ) -> Result<LoadedLibrary, ElfError>
     where
-        F: FnMut(usize, bool, bool, bool) -> Option<*mut u8>,
+        F: FnOnce(&ElfLoader, usize, Option<&Arc<crate::vfs::AddressSpace>>) -> Result<(), ElfError>,
+        // (library, base, page cache) -> mapped
     {
         // Read library file
-        let data = self.read_library_file(path)?;
+        let (data, mapping) = self.read_library_file(path)?;
         
         // Parse ELF
         let loader = ElfLoader::new(&data)?;
@@ -257,14 +260,9 @@ impl DynamicLinker {
             base
         };
         
-        // Create a mutable closure for map_page that adjusts addresses
-        let mut map_page_fn = map_page;
-        let base_offset = actual_base;
-        
-        // Load ELF segments with base address adjustment
-        let elf_info = loader.load(|vaddr, readable, writable, executable| {
-            map_page_fn(base_offset + vaddr, readable, writable, executable)
-        })?;
+        // Map ELF segments at the base address
+        map_segments(&loader, actual_base, mapping.as_ref())?;
+        let elf_info = loader.info();
         
         // Parse dynamic section
         let dynamic_phdr = loader.program_headers()
@@ -642,8 +640,8 @@ impl DynamicLinker {
         Ok(())
     }
     
-    /// Read library file from filesystem
-    fn read_library_file(&self, path: &str) -> Result<Vec<u8>, ElfError> {
+    /// Read library file from filesystem, along with its page cache
+    fn read_library_file(&self, path: &str) -> Result<(Vec<u8>, Option<Arc<crate::vfs::AddressSpace>>), ElfError> {
         // Try direct path first
         if let Ok(mut file) = crate::vfs::vfs().open(path, crate::posix::O_RDONLY as u32) {
             let mut data = Vec::new();
@@ -655,7 +653,7 @@ impl DynamicLinker {
                     Err(_) => break,
                 }
             }
-            return Ok(data);
+            return Ok((data, file.inode.mapping()));
         }
         
         // Try search paths
@@ -671,7 +669,7 @@ impl DynamicLinker {
                         Err(_) => break,
                     }
                 }
-                return Ok(data);
+                return Ok((data, file.inode.mapping()));
             }
         }
         
@@ -16,9 +16,15 @@ pub const ELFCLASS64: u8 = 2;
 /// ELF Data encoding
 pub const ELFDATA2LSB: u8 = 1; // Little endian
 
+/// OS ABI byte of e_ident
+pub const EI_OSABI: usize = 7;
+pub const ELFOSABI_SYSV: u8 = 0;
+pub const ELFOSABI_LINUX: u8 = 3;
+
 /// ELF Type
 pub const ET_EXEC: u16 = 2;    // Executable
 pub const ET_DYN: u16 = 3;     // Shared object (PIE)
+pub const ET_CORE: u16 = 4;    // Core dump
 
 /// ELF Machine
 pub const EM_RISCV: u16 = 243;
@@ -34,6 +40,14 @@ pub const PT_NOTE: u32 = 4;
 pub const PT_PHDR: u32 = 6;
 pub const PT_TLS: u32 = 7;
 
+/// Note types, qualified by the note's owner name
+pub const NT_GNU_ABI_TAG: u32 = 1; // "GNU": target OS and minimum kernel
+pub const NT_NOS_ABI: u32 = 1;     // "NOS": built against the native syscall ABI
+pub const NT_PRSTATUS: u32 = 1;    // "CORE": thread registers and signal state
+pub const NT_PRPSINFO: u32 = 3;    // "CORE": process name, IDs and arguments
+pub const NT_AUXV: u32 = 6;        // "CORE": auxiliary vector
+pub const NT_FILE: u32 = 0x4649_4c45; // "CORE": file-backed mappings
+
 /// Program header flags
 pub const PF_X: u32 = 1;       // Executable
 pub const PF_W: u32 = 2;       // Writable
@@ -345,9 +359,7 @@ impl<'a> ElfLoader<'a> {
         F: FnMut(usize, bool, bool, bool) -> Option<*mut u8>,
         // (vaddr, readable, writable, executable) -> mapped_ptr
     {
-        let mut brk: usize = 0;
-        let mut base = 0usize;
-        let mut has_interp = false;
+        let base = 0usize;
         
         // Load each segment
         for ph in self.load_segments() {
@@ -356,9 +368,6 @@ impl<'a> ElfLoader<'a> {
             let file_start = ph.p_offset as usize;
             let file_size = ph.p_filesz as usize;
             
-            // Update program break
-            brk = brk.max(vaddr_end);
-            
             // Map pages for this segment
             let page_start = page_align_down(vaddr_start);
             let page_end = page_align_up(vaddr_end);
@@ -401,6 +410,22 @@ impl<'a> ElfLoader<'a> {
             }
         }
         
+        Ok(self.info())
+    }
+
+    /// Entry point, base, program break and interpreter of the program,
+    /// as `load` reports them, without loading anything
+    pub fn info(&self) -> ElfInfo {
+        let mut base = 0usize;
+        let mut has_interp = false;
+
+        // Program break (end of loaded segments)
+        let brk = self
+            .load_segments()
+            .map(|ph| ph.p_vaddr as usize + ph.p_memsz as usize)
+            .max()
+            .unwrap_or(0);
+        
         // Default stack at high address
         let default_stack = 0x7FFF_FFFF_F000usize;
         
@@ -420,13 +445,13 @@ impl<'a> ElfLoader<'a> {
         if self.header.e_type == ET_DYN || has_interp {
             base = 0x400000;
         }
-        Ok(ElfInfo {
+        ElfInfo {
             entry: (self.header.e_entry as usize).wrapping_add(base),
             base,
             brk: page_align_up(brk),
             sp: default_stack,
             interp,
-        })
+        }
     }
     
     /// Get segment data for a program header
@@ -440,6 +465,58 @@ impl<'a> ElfLoader<'a> {
             None
         }
     }
+
+    /// Notes from every PT_NOTE segment
+    pub fn notes(&self) -> impl Iterator<Item = ElfNote<'a>> + '_ {
+        self.program_headers()
+            .filter(|ph| ph.p_type == PT_NOTE)
+            .filter_map(|ph| self.segment_data(ph))
+            .flat_map(|data| ElfNotes { data })
+    }
+}
+
+/// One entry of a note segment
+#[derive(Debug, Clone, Copy)]
+pub struct ElfNote<'a> {
+    /// Owner name without its NUL terminator
+    pub name: &'a [u8],
+    pub n_type: u32,
+    pub desc: &'a [u8],
+}
+
+/// Iterator over the 4-byte aligned entries of a note segment
+struct ElfNotes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for ElfNotes<'a> {
+    type Item = ElfNote<'a>;
+
+    fn next(&mut self) -> Option<ElfNote<'a>> {
+        let word = |i: usize| {
+            self.data.get(i * 4..i * 4 + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+        };
+        let namesz = word(0)?;
+        let descsz = word(1)?;
+        let n_type = word(2)? as u32;
+
+        let name_end = 12usize.checked_add(namesz)?;
+        let desc_start = 12 + namesz.checked_add(3)? / 4 * 4;
+        let desc_end = desc_start.checked_add(descsz)?;
+        let next = desc_start + descsz.checked_add(3)? / 4 * 4;
+        if desc_end > self.data.len() {
+            self.data = &[];
+            return None;
+        }
+
+        let mut name = &self.data[12..name_end];
+        if let [rest @ .., 0] = name {
+            name = rest;
+        }
+        let note = ElfNote { name, n_type, desc: &self.data[desc_start..desc_end] };
+        self.data = self.data.get(next..).unwrap_or(&[]);
+        Some(note)
+    }
 }
 
 // ============================================================================
@@ -5,15 +5,17 @@
 
 extern crate alloc;
 
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::ptr;
 
-use crate::process::elf::{ElfLoader, ElfError, AuxEntry, AuxType, PT_INTERP, PT_DYNAMIC};
+use crate::process::elf::{page_align_down, page_align_up, ElfLoader, ElfError, AuxEntry, AuxType, PT_INTERP, PT_DYNAMIC};
 use crate::process::dynamic_linker::DynamicLinker;
 use crate::subsystems::mm::{kalloc, kfree, PAGE_SIZE};
-use crate::process::{myproc, TrapFrame, PROC_TABLE};
+use crate::process::{myproc, Pid, TrapFrame, PROC_TABLE};
 use crate::subsystems::mm::vm::arch::PageTable;
-use crate::subsystems::mm::vm::{activate, map_pages, flags, copyout, PTE_COUNT};
+use crate::subsystems::mm::vm::{activate, map_pages, flags, copyout, user_frame, VmArea, VmPerm, VmSpace, PTE_COUNT};
+use crate::vfs::AddressSpace;
 use crate::reliability::errno::{errno_neg, ENOENT};
 use alloc::string::String as AString;
 
@@ -59,13 +61,67 @@ impl From<ElfError> for ExecError {
     }
 }
 
+/// What exec loaded into a process, kept for core dumps
+#[derive(Debug, Clone, Default)]
+pub struct ExecImage {
+    /// Path the program was executed as (`AT_EXECFN`)
+    pub path: Vec<u8>,
+    /// Arguments separated by spaces
+    pub args: Vec<u8>,
+    /// Auxiliary vector given to the program, ending with `AT_NULL`
+    pub auxv: Vec<(u64, u64)>,
+    /// Ranges loaded from the executable: start, end and file offset in
+    /// pages
+    pub file_ranges: Vec<(usize, usize, usize)>,
+    /// Page-aligned ranges exec mapped: the program's segments and the
+    /// stack. Heap and anonymous mappings lie between `stack_top` and
+    /// `Proc::sz`.
+    pub regions: Vec<(usize, usize)>,
+    pub stack_top: usize,
+}
+
+impl ExecImage {
+    /// Last path component, cut to 15 bytes like Linux's `comm`
+    pub fn comm(&self) -> &[u8] {
+        let name = self.path.rsplit(|&b| b == b'/').next().unwrap_or(&[]);
+        &name[..name.len().min(15)]
+    }
+}
+
 /// Execute a program from ELF data
 ///
 /// Loads an ELF binary from `elf_data` and replaces the current process's
 /// memory image with it. The `argv` array contains command line arguments.
+/// With the page cache of the file the binary was read from, `mapping`,
+/// its segments are mapped from the cache rather than copied.
 ///
 /// Returns the entry point on success, or an error.
-pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8]>) -> Result<usize, ExecError> {
+pub fn exec(
+    elf_data: &[u8],
+    mapping: Option<Arc<AddressSpace>>,
+    argv: &[&[u8]],
+    envp: &[&[u8]],
+    execfn: Option<&[u8]>,
+) -> Result<usize, ExecError> {
+    let pid = myproc().ok_or(ExecError::NoProcess)?;
+    let entry = exec_as(pid, elf_data, mapping, argv, envp, execfn)?;
+    // The old image's thread and process keyrings go with it
+    crate::security::keys::exec(pid as u64);
+    // A traced process stops once the new image is in place
+    super::ptrace::report_exec(pid);
+    Ok(entry)
+}
+
+/// Replace the memory image of `pid`, which need not be the current
+/// process; the new page table is only switched to if it is
+pub fn exec_as(
+    pid: Pid,
+    elf_data: &[u8],
+    mapping: Option<Arc<AddressSpace>>,
+    argv: &[&[u8]],
+    envp: &[&[u8]],
+    execfn: Option<&[u8]>,
+) -> Result<usize, ExecError> {
     // Validate arguments
     if argv.len() > MAX_ARGS {
         return Err(ExecError::TooManyArgs);
@@ -76,21 +132,36 @@ pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8
             return Err(ExecError::ArgTooLong);
         }
     }
-    
+
+    // Like Linux, strings plus pointers may take at most a quarter of
+    // RLIMIT_STACK
+    let arg_bytes: usize = argv
+        .iter()
+        .chain(envp)
+        .map(|s| s.len() + 1 + core::mem::size_of::<usize>())
+        .sum();
+    let stack_limit = PROC_TABLE
+        .lock()
+        .find_ref(pid)
+        .ok_or(ExecError::NoProcess)?
+        .rlimits[crate::posix::RLIMIT_STACK as usize]
+        .rlim_cur;
+    if arg_bytes as u64 > stack_limit / 4 {
+        return Err(ExecError::TooManyArgs);
+    }
+
     if elf_data.len() > MAX_EXEC_SIZE {
         return Err(ExecError::FileTooLarge);
     }
     
     // Parse and validate ELF
     let loader = ElfLoader::new(elf_data)?;
+    let personality = crate::compat::linux::personality_of(&loader);
     
     // Check if this is a dynamically linked executable
     let has_interp = loader.program_headers().any(|ph| ph.p_type == PT_INTERP);
     let has_dynamic = loader.program_headers().any(|ph| ph.p_type == PT_DYNAMIC);
     
-    // Get current process PID
-    let pid = myproc().ok_or(ExecError::NoProcess)?;
-    
     // Initialize ASLR for this process if not already initialized
     if crate::security::is_aslr_enabled() {
         let _ = crate::security::init_process_aslr_by_pid(pid as u64);
@@ -106,22 +177,10 @@ pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8
         None
     };
     
-    // Load ELF using the ElfLoader API
-    let elf_info = loader.load(|vaddr, readable, writable, executable| {
-        let pa = kalloc();
-        if pa.is_null() { return None; }
-        unsafe {
-            let mut perm = flags::PTE_U;
-            if readable { perm |= flags::PTE_R; }
-            if writable { perm |= flags::PTE_W; }
-            if executable { perm |= flags::PTE_X; }
-            if map_pages(new_pagetable, vaddr, pa as usize, PAGE_SIZE, perm).is_err() {
-                kfree(pa);
-                return None;
-            }
-        }
-        Some(pa)
-    })?;
+    // Map the segments, from the page cache where the file has one
+    let mut vm = VmSpace::default();
+    let elf_info = loader.info();
+    map_segments(&loader, new_pagetable, mapping.as_ref(), 0, &mut vm)?;
     
     // Randomize stack base address if ASLR is enabled
     let stack_top = if crate::security::is_aslr_enabled() {
@@ -156,6 +215,7 @@ pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8
             }
         }
     }
+    add_segment_area(&mut vm, VmArea::anonymous(stack_bottom..stack_top, VmPerm::rw()))?;
     
     // Load shared libraries if dynamically linked
     if let Some(ref mut linker) = dynamic_linker {
@@ -173,20 +233,8 @@ pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8
                 let _ = linker.load_library(
                     &lib_name,
                     0, // ASLR base
-                    |vaddr, readable, writable, executable| {
-                        let pa = kalloc();
-                        if pa.is_null() { return None; }
-                        unsafe {
-                            let mut perm = flags::PTE_U;
-                            if readable { perm |= flags::PTE_R; }
-                            if writable { perm |= flags::PTE_W; }
-                            if executable { perm |= flags::PTE_X; }
-                            if map_pages(new_pagetable, vaddr, pa as usize, PAGE_SIZE, perm).is_err() {
-                                kfree(pa);
-                                return None;
-                            }
-                        }
-                        Some(pa)
+                    |library, base, mapping| {
+                        map_segments(library, new_pagetable, mapping, base, &mut vm).map_err(|_| ElfError::OutOfMemory)
                     },
                 );
             }
@@ -245,20 +293,63 @@ pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8
     let (sp, argc, argv_ptr) = write_args_to_stack(new_pagetable, stack_top, argv, &mut auxv, envp, execfn, Some(platform_bytes()))?;
     
     let entry = elf_info.entry;
+
+    let image = ExecImage {
+        path: execfn.unwrap_or_default().to_vec(),
+        args: argv.join(&b' '),
+        auxv: auxv.iter().map(|e| (e.a_type as u64, e.a_val as u64)).collect(),
+        file_ranges: loader
+            .program_headers()
+            .filter(|ph| ph.is_load() && ph.p_filesz > 0)
+            .map(|ph| {
+                let start = elf_info.base + ph.p_vaddr as usize;
+                let skew = start & (PAGE_SIZE - 1);
+                let end = (start + ph.p_filesz as usize + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+                (start - skew, end, (ph.p_offset as usize - skew) / PAGE_SIZE)
+            })
+            .collect(),
+        regions: loader
+            .program_headers()
+            .filter(|ph| ph.is_load())
+            .map(|ph| {
+                let start = elf_info.base + ph.p_vaddr as usize;
+                let end = start + ph.p_memsz as usize;
+                (start & !(PAGE_SIZE - 1), (end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1))
+            })
+            .chain(core::iter::once((stack_bottom, stack_top)))
+            .collect(),
+        stack_top,
+    };
     
     // Update process with new address space
     {
         let mut table = PROC_TABLE.lock();
         if let Some(proc) = table.find(pid) {
-            // Free old page table
+            // Free old page table, its pages and swap slots released first
             let old_pagetable = proc.pagetable;
             if !old_pagetable.is_null() {
+                unsafe { crate::subsystems::mm::vm::release_user_pages(old_pagetable, &proc.vm) };
                 free_user_pagetable(old_pagetable);
             }
+            crate::subsystems::mm::swap::lru::forget_pid(pid);
             
             // Install new page table
             proc.pagetable = new_pagetable;
+            proc.vm = vm;
             proc.sz = stack_top;
+            proc.personality = personality;
+            proc.image = Some(alloc::sync::Arc::new(image));
+            proc.dumpable = super::coredump::exec_dumpable(proc.uid, proc.euid, proc.gid, proc.egid);
+
+            // A descriptor table shared through CLONE_FILES is unshared
+            // so the old image's sharers don't see the new program's files
+            if alloc::sync::Arc::strong_count(&proc.ofile) > 1 {
+                let fds = proc.ofile.lock().clone();
+                for (_, file_idx) in fds.iter() {
+                    crate::fs::file::file_dup(file_idx);
+                }
+                proc.ofile = fds.shared();
+            }
             
             // Set up trapframe for return to user
             let tf = proc.trapframe;
@@ -269,9 +360,12 @@ pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8
             }
             
             // Activate new page table
-            unsafe { activate(new_pagetable); }
+            if myproc() == Some(pid) {
+                unsafe { activate(new_pagetable); }
+            }
         } else {
             // Process not found, clean up
+            unsafe { crate::subsystems::mm::vm::release_user_pages(new_pagetable, &vm) };
             free_user_pagetable(new_pagetable);
             return Err(ExecError::NoProcess);
         }
@@ -280,6 +374,102 @@ pub fn exec(elf_data: &[u8], argv: &[&[u8]], envp: &[&[u8]], execfn: Option<&[u8
     Ok(entry)
 }
 
+/// Map the PT_LOAD segments of `loader` at `base` into `pagetable`,
+/// recording their areas in `vm`
+///
+/// With the file's page cache at hand, file contents are mapped private:
+/// they are paged in as they are touched, shared by every process running
+/// the program, and copied at the first write. The page where the file
+/// contents end and the bss begins is copied up front, since its tail has
+/// to read as zeroes, as is a page an earlier segment mapped already; the
+/// rest of the bss is anonymous memory. Without a page cache, or for a
+/// segment whose file offset doesn't line up with its address, the file
+/// contents are all copied.
+fn map_segments(
+    loader: &ElfLoader,
+    pagetable: *mut PageTable,
+    mapping: Option<&Arc<AddressSpace>>,
+    base: usize,
+    vm: &mut VmSpace,
+) -> Result<(), ExecError> {
+    for ph in loader.load_segments() {
+        let perm = VmPerm {
+            read: ph.is_readable(),
+            write: ph.is_writable(),
+            exec: ph.is_executable(),
+            user: true,
+        };
+        let data = loader.segment_data(ph).ok_or(ExecError::InvalidElf)?;
+        let start = base + ph.p_vaddr as usize;
+        let file_end = start + data.len();
+        let mem_end = start + (ph.p_memsz as usize).max(data.len());
+        let page_start = page_align_down(start);
+
+        // [file_start, file_stop) maps the file, the rest is copied
+        let mut file_start = page_start;
+        let mut file_stop = page_start;
+        if let Some(mapping) = mapping
+            && ph.p_offset as usize % PAGE_SIZE == start % PAGE_SIZE
+        {
+            if user_frame(pagetable, page_start).is_some() {
+                file_start += PAGE_SIZE;
+            }
+            file_stop = if mem_end > file_end { page_align_down(file_end) } else { page_align_up(file_end) };
+            if file_stop > file_start {
+                let offset = ph.p_offset as usize - (start - file_start);
+                let area = VmArea::file(file_start..file_stop, perm, mapping.clone(), offset, true);
+                add_segment_area(vm, area)?;
+            } else {
+                (file_start, file_stop) = (page_start, page_start);
+            }
+        }
+
+        let copied = (page_start..file_start).chain(file_stop.max(file_start)..page_align_up(file_end));
+        for va in copied.step_by(PAGE_SIZE) {
+            let page = segment_page(pagetable, va, perm)?;
+            let (from, to) = (start.max(va), file_end.min(va + PAGE_SIZE));
+            if to > from {
+                unsafe { ptr::copy_nonoverlapping(data.as_ptr().add(from - start), page.add(from - va), to - from) };
+            }
+        }
+        if file_start > page_start {
+            add_segment_area(vm, VmArea::anonymous(page_start..file_start, perm))?;
+        }
+        let anon_start = file_stop.max(file_start);
+        if page_align_up(mem_end) > anon_start {
+            add_segment_area(vm, VmArea::anonymous(anon_start..page_align_up(mem_end), perm))?;
+        }
+    }
+    Ok(())
+}
+
+/// The page at `va` to copy segment contents into: a zeroed page mapped
+/// for it, or the page an earlier segment sharing it mapped
+fn segment_page(pagetable: *mut PageTable, va: usize, perm: VmPerm) -> Result<*mut u8, ExecError> {
+    if let Some(pa) = user_frame(pagetable, va) {
+        return Ok(pa as *mut u8);
+    }
+    let pa = kalloc();
+    if pa.is_null() {
+        return Err(ExecError::OutOfMemory);
+    }
+    unsafe {
+        ptr::write_bytes(pa, 0, PAGE_SIZE);
+        if map_pages(pagetable, va, pa as usize, PAGE_SIZE, perm.to_pte_flags()).is_err() {
+            kfree(pa);
+            return Err(ExecError::OutOfMemory);
+        }
+    }
+    Ok(pa)
+}
+
+/// Record a segment's area, taking over from an earlier segment where they
+/// share pages
+fn add_segment_area(vm: &mut VmSpace, area: VmArea) -> Result<(), ExecError> {
+    vm.remove_range(area.range.start, area.range.end);
+    vm.insert(area).map_err(|_| ExecError::InvalidElf)
+}
+
 /// Set up trapframe for returning to user space
 unsafe fn setup_trapframe(tf: *mut TrapFrame, entry: usize, sp: usize, argc: usize, argv: usize) {
     // Validate entry point and stack pointer
@@ -476,9 +666,7 @@ pub fn sys_exec(path: usize, argv: usize) -> isize {
     
     // Read ELF from VFS and execute
     let path_str = match alloc::string::String::from_utf8(path_slice) { Ok(s) => s, Err(_) => return -1 };
-    let abs_path = resolve_with_cwd(&path_str);
-    let vfs = crate::vfs::vfs();
-    let mut file = match vfs.open(&abs_path, crate::posix::O_RDONLY as u32) { Ok(f) => f, Err(_) => return errno_neg(ENOENT) };
+    let (mut file, abs_path) = match open_program(&path_str) { Ok(r) => r, Err(_) => return errno_neg(ENOENT) };
     let mut buf = alloc::vec::Vec::new();
     let mut tmp = [0u8; 512];
     loop {
@@ -486,7 +674,7 @@ pub fn sys_exec(path: usize, argv: usize) -> isize {
         if n == 0 { break; }
         buf.extend_from_slice(&tmp[..n]);
     }
-    match exec(&buf, &arg_slices, &[], Some(abs_path.as_bytes())) {
+    match exec(&buf, file.inode.mapping(), &arg_slices, &[], Some(abs_path.as_bytes())) {
         Ok(_) => 0,
         Err(_) => -1,
     }
@@ -499,9 +687,7 @@ pub fn sys_execve(path: usize, argv: usize, envp: usize) -> isize {
     let arg_slices: Vec<&[u8]> = args.iter().map(|a| a.as_slice()).collect();
     let env_slices: Vec<&[u8]> = envs.iter().map(|a| a.as_slice()).collect();
     let path_str = match alloc::string::String::from_utf8(path_slice) { Ok(s) => s, Err(_) => return -1 };
-    let abs_path = resolve_with_cwd(&path_str);
-    let vfs = crate::vfs::vfs();
-    let mut file = match vfs.open(&abs_path, crate::posix::O_RDONLY as u32) { Ok(f) => f, Err(_) => return errno_neg(ENOENT) };
+    let (mut file, abs_path) = match open_program(&path_str) { Ok(r) => r, Err(_) => return errno_neg(ENOENT) };
     let mut buf = alloc::vec::Vec::new();
     let mut tmp = [0u8; 512];
     loop {
@@ -509,39 +695,73 @@ pub fn sys_execve(path: usize, argv: usize, envp: usize) -> isize {
         if n == 0 { break; }
         buf.extend_from_slice(&tmp[..n]);
     }
-    match exec(&buf, &arg_slices, &env_slices, Some(abs_path.as_bytes())) {
+    match exec(&buf, file.inode.mapping(), &arg_slices, &env_slices, Some(abs_path.as_bytes())) {
         Ok(_) => 0,
         Err(_) => -1,
     }
 }
 
-fn join_path(base: &str, rel: &str) -> AString {
-    let mut out: alloc::vec::Vec<AString> = alloc::vec::Vec::new();
-    let is_abs = rel.starts_with('/');
-    if !is_abs {
-        for p in base.split('/').filter(|s| !s.is_empty()) { out.push(AString::from(p)); }
+/// Start the program at `path` in a new process on behalf of the kernel,
+/// like a Linux usermode helper
+///
+/// The program runs as root from the global root directory, is adopted by
+/// init, and gets `stdin` (a file table index) as descriptor 0.
+pub fn spawn(path: &str, argv: &[&[u8]], stdin: Option<usize>) -> Result<Pid, ExecError> {
+    let ctx = crate::vfs::FsContext::global().map_err(|_| ExecError::FileNotFound)?;
+    let (elf_data, mapping, name) = read_program(&ctx, path).map_err(|_| ExecError::FileNotFound)?;
+
+    let pid = PROC_TABLE.lock().alloc().ok_or(ExecError::OutOfMemory)?.pid;
+    if let Err(e) = exec_as(pid, &elf_data, mapping, argv, &[], Some(name.as_bytes())) {
+        PROC_TABLE.lock().free(pid);
+        return Err(e);
     }
-    for p in rel.split('/').filter(|s| !s.is_empty()) {
-        if p == "." { continue; }
-        if p == ".." { if !out.is_empty() { out.pop(); } continue; }
-        out.push(AString::from(p));
+
+    let mut table = PROC_TABLE.lock();
+    let proc = table.find(pid).ok_or(ExecError::NoProcess)?;
+    if let Some(file_idx) = stdin {
+        crate::fs::file::file_dup(file_idx);
+        let _ = proc.ofile.lock().install(0, file_idx, 1);
     }
-    let mut s = AString::from("/");
-    for (i, seg) in out.iter().enumerate() {
-        if i > 0 { s.push('/'); }
-        s.push_str(seg);
+    proc.parent = Some(1);
+    proc.state = crate::process::ProcState::Runnable;
+    table.add_child_to_parent(1, pid);
+    Ok(pid)
+}
+
+/// Read a whole executable, along with its page cache and the path to
+/// report for it
+fn read_program(
+    ctx: &crate::vfs::FsContext,
+    path: &str,
+) -> crate::vfs::VfsResult<(Vec<u8>, Option<Arc<AddressSpace>>, AString)> {
+    let (mut file, name) = open_program_in(ctx, path)?;
+    let mut buf = Vec::new();
+    let mut tmp = [0u8; 512];
+    loop {
+        let n = file
+            .read(tmp.as_mut_ptr() as usize, tmp.len())
+            .map_err(|()| crate::vfs::VfsError::IoError)?;
+        if n == 0 { break; }
+        buf.extend_from_slice(&tmp[..n]);
     }
-    s
+    Ok((buf, file.inode.mapping(), name))
 }
 
-fn resolve_with_cwd(in_path: &str) -> AString {
-    if in_path.starts_with('/') { return AString::from(in_path); }
-    let mut ptable = PROC_TABLE.lock();
-    let cur = match crate::process::myproc().and_then(|pid| ptable.find(pid).and_then(|p| p.cwd_path.clone())) {
-        Some(s) => s,
-        None => AString::from("/"),
+/// Open an executable relative to the caller's root and working directory
+///
+/// Also returns the path as the caller sees it, for `AT_EXECFN`.
+fn open_program(path: &str) -> crate::vfs::VfsResult<(crate::vfs::VfsFile, AString)> {
+    open_program_in(&crate::process::fs_context()?, path)
+}
+
+fn open_program_in(ctx: &crate::vfs::FsContext, path: &str) -> crate::vfs::VfsResult<(crate::vfs::VfsFile, AString)> {
+    let flags = crate::vfs::LookupFlags(crate::vfs::LookupFlags::FOLLOW);
+    let file = crate::vfs::vfs().open_at(&ctx, None, path, flags, crate::posix::O_RDONLY as u32)?;
+    let name = match &file.loc {
+        Some(loc) => ctx.display_path(loc),
+        None => AString::from(path),
     };
-    join_path(&cur, in_path)
+    Ok((file, name))
 }
 
 /// Read a null-terminated string from user space
@@ -603,6 +823,19 @@ fn read_user_argv(addr: usize) -> Option<Vec<Vec<u8>>> {
     Some(args)
 }
 /// Write argv onto user stack
+///
+/// The stack is the System V initial one that `_start` of libc and of the
+/// native runtime reads, with `sp` 16-byte aligned:
+///
+/// ```text
+/// [sp]            argc
+/// [sp + 8]        argv[0] .. argv[argc - 1], NULL
+///                 envp[0] .. envp[n - 1], NULL
+///                 auxv pairs (type, value) .., AT_NULL
+///                 AT_RANDOM bytes and the strings
+/// ```
+///
+/// Returns `sp`, argc and the address of argv.
 fn write_args_to_stack(pagetable: *mut PageTable, stack_top: usize, argv: &[&[u8]], aux_entries: &mut [AuxEntry], envp: &[&[u8]], execfn: Option<&[u8]>, platform: Option<&[u8]>) -> Result<(usize, usize, usize), ExecError> {
     let argc = argv.len();
     let usize_sz = core::mem::size_of::<usize>();
@@ -615,9 +848,10 @@ fn write_args_to_stack(pagetable: *mut PageTable, stack_top: usize, argv: &[&[u8
     let aux_size = aux_entries.len() * 2 * usize_sz;
     let env_ptrs_size = (envp.len() + 1) * usize_sz;
     let rand_size = 16;
-    let total = strings_size + ptrs_size + env_ptrs_size + aux_size + usize_sz + rand_size + 16;
+    let argc_size = usize_sz;
+    let total = strings_size + argc_size + ptrs_size + env_ptrs_size + aux_size + rand_size + 16;
     let sp = (stack_top - total) & !0xF;
-    let mut cursor = sp;
+    let mut cursor = sp + argc_size;
     let argv_ptr = cursor; cursor += ptrs_size;
     let envp_ptr = cursor; cursor += env_ptrs_size;
     let auxv_ptr = cursor; cursor += aux_size;
@@ -634,6 +868,8 @@ fn write_args_to_stack(pagetable: *mut PageTable, stack_top: usize, argv: &[&[u8
     }
     ptrs.push(0);
 
+    let argc_bytes = argc.to_le_bytes();
+    unsafe { copyout(pagetable, sp, argc_bytes.as_ptr(), usize_sz).map_err(|_| ExecError::OutOfMemory)?; }
     for (i, p) in ptrs.iter().enumerate() {
         let bytes = (*p as usize).to_le_bytes();
         unsafe { copyout(pagetable, argv_ptr + i * usize_sz, bytes.as_ptr(), usize_sz).map_err(|_| ExecError::OutOfMemory)?; }
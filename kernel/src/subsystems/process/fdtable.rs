@@ -0,0 +1,162 @@
+//! Per-process file descriptor table
+//!
+//! Slots index into the global `FILE_TABLE`. The table starts small and
+//! doubles on demand up to the caller's `RLIMIT_NOFILE`, so the limit is
+//! checked at allocation time rather than baked into the table size.
+//! Processes created with `CLONE_FILES` share one table through
+//! [`SharedFdTable`].
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::subsystems::sync::Mutex;
+
+/// Slots allocated up front; enough for stdio and a few more
+pub const NOFILE_INIT: usize = 16;
+
+/// Hard ceiling on `RLIMIT_NOFILE` (Linux `nr_open`)
+pub const NR_OPEN: usize = 1024 * 1024;
+
+/// A descriptor table that may be shared between processes
+pub type SharedFdTable = Arc<Mutex<FdTable>>;
+
+#[derive(Debug, Clone)]
+pub struct FdTable {
+    slots: Vec<Option<usize>>,
+}
+
+impl FdTable {
+    pub fn new() -> Self {
+        let mut slots = Vec::new();
+        slots.resize(NOFILE_INIT, None);
+        Self { slots }
+    }
+
+    pub fn shared(self) -> SharedFdTable {
+        Arc::new(Mutex::new(self))
+    }
+
+    /// Number of slots currently allocated (not the number of open files)
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// File table index behind `fd`
+    pub fn get(&self, fd: i32) -> Option<usize> {
+        usize::try_from(fd).ok().and_then(|fd| self.slots.get(fd).copied().flatten())
+    }
+
+    /// Put `file_idx` in the lowest free slot at or above `min` and below
+    /// `limit`, growing the table if needed
+    pub fn alloc(&mut self, min: usize, file_idx: usize, limit: usize) -> Option<i32> {
+        let limit = limit.min(NR_OPEN);
+        let fd = (min..self.slots.len().min(limit))
+            .find(|&fd| self.slots[fd].is_none())
+            .or_else(|| {
+                let fd = min.max(self.slots.len());
+                (fd < limit).then_some(fd)
+            })?;
+        self.grow_to(fd, limit);
+        self.slots[fd] = Some(file_idx);
+        Some(fd as i32)
+    }
+
+    /// Put `file_idx` at exactly `fd`, returning whatever was there
+    pub fn install(&mut self, fd: i32, file_idx: usize, limit: usize) -> Result<Option<usize>, ()> {
+        let fd = usize::try_from(fd).map_err(|_| ())?;
+        let limit = limit.min(NR_OPEN);
+        if fd >= limit {
+            return Err(());
+        }
+        self.grow_to(fd, limit);
+        Ok(self.slots[fd].replace(file_idx))
+    }
+
+    /// Empty the slot for `fd`, returning the file it held
+    pub fn remove(&mut self, fd: i32) -> Option<usize> {
+        usize::try_from(fd).ok().and_then(|fd| self.slots.get_mut(fd)?.take())
+    }
+
+    /// Open descriptors and their file table indices, lowest fd first
+    pub fn iter(&self) -> impl Iterator<Item = (i32, usize)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(fd, slot)| slot.map(|file_idx| (fd as i32, file_idx)))
+    }
+
+    /// Number of open descriptors
+    pub fn count(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Empty the table, returning every file it held
+    pub fn take_all(&mut self) -> Vec<usize> {
+        self.slots.iter_mut().filter_map(Option::take).collect()
+    }
+
+    /// Make room for `fd`, doubling so repeated opens stay amortised O(1)
+    fn grow_to(&mut self, fd: usize, limit: usize) {
+        if fd < self.slots.len() {
+            return;
+        }
+        let size = (fd + 1).next_power_of_two().max(NOFILE_INIT).min(limit);
+        self.slots.resize(size, None);
+    }
+}
+
+impl Default for FdTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lowest_free_and_growth() {
+        let mut table = FdTable::new();
+        for fd in 0..NOFILE_INIT as i32 {
+            assert_eq!(table.alloc(0, 100 + fd as usize, 1024), Some(fd));
+        }
+        assert_eq!(table.capacity(), NOFILE_INIT);
+        assert_eq!(table.alloc(0, 7, 1024), Some(NOFILE_INIT as i32));
+        assert_eq!(table.capacity(), NOFILE_INIT * 2);
+
+        assert_eq!(table.remove(3), Some(103));
+        assert_eq!(table.alloc(0, 8, 1024), Some(3));
+        assert_eq!(table.alloc(20, 9, 1024), Some(20));
+        assert_eq!(table.count(), NOFILE_INIT + 2);
+    }
+
+    #[test]
+    fn test_limit_is_enforced() {
+        let mut table = FdTable::new();
+        assert_eq!(table.alloc(0, 1, 2), Some(0));
+        assert_eq!(table.alloc(0, 2, 2), Some(1));
+        assert_eq!(table.alloc(0, 3, 2), None);
+        // Lowering the limit does not hide descriptors already open
+        assert_eq!(table.get(1), Some(2));
+
+        assert_eq!(table.install(100, 4, 64), Err(()));
+        assert_eq!(table.install(40, 4, 64), Ok(None));
+        assert_eq!(table.install(40, 5, 64), Ok(Some(4)));
+        assert_eq!(table.capacity(), 64);
+        assert_eq!(table.get(40), Some(5));
+        assert_eq!(table.get(-1), None);
+    }
+
+    #[test]
+    fn test_take_all() {
+        let mut table = FdTable::new();
+        table.alloc(0, 10, 64);
+        table.install(30, 11, 64).unwrap();
+        assert_eq!(table.iter().collect::<Vec<_>>(), [(0, 10), (30, 11)]);
+        assert_eq!(table.take_all(), [10, 11]);
+        assert_eq!(table.count(), 0);
+    }
+}
@@ -12,6 +12,7 @@
 extern crate alloc;
 
 use core::ptr::null_mut;
+use alloc::boxed::Box;
 use alloc::string::String;
 use hashbrown::HashMap;
 use alloc::vec::Vec;
@@ -20,17 +21,19 @@ use crate::subsystems::sync::Mutex;
 use crate::compat::DefaultHasherBuilder;
 use crate::subsystems::mm::{kalloc, kfree, PAGE_SIZE};
 use crate::ipc::signal::SignalState;
+use super::fdtable::{FdTable, SharedFdTable};
+use super::pid::PidAllocator;
 use crate::subsystems::mm::vm::{PageTable, free_pagetable};
 
 // ============================================================================
 // Constants
 // ============================================================================
 
-/// Maximum number of processes
-pub const NPROC: usize = 64;
+// There is no fixed process or descriptor count: the table grows up to
+// `pid_max` processes and each descriptor table up to `RLIMIT_NOFILE`.
 
-/// Maximum number of file descriptors per process
-pub const NOFILE: usize = 16;
+/// Kernel stacks and trapframes kept around for reuse
+const POOL_MAX: usize = 64;
 
 // ============================================================================
 // Types
@@ -283,6 +286,14 @@ pub struct TrapFrame {
     pub ss: usize,
 }
 
+/// Number of registers in `elf_gregset_t`
+#[cfg(target_arch = "riscv64")]
+pub const ELF_NGREG: usize = 32;
+#[cfg(target_arch = "aarch64")]
+pub const ELF_NGREG: usize = 34;
+#[cfg(target_arch = "x86_64")]
+pub const ELF_NGREG: usize = 27;
+
 impl TrapFrame {
     pub const fn new() -> Self {
         #[cfg(target_arch = "riscv64")]
@@ -310,6 +321,186 @@ impl TrapFrame {
             }
         }
     }
+
+    /// User program counter at the time of the trap
+    pub fn pc(&self) -> usize {
+        #[cfg(target_arch = "riscv64")]
+        {
+            self.epc
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            self.elr
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            self.rip
+        }
+    }
+
+    /// System call number register (a7, x8 or rax)
+    pub fn syscall_nr(&self) -> usize {
+        #[cfg(target_arch = "riscv64")]
+        {
+            self.a7
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            self.regs[8]
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            self.rax
+        }
+    }
+
+    /// The six system call argument registers, in order
+    pub fn syscall_args(&self) -> [u64; 6] {
+        #[cfg(target_arch = "riscv64")]
+        {
+            [self.a0, self.a1, self.a2, self.a3, self.a4, self.a5].map(|r| r as u64)
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            [self.regs[0], self.regs[1], self.regs[2], self.regs[3], self.regs[4], self.regs[5]]
+                .map(|r| r as u64)
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            [self.rdi, self.rsi, self.rdx, self.r10, self.r8, self.r9].map(|r| r as u64)
+        }
+    }
+
+    /// Step past the trapping instruction where the hardware leaves the pc
+    /// on it (RISC-V `ecall`). Done before dispatch so exec can replace the pc.
+    pub fn skip_syscall_insn(&mut self) {
+        #[cfg(target_arch = "riscv64")]
+        {
+            self.epc += 4;
+        }
+    }
+
+    /// User registers in the order of the architecture's `elf_gregset_t`
+    /// (`struct user_regs_struct`), as core dumps and debuggers expect them
+    pub fn user_regs(&self) -> [u64; ELF_NGREG] {
+        #[cfg(target_arch = "riscv64")]
+        {
+            [
+                self.epc, self.ra, self.sp, self.gp, self.tp, self.t0, self.t1, self.t2,
+                self.s0, self.s1, self.a0, self.a1, self.a2, self.a3, self.a4, self.a5,
+                self.a6, self.a7, self.s2, self.s3, self.s4, self.s5, self.s6, self.s7,
+                self.s8, self.s9, self.s10, self.s11, self.t3, self.t4, self.t5, self.t6,
+            ]
+            .map(|r| r as u64)
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            let mut regs = [0u64; ELF_NGREG];
+            for (dst, src) in regs.iter_mut().zip(self.regs) {
+                *dst = src as u64;
+            }
+            regs[31] = self.sp as u64;
+            regs[32] = self.elr as u64;
+            regs[33] = self.spsr as u64;
+            regs
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            // orig_rax is -1 outside a system call; fs/gs bases and the data
+            // segment selectors are not part of the trap frame
+            [
+                self.r15, self.r14, self.r13, self.r12, self.rbp, self.rbx, self.r11, self.r10,
+                self.r9, self.r8, self.rax, self.rcx, self.rdx, self.rsi, self.rdi, usize::MAX,
+                self.rip, self.cs, self.rflags, self.rsp, self.ss, 0, 0, 0, 0, 0, 0,
+            ]
+            .map(|r| r as u64)
+        }
+    }
+
+    /// Load registers laid out as by [`TrapFrame::user_regs`], for a
+    /// debugger. Privileged state is kept: only the condition flags of
+    /// SPSR, and the flags user code may set in RFLAGS, are taken; x86
+    /// segment selectors and orig_rax are ignored.
+    pub fn set_user_regs(&mut self, regs: &[u64; ELF_NGREG]) {
+        let r = regs.map(|r| r as usize);
+        #[cfg(target_arch = "riscv64")]
+        {
+            [
+                self.epc, self.ra, self.sp, self.gp, self.tp, self.t0, self.t1, self.t2,
+                self.s0, self.s1, self.a0, self.a1, self.a2, self.a3, self.a4, self.a5,
+                self.a6, self.a7, self.s2, self.s3, self.s4, self.s5, self.s6, self.s7,
+                self.s8, self.s9, self.s10, self.s11, self.t3, self.t4, self.t5, self.t6,
+            ] = r;
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            // N, Z, C and V
+            const SPSR_NZCV: usize = 0xf << 28;
+            self.regs.copy_from_slice(&r[..31]);
+            self.sp = r[31];
+            self.elr = r[32];
+            self.spsr = (self.spsr & !SPSR_NZCV) | (r[33] & SPSR_NZCV);
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            // CF PF AF ZF SF TF DF OF RF AC, as Linux's FLAG_MASK
+            const RFLAGS_USER: usize = 0x50dd5;
+            [
+                self.r15, self.r14, self.r13, self.r12, self.rbp, self.rbx, self.r11, self.r10,
+                self.r9, self.r8, self.rax, self.rcx, self.rdx, self.rsi, self.rdi,
+            ] = [r[0], r[1], r[2], r[3], r[4], r[5], r[6], r[7], r[8], r[9], r[10], r[11], r[12], r[13], r[14]];
+            self.rip = r[16];
+            self.rflags = (self.rflags & !RFLAGS_USER) | (r[18] & RFLAGS_USER);
+            self.rsp = r[19];
+        }
+    }
+
+    /// Trap after the next user instruction (x86 RFLAGS.TF, AArch64
+    /// SPSR.SS); false where the hardware cannot single-step user code
+    pub fn set_single_step(&mut self, on: bool) -> bool {
+        #[cfg(target_arch = "riscv64")]
+        {
+            // No user single-step without a debug module trigger
+            !on
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            const SPSR_SS: usize = 1 << 21;
+            if on { self.spsr |= SPSR_SS } else { self.spsr &= !SPSR_SS }
+            true
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            const RFLAGS_TF: usize = 1 << 8;
+            if on { self.rflags |= RFLAGS_TF } else { self.rflags &= !RFLAGS_TF }
+            true
+        }
+    }
+
+    /// Store a system call return value
+    pub fn set_syscall_return(&mut self, ret: isize) {
+        #[cfg(target_arch = "riscv64")]
+        {
+            self.a0 = ret as usize;
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            self.regs[0] = ret as usize;
+        }
+        #[cfg(target_arch = "x86_64")]
+        {
+            self.rax = ret as usize;
+        }
+    }
+}
+
+/// System call ABI spoken by a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Personality {
+    /// NOS category-numbered system calls
+    Native,
+    /// Linux system call numbers and structure layouts for this architecture
+    Linux,
 }
 
 // CachedFd structure has been replaced by ExtendedFdCache from fd_cache module
@@ -334,36 +525,55 @@ pub struct Proc {
     pub kstack: usize,
     pub trapframe: *mut TrapFrame,
     pub context: Context,
-    pub ofile: [Option<usize>; NOFILE],  // Open file descriptors (index into FILE_TABLE)
+    /// Open file descriptors, shared with other processes under CLONE_FILES
+    pub ofile: SharedFdTable,
     /// Extended file descriptor cache for fast access (FDs 0-15)
     /// This cache reduces file table lookups for commonly used file descriptors
     /// and provides statistics and LRU/LFU replacement policies
     fd_cache: crate::subsystems::process::fd_cache::ExtendedFdCache,
     pub cwd_path: Option<String>,
+    /// Root directory set by chroot, as a canonical path; `None` is `/`
+    pub root_path: Option<String>,
     pub cwd: Option<usize>,  // Current working directory file index
     pub signals: Option<SignalState>,
     pub alt_signal_stack: Option<crate::posix::StackT>,  // Alternate signal stack
-    pub rlimits: [crate::posix::Rlimit; 16],  // Resource limits
+    pub rlimits: [crate::posix::Rlimit; crate::posix::RLIM_NLIMITS],  // Resource limits
     pub chan: usize,  // Sleep channel
     pub killed: bool,
     pub xstate: i32,  // Exit status
+    /// Timer ticks spent running, checked against RLIMIT_CPU
+    pub cpu_ticks: u64,
     pub sz: usize,    // Memory size
     pub pagetable: *mut PageTable,  // Page table pointer
+    /// Mapped regions, which say where user memory may be faulted in
+    pub vm: crate::subsystems::mm::vm::VmSpace,
     pub nice: i32,    // Process nice value (-20 to 19)
     pub umask: u32,   // File creation mask
+    /// System call ABI, chosen by exec from the ELF image
+    pub personality: Personality,
+    /// What the last exec loaded; shared with children until they exec
+    pub image: Option<alloc::sync::Arc<super::exec::ExecImage>>,
+    /// Tracer link and stop state while a debugger is attached
+    pub ptrace: Option<super::ptrace::Tracee>,
     pub domain_id: crate::subsystems::mm::memory_isolation::ProtectionDomainId,  // Memory protection domain ID
     /// Namespace IDs for this process (one per namespace type)
     /// Maps namespace type to namespace ID
     pub namespaces: alloc::collections::BTreeMap<crate::subsystems::cloud_native::namespaces::NamespaceType, u64>,
     /// Cgroup name for this process (if assigned to a cgroup)
     pub cgroup: Option<String>,
+    /// Bias of the OOM killer for (positive) or against (negative) this
+    /// process, -1000 to 1000; -1000 exempts it
+    pub oom_score_adj: i16,
+    /// Whether and as whom a crash dumps core, one of the `SUID_DUMP_*`
+    /// modes in `coredump`
+    pub dumpable: u8,
 }
 
 // Safety: Process control block is protected by PROC_TABLE mutex
 unsafe impl Send for Proc {}
 
 impl Proc {
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             pid: 0,
             pgid: 0,
@@ -374,28 +584,36 @@ impl Proc {
             egid: 0,
             suid: 0,
             sgid: 0,
-            rlimits: [crate::posix::Rlimit { rlim_cur: 0, rlim_max: 0 }; 16],
+            rlimits: super::rlimit::defaults(),
             state: ProcState::Unused,
             parent: None,
             kstack: 0,
             trapframe: null_mut(),
             context: Context::new(),
-            ofile: [None; NOFILE],
+            ofile: FdTable::new().shared(),
             fd_cache: crate::subsystems::process::fd_cache::ExtendedFdCache::new(),
             cwd_path: None,
+            root_path: None,
             cwd: None,
             signals: None,
             alt_signal_stack: None,
             chan: 0,
             killed: false,
             xstate: 0,
+            cpu_ticks: 0,
             sz: 0,
             pagetable: null_mut(),
+            vm: crate::subsystems::mm::vm::VmSpace::default(),
             nice: 0,
             umask: 0o022,  // Default umask
+            personality: Personality::Native,
+            image: None,
+            ptrace: None,
             domain_id: 0,  // Default to kernel domain
             namespaces: alloc::collections::BTreeMap::new(),
             cgroup: None,
+            oom_score_adj: 0,
+            dumpable: super::coredump::SUID_DUMP_USER,
         }
     }
     
@@ -491,7 +709,7 @@ impl ResourcePools {
 
     /// Return kernel stack to pool
     fn free_stack(&mut self, stack_addr: usize) {
-        if stack_addr != 0 && self.stack_pool.len() < NPROC {
+        if stack_addr != 0 && self.stack_pool.len() < POOL_MAX {
             self.stack_pool.push(stack_addr);
         } else if stack_addr != 0 {
             unsafe { kfree(stack_addr as *mut u8); }
@@ -508,7 +726,7 @@ impl ResourcePools {
 
     /// Return trapframe to pool
     fn free_trapframe(&mut self, tf_addr: usize) {
-        if tf_addr != 0 && self.trapframe_pool.len() < NPROC {
+        if tf_addr != 0 && self.trapframe_pool.len() < POOL_MAX {
             self.trapframe_pool.push(tf_addr);
         } else if tf_addr != 0 {
             unsafe { kfree(tf_addr as *mut u8); }
@@ -517,9 +735,11 @@ impl ResourcePools {
 }
 
 /// Process table with O(1) average-case PID lookup
+///
+/// Slots are boxed so a `Proc` stays put when the table grows.
 pub struct ProcTable {
-    procs: [Proc; NPROC],
-    next_pid: Pid,
+    procs: Vec<Box<Proc>>,
+    pids: PidAllocator,
     pid_to_index: HashMap<Pid, usize, DefaultHasherBuilder>,  // O(1) average-case PID lookup using HashMap (always initialized)
     parent_to_children: HashMap<Pid, Vec<Pid>, DefaultHasherBuilder>,  // O(1) child lookup by parent PID (always initialized)
     free_list: Vec<usize>,  // Free process slot indices for O(1) allocation
@@ -530,10 +750,9 @@ pub struct ProcTable {
 impl ProcTable {
     /// Create a new process table for static initialization
     pub const fn const_new() -> Self {
-        const INIT_PROC: Proc = Proc::new();
         Self {
-            procs: [INIT_PROC; NPROC],
-            next_pid: 1,
+            procs: Vec::new(),
+            pids: PidAllocator::new(),
             pid_to_index: HashMap::with_hasher(DefaultHasherBuilder),  // Will be properly initialized at runtime
             parent_to_children: HashMap::with_hasher(DefaultHasherBuilder),  // Will be properly initialized at runtime
             free_list: Vec::new(),
@@ -545,10 +764,9 @@ impl ProcTable {
     /// Create a new process table
     /// HashMap is always initialized (not Option)
     pub fn new() -> Self {
-        const INIT_PROC: Proc = Proc::new();
         let mut table = Self {
-            procs: [INIT_PROC; NPROC],
-            next_pid: 1,
+            procs: Vec::new(),
+            pids: PidAllocator::new(),
             pid_to_index: HashMap::with_hasher(DefaultHasherBuilder),  // Always initialized
             parent_to_children: HashMap::with_hasher(DefaultHasherBuilder),  // Always initialized
             free_list: Vec::new(),
@@ -556,8 +774,8 @@ impl ProcTable {
             initialized: false,
         };
         // Pre-allocate capacity for optimal performance
-        table.pid_to_index.reserve(NPROC);
-        table.parent_to_children.reserve(NPROC);
+        table.pid_to_index.reserve(POOL_MAX);
+        table.parent_to_children.reserve(POOL_MAX);
         table.initialized = true;
         table
     }
@@ -574,33 +792,36 @@ impl ProcTable {
         // No-op: HashMap is always initialized in new()
     }
 
-    /// Allocate a new process - O(1) with free list
+    /// Allocate a new process - O(1) with free list, growing the table
+    /// when every slot is taken
     pub fn alloc(&mut self) -> Option<&mut Proc> {
         // Ensure initialized first (before any borrows)
         self.ensure_initialized();
-        
-        // Initialize free list if it's empty
-        if self.free_list.is_empty() {
-            // Populate free list with all available indices
-            for i in 0..NPROC {
-                self.free_list.push(i);
-            }
-        }
 
-        // Get the first available index from free list
-        let idx = self.free_list.pop()?;
+        let now = crate::subsystems::time::get_ticks();
+        let new_pid = self.pids.alloc(now)?;
+
+        // Reuse a free slot, or add one
+        let idx = match self.free_list.pop() {
+            Some(idx) => idx,
+            None => {
+                self.procs.push(Box::new(Proc::new()));
+                self.procs.len() - 1
+            }
+        };
         let proc = &mut self.procs[idx];
         
         // Ensure the process is actually unused (sanity check)
         if proc.state != ProcState::Unused {
             // Push it back if it's not unused
             self.free_list.push(idx);
+            self.pids.free(new_pid, now);
             return None;
         }
 
-        let new_pid = self.next_pid;
+        // Start from a clean slate; nothing of the slot's last user survives
+        **proc = Proc::new();
         proc.pid = new_pid;
-        self.next_pid += 1;
         proc.state = ProcState::Used;
         
         // Initialize ASLR for new process
@@ -622,6 +843,7 @@ impl ProcTable {
                 proc.state = ProcState::Unused;
                 proc.signals = None;
                 self.free_list.push(idx); // Return to free list
+                self.pids.free(new_pid, now);
                 return None;
             }
         };
@@ -635,6 +857,7 @@ impl ProcTable {
                 proc.state = ProcState::Unused;
                 proc.signals = None;
                 self.free_list.push(idx); // Return to free list
+                self.pids.free(new_pid, now);
                 return None;
             }
         };
@@ -669,7 +892,7 @@ impl ProcTable {
         if let Some(&idx) = self.pid_to_index.get(&pid) {
             // Index is guaranteed valid since it's managed internally
             // Use unsafe access to skip bounds checking for maximum performance
-            Some(unsafe { self.procs.get_unchecked_mut(idx) })
+            Some(unsafe { self.procs.get_unchecked_mut(idx) }.as_mut())
         } else {
             None
         }
@@ -690,7 +913,7 @@ impl ProcTable {
         if let Some(&idx) = self.pid_to_index.get(&pid) {
             // Index is guaranteed valid since it's managed internally
             // Use unsafe access to skip bounds checking for maximum performance
-            Some(unsafe { self.procs.get_unchecked(idx) })
+            Some(unsafe { self.procs.get_unchecked(idx) }.as_ref())
         } else {
             None
         }
@@ -699,7 +922,7 @@ impl ProcTable {
     /// Add child to parent's children list - O(1) average-case
     /// No fallback path - HashMap is always initialized
     #[inline]
-    fn add_child_to_parent(&mut self, parent_pid: Pid, child_pid: Pid) {
+    pub(super) fn add_child_to_parent(&mut self, parent_pid: Pid, child_pid: Pid) {
         self.ensure_initialized();
         self.parent_to_children.entry(parent_pid).or_insert_with(Vec::new).push(child_pid);
     }
@@ -756,20 +979,27 @@ impl ProcTable {
 
                 // Free page table and all user pages
                 if !proc.pagetable.is_null() {
-                    unsafe { free_pagetable(proc.pagetable); }
+                    unsafe {
+                        crate::subsystems::mm::vm::release_user_pages(proc.pagetable, &proc.vm);
+                        free_pagetable(proc.pagetable);
+                    }
                     proc.pagetable = null_mut();
                 }
+                crate::subsystems::mm::swap::lru::forget_pid(pid);
+                proc.vm = Default::default();
 
             // Reset process state
             proc.state = ProcState::Unused;
             proc.signals = None;
             proc.cwd_path = None;
+            proc.root_path = None;
             proc.cwd = None;
             proc.sz = 0;
             proc.parent = None;
 
-            // Remove from PID map
+            // Remove from PID map; the PID itself cools off before reuse
             self.pid_to_index.remove(&pid);
+            self.pids.free(pid, crate::subsystems::time::get_ticks());
             crate::process::rcu_table::with_sharded(|s| s.remove(pid));
 
             // Add back to free list for O(1) reuse
@@ -779,12 +1009,39 @@ impl ProcTable {
 
     /// Get iterator over all processes
     pub fn iter(&self) -> impl Iterator<Item = &Proc> {
-        self.procs.iter()
+        self.procs.iter().map(|proc| &**proc)
     }
 
     /// Get mutable iterator over all processes
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Proc> {
-        self.procs.iter_mut()
+        self.procs.iter_mut().map(|proc| &mut **proc)
+    }
+
+    /// Number of process slots, used or not
+    pub fn slots(&self) -> usize {
+        self.procs.len()
+    }
+
+    /// Process slot by index, used or not
+    pub fn slot(&self, idx: usize) -> Option<&Proc> {
+        self.procs.get(idx).map(|proc| &**proc)
+    }
+
+    /// Exclusive upper bound on new PIDs
+    pub fn pid_max(&self) -> Pid {
+        self.pids.pid_max()
+    }
+
+    /// Change `pid_max` (within `RESERVED_PIDS + 1..=PID_MAX_LIMIT`)
+    pub fn set_pid_max(&mut self, pid_max: Pid) -> Result<(), ()> {
+        self.pids.set_pid_max(pid_max)
+    }
+
+    /// Live processes owned by real user `uid`, for RLIMIT_NPROC
+    pub fn count_user(&self, uid: posix::Uid) -> usize {
+        self.iter()
+            .filter(|proc| proc.state != ProcState::Unused && proc.uid == uid)
+            .count()
     }
 
     /// Validate process resource consistency (for debugging)
@@ -885,19 +1142,55 @@ fn set_current(pid: Option<Pid>) {
     unsafe { CURRENT_PID[cpu_id] = pid; }
 }
 
+/// Run `f` on behalf of `pid`
+///
+/// For kernel code serving a process it may not be running in (the
+/// io_uring SQ thread): descriptor and path lookups inside `f` resolve
+/// against `pid`. `f` must not sleep.
+pub fn with_proc<R>(pid: Pid, f: impl FnOnce() -> R) -> R {
+    let saved = myproc();
+    set_current(Some(pid));
+    let result = f();
+    set_current(saved);
+    result
+}
+
+/// Why fork failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkError {
+    /// Not called from a process
+    NoProcess,
+    /// RLIMIT_NPROC reached (EAGAIN)
+    LimitReached,
+    /// No PID, slot, stack or page table could be had
+    NoMemory,
+}
+
 /// Fork current process
 pub fn fork() -> Option<Pid> {
-    let parent_pid = myproc()?;
+    fork_with(false).ok()
+}
+
+/// Fork the current process, sharing its descriptor table if `share_files`
+/// (CLONE_FILES) instead of copying it
+pub fn fork_with(share_files: bool) -> Result<Pid, ForkError> {
+    let parent_pid = myproc().ok_or(ForkError::NoProcess)?;
     let mut table = PROC_TABLE.lock();
 
     // Extract all parent data first, then release borrow
-    let (parent_pgid, parent_sid, parent_uid, parent_gid, parent_euid, parent_egid, parent_suid, parent_sgid, parent_nice, parent_umask, parent_ofile, parent_cwd_path, parent_cwd, parent_rlimits, parent_pagetable, parent_sz, parent_trapframe, parent_namespaces, parent_cgroup) = {
-        let parent = table.find(parent_pid)?;
-        (parent.pgid, parent.sid, parent.uid, parent.gid, parent.euid, parent.egid, parent.suid, parent.sgid, parent.nice, parent.umask, parent.ofile.clone(), parent.cwd_path.clone(), parent.cwd, parent.rlimits.clone(), parent.pagetable, parent.sz, parent.trapframe, parent.namespaces.clone(), parent.cgroup.clone())
+    let (parent_pgid, parent_sid, parent_uid, parent_gid, parent_euid, parent_egid, parent_suid, parent_sgid, parent_nice, parent_umask, parent_personality, parent_image, parent_ofile, parent_cwd_path, parent_root_path, parent_cwd, parent_rlimits, parent_pagetable, parent_vm, parent_sz, parent_trapframe, parent_namespaces, parent_cgroup, parent_oom_score_adj, parent_dumpable) = {
+        let parent = table.find(parent_pid).ok_or(ForkError::NoProcess)?;
+        (parent.pgid, parent.sid, parent.uid, parent.gid, parent.euid, parent.egid, parent.suid, parent.sgid, parent.nice, parent.umask, parent.personality, parent.image.clone(), parent.ofile.clone(), parent.cwd_path.clone(), parent.root_path.clone(), parent.cwd, parent.rlimits.clone(), parent.pagetable, parent.vm.clone(), parent.sz, parent.trapframe, parent.namespaces.clone(), parent.cgroup.clone(), parent.oom_score_adj, parent.dumpable)
     };
 
+    // Root is exempt from RLIMIT_NPROC, as on Linux
+    let nproc = parent_rlimits[crate::posix::RLIMIT_NPROC as usize].rlim_cur;
+    if parent_uid != 0 && table.count_user(parent_uid) as u64 >= nproc {
+        return Err(ForkError::LimitReached);
+    }
+
     // Allocate child process (now we can use table mutably again)
-    let child = table.alloc()?;
+    let child = table.alloc().ok_or(ForkError::NoMemory)?;
     let child_pid = child.pid;
 
     // Initialize child process state
@@ -918,7 +1211,7 @@ pub fn fork() -> Option<Pid> {
         Err(_) => {
             // Failed to create security context, clean up and return None
             table.free(child_pid);
-            return None;
+            return Err(ForkError::NoMemory);
         }
     };
     
@@ -933,6 +1226,10 @@ pub fn fork() -> Option<Pid> {
     child.sgid = parent_sgid;
     child.nice = parent_nice;
     child.umask = parent_umask;
+    child.personality = parent_personality;
+    child.image = parent_image;
+    child.oom_score_adj = parent_oom_score_adj;
+    child.dumpable = parent_dumpable;
     
     // Drop mutable borrow of child before calling add_child_to_parent
     drop(child);
@@ -947,26 +1244,28 @@ pub fn fork() -> Option<Pid> {
     let child = if let Some(idx) = child_idx {
         &mut table.procs[idx]
     } else {
-        return None;
+        return Err(ForkError::NoMemory);
     };
 
-    // Copy parent's file descriptors (shallow copy)
-    child.ofile.copy_from_slice(&parent_ofile);
-    // Increment file reference counts for copied file descriptors
-    for fd in &child.ofile {
-        if let Some(file) = fd {
-            // Note: This will be properly implemented when the file system is complete
-            // For now, we acknowledge that file references should be incremented
-            crate::println!("[process] Copied file descriptor, should increment ref count");
+    // Share the parent's descriptor table, or copy it taking a reference on
+    // every open file
+    child.ofile = if share_files {
+        parent_ofile
+    } else {
+        let fds = parent_ofile.lock().clone();
+        for (_, file_idx) in fds.iter() {
+            crate::fs::file::file_dup(file_idx);
         }
-    }
+        fds.shared()
+    };
 
     // Copy working directory
     child.cwd_path = parent_cwd_path;
+    child.root_path = parent_root_path;
     child.cwd = parent_cwd;
 
     // Copy resource limits
-    child.rlimits.copy_from_slice(&parent_rlimits);
+    child.rlimits = parent_rlimits;
 
     // Inherit namespaces from parent
     child.namespaces = parent_namespaces;
@@ -983,13 +1282,43 @@ pub fn fork() -> Option<Pid> {
     // Copy page table with copy-on-write semantics
     if let Some(pagetable) = unsafe { crate::subsystems::mm::vm::copy_pagetable(parent_pagetable) } {
         child.pagetable = pagetable;
+        // MADV_DONTFORK areas stay with the parent; the copied entries
+        // share its frames without a reference of their own
+        for area in parent_vm.iter().filter(|area| area.advice.dontfork) {
+            unsafe { crate::subsystems::mm::vm::unmap_user_range(pagetable, area.range.start, area.range.end) };
+        }
+        // and page cache pages are faulted in again, mapcount and all.
+        // Private pages are shared copy-on-write, swapped-out ones by
+        // their swap slot
+        let mut shared = Ok(());
+        for area in parent_vm.iter().filter(|area| !area.advice.dontfork) {
+            unsafe { crate::subsystems::mm::vm::forget_cached_pages(pagetable, area) };
+            if shared.is_ok() {
+                shared = unsafe { crate::subsystems::mm::vm::fork_anon_pages(parent_pagetable, pagetable, area, child_pid) };
+            }
+            if shared.is_err() {
+                // Nothing of this area is shared yet, so its copied
+                // entries must not be released with the child's
+                unsafe { crate::subsystems::mm::vm::unmap_user_range(pagetable, area.range.start, area.range.end) };
+            }
+        }
+        child.vm = parent_vm.fork();
         child.sz = parent_sz;
+        if shared.is_err() {
+            table.free(child_pid);
+            return Err(ForkError::NoMemory);
+        }
     } else {
         // Failed to copy pagetable, clean up and return None
         table.free(child_pid);
-        return None;
+        return Err(ForkError::NoMemory);
     }
 
+    // Inherit the caller's seccomp filters and no_new_privs
+    crate::security::seccomp::fork_task(child_pid as u64);
+    // and its session keyring
+    crate::security::keys::fork_task(child_pid as u64);
+
     // Copy trapframe from parent and set child's return value to 0
     unsafe {
         *child.trapframe = *parent_trapframe;
@@ -1013,24 +1342,39 @@ pub fn fork() -> Option<Pid> {
         }
     }
 
-    Some(child_pid)
+    Ok(child_pid)
 }
 
 /// Exit current process
 pub fn exit(status: i32) {
+    // Encode exit status according to POSIX format
+    // Normal exit: bits 8-15 contain exit code, bit 7 is 0
+    exit_with_xstate((status & 0xff) << 8);
+}
+
+/// Exit the current process because of `sig`, as wait() reports it:
+/// the signal in the low bits and WCOREDUMP (0x80) if core was dumped
+pub fn exit_signaled(sig: u32, core_dumped: bool) {
+    let core = if core_dumped { 0x80 } else { 0 };
+    exit_with_xstate((sig as i32 & 0x7f) | core);
+}
+
+fn exit_with_xstate(xstate: i32) {
+    // PTRACE_EVENT_EXIT comes while the process is still intact
+    super::ptrace::report_exit(xstate);
+
     if let Some(pid) = myproc() {
         let mut table = PROC_TABLE.lock();
         if let Some(proc) = table.find(pid) {
-            // Encode exit status according to POSIX format
-            // Normal exit: bits 8-15 contain exit code, bit 7 is 0
-            proc.xstate = (status & 0xff) << 8;
+            proc.xstate = xstate;
             proc.state = ProcState::Zombie;
 
-            // Close all open files efficiently
-            for fd_slot in proc.ofile.iter_mut() {
-                if let Some(fd_idx) = *fd_slot {
-                    crate::fs::file_close(fd_idx);
-                    *fd_slot = None;
+            // Drop our hold on the descriptor table; the last process
+            // sharing it closes the files
+            let ofile = core::mem::replace(&mut proc.ofile, FdTable::new().shared());
+            if let Some(fds) = alloc::sync::Arc::into_inner(ofile) {
+                for file_idx in fds.into_inner().take_all() {
+                    crate::fs::file_close(file_idx);
                 }
             }
 
@@ -1039,6 +1383,7 @@ pub fn exit(status: i32) {
 
             // Clear working directory references
             proc.cwd_path = None;
+            proc.root_path = None;
             proc.cwd = None;
 
             // Wake up parent
@@ -1048,9 +1393,25 @@ pub fn exit(status: i32) {
 
             // Reparent children to init
             reparent_children(&mut table, pid);
+
+            // Tell a tracer, and let go of our own tracees
+            super::ptrace::exit_notify(&mut table, pid);
             
             // Remove security context for the exiting process
             let _ = crate::security::remove_process_security_context(pid);
+            crate::security::seccomp::exit_task(pid as u64);
+            crate::security::keys::exit_task(pid as u64);
+
+            // Give the memory back now rather than when reaped, which an
+            // OOM kill can't wait for; the page table pages go then
+            if let Some(proc) = table.find(pid) {
+                if !proc.pagetable.is_null() {
+                    unsafe { crate::subsystems::mm::vm::release_user_pages(proc.pagetable, &proc.vm) };
+                }
+                proc.vm = Default::default();
+            }
+            crate::subsystems::mm::swap::lru::forget_pid(pid);
+            crate::subsystems::mm::oom::exit_task(pid);
         }
     }
 
@@ -1133,11 +1494,23 @@ pub fn waitpid(pid: i32, status: *mut i32, options: i32) -> Option<Pid> {
         let mut table = PROC_TABLE.lock();
         let mut found_child: Option<(Pid, i32)> = None;
 
+        // Tracees report ptrace stops and exits to their tracer, whether
+        // or not it is their parent
+        if let Some((tracee, tracee_status)) = super::ptrace::wait_tracee(&mut table, parent_pid, pid) {
+            if !status.is_null() {
+                unsafe { *status = tracee_status; }
+            }
+            return Some(tracee);
+        }
+        let tracing = super::ptrace::has_tracees(&table, parent_pid);
+
         // Determine which children to check
         let children_to_check: Vec<Pid> = if pid == -1 {
             // Wait for any child
             if let Some(children) = table.get_children(parent_pid) {
                 children.clone()
+            } else if tracing {
+                Vec::new()
             } else {
                 // No children exist
                 return None;
@@ -1150,6 +1523,8 @@ pub fn waitpid(pid: i32, status: *mut i32, options: i32) -> Option<Pid> {
             // For now, treat as any child
             if let Some(children) = table.get_children(parent_pid) {
                 children.clone()
+            } else if tracing {
+                Vec::new()
             } else {
                 return None;
             }
@@ -1163,6 +1538,11 @@ pub fn waitpid(pid: i32, status: *mut i32, options: i32) -> Option<Pid> {
                     continue;
                 }
 
+                // A tracer other than the parent sees traced children first
+                if child_proc.ptrace.is_some() {
+                    continue;
+                }
+
                 // Check for zombie state (exited)
                 if child_proc.state == ProcState::Zombie {
                     found_child = Some((child_pid, child_proc.xstate));
@@ -1218,7 +1598,7 @@ pub fn kill(pid: usize) -> bool {
     let mut table = PROC_TABLE.lock();
     if let Some(proc) = table.find(pid as Pid) {
         proc.killed = true;
-        if proc.state == ProcState::Sleeping {
+        if matches!(proc.state, ProcState::Sleeping | ProcState::Stopped) {
             proc.state = ProcState::Runnable;
         }
         true
@@ -1234,7 +1614,8 @@ pub fn kill_proc(pid: Pid, sig: u32) -> Result<(), ()> {
         if let Some(ref mut signals) = proc.signals {
             let _ = signals.send_signal(sig);
         }
-        if proc.state == ProcState::Sleeping {
+        // SIGKILL also gets through a stop, ptrace or job control
+        if proc.state == ProcState::Sleeping || (sig == crate::ipc::signal::SIGKILL && proc.state == ProcState::Stopped) {
             proc.state = ProcState::Runnable;
         }
         Ok(())
@@ -1249,25 +1630,40 @@ pub fn getpid() -> Pid {
     myproc().unwrap_or(0)
 }
 
+/// Root and working directory of the current process for path lookups
+///
+/// Kernel threads without a process resolve from the global root.
+pub fn fs_context() -> crate::vfs::VfsResult<crate::vfs::FsContext> {
+    let (root, cwd) = myproc()
+        .and_then(|pid| {
+            let table = PROC_TABLE.lock();
+            table.find_ref(pid).map(|p| (p.root_path.clone(), p.cwd_path.clone()))
+        })
+        .unwrap_or((None, None));
+    crate::vfs::FsContext::new(root.as_deref(), cwd.as_deref())
+}
+
 /// Allocate file descriptor for current process
 /// 
 /// This function allocates a file descriptor and updates the cache
 /// for commonly used file descriptors (0-7) to enable O(1) lookup.
 pub fn fdalloc(file_idx: usize) -> Option<i32> {
+    fdalloc_from(0, file_idx)
+}
+
+/// Allocate the lowest free file descriptor that is at least `min`
+///
+/// Fails once every descriptor below `RLIMIT_NOFILE` is taken.
+pub fn fdalloc_from(min: i32, file_idx: usize) -> Option<i32> {
     let pid = myproc()?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid)?;
     
-    for (i, slot) in proc.ofile.iter_mut().enumerate() {
-        if slot.is_none() {
-            *slot = Some(file_idx);
-            let fd = i as i32;
-            // Update cache for commonly used file descriptors (0-7)
-            proc.update_fd_cache(fd, file_idx);
-            return Some(fd);
-        }
-    }
-    None
+    let limit = super::rlimit::nofile_bound(&proc.rlimits);
+    let fd = proc.ofile.lock().alloc(min.max(0) as usize, file_idx, limit)?;
+    // Update cache for commonly used file descriptors (0-7)
+    proc.update_fd_cache(fd, file_idx);
+    Some(fd)
 }
 
 /// Close file descriptor for current process
@@ -1278,13 +1674,11 @@ pub fn fdclose(fd: i32) -> Option<usize> {
     if let Some(pid) = myproc() {
         let mut table = PROC_TABLE.lock();
         if let Some(proc) = table.find(pid) {
-            if fd >= 0 && (fd as usize) < NOFILE {
-                if let Some(file_idx) = proc.ofile[fd as usize] {
-                    proc.ofile[fd as usize] = None;
-                    // Invalidate cache for commonly used file descriptors (0-7)
-                    proc.invalidate_fd_cache(fd);
-                    return Some(file_idx);
-                }
+            let closed = proc.ofile.lock().remove(fd);
+            if let Some(file_idx) = closed {
+                // Invalidate cache for commonly used file descriptors (0-7)
+                proc.invalidate_fd_cache(fd);
+                return Some(file_idx);
             }
         }
     }
@@ -1302,44 +1696,60 @@ pub fn fdlookup(fd: i32) -> Option<usize> {
     let table = PROC_TABLE.lock();
     let proc = table.find_ref(pid)?;
     
-    // Try cache first for commonly used file descriptors (0-15)
+    let ofile = proc.ofile.lock();
+
+    // Try cache first for commonly used file descriptors (0-15). Another
+    // process sharing the table may have changed it, so verify against ofile.
     if fd >= 0 && fd < 16 {
         if let Some(file_idx) = proc.get_cached_fd(fd) {
-            // Cache hit - verify it's still valid by checking ofile
-            if (fd as usize) < NOFILE && proc.ofile[fd as usize] == Some(file_idx) {
+            if ofile.get(fd) == Some(file_idx) {
                 return Some(file_idx);
             }
         }
     }
     
-    // Fall back to regular lookup and update cache if found
-    if fd >= 0 && (fd as usize) < NOFILE {
-        if let Some(file_idx) = proc.ofile[fd as usize] {
-            // Update cache for future lookups (requires mutable access)
-            // Note: We can't mutate here, so cache will be updated on next fdinstall/fdalloc
-            return Some(file_idx);
-        }
-    }
-    
-    None
+    // Fall back to regular lookup; the cache is refreshed on the next
+    // fdinstall/fdalloc since we can't mutate here
+    ofile.get(fd)
 }
 
 /// Install a file at a specific file descriptor
 /// 
 /// This function installs a file at a specific file descriptor and
 /// updates the extended cache for commonly used file descriptors (0-15).
-pub fn fdinstall(fd: i32, file_idx: usize) -> Result<(), ()> {
+/// Returns the file previously at `fd` for the caller to close; fails if
+/// `fd` is not below `RLIMIT_NOFILE`.
+pub fn fdinstall(fd: i32, file_idx: usize) -> Result<Option<usize>, ()> {
     let pid = myproc().ok_or(())?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(())?;
     
-    if fd >= 0 && (fd as usize) < NOFILE {
-        proc.ofile[fd as usize] = Some(file_idx);
-        // Update extended cache for FDs 0-15
-        proc.update_fd_cache(fd, file_idx);
-        Ok(())
-    } else {
-        Err(())
+    let limit = super::rlimit::nofile_bound(&proc.rlimits);
+    let replaced = proc.ofile.lock().install(fd, file_idx, limit)?;
+    // Update extended cache for FDs 0-15
+    proc.update_fd_cache(fd, file_idx);
+    Ok(replaced)
+}
+
+/// Charge the current timer tick to the running process and enforce
+/// RLIMIT_CPU
+pub fn charge_tick() {
+    use super::rlimit::{cpu_limit, CpuLimit};
+
+    let Some(pid) = myproc() else { return };
+    // Called from the timer interrupt: skip the tick rather than spin on a
+    // lock the interrupted code may hold
+    let Some(mut table) = PROC_TABLE.try_lock() else { return };
+    let Some(proc) = table.find(pid) else { return };
+    proc.cpu_ticks += 1;
+    let limit = proc.rlimits[crate::posix::RLIMIT_CPU as usize];
+    let verdict = cpu_limit(proc.cpu_ticks, limit, crate::subsystems::time::TIMER_FREQ);
+    drop(table);
+
+    match verdict {
+        CpuLimit::Within => {}
+        CpuLimit::Soft => { let _ = kill_proc(pid, crate::ipc::signal::SIGXCPU); }
+        CpuLimit::Hard => { let _ = kill_proc(pid, crate::ipc::signal::SIGKILL); }
     }
 }
 
@@ -1355,6 +1765,27 @@ pub fn sleep(chan: usize) {
     yield_cpu();
 }
 
+/// Sleep on a channel unless `done` already holds
+///
+/// `done` is checked with the process table locked, the same lock
+/// [`wakeup`] takes, so a wakeup between the caller's own check and going
+/// to sleep is not lost. Returns at once without a current process; callers
+/// loop on their condition either way.
+pub fn sleep_unless(chan: usize, done: impl Fn() -> bool) {
+    let Some(pid) = myproc() else { return };
+    {
+        let mut table = PROC_TABLE.lock();
+        if done() {
+            return;
+        }
+        if let Some(proc) = table.find(pid) {
+            proc.chan = chan;
+            proc.state = ProcState::Sleeping;
+        }
+    }
+    yield_cpu();
+}
+
 /// Wake up all processes sleeping on a channel
 pub fn wakeup(chan: usize) {
     let mut table = PROC_TABLE.lock();
@@ -1365,7 +1796,7 @@ pub fn wakeup(chan: usize) {
     }
 }
 
-fn wakeup_pid(table: &mut ProcTable, pid: Pid) {
+pub(super) fn wakeup_pid(table: &mut ProcTable, pid: Pid) {
     if let Some(proc) = table.find(pid) {
         if proc.state == ProcState::Sleeping {
             proc.state = ProcState::Runnable;
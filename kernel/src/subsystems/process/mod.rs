@@ -5,6 +5,11 @@ pub mod exec;
 pub mod elf;
 pub mod dynamic_linker;
 pub mod fd_cache;
+pub mod fdtable;
+pub mod pid;
+pub mod rlimit;
+pub mod coredump;
+pub mod ptrace;
 pub mod lock_optimized; // Optional: Optimized locking with RW locks and fine-grained locks
 pub mod rcu_table;
 pub mod context_switch;
@@ -0,0 +1,164 @@
+//! PID allocation
+//!
+//! PIDs are handed out in increasing order up to `pid_max` and then wrap
+//! around to [`RESERVED_PIDS`], as on Linux, so a PID is normally not reused
+//! until the whole range has been cycled through. On top of that a freed PID
+//! is held back for [`PID_REUSE_DELAY`] ticks, so a busy system that wraps
+//! quickly still cannot hand a just-reaped PID to a new process while
+//! someone may be about to `kill()` the old one.
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::manager::Pid;
+
+/// Default upper bound (exclusive) on PIDs
+pub const PID_MAX_DEFAULT: Pid = 32768;
+
+/// Largest value `pid_max` may be raised to
+pub const PID_MAX_LIMIT: Pid = 4 * 1024 * 1024;
+
+/// PIDs below this are skipped after the first wrap; they belong to
+/// daemons started at boot
+pub const RESERVED_PIDS: Pid = 300;
+
+/// Ticks a freed PID stays unavailable (one second at the 100 Hz timer)
+pub const PID_REUSE_DELAY: u64 = crate::subsystems::time::TIMER_FREQ;
+
+/// PID bitmap with wrap-around allocation and a reuse delay
+pub struct PidAllocator {
+    /// One bit per live PID, grown as higher PIDs are handed out
+    used: Vec<u64>,
+    /// Recently freed PIDs and the tick they were freed at
+    cooling: BTreeMap<Pid, u64>,
+    last: Pid,
+    pid_max: Pid,
+    live: usize,
+}
+
+impl PidAllocator {
+    pub const fn new() -> Self {
+        Self {
+            used: Vec::new(),
+            cooling: BTreeMap::new(),
+            last: 0,
+            pid_max: PID_MAX_DEFAULT,
+            live: 0,
+        }
+    }
+
+    /// Exclusive upper bound on newly allocated PIDs
+    pub fn pid_max(&self) -> Pid {
+        self.pid_max
+    }
+
+    /// Change `pid_max`. Live PIDs above the new bound stay valid.
+    pub fn set_pid_max(&mut self, pid_max: Pid) -> Result<(), ()> {
+        if !(RESERVED_PIDS + 1..=PID_MAX_LIMIT).contains(&pid_max) {
+            return Err(());
+        }
+        self.pid_max = pid_max;
+        if self.last >= pid_max {
+            self.last = RESERVED_PIDS - 1;
+        }
+        Ok(())
+    }
+
+    /// Number of PIDs currently allocated
+    pub fn live(&self) -> usize {
+        self.live
+    }
+
+    pub fn in_use(&self, pid: Pid) -> bool {
+        let pid = pid as usize;
+        self.used
+            .get(pid / 64)
+            .is_some_and(|word| word & (1 << (pid % 64)) != 0)
+    }
+
+    /// Allocate the next free PID after the last one handed out
+    pub fn alloc(&mut self, now: u64) -> Option<Pid> {
+        self.cooling
+            .retain(|_, freed| now.saturating_sub(*freed) < PID_REUSE_DELAY);
+
+        let first = self.last + 1;
+        let candidates = (first..self.pid_max).chain(RESERVED_PIDS.min(first)..first);
+        for pid in candidates {
+            if pid > 0 && !self.in_use(pid) && !self.cooling.contains_key(&pid) {
+                self.mark(pid, true);
+                self.last = pid;
+                self.live += 1;
+                return Some(pid);
+            }
+        }
+        None
+    }
+
+    /// Release a PID; it becomes reusable after [`PID_REUSE_DELAY`]
+    pub fn free(&mut self, pid: Pid, now: u64) {
+        if self.in_use(pid) {
+            self.mark(pid, false);
+            self.live -= 1;
+            self.cooling.insert(pid, now);
+        }
+    }
+
+    fn mark(&mut self, pid: Pid, used: bool) {
+        let pid = pid as usize;
+        let word = pid / 64;
+        if word >= self.used.len() {
+            self.used.resize(word + 1, 0);
+        }
+        if used {
+            self.used[word] |= 1 << (pid % 64);
+        } else {
+            self.used[word] &= !(1 << (pid % 64));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_then_wrap_past_reserved() {
+        let mut pids = PidAllocator::new();
+        pids.set_pid_max(RESERVED_PIDS + 3).unwrap();
+        assert_eq!(pids.alloc(0), Some(1));
+        for expected in 2..RESERVED_PIDS + 3 {
+            assert_eq!(pids.alloc(0), Some(expected));
+        }
+        assert_eq!(pids.alloc(0), None);
+
+        pids.free(2, 0);
+        pids.free(RESERVED_PIDS + 1, 0);
+        // PIDs below RESERVED_PIDS are not handed out again
+        assert_eq!(pids.alloc(PID_REUSE_DELAY), Some(RESERVED_PIDS + 1));
+        assert_eq!(pids.alloc(PID_REUSE_DELAY), None);
+        assert_eq!(pids.live(), RESERVED_PIDS as usize + 1);
+    }
+
+    #[test]
+    fn test_freed_pid_waits_out_reuse_delay() {
+        let mut pids = PidAllocator::new();
+        pids.set_pid_max(RESERVED_PIDS + 2).unwrap();
+        while pids.alloc(0).is_some() {}
+
+        pids.free(RESERVED_PIDS, 10);
+        assert_eq!(pids.alloc(10 + PID_REUSE_DELAY - 1), None);
+        assert_eq!(pids.alloc(10 + PID_REUSE_DELAY), Some(RESERVED_PIDS));
+        assert!(pids.in_use(RESERVED_PIDS));
+    }
+
+    #[test]
+    fn test_pid_max_bounds() {
+        let mut pids = PidAllocator::new();
+        assert!(pids.set_pid_max(RESERVED_PIDS).is_err());
+        assert!(pids.set_pid_max(PID_MAX_LIMIT + 1).is_err());
+        assert!(pids.set_pid_max(PID_MAX_LIMIT).is_ok());
+        assert_eq!(pids.pid_max(), PID_MAX_LIMIT);
+    }
+}
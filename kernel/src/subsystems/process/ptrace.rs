@@ -0,0 +1,967 @@
+//! Process tracing (ptrace)
+//!
+//! A tracer attaches with `PTRACE_ATTACH`, `PTRACE_SEIZE` or the tracee's
+//! own `PTRACE_TRACEME`. From then on the tracee stops at the points the
+//! tracer asked for and waits to be resumed:
+//!
+//! - signal-delivery-stop: before any signal but SIGKILL is acted on; the
+//!   tracer chooses the signal actually delivered when it resumes
+//! - syscall-stop: on entry to and exit from every system call under
+//!   `PTRACE_SYSCALL`
+//! - event stops for fork, vfork, clone, exec, exit and seccomp
+//!   `SECCOMP_RET_TRACE`, as enabled with `PTRACE_O_TRACE*` options
+//! - single-step and breakpoint traps, which arrive as SIGTRAP
+//!
+//! Stops are reported by `waitpid` to the tracer whether or not it is the
+//! tracee's parent, encoded as on Linux. All of the state lives in
+//! `Proc::ptrace` under the process table lock. A tracee that stops itself
+//! parks in `ProcState::Stopped` until the tracer resumes it; one stopped
+//! from outside (attach, new children) is simply left `Stopped`.
+
+extern crate alloc;
+
+use crate::ipc::signal::{
+    si_code, SigInfo, Signal, NSIG, SIGBUS, SIGCHLD, SIGFPE, SIGILL, SIGKILL, SIGSEGV, SIGSTOP,
+    SIGTRAP,
+};
+use crate::security::capabilities::{capable, Capability};
+use crate::subsystems::mm::vm::{copyin, copyout, copyout_force, PageTable};
+
+use super::manager::{
+    myproc, wakeup_pid, yield_cpu, Pid, Proc, ProcState, ProcTable, TrapFrame, ELF_NGREG,
+    PROC_TABLE,
+};
+
+pub const PTRACE_TRACEME: u32 = 0;
+pub const PTRACE_PEEKTEXT: u32 = 1;
+pub const PTRACE_PEEKDATA: u32 = 2;
+pub const PTRACE_PEEKUSER: u32 = 3;
+pub const PTRACE_POKETEXT: u32 = 4;
+pub const PTRACE_POKEDATA: u32 = 5;
+pub const PTRACE_POKEUSER: u32 = 6;
+pub const PTRACE_CONT: u32 = 7;
+pub const PTRACE_KILL: u32 = 8;
+pub const PTRACE_SINGLESTEP: u32 = 9;
+pub const PTRACE_GETREGS: u32 = 12;
+pub const PTRACE_SETREGS: u32 = 13;
+pub const PTRACE_ATTACH: u32 = 16;
+pub const PTRACE_DETACH: u32 = 17;
+pub const PTRACE_SYSCALL: u32 = 24;
+pub const PTRACE_SETOPTIONS: u32 = 0x4200;
+pub const PTRACE_GETEVENTMSG: u32 = 0x4201;
+pub const PTRACE_GETSIGINFO: u32 = 0x4202;
+pub const PTRACE_SETSIGINFO: u32 = 0x4203;
+pub const PTRACE_GETREGSET: u32 = 0x4204;
+pub const PTRACE_SETREGSET: u32 = 0x4205;
+pub const PTRACE_SEIZE: u32 = 0x4206;
+pub const PTRACE_INTERRUPT: u32 = 0x4207;
+
+pub const PTRACE_O_TRACESYSGOOD: u32 = 0x1;
+pub const PTRACE_O_TRACEFORK: u32 = 0x2;
+pub const PTRACE_O_TRACEVFORK: u32 = 0x4;
+pub const PTRACE_O_TRACECLONE: u32 = 0x8;
+pub const PTRACE_O_TRACEEXEC: u32 = 0x10;
+pub const PTRACE_O_TRACEVFORKDONE: u32 = 0x20;
+pub const PTRACE_O_TRACEEXIT: u32 = 0x40;
+pub const PTRACE_O_TRACESECCOMP: u32 = 0x80;
+pub const PTRACE_O_EXITKILL: u32 = 0x10_0000;
+
+/// Every option this kernel understands
+pub const PTRACE_O_MASK: u32 = 0xff | PTRACE_O_EXITKILL;
+
+pub const PTRACE_EVENT_FORK: u32 = 1;
+pub const PTRACE_EVENT_VFORK: u32 = 2;
+pub const PTRACE_EVENT_CLONE: u32 = 3;
+pub const PTRACE_EVENT_EXEC: u32 = 4;
+pub const PTRACE_EVENT_VFORK_DONE: u32 = 5;
+pub const PTRACE_EVENT_EXIT: u32 = 6;
+pub const PTRACE_EVENT_SECCOMP: u32 = 7;
+pub const PTRACE_EVENT_STOP: u32 = 128;
+
+/// `NT_PRSTATUS`, the general registers, for GETREGSET/SETREGSET
+const NT_PRSTATUS: usize = super::elf::NT_PRSTATUS as usize;
+
+/// Size of `siginfo_t`
+pub const SIGINFO_SIZE: usize = 128;
+
+const CLONE_VFORK: u64 = 0x4000;
+/// Exit signal bits of the clone flags
+const CSIGNAL: u64 = 0xff;
+
+/// Index of orig_rax in x86-64 `user_regs_struct`
+#[cfg(target_arch = "x86_64")]
+const ORIG_RAX: usize = 15;
+
+/// Why a ptrace request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceError {
+    /// No such tracee, or it is not stopped (ESRCH)
+    NoProcess,
+    /// Not allowed to trace the target (EPERM)
+    NotPermitted,
+    /// Bad option, signal or register set (EINVAL)
+    Invalid,
+    /// The tracer's buffer is not accessible (EFAULT)
+    Fault,
+    /// Unknown request, tracee memory that is not mapped, or something
+    /// this architecture cannot do (EIO)
+    Io,
+}
+
+/// Why a tracee is stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// About to act on a signal
+    Signal(Signal),
+    SyscallEnter,
+    SyscallExit,
+    /// A `PTRACE_EVENT_*` stop
+    Event(u32),
+}
+
+/// How the tracer last resumed the tracee
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Cont,
+    /// Also stop at system call entry and exit
+    Syscall,
+    /// Trap after one instruction
+    SingleStep,
+}
+
+/// Ptrace state of a traced process
+#[derive(Debug, Clone)]
+pub struct Tracee {
+    pub tracer: Pid,
+    pub options: u32,
+    /// Attached with PTRACE_SEIZE: no SIGSTOP on attach, and
+    /// PTRACE_EVENT_STOP instead of SIGSTOP for new children
+    pub seized: bool,
+    pub stop: Option<Stop>,
+    /// waitpid has returned the current stop to the tracer
+    pub reported: bool,
+    /// The tracee stopped itself and waits in [`stop_current`]
+    parked: bool,
+    /// Stop asked for while the tracee was on a CPU, taken at its next
+    /// system call boundary
+    pending: Option<Stop>,
+    pub resume: Resume,
+    /// Signal the tracer resumed a signal-delivery-stop with
+    resume_sig: Signal,
+    /// PTRACE_GETEVENTMSG: new child PID, exit status or seccomp data
+    pub event_msg: u64,
+    /// PTRACE_GETSIGINFO/SETSIGINFO for the current stop
+    pub siginfo: SigInfo,
+    /// Number of the system call in progress (x86-64 orig_rax)
+    syscall_nr: Option<usize>,
+}
+
+impl Tracee {
+    pub fn new(tracer: Pid, options: u32, seized: bool) -> Self {
+        Self {
+            tracer,
+            options,
+            seized,
+            stop: None,
+            reported: false,
+            parked: false,
+            pending: None,
+            resume: Resume::Cont,
+            resume_sig: 0,
+            event_msg: 0,
+            siginfo: SigInfo::default(),
+            syscall_nr: None,
+        }
+    }
+}
+
+/// Credentials compared by [`may_attach`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Creds {
+    pub uid: u32,
+    pub gid: u32,
+    pub euid: u32,
+    pub egid: u32,
+    pub suid: u32,
+    pub sgid: u32,
+}
+
+impl Creds {
+    pub fn of(proc: &Proc) -> Self {
+        Self {
+            uid: proc.uid,
+            gid: proc.gid,
+            euid: proc.euid,
+            egid: proc.egid,
+            suid: proc.suid,
+            sgid: proc.sgid,
+        }
+    }
+}
+
+/// Whether `tracer` may trace `target` without CAP_SYS_PTRACE: every user
+/// and group ID of the target must be the tracer's real one, so setuid
+/// programs cannot be traced by the user who ran them
+pub fn may_attach(tracer: &Creds, target: &Creds) -> bool {
+    [target.uid, target.euid, target.suid].iter().all(|&id| id == tracer.uid)
+        && [target.gid, target.egid, target.sgid].iter().all(|&id| id == tracer.gid)
+}
+
+/// The `waitpid` status reporting a stop
+pub fn wait_status(stop: Stop, options: u32) -> i32 {
+    let sig = match stop {
+        Stop::Signal(sig) => sig as i32,
+        Stop::SyscallEnter | Stop::SyscallExit if options & PTRACE_O_TRACESYSGOOD != 0 => {
+            SIGTRAP as i32 | 0x80
+        }
+        Stop::SyscallEnter | Stop::SyscallExit => SIGTRAP as i32,
+        Stop::Event(event) => SIGTRAP as i32 | (event as i32) << 8,
+    };
+    (sig << 8) | 0x7f
+}
+
+/// Event reported for a clone with `flags`: a vfork, a fork (exit signal
+/// SIGCHLD), or anything else
+pub fn clone_event(flags: u64) -> u32 {
+    if flags & CLONE_VFORK != 0 {
+        PTRACE_EVENT_VFORK
+    } else if flags & CSIGNAL == SIGCHLD as u64 {
+        PTRACE_EVENT_FORK
+    } else {
+        PTRACE_EVENT_CLONE
+    }
+}
+
+/// The `PTRACE_O_TRACE*` option enabling an event
+fn event_option(event: u32) -> u32 {
+    match event {
+        PTRACE_EVENT_FORK..=PTRACE_EVENT_SECCOMP => 1 << event,
+        _ => 0,
+    }
+}
+
+/// Signals whose siginfo carries a fault address rather than a sender
+fn is_fault(info: &SigInfo) -> bool {
+    match info.signo as Signal {
+        SIGILL | SIGFPE | SIGSEGV | SIGBUS => true,
+        SIGTRAP => (si_code::TRAP_BRKPT..=4).contains(&info.code),
+        _ => false,
+    }
+}
+
+/// Lay out `info` as the user `siginfo_t`
+pub fn siginfo_to_user(info: &SigInfo) -> [u8; SIGINFO_SIZE] {
+    let mut buf = [0u8; SIGINFO_SIZE];
+    buf[0..4].copy_from_slice(&info.signo.to_ne_bytes());
+    buf[4..8].copy_from_slice(&info.errno.to_ne_bytes());
+    buf[8..12].copy_from_slice(&info.code.to_ne_bytes());
+    if is_fault(info) {
+        buf[16..24].copy_from_slice(&(info.addr as u64).to_ne_bytes());
+    } else {
+        buf[16..20].copy_from_slice(&info.pid.to_ne_bytes());
+        buf[20..24].copy_from_slice(&info.uid.to_ne_bytes());
+        if info.signo as Signal == SIGCHLD {
+            buf[24..28].copy_from_slice(&info.status.to_ne_bytes());
+        } else {
+            buf[24..32].copy_from_slice(&(info.value as u64).to_ne_bytes());
+        }
+    }
+    buf
+}
+
+/// Read a user `siginfo_t`
+pub fn siginfo_from_user(buf: &[u8; SIGINFO_SIZE]) -> SigInfo {
+    let i32_at = |off: usize| i32::from_ne_bytes(buf[off..off + 4].try_into().unwrap());
+    let u64_at = |off: usize| u64::from_ne_bytes(buf[off..off + 8].try_into().unwrap());
+    let mut info = SigInfo { signo: i32_at(0), errno: i32_at(4), code: i32_at(8), ..SigInfo::default() };
+    if is_fault(&info) {
+        info.addr = u64_at(16) as usize;
+    } else {
+        info.pid = i32_at(16);
+        info.uid = i32_at(20) as u32;
+        if info.signo as Signal == SIGCHLD {
+            info.status = i32_at(24);
+        } else {
+            info.value = u64_at(24) as usize;
+        }
+    }
+    info
+}
+
+// ============================================================================
+// Tracee side
+// ============================================================================
+
+fn with_tracee<R>(pid: Pid, f: impl FnOnce(&mut Tracee, *mut TrapFrame) -> R) -> Option<R> {
+    let mut table = PROC_TABLE.lock();
+    let proc = table.find(pid)?;
+    let tf = proc.trapframe;
+    proc.ptrace.as_mut().map(|tracee| f(tracee, tf))
+}
+
+/// Stop the current process and wait for its tracer
+///
+/// Returns the signal the tracer resumed with, 0 if it detached or the
+/// process is being killed, and `None` if the process is not traced.
+fn stop_current(pid: Pid, stop: Stop, event_msg: Option<u64>) -> Option<Signal> {
+    {
+        let mut table = PROC_TABLE.lock();
+        let proc = table.find(pid)?;
+        if proc.killed {
+            return None;
+        }
+        let (tracee_pid, uid) = (proc.pid, proc.uid);
+        let tracee = proc.ptrace.as_mut()?;
+        if let Some(msg) = event_msg {
+            tracee.event_msg = msg;
+        }
+        if !matches!(stop, Stop::Signal(_)) {
+            // What Linux's ptrace_notify() leaves for PTRACE_GETSIGINFO
+            tracee.siginfo = SigInfo {
+                signo: SIGTRAP as i32,
+                code: wait_status(stop, tracee.options) >> 8,
+                pid: tracee_pid,
+                uid,
+                ..SigInfo::default()
+            };
+        }
+        tracee.stop = Some(stop);
+        tracee.reported = false;
+        tracee.parked = true;
+        let tracer = tracee.tracer;
+        proc.state = ProcState::Stopped;
+        wakeup_pid(&mut table, tracer);
+    }
+
+    loop {
+        yield_cpu();
+        let mut table = PROC_TABLE.lock();
+        let proc = table.find(pid)?;
+        let killed = proc.killed
+            || proc.signals.as_ref().is_some_and(|s| s.pending_signals().contains(SIGKILL));
+        let Some(tracee) = proc.ptrace.as_mut() else {
+            return Some(0);
+        };
+        if tracee.stop.is_none() || killed {
+            tracee.stop = None;
+            tracee.parked = false;
+            sync_single_step(tracee.resume == Resume::SingleStep);
+            let sig = core::mem::take(&mut tracee.resume_sig);
+            return Some(if killed { 0 } else { sig });
+        }
+        // Woken for something else; the tracer has not resumed us
+        proc.state = ProcState::Stopped;
+    }
+}
+
+/// AArch64 also needs MDSCR_EL1.SS for SPSR.SS to single-step EL0
+fn sync_single_step(on: bool) {
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        const MDSCR_SS: u64 = 1 << 0;
+        let mut mdscr: u64;
+        core::arch::asm!("mrs {}, mdscr_el1", out(reg) mdscr);
+        if on { mdscr |= MDSCR_SS } else { mdscr &= !MDSCR_SS }
+        core::arch::asm!("msr mdscr_el1, {}", "isb", in(reg) mdscr);
+    }
+    #[cfg(not(target_arch = "aarch64"))]
+    let _ = on;
+}
+
+/// The system call number as the tracer left it
+fn current_nr(tracee: &mut Tracee, _tf: *mut TrapFrame) -> Option<usize> {
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        tracee.syscall_nr = Some(unsafe { (*_tf).syscall_nr() });
+    }
+    tracee.syscall_nr
+}
+
+/// System call entry: take a pending stop and the PTRACE_SYSCALL entry stop
+///
+/// Returns the number of the call to run, which the tracer may have
+/// rewritten, or `None` if it cancelled the call by setting it to -1.
+pub fn syscall_enter(nr: usize) -> Option<usize> {
+    let Some(pid) = myproc() else { return Some(nr) };
+    let Some(pending) = with_tracee(pid, |tracee, _tf| {
+        tracee.syscall_nr = Some(nr);
+        // A call the tracer skips returns -ENOSYS unless it sets rax
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            (*_tf).set_syscall_return(-(crate::reliability::errno::ENOSYS as isize));
+        }
+        tracee.pending.take()
+    }) else {
+        return Some(nr);
+    };
+
+    if let Some(stop) = pending {
+        stop_current(pid, stop, None);
+    }
+    if with_tracee(pid, |tracee, _| tracee.resume) == Some(Resume::Syscall) {
+        stop_current(pid, Stop::SyscallEnter, None);
+    }
+    let nr = with_tracee(pid, current_nr).flatten().unwrap_or(nr);
+    (nr != usize::MAX).then_some(nr)
+}
+
+/// System call exit, once the return value is in place: the PTRACE_SYSCALL
+/// exit stop and any stop asked for while the call ran
+pub fn syscall_exit() {
+    let Some(pid) = myproc() else { return };
+    let Some(mode) = with_tracee(pid, |tracee, _| tracee.resume) else { return };
+
+    if mode == Resume::Syscall {
+        stop_current(pid, Stop::SyscallExit, None);
+    }
+    if let Some(stop) = with_tracee(pid, |tracee, _| tracee.pending.take()).flatten() {
+        stop_current(pid, stop, None);
+    }
+    with_tracee(pid, |tracee, _| tracee.syscall_nr = None);
+}
+
+/// Signal-delivery-stop: show `sig` to the tracer before acting on it
+///
+/// Returns the signal to act on instead, with its siginfo, or `None` if
+/// the tracer suppressed it. Untraced processes get `sig` back unchanged,
+/// and SIGKILL is never intercepted.
+pub fn signal_stop(sig: Signal, info: &SigInfo) -> Option<(Signal, SigInfo)> {
+    if sig == SIGKILL {
+        return Some((sig, *info));
+    }
+    let Some(pid) = myproc() else { return Some((sig, *info)) };
+    let seen = SigInfo { signo: sig as i32, ..*info };
+    if with_tracee(pid, |tracee, _| tracee.siginfo = seen).is_none() {
+        return Some((sig, *info));
+    }
+
+    let new_sig = stop_current(pid, Stop::Signal(sig), None).unwrap_or(sig);
+    if new_sig == 0 {
+        return None;
+    }
+    // Keep what PTRACE_SETSIGINFO left unless the signal itself changed
+    let info = with_tracee(pid, |tracee, _| tracee.siginfo).unwrap_or(seen);
+    if info.signo as Signal == new_sig {
+        Some((new_sig, info))
+    } else {
+        let tracer = with_tracee(pid, |tracee, _| tracee.tracer).unwrap_or(0);
+        Some((new_sig, SigInfo { signo: new_sig as i32, code: si_code::SI_USER, pid: tracer, ..SigInfo::default() }))
+    }
+}
+
+/// A breakpoint or single-step trap from user mode: queue SIGTRAP
+pub fn debug_trap(single_step: bool, addr: usize) {
+    let Some(pid) = myproc() else { return };
+    let table = PROC_TABLE.lock();
+    if let Some(signals) = table.find_ref(pid).and_then(|proc| proc.signals.as_ref()) {
+        let code = if single_step { si_code::TRAP_TRACE } else { si_code::TRAP_BRKPT };
+        let info = SigInfo { signo: SIGTRAP as i32, code, addr, ..SigInfo::default() };
+        let _ = signals.send_signal_info(SIGTRAP, info);
+    }
+}
+
+/// The current process created `child` with `event`: if the tracer asked
+/// for that event the child is traced too, starting out stopped, and the
+/// creator reports it
+pub fn report_new_child(child: Pid, event: u32) {
+    let Some(pid) = myproc() else { return };
+    {
+        let mut table = PROC_TABLE.lock();
+        let Some(parent) = table.find_ref(pid).and_then(|proc| proc.ptrace.clone()) else { return };
+        if parent.options & event_option(event) == 0 {
+            return;
+        }
+        if let Some(proc) = table.find(child) {
+            proc.ptrace = Some(Tracee::new(parent.tracer, parent.options, parent.seized));
+            let stop = if parent.seized { Stop::Event(PTRACE_EVENT_STOP) } else { Stop::Signal(SIGSTOP) };
+            stop_remote(proc, stop);
+        }
+    }
+    stop_current(pid, Stop::Event(event), Some(child as u64));
+}
+
+/// The current process has a new image: PTRACE_EVENT_EXEC if asked for,
+/// otherwise the traditional SIGTRAP unless attached with PTRACE_SEIZE
+pub fn report_exec(pid: Pid) {
+    let Some((options, seized)) = with_tracee(pid, |tracee, _| (tracee.options, tracee.seized)) else {
+        return;
+    };
+    if options & PTRACE_O_TRACEEXEC != 0 {
+        stop_current(pid, Stop::Event(PTRACE_EVENT_EXEC), Some(pid as u64));
+    } else if !seized {
+        let _ = super::manager::kill_proc(pid, SIGTRAP);
+    }
+}
+
+/// The current process is about to exit with `xstate`: PTRACE_EVENT_EXIT
+pub fn report_exit(xstate: i32) {
+    let Some(pid) = myproc() else { return };
+    if with_tracee(pid, |tracee, _| tracee.options & PTRACE_O_TRACEEXIT != 0) == Some(true) {
+        stop_current(pid, Stop::Event(PTRACE_EVENT_EXIT), Some(xstate as u64));
+    }
+}
+
+/// SECCOMP_RET_TRACE: stop for a tracer that set PTRACE_O_TRACESECCOMP
+///
+/// `None` means nobody is listening and the call fails with ENOSYS;
+/// otherwise whether to run the call, which the tracer can cancel by
+/// setting the number to -1.
+pub fn seccomp_stop(data: u32) -> Option<bool> {
+    let pid = myproc()?;
+    if !with_tracee(pid, |tracee, _| tracee.options & PTRACE_O_TRACESECCOMP != 0)? {
+        return None;
+    }
+    stop_current(pid, Stop::Event(PTRACE_EVENT_SECCOMP), Some(data as u64));
+    Some(with_tracee(pid, current_nr).flatten() != Some(usize::MAX))
+}
+
+/// Called with the table locked as `pid` exits. Its tracer hears about the
+/// exit like a parent would; its own tracees are let go, or killed under
+/// PTRACE_O_EXITKILL.
+pub(super) fn exit_notify(table: &mut ProcTable, pid: Pid) {
+    if let Some(tracer) = table.find_ref(pid).and_then(|proc| proc.ptrace.as_ref()).map(|t| t.tracer) {
+        wakeup_pid(table, tracer);
+    }
+
+    let mut parents = alloc::vec::Vec::new();
+    for proc in table.iter_mut() {
+        let Some(tracee) = proc.ptrace.take_if(|t| t.tracer == pid) else { continue };
+        if !proc.trapframe.is_null() {
+            unsafe { (*proc.trapframe).set_single_step(false) };
+        }
+        if tracee.options & PTRACE_O_EXITKILL != 0 {
+            proc.killed = true;
+            if let Some(ref signals) = proc.signals {
+                let _ = signals.send_signal(SIGKILL);
+            }
+        }
+        match proc.state {
+            ProcState::Stopped if tracee.stop.is_some() => proc.state = ProcState::Runnable,
+            // The real parent may reap it now
+            ProcState::Zombie => parents.extend(proc.parent),
+            _ => {}
+        }
+    }
+    for parent in parents {
+        wakeup_pid(table, parent);
+    }
+}
+
+/// Stop a tracee other than the current process
+fn stop_remote(proc: &mut Proc, stop: Stop) {
+    let Some(tracee) = proc.ptrace.as_mut() else { return };
+    if proc.state == ProcState::Running {
+        // On another CPU: it stops at its next system call boundary
+        tracee.pending = Some(stop);
+        return;
+    }
+    tracee.stop = Some(stop);
+    tracee.reported = false;
+    tracee.parked = false;
+    proc.state = ProcState::Stopped;
+}
+
+// ============================================================================
+// Tracer side
+// ============================================================================
+
+/// Whether `tracer` has any tracees, for waitpid
+pub(super) fn has_tracees(table: &ProcTable, tracer: Pid) -> bool {
+    table.iter().any(|proc| proc.ptrace.as_ref().is_some_and(|t| t.tracer == tracer))
+}
+
+/// Find an unreported stop or exit among `tracer`'s tracees matching
+/// `pid` (any if not positive), for waitpid
+///
+/// A stop is marked reported. An exited tracee is detached, and reaped
+/// only if the tracer is also its parent; otherwise the parent is woken
+/// to collect it.
+pub(super) fn wait_tracee(table: &mut ProcTable, tracer: Pid, pid: i32) -> Option<(Pid, i32)> {
+    let (tracee, status, zombie) = table.iter().find_map(|proc| {
+        let t = proc.ptrace.as_ref().filter(|t| t.tracer == tracer)?;
+        if pid > 0 && proc.pid != pid {
+            return None;
+        }
+        if proc.state == ProcState::Zombie {
+            return Some((proc.pid, proc.xstate, true));
+        }
+        match t.stop {
+            Some(stop) if !t.reported => Some((proc.pid, wait_status(stop, t.options), false)),
+            _ => None,
+        }
+    })?;
+
+    let proc = table.find(tracee)?;
+    if !zombie {
+        proc.ptrace.as_mut()?.reported = true;
+        return Some((tracee, status));
+    }
+    proc.ptrace = None;
+    match proc.parent {
+        Some(parent) if parent == tracer => table.free(tracee),
+        Some(parent) => wakeup_pid(table, parent),
+        None => {}
+    }
+    Some((tracee, status))
+}
+
+/// Look up a tracee of `tracer`, which must be in a ptrace stop if `stopped`
+fn tracee_of(table: &mut ProcTable, tracer: Pid, pid: Pid, stopped: bool) -> Result<&mut Proc, PtraceError> {
+    let proc = table.find(pid).ok_or(PtraceError::NoProcess)?;
+    match &proc.ptrace {
+        Some(t) if t.tracer == tracer && (!stopped || t.stop.is_some()) && proc.state != ProcState::Zombie => {
+            Ok(proc)
+        }
+        _ => Err(PtraceError::NoProcess),
+    }
+}
+
+/// The tracee's registers as `user_regs_struct`
+fn read_regs(proc: &Proc) -> [u64; ELF_NGREG] {
+    let tracee = proc.ptrace.as_ref();
+    let regs = unsafe { (*proc.trapframe).user_regs() };
+    #[cfg(target_arch = "x86_64")]
+    let regs = {
+        let mut regs = regs;
+        if let Some(nr) = tracee.and_then(|t| t.syscall_nr) {
+            regs[ORIG_RAX] = nr as u64;
+        }
+        regs
+    };
+    let _ = tracee;
+    regs
+}
+
+fn write_regs(proc: &mut Proc, regs: &[u64; ELF_NGREG]) {
+    unsafe { (*proc.trapframe).set_user_regs(regs) };
+    #[cfg(target_arch = "x86_64")]
+    if let Some(tracee) = proc.ptrace.as_mut()
+        && tracee.syscall_nr.is_some()
+    {
+        tracee.syscall_nr = Some(regs[ORIG_RAX] as usize);
+    }
+}
+
+fn copy_to_tracer(pagetable: *mut PageTable, dst: usize, bytes: &[u8]) -> Result<(), PtraceError> {
+    if pagetable.is_null() {
+        return Err(PtraceError::Fault);
+    }
+    unsafe { copyout(pagetable, dst, bytes.as_ptr(), bytes.len()) }.map_err(|_| PtraceError::Fault)
+}
+
+fn copy_from_tracer(pagetable: *mut PageTable, src: usize, bytes: &mut [u8]) -> Result<(), PtraceError> {
+    if pagetable.is_null() {
+        return Err(PtraceError::Fault);
+    }
+    unsafe { copyin(pagetable, bytes.as_mut_ptr(), src, bytes.len()) }.map_err(|_| PtraceError::Fault)
+}
+
+/// `struct iovec` for GETREGSET/SETREGSET
+#[repr(C)]
+#[derive(Default)]
+struct Iovec {
+    base: u64,
+    len: u64,
+}
+
+/// Carry out a ptrace request from the current process
+pub fn ptrace(request: u32, pid: Pid, addr: usize, data: usize) -> Result<u64, PtraceError> {
+    let me = myproc().ok_or(PtraceError::NoProcess)?;
+    match request {
+        PTRACE_TRACEME => return trace_me(me),
+        PTRACE_ATTACH => return attach(me, pid, false, 0),
+        PTRACE_SEIZE if addr != 0 => return Err(PtraceError::Invalid),
+        PTRACE_SEIZE => return attach(me, pid, true, data as u32),
+        _ => {}
+    }
+
+    let mut table = PROC_TABLE.lock();
+    let my_pagetable = table.find_ref(me).ok_or(PtraceError::NoProcess)?.pagetable;
+
+    match request {
+        PTRACE_KILL => {
+            let proc = tracee_of(&mut table, me, pid, false)?;
+            if let Some(ref signals) = proc.signals {
+                let _ = signals.send_signal(SIGKILL);
+            }
+            proc.killed = true;
+            if let Some(tracee) = proc.ptrace.as_mut() {
+                tracee.stop = None;
+            }
+            if matches!(proc.state, ProcState::Stopped | ProcState::Sleeping) {
+                proc.state = ProcState::Runnable;
+            }
+            return Ok(0);
+        }
+        PTRACE_INTERRUPT => {
+            let proc = tracee_of(&mut table, me, pid, false)?;
+            match proc.ptrace.as_ref() {
+                Some(t) if !t.seized => return Err(PtraceError::Io),
+                Some(t) if t.stop.is_some() => {}
+                _ => stop_remote(proc, Stop::Event(PTRACE_EVENT_STOP)),
+            }
+            return Ok(0);
+        }
+        _ => {}
+    }
+
+    let proc = tracee_of(&mut table, me, pid, true)?;
+    match request {
+        PTRACE_PEEKTEXT | PTRACE_PEEKDATA => {
+            let pagetable = proc.pagetable;
+            drop(table);
+            let mut word = [0u8; 8];
+            unsafe { copyin(pagetable, word.as_mut_ptr(), addr, word.len()) }.map_err(|_| PtraceError::Io)?;
+            copy_to_tracer(my_pagetable, data, &word)?;
+            Ok(0)
+        }
+        PTRACE_POKETEXT | PTRACE_POKEDATA => {
+            let pagetable = proc.pagetable;
+            drop(table);
+            let word = (data as u64).to_ne_bytes();
+            unsafe { copyout_force(pagetable, addr, word.as_ptr(), word.len()) }.map_err(|_| PtraceError::Io)?;
+            Ok(0)
+        }
+        PTRACE_PEEKUSER => {
+            let index = user_index(addr)?;
+            let value = read_regs(proc)[index];
+            drop(table);
+            copy_to_tracer(my_pagetable, data, &value.to_ne_bytes())?;
+            Ok(0)
+        }
+        PTRACE_POKEUSER => {
+            let index = user_index(addr)?;
+            let mut regs = read_regs(proc);
+            regs[index] = data as u64;
+            write_regs(proc, &regs);
+            Ok(0)
+        }
+        PTRACE_GETREGS => {
+            let regs = read_regs(proc);
+            drop(table);
+            copy_to_tracer(my_pagetable, data, regs_bytes(&regs))?;
+            Ok(0)
+        }
+        PTRACE_SETREGS => {
+            drop(table);
+            let mut regs = [0u64; ELF_NGREG];
+            copy_from_tracer(my_pagetable, data, regs_bytes_mut(&mut regs))?;
+            let mut table = PROC_TABLE.lock();
+            write_regs(tracee_of(&mut table, me, pid, true)?, &regs);
+            Ok(0)
+        }
+        PTRACE_GETREGSET | PTRACE_SETREGSET => {
+            if addr != NT_PRSTATUS {
+                return Err(PtraceError::Invalid);
+            }
+            let mut regs = read_regs(proc);
+            drop(table);
+            let mut iov = Iovec::default();
+            copy_from_tracer(my_pagetable, data, iov_bytes_mut(&mut iov))?;
+            let len = (iov.len as usize).min(ELF_NGREG * 8);
+            if request == PTRACE_GETREGSET {
+                copy_to_tracer(my_pagetable, iov.base as usize, &regs_bytes(&regs)[..len])?;
+            } else {
+                copy_from_tracer(my_pagetable, iov.base as usize, &mut regs_bytes_mut(&mut regs)[..len])?;
+                let mut table = PROC_TABLE.lock();
+                write_regs(tracee_of(&mut table, me, pid, true)?, &regs);
+            }
+            iov.len = len as u64;
+            copy_to_tracer(my_pagetable, data + 8, &iov.len.to_ne_bytes())?;
+            Ok(0)
+        }
+        PTRACE_GETSIGINFO => {
+            let info = proc.ptrace.as_ref().map(|t| t.siginfo).unwrap_or_default();
+            drop(table);
+            copy_to_tracer(my_pagetable, data, &siginfo_to_user(&info))?;
+            Ok(0)
+        }
+        PTRACE_SETSIGINFO => {
+            drop(table);
+            let mut buf = [0u8; SIGINFO_SIZE];
+            copy_from_tracer(my_pagetable, data, &mut buf)?;
+            let mut table = PROC_TABLE.lock();
+            let proc = tracee_of(&mut table, me, pid, true)?;
+            if let Some(tracee) = proc.ptrace.as_mut() {
+                tracee.siginfo = siginfo_from_user(&buf);
+            }
+            Ok(0)
+        }
+        PTRACE_GETEVENTMSG => {
+            let msg = proc.ptrace.as_ref().map(|t| t.event_msg).unwrap_or(0);
+            drop(table);
+            copy_to_tracer(my_pagetable, data, &msg.to_ne_bytes())?;
+            Ok(0)
+        }
+        PTRACE_SETOPTIONS => {
+            let options = u32::try_from(data).ok().filter(|o| o & !PTRACE_O_MASK == 0).ok_or(PtraceError::Invalid)?;
+            if let Some(tracee) = proc.ptrace.as_mut() {
+                tracee.options = options;
+            }
+            Ok(0)
+        }
+        PTRACE_CONT => resume(proc, Some(Resume::Cont), data),
+        PTRACE_SYSCALL => resume(proc, Some(Resume::Syscall), data),
+        PTRACE_SINGLESTEP => resume(proc, Some(Resume::SingleStep), data),
+        PTRACE_DETACH => resume(proc, None, data),
+        _ => Err(PtraceError::Io),
+    }
+}
+
+fn trace_me(me: Pid) -> Result<u64, PtraceError> {
+    let mut table = PROC_TABLE.lock();
+    let proc = table.find(me).ok_or(PtraceError::NoProcess)?;
+    if proc.ptrace.is_some() {
+        return Err(PtraceError::NotPermitted);
+    }
+    let parent = proc.parent.ok_or(PtraceError::NotPermitted)?;
+    proc.ptrace = Some(Tracee::new(parent, 0, false));
+    Ok(0)
+}
+
+fn attach(tracer: Pid, pid: Pid, seize: bool, options: u32) -> Result<u64, PtraceError> {
+    if options & !PTRACE_O_MASK != 0 {
+        return Err(PtraceError::Invalid);
+    }
+    if pid == tracer {
+        return Err(PtraceError::NotPermitted);
+    }
+    let (creds, euid) = {
+        let table = PROC_TABLE.lock();
+        let me = table.find_ref(tracer).ok_or(PtraceError::NoProcess)?;
+        (Creds::of(me), me.euid)
+    };
+    let privileged = capable(tracer as u64, euid, Capability::SysPtrace);
+
+    let mut table = PROC_TABLE.lock();
+    let target = table.find(pid).ok_or(PtraceError::NoProcess)?;
+    if target.state == ProcState::Zombie || target.ptrace.is_some() {
+        return Err(PtraceError::NotPermitted);
+    }
+    if !privileged && !may_attach(&creds, &Creds::of(target)) {
+        return Err(PtraceError::NotPermitted);
+    }
+    target.ptrace = Some(Tracee::new(tracer, options, seize));
+    if !seize {
+        stop_remote(target, Stop::Signal(SIGSTOP));
+    }
+    Ok(0)
+}
+
+/// Restart a stopped tracee, or let it go if `mode` is `None`
+fn resume(proc: &mut Proc, mode: Option<Resume>, sig: usize) -> Result<u64, PtraceError> {
+    let sig = u32::try_from(sig).ok().filter(|&s| (s as usize) < NSIG).ok_or(PtraceError::Io)?;
+    let step = mode == Some(Resume::SingleStep);
+    if !proc.trapframe.is_null() && !unsafe { (*proc.trapframe).set_single_step(step) } {
+        return Err(PtraceError::Io);
+    }
+    let Some(tracee) = proc.ptrace.as_mut() else { return Err(PtraceError::NoProcess) };
+    let parked = tracee.parked;
+    match mode {
+        Some(mode) => {
+            tracee.resume = mode;
+            tracee.resume_sig = sig;
+            tracee.stop = None;
+        }
+        None => proc.ptrace = None,
+    }
+    // A parked tracee picks its signal up itself; otherwise it is queued
+    if sig != 0 && (!parked || mode.is_none()) {
+        if let Some(ref signals) = proc.signals {
+            let _ = signals.send_signal(sig);
+        }
+    }
+    if proc.state == ProcState::Stopped {
+        proc.state = ProcState::Runnable;
+    }
+    Ok(0)
+}
+
+/// Register index for a PEEKUSER/POKEUSER offset into `struct user`
+fn user_index(addr: usize) -> Result<usize, PtraceError> {
+    if addr % 8 != 0 || addr / 8 >= ELF_NGREG {
+        return Err(PtraceError::Io);
+    }
+    Ok(addr / 8)
+}
+
+fn regs_bytes(regs: &[u64; ELF_NGREG]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(regs.as_ptr() as *const u8, ELF_NGREG * 8) }
+}
+
+fn regs_bytes_mut(regs: &mut [u64; ELF_NGREG]) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(regs.as_mut_ptr() as *mut u8, ELF_NGREG * 8) }
+}
+
+fn iov_bytes_mut(iov: &mut Iovec) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(iov as *mut Iovec as *mut u8, core::mem::size_of::<Iovec>()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_status() {
+        assert_eq!(wait_status(Stop::Signal(SIGSTOP), 0), 0x137f);
+        assert_eq!(wait_status(Stop::SyscallEnter, 0), 0x57f);
+        assert_eq!(wait_status(Stop::SyscallExit, PTRACE_O_TRACESYSGOOD), 0x857f);
+        assert_eq!(wait_status(Stop::Event(PTRACE_EVENT_EXEC), 0), 0x4057f);
+        assert_eq!(wait_status(Stop::Event(PTRACE_EVENT_STOP), 0), 0x80057f);
+
+        // WIFSTOPPED and WSTOPSIG
+        let status = wait_status(Stop::Event(PTRACE_EVENT_FORK), 0);
+        assert_eq!(status & 0xff, 0x7f);
+        assert_eq!((status >> 8) & 0xff, SIGTRAP as i32);
+        assert_eq!(status >> 16, PTRACE_EVENT_FORK as i32);
+    }
+
+    #[test]
+    fn test_may_attach() {
+        let user = Creds { uid: 1000, gid: 100, euid: 1000, egid: 100, suid: 1000, sgid: 100 };
+        assert!(may_attach(&user, &user));
+
+        // A setuid program run by the same user
+        let setuid = Creds { euid: 0, suid: 0, ..user };
+        assert!(!may_attach(&user, &setuid));
+        let setgid = Creds { sgid: 5, ..user };
+        assert!(!may_attach(&user, &setgid));
+
+        let other = Creds { uid: 1001, euid: 1001, suid: 1001, ..user };
+        assert!(!may_attach(&user, &other));
+        // Only the tracer's real IDs count
+        let tracer = Creds { euid: 1001, ..user };
+        assert!(may_attach(&tracer, &user));
+    }
+
+    #[test]
+    fn test_events() {
+        assert_eq!(clone_event(SIGCHLD as u64), PTRACE_EVENT_FORK);
+        assert_eq!(clone_event(CLONE_VFORK | SIGCHLD as u64), PTRACE_EVENT_VFORK);
+        assert_eq!(clone_event(0x10f00), PTRACE_EVENT_CLONE);
+        assert_eq!(event_option(PTRACE_EVENT_FORK), PTRACE_O_TRACEFORK);
+        assert_eq!(event_option(PTRACE_EVENT_CLONE), PTRACE_O_TRACECLONE);
+        assert_eq!(event_option(PTRACE_EVENT_EXIT), PTRACE_O_TRACEEXIT);
+        assert_eq!(event_option(PTRACE_EVENT_SECCOMP), PTRACE_O_TRACESECCOMP);
+        assert_eq!(event_option(PTRACE_EVENT_STOP), 0);
+    }
+
+    #[test]
+    fn test_siginfo_layout() {
+        let kill = SigInfo { signo: 15, code: si_code::SI_USER, pid: 42, uid: 1000, ..SigInfo::default() };
+        let buf = siginfo_to_user(&kill);
+        assert_eq!(&buf[0..4], &15i32.to_ne_bytes());
+        assert_eq!(&buf[16..20], &42i32.to_ne_bytes());
+        assert_eq!(&buf[20..24], &1000u32.to_ne_bytes());
+        assert_eq!(siginfo_from_user(&buf).pid, 42);
+
+        let fault = SigInfo { signo: SIGSEGV as i32, code: si_code::SEGV_MAPERR, addr: 0xdead_0000, ..SigInfo::default() };
+        let buf = siginfo_to_user(&fault);
+        assert_eq!(&buf[16..24], &0xdead_0000u64.to_ne_bytes());
+        let back = siginfo_from_user(&buf);
+        assert_eq!((back.signo, back.code, back.addr), (SIGSEGV as i32, si_code::SEGV_MAPERR, 0xdead_0000));
+
+        let brkpt = SigInfo { signo: SIGTRAP as i32, code: si_code::TRAP_BRKPT, addr: 0x1000, ..SigInfo::default() };
+        assert_eq!(siginfo_from_user(&siginfo_to_user(&brkpt)).addr, 0x1000);
+    }
+}
@@ -8,7 +8,7 @@
 //! and use the RCU mechanism, while writes create a new copy of the table
 //! and update the pointer atomically.
 
-use crate::subsystems::process::manager::{Pid, Proc, ProcTable, ProcState, NPROC};
+use crate::subsystems::process::manager::{Pid, Proc, ProcTable, ProcState};
 use crate::subsystems::sync::{Mutex, rcu};
 use core::sync::atomic::{AtomicPtr, Ordering};
 
@@ -218,11 +218,9 @@ impl Iterator for ProcTableIterator {
     fn next(&mut self) -> Option<Self::Item> {
         unsafe {
             let table = &*self._guard.table_ptr;
-            if self.index >= NPROC {
+            let Some(proc) = table.slot(self.index) else {
                 return None;
-            }
-            
-            let proc = &table.procs[self.index];
+            };
             self.index += 1;
             
             if proc.state == ProcState::Unused {
@@ -235,7 +233,7 @@ impl Iterator for ProcTableIterator {
 }
 
 // Import necessary types
-// use crate::subsystems::process::manager::{ProcState, NPROC}; // Already imported earlier
+// use crate::subsystems::process::manager::ProcState; // Already imported earlier
 
 /// Global RCU-protected process table
 static RCU_PROC_TABLE: Mutex<Option<RcuProcTable>> = Mutex::new(None);
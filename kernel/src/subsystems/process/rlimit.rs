@@ -0,0 +1,160 @@
+//! Resource limits
+//!
+//! Limits live in `Proc::rlimits`, are inherited across fork and survive
+//! exec. Where each one is enforced:
+//!
+//! - `RLIMIT_NOFILE`: descriptor allocation (`fdalloc`, `dup2`, `F_DUPFD`)
+//! - `RLIMIT_NPROC`: `fork`/`clone`, counted per real user ID
+//! - `RLIMIT_AS`: `brk`, `mmap` and `mremap` growth
+//! - `RLIMIT_STACK`: exec refuses arguments larger than a quarter of it
+//! - `RLIMIT_CPU`: the timer tick, with `SIGXCPU` each second past the soft
+//!   limit and `SIGKILL` at the hard one
+//! - `RLIMIT_FSIZE`: regular file writes, with `SIGXFSZ` and `EFBIG`
+//! - `RLIMIT_CORE`: the size of core dumps
+
+use crate::posix::{
+    Rlimit, RLIM_INFINITY, RLIM_NLIMITS, RLIMIT_CORE, RLIMIT_MEMLOCK, RLIMIT_MSGQUEUE,
+    RLIMIT_NICE, RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_RTPRIO, RLIMIT_SIGPENDING, RLIMIT_STACK,
+};
+
+use super::fdtable::NR_OPEN;
+use super::manager::{myproc, PROC_TABLE};
+use super::pid::PID_MAX_DEFAULT;
+
+/// Default soft and hard `RLIMIT_NOFILE`
+pub const NOFILE_DEFAULT: Rlimit = Rlimit::new(1024, 65536);
+
+/// Default `RLIMIT_NPROC`, half the PID space like Linux
+pub const NPROC_DEFAULT: u64 = PID_MAX_DEFAULT as u64 / 2;
+
+/// Why a new limit was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RlimitError {
+    /// Unknown resource or soft limit above the hard one (EINVAL)
+    Invalid,
+    /// Raising a hard limit needs privilege (EPERM)
+    NotPermitted,
+}
+
+/// Limits of the first process; everything else inherits them
+pub const fn defaults() -> [Rlimit; RLIM_NLIMITS] {
+    let mut limits = [Rlimit::new(RLIM_INFINITY, RLIM_INFINITY); RLIM_NLIMITS];
+    limits[RLIMIT_STACK as usize] = Rlimit::new(8 * 1024 * 1024, RLIM_INFINITY);
+    limits[RLIMIT_CORE as usize] = Rlimit::new(0, RLIM_INFINITY);
+    limits[RLIMIT_NPROC as usize] = Rlimit::new(NPROC_DEFAULT, NPROC_DEFAULT);
+    limits[RLIMIT_NOFILE as usize] = NOFILE_DEFAULT;
+    limits[RLIMIT_MEMLOCK as usize] = Rlimit::new(8 * 1024 * 1024, 8 * 1024 * 1024);
+    limits[RLIMIT_SIGPENDING as usize] = Rlimit::new(NPROC_DEFAULT, NPROC_DEFAULT);
+    limits[RLIMIT_MSGQUEUE as usize] = Rlimit::new(819200, 819200);
+    limits[RLIMIT_NICE as usize] = Rlimit::new(0, 0);
+    limits[RLIMIT_RTPRIO as usize] = Rlimit::new(0, 0);
+    limits
+}
+
+/// Index into `Proc::rlimits` for a user-supplied resource number
+pub fn index(resource: i32) -> Result<usize, RlimitError> {
+    usize::try_from(resource)
+        .ok()
+        .filter(|&r| r < RLIM_NLIMITS)
+        .ok_or(RlimitError::Invalid)
+}
+
+/// Check a setrlimit/prlimit request against the current limit
+pub fn validate(resource: i32, old: Rlimit, new: Rlimit, privileged: bool) -> Result<(), RlimitError> {
+    index(resource)?;
+    if new.rlim_cur > new.rlim_max {
+        return Err(RlimitError::Invalid);
+    }
+    if resource == RLIMIT_NOFILE && new.rlim_max > NR_OPEN as u64 {
+        return Err(RlimitError::NotPermitted);
+    }
+    if new.rlim_max > old.rlim_max && !privileged {
+        return Err(RlimitError::NotPermitted);
+    }
+    Ok(())
+}
+
+/// Soft limit of the current process; unlimited outside process context
+pub fn current(resource: i32) -> u64 {
+    let Some(pid) = myproc() else { return RLIM_INFINITY };
+    let table = PROC_TABLE.lock();
+    table
+        .find_ref(pid)
+        .map(|proc| proc.rlimits[resource as usize].rlim_cur)
+        .unwrap_or(RLIM_INFINITY)
+}
+
+/// Soft `RLIMIT_NOFILE` as a descriptor bound
+pub fn nofile_bound(limits: &[Rlimit; RLIM_NLIMITS]) -> usize {
+    usize::try_from(limits[RLIMIT_NOFILE as usize].rlim_cur)
+        .unwrap_or(usize::MAX)
+        .min(NR_OPEN)
+}
+
+/// What the CPU time limit calls for after a process has run `ticks`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuLimit {
+    Within,
+    /// Past the soft limit: SIGXCPU, repeated once a second
+    Soft,
+    /// Reached the hard limit: SIGKILL
+    Hard,
+}
+
+/// Check `RLIMIT_CPU` (in seconds) against CPU time in timer ticks
+pub fn cpu_limit(ticks: u64, limit: Rlimit, ticks_per_sec: u64) -> CpuLimit {
+    // Only act as each whole second of CPU time is completed
+    if ticks == 0 || ticks % ticks_per_sec != 0 {
+        return CpuLimit::Within;
+    }
+    let secs = ticks / ticks_per_sec;
+    if secs >= limit.rlim_max {
+        CpuLimit::Hard
+    } else if secs >= limit.rlim_cur {
+        CpuLimit::Soft
+    } else {
+        CpuLimit::Within
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::posix::{RLIMIT_CPU, RLIMIT_AS};
+
+    #[test]
+    fn test_validate() {
+        let old = Rlimit::new(1024, 4096);
+        assert_eq!(validate(RLIMIT_AS, old, Rlimit::new(10, 4096), false), Ok(()));
+        assert_eq!(validate(RLIMIT_AS, old, Rlimit::new(10, 5), false), Ok(()));
+        assert_eq!(validate(RLIMIT_AS, old, Rlimit::new(10, 5000), false), Err(RlimitError::NotPermitted));
+        assert_eq!(validate(RLIMIT_AS, old, Rlimit::new(10, 5000), true), Ok(()));
+        assert_eq!(validate(RLIMIT_AS, old, Rlimit::new(11, 10), true), Err(RlimitError::Invalid));
+        assert_eq!(validate(16, old, old, true), Err(RlimitError::Invalid));
+        assert_eq!(validate(-1, old, old, true), Err(RlimitError::Invalid));
+
+        let huge = Rlimit::new(1024, NR_OPEN as u64 + 1);
+        assert_eq!(validate(RLIMIT_NOFILE, old, huge, true), Err(RlimitError::NotPermitted));
+        assert_eq!(validate(RLIMIT_AS, old, huge, true), Ok(()));
+    }
+
+    #[test]
+    fn test_defaults() {
+        let limits = defaults();
+        assert_eq!(nofile_bound(&limits), 1024);
+        assert_eq!(limits[RLIMIT_CORE as usize].rlim_cur, 0);
+        assert_eq!(limits[RLIMIT_CPU as usize].rlim_cur, RLIM_INFINITY);
+    }
+
+    #[test]
+    fn test_cpu_limit() {
+        let limit = Rlimit::new(2, 4);
+        assert_eq!(cpu_limit(150, limit, 100), CpuLimit::Within);
+        assert_eq!(cpu_limit(100, limit, 100), CpuLimit::Within);
+        assert_eq!(cpu_limit(200, limit, 100), CpuLimit::Soft);
+        assert_eq!(cpu_limit(250, limit, 100), CpuLimit::Within);
+        assert_eq!(cpu_limit(300, limit, 100), CpuLimit::Soft);
+        assert_eq!(cpu_limit(400, limit, 100), CpuLimit::Hard);
+        assert_eq!(cpu_limit(400, Rlimit::new(RLIM_INFINITY, RLIM_INFINITY), 100), CpuLimit::Within);
+    }
+}
@@ -51,8 +51,9 @@ pub mod process_tests {
             test_assert_eq!(proc.state, crate::process::ProcState::Unused);
         }
 
-        // Check that PID counter starts at 1
-        test_assert_eq!(table.next_pid, 1);
+        // The table starts empty and grows on demand
+        test_assert_eq!(table.slots(), 0);
+        test_assert_eq!(table.pid_max(), crate::process::pid::PID_MAX_DEFAULT);
 
         Ok(())
     }
@@ -93,31 +94,33 @@ pub mod process_tests {
     pub fn test_process_table_capacity() -> TestResult {
         let mut table = crate::process::ProcTable::new();
 
-        // Allocate all processes
+        // The table grows well past its old fixed size of 64
         let mut allocated = Vec::new();
-        for _ in 0..crate::process::NPROC {
+        for _ in 0..128 {
             let proc = table.alloc();
             test_assert!(proc.is_some(), "Process allocation should succeed");
             allocated.push(proc.unwrap().pid);
         }
+        test_assert_eq!(table.slots(), 128);
 
-        // Next allocation should fail
-        let overflow_proc = table.alloc();
-        test_assert!(overflow_proc.is_none(), "Process allocation should fail when table is full");
+        // Shrink the PID space and use it up
+        let pid_max = crate::process::pid::RESERVED_PIDS + 1;
+        test_assert!(table.set_pid_max(pid_max).is_ok());
+        while let Some(proc) = table.alloc() {
+            allocated.push(proc.pid);
+        }
+        test_assert_eq!(allocated.len(), (pid_max - 1) as usize);
 
-        // Free one process
-        let freed_pid = allocated[0];
+        // A freed PID sits out the reuse delay, and its slot is kept
+        let freed_pid = allocated.pop().unwrap();
         table.free(freed_pid);
-
-        // Now allocation should succeed again
-        let new_proc = table.alloc();
-        test_assert!(new_proc.is_some(), "Process allocation should succeed after freeing");
+        test_assert!(table.alloc().is_none(), "A just-freed PID should not be reused");
+        test_assert_eq!(table.slots(), (pid_max - 1) as usize);
 
         // Clean up
-        for pid in allocated.iter().skip(1) {
-            table.free(*pid);
+        for pid in allocated {
+            table.free(pid);
         }
-        table.free(new_proc.unwrap().pid);
 
         Ok(())
     }
@@ -153,23 +156,27 @@ pub mod process_tests {
         let mut table = crate::process::ProcTable::new();
         let proc = table.alloc().unwrap();
 
+        let mut ofile = proc.ofile.lock();
+
         // Initially all FDs should be None
-        for i in 0..crate::process::NOFILE {
-            test_assert!(proc.ofile[i].is_none(), alloc::format!("FD {} should be None initially", i));
-        }
+        test_assert_eq!(ofile.count(), 0);
 
-        // Allocate some FDs
-        proc.ofile[0] = Some(5);
-        proc.ofile[3] = Some(10);
-        proc.ofile[15] = Some(20);
+        // Allocate some FDs, including past the initial table size
+        test_assert_eq!(ofile.alloc(0, 5, 1024), Some(0));
+        test_assert!(ofile.install(3, 10, 1024).is_ok());
+        test_assert!(ofile.install(100, 20, 1024).is_ok());
 
-        test_assert_eq!(proc.ofile[0], Some(5));
-        test_assert_eq!(proc.ofile[3], Some(10));
-        test_assert_eq!(proc.ofile[15], Some(20));
+        test_assert_eq!(ofile.get(0), Some(5));
+        test_assert_eq!(ofile.get(3), Some(10));
+        test_assert_eq!(ofile.get(100), Some(20));
 
         // Other FDs should still be None
-        test_assert!(proc.ofile[1].is_none());
-        test_assert!(proc.ofile[7].is_none());
+        test_assert!(ofile.get(1).is_none());
+        test_assert!(ofile.get(7).is_none());
+
+        // RLIMIT_NOFILE bounds new descriptors
+        test_assert!(ofile.install(2048, 30, 1024).is_err());
+        drop(ofile);
 
         // Clean up
         table.free(proc.pid);
@@ -314,11 +321,11 @@ pub mod process_tests {
     pub fn test_process_rlimits_init() -> TestResult {
         let proc = crate::process::Proc::new();
 
-        // Check that resource limits are initialized to zero
-        for rlimit in &proc.rlimits {
-            test_assert_eq!(rlimit.rlim_cur, 0);
-            test_assert_eq!(rlimit.rlim_max, 0);
-        }
+        // Resource limits start at the system defaults
+        let nofile = proc.rlimits[crate::posix::RLIMIT_NOFILE as usize];
+        test_assert_eq!(nofile.rlim_cur, 1024);
+        test_assert_eq!(nofile.rlim_max, 65536);
+        test_assert_eq!(proc.rlimits[crate::posix::RLIMIT_CORE as usize].rlim_cur, 0);
 
         Ok(())
     }
@@ -513,3 +520,151 @@ pub mod process_tests {
 // Memory management tests
 // ============================================================================
 
+
+// ============================================================================
+// Exec tests
+// ============================================================================
+
+#[cfg(feature = "kernel_tests")]
+pub mod exec_tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use crate::{test_assert_eq, test_assert};
+    use crate::tests::TestResult;
+    use crate::process::exec::exec_as;
+    use crate::process::PROC_TABLE;
+    use crate::process::elf::{EI_OSABI, ELFOSABI_LINUX};
+    use crate::subsystems::mm::vm::{copyin, copyinstr, PageTable};
+    use crate::process::Personality;
+
+    const WORD: usize = core::mem::size_of::<usize>();
+
+    /// A static ELF64 for this architecture with one empty PT_LOAD segment
+    fn minimal_elf() -> Vec<u8> {
+        let entry: u64 = 0x400000;
+        let mut elf = vec![0u8; 4096];
+        elf[0..4].copy_from_slice(&[0x7f, b'E', b'L', b'F']);
+        elf[4] = 2; // ELFCLASS64
+        elf[5] = 1; // ELFDATA2LSB
+        elf[6] = 1; // EV_CURRENT
+        elf[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+        #[cfg(target_arch = "riscv64")]
+        elf[18..20].copy_from_slice(&243u16.to_le_bytes());
+        #[cfg(target_arch = "aarch64")]
+        elf[18..20].copy_from_slice(&183u16.to_le_bytes());
+        #[cfg(target_arch = "x86_64")]
+        elf[18..20].copy_from_slice(&62u16.to_le_bytes());
+        elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+        elf[24..32].copy_from_slice(&entry.to_le_bytes());
+        elf[32..40].copy_from_slice(&64u64.to_le_bytes()); // e_phoff
+        elf[52..54].copy_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        elf[54..56].copy_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        elf[56..58].copy_from_slice(&1u16.to_le_bytes()); // e_phnum
+
+        let ph = 64;
+        elf[ph..ph + 4].copy_from_slice(&1u32.to_le_bytes()); // PT_LOAD
+        elf[ph + 4..ph + 8].copy_from_slice(&5u32.to_le_bytes()); // PF_R | PF_X
+        elf[ph + 16..ph + 24].copy_from_slice(&entry.to_le_bytes());
+        elf[ph + 40..ph + 48].copy_from_slice(&4096u64.to_le_bytes()); // p_memsz
+        elf[ph + 48..ph + 56].copy_from_slice(&4096u64.to_le_bytes()); // p_align
+        elf
+    }
+
+    fn word(pagetable: *mut PageTable, va: usize) -> Option<usize> {
+        let mut bytes = [0u8; WORD];
+        unsafe { copyin(pagetable, bytes.as_mut_ptr(), va, WORD) }.ok()?;
+        Some(usize::from_le_bytes(bytes))
+    }
+
+    fn string(pagetable: *mut PageTable, va: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0u8; 256];
+        let len = unsafe { copyinstr(pagetable, va, buf.as_mut_ptr(), buf.len()) }.ok()?;
+        buf.truncate(len);
+        Some(buf)
+    }
+
+    /// Test the new image finds argc at `[sp]`, then argv, envp and auxv
+    /// after it, as `_start` reads them
+    pub fn test_exec_initial_stack() -> TestResult {
+        let Some(pid) = PROC_TABLE.lock().alloc().map(|proc| proc.pid) else { return Ok(()) };
+        let argv: [&[u8]; 2] = [b"prog", b"-v"];
+        let envp: [&[u8]; 1] = [b"HOME=/"];
+        let result = exec_as(pid, &minimal_elf(), None, &argv, &envp, Some(b"/bin/prog"));
+
+        let check = || -> TestResult {
+            test_assert!(result.is_ok());
+            let image = PROC_TABLE.lock().find_ref(pid).map(|proc| (proc.pagetable, proc.trapframe));
+            test_assert!(image.is_some_and(|(pagetable, tf)| !pagetable.is_null() && !tf.is_null()));
+            let (pagetable, tf) = image.unwrap_or((core::ptr::null_mut(), core::ptr::null_mut()));
+            #[cfg(target_arch = "x86_64")]
+            let sp = unsafe { (*tf).rsp };
+            #[cfg(not(target_arch = "x86_64"))]
+            let sp = unsafe { (*tf).sp };
+            test_assert_eq!(sp % 16, 0);
+
+            test_assert!(word(pagetable, sp) == Some(2));
+            let argv_at = sp + WORD;
+            test_assert!(word(pagetable, argv_at).and_then(|p| string(pagetable, p)) == Some(b"prog".to_vec()));
+            test_assert!(word(pagetable, argv_at + WORD).and_then(|p| string(pagetable, p)) == Some(b"-v".to_vec()));
+            test_assert!(word(pagetable, argv_at + 2 * WORD) == Some(0));
+
+            let envp_at = argv_at + 3 * WORD;
+            test_assert!(word(pagetable, envp_at).and_then(|p| string(pagetable, p)) == Some(b"HOME=/".to_vec()));
+            test_assert!(word(pagetable, envp_at + WORD) == Some(0));
+
+            // The auxiliary vector follows, ending in AT_NULL
+            const AT_PAGESZ: usize = 6;
+            const AT_EXECFN: usize = 31;
+            let mut auxv = Vec::new();
+            let mut at = envp_at + 2 * WORD;
+            while let (Some(ty), Some(val)) = (word(pagetable, at), word(pagetable, at + WORD)) {
+                if ty == 0 {
+                    break;
+                }
+                auxv.push((ty, val));
+                at += 2 * WORD;
+            }
+            test_assert!(auxv.contains(&(AT_PAGESZ, crate::subsystems::mm::vm::PAGE_SIZE)));
+            let execfn = auxv.iter().find(|&&(ty, _)| ty == AT_EXECFN).map(|&(_, val)| val);
+            test_assert!(execfn.and_then(|p| string(pagetable, p)) == Some(b"/bin/prog".to_vec()));
+            Ok(())
+        };
+        let outcome = check();
+        PROC_TABLE.lock().free(pid);
+        outcome
+    }
+    /// Test a static Linux binary gets the Linux personality and the
+    /// stack libc's `_start` expects, with no `rtld_fini` in `rdx`
+    pub fn test_exec_linux_static() -> TestResult {
+        let Some(pid) = PROC_TABLE.lock().alloc().map(|proc| proc.pid) else { return Ok(()) };
+        let mut elf = minimal_elf();
+        elf[EI_OSABI] = ELFOSABI_LINUX;
+        let argv: [&[u8]; 1] = [b"busybox"];
+        let result = exec_as(pid, &elf, None, &argv, &[], None);
+
+        let check = || -> TestResult {
+            test_assert!(result.is_ok());
+            let image = PROC_TABLE.lock().find_ref(pid).map(|proc| (proc.personality, proc.pagetable, proc.trapframe));
+            test_assert!(image.is_some_and(|(_, pagetable, tf)| !pagetable.is_null() && !tf.is_null()));
+            let (personality, pagetable, tf) = image.unwrap_or((Personality::Native, core::ptr::null_mut(), core::ptr::null_mut()));
+            test_assert_eq!(personality, Personality::Linux);
+            #[cfg(target_arch = "x86_64")]
+            let sp = unsafe {
+                test_assert_eq!((*tf).rdx, 0);
+                (*tf).rsp
+            };
+            #[cfg(not(target_arch = "x86_64"))]
+            let sp = unsafe { (*tf).sp };
+            test_assert_eq!(sp % 16, 0);
+            test_assert!(word(pagetable, sp) == Some(1));
+            test_assert!(word(pagetable, sp + WORD).and_then(|p| string(pagetable, p)) == Some(b"busybox".to_vec()));
+            test_assert!(word(pagetable, sp + 2 * WORD) == Some(0));
+            // Empty environment: envp is just its NULL terminator
+            test_assert!(word(pagetable, sp + 3 * WORD) == Some(0));
+            Ok(())
+        };
+        let outcome = check();
+        PROC_TABLE.lock().free(pid);
+        outcome
+    }
+}
@@ -853,6 +853,8 @@ pub fn thread_exit(retval: *mut u8) -> ! {
         let mut table = thread_table();
         if let Some(thread) = table.find_thread(tid) {
             thread.return_value = retval;
+            crate::security::seccomp::exit_thread(thread.pid as u64, tid as u64);
+            crate::security::keys::exit_thread(thread.pid as u64, tid as u64);
 
             // Handle CLONE_CHILD_CLEARTID: clear the TID pointer on exit
             if thread.child_tid_ptr != 0 {
@@ -1054,6 +1056,10 @@ pub fn schedule() {
                 // TODO: Add TLS setup for other architectures
             }
 
+            // `syscall` does not switch stacks by itself
+            #[cfg(target_arch = "x86_64")]
+            crate::trap::set_syscall_stack(thread.kstack);
+
             // Handle real-time scheduler context switch
             if let Some(rt_scheduler) = crate::subsystems::scheduler::get_rt_scheduler() {
                 rt_scheduler.handle_context_switch(tid, current_time);
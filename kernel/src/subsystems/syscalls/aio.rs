@@ -28,7 +28,8 @@ use core::sync::atomic::{AtomicU64, Ordering};
 
 use super::common::{SyscallError, SyscallResult, extract_args};
 use crate::fs::file::FILE_TABLE;
-use crate::process::{myproc, NOFILE};
+use crate::process::myproc;
+use crate::process::fdtable::NR_OPEN;
 use crate::posix::{off_t, aiocb, AioOffsetT, aio_reqprio_t, aio_sigevent_t, AIO_CANCELED, AIO_NOTCANCELED, AIO_ALLDONE, LIO_READ, LIO_WRITE, SIGEV_SIGNAL};
 use crate::subsystems::sync::Mutex;
 use crate::subsystems::mm::vm;
@@ -366,22 +367,18 @@ fn execute_aio_write(aiocb_ptr: *mut aiocb, file_idx: usize) -> (isize, i32) {
     }
 }
 /// Execute asynchronous fsync operation
-fn execute_aio_fsync(aiocb_ptr: *mut aiocb, file_idx: usize) -> (isize, i32) {
-    let mode = unsafe {
-        let aiocb = &*aiocb_ptr;
-        aiocb.aio_fsync_mode
-    };
-    
-    // Get file reference and perform operations while holding the table lock
-    let mut file_table = FILE_TABLE.lock();
-    let file = match file_table.get_mut(file_idx) {
-        Some(f) => f,
-        None => return (-1, crate::reliability::errno::EBADF),
-    };
+fn execute_aio_fsync(_aiocb_ptr: *mut aiocb, file_idx: usize) -> (isize, i32) {
+    if FILE_TABLE.lock().get(file_idx).is_none() {
+        return (-1, crate::reliability::errno::EBADF);
+    }
     
-    // Perform fsync operation (fix: assume fsync returns 0 on success, -1 on error)
-    // Note: We're assuming fsync returns 0 on success for now
-    (0, 0)
+    // O_SYNC and O_DSYNC are the same here: inodes have no metadata that
+    // is written back separately from their data
+    match crate::fs::file::file_fsync(file_idx) {
+        Ok(()) => (0, 0),
+        Err(crate::vfs::VfsError::IoError) => (-1, crate::reliability::errno::EIO),
+        Err(_) => (-1, crate::reliability::errno::EINVAL),
+    }
 }
 
 /// Send completion notification if requested
@@ -659,7 +656,7 @@ fn queue_aio_operation(aiocb_ptr: *mut aiocb, operation: AioOperation, pid: usiz
     };
     
     // Validate file descriptor
-    if fd < 0 || (fd as usize) >= NOFILE {
+    if fd < 0 || (fd as usize) >= NR_OPEN {
         return Err(crate::reliability::errno::EBADF);
     }
     
@@ -668,7 +665,7 @@ fn queue_aio_operation(aiocb_ptr: *mut aiocb, operation: AioOperation, pid: usiz
         let proc_table = crate::process::PROC_TABLE.lock();
         let proc = proc_table.find_ref(pid).ok_or(crate::reliability::errno::ESRCH)?;
         
-        proc.ofile[fd as usize].ok_or(crate::reliability::errno::EBADF)?
+        proc.ofile.lock().get(fd).ok_or(crate::reliability::errno::EBADF)?
     };
     
     // Generate operation ID
@@ -938,7 +935,7 @@ fn queue_aio_operation_internal(aiocb_ptr: *mut aiocb) -> Result<usize, i32> {
     };
     
     // Validate file descriptor
-    if fd < 0 || (fd as usize) >= NOFILE {
+    if fd < 0 || (fd as usize) >= NR_OPEN {
         return Err(crate::reliability::errno::EBADF);
     }
     
@@ -947,7 +944,7 @@ fn queue_aio_operation_internal(aiocb_ptr: *mut aiocb) -> Result<usize, i32> {
         let proc_table = crate::process::PROC_TABLE.lock();
         let proc = proc_table.find_ref(pid).ok_or(crate::reliability::errno::ESRCH)?;
         
-        proc.ofile[fd as usize].ok_or(crate::reliability::errno::EBADF)?
+        proc.ofile.lock().get(fd).ok_or(crate::reliability::errno::EBADF)?
     };
     
     // Determine operation type from aiocb
@@ -33,6 +33,11 @@ pub enum SyscallError {
     ConnectionReset,         // ECONNRESET
     BrokenPipe,              // EPIPE
     TimedOut,                // ETIMEDOUT
+    KeyNotAvailable,         // ENOKEY
+    KeyExpired,              // EKEYEXPIRED
+    KeyRevoked,              // EKEYREVOKED
+    KeyRejected,             // EKEYREJECTED
+    QuotaExceeded,           // EDQUOT
 }
 
 pub type SyscallResult = Result<u64, SyscallError>;
@@ -72,6 +77,11 @@ pub fn result_to_raw(result: SyscallResult) -> u64 {
                 SyscallError::ConnectionReset => u64::MAX - 25,
                 SyscallError::BrokenPipe => u64::MAX - 26,
                 SyscallError::TimedOut => u64::MAX - 27,
+                SyscallError::KeyNotAvailable => u64::MAX - 28,
+                SyscallError::KeyExpired => u64::MAX - 29,
+                SyscallError::KeyRevoked => u64::MAX - 30,
+                SyscallError::KeyRejected => u64::MAX - 31,
+                SyscallError::QuotaExceeded => u64::MAX - 32,
             }
         }
     }
@@ -117,6 +127,11 @@ pub fn syscall_error_to_errno(error: SyscallError) -> i32 {
         SyscallError::ConnectionReset => ECONNRESET,
         SyscallError::BrokenPipe => EPIPE,
         SyscallError::TimedOut => ETIMEDOUT,
+        SyscallError::KeyNotAvailable => ENOKEY,
+        SyscallError::KeyExpired => EKEYEXPIRED,
+        SyscallError::KeyRevoked => EKEYREVOKED,
+        SyscallError::KeyRejected => EKEYREJECTED,
+        SyscallError::QuotaExceeded => EDQUOT,
     }
 }
 
@@ -146,6 +161,48 @@ impl From<crate::process::exec::ExecError> for SyscallError {
 }
 
 
+/// Convert ForkError to SyscallError
+impl From<crate::process::ForkError> for SyscallError {
+    fn from(err: crate::process::ForkError) -> Self {
+        use crate::process::ForkError;
+        match err {
+            ForkError::NoProcess => SyscallError::NotFound,
+            ForkError::LimitReached => SyscallError::WouldBlock,
+            ForkError::NoMemory => SyscallError::OutOfMemory,
+        }
+    }
+}
+
+
+/// Convert SocketError to SyscallError
+#[cfg(feature = "net_stack")]
+impl From<crate::net::socket::SocketError> for SyscallError {
+    fn from(err: crate::net::socket::SocketError) -> Self {
+        use crate::net::socket::SocketError;
+        match err {
+            SocketError::InvalidFd => SyscallError::BadFileDescriptor,
+            SocketError::InvalidAddress => SyscallError::InvalidArgument,
+            // No EADDRINUSE in SyscallError
+            SocketError::AddressInUse => SyscallError::InvalidArgument,
+            SocketError::NotConnected => SyscallError::InvalidArgument,
+            SocketError::NotBound => SyscallError::InvalidArgument,
+            SocketError::ConnectionRefused => SyscallError::ConnectionRefused,
+            SocketError::ConnectionTimeout => SyscallError::TimedOut,
+            SocketError::ConnectionReset => SyscallError::ConnectionReset,
+            SocketError::WouldBlock => SyscallError::WouldBlock,
+            SocketError::InvalidValue => SyscallError::InvalidArgument,
+            SocketError::NoBufferSpace => SyscallError::NoBufferSpace,
+            SocketError::NotSupported => SyscallError::NotSupported,
+            SocketError::PermissionDenied => SyscallError::PermissionDenied,
+            // No EINPROGRESS; the handshake continues and the caller polls
+            SocketError::InProgress => SyscallError::WouldBlock,
+            SocketError::AlreadyConnected => SyscallError::InvalidArgument,
+            SocketError::BrokenPipe => SyscallError::BrokenPipe,
+            SocketError::NetworkUnreachable => SyscallError::NotFound,
+        }
+    }
+}
+
 /// Convert VfsError to SyscallError
 impl From<crate::vfs::error::VfsError> for SyscallError {
     fn from(err: crate::vfs::error::VfsError) -> Self {
@@ -165,6 +222,76 @@ impl From<crate::vfs::error::VfsError> for SyscallError {
             VfsError::IoError => SyscallError::IoError,
             VfsError::NotSupported => SyscallError::NotSupported,
             VfsError::InvalidOperation => SyscallError::InvalidArgument,
+            // No ENODATA in SyscallError; a missing attribute is closest to ENOENT
+            VfsError::NoData => SyscallError::NotFound,
+            VfsError::CrossDevice => SyscallError::CrossDeviceLink,
+            VfsError::NameTooLong => SyscallError::NameTooLong,
+            VfsError::TooManyLinks => SyscallError::TooManySymlinks,
+            VfsError::NoKey => SyscallError::KeyNotAvailable,
+        }
+    }
+}
+/// Convert PtraceError to SyscallError
+impl From<crate::process::ptrace::PtraceError> for SyscallError {
+    fn from(err: crate::process::ptrace::PtraceError) -> Self {
+        use crate::process::ptrace::PtraceError;
+        match err {
+            // No ESRCH in SyscallError
+            PtraceError::NoProcess => SyscallError::NotFound,
+            PtraceError::NotPermitted => SyscallError::PermissionDenied,
+            PtraceError::Invalid => SyscallError::InvalidArgument,
+            PtraceError::Fault => SyscallError::BadAddress,
+            PtraceError::Io => SyscallError::IoError,
+        }
+    }
+}
+/// Convert KeyError to SyscallError
+impl From<crate::security::keys::KeyError> for SyscallError {
+    fn from(err: crate::security::keys::KeyError) -> Self {
+        use crate::security::keys::KeyError;
+        match err {
+            KeyError::NotFound => SyscallError::KeyNotAvailable,
+            KeyError::Expired => SyscallError::KeyExpired,
+            KeyError::Revoked => SyscallError::KeyRevoked,
+            KeyError::Rejected => SyscallError::KeyRejected,
+            KeyError::AccessDenied | KeyError::NotPermitted => SyscallError::PermissionDenied,
+            KeyError::InvalidArgument => SyscallError::InvalidArgument,
+            KeyError::NotSupported => SyscallError::NotSupported,
+            KeyError::NotKeyring => SyscallError::NotADirectory,
+            KeyError::NotLinked => SyscallError::NotFound,
+            KeyError::Cycle => SyscallError::DeadlockWouldOccur,
+            KeyError::QuotaExceeded => SyscallError::QuotaExceeded,
+            KeyError::Fault => SyscallError::BadAddress,
+        }
+    }
+}
+/// Convert SeccompError to SyscallError
+impl From<crate::security::seccomp::SeccompError> for SyscallError {
+    fn from(err: crate::security::seccomp::SeccompError) -> Self {
+        use crate::security::seccomp::SeccompError;
+        match err {
+            SeccompError::InvalidArgument => SyscallError::InvalidArgument,
+            SeccompError::PermissionDenied => SyscallError::PermissionDenied,
+            SeccompError::TooLarge => SyscallError::OutOfMemory,
+            // No EBUSY in SyscallError
+            SeccompError::Busy => SyscallError::WouldBlock,
+            // No ESRCH in SyscallError; TSYNC_ESRCH asks for it instead of the thread ID
+            SeccompError::Unsynchronized(_) => SyscallError::NotFound,
         }
     }
-}
\ No newline at end of file
+}
+/// Convert SwapError to SyscallError
+impl From<crate::subsystems::mm::swap::SwapError> for SyscallError {
+    fn from(err: crate::subsystems::mm::swap::SwapError) -> Self {
+        use crate::subsystems::mm::swap::SwapError;
+        match err {
+            SwapError::NoSpace => SyscallError::NoSpaceLeft,
+            SwapError::Io => SyscallError::IoError,
+            SwapError::NoMemory => SyscallError::OutOfMemory,
+            // No EBUSY in SyscallError
+            SwapError::Busy => SyscallError::WouldBlock,
+            SwapError::NotFound => SyscallError::NotFound,
+            SwapError::Invalid => SyscallError::InvalidArgument,
+        }
+    }
+}
@@ -169,37 +169,31 @@ pub fn sys_eventfd2(args: &[u64]) -> SyscallResult {
     let instance_idx = alloc_eventfd_instance(initval, flags)
         .ok_or(SyscallError::OutOfMemory)?;
     
-    // Allocate file descriptor
-    let pid = crate::subsystems::process::manager::myproc()
-        .ok_or(SyscallError::InvalidArgument)?;
-    
-    let mut proc_table = crate::subsystems::process::manager::PROC_TABLE.lock();
-    if let Some(proc) = proc_table.find(pid) {
-        // Find free file descriptor
-        for (fd, file) in proc.ofile.iter_mut().enumerate() {
-            if file.is_none() {
-                *file = Some(crate::subsystems::fs::file::File {
-                    ftype: crate::subsystems::fs::file::FileType::EventFd,
-                    readable: true,
-                    writable: true,
-                    eventfd_instance: Some(instance_idx),
-                    ..Default::default()
-                });
-                
-                // Apply flags
-                if (flags & flags::EFD_NONBLOCK) != 0 {
-                    file.as_mut().unwrap().nonblock = true;
-                }
-                if (flags & flags::EFD_CLOEXEC) != 0 {
-                    file.as_mut().unwrap().close_on_exec = true;
-                }
-                
-                return Ok(fd as u64);
-            }
+    // Create the file and give it a descriptor
+    let file_idx = crate::fs::file::file_alloc().ok_or(SyscallError::OutOfMemory)?;
+    {
+        let mut table = crate::fs::file::FILE_TABLE.lock();
+        let file = table.get_mut(file_idx).ok_or(SyscallError::OutOfMemory)?;
+        file.ftype = crate::subsystems::fs::file::FileType::EventFd;
+        file.readable = true;
+        file.writable = true;
+        file.eventfd_instance = Some(instance_idx);
+
+        // Apply flags
+        if (flags & flags::EFD_NONBLOCK) != 0 {
+            file.status_flags |= crate::posix::O_NONBLOCK;
+        }
+        if (flags & flags::EFD_CLOEXEC) != 0 {
+            file.status_flags |= crate::posix::O_CLOEXEC;
+        }
+    }
+
+    match crate::process::fdalloc(file_idx) {
+        Some(fd) => Ok(fd as u64),
+        None => {
+            crate::fs::file::file_close(file_idx);
+            Err(SyscallError::TooManyOpenFiles)
         }
-        Err(SyscallError::TooManyFiles)
-    } else {
-        Err(SyscallError::InvalidArgument)
     }
 }
 
@@ -30,6 +30,21 @@ pub fn dispatch(syscall_number: u32, args: &[u64]) -> Result<u64, KernelError> {
         0x7011 => handlers::handle_lstat(args),     // lstat
         0x7012 => handlers::handle_access(args),    // access
         0x7013 => handlers::handle_readdir(args),   // readdir/getdents
+        0x7014 => handlers::handle_chroot(args),    // chroot
+        0x7015 => handlers::handle_mkdirat(args),   // mkdirat
+        0x7016 => handlers::handle_unlinkat(args),  // unlinkat
+        0x7017 => handlers::handle_renameat2(args), // renameat2
+        0x7018 => handlers::handle_linkat(args),    // linkat
+        0x7019 => handlers::handle_symlinkat(args), // symlinkat
+        0x701A => handlers::handle_readlinkat(args), // readlinkat
+        0x701B => handlers::handle_fchmodat(args),  // fchmodat
+        0x701C => handlers::handle_fchownat(args),  // fchownat
+        0x701D => handlers::handle_fstatat(args),   // fstatat
+        0x701E => handlers::handle_faccessat(args), // faccessat
+        0x701F => handlers::handle_fsync(args),     // fsync
+        0x7020 => handlers::handle_fdatasync(args), // fdatasync
+        0x7021 => handlers::handle_sync(args),      // sync
+        0x7022 => handlers::handle_ftruncate(args), // ftruncate
         _ => Err(KernelError::InvalidSyscall),
     }
 }
@@ -1,11 +1,13 @@
 //! File I/O related system calls
 //!
-//! Implements read, write, open, close, fstat, lseek, dup, dup2, fcntl, poll, select
+//! Implements read, write, open, openat, close, fstat, stat, lstat, lseek, ioctl, dup, dup2, fcntl, poll, select
 
 use crate::fs::file::{FILE_TABLE, FileType, file_alloc, file_close, file_read, file_write, file_stat, file_lseek, file_unsubscribe};
 use crate::syscalls::common::{SyscallError, SyscallResult, extract_args};
+use crate::posix::{AT_FDCWD, O_CREAT, O_DIRECTORY, O_EXCL, O_NOFOLLOW};
+use crate::vfs::{FileMode, LookupFlags, VfsFile};
 use crate::subsystems::sync::Mutex;
-use alloc::string::ToString;
+use crate::subsystems::mm::uaccess::UserSlice;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 /// 全局文件I/O统计
@@ -69,6 +71,31 @@ pub fn get_io_stats() -> (usize, usize, usize, usize, usize, usize) {
     IO_STATS.lock().get_stats()
 }
 
+/// Largest transfer one read or write moves through its kernel buffer;
+/// a longer one comes back short
+const MAX_IO: usize = 1 << 20;
+
+/// Read from a file into user memory, through a kernel buffer
+fn read_to_user(file_idx: usize, buf: UserSlice) -> isize {
+    if !crate::subsystems::mm::uaccess::access_ok(buf.addr(), buf.len()) {
+        return crate::reliability::errno::errno_neg(crate::reliability::errno::EFAULT);
+    }
+    let mut data = alloc::vec![0u8; buf.len().min(MAX_IO)];
+    let result = file_read(file_idx, &mut data);
+    if result > 0 && buf.write(&data[..result as usize]).is_err() {
+        return crate::reliability::errno::errno_neg(crate::reliability::errno::EFAULT);
+    }
+    result
+}
+
+/// Write user memory to a file, through a kernel buffer
+fn write_from_user(file_idx: usize, buf: UserSlice) -> isize {
+    match buf.sub(0, MAX_IO).read_to_vec() {
+        Ok(data) => file_write(file_idx, &data),
+        Err(_) => crate::reliability::errno::errno_neg(crate::reliability::errno::EFAULT),
+    }
+}
+
 /// Read from a file descriptor
 pub fn sys_read(fd: i32, buf: *mut u8, len: usize) -> isize {
     if fd < 0 {
@@ -80,11 +107,7 @@ pub fn sys_read(fd: i32, buf: *mut u8, len: usize) -> isize {
         None => return crate::reliability::errno::errno_neg(crate::reliability::errno::EBADF),
     };
     
-    let user_buf = unsafe {
-        core::slice::from_raw_parts_mut(buf, len)
-    };
-    
-    let result = file_read(file_idx, user_buf);
+    let result = read_to_user(file_idx, UserSlice::new(buf as usize, len));
     
     // Record statistics
     if result >= 0 {
@@ -105,11 +128,7 @@ pub fn sys_write(fd: i32, buf: *const u8, len: usize) -> isize {
         None => return crate::reliability::errno::errno_neg(crate::reliability::errno::EBADF),
     };
     
-    let user_buf = unsafe {
-        core::slice::from_raw_parts(buf, len)
-    };
-    
-    let result = file_write(file_idx, user_buf);
+    let result = write_from_user(file_idx, UserSlice::new(buf as usize, len));
     
     // Record statistics
     if result >= 0 {
@@ -151,40 +170,10 @@ pub fn sys_open(path: *const u8, flags: i32, mode: u32) -> isize {
         Err(_) => return crate::reliability::errno::errno_neg(crate::reliability::errno::EINVAL),
     };
     
-    // Resolve absolute path
-    let abs_path = if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        // Get current working directory from process
-        let pid = match crate::process::myproc() {
-            Some(pid) => pid,
-            None => return crate::reliability::errno::errno_neg(crate::reliability::errno::EPERM),
-        };
-        
-        let proc_table = crate::process::manager::PROC_TABLE.lock();
-        let proc = match proc_table.find_ref(pid) {
-            Some(proc) => proc,
-            None => return crate::reliability::errno::errno_neg(crate::reliability::errno::EPERM),
-        };
-        
-        let cwd = proc.cwd_path.clone().unwrap_or_else(|| "/".to_string());
-        format!("{}/{}", cwd, path_str)
-    };
-    
-    // Check if we need to create the file
-    let vfs_file = if (flags & (crate::posix::O_CREAT as i32)) != 0 {
-        // Create or open the file
-        let file_mode = crate::vfs::FileMode::new(mode);
-        match crate::vfs::vfs().create(&abs_path, file_mode) {
-            Ok(file) => file,
-            Err(_) => return crate::reliability::errno::errno_neg(crate::reliability::errno::ENOENT),
-        }
-    } else {
-        // Open existing file
-        match crate::vfs::vfs().open(&abs_path, flags as u32) {
-            Ok(file) => file,
-            Err(_) => return crate::reliability::errno::errno_neg(crate::reliability::errno::ENOENT),
-        }
+    // Resolve against the process root and cwd, creating the file if asked
+    let vfs_file = match open_vfs(AT_FDCWD, path_str, flags, mode) {
+        Ok(file) => file,
+        Err(e) => return crate::syscalls::common::syscall_error_to_neg_errno(e),
     };
     
     // Allocate a file entry in FILE_TABLE
@@ -274,7 +263,15 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
         0x2004 => sys_lseek_impl(args),    // lseek
         0x2005 => sys_fstat_impl(args),    // fstat
         0x2006 => sys_stat_impl(args),    // stat
-        0x2007 => Err(SyscallError::NotSupported), // lstat - TODO: implement later
+        0x2007 => sys_lstat_impl(args),    // lstat
+        0x2008 => sys_openat_impl(args),   // openat
+        0x2009 => sys_ioctl_impl(args),    // ioctl
+        0x200A => sys_fcntl_impl(args),    // fcntl
+        0x200B => sys_dup_impl(args),      // dup
+        0x200C => sys_dup2_impl(args),     // dup2
+        0x200D => sys_dup3_impl(args),     // dup3
+        0x200E => sys_pipe_impl(args),     // pipe
+        0x200F => sys_pipe2_impl(args),    // pipe2
         _ => Err(SyscallError::InvalidSyscall),
     }
 }
@@ -282,58 +279,22 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
 /// Syscall implementation wrappers that return SyscallResult
 fn sys_open_impl(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 3)?;
-    let path_ptr = args[0] as *const u8;
-    let flags = args[1] as i32;
-    let mode = args[2] as u32;
-    
-    // Read path from user space
-    const MAX_PATH_LEN: usize = 4096;
-    let mut path_buf = [0u8; MAX_PATH_LEN];
+    openat(AT_FDCWD, args[0] as *const u8, args[1] as i32, args[2] as u32)
+}
 
-    let pagetable = match crate::process::myproc() {
-        Some(pid) => {
-            let mut table = crate::process::manager::PROC_TABLE.lock();
-            match table.find(pid) {
-                Some(proc) => proc.pagetable,
-                None => return Err(SyscallError::BadAddress),
-            }
-        }
-        None => return Err(SyscallError::BadAddress),
-    };
+/// Implementation of syscall 0x2008: openat
+fn sys_openat_impl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 4)?;
+    openat(args[0] as i32, args[1] as *const u8, args[2] as i32, args[3] as u32)
+}
 
-    let path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable,
-                                path_ptr as usize,
-                                path_buf.as_mut_ptr(),
-                                MAX_PATH_LEN)
-            .map_err(|_| SyscallError::BadAddress)?
-    };
-    
-    let path_str = core::str::from_utf8(&path_buf[..path_len])
+/// Open a path for the current process and return its new descriptor
+pub(crate) fn openat(dirfd: i32, path_ptr: *const u8, flags: i32, mode: u32) -> SyscallResult {
+    let path_buf = read_user_path(path_ptr)?;
+    let path_str = core::str::from_utf8(&path_buf)
         .map_err(|_| SyscallError::InvalidArgument)?;
-    
-    // Resolve absolute path
-    let abs_path = if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        // Get current working directory from process
-        let pid = crate::process::myproc().ok_or(SyscallError::IoError)?;
-        let proc_table = crate::process::manager::PROC_TABLE.lock();
-        let proc = proc_table.find_ref(pid).ok_or(SyscallError::IoError)?;
-        let cwd = proc.cwd_path.clone().unwrap_or_else(|| "/".to_string());
-        format!("{}/{}", cwd, path_str)
-    };
-    
-    // Check if we need to create the file
-    let vfs_file = if (flags & (crate::posix::O_CREAT as i32)) != 0 {
-        // Create or open the file
-        let file_mode = crate::vfs::FileMode::new(mode);
-        crate::vfs::vfs().create(&abs_path, file_mode)
-    } else {
-        // Open existing file
-        crate::vfs::vfs().open(&abs_path, flags as u32)
-    }
-    .map_err(|_| SyscallError::NotFound)?;
+
+    let vfs_file = open_vfs(dirfd, path_str, flags, mode)?;
     
     // Allocate a file entry in FILE_TABLE
     let file_idx = file_alloc().ok_or(SyscallError::IoError)?;
@@ -346,13 +307,50 @@ fn sys_open_impl(args: &[u64]) -> SyscallResult {
     file.writable = (flags & (crate::posix::O_WRONLY | crate::posix::O_RDWR) as i32) != 0;
     file.status_flags = flags;
     file.vfs_file = Some(vfs_file);
+    drop(table);
     
     // Allocate a file descriptor for the process
     let fd = crate::process::fdalloc(file_idx).ok_or(SyscallError::IoError)?;
     
+    IO_STATS.lock().record_open();
     Ok(fd as u64)
 }
 
+/// Resolve `path` from `dirfd` (or the cwd) and open it, honouring
+/// `O_CREAT`, `O_EXCL`, `O_NOFOLLOW` and `O_DIRECTORY`
+fn open_vfs(dirfd: i32, path: &str, flags: i32, mode: u32) -> Result<VfsFile, SyscallError> {
+    let ctx = crate::process::fs_context()?;
+    let dir = super::handlers::dirfd_loc(dirfd, path).map_err(|_| SyscallError::BadFileDescriptor)?;
+
+    let mut lookup = LookupFlags::FOLLOW;
+    if flags & O_NOFOLLOW != 0 {
+        lookup &= !LookupFlags::FOLLOW;
+    }
+    if flags & O_DIRECTORY != 0 {
+        lookup |= LookupFlags::DIRECTORY;
+    }
+    let lookup = LookupFlags(lookup);
+
+    let vfs = crate::vfs::vfs();
+    if flags & O_CREAT != 0 {
+        let umask = {
+            let pid = crate::process::myproc().ok_or(SyscallError::IoError)?;
+            let proc_table = crate::process::manager::PROC_TABLE.lock();
+            proc_table.find_ref(pid).map(|proc| proc.umask).unwrap_or(0)
+        };
+        let mode = FileMode::new(mode & 0o7777 & !umask);
+        Ok(vfs.create_at(&ctx, dir.as_ref(), path, mode, lookup, flags & O_EXCL != 0)?)
+    } else {
+        Ok(vfs.open_at(&ctx, dir.as_ref(), path, lookup, flags as u32)?)
+    }
+}
+
+/// Copy a NUL-terminated path in from the current process
+fn read_user_path(path_ptr: *const u8) -> Result<alloc::vec::Vec<u8>, SyscallError> {
+    const MAX_PATH_LEN: usize = 4096;
+    crate::subsystems::mm::uaccess::user_string(path_ptr as usize, MAX_PATH_LEN)
+}
+
 fn sys_read_impl(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 3)?;
     let fd = args[0] as i32;
@@ -368,12 +366,10 @@ fn sys_read_impl(args: &[u64]) -> SyscallResult {
         None => return Err(SyscallError::BadFileDescriptor),
     };
     
-    let user_buf = unsafe {
-        core::slice::from_raw_parts_mut(buf_ptr, count)
-    };
-    
-    let result = file_read(file_idx, user_buf);
-    if result < 0 {
+    let result = read_to_user(file_idx, UserSlice::new(buf_ptr as usize, count));
+    if result == crate::reliability::errno::errno_neg(crate::reliability::errno::EFAULT) {
+        Err(SyscallError::BadAddress)
+    } else if result < 0 {
         Err(SyscallError::IoError)
     } else {
         Ok(result as u64)
@@ -395,12 +391,10 @@ fn sys_write_impl(args: &[u64]) -> SyscallResult {
         None => return Err(SyscallError::BadFileDescriptor),
     };
     
-    let user_buf = unsafe {
-        core::slice::from_raw_parts(buf_ptr, count)
-    };
-    
-    let result = file_write(file_idx, user_buf);
-    if result < 0 {
+    let result = write_from_user(file_idx, UserSlice::new(buf_ptr as usize, count));
+    if result == crate::reliability::errno::errno_neg(crate::reliability::errno::EFAULT) {
+        Err(SyscallError::BadAddress)
+    } else if result < 0 {
         Err(SyscallError::IoError)
     } else {
         Ok(result as u64)
@@ -409,8 +403,11 @@ fn sys_write_impl(args: &[u64]) -> SyscallResult {
 
 fn sys_close_impl(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 1)?;
-    let fd = args[0] as i32;
-    
+    close(args[0] as i32)
+}
+
+/// Close a descriptor of the current process
+pub(crate) fn close(fd: i32) -> SyscallResult {
     if fd < 0 {
         return Err(SyscallError::BadFileDescriptor);
     }
@@ -510,52 +507,146 @@ fn sys_fstat_impl(args: &[u64]) -> SyscallResult {
    }
 }
 
-/// Implementation of syscall 0x2006: stat
-fn sys_stat_impl(args: &[u64]) -> SyscallResult {
-    let args = extract_args(args, 2)?;
-    let path_ptr = args[0] as *const u8;
-    let statbuf_ptr = args[1] as *mut crate::posix::stat;
+/// Implementation of syscall 0x2009: ioctl
+///
+/// Only seccomp listener files take requests so far.
+fn sys_ioctl_impl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 3)?;
+    let fd = args[0] as i32;
+    let request = args[1];
+    let argp = args[2] as usize;
 
-    // Read path from user space
-    const MAX_PATH_LEN: usize = 4096;
-    let mut path_buf = [0u8; MAX_PATH_LEN];
+    if fd < 0 {
+        return Err(SyscallError::BadFileDescriptor);
+    }
 
-    let pagetable = match crate::process::myproc() {
-        Some(pid) => {
-            let mut table = crate::process::manager::PROC_TABLE.lock();
-            match table.find(pid) {
-                Some(proc) => proc.pagetable,
-                None => return Err(SyscallError::BadAddress),
-            }
+    let file_idx = match crate::process::fdlookup(fd) {
+        Some(idx) => idx,
+        None => return Err(SyscallError::BadFileDescriptor),
+    };
+
+    let (ftype, nonblock) = match FILE_TABLE.lock().get(file_idx) {
+        Some(f) => (f.ftype, (f.status_flags & crate::posix::O_NONBLOCK) != 0),
+        None => return Err(SyscallError::BadFileDescriptor),
+    };
+    match ftype {
+        FileType::SeccompNotify => seccomp_notify_ioctl(file_idx, request, argp, nonblock),
+        FileType::Vfs if crate::vfs::fscrypt::is_ioctl(request as u32) => {
+            fscrypt_ioctl(file_idx, request as u32, argp)
         }
-        None => return Err(SyscallError::BadAddress),
+        // No ENOTTY in SyscallError
+        _ => Err(SyscallError::NotSupported),
+    }
+}
+
+/// FS_IOC_*ENCRYPTION* requests on a file of an encrypting filesystem
+fn fscrypt_ioctl(file_idx: usize, request: u32, argp: usize) -> SyscallResult {
+    use crate::subsystems::mm::vm::{copyin, copyout};
+
+    let inode = {
+        let table = FILE_TABLE.lock();
+        let file = table.get(file_idx).ok_or(SyscallError::BadFileDescriptor)?;
+        file.vfs_file.as_ref().ok_or(SyscallError::BadFileDescriptor)?.inode.clone()
     };
+    let pagetable = {
+        let pid = crate::process::myproc().ok_or(SyscallError::NotFound)?;
+        let table = crate::process::PROC_TABLE.lock();
+        table.find_ref(pid).ok_or(SyscallError::NotFound)?.pagetable
+    };
+    if pagetable.is_null() || argp == 0 {
+        return Err(SyscallError::BadAddress);
+    }
 
-    let path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable,
-                                path_ptr as usize,
-                                path_buf.as_mut_ptr(),
-                                MAX_PATH_LEN)
-            .map_err(|_| SyscallError::BadAddress)?
+    crate::vfs::fscrypt::ioctl(
+        inode.as_ref(),
+        request,
+        |offset, buf: &mut [u8]| unsafe {
+            copyin(pagetable, buf.as_mut_ptr(), argp + offset, buf.len())
+                .map_err(|_| SyscallError::BadAddress)
+        },
+        |offset, buf: &[u8]| unsafe {
+            copyout(pagetable, argp + offset, buf.as_ptr(), buf.len())
+                .map_err(|_| SyscallError::BadAddress)
+        },
+    )?;
+    Ok(0)
+}
+
+/// SECCOMP_IOCTL_NOTIF_* requests on a seccomp listener
+fn seccomp_notify_ioctl(file_idx: usize, request: u64, argp: usize, nonblock: bool) -> SyscallResult {
+    use crate::security::seccomp::notify::*;
+    use crate::subsystems::mm::vm::{copyin, copyout};
+
+    let listener = crate::fs::file::file_get_seccomp_listener(file_idx)
+        .ok_or(SyscallError::BadFileDescriptor)?;
+    let pagetable = {
+        let pid = crate::process::myproc().ok_or(SyscallError::NotFound)?;
+        let table = crate::process::PROC_TABLE.lock();
+        table.find_ref(pid).ok_or(SyscallError::NotFound)?.pagetable
     };
-    
-    let path_str = core::str::from_utf8(&path_buf[..path_len])
-        .map_err(|_| SyscallError::InvalidArgument)?;
-    
-    // Resolve absolute path
-    let abs_path = if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        // Get current working directory from process
-        let pid = crate::process::myproc().ok_or(SyscallError::IoError)?;
-        let proc_table = crate::process::manager::PROC_TABLE.lock();
-        let proc = proc_table.find_ref(pid).ok_or(SyscallError::IoError)?;
-        let cwd = proc.cwd_path.clone().unwrap_or_else(|| "/".to_string());
-        format!("{}/{}", cwd, path_str)
+    if pagetable.is_null() || argp == 0 {
+        return Err(SyscallError::BadAddress);
+    }
+    let map_err = |err: NotifyError| match err {
+        NotifyError::WouldBlock => SyscallError::WouldBlock,
+        NotifyError::NotFound => SyscallError::NotFound,
+        NotifyError::InvalidArgument => SyscallError::InvalidArgument,
     };
+
+    match request {
+        SECCOMP_IOCTL_NOTIF_RECV => {
+            let notif = listener.recv(nonblock).map_err(map_err)?;
+            unsafe {
+                copyout(pagetable, argp, &notif as *const _ as *const u8,
+                        core::mem::size_of::<SeccompNotif>())
+                    .map_err(|_| SyscallError::BadAddress)?;
+            }
+            Ok(0)
+        }
+        SECCOMP_IOCTL_NOTIF_SEND => {
+            let mut resp = SeccompNotifResp::default();
+            unsafe {
+                copyin(pagetable, &mut resp as *mut _ as *mut u8, argp,
+                       core::mem::size_of::<SeccompNotifResp>())
+                    .map_err(|_| SyscallError::BadAddress)?;
+            }
+            listener.send(&resp).map_err(map_err)?;
+            Ok(0)
+        }
+        SECCOMP_IOCTL_NOTIF_ID_VALID | SECCOMP_IOCTL_NOTIF_ID_VALID_WRONG_DIR => {
+            let mut id = 0u64;
+            unsafe {
+                copyin(pagetable, &mut id as *mut _ as *mut u8, argp, core::mem::size_of::<u64>())
+                    .map_err(|_| SyscallError::BadAddress)?;
+            }
+            if listener.id_valid(id) { Ok(0) } else { Err(SyscallError::NotFound) }
+        }
+        _ => Err(SyscallError::InvalidArgument),
+    }
+}
+
+/// Implementation of syscall 0x2006: stat
+fn sys_stat_impl(args: &[u64]) -> SyscallResult {
+    stat_path(args, LookupFlags(LookupFlags::FOLLOW))
+}
+
+/// Implementation of syscall 0x2007: lstat
+fn sys_lstat_impl(args: &[u64]) -> SyscallResult {
+    stat_path(args, LookupFlags::default())
+}
+
+fn stat_path(args: &[u64], lookup: LookupFlags) -> SyscallResult {
+    let args = extract_args(args, 2)?;
+    let path_ptr = args[0] as *const u8;
+    let statbuf_ptr = args[1] as *mut crate::posix::stat;
+
+    let path_buf = read_user_path(path_ptr)?;
+    let path_str = core::str::from_utf8(&path_buf)
+        .map_err(|_| SyscallError::InvalidArgument)?;
     
-    // Call VFS stat
-    match crate::vfs::stat(&abs_path) {
+    // Call VFS stat from the process root and cwd
+    let ctx = crate::process::fs_context()?;
+    match crate::vfs::vfs().stat_at(&ctx, None, path_str, lookup) {
         Ok(file_attr) => {
             // Convert VFS FileAttr directly to C posix::stat struct
             let c_stat = crate::posix::stat {
@@ -583,7 +674,167 @@ fn sys_stat_impl(args: &[u64]) -> SyscallResult {
             }
             Ok(0)
         }
-        Err(_) => Err(SyscallError::NotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Bump the file's reference count and give it a descriptor no lower than `min`
+fn dup_from(file_idx: usize, min: i32) -> SyscallResult {
+    let dup_idx = crate::fs::file::file_dup(file_idx).ok_or(SyscallError::BadFileDescriptor)?;
+    match crate::process::fdalloc_from(min, dup_idx) {
+        Some(fd) => Ok(fd as u64),
+        None => {
+            file_close(dup_idx);
+            Err(SyscallError::TooManyOpenFiles)
+        }
+    }
+}
+
+/// Point `newfd` at `oldfd`'s file, closing whatever `newfd` held
+fn dup_onto(oldfd: i32, newfd: i32) -> SyscallResult {
+    let file_idx = crate::process::fdlookup(oldfd).ok_or(SyscallError::BadFileDescriptor)?;
+    let dup_idx = crate::fs::file::file_dup(file_idx).ok_or(SyscallError::BadFileDescriptor)?;
+    // fdinstall refuses descriptors at or above RLIMIT_NOFILE
+    match crate::process::fdinstall(newfd, dup_idx) {
+        Ok(replaced) => {
+            if let Some(old_idx) = replaced {
+                file_close(old_idx);
+            }
+            Ok(newfd as u64)
+        }
+        Err(()) => {
+            file_close(dup_idx);
+            Err(SyscallError::BadFileDescriptor)
+        }
+    }
+}
+
+/// Implementation of syscall 0x200B: dup
+fn sys_dup_impl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 1)?;
+    let file_idx = crate::process::fdlookup(args[0] as i32).ok_or(SyscallError::BadFileDescriptor)?;
+    dup_from(file_idx, 0)
+}
+
+/// Implementation of syscall 0x200C: dup2
+fn sys_dup2_impl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 2)?;
+    let (oldfd, newfd) = (args[0] as i32, args[1] as i32);
+    if oldfd == newfd {
+        crate::process::fdlookup(oldfd).ok_or(SyscallError::BadFileDescriptor)?;
+        return Ok(newfd as u64);
+    }
+    dup_onto(oldfd, newfd)
+}
+
+/// Implementation of syscall 0x200D: dup3
+///
+/// Close-on-exec is not tracked per descriptor, so `O_CLOEXEC` is accepted
+/// and has no effect.
+fn sys_dup3_impl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 3)?;
+    let (oldfd, newfd, flags) = (args[0] as i32, args[1] as i32, args[2] as i32);
+    if oldfd == newfd || flags & !crate::posix::O_CLOEXEC != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    dup_onto(oldfd, newfd)
+}
+
+/// Implementation of syscall 0x200A: fcntl
+fn sys_fcntl_impl(args: &[u64]) -> SyscallResult {
+    use crate::posix::{F_DUPFD, F_GETFD, F_GETFL, F_SETFD, F_SETFL, O_APPEND, O_NONBLOCK};
+    const F_DUPFD_CLOEXEC: i32 = 1030;
+
+    let args = extract_args(args, 3)?;
+    let fd = args[0] as i32;
+    let cmd = args[1] as i32;
+    let arg = args[2];
+    let file_idx = crate::process::fdlookup(fd).ok_or(SyscallError::BadFileDescriptor)?;
+
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            if arg >= crate::process::rlimit::current(crate::posix::RLIMIT_NOFILE) {
+                return Err(SyscallError::InvalidArgument);
+            }
+            dup_from(file_idx, arg as i32)
+        }
+        // No per-descriptor flags yet; FD_CLOEXEC reads back clear
+        F_GETFD => Ok(0),
+        F_SETFD => Ok(0),
+        F_GETFL => {
+            let table = FILE_TABLE.lock();
+            let file = table.get(file_idx).ok_or(SyscallError::BadFileDescriptor)?;
+            Ok(file.status_flags as u32 as u64)
+        }
+        F_SETFL => {
+            // Only the status bits may change; access mode and creation flags stay
+            let settable = O_APPEND | O_NONBLOCK;
+            let mut table = FILE_TABLE.lock();
+            let file = table.get_mut(file_idx).ok_or(SyscallError::BadFileDescriptor)?;
+            file.status_flags = (file.status_flags & !settable) | (arg as i32 & settable);
+            Ok(0)
+        }
+        _ => Err(SyscallError::InvalidArgument),
     }
 }
 
+/// Implementation of syscall 0x200E: pipe
+fn sys_pipe_impl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 1)?;
+    pipe2(args[0] as usize, 0)
+}
+
+/// Implementation of syscall 0x200F: pipe2
+fn sys_pipe2_impl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 2)?;
+    pipe2(args[0] as usize, args[1] as i32)
+}
+
+fn pipe2(fds_ptr: usize, flags: i32) -> SyscallResult {
+    use crate::posix::{O_CLOEXEC, O_NONBLOCK};
+
+    if flags & !(O_CLOEXEC | O_NONBLOCK) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let pid = crate::process::myproc().ok_or(SyscallError::BadAddress)?;
+    let pagetable = crate::process::manager::PROC_TABLE
+        .lock()
+        .find_ref(pid)
+        .map(|proc| proc.pagetable)
+        .ok_or(SyscallError::BadAddress)?;
+
+    let (ridx, widx) = crate::ipc::pipe::pipe_alloc().ok_or(SyscallError::NoSpaceLeft)?;
+    if flags & O_NONBLOCK != 0 {
+        let mut table = FILE_TABLE.lock();
+        for idx in [ridx, widx] {
+            if let Some(file) = table.get_mut(idx) {
+                file.status_flags |= O_NONBLOCK;
+            }
+        }
+    }
+
+    let Some(rfd) = crate::process::fdalloc(ridx) else {
+        file_close(ridx);
+        file_close(widx);
+        return Err(SyscallError::TooManyOpenFiles);
+    };
+    let Some(wfd) = crate::process::fdalloc(widx) else {
+        crate::process::fdclose(rfd);
+        file_close(ridx);
+        file_close(widx);
+        return Err(SyscallError::TooManyOpenFiles);
+    };
+
+    let fds = [rfd, wfd];
+    let copied = unsafe {
+        crate::subsystems::mm::vm::copyout(pagetable, fds_ptr, fds.as_ptr() as *const u8, core::mem::size_of_val(&fds))
+    };
+    if copied.is_err() {
+        for (fd, idx) in [(rfd, ridx), (wfd, widx)] {
+            crate::process::fdclose(fd);
+            file_close(idx);
+        }
+        return Err(SyscallError::BadAddress);
+    }
+    Ok(0)
+}
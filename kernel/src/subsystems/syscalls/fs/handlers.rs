@@ -3,10 +3,16 @@
 //! This module contains the actual system call handler functions for filesystem operations.
 //! These handlers are migrated from the original fs.rs implementation and adapted
 //! for the new modular service architecture.
+//!
+//! Path arguments are resolved with the calling process's root and working
+//! directory (see [`crate::vfs::namei`]). Each classic call is the `*at()`
+//! variant with `dirfd == AT_FDCWD`, so both share one implementation.
 
 use super::types::*;
 // use crate::syscalls::common::{SyscallError};
 use nos_nos_error_handling::unified::KernelError;
+use crate::posix::{AT_EMPTY_PATH, AT_FDCWD, AT_REMOVEDIR, AT_SYMLINK_FOLLOW, AT_SYMLINK_NOFOLLOW};
+use crate::vfs::{FileMode, FsContext, LookupFlags, PathLoc, RenameFlags, SetAttr};
 use alloc::string::ToString;
 
 /// Handle chdir system call - change current working directory
@@ -17,65 +23,20 @@ pub fn handle_chdir(args: &[u64]) -> Result<u64, KernelError> {
 
     let pathname_ptr = args[0] as usize;
 
-    // Get current process
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let mut proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find(pid).ok_or(KernelError::NotFound)?;
-    let pagetable = proc.pagetable;
-    let cwd_path = proc.cwd_path.clone();
-    drop(proc_table);
-
-    if pagetable.is_null() {
-        return Err(KernelError::BadAddress);
-    }
-
-    // Read pathname from user space
-    const MAX_PATH_LEN: usize = 4096;
-    let mut path_buf = [0u8; MAX_PATH_LEN];
-    let path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable as *mut crate::subsystems::mm::vm::PageTable, pathname_ptr, path_buf.as_mut_ptr(), MAX_PATH_LEN)
-            .map_err(|_| KernelError::BadAddress)?
-    };
-
-    // Convert to string
-    let path_str = core::str::from_utf8(&path_buf[..path_len])
-        .map_err(|_| KernelError::InvalidArgument)?;
-
     // Check if root file system is mounted
     if !crate::vfs::is_root_mounted() {
         return Err(KernelError::IoError);
     }
 
-    // Resolve path (handle relative paths)
-    let abs_path = if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        if let Some(ref cwd) = cwd_path {
-            format!("{}/{}", cwd, path_str)
-        } else {
-            format!("/{}", path_str)
-        }
-    };
-
-    // Normalize path (remove . and .. components)
-    let normalized_path = normalize_path(&abs_path);
+    let (pagetable, ctx) = get_path_context()?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
 
     // Verify that the path exists and is a directory
-    let vfs = crate::vfs::vfs();
-    let attr = vfs.stat(&normalized_path)
-        .map_err(|_| KernelError::NotFound)?;
-
-    // Check if it's a directory
-    if !attr.mode.is_dir() {
-        return Err(KernelError::NotADirectory);
-    }
-
-    // Update process's current working directory
-    let mut proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find(pid).ok_or(KernelError::NotFound)?;
-    proc.cwd_path = Some(normalized_path);
+    let loc = ctx
+        .lookup(None, &path, LookupFlags(LookupFlags::FOLLOW | LookupFlags::DIRECTORY))
+        .map_err(vfs_error)?;
 
-    Ok(0)
+    set_cwd(loc)
 }
 
 /// Handle fchdir system call - change working directory by file descriptor
@@ -86,31 +47,13 @@ pub fn handle_fchdir(args: &[u64]) -> Result<u64, KernelError> {
 
     let fd = args[0] as i32;
 
-    // Lookup the file descriptor
-    let file_idx = match crate::process::fdlookup(fd) {
-        Some(idx) => idx,
-        None => return Err(KernelError::BadFileDescriptor),
-    };
-
-    // Get the file
-    let table = crate::fs::file::FILE_TABLE.lock();
-    let file = match table.get(file_idx) {
-        Some(f) => f,
-        None => return Err(KernelError::BadFileDescriptor),
-    };
-
-    // Check if it's a directory (VFS file)
-    if file.ftype != crate::fs::file::FileType::Vfs {
+    // The descriptor must refer to an open directory
+    let loc = fd_loc(fd)?;
+    if !loc.is_dir().map_err(vfs_error)? {
         return Err(KernelError::NotADirectory);
     }
 
-    // Update process's current working directory
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let mut proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find(pid).ok_or(KernelError::NotFound)?;
-    proc.cwd = Some(file_idx);
-
-    Ok(0)
+    set_cwd(loc)
 }
 
 /// Handle getcwd system call - get current working directory
@@ -127,20 +70,9 @@ pub fn handle_getcwd(args: &[u64]) -> Result<u64, KernelError> {
         return Err(KernelError::InvalidArgument);
     }
 
-    // Get current process
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find_ref(pid).ok_or(KernelError::NotFound)?;
-    let pagetable = proc.pagetable;
-    let cwd_path = proc.cwd_path.clone();
-    drop(proc_table);
-
-    if pagetable.is_null() {
-        return Err(KernelError::BadAddress);
-    }
-
-    // Get current working directory path
-    let cwd = cwd_path.unwrap_or_else(|| alloc::string::String::from("/"));
+    // The path is relative to the process root, so chroot hides what is above it
+    let (pagetable, ctx) = get_path_context()?;
+    let cwd = ctx.display_path(&ctx.cwd);
     let cwd_bytes = cwd.as_bytes();
 
     // Check if buffer is large enough (including null terminator)
@@ -160,119 +92,72 @@ pub fn handle_getcwd(args: &[u64]) -> Result<u64, KernelError> {
     Ok(cwd_bytes.len() as u64)
 }
 
-/// Handle mkdir system call - create a directory
-pub fn handle_mkdir(args: &[u64]) -> Result<u64, KernelError> {
-    if args.len() != 2 {
+/// Handle chroot system call - change the root directory of the process
+pub fn handle_chroot(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 1 {
         return Err(KernelError::InvalidArgument);
     }
 
     let pathname_ptr = args[0] as usize;
-    let mode = args[1] as u32;
 
-    // Get current process
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find_ref(pid).ok_or(KernelError::NotFound)?;
-    let pagetable = proc.pagetable;
-    let cwd_path = proc.cwd_path.clone();
-    drop(proc_table);
-
-    if pagetable.is_null() {
-        return Err(KernelError::BadAddress);
+    // Only root may change its root directory
+    if crate::process::getuid() != 0 {
+        return Err(KernelError::PermissionDenied);
     }
 
-    // Read pathname from user space
-    const MAX_PATH_LEN: usize = 4096;
-    let mut path_buf = [0u8; MAX_PATH_LEN];
-    let path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable as *mut crate::subsystems::mm::vm::PageTable, pathname_ptr, path_buf.as_mut_ptr(), MAX_PATH_LEN)
-            .map_err(|_| KernelError::BadAddress)?
-    };
-
-    // Convert to string
-    let path_str = core::str::from_utf8(&path_buf[..path_len])
-        .map_err(|_| KernelError::InvalidArgument)?;
-
-    // Resolve path (handle relative paths)
-    let abs_path = if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        if let Some(ref cwd) = cwd_path {
-            format!("{}/{}", cwd, path_str)
-        } else {
-            format!("/{}", path_str)
-        }
-    };
+    let (pagetable, ctx) = get_path_context()?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
+    let loc = ctx
+        .lookup(None, &path, LookupFlags(LookupFlags::FOLLOW | LookupFlags::DIRECTORY))
+        .map_err(vfs_error)?;
 
-    // Create directory via fs API
-    crate::subsystems::fs::api::dir_ops::mkdir(&abs_path, mode)
-        .map_err(|e| match e {
-            crate::subsystems::fs::api::FsError::FileExists => KernelError::FileExists,
-            crate::subsystems::fs::api::FsError::PathNotFound => KernelError::NotFound,
-            crate::subsystems::fs::api::FsError::NotADirectory => KernelError::InvalidArgument,
-            crate::subsystems::fs::api::FsError::PermissionDenied => KernelError::PermissionDenied,
-            _ => KernelError::IoError,
-        })?;
+    // Like Linux, the working directory is left alone
+    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
+    let mut proc_table = crate::process::manager::PROC_TABLE.lock();
+    let proc = proc_table.find(pid).ok_or(KernelError::NotFound)?;
+    proc.root_path = Some(loc.path);
 
     Ok(0)
 }
 
-/// Handle rmdir system call - remove a directory
-pub fn handle_rmdir(args: &[u64]) -> Result<u64, KernelError> {
-    if args.len() != 1 {
+/// Handle mkdir system call - create a directory
+pub fn handle_mkdir(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 2 {
         return Err(KernelError::InvalidArgument);
     }
 
-    let pathname_ptr = args[0] as usize;
-
-    // Get current process
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find_ref(pid).ok_or(KernelError::NotFound)?;
-    let pagetable = proc.pagetable;
-    let cwd_path = proc.cwd_path.clone();
-    drop(proc_table);
+    mkdirat(AT_FDCWD, args[0] as usize, args[1] as u32)
+}
 
-    if pagetable.is_null() {
-        return Err(KernelError::BadAddress);
+/// Handle mkdirat system call - create a directory relative to a dirfd
+pub fn handle_mkdirat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 3 {
+        return Err(KernelError::InvalidArgument);
     }
 
-    // Read pathname from user space
-    const MAX_PATH_LEN: usize = 4096;
-    let mut path_buf = [0u8; MAX_PATH_LEN];
-    let path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable as *mut crate::subsystems::mm::vm::PageTable, pathname_ptr, path_buf.as_mut_ptr(), MAX_PATH_LEN)
-            .map_err(|_| KernelError::BadAddress)?
-    };
+    mkdirat(args[0] as i32, args[1] as usize, args[2] as u32)
+}
 
-    // Convert to string
-    let path_str = core::str::from_utf8(&path_buf[..path_len])
-        .map_err(|_| KernelError::InvalidArgument)?;
+fn mkdirat(dirfd: i32, pathname_ptr: usize, mode: u32) -> Result<u64, KernelError> {
+    let (pagetable, ctx) = get_path_context()?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
+    let dir = dirfd_loc(dirfd, &path)?;
 
-    // Resolve path (handle relative paths)
-    let abs_path = if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        if let Some(ref cwd) = cwd_path {
-            format!("{}/{}", cwd, path_str)
-        } else {
-            format!("/{}", path_str)
-        }
-    };
-
-    // Remove directory via fs API
-    crate::subsystems::fs::api::dir_ops::rmdir(&abs_path)
-        .map_err(|e| match e {
-            crate::subsystems::fs::api::FsError::PathNotFound => KernelError::NotFound,
-            crate::subsystems::fs::api::FsError::NotADirectory => KernelError::InvalidArgument,
-            crate::subsystems::fs::api::FsError::DirectoryNotEmpty => KernelError::DirectoryNotEmpty,
-            crate::subsystems::fs::api::FsError::PermissionDenied => KernelError::PermissionDenied,
-            _ => KernelError::IoError,
-        })?;
+    let mode = FileMode::new(FileMode::S_IFDIR | (mode & 0o7777 & !current_umask()));
+    crate::vfs::vfs().mkdir_at(&ctx, dir.as_ref(), &path, mode).map_err(vfs_error)?;
 
     Ok(0)
 }
 
+/// Handle rmdir system call - remove a directory
+pub fn handle_rmdir(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 1 {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    unlinkat(AT_FDCWD, args[0] as usize, AT_REMOVEDIR)
+}
+
 /// Handle readdir/getdents system call - read directory entries
 pub fn handle_readdir(args: &[u64]) -> Result<u64, KernelError> {
     if args.len() < 2 {
@@ -311,57 +196,42 @@ pub fn handle_readdir(args: &[u64]) -> Result<u64, KernelError> {
     Ok(entries.len() as u64)
 }
 
+
 /// Handle unlink system call - remove a file
 pub fn handle_unlink(args: &[u64]) -> Result<u64, KernelError> {
     if args.len() != 1 {
         return Err(KernelError::InvalidArgument);
     }
 
-    let pathname_ptr = args[0] as usize;
-
-    // Get current process
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find_ref(pid).ok_or(KernelError::NotFound)?;
-    let pagetable = proc.pagetable;
-    let cwd_path = proc.cwd_path.clone();
-    drop(proc_table);
+    unlinkat(AT_FDCWD, args[0] as usize, 0)
+}
 
-    if pagetable.is_null() {
-        return Err(KernelError::BadAddress);
+/// Handle unlinkat system call - remove a file, or a directory with AT_REMOVEDIR
+pub fn handle_unlinkat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 3 {
+        return Err(KernelError::InvalidArgument);
     }
 
-    // Read pathname from user space
-    const MAX_PATH_LEN: usize = 4096;
-    let mut path_buf = [0u8; MAX_PATH_LEN];
-    let path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable as *mut crate::subsystems::mm::vm::PageTable, pathname_ptr, path_buf.as_mut_ptr(), MAX_PATH_LEN)
-            .map_err(|_| KernelError::BadAddress)?
-    };
+    unlinkat(args[0] as i32, args[1] as usize, args[2] as i32)
+}
 
-    // Convert to string
-    let path_str = core::str::from_utf8(&path_buf[..path_len])
-        .map_err(|_| KernelError::InvalidArgument)?;
+fn unlinkat(dirfd: i32, pathname_ptr: usize, flags: i32) -> Result<u64, KernelError> {
+    if flags & !AT_REMOVEDIR != 0 {
+        return Err(KernelError::InvalidArgument);
+    }
 
-    // Resolve path (handle relative paths)
-    let abs_path = if path_str.starts_with('/') {
-        path_str.to_string()
-    } else {
-        if let Some(ref cwd) = cwd_path {
-            format!("{}/{}", cwd, path_str)
-        } else {
-            format!("/{}", path_str)
-        }
-    };
+    let (pagetable, ctx) = get_path_context()?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
+    let dir = dirfd_loc(dirfd, &path)?;
 
-    // Remove file via VFS
+    // A final symlink is removed itself, never its target
     let vfs = crate::vfs::vfs();
-    vfs.unlink(&abs_path)
-        .map_err(|e| match e {
-            crate::vfs::VfsError::NotFound => KernelError::NotFound,
-            crate::vfs::VfsError::IsDirectory => KernelError::IsADirectory,
-            _ => KernelError::IoError,
-        })?;
+    if flags & AT_REMOVEDIR != 0 {
+        vfs.rmdir_at(&ctx, dir.as_ref(), &path)
+    } else {
+        vfs.unlink_at(&ctx, dir.as_ref(), &path)
+    }
+    .map_err(vfs_error)?;
 
     Ok(0)
 }
@@ -372,74 +242,28 @@ pub fn handle_rename(args: &[u64]) -> Result<u64, KernelError> {
         return Err(KernelError::InvalidArgument);
     }
 
-    let oldpath_ptr = args[0] as usize;
-    let newpath_ptr = args[1] as usize;
-
-    // Get current process
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find_ref(pid).ok_or(KernelError::NotFound)?;
-    let pagetable = proc.pagetable;
-    let cwd_path = proc.cwd_path.clone();
-    drop(proc_table);
+    renameat2(AT_FDCWD, args[0] as usize, AT_FDCWD, args[1] as usize, 0)
+}
 
-    if pagetable.is_null() {
-        return Err(KernelError::BadAddress);
+/// Handle renameat2 system call - rename relative to two dirfds, with RENAME_* flags
+pub fn handle_renameat2(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 5 {
+        return Err(KernelError::InvalidArgument);
     }
 
-    // Read old and new pathnames from user space
-    const MAX_PATH_LEN: usize = 4096;
-    let mut old_path_buf = [0u8; MAX_PATH_LEN];
-    let mut new_path_buf = [0u8; MAX_PATH_LEN];
-
-    let old_path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable as *mut crate::subsystems::mm::vm::PageTable, oldpath_ptr, old_path_buf.as_mut_ptr(), MAX_PATH_LEN)
-            .map_err(|_| KernelError::BadAddress)?
-    };
-
-    let new_path_len = unsafe {
-        crate::subsystems::mm::vm::copyinstr(pagetable as *mut crate::subsystems::mm::vm::PageTable, newpath_ptr, new_path_buf.as_mut_ptr(), MAX_PATH_LEN)
-            .map_err(|_| KernelError::BadAddress)?
-    };
-
-    // Convert to strings
-    let old_path_str = core::str::from_utf8(&old_path_buf[..old_path_len])
-        .map_err(|_| KernelError::InvalidArgument)?;
-
-    let new_path_str = core::str::from_utf8(&new_path_buf[..new_path_len])
-        .map_err(|_| KernelError::InvalidArgument)?;
-
-    // Resolve paths (handle relative paths)
-    let abs_old_path = resolve_path(old_path_str, &cwd_path);
-    let abs_new_path = resolve_path(new_path_str, &cwd_path);
-
-    // Get old inode for rename operation
-    let vfs = crate::vfs::vfs();
-    let old_dentry = vfs.lookup_path(&abs_old_path)
-        .map_err(|_| KernelError::NotFound)?;
-    let old_inode = old_dentry.lock().inode.clone();
-
-    // Split paths
-    let (new_parent_path, new_name) = split_path(&abs_new_path)?;
-    let (old_parent_path, old_name) = split_path(&abs_old_path)?;
-
-    // Get parent directories
-    let new_parent_dentry = vfs.lookup_path(&new_parent_path)
-        .map_err(|_| KernelError::NotFound)?;
-    let new_parent_inode = new_parent_dentry.lock().inode.clone();
+    renameat2(args[0] as i32, args[1] as usize, args[2] as i32, args[3] as usize, args[4] as u32)
+}
 
-    let old_parent_dentry = vfs.lookup_path(&old_parent_path)
-        .map_err(|_| KernelError::NotFound)?;
-    let old_parent_inode = old_parent_dentry.lock().inode.clone();
+fn renameat2(olddirfd: i32, oldpath_ptr: usize, newdirfd: i32, newpath_ptr: usize, flags: u32) -> Result<u64, KernelError> {
+    let (pagetable, ctx) = get_path_context()?;
+    let old_path = read_path_from_user(pagetable, oldpath_ptr)?;
+    let new_path = read_path_from_user(pagetable, newpath_ptr)?;
+    let old_dir = dirfd_loc(olddirfd, &old_path)?;
+    let new_dir = dirfd_loc(newdirfd, &new_path)?;
 
-    // Perform rename operation
-    old_parent_inode.rename(&old_name, new_parent_inode.as_ref(), &new_name)
-        .map_err(|e| match e {
-            crate::vfs::VfsError::NotFound => KernelError::NotFound,
-            crate::vfs::VfsError::Exists => KernelError::FileExists,
-            crate::vfs::VfsError::NotSupported => KernelError::NotSupported,
-            _ => KernelError::IoError,
-        })?;
+    crate::vfs::vfs()
+        .rename_at(&ctx, old_dir.as_ref(), &old_path, new_dir.as_ref(), &new_path, RenameFlags(flags))
+        .map_err(vfs_error)?;
 
     Ok(0)
 }
@@ -450,46 +274,41 @@ pub fn handle_link(args: &[u64]) -> Result<u64, KernelError> {
         return Err(KernelError::InvalidArgument);
     }
 
-    let oldpath_ptr = args[0] as usize;
-    let newpath_ptr = args[1] as usize;
+    linkat(AT_FDCWD, args[0] as usize, AT_FDCWD, args[1] as usize, 0)
+}
 
-    // Get current process details
-    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
-    let proc_table = crate::process::manager::PROC_TABLE.lock();
-    let proc = proc_table.find_ref(pid).ok_or(KernelError::NotFound)?;
-    let pagetable = proc.pagetable;
-    let cwd_path = proc.cwd_path.clone();
-    drop(proc_table);
+/// Handle linkat system call - create a hard link relative to two dirfds
+pub fn handle_linkat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 5 {
+        return Err(KernelError::InvalidArgument);
+    }
 
-    if pagetable.is_null() {
-        return Err(KernelError::BadAddress);
+    linkat(args[0] as i32, args[1] as usize, args[2] as i32, args[3] as usize, args[4] as i32)
+}
+
+fn linkat(olddirfd: i32, oldpath_ptr: usize, newdirfd: i32, newpath_ptr: usize, flags: i32) -> Result<u64, KernelError> {
+    if flags & !(AT_SYMLINK_FOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(KernelError::InvalidArgument);
     }
 
-    // Read paths from user space and resolve them
-    let abs_old_path = read_and_resolve_path(pagetable as usize, oldpath_ptr, &cwd_path)?;
-    let abs_new_path = read_and_resolve_path(pagetable as usize, newpath_ptr, &cwd_path)?;
+    let (pagetable, ctx) = get_path_context()?;
+    let old_path = read_path_from_user(pagetable, oldpath_ptr)?;
+    let new_path = read_path_from_user(pagetable, newpath_ptr)?;
+    let old_dir = dirfd_loc(olddirfd, &old_path)?;
+    let new_dir = dirfd_loc(newdirfd, &new_path)?;
 
-    // Get old inode
-    let vfs = crate::vfs::vfs();
-    let old_dentry = vfs.lookup_path(&abs_old_path)
-        .map_err(|_| KernelError::NotFound)?;
-    let old_inode = old_dentry.lock().inode.clone();
-
-    // Split new path and get parent directory
-    let (new_parent_path, new_name) = split_path(&abs_new_path)?;
-    let new_parent_dentry = vfs.lookup_path(&new_parent_path)
-        .map_err(|_| KernelError::NotFound)?;
-    let new_parent_inode = new_parent_dentry.lock().inode.clone();
-
-    // Create hard link
-    new_parent_inode.link(&new_name, old_inode)
-        .map_err(|e| match e {
-            crate::vfs::VfsError::NotFound => KernelError::NotFound,
-            crate::vfs::VfsError::Exists => KernelError::FileExists,
-            crate::vfs::VfsError::NotSupported => KernelError::NotSupported,
-            crate::vfs::VfsError::IsDirectory => KernelError::IsADirectory,
-            _ => KernelError::IoError,
-        })?;
+    // Unlike most calls, linkat only follows a final symlink when asked to
+    let mut lookup = 0;
+    if flags & AT_SYMLINK_FOLLOW != 0 {
+        lookup |= LookupFlags::FOLLOW;
+    }
+    if flags & AT_EMPTY_PATH != 0 {
+        lookup |= LookupFlags::EMPTY_PATH;
+    }
+
+    crate::vfs::vfs()
+        .link_at(&ctx, old_dir.as_ref(), &old_path, new_dir.as_ref(), &new_path, LookupFlags(lookup))
+        .map_err(vfs_error)?;
 
     Ok(0)
 }
@@ -500,24 +319,29 @@ pub fn handle_symlink(args: &[u64]) -> Result<u64, KernelError> {
         return Err(KernelError::InvalidArgument);
     }
 
-    let target_path_ptr = args[0] as usize;
-    let link_path_ptr = args[1] as usize;
+    symlinkat(args[0] as usize, AT_FDCWD, args[1] as usize)
+}
 
-    // Get process context
-    let (pagetable, cwd_path) = get_process_context()?;
+/// Handle symlinkat system call - create a symbolic link relative to a dirfd
+pub fn handle_symlinkat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 3 {
+        return Err(KernelError::InvalidArgument);
+    }
 
-    // Read and resolve paths
-    let target_path = read_path_from_user(pagetable as usize, target_path_ptr)?;
-    let abs_link_path = read_and_resolve_path(pagetable as usize, link_path_ptr, &cwd_path)?;
+    symlinkat(args[0] as usize, args[1] as i32, args[2] as usize)
+}
 
-    // Create symbolic link
-    crate::vfs::vfs().symlink(&abs_link_path, &target_path)
-        .map_err(|e| match e {
-            crate::vfs::VfsError::Exists => KernelError::FileExists,
-            crate::vfs::VfsError::NotFound => KernelError::NotFound,
-            crate::vfs::VfsError::NotSupported => KernelError::NotSupported,
-            _ => KernelError::IoError,
-        })?;
+fn symlinkat(target_path_ptr: usize, newdirfd: i32, link_path_ptr: usize) -> Result<u64, KernelError> {
+    let (pagetable, ctx) = get_path_context()?;
+
+    // The target is stored verbatim and only resolved when the link is followed
+    let target_path = read_path_from_user(pagetable, target_path_ptr)?;
+    let link_path = read_path_from_user(pagetable, link_path_ptr)?;
+    let dir = dirfd_loc(newdirfd, &link_path)?;
+
+    crate::vfs::vfs()
+        .symlink_at(&ctx, dir.as_ref(), &link_path, &target_path)
+        .map_err(vfs_error)?;
 
     Ok(0)
 }
@@ -528,28 +352,32 @@ pub fn handle_readlink(args: &[u64]) -> Result<u64, KernelError> {
         return Err(KernelError::InvalidArgument);
     }
 
-    let path_ptr = args[0] as usize;
-    let buf_ptr = args[1] as usize;
-    let bufsize = args[2] as usize;
+    readlinkat(AT_FDCWD, args[0] as usize, args[1] as usize, args[2] as usize)
+}
+
+/// Handle readlinkat system call - read a symbolic link relative to a dirfd
+pub fn handle_readlinkat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 4 {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    readlinkat(args[0] as i32, args[1] as usize, args[2] as usize, args[3] as usize)
+}
 
+fn readlinkat(dirfd: i32, path_ptr: usize, buf_ptr: usize, bufsize: usize) -> Result<u64, KernelError> {
     // Validate buffer size
     if bufsize == 0 {
         return Err(KernelError::InvalidArgument);
     }
 
-    // Get process context
-    let (pagetable, cwd_path) = get_process_context()?;
-
-    // Read and resolve path
-    let abs_path = read_and_resolve_path(pagetable as usize, path_ptr, &cwd_path)?;
+    let (pagetable, ctx) = get_path_context()?;
+    let path = read_path_from_user(pagetable, path_ptr)?;
+    let dir = dirfd_loc(dirfd, &path)?;
 
     // Read symbolic link target
-    let target = crate::vfs::vfs().readlink(&abs_path)
-        .map_err(|e| match e {
-            crate::vfs::VfsError::NotFound => KernelError::NotFound,
-            crate::vfs::VfsError::InvalidOperation => KernelError::InvalidArgument,
-            _ => KernelError::IoError,
-        })?;
+    let target = crate::vfs::vfs()
+        .readlink_at(&ctx, dir.as_ref(), &path)
+        .map_err(vfs_error)?;
 
     // Copy target to user buffer
     let target_bytes = target.as_bytes();
@@ -572,35 +400,39 @@ pub fn handle_chmod(args: &[u64]) -> Result<u64, KernelError> {
         return Err(KernelError::InvalidArgument);
     }
 
-    let pathname_ptr = args[0] as usize;
-    let mode = args[1] as u32;
+    fchmodat(AT_FDCWD, args[0] as usize, args[1] as u32, 0)
+}
 
-    // Get process context and permissions
-    let (pagetable, cwd_path) = get_process_context()?;
+/// Handle fchmodat system call - change permissions relative to a dirfd
+pub fn handle_fchmodat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 4 {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    fchmodat(args[0] as i32, args[1] as usize, args[2] as u32, args[3] as i32)
+}
+
+fn fchmodat(dirfd: i32, pathname_ptr: usize, mode: u32, flags: i32) -> Result<u64, KernelError> {
+    if flags & !AT_SYMLINK_NOFOLLOW != 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let (pagetable, ctx) = get_path_context()?;
     let current_uid = crate::process::getuid();
     let is_root = current_uid == 0;
 
-    // Read and resolve path
-    let abs_path = read_and_resolve_path(pagetable as usize, pathname_ptr, &cwd_path)?;
-
-    // Check permissions
-    let vfs = crate::vfs::vfs();
-    let attr = vfs.stat(&abs_path).map_err(|_| KernelError::NotFound)?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
+    let dir = dirfd_loc(dirfd, &path)?;
+    let loc = ctx.lookup(dir.as_ref(), &path, at_lookup_flags(flags)).map_err(vfs_error)?;
 
     // Only owner or root can change file mode
+    let attr = loc.inode.getattr().map_err(vfs_error)?;
     if !is_root && attr.uid != current_uid {
         return Err(KernelError::PermissionDenied);
     }
 
     // Update file permissions
-    let file_mode = crate::vfs::FileMode::new((attr.mode.0 & 0o170000) | (mode & 0o7777));
-    let mut new_attr = attr;
-    new_attr.mode = file_mode;
-
-    let vfs_file = vfs.open(&abs_path, crate::posix::O_RDWR as u32)
-        .map_err(|_| KernelError::PermissionDenied)?;
-
-    vfs_file.set_attr(&new_attr).map_err(|_| KernelError::PermissionDenied)?;
+    loc.inode.setattr(&SetAttr::chmod(mode & 0o7777)).map_err(vfs_error)?;
 
     Ok(0)
 }
@@ -626,40 +458,55 @@ pub fn handle_fchmod(args: &[u64]) -> Result<u64, KernelError> {
     Ok(0)
 }
 
+
 /// Handle chown system call - change file ownership
 pub fn handle_chown(args: &[u64]) -> Result<u64, KernelError> {
     if args.len() != 3 {
         return Err(KernelError::InvalidArgument);
     }
 
-    let pathname_ptr = args[0] as usize;
-    let uid = args[1] as u32;
-    let gid = args[2] as u32;
+    fchownat(AT_FDCWD, args[0] as usize, args[1] as u32, args[2] as u32, 0)
+}
 
-    // Only root can change ownership
-    let current_uid = crate::process::getuid();
-    if current_uid != 0 {
-        return Err(KernelError::PermissionDenied);
+/// Handle lchown system call - change ownership of a symlink itself
+pub fn handle_lchown(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 3 {
+        return Err(KernelError::InvalidArgument);
     }
 
-    let (pagetable, cwd_path) = get_process_context()?;
-    let abs_path = read_and_resolve_path(pagetable as usize, pathname_ptr, &cwd_path)?;
+    fchownat(AT_FDCWD, args[0] as usize, args[1] as u32, args[2] as u32, AT_SYMLINK_NOFOLLOW)
+}
 
-    // Get and update attributes
-    let vfs = crate::vfs::vfs();
-    let mut attr = vfs.stat(&abs_path).map_err(|_| KernelError::NotFound)?;
+/// Handle fchownat system call - change ownership relative to a dirfd
+pub fn handle_fchownat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 5 {
+        return Err(KernelError::InvalidArgument);
+    }
 
-    if uid != u32::MAX {
-        attr.uid = uid;
+    fchownat(args[0] as i32, args[1] as usize, args[2] as u32, args[3] as u32, args[4] as i32)
+}
+
+fn fchownat(dirfd: i32, pathname_ptr: usize, uid: u32, gid: u32, flags: i32) -> Result<u64, KernelError> {
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(KernelError::InvalidArgument);
     }
-    if gid != u32::MAX {
-        attr.gid = gid;
+
+    // Only root can change ownership
+    let current_uid = crate::process::getuid();
+    if current_uid != 0 {
+        return Err(KernelError::PermissionDenied);
     }
 
-    let vfs_file = vfs.open(&abs_path, crate::posix::O_RDWR as u32)
-        .map_err(|_| KernelError::PermissionDenied)?;
+    let (pagetable, ctx) = get_path_context()?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
+    let dir = dirfd_loc(dirfd, &path)?;
 
-    vfs_file.set_attr(&attr).map_err(|_| KernelError::PermissionDenied)?;
+    // u32::MAX (-1) leaves the id unchanged
+    let uid = (uid != u32::MAX).then_some(uid);
+    let gid = (gid != u32::MAX).then_some(gid);
+    crate::vfs::vfs()
+        .setattr_at(&ctx, dir.as_ref(), &path, at_lookup_flags(flags), &SetAttr::chown(uid, gid))
+        .map_err(vfs_error)?;
 
     Ok(0)
 }
@@ -703,35 +550,14 @@ pub fn handle_umask(args: &[u64]) -> Result<u64, KernelError> {
     Ok(old_mask as u64)
 }
 
+
 /// Handle stat system call - get file status (follows symlinks)
 pub fn handle_stat(args: &[u64]) -> Result<u64, KernelError> {
     if args.len() != 2 {
         return Err(KernelError::InvalidArgument);
     }
 
-    let pathname_ptr = args[0] as usize;
-    let statbuf_ptr = args[1] as *mut crate::posix::stat;
-
-    if statbuf_ptr.is_null() {
-        return Err(KernelError::BadAddress);
-    }
-
-    let (pagetable, cwd_path) = get_process_context()?;
-    let abs_path = read_and_resolve_path(pagetable as usize, pathname_ptr, &cwd_path)?;
-
-    // Get file attributes
-    let vfs = crate::vfs::vfs();
-    let attr = vfs.stat(&abs_path).map_err(|_| KernelError::NotFound)?;
-
-    // Convert to POSIX stat and copy out
-    let stat_buf = file_attr_to_stat(&attr);
-    unsafe {
-        crate::subsystems::mm::vm::copyout(pagetable as *mut crate::subsystems::mm::vm::PageTable, statbuf_ptr as usize,
-            &stat_buf as *const _ as *const u8, core::mem::size_of::<crate::posix::stat>())
-            .map_err(|_| KernelError::BadAddress)?;
-    }
-
-    Ok(0)
+    fstatat(AT_FDCWD, args[0] as usize, args[1] as usize, 0)
 }
 
 /// Handle lstat system call - get file status (doesn't follow symlinks)
@@ -740,25 +566,30 @@ pub fn handle_lstat(args: &[u64]) -> Result<u64, KernelError> {
         return Err(KernelError::InvalidArgument);
     }
 
-    let pathname_ptr = args[0] as usize;
-    let statbuf_ptr = args[1] as *mut crate::posix::stat;
+    fstatat(AT_FDCWD, args[0] as usize, args[1] as usize, AT_SYMLINK_NOFOLLOW)
+}
 
-    if statbuf_ptr.is_null() {
-        return Err(KernelError::BadAddress);
+/// Handle fstatat system call - get file status relative to a dirfd
+pub fn handle_fstatat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 4 {
+        return Err(KernelError::InvalidArgument);
     }
 
-    let (pagetable, cwd_path) = get_process_context()?;
-    let abs_path = read_and_resolve_path(pagetable as usize, pathname_ptr, &cwd_path)?;
+    fstatat(args[0] as i32, args[1] as usize, args[2] as usize, args[3] as i32)
+}
 
-    // Get file attributes without following symlinks
-    let vfs = crate::vfs::vfs();
-    let dentry = vfs.lookup_path(&abs_path).map_err(|_| KernelError::NotFound)?;
-    let attr = dentry.lock().inode.getattr().map_err(|_| KernelError::IoError)?;
+fn fstatat(dirfd: i32, pathname_ptr: usize, statbuf_ptr: usize, flags: i32) -> Result<u64, KernelError> {
+    if statbuf_ptr == 0 {
+        return Err(KernelError::BadAddress);
+    }
+
+    let attr = stat_attr_at(dirfd, pathname_ptr, flags)?;
+    let (pagetable, _) = get_process_context()?;
 
     // Convert to POSIX stat and copy out
     let stat_buf = file_attr_to_stat(&attr);
     unsafe {
-        crate::subsystems::mm::vm::copyout(pagetable as *mut crate::subsystems::mm::vm::PageTable, statbuf_ptr as usize,
+        crate::subsystems::mm::vm::copyout(pagetable as *mut crate::subsystems::mm::vm::PageTable, statbuf_ptr,
             &stat_buf as *const _ as *const u8, core::mem::size_of::<crate::posix::stat>())
             .map_err(|_| KernelError::BadAddress)?;
     }
@@ -766,27 +597,60 @@ pub fn handle_lstat(args: &[u64]) -> Result<u64, KernelError> {
     Ok(0)
 }
 
+/// Attributes of the file an fstatat() call names
+///
+/// An empty path with `AT_EMPTY_PATH` means `dirfd` itself. Other ABIs
+/// lay the result out in their own stat structures from this.
+pub fn stat_attr_at(dirfd: i32, pathname_ptr: usize, flags: i32) -> Result<crate::vfs::FileAttr, KernelError> {
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let (pagetable, ctx) = get_path_context()?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
+    if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        let file_idx = crate::process::fdlookup(dirfd).ok_or(KernelError::BadFileDescriptor)?;
+        return crate::fs::file::file_attr(file_idx).map_err(|_| KernelError::BadFileDescriptor);
+    }
+    let dir = dirfd_loc(dirfd, &path)?;
+
+    crate::vfs::vfs()
+        .stat_at(&ctx, dir.as_ref(), &path, at_lookup_flags(flags))
+        .map_err(vfs_error)
+}
+
 /// Handle access system call - check file access permissions
 pub fn handle_access(args: &[u64]) -> Result<u64, KernelError> {
     if args.len() != 2 {
         return Err(KernelError::InvalidArgument);
     }
 
-    let pathname_ptr = args[0] as usize;
-    let mode = args[1] as i32;
+    faccessat(AT_FDCWD, args[0] as usize, args[1] as i32, 0)
+}
+
+/// Handle faccessat system call - check access permissions relative to a dirfd
+pub fn handle_faccessat(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 4 {
+        return Err(KernelError::InvalidArgument);
+    }
 
-    let (pagetable, cwd_path, uid, gid) = get_process_context_full()?;
-    let abs_path = read_and_resolve_path(pagetable as usize, pathname_ptr, &cwd_path)?;
+    faccessat(args[0] as i32, args[1] as usize, args[2] as i32, args[3] as i32)
+}
+
+fn faccessat(dirfd: i32, pathname_ptr: usize, mode: i32, flags: i32) -> Result<u64, KernelError> {
+    let (pagetable, ctx) = get_path_context()?;
+    let (_, _, uid, gid) = get_process_context_full()?;
+    let path = read_path_from_user(pagetable, pathname_ptr)?;
+    let dir = dirfd_loc(dirfd, &path)?;
 
-    // F_OK check
+    // Get file attributes; F_OK only needs the lookup to succeed
+    let attr = crate::vfs::vfs()
+        .stat_at(&ctx, dir.as_ref(), &path, at_lookup_flags(flags & AT_SYMLINK_NOFOLLOW))
+        .map_err(vfs_error)?;
     if mode == crate::posix::F_OK {
         return Ok(0);
     }
 
-    // Get file attributes and check permissions
-    let vfs = crate::vfs::vfs();
-    let attr = vfs.stat(&abs_path).map_err(|_| KernelError::NotFound)?;
-
     let file_mode = attr.mode.0;
     let file_uid = attr.uid;
     let file_gid = attr.gid;
@@ -820,62 +684,147 @@ pub fn handle_access(args: &[u64]) -> Result<u64, KernelError> {
     Ok(0)
 }
 
-// Helper Functions
+/// Handle fsync system call - write back a file's cached data
+pub fn handle_fsync(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 1 {
+        return Err(KernelError::InvalidArgument);
+    }
 
-/// Normalize a path by removing . and .. components
-fn normalize_path(path: &str) -> alloc::string::String {
-    let mut components = alloc::vec::Vec::new();
-
-    for component in path.split('/') {
-        match component {
-            "" | "." => continue,
-            ".." => {
-                if !components.is_empty() && components.last() != Some(&"..") {
-                    components.pop();
-                } else if !path.starts_with('/') {
-                    components.push("..");
-                }
-            }
-            _ => components.push(component),
-        }
+    let fd = args[0] as i32;
+    if fd < 0 {
+        return Err(KernelError::BadFileDescriptor);
     }
 
-    if path.starts_with('/') {
-        format!("/{}", components.join("/"))
-    } else if components.is_empty() {
-        ".".to_string()
-    } else {
-        components.join("/")
+    let file_idx = crate::process::fdlookup(fd).ok_or(KernelError::BadFileDescriptor)?;
+    crate::fs::file::file_fsync(file_idx).map_err(vfs_error)?;
+
+    Ok(0)
+}
+
+/// Handle fdatasync system call
+///
+/// Inodes keep no metadata that is written back separately from their
+/// data, so this is the same as fsync.
+pub fn handle_fdatasync(args: &[u64]) -> Result<u64, KernelError> {
+    handle_fsync(args)
+}
+
+/// Handle sync system call - write back all cached data
+pub fn handle_sync(_args: &[u64]) -> Result<u64, KernelError> {
+    // sync() has no way to report errors
+    let _ = crate::vfs::vfs().sync_all();
+    Ok(0)
+}
+
+/// Handle ftruncate system call - set the size of an open file
+pub fn handle_ftruncate(args: &[u64]) -> Result<u64, KernelError> {
+    if args.len() != 2 {
+        return Err(KernelError::InvalidArgument);
+    }
+
+    let fd = args[0] as i32;
+    let length = args[1] as i64;
+    if fd < 0 {
+        return Err(KernelError::BadFileDescriptor);
+    }
+    if length < 0 {
+        return Err(KernelError::InvalidArgument);
     }
+
+    let file_idx = crate::process::fdlookup(fd).ok_or(KernelError::BadFileDescriptor)?;
+    crate::fs::file::file_truncate(file_idx, length as u64).map_err(|_| KernelError::InvalidArgument)?;
+
+    Ok(0)
 }
 
-/// Resolve a path to absolute path given current working directory
-fn resolve_path(path: &str, cwd: &Option<alloc::string::String>) -> alloc::string::String {
-    if path.starts_with('/') {
-        path.to_string()
-    } else {
-        if let Some(ref cwd_path) = cwd {
-            format!("{}/{}", cwd_path, path)
-        } else {
-            format!("/{}", path)
-        }
+// Helper Functions
+
+/// Map a VFS error onto the closest kernel error
+fn vfs_error(e: crate::vfs::VfsError) -> KernelError {
+    use crate::vfs::VfsError;
+    match e {
+        VfsError::NotFound | VfsError::NoData => KernelError::NotFound,
+        VfsError::PermissionDenied | VfsError::ReadOnly => KernelError::PermissionDenied,
+        VfsError::NotDirectory => KernelError::NotADirectory,
+        VfsError::IsDirectory => KernelError::IsADirectory,
+        VfsError::NotEmpty => KernelError::DirectoryNotEmpty,
+        VfsError::Exists => KernelError::FileExists,
+        VfsError::Busy => KernelError::Busy,
+        VfsError::NotSupported => KernelError::NotSupported,
+        // KernelError has no ELOOP, ENAMETOOLONG, EXDEV or ENOKEY yet
+        VfsError::InvalidPath
+        | VfsError::InvalidOperation
+        | VfsError::NameTooLong
+        | VfsError::TooManyLinks
+        | VfsError::CrossDevice
+        | VfsError::NoKey => KernelError::InvalidArgument,
+        VfsError::NoSpace | VfsError::NotMounted | VfsError::IoError => KernelError::IoError,
     }
 }
 
-/// Split a path into parent directory and file name
-fn split_path(path: &str) -> Result<(alloc::string::String, alloc::string::String), KernelError> {
-    if path.is_empty() || path == "/" {
-        return Err(KernelError::InvalidArgument);
+/// Lookup flags for the `AT_SYMLINK_NOFOLLOW`/`AT_EMPTY_PATH` bits of a `*at()` call
+fn at_lookup_flags(flags: i32) -> LookupFlags {
+    let mut lookup = LookupFlags::FOLLOW;
+    if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        lookup &= !LookupFlags::FOLLOW;
     }
+    if flags & AT_EMPTY_PATH != 0 {
+        lookup |= LookupFlags::EMPTY_PATH;
+    }
+    LookupFlags(lookup)
+}
 
-    let path = path.trim_end_matches('/');
-    if let Some(pos) = path.rfind('/') {
-        let parent = if pos == 0 { "/" } else { &path[..pos] };
-        let name = &path[pos + 1..];
-        Ok((parent.to_string(), name.to_string()))
-    } else {
-        Ok(("/".to_string(), path.to_string()))
+/// Get current process pagetable and its root/cwd for path lookups
+fn get_path_context() -> Result<(usize, FsContext), KernelError> {
+    let (pagetable, _) = get_process_context()?;
+    let ctx = crate::process::fs_context().map_err(vfs_error)?;
+    Ok((pagetable, ctx))
+}
+
+/// Starting directory of a `*at()` lookup
+///
+/// `None` means the working directory (`AT_FDCWD`); absolute paths ignore
+/// `dirfd` altogether, as POSIX specifies.
+pub(crate) fn dirfd_loc(dirfd: i32, path: &str) -> Result<Option<PathLoc>, KernelError> {
+    if path.starts_with('/') || dirfd == AT_FDCWD {
+        return Ok(None);
     }
+    fd_loc(dirfd).map(Some)
+}
+
+/// Location an open descriptor was resolved to when it was opened
+pub(crate) fn fd_loc(fd: i32) -> Result<PathLoc, KernelError> {
+    if fd < 0 {
+        return Err(KernelError::BadFileDescriptor);
+    }
+    let file_idx = crate::process::fdlookup(fd).ok_or(KernelError::BadFileDescriptor)?;
+    let table = crate::fs::file::FILE_TABLE.lock();
+    let file = table.get(file_idx).ok_or(KernelError::BadFileDescriptor)?;
+
+    // Pipes, sockets and devices have no place in the tree
+    file.vfs_file
+        .as_ref()
+        .and_then(|f| f.loc.clone())
+        .ok_or(KernelError::NotADirectory)
+}
+
+/// Make `loc` the working directory of the current process
+fn set_cwd(loc: PathLoc) -> Result<u64, KernelError> {
+    let pid = crate::process::myproc().ok_or(KernelError::NotFound)?;
+    let mut proc_table = crate::process::manager::PROC_TABLE.lock();
+    let proc = proc_table.find(pid).ok_or(KernelError::NotFound)?;
+    proc.cwd_path = Some(loc.path);
+
+    Ok(0)
+}
+
+/// File creation mask of the current process
+fn current_umask() -> u32 {
+    let Some(pid) = crate::process::myproc() else {
+        return 0;
+    };
+    let proc_table = crate::process::manager::PROC_TABLE.lock();
+    proc_table.find_ref(pid).map(|proc| proc.umask).unwrap_or(0)
 }
 
 /// Get current process context (pagetable and cwd)
@@ -925,12 +874,6 @@ fn read_path_from_user(pagetable: usize, ptr: usize) -> Result<alloc::string::St
         .map_err(|_| KernelError::InvalidArgument)
 }
 
-/// Read and resolve a path from user space
-fn read_and_resolve_path(pagetable: usize, ptr: usize, cwd: &Option<alloc::string::String>) -> Result<alloc::string::String, KernelError> {
-    let path_str = read_path_from_user(pagetable, ptr)?;
-    Ok(resolve_path(&path_str, cwd))
-}
-
 /// Convert VFS FileAttr to POSIX stat structure
 fn file_attr_to_stat(attr: &crate::vfs::types::FileAttr) -> crate::posix::stat {
     crate::posix::stat {
@@ -953,6 +896,7 @@ fn file_attr_to_stat(attr: &crate::vfs::types::FileAttr) -> crate::posix::stat {
     }
 }
 
+
 /// Get list of filesystem syscalls supported by this module
 pub fn get_supported_syscalls() -> alloc::vec::Vec<u32> {
     alloc::vec![
@@ -976,5 +920,19 @@ pub fn get_supported_syscalls() -> alloc::vec::Vec<u32> {
         0x7011, // lstat
         0x7012, // access
         0x7013, // readdir/getdents
+        0x7014, // chroot
+        0x7015, // mkdirat
+        0x7016, // unlinkat
+        0x7017, // renameat2
+        0x7018, // linkat
+        0x7019, // symlinkat
+        0x701A, // readlinkat
+        0x701B, // fchmodat
+        0x701C, // fchownat
+        0x701D, // fstatat
+        0x701E, // faccessat
+        0x701F, // fsync
+        0x7020, // fdatasync
+        0x7021, // sync
     ]
 }
@@ -10,6 +10,7 @@ pub mod journaling_handlers;
 pub mod service;
 pub mod types;
 pub mod dispatch;
+pub mod file_io;
 
 /// 文件系统系统调用处理器
 pub struct FsSyscallHandler {
@@ -282,10 +282,21 @@ impl SyscallService for FilesystemService {
             0x700B => FilesystemOperation::ChangeMode, // fchmod
             0x700C => FilesystemOperation::ChangeOwner,
             0x700D => FilesystemOperation::ChangeOwner, // fchown
+            0x700E => FilesystemOperation::ChangeOwner, // lchown
             0x700F => FilesystemOperation::SetUmask,
             0x7010 => FilesystemOperation::Stat,
             0x7011 => FilesystemOperation::Lstat,
             0x7012 => FilesystemOperation::Access,
+            0x7015 => FilesystemOperation::MakeDirectory, // mkdirat
+            0x7016 => FilesystemOperation::Unlink, // unlinkat
+            0x7017 => FilesystemOperation::Rename, // renameat2
+            0x7018 => FilesystemOperation::Link, // linkat
+            0x7019 => FilesystemOperation::Symlink, // symlinkat
+            0x701A => FilesystemOperation::Readlink, // readlinkat
+            0x701B => FilesystemOperation::ChangeMode, // fchmodat
+            0x701C => FilesystemOperation::ChangeOwner, // fchownat
+            0x701D => FilesystemOperation::Stat, // fstatat
+            0x701E => FilesystemOperation::Access, // faccessat
             _ => FilesystemOperation::Mount, // Default for unsupported
         };
         self.update_stats(operation);
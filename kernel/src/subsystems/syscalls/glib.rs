@@ -183,32 +183,30 @@ pub fn get_signalfd_instance(idx: usize) -> Option<&'static mut SignalfdInstance
 /// Send signal to all matching signalfd instances for a process
 pub fn deliver_signal_to_signalfd(pid: usize, sig: crate::ipc::signal::Signal, info: crate::ipc::signal::SigInfo) -> bool {
     // Find all signalfd file descriptors for this process
-    let proc_table = crate::process::manager::PROC_TABLE.lock();
-    if let Some(proc) = proc_table.find_ref(pid as crate::process::Pid) {
-        let mut delivered = false;
-
-        // Check all file descriptors for signalfd instances
-        for fd in 0..crate::process::NOFILE as usize {
-            if let Some(file_idx) = crate::process::fdlookup(fd as i32) {
-                let file_table = crate::fs::file::FILE_TABLE.lock();
-                if let Some(file) = file_table.get(file_idx) {
-                    if file.ftype == crate::fs::file::FileType::Signalfd {
-                        if let Some(signalfd_idx) = file.signalfd_instance {
-                            if let Some(signalfd) = get_signalfd_instance(signalfd_idx) {
-                                if signalfd.enqueue_signal(sig, info) {
-                                    delivered = true;
-                                }
-                            }
+    let files: Vec<usize> = {
+        let proc_table = crate::process::manager::PROC_TABLE.lock();
+        match proc_table.find_ref(pid as crate::process::Pid) {
+            Some(proc) => proc.ofile.lock().iter().map(|(_, file_idx)| file_idx).collect(),
+            None => return false,
+        }
+    };
+
+    let mut delivered = false;
+    let file_table = crate::fs::file::FILE_TABLE.lock();
+    for file_idx in files {
+        if let Some(file) = file_table.get(file_idx) {
+            if file.ftype == crate::fs::file::FileType::Signalfd {
+                if let Some(signalfd_idx) = file.signalfd_instance {
+                    if let Some(signalfd) = get_signalfd_instance(signalfd_idx) {
+                        if signalfd.enqueue_signal(sig, info) {
+                            delivered = true;
                         }
                     }
                 }
             }
         }
-
-        delivered
-    } else {
-        false
     }
+    delivered
 }
 
 // ============================================================================
@@ -845,15 +843,7 @@ impl MemFdInstance {
 
         // Ensure pages are allocated
         let required_pages = (offset + buf.len() + crate::subsystems::mm::PAGE_SIZE - 1) / crate::subsystems::mm::PAGE_SIZE;
-        while self.pages.len() < required_pages {
-            let page = unsafe { crate::subsystems::mm::kalloc() };
-            if page.is_null() {
-                return Err(SyscallError::OutOfMemory);
-            }
-            unsafe { core::ptr::write_bytes(page, 0, crate::subsystems::mm::PAGE_SIZE); }
-            let page_addr = page as usize;
-            self.pages.push(page_addr);
-        }
+        self.populate(required_pages)?;
 
         let mut bytes_written = 0;
         while bytes_written < buf.len() {
@@ -921,11 +911,83 @@ impl MemFdInstance {
             return Err(SyscallError::PermissionDenied);
         }
 
+        if new_size > self.max_size {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        // Pages past the end go; whoever still maps them keeps them. The
+        // rest of the last page is zeroed so growing again reads zeros.
+        let keep = new_size.div_ceil(crate::subsystems::mm::PAGE_SIZE);
+        for page_addr in self.pages.drain(keep.min(self.pages.len())..) {
+            crate::subsystems::mm::vm::page_ref_dec(page_addr);
+        }
+        let tail = new_size % crate::subsystems::mm::PAGE_SIZE;
+        if tail != 0 && keep <= self.pages.len() {
+            unsafe {
+                let page_ptr = crate::subsystems::mm::vm::phys_to_kernel_ptr(self.pages[keep - 1]);
+                core::ptr::write_bytes(page_ptr.add(tail), 0, crate::subsystems::mm::PAGE_SIZE - tail);
+            }
+        }
+
         // Update size
         self.size = new_size;
         Ok(())
     }
 
+    /// Allocate zeroed pages until the file has `count`
+    ///
+    /// Each page holds a reference for the file, so mappings and display
+    /// pools that take their own can outlive it.
+    fn populate(&mut self, count: usize) -> Result<(), SyscallError> {
+        while self.pages.len() < count {
+            let page = unsafe { crate::subsystems::mm::kalloc() };
+            if page.is_null() {
+                return Err(SyscallError::OutOfMemory);
+            }
+            unsafe { core::ptr::write_bytes(page, 0, crate::subsystems::mm::PAGE_SIZE); }
+            let page_addr = page as usize;
+            crate::subsystems::mm::vm::page_ref_inc(page_addr);
+            self.pages.push(page_addr);
+        }
+        Ok(())
+    }
+
+    /// Physical pages backing `len` bytes from `offset`, which must be page
+    /// aligned and within the file's last page
+    ///
+    /// Missing pages are allocated. The caller takes its own reference on
+    /// any page it keeps.
+    pub fn frames(&mut self, offset: usize, len: usize) -> Result<Vec<usize>, SyscallError> {
+        let end = offset.checked_add(len).ok_or(SyscallError::InvalidArgument)?;
+        if !offset.is_multiple_of(crate::subsystems::mm::PAGE_SIZE) || len == 0 || end > self.size.next_multiple_of(crate::subsystems::mm::PAGE_SIZE) {
+            return Err(SyscallError::InvalidArgument);
+        }
+        let first = offset / crate::subsystems::mm::PAGE_SIZE;
+        let last = end.div_ceil(crate::subsystems::mm::PAGE_SIZE);
+        self.populate(last)?;
+        Ok(self.pages[first..last].to_vec())
+    }
+
+    /// Map `len` bytes from `offset` at `va`, shared with the file
+    pub fn mmap(
+        &mut self,
+        pagetable: *mut crate::subsystems::mm::vm::PageTable,
+        va: usize,
+        offset: usize,
+        len: usize,
+        perm: usize,
+    ) -> Result<(), SyscallError> {
+        if perm & crate::subsystems::mm::vm::flags::PTE_W != 0 && (self.seals & fcntl_seals::F_SEAL_WRITE) != 0 {
+            return Err(SyscallError::PermissionDenied);
+        }
+        for (i, frame) in self.frames(offset, len)?.into_iter().enumerate() {
+            unsafe { crate::subsystems::mm::vm::map_page(pagetable, va + i * crate::subsystems::mm::PAGE_SIZE, frame, perm) }
+                .map_err(|_| SyscallError::OutOfMemory)?;
+            crate::subsystems::mm::vm::page_ref_inc(frame);
+        }
+        Ok(())
+    }
+
     /// Increment reference count
     pub fn inc_ref(&self) {
         self.ref_count.fetch_add(1, Ordering::SeqCst);
@@ -949,10 +1011,10 @@ impl MemFdInstance {
 
 impl Drop for MemFdInstance {
     fn drop(&mut self) {
-        // Free all allocated pages
+        // Drop the file's references; pages still mapped stay until unmapped
         for page_addr in &self.pages {
             if *page_addr != 0 {
-                unsafe { crate::subsystems::mm::kfree(*page_addr as *mut u8) };
+                crate::subsystems::mm::vm::page_ref_dec(*page_addr);
             }
         }
         self.pages.clear();
@@ -1013,6 +1075,8 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
         0xB00A => sys_inotify_init1(args),  // inotify_init1
         0xB00B => sys_inotify_add_watch(args), // inotify_add_watch
         0xB00C => sys_inotify_rm_watch(args), // inotify_rm_watch
+        #[cfg(feature = "graphics_subsystem")]
+        0xB00D => crate::graphics::display::sys_display_connect(args), // display_connect
         // Test syscall for inotify (temporary)
         0xFFFF => {
             // Simple test: create inotify instance and add a watch
@@ -556,14 +556,7 @@ pub fn handle_chmod(args: &[u64]) -> Result<u64, KernelError> {
     }
 
     // Update file permissions
-    let file_mode = crate::vfs::FileMode::new((attr.mode.0 & 0o170000) | (mode & 0o7777));
-    let mut new_attr = attr;
-    new_attr.mode = file_mode;
-
-    let vfs_file = vfs.open(&abs_path, crate::posix::O_RDWR as u32)
-        .map_err(|_| KernelError::PermissionDenied)?;
-
-    vfs_file.set_attr(&new_attr).map_err(|_| KernelError::PermissionDenied)?;
+    vfs.chmod(&abs_path, mode & 0o7777).map_err(|_| KernelError::PermissionDenied)?;
 
     Ok(0)
 }
@@ -608,21 +601,13 @@ pub fn handle_chown(args: &[u64]) -> Result<u64, KernelError> {
     let (pagetable, cwd_path) = get_process_context()?;
     let abs_path = read_and_resolve_path(pagetable as usize, pathname_ptr, &cwd_path)?;
 
-    // Get and update attributes
-    let vfs = crate::vfs::vfs();
-    let mut attr = vfs.stat(&abs_path).map_err(|_| KernelError::NotFound)?;
-
-    if uid != u32::MAX {
-        attr.uid = uid;
-    }
-    if gid != u32::MAX {
-        attr.gid = gid;
-    }
-
-    let vfs_file = vfs.open(&abs_path, crate::posix::O_RDWR as u32)
-        .map_err(|_| KernelError::PermissionDenied)?;
-
-    vfs_file.set_attr(&attr).map_err(|_| KernelError::PermissionDenied)?;
+    // u32::MAX (-1) leaves the id unchanged
+    let uid = (uid != u32::MAX).then_some(uid);
+    let gid = (gid != u32::MAX).then_some(gid);
+    crate::vfs::vfs().chown(&abs_path, uid, gid).map_err(|e| match e {
+        crate::vfs::VfsError::NotFound => KernelError::NotFound,
+        _ => KernelError::PermissionDenied,
+    })?;
 
     Ok(0)
 }
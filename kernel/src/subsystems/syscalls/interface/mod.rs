@@ -318,4 +318,16 @@ pub mod syscall_numbers {
     pub const SYS_CONNECT: u32 = 0x4002;
     pub const SYS_LISTEN: u32 = 0x4003;
     pub const SYS_ACCEPT: u32 = 0x4004;
+    pub const SYS_SEND: u32 = 0x4005;
+    pub const SYS_RECV: u32 = 0x4006;
+    pub const SYS_SENDTO: u32 = 0x4007;
+    pub const SYS_RECVFROM: u32 = 0x4008;
+    pub const SYS_SHUTDOWN: u32 = 0x4009;
+    pub const SYS_GETSOCKNAME: u32 = 0x400A;
+    pub const SYS_GETPEERNAME: u32 = 0x400B;
+    pub const SYS_SETSOCKOPT: u32 = 0x400C;
+    pub const SYS_GETSOCKOPT: u32 = 0x400D;
+    pub const SYS_SENDMSG: u32 = 0x400E;
+    pub const SYS_RECVMSG: u32 = 0x400F;
+    pub const SYS_SOCKETPAIR: u32 = 0x4010;
 }
\ No newline at end of file
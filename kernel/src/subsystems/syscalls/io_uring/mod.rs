@@ -0,0 +1,1057 @@
+//! io_uring asynchronous I/O
+//!
+//! `io_uring_setup` creates an instance and returns a file for it. Userspace
+//! maps the shared rings through that file (`IORING_OFF_SQ_RING`,
+//! `IORING_OFF_CQ_RING`, `IORING_OFF_SQES`), queues SQEs by advancing the SQ
+//! tail and reaps CQEs from the CQ ring, with `io_uring_enter` to submit and
+//! wait. Layouts and constants follow Linux, so liburing-style code works
+//! unchanged.
+//!
+//! SQEs are copied out of the ring at submission. Those linked with
+//! `IOSQE_IO_LINK`/`IOSQE_IO_HARDLINK` form a chain that is issued strictly
+//! in order; a failed link cancels the rest of its chain with `-ECANCELED`.
+//! A request that cannot complete yet (a pipe or socket that is not ready,
+//! a poll or timeout) stays queued and is retried whenever the ring is
+//! entered or, with `IORING_SETUP_SQPOLL`, by the instance's SQ thread.
+//!
+//! Requests run on behalf of the process that created the ring: its
+//! descriptors, working directory and address space, whichever process
+//! happens to enter the ring.
+
+extern crate alloc;
+
+mod ops;
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use super::common::{extract_args, SyscallError, SyscallResult};
+use crate::process::Pid;
+use crate::subsystems::mm::vm::{copyin, copyout, page_ref_dec, page_ref_inc, PageTable, PAGE_SIZE};
+use crate::subsystems::mm::kalloc;
+use crate::subsystems::mm::uaccess::{UserPtr, UserSpace};
+use crate::subsystems::sync::Mutex;
+
+// io_uring_setup flags
+pub const IORING_SETUP_IOPOLL: u32 = 1 << 0;
+pub const IORING_SETUP_SQPOLL: u32 = 1 << 1;
+pub const IORING_SETUP_SQ_AFF: u32 = 1 << 2;
+pub const IORING_SETUP_CQSIZE: u32 = 1 << 3;
+pub const IORING_SETUP_CLAMP: u32 = 1 << 4;
+
+const SETUP_FLAGS: u32 =
+    IORING_SETUP_SQPOLL | IORING_SETUP_SQ_AFF | IORING_SETUP_CQSIZE | IORING_SETUP_CLAMP;
+
+// io_uring_params.features
+pub const IORING_FEAT_SINGLE_MMAP: u32 = 1 << 0;
+pub const IORING_FEAT_NODROP: u32 = 1 << 1;
+pub const IORING_FEAT_SUBMIT_STABLE: u32 = 1 << 2;
+pub const IORING_FEAT_RW_CUR_POS: u32 = 1 << 3;
+
+// io_uring_enter flags
+pub const IORING_ENTER_GETEVENTS: u32 = 1 << 0;
+pub const IORING_ENTER_SQ_WAKEUP: u32 = 1 << 1;
+pub const IORING_ENTER_SQ_WAIT: u32 = 1 << 2;
+
+// SQ ring flags
+pub const IORING_SQ_NEED_WAKEUP: u32 = 1 << 0;
+pub const IORING_SQ_CQ_OVERFLOW: u32 = 1 << 1;
+
+// mmap offsets of the three regions
+pub const IORING_OFF_SQ_RING: u64 = 0;
+pub const IORING_OFF_CQ_RING: u64 = 0x800_0000;
+pub const IORING_OFF_SQES: u64 = 0x1000_0000;
+
+// io_uring_register opcodes
+pub const IORING_REGISTER_BUFFERS: u32 = 0;
+pub const IORING_UNREGISTER_BUFFERS: u32 = 1;
+pub const IORING_REGISTER_FILES: u32 = 2;
+pub const IORING_UNREGISTER_FILES: u32 = 3;
+
+// sqe.flags
+pub const IOSQE_FIXED_FILE: u8 = 1 << 0;
+pub const IOSQE_IO_DRAIN: u8 = 1 << 1;
+pub const IOSQE_IO_LINK: u8 = 1 << 2;
+pub const IOSQE_IO_HARDLINK: u8 = 1 << 3;
+
+// Opcodes
+pub const IORING_OP_NOP: u8 = 0;
+pub const IORING_OP_READV: u8 = 1;
+pub const IORING_OP_WRITEV: u8 = 2;
+pub const IORING_OP_FSYNC: u8 = 3;
+pub const IORING_OP_READ_FIXED: u8 = 4;
+pub const IORING_OP_WRITE_FIXED: u8 = 5;
+pub const IORING_OP_POLL_ADD: u8 = 6;
+pub const IORING_OP_POLL_REMOVE: u8 = 7;
+pub const IORING_OP_TIMEOUT: u8 = 11;
+pub const IORING_OP_TIMEOUT_REMOVE: u8 = 12;
+pub const IORING_OP_ACCEPT: u8 = 13;
+pub const IORING_OP_ASYNC_CANCEL: u8 = 14;
+pub const IORING_OP_CONNECT: u8 = 16;
+pub const IORING_OP_OPENAT: u8 = 18;
+pub const IORING_OP_CLOSE: u8 = 19;
+pub const IORING_OP_READ: u8 = 22;
+pub const IORING_OP_WRITE: u8 = 23;
+pub const IORING_OP_SEND: u8 = 26;
+pub const IORING_OP_RECV: u8 = 27;
+
+// timeout_flags
+pub const IORING_TIMEOUT_ABS: u32 = 1 << 0;
+
+/// Largest SQ ring; the CQ ring may be twice that
+pub const IORING_MAX_ENTRIES: u32 = 4096;
+pub const IORING_MAX_CQ_ENTRIES: u32 = 2 * IORING_MAX_ENTRIES;
+
+/// Most buffers or files that can be registered at once
+const IORING_MAX_REGISTERED: usize = 1024;
+/// Largest registered buffer
+const IORING_MAX_BUFFER: u64 = 1 << 30;
+
+/// How long an idle SQ thread polls before it wants a wakeup
+const SQ_THREAD_IDLE_MS: u64 = 1000;
+
+/// `struct io_uring_sqe`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IoUringSqe {
+    pub opcode: u8,
+    pub flags: u8,
+    pub ioprio: u16,
+    pub fd: i32,
+    /// File offset, or `addr2`
+    pub off: u64,
+    pub addr: u64,
+    pub len: u32,
+    /// rw_flags, fsync_flags, poll32_events, timeout_flags, open_flags, ...
+    pub op_flags: u32,
+    pub user_data: u64,
+    pub buf_index: u16,
+    pub personality: u16,
+    pub splice_fd_in: i32,
+    pub pad: [u64; 2],
+}
+
+/// `struct io_uring_cqe`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoUringCqe {
+    pub user_data: u64,
+    pub res: i32,
+    pub flags: u32,
+}
+
+/// `struct io_sqring_offsets`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoSqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub flags: u32,
+    pub dropped: u32,
+    pub array: u32,
+    pub resv1: u32,
+    pub resv2: u64,
+}
+
+/// `struct io_cqring_offsets`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoCqringOffsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub overflow: u32,
+    pub cqes: u32,
+    pub flags: u32,
+    pub resv1: u32,
+    pub resv2: u64,
+}
+
+/// `struct io_uring_params`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IoUringParams {
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+    pub flags: u32,
+    pub sq_thread_cpu: u32,
+    pub sq_thread_idle: u32,
+    pub features: u32,
+    pub wq_fd: u32,
+    pub resv: [u32; 3],
+    pub sq_off: IoSqringOffsets,
+    pub cq_off: IoCqringOffsets,
+}
+
+/// `struct iovec`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Iovec {
+    base: u64,
+    len: u64,
+}
+
+/// Where the ring indices and arrays live in the shared ring region
+///
+/// The SQ and CQ rings share one region (`IORING_FEAT_SINGLE_MMAP`): the
+/// indices, then the CQEs, then the SQ index array. Every field is
+/// naturally aligned, so none of them straddles a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    sq_entries: u32,
+    cq_entries: u32,
+}
+
+impl Layout {
+    const SQ_HEAD: usize = 0;
+    const SQ_TAIL: usize = 4;
+    const SQ_MASK: usize = 8;
+    const SQ_ENTRIES: usize = 12;
+    const SQ_FLAGS: usize = 16;
+    const SQ_DROPPED: usize = 20;
+    const CQ_HEAD: usize = 24;
+    const CQ_TAIL: usize = 28;
+    const CQ_MASK: usize = 32;
+    const CQ_ENTRIES: usize = 36;
+    const CQ_OVERFLOW: usize = 40;
+    const CQ_FLAGS: usize = 44;
+    const CQES: usize = 64;
+
+    /// Ring sizes for `entries` SQEs, as Linux rounds and clamps them
+    fn new(entries: u32, flags: u32, cq_entries: u32) -> Result<Self, SyscallError> {
+        let clamp = |n: u32, max: u32| match n {
+            0 => Err(SyscallError::InvalidArgument),
+            n if n > max && flags & IORING_SETUP_CLAMP == 0 => Err(SyscallError::InvalidArgument),
+            n => Ok(n.min(max).next_power_of_two()),
+        };
+        let sq_entries = clamp(entries, IORING_MAX_ENTRIES)?;
+        let cq_entries = if flags & IORING_SETUP_CQSIZE != 0 {
+            let cq_entries = clamp(cq_entries, IORING_MAX_CQ_ENTRIES)?;
+            if cq_entries < sq_entries {
+                return Err(SyscallError::InvalidArgument);
+            }
+            cq_entries
+        } else {
+            2 * sq_entries
+        };
+        Ok(Self { sq_entries, cq_entries })
+    }
+
+    fn sq_array(&self) -> usize {
+        Self::CQES + self.cq_entries as usize * core::mem::size_of::<IoUringCqe>()
+    }
+
+    fn ring_size(&self) -> usize {
+        self.sq_array() + self.sq_entries as usize * core::mem::size_of::<u32>()
+    }
+
+    fn sqes_size(&self) -> usize {
+        self.sq_entries as usize * core::mem::size_of::<IoUringSqe>()
+    }
+
+    fn sq_offsets(&self) -> IoSqringOffsets {
+        IoSqringOffsets {
+            head: Self::SQ_HEAD as u32,
+            tail: Self::SQ_TAIL as u32,
+            ring_mask: Self::SQ_MASK as u32,
+            ring_entries: Self::SQ_ENTRIES as u32,
+            flags: Self::SQ_FLAGS as u32,
+            dropped: Self::SQ_DROPPED as u32,
+            array: self.sq_array() as u32,
+            ..Default::default()
+        }
+    }
+
+    fn cq_offsets(&self) -> IoCqringOffsets {
+        IoCqringOffsets {
+            head: Self::CQ_HEAD as u32,
+            tail: Self::CQ_TAIL as u32,
+            ring_mask: Self::CQ_MASK as u32,
+            ring_entries: Self::CQ_ENTRIES as u32,
+            overflow: Self::CQ_OVERFLOW as u32,
+            cqes: Self::CQES as u32,
+            flags: Self::CQ_FLAGS as u32,
+            ..Default::default()
+        }
+    }
+}
+
+/// Frames shared between the kernel and the ring's user mappings
+///
+/// The ring holds one reference to each frame and every user mapping
+/// another, so a frame outlives whichever lets go first.
+struct RingPages {
+    frames: Vec<usize>,
+}
+
+impl RingPages {
+    fn alloc(size: usize) -> Result<Self, SyscallError> {
+        let mut pages = Self { frames: Vec::new() };
+        for _ in 0..size.div_ceil(PAGE_SIZE) {
+            let frame = kalloc();
+            if frame.is_null() {
+                return Err(SyscallError::OutOfMemory);
+            }
+            unsafe { core::ptr::write_bytes(frame, 0, PAGE_SIZE) };
+            page_ref_inc(frame as usize);
+            pages.frames.push(frame as usize);
+        }
+        Ok(pages)
+    }
+
+    fn len(&self) -> usize {
+        self.frames.len() * PAGE_SIZE
+    }
+
+    fn ptr(&self, off: usize) -> *mut u8 {
+        (self.frames[off / PAGE_SIZE] + off % PAGE_SIZE) as *mut u8
+    }
+
+    /// A ring index shared with userspace
+    fn atomic(&self, off: usize) -> &AtomicU32 {
+        unsafe { &*(self.ptr(off) as *const AtomicU32) }
+    }
+
+    fn read<T: Copy>(&self, off: usize) -> T {
+        unsafe { core::ptr::read_volatile(self.ptr(off) as *const T) }
+    }
+
+    fn write<T: Copy>(&self, off: usize, value: T) {
+        unsafe { core::ptr::write_volatile(self.ptr(off) as *mut T, value) }
+    }
+}
+
+impl Drop for RingPages {
+    fn drop(&mut self) {
+        for &frame in &self.frames {
+            page_ref_dec(frame);
+        }
+    }
+}
+
+/// A `IORING_OP_TIMEOUT` once it has been armed
+#[derive(Debug, Clone, Copy)]
+struct Timer {
+    /// `uptime_ms` at which it expires with `-ETIME`
+    deadline: u64,
+    /// Completion count at which it fires early, if any
+    target: Option<u64>,
+}
+
+/// A submitted SQE
+struct Request {
+    sqe: IoUringSqe,
+    timer: Option<Timer>,
+}
+
+/// SQEs linked together, issued strictly in order
+struct Chain {
+    requests: VecDeque<Request>,
+}
+
+impl Chain {
+    fn drains(&self) -> bool {
+        self.requests.front().is_some_and(|req| req.sqe.flags & IOSQE_IO_DRAIN != 0)
+    }
+}
+
+/// Outcome of trying to issue a request
+enum Issue {
+    /// Complete with this result
+    Done(i32),
+    /// Not ready yet; try again later
+    Again,
+}
+
+/// Split submitted SQEs into chains at each SQE without a link flag
+///
+/// A link flag on the last SQE just ends its chain.
+fn chains(sqes: impl IntoIterator<Item = IoUringSqe>) -> Vec<Chain> {
+    let mut chains = Vec::new();
+    let mut current = VecDeque::new();
+    for sqe in sqes {
+        let linked = sqe.flags & (IOSQE_IO_LINK | IOSQE_IO_HARDLINK) != 0;
+        current.push_back(Request { sqe, timer: None });
+        if !linked {
+            chains.push(Chain { requests: core::mem::take(&mut current) });
+        }
+    }
+    if !current.is_empty() {
+        chains.push(Chain { requests: current });
+    }
+    chains
+}
+
+/// Whether a result cancels the rest of the chain after `sqe`
+///
+/// Errors and short transfers break an `IOSQE_IO_LINK`; a timeout
+/// expiring is its normal outcome. `IOSQE_IO_HARDLINK` never breaks.
+fn breaks_chain(sqe: &IoUringSqe, res: i32) -> bool {
+    if sqe.flags & IOSQE_IO_HARDLINK != 0 {
+        return false;
+    }
+    let short = matches!(
+        sqe.opcode,
+        IORING_OP_READ | IORING_OP_WRITE | IORING_OP_READ_FIXED | IORING_OP_WRITE_FIXED
+            | IORING_OP_SEND | IORING_OP_RECV
+    ) && res >= 0
+        && (res as u32) < sqe.len;
+    let expired = sqe.opcode == IORING_OP_TIMEOUT && res == -crate::reliability::errno::ETIME;
+    (res < 0 && !expired) || short
+}
+
+/// Mutable state, never held across issuing a request
+struct RingState {
+    pending: VecDeque<Chain>,
+    /// CQEs waiting for room in the CQ ring (`IORING_FEAT_NODROP`)
+    overflow: VecDeque<IoUringCqe>,
+    buffers: Vec<Iovec>,
+    /// File table indices of registered files, each holding a reference
+    files: Vec<Option<usize>>,
+    /// CQEs posted so far, for `IORING_OP_TIMEOUT` completion counts
+    completions: u64,
+}
+
+/// One io_uring instance
+pub struct IoUring {
+    owner: Pid,
+    flags: u32,
+    layout: Layout,
+    /// The owner uses the Linux personality: open flags are Linux values
+    linux_abi: bool,
+    rings: RingPages,
+    sqes: RingPages,
+    state: Mutex<RingState>,
+    /// Serializes submitting and issuing between the SQ thread and callers
+    issue_lock: Mutex<()>,
+    wakeup: AtomicBool,
+    dead: AtomicBool,
+}
+
+impl IoUring {
+    fn new(owner: Pid, flags: u32, layout: Layout, linux_abi: bool) -> Result<Self, SyscallError> {
+        let ring = Self {
+            owner,
+            flags,
+            layout,
+            linux_abi,
+            rings: RingPages::alloc(layout.ring_size())?,
+            sqes: RingPages::alloc(layout.sqes_size())?,
+            state: Mutex::new(RingState {
+                pending: VecDeque::new(),
+                overflow: VecDeque::new(),
+                buffers: Vec::new(),
+                files: Vec::new(),
+                completions: 0,
+            }),
+            issue_lock: Mutex::new(()),
+            wakeup: AtomicBool::new(false),
+            dead: AtomicBool::new(false),
+        };
+        ring.rings.write(Layout::SQ_MASK, layout.sq_entries - 1);
+        ring.rings.write(Layout::SQ_ENTRIES, layout.sq_entries);
+        ring.rings.write(Layout::CQ_MASK, layout.cq_entries - 1);
+        ring.rings.write(Layout::CQ_ENTRIES, layout.cq_entries);
+        Ok(ring)
+    }
+
+    /// Map one of the ring regions at `va` in `pagetable`
+    pub fn mmap(
+        &self,
+        pagetable: *mut PageTable,
+        va: usize,
+        offset: u64,
+        len: usize,
+        perm: usize,
+    ) -> Result<(), SyscallError> {
+        let region = match offset {
+            IORING_OFF_SQ_RING | IORING_OFF_CQ_RING => &self.rings,
+            IORING_OFF_SQES => &self.sqes,
+            _ => return Err(SyscallError::InvalidArgument),
+        };
+        if len > region.len() {
+            return Err(SyscallError::InvalidArgument);
+        }
+        for (i, &frame) in region.frames.iter().take(len / PAGE_SIZE).enumerate() {
+            unsafe { crate::subsystems::mm::vm::map_page(pagetable, va + i * PAGE_SIZE, frame, perm) }
+                .map_err(|_| SyscallError::OutOfMemory)?;
+            page_ref_inc(frame);
+        }
+        Ok(())
+    }
+
+    /// Stop the instance once its file is closed
+    ///
+    /// Returns the registered files for the caller to close; the SQ thread
+    /// notices on its next pass and exits.
+    pub fn shutdown(&self) -> Vec<usize> {
+        self.dead.store(true, Ordering::Release);
+        let mut state = self.state.lock();
+        state.pending.clear();
+        core::mem::take(&mut state.files).into_iter().flatten().collect()
+    }
+
+    /// Poll events: readable with CQEs waiting, writable with SQ room
+    pub fn poll(&self) -> i16 {
+        let mut events = 0;
+        if self.cq_ready() > 0 {
+            events |= crate::posix::POLLIN;
+        }
+        if self.sq_ready() < self.layout.sq_entries {
+            events |= crate::posix::POLLOUT;
+        }
+        events
+    }
+
+    /// SQEs queued by userspace and not yet consumed
+    fn sq_ready(&self) -> u32 {
+        let tail = self.rings.atomic(Layout::SQ_TAIL).load(Ordering::Acquire);
+        let head = self.rings.atomic(Layout::SQ_HEAD).load(Ordering::Relaxed);
+        tail.wrapping_sub(head).min(self.layout.sq_entries)
+    }
+
+    /// CQEs posted and not yet reaped by userspace
+    fn cq_ready(&self) -> u32 {
+        let tail = self.rings.atomic(Layout::CQ_TAIL).load(Ordering::Relaxed);
+        let head = self.rings.atomic(Layout::CQ_HEAD).load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    fn set_sq_flag(&self, flag: u32, on: bool) {
+        let flags = self.rings.atomic(Layout::SQ_FLAGS);
+        if on {
+            flags.fetch_or(flag, Ordering::AcqRel);
+        } else {
+            flags.fetch_and(!flag, Ordering::AcqRel);
+        }
+    }
+
+    /// Consume up to `to_submit` SQEs and issue them; returns how many
+    /// were consumed
+    fn submit(&self, to_submit: u32) -> Result<u32, SyscallError> {
+        let _issuing = self.issue_lock.lock();
+        // With completions already backed up, stop taking more work
+        if self.state.lock().overflow.len() >= self.layout.cq_entries as usize {
+            return Err(SyscallError::WouldBlock);
+        }
+
+        let head = self.rings.atomic(Layout::SQ_HEAD).load(Ordering::Relaxed);
+        let count = self.sq_ready().min(to_submit);
+        let mut sqes = Vec::with_capacity(count as usize);
+        let mut dropped = 0;
+        for i in 0..count {
+            let slot = head.wrapping_add(i) & (self.layout.sq_entries - 1);
+            let index: u32 = self.rings.read(self.layout.sq_array() + slot as usize * 4);
+            if index >= self.layout.sq_entries {
+                dropped += 1;
+                continue;
+            }
+            sqes.push(self.sqes.read::<IoUringSqe>(index as usize * core::mem::size_of::<IoUringSqe>()));
+        }
+        self.rings.atomic(Layout::SQ_HEAD).store(head.wrapping_add(count), Ordering::Release);
+        if dropped > 0 {
+            self.rings.atomic(Layout::SQ_DROPPED).fetch_add(dropped, Ordering::Relaxed);
+        }
+
+        self.state.lock().pending.extend(chains(sqes));
+        self.run();
+        Ok(count)
+    }
+
+    /// Retry whatever is still pending
+    fn reap(&self) {
+        let _issuing = self.issue_lock.lock();
+        self.run();
+    }
+
+    /// Issue pending chains in submission order; the caller holds
+    /// `issue_lock`
+    fn run(&self) {
+        let mut queue: Vec<Option<Chain>> = {
+            let mut state = self.state.lock();
+            self.flush_overflow(&mut state);
+            core::mem::take(&mut state.pending).into_iter().map(Some).collect()
+        };
+        for i in 0..queue.len() {
+            let Some(mut chain) = queue[i].take() else { continue };
+            // IOSQE_IO_DRAIN waits for everything submitted before it
+            let blocked = chain.drains() && queue[..i].iter().any(Option::is_some);
+            if blocked || !self.advance(&mut chain, &mut queue) {
+                queue[i] = Some(chain);
+            }
+        }
+        let mut state = self.state.lock();
+        if !self.dead.load(Ordering::Acquire) {
+            state.pending = queue.into_iter().flatten().collect();
+        }
+    }
+
+    /// Issue a chain until a request has to wait; true once it is done
+    fn advance(&self, chain: &mut Chain, queue: &mut [Option<Chain>]) -> bool {
+        while let Some(req) = chain.requests.front_mut() {
+            let res = match req.sqe.opcode {
+                IORING_OP_ASYNC_CANCEL | IORING_OP_POLL_REMOVE | IORING_OP_TIMEOUT_REMOVE => {
+                    self.cancel(&req.sqe, queue)
+                }
+                _ => match ops::issue(self, req) {
+                    Issue::Done(res) => res,
+                    Issue::Again => return false,
+                },
+            };
+            let Some(req) = chain.requests.pop_front() else { break };
+            self.complete(req.sqe.user_data, res);
+            if breaks_chain(&req.sqe, res) {
+                for rest in chain.requests.drain(..) {
+                    self.complete(rest.sqe.user_data, -crate::reliability::errno::ECANCELED);
+                }
+            }
+        }
+        true
+    }
+
+    /// Cancel the waiting request whose `user_data` is `sqe.addr`, along
+    /// with the rest of its chain
+    fn cancel(&self, sqe: &IoUringSqe, queue: &mut [Option<Chain>]) -> i32 {
+        let only = match sqe.opcode {
+            IORING_OP_POLL_REMOVE => Some(IORING_OP_POLL_ADD),
+            IORING_OP_TIMEOUT_REMOVE => Some(IORING_OP_TIMEOUT),
+            _ => None,
+        };
+        let found = queue.iter_mut().find(|slot| {
+            slot.as_ref().and_then(|chain| chain.requests.front()).is_some_and(|req| {
+                req.sqe.user_data == sqe.addr && only.is_none_or(|op| req.sqe.opcode == op)
+            })
+        });
+        match found.and_then(Option::take) {
+            Some(chain) => {
+                for req in chain.requests {
+                    self.complete(req.sqe.user_data, -crate::reliability::errno::ECANCELED);
+                }
+                0
+            }
+            None => -crate::reliability::errno::ENOENT,
+        }
+    }
+
+    /// Post a CQE, holding it back while the CQ ring is full
+    fn complete(&self, user_data: u64, res: i32) {
+        let mut state = self.state.lock();
+        state.completions += 1;
+        let cqe = IoUringCqe { user_data, res, flags: 0 };
+        if !state.overflow.is_empty() || !self.push_cqe(cqe) {
+            state.overflow.push_back(cqe);
+            self.set_sq_flag(IORING_SQ_CQ_OVERFLOW, true);
+        }
+    }
+
+    fn push_cqe(&self, cqe: IoUringCqe) -> bool {
+        if self.cq_ready() >= self.layout.cq_entries {
+            return false;
+        }
+        let tail = self.rings.atomic(Layout::CQ_TAIL).load(Ordering::Relaxed);
+        let slot = (tail & (self.layout.cq_entries - 1)) as usize;
+        self.rings.write(Layout::CQES + slot * core::mem::size_of::<IoUringCqe>(), cqe);
+        self.rings.atomic(Layout::CQ_TAIL).store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    fn flush_overflow(&self, state: &mut RingState) {
+        while let Some(&cqe) = state.overflow.front() {
+            if !self.push_cqe(cqe) {
+                return;
+            }
+            state.overflow.pop_front();
+        }
+        self.set_sq_flag(IORING_SQ_CQ_OVERFLOW, false);
+    }
+
+    /// Completions so far, for arming a timeout
+    fn completions(&self) -> u64 {
+        self.state.lock().completions
+    }
+
+    /// A registered file's table index
+    fn registered_file(&self, index: i32) -> Option<usize> {
+        let state = self.state.lock();
+        state.files.get(usize::try_from(index).ok()?).copied().flatten()
+    }
+
+    /// A registered buffer, as `(addr, len)`
+    fn registered_buffer(&self, index: u16) -> Option<(u64, u64)> {
+        let state = self.state.lock();
+        state.buffers.get(index as usize).map(|iov| (iov.base, iov.len))
+    }
+}
+
+/// Page table of `pid`
+fn pagetable_of(pid: Pid) -> Result<*mut PageTable, SyscallError> {
+    let table = crate::process::PROC_TABLE.lock();
+    let proc = table.find_ref(pid).ok_or(SyscallError::BadAddress)?;
+    Ok(proc.pagetable)
+}
+
+fn copy_from_user<T: Copy>(pagetable: *mut PageTable, src: u64) -> Result<T, SyscallError> {
+    Ok(UserPtr::new(src as usize).read_in(&UserSpace::of_pagetable(pagetable))?)
+}
+
+/// The io_uring instance behind a descriptor of the current process
+fn ring_of(fd: i32) -> Result<Arc<IoUring>, SyscallError> {
+    crate::process::fdlookup(fd)
+        .and_then(crate::fs::file::file_get_io_uring)
+        .ok_or(SyscallError::BadFileDescriptor)
+}
+
+/// Whether the current process has been killed or has a signal pending
+fn interrupted() -> bool {
+    let Some(pid) = crate::process::myproc() else { return false };
+    let table = crate::process::PROC_TABLE.lock();
+    table.find_ref(pid).is_some_and(|proc| {
+        proc.killed || proc.signals.as_ref().is_some_and(|signals| signals.has_pending())
+    })
+}
+
+/// io_uring_setup: create an instance and return its descriptor
+/// Arguments: [entries, params_ptr]
+pub fn sys_io_uring_setup(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 2)?;
+    let entries = args[0] as u32;
+    let params_ptr = args[1];
+
+    let pid = crate::process::myproc().ok_or(SyscallError::InvalidArgument)?;
+    let (pagetable, linux_abi) = {
+        let table = crate::process::PROC_TABLE.lock();
+        let proc = table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
+        (proc.pagetable, proc.personality == crate::process::Personality::Linux)
+    };
+    let mut params: IoUringParams = copy_from_user(pagetable, params_ptr)?;
+    if params.flags & !SETUP_FLAGS != 0 || params.resv != [0; 3] {
+        return Err(SyscallError::InvalidArgument);
+    }
+    if params.flags & IORING_SETUP_SQ_AFF != 0 && params.flags & IORING_SETUP_SQPOLL == 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let layout = Layout::new(entries, params.flags, params.cq_entries)?;
+    let ring = Arc::new(IoUring::new(pid, params.flags, layout, linux_abi)?);
+
+    params.sq_entries = layout.sq_entries;
+    params.cq_entries = layout.cq_entries;
+    params.features = IORING_FEAT_SINGLE_MMAP
+        | IORING_FEAT_NODROP
+        | IORING_FEAT_SUBMIT_STABLE
+        | IORING_FEAT_RW_CUR_POS;
+    params.sq_off = layout.sq_offsets();
+    params.cq_off = layout.cq_offsets();
+    unsafe {
+        copyout(
+            pagetable,
+            params_ptr as usize,
+            &params as *const IoUringParams as *const u8,
+            core::mem::size_of::<IoUringParams>(),
+        )
+    }
+    .map_err(|_| SyscallError::BadAddress)?;
+
+    let file_idx = crate::fs::file::file_io_uring_new(ring.clone()).ok_or(SyscallError::TooManyOpenFiles)?;
+    let Some(fd) = crate::process::fdalloc(file_idx) else {
+        crate::fs::file::file_close(file_idx);
+        return Err(SyscallError::TooManyOpenFiles);
+    };
+
+    // Started last: closing the file is what stops the thread
+    if params.flags & IORING_SETUP_SQPOLL != 0 {
+        let idle_ms = match params.sq_thread_idle {
+            0 => SQ_THREAD_IDLE_MS,
+            ms => ms as u64,
+        };
+        if let Err(e) = start_sq_thread(ring, idle_ms) {
+            let _ = super::fs::file_io::close(fd);
+            return Err(e);
+        }
+    }
+    Ok(fd as u64)
+}
+
+/// io_uring_enter: submit SQEs and/or wait for completions
+/// Arguments: [fd, to_submit, min_complete, flags, sig_ptr]
+pub fn sys_io_uring_enter(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 5)?;
+    let ring = ring_of(args[0] as i32)?;
+    let to_submit = args[1] as u32;
+    let min_complete = (args[2] as u32).min(ring.layout.cq_entries);
+    let flags = args[3] as u32;
+
+    if flags & !(IORING_ENTER_GETEVENTS | IORING_ENTER_SQ_WAKEUP | IORING_ENTER_SQ_WAIT) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    // Waiting with a temporary signal mask is not supported
+    if args[4] != 0 {
+        return Err(SyscallError::NotSupported);
+    }
+
+    let submitted = if ring.flags & IORING_SETUP_SQPOLL != 0 {
+        if flags & IORING_ENTER_SQ_WAKEUP != 0 {
+            ring.wakeup.store(true, Ordering::Release);
+        }
+        if flags & IORING_ENTER_SQ_WAIT != 0 {
+            while ring.sq_ready() >= ring.layout.sq_entries {
+                if interrupted() {
+                    return Err(SyscallError::Interrupted);
+                }
+                crate::process::yield_cpu();
+            }
+        }
+        to_submit.min(ring.layout.sq_entries)
+    } else if to_submit > 0 {
+        ring.submit(to_submit)?
+    } else {
+        0
+    };
+
+    if flags & IORING_ENTER_GETEVENTS != 0 {
+        loop {
+            ring.reap();
+            if ring.cq_ready() >= min_complete {
+                break;
+            }
+            if interrupted() {
+                return Err(SyscallError::Interrupted);
+            }
+            crate::process::yield_cpu();
+        }
+    }
+    Ok(submitted as u64)
+}
+
+/// io_uring_register: register buffers or files
+/// Arguments: [fd, opcode, arg_ptr, nr_args]
+pub fn sys_io_uring_register(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 4)?;
+    let ring = ring_of(args[0] as i32)?;
+    let opcode = args[1] as u32;
+    let arg = args[2];
+    let nr_args = args[3] as usize;
+
+    match opcode {
+        IORING_REGISTER_BUFFERS => register_buffers(&ring, arg, nr_args),
+        IORING_UNREGISTER_BUFFERS => {
+            let mut state = ring.state.lock();
+            if state.buffers.is_empty() {
+                return Err(SyscallError::InvalidArgument);
+            }
+            state.buffers.clear();
+            Ok(0)
+        }
+        IORING_REGISTER_FILES => register_files(&ring, arg, nr_args),
+        IORING_UNREGISTER_FILES => {
+            let files = core::mem::take(&mut ring.state.lock().files);
+            if files.is_empty() {
+                return Err(SyscallError::InvalidArgument);
+            }
+            for file_idx in files.into_iter().flatten() {
+                crate::fs::file::file_close(file_idx);
+            }
+            Ok(0)
+        }
+        _ => Err(SyscallError::InvalidArgument),
+    }
+}
+
+fn register_buffers(ring: &IoUring, arg: u64, nr_args: usize) -> SyscallResult {
+    if nr_args == 0 || nr_args > IORING_MAX_REGISTERED {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let pid = crate::process::myproc().ok_or(SyscallError::InvalidArgument)?;
+    let pagetable = pagetable_of(pid)?;
+    let mut buffers = Vec::with_capacity(nr_args);
+    for i in 0..nr_args {
+        let iov: Iovec = copy_from_user(pagetable, arg + (i * core::mem::size_of::<Iovec>()) as u64)?;
+        if iov.base == 0 || iov.len == 0 || iov.len > IORING_MAX_BUFFER {
+            return Err(SyscallError::InvalidArgument);
+        }
+        buffers.push(iov);
+    }
+
+    let mut state = ring.state.lock();
+    if !state.buffers.is_empty() {
+        return Err(SyscallError::WouldBlock);
+    }
+    state.buffers = buffers;
+    Ok(0)
+}
+
+fn register_files(ring: &IoUring, arg: u64, nr_args: usize) -> SyscallResult {
+    if nr_args == 0 || nr_args > IORING_MAX_REGISTERED {
+        return Err(SyscallError::InvalidArgument);
+    }
+    if !ring.state.lock().files.is_empty() {
+        return Err(SyscallError::WouldBlock);
+    }
+    let pid = crate::process::myproc().ok_or(SyscallError::InvalidArgument)?;
+    let pagetable = pagetable_of(pid)?;
+
+    let mut files = Vec::with_capacity(nr_args);
+    let release = |files: Vec<Option<usize>>| {
+        for file_idx in files.into_iter().flatten() {
+            crate::fs::file::file_close(file_idx);
+        }
+    };
+    for i in 0..nr_args {
+        let fd = match copy_from_user::<i32>(pagetable, arg + (i * 4) as u64) {
+            Ok(fd) => fd,
+            Err(e) => {
+                release(files);
+                return Err(e);
+            }
+        };
+        if fd == -1 {
+            files.push(None);
+            continue;
+        }
+        // A ring cannot hold a reference to a ring
+        let file_idx = crate::process::fdlookup(fd)
+            .filter(|&idx| crate::fs::file::file_get_io_uring(idx).is_none())
+            .and_then(crate::fs::file::file_dup);
+        match file_idx {
+            Some(idx) => files.push(Some(idx)),
+            None => {
+                release(files);
+                return Err(SyscallError::BadFileDescriptor);
+            }
+        }
+    }
+
+    let mut state = ring.state.lock();
+    if !state.files.is_empty() || ring.dead.load(Ordering::Acquire) {
+        drop(state);
+        release(files);
+        return Err(SyscallError::WouldBlock);
+    }
+    state.files = files;
+    Ok(0)
+}
+
+/// Start the kernel thread that polls the SQ ring (`IORING_SETUP_SQPOLL`)
+fn start_sq_thread(ring: Arc<IoUring>, idle_ms: u64) -> Result<(), SyscallError> {
+    let owner = ring.owner;
+    let arg = Arc::into_raw(Arc::new((ring, idle_ms)));
+    crate::process::thread::create_thread(
+        owner,
+        crate::process::thread::ThreadType::Kernel,
+        Some(sq_thread_main),
+        arg as *mut u8,
+    )
+    .map(|_| ())
+    .map_err(|_| {
+        drop(unsafe { Arc::from_raw(arg) });
+        SyscallError::OutOfMemory
+    })
+}
+
+unsafe extern "C" fn sq_thread_main(arg: *mut u8) -> *mut u8 {
+    let (ring, idle_ms) = {
+        let arg = unsafe { Arc::from_raw(arg as *const (Arc<IoUring>, u64)) };
+        (arg.0.clone(), arg.1)
+    };
+    let mut last_work = crate::subsystems::time::uptime_ms();
+    while !ring.dead.load(Ordering::Acquire) {
+        let queued = ring.sq_ready();
+        if queued > 0 {
+            let _ = ring.submit(queued);
+            last_work = crate::subsystems::time::uptime_ms();
+        } else {
+            ring.reap();
+        }
+
+        if crate::subsystems::time::uptime_ms().saturating_sub(last_work) >= idle_ms {
+            // Raise the flag before the final check so a submission
+            // racing with it is not missed
+            ring.set_sq_flag(IORING_SQ_NEED_WAKEUP, true);
+            while ring.sq_ready() == 0
+                && !ring.wakeup.swap(false, Ordering::AcqRel)
+                && !ring.dead.load(Ordering::Acquire)
+            {
+                crate::process::thread::thread_yield();
+            }
+            ring.set_sq_flag(IORING_SQ_NEED_WAKEUP, false);
+            last_work = crate::subsystems::time::uptime_ms();
+        }
+        crate::process::thread::thread_yield();
+    }
+    drop(ring);
+    crate::process::thread::thread_exit(core::ptr::null_mut())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqe(opcode: u8, flags: u8, len: u32) -> IoUringSqe {
+        IoUringSqe { opcode, flags, len, ..Default::default() }
+    }
+
+    #[test]
+    fn test_abi_sizes() {
+        assert_eq!(core::mem::size_of::<IoUringSqe>(), 64);
+        assert_eq!(core::mem::size_of::<IoUringCqe>(), 16);
+        assert_eq!(core::mem::size_of::<IoSqringOffsets>(), 40);
+        assert_eq!(core::mem::size_of::<IoCqringOffsets>(), 40);
+        assert_eq!(core::mem::size_of::<IoUringParams>(), 120);
+        assert_eq!(core::mem::offset_of!(IoUringSqe, user_data), 32);
+        assert_eq!(core::mem::offset_of!(IoUringSqe, buf_index), 40);
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = Layout::new(100, 0, 0).unwrap();
+        assert_eq!((layout.sq_entries, layout.cq_entries), (128, 256));
+        assert_eq!(layout.sq_array(), 64 + 256 * 16);
+        assert_eq!(layout.ring_size(), 64 + 256 * 16 + 128 * 4);
+        assert_eq!(layout.sqes_size(), 128 * 64);
+
+        let layout = Layout::new(8, IORING_SETUP_CQSIZE, 100).unwrap();
+        assert_eq!((layout.sq_entries, layout.cq_entries), (8, 128));
+        assert!(Layout::new(8, IORING_SETUP_CQSIZE, 4).is_err());
+
+        assert!(Layout::new(0, 0, 0).is_err());
+        assert!(Layout::new(IORING_MAX_ENTRIES + 1, 0, 0).is_err());
+        let layout = Layout::new(IORING_MAX_ENTRIES + 1, IORING_SETUP_CLAMP, 0).unwrap();
+        assert_eq!(layout.sq_entries, IORING_MAX_ENTRIES);
+
+        // Indices sit before the CQEs, which sit before the SQ array
+        let sq = layout.sq_offsets();
+        let cq = layout.cq_offsets();
+        assert!(cq.flags < cq.cqes);
+        assert_eq!(sq.array as usize, cq.cqes as usize + layout.cq_entries as usize * 16);
+    }
+
+    #[test]
+    fn test_chains() {
+        let sqes = [
+            sqe(IORING_OP_NOP, 0, 0),
+            sqe(IORING_OP_WRITE, IOSQE_IO_LINK, 4),
+            sqe(IORING_OP_FSYNC, IOSQE_IO_HARDLINK, 0),
+            sqe(IORING_OP_READ, 0, 4),
+            sqe(IORING_OP_NOP, IOSQE_IO_LINK, 0),
+        ];
+        let lens: Vec<usize> = chains(sqes).iter().map(|chain| chain.requests.len()).collect();
+        assert_eq!(lens, [1, 3, 1]);
+    }
+
+    #[test]
+    fn test_breaks_chain() {
+        use crate::reliability::errno::{EBADF, ETIME};
+
+        assert!(breaks_chain(&sqe(IORING_OP_READ, IOSQE_IO_LINK, 8), -EBADF));
+        assert!(breaks_chain(&sqe(IORING_OP_READ, IOSQE_IO_LINK, 8), 4));
+        assert!(!breaks_chain(&sqe(IORING_OP_READ, IOSQE_IO_LINK, 8), 8));
+        assert!(!breaks_chain(&sqe(IORING_OP_READ, IOSQE_IO_HARDLINK, 8), -EBADF));
+        assert!(!breaks_chain(&sqe(IORING_OP_FSYNC, IOSQE_IO_LINK, 0), 0));
+        assert!(!breaks_chain(&sqe(IORING_OP_TIMEOUT, IOSQE_IO_LINK, 1), -ETIME));
+    }
+}
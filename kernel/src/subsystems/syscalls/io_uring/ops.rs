@@ -0,0 +1,330 @@
+//! Issuing io_uring requests
+//!
+//! Each opcode maps onto the descriptor and socket code the synchronous
+//! syscalls use, run as the ring's owner. Pipes, sockets and devices are
+//! polled first and the request reports [`Issue::Again`] until they are
+//! ready, so nothing here sleeps except CONNECT, which waits out the
+//! handshake as connect(2) does.
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::*;
+use crate::fs::file::FileType;
+use crate::reliability::errno::{EAGAIN, EBADF, EFAULT, EINVAL, EMFILE, ETIME};
+use crate::subsystems::syscalls::common::syscall_error_to_errno;
+
+/// Largest transfer a single request moves through its kernel buffer
+const MAX_IO: usize = 1 << 20;
+
+/// Most iovecs in one READV/WRITEV
+const MAX_IOVECS: usize = 1024;
+
+/// Issue `req` on behalf of the ring's owner
+pub(super) fn issue(ring: &IoUring, req: &mut Request) -> Issue {
+    crate::process::with_proc(ring.owner, || {
+        let pagetable = match pagetable_of(ring.owner) {
+            Ok(pagetable) => pagetable,
+            Err(e) => return Issue::Done(-syscall_error_to_errno(e)),
+        };
+        let sqe = req.sqe;
+        match sqe.opcode {
+            IORING_OP_NOP => Issue::Done(0),
+            IORING_OP_READ | IORING_OP_READ_FIXED => {
+                if sqe.opcode == IORING_OP_READ_FIXED && !fixed_buffer_ok(ring, &sqe) {
+                    return Issue::Done(-EFAULT);
+                }
+                with_file(ring, &sqe, |idx| read(pagetable, idx, sqe.addr, sqe.len as usize, sqe.off))
+            }
+            IORING_OP_WRITE | IORING_OP_WRITE_FIXED => {
+                if sqe.opcode == IORING_OP_WRITE_FIXED && !fixed_buffer_ok(ring, &sqe) {
+                    return Issue::Done(-EFAULT);
+                }
+                with_file(ring, &sqe, |idx| write(pagetable, idx, sqe.addr, sqe.len as usize, sqe.off))
+            }
+            IORING_OP_READV => with_file(ring, &sqe, |idx| readv(pagetable, idx, &sqe)),
+            IORING_OP_WRITEV => with_file(ring, &sqe, |idx| writev(pagetable, idx, &sqe)),
+            IORING_OP_FSYNC => with_file(ring, &sqe, |idx| match crate::fs::file::file_fsync(idx) {
+                Ok(()) => Issue::Done(0),
+                Err(e) => Issue::Done(-syscall_error_to_errno(e.into())),
+            }),
+            IORING_OP_POLL_ADD => with_file(ring, &sqe, |idx| {
+                let events = sqe.op_flags as u16 as i16 | crate::posix::POLLERR | crate::posix::POLLHUP;
+                match crate::fs::file::file_poll(idx) & events {
+                    0 => Issue::Again,
+                    revents => Issue::Done(revents as u16 as i32),
+                }
+            }),
+            IORING_OP_TIMEOUT => timeout(ring, pagetable, req),
+            IORING_OP_OPENAT => {
+                let mut flags = sqe.op_flags as i32;
+                if ring.linux_abi {
+                    flags = crate::compat::linux::abi::open_flags_to_native(flags);
+                }
+                let dirfd = sqe.fd;
+                result(crate::subsystems::syscalls::fs::file_io::openat(
+                    dirfd,
+                    sqe.addr as *const u8,
+                    flags,
+                    sqe.len,
+                ))
+            }
+            IORING_OP_CLOSE => {
+                if sqe.flags & IOSQE_FIXED_FILE != 0 {
+                    return Issue::Done(-EBADF);
+                }
+                result(crate::subsystems::syscalls::fs::file_io::close(sqe.fd))
+            }
+            IORING_OP_ACCEPT => ready(ring, &sqe, crate::posix::POLLIN, |fd| {
+                crate::subsystems::syscalls::network::socket::sys_accept(&[fd, sqe.addr, sqe.off])
+            }),
+            IORING_OP_CONNECT => with_fd(ring, &sqe, |fd| {
+                result(crate::subsystems::syscalls::network::socket::sys_connect(&[fd, sqe.addr, sqe.off]))
+            }),
+            IORING_OP_SEND => ready(ring, &sqe, crate::posix::POLLOUT, |fd| {
+                let args = [fd, sqe.addr, sqe.len as u64, sqe.op_flags as u64];
+                crate::subsystems::syscalls::network::data::sys_send(&args)
+            }),
+            IORING_OP_RECV => ready(ring, &sqe, crate::posix::POLLIN, |fd| {
+                let args = [fd, sqe.addr, sqe.len as u64, sqe.op_flags as u64];
+                crate::subsystems::syscalls::network::data::sys_recv(&args)
+            }),
+            _ => Issue::Done(-EINVAL),
+        }
+    })
+}
+
+/// A syscall result as a CQE result; not being ready yet means retry
+fn result(res: SyscallResult) -> Issue {
+    match res {
+        Ok(value) => Issue::Done(value as i32),
+        Err(SyscallError::WouldBlock) => Issue::Again,
+        Err(e) => Issue::Done(-syscall_error_to_errno(e)),
+    }
+}
+
+/// The file table index the SQE names, through the registered files with
+/// `IOSQE_FIXED_FILE`
+fn file_index(ring: &IoUring, sqe: &IoUringSqe) -> Option<usize> {
+    if sqe.flags & IOSQE_FIXED_FILE != 0 {
+        ring.registered_file(sqe.fd)
+    } else {
+        crate::process::fdlookup(sqe.fd)
+    }
+}
+
+fn with_file(ring: &IoUring, sqe: &IoUringSqe, f: impl FnOnce(usize) -> Issue) -> Issue {
+    match file_index(ring, sqe) {
+        Some(idx) => f(idx),
+        None => Issue::Done(-EBADF),
+    }
+}
+
+/// Run a descriptor-taking handler; a registered file is lent a descriptor
+/// for the call
+fn with_fd(ring: &IoUring, sqe: &IoUringSqe, f: impl FnOnce(u64) -> Issue) -> Issue {
+    if sqe.flags & IOSQE_FIXED_FILE == 0 {
+        return f(sqe.fd as u64);
+    }
+    let Some(idx) = ring.registered_file(sqe.fd).and_then(crate::fs::file::file_dup) else {
+        return Issue::Done(-EBADF);
+    };
+    let Some(fd) = crate::process::fdalloc(idx) else {
+        crate::fs::file::file_close(idx);
+        return Issue::Done(-EMFILE);
+    };
+    let issued = f(fd as u64);
+    let _ = crate::subsystems::syscalls::fs::file_io::close(fd);
+    issued
+}
+
+/// Run a socket handler once the socket reports `events`
+fn ready(ring: &IoUring, sqe: &IoUringSqe, events: i16, f: impl FnOnce(u64) -> SyscallResult) -> Issue {
+    let Some(idx) = file_index(ring, sqe) else { return Issue::Done(-EBADF) };
+    let mask = events | crate::posix::POLLERR | crate::posix::POLLHUP;
+    if crate::fs::file::file_poll(idx) & mask == 0 {
+        return Issue::Again;
+    }
+    with_fd(ring, sqe, |fd| result(f(fd)))
+}
+
+/// Whether a stream file has `events` ready; files with a position always do
+fn stream_ready(idx: usize, events: i16) -> bool {
+    let ftype = crate::fs::file::FILE_TABLE.lock().get(idx).map(|f| f.ftype);
+    match ftype {
+        Some(FileType::Pipe | FileType::Socket | FileType::Device) => {
+            let mask = events | crate::posix::POLLERR | crate::posix::POLLHUP;
+            crate::fs::file::file_poll(idx) & mask != 0
+        }
+        _ => true,
+    }
+}
+
+/// A file operation's return as a CQE result
+fn transferred(n: isize) -> Issue {
+    match n {
+        n if n >= 0 => Issue::Done(n as i32),
+        n if n == -(EAGAIN as isize) => Issue::Again,
+        -1 => Issue::Done(-EBADF),
+        n => Issue::Done(n as i32),
+    }
+}
+
+/// A buffer for READ_FIXED/WRITE_FIXED must lie in the registered buffer
+/// it names
+fn fixed_buffer_ok(ring: &IoUring, sqe: &IoUringSqe) -> bool {
+    let Some((base, len)) = ring.registered_buffer(sqe.buf_index) else { return false };
+    sqe.addr >= base && sqe.addr.saturating_add(sqe.len as u64) <= base + len
+}
+
+/// Read at `offset`, or at the file position when it is `-1`
+fn read_at(idx: usize, offset: u64, buf: &mut [u8]) -> isize {
+    if offset == u64::MAX {
+        crate::fs::file::file_read(idx, buf)
+    } else {
+        crate::fs::file::file_read_at(idx, offset, buf)
+    }
+}
+
+fn write_at(idx: usize, offset: u64, buf: &[u8]) -> isize {
+    if offset == u64::MAX {
+        crate::fs::file::file_write(idx, buf)
+    } else {
+        crate::fs::file::file_write_at(idx, offset, buf)
+    }
+}
+
+fn read(pagetable: *mut PageTable, idx: usize, addr: u64, len: usize, offset: u64) -> Issue {
+    if !stream_ready(idx, crate::posix::POLLIN) {
+        return Issue::Again;
+    }
+    let mut buf = vec![0u8; len.min(MAX_IO)];
+    let n = read_at(idx, offset, &mut buf);
+    if n > 0 && unsafe { copyout(pagetable, addr as usize, buf.as_ptr(), n as usize) }.is_err() {
+        return Issue::Done(-EFAULT);
+    }
+    transferred(n)
+}
+
+fn write(pagetable: *mut PageTable, idx: usize, addr: u64, len: usize, offset: u64) -> Issue {
+    if !stream_ready(idx, crate::posix::POLLOUT) {
+        return Issue::Again;
+    }
+    let mut buf = vec![0u8; len.min(MAX_IO)];
+    if !buf.is_empty() && unsafe { copyin(pagetable, buf.as_mut_ptr(), addr as usize, buf.len()) }.is_err() {
+        return Issue::Done(-EFAULT);
+    }
+    transferred(write_at(idx, offset, &buf))
+}
+
+fn iovecs(pagetable: *mut PageTable, sqe: &IoUringSqe) -> Result<Vec<Iovec>, i32> {
+    let count = sqe.len as usize;
+    if count > MAX_IOVECS {
+        return Err(-EINVAL);
+    }
+    (0..count)
+        .map(|i| {
+            copy_from_user::<Iovec>(pagetable, sqe.addr + (i * core::mem::size_of::<Iovec>()) as u64)
+                .map_err(|_| -EFAULT)
+        })
+        .collect()
+}
+
+fn readv(pagetable: *mut PageTable, idx: usize, sqe: &IoUringSqe) -> Issue {
+    let iovs = match iovecs(pagetable, sqe) {
+        Ok(iovs) => iovs,
+        Err(res) => return Issue::Done(res),
+    };
+    if !stream_ready(idx, crate::posix::POLLIN) {
+        return Issue::Again;
+    }
+    let mut total = 0usize;
+    for iov in iovs {
+        let offset = if sqe.off == u64::MAX { u64::MAX } else { sqe.off + total as u64 };
+        let mut buf = vec![0u8; (iov.len as usize).min(MAX_IO)];
+        let n = read_at(idx, offset, &mut buf);
+        if n < 0 {
+            return if total > 0 { Issue::Done(total as i32) } else { transferred(n) };
+        }
+        let n = n as usize;
+        if n > 0 && unsafe { copyout(pagetable, iov.base as usize, buf.as_ptr(), n) }.is_err() {
+            return Issue::Done(-EFAULT);
+        }
+        total += n;
+        if n < buf.len() {
+            break;
+        }
+    }
+    Issue::Done(total as i32)
+}
+
+fn writev(pagetable: *mut PageTable, idx: usize, sqe: &IoUringSqe) -> Issue {
+    let iovs = match iovecs(pagetable, sqe) {
+        Ok(iovs) => iovs,
+        Err(res) => return Issue::Done(res),
+    };
+    if !stream_ready(idx, crate::posix::POLLOUT) {
+        return Issue::Again;
+    }
+    let mut total = 0usize;
+    for iov in iovs {
+        let offset = if sqe.off == u64::MAX { u64::MAX } else { sqe.off + total as u64 };
+        let mut buf = vec![0u8; (iov.len as usize).min(MAX_IO)];
+        if !buf.is_empty() && unsafe { copyin(pagetable, buf.as_mut_ptr(), iov.base as usize, buf.len()) }.is_err() {
+            return Issue::Done(-EFAULT);
+        }
+        let n = write_at(idx, offset, &buf);
+        if n < 0 {
+            return if total > 0 { Issue::Done(total as i32) } else { transferred(n) };
+        }
+        total += n as usize;
+        if (n as usize) < buf.len() {
+            break;
+        }
+    }
+    Issue::Done(total as i32)
+}
+
+/// `struct __kernel_timespec`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct Timespec {
+    sec: i64,
+    nsec: i64,
+}
+
+/// Fire after the given time or once `off` more completions are posted
+fn timeout(ring: &IoUring, pagetable: *mut PageTable, req: &mut Request) -> Issue {
+    let now = crate::subsystems::time::uptime_ms();
+    let timer = match req.timer {
+        Some(timer) => timer,
+        None => {
+            let sqe = &req.sqe;
+            if sqe.len != 1 || sqe.op_flags & !IORING_TIMEOUT_ABS != 0 {
+                return Issue::Done(-EINVAL);
+            }
+            let ts = match copy_from_user::<Timespec>(pagetable, sqe.addr) {
+                Ok(ts) => ts,
+                Err(_) => return Issue::Done(-EFAULT),
+            };
+            if ts.sec < 0 || !(0..1_000_000_000).contains(&ts.nsec) {
+                return Issue::Done(-EINVAL);
+            }
+            let ms = ts.sec as u64 * 1000 + ts.nsec as u64 / 1_000_000;
+            let deadline = if sqe.op_flags & IORING_TIMEOUT_ABS != 0 { ms } else { now + ms };
+            let target = (sqe.off > 0).then(|| ring.completions() + sqe.off);
+            let timer = Timer { deadline, target };
+            req.timer = Some(timer);
+            timer
+        }
+    };
+    if timer.target.is_some_and(|target| ring.completions() >= target) {
+        Issue::Done(0)
+    } else if now >= timer.deadline {
+        Issue::Done(-ETIME)
+    } else {
+        Issue::Again
+    }
+}
@@ -11,15 +11,13 @@
 
 use alloc::boxed::Box;
 use alloc::string::{String, ToString};
-use alloc::vec::Vec;
 use alloc::collections::BTreeMap;
 use crate::syscalls::services::SyscallService;
 use crate::syscalls::common::SyscallError;
 use crate::subsystems::ipc::mqueue;
 use crate::subsystems::ipc::mqueue::{MqAttr, MqNotify, MqOpenFlags, MqNotifyType};
 use crate::subsystems::time::{Timespec, get_current_time};
-use core::ptr;
-use core::slice;
+use crate::subsystems::mm::uaccess::{self, UserPtr, UserSlice};
 
 /// IPC System Call Service
 ///
@@ -89,11 +87,7 @@ impl IpcService {
         }
 
         // Read attributes if provided
-        let attr = if !attr_ptr.is_null() {
-            Some(unsafe { ptr::read(attr_ptr) })
-        } else {
-            None
-        };
+        let attr = UserPtr::new(attr_ptr as usize).read_opt()?;
 
         // Convert flags
         let mq_flags = match MqOpenFlags::from_bits(flags) {
@@ -129,7 +123,7 @@ impl IpcService {
         }
 
         let mqd = args[0] as i32;
-        let attr_ptr = args[1] as *mut MqAttr;
+        let attr_ptr = UserPtr::<MqAttr>::new(args[1] as usize);
 
         // Validate attributes pointer
         if attr_ptr.is_null() {
@@ -138,7 +132,7 @@ impl IpcService {
 
         match mqueue::mq_getattr(mqd) {
             Ok(attr) => {
-                unsafe { ptr::write(attr_ptr, attr); }
+                attr_ptr.write(&attr)?;
                 Ok(0)
             },
             Err(_) => Err(SyscallError::InvalidArgument),
@@ -160,13 +154,11 @@ impl IpcService {
             return Err(SyscallError::InvalidArgument);
         }
 
-        let new_attr = unsafe { ptr::read(new_attr_ptr) };
+        let new_attr = UserPtr::<MqAttr>::new(new_attr_ptr as usize).read()?;
 
         match mqueue::mq_setattr(mqd, &new_attr) {
             Ok(old_attr) => {
-                if !old_attr_ptr.is_null() {
-                    unsafe { ptr::write(old_attr_ptr, old_attr); }
-                }
+                UserPtr::new(old_attr_ptr as usize).write_opt(&old_attr)?;
                 Ok(0)
             },
             Err(_) => Err(SyscallError::InvalidArgument),
@@ -191,14 +183,10 @@ impl IpcService {
         }
 
         // Read message
-        let msg = unsafe { slice::from_raw_parts(msg_ptr, msg_len) }.to_vec();
+        let msg = UserSlice::new(msg_ptr as usize, msg_len).read_to_vec()?;
 
         // Read timeout if provided
-        let timeout = if !timeout_ptr.is_null() {
-            Some(unsafe { ptr::read(timeout_ptr) })
-        } else {
-            None
-        };
+        let timeout = UserPtr::new(timeout_ptr as usize).read_opt()?;
 
         match mqueue::mq_timedsend(mqd, &msg, msg_prio, timeout.as_ref()) {
             Ok(()) => Ok(0),
@@ -224,24 +212,15 @@ impl IpcService {
         }
 
         // Read timeout if provided
-        let timeout = if !timeout_ptr.is_null() {
-            Some(unsafe { ptr::read(timeout_ptr) })
-        } else {
-            None
-        };
+        let timeout = UserPtr::new(timeout_ptr as usize).read_opt()?;
 
         match mqueue::mq_timedreceive(mqd, msg_len, timeout.as_ref()) {
             Ok((msg, prio)) => {
                 // Copy message to user buffer
-                let copy_len = core::cmp::min(msg.len(), msg_len);
-                unsafe {
-                    ptr::copy_nonoverlapping(msg.as_ptr(), msg_ptr, copy_len);
-                }
+                let copy_len = UserSlice::new(msg_ptr as usize, msg_len).write(&msg)?;
                 
                 // Set priority if requested
-                if !msg_prio_ptr.is_null() {
-                    unsafe { ptr::write(msg_prio_ptr, prio); }
-                }
+                UserPtr::new(msg_prio_ptr as usize).write_opt(&prio)?;
                 
                 Ok(copy_len as u64)
             },
@@ -259,11 +238,7 @@ impl IpcService {
         let notify_ptr = args[1] as *const MqNotify;
 
         // Read notification if provided
-        let notify = if !notify_ptr.is_null() {
-            Some(unsafe { ptr::read(notify_ptr) })
-        } else {
-            None
-        };
+        let notify = UserPtr::new(notify_ptr as usize).read_opt()?;
 
         match mqueue::mq_notify(mqd, notify.as_ref()) {
             Ok(()) => Ok(0),
@@ -282,17 +257,11 @@ impl IpcService {
         let old_attr_ptr = args[2] as *mut MqAttr;
 
         // Read new attributes if provided
-        let new_attr = if !new_attr_ptr.is_null() {
-            Some(unsafe { ptr::read(new_attr_ptr) })
-        } else {
-            None
-        };
+        let new_attr = UserPtr::new(new_attr_ptr as usize).read_opt()?;
 
         match mqueue::mq_getsetattr(mqd, new_attr.as_ref()) {
             Ok(old_attr) => {
-                if !old_attr_ptr.is_null() {
-                    unsafe { ptr::write(old_attr_ptr, old_attr); }
-                }
+                UserPtr::new(old_attr_ptr as usize).write_opt(&old_attr)?;
                 Ok(0)
             },
             Err(_) => Err(SyscallError::InvalidArgument),
@@ -305,25 +274,7 @@ impl IpcService {
             return Err(());
         }
 
-        let mut buf = Vec::new();
-        let mut offset = 0;
-        
-        loop {
-            let byte = unsafe { ptr.add(offset).read() };
-            
-            if byte == 0 {
-                break;
-            }
-            
-            buf.push(byte);
-            offset += 1;
-            
-            // Prevent infinite loops
-            if offset > 256 {
-                return Err(());
-            }
-        }
-        
+        let buf = uaccess::user_string(ptr as usize, 256).map_err(|_| ())?;
         String::from_utf8(buf).map_err(|_| ())
     }
 }
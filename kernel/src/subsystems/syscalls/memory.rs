@@ -2,17 +2,19 @@
 
 use super::common::{SyscallError, SyscallResult, extract_args};
 use crate::process::{PROC_TABLE, myproc};
-use crate::subsystems::mm::vm::{map_pages, flags, PAGE_SIZE, PageTable, map_page, flush_tlb_page, phys_to_kernel_ptr};
+use crate::subsystems::mm::vm::{map_pages, flags, PAGE_SIZE, map_page};
 use crate::subsystems::mm::{kalloc, kfree};
+use crate::subsystems::mm::uaccess::UserSlice;
+use crate::subsystems::mm::vm::{
+    discard_user_range, move_user_range, release_area_range, reprotect_area_range, VmAccess, VmAdvice, VmArea, VmLock,
+    VmPerm,
+};
 use crate::posix;
 use crate::subsystems::sync::Mutex;
 use core::ptr;
-use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-// Import from compat
-use crate::compat::MemoryRegion;
-
 // Import advanced memory mapping functions
 
 /// 全局内存统计
@@ -117,6 +119,9 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
         0x300E => sys_shmat(args),          // shmat
         0x300F => sys_shmdt(args),          // shmdt
         0x3010 => sys_shmctl(args),         // shmctl
+        0x3011 => sys_mlock2(args),         // mlock2
+        0x3012 => sys_swapon(args),         // swapon
+        0x3013 => sys_swapoff(args),        // swapoff
         
         // Optimized memory allocator operations
         0x3100 => sys_optimized_alloc(args),      // optimized_alloc
@@ -132,6 +137,12 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
 
 // Placeholder implementations - to be replaced with actual syscall logic
 
+/// Whether growing the address space of `proc` to `new_top` would exceed
+/// its RLIMIT_AS
+fn exceeds_as_limit(proc: &crate::process::Proc, new_top: usize) -> bool {
+    new_top as u64 > proc.rlimits[crate::posix::RLIMIT_AS as usize].rlim_cur
+}
+
 fn sys_brk(args: &[u64]) -> SyscallResult {
     let addr = extract_args(args, 1)?[0] as usize;
 
@@ -154,6 +165,10 @@ fn sys_brk(args: &[u64]) -> SyscallResult {
 
     // For now, only allow increasing the break (simplified implementation)
     if addr > old_sz {
+        if exceeds_as_limit(proc, addr) {
+            return Err(SyscallError::OutOfMemory);
+        }
+
         // Calculate how many pages to allocate
         let pages_needed = ((addr - old_sz + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
         let allocated_bytes = pages_needed * PAGE_SIZE;
@@ -271,6 +286,7 @@ pub fn sys_mmap(args: &[u64]) -> SyscallResult {
     
     // Get current process
     let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+    let privileged = can_lock_unlimited(pid)?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
     let pagetable = proc.pagetable;
@@ -279,15 +295,17 @@ pub fn sys_mmap(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::InvalidArgument);
     }
     
-    // Validate memory access permissions
-    let is_write = (prot & crate::posix::PROT_WRITE) != 0;
-    let is_execute = (prot & crate::posix::PROT_EXEC) != 0;
-    if !crate::security::validate_memory_access(pid, target_addr, aligned_length, is_write, is_execute).unwrap_or(false) {
-        return Err(SyscallError::PermissionDenied);
-    }
-    
     // Get user-space address range to map
     let mut target_addr = aligned_addr;
+
+    // A hint that collides with an existing mapping is only a hint, while
+    // MAP_FIXED replaces what is there
+    if target_addr != 0
+        && (flags & posix::MAP_FIXED) == 0
+        && proc.vm.overlapping(target_addr, target_addr + aligned_length).next().is_some()
+    {
+        target_addr = 0;
+    }
     
     // If no address was specified, find a free range in user space
     if target_addr == 0 {
@@ -298,6 +316,17 @@ pub fn sys_mmap(args: &[u64]) -> SyscallResult {
             return Err(SyscallError::OutOfMemory);
         }
     }
+
+    if target_addr + aligned_length > proc.sz && exceeds_as_limit(proc, target_addr + aligned_length) {
+        return Err(SyscallError::OutOfMemory);
+    }
+
+    // Validate memory access permissions
+    let is_write = (prot & crate::posix::PROT_WRITE) != 0;
+    let is_execute = (prot & crate::posix::PROT_EXEC) != 0;
+    if !crate::security::validate_memory_access(pid, target_addr, aligned_length, is_write, is_execute).unwrap_or(false) {
+        return Err(SyscallError::PermissionDenied);
+    }
     
     // Allocate and map pages - use map_pages for batch operation (more efficient than individual map_page)
     // Zero-initialize pages for anonymous mappings
@@ -317,6 +346,45 @@ pub fn sys_mmap(args: &[u64]) -> SyscallResult {
     if (prot & crate::posix::PROT_EXEC) != 0 {
         vm_flags |= flags::PTE_X;
     }
+
+    // Locked from the start with MAP_LOCKED or after mlockall(MCL_FUTURE)
+    let lock = if (flags & posix::MAP_LOCKED) != 0 { VmLock::Locked } else { proc.vm.def_lock };
+    if lock != VmLock::None {
+        check_memlock(proc, aligned_length, privileged).map_err(|_| SyscallError::WouldBlock)?;
+    }
+    let perm = VmPerm {
+        read: (prot & crate::posix::PROT_READ) != 0,
+        write: is_write,
+        exec: is_execute,
+        user: true,
+    };
+
+    // The page cache of a regular file, looked up apart so that the file
+    // table isn't held while the pages are faulted in
+    let file_mapping = if (flags & crate::posix::MAP_ANONYMOUS) == 0 {
+        proc.ofile.lock().get(fd).and_then(crate::fs::file::file_get_mapping)
+    } else {
+        None
+    };
+    let private = (flags & posix::MAP_PRIVATE) != 0;
+    if let Some((_, readable, writable)) = &file_mapping {
+        let sharing = flags & (posix::MAP_SHARED | posix::MAP_PRIVATE);
+        if sharing != posix::MAP_SHARED && sharing != posix::MAP_PRIVATE {
+            return Err(SyscallError::InvalidArgument);
+        }
+        if offset < 0 || offset as usize % PAGE_SIZE != 0 {
+            return Err(SyscallError::InvalidArgument);
+        }
+        // Every mapping reads the file; only a shared one writes to it
+        if !readable || (!private && is_write && !writable) {
+            return Err(SyscallError::PermissionDenied);
+        }
+    }
+
+    // The old pages of a MAP_FIXED range go, dirty shared ones to the page cache
+    if (flags & posix::MAP_FIXED) != 0 {
+        unmap_range(proc, target_addr, target_addr + aligned_length);
+    }
     
     // For now, handle only anonymous mappings (MAP_ANONYMOUS flag)
     if (flags & crate::posix::MAP_ANONYMOUS) != 0 {
@@ -389,806 +457,640 @@ pub fn sys_mmap(args: &[u64]) -> SyscallResult {
             
             current_offset += batch_size;
         }
+        add_area(proc, target_addr, aligned_length, perm, false, lock);
         
         // Update process size if we mapped beyond the current heap end
         if target_addr + aligned_length > proc.sz {
             proc.sz = target_addr + aligned_length;
         }
         
+        Ok(target_addr as u64)
+    } else if let Some(ring) = proc.ofile.lock().get(fd).and_then(crate::fs::file::file_get_io_uring) {
+        // The SQ/CQ rings and SQE array of an io_uring instance
+        if (flags & crate::posix::MAP_SHARED) == 0 {
+            return Err(SyscallError::InvalidArgument);
+        }
+        ring.mmap(pagetable, target_addr, offset as u64, aligned_length, vm_flags)?;
+        add_area(proc, target_addr, aligned_length, perm, true, lock);
+        if target_addr + aligned_length > proc.sz {
+            proc.sz = target_addr + aligned_length;
+        }
+        Ok(target_addr as u64)
+    } else if let Some(memfd) = proc.ofile.lock().get(fd).and_then(crate::fs::file::file_get_memfd) {
+        // Shared memory, as display clients draw into
+        if (flags & crate::posix::MAP_SHARED) == 0 || offset < 0 {
+            return Err(SyscallError::InvalidArgument);
+        }
+        let instance = crate::syscalls::glib::get_memfd_instance(memfd).ok_or(SyscallError::BadFileDescriptor)?;
+        instance.mmap(pagetable, target_addr, offset as usize, aligned_length, vm_flags)?;
+        add_area(proc, target_addr, aligned_length, perm, true, lock);
+        if target_addr + aligned_length > proc.sz {
+            proc.sz = target_addr + aligned_length;
+        }
+        Ok(target_addr as u64)
+    } else if let Some((mapping, _, writable)) = file_mapping {
+        // A regular file, paged in from its page cache as it is touched
+        let mut area = VmArea::file(target_addr..target_addr + aligned_length, perm, mapping, offset as usize, private);
+        area.may_write = private || writable;
+        area.lock = lock;
+        proc.vm.insert(area).map_err(|_| SyscallError::InvalidArgument)?;
+        if target_addr + aligned_length > proc.sz {
+            proc.sz = target_addr + aligned_length;
+        }
+        drop(table);
+
+        // Failing to read ahead is no failure of the mapping itself
+        if (flags & posix::MAP_POPULATE) != 0 || lock == VmLock::Locked {
+            let _ = populate(pid, target_addr, target_addr + aligned_length);
+        }
         Ok(target_addr as u64)
     } else {
-        // TODO: Handle file-backed mappings
+        // Sockets, pipes and devices have nothing to map
         Err(SyscallError::NotSupported)
     }
 }
 
+/// Record a new mapping in the areas of `proc`
+///
+/// The mapping takes the place of whatever was there before, as its
+/// pages did. Areas with pages of their own rather than anonymous memory
+/// are `file_backed`, so that nothing discards them.
+fn add_area(proc: &mut crate::process::Proc, start: usize, len: usize, perm: VmPerm, file_backed: bool, lock: VmLock) {
+    proc.vm.remove_range(start, start + len);
+    if proc.vm.map(start, len, perm, file_backed, 0).is_ok() {
+        for area in proc.vm.split_range(start, start + len) {
+            area.lock = lock;
+        }
+    }
+}
+
+/// Unmap `[start, end)` of `proc`: its areas with their pages, and the
+/// pages mapped outside any area such as the brk heap
+///
+/// Pages a shared file mapping wrote go back to the page cache dirty, for
+/// the flushers to write out. Returns how many pages were mapped.
+fn unmap_range(proc: &mut crate::process::Proc, start: usize, end: usize) -> usize {
+    let pagetable = proc.pagetable;
+    let mut unmapped = 0;
+    for area in proc.vm.remove_range(start, end) {
+        unmapped += unsafe { release_area_range(pagetable, &area, area.range.start, area.range.end) };
+    }
+    crate::subsystems::mm::swap::lru::putback_anon(proc.pid, start..end);
+    unmapped + unsafe { discard_user_range(pagetable, start, end) }
+}
+
+/// Unmap memory
+/// Arguments: [addr, length]
 pub fn sys_munmap(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 2)?;
-    let addr = args[0] as usize;
-    let length = args[1] as usize;
+    let (start, end) = page_range(args[0] as usize, args[1] as usize)?;
 
     // Validate arguments
-    if length == 0 || addr == 0 {
+    if start == end || start == 0 {
         return Err(SyscallError::InvalidArgument);
     }
-
-    // Align to page boundaries
-    let start = addr & !(PAGE_SIZE - 1);
-    let aligned_length = (length + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
-    let end = start + aligned_length;
-
-    if crate::arch::memory_layout::is_kernel_address(start) || crate::arch::memory_layout::is_kernel_address(end) {
+    if crate::arch::memory_layout::is_kernel_address(start) || crate::arch::memory_layout::is_kernel_address(end - 1) {
         return Err(SyscallError::InvalidArgument);
     }
 
     let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
-    let pagetable = proc.pagetable;
-
-    if pagetable.is_null() {
+    if proc.pagetable.is_null() {
         return Err(SyscallError::InvalidArgument);
     }
 
-
-    
-    // Validate memory access permissions
-    let is_write = (prot & crate::posix::PROT_WRITE) != 0;
-    let is_execute = (prot & crate::posix::PROT_EXEC) != 0;
-    if !crate::security::validate_memory_access(pid, addr, len, is_write, is_execute).unwrap_or(false) {
-        return Err(SyscallError::PermissionDenied);
-    }
-
-    // For each page in range, unmap it and free physical memory
-    let mut current = start;
-    let mut unmapped_count = 0;
-    
-    while current < end {
-        // Try to unmap the page and get physical address
-        #[cfg(target_arch = "riscv64")]
-        {
-            use crate::subsystems::mm::vm::riscv64;
-            if let Some(pa) = unsafe { riscv64::unmap_page(pagetable, current) } {
-                // Free the physical page
-                kfree(pa as *mut u8);
-                unmapped_count += 1;
-            }
-        }
-        
-        #[cfg(target_arch = "aarch64")]
-        {
-            // For aarch64, use the exported unmap_page function
-            unsafe {
-                // Unmap the page (returns Result<(), ()>)
-                if crate::subsystems::mm::vm::unmap_page(pagetable, current).is_ok() {
-                    // Note: For aarch64, we would need to track physical addresses
-                    // separately. For now, we just unmap without freeing physical memory.
-                    // TODO: Implement proper physical page tracking for aarch64
-                    unmapped_count += 1;
-                }
-            }
-        }
-        
-        #[cfg(target_arch = "x86_64")]
-        {
-            // x86_64 implementation would go here
-            // For now, just increment count
-            // TODO: Implement proper unmapping for x86_64
-            unmapped_count += 1;
-        }
-
-        current += PAGE_SIZE;
-    }
-
-    // Flush TLB for the unmapped region
-    let mut current = start;
-    while current < end {
-        crate::subsystems::mm::vm::flush_tlb_page(current);
-        current += PAGE_SIZE;
-    }
+    unmap_range(proc, start, end);
 
     // Update process size if we unmapped memory beyond current break
     if end >= proc.sz {
         proc.sz = start.min(proc.sz);
     }
 
-    Ok(unmapped_count as u64)
+    Ok(0)
 }
 
+/// Change the protection of memory
+/// Arguments: [addr, length, prot]
+///
+/// The whole range has to be mapped. A shared mapping of a file opened
+/// read-only can't be made writable.
 fn sys_mprotect(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 3)?;
-    let addr = args[0] as usize;
-    let len = args[1] as usize;
+    let (start, end) = page_range(args[0] as usize, args[1] as usize)?;
     let prot = args[2] as i32;
-
-    // Validate arguments
-    if len == 0 || addr == 0 {
-        return Err(SyscallError::InvalidArgument);
-    }
-
-    // Align to page boundaries
-    let start = addr & !(PAGE_SIZE - 1);
-    let aligned_length = (len + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
-    let end = start + aligned_length;
-
-    if crate::arch::memory_layout::is_kernel_address(start) || crate::arch::memory_layout::is_kernel_address(end) {
+    if prot & !(posix::PROT_READ | posix::PROT_WRITE | posix::PROT_EXEC) != 0 {
         return Err(SyscallError::InvalidArgument);
     }
+    let perm = VmPerm {
+        read: (prot & posix::PROT_READ) != 0,
+        write: (prot & posix::PROT_WRITE) != 0,
+        exec: (prot & posix::PROT_EXEC) != 0,
+        user: true,
+    };
 
     let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
     let pagetable = proc.pagetable;
-
     if pagetable.is_null() {
         return Err(SyscallError::InvalidArgument);
     }
+    if !proc.vm.covers(start, end) {
+        return Err(SyscallError::OutOfMemory);
+    }
+    if perm.write && proc.vm.overlapping(start, end).any(|area| !area.may_write) {
+        return Err(SyscallError::PermissionDenied);
+    }
+
+    for area in proc.vm.split_range(start, end) {
+        let old = area.clone();
+        area.perm = perm;
+        unsafe { reprotect_area_range(pagetable, &old, area, area.range.start, area.range.end) };
+    }
+    proc.vm.coalesce(start, end);
+    Ok(0)
+}
+
+/// `[start, end)` covering `len` bytes from page-aligned `start`
+fn page_range(start: usize, len: usize) -> Result<(usize, usize), SyscallError> {
+    if start % PAGE_SIZE != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let end = start
+        .checked_add(len)
+        .and_then(|end| end.checked_next_multiple_of(PAGE_SIZE))
+        .ok_or(SyscallError::InvalidArgument)?;
+    Ok((start, end))
+}
 
-    // Build new permissions
-    let mut new_perm = flags::PTE_U; // User accessible
-    if (prot & posix::PROT_READ) != 0 {
-        new_perm |= flags::PTE_R;
+/// `[start, end)` of the pages `len` bytes at `addr` touch, as mlock takes
+fn lock_range(addr: usize, len: usize) -> Result<(usize, usize), SyscallError> {
+    let offset = addr % PAGE_SIZE;
+    page_range(addr - offset, len.checked_add(offset).ok_or(SyscallError::OutOfMemory)?)
+}
+
+/// Whether `pid` may lock memory past its RLIMIT_MEMLOCK
+///
+/// Asked before taking the process table, which the capability check
+/// must not run under.
+fn can_lock_unlimited(pid: crate::process::Pid) -> Result<bool, SyscallError> {
+    let euid = PROC_TABLE.lock().find_ref(pid).ok_or(SyscallError::InvalidArgument)?.euid;
+    Ok(crate::security::capabilities::capable(pid as u64, euid, crate::security::capabilities::Capability::IpcLock))
+}
+
+/// Check that `proc` may lock `more` bytes on top of what it has locked
+fn check_memlock(proc: &crate::process::Proc, more: usize, privileged: bool) -> Result<(), SyscallError> {
+    if privileged {
+        return Ok(());
+    }
+    let limit = proc.rlimits[posix::RLIMIT_MEMLOCK as usize].rlim_cur;
+    if limit == 0 {
+        return Err(SyscallError::PermissionDenied);
+    }
+    if (proc.vm.locked_bytes() + more) as u64 > limit {
+        return Err(SyscallError::OutOfMemory);
     }
-    if (prot & posix::PROT_WRITE) != 0 {
-        new_perm |= flags::PTE_W;
+    Ok(())
+}
+
+/// Fault in the pages of `[start, end)` in the address space of `pid` the
+/// way an access would, so that locked memory is resident; writable
+/// private pages get their own copy now rather than at the first write
+///
+/// Runs without the process table held, as file pages may have to be read
+/// in. Pages of a file mapping past the end of the file are left out.
+fn populate(pid: crate::process::Pid, start: usize, end: usize) -> Result<(), SyscallError> {
+    use crate::subsystems::mm::vm::{fault_in_area, user_frame, user_range_check, PageFaultResult};
+
+    let (pagetable, areas) = {
+        let table = PROC_TABLE.lock();
+        let proc = table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
+        (proc.pagetable, proc.vm.overlapping(start, end).cloned().collect::<Vec<_>>())
+    };
+    for area in &areas {
+        // PROT_NONE memory is there to fault
+        if !area.perm.read && !area.perm.write && !area.perm.exec {
+            continue;
+        }
+        let write = area.perm.write && !area.shared();
+        let exec = !area.perm.read && area.perm.exec;
+        for va in (area.range.start.max(start)..area.range.end.min(end)).step_by(PAGE_SIZE) {
+            let resident = user_frame(pagetable, va).is_some()
+                && (!write || user_range_check(pagetable, va, 1, true, false).is_ok());
+            if resident {
+                continue;
+            }
+            match unsafe { fault_in_area(pagetable, area, va, write, exec) } {
+                PageFaultResult::Handled => {}
+                PageFaultResult::BusError => break,
+                PageFaultResult::OutOfMemory | PageFaultResult::SegFault => return Err(SyscallError::OutOfMemory),
+            }
+        }
     }
-    if (prot & posix::PROT_EXEC) != 0 {
-        new_perm |= flags::PTE_X;
+    Ok(())
+}
+
+/// Give advice about the use of memory
+/// Arguments: [addr, length, advice]
+///
+/// MADV_DONTNEED and MADV_FREE drop private anonymous pages, which read
+/// back as zeroes; shared and file pages keep their contents and stay.
+/// A range with holes takes the advice where it is mapped and then
+/// fails with ENOMEM.
+fn sys_madvise(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 3)?;
+    let (start, end) = page_range(args[0] as usize, args[1] as usize)?;
+    let advice = args[2] as i32;
+
+    let update: fn(&mut VmAdvice) = match advice {
+        posix::MADV_NORMAL => |a| a.access = VmAccess::Normal,
+        posix::MADV_SEQUENTIAL => |a| a.access = VmAccess::Sequential,
+        posix::MADV_RANDOM => |a| a.access = VmAccess::Random,
+        posix::MADV_HUGEPAGE => |a| a.hugepage = Some(true),
+        posix::MADV_NOHUGEPAGE => |a| a.hugepage = Some(false),
+        posix::MADV_DONTFORK => |a| a.dontfork = true,
+        posix::MADV_DOFORK => |a| a.dontfork = false,
+        // Acted on below rather than remembered
+        posix::MADV_WILLNEED | posix::MADV_DONTNEED | posix::MADV_FREE => |_| {},
+        _ => return Err(SyscallError::InvalidArgument),
+    };
+    if start == end {
+        return Ok(0);
     }
 
-    // For each page, update permissions
-    let mut current = start;
-    let mut updated_count = 0;
-    
-    while current < end {
-        unsafe {
-            #[cfg(target_arch = "riscv64")]
-            {
-                // Get current PTE
-                if let Some(pte_ptr) = crate::subsystems::mm::vm::riscv64::walk(pagetable, current, false) {
-                    if *pte_ptr & crate::subsystems::mm::vm::riscv64::PTE_V != 0 {
-                        // Page is mapped, update permissions
-                        let old_pte = *pte_ptr;
-                        let pa = crate::subsystems::mm::vm::riscv64::pte_to_pa(old_pte);
-                        let new_pte = crate::subsystems::mm::vm::riscv64::pa_to_pte(pa) | new_perm | crate::subsystems::mm::vm::riscv64::PTE_V;
-                        *pte_ptr = new_pte;
-                        updated_count += 1;
-                    }
+    let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+    let mut readahead = Vec::new();
+    let mapped = {
+        let mut table = PROC_TABLE.lock();
+        let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
+        let pagetable = proc.pagetable;
+        let mapped = proc.vm.covers(start, end);
+
+        match advice {
+            posix::MADV_DONTNEED | posix::MADV_FREE => {
+                // Locked pages have to stay
+                if proc.vm.overlapping(start, end).any(|area| area.locked()) {
+                    return Err(SyscallError::InvalidArgument);
+                }
+                // MADV_FREE may keep the pages until memory runs short;
+                // freeing them now is what reclaim would come to
+                for area in proc.vm.overlapping(start, end).filter(|area| !area.file_backed) {
+                    unsafe { discard_user_range(pagetable, area.range.start.max(start), area.range.end.min(end)) };
                 }
             }
-            
-            #[cfg(target_arch = "aarch64")]
-            {
-                // Use the exported walk function
-                if let Some(desc_ptr) = crate::subsystems::mm::vm::walk(pagetable, current, false) {
-                    // Constants for aarch64 descriptor flags
-                    const DESC_VALID: usize = 1 << 0;
-                    const DESC_AF: usize = 1 << 10;
-                    const DESC_AP_RO: usize = 1 << 7;
-                    const DESC_AP_USER: usize = 1 << 6;
-                    const DESC_UXN: usize = 1 << 54;
-                    const DESC_PXN: usize = 1 << 53;
-                    
-                    if *desc_ptr & DESC_VALID != 0 {
-                        // Page is mapped, update permissions
-                        let old_desc = *desc_ptr;
-                        let pa = old_desc & !0xFFF;
-                        let mut new_desc = pa | DESC_VALID | DESC_AF;
-                        
-                        if (new_perm & flags::PTE_W) == 0 {
-                            new_desc |= DESC_AP_RO;
-                        }
-                        if (new_perm & flags::PTE_U) != 0 {
-                            new_desc |= DESC_AP_USER;
-                        }
-                        if (new_perm & flags::PTE_X) == 0 {
-                            new_desc |= DESC_UXN | DESC_PXN;
-                        }
-                        
-                        *desc_ptr = new_desc;
-                        updated_count += 1;
+            posix::MADV_WILLNEED => {
+                for area in proc.vm.overlapping(start, end) {
+                    if let Some(mapping) = &area.mapping {
+                        let (from, to) = (area.range.start.max(start), area.range.end.min(end));
+                        readahead.push((mapping.clone(), area.file_index(from)..area.file_index(to - 1) + 1));
                     }
                 }
             }
-            
-            #[cfg(target_arch = "x86_64")]
-            {
-                // x86_64 implementation would go here
-                updated_count += 1;
+            _ => {
+                for area in proc.vm.split_range(start, end) {
+                    update(&mut area.advice);
+                }
+                proc.vm.coalesce(start, end);
             }
         }
-        
-        current += PAGE_SIZE;
-    }
+        mapped
+    };
 
-    // Flush TLB for the updated region
-    let mut current = start;
-    while current < end {
-        unsafe {
-            crate::subsystems::mm::vm::flush_tlb_page(current);
-        }
-        current += PAGE_SIZE;
+    // Reading the file in is no business of the process table
+    for (mapping, pages) in readahead {
+        mapping.willneed(pages);
     }
-
-    Ok(updated_count as u64)
+    if mapped { Ok(0) } else { Err(SyscallError::OutOfMemory) }
 }
 
-fn sys_madvise(args: &[u64]) -> SyscallResult {
-    // TODO: Implement madvise syscall
-    Err(SyscallError::NotSupported)
+/// Set the mlock state of `[start, end)`, faulting the pages in when
+/// they are to be locked now
+fn set_lock(start: usize, end: usize, lock: VmLock) -> SyscallResult {
+    let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+    let privileged = lock != VmLock::None && can_lock_unlimited(pid)?;
+    let mut table = PROC_TABLE.lock();
+    let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
+    if !proc.vm.covers(start, end) {
+        return Err(SyscallError::OutOfMemory);
+    }
+    if lock != VmLock::None {
+        check_memlock(proc, proc.vm.unlocked_bytes_in(start, end), privileged)?;
+    }
+    for area in proc.vm.split_range(start, end) {
+        area.lock = lock;
+    }
+    proc.vm.coalesce(start, end);
+    drop(table);
+    match lock {
+        VmLock::Locked => populate(pid, start, end)?,
+        VmLock::OnFault => {}
+        VmLock::None => crate::subsystems::mm::swap::lru::putback_anon(pid, start..end),
+    }
+    Ok(0)
 }
 
+/// Lock pages in memory
+/// Arguments: [addr, length]
 fn sys_mlock(args: &[u64]) -> SyscallResult {
-    // TODO: Implement mlock syscall
-    Err(SyscallError::NotSupported)
+    let args = extract_args(args, 2)?;
+    let (start, end) = lock_range(args[0] as usize, args[1] as usize)?;
+    set_lock(start, end, VmLock::Locked)
+}
+
+/// Lock pages in memory, possibly only as they are touched
+/// Arguments: [addr, length, flags]
+fn sys_mlock2(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 3)?;
+    let (start, end) = lock_range(args[0] as usize, args[1] as usize)?;
+    let flags = args[2] as i32;
+    if flags & !posix::MLOCK_ONFAULT != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let lock = if flags & posix::MLOCK_ONFAULT != 0 { VmLock::OnFault } else { VmLock::Locked };
+    set_lock(start, end, lock)
 }
 
+/// Unlock pages
+/// Arguments: [addr, length]
 fn sys_munlock(args: &[u64]) -> SyscallResult {
-    // TODO: Implement munlock syscall
-    Err(SyscallError::NotSupported)
+    let args = extract_args(args, 2)?;
+    let (start, end) = lock_range(args[0] as usize, args[1] as usize)?;
+    set_lock(start, end, VmLock::None)
 }
 
+/// Lock the whole address space
+/// Arguments: [flags]
+///
+/// MCL_CURRENT locks what is mapped now, MCL_FUTURE what gets mapped
+/// later; MCL_ONFAULT makes either lock pages only once touched. Without
+/// MCL_FUTURE an earlier one is cancelled.
 fn sys_mlockall(args: &[u64]) -> SyscallResult {
-    // TODO: Implement mlockall syscall
-    Err(SyscallError::NotSupported)
+    let flags = extract_args(args, 1)?[0] as i32;
+    let known = posix::MCL_CURRENT | posix::MCL_FUTURE | posix::MCL_ONFAULT;
+    if flags & !known != 0 || flags & (posix::MCL_CURRENT | posix::MCL_FUTURE) == 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let lock = if flags & posix::MCL_ONFAULT != 0 { VmLock::OnFault } else { VmLock::Locked };
+
+    let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+    let privileged = can_lock_unlimited(pid)?;
+    let mut table = PROC_TABLE.lock();
+    let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
+    if !privileged && proc.rlimits[posix::RLIMIT_MEMLOCK as usize].rlim_cur == 0 {
+        return Err(SyscallError::PermissionDenied);
+    }
+
+    proc.vm.def_lock = if flags & posix::MCL_FUTURE != 0 { lock } else { VmLock::None };
+    if flags & posix::MCL_CURRENT != 0 {
+        check_memlock(proc, proc.vm.unlocked_bytes_in(0, usize::MAX), privileged)?;
+        for area in proc.vm.iter_mut() {
+            area.lock = lock;
+        }
+        proc.vm.coalesce(0, usize::MAX);
+        drop(table);
+        if lock == VmLock::Locked {
+            populate(pid, 0, usize::MAX)?;
+        }
+    }
+    Ok(0)
 }
 
-fn sys_munlockall(args: &[u64]) -> SyscallResult {
-    // TODO: Implement munlockall syscall
-    Err(SyscallError::NotSupported)
+/// Unlock the whole address space and cancel MCL_FUTURE
+fn sys_munlockall(_args: &[u64]) -> SyscallResult {
+    let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+    let mut table = PROC_TABLE.lock();
+    let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
+    proc.vm.def_lock = VmLock::None;
+    for area in proc.vm.iter_mut() {
+        area.lock = VmLock::None;
+    }
+    proc.vm.coalesce(0, usize::MAX);
+    drop(table);
+    crate::subsystems::mm::swap::lru::putback_anon(pid, 0..usize::MAX);
+    Ok(0)
 }
 
+/// Report which pages are resident
+/// Arguments: [addr, length, vec]
+///
+/// One byte per page goes to `vec`, 1 for a page in memory. A file page
+/// counts while it is in the page cache, mapped here or not.
 fn sys_mincore(args: &[u64]) -> SyscallResult {
-    // TODO: Implement mincore syscall
-    Err(SyscallError::NotSupported)
+    let args = extract_args(args, 3)?;
+    let (start, end) = page_range(args[0] as usize, args[1] as usize)?;
+    let vec = UserSlice::new(args[2] as usize, (end - start) / PAGE_SIZE);
+
+    let residency = {
+        let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+        let table = PROC_TABLE.lock();
+        let proc = table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
+        if !proc.vm.covers(start, end) {
+            return Err(SyscallError::OutOfMemory);
+        }
+        let mut residency = Vec::with_capacity(vec.len());
+        for area in proc.vm.overlapping(start, end) {
+            for va in (area.range.start.max(start)..area.range.end.min(end)).step_by(PAGE_SIZE) {
+                let resident = crate::subsystems::mm::vm::user_frame(proc.pagetable, va).is_some()
+                    || area.mapping.as_ref().is_some_and(|mapping| mapping.find_page(area.file_index(va)).is_some());
+                residency.push(resident as u8);
+            }
+        }
+        residency
+    };
+    vec.write(&residency)?;
+    Ok(0)
 }
 
 /// Synchronize a memory-mapped file with storage
 /// Arguments: [addr, length, flags]
 /// Returns: 0 on success, error on failure
+///
+/// Shared file mappings in the range are written back, waited for with
+/// MS_SYNC; other mappings have nothing to flush. MS_INVALIDATE drops the
+/// clean cached pages so that they are read again.
 fn sys_msync(args: &[u64]) -> SyscallResult {
-    use super::common::extract_args;
-    
     let args = extract_args(args, 3)?;
-    let addr = args[0] as usize;
-    let length = args[1] as usize;
+    let (start, end) = page_range(args[0] as usize, args[1] as usize)?;
     let flags = args[2] as i32;
-    
-    // Validate arguments
-    if addr == 0 || length == 0 {
-        return Err(SyscallError::InvalidArgument);
-    }
-    
+
     // Validate flags (MS_ASYNC, MS_INVALIDATE, MS_SYNC)
-    const MS_ASYNC: i32 = 0x1;
-    const MS_INVALIDATE: i32 = 0x2;
-    const MS_SYNC: i32 = 0x4;
-    
-    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0 {
+    if flags & !(posix::MS_ASYNC | posix::MS_INVALIDATE | posix::MS_SYNC) != 0 {
         return Err(SyscallError::InvalidArgument);
     }
-    
-    // Get current process
-    let pid = crate::process::myproc().ok_or(SyscallError::InvalidArgument)?;
-    let mut table = crate::process::PROC_TABLE.lock();
-    let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
-    let pagetable = proc.pagetable;
-    drop(table);
-    
-    // Align to page boundaries
-    let start = addr & !(crate::subsystems::mm::vm::PAGE_SIZE - 1);
-    let aligned_length = (length + crate::subsystems::mm::vm::PAGE_SIZE - 1) & !(crate::subsystems::mm::vm::PAGE_SIZE - 1);
-    let end = start + aligned_length;
-    
-    if crate::arch::memory_layout::is_kernel_address(start) || crate::arch::memory_layout::is_kernel_address(end) {
+    if flags & posix::MS_ASYNC != 0 && flags & posix::MS_SYNC != 0 {
         return Err(SyscallError::InvalidArgument);
     }
-    
-    // Validate memory access permissions (msync requires write access)
-    if !crate::security::validate_memory_access(pid, addr, length, true, false).unwrap_or(false) {
-        return Err(SyscallError::PermissionDenied);
-    }
-    
-    // For each page in range, check if it's mapped and write back to file if needed
-    let mut current = start;
-    let mut synced_pages = 0;
-    
-    while current < end {
-        // Check if page is mapped
-        #[cfg(target_arch = "riscv64")]
-        {
-            use crate::subsystems::mm::vm::riscv64;
-            if let Some(pte_ptr) = unsafe { riscv64::walk(pagetable, current, false) } {
-                if *pte_ptr & riscv64::PTE_V != 0 {
-                    // Page is mapped, check if it's a file-backed mapping
-                    // For now, we just count synced pages
-                    synced_pages += 1;
-                }
-            }
+
+    let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+    let ranges = {
+        let table = PROC_TABLE.lock();
+        let proc = table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
+        if !proc.vm.covers(start, end) {
+            return Err(SyscallError::OutOfMemory);
         }
-        
-        #[cfg(target_arch = "aarch64")]
-        {
-            // For aarch64, check if page is mapped
-            // TODO: Implement proper page table walk for aarch64
-            synced_pages += 1;
+        proc.vm
+            .overlapping(start, end)
+            .filter(|area| area.shared())
+            .filter_map(|area| {
+                let (from, to) = (area.range.start.max(start), area.range.end.min(end));
+                Some((area.mapping.clone()?, area.file_index(from), area.file_index(to - 1)))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Writeback is no business of the process table
+    let mut result = Ok(0);
+    for (mapping, first, last) in ranges {
+        if mapping.sync_range(first, last, flags & posix::MS_SYNC != 0).is_err() {
+            result = Err(SyscallError::IoError);
         }
-        
-        #[cfg(target_arch = "x86_64")]
-        {
-            // For x86_64, check if page is mapped
-            // TODO: Implement proper page table walk for x86_64
-            synced_pages += 1;
+        if flags & posix::MS_INVALIDATE != 0 {
+            mapping.invalidate_range(first, last);
         }
-        
-        current += crate::subsystems::mm::vm::PAGE_SIZE;
     }
-    
-    // For now, we just return success
-    // In a full implementation, we would:
-    // 1. Identify which file backs this mapping
-    // 2. Write back dirty pages to file
-    // 3. Update file metadata
-    // 4. Handle MS_ASYNC, MS_INVALIDATE, and MS_SYNC flags
-    
-    crate::println!("[msync] Synced {} pages at addr 0x{:x}, length 0x{:x}, flags 0x{:x}",
-        synced_pages, addr, length, flags);
-    
-    Ok(0)
+    result
 }
 
+/// Resize and possibly move a mapping
+/// Arguments: [old_addr, old_size, new_size, flags, new_addr]
+///
+/// The old range has to lie within one area. A mapping shrinks in place
+/// and grows in place when the addresses after it are free; otherwise
+/// MREMAP_MAYMOVE lets it move, or MREMAP_FIXED puts it at `new_addr`.
+/// Moving takes the pages along rather than copying them. With
+/// MREMAP_DONTUNMAP a private anonymous mapping always moves and its old
+/// range stays mapped, to be filled with zeroes again as it is touched.
 fn sys_mremap(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 5)?;
-    let old_addr = args[0] as usize;
-    let old_size = args[1] as usize;
-    let new_size = args[2] as usize;
+    let (old_start, old_end) = page_range(args[0] as usize, args[1] as usize)?;
+    let new_size = (args[2] as usize).checked_next_multiple_of(PAGE_SIZE).ok_or(SyscallError::InvalidArgument)?;
     let flags = args[3] as i32;
     let new_addr = args[4] as usize; // Only used with MREMAP_FIXED
+    let old_size = old_end - old_start;
 
     // Validate arguments
-    if old_addr == 0 || old_size == 0 || new_size == 0 {
+    let valid_flags = posix::MREMAP_MAYMOVE | posix::MREMAP_FIXED | posix::MREMAP_DONTUNMAP;
+    if flags & !valid_flags != 0 || old_size == 0 || new_size == 0 {
         return Err(SyscallError::InvalidArgument);
     }
 
-    // Check alignment
-    if old_addr & (PAGE_SIZE - 1) != 0 {
+    // MREMAP_DONTUNMAP moves a mapping as it is
+    let dontunmap = (flags & posix::MREMAP_DONTUNMAP) != 0;
+    if dontunmap && ((flags & posix::MREMAP_MAYMOVE) == 0 || new_size != old_size) {
         return Err(SyscallError::InvalidArgument);
     }
 
-    // Validate flags
-    const MREMAP_MAYMOVE: i32 = 1;
-    const MREMAP_FIXED: i32 = 2;
-    const MREMAP_DONTUNMAP: i32 = 4;
-
-    let valid_flags = MREMAP_MAYMOVE | MREMAP_FIXED | MREMAP_DONTUNMAP;
-    if flags & !valid_flags != 0 {
-        return Err(SyscallError::InvalidArgument);
-    }
-
-    // MREMAP_FIXED requires new_addr to be specified and page-aligned
-    if (flags & MREMAP_FIXED) != 0 {
-        if new_addr == 0 || new_addr & (PAGE_SIZE - 1) != 0 {
+    // MREMAP_FIXED needs MREMAP_MAYMOVE and a page-aligned target clear of the old range
+    let fixed = (flags & posix::MREMAP_FIXED) != 0;
+    if fixed {
+        if (flags & posix::MREMAP_MAYMOVE) == 0 || new_addr % PAGE_SIZE != 0 || new_addr == 0 {
             return Err(SyscallError::InvalidArgument);
         }
-        if crate::arch::memory_layout::is_kernel_address(new_addr) {
+        let new_end = new_addr.checked_add(new_size).ok_or(SyscallError::InvalidArgument)?;
+        if crate::arch::memory_layout::is_kernel_address(new_end - 1) || (new_addr < old_end && old_start < new_end) {
             return Err(SyscallError::InvalidArgument);
         }
     }
 
     let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
-    let mut table = PROC_TABLE.lock();
-    let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
-    let pagetable = proc.pagetable;
-
-    if pagetable.is_null() {
-        return Err(SyscallError::InvalidArgument);
-    }
-
-    // Validate memory access permissions (mremap requires read/write access)
-    if !crate::security::validate_memory_access(pid, old_addr, old_size, true, false).unwrap_or(false) {
-        return Err(SyscallError::PermissionDenied);
-    }
-
-    // Align sizes to page boundaries
-    let aligned_old_size = (old_size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
-    let aligned_new_size = (new_size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
-
-    // Check if old region is valid
-    if crate::arch::memory_layout::is_kernel_address(old_addr) || crate::arch::memory_layout::is_kernel_address(old_addr + aligned_old_size) {
-        return Err(SyscallError::InvalidArgument);
-    }
-
-    // Find the memory region
-    // For now, create a local regions map - this should be replaced with proper global state
-    let mut regions: BTreeMap<usize, MemoryRegion> = BTreeMap::new();
-    let region_key = old_addr;
-    // Find the memory region and clone it to avoid borrowing issues
-    let region = match regions.get(&region_key) {
-        Some(r) if r.virtual_addr <= old_addr && (r.virtual_addr + r.size) >= old_addr + aligned_old_size => {
-            // Clone the region to avoid immutable borrow issues later
-            r.clone()
-        },
-        _ => return Err(SyscallError::InvalidArgument),
-    };
-
-    // Check if region is anonymous (we only support anonymous mappings for now)
-    // For now, we assume anonymous mappings (file-backed mappings not supported yet)
-    // TODO: Add file descriptor field to MemoryRegion if needed
-
-    let result_addr = if aligned_new_size <= aligned_old_size {
-        // Shrinking the mapping
-        handle_mremap_shrink(pagetable, old_addr, aligned_old_size, aligned_new_size, &region, &mut regions)
-    } else {
-        // Expanding the mapping
-        if (flags & MREMAP_MAYMOVE) != 0 {
-            // Try to expand in place first
-            match handle_mremap_expand_inplace(pagetable, old_addr, aligned_old_size, aligned_new_size, &region) {
-                Ok(addr) => Ok(addr),
-                Err(_) => {
-                    // Expansion in place failed, try to move
-                    handle_mremap_move(pagetable, old_addr, aligned_old_size, aligned_new_size, flags, new_addr, &region, &mut regions)
-                }
-            }
-        } else {
-            // Must expand in place
-            handle_mremap_expand_inplace(pagetable, old_addr, aligned_old_size, aligned_new_size, &region)
-        }
-    }?;
-
-    // Update process size if necessary
-    if result_addr + aligned_new_size > proc.sz {
-        proc.sz = result_addr + aligned_new_size;
-    }
-
-    crate::println!("[mremap] Remapped 0x{:x}-0x{:x} to 0x{:x}-0x{:x}, flags=0x{:x}",
-        old_addr, old_addr + aligned_old_size, result_addr, result_addr + aligned_new_size, flags);
-
-    Ok(result_addr as u64)
-}
-
-/// Handle shrinking of a memory mapping
-fn handle_mremap_shrink(
-    pagetable: *mut PageTable,
-    old_addr: usize,
-    old_size: usize,
-    new_size: usize,
-    region: &MemoryRegion,
-    regions: &mut BTreeMap<usize, MemoryRegion>,
-) -> Result<usize, SyscallError> {
-    let pages_to_free = (old_size - new_size) / PAGE_SIZE;
-
-    // Unmap excess pages
-    for i in 0..pages_to_free {
-        let va = old_addr + new_size + i * PAGE_SIZE;
-
-        #[cfg(target_arch = "riscv64")]
-        unsafe {
-            if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, va) {
-                kfree(pa as *mut u8);
-            }
-        }
-
-        #[cfg(target_arch = "aarch64")]
-        unsafe {
-            if crate::subsystems::mm::vm::unmap_page(pagetable, va).is_ok() {
-                // Note: AArch64 unmap_page doesn't return PA
-            }
+    let privileged = can_lock_unlimited(pid)?;
+    let (result_addr, locked) = {
+        let mut table = PROC_TABLE.lock();
+        let proc = table.find(pid).ok_or(SyscallError::InvalidArgument)?;
+        let pagetable = proc.pagetable;
+        if pagetable.is_null() {
+            return Err(SyscallError::InvalidArgument);
         }
 
-        #[cfg(target_arch = "x86_64")]
-        {
-            // TODO: Implement x86_64 unmap
+        let area = proc
+            .vm
+            .find(old_start)
+            .filter(|area| area.range.end >= old_end)
+            .cloned()
+            .ok_or(SyscallError::BadAddress)?;
+        if dontunmap && area.file_backed {
+            return Err(SyscallError::InvalidArgument);
         }
-    }
-
-    // Flush TLB for unmapped pages
-    for i in 0..pages_to_free {
-        let va = old_addr + new_size + i * PAGE_SIZE;
-        flush_tlb_page(va);
-    }
-
-    // Update region
-    let mut updated_region = region.clone();
-    updated_region.size = new_size;
-    regions.insert(old_addr, updated_region);
-
-    Ok(old_addr)
-}
-
-/// Handle expanding a memory mapping in place
-fn handle_mremap_expand_inplace(
-    pagetable: *mut PageTable,
-    old_addr: usize,
-    old_size: usize,
-    new_size: usize,
-    region: &MemoryRegion,
-) -> Result<usize, SyscallError> {
-    let pages_to_add = (new_size - old_size) / PAGE_SIZE;
-
-    // Check if we can expand in place (no conflicts with adjacent mappings)
-    // For now, create a local regions map - this should be replaced with proper global state
-    let regions: BTreeMap<usize, MemoryRegion> = BTreeMap::new();
-    let next_region_start = regions.range(old_addr + old_size..)
-        .next()
-        .map(|(_, r)| r.virtual_addr)
-        .unwrap_or(usize::MAX);
-
-    if old_addr + new_size > next_region_start {
-        return Err(SyscallError::InvalidArgument); // Cannot expand in place
-    }
-    drop(regions);
-
-    // Allocate and map new pages
-    for i in 0..pages_to_add {
-        let va = old_addr + old_size + i * PAGE_SIZE;
-
-        let page = kalloc();
-        if page.is_null() {
-            // Clean up already allocated pages
-            for j in 0..i {
-                let cleanup_va = old_addr + old_size + j * PAGE_SIZE;
-                #[cfg(target_arch = "riscv64")]
-                unsafe {
-                    if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, cleanup_va) {
-                        kfree(pa as *mut u8);
-                    }
-                }
-            }
+        if dontunmap && exceeds_as_limit(proc, proc.sz + new_size) {
             return Err(SyscallError::OutOfMemory);
         }
-
-        // Zero the page
-        unsafe { ptr::write_bytes(page, 0, PAGE_SIZE); }
-
-        // Map page with same permissions as original region
-        let perm = if region.permissions.read { flags::PTE_R } else { 0 } |
-                   if region.permissions.write { flags::PTE_W } else { 0 } |
-                   if region.permissions.execute { flags::PTE_X } else { 0 } |
-                   flags::PTE_U;
-
-        unsafe {
-            if map_page(pagetable, va, page as usize, perm).is_err() {
-                kfree(page);
-                // Clean up already allocated pages
-                for j in 0..i {
-                    let cleanup_va = old_addr + old_size + j * PAGE_SIZE;
-                    #[cfg(target_arch = "riscv64")]
-                    if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, cleanup_va) {
-                        kfree(pa as *mut u8);
-                    }
-                }
+        if new_size > old_size {
+            if area.locked() {
+                check_memlock(proc, new_size - old_size, privileged).map_err(|_| SyscallError::WouldBlock)?;
+            }
+            if exceeds_as_limit(proc, proc.sz + (new_size - old_size)) {
                 return Err(SyscallError::OutOfMemory);
             }
         }
-    }
-
-    Ok(old_addr)
-}
-
-/// Handle moving a memory mapping to a new location
-fn handle_mremap_move(
-    pagetable: *mut PageTable,
-    old_addr: usize,
-    old_size: usize,
-    new_size: usize,
-    flags: i32,
-    new_addr: usize,
-    region: &MemoryRegion,
-    regions: &mut BTreeMap<usize, MemoryRegion>,
-) -> Result<usize, SyscallError> {
-    const MREMAP_FIXED: i32 = 2;
-
-    // Determine new address
-    let target_addr = if (flags & MREMAP_FIXED) != 0 {
-        new_addr
-    } else {
-        // Find a suitable new location
-        find_free_address_range(&region, new_size)?
-    };
-
-    // Check if target location is available
-    // For now, create a local regions map - this should be replaced with proper global state
-    let regions_check: BTreeMap<usize, MemoryRegion> = BTreeMap::new();
-    for existing_region in regions_check.values() {
-        let existing_end = existing_region.virtual_addr + existing_region.size;
-        let target_end = target_addr + new_size;
-        // Check for overlap: not (existing_end <= target_addr || target_end <= existing_region.virtual_addr)
-        if !(existing_end <= target_addr || target_end <= existing_region.virtual_addr) {
-            return Err(SyscallError::InvalidArgument);
-        }
-    }
-    drop(regions_check);
-
-    // Copy data from old location to new location
-    let pages_to_copy = old_size / PAGE_SIZE;
-
-    for i in 0..pages_to_copy {
-        let src_va = old_addr + i * PAGE_SIZE;
-        let dst_va = target_addr + i * PAGE_SIZE;
 
-        // Get source physical address
-        #[cfg(target_arch = "riscv64")]
-        let src_pa = unsafe {
-            match crate::subsystems::mm::vm::riscv64::translate(pagetable, src_va) {
-                Some(pa) => pa,
-                None => continue, // Skip if not mapped
-            }
+        // The part of the area grown onto, as the area would continue
+        let tail = |at: usize| {
+            let mut tail = area.clone();
+            tail.range = at..at + (new_size - old_size);
+            tail.file_offset = area.file_offset + (old_end - area.range.start);
+            tail
         };
 
-        #[cfg(target_arch = "aarch64")]
-        let src_pa = unsafe {
-            match crate::subsystems::mm::vm::walk(pagetable, src_va, false) {
-                Some(pte) => (*pte & !0xFFF) | (src_va & (PAGE_SIZE - 1)),
-                None => continue,
-            }
-        };
-
-        #[cfg(target_arch = "x86_64")]
-        let src_pa = unsafe {
-            match crate::subsystems::mm::vm::walk(pagetable, src_va, false) {
-                Some(pte) => (*pte & !0xFFF) | (src_va & (PAGE_SIZE - 1)),
-                None => continue,
-            }
-        };
-
-        // Allocate new page for destination
-        let dst_page = kalloc();
-        if dst_page.is_null() {
-            // Clean up: unmap any pages we already allocated
-            for j in 0..i {
-                let cleanup_va = target_addr + j * PAGE_SIZE;
-                #[cfg(target_arch = "riscv64")]
-                unsafe {
-                    if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, cleanup_va) {
-                        kfree(pa as *mut u8);
-                    }
-                }
-            }
+        let moving = fixed || dontunmap;
+        let result_addr = if !moving && new_size <= old_size {
+            // Shrinking the mapping
+            unmap_range(proc, old_start + new_size, old_end);
+            old_start
+        } else if !moving
+            && old_end == area.range.end
+            && !crate::arch::memory_layout::is_kernel_address(old_start + new_size - 1)
+            && proc.vm.overlapping(old_end, old_start + new_size).next().is_none()
+        {
+            // Expanding in place over free addresses
+            proc.vm.insert(tail(old_end)).map_err(|_| SyscallError::OutOfMemory)?;
+            proc.vm.coalesce(old_start, old_start + new_size);
+            old_start
+        } else if (flags & posix::MREMAP_MAYMOVE) == 0 {
             return Err(SyscallError::OutOfMemory);
-        }
-
-        // Copy data
-        unsafe {
-            ptr::copy_nonoverlapping(
-                phys_to_kernel_ptr(src_pa) as *const u8,
-                dst_page,
-                PAGE_SIZE,
-            );
-        }
-
-        // Map destination page
-        let perm = if region.permissions.read { flags::PTE_R } else { 0 } |
-                   if region.permissions.write { flags::PTE_W } else { 0 } |
-                   if region.permissions.execute { flags::PTE_X } else { 0 } |
-                   flags::PTE_U;
-
-        unsafe {
-            if map_page(pagetable, dst_va, dst_page as usize, perm).is_err() {
-                kfree(dst_page);
-                // Clean up
-                for j in 0..i {
-                    let cleanup_va = target_addr + j * PAGE_SIZE;
-                    #[cfg(target_arch = "riscv64")]
-                    if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, cleanup_va) {
-                        kfree(pa as *mut u8);
-                    }
-                }
-                return Err(SyscallError::OutOfMemory);
-            }
-        }
-    }
-
-    // Allocate additional pages if new_size > old_size
-    if new_size > old_size {
-        let additional_pages = (new_size - old_size) / PAGE_SIZE;
-
-        for i in 0..additional_pages {
-            let va = target_addr + old_size + i * PAGE_SIZE;
-
-            let page = kalloc();
-            if page.is_null() {
-                // Clean up
-                for j in 0..(pages_to_copy + i) {
-                    let cleanup_va = target_addr + j * PAGE_SIZE;
-                    #[cfg(target_arch = "riscv64")]
-                    unsafe {
-                        if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, cleanup_va) {
-                            kfree(pa as *mut u8);
-                        }
-                    }
-                }
-                return Err(SyscallError::OutOfMemory);
-            }
-
-            // Zero the page
-            unsafe { ptr::write_bytes(page, 0, PAGE_SIZE); }
-
-            // Map page
-            let perm = if region.permissions.read { flags::PTE_R } else { 0 } |
-                       if region.permissions.write { flags::PTE_W } else { 0 } |
-                       if region.permissions.execute { flags::PTE_X } else { 0 } |
-                       flags::PTE_U;
-
-            unsafe {
-                if map_page(pagetable, va, page as usize, perm).is_err() {
-                    kfree(page);
-                    // Clean up
-                    for j in 0..(pages_to_copy + i) {
-                        let cleanup_va = target_addr + j * PAGE_SIZE;
-                        #[cfg(target_arch = "riscv64")]
-                        if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, cleanup_va) {
-                            kfree(pa as *mut u8);
-                        }
-                    }
-                    return Err(SyscallError::OutOfMemory);
-                }
+        } else {
+            // Moving the mapping
+            let target = if fixed {
+                unmap_range(proc, new_addr, new_addr + new_size);
+                new_addr
+            } else {
+                proc.vm.find_free_area(new_size).ok_or(SyscallError::OutOfMemory)?
+            };
+            if new_size < old_size {
+                unmap_range(proc, old_start + new_size, old_end);
             }
-        }
-    }
-
-    // Remove old mapping (unless MREMAP_DONTUNMAP is set)
-    if (flags & 4) == 0 { // MREMAP_DONTUNMAP
-        for i in 0..(old_size / PAGE_SIZE) {
-            let va = old_addr + i * PAGE_SIZE;
-
-            #[cfg(target_arch = "riscv64")]
-            unsafe {
-                if let Some(pa) = crate::subsystems::mm::vm::riscv64::unmap_page(pagetable, va) {
-                    kfree(pa as *mut u8);
+            let moved = old_size.min(new_size);
+            unsafe { move_user_range(pagetable, old_start, target, moved) }.map_err(|_| SyscallError::OutOfMemory)?;
+            for mut piece in proc.vm.remove_range(old_start, old_start + moved) {
+                if dontunmap {
+                    // Its pages went along, so it is no longer locked either
+                    let mut old = piece.clone();
+                    old.lock = VmLock::None;
+                    let _ = proc.vm.insert(old);
                 }
+                let shift = piece.range.start - old_start;
+                piece.range = target + shift..target + shift + piece.len();
+                let _ = proc.vm.insert(piece);
             }
-
-            #[cfg(target_arch = "aarch64")]
-            unsafe {
-                let _ = crate::subsystems::mm::vm::unmap_page(pagetable, va);
-            }
-
-            #[cfg(target_arch = "x86_64")]
-            {
-                // TODO: Implement x86_64 unmap
+            if new_size > old_size {
+                let _ = proc.vm.insert(tail(target + old_size));
             }
+            proc.vm.coalesce(target, target + new_size);
+            target
+        };
 
-            flush_tlb_page(va);
+        // Update process size if necessary
+        if result_addr + new_size > proc.sz {
+            proc.sz = result_addr + new_size;
         }
+        (result_addr, area.lock == VmLock::Locked && new_size > old_size)
+    };
 
-        // Remove old region
-        regions.remove(&old_addr);
-    }
-
-    // Add new region
-    let mut new_region = region.clone();
-    new_region.virtual_addr = target_addr;
-    new_region.size = new_size;
-    regions.insert(target_addr, new_region);
-
-    Ok(target_addr)
-}
-
-/// Find a free address range for moving a mapping
-fn find_free_address_range(region: &MemoryRegion, size: usize) -> Result<usize, SyscallError> {
-    // Implement proper address space management
-    // Start searching from the end of the current region
-    let mut candidate = region.end;
-    
-    // Align to page boundary
-    candidate = (candidate + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
-    
-    // Check if the candidate range is free
-    // For now, we'll use a simple check against known regions
-    // In a real implementation, this would use a proper address space manager
-    let max_user_addr = crate::arch::memory_layout::user_max() - PAGE_SIZE;
-    
-    // Ensure we don't exceed the maximum user address
-    if candidate + size > max_user_addr {
-        return Err(SyscallError::OutOfMemory);
-    }
-    
-    // For now, just return the candidate address
-    // In a real implementation, this would check for conflicts with other mappings
-    Ok(candidate)
-    } else {
-        Err(SyscallError::OutOfMemory)
+    // Memory a locked mapping grows by is locked too
+    if locked {
+        populate(pid, result_addr + old_size, result_addr + new_size)?;
     }
+    Ok(result_addr as u64)
 }
 
 fn sys_remap_file_pages(args: &[u64]) -> SyscallResult {
@@ -1262,6 +1164,39 @@ fn sys_shmctl(args: &[u64]) -> SyscallResult {
     }
 }
 
+/// Swap path argument, for a caller allowed to manage swap
+fn swap_path(ptr: u64) -> Result<alloc::string::String, SyscallError> {
+    const MAX_PATH_LEN: usize = 4096;
+    let pid = myproc().ok_or(SyscallError::InvalidArgument)?;
+    let euid = PROC_TABLE.lock().find_ref(pid).ok_or(SyscallError::InvalidArgument)?.euid;
+    if !crate::security::capabilities::capable(pid as u64, euid, crate::security::capabilities::Capability::SysAdmin) {
+        return Err(SyscallError::PermissionDenied);
+    }
+    let path = crate::subsystems::mm::uaccess::user_string(ptr as usize, MAX_PATH_LEN)?;
+    alloc::string::String::from_utf8(path).map_err(|_| SyscallError::InvalidArgument)
+}
+
+/// Start swapping to a block device or file
+/// Arguments: [path, flags]
+///
+/// SWAP_FLAG_PREFER with a priority in SWAP_FLAG_PRIO_MASK places the area
+/// among the others; without it areas come after those added before.
+fn sys_swapon(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 2)?;
+    let path = swap_path(args[0])?;
+    crate::subsystems::mm::swap::swapon(&path, args[1] as u32)?;
+    Ok(0)
+}
+
+/// Stop swapping to a device or file, reading its pages back in
+/// Arguments: [path]
+fn sys_swapoff(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 1)?;
+    let path = swap_path(args[0])?;
+    crate::subsystems::mm::swap::swapoff(&path)?;
+    Ok(0)
+}
+
 // ============================================================================
 // Optimized Memory Allocator Syscalls
 // ============================================================================
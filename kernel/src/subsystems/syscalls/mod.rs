@@ -30,6 +30,13 @@ pub mod timerfd;
 pub mod eventfd;
 pub mod signalfd;
 pub mod posix_fd;
+pub mod interface;
+pub mod signal_advanced;
+pub mod time;
+pub mod thread;
+pub mod glib;
+pub mod zero_copy;
+pub mod io_uring;
 
 // 重新导出主要接口
 pub use core::*;
@@ -46,3 +53,58 @@ pub use common::*;
 pub use types::*;
 pub use security::*;
 pub use fast_path::*;
+
+use interface::{SyscallCategory, get_syscall_category};
+
+/// Native system call entry point
+///
+/// Routes a NOS category-numbered call to its category's dispatcher and
+/// folds the result into the register convention: the value on success,
+/// `-errno` on failure.
+pub fn dispatch(num: usize, args: &[u64]) -> isize {
+    let result = u32::try_from(num)
+        .map_err(|_| SyscallError::InvalidSyscall)
+        .and_then(|num| dispatch_native(num, args));
+    match result {
+        Ok(value) => value as isize,
+        Err(e) => syscall_error_to_neg_errno(e),
+    }
+}
+
+/// Route a native call by its category nibble
+pub fn dispatch_native(num: u32, args: &[u64]) -> SyscallResult {
+    match get_syscall_category(num) {
+        Some(SyscallCategory::Process) => process::dispatch(num, args),
+        Some(SyscallCategory::FileIo) => fs::file_io::dispatch(num, args),
+        Some(SyscallCategory::Memory) => memory::dispatch(num, args),
+        Some(SyscallCategory::Network) => network::dispatch(num, args),
+        Some(SyscallCategory::Signal) => signal_advanced::dispatch(num, args),
+        Some(SyscallCategory::Time) => time::dispatch(num, args),
+        Some(SyscallCategory::Filesystem) => fs::dispatch::dispatch(num, args).map_err(fs_error),
+        Some(SyscallCategory::Thread) => thread::dispatch(num, args),
+        Some(SyscallCategory::Glib) => glib::dispatch(num, args),
+        Some(SyscallCategory::ZeroCopyIo) => zero_copy::dispatch(num, args),
+        _ => Err(SyscallError::InvalidSyscall),
+    }
+}
+
+/// The filesystem dispatcher reports `KernelError`; bring it to `SyscallError`
+pub(crate) fn fs_error(e: nos_nos_error_handling::unified::KernelError) -> SyscallError {
+    use nos_nos_error_handling::unified::KernelError;
+    match e {
+        KernelError::InvalidSyscall => SyscallError::InvalidSyscall,
+        KernelError::PermissionDenied => SyscallError::PermissionDenied,
+        KernelError::InvalidArgument => SyscallError::InvalidArgument,
+        KernelError::NotFound => SyscallError::NotFound,
+        KernelError::BadAddress => SyscallError::BadAddress,
+        KernelError::BadFileDescriptor => SyscallError::BadFileDescriptor,
+        KernelError::NotADirectory => SyscallError::NotADirectory,
+        KernelError::IsADirectory => SyscallError::IsADirectory,
+        KernelError::DirectoryNotEmpty => SyscallError::DirectoryNotEmpty,
+        KernelError::FileExists => SyscallError::FileExists,
+        KernelError::NotSupported => SyscallError::NotSupported,
+        // SyscallError has no EBUSY
+        KernelError::Busy => SyscallError::WouldBlock,
+        _ => SyscallError::IoError,
+    }
+}
@@ -2,6 +2,7 @@
 
 use super::*;
 use crate::syscalls::common::{SyscallError, SyscallResult};
+use crate::subsystems::mm::uaccess::UserSlice;
 
 /// Send data on a socket
 ///
@@ -75,13 +76,14 @@ pub fn sys_send(args: &[u64]) -> SyscallResult {
 
     // Extract data before borrowing socket
     let remote_addr = socket_entry.remote_addr;
+    let data = UserSlice::new(buf as usize, send_len).read_to_vec()?;
 
     // Perform actual send operation using the socket implementation
     // Use zero-copy optimization for large buffers (>4KB)
     {
         let mut socket_guard = socket_entry.socket.lock();
         if let Some(socket) = socket_guard.as_mut() {
-            let data = unsafe { core::slice::from_raw_parts(buf, send_len) };
+            let data = &data[..];
 
             match socket {
                 Socket::Tcp(tcp_socket) => {
@@ -198,9 +200,10 @@ pub fn sys_recv(args: &[u64]) -> SyscallResult {
     // Use zero-copy optimization for large buffers (>4KB)
     let mut socket_guard = socket_entry.socket.lock();
     if let Some(socket) = socket_guard.as_mut() {
-        let recv_buf = unsafe { core::slice::from_raw_parts_mut(buf, recv_len) };
+        let mut kbuf = alloc::vec![0u8; recv_len];
+        let recv_buf = &mut kbuf[..];
 
-        match socket {
+        let received = match socket {
             Socket::Tcp(tcp_socket) => {
                 // Use zero-copy receive for large buffers (>4KB)
                 let received = if recv_len > 4096 {
@@ -230,7 +233,10 @@ pub fn sys_recv(args: &[u64]) -> SyscallResult {
                 Ok(received as u64)
             }
             _ => Err(SyscallError::NotSupported),
-        }
+        }?;
+        drop(socket_guard);
+        UserSlice::new(buf as usize, recv_len).write(&kbuf[..received as usize])?;
+        Ok(received)
     } else {
         Err(SyscallError::NotFound)
     }
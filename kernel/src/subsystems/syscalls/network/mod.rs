@@ -6,6 +6,36 @@ use nos_api::{Result, interfaces::SyscallHandler};
 use alloc::sync::Arc;
 use crate::subsystems::net::enhanced_network_manager;
 
+pub mod data;
+pub mod options;
+pub mod socket;
+
+use crate::syscalls::common::{SyscallError, SyscallResult};
+
+/// Dispatch network syscalls
+pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
+    match syscall_id {
+        0x4000 => socket::sys_socket(args),       // socket
+        0x4001 => socket::sys_bind(args),         // bind
+        0x4002 => socket::sys_connect(args),      // connect
+        0x4003 => socket::sys_listen(args),       // listen
+        0x4004 => socket::sys_accept(args),       // accept
+        0x4005 => data::sys_send(args),           // send
+        0x4006 => data::sys_recv(args),           // recv
+        0x4007 => data::sys_sendto(args),         // sendto
+        0x4008 => data::sys_recvfrom(args),       // recvfrom
+        0x4009 => socket::sys_shutdown(args),     // shutdown
+        0x400A => options::sys_getsockname(args), // getsockname
+        0x400B => options::sys_getpeername(args), // getpeername
+        0x400C => options::sys_setsockopt(args),  // setsockopt
+        0x400D => options::sys_getsockopt(args),  // getsockopt
+        0x400E => data::sys_sendmsg(args),        // sendmsg
+        0x400F => data::sys_recvmsg(args),        // recvmsg
+        0x4010 => socket::sys_socketpair(args),   // socketpair
+        _ => Err(SyscallError::InvalidSyscall),
+    }
+}
+
 /// 网络系统调用处理器
 pub struct NetworkSyscallHandler {
     // Enhanced network manager for POSIX compatibility
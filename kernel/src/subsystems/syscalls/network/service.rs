@@ -7,6 +7,7 @@ use nos_nos_error_handling::unified::KernelError;
 use super::socket;
 use super::interface;
 use super::options;
+use crate::subsystems::mm::uaccess::UserSlice;
 use crate::syscalls::services::{BaseService, ServiceStatus, SyscallService};
 use alloc::boxed::Box;
 use alloc::string::{String, ToString};
@@ -298,22 +299,20 @@ impl SyscallService for NetworkService {
             }
             0x8001 => { // bind
                 let sockfd = args.get(0).copied().unwrap_or(0) as i32;
-                let addr_ptr = args.get(1).copied().unwrap_or(0) as *const u8;
+                let addr_ptr = args.get(1).copied().unwrap_or(0) as usize;
                 let addr_len = args.get(2).copied().unwrap_or(0) as usize;
                 
-                // TODO: Safely read address from user space
-                let addr = unsafe { core::slice::from_raw_parts(addr_ptr, addr_len) };
-                self.bind_socket(sockfd, addr)?;
+                let addr = UserSlice::new(addr_ptr, addr_len).read_to_vec().map_err(|_| KernelError::BadAddress)?;
+                self.bind_socket(sockfd, &addr)?;
                 Ok(0)
             }
             0x8004 => { // connect
                 let sockfd = args.get(0).copied().unwrap_or(0) as i32;
-                let addr_ptr = args.get(1).copied().unwrap_or(0) as *const u8;
+                let addr_ptr = args.get(1).copied().unwrap_or(0) as usize;
                 let addr_len = args.get(2).copied().unwrap_or(0) as usize;
                 
-                // TODO: Safely read address from user space
-                let addr = unsafe { core::slice::from_raw_parts(addr_ptr, addr_len) };
-                self.connect_socket(sockfd, addr)?;
+                let addr = UserSlice::new(addr_ptr, addr_len).read_to_vec().map_err(|_| KernelError::BadAddress)?;
+                self.connect_socket(sockfd, &addr)?;
                 Ok(0)
             }
             0x8002 => { // listen
@@ -329,22 +328,21 @@ impl SyscallService for NetworkService {
             }
             0x8005 => { // send
                 let sockfd = args.get(0).copied().unwrap_or(0) as i32;
-                let buf_ptr = args.get(1).copied().unwrap_or(0) as *const u8;
+                let buf_ptr = args.get(1).copied().unwrap_or(0) as usize;
                 let len = args.get(2).copied().unwrap_or(0) as usize;
                 
-                // TODO: Safely read data from user space
-                let buf = unsafe { core::slice::from_raw_parts(buf_ptr, len) };
-                let sent = self.send_data(sockfd, buf)?;
+                let buf = UserSlice::new(buf_ptr, len).read_to_vec().map_err(|_| KernelError::BadAddress)?;
+                let sent = self.send_data(sockfd, &buf)?;
                 Ok(sent as u64)
             }
             0x8006 => { // recv
                 let sockfd = args.get(0).copied().unwrap_or(0) as i32;
-                let buf_ptr = args.get(1).copied().unwrap_or(0) as *mut u8;
+                let buf_ptr = args.get(1).copied().unwrap_or(0) as usize;
                 let len = args.get(2).copied().unwrap_or(0) as usize;
                 
-                // TODO: Safely write data to user space
-                let buf = unsafe { core::slice::from_raw_parts_mut(buf_ptr, len) };
-                let received = self.recv_data(sockfd, buf)?;
+                let mut buf = alloc::vec![0u8; len];
+                let received = self.recv_data(sockfd, &mut buf)?;
+                UserSlice::new(buf_ptr, len).write(&buf[..received]).map_err(|_| KernelError::BadAddress)?;
                 Ok(received as u64)
             }
             _ => {
@@ -3,6 +3,7 @@
 use super::*;
 use crate::syscalls::common::{SyscallError, SyscallResult};
 use crate::net::socket::SocketAddr;
+use crate::subsystems::mm::uaccess::{UserPtr, UserSlice};
 
 /// Create a new socket
 ///
@@ -11,7 +12,7 @@ use crate::net::socket::SocketAddr;
 ///
 /// # Arguments
 ///
-/// * `args[0]` - `domain`: Address family (e.g., `AF_INET` for IPv4)
+/// * `args[0]` - `domain`: Address family (`AF_INET` for IPv4, `AF_INET6` for IPv6)
 /// * `args[1]` - `type_`: Socket type (e.g., `SOCK_STREAM` for TCP, `SOCK_DGRAM` for UDP)
 /// * `args[2]` - `protocol`: Protocol to use (0 for default protocol)
 ///
@@ -36,7 +37,7 @@ use crate::net::socket::SocketAddr;
 /// # Errors
 ///
 /// This function will return an error if:
-/// - The domain is not supported (currently only `AF_INET` is supported)
+/// - The domain is not supported (only `AF_INET` and `AF_INET6` are supported)
 /// - The socket type is not supported
 /// - The protocol doesn't match the socket type
 /// - System resources are exhausted
@@ -90,7 +91,7 @@ pub fn sys_socket(args: &[u64]) -> SyscallResult {
 
     // Store socket in unified file descriptor system
     let socket_arc = socket.ok_or(SyscallError::IoError)?;
-    match crate::fs::file::file_socket_new(socket_arc, true, true) {
+    match crate::fs::file::file_socket_new(socket_arc.clone(), true, true) {
         Some(file_fd) => {
             // Create socket entry for tracking (legacy compatibility)
             let socket_entry = Arc::new(SocketEntry {
@@ -101,7 +102,7 @@ pub fn sys_socket(args: &[u64]) -> SyscallResult {
                 local_addr: None,
                 remote_addr: None,
                 state: SocketState::Uninitialized,
-                socket: Mutex::new(None), // Already stored in file system
+                socket: Mutex::new(Some(socket_arc)), // Shares state with the file's copy
                 connection_id: None,
             });
 
@@ -164,7 +165,7 @@ pub fn sys_bind(args: &[u64]) -> SyscallResult {
     let addrlen = args[2] as usize;
 
     // Validate parameters
-    if addrlen < core::mem::size_of::<crate::posix::Sockaddr>() {
+    if addr.is_null() || addrlen < SocketAddr::SOCKADDR_IN_LEN {
         return Err(SyscallError::InvalidArgument);
     }
 
@@ -174,8 +175,10 @@ pub fn sys_bind(args: &[u64]) -> SyscallResult {
         None => return Err(SyscallError::NotFound),
     };
 
-    // Parse socket address
-    let socket_addr = match SocketAddr::from_posix_sockaddr(unsafe { &*addr }) {
+    // Parse socket address (sockaddr_in or sockaddr_in6)
+    let mut addr_bytes = [0u8; SocketAddr::SOCKADDR_IN6_LEN];
+    let addr_len = UserSlice::new(addr as usize, addrlen).read(&mut addr_bytes)?;
+    let socket_addr = match SocketAddr::from_sockaddr_bytes(&addr_bytes[..addr_len]) {
         Some(addr) => addr,
         None => return Err(SyscallError::InvalidArgument),
     };
@@ -185,48 +188,20 @@ pub fn sys_bind(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::InvalidArgument);
     }
 
-    // Check if address is already in use (for TCP sockets)
-    if socket_entry.socket_type.is_connection_oriented() && !socket_entry.options.reuse_addr {
-        // TODO: Check if address is already bound
-        // For now, just allow it
-    }
-
     // Perform actual binding using the socket implementation
     if let Some(ref mut socket) = socket_entry.socket.lock().as_mut() {
         match socket {
             Socket::Tcp(tcp_socket) => {
-                // For TCP sockets, use the TCP connection manager
-                // 使用 tcp_socket 获取或设置 TCP 特定的选项
-                let _tcp_socket_ref = tcp_socket; // 使用 tcp_socket 进行验证或配置
-                let mut tcp_manager = TcpConnectionManager::new();
-                // Map generic SocketOptions -> TcpOptions explicitly
-                let opts = socket_entry.options.clone();
-                let tcp_opts = crate::net::tcp::manager::TcpOptions {
-                    keep_alive: opts.keep_alive,
-                    keep_alive_interval: 30,
-                    keep_alive_time: 7200,
-                    keep_alive_probes: 9,
-                    nagle_enabled: !opts.nodelay,
-                    reuse_addr: opts.reuse_addr,
-                    reuse_port: opts.reuse_port,
-                    recv_buf_size: opts.rcvbuf,
-                    send_buf_size: opts.sndbuf,
-                };
-
-                let conn_id = tcp_manager.listen(
-                    socket_addr.ipv4_addr().unwrap_or(Ipv4Addr::UNSPECIFIED),
-                    socket_addr.port,
-                    tcp_opts
-                ).map_err(|e: crate::net::tcp::manager::TcpError| SyscallError::from(e))?;
-
-                // Update socket entry with connection ID
+                // The TCP manager checks the address against other bindings
+                tcp_socket.bind(socket_addr).map_err(|e: crate::net::socket::SocketError| SyscallError::from(e))?;
+
+                // Update socket entry
                 let socket_table = get_socket_table();
                 if let Some(Some(entry)) = socket_table.get_mut(fd as usize) {
                     // Since SocketEntry is Clone, we can create a new entry with updated values
                     let old_entry = entry.as_ref();
                     let mut new_entry = old_entry.clone();
-                    new_entry.local_addr = Some(socket_addr);
-                    new_entry.connection_id = Some(conn_id);
+                    new_entry.local_addr = tcp_socket.local_addr();
                     new_entry.state = SocketState::Bound;
                     *entry = Arc::new(new_entry);
                 }
@@ -400,7 +375,7 @@ pub fn sys_accept(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::InvalidArgument);
     }
 
-    let addrlen_value = unsafe { *addrlen };
+    let addrlen_value = UserPtr::<usize>::new(addrlen as usize).read()?;
     if addrlen_value < core::mem::size_of::<crate::posix::Sockaddr>() {
         return Err(SyscallError::InvalidArgument);
     }
@@ -445,18 +420,11 @@ pub fn sys_accept(args: &[u64]) -> SyscallResult {
                         connection_id: None,
                     });
 
-                    // Set peer address in user space
-                    let peer_posix_addr = peer_addr.to_posix_sockaddr();
-                    unsafe {
-                        // Copy address to user space
-                        core::ptr::copy_nonoverlapping(
-                            peer_posix_addr.sa_family as *const u8,
-                            addr as *mut u8,
-                            core::mem::size_of::<crate::posix::Sockaddr>(),
-                        );
-                        // Update addrlen
-                        *addrlen = core::mem::size_of::<crate::posix::Sockaddr>();
-                    }
+                    // Set peer address in user space, truncated to the caller's buffer
+                    let peer_bytes = peer_addr.to_sockaddr_bytes();
+                    UserSlice::new(addr as usize, addrlen_value).write(&peer_bytes)?;
+                    // Update addrlen with the full address length
+                    UserPtr::<usize>::new(addrlen as usize).write(&peer_bytes.len())?;
 
                     // Store in socket table
                     set_socket_entry(new_fd as i32, Some(new_socket_entry));
@@ -530,7 +498,7 @@ pub fn sys_connect(args: &[u64]) -> SyscallResult {
     let addrlen = args[2] as usize;
 
     // Validate parameters
-    if addrlen < core::mem::size_of::<crate::posix::Sockaddr>() {
+    if addr.is_null() || addrlen < SocketAddr::SOCKADDR_IN_LEN {
         return Err(SyscallError::InvalidArgument);
     }
 
@@ -545,8 +513,10 @@ pub fn sys_connect(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::NotSupported);
     }
 
-    // Parse socket address
-    let socket_addr = match SocketAddr::from_posix_sockaddr(unsafe { &*addr }) {
+    // Parse socket address (sockaddr_in or sockaddr_in6)
+    let mut addr_bytes = [0u8; SocketAddr::SOCKADDR_IN6_LEN];
+    let addr_len = UserSlice::new(addr as usize, addrlen).read(&mut addr_bytes)?;
+    let socket_addr = match SocketAddr::from_sockaddr_bytes(&addr_bytes[..addr_len]) {
         Some(addr) => addr,
         None => return Err(SyscallError::InvalidArgument),
     };
@@ -556,12 +526,6 @@ pub fn sys_connect(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::InvalidArgument);
     }
 
-    // Check if socket is already bound
-    if socket_entry.local_addr.is_none() {
-        // Auto-bind to any available address
-        // This would integrate with socket manager
-    }
-
     // Check if already connected
     if socket_entry.state == SocketState::Connected {
         return Err(SyscallError::InvalidArgument);
@@ -578,38 +542,13 @@ pub fn sys_connect(args: &[u64]) -> SyscallResult {
         if let Some(ref mut socket) = new_entry.socket.lock().as_mut() {
             match socket {
                 Socket::Tcp(tcp_socket) => {
-                    // Use TCP connection manager for proper connection establishment
-                    let mut tcp_manager = TcpConnectionManager::new();
-                    let opts = new_entry.options.clone();
-                    let tcp_opts = crate::net::tcp::manager::TcpOptions {
-                        keep_alive: opts.keep_alive,
-                        keep_alive_interval: 30,
-                        keep_alive_time: 7200,
-                        keep_alive_probes: 9,
-                        nagle_enabled: !opts.nodelay,
-                        reuse_addr: opts.reuse_addr,
-                        reuse_port: opts.reuse_port,
-                        recv_buf_size: opts.rcvbuf,
-                        send_buf_size: opts.sndbuf,
-                    };
-
-                    // Get local address (auto-bind if not bound)
-                    let local_addr = new_entry.local_addr
-                        .unwrap_or_else(|| SocketAddr::new_ipv4(Ipv4Addr::UNSPECIFIED, 0));
-                    
-                    // Establish connection
-                    let conn_id = tcp_manager.connect(
-                        local_addr.ipv4_addr().unwrap_or(Ipv4Addr::UNSPECIFIED),
-                        socket_addr.ipv4_addr().unwrap_or(Ipv4Addr::UNSPECIFIED),
-                        socket_addr.port,
-                        tcp_opts
-                    ).map_err(|e: crate::net::tcp::manager::TcpError| SyscallError::from(e))?;
-
-                    // Update socket with connection ID
-                    new_entry.connection_id = Some(conn_id);
-                    
-                    // Also call connect on socket wrapper for state update
-                    tcp_socket.connect(socket_addr).map_err(|e: crate::net::socket::SocketError| SyscallError::from(e))?;
+                    // Auto-binds an ephemeral port; a non-blocking socket
+                    // returns while the handshake runs
+                    match tcp_socket.connect(socket_addr) {
+                        Ok(()) | Err(crate::net::socket::SocketError::InProgress) => {}
+                        Err(e) => return Err(SyscallError::from(e)),
+                    }
+                    new_entry.local_addr = tcp_socket.local_addr();
                 }
                 _ => return Err(SyscallError::NotSupported), // Only TCP sockets can connect
             }
@@ -619,7 +558,6 @@ pub fn sys_connect(args: &[u64]) -> SyscallResult {
         new_entry.state = SocketState::Connected;
         *entry = Arc::new(new_entry);
 
-        // This would be errno_neg(EINPROGRESS) if non-blocking
         Ok(0)
     } else {
         Err(SyscallError::NotFound)
@@ -652,7 +590,17 @@ pub fn sys_shutdown(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::InvalidArgument);
     }
 
-    Ok(0)
+    match file.socket.clone() {
+        Some(Socket::Tcp(mut tcp_socket)) => {
+            drop(ft);
+            let read = how != crate::posix::SHUT_WR;
+            let write = how != crate::posix::SHUT_RD;
+            tcp_socket.shutdown(read, write).map_err(SyscallError::from)?;
+            Ok(0)
+        }
+        Some(_) => Ok(0),
+        None => Err(SyscallError::BadFileDescriptor),
+    }
 }
 
 /// Create socket pair
@@ -768,10 +716,7 @@ pub fn sys_socketpair(args: &[u64]) -> SyscallResult {
     set_socket_entry(fd2 as i32, Some(socket_entry2));
     
     // Return file descriptors to user space
-    unsafe {
-        *fds_ptr = file_fd1 as i32;
-        *fds_ptr.add(1) = file_fd2 as i32;
-    }
+    UserPtr::<[i32; 2]>::new(fds_ptr as usize).write(&[file_fd1 as i32, file_fd2 as i32])?;
     
     Ok(0)
 }
@@ -21,14 +21,14 @@ pub struct FileDescriptorValidator;
 impl FileDescriptorValidator {
     /// 验证文件描述符是否有效
     pub fn is_valid_fd(fd: i32) -> bool {
-        fd >= 0 && (fd as usize) < crate::process::NOFILE
+        fd >= 0 && (fd as usize) < crate::process::fdtable::NR_OPEN
     }
 
     /// 验证文件描述符范围
     pub fn validate_fd_range(fd: i32) -> Result<(), crate::syscalls::common::SyscallError> {
         if fd < 0 {
             Err(crate::syscalls::common::SyscallError::BadFileDescriptor)
-        } else if (fd as usize) >= crate::process::NOFILE {
+        } else if (fd as usize) >= crate::process::fdtable::NR_OPEN {
             Err(crate::syscalls::common::SyscallError::BadFileDescriptor)
         } else {
             Ok(())
@@ -43,7 +43,7 @@ impl FileDescriptorValidator {
         let proc_table = crate::process::PROC_TABLE.lock();
         let proc = proc_table.find_ref(pid).ok_or(crate::syscalls::common::SyscallError::InvalidArgument)?;
         
-        proc.ofile[fd as usize].ok_or(crate::syscalls::common::SyscallError::BadFileDescriptor)
+        proc.ofile.lock().get(fd).ok_or(crate::syscalls::common::SyscallError::BadFileDescriptor)
     }
 }
 
@@ -1,6 +1,7 @@
 // Process management syscalls
 
 use crate::syscalls::common::{SyscallError, SyscallResult, extract_args};
+use crate::subsystems::mm::uaccess::UserPtr;
 use crate::subsystems::mm::vm::copyinstr;
 use crate::process::{myproc, PROC_TABLE};
 use alloc::string::String;
@@ -53,6 +54,12 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
         0x101F => sys_setrlimit(args),      // setrlimit
         0x1020 => sys_wait4(args),          // wait4
         0x1021 => sys_raise(args),          // raise
+        0x1022 => sys_seccomp(args),        // seccomp
+        0x1023 => sys_prlimit64(args),      // prlimit64
+        0x1024 => sys_ptrace(args),         // ptrace
+        0x1025 => sys_add_key(args),        // add_key
+        0x1026 => sys_request_key(args),    // request_key
+        0x1027 => sys_keyctl(args),         // keyctl
         _ => Err(SyscallError::InvalidSyscall),
     }
 }
@@ -66,8 +73,10 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
 /// Returns: 0 in child process, child PID in parent process, error on failure
 fn sys_fork(_args: &[u64]) -> SyscallResult {
     // Call the fork implementation
-    match crate::process::manager::fork() {
-        Some(child_pid) => {
+    match crate::process::manager::fork_with(false) {
+        Ok(child_pid) => {
+            crate::process::ptrace::report_new_child(child_pid, crate::process::ptrace::PTRACE_EVENT_FORK);
+
             // Check if current process is the child
             if let Some(current_pid) = crate::process::myproc() {
                 if current_pid == child_pid {
@@ -81,7 +90,7 @@ fn sys_fork(_args: &[u64]) -> SyscallResult {
                 Err(SyscallError::NotFound)
             }
         }
-        None => Err(SyscallError::OutOfMemory),
+        Err(err) => Err(err.into()),
     }
 }
 
@@ -135,27 +144,15 @@ fn sys_execve(args: &[u64]) -> SyscallResult {
     let path_str = String::from_utf8(path_slice.to_vec())
         .map_err(|_| SyscallError::InvalidArgument)?;
 
-    // Resolve path
-    let abs_path = {
-        let pid = crate::process::myproc().ok_or(SyscallError::NotFound)?;
-        let proc_table = crate::process::manager::PROC_TABLE.lock();
-        let proc = proc_table.find_ref(pid).ok_or(SyscallError::NotFound)?;
-        let cwd = proc.cwd_path.as_ref().map(|s| s.as_str()).unwrap_or("/");
-        
-        if path_str.starts_with('/') {
-            path_str
-        } else if cwd == "/" {
-            format!("/{}", path_str)
-        } else {
-            format!("{}/{}", cwd, path_str)
-        }
+    // Resolve and open the program inside the caller's root
+    let ctx = crate::process::fs_context()?;
+    let lookup = crate::vfs::LookupFlags(crate::vfs::LookupFlags::FOLLOW);
+    let mut file = crate::vfs::vfs().open_at(&ctx, None, &path_str, lookup, crate::posix::O_RDONLY as u32)?;
+    let abs_path = match &file.loc {
+        Some(loc) => ctx.display_path(loc),
+        None => path_str.clone(),
     };
 
-    // Open file via VFS
-    let vfs = crate::vfs::vfs();
-    let mut file = vfs.open(&abs_path, crate::posix::O_RDONLY as u32)
-        .map_err(|_| SyscallError::NotFound)?;
-
     // Read file contents
     let mut buf = alloc::vec::Vec::new();
     let mut tmp = [0u8; 512];
@@ -169,7 +166,7 @@ fn sys_execve(args: &[u64]) -> SyscallResult {
     }
 
     // Execute the program
-    match crate::process::exec::exec(&buf, &arg_slices, &env_slices, Some(abs_path.as_bytes())) {
+    match crate::process::exec::exec(&buf, file.inode.mapping(), &arg_slices, &env_slices, Some(abs_path.as_bytes())) {
         Ok(_) => {
             // exec does not return on success
             // This should never be reached, but we include it for completeness
@@ -196,35 +193,16 @@ fn sys_waitpid(args: &[u64]) -> SyscallResult {
     let pagetable = proc.pagetable;
     drop(proc_table);
 
-    // Convert status pointer
-    let status_mut_ptr = if status_ptr != 0 {
-        Some(status_ptr as *mut i32)
-    } else {
-        None
-    };
-
-    // Call wait implementation with options
-    match crate::process::manager::waitpid(pid, status_mut_ptr.unwrap_or(core::ptr::null_mut()), options) {
+    // Call wait implementation with options; the status it reports may
+    // be a stop rather than an exit, so it is not re-read from the child
+    let mut status = 0i32;
+    match crate::process::manager::waitpid(pid, &mut status, options) {
         Some(child_pid) => {
-            // Write status to user space if pointer provided
-            if let Some(_ptr) = status_mut_ptr {
-                if !pagetable.is_null() {
-                    // Get exit status from process table
-                    let proc_table = crate::process::manager::PROC_TABLE.lock();
-                    if let Some(child_proc) = proc_table.find_ref(child_pid) {
-                        let exit_status = child_proc.xstate;
-                        drop(proc_table);
-
-                        // Copy status to user space
-                        unsafe {
-                            let status_slice = core::slice::from_raw_parts_mut(
-                                &exit_status as *const i32 as *mut u8,
-                                core::mem::size_of::<i32>()
-                            );
-                            crate::subsystems::mm::vm::copyout(pagetable, status_ptr, status_slice.as_ptr(), status_slice.len())
-                                .map_err(|_| SyscallError::BadAddress)?;
-                        }
-                    }
+            // Copy status to user space if a pointer was provided
+            if status_ptr != 0 && !pagetable.is_null() {
+                unsafe {
+                    crate::subsystems::mm::vm::copyout(pagetable, status_ptr, &status as *const i32 as *const u8, core::mem::size_of::<i32>())
+                        .map_err(|_| SyscallError::BadAddress)?;
                 }
             }
             Ok(child_pid as u64)
@@ -308,6 +286,14 @@ fn read_user_argv_array(pagetable: *mut crate::subsystems::mm::vm::PageTable, ad
     Some(args)
 }
 
+/// Changing the effective IDs makes the process dumpable only as
+/// `fs.suid_dumpable` allows, as on Linux
+fn creds_changed(proc: &mut crate::process::Proc, old_ids: (u32, u32)) {
+    if (proc.euid, proc.egid) != old_ids {
+        proc.dumpable = crate::subsystems::process::coredump::suid_dumpable();
+    }
+}
+
 fn sys_setuid(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 1)?;
     let uid = args[0] as u32;
@@ -315,6 +301,7 @@ fn sys_setuid(args: &[u64]) -> SyscallResult {
     let pid = myproc().ok_or(SyscallError::NotFound)?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(SyscallError::NotFound)?;
+    let old_ids = (proc.euid, proc.egid);
     
     // Check permissions: only root (euid=0) can set arbitrary UIDs
     // Non-root can only set uid to real uid, effective uid, or saved uid
@@ -334,6 +321,7 @@ fn sys_setuid(args: &[u64]) -> SyscallResult {
         proc.euid = uid;
     }
     
+    creds_changed(proc, old_ids);
     Ok(0)
 }
 
@@ -351,6 +339,7 @@ fn sys_setgid(args: &[u64]) -> SyscallResult {
     let pid = myproc().ok_or(SyscallError::NotFound)?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(SyscallError::NotFound)?;
+    let old_ids = (proc.euid, proc.egid);
     
     // Check permissions: only root (euid=0) can set arbitrary GIDs
     // Non-root can only set gid to real gid, effective gid, or saved gid
@@ -370,6 +359,7 @@ fn sys_setgid(args: &[u64]) -> SyscallResult {
         proc.egid = gid;
     }
     
+    creds_changed(proc, old_ids);
     Ok(0)
 }
 
@@ -402,6 +392,7 @@ fn sys_setreuid(args: &[u64]) -> SyscallResult {
     let pid = myproc().ok_or(SyscallError::NotFound)?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(SyscallError::NotFound)?;
+    let old_ids = (proc.euid, proc.egid);
     
     // Check permissions
     if proc.euid != 0 {
@@ -427,6 +418,7 @@ fn sys_setreuid(args: &[u64]) -> SyscallResult {
         proc.suid = euid as u32;
     }
     
+    creds_changed(proc, old_ids);
     Ok(0)
 }
 
@@ -438,6 +430,7 @@ fn sys_setregid(args: &[u64]) -> SyscallResult {
     let pid = myproc().ok_or(SyscallError::NotFound)?;
     let mut table = PROC_TABLE.lock();
     let proc = table.find(pid).ok_or(SyscallError::NotFound)?;
+    let old_ids = (proc.euid, proc.egid);
     
     // Check permissions
     if proc.euid != 0 {
@@ -463,6 +456,7 @@ fn sys_setregid(args: &[u64]) -> SyscallResult {
         proc.sgid = egid as u32;
     }
     
+    creds_changed(proc, old_ids);
     Ok(0)
 }
 
@@ -857,14 +851,18 @@ const PR_SET_DUMPABLE: i32 = 4;
 const PR_GET_DUMPABLE: i32 = 3;
 const PR_SET_KEEPCAPS: i32 = 8;
 const PR_GET_KEEPCAPS: i32 = 7;
+const PR_GET_SECCOMP: i32 = 21;
+const PR_SET_SECCOMP: i32 = 22;
+const PR_SET_NO_NEW_PRIVS: i32 = 38;
+const PR_GET_NO_NEW_PRIVS: i32 = 39;
 
 fn sys_prctl(args: &[u64]) -> SyscallResult {
     let args = extract_args(args, 5)?;
     let option = args[0] as i32;
     let arg2 = args[1];
-    let _arg3 = args[2];
-    let _arg4 = args[3];
-    let _arg5 = args[4];
+    let arg3 = args[2];
+    let arg4 = args[3];
+    let arg5 = args[4];
     
     match option {
         PR_SET_NAME => {
@@ -893,18 +891,177 @@ fn sys_prctl(args: &[u64]) -> SyscallResult {
             }
             Ok(0)
         }
-        PR_SET_DUMPABLE | PR_SET_KEEPCAPS => {
-            // Accept these options but don't enforce
+        PR_SET_DUMPABLE => {
+            use crate::subsystems::process::coredump::{SUID_DUMP_DISABLE, SUID_DUMP_USER};
+            // SUID_DUMP_ROOT is for fs.suid_dumpable only
+            let mode = match arg2 {
+                0 => SUID_DUMP_DISABLE,
+                1 => SUID_DUMP_USER,
+                _ => return Err(SyscallError::InvalidArgument),
+            };
+            let my_pid = myproc().ok_or(SyscallError::NotFound)?;
+            let mut table = PROC_TABLE.lock();
+            table.find(my_pid).ok_or(SyscallError::NotFound)?.dumpable = mode;
             Ok(0)
         }
         PR_GET_DUMPABLE => {
-            // Return 1 (SUID_DUMP_USER - normal dump)
-            Ok(1)
+            let my_pid = myproc().ok_or(SyscallError::NotFound)?;
+            let table = PROC_TABLE.lock();
+            Ok(table.find_ref(my_pid).ok_or(SyscallError::NotFound)?.dumpable as u64)
+        }
+        PR_SET_KEEPCAPS => {
+            // Accept but don't enforce
+            Ok(0)
         }
         PR_GET_KEEPCAPS => {
             // Return 0 (keepcaps not set)
             Ok(0)
         }
+        PR_GET_SECCOMP => {
+            Ok(crate::security::seccomp::current_mode() as u64)
+        }
+        PR_SET_SECCOMP => {
+            // prctl(PR_SET_SECCOMP, mode, prog): same as seccomp(mode, 0, prog)
+            let op = match arg2 as u32 {
+                1 => crate::security::seccomp::SECCOMP_SET_MODE_STRICT,
+                2 => crate::security::seccomp::SECCOMP_SET_MODE_FILTER,
+                _ => return Err(SyscallError::InvalidArgument),
+            };
+            sys_seccomp(&[op as u64, 0, arg3])
+        }
+        PR_SET_NO_NEW_PRIVS => {
+            if arg2 != 1 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                return Err(SyscallError::InvalidArgument);
+            }
+            crate::security::seccomp::set_no_new_privs();
+            Ok(0)
+        }
+        PR_GET_NO_NEW_PRIVS => {
+            if arg2 != 0 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                return Err(SyscallError::InvalidArgument);
+            }
+            Ok(crate::security::seccomp::no_new_privs() as u64)
+        }
+        _ => Err(SyscallError::InvalidArgument),
+    }
+}
+
+/// seccomp(operation, flags, args)
+///
+/// Filters come as a `struct sock_fprog` and are verified before they are
+/// stacked on the caller. With SECCOMP_FILTER_FLAG_NEW_LISTENER the result is
+/// the listener fd; a TSYNC failure returns the thread that could not be
+/// synchronized.
+fn sys_seccomp(args: &[u64]) -> SyscallResult {
+    use crate::security::seccomp::*;
+    use crate::security::seccomp::bpf::{SockFilter, BPF_MAXINSNS};
+    use crate::security::seccomp::notify::{NotifyListener, SeccompNotifSizes};
+    use crate::subsystems::mm::vm::{copyin, copyout};
+
+    let args = extract_args(args, 3)?;
+    let operation = args[0] as u32;
+    let flags = args[1] as u32;
+    let uargs = args[2] as usize;
+
+    let my_pid = myproc().ok_or(SyscallError::NotFound)?;
+    let (pagetable, euid) = {
+        let table = PROC_TABLE.lock();
+        let proc = table.find_ref(my_pid).ok_or(SyscallError::NotFound)?;
+        (proc.pagetable, proc.euid)
+    };
+
+    match operation {
+        SECCOMP_SET_MODE_STRICT => {
+            if flags != 0 || uargs != 0 {
+                return Err(SyscallError::InvalidArgument);
+            }
+            set_mode_strict()?;
+            Ok(0)
+        }
+        SECCOMP_SET_MODE_FILTER => {
+            if pagetable.is_null() || uargs == 0 {
+                return Err(SyscallError::BadAddress);
+            }
+
+            // struct sock_fprog { unsigned short len; struct sock_filter *filter; }
+            #[repr(C)]
+            struct SockFprog {
+                len: u16,
+                filter: u64,
+            }
+
+            let mut fprog = SockFprog { len: 0, filter: 0 };
+            unsafe {
+                copyin(pagetable, &mut fprog as *mut _ as *mut u8, uargs,
+                       core::mem::size_of::<SockFprog>())
+                    .map_err(|_| SyscallError::BadAddress)?;
+            }
+            let len = fprog.len as usize;
+            if len == 0 || len > BPF_MAXINSNS {
+                return Err(SyscallError::InvalidArgument);
+            }
+            let mut prog = alloc::vec![SockFilter::default(); len];
+            unsafe {
+                copyin(pagetable, prog.as_mut_ptr() as *mut u8, fprog.filter as usize,
+                       len * core::mem::size_of::<SockFilter>())
+                    .map_err(|_| SyscallError::BadAddress)?;
+            }
+
+            let listener = if flags & SECCOMP_FILTER_FLAG_NEW_LISTENER != 0 {
+                let listener = alloc::sync::Arc::new(NotifyListener::new());
+                let file_idx = crate::fs::file::file_seccomp_listener_new(listener.clone())
+                    .ok_or(SyscallError::TooManyOpenFiles)?;
+                Some((listener, file_idx))
+            } else {
+                None
+            };
+
+            // Root stands in for CAP_SYS_ADMIN
+            let result = set_mode_filter(prog, flags, listener.as_ref().map(|(l, _)| l.clone()), euid == 0);
+            match (result, listener) {
+                (Ok(_), Some((_, file_idx))) => match crate::process::fdalloc(file_idx) {
+                    Some(fd) => Ok(fd as u64),
+                    None => {
+                        // The filter stays; its listener is closed like any other
+                        crate::fs::file::file_close(file_idx);
+                        Err(SyscallError::TooManyOpenFiles)
+                    }
+                },
+                (Ok(_), None) => Ok(0),
+                (Err(err), listener) => {
+                    if let Some((_, file_idx)) = listener {
+                        crate::fs::file::file_close(file_idx);
+                    }
+                    match err {
+                        SeccompError::Unsynchronized(tid) if flags & SECCOMP_FILTER_FLAG_TSYNC_ESRCH == 0 => Ok(tid),
+                        err => Err(err.into()),
+                    }
+                }
+            }
+        }
+        SECCOMP_GET_ACTION_AVAIL => {
+            if flags != 0 || pagetable.is_null() {
+                return Err(SyscallError::InvalidArgument);
+            }
+            let mut action = 0u32;
+            unsafe {
+                copyin(pagetable, &mut action as *mut _ as *mut u8, uargs, core::mem::size_of::<u32>())
+                    .map_err(|_| SyscallError::BadAddress)?;
+            }
+            if SeccompAction::available(action) { Ok(0) } else { Err(SyscallError::NotSupported) }
+        }
+        SECCOMP_GET_NOTIF_SIZES => {
+            if flags != 0 || pagetable.is_null() {
+                return Err(SyscallError::InvalidArgument);
+            }
+            let sizes = SeccompNotifSizes::current();
+            unsafe {
+                copyout(pagetable, uargs, &sizes as *const _ as *const u8,
+                        core::mem::size_of::<SeccompNotifSizes>())
+                    .map_err(|_| SyscallError::BadAddress)?;
+            }
+            Ok(0)
+        }
         _ => Err(SyscallError::InvalidArgument),
     }
 }
@@ -1034,6 +1191,10 @@ fn sys_sbrk(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::InvalidArgument);
     }
 
+    if new_sz > old_sz && new_sz as u64 > proc.rlimits[crate::posix::RLIMIT_AS as usize].rlim_cur {
+        return Err(SyscallError::OutOfMemory);
+    }
+
     // Check if we need to extend or shrink the heap
     if new_sz > old_sz {
         // Extend heap: allocate and map new pages
@@ -1238,184 +1399,146 @@ fn sys_getpgid(args: &[u64]) -> SyscallResult {
     Ok(proc.pgid as u64)
 }
 
+fn rlimit_error(err: crate::process::rlimit::RlimitError) -> SyscallError {
+    match err {
+        crate::process::rlimit::RlimitError::Invalid => SyscallError::InvalidArgument,
+        crate::process::rlimit::RlimitError::NotPermitted => SyscallError::PermissionDenied,
+    }
+}
+
+/// Read and optionally replace one resource limit of `target`
+///
+/// Touching another process needs the caller's real and effective IDs to
+/// match all of the target's real, effective and saved IDs, or root.
+/// Returns the limit in effect before the call.
+fn do_prlimit(target: crate::process::Pid, resource: i32, new_limit: Option<crate::posix::Rlimit>) -> Result<crate::posix::Rlimit, SyscallError> {
+    use crate::process::rlimit;
+
+    let index = rlimit::index(resource).map_err(rlimit_error)?;
+    let my_pid = myproc().ok_or(SyscallError::NotFound)?;
+    let target = if target == 0 { my_pid } else { target };
+
+    let mut table = PROC_TABLE.lock();
+    let caller = table.find_ref(my_pid).ok_or(SyscallError::NotFound)?;
+    let (uid, euid, gid, egid) = (caller.uid, caller.euid, caller.gid, caller.egid);
+
+    let proc = table.find(target).ok_or(SyscallError::NotFound)?;
+    if target != my_pid && euid != 0 {
+        let same_user = [proc.uid, proc.euid, proc.suid].iter().all(|&id| id == uid && id == euid);
+        let same_group = [proc.gid, proc.egid, proc.sgid].iter().all(|&id| id == gid && id == egid);
+        if !same_user || !same_group {
+            return Err(SyscallError::PermissionDenied);
+        }
+    }
+
+    let old_limit = proc.rlimits[index];
+    if let Some(new_limit) = new_limit {
+        rlimit::validate(resource, old_limit, new_limit, euid == 0).map_err(rlimit_error)?;
+        proc.rlimits[index] = new_limit;
+    }
+    Ok(old_limit)
+}
+
+fn copyin_rlimit(ptr: usize) -> Result<crate::posix::Rlimit, SyscallError> {
+    Ok(UserPtr::new(ptr).read()?)
+}
+
+fn copyout_rlimit(ptr: usize, limit: &crate::posix::Rlimit) -> Result<(), SyscallError> {
+    Ok(UserPtr::new(ptr).write(limit)?)
+}
+
 fn sys_getrlimit(args: &[u64]) -> SyscallResult {
-    use crate::subsystems::mm::vm::copyout;
-    use crate::posix::{Rlimit, RLIM_INFINITY, RLIMIT_NOFILE, RLIMIT_STACK, RLIMIT_AS};
-    
     let args = extract_args(args, 2)?;
     let resource = args[0] as i32;
     let rlim_ptr = args[1] as usize;
-    
+
     if rlim_ptr == 0 {
         return Err(SyscallError::BadAddress);
     }
-    
-    // Validate resource type
-    if resource < 0 || resource >= 16 {
-        return Err(SyscallError::InvalidArgument);
-    }
-    
-    let my_pid = myproc().ok_or(SyscallError::NotFound)?;
-    let table = PROC_TABLE.lock();
-    let proc = table.find_ref(my_pid).ok_or(SyscallError::NotFound)?;
-    let pagetable = proc.pagetable;
-    
-    if pagetable.is_null() {
-        return Err(SyscallError::BadAddress);
-    }
-    
-    // Get the resource limit
-    let rlimit = proc.rlimits[resource as usize];
-    
-    // If not set, return defaults
-    let result = if rlimit.rlim_cur == 0 && rlimit.rlim_max == 0 {
-        // Default limits
-        match resource {
-            r if r == RLIMIT_NOFILE => Rlimit { rlim_cur: 1024, rlim_max: 65536 },
-            r if r == RLIMIT_STACK => Rlimit { rlim_cur: 8 * 1024 * 1024, rlim_max: RLIM_INFINITY },
-            r if r == RLIMIT_AS => Rlimit { rlim_cur: RLIM_INFINITY, rlim_max: RLIM_INFINITY },
-            _ => Rlimit { rlim_cur: RLIM_INFINITY, rlim_max: RLIM_INFINITY },
-        }
-    } else {
-        rlimit
-    };
-    
-    // Copy to user space
-    unsafe {
-        copyout(pagetable, rlim_ptr, &result as *const _ as *const u8,
-                core::mem::size_of::<Rlimit>())
-            .map_err(|_| SyscallError::BadAddress)?;
-    }
-    
+
+    let limit = do_prlimit(0, resource, None)?;
+    copyout_rlimit(rlim_ptr, &limit)?;
     Ok(0)
 }
 
 fn sys_setrlimit(args: &[u64]) -> SyscallResult {
-    use crate::subsystems::mm::vm::copyin;
-    use crate::posix::Rlimit;
-    
     let args = extract_args(args, 2)?;
     let resource = args[0] as i32;
     let rlim_ptr = args[1] as usize;
-    
+
     if rlim_ptr == 0 {
         return Err(SyscallError::BadAddress);
     }
-    
-    // Validate resource type
-    if resource < 0 || resource >= 16 {
-        return Err(SyscallError::InvalidArgument);
-    }
-    
-    let my_pid = myproc().ok_or(SyscallError::NotFound)?;
-    let mut table = PROC_TABLE.lock();
-    let proc = table.find(my_pid).ok_or(SyscallError::NotFound)?;
-    let pagetable = proc.pagetable;
-    
-    if pagetable.is_null() {
-        return Err(SyscallError::BadAddress);
-    }
-    
-    // Read new limit from user space
-    let mut new_limit = Rlimit { rlim_cur: 0, rlim_max: 0 };
-    unsafe {
-        copyin(pagetable, &mut new_limit as *mut _ as *mut u8, rlim_ptr,
-               core::mem::size_of::<Rlimit>())
-            .map_err(|_| SyscallError::BadAddress)?;
-    }
-    
-    // Get current limit
-    let old_limit = proc.rlimits[resource as usize];
-    
-    // Validate new limits
-    // Soft limit cannot exceed hard limit
-    if new_limit.rlim_cur > new_limit.rlim_max {
-        return Err(SyscallError::InvalidArgument);
-    }
-    
-    // Only root can raise hard limit above current value
-    if new_limit.rlim_max > old_limit.rlim_max && proc.euid != 0 {
-        return Err(SyscallError::PermissionDenied);
-    }
-    
-    // Set the new limit
-    proc.rlimits[resource as usize] = new_limit;
-    
+
+    let new_limit = copyin_rlimit(rlim_ptr)?;
+    do_prlimit(0, resource, Some(new_limit))?;
     Ok(0)
 }
 
+/// Get and/or set a resource limit of any process
+/// Arguments: [pid, resource, new_rlim_ptr, old_rlim_ptr]; pid 0 is the caller
 fn sys_prlimit64(args: &[u64]) -> SyscallResult {
-    use crate::subsystems::mm::vm::{copyin, copyout};
-    use crate::posix::Rlimit;
-    
     let args = extract_args(args, 4)?;
-    let target_pid = args[0] as usize;
+    let target_pid = args[0] as i32;
     let resource = args[1] as i32;
     let new_rlim_ptr = args[2] as usize;
     let old_rlim_ptr = args[3] as usize;
-    
-    // Validate resource type
-    if resource < 0 || resource >= 16 {
-        return Err(SyscallError::InvalidArgument);
-    }
-    
-    let my_pid = myproc().ok_or(SyscallError::NotFound)?;
-    let target = if target_pid == 0 { my_pid } else { target_pid };
-    
-    let mut table = PROC_TABLE.lock();
-    
-    // Check permissions if targeting another process
-    if target != my_pid {
-        let caller = table.find_ref(my_pid).ok_or(SyscallError::NotFound)?;
-        if caller.euid != 0 {
-            return Err(SyscallError::PermissionDenied);
-        }
-    }
-    
-    let proc = table.find(target).ok_or(SyscallError::NotFound)?;
-    let pagetable = proc.pagetable;
-    
-    if pagetable.is_null() {
-        return Err(SyscallError::BadAddress);
-    }
-    
-    // Get old limit if requested
+
+    let new_limit = if new_rlim_ptr != 0 {
+        Some(copyin_rlimit(new_rlim_ptr)?)
+    } else {
+        None
+    };
+    let old_limit = do_prlimit(target_pid, resource, new_limit)?;
     if old_rlim_ptr != 0 {
-        let old_limit = proc.rlimits[resource as usize];
-        unsafe {
-            copyout(pagetable, old_rlim_ptr, &old_limit as *const _ as *const u8,
-                    core::mem::size_of::<Rlimit>())
-                .map_err(|_| SyscallError::BadAddress)?;
-        }
+        copyout_rlimit(old_rlim_ptr, &old_limit)?;
     }
-    
-    // Set new limit if provided
-    if new_rlim_ptr != 0 {
-        let mut new_limit = Rlimit { rlim_cur: 0, rlim_max: 0 };
-        unsafe {
-            copyin(pagetable, &mut new_limit as *mut _ as *mut u8, new_rlim_ptr,
-                   core::mem::size_of::<Rlimit>())
-                .map_err(|_| SyscallError::BadAddress)?;
-        }
-        
-        // Validate: soft limit cannot exceed hard limit
-        if new_limit.rlim_cur > new_limit.rlim_max {
-            return Err(SyscallError::InvalidArgument);
-        }
-        
-        let old_limit = proc.rlimits[resource as usize];
-        
-        // Only root can raise hard limit
-        if new_limit.rlim_max > old_limit.rlim_max && proc.euid != 0 {
-            return Err(SyscallError::PermissionDenied);
-        }
-        
-        proc.rlimits[resource as usize] = new_limit;
-    }
-    
     Ok(0)
 }
 
+/// Trace another process
+/// Arguments: [request, pid, addr, data]
+/// Returns: 0 on success; PEEK requests store the word at `data`
+fn sys_ptrace(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 4)?;
+    let request = u32::try_from(args[0]).map_err(|_| SyscallError::IoError)?;
+    let pid = args[1] as crate::process::Pid;
+    crate::process::ptrace::ptrace(request, pid, args[2] as usize, args[3] as usize).map_err(Into::into)
+}
+
+/// Add a key to a keyring, or update the matching key already there
+/// Arguments: [type, description, payload, plen, keyring]
+/// Returns: serial number of the key
+fn sys_add_key(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 5)?;
+    crate::security::keys::keyctl::add_key(
+        args[0] as usize,
+        args[1] as usize,
+        args[2] as usize,
+        args[3] as usize,
+        args[4] as i32,
+    )
+    .map_err(Into::into)
+}
+
+/// Find a key in the caller's keyrings
+/// Arguments: [type, description, callout_info, dest_keyring]
+/// Returns: serial number of the key
+fn sys_request_key(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 4)?;
+    crate::security::keys::keyctl::request_key(args[0] as usize, args[1] as usize, args[2] as usize, args[3] as i32)
+        .map_err(Into::into)
+}
+
+/// Manipulate keys and keyrings
+/// Arguments: [operation, arg2, arg3, arg4, arg5]
+/// Returns: depends on the operation
+fn sys_keyctl(args: &[u64]) -> SyscallResult {
+    let args = extract_args(args, 5)?;
+    let op = u32::try_from(args[0]).map_err(|_| SyscallError::NotSupported)?;
+    crate::security::keys::keyctl::keyctl(op, args[1], args[2], args[3], args[4]).map_err(Into::into)
+}
+
 // Global domain name storage
 static DOMAINNAME: crate::subsystems::sync::Mutex<[u8; 256]> = crate::subsystems::sync::Mutex::new([0u8; 256]);
 static DOMAINNAME_LEN: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
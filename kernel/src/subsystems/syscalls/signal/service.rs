@@ -10,6 +10,7 @@ use nos_nos_error_handling::unified::KernelError;
 use crate::syscalls::services::{BaseService, ServiceStatus, SyscallService};
 use crate::syscalls::signal_service::handlers::*;
 use crate::process::ProcessId;
+use crate::subsystems::mm::uaccess::UserPtr;
 use alloc::boxed::Box;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
@@ -258,58 +259,48 @@ impl SyscallService for SignalService {
             0x2001 => { // sigaction
                 let pid = ProcessId::new(args.get(0).copied().unwrap_or(0) as u32);
                 let sig = args.get(1).copied().unwrap_or(0) as SignalNumber;
-                let action_ptr = args.get(2).copied().unwrap_or(0) as *const SignalAction;
-                let old_action_ptr = args.get(3).copied().unwrap_or(0) as *mut SignalAction;
+                let action_ptr = UserPtr::<usize>::new(args.get(2).copied().unwrap_or(0) as usize);
+                let old_action_ptr = UserPtr::<usize>::new(args.get(3).copied().unwrap_or(0) as usize);
                 
-                // TODO: 安全地从用户空间读取信号处理程序
-                let action = unsafe { action_ptr.read() };
+                // 用户空间以处理程序地址表示信号动作
+                let action = decode_action(action_ptr.read().map_err(|_| KernelError::BadAddress)?);
                 let old_action = self.set_sigaction(pid, sig, action)?;
                 
-                // TODO: 安全地向用户空间写入旧的信号处理程序
-                if !old_action_ptr.is_null() {
-                    unsafe { old_action_ptr.write(old_action.unwrap_or(SignalAction::Default)) };
-                }
+                let old = encode_action(&old_action.unwrap_or(SignalAction::Default));
+                old_action_ptr.write_opt(&old).map_err(|_| KernelError::BadAddress)?;
                 
                 Ok(0)
             }
             0x2002 => { // sigprocmask
                 let pid = ProcessId::new(args.get(0).copied().unwrap_or(0) as u32);
                 let how = args.get(1).copied().unwrap_or(0) as u32;
-                let new_mask_ptr = args.get(2).copied().unwrap_or(0) as *const SignalSet;
-                let old_mask_ptr = args.get(3).copied().unwrap_or(0) as *mut SignalSet;
+                let new_mask_ptr = UserPtr::<SignalSet>::new(args.get(2).copied().unwrap_or(0) as usize);
+                let old_mask_ptr = UserPtr::<SignalSet>::new(args.get(3).copied().unwrap_or(0) as usize);
                 
-                // TODO: 安全地从用户空间读取信号集
-                let new_mask = unsafe { new_mask_ptr.read() };
+                let new_mask = new_mask_ptr.read().map_err(|_| KernelError::BadAddress)?;
                 let mut old_mask = SignalSet::empty();
                 
                 self.set_process_sigmask(pid, how, new_mask, Some(&mut old_mask))?;
                 
-                // TODO: 安全地向用户空间写入旧的信号集
-                if !old_mask_ptr.is_null() {
-                    unsafe { old_mask_ptr.write(old_mask) };
-                }
+                old_mask_ptr.write_opt(&old_mask).map_err(|_| KernelError::BadAddress)?;
                 
                 Ok(0)
             }
             0x2003 => { // sigpending
                 let pid = ProcessId::new(args.get(0).copied().unwrap_or(0) as u32);
-                let set_ptr = args.get(1).copied().unwrap_or(0) as *mut SignalSet;
+                let set_ptr = UserPtr::<SignalSet>::new(args.get(1).copied().unwrap_or(0) as usize);
                 
                 let pending = self.get_pending_signals(pid)?;
                 
-                // TODO: 安全地向用户空间写入挂起的信号集
-                if !set_ptr.is_null() {
-                    unsafe { set_ptr.write(pending) };
-                }
+                set_ptr.write_opt(&pending).map_err(|_| KernelError::BadAddress)?;
                 
                 Ok(0)
             }
             0x2004 => { // sigsuspend
                 let pid = ProcessId::new(args.get(0).copied().unwrap_or(0) as u32);
-                let mask_ptr = args.get(1).copied().unwrap_or(0) as *const SignalSet;
+                let mask_ptr = UserPtr::<SignalSet>::new(args.get(1).copied().unwrap_or(0) as usize);
                 
-                // TODO: 安全地从用户空间读取信号集
-                let mask = unsafe { mask_ptr.read() };
+                let mask = mask_ptr.read().map_err(|_| KernelError::BadAddress)?;
                 
                 // 设置新的信号掩码并挂起进程
                 self.set_process_sigmask(pid, 2, mask, None)?; // 2 = SIG_SETMASK
@@ -334,6 +325,30 @@ impl SyscallService for SignalService {
     }
 }
 
+/// 用户空间的SIG_DFL
+const SIG_DFL: usize = 0;
+/// 用户空间的SIG_IGN
+const SIG_IGN: usize = 1;
+
+/// 由用户空间的处理程序地址得到信号动作
+fn decode_action(handler: usize) -> SignalAction {
+    match handler {
+        SIG_DFL => SignalAction::Default,
+        SIG_IGN => SignalAction::Ignore,
+        // 处理程序在用户空间运行，这里只保存其地址
+        addr => SignalAction::Handler(unsafe { core::mem::transmute::<usize, SignalHandler>(addr) }),
+    }
+}
+
+/// 信号动作对应的用户空间处理程序地址
+fn encode_action(action: &SignalAction) -> usize {
+    match action {
+        SignalAction::Default => SIG_DFL,
+        SignalAction::Ignore => SIG_IGN,
+        SignalAction::Handler(handler) => *handler as usize,
+    }
+}
+
 /// 信号操作类型，用于统计
 #[derive(Debug, Clone, Copy)]
 pub enum SignalOperation {
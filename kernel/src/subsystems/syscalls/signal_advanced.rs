@@ -21,6 +21,7 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
         0x500C => sys_rt_sigsuspend(args),  // rt_sigsuspend
         0x500D => sys_tkill(args),          // tkill
         0x500E => sys_tgkill(args),         // tgkill
+        0x500F => sys_rt_sigreturn(args),   // rt_sigreturn
         _ => Err(SyscallError::InvalidSyscall),
     }
 }
@@ -900,6 +901,21 @@ fn sys_rt_sigsuspend(args: &[u64]) -> SyscallResult {
     Err(SyscallError::Interrupted)
 }
 
+/// Return from a signal handler
+/// Arguments: none
+/// Returns: 0; the mask the handler interrupted is back in force
+///
+/// Signal frames carry no registers yet, so the mask and the handler state
+/// are all there is to restore.
+fn sys_rt_sigreturn(_args: &[u64]) -> SyscallResult {
+    let pid = crate::process::myproc().ok_or(SyscallError::NotFound)?;
+    let proc_table = crate::process::manager::PROC_TABLE.lock();
+    let proc = proc_table.find_ref(pid).ok_or(SyscallError::NotFound)?;
+    let signals = proc.signals.as_ref().ok_or(SyscallError::NotFound)?;
+    signals.return_from_handler().map_err(|_| SyscallError::InvalidArgument)?;
+    Ok(0)
+}
+
 fn sys_tkill(args: &[u64]) -> SyscallResult {
     use super::common::extract_args;
 
@@ -258,64 +258,49 @@ pub fn sys_signalfd4(args: &[u64]) -> SyscallResult {
         let instance_idx = alloc_signalfd_instance(mask, flags)
             .ok_or(SyscallError::OutOfMemory)?;
         
-        // Allocate file descriptor
-        let mut proc_table = crate::subsystems::process::manager::PROC_TABLE.lock();
-        if let Some(proc) = proc_table.find(pid) {
-            // Find free file descriptor
-            for (fd, file) in proc.ofile.iter_mut().enumerate() {
-                if file.is_none() {
-                    *file = Some(crate::subsystems::fs::file::File {
-                        ftype: crate::subsystems::fs::file::FileType::Signalfd,
-                        readable: true,
-                        writable: false,
-                        signalfd_instance: Some(instance_idx),
-                        ..Default::default()
-                    });
-                    
-                    // Apply flags
-                    if (flags & flags::SFD_NONBLOCK) != 0 {
-                        file.as_mut().unwrap().nonblock = true;
-                    }
-                    if (flags & flags::SFD_CLOEXEC) != 0 {
-                        file.as_mut().unwrap().close_on_exec = true;
-                    }
-                    
-                    return Ok(fd as u64);
-                }
+        // Create the file and give it a descriptor
+        let file_idx = crate::fs::file::file_alloc().ok_or(SyscallError::OutOfMemory)?;
+        {
+            let mut table = crate::fs::file::FILE_TABLE.lock();
+            let file = table.get_mut(file_idx).ok_or(SyscallError::OutOfMemory)?;
+            file.ftype = crate::subsystems::fs::file::FileType::Signalfd;
+            file.readable = true;
+            file.writable = false;
+            file.signalfd_instance = Some(instance_idx);
+
+            // Apply flags
+            if (flags & flags::SFD_NONBLOCK) != 0 {
+                file.status_flags |= crate::posix::O_NONBLOCK;
+            }
+            if (flags & flags::SFD_CLOEXEC) != 0 {
+                file.status_flags |= crate::posix::O_CLOEXEC;
+            }
+        }
+
+        match crate::process::fdalloc(file_idx) {
+            Some(fd) => Ok(fd as u64),
+            None => {
+                crate::fs::file::file_close(file_idx);
+                Err(SyscallError::TooManyOpenFiles)
             }
-            Err(SyscallError::TooManyFiles)
-        } else {
-            Err(SyscallError::InvalidArgument)
         }
     } else {
         // Modify existing signalfd
-        let mut proc_table = crate::subsystems::process::manager::PROC_TABLE.lock();
-        if let Some(proc) = proc_table.find(pid) {
-            if fd < 0 || fd as usize >= crate::subsystems::process::manager::NOFILE {
-                return Err(SyscallError::BadFileDescriptor);
-            }
-            
-            if let Some(ref file) = proc.ofile[fd as usize] {
-                // Check if it's a signalfd file
-                if file.ftype != crate::subsystems::fs::file::FileType::Signalfd {
-                    return Err(SyscallError::InvalidArgument);
-                }
-                
-                // Update the mask
-                if let Some(instance_idx) = file.signalfd_instance {
-                    drop(proc_table);
-                    if let Some(instance) = get_signalfd_instance(instance_idx) {
-                        instance.mask = mask;
-                        Ok(fd as u64)
-                    } else {
-                        Err(SyscallError::InvalidArgument)
-                    }
-                } else {
-                    Err(SyscallError::InvalidArgument)
-                }
-            } else {
-                Err(SyscallError::BadFileDescriptor)
+        let file_idx = crate::process::fdlookup(fd).ok_or(SyscallError::BadFileDescriptor)?;
+        let instance_idx = {
+            let table = crate::fs::file::FILE_TABLE.lock();
+            let file = table.get(file_idx).ok_or(SyscallError::BadFileDescriptor)?;
+            // Check if it's a signalfd file
+            if file.ftype != crate::subsystems::fs::file::FileType::Signalfd {
+                return Err(SyscallError::InvalidArgument);
             }
+            file.signalfd_instance.ok_or(SyscallError::InvalidArgument)?
+        };
+
+        // Update the mask
+        if let Some(instance) = get_signalfd_instance(instance_idx) {
+            instance.mask = mask;
+            Ok(fd as u64)
         } else {
             Err(SyscallError::InvalidArgument)
         }
@@ -69,7 +69,7 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
         0x8001 => sys_fork(args),           // fork (also in process, but thread-specific)
         0x8002 => sys_vfork(args),          // vfork
         0x8003 => sys_execve(args),         // execve (also in process)
-        0x8004 => sys_exit(args),           // exit (also in process)
+        0x8004 => sys_exit(args),           // exit the calling thread
         0x8005 => sys_wait4(args),          // wait4
         0x8006 => sys_gettid(args),         // gettid
         0x8007 => sys_getpid(args),         // getpid (also in process)
@@ -130,6 +130,10 @@ fn sys_clone(args: &[u64]) -> SyscallResult {
 
         match thread_result {
             Ok(tid) => {
+                // The new thread runs under the creator's seccomp filters
+                crate::security::seccomp::clone_thread(tid as u64);
+                crate::security::keys::clone_thread(tid as u64);
+
                 // Get the newly created thread to set up its stack and TLS
                 let mut thread_table = crate::process::thread::thread_table();
                 let thread = thread_table.find_thread(tid)
@@ -221,16 +225,15 @@ fn sys_clone(args: &[u64]) -> SyscallResult {
         }
 
         // Create new process with resource sharing based on flags
-        let child_pid = if (flags & CLONE_VM) != 0 {
-            // Share VM space - this is not fully implemented yet, fall back to fork
-            crate::process::manager::fork()
-        } else {
-            // Normal fork
-            crate::process::manager::fork()
-        };
+        // Sharing the VM is not implemented yet, so CLONE_VM falls back to
+        // a copy; the descriptor table is shared for CLONE_FILES
+        let child_pid = crate::process::manager::fork_with((flags & CLONE_FILES) != 0);
 
         match child_pid {
-            Some(child_pid) => {
+            Ok(child_pid) => {
+                let event = crate::process::ptrace::clone_event(flags as u32 as u64);
+                crate::process::ptrace::report_new_child(child_pid, event);
+
                 // Apply namespaces to child process if requested
                 if !namespace_configs.is_empty() {
                     // Get namespace manager instance
@@ -306,7 +309,7 @@ fn sys_clone(args: &[u64]) -> SyscallResult {
 
                 Ok(child_pid as u64)
             }
-            None => Err(SyscallError::OutOfMemory),
+            Err(err) => Err(err.into()),
         }
     }
 }
@@ -316,20 +319,19 @@ fn sys_fork(_args: &[u64]) -> SyscallResult {
     use crate::process::manager;
     
     // Call the process manager's fork function
-    let result = manager::fork();
+    let pid = manager::fork_with(false)?;
+    crate::process::ptrace::report_new_child(pid, crate::process::ptrace::PTRACE_EVENT_FORK);
 
-    let pid = match result {
-        Some(pid) => pid as u64,
-        None => return Err(SyscallError::OutOfMemory),
-    };
-
-    Ok(pid)
+    Ok(pid as u64)
 }
 
 fn sys_vfork(_args: &[u64]) -> SyscallResult {
     // vfork is similar to fork but shares address space until exec
     // For now, implement as regular fork
-    sys_fork(_args)
+    let pid = crate::process::manager::fork_with(false)?;
+    crate::process::ptrace::report_new_child(pid, crate::process::ptrace::PTRACE_EVENT_VFORK);
+
+    Ok(pid as u64)
 }
 
 fn sys_execve(args: &[u64]) -> SyscallResult {
@@ -380,9 +382,26 @@ fn sys_execve(args: &[u64]) -> SyscallResult {
     Err(SyscallError::NotSupported)
 }
 
+/// End the calling thread
+/// Arguments: [status]
+///
+/// The other threads of the process carry on; the last one to leave takes
+/// the process with it, with `status` as its exit status.
 fn sys_exit(args: &[u64]) -> SyscallResult {
     let status = if !args.is_empty() { args[0] as i32 } else { 0 };
-    
+
+    if let Some(tid) = crate::process::thread::current_thread()
+        && let Some(pid) = crate::process::myproc()
+    {
+        let others = crate::process::thread::thread_table()
+            .find_threads_by_pid(pid)
+            .iter()
+            .any(|thread| thread.tid != tid && !thread.is_terminated());
+        if others {
+            crate::process::thread::thread_exit(status as isize as *mut u8);
+        }
+    }
+
     // Call the process manager's exit function
     crate::process::manager::exit(status);
     
@@ -195,40 +195,45 @@ pub fn sys_timerfd_create(args: &[u64]) -> SyscallResult {
     let instance_idx = alloc_timerfd_instance(clockid, flags)
         .ok_or(SyscallError::OutOfMemory)?;
     
-    // Allocate file descriptor
-    let pid = crate::subsystems::process::manager::myproc()
-        .ok_or(SyscallError::InvalidArgument)?;
-    
-    let mut proc_table = crate::subsystems::process::manager::PROC_TABLE.lock();
-    if let Some(proc) = proc_table.find(pid) {
-        // Find free file descriptor
-        for (fd, file) in proc.ofile.iter_mut().enumerate() {
-            if file.is_none() {
-                *file = Some(crate::subsystems::fs::file::File {
-                    ftype: crate::subsystems::fs::file::FileType::TimerFd,
-                    readable: true,
-                    writable: false,
-                    timerfd_instance: Some(instance_idx),
-                    ..Default::default()
-                });
-                
-                // Apply flags
-                if (flags & flags::TFD_NONBLOCK) != 0 {
-                    file.as_mut().unwrap().nonblock = true;
-                }
-                if (flags & flags::TFD_CLOEXEC) != 0 {
-                    file.as_mut().unwrap().close_on_exec = true;
-                }
-                
-                return Ok(fd as u64);
-            }
+    // Create the file and give it a descriptor
+    let file_idx = crate::fs::file::file_alloc().ok_or(SyscallError::OutOfMemory)?;
+    {
+        let mut table = crate::fs::file::FILE_TABLE.lock();
+        let file = table.get_mut(file_idx).ok_or(SyscallError::OutOfMemory)?;
+        file.ftype = crate::subsystems::fs::file::FileType::TimerFd;
+        file.readable = true;
+        file.writable = false;
+        file.timerfd_instance = Some(instance_idx);
+
+        // Apply flags
+        if (flags & flags::TFD_NONBLOCK) != 0 {
+            file.status_flags |= crate::posix::O_NONBLOCK;
+        }
+        if (flags & flags::TFD_CLOEXEC) != 0 {
+            file.status_flags |= crate::posix::O_CLOEXEC;
+        }
+    }
+
+    match crate::process::fdalloc(file_idx) {
+        Some(fd) => Ok(fd as u64),
+        None => {
+            crate::fs::file::file_close(file_idx);
+            Err(SyscallError::TooManyOpenFiles)
         }
-        Err(SyscallError::TooManyFiles)
-    } else {
-        Err(SyscallError::InvalidArgument)
     }
 }
 
+/// Timer instance behind a timerfd descriptor
+fn timerfd_instance_of(fd: i32) -> Result<usize, SyscallError> {
+    let file_idx = crate::process::fdlookup(fd).ok_or(SyscallError::BadFileDescriptor)?;
+    let table = crate::fs::file::FILE_TABLE.lock();
+    let file = table.get(file_idx).ok_or(SyscallError::BadFileDescriptor)?;
+    if file.ftype != crate::subsystems::fs::file::FileType::TimerFd {
+        return Err(SyscallError::InvalidArgument);
+    }
+    file.timerfd_instance.ok_or(SyscallError::InvalidArgument)
+}
+
 /// timerfd_settime system call
 /// Arguments: [fd, flags, new_value_ptr, old_value_ptr]
 /// Returns: 0 on success, error on failure
@@ -258,64 +263,45 @@ pub fn sys_timerfd_settime(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::BadAddress);
     }
     
-    // Check if it's a timerfd file
-    if fd < 0 || fd as usize >= crate::subsystems::process::manager::NOFILE {
-        return Err(SyscallError::BadFileDescriptor);
-    }
-    
-    let proc_table = crate::subsystems::process::manager::PROC_TABLE.lock();
-    if let Some(proc) = proc_table.find(pid) {
-        if let Some(ref file) = proc.ofile[fd as usize] {
-            if file.ftype != crate::subsystems::fs::file::FileType::TimerFd {
-                return Err(SyscallError::InvalidArgument);
-            }
-            
-            // Get timerfd instance
-            let instance_idx = file.timerfd_instance.ok_or(SyscallError::InvalidArgument)?;
-            drop(proc_table);
-            
-            if let Some(instance) = get_timerfd_instance(instance_idx) {
-                // Read new_value from user space
-                let new_value = unsafe {
-                    crate::subsystems::mm::vm::copyin(
-                        pagetable,
-                        new_value_ptr as *mut u8,
-                        new_value_ptr,
-                        core::mem::size_of::<u64>(),
-                    ).map_err(|_| SyscallError::BadAddress)?;
-                    *(new_value_ptr as *const u64)
-                };
-                
-                // For simplicity, assume interval is 0 (one-shot timer)
-                // In a full implementation, we'd read a timespec structure
-                let new_interval = 0u64;
-                
-                // Get old value before setting
-                let (old_value, old_interval) = instance.get_time();
-                
-                // Set new timer value
-                instance.set_time(new_value, new_interval, flags);
-                
-                // Write old_value to user space if requested
-                if old_value_ptr != 0 {
-                    unsafe {
-                        crate::subsystems::mm::vm::copyin(
-                            pagetable,
-                            old_value_ptr as *mut u8,
-                            old_value_ptr,
-                            core::mem::size_of::<u64>(),
-                        ).map_err(|_| SyscallError::BadAddress)?;
-                        *(old_value_ptr as *mut u64) = old_value;
-                    }
-                }
-                
-                Ok(0)
-            } else {
-                Err(SyscallError::InvalidArgument)
+    // Get timerfd instance
+    let instance_idx = timerfd_instance_of(fd)?;
+
+    if let Some(instance) = get_timerfd_instance(instance_idx) {
+        // Read new_value from user space
+        let new_value = unsafe {
+            crate::subsystems::mm::vm::copyin(
+                pagetable,
+                new_value_ptr as *mut u8,
+                new_value_ptr,
+                core::mem::size_of::<u64>(),
+            ).map_err(|_| SyscallError::BadAddress)?;
+            *(new_value_ptr as *const u64)
+        };
+        
+        // For simplicity, assume interval is 0 (one-shot timer)
+        // In a full implementation, we'd read a timespec structure
+        let new_interval = 0u64;
+        
+        // Get old value before setting
+        let (old_value, old_interval) = instance.get_time();
+        
+        // Set new timer value
+        instance.set_time(new_value, new_interval, flags);
+        
+        // Write old_value to user space if requested
+        if old_value_ptr != 0 {
+            unsafe {
+                crate::subsystems::mm::vm::copyin(
+                    pagetable,
+                    old_value_ptr as *mut u8,
+                    old_value_ptr,
+                    core::mem::size_of::<u64>(),
+                ).map_err(|_| SyscallError::BadAddress)?;
+                *(old_value_ptr as *mut u64) = old_value;
             }
-        } else {
-            Err(SyscallError::BadFileDescriptor)
         }
+        
+        Ok(0)
     } else {
         Err(SyscallError::InvalidArgument)
     }
@@ -342,43 +328,24 @@ pub fn sys_timerfd_gettime(args: &[u64]) -> SyscallResult {
         return Err(SyscallError::BadAddress);
     }
     
-    // Check file descriptor
-    if fd < 0 || fd as usize >= crate::subsystems::process::manager::NOFILE {
-        return Err(SyscallError::BadFileDescriptor);
-    }
-    
-    let proc_table = crate::subsystems::process::manager::PROC_TABLE.lock();
-    if let Some(proc) = proc_table.find(pid) {
-        if let Some(ref file) = proc.ofile[fd as usize] {
-            if file.ftype != crate::subsystems::fs::file::FileType::TimerFd {
-                return Err(SyscallError::InvalidArgument);
-            }
-            
-            // Get timerfd instance
-            let instance_idx = file.timerfd_instance.ok_or(SyscallError::InvalidArgument)?;
-            drop(proc_table);
-            
-            if let Some(instance) = get_timerfd_instance(instance_idx) {
-                let (value, interval) = instance.get_time();
-                
-                // Write current value to user space
-                unsafe {
-                    crate::subsystems::mm::vm::copyin(
-                        pagetable,
-                        curr_value_ptr as *mut u8,
-                        curr_value_ptr,
-                        core::mem::size_of::<u64>(),
-                    ).map_err(|_| SyscallError::BadAddress)?;
-                    *(curr_value_ptr as *mut u64) = value;
-                }
-                
-                Ok(0)
-            } else {
-                Err(SyscallError::InvalidArgument)
-            }
-        } else {
-            Err(SyscallError::BadFileDescriptor)
+    // Get timerfd instance
+    let instance_idx = timerfd_instance_of(fd)?;
+
+    if let Some(instance) = get_timerfd_instance(instance_idx) {
+        let (value, interval) = instance.get_time();
+        
+        // Write current value to user space
+        unsafe {
+            crate::subsystems::mm::vm::copyin(
+                pagetable,
+                curr_value_ptr as *mut u8,
+                curr_value_ptr,
+                core::mem::size_of::<u64>(),
+            ).map_err(|_| SyscallError::BadAddress)?;
+            *(curr_value_ptr as *mut u64) = value;
         }
+        
+        Ok(0)
     } else {
         Err(SyscallError::InvalidArgument)
     }
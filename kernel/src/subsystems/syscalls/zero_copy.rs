@@ -15,14 +15,17 @@
 //! - Implement true zero-copy for pipe-to-pipe using page reference moving
 //! - Add DMA support for file-to-socket transfers
 //! - Implement page mapping for large file transfers
-//! - Add io_uring support for async zero-copy I/O
+//!
+//! The io_uring calls in this range live in [`super::io_uring`].
 
 extern crate alloc;
 
 use super::common::{SyscallError, SyscallResult, extract_args};
+use super::io_uring;
 // Error codes are handled through SyscallError enum
 use crate::fs::file::{FILE_TABLE, FileType};
-use crate::process::{myproc, NOFILE};
+use crate::process::myproc;
+use crate::process::fdtable::NR_OPEN;
 
 /// Dispatch zero-copy I/O syscalls
 pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
@@ -34,9 +37,9 @@ pub fn dispatch(syscall_id: u32, args: &[u64]) -> SyscallResult {
         0x9003 => sys_vmsplice(args),       // vmsplice
         0x9004 => sys_copy_file_range(args), // copy_file_range
         0x9005 => sys_sendfile64(args),     // sendfile64
-        0x9006 => sys_io_uring_setup(args), // io_uring_setup
-        0x9007 => sys_io_uring_enter(args), // io_uring_enter
-        0x9008 => sys_io_uring_register(args), // io_uring_register
+        0x9006 => io_uring::sys_io_uring_setup(args), // io_uring_setup
+        0x9007 => io_uring::sys_io_uring_enter(args), // io_uring_enter
+        0x9008 => io_uring::sys_io_uring_register(args), // io_uring_register
         _ => Err(SyscallError::InvalidSyscall),
     }
 }
@@ -63,7 +66,7 @@ fn sys_sendfile(args: &[u64]) -> SyscallResult {
     }
     
     // Validate file descriptor indices
-    if (in_fd as usize) >= NOFILE || (out_fd as usize) >= NOFILE {
+    if (in_fd as usize) >= NR_OPEN || (out_fd as usize) >= NR_OPEN {
         return Err(SyscallError::BadFileDescriptor);
     }
     
@@ -74,8 +77,8 @@ fn sys_sendfile(args: &[u64]) -> SyscallResult {
     let proc_table = crate::process::PROC_TABLE.lock();
     let proc = proc_table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
     
-    let in_file_idx = proc.ofile[in_fd as usize].ok_or(SyscallError::BadFileDescriptor)?;
-    let out_file_idx = proc.ofile[out_fd as usize].ok_or(SyscallError::BadFileDescriptor)?;
+    let in_file_idx = proc.ofile.lock().get(in_fd).ok_or(SyscallError::BadFileDescriptor)?;
+    let out_file_idx = proc.ofile.lock().get(out_fd).ok_or(SyscallError::BadFileDescriptor)?;
     
     drop(proc_table);
     
@@ -270,7 +273,7 @@ fn sys_splice(args: &[u64]) -> SyscallResult {
     }
     
     // Validate file descriptor indices
-    if (fd_in as usize) >= NOFILE || (fd_out as usize) >= NOFILE {
+    if (fd_in as usize) >= NR_OPEN || (fd_out as usize) >= NR_OPEN {
         return Err(SyscallError::BadFileDescriptor);
     }
     
@@ -281,8 +284,8 @@ fn sys_splice(args: &[u64]) -> SyscallResult {
     let proc_table = crate::process::PROC_TABLE.lock();
     let proc = proc_table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
     
-    let in_file_idx = proc.ofile[fd_in as usize].ok_or(SyscallError::BadFileDescriptor)?;
-    let out_file_idx = proc.ofile[fd_out as usize].ok_or(SyscallError::BadFileDescriptor)?;
+    let in_file_idx = proc.ofile.lock().get(fd_in).ok_or(SyscallError::BadFileDescriptor)?;
+    let out_file_idx = proc.ofile.lock().get(fd_out).ok_or(SyscallError::BadFileDescriptor)?;
     
     drop(proc_table);
     
@@ -495,7 +498,7 @@ fn sys_tee(args: &[u64]) -> SyscallResult {
     }
     
     // Validate file descriptor indices
-    if (fd_in as usize) >= NOFILE || (fd_out as usize) >= NOFILE {
+    if (fd_in as usize) >= NR_OPEN || (fd_out as usize) >= NR_OPEN {
         return Err(SyscallError::BadFileDescriptor);
     }
     
@@ -506,8 +509,8 @@ fn sys_tee(args: &[u64]) -> SyscallResult {
     let proc_table = crate::process::PROC_TABLE.lock();
     let proc = proc_table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
     
-    let in_file_idx = proc.ofile[fd_in as usize].ok_or(SyscallError::BadFileDescriptor)?;
-    let out_file_idx = proc.ofile[fd_out as usize].ok_or(SyscallError::BadFileDescriptor)?;
+    let in_file_idx = proc.ofile.lock().get(fd_in).ok_or(SyscallError::BadFileDescriptor)?;
+    let out_file_idx = proc.ofile.lock().get(fd_out).ok_or(SyscallError::BadFileDescriptor)?;
     
     drop(proc_table);
     
@@ -612,7 +615,7 @@ fn sys_vmsplice(args: &[u64]) -> SyscallResult {
     }
     
     // Validate file descriptor index
-    if (fd as usize) >= NOFILE {
+    if (fd as usize) >= NR_OPEN {
         return Err(SyscallError::BadFileDescriptor);
     }
     
@@ -623,7 +626,7 @@ fn sys_vmsplice(args: &[u64]) -> SyscallResult {
     let proc_table = crate::process::PROC_TABLE.lock();
     let proc = proc_table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
     
-    let file_idx = proc.ofile[fd as usize].ok_or(SyscallError::BadFileDescriptor)?;
+    let file_idx = proc.ofile.lock().get(fd).ok_or(SyscallError::BadFileDescriptor)?;
     
     drop(proc_table);
     
@@ -737,7 +740,7 @@ fn sys_copy_file_range(args: &[u64]) -> SyscallResult {
     }
     
     // Validate file descriptor indices
-    if (fd_in as usize) >= NOFILE || (fd_out as usize) >= NOFILE {
+    if (fd_in as usize) >= NR_OPEN || (fd_out as usize) >= NR_OPEN {
         return Err(SyscallError::BadFileDescriptor);
     }
     
@@ -748,8 +751,8 @@ fn sys_copy_file_range(args: &[u64]) -> SyscallResult {
     let proc_table = crate::process::PROC_TABLE.lock();
     let proc = proc_table.find_ref(pid).ok_or(SyscallError::InvalidArgument)?;
     
-    let in_file_idx = proc.ofile[fd_in as usize].ok_or(SyscallError::BadFileDescriptor)?;
-    let out_file_idx = proc.ofile[fd_out as usize].ok_or(SyscallError::BadFileDescriptor)?;
+    let in_file_idx = proc.ofile.lock().get(fd_in).ok_or(SyscallError::BadFileDescriptor)?;
+    let out_file_idx = proc.ofile.lock().get(fd_out).ok_or(SyscallError::BadFileDescriptor)?;
     
     drop(proc_table);
     
@@ -868,46 +871,3 @@ fn sys_sendfile64(args: &[u64]) -> SyscallResult {
     // Same as sendfile but with 64-bit offset
     sys_sendfile(args)
 }
-
-/// io_uring_setup: Setup io_uring instance
-/// Arguments: [entries, params_ptr]
-fn sys_io_uring_setup(args: &[u64]) -> SyscallResult {
-    let args = extract_args(args, 2)?;
-    
-    let entries = args[0] as u32;
-    let params_ptr = args[1] as *mut u8;
-    
-    // Validate parameters
-    if entries == 0 || entries > 4096 {
-        return Err(SyscallError::InvalidArgument);
-    }
-    
-    if params_ptr.is_null() {
-        return Err(SyscallError::BadAddress);
-    }
-    
-    // TODO: Implement io_uring setup
-    // This is a more advanced async I/O interface
-    
-    Err(SyscallError::NotSupported)
-}
-
-/// io_uring_enter: Submit and/or wait for io_uring events
-/// Arguments: [fd, to_submit, min_complete, flags, sig_ptr]
-fn sys_io_uring_enter(args: &[u64]) -> SyscallResult {
-    let _args = extract_args(args, 5)?;
-    
-    // TODO: Implement io_uring_enter
-    
-    Err(SyscallError::NotSupported)
-}
-
-/// io_uring_register: Register buffers or files for io_uring
-/// Arguments: [fd, opcode, arg_ptr, nr_args]
-fn sys_io_uring_register(args: &[u64]) -> SyscallResult {
-    let _args = extract_args(args, 4)?;
-    
-    // TODO: Implement io_uring_register
-    
-    Err(SyscallError::NotSupported)
-}
@@ -95,27 +95,43 @@ pub mod imp {
 
 #[cfg(target_arch = "riscv64")]
 pub mod imp {
-    /// CLINT base address for QEMU virt machine
-    const CLINT_BASE: usize = 0x0200_0000;
-    const CLINT_MTIME: *const u64 = (CLINT_BASE + 0xBFF8) as *const u64;
-    const CLINT_MTIMECMP: *mut u64 = (CLINT_BASE + 0x4000) as *mut u64;
+    use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
-    /// Timer frequency (10 MHz for QEMU virt)
-    const TIMER_FREQ_HZ: u64 = 10_000_000;
+    /// CLINT base address; QEMU virt machine until the device tree says otherwise
+    static CLINT_BASE: AtomicUsize = AtomicUsize::new(0x0200_0000);
+    const CLINT_MTIME: usize = 0xBFF8;
+    const CLINT_MTIMECMP: usize = 0x4000;
+
+    /// Timer frequency (10 MHz for QEMU virt), replaced by `timebase-frequency`
+    static TIMER_FREQ_HZ: AtomicU64 = AtomicU64::new(10_000_000);
+
+    pub fn set_clint_base(base: usize) {
+        CLINT_BASE.store(base, Ordering::Relaxed);
+    }
+
+    pub fn set_timebase_hz(hz: u64) {
+        if hz != 0 {
+            TIMER_FREQ_HZ.store(hz, Ordering::Relaxed);
+        }
+    }
+
+    fn mtimecmp() -> *mut u64 {
+        (CLINT_BASE.load(Ordering::Relaxed) + CLINT_MTIMECMP) as *mut u64
+    }
 
     pub fn now_ticks() -> u64 {
-        crate::subsystems::mm::mmio_read64(CLINT_MTIME)
+        crate::subsystems::mm::mmio_read64((CLINT_BASE.load(Ordering::Relaxed) + CLINT_MTIME) as *const u64)
     }
 
     pub fn freq_hz() -> u64 {
-        TIMER_FREQ_HZ
+        TIMER_FREQ_HZ.load(Ordering::Relaxed)
     }
 
     /// Initialize timer for periodic interrupts
     pub fn init() {
-        let interval = TIMER_FREQ_HZ / super::TIMER_FREQ;
+        let interval = freq_hz() / super::TIMER_FREQ;
         let next = now_ticks() + interval;
-        crate::subsystems::mm::mmio_write64(CLINT_MTIMECMP, next);
+        crate::subsystems::mm::mmio_write64(mtimecmp(), next);
         // Enable timer interrupt in SIE
         unsafe {
             core::arch::asm!("csrs sie, {}", in(reg) 1 << 5);
@@ -124,9 +140,9 @@ pub mod imp {
 
     /// Set next timer interrupt
     pub fn set_next_timer() {
-        let interval = TIMER_FREQ_HZ / super::TIMER_FREQ;
+        let interval = freq_hz() / super::TIMER_FREQ;
         let next = now_ticks() + interval;
-        crate::subsystems::mm::mmio_write64(CLINT_MTIMECMP, next);
+        crate::subsystems::mm::mmio_write64(mtimecmp(), next);
     }
 
     /// Read time CSR
@@ -211,6 +227,9 @@ pub fn tick() {
     
     // Wake up sleeping processes if needed
     wakeup_sleepers(ticks + 1);
+
+    // Account the tick to whoever was running, for RLIMIT_CPU
+    crate::process::charge_tick();
     // crate::subsystems::mm::mmio_stats_periodic(ticks + 1); // Function not properly exported
 }
 
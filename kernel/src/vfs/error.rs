@@ -17,6 +17,15 @@ pub enum VfsError {
     IoError,
     NotSupported,
     InvalidOperation,
+    /// Requested extended attribute does not exist (ENODATA)
+    NoData,
+    /// Operation would span two filesystems (EXDEV)
+    CrossDevice,
+    NameTooLong,
+    /// Too many symbolic links in a path walk (ELOOP)
+    TooManyLinks,
+    /// The file is encrypted and its key has not been added (ENOKEY)
+    NoKey,
 }
 
 pub type VfsResult<T> = Result<T, VfsError>;
\ No newline at end of file
@@ -5,17 +5,22 @@
 
 extern crate alloc;
 
-use alloc::{string::{String, ToString}, sync::Arc, vec::Vec, collections::BTreeMap};
+use alloc::{string::String, sync::{Arc, Weak}, vec::Vec, collections::BTreeMap};
+use core::any::Any;
 use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
 use crate::subsystems::sync::Mutex;
 use crate::drivers::BlockDevice;
+use crate::subsystems::mm::vm::PAGE_SIZE;
 
 use super::{
     error::*,
     types::*,
-    fs::{FileSystemType, SuperBlock, InodeOps, FsStats},
+    fs::{FileSystemType, SuperBlock, InodeOps, FsStats, RenameFlags, SetAttr, rename_entries},
     dir::DirEntry,
+    fscrypt::{InodeCrypt, Keyring},
+    page_cache::{AddressSpace, CachedPage, PageIo},
+    xattr::{self, XattrFlags, XattrMap},
 };
 
 // ============================================================================
@@ -31,6 +36,9 @@ const EXT4_BLOCK_SIZE: usize = 4096;
 /// EXT4 inode size
 const EXT4_INODE_SIZE: usize = 256;
 
+/// Space for extended attributes: what fits in one external xattr block
+const EXT4_XATTR_SPACE: usize = EXT4_BLOCK_SIZE;
+
 /// EXT4 directory entry file type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -161,7 +169,7 @@ struct Ext4SuperBlockImpl {
 impl Ext4SuperBlockImpl {
     fn new() -> Self {
         // Create root inode
-        let root_ino = Ext4InodeImpl::new_dir(2); // Inode 2 is root in EXT4
+        let root_ino = Ext4InodeImpl::new_dir(2, Arc::new(Keyring::new())); // Inode 2 is root in EXT4
         
         Self {
             root: Arc::new(root_ino),
@@ -217,31 +225,44 @@ impl SuperBlock for Ext4SuperBlockImpl {
 /// EXT4 inode implementation
 struct Ext4InodeImpl {
     attr: Mutex<FileAttr>,
-    // For regular files
+    // For regular files: contents as stored on disk
     data: Mutex<Vec<u8>>,
+    // For regular files: cached pages, written back to `data`
+    mapping: Option<Arc<AddressSpace>>,
     // For directories
     children: Mutex<BTreeMap<String, Arc<dyn InodeOps>>>,
     // For symlinks
     target: Mutex<Option<String>>,
+    // Extended attributes
+    xattrs: Mutex<XattrMap>,
+    // Encryption policy and key; names, contents and targets are stored
+    // encrypted when set
+    crypt: InodeCrypt,
 }
 
 impl Ext4InodeImpl {
-    fn new_file(ino: u64) -> Self {
-        Self {
-            attr: Mutex::new(FileAttr {
-                ino,
-                mode: FileMode(FileMode::S_IFREG | 0o644),
-                nlink: 1,
-                size: 0,
-                ..Default::default()
-            }),
-            data: Mutex::new(Vec::new()),
-            children: Mutex::new(BTreeMap::new()),
-            target: Mutex::new(None),
-        }
+    fn new_file(ino: u64, keyring: Arc<Keyring>) -> Arc<Self> {
+        Arc::new_cyclic(|this: &Weak<Self>| {
+            let mapping = AddressSpace::new(this.clone() as Weak<dyn PageIo>);
+            Self {
+                attr: Mutex::new(FileAttr {
+                    ino,
+                    mode: FileMode(FileMode::S_IFREG | 0o644),
+                    nlink: 1,
+                    size: 0,
+                    ..Default::default()
+                }),
+                data: Mutex::new(Vec::new()),
+                crypt: InodeCrypt::new(keyring, true, Some(Arc::downgrade(&mapping))),
+                mapping: Some(mapping),
+                children: Mutex::new(BTreeMap::new()),
+                target: Mutex::new(None),
+                xattrs: Mutex::new(XattrMap::with_limit(EXT4_XATTR_SPACE)),
+            }
+        })
     }
     
-    fn new_dir(ino: u64) -> Self {
+    fn new_dir(ino: u64, keyring: Arc<Keyring>) -> Self {
         Self {
             attr: Mutex::new(FileAttr {
                 ino,
@@ -251,25 +272,93 @@ impl Ext4InodeImpl {
                 ..Default::default()
             }),
             data: Mutex::new(Vec::new()),
+            mapping: None,
             children: Mutex::new(BTreeMap::new()),
             target: Mutex::new(None),
+            xattrs: Mutex::new(XattrMap::with_limit(EXT4_XATTR_SPACE)),
+            crypt: InodeCrypt::new(keyring, false, None),
         }
     }
     
-    fn new_symlink(ino: u64, target: &str) -> Self {
+    /// A symlink; the caller stores the target once the inode's policy is set
+    fn new_symlink(ino: u64, len: usize, keyring: Arc<Keyring>) -> Self {
         Self {
             attr: Mutex::new(FileAttr {
                 ino,
                 mode: FileMode(FileMode::S_IFLNK | 0o777),
                 nlink: 1,
-                size: target.len() as u64,
+                size: len as u64,
                 ..Default::default()
             }),
             data: Mutex::new(Vec::new()),
+            mapping: None,
             children: Mutex::new(BTreeMap::new()),
-            target: Mutex::new(Some(target.to_string())),
+            target: Mutex::new(None),
+            xattrs: Mutex::new(XattrMap::with_limit(EXT4_XATTR_SPACE)),
+            crypt: InodeCrypt::new(keyring, false, None),
         }
     }
+
+    /// Downcast an inode that must belong to ext4
+    fn of(inode: &dyn InodeOps) -> VfsResult<&Self> {
+        inode.as_any().downcast_ref::<Self>().ok_or(VfsError::CrossDevice)
+    }
+
+    fn adjust_nlink(&self, delta: i32) {
+        let mut attr = self.attr.lock();
+        attr.nlink = attr.nlink.saturating_add_signed(delta);
+    }
+
+    fn keyring(&self) -> Arc<Keyring> {
+        self.crypt.keyring().clone()
+    }
+}
+
+impl PageIo for Ext4InodeImpl {
+    fn readpage(&self, page: &CachedPage) -> VfsResult<()> {
+        let data = self.data.lock();
+        let start = page.index() as usize * PAGE_SIZE;
+        if start >= data.len() {
+            return Ok(());
+        }
+        if self.crypt.is_encrypted() {
+            // Encrypted files are stored in whole blocks
+            let mut block = data[start..start + EXT4_BLOCK_SIZE].to_vec();
+            self.crypt.decrypt_block(page.index(), &mut block)?;
+            page.write_at(0, &block);
+        } else {
+            let end = (start + PAGE_SIZE).min(data.len());
+            page.write_at(0, &data[start..end]);
+        }
+        Ok(())
+    }
+    
+    fn writepage(&self, page: &CachedPage, len: usize) -> VfsResult<()> {
+        let mut data = self.data.lock();
+        let start = page.index() as usize * PAGE_SIZE;
+        if self.crypt.is_encrypted() {
+            let mut block = alloc::vec![0u8; EXT4_BLOCK_SIZE];
+            page.read_at(0, &mut block[..len]);
+            self.crypt.encrypt_block(page.index(), &mut block)?;
+            // A hole before this block must still decrypt to zeroes
+            while data.len() < start {
+                let mut zero = alloc::vec![0u8; EXT4_BLOCK_SIZE];
+                self.crypt.encrypt_block((data.len() / EXT4_BLOCK_SIZE) as u64, &mut zero)?;
+                data.extend_from_slice(&zero);
+            }
+            if data.len() == start {
+                data.extend_from_slice(&block);
+            } else {
+                data[start..start + EXT4_BLOCK_SIZE].copy_from_slice(&block);
+            }
+            return Ok(());
+        }
+        if data.len() < start + len {
+            data.resize(start + len, 0);
+        }
+        page.read_at(0, &mut data[start..start + len]);
+        Ok(())
+    }
 }
 
 impl InodeOps for Ext4InodeImpl {
@@ -277,15 +366,18 @@ impl InodeOps for Ext4InodeImpl {
         Ok(self.attr.lock().clone())
     }
     
-    fn setattr(&self, attr: &FileAttr) -> VfsResult<()> {
-        let mut my_attr = self.attr.lock();
-        my_attr.mode = attr.mode;
-        my_attr.uid = attr.uid;
-        my_attr.gid = attr.gid;
-        my_attr.size = attr.size;
-        my_attr.atime = attr.atime;
-        my_attr.mtime = attr.mtime;
-        my_attr.ctime = attr.ctime;
+    fn setattr(&self, attr: &SetAttr) -> VfsResult<()> {
+        if attr.has(SetAttr::SIZE) {
+            let mode = self.attr.lock().mode;
+            if mode.is_dir() {
+                return Err(VfsError::IsDirectory);
+            }
+            if !mode.is_regular() {
+                return Err(VfsError::InvalidOperation);
+            }
+            self.truncate(attr.size)?;
+        }
+        attr.apply(&mut self.attr.lock());
         Ok(())
     }
     
@@ -297,9 +389,8 @@ impl InodeOps for Ext4InodeImpl {
         drop(attr);
         
         let children = self.children.lock();
-        children.get(name)
-            .cloned()
-            .ok_or(VfsError::NotFound)
+        let stored = self.crypt.find_entry(name, &children)?.ok_or(VfsError::NotFound)?;
+        Ok(children[stored].clone())
     }
     
     fn create(&self, name: &str, mode: FileMode) -> VfsResult<Arc<dyn InodeOps>> {
@@ -309,8 +400,9 @@ impl InodeOps for Ext4InodeImpl {
         }
         drop(attr);
         
+        let stored = self.crypt.encrypt_name(name)?;
         let mut children = self.children.lock();
-        if children.contains_key(name) {
+        if children.contains_key(&stored) {
             return Err(VfsError::Exists);
         }
         
@@ -318,13 +410,14 @@ impl InodeOps for Ext4InodeImpl {
         static NEXT_INO: AtomicU64 = AtomicU64::new(100);
         let ino = NEXT_INO.fetch_add(1, Ordering::Relaxed);
         
-        let inode = Arc::new(Ext4InodeImpl::new_file(ino));
+        let inode = Ext4InodeImpl::new_file(ino, self.keyring());
+        inode.crypt.inherit(&self.crypt)?;
         {
             let mut attr = inode.attr.lock();
             attr.mode = mode;
         }
         
-        children.insert(name.to_string(), inode.clone());
+        children.insert(stored, inode.clone());
         Ok(inode)
     }
     
@@ -335,21 +428,25 @@ impl InodeOps for Ext4InodeImpl {
         }
         drop(attr);
         
+        let stored = self.crypt.encrypt_name(name)?;
         let mut children = self.children.lock();
-        if children.contains_key(name) {
+        if children.contains_key(&stored) {
             return Err(VfsError::Exists);
         }
         
         static NEXT_INO: AtomicU64 = AtomicU64::new(100);
         let ino = NEXT_INO.fetch_add(1, Ordering::Relaxed);
         
-        let inode = Arc::new(Ext4InodeImpl::new_dir(ino));
+        let inode = Arc::new(Ext4InodeImpl::new_dir(ino, self.keyring()));
+        inode.crypt.inherit(&self.crypt)?;
         {
             let mut attr = inode.attr.lock();
             attr.mode = FileMode(FileMode::S_IFDIR | mode.permissions());
         }
         
-        children.insert(name.to_string(), inode.clone());
+        children.insert(stored, inode.clone());
+        // The new directory's ".." refers to us
+        self.adjust_nlink(1);
         Ok(inode)
     }
     
@@ -361,21 +458,15 @@ impl InodeOps for Ext4InodeImpl {
         drop(attr);
         
         let mut children = self.children.lock();
-        let inode = children.get(name).ok_or(VfsError::NotFound)?;
+        let stored = self.crypt.find_entry(name, &children)?.ok_or(VfsError::NotFound)?.clone();
+        let inode = &children[&stored];
         
-        let iattr = inode.getattr()?;
-        if iattr.mode.is_dir() {
+        if inode.getattr()?.mode.is_dir() {
             return Err(VfsError::IsDirectory);
         }
         
-        // Decrement nlink
-        let mut new_attr = iattr.clone();
-        if new_attr.nlink > 0 {
-            new_attr.nlink -= 1;
-            inode.setattr(&new_attr)?;
-        }
-        
-        children.remove(name);
+        Self::of(inode.as_ref())?.adjust_nlink(-1);
+        children.remove(&stored);
         Ok(())
     }
     
@@ -387,7 +478,8 @@ impl InodeOps for Ext4InodeImpl {
         drop(attr);
         
         let mut children = self.children.lock();
-        let inode = children.get(name).ok_or(VfsError::NotFound)?;
+        let stored = self.crypt.find_entry(name, &children)?.ok_or(VfsError::NotFound)?.clone();
+        let inode = &children[&stored];
         
         if !inode.getattr()?.mode.is_dir() {
             return Err(VfsError::NotDirectory);
@@ -397,7 +489,9 @@ impl InodeOps for Ext4InodeImpl {
             return Err(VfsError::NotEmpty);
         }
         
-        children.remove(name);
+        Self::of(inode.as_ref())?.attr.lock().nlink = 0;
+        children.remove(&stored);
+        self.adjust_nlink(-1);
         Ok(())
     }
     
@@ -407,56 +501,84 @@ impl InodeOps for Ext4InodeImpl {
     }
     
     fn link(&self, name: &str, inode: Arc<dyn InodeOps>) -> VfsResult<()> {
+        let target = Self::of(inode.as_ref())?;
+        if target.attr.lock().mode.is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.crypt.check_link(&target.crypt)?;
+        
+        let stored = self.crypt.encrypt_name(name)?;
         let mut children = self.children.lock();
-        if children.contains_key(name) {
+        if children.contains_key(&stored) {
             return Err(VfsError::Exists);
         }
         
-        // Increment nlink
-        let mut attr = inode.getattr()?;
-        attr.nlink += 1;
-        inode.setattr(&attr)?;
-        
-        children.insert(name.to_string(), inode);
+        target.adjust_nlink(1);
+        children.insert(stored, inode);
         Ok(())
     }
     
-    fn rename(&self, old_name: &str, new_dir: &dyn InodeOps, new_name: &str) -> VfsResult<()> {
-        let mut children = self.children.lock();
-        let inode = children.remove(old_name).ok_or(VfsError::NotFound)?;
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn InodeOps>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> VfsResult<()> {
+        let new_dir = Self::of(new_dir.as_ref())?;
+        if !new_dir.attr.lock().mode.is_dir() {
+            return Err(VfsError::NotDirectory);
+        }
         
-        // Add to new directory
-        // Note: This is simplified - real implementation would handle cross-directory rename
-        drop(children);
+        // Names are stored encrypted, so both directories need their keys
+        self.crypt.require_key()?;
+        new_dir.crypt.require_key()?;
+        let old_stored = self.crypt.encrypt_name(old_name)?;
+        let new_stored = new_dir.crypt.encrypt_name(new_name)?;
+        if !core::ptr::eq(self, new_dir) {
+            let moved = self.lookup(old_name)?;
+            new_dir.crypt.check_link(&Self::of(moved.as_ref())?.crypt)?;
+            if flags.contains(RenameFlags::EXCHANGE) {
+                let other = new_dir.lookup(new_name)?;
+                self.crypt.check_link(&Self::of(other.as_ref())?.crypt)?;
+            }
+        }
         
-        // For same directory rename, just update the name
-        if core::ptr::eq(self as *const _ as *const (), new_dir as *const _ as *const ()) {
-            let mut children = self.children.lock();
-            children.insert(new_name.to_string(), inode);
-            Ok(())
-        } else {
-            // Cross-directory rename - would need proper implementation
-            Err(VfsError::NotSupported)
+        let outcome = rename_entries(&self.children, &old_stored, &new_dir.children, &new_stored, flags)?;
+        self.adjust_nlink(outcome.old_dir_links);
+        new_dir.adjust_nlink(outcome.new_dir_links);
+        if let Some(replaced) = outcome.replaced {
+            let replaced = Self::of(replaced.as_ref())?;
+            if replaced.attr.lock().mode.is_dir() {
+                replaced.attr.lock().nlink = 0;
+            } else {
+                replaced.adjust_nlink(-1);
+            }
         }
+        Ok(())
     }
     
     fn symlink(&self, name: &str, target: &str) -> VfsResult<Arc<dyn InodeOps>> {
+        let stored = self.crypt.encrypt_name(name)?;
         let mut children = self.children.lock();
-        if children.contains_key(name) {
+        if children.contains_key(&stored) {
             return Err(VfsError::Exists);
         }
         
         static NEXT_INO: AtomicU64 = AtomicU64::new(100);
         let ino = NEXT_INO.fetch_add(1, Ordering::Relaxed);
         
-        let inode = Arc::new(Ext4InodeImpl::new_symlink(ino, target));
-        children.insert(name.to_string(), inode.clone());
+        let inode = Arc::new(Ext4InodeImpl::new_symlink(ino, target.len(), self.keyring()));
+        inode.crypt.inherit(&self.crypt)?;
+        *inode.target.lock() = Some(inode.crypt.encrypt_target(target)?);
+        children.insert(stored, inode.clone());
         Ok(inode)
     }
     
     fn readlink(&self) -> VfsResult<String> {
         let target = self.target.lock();
-        target.clone().ok_or(VfsError::InvalidOperation)
+        let target = target.as_ref().ok_or(VfsError::InvalidOperation)?;
+        Ok(self.crypt.decrypt_target(target))
     }
     
     fn readdir(&self, _offset: usize) -> VfsResult<Vec<DirEntry>> {
@@ -472,7 +594,7 @@ impl InodeOps for Ext4InodeImpl {
         for (name, inode) in children.iter() {
             let iattr = inode.getattr()?;
             entries.push(DirEntry {
-                name: name.clone(),
+                name: self.crypt.decrypt_name(name),
                 ino: iattr.ino,
                 file_type: iattr.mode.file_type(),
             });
@@ -482,51 +604,80 @@ impl InodeOps for Ext4InodeImpl {
     }
     
     fn read(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
-        let data = self.data.lock();
-        let start = offset as usize;
-        
-        if start >= data.len() {
-            return Ok(0);
-        }
-        
-        let end = (start + buf.len()).min(data.len());
-        let len = end - start;
-        buf[..len].copy_from_slice(&data[start..end]);
-        
-        Ok(len)
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        self.crypt.require_key()?;
+        mapping.read(offset, buf)
     }
     
     fn write(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
-        let mut data = self.data.lock();
-        let start = offset as usize;
-        
-        // Extend if necessary
-        if start + buf.len() > data.len() {
-            data.resize(start + buf.len(), 0);
-        }
-        
-        data[start..start + buf.len()].copy_from_slice(buf);
-        
-        // Update size
-        let mut attr = self.attr.lock();
-        attr.size = data.len() as u64;
-        
-        Ok(buf.len())
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        self.crypt.require_key()?;
+        let written = mapping.write(offset, buf)?;
+        self.attr.lock().size = mapping.size();
+        Ok(written)
     }
     
     fn truncate(&self, size: u64) -> VfsResult<()> {
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        self.crypt.require_key()?;
+        mapping.truncate(size);
+        // Growing leaves a hole that reads as zeroes; shrinking frees the
+        // blocks past the new end
         let mut data = self.data.lock();
-        data.resize(size as usize, 0);
+        let size = size as usize;
+        if self.crypt.is_encrypted() {
+            // Whole blocks are kept; the tail of a partial last block must
+            // be zeroed in the plaintext
+            if size < data.len() {
+                data.truncate(size.next_multiple_of(EXT4_BLOCK_SIZE));
+                let tail = size % EXT4_BLOCK_SIZE;
+                if tail != 0 {
+                    let lblk = (size / EXT4_BLOCK_SIZE) as u64;
+                    let block = &mut data[size - tail..];
+                    self.crypt.decrypt_block(lblk, block)?;
+                    block[tail..].fill(0);
+                    self.crypt.encrypt_block(lblk, block)?;
+                }
+            }
+        } else if size < data.len() {
+            data.truncate(size);
+        }
         
         let mut attr = self.attr.lock();
-        attr.size = size;
+        attr.size = size as u64;
         
         Ok(())
     }
-    
-    fn sync(&self) -> VfsResult<()> {
-        // TODO: Sync inode to disk
-        Ok(())
+
+    fn mapping(&self) -> Option<Arc<AddressSpace>> {
+        self.mapping.clone()
+    }
+
+    fn getxattr(&self, name: &str) -> VfsResult<Vec<u8>> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().get(name)
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> VfsResult<()> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().set(name, value, flags)
+    }
+
+    fn listxattr(&self) -> VfsResult<Vec<String>> {
+        Ok(self.xattrs.lock().list())
+    }
+
+    fn removexattr(&self, name: &str) -> VfsResult<()> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().remove(name)
+    }
+
+    fn fscrypt(&self) -> Option<&InodeCrypt> {
+        Some(&self.crypt)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 }
 
@@ -2,13 +2,15 @@
 extern crate alloc;
 use alloc::sync::Arc;
 
-use super::{fs::InodeOps, error::VfsResult, types::FileAttr};
+use super::{fs::{InodeOps, SetAttr}, error::VfsResult, namei::PathLoc, types::FileAttr};
 
 /// Open file handle
 pub struct VfsFile {
     pub inode: Arc<dyn InodeOps>,
     pub offset: u64,
     flags: u32,
+    /// Where the file was opened, for `fchdir` and `*at()` lookups
+    pub loc: Option<PathLoc>,
 }
 
 impl VfsFile {
@@ -17,6 +19,17 @@ impl VfsFile {
             inode,
             offset: 0,
             flags,
+            loc: None,
+        }
+    }
+
+    /// Open handle for a resolved path
+    pub fn at(loc: PathLoc, flags: u32) -> Self {
+        Self {
+            inode: loc.inode.clone(),
+            offset: 0,
+            flags,
+            loc: Some(loc),
         }
     }
     
@@ -61,10 +74,7 @@ impl VfsFile {
 
     /// Truncate file
     pub fn truncate(&self, size: u64) -> VfsResult<()> {
-        let attr = self.inode.getattr()?;
-        let mut new_attr = attr;
-        new_attr.size = size;
-        self.inode.setattr(&new_attr)
+        self.inode.setattr(&SetAttr::truncate(size))
     }
     
     /// Get file attributes
@@ -73,7 +83,7 @@ impl VfsFile {
     }
 
     /// Set file attributes
-    pub fn set_attr(&self, attr: &FileAttr) -> VfsResult<()> {
+    pub fn set_attr(&self, attr: &SetAttr) -> VfsResult<()> {
         self.inode.setattr(attr)
     }
 }
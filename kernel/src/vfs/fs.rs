@@ -1,307 +1,448 @@
-//! SysFS file system type and superblock
+//! Filesystem, superblock and inode operation traits
+//!
+//! Every filesystem driver (ramfs, tmpfs, ext4, procfs, sysfs) implements
+//! these traits; the VFS manager only ever talks to `dyn InodeOps`.
+//! Optional operations have default implementations returning
+//! `NotSupported` so read-only pseudo filesystems stay small.
 
 extern crate alloc;
-use alloc::{string::String, string::ToString, sync::Arc, vec::Vec, collections::BTreeMap, boxed::Box};
-use crate::vfs::types::FileType;
-use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::any::Any;
 
 use crate::subsystems::sync::Mutex;
 
 use super::{
-    devices,
-    kernel,
-};
-use crate::vfs::{
     error::*,
     types::*,
     dir::DirEntry,
+    page_cache::AddressSpace,
+    xattr::XattrFlags,
 };
 
-/// SysFS file system type
-pub struct SysFsType;
+/// Filesystem type registered with the VFS manager
+pub trait FileSystemType: Send + Sync {
+    /// Name used by `mount` (e.g. "tmpfs")
+    fn name(&self) -> &str;
 
-impl super::fs::FileSystemType for SysFsType {
-    fn name(&self) -> &str {
-        "sysfs"
-    }
-    
-    fn mount(&self, _device: Option<&str>, _flags: u32) -> VfsResult<Arc<dyn super::fs::SuperBlock>> {
-        Ok(Arc::new(SysFsSuperBlock::new()))
-    }
+    /// Create a superblock for a new mount of this filesystem
+    fn mount(&self, device: Option<&str>, flags: u32) -> VfsResult<Arc<dyn SuperBlock>>;
 }
 
-/// SysFS superblock
-struct SysFsSuperBlock {
-    root: Arc<SysFsInode>,
-    next_ino: AtomicUsize,
+/// Filesystem statistics (statfs)
+#[derive(Debug, Clone, Default)]
+pub struct FsStats {
+    pub bsize: u64,     // Block size
+    pub blocks: u64,    // Total blocks
+    pub bfree: u64,     // Free blocks
+    pub bavail: u64,    // Free blocks available to unprivileged users
+    pub files: u64,     // Total inodes
+    pub ffree: u64,     // Free inodes
+    pub namelen: u64,   // Maximum filename length
 }
 
-impl SysFsSuperBlock {
-    fn new() -> Self {
-        let root = Arc::new(SysFsInode::new_dir(1));
-        
-        Self {
-            root,
-            next_ino: AtomicUsize::new(2),
-        }
-    }
-    
-    fn alloc_ino(&self) -> u64 {
-        self.next_ino.fetch_add(1, Ordering::Relaxed) as u64
-    }
-}
+/// A mounted filesystem instance
+pub trait SuperBlock: Send + Sync {
+    /// Root inode of this mount
+    fn root(&self) -> Arc<dyn InodeOps>;
 
-impl super::fs::SuperBlock for SysFsSuperBlock {
-    fn root(&self) -> Arc<dyn InodeOps> {
-        self.root.clone()
-    }
-    
-    fn fs_type(&self) -> &str {
-        "sysfs"
-    }
-    
-    fn sync(&self) -> VfsResult<()> {
-        Ok(()) // SysFS doesn't need sync
-    }
-    
-    fn statfs(&self) -> VfsResult<super::fs::FsStats> {
-        Ok(FsStats {
-            bsize: 4096,
-            blocks: 0,
-            bfree: 0,
-            bavail: 0,
-            files: self.next_ino.load(Ordering::Relaxed) as u64,
-            ffree: u64::MAX,
-            namelen: 255,
-        })
-    }
-    
-    fn unmount(&self) -> VfsResult<()> {
-        Ok(())
-    }
-}
+    /// Filesystem type name
+    fn fs_type(&self) -> &str;
 
-/// SysFS inode
-pub struct SysFsInode {
-    attr: Mutex<FileAttr>,
-    // For directories
-    children: Mutex<BTreeMap<String, Arc<dyn InodeOps>>>,
-    // For regular files - content generator
-    content_gen: Mutex<Option<Box<dyn Fn() -> String + Send + Sync>>>,
-    // Inode type
-    inode_type: SysFsInodeType,
+    /// Flush dirty state to the backing device
+    fn sync(&self) -> VfsResult<()>;
+
+    /// Filesystem statistics
+    fn statfs(&self) -> VfsResult<FsStats>;
+
+    /// Tear down the mount
+    fn unmount(&self) -> VfsResult<()>;
 }
 
-#[derive(Clone, Copy)]
-enum SysFsInodeType {
-    Directory,
-    RegularFile,
-    Symlink,
+/// Attribute change request for [`InodeOps::setattr`]
+///
+/// Only the fields whose bit is set in `valid` are applied, so one call
+/// covers truncate, chmod, chown and utimens.
+#[derive(Debug, Clone, Default)]
+pub struct SetAttr {
+    pub valid: u32,
+    pub mode: u32,      // Permission bits only; the file type is never changed
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
 }
 
-impl SysFsInode {
-    /// Create a new directory inode
-    pub fn new_dir(ino: u64) -> Self {
-        Self {
-            attr: Mutex::new(FileAttr {
-                ino,
-                mode: FileMode(FileMode::S_IFDIR | 0o555),
-                nlink: 2,
-                ..Default::default()
-            }),
-            children: Mutex::new(BTreeMap::new()),
-            content_gen: Mutex::new(None),
-            inode_type: SysFsInodeType::Directory,
-        }
+impl SetAttr {
+    pub const MODE: u32  = 1 << 0;
+    pub const UID: u32   = 1 << 1;
+    pub const GID: u32   = 1 << 2;
+    pub const SIZE: u32  = 1 << 3;
+    pub const ATIME: u32 = 1 << 4;
+    pub const MTIME: u32 = 1 << 5;
+    pub const CTIME: u32 = 1 << 6;
+
+    /// Change permission bits (chmod)
+    pub fn chmod(mode: u32) -> Self {
+        Self { valid: Self::MODE, mode, ..Default::default() }
     }
-    
-    /// Create a new regular file inode with content generator
-    pub fn new_file(ino: u64, content_gen: Box<dyn Fn() -> String + Send + Sync>) -> Self {
-        Self {
-            attr: Mutex::new(FileAttr {
-                ino,
-                mode: FileMode(FileMode::S_IFREG | 0o444),
-                nlink: 1,
-                size: 0, // Will be calculated on read
-                ..Default::default()
-            }),
-            children: Mutex::new(BTreeMap::new()),
-            content_gen: Mutex::new(Some(content_gen)),
-            inode_type: SysFsInodeType::RegularFile,
+
+    /// Change owner and/or group (chown); `None` leaves the id unchanged
+    pub fn chown(uid: Option<u32>, gid: Option<u32>) -> Self {
+        let mut attr = Self::default();
+        if let Some(uid) = uid {
+            attr.valid |= Self::UID;
+            attr.uid = uid;
         }
-    }
-    
-    /// Add a child inode
-    pub fn add_child(&self, name: String, inode: Arc<dyn InodeOps>) {
-        self.children.lock().insert(name, inode);
-    }
-
-    /// Get mutable reference to children map
-    pub fn children(&self) -> &Mutex<BTreeMap<String, Arc<dyn InodeOps>>> {
-        &self.children
-    }
-    
-    /// Create a symlink inode
-    pub fn new_symlink(ino: u64, target: &str) -> Self {
-        let target_clone = target.to_string();
-        Self {
-            attr: Mutex::new(FileAttr {
-                ino,
-                mode: FileMode(FileMode::S_IFLNK | 0o777),
-                nlink: 1,
-                size: target_clone.len() as u64,
-                ..Default::default()
-            }),
-            children: Mutex::new(BTreeMap::new()),
-            content_gen: Mutex::new(Some(Box::new(move || target_clone.clone()))),
-            inode_type: SysFsInodeType::Symlink,
+        if let Some(gid) = gid {
+            attr.valid |= Self::GID;
+            attr.gid = gid;
         }
+        attr
     }
-}
 
-impl super::fs::InodeOps for SysFsInode {
-    fn getattr(&self) -> VfsResult<FileAttr> {
-        Ok(self.attr.lock().clone())
-    }
-
-    fn lookup(&self, name: &str) -> VfsResult<Arc<dyn InodeOps>> {
-        // Check standard /sys directories
-        match name {
-            "devices" => {
-                return devices::create_root();
-            }
-            "bus" => {
-                return devices::create_bus_root();
-            }
-            "class" => {
-                return devices::create_class_root();
-            }
-            "dev" => {
-                return devices::create_dev_root();
-            }
-            "kernel" => {
-                return kernel::create_root();
-            }
-            "module" => {
-                return kernel::create_module_root();
-            }
-            "firmware" => {
-                return devices::create_firmware_root();
-            }
-            "fs" => {
-                return devices::create_fs_root();
-            }
-            "power" => {
-                return devices::create_power_root();
-            }
-            _ => {}
+    /// Change the file size (truncate)
+    pub fn truncate(size: u64) -> Self {
+        Self { valid: Self::SIZE, size, ..Default::default() }
+    }
+
+    /// Change access and/or modification time (utimens)
+    pub fn utimens(atime: Option<u64>, mtime: Option<u64>) -> Self {
+        let mut attr = Self::default();
+        if let Some(atime) = atime {
+            attr.valid |= Self::ATIME;
+            attr.atime = atime;
         }
-        
-        // Check children
-        let children = self.children.lock();
-        if let Some(inode) = children.get(name) {
-            return Ok(inode.clone());
+        if let Some(mtime) = mtime {
+            attr.valid |= Self::MTIME;
+            attr.mtime = mtime;
         }
-        
-        Err(VfsError::NotFound)
+        attr
     }
 
-    fn readdir(&self, offset: usize) -> VfsResult<Vec<DirEntry>> {
-        if !self.attr.lock().mode.is_dir() {
-            return Err(VfsError::NotDirectory);
+    pub fn has(&self, flag: u32) -> bool {
+        self.valid & flag != 0
+    }
+
+    /// Apply every field except `SIZE` to `attr`
+    ///
+    /// Size changes need the filesystem to resize its data, so drivers
+    /// handle `SIZE` themselves before calling this.
+    pub fn apply(&self, attr: &mut FileAttr) {
+        if self.has(Self::MODE) {
+            attr.mode = FileMode((attr.mode.0 & FileMode::S_IFMT) | (self.mode & !FileMode::S_IFMT));
+        }
+        if self.has(Self::UID) {
+            attr.uid = self.uid;
         }
-        
-        let mut entries = Vec::new();
-        
-        // Add "." and ".."
-        if offset == 0 {
-            entries.push(DirEntry {
-                ino: self.attr.lock().ino,
-                name: ".".to_string(),
-                file_type: FileType::Directory,
-            });
+        if self.has(Self::GID) {
+            attr.gid = self.gid;
         }
-        if offset <= 1 {
-            entries.push(DirEntry {
-                ino: 1, // Root inode
-                name: "..".to_string(),
-                file_type: FileType::Directory,
-            });
+        if self.has(Self::ATIME) {
+            attr.atime = self.atime;
         }
-        
-        // Add standard /sys directories
-        let standard_dirs = ["devices", "bus", "class", "dev", "kernel", "module", "firmware", "fs", "power"];
-        let start_idx = if offset > 2 { offset - 2 } else { 0 };
-        for (idx, dir) in standard_dirs.iter().enumerate().skip(start_idx) {
-            entries.push(DirEntry {
-                ino: 1000 + idx as u64,
-                name: dir.to_string(),
-                file_type: FileType::Directory,
-            });
+        if self.has(Self::MTIME) {
+            attr.mtime = self.mtime;
         }
-        
-        // Add children
-        let children = self.children.lock();
-        let child_start = if offset > 2 + standard_dirs.len() { offset - 2 - standard_dirs.len() } else { 0 };
-        for (idx, (name, _)) in children.iter().enumerate().skip(child_start) {
-            entries.push(DirEntry {
-                ino: 2000 + idx as u64,
-                name: name.clone(),
-                file_type: FileType::Directory,
-            });
+        if self.has(Self::CTIME) {
+            attr.ctime = self.ctime;
         }
-        
-        Ok(entries)
     }
+}
+
+/// Flags for [`InodeOps::rename`], same values as Linux `renameat2`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenameFlags(pub u32);
 
-    fn read(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
-        if self.attr.lock().mode.is_dir() {
-            return Err(VfsError::IsDirectory);
+impl RenameFlags {
+    /// Fail with `Exists` instead of replacing the target
+    pub const NOREPLACE: u32 = 1 << 0;
+    /// Atomically swap source and target; both must exist
+    pub const EXCHANGE: u32  = 1 << 1;
+    /// Leave a whiteout behind (overlay filesystems only)
+    pub const WHITEOUT: u32  = 1 << 2;
+
+    pub fn contains(&self, flag: u32) -> bool {
+        self.0 & flag != 0
+    }
+
+    /// Reject unknown bits and contradictory combinations
+    pub fn validate(&self) -> VfsResult<()> {
+        if self.0 & !(Self::NOREPLACE | Self::EXCHANGE | Self::WHITEOUT) != 0 {
+            return Err(VfsError::InvalidOperation);
+        }
+        if self.contains(Self::NOREPLACE) && self.contains(Self::EXCHANGE) {
+            return Err(VfsError::InvalidOperation);
         }
-        
-        let content_gen = self.content_gen.lock();
-        if let Some(ref r#gen) = *content_gen {
-            let content = r#gen();
-            let content_bytes = content.as_bytes();
-            let start = offset as usize;
-            if start >= content_bytes.len() {
-                return Ok(0);
-            }
-            let end = core::cmp::min(start + buf.len(), content_bytes.len());
-            let len = end - start;
-            buf[..len].copy_from_slice(&content_bytes[start..end]);
-            
-            // Update size in attributes
-            let mut attr = self.attr.lock();
-            attr.size = content_bytes.len() as u64;
-            
-            Ok(len)
-        } else {
-            Err(VfsError::InvalidOperation)
+        if self.contains(Self::WHITEOUT) {
+            return Err(VfsError::NotSupported);
         }
+        Ok(())
+    }
+}
+
+/// Inode operations
+///
+/// Directory operations take the name of an entry inside `self`. Inodes
+/// passed back in (`link`, `rename`) must belong to the same filesystem;
+/// drivers downcast them through [`InodeOps::as_any`] and return
+/// `CrossDevice` otherwise.
+pub trait InodeOps: Send + Sync {
+    /// Get file attributes
+    fn getattr(&self) -> VfsResult<FileAttr>;
+
+    /// Change the attributes selected by `attr.valid`
+    fn setattr(&self, _attr: &SetAttr) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Look up a directory entry
+    fn lookup(&self, _name: &str) -> VfsResult<Arc<dyn InodeOps>> {
+        Err(VfsError::NotDirectory)
+    }
+
+    /// Create a regular file
+    fn create(&self, _name: &str, _mode: FileMode) -> VfsResult<Arc<dyn InodeOps>> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Create a directory
+    fn mkdir(&self, _name: &str, _mode: FileMode) -> VfsResult<Arc<dyn InodeOps>> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Remove a non-directory entry
+    fn unlink(&self, _name: &str) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Remove an empty directory
+    fn rmdir(&self, _name: &str) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Whether a directory has no entries
+    fn is_empty(&self) -> VfsResult<bool> {
+        Err(VfsError::NotDirectory)
+    }
+
+    /// Add a hard link `name` to an existing inode
+    fn link(&self, _name: &str, _inode: Arc<dyn InodeOps>) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
     }
-    
+
+    /// Move `old_name` in this directory to `new_name` in `new_dir`
+    ///
+    /// Without flags an existing target is atomically replaced. The caller
+    /// must already have rejected moving a directory into its own subtree
+    /// and replacing an ancestor of the source; the driver only sees two
+    /// directories and cannot check that itself.
+    fn rename(
+        &self,
+        _old_name: &str,
+        _new_dir: &Arc<dyn InodeOps>,
+        _new_name: &str,
+        _flags: RenameFlags,
+    ) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Create a symbolic link pointing at `target`
+    fn symlink(&self, _name: &str, _target: &str) -> VfsResult<Arc<dyn InodeOps>> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Read the target of a symbolic link
     fn readlink(&self) -> VfsResult<String> {
-        if self.attr.lock().mode.file_type() != FileType::Symlink {
-            return Err(VfsError::InvalidOperation);
-        }
-        
-        let content_gen = self.content_gen.lock();
-        if let Some(ref r#gen) = *content_gen {
-            Ok(r#gen())
-        } else {
-            Err(VfsError::InvalidOperation)
+        Err(VfsError::InvalidOperation)
+    }
+
+    /// List directory entries starting at `offset`
+    fn readdir(&self, _offset: usize) -> VfsResult<Vec<DirEntry>> {
+        Err(VfsError::NotDirectory)
+    }
+
+    /// Read file data
+    fn read(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidOperation)
+    }
+
+    /// Write file data
+    fn write(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidOperation)
+    }
+
+    /// Resize file data; prefer `setattr(&SetAttr::truncate(..))` from callers
+    fn truncate(&self, _size: u64) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Page cache holding the file's data, shared by `read`, `write` and
+    /// file-backed mappings
+    fn mapping(&self) -> Option<Arc<AddressSpace>> {
+        None
+    }
+
+    /// Flush this inode to the backing device
+    ///
+    /// The default writes back the dirty pages of [`InodeOps::mapping`].
+    fn sync(&self) -> VfsResult<()> {
+        match self.mapping() {
+            Some(mapping) => mapping.fsync(),
+            None => Ok(()),
         }
     }
+
+    /// Get the value of an extended attribute
+    fn getxattr(&self, _name: &str) -> VfsResult<Vec<u8>> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Set an extended attribute
+    fn setxattr(&self, _name: &str, _value: &[u8], _flags: XattrFlags) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// List extended attribute names
+    fn listxattr(&self) -> VfsResult<Vec<String>> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Remove an extended attribute
+    fn removexattr(&self, _name: &str) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// Encryption state, on filesystems that support fscrypt
+    fn fscrypt(&self) -> Option<&super::fscrypt::InodeCrypt> {
+        None
+    }
+
+    /// Concrete inode, for drivers downcasting inodes of their own filesystem
+    fn as_any(&self) -> &dyn Any;
 }
 
-/// Initialize and register SysFS
-pub fn init() {
-    let sysfs = Arc::new(SysFsType);
-    if let Err(e) = super::super::vfs().register_fs(sysfs) {
-        crate::println!("[sysfs] Failed to register sysfs: {:?}", e);
-    } else {
-        crate::println!("[sysfs] Registered sysfs filesystem");
+/// Directory contents of the in-memory filesystems
+pub type DirMap = BTreeMap<String, Arc<dyn InodeOps>>;
+
+/// Link count changes produced by [`rename_entries`]
+///
+/// The driver applies these to its own inodes, since `nlink` lives in
+/// driver-private state.
+pub struct RenameOutcome {
+    /// Change to the source directory's `nlink`
+    pub old_dir_links: i32,
+    /// Change to the target directory's `nlink`
+    pub new_dir_links: i32,
+    /// Inode that lost its name at the target, if one was replaced
+    pub replaced: Option<Arc<dyn InodeOps>>,
+}
+
+impl RenameOutcome {
+    fn unchanged() -> Self {
+        Self { old_dir_links: 0, new_dir_links: 0, replaced: None }
     }
 }
 
+/// Rename between two `DirMap`s, shared by ramfs, tmpfs and ext4
+///
+/// `old_dir` and `new_dir` may be the same map. When they differ both are
+/// locked in address order so concurrent renames in opposite directions
+/// cannot deadlock.
+pub fn rename_entries(
+    old_dir: &Mutex<DirMap>,
+    old_name: &str,
+    new_dir: &Mutex<DirMap>,
+    new_name: &str,
+    flags: RenameFlags,
+) -> VfsResult<RenameOutcome> {
+    flags.validate()?;
+    for name in [old_name, new_name] {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(VfsError::InvalidPath);
+        }
+    }
+
+    if core::ptr::eq(old_dir, new_dir) {
+        let mut dir = old_dir.lock();
+        return rename_locked(&mut dir, old_name, None, new_name, flags);
+    }
+
+    let old_first = (old_dir as *const Mutex<DirMap>) < (new_dir as *const Mutex<DirMap>);
+    let (mut src, mut dst) = if old_first {
+        let src = old_dir.lock();
+        (src, new_dir.lock())
+    } else {
+        let dst = new_dir.lock();
+        (old_dir.lock(), dst)
+    };
+    rename_locked(&mut src, old_name, Some(&mut dst), new_name, flags)
+}
+
+/// Rename with both directories locked; `dst == None` means same directory
+fn rename_locked(
+    src: &mut DirMap,
+    old_name: &str,
+    mut dst: Option<&mut DirMap>,
+    new_name: &str,
+    flags: RenameFlags,
+) -> VfsResult<RenameOutcome> {
+    let same_dir = dst.is_none();
+    let source = src.get(old_name).cloned().ok_or(VfsError::NotFound)?;
+    let target = match dst.as_deref() {
+        Some(dst) => dst.get(new_name).cloned(),
+        None => src.get(new_name).cloned(),
+    };
+    let source_is_dir = source.getattr()?.mode.is_dir() as i32;
+    let moves_dir = if same_dir { 0 } else { source_is_dir };
+
+    if flags.contains(RenameFlags::EXCHANGE) {
+        let target = target.ok_or(VfsError::NotFound)?;
+        if Arc::ptr_eq(&source, &target) {
+            return Ok(RenameOutcome::unchanged());
+        }
+        let target_is_dir = if same_dir { 0 } else { target.getattr()?.mode.is_dir() as i32 };
+        src.insert(old_name.into(), target);
+        match dst.as_deref_mut() {
+            Some(dst) => dst.insert(new_name.into(), source),
+            None => src.insert(new_name.into(), source),
+        };
+        return Ok(RenameOutcome {
+            old_dir_links: target_is_dir - moves_dir,
+            new_dir_links: moves_dir - target_is_dir,
+            replaced: None,
+        });
+    }
+
+    let mut replaced_dir = 0;
+    if let Some(target) = &target {
+        if flags.contains(RenameFlags::NOREPLACE) {
+            return Err(VfsError::Exists);
+        }
+        // Both names already refer to the same inode: POSIX says do nothing.
+        if Arc::ptr_eq(&source, target) {
+            return Ok(RenameOutcome::unchanged());
+        }
+        let target_is_dir = target.getattr()?.mode.is_dir();
+        match (source_is_dir != 0, target_is_dir) {
+            (true, false) => return Err(VfsError::NotDirectory),
+            (false, true) => return Err(VfsError::IsDirectory),
+            (true, true) if !target.is_empty()? => return Err(VfsError::NotEmpty),
+            _ => {}
+        }
+        replaced_dir = target_is_dir as i32;
+    }
+
+    src.remove(old_name);
+    match dst.as_deref_mut() {
+        Some(dst) => dst.insert(new_name.into(), source),
+        None => src.insert(new_name.into(), source),
+    };
+    Ok(RenameOutcome {
+        old_dir_links: -moves_dir,
+        new_dir_links: moves_dir - replaced_dir,
+        replaced: target,
+    })
+}
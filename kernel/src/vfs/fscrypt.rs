@@ -0,0 +1,811 @@
+//! Per-directory filesystem encryption, after Linux fscrypt
+//!
+//! Only version 2 policies are supported, with the modes Linux uses by
+//! default. Policies, contexts, key identifiers and the per-file key
+//! derivation match Linux, but directory entries hold the base64url of the
+//! name ciphertext rather than the raw bytes, so encrypted directories are
+//! not interchangeable with Linux ones:
+//!
+//! - A policy is set on an empty directory and names a master key by its
+//!   identifier. Everything created below inherits it; each inode gets its
+//!   own random 16-byte nonce, kept in its [`Context`].
+//! - Master keys are added to and removed from the filesystem's
+//!   [`Keyring`], by value or from an `fscrypt-provisioning` key.
+//!   Per-file keys are HKDF-SHA512 of the master key with the inode's
+//!   nonce, so no two files share a key.
+//! - File contents are AES-256-XTS per filesystem block, the tweak being
+//!   the logical block number. Names and symlink targets are AES-256-CTS
+//!   with a zero IV, NUL-padded to the policy's padding.
+//! - Without the master key a directory is locked: its entries are listed
+//!   and looked up by a "no-key" name, the base64url of the ciphertext,
+//!   nothing can be created in it and encrypted files cannot be read or
+//!   written. Removing a key writes back and drops the cached plaintext
+//!   of the files it unlocked. No-key names are only stable on this system.
+//!
+//! The ext4 driver keeps an [`InodeCrypt`] in every inode and calls into it
+//! for names, data blocks and the `FS_IOC_*ENCRYPTION*` ioctls.
+
+extern crate alloc;
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::{Arc, Weak}, vec, vec::Vec};
+
+use crate::security::keys::{self, KeyError, KeySerial, KeyType};
+use crate::subsystems::crypto::{self, aes, hkdf, modes, random, Aes, Digest, Sha256, Sha512, Xts};
+use crate::subsystems::sync::Mutex;
+
+use super::{error::*, fs::InodeOps, page_cache::AddressSpace};
+
+pub const FSCRYPT_POLICY_V2: u8 = 2;
+const FSCRYPT_CONTEXT_V2: u8 = 2;
+
+pub const FSCRYPT_MODE_AES_256_XTS: u8 = 1;
+pub const FSCRYPT_MODE_AES_256_CTS: u8 = 4;
+
+/// Name padding: 4, 8, 16 or 32 bytes
+pub const FSCRYPT_POLICY_FLAGS_PAD_MASK: u8 = 0x03;
+
+pub const FSCRYPT_KEY_IDENTIFIER_SIZE: usize = 16;
+pub const FSCRYPT_FILE_NONCE_SIZE: usize = 16;
+pub const FSCRYPT_MIN_KEY_SIZE: usize = 16;
+pub const FSCRYPT_MAX_KEY_SIZE: usize = 64;
+
+/// Names shorter than this are padded up to it
+const FSCRYPT_FNAME_MIN_MSG_LEN: usize = 16;
+const NAME_MAX: usize = 255;
+
+/// `info` prefixes for the HKDF expansions, as in Linux
+const HKDF_CONTEXT_KEY_IDENTIFIER: u8 = 1;
+const HKDF_CONTEXT_PER_FILE_ENC_KEY: u8 = 2;
+
+pub const FS_IOC_SET_ENCRYPTION_POLICY: u32 = 0x800c_6613;
+pub const FS_IOC_GET_ENCRYPTION_POLICY_EX: u32 = 0xc009_6616;
+pub const FS_IOC_ADD_ENCRYPTION_KEY: u32 = 0xc050_6617;
+pub const FS_IOC_REMOVE_ENCRYPTION_KEY: u32 = 0xc040_6618;
+pub const FS_IOC_REMOVE_ENCRYPTION_KEY_ALL_USERS: u32 = 0xc040_6619;
+pub const FS_IOC_GET_ENCRYPTION_KEY_STATUS: u32 = 0xc080_661a;
+pub const FS_IOC_GET_ENCRYPTION_NONCE: u32 = 0x8010_661b;
+
+/// `fscrypt_key_specifier.type` naming a v2 key by identifier
+pub const FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER: u32 = 2;
+
+pub const FSCRYPT_KEY_STATUS_ABSENT: u32 = 1;
+pub const FSCRYPT_KEY_STATUS_PRESENT: u32 = 2;
+
+/// Some files were still mapped and keep their plaintext pages until unmapped
+pub const FSCRYPT_KEY_REMOVAL_STATUS_FLAG_FILES_BUSY: u32 = 0x1;
+
+/// `struct fscrypt_key_specifier`: type, reserved, 32-byte union
+const KEY_SPEC_SIZE: usize = 40;
+/// `struct fscrypt_add_key_arg` without the trailing raw key
+const ADD_KEY_ARG_SIZE: usize = 80;
+const REMOVE_KEY_ARG_SIZE: usize = 64;
+const KEY_STATUS_ARG_SIZE: usize = 128;
+
+/// `struct fscrypt_policy_v2`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyV2 {
+    pub contents_mode: u8,
+    pub filenames_mode: u8,
+    pub flags: u8,
+    pub master_key_identifier: [u8; FSCRYPT_KEY_IDENTIFIER_SIZE],
+}
+
+impl PolicyV2 {
+    pub const SIZE: usize = 24;
+
+    /// Parse and validate a policy passed in from user space
+    pub fn from_bytes(bytes: &[u8]) -> VfsResult<Self> {
+        if bytes.len() != Self::SIZE || bytes[0] != FSCRYPT_POLICY_V2 {
+            return Err(VfsError::InvalidOperation);
+        }
+        // log2_data_unit_size and the reserved bytes: only the defaults
+        if bytes[4..8] != [0; 4] {
+            return Err(VfsError::InvalidOperation);
+        }
+        let policy = Self {
+            contents_mode: bytes[1],
+            filenames_mode: bytes[2],
+            flags: bytes[3],
+            master_key_identifier: bytes[8..24].try_into().unwrap(),
+        };
+        if policy.contents_mode != FSCRYPT_MODE_AES_256_XTS
+            || policy.filenames_mode != FSCRYPT_MODE_AES_256_CTS
+            || policy.flags & !FSCRYPT_POLICY_FLAGS_PAD_MASK != 0
+        {
+            return Err(VfsError::InvalidOperation);
+        }
+        Ok(policy)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[..4].copy_from_slice(&[FSCRYPT_POLICY_V2, self.contents_mode, self.filenames_mode, self.flags]);
+        bytes[8..].copy_from_slice(&self.master_key_identifier);
+        bytes
+    }
+
+    /// Names are padded to a multiple of this
+    fn name_padding(&self) -> usize {
+        4 << (self.flags & FSCRYPT_POLICY_FLAGS_PAD_MASK)
+    }
+}
+
+/// `struct fscrypt_context_v2`, stored with each encrypted inode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Context {
+    pub policy: PolicyV2,
+    pub nonce: [u8; FSCRYPT_FILE_NONCE_SIZE],
+}
+
+impl Context {
+    pub const SIZE: usize = 40;
+
+    pub fn from_bytes(bytes: &[u8]) -> VfsResult<Self> {
+        if bytes.len() != Self::SIZE || bytes[0] != FSCRYPT_CONTEXT_V2 {
+            return Err(VfsError::IoError);
+        }
+        let mut policy = bytes[..PolicyV2::SIZE].to_vec();
+        policy[0] = FSCRYPT_POLICY_V2;
+        let policy = PolicyV2::from_bytes(&policy).map_err(|_| VfsError::IoError)?;
+        Ok(Self { policy, nonce: bytes[24..].try_into().unwrap() })
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[..PolicyV2::SIZE].copy_from_slice(&self.policy.to_bytes());
+        bytes[0] = FSCRYPT_CONTEXT_V2;
+        bytes[24..].copy_from_slice(&self.nonce);
+        bytes
+    }
+}
+
+/// `HKDF-Expand(prk, "fscrypt\0" || context || info)`
+fn derive(prk: &[u8], context: u8, info: &[u8], okm: &mut [u8]) {
+    let mut full = Vec::with_capacity(9 + info.len());
+    full.extend_from_slice(b"fscrypt\0");
+    full.push(context);
+    full.extend_from_slice(info);
+    // Output lengths here are far below the HKDF limit
+    hkdf::expand::<Sha512>(prk, &full, okm).unwrap();
+}
+
+/// A master key added to a filesystem
+pub struct MasterKey {
+    identifier: [u8; FSCRYPT_KEY_IDENTIFIER_SIZE],
+    /// HKDF pseudorandom key; wiped when the key is removed
+    prk: Mutex<Option<Vec<u8>>>,
+    /// Page caches holding plaintext decrypted under this key
+    users: Mutex<Vec<Weak<AddressSpace>>>,
+}
+
+impl MasterKey {
+    fn derive(&self, context: u8, info: &[u8], okm: &mut [u8]) -> VfsResult<()> {
+        let prk = self.prk.lock();
+        let prk = prk.as_ref().ok_or(VfsError::NoKey)?;
+        derive(prk, context, info, okm);
+        Ok(())
+    }
+
+    fn is_present(&self) -> bool {
+        self.prk.lock().is_some()
+    }
+}
+
+/// The master keys of one filesystem
+///
+/// Keys are not tracked per user: any caller may add a key, and removing
+/// it locks the files for everyone.
+#[derive(Default)]
+pub struct Keyring {
+    keys: Mutex<BTreeMap<[u8; FSCRYPT_KEY_IDENTIFIER_SIZE], Arc<MasterKey>>>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a raw master key and return its identifier
+    ///
+    /// Adding a key that is already present succeeds and changes nothing.
+    pub fn add(&self, raw: &[u8]) -> VfsResult<[u8; FSCRYPT_KEY_IDENTIFIER_SIZE]> {
+        if !(FSCRYPT_MIN_KEY_SIZE..=FSCRYPT_MAX_KEY_SIZE).contains(&raw.len()) {
+            return Err(VfsError::InvalidOperation);
+        }
+        let prk = hkdf::extract::<Sha512>(&[], raw);
+        let mut identifier = [0u8; FSCRYPT_KEY_IDENTIFIER_SIZE];
+        derive(&prk, HKDF_CONTEXT_KEY_IDENTIFIER, &[], &mut identifier);
+
+        let mut keys = self.keys.lock();
+        if let Some(key) = keys.get(&identifier)
+            && key.is_present()
+        {
+            let mut prk = prk;
+            crypto::zeroize(&mut prk);
+            return Ok(identifier);
+        }
+        keys.insert(
+            identifier,
+            Arc::new(MasterKey { identifier, prk: Mutex::new(Some(prk)), users: Mutex::new(Vec::new()) }),
+        );
+        Ok(identifier)
+    }
+
+    /// Remove a master key, locking every file it unlocked
+    ///
+    /// Dirty data is written back while the key is still usable and cached
+    /// plaintext is dropped. Pages still mapped somewhere cannot be dropped;
+    /// the returned flags then include `FILES_BUSY`.
+    pub fn remove(&self, identifier: &[u8; FSCRYPT_KEY_IDENTIFIER_SIZE]) -> VfsResult<u32> {
+        let key = self.keys.lock().remove(identifier).ok_or(VfsError::NoKey)?;
+        let users = core::mem::take(&mut *key.users.lock());
+        let mut flags = 0;
+        for mapping in users.iter().filter_map(Weak::upgrade) {
+            let _ = mapping.fsync();
+            mapping.invalidate_range(0, u64::MAX);
+            if mapping.nr_pages() != 0 {
+                flags |= FSCRYPT_KEY_REMOVAL_STATUS_FLAG_FILES_BUSY;
+            }
+        }
+        if let Some(mut prk) = key.prk.lock().take() {
+            crypto::zeroize(&mut prk);
+        }
+        Ok(flags)
+    }
+
+    /// `FSCRYPT_KEY_STATUS_*` of a key
+    pub fn status(&self, identifier: &[u8; FSCRYPT_KEY_IDENTIFIER_SIZE]) -> u32 {
+        match self.keys.lock().get(identifier) {
+            Some(key) if key.is_present() => FSCRYPT_KEY_STATUS_PRESENT,
+            _ => FSCRYPT_KEY_STATUS_ABSENT,
+        }
+    }
+
+    fn get(&self, identifier: &[u8; FSCRYPT_KEY_IDENTIFIER_SIZE]) -> Option<Arc<MasterKey>> {
+        self.keys.lock().get(identifier).filter(|key| key.is_present()).cloned()
+    }
+}
+
+/// Per-file key, derived from the master key and the inode's nonce
+enum FileCipher {
+    /// Regular file contents
+    Contents(Box<Xts>),
+    /// Directory entry names, or a symlink's target
+    Names(Box<Aes>),
+}
+
+struct FileKey {
+    master: Arc<MasterKey>,
+    cipher: FileCipher,
+}
+
+struct CryptState {
+    context: Context,
+    key: Option<FileKey>,
+}
+
+/// Encryption state of one inode
+pub struct InodeCrypt {
+    keyring: Arc<Keyring>,
+    /// Regular files use the contents mode, everything else the names mode
+    regular: bool,
+    /// Page cache of a regular file, dropped when its key is removed
+    mapping: Option<Weak<AddressSpace>>,
+    state: Mutex<Option<CryptState>>,
+}
+
+impl InodeCrypt {
+    pub fn new(keyring: Arc<Keyring>, regular: bool, mapping: Option<Weak<AddressSpace>>) -> Self {
+        Self { keyring, regular, mapping, state: Mutex::new(None) }
+    }
+
+    pub fn keyring(&self) -> &Arc<Keyring> {
+        &self.keyring
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.state.lock().is_some()
+    }
+
+    pub fn context(&self) -> Option<Context> {
+        self.state.lock().as_ref().map(|state| state.context)
+    }
+
+    /// Policy of an encrypted inode; `NoData` otherwise
+    pub fn policy(&self) -> VfsResult<PolicyV2> {
+        self.context().map(|context| context.policy).ok_or(VfsError::NoData)
+    }
+
+    /// Attach a context read back from disk
+    pub fn load(&self, context: Context) {
+        *self.state.lock() = Some(CryptState { context, key: None });
+    }
+
+    /// FS_IOC_SET_ENCRYPTION_POLICY on a directory
+    ///
+    /// Setting the policy a directory already has is allowed; changing it
+    /// is not. The directory must be empty and the key must have been added.
+    pub fn set_policy(&self, policy: &PolicyV2, is_empty: bool) -> VfsResult<()> {
+        let mut state = self.state.lock();
+        if let Some(state) = state.as_ref() {
+            return if state.context.policy == *policy { Ok(()) } else { Err(VfsError::Exists) };
+        }
+        if !is_empty {
+            return Err(VfsError::NotEmpty);
+        }
+        if self.keyring.get(&policy.master_key_identifier).is_none() {
+            return Err(VfsError::NoKey);
+        }
+        *state = Some(CryptState { context: new_context(policy), key: None });
+        Ok(())
+    }
+
+    /// Give a new inode created in `parent` the parent's policy
+    pub fn inherit(&self, parent: &InodeCrypt) -> VfsResult<()> {
+        let Some(policy) = parent.context().map(|context| context.policy) else { return Ok(()) };
+        parent.require_key()?;
+        *self.state.lock() = Some(CryptState { context: new_context(&policy), key: None });
+        Ok(())
+    }
+
+    /// Whether `child` may be linked or moved into this directory
+    ///
+    /// Inside an encrypted directory everything must share its policy, so
+    /// nothing unencrypted or under another key can be brought in.
+    pub fn check_link(&self, child: &InodeCrypt) -> VfsResult<()> {
+        let Some(policy) = self.context().map(|context| context.policy) else { return Ok(()) };
+        self.require_key()?;
+        match child.context() {
+            Some(context) if context.policy == policy => Ok(()),
+            _ => Err(VfsError::CrossDevice),
+        }
+    }
+
+    /// Fail with `NoKey` if the inode is encrypted and locked
+    pub fn require_key(&self) -> VfsResult<()> {
+        self.with_key(|_| ()).map(|_| ())
+    }
+
+    /// Run `f` with the per-file key; `None` for an unencrypted inode
+    fn with_key<R>(&self, f: impl FnOnce(&FileCipher) -> R) -> VfsResult<Option<R>> {
+        let mut guard = self.state.lock();
+        let Some(state) = guard.as_mut() else { return Ok(None) };
+        if state.key.as_ref().is_some_and(|key| !key.master.is_present()) {
+            state.key = None;
+        }
+        if state.key.is_none() {
+            state.key = Some(self.setup_key(&state.context)?);
+        }
+        Ok(Some(f(&state.key.as_ref().unwrap().cipher)))
+    }
+
+    fn setup_key(&self, context: &Context) -> VfsResult<FileKey> {
+        let master = self.keyring.get(&context.policy.master_key_identifier).ok_or(VfsError::NoKey)?;
+        let mut raw = [0u8; FSCRYPT_MAX_KEY_SIZE];
+        let cipher = if self.regular {
+            master.derive(HKDF_CONTEXT_PER_FILE_ENC_KEY, &context.nonce, &mut raw)?;
+            FileCipher::Contents(Box::new(Xts::new(&raw).unwrap()))
+        } else {
+            master.derive(HKDF_CONTEXT_PER_FILE_ENC_KEY, &context.nonce, &mut raw[..32])?;
+            FileCipher::Names(Box::new(Aes::new(&raw[..32]).unwrap()))
+        };
+        crypto::zeroize(&mut raw);
+        if let Some(mapping) = &self.mapping {
+            let mut users = master.users.lock();
+            users.retain(|user| user.strong_count() > 0);
+            users.push(mapping.clone());
+        }
+        debug_assert_eq!(master.identifier, context.policy.master_key_identifier);
+        Ok(FileKey { master, cipher })
+    }
+
+    /// Encrypt filesystem block `lblk` of a regular file in place
+    pub fn encrypt_block(&self, lblk: u64, block: &mut [u8]) -> VfsResult<()> {
+        self.crypt_block(lblk, block, true)
+    }
+
+    pub fn decrypt_block(&self, lblk: u64, block: &mut [u8]) -> VfsResult<()> {
+        self.crypt_block(lblk, block, false)
+    }
+
+    fn crypt_block(&self, lblk: u64, block: &mut [u8], encrypt: bool) -> VfsResult<()> {
+        let result = self.with_key(|cipher| match cipher {
+            FileCipher::Contents(xts) if encrypt => xts.encrypt(&Xts::sector_tweak(lblk), block),
+            FileCipher::Contents(xts) => xts.decrypt(&Xts::sector_tweak(lblk), block),
+            FileCipher::Names(_) => Err(crypto::CryptoError::InvalidKeyLength),
+        })?;
+        match result {
+            None | Some(Ok(())) => Ok(()),
+            Some(Err(_)) => Err(VfsError::IoError),
+        }
+    }
+
+    /// Encrypt `plain` with the names key, padded as the policy asks
+    ///
+    /// Padding stops at `max_len`, so the ciphertext is never longer than
+    /// that; longer input is refused.
+    fn encrypt_bytes(&self, plain: &[u8], max_len: usize) -> VfsResult<Option<Vec<u8>>> {
+        if plain.len() > max_len {
+            return Err(VfsError::NameTooLong);
+        }
+        let Some(padding) = self.context().map(|context| context.policy.name_padding()) else { return Ok(None) };
+        let len = plain.len().max(FSCRYPT_FNAME_MIN_MSG_LEN).next_multiple_of(padding).min(max_len);
+        let mut data = vec![0u8; len];
+        data[..plain.len()].copy_from_slice(plain);
+        self.with_key(|cipher| match cipher {
+            FileCipher::Names(aes) => modes::cts_encrypt(aes, &[0; aes::BLOCK_SIZE], &mut data),
+            FileCipher::Contents(_) => Err(crypto::CryptoError::InvalidKeyLength),
+        })?
+        .transpose()
+        .map_err(|_| VfsError::IoError)?;
+        Ok(Some(data))
+    }
+
+    /// Decrypt and unpad; `None` when locked or the ciphertext is invalid
+    fn decrypt_bytes(&self, ciphertext: &[u8]) -> Option<String> {
+        let mut data = ciphertext.to_vec();
+        let result = self.with_key(|cipher| match cipher {
+            FileCipher::Names(aes) => modes::cts_decrypt(aes, &[0; aes::BLOCK_SIZE], &mut data),
+            FileCipher::Contents(_) => Err(crypto::CryptoError::InvalidKeyLength),
+        });
+        if !matches!(result, Ok(Some(Ok(())))) {
+            return None;
+        }
+        let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        data.truncate(len);
+        String::from_utf8(data).ok()
+    }
+
+    /// Name under which `name` is stored in this directory
+    ///
+    /// Unencrypted directories store names as they are. Encrypted ones
+    /// store the base64url of the ciphertext, and need the key to make it.
+    pub fn encrypt_name(&self, name: &str) -> VfsResult<String> {
+        if !self.is_encrypted() {
+            return Ok(String::from(name));
+        }
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(VfsError::InvalidPath);
+        }
+        // Like Linux, bound the ciphertext rather than its encoding
+        let ciphertext = self.encrypt_bytes(name.as_bytes(), NAME_MAX)?.unwrap();
+        Ok(base64url_encode(&ciphertext))
+    }
+
+    /// Name to show for stored entry `stored`: the plaintext when unlocked,
+    /// the no-key name otherwise
+    pub fn decrypt_name(&self, stored: &str) -> String {
+        if !self.is_encrypted() {
+            return String::from(stored);
+        }
+        base64url_decode(stored)
+            .and_then(|ciphertext| self.decrypt_bytes(&ciphertext))
+            .unwrap_or_else(|| nokey_name(stored))
+    }
+
+    /// Stored name of the entry `name` refers to, if there is one
+    ///
+    /// With the key `name` is a plaintext name; without it, a no-key name
+    /// as listed by `readdir`.
+    pub fn find_entry<'a, V>(&self, name: &str, entries: &'a BTreeMap<String, V>) -> VfsResult<Option<&'a String>> {
+        if !self.is_encrypted() {
+            return Ok(entries.get_key_value(name).map(|(stored, _)| stored));
+        }
+        match self.encrypt_name(name) {
+            Ok(stored) => return Ok(entries.get_key_value(&stored).map(|(stored, _)| stored)),
+            Err(VfsError::NoKey) => {}
+            Err(err) => return Err(err),
+        }
+        if let Some((stored, _)) = entries.get_key_value(name) {
+            return Ok(Some(stored));
+        }
+        Ok(entries.keys().find(|stored| nokey_name(stored) == name))
+    }
+
+    /// On-disk form of a symlink target; `target` unchanged if unencrypted
+    pub fn encrypt_target(&self, target: &str) -> VfsResult<String> {
+        const SYMLINK_MAX: usize = 4096 - 2;
+        if target.len() > SYMLINK_MAX {
+            return Err(VfsError::NameTooLong);
+        }
+        match self.encrypt_bytes(target.as_bytes(), SYMLINK_MAX)? {
+            Some(ciphertext) => Ok(base64url_encode(&ciphertext)),
+            None => Ok(String::from(target)),
+        }
+    }
+
+    /// Symlink target to show: decrypted, or the stored form when locked
+    pub fn decrypt_target(&self, stored: &str) -> String {
+        if !self.is_encrypted() {
+            return String::from(stored);
+        }
+        base64url_decode(stored)
+            .and_then(|ciphertext| self.decrypt_bytes(&ciphertext))
+            .unwrap_or_else(|| String::from(stored))
+    }
+}
+
+fn new_context(policy: &PolicyV2) -> Context {
+    let mut nonce = [0u8; FSCRYPT_FILE_NONCE_SIZE];
+    random::fill(&mut nonce);
+    Context { policy: *policy, nonce }
+}
+
+/// Bytes of ciphertext kept as-is in a long no-key name
+const NOKEY_PREFIX: usize = 149;
+
+/// No-key name of the entry stored as `stored`
+///
+/// Short names show the whole stored form. Longer ones would not fit in
+/// NAME_MAX, so the tail of the ciphertext is replaced by its SHA-256.
+pub fn nokey_name(stored: &str) -> String {
+    if stored.len() <= NAME_MAX {
+        return String::from(stored);
+    }
+    let ciphertext = base64url_decode(stored).unwrap_or_default();
+    let mut short = ciphertext[..NOKEY_PREFIX.min(ciphertext.len())].to_vec();
+    short.extend_from_slice(&Sha256::digest(&ciphertext[short.len()..]));
+    base64url_encode(&short)
+}
+
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Unpadded base64url, which never produces '/' or '.'
+fn base64url_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(BASE64URL[(bits >> (18 - 6 * i)) as usize & 0x3f] as char);
+        }
+    }
+    out
+}
+
+fn base64url_decode(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut nbits = 0;
+    for c in text.bytes() {
+        let value = BASE64URL.iter().position(|&b| b == c)? as u32;
+        bits = (bits << 6) | value;
+        nbits += 6;
+        if nbits >= 8 {
+            nbits -= 8;
+            out.push((bits >> nbits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Whether `request` is one of the fscrypt ioctls
+pub fn is_ioctl(request: u32) -> bool {
+    matches!(
+        request,
+        FS_IOC_SET_ENCRYPTION_POLICY
+            | FS_IOC_GET_ENCRYPTION_POLICY_EX
+            | FS_IOC_ADD_ENCRYPTION_KEY
+            | FS_IOC_REMOVE_ENCRYPTION_KEY
+            | FS_IOC_REMOVE_ENCRYPTION_KEY_ALL_USERS
+            | FS_IOC_GET_ENCRYPTION_KEY_STATUS
+            | FS_IOC_GET_ENCRYPTION_NONCE
+    )
+}
+
+/// Key identifier from a `struct fscrypt_key_specifier`
+fn key_spec(spec: &[u8]) -> VfsResult<[u8; FSCRYPT_KEY_IDENTIFIER_SIZE]> {
+    let kind = u32::from_ne_bytes(spec[..4].try_into().unwrap());
+    // v1 descriptors are not supported; reserved fields must be zero
+    if kind != FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER || spec[4..8] != [0; 4] || spec[24..KEY_SPEC_SIZE] != [0; 16] {
+        return Err(VfsError::InvalidOperation);
+    }
+    Ok(spec[8..24].try_into().unwrap())
+}
+
+/// Copy the raw key of fscrypt-provisioning key `key_id` into `raw`
+///
+/// Returns its length. The provisioning key must be meant for a v2 policy,
+/// and the caller needs SEARCH permission on it.
+fn provisioned_key(key_id: KeySerial, raw: &mut [u8; FSCRYPT_MAX_KEY_SIZE]) -> Result<usize, KeyError> {
+    let mut payload = keys::payload(key_id, KeyType::FscryptProvisioning)?;
+    // struct fscrypt_provisioning_key_payload: u32 type, u32 reserved, raw
+    let len = payload.len() - 8;
+    let result = if u32::from_ne_bytes(payload[..4].try_into().unwrap()) != FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER {
+        Err(KeyError::Rejected)
+    } else {
+        raw[..len].copy_from_slice(&payload[8..]);
+        Ok(len)
+    };
+    crypto::zeroize(&mut payload);
+    result
+}
+
+/// Handle an fscrypt ioctl on `inode`
+///
+/// `read` and `write` copy from and to the argument in user memory at a
+/// byte offset, so the layouts stay here and the caller only supplies the
+/// copy routines.
+pub fn ioctl<E: From<VfsError> + From<KeyError>>(
+    inode: &dyn InodeOps,
+    request: u32,
+    read: impl Fn(usize, &mut [u8]) -> Result<(), E>,
+    write: impl Fn(usize, &[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    let crypt = inode.fscrypt().ok_or(VfsError::NotSupported)?;
+    match request {
+        FS_IOC_SET_ENCRYPTION_POLICY => {
+            let mut arg = [0u8; PolicyV2::SIZE];
+            read(0, &mut arg[..1])?;
+            // v1 policies and their 12-byte layout are not supported
+            if arg[0] != FSCRYPT_POLICY_V2 {
+                return Err(VfsError::InvalidOperation.into());
+            }
+            read(0, &mut arg)?;
+            let policy = PolicyV2::from_bytes(&arg)?;
+            if !inode.getattr()?.mode.is_dir() {
+                return Err(VfsError::NotDirectory.into());
+            }
+            crypt.set_policy(&policy, inode.is_empty()?)?;
+        }
+        FS_IOC_GET_ENCRYPTION_POLICY_EX => {
+            // struct fscrypt_get_policy_ex_arg: u64 policy_size, then the policy
+            let mut size = [0u8; 8];
+            read(0, &mut size)?;
+            let policy = crypt.policy()?;
+            // Linux says EOVERFLOW; the closest we have is EINVAL
+            if u64::from_ne_bytes(size) < PolicyV2::SIZE as u64 {
+                return Err(VfsError::InvalidOperation.into());
+            }
+            write(0, &(PolicyV2::SIZE as u64).to_ne_bytes())?;
+            write(8, &policy.to_bytes())?;
+        }
+        FS_IOC_GET_ENCRYPTION_NONCE => {
+            let context = crypt.context().ok_or(VfsError::NoData)?;
+            write(0, &context.nonce)?;
+        }
+        FS_IOC_ADD_ENCRYPTION_KEY => {
+            let mut arg = [0u8; ADD_KEY_ARG_SIZE];
+            read(0, &mut arg)?;
+            let raw_size = u32::from_ne_bytes(arg[40..44].try_into().unwrap()) as usize;
+            let key_id = u32::from_ne_bytes(arg[44..48].try_into().unwrap());
+            let kind = u32::from_ne_bytes(arg[..4].try_into().unwrap());
+            // The identifier is an output
+            if kind != FSCRYPT_KEY_SPEC_TYPE_IDENTIFIER
+                || arg[4..KEY_SPEC_SIZE] != [0; 36]
+                || arg[48..] != [0; 32]
+            {
+                return Err(VfsError::InvalidOperation.into());
+            }
+            let mut raw = [0u8; FSCRYPT_MAX_KEY_SIZE];
+            let result = if key_id != 0 {
+                // The raw key comes from an fscrypt-provisioning key instead
+                if raw_size != 0 {
+                    return Err(VfsError::InvalidOperation.into());
+                }
+                provisioned_key(key_id as KeySerial, &mut raw).map_err(E::from)
+            } else if (FSCRYPT_MIN_KEY_SIZE..=FSCRYPT_MAX_KEY_SIZE).contains(&raw_size) {
+                read(ADD_KEY_ARG_SIZE, &mut raw[..raw_size]).map(|()| raw_size)
+            } else {
+                return Err(VfsError::InvalidOperation.into());
+            };
+            let result = result.and_then(|len| crypt.keyring().add(&raw[..len]).map_err(E::from));
+            crypto::zeroize(&mut raw);
+            write(8, &result?)?;
+        }
+        FS_IOC_REMOVE_ENCRYPTION_KEY | FS_IOC_REMOVE_ENCRYPTION_KEY_ALL_USERS => {
+            let mut arg = [0u8; REMOVE_KEY_ARG_SIZE];
+            read(0, &mut arg)?;
+            let identifier = key_spec(&arg[..KEY_SPEC_SIZE])?;
+            let flags = crypt.keyring().remove(&identifier)?;
+            write(KEY_SPEC_SIZE, &flags.to_ne_bytes())?;
+        }
+        FS_IOC_GET_ENCRYPTION_KEY_STATUS => {
+            let mut arg = [0u8; KEY_STATUS_ARG_SIZE];
+            read(0, &mut arg)?;
+            let identifier = key_spec(&arg[..KEY_SPEC_SIZE])?;
+            // status, status_flags, user_count, reserved
+            let mut out = [0u8; KEY_STATUS_ARG_SIZE - 64];
+            out[..4].copy_from_slice(&crypt.keyring().status(&identifier).to_ne_bytes());
+            write(64, &out)?;
+        }
+        _ => return Err(VfsError::NotSupported.into()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring_with_key() -> (Arc<Keyring>, [u8; 16]) {
+        let keyring = Arc::new(Keyring::new());
+        let raw: Vec<u8> = (0..64).collect();
+        let identifier = keyring.add(&raw).unwrap();
+        (keyring, identifier)
+    }
+
+    fn policy(identifier: [u8; 16]) -> PolicyV2 {
+        PolicyV2 {
+            contents_mode: FSCRYPT_MODE_AES_256_XTS,
+            filenames_mode: FSCRYPT_MODE_AES_256_CTS,
+            flags: 2,
+            master_key_identifier: identifier,
+        }
+    }
+
+    #[test]
+    fn test_key_identifier() {
+        // HKDF-SHA512 with an empty salt, info "fscrypt\0\x01", as Linux
+        let (_, identifier) = keyring_with_key();
+        assert_eq!(identifier, [
+            0x86, 0x99, 0xc2, 0xc5, 0x37, 0x07, 0x40, 0x5d, 0xa5, 0xab, 0xa5, 0xae, 0x4d, 0x85, 0x83, 0xc0,
+        ]);
+    }
+
+    #[test]
+    fn test_policy_round_trip() {
+        let policy = policy([7; 16]);
+        assert_eq!(PolicyV2::from_bytes(&policy.to_bytes()), Ok(policy));
+        let mut bad = policy.to_bytes();
+        bad[1] = 9;
+        assert_eq!(PolicyV2::from_bytes(&bad), Err(VfsError::InvalidOperation));
+
+        let context = Context { policy, nonce: [3; 16] };
+        assert_eq!(context.to_bytes()[0], FSCRYPT_CONTEXT_V2);
+        assert_eq!(Context::from_bytes(&context.to_bytes()), Ok(context));
+    }
+
+    #[test]
+    fn test_names_and_locking() {
+        let (keyring, identifier) = keyring_with_key();
+        let dir = InodeCrypt::new(keyring.clone(), false, None);
+        assert_eq!(dir.set_policy(&policy([9; 16]), true), Err(VfsError::NoKey));
+        assert_eq!(dir.set_policy(&policy(identifier), false), Err(VfsError::NotEmpty));
+        dir.set_policy(&policy(identifier), true).unwrap();
+
+        let stored = dir.encrypt_name("report.txt").unwrap();
+        // 10 bytes pads to 16, which base64url-encodes to 22 characters
+        assert_eq!(stored.len(), 22);
+        assert_eq!(dir.decrypt_name(&stored), "report.txt");
+        assert_eq!(dir.encrypt_name(".."), Err(VfsError::InvalidPath));
+        // The longest name pads no further than NAME_MAX
+        let long = "x".repeat(NAME_MAX);
+        let stored_long = dir.encrypt_name(&long).unwrap();
+        assert_eq!(base64url_decode(&stored_long).map(|c| c.len()), Some(NAME_MAX));
+        assert_eq!(dir.decrypt_name(&stored_long), long);
+        assert_eq!(dir.encrypt_name(&"x".repeat(NAME_MAX + 1)), Err(VfsError::NameTooLong));
+
+        let mut entries = BTreeMap::new();
+        entries.insert(stored.clone(), ());
+        assert_eq!(dir.find_entry("report.txt", &entries), Ok(Some(&stored)));
+
+        let file = InodeCrypt::new(keyring.clone(), true, None);
+        file.inherit(&dir).unwrap();
+        let mut block = [0x5au8; 4096];
+        file.encrypt_block(3, &mut block).unwrap();
+        assert_ne!(block, [0x5a; 4096]);
+
+        assert_eq!(keyring.remove(&identifier), Ok(0));
+        // Locked: the no-key name lists and finds the entry
+        assert_eq!(dir.decrypt_name(&stored), stored);
+        assert_eq!(dir.find_entry(&stored, &entries), Ok(Some(&stored)));
+        assert_eq!(dir.encrypt_name("new"), Err(VfsError::NoKey));
+        assert_eq!(file.decrypt_block(3, &mut block), Err(VfsError::NoKey));
+
+        keyring.add(&(0..64).collect::<Vec<u8>>()).unwrap();
+        file.decrypt_block(3, &mut block).unwrap();
+        assert_eq!(block, [0x5a; 4096]);
+    }
+
+    #[test]
+    fn test_base64url() {
+        for len in 0..8 {
+            let data: Vec<u8> = (0..len).map(|i| 0xf0 ^ i).collect();
+            assert_eq!(base64url_decode(&base64url_encode(&data)), Some(data));
+        }
+        assert_eq!(base64url_encode(b"\xfb\xff"), "-_8");
+    }
+}
@@ -67,6 +67,40 @@ pub fn create_root() -> VfsResult<Arc<dyn InodeOps>> {
         })
     )));
     
+    // /sys/kernel/pid_max - exclusive upper bound on PIDs
+    children.insert("pid_max".to_string(), Arc::new(SysFsInode::new_file(
+        2007,
+        Box::new(|| {
+            format!("{}\n", crate::subsystems::process::manager::PROC_TABLE.lock().pid_max())
+        })
+    )));
+    
+    // /sys/kernel/core_pattern - where core dumps are written
+    children.insert("core_pattern".to_string(), Arc::new(SysFsInode::new_rw_file(
+        2008,
+        Box::new(|| format!("{}\n", crate::subsystems::process::coredump::core_pattern())),
+        Box::new(|pattern| {
+            if crate::subsystems::process::geteuid() != 0 {
+                return Err(VfsError::PermissionDenied);
+            }
+            crate::subsystems::process::coredump::set_core_pattern(pattern)
+                .map_err(|_| VfsError::InvalidOperation)
+        })
+    )));
+    
+    // /sys/kernel/suid_dumpable - whether processes that changed credentials dump core
+    children.insert("suid_dumpable".to_string(), Arc::new(SysFsInode::new_rw_file(
+        2009,
+        Box::new(|| format!("{}\n", crate::subsystems::process::coredump::suid_dumpable())),
+        Box::new(|mode| {
+            if crate::subsystems::process::geteuid() != 0 {
+                return Err(VfsError::PermissionDenied);
+            }
+            crate::subsystems::process::coredump::set_suid_dumpable(mode)
+                .map_err(|_| VfsError::InvalidOperation)
+        })
+    )));
+    
     drop(children);
     
     Ok(root)
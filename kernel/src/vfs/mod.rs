@@ -9,6 +9,7 @@ pub mod devices;
 pub mod kernel;
 pub mod error;
 pub mod mount;
+pub mod namei;
 pub mod dentry;
 pub mod dir;
 pub mod file;
@@ -18,6 +19,9 @@ pub mod tmpfs;
 pub mod ext4;
 pub mod procfs;
 pub mod sysfs;
+pub mod xattr;
+pub mod fscrypt;
+pub mod page_cache;
 
 pub use fs::*;
 pub use types::*;
@@ -26,6 +30,9 @@ pub use mount::*;
 pub use dentry::*;
 pub use dir::*;
 pub use file::*;
+pub use namei::{FsContext, LookupFlags, PathLoc};
+pub use xattr::{XattrFlags, XattrMap};
+pub use page_cache::AddressSpace;
 
 /// Get the global VFS manager instance
 /// 
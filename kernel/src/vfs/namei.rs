@@ -0,0 +1,296 @@
+//! Path name resolution
+//!
+//! Paths are walked one component at a time from a process's root or working
+//! directory ([`FsContext`]), or from an open directory for the `*at()`
+//! calls. Along the way the walk
+//!
+//! - steps into a filesystem mounted on a directory, and back out of it
+//!   through `..`;
+//! - never lets `..` climb above the context root, which is what makes
+//!   chroot a boundary;
+//! - follows symbolic links, failing with `TooManyLinks` (ELOOP) after
+//!   [`MAXSYMLINKS`] of them;
+//! - leaves a symlink in the final component alone unless
+//!   [`LookupFlags::FOLLOW`] is set, as for `lstat` or `O_NOFOLLOW`.
+//!
+//! Every resolved [`PathLoc`] carries its canonical path from the global
+//! root; mounts are matched and `getcwd` is answered from that path.
+
+extern crate alloc;
+
+use alloc::{format, string::{String, ToString}, sync::Arc, vec::Vec};
+use core::fmt;
+
+use super::{error::*, fs::InodeOps, mount::Mount};
+
+/// Symlinks followed in one lookup before giving up with ELOOP
+pub const MAXSYMLINKS: usize = 40;
+/// Longest path accepted, including the terminating NUL
+pub const PATH_MAX: usize = 4096;
+/// Longest single path component
+pub const NAME_MAX: usize = 255;
+
+/// A resolved location: an inode together with the mount it was reached in
+#[derive(Clone)]
+pub struct PathLoc {
+    pub mount: Arc<Mount>,
+    pub inode: Arc<dyn InodeOps>,
+    /// Canonical absolute path from the global root, without symlinks
+    pub path: String,
+}
+
+impl PathLoc {
+    /// Root directory of `mount`, located at its mount point
+    pub fn mount_root(mount: Arc<Mount>) -> Self {
+        Self {
+            inode: mount.superblock.root(),
+            path: mount.path.clone(),
+            mount,
+        }
+    }
+
+    pub fn is_dir(&self) -> VfsResult<bool> {
+        Ok(self.inode.getattr()?.mode.is_dir())
+    }
+
+    /// Canonical path of the entry `name` in this directory
+    pub fn child_path(&self, name: &str) -> String {
+        let mut path = self.path.clone();
+        if path != "/" {
+            path.push('/');
+        }
+        path.push_str(name);
+        path
+    }
+}
+
+impl fmt::Debug for PathLoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PathLoc")
+            .field("mount", &self.mount.path)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Lookup behaviour flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LookupFlags(pub u32);
+
+impl LookupFlags {
+    /// Follow a symlink in the final component
+    pub const FOLLOW: u32      = 1 << 0;
+    /// Fail with `NotDirectory` unless the result is a directory
+    pub const DIRECTORY: u32   = 1 << 1;
+    /// An empty path names the starting directory itself (AT_EMPTY_PATH)
+    pub const EMPTY_PATH: u32  = 1 << 2;
+
+    pub fn contains(&self, flag: u32) -> bool {
+        self.0 & flag != 0
+    }
+}
+
+/// Root and working directory that relative and absolute paths start from
+#[derive(Debug, Clone)]
+pub struct FsContext {
+    pub root: PathLoc,
+    pub cwd: PathLoc,
+}
+
+impl FsContext {
+    /// Context at the global root, used for kernel-internal path operations
+    pub fn global() -> VfsResult<Self> {
+        let root = PathLoc::mount_root(super::vfs().root_mount()?);
+        Ok(Self { cwd: root.clone(), root })
+    }
+
+    /// Context from a process's saved root and cwd (canonical paths)
+    ///
+    /// A working directory that no longer resolves, because it was removed
+    /// or renamed, falls back to the root.
+    pub fn new(root_path: Option<&str>, cwd_path: Option<&str>) -> VfsResult<Self> {
+        let global = Self::global()?;
+        let dir = LookupFlags(LookupFlags::DIRECTORY);
+        let root = match root_path {
+            Some(path) if path != "/" => global.lookup(None, path, dir)?,
+            _ => global.root.clone(),
+        };
+        let cwd = cwd_path
+            .and_then(|path| global.lookup(None, path, dir).ok())
+            .unwrap_or_else(|| root.clone());
+        Ok(Self { root, cwd })
+    }
+
+    /// Resolve `path`
+    ///
+    /// Relative paths start at `start`, or at the working directory when
+    /// `start` is `None`; absolute paths start at the context root.
+    pub fn lookup(&self, start: Option<&PathLoc>, path: &str, flags: LookupFlags) -> VfsResult<PathLoc> {
+        let start = start.unwrap_or(&self.cwd);
+        if path.is_empty() {
+            return if flags.contains(LookupFlags::EMPTY_PATH) {
+                Ok(start.clone())
+            } else {
+                Err(VfsError::NotFound)
+            };
+        }
+        if path.len() >= PATH_MAX {
+            return Err(VfsError::NameTooLong);
+        }
+
+        let mut walk = Walk {
+            ctx: self,
+            cur: start.clone(),
+            parents: Vec::new(),
+            pending: Vec::new(),
+            links: 0,
+        };
+        walk.push(path)?;
+        let loc = walk.run(flags.contains(LookupFlags::FOLLOW))?;
+        if flags.contains(LookupFlags::DIRECTORY) && !loc.is_dir()? {
+            return Err(VfsError::NotDirectory);
+        }
+        Ok(loc)
+    }
+
+    /// Resolve the directory containing `path` and return it with the final
+    /// component, for operations that create or remove that entry
+    ///
+    /// Trailing slashes are ignored. A final `.` or `..` cannot be created or
+    /// removed and fails with `InvalidOperation`; `/` has no parent entry and
+    /// fails with `Busy`.
+    pub fn lookup_parent(&self, start: Option<&PathLoc>, path: &str) -> VfsResult<(PathLoc, String)> {
+        if path.is_empty() {
+            return Err(VfsError::NotFound);
+        }
+        let trimmed = path.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return Err(VfsError::Busy);
+        }
+        let (dir, name) = match trimmed.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((dir, name)) => (dir, name),
+            None => ("", trimmed),
+        };
+        if name == "." || name == ".." {
+            return Err(VfsError::InvalidOperation);
+        }
+        if name.len() > NAME_MAX {
+            return Err(VfsError::NameTooLong);
+        }
+
+        let parent = if dir.is_empty() {
+            start.unwrap_or(&self.cwd).clone()
+        } else {
+            self.lookup(start, dir, LookupFlags(LookupFlags::FOLLOW))?
+        };
+        if !parent.is_dir()? {
+            return Err(VfsError::NotDirectory);
+        }
+        Ok((parent, name.to_string()))
+    }
+
+    /// Path of `loc` as seen from inside this context's root
+    ///
+    /// A location outside the root (a working directory left behind by
+    /// chroot) is reported with an "(unreachable)" prefix, as Linux does.
+    pub fn display_path(&self, loc: &PathLoc) -> String {
+        let root = self.root.path.as_str();
+        if root == "/" {
+            loc.path.clone()
+        } else if loc.path == root {
+            "/".to_string()
+        } else if loc.path.starts_with(root) && loc.path.as_bytes()[root.len()] == b'/' {
+            loc.path[root.len()..].to_string()
+        } else {
+            format!("(unreachable){}", loc.path)
+        }
+    }
+}
+
+/// State of one path walk
+struct Walk<'a> {
+    ctx: &'a FsContext,
+    cur: PathLoc,
+    /// Directories passed on the way down to `cur`, so `..` can go back up
+    parents: Vec<PathLoc>,
+    /// Components still to resolve, next one last
+    pending: Vec<String>,
+    links: usize,
+}
+
+impl Walk<'_> {
+    /// Queue the components of `path` ahead of whatever is still pending
+    fn push(&mut self, path: &str) -> VfsResult<()> {
+        if path.is_empty() {
+            return Err(VfsError::NotFound);
+        }
+        if path.starts_with('/') {
+            self.cur = self.ctx.root.clone();
+            self.parents.clear();
+        }
+        // A trailing slash requires a directory and follows a final
+        // symlink; a trailing "." component does exactly that.
+        if path.ends_with('/') && path.bytes().any(|b| b != b'/') {
+            self.pending.push(".".to_string());
+        }
+        for name in path.rsplit('/').filter(|c| !c.is_empty()) {
+            self.pending.push(name.to_string());
+        }
+        Ok(())
+    }
+
+    fn run(mut self, follow_final: bool) -> VfsResult<PathLoc> {
+        while let Some(name) = self.pending.pop() {
+            let last = self.pending.is_empty();
+            if !self.cur.is_dir()? {
+                return Err(VfsError::NotDirectory);
+            }
+            match name.as_str() {
+                "." => {}
+                ".." => self.up()?,
+                _ => {
+                    if name.len() > NAME_MAX {
+                        return Err(VfsError::NameTooLong);
+                    }
+                    let inode = self.cur.inode.lookup(&name)?;
+                    if inode.getattr()?.mode.is_symlink() && (!last || follow_final) {
+                        self.links += 1;
+                        if self.links > MAXSYMLINKS {
+                            return Err(VfsError::TooManyLinks);
+                        }
+                        // Relative targets resolve from the link's directory
+                        let target = inode.readlink()?;
+                        self.push(&target)?;
+                        continue;
+                    }
+                    let path = self.cur.child_path(&name);
+                    let next = match super::vfs().mount_at(&path) {
+                        Some(mount) => PathLoc::mount_root(mount),
+                        None => PathLoc { mount: self.cur.mount.clone(), inode, path },
+                    };
+                    self.parents.push(core::mem::replace(&mut self.cur, next));
+                }
+            }
+        }
+        Ok(self.cur)
+    }
+
+    fn up(&mut self) -> VfsResult<()> {
+        if self.cur.path == self.ctx.root.path {
+            return Ok(());
+        }
+        if let Some(parent) = self.parents.pop() {
+            self.cur = parent;
+            return Ok(());
+        }
+        // The walk started below the root (cwd or a dirfd); find the parent
+        // again from the global root, which also steps out of a mount.
+        let parent = match self.cur.path.rsplit_once('/') {
+            Some(("", _)) | None => "/",
+            Some((dir, _)) => dir,
+        };
+        self.cur = FsContext::global()?.lookup(None, parent, LookupFlags::default())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,679 @@
+//! Page cache
+//!
+//! The data of every regular file is cached in whole pages owned by the
+//! file's [`AddressSpace`] and indexed by page offset in an [`XArray`].
+//! `read`, `write` and faults on file-backed mappings all use the same
+//! pages, so a store through a shared mapping is seen by the next `read()`
+//! and the other way round.
+//!
+//! - A missing page is read in through the owner's [`PageIo`]. Sequential
+//!   readers get a readahead window that doubles up to 32 pages.
+//! - Writes and write faults mark pages dirty. The flusher threads in
+//!   [`writeback`] clean them in the background once they are old enough,
+//!   or sooner when too many pages are dirty. `fsync`, `msync(MS_SYNC)`
+//!   and `sync` write them back synchronously.
+//! - Filesystems with no backing store (ramfs, tmpfs) use
+//!   [`AddressSpace::new_memory`]. Their pages are the file's only copy, so
+//!   they are never dirtied or written back.
+//! - Pages read in from a store go on the file LRU of `mm::swap::lru`.
+//!   Reclaim drops them again with [`AddressSpace::try_evict`] once they
+//!   are clean, unmapped and unused since the last look.
+//!
+//! Lock order: a mapping's writeback lock, then its page tree, then the
+//! global dirty list or the file LRU. [`PageIo`] calls are made with the page tree locked
+//! during reads, so a filesystem must not call back into the mapping from
+//! them.
+
+extern crate alloc;
+
+use alloc::{sync::{Arc, Weak}, vec::Vec};
+use core::fmt;
+use core::ops::{Range, RangeInclusive};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use crate::subsystems::mm::kalloc;
+use crate::subsystems::mm::vm::{page_ref_count, page_ref_dec, page_ref_inc, PAGE_SIZE};
+use crate::subsystems::sync::Mutex;
+
+use super::error::{VfsError, VfsResult};
+
+pub mod writeback;
+pub mod xarray;
+
+#[cfg(feature = "kernel_tests")]
+pub mod tests;
+
+pub use writeback::{sync_all, WritebackTunables};
+pub use xarray::{XArray, XaMark};
+
+/// Page is dirty
+pub const MARK_DIRTY: XaMark = XaMark(0);
+/// Page is mapped writable into some address space
+pub const MARK_MAPPED: XaMark = XaMark(1);
+
+const PAGE: u64 = PAGE_SIZE as u64;
+
+/// Readahead window bounds, in pages
+const RA_MIN_PAGES: u64 = 4;
+const RA_MAX_PAGES: u64 = 32;
+
+/// Number of pages needed to hold `size` bytes
+fn pages_for(size: u64) -> u64 {
+    size.div_ceil(PAGE)
+}
+
+/// One page of file data
+///
+/// The cache holds one reference on the frame through the page's
+/// refcount in `mm::vm`; each page table entry mapping it holds another.
+pub struct CachedPage {
+    frame: usize,
+    index: u64,
+    flags: AtomicU32,
+    /// Writable shared mappings of the page
+    mapcount: AtomicU32,
+}
+
+impl CachedPage {
+    /// Contents match the file
+    pub const UPTODATE: u32   = 1 << 0;
+    /// Modified since it was last written back
+    pub const DIRTY: u32      = 1 << 1;
+    /// Being written back
+    pub const WRITEBACK: u32  = 1 << 2;
+    /// The last writeback of the page failed
+    pub const ERROR: u32      = 1 << 3;
+    /// Accessed since it was read in
+    pub const REFERENCED: u32 = 1 << 4;
+
+    fn alloc(index: u64) -> VfsResult<Self> {
+        let frame = kalloc();
+        if frame.is_null() {
+            return Err(VfsError::NoSpace);
+        }
+        unsafe { core::ptr::write_bytes(frame, 0, PAGE_SIZE) };
+        page_ref_inc(frame as usize);
+        Ok(Self {
+            frame: frame as usize,
+            index,
+            flags: AtomicU32::new(0),
+            mapcount: AtomicU32::new(0),
+        })
+    }
+
+    /// Physical address of the page, which is also its kernel address
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Page offset in the file
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn test(&self, flag: u32) -> bool {
+        self.flags.load(Ordering::Acquire) & flag != 0
+    }
+
+    pub fn set(&self, flags: u32) {
+        self.flags.fetch_or(flags, Ordering::AcqRel);
+    }
+
+    pub fn clear(&self, flags: u32) {
+        self.flags.fetch_and(!flags, Ordering::AcqRel);
+    }
+
+    /// Set `flag`, returning whether it was already set
+    fn test_and_set(&self, flag: u32) -> bool {
+        self.flags.fetch_or(flag, Ordering::AcqRel) & flag != 0
+    }
+
+    /// Clear `flag`, returning whether it was set
+    fn test_and_clear(&self, flag: u32) -> bool {
+        self.flags.fetch_and(!flag, Ordering::AcqRel) & flag != 0
+    }
+
+    /// Copy page contents starting at `offset` into `buf`
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) {
+        assert!(offset + buf.len() <= PAGE_SIZE);
+        unsafe {
+            core::ptr::copy_nonoverlapping((self.frame + offset) as *const u8, buf.as_mut_ptr(), buf.len());
+        }
+    }
+
+    /// Copy `buf` into the page starting at `offset`
+    pub fn write_at(&self, offset: usize, buf: &[u8]) {
+        assert!(offset + buf.len() <= PAGE_SIZE);
+        unsafe {
+            core::ptr::copy_nonoverlapping(buf.as_ptr(), (self.frame + offset) as *mut u8, buf.len());
+        }
+    }
+
+    fn zero_from(&self, offset: usize) {
+        unsafe { core::ptr::write_bytes((self.frame + offset) as *mut u8, 0, PAGE_SIZE - offset) };
+    }
+}
+
+impl Drop for CachedPage {
+    fn drop(&mut self) {
+        page_ref_dec(self.frame);
+    }
+}
+
+/// What [`AddressSpace::try_evict`] did with a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evict {
+    /// Dropped from the cache
+    Evicted,
+    /// Accessed since the last look; no longer marked as such
+    Referenced,
+    /// Mapped by some process
+    Mapped,
+    /// Dirty or under writeback
+    Dirty,
+    /// Not cached, or the cache is the file's only copy
+    Gone,
+}
+
+/// Backing store of an address space, implemented by the owning inode
+pub trait PageIo: Send + Sync {
+    /// Fill `page` from the store
+    ///
+    /// The page arrives zeroed; whatever lies past the end of the file must
+    /// stay zero.
+    fn readpage(&self, page: &CachedPage) -> VfsResult<()>;
+
+    /// Store the first `len` bytes of `page`; the rest is past the end of
+    /// the file
+    fn writepage(&self, page: &CachedPage, len: usize) -> VfsResult<()>;
+}
+
+/// Sequential read detection for one file
+#[derive(Default)]
+struct Readahead {
+    /// Page a sequential reader asks for next
+    next: u64,
+    /// Pages read ahead per step, 0 for a random reader
+    window: u64,
+    /// First page not read ahead yet
+    end: u64,
+}
+
+impl Readahead {
+    /// Note a read of pages `first..=last`; returns the pages to read ahead
+    fn advance(&mut self, first: u64, last: u64) -> Range<u64> {
+        if first == self.next {
+            self.window = (self.window * 2).clamp(RA_MIN_PAGES, RA_MAX_PAGES);
+        } else if first + 1 != self.next {
+            // Random access: stop reading ahead until the reader settles
+            self.window = 0;
+            self.end = 0;
+        }
+        self.next = last + 1;
+        if self.window == 0 {
+            return 0..0;
+        }
+        let start = self.end.max(last + 1);
+        self.end = self.end.max(last + 1 + self.window);
+        start..self.end
+    }
+}
+
+struct Inner {
+    pages: XArray<Arc<CachedPage>>,
+    ra: Readahead,
+}
+
+/// Page cache of one file
+pub struct AddressSpace {
+    this: Weak<AddressSpace>,
+    backing: Option<Weak<dyn PageIo>>,
+    inner: Mutex<Inner>,
+    /// Serialises writeback so `fsync` waits for a flusher already at work
+    wb_lock: Mutex<()>,
+    /// File size in bytes
+    size: AtomicU64,
+    nr_dirty: AtomicUsize,
+    /// Uptime in milliseconds when the mapping last went from clean to dirty
+    dirtied_at: AtomicU64,
+    /// On the writeback list
+    queued: AtomicBool,
+    /// First background writeback error, reported by the next `fsync`
+    wb_err: Mutex<Option<VfsError>>,
+}
+
+impl AddressSpace {
+    /// Mapping whose pages are read from and written back to `backing`
+    pub fn new(backing: Weak<dyn PageIo>) -> Arc<Self> {
+        Self::build(Some(backing))
+    }
+
+    /// Mapping with no backing store
+    pub fn new_memory() -> Arc<Self> {
+        Self::build(None)
+    }
+
+    fn build(backing: Option<Weak<dyn PageIo>>) -> Arc<Self> {
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            backing,
+            inner: Mutex::new(Inner { pages: XArray::new(), ra: Readahead::default() }),
+            wb_lock: Mutex::new(()),
+            size: AtomicU64::new(0),
+            nr_dirty: AtomicUsize::new(0),
+            dirtied_at: AtomicU64::new(0),
+            queued: AtomicBool::new(false),
+            wb_err: Mutex::new(None),
+        })
+    }
+
+    /// File size in bytes
+    pub fn size(&self) -> u64 {
+        self.size.load(Ordering::Acquire)
+    }
+
+    /// Number of cached pages
+    pub fn nr_pages(&self) -> usize {
+        self.inner.lock().pages.len()
+    }
+
+    /// Number of dirty pages
+    pub fn nr_dirty(&self) -> usize {
+        self.nr_dirty.load(Ordering::Acquire)
+    }
+
+    /// Cached page at `index`, without reading it in
+    pub fn find_page(&self, index: u64) -> Option<Arc<CachedPage>> {
+        self.inner.lock().pages.get(index).cloned()
+    }
+
+    fn backing(&self) -> Option<Arc<dyn PageIo>> {
+        self.backing.as_ref()?.upgrade()
+    }
+
+    /// Page at `index`, read in from the store on a miss unless `fill` is
+    /// false because the caller overwrites all of it
+    fn get_page(&self, inner: &mut Inner, index: u64, fill: bool) -> VfsResult<Arc<CachedPage>> {
+        if let Some(page) = inner.pages.get(index) {
+            page.set(CachedPage::REFERENCED);
+            return Ok(page.clone());
+        }
+        let page = Arc::new(CachedPage::alloc(index)?);
+        if fill
+            && index < pages_for(self.size())
+            && let Some(backing) = self.backing()
+        {
+            backing.readpage(&page)?;
+        }
+        page.set(CachedPage::UPTODATE);
+        inner.pages.insert(index, page.clone());
+        if self.backing.is_some() {
+            crate::subsystems::mm::swap::lru::add_file(&self.this, index);
+        }
+        Ok(page)
+    }
+
+    /// Page at `index` for a read; `None` is a hole of a memory mapping,
+    /// which reads as zeroes without allocating a page
+    fn read_page(&self, inner: &mut Inner, index: u64) -> VfsResult<Option<Arc<CachedPage>>> {
+        if self.backing.is_none() {
+            return Ok(inner.pages.get(index).cloned());
+        }
+        self.get_page(inner, index, true).map(Some)
+    }
+
+    fn readahead(&self, inner: &mut Inner, range: Range<u64>) {
+        let end = range.end.min(pages_for(self.size()));
+        for index in range.start..end {
+            // Best effort: the read itself reports errors for pages it needs
+            if inner.pages.get(index).is_none() && self.get_page(inner, index, true).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Read pages `range` in ahead of use (MADV_WILLNEED)
+    pub fn willneed(&self, range: Range<u64>) {
+        self.readahead(&mut self.inner.lock(), range);
+    }
+
+    /// Read file data at `offset`
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let size = self.size();
+        if offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = (size - offset).min(buf.len() as u64) as usize;
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        if self.backing.is_some() {
+            let ahead = inner.ra.advance(offset / PAGE, (offset + len as u64 - 1) / PAGE);
+            self.readahead(inner, ahead);
+        }
+
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let in_page = (pos % PAGE) as usize;
+            let n = (PAGE_SIZE - in_page).min(len - done);
+            match self.read_page(inner, pos / PAGE) {
+                Ok(Some(page)) => page.read_at(in_page, &mut buf[done..done + n]),
+                Ok(None) => buf[done..done + n].fill(0),
+                Err(e) if done == 0 => return Err(e),
+                Err(_) => break,
+            }
+            done += n;
+        }
+        Ok(done)
+    }
+
+    /// Write file data at `offset`, extending the file as needed
+    pub fn write(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        offset.checked_add(buf.len() as u64).ok_or(VfsError::InvalidOperation)?;
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let in_page = (pos % PAGE) as usize;
+            let n = (PAGE_SIZE - in_page).min(buf.len() - done);
+            // A page the write covers completely need not be read first
+            let page = match self.get_page(inner, pos / PAGE, n != PAGE_SIZE) {
+                Ok(page) => page,
+                Err(e) if done == 0 => return Err(e),
+                Err(_) => break,
+            };
+            page.write_at(in_page, &buf[done..done + n]);
+            self.mark_dirty(inner, &page);
+            done += n;
+            self.size.fetch_max(pos + n as u64, Ordering::AcqRel);
+        }
+        Ok(done)
+    }
+
+    /// Resize the file to `size`, dropping the pages past the new end
+    ///
+    /// Pages still mapped keep their frames until unmapped, but are no
+    /// longer part of the file.
+    pub fn truncate(&self, size: u64) {
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        while let Some((index, page)) = inner.pages.next(pages_for(size)).map(|(i, p)| (i, p.clone())) {
+            self.clear_dirty(inner, &page);
+            inner.pages.remove(index);
+        }
+        // Zero the tail of the last page so that growing the file again
+        // reads zeroes there
+        let tail = (size % PAGE) as usize;
+        if tail != 0
+            && let Some(page) = inner.pages.get(size / PAGE)
+        {
+            page.zero_from(tail);
+        }
+        self.size.store(size, Ordering::Release);
+    }
+
+    fn mark_dirty(&self, inner: &mut Inner, page: &CachedPage) {
+        if self.backing.is_none() || page.test_and_set(CachedPage::DIRTY) {
+            return;
+        }
+        inner.pages.set_mark(page.index, MARK_DIRTY);
+        writeback::NR_DIRTY.fetch_add(1, Ordering::AcqRel);
+        if self.nr_dirty.fetch_add(1, Ordering::AcqRel) == 0 {
+            self.dirtied_at.store(crate::time::uptime_ms(), Ordering::Release);
+        }
+        if !self.queued.swap(true, Ordering::AcqRel)
+            && let Some(this) = self.this.upgrade()
+        {
+            writeback::queue(&this);
+        }
+    }
+
+    fn clear_dirty(&self, inner: &mut Inner, page: &CachedPage) -> bool {
+        if !page.test_and_clear(CachedPage::DIRTY) {
+            return false;
+        }
+        inner.pages.clear_mark(page.index, MARK_DIRTY);
+        writeback::NR_DIRTY.fetch_sub(1, Ordering::AcqRel);
+        self.nr_dirty.fetch_sub(1, Ordering::AcqRel);
+        true
+    }
+
+    /// Dirty `page` again after a failed writeback, unless it was
+    /// truncated away meanwhile
+    fn redirty(&self, page: &Arc<CachedPage>) {
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        if inner.pages.get(page.index).is_some_and(|p| Arc::ptr_eq(p, page)) {
+            self.mark_dirty(inner, page);
+        }
+    }
+
+    /// Write back up to `limit` dirty pages in `range`
+    ///
+    /// Returns the number of pages written, or the first error. Pages that
+    /// fail keep `ERROR` set and stay dirty.
+    pub fn writeback(&self, range: RangeInclusive<u64>, limit: usize) -> VfsResult<usize> {
+        let _wb = self.wb_lock.lock();
+        self.writeback_locked(range, limit)
+    }
+
+    fn writeback_locked(&self, range: RangeInclusive<u64>, limit: usize) -> VfsResult<usize> {
+        let Some(backing) = self.backing() else { return Ok(0) };
+        let batch = {
+            let mut guard = self.inner.lock();
+            let inner = &mut *guard;
+            let mut batch = Vec::new();
+            let mut next = *range.start();
+            while batch.len() < limit
+                && let Some((index, page)) = inner.pages.next_marked(next, MARK_DIRTY)
+                && index <= *range.end()
+            {
+                let page = page.clone();
+                self.clear_dirty(inner, &page);
+                page.set(CachedPage::WRITEBACK);
+                batch.push(page);
+                match index.checked_add(1) {
+                    Some(i) => next = i,
+                    None => break,
+                }
+            }
+            batch
+        };
+
+        let size = self.size();
+        let mut written = 0;
+        let mut result = Ok(());
+        for page in batch {
+            let start = page.index * PAGE;
+            // Pages wholly past the end of the file have nothing to store
+            if start < size {
+                let len = (size - start).min(PAGE) as usize;
+                match backing.writepage(&page, len) {
+                    Ok(()) => {
+                        page.clear(CachedPage::ERROR);
+                        written += 1;
+                    }
+                    Err(e) => {
+                        page.set(CachedPage::ERROR);
+                        self.redirty(&page);
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
+            page.clear(CachedPage::WRITEBACK);
+        }
+        result.map(|()| written)
+    }
+
+    /// Background writeback of up to `limit` pages; skips the mapping if
+    /// someone else is already writing it back
+    fn writeback_background(&self, limit: usize) -> usize {
+        let Some(_wb) = self.wb_lock.try_lock() else { return 0 };
+        match self.writeback_locked(0..=u64::MAX, limit) {
+            Ok(written) => written,
+            Err(e) => {
+                self.wb_err.lock().get_or_insert(e);
+                0
+            }
+        }
+    }
+
+    /// Write back every dirty page (fsync)
+    ///
+    /// Also reports a background writeback error that happened since the
+    /// last call, which would otherwise be lost.
+    pub fn fsync(&self) -> VfsResult<()> {
+        let result = self.writeback(0..=u64::MAX, usize::MAX);
+        let earlier = self.wb_err.lock().take();
+        result?;
+        earlier.map_or(Ok(()), Err)
+    }
+
+    /// `msync` of pages `first..=last`
+    ///
+    /// Stores through a mapping are not tracked, so pages mapped writable
+    /// are dirtied again first. With `wait` they are written back before
+    /// returning (`MS_SYNC`); otherwise the flushers pick them up.
+    pub fn sync_range(&self, first: u64, last: u64, wait: bool) -> VfsResult<()> {
+        {
+            let mut guard = self.inner.lock();
+            let inner = &mut *guard;
+            let mut next = first;
+            while let Some((index, page)) = inner.pages.next_marked(next, MARK_MAPPED)
+                && index <= last
+            {
+                let page = page.clone();
+                self.mark_dirty(inner, &page);
+                match index.checked_add(1) {
+                    Some(i) => next = i,
+                    None => break,
+                }
+            }
+        }
+        if wait {
+            self.writeback(first..=last, usize::MAX)?;
+        }
+        Ok(())
+    }
+
+    /// Drop the clean, unmapped pages of `first..=last` so they are read
+    /// from the store again (`MS_INVALIDATE`)
+    pub fn invalidate_range(&self, first: u64, last: u64) {
+        if self.backing.is_none() {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        let mut next = first;
+        while let Some((index, page)) = inner.pages.next(next).map(|(i, p)| (i, p.clone()))
+            && index <= last
+        {
+            let busy = CachedPage::DIRTY | CachedPage::WRITEBACK;
+            if !page.test(busy) && page.mapcount.load(Ordering::Acquire) == 0 {
+                inner.pages.remove(index);
+            }
+            match index.checked_add(1) {
+                Some(i) => next = i,
+                None => break,
+            }
+        }
+    }
+
+    /// Drop page `index` for reclaim if it can be read in again as is
+    ///
+    /// A page accessed since the last call only loses its referenced bit.
+    pub fn try_evict(&self, index: u64) -> Evict {
+        if self.backing.is_none() {
+            return Evict::Gone;
+        }
+        let mut inner = self.inner.lock();
+        let Some(page) = inner.pages.get(index).cloned() else { return Evict::Gone };
+        if page.test_and_clear(CachedPage::REFERENCED) {
+            return Evict::Referenced;
+        }
+        if page.test(CachedPage::DIRTY | CachedPage::WRITEBACK) {
+            return Evict::Dirty;
+        }
+        // The cache holds one reference, each page table entry another
+        if page.mapcount.load(Ordering::Acquire) != 0 || page_ref_count(page.frame) > 1 {
+            return Evict::Mapped;
+        }
+        inner.pages.remove(index);
+        Evict::Evicted
+    }
+
+    /// Page for a fault at `index` of a shared file mapping
+    ///
+    /// Returns the page's frame with a reference taken for the page table
+    /// entry. A write fault also counts the page as mapped writable and
+    /// dirties it; [`AddressSpace::unmap_page`] undoes that.
+    pub fn fault(&self, index: u64, write: bool) -> VfsResult<usize> {
+        // Past the end of the file (SIGBUS)
+        if index >= pages_for(self.size()) {
+            return Err(VfsError::InvalidOperation);
+        }
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        let page = self.get_page(inner, index, true)?;
+        if write {
+            if page.mapcount.fetch_add(1, Ordering::AcqRel) == 0 {
+                inner.pages.set_mark(index, MARK_MAPPED);
+            }
+            self.mark_dirty(inner, &page);
+        }
+        page_ref_inc(page.frame);
+        Ok(page.frame)
+    }
+
+    /// Drop a writable mapping of page `index` taken by
+    /// [`AddressSpace::fault`]
+    ///
+    /// The page may have been written through the mapping, so it is
+    /// dirtied. The caller drops the page table entry's frame reference.
+    pub fn unmap_page(&self, index: u64) {
+        let mut guard = self.inner.lock();
+        let inner = &mut *guard;
+        let Some(page) = inner.pages.get(index).cloned() else { return };
+        let unmapped = page.mapcount.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+        if unmapped == Ok(1) {
+            inner.pages.clear_mark(index, MARK_MAPPED);
+        }
+        self.mark_dirty(inner, &page);
+    }
+
+    /// Whether the oldest dirty page has waited `expire_ms`
+    fn expired(&self, now: u64, expire_ms: u64) -> bool {
+        now.saturating_sub(self.dirtied_at.load(Ordering::Acquire)) >= expire_ms
+    }
+
+    /// Whether a clean mapping may leave the writeback list
+    ///
+    /// Called with the list locked. A writer that dirties the mapping
+    /// meanwhile sees `queued` clear and adds it back itself.
+    fn try_dequeue(&self) -> bool {
+        if self.nr_dirty() > 0 {
+            return false;
+        }
+        self.queued.store(false, Ordering::Release);
+        if self.nr_dirty() > 0 {
+            // Keep this entry unless a writer already took over requeueing
+            return self.queued.swap(true, Ordering::AcqRel);
+        }
+        true
+    }
+}
+
+impl fmt::Debug for AddressSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AddressSpace")
+            .field("size", &self.size())
+            .field("nr_dirty", &self.nr_dirty())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,217 @@
+//! Page cache tests
+//!
+//! Tests for read/write/fault coherence, writeback, truncation and
+//! eviction
+
+#[cfg(feature = "kernel_tests")]
+pub mod page_cache_tests {
+    extern crate alloc;
+
+    use alloc::{sync::{Arc, Weak}, vec, vec::Vec};
+
+    use crate::subsystems::mm::vm::page_ref_dec;
+    use crate::subsystems::sync::Mutex;
+    use crate::tests::{TestResult, test_assert_eq, test_assert};
+    use crate::vfs::error::{VfsError, VfsResult};
+    use crate::vfs::page_cache::{AddressSpace, CachedPage, Evict, PageIo, XArray, XaMark, writeback};
+
+    const PAGE: usize = 4096;
+
+    /// Backing store that records which pages were read
+    struct Store {
+        data: Mutex<Vec<u8>>,
+        reads: Mutex<Vec<u64>>,
+        fail: Mutex<bool>,
+    }
+
+    impl PageIo for Store {
+        fn readpage(&self, page: &CachedPage) -> VfsResult<()> {
+            self.reads.lock().push(page.index());
+            let data = self.data.lock();
+            let start = page.index() as usize * PAGE;
+            if start < data.len() {
+                page.write_at(0, &data[start..(start + PAGE).min(data.len())]);
+            }
+            Ok(())
+        }
+
+        fn writepage(&self, page: &CachedPage, len: usize) -> VfsResult<()> {
+            if *self.fail.lock() {
+                return Err(VfsError::IoError);
+            }
+            let mut data = self.data.lock();
+            let start = page.index() as usize * PAGE;
+            if data.len() < start + len {
+                data.resize(start + len, 0);
+            }
+            page.read_at(0, &mut data[start..start + len]);
+            Ok(())
+        }
+    }
+
+    fn mapping(data: Vec<u8>) -> (Arc<Store>, Arc<AddressSpace>) {
+        let size = data.len() as u64;
+        let store = Arc::new(Store {
+            data: Mutex::new(data),
+            reads: Mutex::new(Vec::new()),
+            fail: Mutex::new(false),
+        });
+        let backing: Weak<dyn PageIo> = Arc::downgrade(&store) as Weak<dyn PageIo>;
+        let mapping = AddressSpace::new(backing);
+        mapping.truncate(size);
+        (store, mapping)
+    }
+
+    /// Test lookups, marks and growth of the radix tree
+    pub fn test_xarray() -> TestResult {
+        const DIRTY: XaMark = XaMark(0);
+        let mut xa = XArray::new();
+        test_assert!(xa.insert(5, 50).is_none());
+        test_assert!(xa.insert(100_000, 7).is_none());
+        test_assert!(xa.insert(5, 51) == Some(50));
+        test_assert_eq!(xa.len(), 2);
+        test_assert!(xa.get(5) == Some(&51));
+        test_assert!(xa.next(6) == Some((100_000, &7)));
+
+        test_assert!(xa.set_mark(100_000, DIRTY));
+        test_assert!(!xa.set_mark(6, DIRTY));
+        test_assert!(xa.next_marked(0, DIRTY).map(|(i, _)| i) == Some(100_000));
+        test_assert!(xa.remove(100_000) == Some(7));
+        test_assert!(!xa.any_marked(DIRTY));
+        test_assert!(xa.remove(5) == Some(51));
+        test_assert!(xa.is_empty());
+        Ok(())
+    }
+
+    /// Test read, write and a shared mapping see the same pages
+    pub fn test_page_cache_coherence() -> TestResult {
+        let data: Vec<u8> = (0..40_000).map(|i| (i % 251) as u8).collect();
+        let (store, mapping) = mapping(data.clone());
+
+        let mut buf = vec![0u8; 5000];
+        test_assert!(mapping.read(0, &mut buf) == Ok(5000));
+        test_assert!(buf[..] == data[..5000]);
+        // The sequential read pulled in pages ahead of it
+        test_assert!(store.reads.lock().len() > 2);
+
+        test_assert!(mapping.write(4090, b"hello world") == Ok(11));
+        let frame = mapping.fault(0, false);
+        test_assert!(frame.is_ok());
+        let frame = frame.unwrap_or_default();
+        let page = unsafe { core::slice::from_raw_parts(frame as *const u8, PAGE) };
+        test_assert!(page[4090..] == *b"hello ");
+        page_ref_dec(frame);
+
+        let frame = mapping.fault(2, true);
+        test_assert!(frame.is_ok());
+        let frame = frame.unwrap_or_default();
+        unsafe { *(frame as *mut u8) = 0xaa };
+        let mut byte = [0u8];
+        test_assert!(mapping.read(2 * PAGE as u64, &mut byte) == Ok(1));
+        test_assert_eq!(byte[0], 0xaa);
+        mapping.unmap_page(2);
+        page_ref_dec(frame);
+        Ok(())
+    }
+
+    /// Test fsync and msync write dirty and mapped pages back
+    pub fn test_page_cache_fsync() -> TestResult {
+        let (store, mapping) = mapping(vec![0; 3 * PAGE]);
+        test_assert!(mapping.write(10, b"abc").is_ok());
+        test_assert_eq!(mapping.nr_dirty(), 1);
+        test_assert_eq!(store.data.lock()[10], 0);
+        test_assert!(mapping.fsync().is_ok());
+        test_assert_eq!(mapping.nr_dirty(), 0);
+        test_assert!(store.data.lock()[10..13] == *b"abc");
+
+        // A store through a writable mapping is not seen until msync
+        let frame = mapping.fault(1, true);
+        test_assert!(frame.is_ok());
+        let frame = frame.unwrap_or_default();
+        test_assert!(mapping.fsync().is_ok());
+        unsafe { *(frame as *mut u8) = 0x55 };
+        test_assert!(mapping.sync_range(0, 2, true).is_ok());
+        test_assert_eq!(store.data.lock()[PAGE], 0x55);
+        mapping.unmap_page(1);
+        page_ref_dec(frame);
+        Ok(())
+    }
+
+    /// Test background writeback waits for expiry and errors reach fsync
+    pub fn test_page_cache_writeback() -> TestResult {
+        let (store, mapping) = mapping(Vec::new());
+        let expire = writeback::tunables().dirty_expire_ms;
+        let now = crate::time::uptime_ms();
+        test_assert!(mapping.write(0, &[1u8; 2 * PAGE]).is_ok());
+        writeback::writeback_pass(now);
+        test_assert_eq!(mapping.nr_dirty(), 2);
+
+        *store.fail.lock() = true;
+        writeback::writeback_pass(now + expire + 1);
+        test_assert_eq!(mapping.nr_dirty(), 2);
+        *store.fail.lock() = false;
+        // The write itself succeeds now, but the earlier failure is reported
+        test_assert!(mapping.fsync() == Err(VfsError::IoError));
+        test_assert!(mapping.fsync() == Ok(()));
+        test_assert_eq!(store.data.lock().len(), 2 * PAGE);
+        Ok(())
+    }
+
+    /// Test truncation drops pages and zeroes the tail of the last one
+    pub fn test_page_cache_truncate() -> TestResult {
+        let (store, mapping) = mapping(vec![7; 3 * PAGE]);
+        let mut buf = vec![0u8; 3 * PAGE];
+        test_assert!(mapping.read(0, &mut buf) == Ok(3 * PAGE));
+        mapping.truncate(PAGE as u64 + 10);
+        store.data.lock().truncate(PAGE + 10);
+        test_assert_eq!(mapping.nr_pages(), 2);
+        mapping.truncate(3 * PAGE as u64);
+        test_assert!(mapping.read(PAGE as u64, &mut buf[..20]) == Ok(20));
+        test_assert!(buf[..10] == [7u8; 10]);
+        test_assert!(buf[10..20] == [0u8; 10]);
+
+        let memory = AddressSpace::new_memory();
+        test_assert!(memory.write(2 * PAGE as u64, b"x").is_ok());
+        test_assert_eq!(memory.nr_dirty(), 0);
+        test_assert!(memory.read(0, &mut buf[..4]) == Ok(4));
+        test_assert!(buf[..4] == [0u8; 4]);
+        test_assert_eq!(memory.nr_pages(), 1);
+        Ok(())
+    }
+
+    /// Test reclaim only evicts clean, unmapped pages not used since the
+    /// last look
+    pub fn test_page_cache_evict() -> TestResult {
+        let (store, mapping) = mapping(vec![3; 2 * PAGE]);
+        let mut buf = vec![0u8; 2 * PAGE];
+        test_assert!(mapping.read(0, &mut buf) == Ok(2 * PAGE));
+        test_assert!(mapping.try_evict(0) == Evict::Evicted);
+        // Read again: referenced once, evicted the time after
+        test_assert!(mapping.read(0, &mut buf[..1]) == Ok(1));
+        test_assert!(mapping.read(0, &mut buf[..1]) == Ok(1));
+        test_assert!(mapping.try_evict(0) == Evict::Referenced);
+        test_assert!(mapping.try_evict(0) == Evict::Evicted);
+        test_assert!(mapping.try_evict(0) == Evict::Gone);
+
+        let frame = mapping.fault(1, false);
+        test_assert!(frame.is_ok());
+        test_assert!(mapping.try_evict(1) == Evict::Referenced);
+        test_assert!(mapping.try_evict(1) == Evict::Mapped);
+        page_ref_dec(frame.unwrap_or_default());
+        test_assert!(mapping.write(PAGE as u64, b"z").is_ok());
+        test_assert!(mapping.try_evict(1) == Evict::Referenced);
+        test_assert!(mapping.try_evict(1) == Evict::Dirty);
+        test_assert!(mapping.fsync().is_ok());
+        test_assert!(mapping.try_evict(1) == Evict::Evicted);
+        test_assert_eq!(mapping.nr_pages(), 0);
+
+        // Evicted pages are read in again with what was written
+        let reads = store.reads.lock().len();
+        test_assert!(mapping.read(PAGE as u64, &mut buf[..1]) == Ok(1));
+        test_assert!(buf[0] == b'z');
+        test_assert_eq!(store.reads.lock().len(), reads + 1);
+
+        test_assert!(AddressSpace::new_memory().try_evict(0) == Evict::Gone);
+        Ok(())
+    }
+}
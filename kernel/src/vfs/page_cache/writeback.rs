@@ -0,0 +1,139 @@
+//! Background writeback
+//!
+//! A mapping puts itself on a global list when its first page is dirtied.
+//! The flusher threads walk that list every `interval_ms`. They write a
+//! mapping back once its oldest dirty page has waited `dirty_expire_ms`,
+//! or straight away while more than `background_pages` pages are dirty
+//! system-wide. A mapping that someone else is already writing back is
+//! skipped until the next pass.
+
+extern crate alloc;
+
+use alloc::{sync::{Arc, Weak}, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::subsystems::sync::Mutex;
+use crate::vfs::error::VfsResult;
+
+use super::AddressSpace;
+
+/// Writeback tuning knobs
+#[derive(Debug, Clone, Copy)]
+pub struct WritebackTunables {
+    /// How often the flushers look for work, in milliseconds
+    pub interval_ms: u64,
+    /// Age at which dirty data is written back, in milliseconds
+    pub dirty_expire_ms: u64,
+    /// Dirty pages system-wide above which age no longer matters
+    pub background_pages: usize,
+    /// Pages written from one mapping before moving on to the next
+    pub batch_pages: usize,
+}
+
+impl WritebackTunables {
+    const fn new() -> Self {
+        Self {
+            interval_ms: 500,
+            dirty_expire_ms: 3000,
+            background_pages: 1024,
+            batch_pages: 256,
+        }
+    }
+}
+
+impl Default for WritebackTunables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Number of flusher threads
+const FLUSHER_THREADS: usize = 2;
+
+static TUNABLES: Mutex<WritebackTunables> = Mutex::new(WritebackTunables::new());
+
+/// Mappings that had dirty pages when they were last looked at
+static DIRTY_MAPPINGS: Mutex<Vec<Weak<AddressSpace>>> = Mutex::new(Vec::new());
+
+/// Dirty pages across all mappings
+pub(super) static NR_DIRTY: AtomicUsize = AtomicUsize::new(0);
+
+pub fn tunables() -> WritebackTunables {
+    *TUNABLES.lock()
+}
+
+pub fn set_tunables(tunables: WritebackTunables) {
+    *TUNABLES.lock() = tunables;
+}
+
+/// Dirty pages across all mappings
+pub fn nr_dirty() -> usize {
+    NR_DIRTY.load(Ordering::Acquire)
+}
+
+pub(super) fn queue(mapping: &Arc<AddressSpace>) {
+    DIRTY_MAPPINGS.lock().push(Arc::downgrade(mapping));
+}
+
+/// One pass over the dirty mappings at uptime `now`
+///
+/// Returns the number of pages written back.
+pub fn writeback_pass(now: u64) -> usize {
+    let tunables = tunables();
+    let over_limit = nr_dirty() > tunables.background_pages;
+    let mut due = Vec::new();
+    DIRTY_MAPPINGS.lock().retain(|weak| {
+        let Some(mapping) = weak.upgrade() else { return false };
+        if mapping.nr_dirty() == 0 {
+            return !mapping.try_dequeue();
+        }
+        if over_limit || mapping.expired(now, tunables.dirty_expire_ms) {
+            due.push(mapping);
+        }
+        true
+    });
+
+    due.iter().map(|mapping| mapping.writeback_background(tunables.batch_pages)).sum()
+}
+
+/// Write back every dirty page in the system (sync)
+///
+/// Keeps going past errors and returns the first one.
+pub fn sync_all() -> VfsResult<()> {
+    let mappings: Vec<Arc<AddressSpace>> =
+        DIRTY_MAPPINGS.lock().iter().filter_map(Weak::upgrade).collect();
+    let mut result = Ok(());
+    for mapping in mappings {
+        if let Err(e) = mapping.fsync()
+            && result.is_ok()
+        {
+            result = Err(e);
+        }
+    }
+    result
+}
+
+/// Start the flusher threads
+pub fn start() {
+    for i in 0..FLUSHER_THREADS {
+        let _ = crate::process::thread::create_thread(
+            1,
+            crate::process::thread::ThreadType::Kernel,
+            Some(flusher_main),
+            i as *mut u8,
+        );
+    }
+    crate::println!("[writeback] Started {} flusher threads", FLUSHER_THREADS);
+}
+
+unsafe extern "C" fn flusher_main(_arg: *mut u8) -> *mut u8 {
+    let mut next_pass = 0;
+    loop {
+        let now = crate::time::uptime_ms();
+        if now >= next_pass {
+            writeback_pass(now);
+            next_pass = now + tunables().interval_ms;
+        }
+        crate::process::thread::thread_yield();
+    }
+}
@@ -0,0 +1,287 @@
+//! Sparse array indexed by page offset
+//!
+//! A 64-way radix tree. Nodes are only allocated for index ranges that hold
+//! entries, and the tree grows in height as larger indices are inserted, so
+//! a small file costs a single node however far apart its pages are.
+//!
+//! Each node also keeps one bitmap per mark with a bit set for every slot
+//! that has a marked entry somewhere below it. [`XArray::next_marked`]
+//! follows those bits, so finding the dirty pages of a file only visits the
+//! nodes that lead to one.
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+
+const SHIFT: u32 = 6;
+const FANOUT: usize = 1 << SHIFT;
+const MASK: u64 = FANOUT as u64 - 1;
+
+/// Number of marks each entry can carry
+pub const MARKS: usize = 2;
+
+/// Mark index, below [`MARKS`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XaMark(pub usize);
+
+// Both variants are 64 slots of a pointer-sized entry in practice
+#[allow(clippy::large_enum_variant)]
+enum Slots<T> {
+    Leaf([Option<T>; FANOUT]),
+    Inner([Option<Box<Node<T>>>; FANOUT]),
+}
+
+struct Node<T> {
+    /// Index bits below this node's slot number
+    shift: u32,
+    /// Occupied slots
+    count: usize,
+    marks: [u64; MARKS],
+    slots: Slots<T>,
+}
+
+/// Highest index a node at `shift` can reach
+fn node_max(shift: u32) -> u64 {
+    1u64.checked_shl(shift + SHIFT).unwrap_or(0).wrapping_sub(1)
+}
+
+impl<T> Node<T> {
+    fn new(shift: u32) -> Box<Self> {
+        let slots = if shift == 0 {
+            Slots::Leaf(core::array::from_fn(|_| None))
+        } else {
+            Slots::Inner(core::array::from_fn(|_| None))
+        };
+        Box::new(Self { shift, count: 0, marks: [0; MARKS], slots })
+    }
+
+    fn offset(&self, index: u64) -> usize {
+        ((index >> self.shift) & MASK) as usize
+    }
+
+    fn get(&self, index: u64) -> Option<&T> {
+        let off = self.offset(index);
+        match &self.slots {
+            Slots::Leaf(slots) => slots[off].as_ref(),
+            Slots::Inner(slots) => slots[off].as_ref()?.get(index),
+        }
+    }
+
+    fn insert(&mut self, index: u64, value: T) -> Option<T> {
+        let off = self.offset(index);
+        let shift = self.shift;
+        match &mut self.slots {
+            Slots::Leaf(slots) => {
+                let old = slots[off].replace(value);
+                if old.is_none() {
+                    self.count += 1;
+                }
+                old
+            }
+            Slots::Inner(slots) => {
+                if slots[off].is_none() {
+                    slots[off] = Some(Node::new(shift - SHIFT));
+                    self.count += 1;
+                }
+                slots[off].as_mut()?.insert(index, value)
+            }
+        }
+    }
+
+    fn remove(&mut self, index: u64) -> Option<T> {
+        let off = self.offset(index);
+        let bit = 1u64 << off;
+        let removed = match &mut self.slots {
+            Slots::Leaf(slots) => {
+                let old = slots[off].take()?;
+                self.marks.iter_mut().for_each(|m| *m &= !bit);
+                self.count -= 1;
+                return Some(old);
+            }
+            Slots::Inner(slots) => {
+                let child = slots[off].as_mut()?;
+                let removed = child.remove(index)?;
+                for (mark, child_mark) in self.marks.iter_mut().zip(child.marks) {
+                    if child_mark == 0 {
+                        *mark &= !bit;
+                    }
+                }
+                if child.count == 0 {
+                    slots[off] = None;
+                    self.count -= 1;
+                }
+                removed
+            }
+        };
+        Some(removed)
+    }
+
+    fn set_mark(&mut self, index: u64, mark: usize) -> bool {
+        let off = self.offset(index);
+        let present = match &mut self.slots {
+            Slots::Leaf(slots) => slots[off].is_some(),
+            Slots::Inner(slots) => slots[off].as_mut().is_some_and(|c| c.set_mark(index, mark)),
+        };
+        if present {
+            self.marks[mark] |= 1 << off;
+        }
+        present
+    }
+
+    fn clear_mark(&mut self, index: u64, mark: usize) {
+        let off = self.offset(index);
+        let clear = match &mut self.slots {
+            Slots::Leaf(_) => true,
+            Slots::Inner(slots) => match slots[off].as_mut() {
+                Some(child) => {
+                    child.clear_mark(index, mark);
+                    child.marks[mark] == 0
+                }
+                None => true,
+            },
+        };
+        if clear {
+            self.marks[mark] &= !(1 << off);
+        }
+    }
+
+    /// First entry at or after `start` within this node, carrying `mark`
+    /// if one is given
+    fn next(&self, start: u64, mark: Option<usize>) -> Option<(u64, &T)> {
+        let first = self.offset(start);
+        let base = start & !node_max(self.shift);
+        for off in first..FANOUT {
+            if let Some(mark) = mark
+                && self.marks[mark] & (1 << off) == 0
+            {
+                continue;
+            }
+            let slot_base = base | ((off as u64) << self.shift);
+            let found = match &self.slots {
+                Slots::Leaf(slots) => slots[off].as_ref().map(|v| (slot_base, v)),
+                Slots::Inner(slots) => slots[off]
+                    .as_ref()
+                    .and_then(|c| c.next(if off == first { start } else { slot_base }, mark)),
+            };
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+/// Sparse array of `T` keyed by `u64`
+pub struct XArray<T> {
+    root: Option<Box<Node<T>>>,
+    len: usize,
+}
+
+impl<T> Default for XArray<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> XArray<T> {
+    pub const fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: u64) -> Option<&T> {
+        let root = self.root.as_ref()?;
+        if index > node_max(root.shift) {
+            return None;
+        }
+        root.get(index)
+    }
+
+    /// Store `value` at `index`, returning the entry it replaced
+    pub fn insert(&mut self, index: u64, value: T) -> Option<T> {
+        let root = self.root.get_or_insert_with(|| Node::new(0));
+        // Add levels on top until the root reaches `index`
+        while index > node_max(root.shift) {
+            let shift = root.shift + SHIFT;
+            let old = core::mem::replace(root, Node::new(shift));
+            let marks = old.marks.map(|m| (m != 0) as u64);
+            if old.count > 0 {
+                root.marks = marks;
+                root.count = 1;
+                if let Slots::Inner(slots) = &mut root.slots {
+                    slots[0] = Some(old);
+                }
+            }
+        }
+        let old = root.insert(index, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Take the entry at `index` out, along with its marks
+    pub fn remove(&mut self, index: u64) -> Option<T> {
+        let root = self.root.as_mut()?;
+        if index > node_max(root.shift) {
+            return None;
+        }
+        let old = root.remove(index)?;
+        self.len -= 1;
+        if root.count == 0 {
+            self.root = None;
+        }
+        Some(old)
+    }
+
+    /// Mark the entry at `index`; false if there is none
+    pub fn set_mark(&mut self, index: u64, mark: XaMark) -> bool {
+        match self.root.as_mut() {
+            Some(root) if index <= node_max(root.shift) => root.set_mark(index, mark.0),
+            _ => false,
+        }
+    }
+
+    pub fn clear_mark(&mut self, index: u64, mark: XaMark) {
+        if let Some(root) = self.root.as_mut()
+            && index <= node_max(root.shift)
+        {
+            root.clear_mark(index, mark.0);
+        }
+    }
+
+    pub fn get_mark(&self, index: u64, mark: XaMark) -> bool {
+        self.next_marked(index, mark).is_some_and(|(i, _)| i == index)
+    }
+
+    /// Whether any entry carries `mark`
+    pub fn any_marked(&self, mark: XaMark) -> bool {
+        self.root.as_ref().is_some_and(|r| r.marks[mark.0] != 0)
+    }
+
+    /// First entry at or after `start`
+    pub fn next(&self, start: u64) -> Option<(u64, &T)> {
+        let root = self.root.as_ref()?;
+        if start > node_max(root.shift) {
+            return None;
+        }
+        root.next(start, None)
+    }
+
+    /// First entry at or after `start` that carries `mark`
+    pub fn next_marked(&self, start: u64, mark: XaMark) -> Option<(u64, &T)> {
+        let root = self.root.as_ref()?;
+        if start > node_max(root.shift) {
+            return None;
+        }
+        root.next(start, Some(mark.0))
+    }
+}
@@ -241,7 +241,73 @@ impl InodeOps for ProcInfoInode {
                     }),
                 )))
             }
+            "oom_score" => {
+                let pid = self.pid;
+                Ok(Arc::new(super::fs::ProcFsInode::new_file(
+                    pid as u64 + 20004,
+                    Box::new(move || {
+                        crate::subsystems::mm::oom::oom_score(pid as crate::process::Pid)
+                            .map(|score| format!("{}\n", score))
+                            .unwrap_or_default()
+                    }),
+                )))
+            }
+            "oom_score_adj" => Ok(Arc::new(OomScoreAdjInode {
+                pid: self.pid as crate::process::Pid,
+                attr: FileAttr {
+                    ino: self.pid as u64 + 20005,
+                    mode: FileMode(FileMode::S_IFREG | 0o644),
+                    nlink: 1,
+                    ..Default::default()
+                },
+            })),
             _ => Err(VfsError::NotFound),
         }
     }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// /proc/[pid]/oom_score_adj, which can be written to move the process in
+/// or out of the OOM killer's way
+struct OomScoreAdjInode {
+    pid: crate::process::Pid,
+    attr: FileAttr,
+}
+
+impl InodeOps for OomScoreAdjInode {
+    fn getattr(&self) -> VfsResult<FileAttr> {
+        Ok(self.attr.clone())
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let adj = crate::subsystems::mm::oom::oom_score_adj(self.pid).ok_or(VfsError::NotFound)?;
+        let content = format!("{}\n", adj);
+        let start = (offset as usize).min(content.len());
+        let len = buf.len().min(content.len() - start);
+        buf[..len].copy_from_slice(&content.as_bytes()[start..start + len]);
+        Ok(len)
+    }
+
+    fn write(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        use crate::subsystems::mm::oom::{set_oom_score_adj, OomAdjError};
+
+        let adj = core::str::from_utf8(buf)
+            .ok()
+            .and_then(|text| text.trim().parse::<i32>().ok())
+            .ok_or(VfsError::InvalidOperation)?;
+        let caller = crate::process::myproc().ok_or(VfsError::PermissionDenied)?;
+        set_oom_score_adj(self.pid, adj, caller).map_err(|err| match err {
+            OomAdjError::Invalid => VfsError::InvalidOperation,
+            OomAdjError::PermissionDenied => VfsError::PermissionDenied,
+            OomAdjError::NotFound => VfsError::NotFound,
+        })?;
+        Ok(buf.len())
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
 }
@@ -2,6 +2,7 @@
 
 extern crate alloc;
 use alloc::{string::{String, ToString}, sync::Arc, vec::Vec, collections::BTreeMap};
+use core::any::Any;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::subsystems::sync::Mutex;
@@ -9,8 +10,10 @@ use crate::subsystems::sync::Mutex;
 use super::{
     error::*,
     types::*,
-    fs::{FileSystemType, SuperBlock, InodeOps, FsStats},
+    fs::{FileSystemType, SuperBlock, InodeOps, FsStats, RenameFlags, SetAttr, rename_entries},
     dir::DirEntry,
+    page_cache::AddressSpace,
+    xattr::{self, XattrFlags, XattrMap},
 };
 
 /// RamFS file system type
@@ -78,12 +81,14 @@ impl SuperBlock for RamFsSuperBlock {
 /// RamFS inode
 struct RamFsInode {
     attr: Mutex<FileAttr>,
-    // For regular files
-    data: Mutex<Vec<u8>>,
+    // For regular files; the page cache is the only copy of the data
+    mapping: Option<Arc<AddressSpace>>,
     // For directories
     children: Mutex<BTreeMap<String, Arc<dyn InodeOps>>>,
     // For symlinks
     target: Mutex<Option<String>>,
+    // Extended attributes
+    xattrs: Mutex<XattrMap>,
 }
 
 impl RamFsInode {
@@ -96,9 +101,10 @@ impl RamFsInode {
                 size: 0,
                 ..Default::default()
             }),
-            data: Mutex::new(Vec::new()),
+            mapping: Some(AddressSpace::new_memory()),
             children: Mutex::new(BTreeMap::new()),
             target: Mutex::new(None),
+            xattrs: Mutex::new(XattrMap::new()),
         }
     }
     
@@ -110,9 +116,10 @@ impl RamFsInode {
                 nlink: 2,
                 ..Default::default()
             }),
-            data: Mutex::new(Vec::new()),
+            mapping: None,
             children: Mutex::new(BTreeMap::new()),
             target: Mutex::new(None),
+            xattrs: Mutex::new(XattrMap::new()),
         }
     }
     
@@ -125,11 +132,22 @@ impl RamFsInode {
                 size: target.len() as u64,
                 ..Default::default()
             }),
-            data: Mutex::new(Vec::new()),
+            mapping: None,
             children: Mutex::new(BTreeMap::new()),
             target: Mutex::new(Some(target.to_string())),
+            xattrs: Mutex::new(XattrMap::new()),
         }
     }
+
+    /// Downcast an inode that must belong to ramfs
+    fn of(inode: &dyn InodeOps) -> VfsResult<&Self> {
+        inode.as_any().downcast_ref::<Self>().ok_or(VfsError::CrossDevice)
+    }
+
+    fn adjust_nlink(&self, delta: i32) {
+        let mut attr = self.attr.lock();
+        attr.nlink = attr.nlink.saturating_add_signed(delta);
+    }
 }
 
 impl InodeOps for RamFsInode {
@@ -137,16 +155,18 @@ impl InodeOps for RamFsInode {
         Ok(self.attr.lock().clone())
     }
 
-    fn setattr(&self, attr: &FileAttr) -> VfsResult<()> {
-        let mut my_attr = self.attr.lock();
-        my_attr.mode = attr.mode;
-        my_attr.uid = attr.uid;
-        my_attr.gid = attr.gid;
-        my_attr.size = attr.size;
-        my_attr.atime = attr.atime;
-        my_attr.mtime = attr.mtime;
-        my_attr.ctime = attr.ctime;
-        my_attr.nlink = attr.nlink;
+    fn setattr(&self, attr: &SetAttr) -> VfsResult<()> {
+        if attr.has(SetAttr::SIZE) {
+            let mode = self.attr.lock().mode;
+            if mode.is_dir() {
+                return Err(VfsError::IsDirectory);
+            }
+            if !mode.is_regular() {
+                return Err(VfsError::InvalidOperation);
+            }
+            self.truncate(attr.size)?;
+        }
+        attr.apply(&mut self.attr.lock());
         Ok(())
     }
     
@@ -195,6 +215,8 @@ impl InodeOps for RamFsInode {
         }
         
         children.insert(name.to_string(), inode.clone());
+        // The new directory's ".." refers to us
+        self.adjust_nlink(1);
         Ok(inode)
     }
     
@@ -206,13 +228,7 @@ impl InodeOps for RamFsInode {
             return Err(VfsError::IsDirectory);
         }
         
-        // Decrement nlink
-        let mut attr = inode.getattr()?;
-        if attr.nlink > 0 {
-            attr.nlink -= 1;
-            inode.setattr(&attr)?;
-        }
-
+        Self::of(inode.as_ref())?.adjust_nlink(-1);
         children.remove(name);
         Ok(())
     }
@@ -229,7 +245,9 @@ impl InodeOps for RamFsInode {
             return Err(VfsError::NotEmpty);
         }
         
+        Self::of(inode.as_ref())?.attr.lock().nlink = 0;
         children.remove(name);
+        self.adjust_nlink(-1);
         Ok(())
     }
 
@@ -239,19 +257,46 @@ impl InodeOps for RamFsInode {
     }
 
     fn link(&self, name: &str, inode: Arc<dyn InodeOps>) -> VfsResult<()> {
+        let target = Self::of(inode.as_ref())?;
+        if target.attr.lock().mode.is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        
         let mut children = self.children.lock();
         if children.contains_key(name) {
             return Err(VfsError::Exists);
         }
         
-        // Increment nlink
-        let mut attr = inode.getattr()?;
-        attr.nlink += 1;
-        inode.setattr(&attr)?;
-        
+        target.adjust_nlink(1);
         children.insert(name.to_string(), inode);
         Ok(())
     }
+
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn InodeOps>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> VfsResult<()> {
+        let new_dir = Self::of(new_dir.as_ref())?;
+        if !new_dir.attr.lock().mode.is_dir() {
+            return Err(VfsError::NotDirectory);
+        }
+        
+        let outcome = rename_entries(&self.children, old_name, &new_dir.children, new_name, flags)?;
+        self.adjust_nlink(outcome.old_dir_links);
+        new_dir.adjust_nlink(outcome.new_dir_links);
+        if let Some(replaced) = outcome.replaced {
+            let replaced = Self::of(replaced.as_ref())?;
+            if replaced.attr.lock().mode.is_dir() {
+                replaced.attr.lock().nlink = 0;
+            } else {
+                replaced.adjust_nlink(-1);
+            }
+        }
+        Ok(())
+    }
     
     fn symlink(&self, name: &str, target: &str) -> VfsResult<Arc<dyn InodeOps>> {
         let mut children = self.children.lock();
@@ -295,36 +340,49 @@ impl InodeOps for RamFsInode {
     }
     
     fn read(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
-        let data = self.data.lock();
-        let start = offset as usize;
-        
-        if start >= data.len() {
-            return Ok(0);
-        }
-        
-        let end = (start + buf.len()).min(data.len());
-        let len = end - start;
-        buf[..len].copy_from_slice(&data[start..end]);
-        
-        Ok(len)
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        mapping.read(offset, buf)
     }
     
     fn write(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
-        let mut data = self.data.lock();
-        let start = offset as usize;
-        
-        // Extend if necessary
-        if start + buf.len() > data.len() {
-            data.resize(start + buf.len(), 0);
-        }
-        
-        data[start..start + buf.len()].copy_from_slice(buf);
-        
-        // Update size
-        let mut attr = self.attr.lock();
-        attr.size = data.len() as u64;
-        
-        Ok(buf.len())
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        let written = mapping.write(offset, buf)?;
+        self.attr.lock().size = mapping.size();
+        Ok(written)
+    }
+    
+    fn truncate(&self, size: u64) -> VfsResult<()> {
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        mapping.truncate(size);
+        self.attr.lock().size = size;
+        Ok(())
+    }
+
+    fn mapping(&self) -> Option<Arc<AddressSpace>> {
+        self.mapping.clone()
+    }
+
+    fn getxattr(&self, name: &str) -> VfsResult<Vec<u8>> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().get(name)
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> VfsResult<()> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().set(name, value, flags)
+    }
+
+    fn listxattr(&self) -> VfsResult<Vec<String>> {
+        Ok(self.xattrs.lock().list())
+    }
+
+    fn removexattr(&self, name: &str) -> VfsResult<()> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().remove(name)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 }
 
@@ -70,6 +70,9 @@ fn format_meminfo() -> String {
     let total_kb = total_pages * page_size / 1024;
     let free_kb = free_pages * page_size / 1024;
     let used_kb = used_pages * page_size / 1024;
+    let swap = crate::subsystems::mm::swap::stats();
+    let swap_total_kb = swap.total_pages * page_size / 1024;
+    let swap_free_kb = swap.free_pages * page_size / 1024;
     
     format!(
         "MemTotal:       {} kB\n\
@@ -77,8 +80,8 @@ fn format_meminfo() -> String {
          MemAvailable:   {} kB\n\
          Buffers:        0 kB\n\
          Cached:         0 kB\n\
-         SwapTotal:      0 kB\n\
-         SwapFree:       0 kB\n\
+         SwapTotal:      {} kB\n\
+         SwapFree:       {} kB\n\
          Dirty:          0 kB\n\
          Writeback:      0 kB\n\
          AnonPages:      {} kB\n\
@@ -97,7 +100,7 @@ fn format_meminfo() -> String {
          VmallocTotal:   0 kB\n\
          VmallocUsed:    0 kB\n\
          VmallocChunk:   0 kB\n",
-        total_kb, free_kb, free_kb, used_kb, total_kb, used_kb
+        total_kb, free_kb, free_kb, swap_total_kb, swap_free_kb, used_kb, total_kb, used_kb
     )
 }
 
@@ -5,7 +5,8 @@
 #[cfg(feature = "kernel_tests")]
 pub mod vfs_tests {
     use crate::tests::{TestResult, test_assert_eq, test_assert};
-    use crate::vfs::{FileMode, vfs};
+    use crate::vfs::{FileMode, FsContext, LookupFlags, VfsError, XattrFlags, vfs};
+    use crate::vfs::fs::RenameFlags;
 
     /// Test VFS create and write
     pub fn test_vfs_create_write() -> TestResult {
@@ -81,6 +82,219 @@ pub mod vfs_tests {
         Ok(())
     }
 
+    /// Test rename over an existing file and the renameat2 flags
+    pub fn test_vfs_rename() -> TestResult {
+        let (a, b) = ("/test_rename_a", "/test_rename_b");
+        let _ = vfs().create(a, FileMode::new(FileMode::S_IFREG | 0o644));
+        let _ = vfs().create(b, FileMode::new(FileMode::S_IFREG | 0o644));
+        let ino_a = vfs().stat(a).map_err(|e| alloc::format!("stat failed: {:?}", e))?.ino;
+        let ino_b = vfs().stat(b).map_err(|e| alloc::format!("stat failed: {:?}", e))?.ino;
+
+        test_assert!(vfs().rename(a, b, RenameFlags(RenameFlags::NOREPLACE)) == Err(VfsError::Exists));
+
+        if let Err(e) = vfs().rename(a, b, RenameFlags(RenameFlags::EXCHANGE)) {
+            return Err(alloc::format!("exchange failed: {:?}", e));
+        }
+        test_assert!(vfs().stat(a).map(|attr| attr.ino) == Ok(ino_b));
+        test_assert!(vfs().stat(b).map(|attr| attr.ino) == Ok(ino_a));
+
+        if let Err(e) = vfs().rename(a, b, RenameFlags::default()) {
+            return Err(alloc::format!("rename failed: {:?}", e));
+        }
+        test_assert!(vfs().stat(a).is_err());
+        test_assert!(vfs().stat(b).map(|attr| attr.ino) == Ok(ino_b));
+
+        // Cleanup
+        let _ = vfs().unlink(b);
+        Ok(())
+    }
+
+    /// Test hard link counts and cross-directory rename of a directory
+    pub fn test_vfs_link_and_move_dir() -> TestResult {
+        let (file, alias) = ("/test_link_src", "/test_link_alias");
+        let _ = vfs().create(file, FileMode::new(FileMode::S_IFREG | 0o644));
+        if let Err(e) = vfs().link(file, alias) {
+            return Err(alloc::format!("link failed: {:?}", e));
+        }
+        test_assert!(vfs().stat(file).map(|attr| attr.nlink) == Ok(2));
+        let _ = vfs().unlink(alias);
+        test_assert!(vfs().stat(file).map(|attr| attr.nlink) == Ok(1));
+
+        let _ = vfs().mkdir("/test_mv_from", FileMode::new(FileMode::S_IFDIR | 0o755));
+        let _ = vfs().mkdir("/test_mv_to", FileMode::new(FileMode::S_IFDIR | 0o755));
+        let _ = vfs().mkdir("/test_mv_from/sub", FileMode::new(FileMode::S_IFDIR | 0o755));
+        test_assert!(vfs().rename("/test_mv_from", "/test_mv_from/sub/x", RenameFlags::default()) == Err(VfsError::InvalidOperation));
+        if let Err(e) = vfs().rename("/test_mv_from/sub", "/test_mv_to/sub", RenameFlags::default()) {
+            return Err(alloc::format!("move dir failed: {:?}", e));
+        }
+        test_assert!(vfs().stat("/test_mv_from").map(|attr| attr.nlink) == Ok(2));
+        test_assert!(vfs().stat("/test_mv_to").map(|attr| attr.nlink) == Ok(3));
+
+        // Cleanup
+        let _ = vfs().unlink(file);
+        let _ = vfs().rmdir("/test_mv_to/sub");
+        let _ = vfs().rmdir("/test_mv_to");
+        let _ = vfs().rmdir("/test_mv_from");
+        Ok(())
+    }
+
+    /// Test truncate/chmod through setattr
+    pub fn test_vfs_setattr() -> TestResult {
+        let path = "/test_setattr";
+        let mut f = match vfs().create(path, FileMode::new(FileMode::S_IFREG | 0o644)) {
+            Ok(f) => f,
+            Err(e) => return Err(alloc::format!("create failed: {:?}", e)),
+        };
+        let msg = b"0123456789";
+        let _ = f.write(msg.as_ptr() as usize, msg.len());
+
+        test_assert!(vfs().truncate(path, 4).is_ok());
+        test_assert!(vfs().chmod(path, 0o600).is_ok());
+        match vfs().stat(path) {
+            Ok(attr) => {
+                test_assert_eq!(attr.size, 4);
+                test_assert_eq!(attr.mode.permissions(), 0o600);
+                test_assert!(attr.mode.is_regular());
+            }
+            Err(e) => return Err(alloc::format!("stat failed: {:?}", e)),
+        }
+
+        // Cleanup
+        let _ = vfs().unlink(path);
+        Ok(())
+    }
+
+    /// Test extended attribute set/get/list/remove
+    pub fn test_vfs_xattr() -> TestResult {
+        let path = "/test_xattr";
+        let _ = vfs().create(path, FileMode::new(FileMode::S_IFREG | 0o644));
+        let label = b"system_u:object_r:tmp_t";
+
+        test_assert!(vfs().setxattr(path, "security.selinux", label, XattrFlags(XattrFlags::CREATE)).is_ok());
+        test_assert!(vfs().setxattr(path, "security.selinux", label, XattrFlags(XattrFlags::CREATE)) == Err(VfsError::Exists));
+        test_assert!(vfs().setxattr(path, "user.missing", b"x", XattrFlags(XattrFlags::REPLACE)) == Err(VfsError::NoData));
+        test_assert!(vfs().setxattr(path, "bogus.name", b"x", XattrFlags::default()) == Err(VfsError::NotSupported));
+        test_assert!(vfs().getxattr(path, "security.selinux").as_deref() == Ok(&label[..]));
+        test_assert!(vfs().listxattr(path).map(|names| names.len()) == Ok(1));
+        test_assert!(vfs().removexattr(path, "security.selinux").is_ok());
+        test_assert!(vfs().getxattr(path, "security.selinux") == Err(VfsError::NoData));
+
+        // Cleanup
+        let _ = vfs().unlink(path);
+        Ok(())
+    }
+
+    /// Test symlink following, ELOOP and `..` through a symlinked directory
+    pub fn test_vfs_symlink_walk() -> TestResult {
+        let dir = FileMode::new(FileMode::S_IFDIR | 0o755);
+        let _ = vfs().mkdir("/test_walk", dir);
+        let _ = vfs().mkdir("/test_walk/real", dir);
+        let _ = vfs().create("/test_walk/real/file", FileMode::new(FileMode::S_IFREG | 0o644));
+        let _ = vfs().symlink("/test_walk/rel", "real");
+        let _ = vfs().symlink("/test_walk/abs", "/test_walk/real/file");
+        let _ = vfs().symlink("/test_walk/loop", "loop");
+
+        test_assert!(vfs().stat("/test_walk/rel/file").map(|attr| attr.mode.is_regular()) == Ok(true));
+        test_assert!(vfs().stat("/test_walk/abs").map(|attr| attr.mode.is_regular()) == Ok(true));
+        test_assert!(vfs().lstat("/test_walk/abs").map(|attr| attr.mode.is_symlink()) == Ok(true));
+        test_assert!(vfs().stat("/test_walk/loop").is_err_and(|e| e == VfsError::TooManyLinks));
+        test_assert!(vfs().stat("/test_walk/abs/").is_err_and(|e| e == VfsError::NotDirectory));
+
+        // ".." after a symlink goes to the parent of the link's target
+        let ctx = FsContext::global().map_err(|e| alloc::format!("context failed: {:?}", e))?;
+        match ctx.lookup(None, "/test_walk/rel/..", LookupFlags(LookupFlags::FOLLOW)) {
+            Ok(loc) => test_assert!(loc.path == "/test_walk"),
+            Err(e) => return Err(alloc::format!("lookup failed: {:?}", e)),
+        }
+        test_assert!(
+            vfs().open_at(&ctx, None, "/test_walk/abs", LookupFlags::default(), 0).is_err_and(|e| e == VfsError::TooManyLinks)
+        );
+
+        // Cleanup
+        let _ = vfs().unlink("/test_walk/loop");
+        let _ = vfs().unlink("/test_walk/abs");
+        let _ = vfs().unlink("/test_walk/rel");
+        let _ = vfs().unlink("/test_walk/real/file");
+        let _ = vfs().rmdir("/test_walk/real");
+        let _ = vfs().rmdir("/test_walk");
+        Ok(())
+    }
+
+    /// Test an fscrypt-encrypted ext4 directory, unlocked and locked
+    pub fn test_ext4_fscrypt() -> TestResult {
+        use crate::vfs::fs::{FileSystemType, SuperBlock};
+        use crate::vfs::fscrypt::{PolicyV2, FSCRYPT_MODE_AES_256_CTS, FSCRYPT_MODE_AES_256_XTS};
+
+        let sb = crate::vfs::ext4::Ext4FsType.mount(None, 0).map_err(|e| alloc::format!("mount failed: {:?}", e))?;
+        let root = sb.root();
+        let dir = root.mkdir("secret", FileMode::new(FileMode::S_IFDIR | 0o700)).map_err(|e| alloc::format!("mkdir failed: {:?}", e))?;
+        let crypt = dir.fscrypt().ok_or("ext4 has no fscrypt")?;
+        let key: alloc::vec::Vec<u8> = (0..64).collect();
+        let identifier = crypt.keyring().add(&key).map_err(|e| alloc::format!("add key failed: {:?}", e))?;
+        let policy = PolicyV2 {
+            contents_mode: FSCRYPT_MODE_AES_256_XTS,
+            filenames_mode: FSCRYPT_MODE_AES_256_CTS,
+            flags: 2,
+            master_key_identifier: identifier,
+        };
+        test_assert!(crypt.set_policy(&policy, true).is_ok());
+
+        let file = dir.create("notes.txt", FileMode::new(FileMode::S_IFREG | 0o600)).map_err(|e| alloc::format!("create failed: {:?}", e))?;
+        let msg = b"attack at dawn";
+        test_assert!(file.write(5000, msg) == Ok(msg.len()));
+        test_assert!(file.sync().is_ok());
+        let _ = dir.symlink("link", "notes.txt");
+        test_assert!(dir.lookup("notes.txt").is_ok());
+        test_assert!(dir.lookup("link").and_then(|link| link.readlink()).as_deref() == Ok("notes.txt"));
+        test_assert!(dir.readdir(0).map(|entries| entries.iter().any(|e| e.name == "notes.txt")) == Ok(true));
+
+        // An unencrypted file cannot be moved in
+        let plain = root.create("plain", FileMode::new(FileMode::S_IFREG | 0o644)).map_err(|e| alloc::format!("create failed: {:?}", e))?;
+        test_assert!(dir.link("plain", plain) == Err(VfsError::CrossDevice));
+
+        // Locked: entries show no-key names and contents are unreadable
+        test_assert!(crypt.keyring().remove(&identifier) == Ok(0));
+        let entries = dir.readdir(0).map_err(|e| alloc::format!("readdir failed: {:?}", e))?;
+        test_assert!(entries.iter().all(|e| e.name != "notes.txt"));
+        let nokey = entries.iter().find(|e| e.file_type == crate::vfs::types::FileType::Regular).ok_or("entry missing")?;
+        let locked = dir.lookup(&nokey.name).map_err(|e| alloc::format!("lookup failed: {:?}", e))?;
+        let mut buf = [0u8; 16];
+        test_assert!(locked.read(5000, &mut buf) == Err(VfsError::NoKey));
+        test_assert!(dir.create("new", FileMode::new(FileMode::S_IFREG | 0o600)).is_err_and(|e| e == VfsError::NoKey));
+
+        // Unlocked again: the plaintext comes back from the stored ciphertext
+        test_assert!(crypt.keyring().add(&key) == Ok(identifier));
+        test_assert!(locked.read(5000, &mut buf[..msg.len()]) == Ok(msg.len()));
+        test_assert_eq!(&buf[..msg.len()], &msg[..]);
+        test_assert!(locked.read(0, &mut buf) == Ok(buf.len()));
+        test_assert!(buf == [0; 16]);
+        Ok(())
+    }
+
+    /// Test that `..` and absolute symlinks stay inside a changed root
+    pub fn test_vfs_chroot_context() -> TestResult {
+        let dir = FileMode::new(FileMode::S_IFDIR | 0o755);
+        let _ = vfs().mkdir("/test_jail", dir);
+        let _ = vfs().mkdir("/test_jail/etc", dir);
+        let _ = vfs().symlink("/test_jail/etc/up", "/");
+
+        let ctx = match FsContext::new(Some("/test_jail"), Some("/test_jail/etc")) {
+            Ok(ctx) => ctx,
+            Err(e) => return Err(alloc::format!("context failed: {:?}", e)),
+        };
+        let resolve = |path| ctx.lookup(None, path, LookupFlags(LookupFlags::FOLLOW)).map(|loc| loc.path);
+        test_assert!(resolve("../../..").as_deref() == Ok("/test_jail"));
+        test_assert!(resolve("/etc").as_deref() == Ok("/test_jail/etc"));
+        test_assert!(resolve("up/etc").as_deref() == Ok("/test_jail/etc"));
+        test_assert!(ctx.display_path(&ctx.cwd) == "/etc");
+
+        // Cleanup
+        let _ = vfs().unlink("/test_jail/etc/up");
+        let _ = vfs().rmdir("/test_jail/etc");
+        let _ = vfs().rmdir("/test_jail");
+        Ok(())
+    }
+
     /// Test ProcFS servicestats node
     pub fn test_procfs_servicestats() -> TestResult {
         let mut f = match vfs().open("/proc/servicestats", 0) {
@@ -5,6 +5,7 @@
 extern crate alloc;
 
 use alloc::{string::{String, ToString}, sync::Arc, vec::Vec, collections::BTreeMap};
+use core::any::Any;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::subsystems::sync::Mutex;
@@ -12,8 +13,10 @@ use crate::subsystems::sync::Mutex;
 use super::{
     error::*,
     types::*,
-    fs::{FileSystemType, SuperBlock, InodeOps, FsStats},
+    fs::{FileSystemType, SuperBlock, InodeOps, FsStats, RenameFlags, SetAttr, rename_entries},
     dir::DirEntry,
+    page_cache::AddressSpace,
+    xattr::{self, XattrFlags, XattrMap},
 };
 
 /// TmpFS file system type
@@ -85,12 +88,14 @@ impl SuperBlock for TmpFsSuperBlock {
 /// TmpFS inode
 struct TmpFsInode {
     attr: Mutex<FileAttr>,
-    // For regular files
-    data: Mutex<Vec<u8>>,
+    // For regular files; the page cache is the only copy of the data
+    mapping: Option<Arc<AddressSpace>>,
     // For directories
     children: Mutex<BTreeMap<String, Arc<dyn InodeOps>>>,
     // For symlinks
     target: Mutex<Option<String>>,
+    // Extended attributes
+    xattrs: Mutex<XattrMap>,
     // Parent superblock reference
     sb: Option<Arc<TmpFsSuperBlock>>,
 }
@@ -105,9 +110,10 @@ impl TmpFsInode {
                 size: 0,
                 ..Default::default()
             }),
-            data: Mutex::new(Vec::new()),
+            mapping: Some(AddressSpace::new_memory()),
             children: Mutex::new(BTreeMap::new()),
             target: Mutex::new(None),
+            xattrs: Mutex::new(XattrMap::new()),
             sb,
         }
     }
@@ -120,9 +126,10 @@ impl TmpFsInode {
                 nlink: 2,
                 ..Default::default()
             }),
-            data: Mutex::new(Vec::new()),
+            mapping: None,
             children: Mutex::new(BTreeMap::new()),
             target: Mutex::new(None),
+            xattrs: Mutex::new(XattrMap::new()),
             sb,
         }
     }
@@ -136,12 +143,35 @@ impl TmpFsInode {
                 size: target.len() as u64,
                 ..Default::default()
             }),
-            data: Mutex::new(Vec::new()),
+            mapping: None,
             children: Mutex::new(BTreeMap::new()),
             target: Mutex::new(Some(target.to_string())),
+            xattrs: Mutex::new(XattrMap::new()),
             sb,
         }
     }
+
+    /// Downcast an inode that must belong to tmpfs
+    fn of(inode: &dyn InodeOps) -> VfsResult<&Self> {
+        inode.as_any().downcast_ref::<Self>().ok_or(VfsError::CrossDevice)
+    }
+
+    fn adjust_nlink(&self, delta: i32) {
+        let mut attr = self.attr.lock();
+        attr.nlink = attr.nlink.saturating_add_signed(delta);
+    }
+    
+    /// Record a new file size, charging the difference to the superblock
+    fn resized(&self, attr: &mut FileAttr, size: u64) {
+        if let Some(sb) = &self.sb {
+            if size > attr.size {
+                sb.total_bytes.fetch_add((size - attr.size) as usize, Ordering::Relaxed);
+            } else {
+                sb.total_bytes.fetch_sub((attr.size - size) as usize, Ordering::Relaxed);
+            }
+        }
+        attr.size = size;
+    }
 }
 
 impl InodeOps for TmpFsInode {
@@ -149,16 +179,18 @@ impl InodeOps for TmpFsInode {
         Ok(self.attr.lock().clone())
     }
 
-    fn setattr(&self, attr: &FileAttr) -> VfsResult<()> {
-        let mut my_attr = self.attr.lock();
-        my_attr.mode = attr.mode;
-        my_attr.uid = attr.uid;
-        my_attr.gid = attr.gid;
-        my_attr.size = attr.size;
-        my_attr.atime = attr.atime;
-        my_attr.mtime = attr.mtime;
-        my_attr.ctime = attr.ctime;
-        my_attr.nlink = attr.nlink;
+    fn setattr(&self, attr: &SetAttr) -> VfsResult<()> {
+        if attr.has(SetAttr::SIZE) {
+            let mode = self.attr.lock().mode;
+            if mode.is_dir() {
+                return Err(VfsError::IsDirectory);
+            }
+            if !mode.is_regular() {
+                return Err(VfsError::InvalidOperation);
+            }
+            self.truncate(attr.size)?;
+        }
+        attr.apply(&mut self.attr.lock());
         Ok(())
     }
     
@@ -207,6 +239,8 @@ impl InodeOps for TmpFsInode {
         }
         
         children.insert(name.to_string(), inode.clone());
+        // The new directory's ".." refers to us
+        self.adjust_nlink(1);
         Ok(inode)
     }
     
@@ -214,18 +248,11 @@ impl InodeOps for TmpFsInode {
         let mut children = self.children.lock();
         
         let inode = children.get(name).ok_or(VfsError::NotFound)?;
-        let attr = inode.getattr()?;
-        if attr.mode.is_dir() {
+        if inode.getattr()?.mode.is_dir() {
             return Err(VfsError::IsDirectory);
         }
         
-        // Decrement nlink
-        let mut iattr = attr.clone();
-        if iattr.nlink > 0 {
-            iattr.nlink -= 1;
-            inode.setattr(&iattr)?;
-        }
-
+        Self::of(inode.as_ref())?.adjust_nlink(-1);
         children.remove(name);
         Ok(())
     }
@@ -242,7 +269,9 @@ impl InodeOps for TmpFsInode {
             return Err(VfsError::NotEmpty);
         }
         
+        Self::of(inode.as_ref())?.attr.lock().nlink = 0;
         children.remove(name);
+        self.adjust_nlink(-1);
         Ok(())
     }
 
@@ -252,19 +281,46 @@ impl InodeOps for TmpFsInode {
     }
 
     fn link(&self, name: &str, inode: Arc<dyn InodeOps>) -> VfsResult<()> {
+        let target = Self::of(inode.as_ref())?;
+        if target.attr.lock().mode.is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        
         let mut children = self.children.lock();
         if children.contains_key(name) {
             return Err(VfsError::Exists);
         }
         
-        // Increment nlink
-        let mut attr = inode.getattr()?;
-        attr.nlink += 1;
-        inode.setattr(&attr)?;
-        
+        target.adjust_nlink(1);
         children.insert(name.to_string(), inode);
         Ok(())
     }
+
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &Arc<dyn InodeOps>,
+        new_name: &str,
+        flags: RenameFlags,
+    ) -> VfsResult<()> {
+        let new_dir = Self::of(new_dir.as_ref())?;
+        if !new_dir.attr.lock().mode.is_dir() {
+            return Err(VfsError::NotDirectory);
+        }
+        
+        let outcome = rename_entries(&self.children, old_name, &new_dir.children, new_name, flags)?;
+        self.adjust_nlink(outcome.old_dir_links);
+        new_dir.adjust_nlink(outcome.new_dir_links);
+        if let Some(replaced) = outcome.replaced {
+            let replaced = Self::of(replaced.as_ref())?;
+            if replaced.attr.lock().mode.is_dir() {
+                replaced.attr.lock().nlink = 0;
+            } else {
+                replaced.adjust_nlink(-1);
+            }
+        }
+        Ok(())
+    }
     
     fn symlink(&self, name: &str, target: &str) -> VfsResult<Arc<dyn InodeOps>> {
         let mut children = self.children.lock();
@@ -308,74 +364,56 @@ impl InodeOps for TmpFsInode {
     }
     
     fn read(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
-        let data = self.data.lock();
-        let start = offset as usize;
-        
-        if start >= data.len() {
-            return Ok(0);
-        }
-        
-        let end = (start + buf.len()).min(data.len());
-        let len = end - start;
-        buf[..len].copy_from_slice(&data[start..end]);
-        
-        Ok(len)
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        mapping.read(offset, buf)
     }
     
     fn write(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
-        let mut data = self.data.lock();
-        let start = offset as usize;
-        
-        // Extend if necessary
-        let new_len = start + buf.len();
-        if new_len > data.len() {
-            let current_len = data.len();
-            data.resize(new_len, 0);
-            
-            // Update superblock total bytes
-            if let Some(sb) = &self.sb {
-                sb.total_bytes.fetch_add(new_len - current_len, Ordering::Relaxed);
-            }
-        }
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
+        let written = mapping.write(offset, buf)?;
         
-        data[start..start + buf.len()].copy_from_slice(buf);
-        
-        // Update size
+        // Update size and superblock total bytes
         let mut attr = self.attr.lock();
-        attr.size = data.len() as u64;
+        self.resized(&mut attr, mapping.size());
         
-        Ok(buf.len())
+        Ok(written)
     }
     
     fn truncate(&self, size: u64) -> VfsResult<()> {
-        let mut data = self.data.lock();
-        let current_len = data.len();
-        let new_len = size as usize;
-        
-        if new_len < current_len {
-            // Shrink
-            data.truncate(new_len);
-            
-            // Update superblock total bytes
-            if let Some(sb) = &self.sb {
-                sb.total_bytes.fetch_sub(current_len - new_len, Ordering::Relaxed);
-            }
-        } else if new_len > current_len {
-            // Extend
-            data.resize(new_len, 0);
-            
-            // Update superblock total bytes
-            if let Some(sb) = &self.sb {
-                sb.total_bytes.fetch_add(new_len - current_len, Ordering::Relaxed);
-            }
-        }
-        
-        // Update inode size
+        let mapping = self.mapping.as_ref().ok_or(VfsError::InvalidOperation)?;
         let mut attr = self.attr.lock();
-        attr.size = size;
+        mapping.truncate(size);
+        self.resized(&mut attr, size);
         
         Ok(())
     }
+
+    fn mapping(&self) -> Option<Arc<AddressSpace>> {
+        self.mapping.clone()
+    }
+
+    fn getxattr(&self, name: &str) -> VfsResult<Vec<u8>> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().get(name)
+    }
+
+    fn setxattr(&self, name: &str, value: &[u8], flags: XattrFlags) -> VfsResult<()> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().set(name, value, flags)
+    }
+
+    fn listxattr(&self) -> VfsResult<Vec<String>> {
+        Ok(self.xattrs.lock().list())
+    }
+
+    fn removexattr(&self, name: &str) -> VfsResult<()> {
+        xattr::check_name(name, self.attr.lock().mode)?;
+        self.xattrs.lock().remove(name)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /// Initialize and register TmpFS
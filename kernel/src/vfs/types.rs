@@ -69,6 +69,10 @@ impl FileMode {
     pub fn is_regular(&self) -> bool {
         self.0 & Self::S_IFMT == Self::S_IFREG
     }
+
+    pub fn is_symlink(&self) -> bool {
+        self.0 & Self::S_IFMT == Self::S_IFLNK
+    }
     
     pub fn permissions(&self) -> u32 {
         self.0 & 0o777
@@ -0,0 +1,111 @@
+//! Extended attributes
+//!
+//! Names are namespaced like on Linux (`user.`, `trusted.`, `security.`,
+//! `system.`); values are opaque bytes. [`XattrMap`] is the storage used by
+//! the in-memory filesystems and the ext4 driver.
+
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, string::{String, ToString}, vec::Vec};
+
+use super::{error::*, types::*};
+
+/// Longest attribute name, including the namespace prefix
+pub const XATTR_NAME_MAX: usize = 255;
+/// Largest single attribute value
+pub const XATTR_SIZE_MAX: usize = 65536;
+
+const NAMESPACES: [&str; 4] = ["user.", "trusted.", "security.", "system."];
+
+/// Flags for `setxattr`, same values as Linux
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XattrFlags(pub u32);
+
+impl XattrFlags {
+    /// Fail with `Exists` if the attribute is already set
+    pub const CREATE: u32  = 1 << 0;
+    /// Fail with `NoData` if the attribute is not set
+    pub const REPLACE: u32 = 1 << 1;
+
+    pub fn contains(&self, flag: u32) -> bool {
+        self.0 & flag != 0
+    }
+}
+
+/// Check an attribute name and whether `mode` may carry it
+///
+/// As on Linux, `user.` attributes are only allowed on regular files and
+/// directories; on symlinks and device nodes their permission bits mean
+/// something else.
+pub fn check_name(name: &str, mode: FileMode) -> VfsResult<()> {
+    if name.len() > XATTR_NAME_MAX {
+        return Err(VfsError::NameTooLong);
+    }
+    let ns = NAMESPACES
+        .iter()
+        .find(|ns| name.starts_with(*ns))
+        .ok_or(VfsError::NotSupported)?;
+    if name.len() == ns.len() {
+        return Err(VfsError::InvalidOperation);
+    }
+    if *ns == "user." && !(mode.is_regular() || mode.is_dir()) {
+        return Err(VfsError::PermissionDenied);
+    }
+    Ok(())
+}
+
+/// Per-inode attribute storage with an optional space limit
+#[derive(Debug, Default)]
+pub struct XattrMap {
+    entries: BTreeMap<String, Vec<u8>>,
+    /// Bytes of names plus values allowed; 0 means unlimited
+    limit: usize,
+    used: usize,
+}
+
+impl XattrMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Storage bounded to `limit` bytes of names and values
+    pub fn with_limit(limit: usize) -> Self {
+        Self { limit, ..Self::default() }
+    }
+
+    pub fn get(&self, name: &str) -> VfsResult<Vec<u8>> {
+        self.entries.get(name).cloned().ok_or(VfsError::NoData)
+    }
+
+    pub fn set(&mut self, name: &str, value: &[u8], flags: XattrFlags) -> VfsResult<()> {
+        if flags.contains(XattrFlags::CREATE) && flags.contains(XattrFlags::REPLACE) {
+            return Err(VfsError::InvalidOperation);
+        }
+        if value.len() > XATTR_SIZE_MAX {
+            return Err(VfsError::NoSpace);
+        }
+        let old = self.entries.get(name).map(|v| name.len() + v.len());
+        match old {
+            Some(_) if flags.contains(XattrFlags::CREATE) => return Err(VfsError::Exists),
+            None if flags.contains(XattrFlags::REPLACE) => return Err(VfsError::NoData),
+            _ => {}
+        }
+        let used = self.used - old.unwrap_or(0) + name.len() + value.len();
+        if self.limit != 0 && used > self.limit {
+            return Err(VfsError::NoSpace);
+        }
+        self.entries.insert(name.to_string(), value.to_vec());
+        self.used = used;
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    pub fn remove(&mut self, name: &str) -> VfsResult<()> {
+        let value = self.entries.remove(name).ok_or(VfsError::NoData)?;
+        self.used -= name.len() + value.len();
+        Ok(())
+    }
+}
@@ -10,6 +10,8 @@ use alloc::string::String;
 use alloc::vec::Vec;
 
 use crate::vfs::{FileAttr, FileMode, FileType, DirEntry, FilesystemStats, VfsError};
+use crate::vfs::fs::{RenameFlags, SetAttr};
+use crate::vfs::xattr::XattrFlags;
 
 /// 文件系统类型 trait - 所有文件系统实现都需要实现
 pub trait FileSystemType: Send + Sync {
@@ -72,6 +74,46 @@ pub trait Inode: Send + Sync {
     
     /// 软链接目标
     fn symlink_target(&self) -> Option<String>;
+
+    /// 修改属性（truncate/chmod/chown/utimens），只应用 `attr.valid` 中选中的字段
+    fn setattr(&self, _attr: &SetAttr) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// 重命名目录项，可跨目录；支持 RENAME_NOREPLACE/RENAME_EXCHANGE
+    fn rename(&self, _old_name: &str, _new_dir: &Arc<dyn Inode>, _new_name: &str, _flags: RenameFlags) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// 创建硬链接
+    fn link(&self, _name: &str, _inode: Arc<dyn Inode>) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// 创建软链接
+    fn symlink(&self, _name: &str, _target: &str) -> Result<Arc<dyn Inode>, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// 读取扩展属性
+    fn getxattr(&self, _name: &str) -> Result<Vec<u8>, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// 设置扩展属性
+    fn setxattr(&self, _name: &str, _value: &[u8], _flags: XattrFlags) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// 列出扩展属性名
+    fn listxattr(&self) -> Result<Vec<String>, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
+    /// 删除扩展属性
+    fn removexattr(&self, _name: &str) -> Result<(), VfsError> {
+        Err(VfsError::NotSupported)
+    }
 }
 
 /// 挂载点 trait
@@ -0,0 +1,13 @@
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+
+    // Userspace programs are linked at the user image base, never with the
+    // kernel scripts. Host builds (tests, clippy) keep the default layout.
+    if os == "none" {
+        println!("cargo:rustc-link-arg-bins=-T{}/linker.ld", manifest_dir);
+        println!("cargo:rustc-link-arg-bins=-static");
+    }
+
+    println!("cargo:rerun-if-changed=linker.ld");
+}
@@ -0,0 +1,129 @@
+//! /bin/dispcheck — headless check of the display server
+//!
+//! Draws a pattern into a shared buffer, shows it on a surface and reads
+//! the composited screen back with a capture; then redraws part of the
+//! buffer, commits only that damage and checks the screen again. Exits 0
+//! when both captures match what was drawn.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+
+use user::display::{Display, Event, ShmPool, FORMAT_ARGB8888, FORMAT_XRGB8888};
+use user::{eprintln, println};
+
+user::entry!(main);
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const STRIDE: u32 = WIDTH * 4;
+const IMAGE: usize = (STRIDE * HEIGHT) as usize;
+/// Where the surface goes on screen
+const X: i32 = 100;
+const Y: i32 = 50;
+
+fn main() -> i32 {
+    match run() {
+        Ok(()) => {
+            println!("dispcheck: ok");
+            0
+        }
+        Err(msg) => {
+            eprintln!("dispcheck: {}", msg);
+            1
+        }
+    }
+}
+
+fn pattern(x: u32, y: u32, frame: u32) -> u32 {
+    (x * 4) << 16 | (y * 5) << 8 | frame * 0x40
+}
+
+/// Fill the image with frame `frame`'s pattern inside a rectangle
+fn draw(pool: &mut ShmPool, frame: u32, (left, top, right, bottom): (u32, u32, u32, u32)) {
+    let pixels = pool.pixels_mut(0, STRIDE as usize, HEIGHT as usize);
+    for y in top..bottom {
+        for x in left..right {
+            pixels[(y * WIDTH + x) as usize] = pattern(x, y, frame);
+        }
+    }
+}
+
+/// Read events until `want` matches one; an error event fails
+fn wait(display: &mut Display, what: &str, want: impl Fn(&Event) -> bool) -> Result<(), String> {
+    let mut error = None;
+    display
+        .dispatch(|event| match event {
+            Event::Error { object, opcode, errno } => {
+                error = Some(format!("request {} on object {} failed: {}", opcode, object, errno));
+                true
+            }
+            event => want(&event),
+        })
+        .map_err(|e| format!("waiting for {}: {}", what, e))?;
+    error.map_or(Ok(()), Err)
+}
+
+/// Capture the surface's area of the screen and compare it with the image
+fn check(display: &mut Display, pool: &mut ShmPool, capture: u32, what: &str) -> Result<(), String> {
+    display.capture(capture, X, Y);
+    display.flush().map_err(|e| format!("capture: {}", e))?;
+    wait(display, "capture", |event| matches!(event, Event::Captured { buffer } if *buffer == capture))?;
+
+    let image = pool.pixels_mut(0, STRIDE as usize * 2, HEIGHT as usize);
+    let (drawn, captured) = image.split_at(IMAGE / 4);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let i = (y * WIDTH + x) as usize;
+            // XRGB is shown opaque
+            let expected = drawn[i] | 0xff00_0000;
+            if captured[i] != expected {
+                return Err(format!("{}: pixel ({}, {}) is {:#010x}, drew {:#010x}", what, x, y, captured[i], expected));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run() -> Result<(), String> {
+    let mut display = Display::connect().map_err(|e| format!("connect: {}", e))?;
+    println!("dispcheck: screen {}x{} at {} mHz", display.width, display.height, display.refresh_mhz);
+    if (X + WIDTH as i32) as u32 > display.width || (Y + HEIGHT as i32) as u32 > display.height {
+        return Err(String::from("screen too small"));
+    }
+
+    // The image, then room for captures
+    let mut pool = ShmPool::new(IMAGE * 2).map_err(|e| format!("shared memory: {}", e))?;
+    draw(&mut pool, 0, (0, 0, WIDTH, HEIGHT));
+
+    let pool_id = display.create_pool(&pool);
+    let image = display.create_buffer(pool_id, 0, WIDTH, HEIGHT, STRIDE, FORMAT_XRGB8888);
+    let capture = display.create_buffer(pool_id, IMAGE as u32, WIDTH, HEIGHT, STRIDE, FORMAT_ARGB8888);
+    let surface = display.create_surface();
+    display.set_position(surface, X, Y);
+    display.attach(surface, image);
+    display.damage(surface, 0, 0, WIDTH as i32, HEIGHT as i32);
+    let frame = display.frame(surface);
+    display.commit(surface);
+    display.flush().map_err(|e| format!("commit: {}", e))?;
+    wait(&mut display, "first frame", |event| matches!(event, Event::Done { callback, .. } if *callback == frame))?;
+    check(&mut display, &mut pool, capture, "first frame")?;
+
+    // Only the damaged part is taken from the buffer
+    let part = (8, 4, 24, 20);
+    draw(&mut pool, 1, part);
+    display.attach(surface, image);
+    display.damage(surface, part.0 as i32, part.1 as i32, (part.2 - part.0) as i32, (part.3 - part.1) as i32);
+    let frame = display.frame(surface);
+    display.commit(surface);
+    display.flush().map_err(|e| format!("commit: {}", e))?;
+    wait(&mut display, "second frame", |event| matches!(event, Event::Done { callback, .. } if *callback == frame))?;
+    check(&mut display, &mut pool, capture, "second frame")?;
+
+    display.destroy_surface(surface);
+    display.roundtrip(|_| {}).map_err(|e| format!("teardown: {}", e))
+}
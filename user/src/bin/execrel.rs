@@ -0,0 +1,29 @@
+//! /bin/execrel — exercises relative-path `execve`
+//!
+//! Changes into a directory (default `/bin`, or `argv[1]`) and executes the
+//! bare name `hello` without a `PATH` search, so the kernel has to resolve
+//! it against the new working directory.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use user::process::{cstring, CArgs};
+use user::{eprintln, rt, syscall};
+
+user::entry!(main);
+
+fn main() -> i32 {
+    let dir = rt::args().nth(1).unwrap_or(b"/bin");
+    if let Err(e) = syscall::chdir(&cstring(dir)) {
+        eprintln!("execrel: chdir: {}", e);
+        return 1;
+    }
+
+    let argv = CArgs::new([&b"hello"[..], &b"relative"[..]]);
+    let envp = [core::ptr::null()];
+    let err = syscall::execve(b"hello\0", argv.as_ptrs(), &envp);
+    eprintln!("execrel: exec hello: {}", err);
+    1
+}
@@ -0,0 +1,17 @@
+//! /bin/hello — prints its pid and arguments; used by exec smoke tests
+
+#![no_std]
+#![no_main]
+
+use user::{print, println, rt, syscall};
+
+user::entry!(main);
+
+fn main() -> i32 {
+    print!("hello from pid {}:", syscall::getpid());
+    for arg in rt::args() {
+        print!(" {}", core::str::from_utf8(arg).unwrap_or("?"));
+    }
+    println!();
+    0
+}
@@ -0,0 +1,108 @@
+//! /bin/init — first userspace process
+//!
+//! Sets up the console on fds 0-2, creates `/tmp`, then keeps a shell
+//! running on the console and reaps every orphan re-parented to PID 1.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use user::process::{self, CArgs};
+use user::signal::{self, Handler};
+use user::syscall::{self, wait, Timespec, O_RDWR};
+use user::{eprintln, println, Errno};
+
+user::entry!(main);
+
+const CONSOLE: &[u8] = b"/dev/console\0";
+const SHELL: &[u8] = b"/bin/sh";
+
+fn main() -> i32 {
+    if syscall::getpid() != 1 {
+        eprintln!("init: must be run as pid 1");
+        return 1;
+    }
+
+    setup_console();
+    if let Err(e) = syscall::mkdir(b"/tmp\0", 0o1777) {
+        if e != Errno::EEXIST {
+            eprintln!("init: mkdir /tmp: {}", e);
+        }
+    }
+
+    // Keyboard signals are meant for the foreground job, never for init.
+    let _ = signal::signal(signal::SIGINT, Handler::Ignore);
+    let _ = signal::signal(signal::SIGQUIT, Handler::Ignore);
+    let _ = signal::signal(signal::SIGTSTP, Handler::Ignore);
+
+    println!("NOS init: starting shell");
+    loop {
+        let shell = spawn_shell();
+        reap_until(shell);
+        // Avoid a tight respawn loop if the shell dies immediately.
+        let _ = syscall::nanosleep(&Timespec { tv_sec: 1, tv_nsec: 0 });
+    }
+}
+
+/// Make sure fds 0, 1 and 2 refer to the console
+fn setup_console() {
+    match syscall::open(CONSOLE, O_RDWR, 0) {
+        Ok(0) => {
+            let _ = syscall::dup2(0, 1);
+            let _ = syscall::dup2(0, 2);
+        }
+        // The kernel already installed stdio for us.
+        Ok(fd) => {
+            let _ = syscall::close(fd);
+        }
+        Err(_) => {}
+    }
+}
+
+fn spawn_shell() -> Option<i32> {
+    match syscall::fork() {
+        Ok(0) => {
+            let _ = syscall::setsid();
+            let _ = signal::signal(signal::SIGINT, Handler::Default);
+            let _ = signal::signal(signal::SIGQUIT, Handler::Default);
+            let _ = signal::signal(signal::SIGTSTP, Handler::Default);
+            let argv = CArgs::new([SHELL]);
+            let err = process::execvp(&argv, SHELL);
+            eprintln!("init: exec /bin/sh: {}", err);
+            syscall::exit(127)
+        }
+        Ok(pid) => Some(pid),
+        Err(e) => {
+            eprintln!("init: fork: {}", e);
+            None
+        }
+    }
+}
+
+/// Reap children until `shell` exits (or immediately if there is none)
+fn reap_until(shell: Option<i32>) {
+    loop {
+        match syscall::waitpid(-1, 0) {
+            Ok((pid, status)) if Some(pid) == shell => {
+                if wait::signaled(status) {
+                    eprintln!(
+                        "init: shell killed by {}{}",
+                        signal::name(wait::term_sig(status)),
+                        if wait::core_dumped(status) { " (core dumped)" } else { "" }
+                    );
+                } else {
+                    println!("init: shell exited with status {}", wait::exit_status(status));
+                }
+                return;
+            }
+            Ok(_) => {}
+            Err(Errno::EINTR) => {}
+            Err(Errno::ECHILD) => return,
+            Err(e) => {
+                eprintln!("init: waitpid: {}", e);
+                return;
+            }
+        }
+    }
+}
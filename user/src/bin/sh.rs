@@ -0,0 +1,321 @@
+//! /bin/sh — minimal interactive shell
+//!
+//! Supports pipelines, `<`/`>`/`>>` redirection, background jobs with `&`
+//! and the builtins `cd`, `pwd`, `echo`, `exit` and `help`. Run a single
+//! command non-interactively with `sh -c 'command'`.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use user::cmdline::{self, Command, Pipeline};
+use user::process::{self, cstring, CArgs};
+use user::signal::{self, Handler};
+use user::syscall::{self, wait, O_APPEND, O_CREAT, O_RDONLY, O_TRUNC, O_WRONLY, STDIN, WNOHANG};
+use user::{eprintln, print, println, rt, Errno};
+
+user::entry!(main);
+
+const LINE_MAX: usize = 1024;
+
+fn main() -> i32 {
+    let args: Vec<&[u8]> = rt::args().collect();
+    if args.len() >= 3 && args[1] == b"-c" {
+        let mut status = 0;
+        run_line(args[2], &mut status);
+        return status;
+    }
+
+    // Ctrl+C interrupts the foreground job, not the shell itself.
+    let _ = signal::signal(signal::SIGINT, Handler::Ignore);
+    let _ = signal::signal(signal::SIGQUIT, Handler::Ignore);
+
+    let mut status = 0;
+    let mut line = [0u8; LINE_MAX];
+    loop {
+        reap_background();
+        print!("$ ");
+        match user::io::read_line(STDIN, &mut line) {
+            Ok(Some(len)) => {
+                if run_line(&line[..len], &mut status) == EXIT_REQUESTED {
+                    return status;
+                }
+            }
+            Ok(None) => {
+                println!();
+                return status;
+            }
+            Err(Errno::EINTR) => {}
+            Err(e) => {
+                eprintln!("sh: read: {}", e);
+                return 1;
+            }
+        }
+    }
+}
+
+/// Sentinel returned by [`run_line`] when the `exit` builtin ran
+const EXIT_REQUESTED: i32 = -1;
+
+fn run_line(line: &[u8], status: &mut i32) -> i32 {
+    let pipeline = match cmdline::parse(line) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("sh: syntax error: {}", e.description());
+            *status = 2;
+            return *status;
+        }
+    };
+    if pipeline.commands.is_empty() {
+        return *status;
+    }
+
+    // Builtins that change shell state must run in the shell process.
+    if pipeline.commands.len() == 1 && !pipeline.background {
+        let argv = &pipeline.commands[0].argv;
+        match argv[0].as_slice() {
+            b"cd" => {
+                *status = builtin_cd(argv);
+                return *status;
+            }
+            b"exit" => {
+                *status = argv.get(1).and_then(|a| parse_int(a)).unwrap_or(*status);
+                return EXIT_REQUESTED;
+            }
+            _ => {}
+        }
+    }
+
+    *status = run_pipeline(&pipeline);
+    *status
+}
+
+fn run_pipeline(pipeline: &Pipeline) -> i32 {
+    let count = pipeline.commands.len();
+    let mut pids = Vec::with_capacity(count);
+    let mut prev_read: Option<i32> = None;
+
+    for (i, cmd) in pipeline.commands.iter().enumerate() {
+        let (next_read, write_end) = if i + 1 < count {
+            match syscall::pipe() {
+                Ok((r, w)) => (Some(r), Some(w)),
+                Err(e) => {
+                    eprintln!("sh: pipe: {}", e);
+                    break;
+                }
+            }
+        } else {
+            (None, None)
+        };
+
+        match syscall::fork() {
+            Ok(0) => {
+                if let Some(r) = next_read {
+                    let _ = syscall::close(r);
+                }
+                run_child(cmd, prev_read, write_end, pipeline.background)
+            }
+            Ok(pid) => pids.push(pid),
+            Err(e) => eprintln!("sh: fork: {}", e),
+        }
+
+        if let Some(r) = prev_read {
+            let _ = syscall::close(r);
+        }
+        if let Some(w) = write_end {
+            let _ = syscall::close(w);
+        }
+        prev_read = next_read;
+    }
+    if let Some(r) = prev_read {
+        let _ = syscall::close(r);
+    }
+
+    if pipeline.background {
+        if let Some(pid) = pids.last() {
+            println!("[{}]", pid);
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for pid in pids {
+        loop {
+            match syscall::waitpid(pid, 0) {
+                Ok((_, raw)) => {
+                    status = decode_status(raw);
+                    break;
+                }
+                Err(Errno::EINTR) => {}
+                Err(e) => {
+                    eprintln!("sh: waitpid {}: {}", pid, e);
+                    status = 1;
+                    break;
+                }
+            }
+        }
+    }
+    status
+}
+
+/// Set up stdio for one pipeline stage and exec it; never returns
+fn run_child(cmd: &Command, stdin: Option<i32>, stdout: Option<i32>, background: bool) -> ! {
+    if !background {
+        let _ = signal::signal(signal::SIGINT, Handler::Default);
+        let _ = signal::signal(signal::SIGQUIT, Handler::Default);
+    }
+
+    if let Some(fd) = stdin {
+        let _ = syscall::dup2(fd, 0);
+        let _ = syscall::close(fd);
+    }
+    if let Some(fd) = stdout {
+        let _ = syscall::dup2(fd, 1);
+        let _ = syscall::close(fd);
+    }
+    if let Some(path) = &cmd.stdin {
+        redirect(path, O_RDONLY, 0);
+    }
+    if let Some(out) = &cmd.stdout {
+        let mode = if out.append { O_APPEND } else { O_TRUNC };
+        redirect(&out.path, O_WRONLY | O_CREAT | mode, 1);
+    }
+
+    let name = cmd.argv[0].as_slice();
+    match name {
+        b"echo" => syscall::exit(builtin_echo(&cmd.argv)),
+        b"pwd" => syscall::exit(builtin_pwd()),
+        b"help" => syscall::exit(builtin_help()),
+        b"cd" | b"exit" => syscall::exit(0),
+        _ => {}
+    }
+
+    let argv = CArgs::new(cmd.argv.iter());
+    let err = process::execvp(&argv, name);
+    eprintln!("sh: {}: {}", Utf8(name), err);
+    syscall::exit(if err == Errno::ENOENT { 127 } else { 126 })
+}
+
+fn redirect(path: &[u8], flags: i32, target: i32) {
+    match syscall::open(&cstring(path), flags, 0o644) {
+        Ok(fd) => {
+            if fd != target {
+                let _ = syscall::dup2(fd, target);
+                let _ = syscall::close(fd);
+            }
+        }
+        Err(e) => {
+            eprintln!("sh: {}: {}", Utf8(path), e);
+            syscall::exit(1);
+        }
+    }
+}
+
+/// Report finished background jobs before printing the prompt
+fn reap_background() {
+    while let Ok((pid, raw)) = syscall::waitpid(-1, WNOHANG) {
+        if pid <= 0 {
+            break;
+        }
+        println!("[{}] done ({})", pid, decode_status(raw));
+    }
+}
+
+/// Shell-style exit status: the exit code, or 128 + signal number
+fn decode_status(raw: i32) -> i32 {
+    if wait::signaled(raw) {
+        let sig = wait::term_sig(raw);
+        if sig != signal::SIGINT {
+            eprintln!(
+                "{}{}",
+                signal::name(sig),
+                if wait::core_dumped(raw) { " (core dumped)" } else { "" }
+            );
+        }
+        128 + sig
+    } else {
+        wait::exit_status(raw)
+    }
+}
+
+fn builtin_cd(argv: &[Vec<u8>]) -> i32 {
+    let target = argv
+        .get(1)
+        .map(|a| a.as_slice())
+        .or_else(|| rt::var(b"HOME"))
+        .unwrap_or(b"/");
+    match syscall::chdir(&cstring(target)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("sh: cd: {}: {}", Utf8(target), e);
+            1
+        }
+    }
+}
+
+fn builtin_pwd() -> i32 {
+    let mut buf = [0u8; 512];
+    match syscall::getcwd(&mut buf) {
+        Ok(len) => {
+            println!("{}", Utf8(&buf[..len]));
+            0
+        }
+        Err(e) => {
+            eprintln!("sh: pwd: {}", e);
+            1
+        }
+    }
+}
+
+fn builtin_echo(argv: &[Vec<u8>]) -> i32 {
+    for (i, arg) in argv[1..].iter().enumerate() {
+        if i > 0 {
+            print!(" ");
+        }
+        print!("{}", Utf8(arg));
+    }
+    println!();
+    0
+}
+
+fn builtin_help() -> i32 {
+    println!("builtins: cd [dir], pwd, echo [args..], exit [status], help");
+    println!("syntax:   cmd [args..] [< in] [> out | >> out] [| cmd ..] [&]");
+    0
+}
+
+fn parse_int(s: &[u8]) -> Option<i32> {
+    let (neg, digits) = match s.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, s),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut v: i32 = 0;
+    for &d in digits {
+        if !d.is_ascii_digit() {
+            return None;
+        }
+        v = v.checked_mul(10)?.checked_add((d - b'0') as i32)?;
+    }
+    Some(if neg { -v } else { v })
+}
+
+/// Display adapter for byte strings that may not be valid UTF-8
+struct Utf8<'a>(&'a [u8]);
+
+impl core::fmt::Display for Utf8<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                f.write_str("\u{FFFD}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+//! Shell command line parsing
+//!
+//! Grammar understood by `/bin/sh`:
+//!
+//! ```text
+//! line     := pipeline ('&')?
+//! pipeline := command ('|' command)*
+//! command  := (word | '<' word | '>' word | '>>' word)+
+//! word     := bare | 'single quoted' | "double quoted" | \escaped
+//! ```
+
+use alloc::vec::Vec;
+
+/// Output redirection target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub path: Vec<u8>,
+    pub append: bool,
+}
+
+/// One program invocation within a pipeline
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Command {
+    pub argv: Vec<Vec<u8>>,
+    pub stdin: Option<Vec<u8>>,
+    pub stdout: Option<Redirect>,
+}
+
+/// A parsed command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+    pub background: bool,
+}
+
+/// Reasons a line could not be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    UnterminatedQuote,
+    MissingRedirectTarget,
+    EmptyCommand,
+    TrailingInput,
+}
+
+impl ParseError {
+    pub fn description(self) -> &'static str {
+        match self {
+            ParseError::UnterminatedQuote => "unterminated quote",
+            ParseError::MissingRedirectTarget => "missing redirection target",
+            ParseError::EmptyCommand => "empty command in pipeline",
+            ParseError::TrailingInput => "unexpected input after '&'",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Word(Vec<u8>),
+    Pipe,
+    In,
+    Out,
+    Append,
+    Background,
+}
+
+fn tokenize(line: &[u8]) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < line.len() {
+        match line[i] {
+            b' ' | b'\t' | b'\r' => i += 1,
+            b'#' => break,
+            b'|' => {
+                tokens.push(Token::Pipe);
+                i += 1;
+            }
+            b'<' => {
+                tokens.push(Token::In);
+                i += 1;
+            }
+            b'>' if line.get(i + 1) == Some(&b'>') => {
+                tokens.push(Token::Append);
+                i += 2;
+            }
+            b'>' => {
+                tokens.push(Token::Out);
+                i += 1;
+            }
+            b'&' => {
+                tokens.push(Token::Background);
+                i += 1;
+            }
+            _ => {
+                let mut word = Vec::new();
+                while i < line.len() {
+                    match line[i] {
+                        b' ' | b'\t' | b'\r' | b'|' | b'<' | b'>' | b'&' => break,
+                        b'\\' => {
+                            if let Some(&c) = line.get(i + 1) {
+                                word.push(c);
+                            }
+                            i += 2;
+                        }
+                        quote @ (b'\'' | b'"') => {
+                            let end = line[i + 1..]
+                                .iter()
+                                .position(|&c| c == quote)
+                                .ok_or(ParseError::UnterminatedQuote)?;
+                            word.extend_from_slice(&line[i + 1..i + 1 + end]);
+                            i += end + 2;
+                        }
+                        c => {
+                            word.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse one line; an empty or comment-only line yields an empty pipeline
+pub fn parse(line: &[u8]) -> Result<Pipeline, ParseError> {
+    let tokens = tokenize(line)?;
+    let mut pipeline = Pipeline::default();
+    if tokens.is_empty() {
+        return Ok(pipeline);
+    }
+
+    let mut current = Command::default();
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        match token {
+            Token::Word(w) => current.argv.push(w),
+            Token::In | Token::Out | Token::Append => {
+                let path = match iter.next() {
+                    Some(Token::Word(w)) => w,
+                    _ => return Err(ParseError::MissingRedirectTarget),
+                };
+                match token {
+                    Token::In => current.stdin = Some(path),
+                    Token::Out => current.stdout = Some(Redirect { path, append: false }),
+                    _ => current.stdout = Some(Redirect { path, append: true }),
+                }
+            }
+            Token::Pipe => {
+                if current.argv.is_empty() {
+                    return Err(ParseError::EmptyCommand);
+                }
+                pipeline.commands.push(core::mem::take(&mut current));
+            }
+            Token::Background => {
+                if iter.peek().is_some() {
+                    return Err(ParseError::TrailingInput);
+                }
+                pipeline.background = true;
+            }
+        }
+    }
+    if current.argv.is_empty() {
+        return Err(ParseError::EmptyCommand);
+    }
+    pipeline.commands.push(current);
+    Ok(pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn words(cmd: &Command) -> Vec<&[u8]> {
+        cmd.argv.iter().map(|w| w.as_slice()).collect()
+    }
+
+    #[test]
+    fn parses_pipeline_with_redirections() {
+        let p = parse(b"cat < in.txt | grep 'a b' >> out.log &").unwrap();
+        assert!(p.background);
+        assert_eq!(p.commands.len(), 2);
+        assert_eq!(words(&p.commands[0]), vec![&b"cat"[..]]);
+        assert_eq!(p.commands[0].stdin.as_deref(), Some(&b"in.txt"[..]));
+        assert_eq!(words(&p.commands[1]), vec![&b"grep"[..], &b"a b"[..]]);
+        assert_eq!(
+            p.commands[1].stdout,
+            Some(Redirect { path: b"out.log".to_vec(), append: true })
+        );
+    }
+
+    #[test]
+    fn handles_quotes_escapes_and_comments() {
+        let p = parse(b"echo \"x|y\" a\\ b # trailing").unwrap();
+        assert_eq!(words(&p.commands[0]), vec![&b"echo"[..], &b"x|y"[..], &b"a b"[..]]);
+        assert_eq!(parse(b"   # only a comment").unwrap(), Pipeline::default());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(parse(b"echo 'oops"), Err(ParseError::UnterminatedQuote));
+        assert_eq!(parse(b"ls >"), Err(ParseError::MissingRedirectTarget));
+        assert_eq!(parse(b"| wc"), Err(ParseError::EmptyCommand));
+        assert_eq!(parse(b"ls |"), Err(ParseError::EmptyCommand));
+        assert_eq!(parse(b"sleep 1 & ls"), Err(ParseError::TrailingInput));
+    }
+}
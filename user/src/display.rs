@@ -0,0 +1,383 @@
+//! Display server client
+//!
+//! The connection `display_connect` returns carries the protocol of the
+//! kernel's `graphics::protocol`: requests are written to it, events read
+//! from it, each a 4-byte header (opcode, size) followed by 32-bit
+//! arguments in native byte order. Object ids are picked by the client.
+//!
+//! Pixels are shared through [`ShmPool`], a memfd mapped into the client;
+//! the server copies the damaged part of a buffer when its surface is
+//! committed and answers with `Release`, after which the buffer may be
+//! drawn again.
+
+use alloc::vec::Vec;
+
+use crate::errno::{Errno, SysResult};
+use crate::syscall::{self, MAP_SHARED, O_CLOEXEC, PROT_READ, PROT_WRITE};
+
+/// Buffer formats; alpha is premultiplied
+pub const FORMAT_ARGB8888: u32 = 0;
+pub const FORMAT_XRGB8888: u32 = 1;
+
+/// Request opcodes
+pub const REQ_SYNC: u16 = 1;
+pub const REQ_CREATE_POOL: u16 = 2;
+pub const REQ_DESTROY_POOL: u16 = 3;
+pub const REQ_CREATE_BUFFER: u16 = 4;
+pub const REQ_DESTROY_BUFFER: u16 = 5;
+pub const REQ_CREATE_SURFACE: u16 = 6;
+pub const REQ_DESTROY_SURFACE: u16 = 7;
+pub const REQ_ATTACH: u16 = 8;
+pub const REQ_DAMAGE: u16 = 9;
+pub const REQ_FRAME: u16 = 10;
+pub const REQ_COMMIT: u16 = 11;
+pub const REQ_SET_POSITION: u16 = 12;
+pub const REQ_SET_LAYER: u16 = 13;
+pub const REQ_CAPTURE: u16 = 14;
+
+/// Event opcodes
+pub const EV_ERROR: u16 = 1;
+pub const EV_DONE: u16 = 2;
+pub const EV_RELEASE: u16 = 3;
+pub const EV_OUTPUT: u16 = 4;
+pub const EV_KEY: u16 = 5;
+pub const EV_CHAR: u16 = 6;
+pub const EV_MOTION: u16 = 7;
+pub const EV_BUTTON: u16 = 8;
+pub const EV_AXIS: u16 = 9;
+pub const EV_TOUCH: u16 = 10;
+pub const EV_FOCUS: u16 = 11;
+pub const EV_CAPTURED: u16 = 12;
+
+const HEADER_SIZE: usize = 4;
+const MAX_ARGS: usize = 7;
+
+/// A message from the server
+///
+/// Pointer and touch coordinates are 24.8 fixed point relative to the
+/// surface; key and button codes are Linux input event codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Error { object: u32, opcode: u16, errno: Errno },
+    Done { callback: u32, data: u32 },
+    Release { buffer: u32 },
+    Output { width: u32, height: u32, refresh_mhz: u32 },
+    Key { surface: u32, key: u32, state: u32, modifiers: u32 },
+    Char { surface: u32, codepoint: u32 },
+    Motion { surface: u32, x: i32, y: i32 },
+    Button { surface: u32, button: u32, state: u32, x: i32, y: i32 },
+    Axis { surface: u32, dx: i32, dy: i32 },
+    Touch { surface: u32, id: u32, phase: u32, x: i32, y: i32 },
+    Focus { surface: u32, focused: bool },
+    Captured { buffer: u32 },
+    /// An event this library does not know
+    Unknown { opcode: u16 },
+}
+
+impl Event {
+    /// Decode the event at the start of `data`, returning it and its size
+    pub fn decode(data: &[u8]) -> Option<(Self, usize)> {
+        if data.len() < HEADER_SIZE {
+            return None;
+        }
+        let opcode = u16::from_ne_bytes([data[0], data[1]]);
+        let size = u16::from_ne_bytes([data[2], data[3]]) as usize;
+        if size < HEADER_SIZE || size % 4 != 0 || data.len() < size {
+            return None;
+        }
+        let mut a = [0u32; MAX_ARGS];
+        for (i, arg) in a.iter_mut().take((size - HEADER_SIZE) / 4).enumerate() {
+            let at = HEADER_SIZE + i * 4;
+            *arg = u32::from_ne_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        }
+        let event = match opcode {
+            EV_ERROR => Self::Error { object: a[0], opcode: a[1] as u16, errno: Errno(a[2] as i32) },
+            EV_DONE => Self::Done { callback: a[0], data: a[1] },
+            EV_RELEASE => Self::Release { buffer: a[0] },
+            EV_OUTPUT => Self::Output { width: a[0], height: a[1], refresh_mhz: a[2] },
+            EV_KEY => Self::Key { surface: a[0], key: a[1], state: a[2], modifiers: a[3] },
+            EV_CHAR => Self::Char { surface: a[0], codepoint: a[1] },
+            EV_MOTION => Self::Motion { surface: a[0], x: a[1] as i32, y: a[2] as i32 },
+            EV_BUTTON => Self::Button { surface: a[0], button: a[1], state: a[2], x: a[3] as i32, y: a[4] as i32 },
+            EV_AXIS => Self::Axis { surface: a[0], dx: a[1] as i32, dy: a[2] as i32 },
+            EV_TOUCH => Self::Touch { surface: a[0], id: a[1], phase: a[2], x: a[3] as i32, y: a[4] as i32 },
+            EV_FOCUS => Self::Focus { surface: a[0], focused: a[1] != 0 },
+            EV_CAPTURED => Self::Captured { buffer: a[0] },
+            _ => Self::Unknown { opcode },
+        };
+        Some((event, size))
+    }
+}
+
+/// Append a request to `out`
+pub fn encode(out: &mut Vec<u8>, opcode: u16, args: &[u32]) {
+    debug_assert!(args.len() <= MAX_ARGS);
+    out.extend_from_slice(&opcode.to_ne_bytes());
+    out.extend_from_slice(&((HEADER_SIZE + args.len() * 4) as u16).to_ne_bytes());
+    for arg in args {
+        out.extend_from_slice(&arg.to_ne_bytes());
+    }
+}
+
+/// A connection to the display server
+///
+/// Requests are queued until [`flush`](Self::flush); events are read with
+/// [`dispatch`](Self::dispatch).
+pub struct Display {
+    fd: i32,
+    out: Vec<u8>,
+    next_id: u32,
+    /// The screen, from the `Output` event the server sends first
+    pub width: u32,
+    pub height: u32,
+    pub refresh_mhz: u32,
+}
+
+impl Display {
+    pub fn connect() -> SysResult<Self> {
+        let fd = syscall::display_connect(O_CLOEXEC)?;
+        let mut display = Self { fd, out: Vec::new(), next_id: 1, width: 0, height: 0, refresh_mhz: 0 };
+        display.dispatch(|event| matches!(event, Event::Output { .. }))?;
+        Ok(display)
+    }
+
+    pub fn fd(&self) -> i32 {
+        self.fd
+    }
+
+    fn new_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Queue a request
+    pub fn request(&mut self, opcode: u16, args: &[u32]) {
+        encode(&mut self.out, opcode, args);
+    }
+
+    /// Send the queued requests
+    pub fn flush(&mut self) -> SysResult<()> {
+        let result = syscall::write_all(self.fd, &self.out);
+        self.out.clear();
+        result
+    }
+
+    /// Read events, handing each to `f`, until it returns true
+    ///
+    /// Blocks while no events are waiting; fails with `EPIPE` if the
+    /// server closes the connection first.
+    pub fn dispatch(&mut self, mut f: impl FnMut(Event) -> bool) -> SysResult<()> {
+        let mut buf = [0u8; 1024];
+        loop {
+            let len = syscall::read(self.fd, &mut buf)?;
+            if len == 0 {
+                return Err(Errno::EPIPE);
+            }
+            let mut at = 0;
+            let mut done = false;
+            while let Some((event, size)) = Event::decode(&buf[at..len]) {
+                if let Event::Output { width, height, refresh_mhz } = event {
+                    (self.width, self.height, self.refresh_mhz) = (width, height, refresh_mhz);
+                }
+                // The server writes whole events, so the rest of a read is
+                // still handed out once `f` is satisfied
+                done |= f(event);
+                at += size;
+            }
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Send the queued requests and wait until the server has handled them,
+    /// handing the events that arrive meanwhile to `f`
+    pub fn roundtrip(&mut self, mut f: impl FnMut(Event)) -> SysResult<()> {
+        let callback = self.sync();
+        self.flush()?;
+        self.dispatch(|event| match event {
+            Event::Done { callback: c, .. } if c == callback => true,
+            event => {
+                f(event);
+                false
+            }
+        })
+    }
+
+    pub fn sync(&mut self) -> u32 {
+        let callback = self.new_id();
+        self.request(REQ_SYNC, &[callback]);
+        callback
+    }
+
+    /// Share the first `size` bytes of `pool` with the server
+    pub fn create_pool(&mut self, pool: &ShmPool) -> u32 {
+        let id = self.new_id();
+        self.request(REQ_CREATE_POOL, &[id, pool.fd as u32, pool.size as u32]);
+        id
+    }
+
+    pub fn destroy_pool(&mut self, pool: u32) {
+        self.request(REQ_DESTROY_POOL, &[pool]);
+    }
+
+    /// Describe a `width` x `height` image at `offset` in a pool; `stride`
+    /// is in bytes
+    pub fn create_buffer(&mut self, pool: u32, offset: u32, width: u32, height: u32, stride: u32, format: u32) -> u32 {
+        let id = self.new_id();
+        self.request(REQ_CREATE_BUFFER, &[id, pool, offset, width, height, stride, format]);
+        id
+    }
+
+    pub fn destroy_buffer(&mut self, buffer: u32) {
+        self.request(REQ_DESTROY_BUFFER, &[buffer]);
+    }
+
+    pub fn create_surface(&mut self) -> u32 {
+        let id = self.new_id();
+        self.request(REQ_CREATE_SURFACE, &[id]);
+        id
+    }
+
+    pub fn destroy_surface(&mut self, surface: u32) {
+        self.request(REQ_DESTROY_SURFACE, &[surface]);
+    }
+
+    /// Buffer to show from the next commit; 0 hides the surface
+    pub fn attach(&mut self, surface: u32, buffer: u32) {
+        self.request(REQ_ATTACH, &[surface, buffer]);
+    }
+
+    /// Mark part of the attached buffer as changed
+    pub fn damage(&mut self, surface: u32, x: i32, y: i32, width: i32, height: i32) {
+        self.request(REQ_DAMAGE, &[surface, x as u32, y as u32, width as u32, height as u32]);
+    }
+
+    /// Ask for `Done` when it is time to draw the next frame; the data is
+    /// the frame's time in milliseconds
+    pub fn frame(&mut self, surface: u32) -> u32 {
+        let callback = self.new_id();
+        self.request(REQ_FRAME, &[surface, callback]);
+        callback
+    }
+
+    pub fn commit(&mut self, surface: u32) {
+        self.request(REQ_COMMIT, &[surface]);
+    }
+
+    pub fn set_position(&mut self, surface: u32, x: i32, y: i32) {
+        self.request(REQ_SET_POSITION, &[surface, x as u32, y as u32]);
+    }
+
+    pub fn set_layer(&mut self, surface: u32, layer: i32) {
+        self.request(REQ_SET_LAYER, &[surface, layer as u32]);
+    }
+
+    /// Copy the screen from (x, y) into a buffer; answered with `Captured`
+    pub fn capture(&mut self, buffer: u32, x: i32, y: i32) {
+        self.request(REQ_CAPTURE, &[buffer, x as u32, y as u32]);
+    }
+}
+
+impl Drop for Display {
+    fn drop(&mut self) {
+        let _ = syscall::close(self.fd);
+    }
+}
+
+/// Memory shared with the display server: a memfd mapped into the client
+pub struct ShmPool {
+    fd: i32,
+    ptr: *mut u8,
+    size: usize,
+}
+
+impl ShmPool {
+    pub fn new(size: usize) -> SysResult<Self> {
+        let fd = syscall::memfd_create(b"shm-pool\0", syscall::MFD_CLOEXEC)?;
+        let ptr = syscall::ftruncate(fd, size as u64)
+            .and_then(|_| syscall::mmap(0, size, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0));
+        match ptr {
+            Ok(ptr) => Ok(Self { fd, ptr, size }),
+            Err(e) => {
+                let _ = syscall::close(fd);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr, self.size) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, self.size) }
+    }
+
+    /// The pixels of an image at `offset`, `stride` bytes per row
+    pub fn pixels_mut(&mut self, offset: usize, stride: usize, height: usize) -> &mut [u32] {
+        let bytes = &mut self.as_mut_slice()[offset..offset + stride * height];
+        // The mapping is page aligned and offsets are multiples of 4
+        unsafe { core::slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut u32, bytes.len() / 4) }
+    }
+}
+
+impl Drop for ShmPool {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = syscall::munmap(self.ptr, self.size);
+        }
+        let _ = syscall::close(self.fd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_request() {
+        let mut out = Vec::new();
+        encode(&mut out, REQ_ATTACH, &[3, 7]);
+        encode(&mut out, REQ_COMMIT, &[3]);
+        assert_eq!(out.len(), 12 + 8);
+        assert_eq!(u16::from_ne_bytes([out[0], out[1]]), REQ_ATTACH);
+        assert_eq!(u16::from_ne_bytes([out[2], out[3]]), 12);
+        assert_eq!(u32::from_ne_bytes([out[8], out[9], out[10], out[11]]), 7);
+        assert_eq!(u16::from_ne_bytes([out[12], out[13]]), REQ_COMMIT);
+    }
+
+    #[test]
+    fn test_decode_events() {
+        let mut data = Vec::new();
+        encode(&mut data, EV_BUTTON, &[3, 0x110, 1, 2 << 8, (-1i32) as u32]);
+        encode(&mut data, EV_ERROR, &[9, REQ_COMMIT as u32, 2]);
+        encode(&mut data, 99, &[]);
+
+        let (event, size) = Event::decode(&data).unwrap();
+        assert_eq!(event, Event::Button { surface: 3, button: 0x110, state: 1, x: 512, y: -1 });
+        assert_eq!(size, 24);
+        let (event, size2) = Event::decode(&data[size..]).unwrap();
+        assert_eq!(event, Event::Error { object: 9, opcode: REQ_COMMIT, errno: Errno::ENOENT });
+        let (event, _) = Event::decode(&data[size + size2..]).unwrap();
+        assert_eq!(event, Event::Unknown { opcode: 99 });
+    }
+
+    #[test]
+    fn test_decode_incomplete() {
+        let mut data = Vec::new();
+        encode(&mut data, EV_DONE, &[1, 2]);
+        assert!(Event::decode(&data[..3]).is_none());
+        assert!(Event::decode(&data[..8]).is_none());
+        assert!(Event::decode(&data).is_some());
+    }
+}
@@ -0,0 +1,101 @@
+//! Error numbers returned by the kernel
+//!
+//! System calls return a non-negative value on success and `-errno` on
+//! failure. [`Errno::decode`] turns that raw register value into a `Result`.
+
+use core::fmt;
+
+/// A kernel error number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Errno(pub i32);
+
+impl Errno {
+    pub const EPERM: Errno = Errno(1);
+    pub const ENOENT: Errno = Errno(2);
+    pub const ESRCH: Errno = Errno(3);
+    pub const EINTR: Errno = Errno(4);
+    pub const EIO: Errno = Errno(5);
+    pub const E2BIG: Errno = Errno(7);
+    pub const ENOEXEC: Errno = Errno(8);
+    pub const EBADF: Errno = Errno(9);
+    pub const ECHILD: Errno = Errno(10);
+    pub const EAGAIN: Errno = Errno(11);
+    pub const ENOMEM: Errno = Errno(12);
+    pub const EACCES: Errno = Errno(13);
+    pub const EFAULT: Errno = Errno(14);
+    pub const EEXIST: Errno = Errno(17);
+    pub const ENOTDIR: Errno = Errno(20);
+    pub const EISDIR: Errno = Errno(21);
+    pub const EINVAL: Errno = Errno(22);
+    pub const EMFILE: Errno = Errno(24);
+    pub const ENOTTY: Errno = Errno(25);
+    pub const ENOSPC: Errno = Errno(28);
+    pub const EPIPE: Errno = Errno(32);
+    pub const ERANGE: Errno = Errno(34);
+    pub const ENAMETOOLONG: Errno = Errno(36);
+    pub const ENOSYS: Errno = Errno(38);
+    pub const EDQUOT: Errno = Errno(122);
+    pub const ENOKEY: Errno = Errno(126);
+    pub const EKEYEXPIRED: Errno = Errno(127);
+    pub const EKEYREVOKED: Errno = Errno(128);
+    pub const EKEYREJECTED: Errno = Errno(129);
+
+    /// Largest value the kernel uses as an error number; anything in
+    /// `-MAX_ERRNO..0` is an error, everything else is a valid result.
+    pub const MAX_ERRNO: usize = 4095;
+
+    /// Decode a raw system call return value
+    #[inline]
+    pub fn decode(ret: usize) -> Result<usize, Errno> {
+        if ret > usize::MAX - Self::MAX_ERRNO {
+            Err(Errno(-(ret as isize) as i32))
+        } else {
+            Ok(ret)
+        }
+    }
+
+    /// Short description, as printed by the shell and `init`
+    pub fn description(self) -> &'static str {
+        match self {
+            Errno::EPERM => "operation not permitted",
+            Errno::ENOENT => "no such file or directory",
+            Errno::ESRCH => "no such process",
+            Errno::EINTR => "interrupted system call",
+            Errno::EIO => "input/output error",
+            Errno::E2BIG => "argument list too long",
+            Errno::ENOEXEC => "exec format error",
+            Errno::EBADF => "bad file descriptor",
+            Errno::ECHILD => "no child processes",
+            Errno::EAGAIN => "resource temporarily unavailable",
+            Errno::ENOMEM => "out of memory",
+            Errno::EACCES => "permission denied",
+            Errno::EFAULT => "bad address",
+            Errno::EEXIST => "file exists",
+            Errno::ENOTDIR => "not a directory",
+            Errno::EISDIR => "is a directory",
+            Errno::EINVAL => "invalid argument",
+            Errno::EMFILE => "too many open files",
+            Errno::ENOTTY => "inappropriate ioctl for device",
+            Errno::ENOSPC => "no space left on device",
+            Errno::EPIPE => "broken pipe",
+            Errno::ERANGE => "result out of range",
+            Errno::ENAMETOOLONG => "file name too long",
+            Errno::ENOSYS => "function not implemented",
+            Errno::EDQUOT => "disk quota exceeded",
+            Errno::ENOKEY => "required key not available",
+            Errno::EKEYEXPIRED => "key has expired",
+            Errno::EKEYREVOKED => "key has been revoked",
+            Errno::EKEYREJECTED => "key was rejected by service",
+            _ => "unknown error",
+        }
+    }
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (errno {})", self.description(), self.0)
+    }
+}
+
+/// Result type for system call wrappers
+pub type SysResult<T> = Result<T, Errno>;
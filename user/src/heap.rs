@@ -0,0 +1,226 @@
+//! Userspace heap over anonymous `mmap`
+//!
+//! Small requests (up to [`MAX_CLASS`] bytes) are served from power-of-two
+//! size classes carved out of 64 KiB arenas; freed blocks go onto a per-class
+//! free list and are reused. Larger requests get their own mapping and are
+//! returned to the kernel with `munmap` on free.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const PAGE_SIZE: usize = 4096;
+const MIN_CLASS_SHIFT: usize = 4;
+const NUM_CLASSES: usize = 8;
+/// Largest size served from a size class (2 KiB)
+pub const MAX_CLASS: usize = 1 << (MIN_CLASS_SHIFT + NUM_CLASSES - 1);
+const ARENA_SIZE: usize = 64 * 1024;
+
+/// Source of page-granular memory for the heap
+pub trait PageSource {
+    /// Map `len` bytes (a multiple of the page size), or return null
+    fn map(&self, len: usize) -> *mut u8;
+    /// Release a mapping previously returned by [`PageSource::map`]
+    ///
+    /// # Safety
+    ///
+    /// `addr`/`len` must describe a live mapping from this source.
+    unsafe fn unmap(&self, addr: *mut u8, len: usize);
+}
+
+/// Pages from anonymous private `mmap`
+pub struct MmapPages;
+
+impl PageSource for MmapPages {
+    fn map(&self, len: usize) -> *mut u8 {
+        use crate::syscall::*;
+        mmap(0, len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0)
+            .unwrap_or(ptr::null_mut())
+    }
+
+    unsafe fn unmap(&self, addr: *mut u8, len: usize) {
+        let _ = crate::syscall::munmap(addr, len);
+    }
+}
+
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+struct HeapState {
+    free: [*mut FreeBlock; NUM_CLASSES],
+    bump: usize,
+    bump_end: usize,
+}
+
+/// Size-class allocator; programs use it through [`GlobalAlloc`]
+pub struct Heap<P: PageSource> {
+    lock: AtomicBool,
+    state: core::cell::UnsafeCell<HeapState>,
+    pages: P,
+}
+
+// Safety: all access to `state` happens with `lock` held.
+unsafe impl<P: PageSource + Sync> Sync for Heap<P> {}
+
+impl<P: PageSource> Heap<P> {
+    pub const fn new(pages: P) -> Self {
+        Self {
+            lock: AtomicBool::new(false),
+            state: core::cell::UnsafeCell::new(HeapState {
+                free: [ptr::null_mut(); NUM_CLASSES],
+                bump: 0,
+                bump_end: 0,
+            }),
+            pages,
+        }
+    }
+
+    fn class_of(layout: &Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align()).max(1 << MIN_CLASS_SHIFT);
+        if size > MAX_CLASS {
+            return None;
+        }
+        let shift = usize::BITS as usize - (size - 1).leading_zeros() as usize;
+        Some(shift - MIN_CLASS_SHIFT)
+    }
+
+    fn large_len(layout: &Layout) -> usize {
+        (layout.size() + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut HeapState) -> R) -> R {
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        let r = f(unsafe { &mut *self.state.get() });
+        self.lock.store(false, Ordering::Release);
+        r
+    }
+
+    fn alloc_small(&self, class: usize) -> *mut u8 {
+        let size = 1usize << (class + MIN_CLASS_SHIFT);
+        self.with_state(|st| {
+            let head = st.free[class];
+            if !head.is_null() {
+                st.free[class] = unsafe { (*head).next };
+                return head as *mut u8;
+            }
+
+            // Blocks are aligned to their own size so any layout that fits a
+            // class is also suitably aligned.
+            let mut start = (st.bump + size - 1) & !(size - 1);
+            if st.bump == 0 || start + size > st.bump_end {
+                let arena = self.pages.map(ARENA_SIZE);
+                if arena.is_null() {
+                    return ptr::null_mut();
+                }
+                st.bump = arena as usize;
+                st.bump_end = st.bump + ARENA_SIZE;
+                start = st.bump;
+            }
+            st.bump = start + size;
+            start as *mut u8
+        })
+    }
+
+    fn free_small(&self, ptr: *mut u8, class: usize) {
+        self.with_state(|st| {
+            let block = ptr as *mut FreeBlock;
+            unsafe { (*block).next = st.free[class] };
+            st.free[class] = block;
+        })
+    }
+}
+
+unsafe impl<P: PageSource> GlobalAlloc for Heap<P> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match Self::class_of(&layout) {
+            Some(class) => self.alloc_small(class),
+            None if layout.align() <= PAGE_SIZE => self.pages.map(Self::large_len(&layout)),
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match Self::class_of(&layout) {
+            Some(class) => self.free_small(ptr, class),
+            None => self.pages.unmap(ptr, Self::large_len(&layout)),
+        }
+    }
+}
+
+#[cfg(all(feature = "user-bin", target_os = "none"))]
+#[global_allocator]
+static HEAP: Heap<MmapPages> = Heap::new(MmapPages);
+
+/// Prepare the heap before `main` runs
+///
+/// Arenas are mapped lazily, so there is nothing to do yet; the hook keeps
+/// the startup sequence in one place for when that changes.
+pub fn init() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Hands out pages from a fixed buffer and counts live mappings
+    struct BufferPages {
+        buf: *mut u8,
+        used: Cell<usize>,
+        live: Cell<usize>,
+    }
+
+    impl PageSource for BufferPages {
+        fn map(&self, len: usize) -> *mut u8 {
+            let off = self.used.get();
+            self.used.set(off + len);
+            self.live.set(self.live.get() + 1);
+            unsafe { self.buf.add(off) }
+        }
+
+        unsafe fn unmap(&self, _addr: *mut u8, _len: usize) {
+            self.live.set(self.live.get() - 1);
+        }
+    }
+
+    #[repr(align(4096))]
+    struct Backing([u8; 4 * ARENA_SIZE]);
+
+    #[test]
+    fn reuses_freed_blocks_and_unmaps_large() {
+        let mut backing = alloc::boxed::Box::new(Backing([0; 4 * ARENA_SIZE]));
+        let heap = Heap::new(BufferPages {
+            buf: backing.0.as_mut_ptr(),
+            used: Cell::new(0),
+            live: Cell::new(0),
+        });
+
+        unsafe {
+            let small = Layout::from_size_align(24, 8).unwrap();
+            let a = heap.alloc(small);
+            let b = heap.alloc(small);
+            assert!(!a.is_null() && !b.is_null() && a != b);
+            assert_eq!(a as usize % 32, 0);
+            heap.dealloc(a, small);
+            assert_eq!(heap.alloc(small), a);
+
+            let aligned = Layout::from_size_align(8, 256).unwrap();
+            let c = heap.alloc(aligned);
+            assert_eq!(c as usize % 256, 0);
+
+            let live_before = heap.pages.live.get();
+            let large = Layout::from_size_align(MAX_CLASS + 1, 8).unwrap();
+            let d = heap.alloc(large);
+            assert_eq!(d as usize % PAGE_SIZE, 0);
+            assert_eq!(heap.pages.live.get(), live_before + 1);
+            heap.dealloc(d, large);
+            assert_eq!(heap.pages.live.get(), live_before);
+        }
+    }
+}
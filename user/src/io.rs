@@ -0,0 +1,76 @@
+//! Console output and line input
+//!
+//! `print!`/`println!` write straight to fd 1 and `eprint!`/`eprintln!` to
+//! fd 2. Output is unbuffered, so [`flush`] only exists as the hook the
+//! runtime calls before exiting.
+
+use core::fmt;
+
+use crate::errno::SysResult;
+use crate::syscall::{self, STDERR, STDOUT};
+
+/// `fmt::Write` adapter over a file descriptor
+pub struct Fd(pub i32);
+
+impl fmt::Write for Fd {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        syscall::write_all(self.0, s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let _ = fmt::Write::write_fmt(&mut Fd(STDOUT), args);
+}
+
+#[doc(hidden)]
+pub fn _eprint(args: fmt::Arguments) {
+    let _ = fmt::Write::write_fmt(&mut Fd(STDERR), args);
+}
+
+/// Flush pending output before exit
+pub fn flush() {}
+
+/// Read one line from `fd` into `buf`, without the trailing newline
+///
+/// Returns `Ok(None)` at end of file with nothing read. Lines longer than
+/// `buf` are truncated; the rest of the line is discarded.
+pub fn read_line(fd: i32, buf: &mut [u8]) -> SysResult<Option<usize>> {
+    let mut len = 0;
+    let mut byte = [0u8; 1];
+    loop {
+        match syscall::read(fd, &mut byte)? {
+            0 if len == 0 => return Ok(None),
+            0 => return Ok(Some(len)),
+            _ if byte[0] == b'\n' => return Ok(Some(len)),
+            _ => {
+                if len < buf.len() {
+                    buf[len] = byte[0];
+                    len += 1;
+                }
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ($crate::io::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\n"));
+    ($($arg:tt)*) => ($crate::io::_print(format_args!("{}\n", format_args!($($arg)*))));
+}
+
+#[macro_export]
+macro_rules! eprint {
+    ($($arg:tt)*) => ($crate::io::_eprint(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! eprintln {
+    () => ($crate::eprint!("\n"));
+    ($($arg:tt)*) => ($crate::io::_eprint(format_args!("{}\n", format_args!($($arg)*))));
+}
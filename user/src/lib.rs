@@ -1,5 +1,33 @@
 //! User space library for NOS operating system
+//!
+//! Minimal runtime for programs running on top of the NOS kernel:
+//!
+//! - `syscall`: raw system call entry for each architecture plus typed wrappers
+//! - `rt`: `_start`, argv/envp/auxv access and the panic handler
+//! - `heap`: global allocator over anonymous `mmap`
+//! - `io`: console output macros and line input
+//! - `signal`: signal numbers and handler installation
+//! - `process`: `execve` argument marshalling and `PATH` search
+//! - `cmdline`: the command line grammar used by `/bin/sh`
+//! - `display`: display server client and shared-memory pools
+//!
+//! The runtime pieces (`_start`, `#[global_allocator]`, `#[panic_handler]`)
+//! are only compiled with the `user-bin` feature on a bare-metal target, so
+//! the library itself can still be unit-tested on the host.
 
 #![no_std]
 
-// Add your code here
+extern crate alloc;
+
+pub mod cmdline;
+pub mod display;
+pub mod errno;
+pub mod heap;
+pub mod io;
+pub mod nr;
+pub mod process;
+pub mod rt;
+pub mod signal;
+pub mod syscall;
+
+pub use errno::{Errno, SysResult};
@@ -0,0 +1,88 @@
+//! Native NOS system call numbers
+//!
+//! Numbers are grouped by category exactly as the kernel's per-category
+//! dispatchers decode them (`subsystems::syscalls::{process, fs, memory,
+//! signal_advanced, time, thread, zero_copy}`): the high nibble selects the
+//! category, the low 12 bits the call within it.
+
+// Process management (0x1000)
+pub const FORK: usize = 0x1000;
+pub const EXECVE: usize = 0x1001;
+pub const WAITPID: usize = 0x1002;
+pub const EXIT: usize = 0x1003;
+pub const GETPID: usize = 0x1004;
+pub const GETPPID: usize = 0x1005;
+pub const GETUID: usize = 0x1007;
+pub const SETSID: usize = 0x100A;
+pub const SCHED_YIELD: usize = 0x100D;
+pub const PRCTL: usize = 0x1016;
+pub const SETPGID: usize = 0x101C;
+pub const GETRLIMIT: usize = 0x101E;
+pub const SETRLIMIT: usize = 0x101F;
+pub const WAIT4: usize = 0x1020;
+pub const SECCOMP: usize = 0x1022;
+pub const PRLIMIT64: usize = 0x1023;
+pub const PTRACE: usize = 0x1024;
+pub const ADD_KEY: usize = 0x1025;
+pub const REQUEST_KEY: usize = 0x1026;
+pub const KEYCTL: usize = 0x1027;
+
+// File I/O (0x2000)
+pub const OPEN: usize = 0x2000;
+pub const CLOSE: usize = 0x2001;
+pub const READ: usize = 0x2002;
+pub const WRITE: usize = 0x2003;
+pub const LSEEK: usize = 0x2004;
+pub const FSTAT: usize = 0x2005;
+pub const OPENAT: usize = 0x2008;
+pub const IOCTL: usize = 0x2009;
+pub const FCNTL: usize = 0x200A;
+pub const DUP: usize = 0x200B;
+pub const DUP2: usize = 0x200C;
+pub const PIPE: usize = 0x200E;
+pub const PIPE2: usize = 0x200F;
+
+// Memory management (0x3000)
+pub const BRK: usize = 0x3000;
+pub const MMAP: usize = 0x3001;
+pub const MUNMAP: usize = 0x3002;
+pub const MPROTECT: usize = 0x3003;
+
+// Signals (0x5000)
+pub const KILL: usize = 0x5000;
+pub const SIGACTION: usize = 0x5001;
+pub const SIGPROCMASK: usize = 0x5002;
+pub const PAUSE: usize = 0x5006;
+
+// Time (0x6000)
+pub const CLOCK_GETTIME: usize = 0x6003;
+pub const NANOSLEEP: usize = 0x6006;
+
+// Filesystem namespace (0x7000)
+pub const CHDIR: usize = 0x7000;
+pub const GETCWD: usize = 0x7002;
+pub const MKDIR: usize = 0x7003;
+pub const RMDIR: usize = 0x7004;
+pub const UNLINK: usize = 0x7005;
+pub const SYMLINK: usize = 0x7008;
+pub const READLINK: usize = 0x7009;
+pub const STAT: usize = 0x7010;
+pub const GETDENTS: usize = 0x7013;
+pub const CHROOT: usize = 0x7014;
+pub const FSYNC: usize = 0x701F;
+pub const FDATASYNC: usize = 0x7020;
+pub const SYNC: usize = 0x7021;
+pub const FTRUNCATE: usize = 0x7022;
+
+// Threads (0x8000)
+pub const CLONE: usize = 0x8000;
+pub const GETTID: usize = 0x8006;
+
+// Zero-copy and asynchronous I/O (0x9000)
+pub const IO_URING_SETUP: usize = 0x9006;
+pub const IO_URING_ENTER: usize = 0x9007;
+pub const IO_URING_REGISTER: usize = 0x9008;
+
+// GLib extensions (0xB000)
+pub const MEMFD_CREATE: usize = 0xB001;
+pub const DISPLAY_CONNECT: usize = 0xB00D;
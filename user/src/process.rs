@@ -0,0 +1,97 @@
+//! Program execution helpers
+//!
+//! `execve` wants NUL-terminated strings and NULL-terminated pointer arrays;
+//! [`CArgs`] owns both so callers can work with plain byte vectors.
+
+use alloc::vec::Vec;
+use core::ptr;
+
+use crate::errno::Errno;
+use crate::syscall;
+
+/// Directories searched when `PATH` is not set
+pub const DEFAULT_PATH: &[u8] = b"/bin";
+
+/// Owned, NUL-terminated argument vector
+pub struct CArgs {
+    strings: Vec<Vec<u8>>,
+    ptrs: Vec<*const u8>,
+}
+
+impl CArgs {
+    pub fn new<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u8]>,
+    {
+        let strings: Vec<Vec<u8>> = args.into_iter().map(|a| cstring(a.as_ref())).collect();
+        let mut ptrs: Vec<*const u8> = strings.iter().map(|s| s.as_ptr()).collect();
+        ptrs.push(ptr::null());
+        Self { strings, ptrs }
+    }
+
+    /// NULL-terminated pointer array for `execve`
+    pub fn as_ptrs(&self) -> &[*const u8] {
+        &self.ptrs
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// Copy `s` and append a NUL terminator
+pub fn cstring(s: &[u8]) -> Vec<u8> {
+    let mut v = Vec::with_capacity(s.len() + 1);
+    v.extend_from_slice(s);
+    v.push(0);
+    v
+}
+
+/// Execute `argv[0]`, searching `PATH` when it contains no `/`
+///
+/// Relative paths containing a `/` (`./prog`, `bin/prog`) are resolved by
+/// the kernel against the current directory. Only returns on failure.
+pub fn execvp(argv: &CArgs, name: &[u8]) -> Errno {
+    let envp = env_ptrs();
+    if name.contains(&b'/') {
+        return syscall::execve(&cstring(name), argv.as_ptrs(), &envp);
+    }
+
+    let path = crate::rt::var(b"PATH").unwrap_or(DEFAULT_PATH);
+    let mut last = Errno::ENOENT;
+    for dir in path.split(|&b| b == b':').filter(|d| !d.is_empty()) {
+        let mut full = Vec::with_capacity(dir.len() + name.len() + 2);
+        full.extend_from_slice(dir);
+        full.push(b'/');
+        full.extend_from_slice(name);
+        full.push(0);
+        let err = syscall::execve(&full, argv.as_ptrs(), &envp);
+        // Keep searching past missing entries, but report the more telling
+        // error (e.g. EACCES) if some candidate existed.
+        if err != Errno::ENOENT {
+            last = err;
+        }
+    }
+    last
+}
+
+/// The current environment as a NULL-terminated pointer array
+fn env_ptrs() -> Vec<*const u8> {
+    let mut envp = Vec::new();
+    let mut cur = crate::rt::environ();
+    if !cur.is_null() {
+        unsafe {
+            while !(*cur).is_null() {
+                envp.push(*cur);
+                cur = cur.add(1);
+            }
+        }
+    }
+    envp.push(ptr::null());
+    envp
+}
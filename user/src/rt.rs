@@ -0,0 +1,323 @@
+//! Process entry, argv/envp/auxv access and the panic handler
+//!
+//! The kernel (`write_args_to_stack` in `process::exec`) starts every
+//! program with the System V initial stack:
+//!
+//! ```text
+//! [sp]            argc
+//! [sp + 8]        argv[0] .. argv[argc - 1], NULL
+//!                 envp[0] .. envp[n - 1], NULL
+//!                 auxv pairs (type, value) .., AT_NULL
+//! ```
+//!
+//! `_start` hands `sp` to [`__user_start`], which records these vectors,
+//! calls the program's `main` (declared with [`entry!`](crate::entry)) and
+//! exits with its return value.
+
+use core::ffi::CStr;
+use core::ptr;
+
+/// Auxiliary vector types, mirroring `process::elf::AuxType`
+pub mod auxv {
+    pub const AT_NULL: usize = 0;
+    pub const AT_PHDR: usize = 3;
+    pub const AT_PHENT: usize = 4;
+    pub const AT_PHNUM: usize = 5;
+    pub const AT_PAGESZ: usize = 6;
+    pub const AT_BASE: usize = 7;
+    pub const AT_ENTRY: usize = 9;
+    pub const AT_UID: usize = 11;
+    pub const AT_EUID: usize = 12;
+    pub const AT_GID: usize = 13;
+    pub const AT_EGID: usize = 14;
+    pub const AT_HWCAP: usize = 16;
+    pub const AT_CLKTCK: usize = 17;
+    pub const AT_RANDOM: usize = 25;
+    pub const AT_EXECFN: usize = 31;
+}
+
+/// Pointers recovered from the initial stack
+#[derive(Debug, Clone, Copy)]
+pub struct StartupInfo {
+    pub argc: usize,
+    pub argv: *const *const u8,
+    pub envp: *const *const u8,
+    pub auxv: *const [usize; 2],
+}
+
+impl StartupInfo {
+    const fn empty() -> Self {
+        Self { argc: 0, argv: ptr::null(), envp: ptr::null(), auxv: ptr::null() }
+    }
+
+    /// Decode the initial stack layout starting at `sp`
+    ///
+    /// # Safety
+    ///
+    /// `sp` must point at a stack laid out as described in the module docs.
+    pub unsafe fn from_stack(sp: *const usize) -> Self {
+        let argc = *sp;
+        let argv = sp.add(1) as *const *const u8;
+        let envp = argv.add(argc + 1);
+        let mut cursor = envp;
+        while !(*cursor).is_null() {
+            cursor = cursor.add(1);
+        }
+        let auxv = cursor.add(1) as *const [usize; 2];
+        Self { argc, argv, envp, auxv }
+    }
+
+    /// Iterate over the argument strings
+    pub fn args(&self) -> Args {
+        Args { cur: self.argv, remaining: self.argc }
+    }
+
+    /// Iterate over `NAME=value` environment strings
+    pub fn vars(&self) -> Vars {
+        Vars { cur: self.envp }
+    }
+
+    /// Look up an environment variable by name
+    pub fn var(&self, name: &[u8]) -> Option<&'static [u8]> {
+        self.vars().find_map(|entry| {
+            let value = entry.strip_prefix(name)?;
+            value.strip_prefix(b"=")
+        })
+    }
+
+    /// Look up an auxiliary vector entry
+    pub fn auxv(&self, a_type: usize) -> Option<usize> {
+        if self.auxv.is_null() {
+            return None;
+        }
+        let mut cur = self.auxv;
+        unsafe {
+            loop {
+                let [t, v] = *cur;
+                if t == auxv::AT_NULL {
+                    return None;
+                }
+                if t == a_type {
+                    return Some(v);
+                }
+                cur = cur.add(1);
+            }
+        }
+    }
+}
+
+/// Iterator over argv entries, without their NUL terminators
+pub struct Args {
+    cur: *const *const u8,
+    remaining: usize,
+}
+
+impl Iterator for Args {
+    type Item = &'static [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        unsafe {
+            let s = *self.cur;
+            self.cur = self.cur.add(1);
+            self.remaining -= 1;
+            Some(CStr::from_ptr(s as *const _).to_bytes())
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+/// Iterator over envp entries, without their NUL terminators
+pub struct Vars {
+    cur: *const *const u8,
+}
+
+impl Iterator for Vars {
+    type Item = &'static [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cur.is_null() {
+            return None;
+        }
+        unsafe {
+            let s = *self.cur;
+            if s.is_null() {
+                return None;
+            }
+            self.cur = self.cur.add(1);
+            Some(CStr::from_ptr(s as *const _).to_bytes())
+        }
+    }
+}
+
+static mut STARTUP: StartupInfo = StartupInfo::empty();
+
+/// Startup vectors of the running program
+pub fn startup() -> StartupInfo {
+    // Written once in `__user_start` before `main` runs, read-only afterwards.
+    unsafe { ptr::addr_of!(STARTUP).read() }
+}
+
+/// Command line arguments of the running program
+pub fn args() -> Args {
+    startup().args()
+}
+
+/// Environment variable of the running program
+pub fn var(name: &[u8]) -> Option<&'static [u8]> {
+    startup().var(name)
+}
+
+/// The raw `envp` array, suitable for passing on to `execve`
+pub fn environ() -> *const *const u8 {
+    startup().envp
+}
+
+/// Declare the program's entry point
+///
+/// ```ignore
+/// user::entry!(main);
+///
+/// fn main() -> i32 {
+///     0
+/// }
+/// ```
+#[macro_export]
+macro_rules! entry {
+    ($main:path) => {
+        #[no_mangle]
+        pub extern "Rust" fn __user_main() -> i32 {
+            let main: fn() -> i32 = $main;
+            main()
+        }
+    };
+}
+
+#[cfg(all(feature = "user-bin", target_os = "none"))]
+mod start {
+    use super::{StartupInfo, STARTUP};
+
+    extern "Rust" {
+        fn __user_main() -> i32;
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    core::arch::global_asm!(
+        ".section .text._start, \"ax\"",
+        ".global _start",
+        "_start:",
+        "    xor rbp, rbp",
+        "    mov rdi, rsp",
+        "    and rsp, -16",
+        "    call {start}",
+        "    ud2",
+        start = sym __user_start,
+    );
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::global_asm!(
+        ".section .text._start, \"ax\"",
+        ".global _start",
+        "_start:",
+        "    mov x29, #0",
+        "    mov x30, #0",
+        "    mov x0, sp",
+        "    and x1, x0, #-16",
+        "    mov sp, x1",
+        "    bl {start}",
+        "    brk #1",
+        start = sym __user_start,
+    );
+
+    #[cfg(target_arch = "riscv64")]
+    core::arch::global_asm!(
+        ".section .text._start, \"ax\"",
+        ".global _start",
+        "_start:",
+        "    li ra, 0",
+        "    mv a0, sp",
+        "    andi sp, sp, -16",
+        "    call {start}",
+        "    unimp",
+        start = sym __user_start,
+    );
+
+    /// ELF note layout: header, then name and descriptor padded to 4 bytes
+    #[repr(C, align(4))]
+    struct AbiNote {
+        namesz: u32,
+        descsz: u32,
+        n_type: u32,
+        name: [u8; 4],
+    }
+
+    /// Marks the image as speaking the native syscall ABI; exec treats
+    /// untagged binaries as Linux ones
+    #[used]
+    #[link_section = ".note.nos"]
+    static NOS_ABI_NOTE: AbiNote = AbiNote {
+        namesz: 4,
+        descsz: 0,
+        n_type: 1,
+        name: *b"NOS\0",
+    };
+
+    /// Rust-side entry called from `_start` with the initial stack pointer
+    unsafe extern "C" fn __user_start(sp: *const usize) -> ! {
+        STARTUP = StartupInfo::from_stack(sp);
+        crate::heap::init();
+        let code = __user_main();
+        crate::io::flush();
+        crate::syscall::exit(code)
+    }
+
+    #[panic_handler]
+    fn panic(info: &core::panic::PanicInfo) -> ! {
+        crate::eprintln!("panic: {}", info);
+        crate::syscall::exit(101)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn decodes_initial_stack() {
+        let arg0 = b"/bin/sh\0";
+        let arg1 = b"-c\0";
+        let env0 = b"PATH=/bin\0";
+        let env1 = b"HOME=/\0";
+
+        let mut stack: Vec<usize> = vec![
+            2,
+            arg0.as_ptr() as usize,
+            arg1.as_ptr() as usize,
+            0,
+            env0.as_ptr() as usize,
+            env1.as_ptr() as usize,
+            0,
+        ];
+        stack.extend_from_slice(&[auxv::AT_PAGESZ, 4096, auxv::AT_ENTRY, 0x1000_0000]);
+        stack.extend_from_slice(&[auxv::AT_NULL, 0]);
+
+        let info = unsafe { StartupInfo::from_stack(stack.as_ptr()) };
+        let args: Vec<&[u8]> = info.args().collect();
+        assert_eq!(args, [&b"/bin/sh"[..], &b"-c"[..]]);
+        assert_eq!(info.var(b"PATH"), Some(&b"/bin"[..]));
+        assert_eq!(info.var(b"HOM"), None);
+        assert_eq!(info.vars().count(), 2);
+        assert_eq!(info.auxv(auxv::AT_PAGESZ), Some(4096));
+        assert_eq!(info.auxv(auxv::AT_ENTRY), Some(0x1000_0000));
+        assert_eq!(info.auxv(auxv::AT_RANDOM), None);
+    }
+}
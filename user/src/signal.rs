@@ -0,0 +1,82 @@
+//! Signal numbers and handler installation
+//!
+//! Numbers follow `ipc::signal` in the kernel.
+
+use crate::errno::SysResult;
+use crate::syscall::{self, SigAction};
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGFPE: i32 = 8;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGPIPE: i32 = 13;
+pub const SIGALRM: i32 = 14;
+pub const SIGTERM: i32 = 15;
+pub const SIGCHLD: i32 = 17;
+pub const SIGCONT: i32 = 18;
+pub const SIGSTOP: i32 = 19;
+pub const SIGTSTP: i32 = 20;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// `SigActionFlags::SA_RESTART`
+pub const SA_RESTART: u32 = 1 << 4;
+
+/// Signal disposition
+#[derive(Debug, Clone, Copy)]
+pub enum Handler {
+    Default,
+    Ignore,
+    Function(extern "C" fn(i32)),
+}
+
+impl Handler {
+    fn raw(self) -> usize {
+        match self {
+            Handler::Default => SIG_DFL,
+            Handler::Ignore => SIG_IGN,
+            Handler::Function(f) => f as usize,
+        }
+    }
+}
+
+/// Install `handler` for `sig` with `SA_RESTART` and an empty mask
+pub fn signal(sig: i32, handler: Handler) -> SysResult<()> {
+    let act = SigAction { handler: handler.raw(), flags: SA_RESTART, mask: 0, restorer: 0 };
+    syscall::sigaction(sig, Some(&act), None)
+}
+
+/// Human-readable signal name for exit reports
+pub fn name(sig: i32) -> &'static str {
+    match sig {
+        SIGHUP => "SIGHUP",
+        SIGINT => "SIGINT",
+        SIGQUIT => "SIGQUIT",
+        SIGILL => "SIGILL",
+        SIGTRAP => "SIGTRAP",
+        SIGABRT => "SIGABRT",
+        SIGBUS => "SIGBUS",
+        SIGFPE => "SIGFPE",
+        SIGKILL => "SIGKILL",
+        SIGUSR1 => "SIGUSR1",
+        SIGSEGV => "SIGSEGV",
+        SIGUSR2 => "SIGUSR2",
+        SIGPIPE => "SIGPIPE",
+        SIGALRM => "SIGALRM",
+        SIGTERM => "SIGTERM",
+        SIGCHLD => "SIGCHLD",
+        SIGCONT => "SIGCONT",
+        SIGSTOP => "SIGSTOP",
+        SIGTSTP => "SIGTSTP",
+        _ => "signal",
+    }
+}
@@ -0,0 +1,601 @@
+//! Raw system call entry and typed wrappers
+//!
+//! Register convention (matches the kernel trap entry for each arch):
+//!
+//! | arch    | instruction | number | arguments               | return |
+//! |---------|-------------|--------|-------------------------|--------|
+//! | x86_64  | `syscall`   | rax    | rdi rsi rdx r10 r8 r9   | rax    |
+//! | aarch64 | `svc #0`    | x8     | x0 x1 x2 x3 x4 x5       | x0     |
+//! | riscv64 | `ecall`     | a7     | a0 a1 a2 a3 a4 a5       | a0     |
+
+use crate::errno::{Errno, SysResult};
+use crate::nr;
+
+/// Issue a system call with up to six arguments
+///
+/// # Safety
+///
+/// The caller must uphold whatever contract the specific system call has
+/// for pointer arguments.
+#[inline(always)]
+pub unsafe fn syscall6(
+    n: usize,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+    a5: usize,
+) -> usize {
+    let ret: usize;
+
+    #[cfg(target_arch = "x86_64")]
+    core::arch::asm!(
+        "syscall",
+        inlateout("rax") n => ret,
+        in("rdi") a0,
+        in("rsi") a1,
+        in("rdx") a2,
+        in("r10") a3,
+        in("r8") a4,
+        in("r9") a5,
+        lateout("rcx") _,
+        lateout("r11") _,
+        options(nostack),
+    );
+
+    #[cfg(target_arch = "aarch64")]
+    core::arch::asm!(
+        "svc #0",
+        in("x8") n,
+        inlateout("x0") a0 => ret,
+        in("x1") a1,
+        in("x2") a2,
+        in("x3") a3,
+        in("x4") a4,
+        in("x5") a5,
+        options(nostack),
+    );
+
+    #[cfg(target_arch = "riscv64")]
+    core::arch::asm!(
+        "ecall",
+        in("a7") n,
+        inlateout("a0") a0 => ret,
+        in("a1") a1,
+        in("a2") a2,
+        in("a3") a3,
+        in("a4") a4,
+        in("a5") a5,
+        options(nostack),
+    );
+
+    ret
+}
+
+#[inline(always)]
+unsafe fn syscall0(n: usize) -> usize {
+    syscall6(n, 0, 0, 0, 0, 0, 0)
+}
+
+#[inline(always)]
+unsafe fn syscall1(n: usize, a0: usize) -> usize {
+    syscall6(n, a0, 0, 0, 0, 0, 0)
+}
+
+#[inline(always)]
+unsafe fn syscall2(n: usize, a0: usize, a1: usize) -> usize {
+    syscall6(n, a0, a1, 0, 0, 0, 0)
+}
+
+#[inline(always)]
+unsafe fn syscall3(n: usize, a0: usize, a1: usize, a2: usize) -> usize {
+    syscall6(n, a0, a1, a2, 0, 0, 0)
+}
+
+#[inline(always)]
+unsafe fn syscall4(n: usize, a0: usize, a1: usize, a2: usize, a3: usize) -> usize {
+    syscall6(n, a0, a1, a2, a3, 0, 0)
+}
+
+// ============================================================================
+// Constants shared with the kernel
+// ============================================================================
+
+pub const STDIN: i32 = 0;
+pub const STDOUT: i32 = 1;
+pub const STDERR: i32 = 2;
+
+pub const O_RDONLY: i32 = 0;
+pub const O_WRONLY: i32 = 1;
+pub const O_RDWR: i32 = 2;
+pub const O_CREAT: i32 = 0o100;
+pub const O_EXCL: i32 = 0o200;
+pub const O_TRUNC: i32 = 0o1000;
+pub const O_APPEND: i32 = 0o2000;
+pub const O_DIRECTORY: i32 = 0o200000;
+pub const O_NOFOLLOW: i32 = 0o400000;
+pub const O_NONBLOCK: i32 = 0o4000;
+pub const O_CLOEXEC: i32 = 0o2000000;
+
+pub const MFD_CLOEXEC: u32 = 0x1;
+pub const MFD_ALLOW_SEALING: u32 = 0x2;
+
+pub const AT_FDCWD: i32 = -100;
+
+pub const SEEK_SET: i32 = 0;
+pub const SEEK_CUR: i32 = 1;
+pub const SEEK_END: i32 = 2;
+
+pub const PROT_NONE: usize = 0;
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+
+pub const MAP_SHARED: usize = 0x01;
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_FIXED: usize = 0x10;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+pub const WNOHANG: i32 = 1;
+
+// ============================================================================
+// File I/O
+// ============================================================================
+
+pub fn read(fd: i32, buf: &mut [u8]) -> SysResult<usize> {
+    Errno::decode(unsafe { syscall3(nr::READ, fd as usize, buf.as_mut_ptr() as usize, buf.len()) })
+}
+
+pub fn write(fd: i32, buf: &[u8]) -> SysResult<usize> {
+    Errno::decode(unsafe { syscall3(nr::WRITE, fd as usize, buf.as_ptr() as usize, buf.len()) })
+}
+
+/// Write the whole buffer, retrying on short writes
+pub fn write_all(fd: i32, mut buf: &[u8]) -> SysResult<()> {
+    while !buf.is_empty() {
+        match write(fd, buf) {
+            Ok(0) => return Err(Errno::EIO),
+            Ok(n) => buf = &buf[n..],
+            Err(Errno::EINTR) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Open `path`, which must be NUL-terminated
+pub fn open(path: &[u8], flags: i32, mode: u32) -> SysResult<i32> {
+    debug_assert_eq!(path.last(), Some(&0));
+    Errno::decode(unsafe {
+        syscall3(nr::OPEN, path.as_ptr() as usize, flags as usize, mode as usize)
+    })
+    .map(|fd| fd as i32)
+}
+
+/// Open `path` relative to the directory `dirfd`, or the cwd for `AT_FDCWD`
+pub fn openat(dirfd: i32, path: &[u8], flags: i32, mode: u32) -> SysResult<i32> {
+    debug_assert_eq!(path.last(), Some(&0));
+    Errno::decode(unsafe {
+        syscall4(nr::OPENAT, dirfd as usize, path.as_ptr() as usize, flags as usize, mode as usize)
+    })
+    .map(|fd| fd as i32)
+}
+
+pub fn close(fd: i32) -> SysResult<()> {
+    Errno::decode(unsafe { syscall1(nr::CLOSE, fd as usize) }).map(|_| ())
+}
+
+pub fn lseek(fd: i32, offset: i64, whence: i32) -> SysResult<u64> {
+    Errno::decode(unsafe { syscall3(nr::LSEEK, fd as usize, offset as usize, whence as usize) })
+        .map(|off| off as u64)
+}
+
+pub fn dup(fd: i32) -> SysResult<i32> {
+    Errno::decode(unsafe { syscall1(nr::DUP, fd as usize) }).map(|fd| fd as i32)
+}
+
+pub fn dup2(old: i32, new: i32) -> SysResult<i32> {
+    Errno::decode(unsafe { syscall2(nr::DUP2, old as usize, new as usize) }).map(|fd| fd as i32)
+}
+
+/// Write the file's cached data back to its filesystem
+pub fn fsync(fd: i32) -> SysResult<()> {
+    Errno::decode(unsafe { syscall1(nr::FSYNC, fd as usize) }).map(|_| ())
+}
+
+pub fn fdatasync(fd: i32) -> SysResult<()> {
+    Errno::decode(unsafe { syscall1(nr::FDATASYNC, fd as usize) }).map(|_| ())
+}
+
+/// Write all cached file data back
+pub fn sync() {
+    unsafe { syscall0(nr::SYNC) };
+}
+
+/// Set the size of an open file
+pub fn ftruncate(fd: i32, len: u64) -> SysResult<()> {
+    Errno::decode(unsafe { syscall2(nr::FTRUNCATE, fd as usize, len as usize) }).map(|_| ())
+}
+
+/// Create an anonymous memory file; `name` must be NUL-terminated
+pub fn memfd_create(name: &[u8], flags: u32) -> SysResult<i32> {
+    debug_assert_eq!(name.last(), Some(&0));
+    Errno::decode(unsafe { syscall2(nr::MEMFD_CREATE, name.as_ptr() as usize, flags as usize) }).map(|fd| fd as i32)
+}
+
+/// Connect to the display server; `flags` takes `O_NONBLOCK` and
+/// `O_CLOEXEC`
+pub fn display_connect(flags: i32) -> SysResult<i32> {
+    Errno::decode(unsafe { syscall1(nr::DISPLAY_CONNECT, flags as usize) }).map(|fd| fd as i32)
+}
+
+/// Create a pipe, returning `(read_end, write_end)`
+pub fn pipe() -> SysResult<(i32, i32)> {
+    let mut fds = [0i32; 2];
+    Errno::decode(unsafe { syscall1(nr::PIPE, fds.as_mut_ptr() as usize) })?;
+    Ok((fds[0], fds[1]))
+}
+
+// ============================================================================
+// Filesystem namespace
+// ============================================================================
+
+pub fn chdir(path: &[u8]) -> SysResult<()> {
+    debug_assert_eq!(path.last(), Some(&0));
+    Errno::decode(unsafe { syscall1(nr::CHDIR, path.as_ptr() as usize) }).map(|_| ())
+}
+
+/// Fill `buf` with the NUL-terminated current directory, returning its length
+pub fn getcwd(buf: &mut [u8]) -> SysResult<usize> {
+    Errno::decode(unsafe { syscall2(nr::GETCWD, buf.as_mut_ptr() as usize, buf.len()) })?;
+    Ok(buf.iter().position(|&b| b == 0).unwrap_or(buf.len()))
+}
+
+pub fn mkdir(path: &[u8], mode: u32) -> SysResult<()> {
+    debug_assert_eq!(path.last(), Some(&0));
+    Errno::decode(unsafe { syscall2(nr::MKDIR, path.as_ptr() as usize, mode as usize) })
+        .map(|_| ())
+}
+
+pub fn unlink(path: &[u8]) -> SysResult<()> {
+    debug_assert_eq!(path.last(), Some(&0));
+    Errno::decode(unsafe { syscall1(nr::UNLINK, path.as_ptr() as usize) }).map(|_| ())
+}
+
+/// Create a symlink at `link` pointing to `target`; both NUL-terminated
+pub fn symlink(target: &[u8], link: &[u8]) -> SysResult<()> {
+    debug_assert_eq!(target.last(), Some(&0));
+    debug_assert_eq!(link.last(), Some(&0));
+    Errno::decode(unsafe { syscall2(nr::SYMLINK, target.as_ptr() as usize, link.as_ptr() as usize) })
+        .map(|_| ())
+}
+
+/// Read the target of the symlink `path` into `buf`, returning its length
+pub fn readlink(path: &[u8], buf: &mut [u8]) -> SysResult<usize> {
+    debug_assert_eq!(path.last(), Some(&0));
+    Errno::decode(unsafe {
+        syscall3(nr::READLINK, path.as_ptr() as usize, buf.as_mut_ptr() as usize, buf.len())
+    })
+}
+
+pub fn chroot(path: &[u8]) -> SysResult<()> {
+    debug_assert_eq!(path.last(), Some(&0));
+    Errno::decode(unsafe { syscall1(nr::CHROOT, path.as_ptr() as usize) }).map(|_| ())
+}
+
+// ============================================================================
+// Processes
+// ============================================================================
+
+/// Terminate the calling process
+pub fn exit(code: i32) -> ! {
+    unsafe {
+        syscall1(nr::EXIT, code as usize);
+    }
+    // The kernel never returns from exit; spin in case it somehow does.
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Returns 0 in the child and the child's PID in the parent
+pub fn fork() -> SysResult<i32> {
+    Errno::decode(unsafe { syscall0(nr::FORK) }).map(|pid| pid as i32)
+}
+
+/// Replace the current image
+///
+/// `path` must be NUL-terminated; `argv` and `envp` are NULL-terminated
+/// arrays of pointers to NUL-terminated strings. Only returns on failure.
+pub fn execve(path: &[u8], argv: &[*const u8], envp: &[*const u8]) -> Errno {
+    debug_assert_eq!(path.last(), Some(&0));
+    debug_assert_eq!(argv.last(), Some(&core::ptr::null()));
+    debug_assert_eq!(envp.last(), Some(&core::ptr::null()));
+    let ret = unsafe {
+        syscall3(
+            nr::EXECVE,
+            path.as_ptr() as usize,
+            argv.as_ptr() as usize,
+            envp.as_ptr() as usize,
+        )
+    };
+    match Errno::decode(ret) {
+        Err(e) => e,
+        Ok(_) => Errno::ENOEXEC,
+    }
+}
+
+/// Wait for a child, returning `(pid, raw_status)`; pid is 0 with `WNOHANG`
+/// when no child has changed state yet
+pub fn waitpid(pid: i32, options: i32) -> SysResult<(i32, i32)> {
+    let mut status = 0i32;
+    let ret = Errno::decode(unsafe {
+        syscall3(
+            nr::WAITPID,
+            pid as usize,
+            &mut status as *mut i32 as usize,
+            options as usize,
+        )
+    })?;
+    Ok((ret as i32, status))
+}
+
+pub fn getpid() -> i32 {
+    unsafe { syscall0(nr::GETPID) as i32 }
+}
+
+pub fn getppid() -> i32 {
+    unsafe { syscall0(nr::GETPPID) as i32 }
+}
+
+pub fn setsid() -> SysResult<i32> {
+    Errno::decode(unsafe { syscall0(nr::SETSID) }).map(|sid| sid as i32)
+}
+
+pub fn setpgid(pid: i32, pgid: i32) -> SysResult<()> {
+    Errno::decode(unsafe { syscall2(nr::SETPGID, pid as usize, pgid as usize) }).map(|_| ())
+}
+
+pub fn sched_yield() {
+    unsafe {
+        syscall0(nr::SCHED_YIELD);
+    }
+}
+
+pub fn prctl(option: i32, arg2: usize, arg3: usize, arg4: usize, arg5: usize) -> SysResult<usize> {
+    Errno::decode(unsafe { syscall6(nr::PRCTL, option as usize, arg2, arg3, arg4, arg5, 0) })
+}
+
+/// `struct sock_filter`, one classic BPF instruction
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// `struct sock_fprog`
+#[repr(C)]
+struct SockFprog {
+    len: u16,
+    filter: *const SockFilter,
+}
+
+pub const SECCOMP_SET_MODE_STRICT: usize = 0;
+pub const SECCOMP_SET_MODE_FILTER: usize = 1;
+
+/// Stack a seccomp filter on the calling thread
+///
+/// Returns the listener fd with `SECCOMP_FILTER_FLAG_NEW_LISTENER`, and 0
+/// otherwise.
+pub fn seccomp_set_filter(flags: u32, prog: &[SockFilter]) -> SysResult<usize> {
+    let fprog = SockFprog { len: prog.len() as u16, filter: prog.as_ptr() };
+    Errno::decode(unsafe {
+        syscall3(nr::SECCOMP, SECCOMP_SET_MODE_FILTER, flags as usize, &fprog as *const SockFprog as usize)
+    })
+}
+
+pub fn seccomp_set_strict() -> SysResult<()> {
+    Errno::decode(unsafe { syscall3(nr::SECCOMP, SECCOMP_SET_MODE_STRICT, 0, 0) }).map(|_| ())
+}
+
+pub const PTRACE_TRACEME: u32 = 0;
+pub const PTRACE_PEEKDATA: u32 = 2;
+pub const PTRACE_POKEDATA: u32 = 5;
+pub const PTRACE_CONT: u32 = 7;
+pub const PTRACE_KILL: u32 = 8;
+pub const PTRACE_SINGLESTEP: u32 = 9;
+pub const PTRACE_ATTACH: u32 = 16;
+pub const PTRACE_DETACH: u32 = 17;
+pub const PTRACE_SYSCALL: u32 = 24;
+pub const PTRACE_SETOPTIONS: u32 = 0x4200;
+pub const PTRACE_GETEVENTMSG: u32 = 0x4201;
+pub const PTRACE_GETREGSET: u32 = 0x4204;
+pub const PTRACE_SEIZE: u32 = 0x4206;
+pub const PTRACE_INTERRUPT: u32 = 0x4207;
+
+pub const PTRACE_O_TRACESYSGOOD: usize = 0x01;
+pub const PTRACE_O_TRACEFORK: usize = 0x02;
+pub const PTRACE_O_TRACEVFORK: usize = 0x04;
+pub const PTRACE_O_TRACECLONE: usize = 0x08;
+pub const PTRACE_O_TRACEEXEC: usize = 0x10;
+pub const PTRACE_O_TRACEEXIT: usize = 0x40;
+pub const PTRACE_O_EXITKILL: usize = 0x10_0000;
+
+/// Issue a ptrace request against `pid`
+///
+/// For the PEEK requests the word read is the return value.
+pub fn ptrace(request: u32, pid: i32, addr: usize, data: usize) -> SysResult<usize> {
+    Errno::decode(unsafe { syscall4(nr::PTRACE, request as usize, pid as usize, addr, data) })
+}
+
+/// Exit status helpers for the raw `waitpid` status word
+pub mod wait {
+    pub fn exited(status: i32) -> bool {
+        status & 0x7f == 0
+    }
+
+    pub fn exit_status(status: i32) -> i32 {
+        (status >> 8) & 0xff
+    }
+
+    pub fn signaled(status: i32) -> bool {
+        ((status & 0x7f) + 1) as i8 >= 2
+    }
+
+    pub fn term_sig(status: i32) -> i32 {
+        status & 0x7f
+    }
+
+    pub fn core_dumped(status: i32) -> bool {
+        status & 0x80 != 0
+    }
+
+    pub fn stopped(status: i32) -> bool {
+        status & 0xff == 0x7f
+    }
+
+    pub fn stop_sig(status: i32) -> i32 {
+        (status >> 8) & 0xff
+    }
+
+    /// The `PTRACE_EVENT_*` of a ptrace event stop, or 0
+    pub fn ptrace_event(status: i32) -> i32 {
+        (status >> 16) & 0xff
+    }
+}
+
+// ============================================================================
+// Keys
+// ============================================================================
+
+pub const KEY_SPEC_THREAD_KEYRING: i32 = -1;
+pub const KEY_SPEC_PROCESS_KEYRING: i32 = -2;
+pub const KEY_SPEC_SESSION_KEYRING: i32 = -3;
+pub const KEY_SPEC_USER_KEYRING: i32 = -4;
+pub const KEY_SPEC_USER_SESSION_KEYRING: i32 = -5;
+
+pub const KEYCTL_GET_KEYRING_ID: u32 = 0;
+pub const KEYCTL_JOIN_SESSION_KEYRING: u32 = 1;
+pub const KEYCTL_UPDATE: u32 = 2;
+pub const KEYCTL_REVOKE: u32 = 3;
+pub const KEYCTL_CHOWN: u32 = 4;
+pub const KEYCTL_SETPERM: u32 = 5;
+pub const KEYCTL_DESCRIBE: u32 = 6;
+pub const KEYCTL_CLEAR: u32 = 7;
+pub const KEYCTL_LINK: u32 = 8;
+pub const KEYCTL_UNLINK: u32 = 9;
+pub const KEYCTL_SEARCH: u32 = 10;
+pub const KEYCTL_READ: u32 = 11;
+pub const KEYCTL_SET_REQKEY_KEYRING: u32 = 14;
+pub const KEYCTL_SET_TIMEOUT: u32 = 15;
+pub const KEYCTL_GET_SECURITY: u32 = 17;
+pub const KEYCTL_SESSION_TO_PARENT: u32 = 18;
+pub const KEYCTL_INVALIDATE: u32 = 21;
+
+/// Add a key to `keyring`, or update the key of the same type and
+/// description already there; `key_type` and `description` must be
+/// NUL-terminated
+pub fn add_key(key_type: &[u8], description: &[u8], payload: &[u8], keyring: i32) -> SysResult<i32> {
+    debug_assert_eq!(key_type.last(), Some(&0));
+    debug_assert_eq!(description.last(), Some(&0));
+    Errno::decode(unsafe {
+        syscall6(
+            nr::ADD_KEY,
+            key_type.as_ptr() as usize,
+            description.as_ptr() as usize,
+            payload.as_ptr() as usize,
+            payload.len(),
+            keyring as usize,
+            0,
+        )
+    })
+    .map(|serial| serial as i32)
+}
+
+/// Find a key in the caller's keyrings and link it into `dest_keyring`,
+/// unless that is 0
+pub fn request_key(key_type: &[u8], description: &[u8], dest_keyring: i32) -> SysResult<i32> {
+    debug_assert_eq!(key_type.last(), Some(&0));
+    debug_assert_eq!(description.last(), Some(&0));
+    Errno::decode(unsafe {
+        syscall4(nr::REQUEST_KEY, key_type.as_ptr() as usize, description.as_ptr() as usize, 0, dest_keyring as usize)
+    })
+    .map(|serial| serial as i32)
+}
+
+/// Issue a `KEYCTL_*` operation; the arguments depend on `op`
+pub fn keyctl(op: u32, arg2: usize, arg3: usize, arg4: usize, arg5: usize) -> SysResult<usize> {
+    Errno::decode(unsafe { syscall6(nr::KEYCTL, op as usize, arg2, arg3, arg4, arg5, 0) })
+}
+
+// ============================================================================
+// Memory
+// ============================================================================
+
+pub fn mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: i32,
+    offset: usize,
+) -> SysResult<*mut u8> {
+    Errno::decode(unsafe { syscall6(nr::MMAP, addr, len, prot, flags, fd as usize, offset) })
+        .map(|p| p as *mut u8)
+}
+
+/// # Safety
+///
+/// No live references may point into the unmapped range.
+pub unsafe fn munmap(addr: *mut u8, len: usize) -> SysResult<()> {
+    Errno::decode(syscall2(nr::MUNMAP, addr as usize, len)).map(|_| ())
+}
+
+// ============================================================================
+// Signals and time
+// ============================================================================
+
+pub fn kill(pid: i32, sig: i32) -> SysResult<()> {
+    Errno::decode(unsafe { syscall2(nr::KILL, pid as usize, sig as usize) }).map(|_| ())
+}
+
+/// Kernel `SigAction` layout as consumed by `sys_sigaction`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: u32,
+    pub mask: u64,
+    pub restorer: usize,
+}
+
+pub fn sigaction(sig: i32, act: Option<&SigAction>, old: Option<&mut SigAction>) -> SysResult<()> {
+    let act = act.map_or(0, |a| a as *const SigAction as usize);
+    let old = old.map_or(0, |o| o as *mut SigAction as usize);
+    Errno::decode(unsafe { syscall3(nr::SIGACTION, sig as usize, act, old) }).map(|_| ())
+}
+
+/// `struct timespec`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timespec {
+    pub tv_sec: i64,
+    pub tv_nsec: i64,
+}
+
+pub fn nanosleep(req: &Timespec) -> SysResult<()> {
+    Errno::decode(unsafe { syscall2(nr::NANOSLEEP, req as *const Timespec as usize, 0) })
+        .map(|_| ())
+}
+
+pub fn clock_gettime(clock: i32) -> SysResult<Timespec> {
+    let mut ts = Timespec::default();
+    Errno::decode(unsafe {
+        syscall4(nr::CLOCK_GETTIME, clock as usize, &mut ts as *mut Timespec as usize, 0, 0)
+    })?;
+    Ok(ts)
+}
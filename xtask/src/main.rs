@@ -19,7 +19,7 @@ fn main() {
         "user-rel-exec" => {
             let target = args.next().unwrap_or_else(|| "aarch64".to_string());
             build_user(vec![target]);
-            println!("[xtask] built user execrel; run kernel with user-bin to execute /bin/execrel or chdir /tmp and exec relative hello");
+            println!("[xtask] built user execrel; install target/<arch>-nostd/debug/{{init,sh,hello,execrel}} under /bin, then run /bin/execrel (or `execrel /tmp` after copying hello there) to exec a relative hello");
         }
         "bench" => {
             if let Err(e) = run_bench() {
@@ -45,6 +45,7 @@ fn build_kernel(args: Vec<String>) {
         .arg("build")
         .arg("-p").arg("kernel")
         .arg("--target").arg(target_json)
+        .arg("-Z").arg("json-target-spec")
         .arg("-Z").arg("build-std=core,alloc")
         .arg("-Z").arg("build-std-features=compiler-builtins-mem")
         .arg("--features").arg(if tests { "baremetal,kernel_tests" } else { "baremetal" });
@@ -64,6 +65,7 @@ fn build_user(args: Vec<String>) {
         .arg("build")
         .arg("-p").arg("user")
         .arg("--target").arg(target_json)
+        .arg("-Z").arg("json-target-spec")
         .arg("-Z").arg("build-std=core,alloc")
         .arg("-Z").arg("build-std-features=compiler-builtins-mem")
         .arg("--features").arg("user-bin");